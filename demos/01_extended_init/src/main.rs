@@ -15,12 +15,11 @@ use std::fs::{File, OpenOptions};
 use std::io::Write;
 use std::fmt::Write as FWrite;
 use std::cell::Cell;
+use std::sync::mpsc::Receiver;
 
 
 const GL_LOG_FILE: &str = "gl.log";
 
-static mut PREVIOUS_SECONDS: f64 = 0.;
-
 
 #[inline]
 fn glubyte_ptr_to_string(cstr: *const GLubyte) -> String {
@@ -89,15 +88,23 @@ fn glfw_error_callback(error: glfw::Error, description: String, error_count: &Ce
 const G_GL_WIDTH_DEFAULT: u32 = 640;
 const G_GL_HEIGHT_DEFAULT: u32 = 480;
 
-static mut G_GL_WIDTH: u32 = 640;
-static mut G_GL_HEIGHT: u32 = 480;
+// The GLFW window and its event queue, together with the state we keep about
+// the window from frame to frame: the framebuffer size, and the timer and frame
+// count behind the frame rate counter.
+struct GLContext {
+    glfw: glfw::Glfw,
+    window: glfw::Window,
+    events: Receiver<(f64, glfw::WindowEvent)>,
+    width: u32,
+    height: u32,
+    framerate_time_seconds: f64,
+    frame_count: u32,
+}
 
 // We will tell GLFW to run this function whenever the framebuffer size is changed.
-fn glfw_framebuffer_size_callback(window: &mut glfw::Window, width: u32, height: u32) {
-    unsafe {
-        G_GL_WIDTH = width;
-        G_GL_HEIGHT = height;
-    }
+fn glfw_framebuffer_size_callback(context: &mut GLContext, width: u32, height: u32) {
+    context.width = width;
+    context.height = height;
     println!("width {} height {}", width, height);
     /* Update any perspective matrices used here */
 }
@@ -153,25 +160,20 @@ fn log_gl_params() {
 }
 
 // We will use this function to update the window title with a frame rate.
-fn _update_fps_counter(glfw: &glfw::Glfw, window: &mut glfw::Window) {
-    let mut tmp: String = String::new();
-
-    static mut FRAME_COUNT: usize = 0;
-
-    let current_seconds = glfw.get_time();
-    unsafe {
-        let elapsed_seconds = current_seconds - PREVIOUS_SECONDS;
-        if elapsed_seconds > 0.25 {
-            PREVIOUS_SECONDS = current_seconds;
-
-            let fps = FRAME_COUNT as f64 / elapsed_seconds;
-            write!(&mut tmp, "OpenGL @ fps: {:.2}", fps).unwrap();
-            window.set_title(&tmp);
-            FRAME_COUNT = 0;
-        }
-
-        FRAME_COUNT += 1;
+fn update_fps_counter(context: &mut GLContext) {
+    let current_seconds = context.glfw.get_time();
+    let elapsed_seconds = current_seconds - context.framerate_time_seconds;
+    if elapsed_seconds > 0.25 {
+        context.framerate_time_seconds = current_seconds;
+
+        let fps = context.frame_count as f64 / elapsed_seconds;
+        let mut tmp: String = String::new();
+        write!(&mut tmp, "OpenGL @ fps: {:.2}", fps).unwrap();
+        context.window.set_title(&tmp);
+        context.frame_count = 0;
     }
+
+    context.frame_count += 1;
 }
 
 fn main() {
@@ -226,6 +228,16 @@ fn main() {
     gl_log(&format!("renderer: {}\nversion: {}\n", renderer, version));
     log_gl_params();
 
+    let mut context = GLContext {
+        glfw: glfw,
+        window: window,
+        events: events,
+        width: G_GL_WIDTH_DEFAULT,
+        height: G_GL_HEIGHT_DEFAULT,
+        framerate_time_seconds: 0.0,
+        frame_count: 0,
+    };
+
     unsafe {
         // Tell GL to only draw onto a pixel if the shape is closer to the viewer.
         // Enable depth-testing.
@@ -295,24 +307,24 @@ fn main() {
         }
         println!("END SHADER PROGRAM LOG.");
 
-        PREVIOUS_SECONDS = glfw.get_time();
-        while !window.should_close() {
-            _update_fps_counter(&mut glfw, &mut window);
+        context.framerate_time_seconds = context.glfw.get_time();
+        while !context.window.should_close() {
+            update_fps_counter(&mut context);
             // Wipe the drawing surface clear.
             gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
             gl::ClearColor(0.3, 0.3, 0.3, 1.0);
-            gl::Viewport(0, 0, G_GL_WIDTH as GLint, G_GL_HEIGHT as GLint);
+            gl::Viewport(0, 0, context.width as GLint, context.height as GLint);
 
             gl::UseProgram(shader_programme);
             gl::BindVertexArray(vao);
             // Draw points 0-3 from the currently bound VAO with current in-use shader.
             gl::DrawArrays(gl::TRIANGLES, 0, 3);
             // Update other events like input handling.
-            glfw.poll_events();
-            for (time, event) in glfw::flush_messages(&events) {
+            context.glfw.poll_events();
+            for (time, event) in glfw::flush_messages(&context.events) {
                 match event {
                     glfw::WindowEvent::Key(Key::Escape, _, Action::Press, _) => {
-                        window.set_should_close(true);
+                        context.window.set_should_close(true);
                     }
                     _ => {
 
@@ -321,7 +333,7 @@ fn main() {
             }
 
             // Put the stuff we've been drawing onto the display.
-            window.swap_buffers();
+            context.window.swap_buffers();
         }
     }
 }
@@ -6,11 +6,11 @@ mod gl_utils;
 
 
 use glfw::{Action, Context, Key};
-use gl::types::{GLubyte, GLfloat, GLuint, GLsizeiptr, GLchar, GLvoid, GLint, GLenum};
+use gl::types::{GLubyte, GLfloat, GLuint, GLsizeiptr, GLchar, GLvoid, GLint};
 use chrono::prelude::Utc;
 
 use std::string::String;
-use std::ffi::CStr;
+use std::ffi::{CStr, CString};
 use std::mem;
 use std::ptr;
 use std::fs::{File, OpenOptions};
@@ -24,25 +24,6 @@ use std::process;
 const GL_LOG_FILE: &str = "gl.log";
 
 
-fn GL_type_to_string(gl_type: GLenum) -> &'static str {
-    match gl_type {
-        gl::BOOL => "bool",
-        gl::INT => "int",
-        gl::FLOAT => "float",
-        gl::FLOAT_VEC2 => "vec2",
-        gl::FLOAT_VEC3 => "vec3",
-        gl::FLOAT_VEC4 => "vec4",
-        gl::FLOAT_MAT2 => "mat2",
-        gl::FLOAT_MAT3 => "mat3",
-        gl::FLOAT_MAT4 => "mat4",
-        gl::SAMPLER_2D => "sampler2D",
-        gl::SAMPLER_3D => "sampler3D",
-        gl::SAMPLER_CUBE => "samplerCube",
-        gl::SAMPLER_2D_SHADOW => "sampler2DShadow",
-        _ => "other"
-    }
-}
-
 /* print errors in shader compilation */
 fn _print_shader_info_log(shader_index: GLuint) {
     let max_length = 2048;
@@ -93,111 +74,31 @@ fn is_valid(sp: GLuint) -> bool {
     return true;
 }
 
-/* print absolutely everything about a shader - only useful if you get really
-stuck wondering why a shader isn't working properly */
-fn print_all(sp: GLuint) {
-    let mut params = -1;
-
-    unsafe {
-        println!("--------------------\nshader programme {} info:", sp);
-        gl::GetProgramiv(sp, gl::LINK_STATUS, &mut params);
-        println!("GL_LINK_STATUS = {}", params);
-
-        gl::GetProgramiv(sp, gl::ATTACHED_SHADERS, &mut params);
-        println!("GL_ATTACHED_SHADERS = {}", params);
-
-        gl::GetProgramiv(sp, gl::ACTIVE_ATTRIBUTES, &mut params);
-        println!("GL_ACTIVE_ATTRIBUTES = {}", params);
+///
+/// Read the whole of the shader source file `file_name`, ready to hand to GL.
+/// The source has to be UTF-8 without any NUL bytes in it, since GL would stop
+/// reading it at the first one.
+///
+fn read_shader_source(file_name: &str) -> Result<CString, String> {
+    let mut file = match File::open(file_name) {
+        Ok(val) => val,
+        Err(e) => return Err(format!("ERROR: opening file for reading: {}: {}", file_name, e)),
+    };
+
+    let mut bytes = Vec::new();
+    if let Err(e) = file.read_to_end(&mut bytes) {
+        return Err(format!("ERROR: reading shader file {}: {}", file_name, e));
     }
 
-    for i in 0..params {
-        let mut name = [0; 64];
-        let max_length = 64;
-        let mut actual_length = 0;
-        let mut size = 0;
-        let mut gl_type: GLenum = 0;
-        unsafe {
-            gl::GetActiveAttrib(sp, i as GLuint, max_length, &mut actual_length, &mut size, &mut gl_type, &mut name[0]);
-        }
-        if size > 1 {
-            for j in 0..size {
-                let mut long_name = vec![];
-                //write!(long_name, "{}[{}]", name, j);
-                let location = unsafe { gl::GetAttribLocation(sp, long_name.as_ptr() as *const i8) };
-                println!(
-                    "  {}) type:{} name:{} location:{}", 
-                    i, GL_type_to_string(gl_type), long_name.iter().map(|ch| *ch as u8 as char).collect::<String>(), location
-                );
-            }
-        } else {
-            let location = unsafe { gl::GetAttribLocation(sp, &mut name[0]) };
-            println!(
-                "  {}) type:{} name:{} location:{}",
-                i, GL_type_to_string(gl_type), name.iter().map(|ch| *ch as u8 as char).collect::<String>(), location
-            );
-        }
-    }
-    
-    unsafe {
-        gl::GetProgramiv(sp, gl::ACTIVE_UNIFORMS, &mut params);
-    }
-    println!("GL_ACTIVE_UNIFORMS = {}", params);
-    for i in 0..params {
-        let mut name = [0; 64];
-        let max_length = 64;
-        let mut actual_length = 0;
-        let mut size = 0;
-        let mut gl_type: GLenum = 0;
-        unsafe {
-            gl::GetActiveUniform(sp, i as u32, max_length, &mut actual_length, &mut size, &mut gl_type, &mut name[0]);
-        }
-        if size > 1 {
-            for j in 0..size {
-                let long_name = [0; 64];
-
-                //write!(long_name, "{}[{}]", name, j);
-                let location = unsafe { gl::GetUniformLocation(sp, long_name.as_ptr()) };
-                println!(
-                    "  {}) type:{} name:{} location:{}",
-                    i, GL_type_to_string(gl_type), long_name.iter().map(|ch| *ch as u8 as char).collect::<String>(), location
-                );
-            }
-        } else {
-            let location = unsafe { gl::GetUniformLocation(sp, &name[0]) };
-            println!(
-                "  {}) type:{} name:{} location:{}", 
-                i, GL_type_to_string(gl_type), name.iter().map(|ch| *ch as u8 as char).collect::<String>(), location
-            );
-        }
-    }
-
-    _print_programme_info_log(sp);
-}
-
-fn parse_file_into_str(file_name: &str, shader_str: &mut [u8], max_len: usize) -> bool {
-    let file = File::open(file_name);
-    if file.is_err() {
-        gl_utils::gl_log_err(&format!("ERROR: opening file for reading: {}\n", file_name));
-        return false;
-    }
-
-    let mut file = file.unwrap();
+    let source = match String::from_utf8(bytes) {
+        Ok(val) => val,
+        Err(e) => return Err(format!("ERROR: shader file {} is not UTF-8: {}", file_name, e.utf8_error())),
+    };
 
-    let bytes_read = file.read(shader_str);
-    if bytes_read.is_err() {
-        gl_utils::gl_log_err(&format!("ERROR: reading shader file {}\n", file_name));
-        return false;
-    }
-
-    let bytes_read = bytes_read.unwrap();
-    if bytes_read >= (max_len - 1) {
-        gl_utils::gl_log_err(&format!("WARNING: file {} too big - truncated.\n", file_name));
+    match CString::new(source) {
+        Ok(val) => Ok(val),
+        Err(e) => Err(format!("ERROR: shader file {} has a NUL byte at {}", file_name, e.nul_position())),
     }
-
-    // append \0 to end of file string.
-    shader_str[bytes_read] = 0;
-
-    return true;
 }
 
 fn main() {
@@ -210,7 +111,7 @@ fn main() {
     ];
 
     gl_utils::restart_gl_log();
-    let (mut glfw, mut window, events) = gl_utils::start_gl().unwrap();
+    let mut context = gl_utils::start_gl().unwrap();
     unsafe {
         // Tell GL to only draw onto a pixel if the shape is closer to the viewer.
         // Enable depth-testing.
@@ -245,11 +146,21 @@ fn main() {
         gl::EnableVertexAttribArray(1);
 
         /* load shaders from files here */
-        let mut vertex_shader = vec![0; 1024 * 256];
-        parse_file_into_str("src/test_vs.glsl", &mut vertex_shader, 1024 * 256);
+        let vertex_shader = match read_shader_source("src/test_vs.glsl") {
+            Ok(val) => val,
+            Err(e) => {
+                eprintln!("{}", e);
+                process::exit(1);
+            }
+        };
 
-        let mut fragment_shader = vec![0; 1024 * 256];
-        parse_file_into_str("src/test_fs.glsl", &mut fragment_shader, 1024 * 256);
+        let fragment_shader = match read_shader_source("src/test_fs.glsl") {
+            Ok(val) => val,
+            Err(e) => {
+                eprintln!("{}", e);
+                process::exit(1);
+            }
+        };
 
         let vs: GLuint = gl::CreateShader(gl::VERTEX_SHADER);
         let p = vertex_shader.as_ptr() as *const GLchar;
@@ -291,28 +202,28 @@ fn main() {
             _print_programme_info_log(shader_programme);
             process::exit(1);
         }
-        print_all(shader_programme);
+        gl_utils::introspect(shader_programme).log();
         let result = is_valid(shader_programme);
         assert!(result);
 
-        gl_utils::PREVIOUS_SECONDS = glfw.get_time();
-        while !window.should_close() {
-            gl_utils::_update_fps_counter(&mut glfw, &mut window);
+        context.framerate_time_seconds = context.glfw.get_time();
+        while !context.window.should_close() {
+            gl_utils::update_fps_counter(&mut context);
             // Wipe the drawing surface clear.
             gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
             gl::ClearColor(0.3, 0.3, 0.3, 1.0);
-            gl::Viewport(0, 0, gl_utils::G_GL_WIDTH as GLint, gl_utils::G_GL_HEIGHT as GLint);
+            gl::Viewport(0, 0, context.width as GLint, context.height as GLint);
 
             gl::UseProgram(shader_programme);
             gl::BindVertexArray(vao);
             // Draw points 0-3 from the currently bound VAO with current in-use shader.
             gl::DrawArrays(gl::TRIANGLES, 0, 3);
             // Update other events like input handling.
-            glfw.poll_events();
-            for (_, event) in glfw::flush_messages(&events) {
+            context.glfw.poll_events();
+            for (_, event) in glfw::flush_messages(&context.events) {
                 match event {
                     glfw::WindowEvent::Key(Key::Escape, _, Action::Press, _) => {
-                        window.set_should_close(true);
+                        context.window.set_should_close(true);
                     }
                     _ => {
 
@@ -321,7 +232,7 @@ fn main() {
             }
 
             // Put the stuff we've been drawing onto the display.
-            window.swap_buffers();
+            context.window.swap_buffers();
         }
     }
 }
@@ -1,6 +1,13 @@
+use std::cmp;
 use std::fmt;
 use std::ops;
 use std::convert::From;
+use std::convert;
+
+#[cfg(feature = "glam")]
+use glam;
+#[cfg(feature = "cgmath")]
+use cgmath;
 
 
 // Constants used to convert degrees into radians.
@@ -8,11 +15,16 @@ pub const M_PI: f32 = 3.14159265358979323846264338327950288;
 pub const TAU: f32 = 2.0 * M_PI;
 pub const ONE_DEG_IN_RAD: f32 = (2.0 * M_PI) / 360.0; // == 0.017444444
 pub const ONE_RAD_IN_DEG: f32 = 360.0 / (2.0 * M_PI); // == 57.2957795
+pub const EPSILON: f32 = 0.00001; 
 
 
+///
+/// A representation of two-dimensional vectors, with a
+/// Euclidean metric.
+///
 #[derive(Copy, Clone, Debug)]
 pub struct Vec2 {
-    v: [f32; 2],
+    pub v: [f32; 2],
 }
 
 impl Vec2 {
@@ -23,1001 +35,4889 @@ impl Vec2 {
     pub fn zero() -> Vec2 { 
         Vec2 { v: [0.0, 0.0] }
     }
-}
-
-#[inline]
-pub fn vec2(x: f32, y: f32) -> Vec2 {
-    Vec2::new(x, y)
-}
 
-impl fmt::Display for Vec2 {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "[{:.2}, {:.2}]", self.v[0], self.v[1])
+    ///
+    /// The unit vector along the x-axis.
+    ///
+    pub fn unit_x() -> Vec2 {
+        Vec2::new(1.0, 0.0)
     }
-}
-
-#[derive(Copy, Clone, Debug)]
-pub struct Vec3 {
-    v: [f32; 3],
-}
 
-impl Vec3 {
-    pub fn new(x: f32, y: f32, z: f32) -> Vec3 {
-        Vec3 { v: [x, y, z] }
+    ///
+    /// The unit vector along the y-axis.
+    ///
+    pub fn unit_y() -> Vec2 {
+        Vec2::new(0.0, 1.0)
     }
 
-    pub fn zero() -> Vec3 {
-        Vec3 { v: [0.0, 0.0, 0.0] }
-    }
-    
+    ///
+    /// Compute the norm (length) of a vector.
+    ///
     pub fn norm(&self) -> f32 {
-        f32::sqrt(self.v[0] * self.v[0] + self.v[1] * self.v[1] + self.v[2] * self.v[2])
+        f32::sqrt(self.v[0] * self.v[0] + self.v[1] * self.v[1])
     }
 
-    // Squared length.
+    ///
+    /// Compute the squared norm (length) of a vector.
+    ///
     pub fn norm2(&self) -> f32 {
-        self.v[0] * self.v[0] + self.v[1] * self.v[1] + self.v[2] * self.v[2]
+        self.v[0] * self.v[0] + self.v[1] * self.v[1]
     }
 
-    pub fn normalize(&self) -> Vec3 {
+    ///
+    /// Convert an arbitrary vector into a unit vector.
+    ///
+    pub fn normalize(&self) -> Vec2 {
         let norm_v = self.norm();
         if norm_v == 0.0 {
-            return Vec3::zero();
+            return Vec2::zero();
         }
 
-        Vec3::new(self.v[0] / norm_v, self.v[1] / norm_v, self.v[2] / norm_v)
+        Vec2::new(self.v[0] / norm_v, self.v[1] / norm_v)
     }
 
-    pub fn dot(&self, other: &Vec3) -> f32 {
-        self.v[0] * other.v[0] + self.v[1] * other.v[1] + self.v[2] * other.v[2]
+    ///
+    /// Compute the dot product of two vectors.
+    ///
+    pub fn dot(&self, other: &Vec2) -> f32 {
+        self.v[0] * other.v[0] + self.v[1] * other.v[1]
     }
 
-    pub fn cross(&self, other: &Vec3) -> Vec3 {
-        let x = self.v[1] * other.v[2] - self.v[2] * other.v[1];
-        let y = self.v[2] * other.v[0] - self.v[0] * other.v[2];
-        let z = self.v[0] * other.v[1] - self.v[1] * other.v[0];
-    
-        Vec3::new(x, y, z)
+    ///
+    /// Interpolate linearly from this vector to `other`, reaching `other` when
+    /// `t` is one. This is GLSL's `mix`.
+    ///
+    pub fn lerp(&self, other: &Vec2, t: f32) -> Vec2 {
+        self + (other - self) * t
     }
 
-    pub fn get_squared_dist(&self, to: &Vec3) -> f32 {
-        let x = (to.v[0] - self.v[0]) * (to.v[0] - self.v[0]);
-        let y = (to.v[1] - self.v[1]) * (to.v[1] - self.v[1]);
-        let z = (to.v[2] - self.v[2]) * (to.v[2] - self.v[2]);
-    
-        x + y + z
+    ///
+    /// The smaller of each pair of components.
+    ///
+    pub fn min(&self, other: &Vec2) -> Vec2 {
+        Vec2 {
+            v: [
+                f32::min(self.v[0], other.v[0]),
+                f32::min(self.v[1], other.v[1]),
+            ]
+        }
     }
-}
-
-#[inline]
-pub fn vec3<T: Into<Vec3>>(v: T) -> Vec3 {
-    v.into()
-}
 
-impl From<(f32, f32, f32)> for Vec3 {
-    #[inline]
-    fn from((x, y, z): (f32, f32, f32)) -> Vec3 {
-        Vec3::new(x, y, z)
+    ///
+    /// The larger of each pair of components.
+    ///
+    pub fn max(&self, other: &Vec2) -> Vec2 {
+        Vec2 {
+            v: [
+                f32::max(self.v[0], other.v[0]),
+                f32::max(self.v[1], other.v[1]),
+            ]
+        }
     }
-}
 
-impl From<(Vec2, f32)> for Vec3 {
-    #[inline]
-    fn from((v, z): (Vec2, f32)) -> Vec3 {
-        Vec3::new(v.v[0], v.v[1], z)
+    ///
+    /// Clamp each component between the matching components of `min` and `max`.
+    ///
+    pub fn clamp(&self, min: &Vec2, max: &Vec2) -> Vec2 {
+        self.max(min).min(max)
     }
 }
 
-impl<'a> From<(&'a Vec2, f32)> for Vec3 {
-    #[inline]
-    fn from((v, z): (&'a Vec2, f32)) -> Vec3 {
-        Vec3::new(v.v[0], v.v[1], z)
-    }
+#[inline]
+pub fn vec2(x: f32, y: f32) -> Vec2 {
+    Vec2::new(x, y)
 }
 
-impl fmt::Display for Vec3 {
+impl fmt::Display for Vec2 {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "[{:.2}, {:.2}, {:.2}]", self.v[0], self.v[1], self.v[2])
+        write!(f, "[{:.2}, {:.2}]", self.v[0], self.v[1])
     }
 }
 
-impl<'a> ops::Add<Vec3> for &'a Vec3 {
-    type Output = Vec3;
+impl<'a> ops::Add<Vec2> for &'a Vec2 {
+    type Output = Vec2;
 
-    fn add(self, other: Vec3) -> Self::Output {
-        Vec3 {
+    fn add(self, other: Vec2) -> Self::Output {
+        Vec2 {
             v: [
                 self.v[0] + other.v[0],
                 self.v[1] + other.v[1],
-                self.v[2] + other.v[2],
             ]
         }
     }
 }
 
-impl ops::Add<Vec3> for Vec3 {
-    type Output = Vec3;
+impl ops::Add<Vec2> for Vec2 {
+    type Output = Vec2;
 
-    fn add(self, other: Vec3) -> Self::Output {
-        Vec3 {
+    fn add(self, other: Vec2) -> Self::Output {
+        Vec2 {
             v: [
                 self.v[0] + other.v[0],
                 self.v[1] + other.v[1],
-                self.v[2] + other.v[2],
             ]
         }
     }
 }
 
-impl<'a> ops::Add<&'a Vec3> for Vec3 {
-    type Output = Vec3;
+impl<'a> ops::Add<&'a Vec2> for Vec2 {
+    type Output = Vec2;
 
-    fn add(self, other: &'a Vec3) -> Self::Output {
-        Vec3 {
+    fn add(self, other: &'a Vec2) -> Self::Output {
+        Vec2 {
             v: [
                 self.v[0] + other.v[0],
                 self.v[1] + other.v[1],
-                self.v[2] + other.v[2],               
             ]
         }
     }
 }
 
-impl<'a, 'b> ops::Add<&'b Vec3> for &'a Vec3 {
-    type Output = Vec3;
+impl<'a, 'b> ops::Add<&'b Vec2> for &'a Vec2 {
+    type Output = Vec2;
 
-    fn add(self, other: &'b Vec3) -> Self::Output {
-        Vec3 {
+    fn add(self, other: &'b Vec2) -> Self::Output {
+        Vec2 {
             v: [
                 self.v[0] + other.v[0],
                 self.v[1] + other.v[1],
-                self.v[2] + other.v[2],
             ]
         }
     }
 }
 
-impl ops::Add<f32> for Vec3 {
-    type Output = Vec3;
+impl ops::Add<f32> for Vec2 {
+    type Output = Vec2;
 
     fn add(self, other: f32) -> Self::Output {
-        Vec3 {
+        Vec2 {
             v: [
                 self.v[0] + other,
                 self.v[1] + other,
-                self.v[2] + other,
             ]
         }
     }
 }
 
-impl<'a> ops::Sub<Vec3> for &'a Vec3 {
-    type Output = Vec3;
+impl<'a> ops::Sub<Vec2> for &'a Vec2 {
+    type Output = Vec2;
 
-    fn sub(self, other: Vec3) -> Self::Output {
-        Vec3 {
+    fn sub(self, other: Vec2) -> Self::Output {
+        Vec2 {
             v: [
                 self.v[0] - other.v[0],
                 self.v[1] - other.v[1],
-                self.v[2] - other.v[2],
             ]
         }
     }
 }
 
-impl ops::Sub<Vec3> for Vec3 {
-    type Output = Vec3;
+impl ops::Sub<Vec2> for Vec2 {
+    type Output = Vec2;
 
-    fn sub(self, other: Vec3) -> Self::Output {
-        Vec3 {
+    fn sub(self, other: Vec2) -> Self::Output {
+        Vec2 {
             v: [
                 self.v[0] - other.v[0],
                 self.v[1] - other.v[1],
-                self.v[2] - other.v[2],
             ]
         }
     }
 }
 
-impl<'a> ops::Sub<&'a Vec3> for Vec3 {
-    type Output = Vec3;
+impl<'a> ops::Sub<&'a Vec2> for Vec2 {
+    type Output = Vec2;
 
-    fn sub(self, other: &'a Vec3) -> Self::Output {
-        Vec3 {
+    fn sub(self, other: &'a Vec2) -> Self::Output {
+        Vec2 {
             v: [
                 self.v[0] - other.v[0],
                 self.v[1] - other.v[1],
-                self.v[2] - other.v[2],               
             ]
         }
     }
 }
 
-impl<'a, 'b> ops::Sub<&'b Vec3> for &'a Vec3 {
-    type Output = Vec3;
+impl<'a, 'b> ops::Sub<&'b Vec2> for &'a Vec2 {
+    type Output = Vec2;
 
-    fn sub(self, other: &'b Vec3) -> Self::Output {
-        Vec3 {
+    fn sub(self, other: &'b Vec2) -> Self::Output {
+        Vec2 {
             v: [
                 self.v[0] - other.v[0],
                 self.v[1] - other.v[1],
-                self.v[2] - other.v[2],
             ]
         }
     }
 }
 
-impl ops::Sub<f32> for Vec3 {
-    type Output = Vec3;
+impl ops::Sub<f32> for Vec2 {
+    type Output = Vec2;
 
     fn sub(self, other: f32) -> Self::Output {
-        Vec3 {
+        Vec2 {
             v: [
                 self.v[0] - other,
                 self.v[1] - other,
-                self.v[2] - other,
             ]
         }
     }
 }
 
-impl ops::AddAssign<Vec3> for Vec3 {
-    fn add_assign(&mut self, other: Vec3) {
-        *self = Vec3 {
-            v: [
-                self.v[0] + other.v[0],
-                self.v[1] + other.v[1],
-                self.v[2] + other.v[2],
-            ]
-        }
+impl ops::AddAssign<Vec2> for Vec2 {
+    fn add_assign(&mut self, other: Vec2) {
+        self.v[0] += other.v[0];
+        self.v[1] += other.v[1];
     }
 }
 
-impl<'a> ops::AddAssign<&'a Vec3> for Vec3 {
-    fn add_assign(&mut self, other: &'a Vec3) {
-        *self = Vec3 {
-            v: [
-                self.v[0] + other.v[0],
-                self.v[1] + other.v[1],
-                self.v[2] + other.v[2],
-            ]
-        }
+impl<'a> ops::AddAssign<&'a Vec2> for Vec2 {
+    fn add_assign(&mut self, other: &'a Vec2) {
+        self.v[0] += other.v[0];
+        self.v[1] += other.v[1];
     }
 }
 
-impl<'a> ops::AddAssign<Vec3> for &'a mut Vec3 {
-    fn add_assign(&mut self, other: Vec3) {
-        **self = Vec3 {
-            v: [
-                self.v[0] + other.v[0],
-                self.v[1] + other.v[1],
-                self.v[2] + other.v[2],
-            ]
-        }
+impl ops::AddAssign<f32> for Vec2 {
+    fn add_assign(&mut self, other: f32) {
+        self.v[0] += other;
+        self.v[1] += other;
     }
 }
 
-impl<'a, 'b> ops::AddAssign<&'a Vec3> for &'b mut Vec3 {
-    fn add_assign(&mut self, other: &'a Vec3) {
-        **self = Vec3 {
-            v: [
-                self.v[0] + other.v[0],
-                self.v[1] + other.v[1],
-                self.v[2] + other.v[2],
-            ]
-        }
+impl ops::SubAssign<Vec2> for Vec2 {
+    fn sub_assign(&mut self, other: Vec2) {
+        self.v[0] -= other.v[0];
+        self.v[1] -= other.v[1];
     }
 }
 
-impl ops::AddAssign<f32> for Vec3 {
-    fn add_assign(&mut self, other: f32) {
-        *self = Vec3 {
+impl<'a> ops::SubAssign<&'a Vec2> for Vec2 {
+    fn sub_assign(&mut self, other: &'a Vec2) {
+        self.v[0] -= other.v[0];
+        self.v[1] -= other.v[1];
+    }
+}
+
+impl ops::SubAssign<f32> for Vec2 {
+    fn sub_assign(&mut self, other: f32) {
+        self.v[0] -= other;
+        self.v[1] -= other;
+    }
+}
+
+impl ops::Mul<f32> for Vec2 {
+    type Output = Vec2;
+
+    fn mul(self, other: f32) -> Self::Output {
+        Vec2 {
             v: [
-                self.v[0] + other,
-                self.v[1] + other,
-                self.v[2] + other,
+                self.v[0] * other,
+                self.v[1] * other,
             ]
         }
     }
 }
 
-impl ops::SubAssign<Vec3> for Vec3 {
-    fn sub_assign(&mut self, other: Vec3) {
-        *self = Vec3 {
+impl<'a> ops::Mul<f32> for &'a Vec2 {
+    type Output = Vec2;
+
+    fn mul(self, other: f32) -> Self::Output {
+        Vec2 {
             v: [
-                self.v[0] - other.v[0],
-                self.v[1] - other.v[1],
-                self.v[2] - other.v[2],
+                self.v[0] * other,
+                self.v[1] * other,
             ]
         }
     }
 }
 
-impl<'a> ops::SubAssign<&'a Vec3> for Vec3 {
-    fn sub_assign(&mut self, other: &'a Vec3) {
-        *self = Vec3 {
+impl ops::Div<f32> for Vec2 {
+    type Output = Vec2;
+
+    fn div(self, other: f32) -> Self::Output {
+        Vec2 {
             v: [
-                self.v[0] - other.v[0],
-                self.v[1] - other.v[1],
-                self.v[2] - other.v[2],
+                self.v[0] / other,
+                self.v[1] / other,
             ]
         }
     }
 }
 
-impl<'a> ops::SubAssign<Vec3> for &'a mut Vec3 {
-    fn sub_assign(&mut self, other: Vec3) {
-        **self = Vec3 {
+impl<'a> ops::Div<f32> for &'a Vec2 {
+    type Output = Vec2;
+
+    fn div(self, other: f32) -> Self::Output {
+        Vec2 {
             v: [
-                self.v[0] - other.v[0],
-                self.v[1] - other.v[1],
-                self.v[2] - other.v[2],
+                self.v[0] / other,
+                self.v[1] / other,
             ]
         }
     }
 }
 
-impl<'a, 'b> ops::SubAssign<&'a Vec3> for &'b mut Vec3 {
-    fn sub_assign(&mut self, other: &'a Vec3) {
-        **self = Vec3 {
+impl ops::MulAssign<f32> for Vec2 {
+    fn mul_assign(&mut self, other: f32) {
+        self.v[0] *= other;
+        self.v[1] *= other;
+    }
+}
+
+impl ops::DivAssign<f32> for Vec2 {
+    fn div_assign(&mut self, other: f32) {
+        self.v[0] /= other;
+        self.v[1] /= other;
+    }
+}
+
+impl ops::Add<Vec2> for f32 {
+    type Output = Vec2;
+
+    fn add(self, other: Vec2) -> Self::Output {
+        Vec2 {
             v: [
-                self.v[0] - other.v[0],
-                self.v[1] - other.v[1],
-                self.v[2] - other.v[2],
+                self + other.v[0],
+                self + other.v[1],
             ]
         }
     }
 }
 
-impl ops::SubAssign<f32> for Vec3 {
-    fn sub_assign(&mut self, other: f32) {
-        *self = Vec3 {
+impl ops::Sub<Vec2> for f32 {
+    type Output = Vec2;
+
+    fn sub(self, other: Vec2) -> Self::Output {
+        Vec2 {
             v: [
-                self.v[0] - other,
-                self.v[1] - other,
-                self.v[2] - other,
+                self - other.v[0],
+                self - other.v[1],
             ]
         }
     }
 }
 
-impl ops::Mul<f32> for Vec3 {
-    type Output = Vec3;
+impl ops::Mul<Vec2> for f32 {
+    type Output = Vec2;
 
-    fn mul(self, other: f32) -> Vec3 {
-        Vec3 {
+    fn mul(self, other: Vec2) -> Self::Output {
+        Vec2 {
             v: [
-                self.v[0] * other,
-                self.v[1] * other,
-                self.v[2] * other,
+                self * other.v[0],
+                self * other.v[1],
             ]
         }
     }
 }
 
-impl<'a> ops::Mul<f32> for &'a Vec3 {
-    type Output = Vec3;
+impl ops::Div<Vec2> for f32 {
+    type Output = Vec2;
 
-    fn mul(self, other: f32) -> Vec3 {
-        Vec3 {
+    fn div(self, other: Vec2) -> Self::Output {
+        Vec2 {
             v: [
-                self.v[0] * other,
-                self.v[1] * other,
-                self.v[2] * other,
+                self / other.v[0],
+                self / other.v[1],
             ]
         }
     }
 }
 
-impl ops::Div<f32> for Vec3 {
-    type Output = Vec3;
+impl ops::Neg for Vec2 {
+    type Output = Vec2;
 
-    fn div(self, other: f32) -> Vec3 {
-        Vec3 {
+    fn neg(self) -> Self::Output {
+        Vec2 {
             v: [
-                self.v[0] / other,
-                self.v[1] / other,
-                self.v[2] / other,
+                -self.v[0],
+                -self.v[1],
             ]
         }
     }
 }
 
-impl<'a> ops::Div<f32> for &'a Vec3 {
-    type Output = Vec3;
+impl<'a> ops::Neg for &'a Vec2 {
+    type Output = Vec2;
 
-    fn div(self, other: f32) -> Vec3 {
-        Vec3 {
+    fn neg(self) -> Self::Output {
+        Vec2 {
             v: [
-                self.v[0] / other,
-                self.v[1] / other,
-                self.v[2] / other,
+                -self.v[0],
+                -self.v[1],
             ]
         }
     }
 }
 
-impl ops::DivAssign<f32> for Vec3 {
-    fn div_assign(&mut self, other: f32) {
-        *self = Vec3 {
-            v: [
-                self.v[0] / other,
-                self.v[1] / other,
-                self.v[2] / other,
-            ]
-        }
+impl ops::Index<usize> for Vec2 {
+    type Output = f32;
+
+    #[inline]
+    fn index(&self, index: usize) -> &f32 {
+        &self.v[index]
     }
 }
 
-impl<'a> ops::DivAssign<f32> for &'a mut Vec3 {
-    fn div_assign(&mut self, other: f32) {
-        **self = Vec3 {
-            v: [
-                self.v[0] / other,
-                self.v[1] / other,
-                self.v[2] / other,
-            ]
-        }
+impl ops::IndexMut<usize> for Vec2 {
+    #[inline]
+    fn index_mut(&mut self, index: usize) -> &mut f32 {
+        &mut self.v[index]
     }
 }
 
-#[derive(Copy, Clone, Debug)]
-pub struct Vec4 {
-    v: [f32; 4],
+impl cmp::PartialEq for Vec2 {
+    fn eq(&self, other: &Vec2) -> bool {
+        (f32::abs(self.v[0] - other.v[0]) < EPSILON) &&
+        (f32::abs(self.v[1] - other.v[1]) < EPSILON)
+    }
 }
 
-impl Vec4 {
-    pub fn new(x: f32, y: f32, z: f32, w: f32) -> Vec4 {
-        Vec4 { v: [x, y, z, w] }
+///
+/// A representation of three-dimensional vectors, with a
+/// Euclidean metric.
+///
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Vec3 {
+    pub v: [f32; 3],
+}
+
+impl Vec3 {
+    ///
+    /// Create a new vector.
+    ///
+    pub fn new(x: f32, y: f32, z: f32) -> Vec3 {
+        Vec3 { v: [x, y, z] }
     }
 
-    pub fn zero() -> Vec4 {
-        Vec4 { v: [0.0, 0.0, 0.0, 0.0] }
+    ///
+    /// Generate a zero vector.
+    ///
+    pub fn zero() -> Vec3 {
+        Vec3 { v: [0.0, 0.0, 0.0] }
+    }
+
+    ///
+    /// The unit vector along the x-axis.
+    ///
+    pub fn unit_x() -> Vec3 {
+        Vec3::new(1.0, 0.0, 0.0)
+    }
+
+    ///
+    /// The unit vector along the y-axis.
+    ///
+    pub fn unit_y() -> Vec3 {
+        Vec3::new(0.0, 1.0, 0.0)
+    }
+
+    ///
+    /// The unit vector along the z-axis.
+    ///
+    pub fn unit_z() -> Vec3 {
+        Vec3::new(0.0, 0.0, 1.0)
+    }
+    
+    ///
+    /// Compute the norm (length) of a vector.
+    ///
+    pub fn norm(&self) -> f32 {
+        f32::sqrt(self.v[0] * self.v[0] + self.v[1] * self.v[1] + self.v[2] * self.v[2])
+    }
+
+    ///
+    /// Compute the squared norm (length) of a vector.
+    ///
+    pub fn norm2(&self) -> f32 {
+        self.v[0] * self.v[0] + self.v[1] * self.v[1] + self.v[2] * self.v[2]
+    }
+
+    ///
+    /// Convert an arbitrary vector into a unit vector.
+    ///
+    pub fn normalize(&self) -> Vec3 {
+        let norm_v = self.norm();
+        if norm_v == 0.0 {
+            return Vec3::zero();
+        }
+
+        Vec3::new(self.v[0] / norm_v, self.v[1] / norm_v, self.v[2] / norm_v)
+    }
+
+    ///
+    /// Compute the dot product of two vectors.
+    ///
+    pub fn dot(&self, other: &Vec3) -> f32 {
+        self.v[0] * other.v[0] + self.v[1] * other.v[1] + self.v[2] * other.v[2]
+    }
+
+    ///
+    /// Compute the cross product of two three-dimensional vectors. Note that
+    /// with the vectors used in computer graphics (two, three, and four dimensions),
+    /// the cross product is defined only in three dimensions. Also note that the 
+    /// cross product is the hodge dual of the corresponding 2-vector representing 
+    /// the surface element that the crossed vector is normal to. That is, 
+    /// given vectors u and v, u x v == *(u /\ v), where *(.) denotes the hodge dual.
+    ///
+    pub fn cross(&self, other: &Vec3) -> Vec3 {
+        let x = self.v[1] * other.v[2] - self.v[2] * other.v[1];
+        let y = self.v[2] * other.v[0] - self.v[0] * other.v[2];
+        let z = self.v[0] * other.v[1] - self.v[1] * other.v[0];
+    
+        Vec3::new(x, y, z)
+    }
+
+    ///
+    /// Compute the squared distance between two vectors.
+    ///
+    pub fn get_squared_dist(&self, to: &Vec3) -> f32 {
+        let x = (to.v[0] - self.v[0]) * (to.v[0] - self.v[0]);
+        let y = (to.v[1] - self.v[1]) * (to.v[1] - self.v[1]);
+        let z = (to.v[2] - self.v[2]) * (to.v[2] - self.v[2]);
+    
+        x + y + z
+    }
+
+    ///
+    /// Interpolate linearly from this vector to `other`, reaching `other` when
+    /// `t` is one. This is GLSL's `mix`.
+    ///
+    pub fn lerp(&self, other: &Vec3, t: f32) -> Vec3 {
+        self + (other - self) * t
+    }
+
+    ///
+    /// The smaller of each pair of components.
+    ///
+    pub fn min(&self, other: &Vec3) -> Vec3 {
+        Vec3 {
+            v: [
+                f32::min(self.v[0], other.v[0]),
+                f32::min(self.v[1], other.v[1]),
+                f32::min(self.v[2], other.v[2]),
+            ]
+        }
+    }
+
+    ///
+    /// The larger of each pair of components.
+    ///
+    pub fn max(&self, other: &Vec3) -> Vec3 {
+        Vec3 {
+            v: [
+                f32::max(self.v[0], other.v[0]),
+                f32::max(self.v[1], other.v[1]),
+                f32::max(self.v[2], other.v[2]),
+            ]
+        }
+    }
+
+    ///
+    /// Clamp each component between the matching components of `min` and `max`.
+    ///
+    pub fn clamp(&self, min: &Vec3, max: &Vec3) -> Vec3 {
+        self.max(min).min(max)
     }
 }
 
+///
+/// Construct a new three-dimensional vector in the style of
+/// a GLSL vec3 constructor.
+///
 #[inline]
-pub fn vec4<T: Into<Vec4>>(v: T) -> Vec4 {
+pub fn vec3<T: Into<Vec3>>(v: T) -> Vec3 {
     v.into()
 }
 
-impl From<(f32, f32, f32, f32)> for Vec4 {
+impl From<(f32, f32, f32)> for Vec3 {
     #[inline]
-    fn from((x, y, z, w): (f32, f32, f32, f32)) -> Vec4 {
-        Vec4::new(x, y, z, w)
+    fn from((x, y, z): (f32, f32, f32)) -> Vec3 {
+        Vec3::new(x, y, z)
     }
 }
 
-impl From<(Vec2, f32, f32)> for Vec4 {
+impl From<(Vec2, f32)> for Vec3 {
     #[inline]
-    fn from((v, z, w): (Vec2, f32, f32)) -> Vec4 {
-        Vec4::new(v.v[0], v.v[1], z, w)
+    fn from((v, z): (Vec2, f32)) -> Vec3 {
+        Vec3::new(v.v[0], v.v[1], z)
     }
 }
 
-impl<'a> From<(&'a Vec2, f32, f32)> for Vec4 {
+impl<'a> From<(&'a Vec2, f32)> for Vec3 {
     #[inline]
-    fn from((v, z, w): (&'a Vec2, f32, f32)) -> Vec4 {
-        Vec4::new(v.v[0], v.v[1], z, w)
+    fn from((v, z): (&'a Vec2, f32)) -> Vec3 {
+        Vec3::new(v.v[0], v.v[1], z)
     }
 }
 
-impl From<(Vec3, f32)> for Vec4 {
+impl<'a> From<Vec4> for Vec3 {
     #[inline]
-    fn from((v, w): (Vec3, f32)) -> Vec4 {
-        Vec4::new(v.v[0], v.v[1], v.v[2], w)
+    fn from(v: Vec4) -> Vec3 {
+        Vec3::new(v.v[0], v.v[1], v.v[2])
     }
 }
 
-impl<'a> From<(&'a Vec3, f32)> for Vec4 {
+impl<'a> From<&'a Vec4> for Vec3 {
     #[inline]
-    fn from((v, w): (&'a Vec3, f32)) -> Vec4 {
-        Vec4::new(v.v[0], v.v[1], v.v[2], w)
+    fn from(v: &'a Vec4) -> Vec3 {
+        Vec3::new(v.v[0], v.v[1], v.v[2])
     }
 }
 
-impl fmt::Display for Vec4 {
+impl fmt::Display for Vec3 {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "[{:.2}, {:.2}, {:.2}, {:.2}]", self.v[0], self.v[1], self.v[2], self.v[3])
+        write!(f, "[{:.2}, {:.2}, {:.2}]", self.v[0], self.v[1], self.v[2])
     }
 }
 
-///
-/// The `Mat3` type represents 3x3 matrices in column-major order.
-///
-#[derive(Copy, Clone, Debug)]
-pub struct Mat3 {
-    m: [f32; 9],
-}
-
-impl Mat3 {
-    pub fn new(
-        m11: f32, m12: f32, m13: f32, 
-        m21: f32, m22: f32, m23: f32, 
-        m31: f32, m32: f32, m33: f32) -> Mat3 {
+impl<'a> ops::Add<Vec3> for &'a Vec3 {
+    type Output = Vec3;
 
-        Mat3 {
-            m: [
-                m11, m12, m13, // Column 1
-                m21, m22, m23, // Column 2
-                m31, m32, m33  // Column 3
+    fn add(self, other: Vec3) -> Self::Output {
+        Vec3 {
+            v: [
+                self.v[0] + other.v[0],
+                self.v[1] + other.v[1],
+                self.v[2] + other.v[2],
             ]
         }
     }
+}
 
-    pub fn zero() -> Mat3 {
-        Mat3::new(0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0)
-    }
+impl ops::Add<Vec3> for Vec3 {
+    type Output = Vec3;
 
-    pub fn identity() -> Mat3 {
-        Mat3::new(1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0)
+    fn add(self, other: Vec3) -> Self::Output {
+        Vec3 {
+            v: [
+                self.v[0] + other.v[0],
+                self.v[1] + other.v[1],
+                self.v[2] + other.v[2],
+            ]
+        }
     }
+}
 
-    pub fn as_ptr(&self) -> *const f32 {
-        self.m.as_ptr()
+impl<'a> ops::Add<&'a Vec3> for Vec3 {
+    type Output = Vec3;
+
+    fn add(self, other: &'a Vec3) -> Self::Output {
+        Vec3 {
+            v: [
+                self.v[0] + other.v[0],
+                self.v[1] + other.v[1],
+                self.v[2] + other.v[2],               
+            ]
+        }
     }
 }
 
-impl fmt::Display for Mat3 {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        writeln!(f, 
-            "\n[{:.2}][{:.2}][{:.2}]\n[{:.2}][{:.2}][{:.2}]\n[{:.2}][{:.2}][{:.2}]", 
-            self.m[0], self.m[3], self.m[6],
-            self.m[1], self.m[4], self.m[7],
-            self.m[2], self.m[5], self.m[8],
-        )
+impl<'a, 'b> ops::Add<&'b Vec3> for &'a Vec3 {
+    type Output = Vec3;
+
+    fn add(self, other: &'b Vec3) -> Self::Output {
+        Vec3 {
+            v: [
+                self.v[0] + other.v[0],
+                self.v[1] + other.v[1],
+                self.v[2] + other.v[2],
+            ]
+        }
     }
 }
 
-#[inline]
-fn mat3(m11: f32, m12: f32, m13: f32, 
-        m21: f32, m22: f32, m23: f32, 
-        m31: f32, m32: f32, m33: f32) -> Mat3 {
+impl ops::Add<f32> for Vec3 {
+    type Output = Vec3;
 
-    Mat3::new(m11, m12, m13, m21, m22, m23, m31, m32, m33)
+    fn add(self, other: f32) -> Self::Output {
+        Vec3 {
+            v: [
+                self.v[0] + other,
+                self.v[1] + other,
+                self.v[2] + other,
+            ]
+        }
+    }
 }
 
-///
-/// The `Mat4` type represents 4x4 matrices in column-major order.
-///
-#[derive(Copy, Clone, Debug)]
-pub struct Mat4 {
-    pub m: [f32; 16],
+impl<'a> ops::Sub<Vec3> for &'a Vec3 {
+    type Output = Vec3;
+
+    fn sub(self, other: Vec3) -> Self::Output {
+        Vec3 {
+            v: [
+                self.v[0] - other.v[0],
+                self.v[1] - other.v[1],
+                self.v[2] - other.v[2],
+            ]
+        }
+    }
 }
 
-impl Mat4 {
-    pub fn new(
-        m11: f32, m12: f32, m13: f32, m14: f32,
-        m21: f32, m22: f32, m23: f32, m24: f32,
-        m31: f32, m32: f32, m33: f32, m34: f32,
-        m41: f32, m42: f32, m43: f32, m44: f32) -> Mat4 {
+impl ops::Sub<Vec3> for Vec3 {
+    type Output = Vec3;
 
-        Mat4 {
-            m: [
-                m11, m12, m13, m14, // Column 1
-                m21, m22, m23, m24, // Column 2
-                m31, m32, m33, m34, // Column 3
-                m41, m42, m43, m44  // Column 4
+    fn sub(self, other: Vec3) -> Self::Output {
+        Vec3 {
+            v: [
+                self.v[0] - other.v[0],
+                self.v[1] - other.v[1],
+                self.v[2] - other.v[2],
             ]
         }
     }
+}
+
+impl<'a> ops::Sub<&'a Vec3> for Vec3 {
+    type Output = Vec3;
+
+    fn sub(self, other: &'a Vec3) -> Self::Output {
+        Vec3 {
+            v: [
+                self.v[0] - other.v[0],
+                self.v[1] - other.v[1],
+                self.v[2] - other.v[2],               
+            ]
+        }
+    }
+}
+
+impl<'a, 'b> ops::Sub<&'b Vec3> for &'a Vec3 {
+    type Output = Vec3;
+
+    fn sub(self, other: &'b Vec3) -> Self::Output {
+        Vec3 {
+            v: [
+                self.v[0] - other.v[0],
+                self.v[1] - other.v[1],
+                self.v[2] - other.v[2],
+            ]
+        }
+    }
+}
+
+impl ops::Sub<f32> for Vec3 {
+    type Output = Vec3;
+
+    fn sub(self, other: f32) -> Self::Output {
+        Vec3 {
+            v: [
+                self.v[0] - other,
+                self.v[1] - other,
+                self.v[2] - other,
+            ]
+        }
+    }
+}
+
+impl ops::AddAssign<Vec3> for Vec3 {
+    fn add_assign(&mut self, other: Vec3) {
+        *self = Vec3 {
+            v: [
+                self.v[0] + other.v[0],
+                self.v[1] + other.v[1],
+                self.v[2] + other.v[2],
+            ]
+        }
+    }
+}
+
+impl<'a> ops::AddAssign<&'a Vec3> for Vec3 {
+    fn add_assign(&mut self, other: &'a Vec3) {
+        *self = Vec3 {
+            v: [
+                self.v[0] + other.v[0],
+                self.v[1] + other.v[1],
+                self.v[2] + other.v[2],
+            ]
+        }
+    }
+}
+
+impl<'a> ops::AddAssign<Vec3> for &'a mut Vec3 {
+    fn add_assign(&mut self, other: Vec3) {
+        **self = Vec3 {
+            v: [
+                self.v[0] + other.v[0],
+                self.v[1] + other.v[1],
+                self.v[2] + other.v[2],
+            ]
+        }
+    }
+}
+
+impl<'a, 'b> ops::AddAssign<&'a Vec3> for &'b mut Vec3 {
+    fn add_assign(&mut self, other: &'a Vec3) {
+        **self = Vec3 {
+            v: [
+                self.v[0] + other.v[0],
+                self.v[1] + other.v[1],
+                self.v[2] + other.v[2],
+            ]
+        }
+    }
+}
+
+impl ops::AddAssign<f32> for Vec3 {
+    fn add_assign(&mut self, other: f32) {
+        *self = Vec3 {
+            v: [
+                self.v[0] + other,
+                self.v[1] + other,
+                self.v[2] + other,
+            ]
+        }
+    }
+}
+
+impl ops::SubAssign<Vec3> for Vec3 {
+    fn sub_assign(&mut self, other: Vec3) {
+        *self = Vec3 {
+            v: [
+                self.v[0] - other.v[0],
+                self.v[1] - other.v[1],
+                self.v[2] - other.v[2],
+            ]
+        }
+    }
+}
+
+impl<'a> ops::SubAssign<&'a Vec3> for Vec3 {
+    fn sub_assign(&mut self, other: &'a Vec3) {
+        *self = Vec3 {
+            v: [
+                self.v[0] - other.v[0],
+                self.v[1] - other.v[1],
+                self.v[2] - other.v[2],
+            ]
+        }
+    }
+}
+
+impl<'a> ops::SubAssign<Vec3> for &'a mut Vec3 {
+    fn sub_assign(&mut self, other: Vec3) {
+        **self = Vec3 {
+            v: [
+                self.v[0] - other.v[0],
+                self.v[1] - other.v[1],
+                self.v[2] - other.v[2],
+            ]
+        }
+    }
+}
+
+impl<'a, 'b> ops::SubAssign<&'a Vec3> for &'b mut Vec3 {
+    fn sub_assign(&mut self, other: &'a Vec3) {
+        **self = Vec3 {
+            v: [
+                self.v[0] - other.v[0],
+                self.v[1] - other.v[1],
+                self.v[2] - other.v[2],
+            ]
+        }
+    }
+}
+
+impl ops::SubAssign<f32> for Vec3 {
+    fn sub_assign(&mut self, other: f32) {
+        *self = Vec3 {
+            v: [
+                self.v[0] - other,
+                self.v[1] - other,
+                self.v[2] - other,
+            ]
+        }
+    }
+}
+
+impl ops::Mul<f32> for Vec3 {
+    type Output = Vec3;
+
+    fn mul(self, other: f32) -> Vec3 {
+        Vec3 {
+            v: [
+                self.v[0] * other,
+                self.v[1] * other,
+                self.v[2] * other,
+            ]
+        }
+    }
+}
+
+impl<'a> ops::Mul<f32> for &'a Vec3 {
+    type Output = Vec3;
+
+    fn mul(self, other: f32) -> Vec3 {
+        Vec3 {
+            v: [
+                self.v[0] * other,
+                self.v[1] * other,
+                self.v[2] * other,
+            ]
+        }
+    }
+}
+
+impl ops::Div<f32> for Vec3 {
+    type Output = Vec3;
+
+    fn div(self, other: f32) -> Vec3 {
+        Vec3 {
+            v: [
+                self.v[0] / other,
+                self.v[1] / other,
+                self.v[2] / other,
+            ]
+        }
+    }
+}
+
+impl<'a> ops::Div<f32> for &'a Vec3 {
+    type Output = Vec3;
+
+    fn div(self, other: f32) -> Vec3 {
+        Vec3 {
+            v: [
+                self.v[0] / other,
+                self.v[1] / other,
+                self.v[2] / other,
+            ]
+        }
+    }
+}
+
+///
+/// Multiply or divide two vectors component by component, as GLSL does, to scale
+/// each axis by its own amount or to tint one colour by another.
+///
+impl<'a> ops::Mul<Vec3> for &'a Vec3 {
+    type Output = Vec3;
+
+    fn mul(self, other: Vec3) -> Self::Output {
+        Vec3 {
+            v: [
+                self.v[0] * other.v[0],
+                self.v[1] * other.v[1],
+                self.v[2] * other.v[2],
+            ]
+        }
+    }
+}
+
+impl ops::Mul<Vec3> for Vec3 {
+    type Output = Vec3;
+
+    fn mul(self, other: Vec3) -> Self::Output {
+        Vec3 {
+            v: [
+                self.v[0] * other.v[0],
+                self.v[1] * other.v[1],
+                self.v[2] * other.v[2],
+            ]
+        }
+    }
+}
+
+impl<'a> ops::Mul<&'a Vec3> for Vec3 {
+    type Output = Vec3;
+
+    fn mul(self, other: &'a Vec3) -> Self::Output {
+        Vec3 {
+            v: [
+                self.v[0] * other.v[0],
+                self.v[1] * other.v[1],
+                self.v[2] * other.v[2],
+            ]
+        }
+    }
+}
+
+impl<'a, 'b> ops::Mul<&'b Vec3> for &'a Vec3 {
+    type Output = Vec3;
+
+    fn mul(self, other: &'b Vec3) -> Self::Output {
+        Vec3 {
+            v: [
+                self.v[0] * other.v[0],
+                self.v[1] * other.v[1],
+                self.v[2] * other.v[2],
+            ]
+        }
+    }
+}
+
+impl<'a> ops::Div<Vec3> for &'a Vec3 {
+    type Output = Vec3;
+
+    fn div(self, other: Vec3) -> Self::Output {
+        Vec3 {
+            v: [
+                self.v[0] / other.v[0],
+                self.v[1] / other.v[1],
+                self.v[2] / other.v[2],
+            ]
+        }
+    }
+}
+
+impl ops::Div<Vec3> for Vec3 {
+    type Output = Vec3;
+
+    fn div(self, other: Vec3) -> Self::Output {
+        Vec3 {
+            v: [
+                self.v[0] / other.v[0],
+                self.v[1] / other.v[1],
+                self.v[2] / other.v[2],
+            ]
+        }
+    }
+}
+
+impl<'a> ops::Div<&'a Vec3> for Vec3 {
+    type Output = Vec3;
+
+    fn div(self, other: &'a Vec3) -> Self::Output {
+        Vec3 {
+            v: [
+                self.v[0] / other.v[0],
+                self.v[1] / other.v[1],
+                self.v[2] / other.v[2],
+            ]
+        }
+    }
+}
+
+impl<'a, 'b> ops::Div<&'b Vec3> for &'a Vec3 {
+    type Output = Vec3;
+
+    fn div(self, other: &'b Vec3) -> Self::Output {
+        Vec3 {
+            v: [
+                self.v[0] / other.v[0],
+                self.v[1] / other.v[1],
+                self.v[2] / other.v[2],
+            ]
+        }
+    }
+}
+
+impl ops::DivAssign<f32> for Vec3 {
+    fn div_assign(&mut self, other: f32) {
+        *self = Vec3 {
+            v: [
+                self.v[0] / other,
+                self.v[1] / other,
+                self.v[2] / other,
+            ]
+        }
+    }
+}
+
+impl<'a> ops::DivAssign<f32> for &'a mut Vec3 {
+    fn div_assign(&mut self, other: f32) {
+        **self = Vec3 {
+            v: [
+                self.v[0] / other,
+                self.v[1] / other,
+                self.v[2] / other,
+            ]
+        }
+    }
+}
+
+impl ops::MulAssign<f32> for Vec3 {
+    fn mul_assign(&mut self, other: f32) {
+        self.v[0] *= other;
+        self.v[1] *= other;
+        self.v[2] *= other;
+    }
+}
+
+impl ops::Add<Vec3> for f32 {
+    type Output = Vec3;
+
+    fn add(self, other: Vec3) -> Self::Output {
+        Vec3 {
+            v: [
+                self + other.v[0],
+                self + other.v[1],
+                self + other.v[2],
+            ]
+        }
+    }
+}
+
+impl ops::Sub<Vec3> for f32 {
+    type Output = Vec3;
+
+    fn sub(self, other: Vec3) -> Self::Output {
+        Vec3 {
+            v: [
+                self - other.v[0],
+                self - other.v[1],
+                self - other.v[2],
+            ]
+        }
+    }
+}
+
+impl ops::Mul<Vec3> for f32 {
+    type Output = Vec3;
+
+    fn mul(self, other: Vec3) -> Self::Output {
+        Vec3 {
+            v: [
+                self * other.v[0],
+                self * other.v[1],
+                self * other.v[2],
+            ]
+        }
+    }
+}
+
+impl ops::Div<Vec3> for f32 {
+    type Output = Vec3;
+
+    fn div(self, other: Vec3) -> Self::Output {
+        Vec3 {
+            v: [
+                self / other.v[0],
+                self / other.v[1],
+                self / other.v[2],
+            ]
+        }
+    }
+}
+
+impl ops::Neg for Vec3 {
+    type Output = Vec3;
+
+    fn neg(self) -> Self::Output {
+        Vec3 {
+            v: [
+                -self.v[0],
+                -self.v[1],
+                -self.v[2],
+            ]
+        }
+    }
+}
+
+impl<'a> ops::Neg for &'a Vec3 {
+    type Output = Vec3;
+
+    fn neg(self) -> Self::Output {
+        Vec3 {
+            v: [
+                -self.v[0],
+                -self.v[1],
+                -self.v[2],
+            ]
+        }
+    }
+}
+
+impl ops::Index<usize> for Vec3 {
+    type Output = f32;
+
+    #[inline]
+    fn index(&self, index: usize) -> &f32 {
+        &self.v[index]
+    }
+}
+
+impl ops::IndexMut<usize> for Vec3 {
+    #[inline]
+    fn index_mut(&mut self, index: usize) -> &mut f32 {
+        &mut self.v[index]
+    }
+}
+
+
+#[derive(Copy, Clone, Debug)]
+pub struct Vec4 {
+    pub v: [f32; 4],
+}
+
+impl Vec4 {
+    pub fn new(x: f32, y: f32, z: f32, w: f32) -> Vec4 {
+        Vec4 { v: [x, y, z, w] }
+    }
+
+    pub fn zero() -> Vec4 {
+        Vec4 { v: [0.0, 0.0, 0.0, 0.0] }
+    }
+
+    ///
+    /// The unit vector along the x-axis.
+    ///
+    pub fn unit_x() -> Vec4 {
+        Vec4::new(1.0, 0.0, 0.0, 0.0)
+    }
+
+    ///
+    /// The unit vector along the y-axis.
+    ///
+    pub fn unit_y() -> Vec4 {
+        Vec4::new(0.0, 1.0, 0.0, 0.0)
+    }
+
+    ///
+    /// The unit vector along the z-axis.
+    ///
+    pub fn unit_z() -> Vec4 {
+        Vec4::new(0.0, 0.0, 1.0, 0.0)
+    }
+
+    ///
+    /// The unit vector along the w-axis.
+    ///
+    pub fn unit_w() -> Vec4 {
+        Vec4::new(0.0, 0.0, 0.0, 1.0)
+    }
+
+    ///
+    /// Compute the norm (length) of a vector.
+    ///
+    pub fn norm(&self) -> f32 {
+        f32::sqrt(self.v[0] * self.v[0] + self.v[1] * self.v[1] + self.v[2] * self.v[2] + self.v[3] * self.v[3])
+    }
+
+    ///
+    /// Compute the squared norm (length) of a vector.
+    ///
+    pub fn norm2(&self) -> f32 {
+        self.v[0] * self.v[0] + self.v[1] * self.v[1] + self.v[2] * self.v[2] + self.v[3] * self.v[3]
+    }
+
+    ///
+    /// Convert an arbitrary vector into a unit vector.
+    ///
+    pub fn normalize(&self) -> Vec4 {
+        let norm_v = self.norm();
+        if norm_v == 0.0 {
+            return Vec4::zero();
+        }
+
+        Vec4::new(self.v[0] / norm_v, self.v[1] / norm_v, self.v[2] / norm_v, self.v[3] / norm_v)
+    }
+
+    ///
+    /// Compute the dot product of two vectors.
+    ///
+    pub fn dot(&self, other: &Vec4) -> f32 {
+        self.v[0] * other.v[0] + self.v[1] * other.v[1] + self.v[2] * other.v[2] + self.v[3] * other.v[3]
+    }
+
+    ///
+    /// Interpolate linearly from this vector to `other`, reaching `other` when
+    /// `t` is one. This is GLSL's `mix`.
+    ///
+    pub fn lerp(&self, other: &Vec4, t: f32) -> Vec4 {
+        self + (other - self) * t
+    }
+
+    ///
+    /// The smaller of each pair of components.
+    ///
+    pub fn min(&self, other: &Vec4) -> Vec4 {
+        Vec4 {
+            v: [
+                f32::min(self.v[0], other.v[0]),
+                f32::min(self.v[1], other.v[1]),
+                f32::min(self.v[2], other.v[2]),
+                f32::min(self.v[3], other.v[3]),
+            ]
+        }
+    }
+
+    ///
+    /// The larger of each pair of components.
+    ///
+    pub fn max(&self, other: &Vec4) -> Vec4 {
+        Vec4 {
+            v: [
+                f32::max(self.v[0], other.v[0]),
+                f32::max(self.v[1], other.v[1]),
+                f32::max(self.v[2], other.v[2]),
+                f32::max(self.v[3], other.v[3]),
+            ]
+        }
+    }
+
+    ///
+    /// Clamp each component between the matching components of `min` and `max`.
+    ///
+    pub fn clamp(&self, min: &Vec4, max: &Vec4) -> Vec4 {
+        self.max(min).min(max)
+    }
+}
+
+#[inline]
+pub fn vec4<T: Into<Vec4>>(v: T) -> Vec4 {
+    v.into()
+}
+
+impl From<(f32, f32, f32, f32)> for Vec4 {
+    #[inline]
+    fn from((x, y, z, w): (f32, f32, f32, f32)) -> Vec4 {
+        Vec4::new(x, y, z, w)
+    }
+}
+
+impl From<(Vec2, f32, f32)> for Vec4 {
+    #[inline]
+    fn from((v, z, w): (Vec2, f32, f32)) -> Vec4 {
+        Vec4::new(v.v[0], v.v[1], z, w)
+    }
+}
+
+impl<'a> From<(&'a Vec2, f32, f32)> for Vec4 {
+    #[inline]
+    fn from((v, z, w): (&'a Vec2, f32, f32)) -> Vec4 {
+        Vec4::new(v.v[0], v.v[1], z, w)
+    }
+}
+
+impl From<(Vec3, f32)> for Vec4 {
+    #[inline]
+    fn from((v, w): (Vec3, f32)) -> Vec4 {
+        Vec4::new(v.v[0], v.v[1], v.v[2], w)
+    }
+}
+
+impl<'a> From<(&'a Vec3, f32)> for Vec4 {
+    #[inline]
+    fn from((v, w): (&'a Vec3, f32)) -> Vec4 {
+        Vec4::new(v.v[0], v.v[1], v.v[2], w)
+    }
+}
+
+impl fmt::Display for Vec4 {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "[{:.2}, {:.2}, {:.2}, {:.2}]", self.v[0], self.v[1], self.v[2], self.v[3])
+    }
+}
+
+impl cmp::PartialEq for Vec4 {
+    fn eq(&self, other: &Vec4) -> bool {
+        (f32::abs(self.v[0] - other.v[0]) < EPSILON) &&
+        (f32::abs(self.v[1] - other.v[1]) < EPSILON) &&
+        (f32::abs(self.v[2] - other.v[2]) < EPSILON) &&
+        (f32::abs(self.v[3] - other.v[3]) < EPSILON)
+    }
+}
+
+impl<'a> ops::Add<Vec4> for &'a Vec4 {
+    type Output = Vec4;
+
+    fn add(self, other: Vec4) -> Self::Output {
+        Vec4 {
+            v: [
+                self.v[0] + other.v[0],
+                self.v[1] + other.v[1],
+                self.v[2] + other.v[2],
+                self.v[3] + other.v[3],
+            ]
+        }
+    }
+}
+
+impl ops::Add<Vec4> for Vec4 {
+    type Output = Vec4;
+
+    fn add(self, other: Vec4) -> Self::Output {
+        Vec4 {
+            v: [
+                self.v[0] + other.v[0],
+                self.v[1] + other.v[1],
+                self.v[2] + other.v[2],
+                self.v[3] + other.v[3],
+            ]
+        }
+    }
+}
+
+impl<'a> ops::Add<&'a Vec4> for Vec4 {
+    type Output = Vec4;
+
+    fn add(self, other: &'a Vec4) -> Self::Output {
+        Vec4 {
+            v: [
+                self.v[0] + other.v[0],
+                self.v[1] + other.v[1],
+                self.v[2] + other.v[2],
+                self.v[3] + other.v[3],
+            ]
+        }
+    }
+}
+
+impl<'a, 'b> ops::Add<&'b Vec4> for &'a Vec4 {
+    type Output = Vec4;
+
+    fn add(self, other: &'b Vec4) -> Self::Output {
+        Vec4 {
+            v: [
+                self.v[0] + other.v[0],
+                self.v[1] + other.v[1],
+                self.v[2] + other.v[2],
+                self.v[3] + other.v[3],
+            ]
+        }
+    }
+}
+
+impl ops::Add<f32> for Vec4 {
+    type Output = Vec4;
+
+    fn add(self, other: f32) -> Self::Output {
+        Vec4 {
+            v: [
+                self.v[0] + other,
+                self.v[1] + other,
+                self.v[2] + other,
+                self.v[3] + other,
+            ]
+        }
+    }
+}
+
+impl<'a> ops::Sub<Vec4> for &'a Vec4 {
+    type Output = Vec4;
+
+    fn sub(self, other: Vec4) -> Self::Output {
+        Vec4 {
+            v: [
+                self.v[0] - other.v[0],
+                self.v[1] - other.v[1],
+                self.v[2] - other.v[2],
+                self.v[3] - other.v[3],
+            ]
+        }
+    }
+}
+
+impl ops::Sub<Vec4> for Vec4 {
+    type Output = Vec4;
+
+    fn sub(self, other: Vec4) -> Self::Output {
+        Vec4 {
+            v: [
+                self.v[0] - other.v[0],
+                self.v[1] - other.v[1],
+                self.v[2] - other.v[2],
+                self.v[3] - other.v[3],
+            ]
+        }
+    }
+}
+
+impl<'a> ops::Sub<&'a Vec4> for Vec4 {
+    type Output = Vec4;
+
+    fn sub(self, other: &'a Vec4) -> Self::Output {
+        Vec4 {
+            v: [
+                self.v[0] - other.v[0],
+                self.v[1] - other.v[1],
+                self.v[2] - other.v[2],
+                self.v[3] - other.v[3],
+            ]
+        }
+    }
+}
+
+impl<'a, 'b> ops::Sub<&'b Vec4> for &'a Vec4 {
+    type Output = Vec4;
+
+    fn sub(self, other: &'b Vec4) -> Self::Output {
+        Vec4 {
+            v: [
+                self.v[0] - other.v[0],
+                self.v[1] - other.v[1],
+                self.v[2] - other.v[2],
+                self.v[3] - other.v[3],
+            ]
+        }
+    }
+}
+
+impl ops::Sub<f32> for Vec4 {
+    type Output = Vec4;
+
+    fn sub(self, other: f32) -> Self::Output {
+        Vec4 {
+            v: [
+                self.v[0] - other,
+                self.v[1] - other,
+                self.v[2] - other,
+                self.v[3] - other,
+            ]
+        }
+    }
+}
+
+impl ops::AddAssign<Vec4> for Vec4 {
+    fn add_assign(&mut self, other: Vec4) {
+        self.v[0] += other.v[0];
+        self.v[1] += other.v[1];
+        self.v[2] += other.v[2];
+        self.v[3] += other.v[3];
+    }
+}
+
+impl<'a> ops::AddAssign<&'a Vec4> for Vec4 {
+    fn add_assign(&mut self, other: &'a Vec4) {
+        self.v[0] += other.v[0];
+        self.v[1] += other.v[1];
+        self.v[2] += other.v[2];
+        self.v[3] += other.v[3];
+    }
+}
+
+impl ops::AddAssign<f32> for Vec4 {
+    fn add_assign(&mut self, other: f32) {
+        self.v[0] += other;
+        self.v[1] += other;
+        self.v[2] += other;
+        self.v[3] += other;
+    }
+}
+
+impl ops::SubAssign<Vec4> for Vec4 {
+    fn sub_assign(&mut self, other: Vec4) {
+        self.v[0] -= other.v[0];
+        self.v[1] -= other.v[1];
+        self.v[2] -= other.v[2];
+        self.v[3] -= other.v[3];
+    }
+}
+
+impl<'a> ops::SubAssign<&'a Vec4> for Vec4 {
+    fn sub_assign(&mut self, other: &'a Vec4) {
+        self.v[0] -= other.v[0];
+        self.v[1] -= other.v[1];
+        self.v[2] -= other.v[2];
+        self.v[3] -= other.v[3];
+    }
+}
+
+impl ops::SubAssign<f32> for Vec4 {
+    fn sub_assign(&mut self, other: f32) {
+        self.v[0] -= other;
+        self.v[1] -= other;
+        self.v[2] -= other;
+        self.v[3] -= other;
+    }
+}
+
+impl ops::Mul<f32> for Vec4 {
+    type Output = Vec4;
+
+    fn mul(self, other: f32) -> Self::Output {
+        Vec4 {
+            v: [
+                self.v[0] * other,
+                self.v[1] * other,
+                self.v[2] * other,
+                self.v[3] * other,
+            ]
+        }
+    }
+}
+
+impl<'a> ops::Mul<f32> for &'a Vec4 {
+    type Output = Vec4;
+
+    fn mul(self, other: f32) -> Self::Output {
+        Vec4 {
+            v: [
+                self.v[0] * other,
+                self.v[1] * other,
+                self.v[2] * other,
+                self.v[3] * other,
+            ]
+        }
+    }
+}
+
+impl ops::Div<f32> for Vec4 {
+    type Output = Vec4;
+
+    fn div(self, other: f32) -> Self::Output {
+        Vec4 {
+            v: [
+                self.v[0] / other,
+                self.v[1] / other,
+                self.v[2] / other,
+                self.v[3] / other,
+            ]
+        }
+    }
+}
+
+impl<'a> ops::Div<f32> for &'a Vec4 {
+    type Output = Vec4;
+
+    fn div(self, other: f32) -> Self::Output {
+        Vec4 {
+            v: [
+                self.v[0] / other,
+                self.v[1] / other,
+                self.v[2] / other,
+                self.v[3] / other,
+            ]
+        }
+    }
+}
+
+///
+/// Multiply or divide two vectors component by component, as GLSL does, to scale
+/// each axis by its own amount or to tint one colour by another.
+///
+impl<'a> ops::Mul<Vec4> for &'a Vec4 {
+    type Output = Vec4;
+
+    fn mul(self, other: Vec4) -> Self::Output {
+        Vec4 {
+            v: [
+                self.v[0] * other.v[0],
+                self.v[1] * other.v[1],
+                self.v[2] * other.v[2],
+                self.v[3] * other.v[3],
+            ]
+        }
+    }
+}
+
+impl ops::Mul<Vec4> for Vec4 {
+    type Output = Vec4;
+
+    fn mul(self, other: Vec4) -> Self::Output {
+        Vec4 {
+            v: [
+                self.v[0] * other.v[0],
+                self.v[1] * other.v[1],
+                self.v[2] * other.v[2],
+                self.v[3] * other.v[3],
+            ]
+        }
+    }
+}
+
+impl<'a> ops::Mul<&'a Vec4> for Vec4 {
+    type Output = Vec4;
+
+    fn mul(self, other: &'a Vec4) -> Self::Output {
+        Vec4 {
+            v: [
+                self.v[0] * other.v[0],
+                self.v[1] * other.v[1],
+                self.v[2] * other.v[2],
+                self.v[3] * other.v[3],
+            ]
+        }
+    }
+}
+
+impl<'a, 'b> ops::Mul<&'b Vec4> for &'a Vec4 {
+    type Output = Vec4;
+
+    fn mul(self, other: &'b Vec4) -> Self::Output {
+        Vec4 {
+            v: [
+                self.v[0] * other.v[0],
+                self.v[1] * other.v[1],
+                self.v[2] * other.v[2],
+                self.v[3] * other.v[3],
+            ]
+        }
+    }
+}
+
+impl<'a> ops::Div<Vec4> for &'a Vec4 {
+    type Output = Vec4;
+
+    fn div(self, other: Vec4) -> Self::Output {
+        Vec4 {
+            v: [
+                self.v[0] / other.v[0],
+                self.v[1] / other.v[1],
+                self.v[2] / other.v[2],
+                self.v[3] / other.v[3],
+            ]
+        }
+    }
+}
+
+impl ops::Div<Vec4> for Vec4 {
+    type Output = Vec4;
+
+    fn div(self, other: Vec4) -> Self::Output {
+        Vec4 {
+            v: [
+                self.v[0] / other.v[0],
+                self.v[1] / other.v[1],
+                self.v[2] / other.v[2],
+                self.v[3] / other.v[3],
+            ]
+        }
+    }
+}
+
+impl<'a> ops::Div<&'a Vec4> for Vec4 {
+    type Output = Vec4;
+
+    fn div(self, other: &'a Vec4) -> Self::Output {
+        Vec4 {
+            v: [
+                self.v[0] / other.v[0],
+                self.v[1] / other.v[1],
+                self.v[2] / other.v[2],
+                self.v[3] / other.v[3],
+            ]
+        }
+    }
+}
+
+impl<'a, 'b> ops::Div<&'b Vec4> for &'a Vec4 {
+    type Output = Vec4;
+
+    fn div(self, other: &'b Vec4) -> Self::Output {
+        Vec4 {
+            v: [
+                self.v[0] / other.v[0],
+                self.v[1] / other.v[1],
+                self.v[2] / other.v[2],
+                self.v[3] / other.v[3],
+            ]
+        }
+    }
+}
+
+impl ops::MulAssign<f32> for Vec4 {
+    fn mul_assign(&mut self, other: f32) {
+        self.v[0] *= other;
+        self.v[1] *= other;
+        self.v[2] *= other;
+        self.v[3] *= other;
+    }
+}
+
+impl ops::DivAssign<f32> for Vec4 {
+    fn div_assign(&mut self, other: f32) {
+        self.v[0] /= other;
+        self.v[1] /= other;
+        self.v[2] /= other;
+        self.v[3] /= other;
+    }
+}
+
+impl ops::Add<Vec4> for f32 {
+    type Output = Vec4;
+
+    fn add(self, other: Vec4) -> Self::Output {
+        Vec4 {
+            v: [
+                self + other.v[0],
+                self + other.v[1],
+                self + other.v[2],
+                self + other.v[3],
+            ]
+        }
+    }
+}
+
+impl ops::Sub<Vec4> for f32 {
+    type Output = Vec4;
+
+    fn sub(self, other: Vec4) -> Self::Output {
+        Vec4 {
+            v: [
+                self - other.v[0],
+                self - other.v[1],
+                self - other.v[2],
+                self - other.v[3],
+            ]
+        }
+    }
+}
+
+impl ops::Mul<Vec4> for f32 {
+    type Output = Vec4;
+
+    fn mul(self, other: Vec4) -> Self::Output {
+        Vec4 {
+            v: [
+                self * other.v[0],
+                self * other.v[1],
+                self * other.v[2],
+                self * other.v[3],
+            ]
+        }
+    }
+}
+
+impl ops::Div<Vec4> for f32 {
+    type Output = Vec4;
+
+    fn div(self, other: Vec4) -> Self::Output {
+        Vec4 {
+            v: [
+                self / other.v[0],
+                self / other.v[1],
+                self / other.v[2],
+                self / other.v[3],
+            ]
+        }
+    }
+}
+
+impl ops::Neg for Vec4 {
+    type Output = Vec4;
+
+    fn neg(self) -> Self::Output {
+        Vec4 {
+            v: [
+                -self.v[0],
+                -self.v[1],
+                -self.v[2],
+                -self.v[3],
+            ]
+        }
+    }
+}
+
+impl<'a> ops::Neg for &'a Vec4 {
+    type Output = Vec4;
+
+    fn neg(self) -> Self::Output {
+        Vec4 {
+            v: [
+                -self.v[0],
+                -self.v[1],
+                -self.v[2],
+                -self.v[3],
+            ]
+        }
+    }
+}
+
+impl ops::Index<usize> for Vec4 {
+    type Output = f32;
+
+    #[inline]
+    fn index(&self, index: usize) -> &f32 {
+        &self.v[index]
+    }
+}
+
+impl ops::IndexMut<usize> for Vec4 {
+    #[inline]
+    fn index_mut(&mut self, index: usize) -> &mut f32 {
+        &mut self.v[index]
+    }
+}
+
+///
+/// The `Mat3` type represents 3x3 matrices in column-major order.
+///
+#[derive(Copy, Clone, Debug)]
+pub struct Mat3 {
+    m: [f32; 9],
+}
+
+impl Mat3 {
+    pub fn new(
+        m11: f32, m12: f32, m13: f32, 
+        m21: f32, m22: f32, m23: f32, 
+        m31: f32, m32: f32, m33: f32) -> Mat3 {
+
+        Mat3 {
+            m: [
+                m11, m12, m13, // Column 1
+                m21, m22, m23, // Column 2
+                m31, m32, m33  // Column 3
+            ]
+        }
+    }
+
+    pub fn zero() -> Mat3 {
+        Mat3::new(0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0)
+    }
+
+    pub fn identity() -> Mat3 {
+        Mat3::new(1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0)
+    }
+
+    pub fn as_ptr(&self) -> *const f32 {
+        self.m.as_ptr()
+    }
+
+    pub fn as_mut_ptr(&mut self) -> *mut f32 {
+        self.m.as_mut_ptr()
+    }
+}
+
+impl fmt::Display for Mat3 {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, 
+            "\n[{:.2}][{:.2}][{:.2}]\n[{:.2}][{:.2}][{:.2}]\n[{:.2}][{:.2}][{:.2}]", 
+            self.m[0], self.m[3], self.m[6],
+            self.m[1], self.m[4], self.m[7],
+            self.m[2], self.m[5], self.m[8],
+        )
+    }
+}
+
+#[inline]
+fn mat3(m11: f32, m12: f32, m13: f32, 
+        m21: f32, m22: f32, m23: f32, 
+        m31: f32, m32: f32, m33: f32) -> Mat3 {
+
+    Mat3::new(m11, m12, m13, m21, m22, m23, m31, m32, m33)
+}
+
+impl convert::AsRef<[f32; 9]> for Mat3 {
+    fn as_ref(&self) -> &[f32; 9] {
+        &self.m
+    }
+}
+
+impl convert::AsMut<[f32; 9]> for Mat3 {
+    fn as_mut(&mut self) -> &mut [f32; 9] {
+        &mut self.m
+    }
+}
+
+///
+/// The `Mat4` type represents 4x4 matrices in column-major order.
+///
+#[derive(Copy, Clone, Debug)]
+pub struct Mat4 {
+    pub m: [f32; 16],
+}
+
+impl Mat4 {
+    pub fn new(
+        m11: f32, m12: f32, m13: f32, m14: f32,
+        m21: f32, m22: f32, m23: f32, m24: f32,
+        m31: f32, m32: f32, m33: f32, m34: f32,
+        m41: f32, m42: f32, m43: f32, m44: f32) -> Mat4 {
+
+        Mat4 {
+            m: [
+                m11, m12, m13, m14, // Column 1
+                m21, m22, m23, m24, // Column 2
+                m31, m32, m33, m34, // Column 3
+                m41, m42, m43, m44  // Column 4
+            ]
+        }
+    }
+
+    pub fn zero() -> Mat4 {
+        Mat4::new(
+            0.0, 0.0, 0.0, 0.0, 
+            0.0, 0.0, 0.0, 0.0, 
+            0.0, 0.0, 0.0, 0.0, 
+            0.0, 0.0, 0.0, 0.0
+        )
+    }
+
+    pub fn identity() -> Mat4 {
+        Mat4::new(
+            1.0, 0.0, 0.0, 0.0, 
+            0.0, 1.0, 0.0, 0.0, 
+            0.0, 0.0, 1.0, 0.0, 
+            0.0, 0.0, 0.0, 1.0
+        )
+    }
+
+    pub fn transpose(&self) -> Mat4 {
+        Mat4::new(
+            self.m[0], self.m[4], self.m[8],  self.m[12],
+            self.m[1], self.m[5], self.m[9],  self.m[13], 
+            self.m[2], self.m[6], self.m[10], self.m[14], 
+            self.m[3], self.m[7], self.m[11], self.m[15]
+        )
+    }
+
+    ///
+    /// The matrix that translates by `v`.
+    ///
+    pub fn from_translation(v: &Vec3) -> Mat4 {
+        let mut m_t = Mat4::identity();
+        m_t.m[12] = v.v[0];
+        m_t.m[13] = v.v[1];
+        m_t.m[14] = v.v[2];
+
+        m_t
+    }
+
+    // The matrix that rotates around the x axis by an angle in degrees.
+    pub fn from_rotation_x_deg(deg: f32) -> Mat4 {
+        // Convert to radians.
+        let rad = deg * ONE_DEG_IN_RAD;
+        let mut m_r = Mat4::identity();
+        m_r.m[5]  =  f32::cos(rad);
+        m_r.m[9]  = -f32::sin(rad);
+        m_r.m[6]  =  f32::sin(rad);
+        m_r.m[10] =  f32::cos(rad);
+
+        m_r
+    }
+
+    // The matrix that rotates around the y axis by an angle in degrees.
+    pub fn from_rotation_y_deg(deg: f32) -> Mat4 {
+        // Convert to radians.
+        let rad = deg * ONE_DEG_IN_RAD;
+        let mut m_r = Mat4::identity();
+        m_r.m[0]  =  f32::cos(rad);
+        m_r.m[8]  =  f32::sin(rad);
+        m_r.m[2]  = -f32::sin(rad);
+        m_r.m[10] =  f32::cos(rad);
+
+        m_r
+    }
+
+    // The matrix that rotates around the z axis by an angle in degrees.
+    pub fn from_rotation_z_deg(deg: f32) -> Mat4 {
+        // Convert to radians.
+        let rad = deg * ONE_DEG_IN_RAD;
+        let mut m_r = Mat4::identity();
+        m_r.m[0] =  f32::cos(rad);
+        m_r.m[4] = -f32::sin(rad);
+        m_r.m[1] =  f32::sin(rad);
+        m_r.m[5] =  f32::cos(rad);
+
+        m_r
+    }
+
+    // The matrix that scales by [x, y, z].
+    pub fn from_scale(v: &Vec3) -> Mat4 {
+        let mut m_s = Mat4::identity();
+        m_s.m[0]  = v.v[0];
+        m_s.m[5]  = v.v[1];
+        m_s.m[10] = v.v[2];
+
+        m_s
+    }
+
+    ///
+    /// Translate by `v` after the transform `self` makes, the product `T * self`.
+    /// A model matrix scaled and rotated into shape is put in place this way.
+    ///
+    pub fn then_translate(&self, v: &Vec3) -> Mat4 {
+        Mat4::from_translation(v) * self
+    }
+
+    ///
+    /// Translate by `v` before the transform `self` makes, the product `self * T`.
+    /// The translation is along the axes `self` maps to, so `self` scales and
+    /// rotates it along with everything else.
+    ///
+    pub fn pre_translate(&self, v: &Vec3) -> Mat4 {
+        *self * &Mat4::from_translation(v)
+    }
+
+    ///
+    /// Translate by `v` after the transform `self` makes. This is `then_translate`
+    /// under its older name. `Mat4::identity().translate(v)` is `from_translation(v)`.
+    ///
+    pub fn translate(&self, v: &Vec3) -> Mat4 {
+        self.then_translate(v)
+    }
+
+    // Rotate around x axis by an angle in degrees, after the transform `self` makes.
+    pub fn rotate_x_deg(&self, deg: f32) -> Mat4 {
+        Mat4::from_rotation_x_deg(deg) * self
+    }
+
+    // Rotate around y axis by an angle in degrees, after the transform `self` makes.
+    pub fn rotate_y_deg(&self, deg: f32) -> Mat4 {
+        Mat4::from_rotation_y_deg(deg) * self
+    }
+
+    // Rotate around z axis by an angle in degrees, after the transform `self` makes.
+    pub fn rotate_z_deg(&self, deg: f32) -> Mat4 {
+        Mat4::from_rotation_z_deg(deg) * self
+    }
+
+    // scale a matrix by [x, y, z], after the transform `self` makes.
+    pub fn scale(&self, v: &Vec3) -> Mat4 {
+        Mat4::from_scale(v) * self
+    }
+
+    ///
+    /// Build the transform that scales by `scale`, then rotates by `rotation`,
+    /// then translates by `translation`, the order scene-graph nodes store their
+    /// transforms in. This gives the same matrix as chaining `scale`, the rotation
+    /// matrix, and `translate`, without the two matrix products.
+    ///
+    pub fn from_trs(translation: Vec3, rotation: Versor, scale: Vec3) -> Mat4 {
+        let mut m = rotation.to_mat4();
+        for i in 0..3 {
+            m.m[4 * i] *= scale.v[i];
+            m.m[4 * i + 1] *= scale.v[i];
+            m.m[4 * i + 2] *= scale.v[i];
+        }
+        m.m[12] = translation.v[0];
+        m.m[13] = translation.v[1];
+        m.m[14] = translation.v[2];
+
+        m
+    }
+
+    ///
+    /// Split a transform into the translation, rotation, and scale `from_trs`
+    /// would build it from. The matrix should be made of those three alone: any
+    /// shear or projection in it is lost. A transform that mirrors comes back with
+    /// a negative x scale, and an axis scaled to zero comes back unrotated.
+    ///
+    pub fn decompose(&self) -> (Vec3, Versor, Vec3) {
+        let translation = vec3((self.m[12], self.m[13], self.m[14]));
+        let axes = [
+            vec3((self.m[0], self.m[1], self.m[2])),
+            vec3((self.m[4], self.m[5], self.m[6])),
+            vec3((self.m[8], self.m[9], self.m[10])),
+        ];
+        let mut scale = vec3((axes[0].norm(), axes[1].norm(), axes[2].norm()));
+        if axes[0].dot(&axes[1].cross(&axes[2])) < 0.0 {
+            scale.v[0] = -scale.v[0];
+        }
+
+        let mut rotation = Mat4::identity();
+        for i in 0..3 {
+            if f32::abs(scale.v[i]) > EPSILON {
+                rotation.m[4 * i] = axes[i].v[0] / scale.v[i];
+                rotation.m[4 * i + 1] = axes[i].v[1] / scale.v[i];
+                rotation.m[4 * i + 2] = axes[i].v[2] / scale.v[i];
+            }
+        }
+
+        (translation, Versor::from_mat4(&rotation), scale)
+    }
+
+    /// returns a scalar value with the determinant for a 4x4 matrix
+    /// see
+    /// http://www.euclideanspace.com/maths/algebra/matrix/functions/determinant/fourD/index.htm
+    pub fn determinant(&self) -> f32 {
+        self.m[12] * self.m[9]  * self.m[6]  * self.m[3]  -
+        self.m[8]  * self.m[13] * self.m[6]  * self.m[3]  -
+        self.m[12] * self.m[5]  * self.m[10] * self.m[3]  +
+        self.m[4]  * self.m[13] * self.m[10] * self.m[3]  +
+        self.m[8]  * self.m[5]  * self.m[14] * self.m[3]  -
+        self.m[4]  * self.m[9]  * self.m[14] * self.m[3]  -
+        self.m[12] * self.m[9]  * self.m[2]  * self.m[7]  +
+        self.m[8]  * self.m[13] * self.m[2]  * self.m[7]  +
+        self.m[12] * self.m[1]  * self.m[10] * self.m[7]  -
+        self.m[0]  * self.m[13] * self.m[10] * self.m[7]  -
+        self.m[8]  * self.m[1]  * self.m[14] * self.m[7]  +
+        self.m[0]  * self.m[9]  * self.m[14] * self.m[7]  +
+        self.m[12] * self.m[5]  * self.m[2]  * self.m[11] -
+        self.m[4]  * self.m[13] * self.m[2]  * self.m[11] -
+        self.m[12] * self.m[1]  * self.m[6]  * self.m[11] +
+        self.m[0]  * self.m[13] * self.m[6]  * self.m[11] +
+        self.m[4]  * self.m[1]  * self.m[14] * self.m[11] -
+        self.m[0]  * self.m[5]  * self.m[14] * self.m[11] -
+        self.m[8]  * self.m[5]  * self.m[2]  * self.m[15] +
+        self.m[4]  * self.m[9]  * self.m[2]  * self.m[15] +
+        self.m[8]  * self.m[1]  * self.m[6]  * self.m[15] -
+        self.m[0]  * self.m[9]  * self.m[6]  * self.m[15] -
+        self.m[4]  * self.m[1]  * self.m[10] * self.m[15] +
+        self.m[0]  * self.m[5]  * self.m[10] * self.m[15]
+    }
+
+    pub fn is_invertible(&self) -> bool {
+        self.determinant() != 0.0
+    }
+
+    ///
+    /// Compute the inverse of a matrix. A matrix with a zero determinant has no
+    /// inverse, and comes back unchanged. With the `simd` feature the inverse is
+    /// computed with SSE or NEON, and the scalar code below only reports matrices
+    /// that cannot be inverted.
+    ///
+    pub fn inverse(&self) -> Mat4 {
+        #[cfg(all(feature = "simd", any(target_arch = "x86_64", target_arch = "aarch64")))]
+        {
+            if let Some(m) = simd::mat4_inverse(&self.m) {
+                return Mat4 { m: m };
+            }
+        }
+
+        self.inverse_scalar()
+    }
+
+    /* returns a 16-element array that is the inverse of a 16-element array (4x4
+    matrix). see
+    http://www.euclideanspace.com/maths/algebra/matrix/functions/inverse/fourD/index.htm
+    */
+    fn inverse_scalar(&self) -> Mat4 {
+        let det = self.determinant();
+        /* there is no inverse if determinant is zero (not likely unless scale is
+        broken) */
+        if det == 0.0 {
+            eprintln!("WARNING. Matrix has zero determinant. It cannot be inverted.");
+            
+            return *self;
+        }
+
+        let inv_det = 1.0 / det;
+
+        return mat4(
+            inv_det * ( self.m[9] * self.m[14] * self.m[7] - self.m[13] * self.m[10] * self.m[7] +
+                                    self.m[13] * self.m[6] * self.m[11] - self.m[5] * self.m[14] * self.m[11] -
+                                    self.m[9] * self.m[6] * self.m[15] + self.m[5] * self.m[10] * self.m[15] ),
+            inv_det * ( self.m[13] * self.m[10] * self.m[3] - self.m[9] * self.m[14] * self.m[3] -
+                                    self.m[13] * self.m[2] * self.m[11] + self.m[1] * self.m[14] * self.m[11] +
+                                    self.m[9] * self.m[2] * self.m[15] - self.m[1] * self.m[10] * self.m[15] ),
+            inv_det * ( self.m[5] * self.m[14] * self.m[3] - self.m[13] * self.m[6] * self.m[3] +
+                                    self.m[13] * self.m[2] * self.m[7] - self.m[1] * self.m[14] * self.m[7] -
+                                    self.m[5] * self.m[2] * self.m[15] + self.m[1] * self.m[6] * self.m[15] ),
+            inv_det * ( self.m[9] * self.m[6] * self.m[3] - self.m[5] * self.m[10] * self.m[3] -
+                                    self.m[9] * self.m[2] * self.m[7] + self.m[1] * self.m[10] * self.m[7] +
+                                    self.m[5] * self.m[2] * self.m[11] - self.m[1] * self.m[6] * self.m[11] ),
+            inv_det * ( self.m[12] * self.m[10] * self.m[7] - self.m[8] * self.m[14] * self.m[7] -
+                                    self.m[12] * self.m[6] * self.m[11] + self.m[4] * self.m[14] * self.m[11] +
+                                    self.m[8] * self.m[6] * self.m[15] - self.m[4] * self.m[10] * self.m[15] ),
+            inv_det * ( self.m[8] * self.m[14] * self.m[3] - self.m[12] * self.m[10] * self.m[3] +
+                                    self.m[12] * self.m[2] * self.m[11] - self.m[0] * self.m[14] * self.m[11] -
+                                    self.m[8] * self.m[2] * self.m[15] + self.m[0] * self.m[10] * self.m[15] ),
+            inv_det * ( self.m[12] * self.m[6] * self.m[3] - self.m[4] * self.m[14] * self.m[3] -
+                                    self.m[12] * self.m[2] * self.m[7] + self.m[0] * self.m[14] * self.m[7] +
+                                    self.m[4] * self.m[2] * self.m[15] - self.m[0] * self.m[6] * self.m[15] ),
+            inv_det * ( self.m[4] * self.m[10] * self.m[3] - self.m[8] * self.m[6] * self.m[3] +
+                                    self.m[8] * self.m[2] * self.m[7] - self.m[0] * self.m[10] * self.m[7] -
+                                    self.m[4] * self.m[2] * self.m[11] + self.m[0] * self.m[6] * self.m[11] ),
+            inv_det * ( self.m[8] * self.m[13] * self.m[7] - self.m[12] * self.m[9] * self.m[7] +
+                                    self.m[12] * self.m[5] * self.m[11] - self.m[4] * self.m[13] * self.m[11] -
+                                    self.m[8] * self.m[5] * self.m[15] + self.m[4] * self.m[9] * self.m[15] ),
+            inv_det * ( self.m[12] * self.m[9] * self.m[3] - self.m[8] * self.m[13] * self.m[3] -
+                                    self.m[12] * self.m[1] * self.m[11] + self.m[0] * self.m[13] * self.m[11] +
+                                    self.m[8] * self.m[1] * self.m[15] - self.m[0] * self.m[9] * self.m[15] ),
+            inv_det * ( self.m[4] * self.m[13] * self.m[3] - self.m[12] * self.m[5] * self.m[3] +
+                                    self.m[12] * self.m[1] * self.m[7] - self.m[0] * self.m[13] * self.m[7] -
+                                    self.m[4] * self.m[1] * self.m[15] + self.m[0] * self.m[5] * self.m[15] ),
+            inv_det * ( self.m[8] * self.m[5] * self.m[3] - self.m[4] * self.m[9] * self.m[3] -
+                                    self.m[8] * self.m[1] * self.m[7] + self.m[0] * self.m[9] * self.m[7] +
+                                    self.m[4] * self.m[1] * self.m[11] - self.m[0] * self.m[5] * self.m[11] ),
+            inv_det * ( self.m[12] * self.m[9] * self.m[6] - self.m[8] * self.m[13] * self.m[6] -
+                                    self.m[12] * self.m[5] * self.m[10] + self.m[4] * self.m[13] * self.m[10] +
+                                    self.m[8] * self.m[5] * self.m[14] - self.m[4] * self.m[9] * self.m[14] ),
+            inv_det * ( self.m[8] * self.m[13] * self.m[2] - self.m[12] * self.m[9] * self.m[2] +
+                                    self.m[12] * self.m[1] * self.m[10] - self.m[0] * self.m[13] * self.m[10] -
+                                    self.m[8] * self.m[1] * self.m[14] + self.m[0] * self.m[9] * self.m[14] ),
+            inv_det * ( self.m[12] * self.m[5] * self.m[2] - self.m[4] * self.m[13] * self.m[2] -
+                                    self.m[12] * self.m[1] * self.m[6] + self.m[0] * self.m[13] * self.m[6] +
+                                    self.m[4] * self.m[1] * self.m[14] - self.m[0] * self.m[5] * self.m[14] ),
+            inv_det * ( self.m[4] * self.m[9] * self.m[2] - self.m[8] * self.m[5] * self.m[2] +
+                                    self.m[8] * self.m[1] * self.m[6] - self.m[0] * self.m[9] * self.m[6] -
+                                    self.m[4] * self.m[1] * self.m[10] + self.m[0] * self.m[5] * self.m[10] ) );
+    }
+
+    ///
+    /// Compute the perspective matrix for converting from camera space to 
+    /// normalized device coordinates.
+    ///
+    pub fn perspective(fovy: f32, aspect: f32, near: f32, far: f32) -> Mat4 {
+        let fov_rad = fovy * ONE_DEG_IN_RAD;
+        let range = f32::tan(fov_rad * 0.5) * near;
+        let sx = (2.0 * near) / (range * aspect + range * aspect);
+        let sy = near / range;
+        let sz = -(far + near) / (far - near);
+        let pz = -(2.0 * far * near) / (far - near);
+        let mut m = Mat4::zero(); // make sure bottom-right corner is zero
+        m.m[0] = sx;
+        m.m[5] = sy;
+        m.m[10] = sz;
+        m.m[14] = pz;
+        m.m[11] = -1.0;
+        
+        m
+    }
+
+    ///
+    /// A perspective projection with the far clipping plane at infinity, the limit
+    /// of `perspective` as `far` grows without bound. Nothing is ever clipped for
+    /// being too far away, however large the scene.
+    ///
+    pub fn perspective_infinite(fovy: f32, aspect: f32, near: f32) -> Mat4 {
+        let fov_rad = fovy * ONE_DEG_IN_RAD;
+        let range = f32::tan(fov_rad * 0.5) * near;
+        let mut m = Mat4::zero();
+        m.m[0] = near / (range * aspect);
+        m.m[5] = near / range;
+        m.m[10] = -1.0;
+        m.m[14] = -2.0 * near;
+        m.m[11] = -1.0;
+
+        m
+    }
+
+    ///
+    /// An infinite perspective projection with reversed-Z: depth is 1 at the near
+    /// plane and falls towards 0 far away. It needs `glClipControl` to set a depth
+    /// range of 0 to 1, a depth buffer cleared to 0, and `GL_GREATER` depth tests.
+    /// Floating point depth is most precise close to 0, and this spends that
+    /// precision on distant geometry, where depth values crowd together.
+    ///
+    pub fn perspective_reversed_z(fovy: f32, aspect: f32, near: f32) -> Mat4 {
+        let fov_rad = fovy * ONE_DEG_IN_RAD;
+        let range = f32::tan(fov_rad * 0.5) * near;
+        let mut m = Mat4::zero();
+        m.m[0] = near / (range * aspect);
+        m.m[5] = near / range;
+        m.m[14] = near;
+        m.m[11] = -1.0;
+
+        m
+    }
+
+    ///
+    /// Map a point in window coordinates back into world space, as `gluUnProject`
+    /// does, where this matrix is the projection times the view. `window_coords`
+    /// holds x and y in pixels up from the bottom left corner of the window, and
+    /// the depth from 0 at the near plane to 1 at the far plane. `viewport` holds
+    /// the x, y, width, and height passed to `glViewport`.
+    ///
+    /// This assumes OpenGL's default clip space depth range of -1 to 1, so that a
+    /// depth of `d` came from a clip space depth of `2d - 1`. Once `glClipControl`
+    /// has set `GL_ZERO_TO_ONE`, as reversed-Z does, use `unproject_with_clip_near`
+    /// with a `clip_near` of 0 instead.
+    ///
+    pub fn unproject(&self, window_coords: &Vec3, viewport: &[i32; 4]) -> Vec3 {
+        self.unproject_with_clip_near(window_coords, viewport, -1.0)
+    }
+
+    ///
+    /// Map a point in window coordinates back into world space, as `unproject`
+    /// does, for a clip space depth range from `clip_near` to 1: -1 for OpenGL's
+    /// default, or 0 for `GL_ZERO_TO_ONE`.
+    ///
+    pub fn unproject_with_clip_near(&self, window_coords: &Vec3, viewport: &[i32; 4], clip_near: f32) -> Vec3 {
+        let x = 2.0 * (window_coords.v[0] - viewport[0] as f32) / viewport[2] as f32 - 1.0;
+        let y = 2.0 * (window_coords.v[1] - viewport[1] as f32) / viewport[3] as f32 - 1.0;
+        let z = clip_near + (1.0 - clip_near) * window_coords.v[2];
+
+        perspective_divide(&(self.inverse() * vec4((x, y, z, 1.0))))
+    }
+
+    ///
+    /// Compute the model matrix of a spherical billboard: a quad in the xy-plane
+    /// that always faces the camera head on. Its rotation is the inverse of the
+    /// rotation in `view`, so the two cancel out and the quad lands flat on the
+    /// screen at `position`, scaled by `size`. This expects a view matrix with no
+    /// scaling in it.
+    ///
+    pub fn billboard_spherical(view: &Mat4, position: &Vec3, size: &Vec3) -> Mat4 {
+        // The rows of the view rotation are the camera axes in world space.
+        let mut m = Mat4::identity();
+        for i in 0..3 {
+            m.m[4 * i] = view.m[i] * size.v[i];
+            m.m[4 * i + 1] = view.m[i + 4] * size.v[i];
+            m.m[4 * i + 2] = view.m[i + 8] * size.v[i];
+        }
+        m.m[12] = position.v[0];
+        m.m[13] = position.v[1];
+        m.m[14] = position.v[2];
+
+        m
+    }
+
+    ///
+    /// Compute the model matrix of a cylindrical billboard: a quad in the xy-plane
+    /// that turns about the world y-axis to face the camera, but stays upright
+    /// however far the camera looks up or down. This suits things that stand on
+    /// the ground, like trees.
+    ///
+    pub fn billboard_cylindrical(view: &Mat4, position: &Vec3, size: &Vec3) -> Mat4 {
+        // Flatten the camera right axis onto the ground to get the quad's x-axis.
+        let right = vec3((view.m[0], 0.0, view.m[8]));
+        let right = if right.norm() > EPSILON { right.normalize() } else { vec3((1.0, 0.0, 0.0)) };
+        let axes = [right, vec3((0.0, 1.0, 0.0)), vec3((-right.v[2], 0.0, right.v[0]))];
+
+        let mut m = Mat4::identity();
+        for i in 0..3 {
+            m.m[4 * i] = axes[i].v[0] * size.v[i];
+            m.m[4 * i + 1] = axes[i].v[1] * size.v[i];
+            m.m[4 * i + 2] = axes[i].v[2] * size.v[i];
+        }
+        m.m[12] = position.v[0];
+        m.m[13] = position.v[1];
+        m.m[14] = position.v[2];
+
+        m
+    }
+
+    /// 
+    /// Generate a pointer to the underlying array for passing a
+    /// matrix to the graphics hardware.
+    ///
+    pub fn as_ptr(&self) -> *const f32 {
+        self.m.as_ptr()
+    }
+
+    pub fn as_mut_ptr(&mut self) -> *mut f32 {
+        self.m.as_mut_ptr()
+    }
+}
+
+impl fmt::Display for Mat4 {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, 
+            "\n[{:.2}][{:.2}][{:.2}][{:.2}]\n[{:.2}][{:.2}][{:.2}][{:.2}]\n[{:.2}][{:.2}][{:.2}][{:.2}]\n[{:.2}][{:.2}][{:.2}][{:.2}]", 
+            self.m[0], self.m[4], self.m[8],  self.m[12],
+            self.m[1], self.m[5], self.m[9],  self.m[13],
+            self.m[2], self.m[6], self.m[10], self.m[14],
+            self.m[3], self.m[7], self.m[11], self.m[15]
+        )
+    }
+}
+
+pub fn mat4(
+        m11: f32, m12: f32, m13: f32, m14: f32, 
+        m21: f32, m22: f32, m23: f32, m24: f32,
+        m31: f32, m32: f32, m33: f32, m34: f32,
+        m41: f32, m42: f32, m43: f32, m44: f32) -> Mat4 {
+
+    Mat4::new(
+        m11, m12, m13, m14, 
+        m21, m22, m23, m24, 
+        m31, m32, m33, m34, 
+        m41, m42, m43, m44
+    )
+}
+
+///
+/// Turn a point in homogeneous coordinates back into an ordinary one.
+///
+fn perspective_divide(v: &Vec4) -> Vec3 {
+    vec3((v.v[0] / v.v[3], v.v[1] / v.v[3], v.v[2] / v.v[3]))
+}
+
+///
+/// The eight corners in world space of the view frustum of a camera with the view
+/// matrix `view` and the projection `proj`, which needs a far plane. Corner `i` is
+/// on the right if bit 0 of `i` is set, at the top if bit 1 is, and on the far
+/// plane if bit 2 is, so the near plane's corners come first. The projection and
+/// view are inverted apart instead of together, which loses less precision on the
+/// far corners of a deep frustum.
+///
+pub fn frustum_corners_world(view: &Mat4, proj: &Mat4) -> [Vec3; 8] {
+    let proj_inverse = proj.inverse();
+    let view_inverse = view.inverse();
+    let mut corners = [Vec3::zero(); 8];
+    for i in 0..8 {
+        let x = if i & 1 == 0 { -1.0 } else { 1.0 };
+        let y = if i & 2 == 0 { -1.0 } else { 1.0 };
+        let z = if i & 4 == 0 { -1.0 } else { 1.0 };
+        let corner_eye = perspective_divide(&(proj_inverse * vec4((x, y, z, 1.0))));
+        corners[i] = vec3(view_inverse * vec4((corner_eye, 1.0)));
+    }
+
+    corners
+}
+
+impl convert::AsRef<[f32; 16]> for Mat4 {
+    fn as_ref(&self) -> &[f32; 16] {
+        &self.m
+    }
+}
+
+impl convert::AsMut<[f32; 16]> for Mat4 {
+    fn as_mut(&mut self) -> &mut [f32; 16] {
+        &mut self.m
+    }
+}
+
+///
+/// Multiply two matrices one component at a time.
+///
+#[cfg_attr(all(feature = "simd", any(target_arch = "x86_64", target_arch = "aarch64")), allow(dead_code))]
+fn mat4_mul_scalar(a: &Mat4, b: &Mat4) -> Mat4 {
+    let mut mm = Mat4::zero();
+
+    mm.m[0]  = a.m[0]*b.m[0]  + a.m[4]*b.m[1]  + a.m[8]*b.m[2]   + a.m[12]*b.m[3];
+    mm.m[1]  = a.m[1]*b.m[0]  + a.m[5]*b.m[1]  + a.m[9]*b.m[2]   + a.m[13]*b.m[3];
+    mm.m[2]  = a.m[2]*b.m[0]  + a.m[6]*b.m[1]  + a.m[10]*b.m[2]  + a.m[14]*b.m[3];
+    mm.m[3]  = a.m[3]*b.m[0]  + a.m[7]*b.m[1]  + a.m[11]*b.m[2]  + a.m[15]*b.m[3];
+    mm.m[4]  = a.m[0]*b.m[4]  + a.m[4]*b.m[5]  + a.m[8]*b.m[6]   + a.m[12]*b.m[7];
+    mm.m[5]  = a.m[1]*b.m[4]  + a.m[5]*b.m[5]  + a.m[9]*b.m[6]   + a.m[13]*b.m[7];
+    mm.m[6]  = a.m[2]*b.m[4]  + a.m[6]*b.m[5]  + a.m[10]*b.m[6]  + a.m[14]*b.m[7];
+    mm.m[7]  = a.m[3]*b.m[4]  + a.m[7]*b.m[5]  + a.m[11]*b.m[6]  + a.m[15]*b.m[7];
+    mm.m[8]  = a.m[0]*b.m[8]  + a.m[4]*b.m[9]  + a.m[8]*b.m[10]  + a.m[12]*b.m[11];
+    mm.m[9]  = a.m[1]*b.m[8]  + a.m[5]*b.m[9]  + a.m[9]*b.m[10]  + a.m[13]*b.m[11];
+    mm.m[10] = a.m[2]*b.m[8]  + a.m[6]*b.m[9]  + a.m[10]*b.m[10] + a.m[14]*b.m[11];
+    mm.m[11] = a.m[3]*b.m[8]  + a.m[7]*b.m[9]  + a.m[11]*b.m[10] + a.m[15]*b.m[11];
+    mm.m[12] = a.m[0]*b.m[12] + a.m[4]*b.m[13] + a.m[8]*b.m[14]  + a.m[12]*b.m[15];
+    mm.m[13] = a.m[1]*b.m[12] + a.m[5]*b.m[13] + a.m[9]*b.m[14]  + a.m[13]*b.m[15];
+    mm.m[14] = a.m[2]*b.m[12] + a.m[6]*b.m[13] + a.m[10]*b.m[14] + a.m[14]*b.m[15];
+    mm.m[15] = a.m[3]*b.m[12] + a.m[7]*b.m[13] + a.m[11]*b.m[14] + a.m[15]*b.m[15];
+
+    mm
+}
+
+///
+/// Multiply a vector by a matrix one component at a time.
+///
+#[cfg_attr(all(feature = "simd", any(target_arch = "x86_64", target_arch = "aarch64")), allow(dead_code))]
+fn mat4_mul_vec4_scalar(m: &Mat4, v: &Vec4) -> Vec4 {
+    let x = m.m[0] * v.v[0] + m.m[4] * v.v[1] + m.m[8]  * v.v[2] + m.m[12] * v.v[3];
+    let y = m.m[1] * v.v[0] + m.m[5] * v.v[1] + m.m[9]  * v.v[2] + m.m[13] * v.v[3];
+    let z = m.m[2] * v.v[0] + m.m[6] * v.v[1] + m.m[10] * v.v[2] + m.m[14] * v.v[3];
+    let w = m.m[3] * v.v[0] + m.m[7] * v.v[1] + m.m[11] * v.v[2] + m.m[15] * v.v[3];
+
+    Vec4::new(x, y, z, w)
+}
+
+#[cfg(not(all(feature = "simd", any(target_arch = "x86_64", target_arch = "aarch64"))))]
+#[inline]
+fn mat4_mul(a: &Mat4, b: &Mat4) -> Mat4 {
+    mat4_mul_scalar(a, b)
+}
+
+#[cfg(all(feature = "simd", any(target_arch = "x86_64", target_arch = "aarch64")))]
+#[inline]
+fn mat4_mul(a: &Mat4, b: &Mat4) -> Mat4 {
+    Mat4 { m: simd::mat4_mul(&a.m, &b.m) }
+}
+
+#[cfg(not(all(feature = "simd", any(target_arch = "x86_64", target_arch = "aarch64"))))]
+#[inline]
+fn mat4_mul_vec4(m: &Mat4, v: &Vec4) -> Vec4 {
+    mat4_mul_vec4_scalar(m, v)
+}
+
+#[cfg(all(feature = "simd", any(target_arch = "x86_64", target_arch = "aarch64")))]
+#[inline]
+fn mat4_mul_vec4(m: &Mat4, v: &Vec4) -> Vec4 {
+    Vec4 { v: simd::mat4_mul_vec4(&m.m, &v.v) }
+}
+
+impl ops::Mul<Vec4> for Mat4 {
+    type Output = Vec4;
+
+    fn mul(self, other: Vec4) -> Self::Output {
+        mat4_mul_vec4(&self, &other)
+    }
+}
+
+impl<'a> ops::Mul<&'a Mat4> for Mat4 {
+    type Output = Mat4;
+
+    fn mul(self, other: &'a Mat4) -> Mat4 {
+        mat4_mul(&self, other)
+    }
+}
+
+impl<'a, 'b> ops::Mul<&'a Mat4> for &'b Mat4 {
+    type Output = Mat4;
+
+    fn mul(self, other: &'a Mat4) -> Mat4 {
+        mat4_mul(self, other)
+    }
+}
+
+impl ops::Mul<Mat4> for Mat4 {
+    type Output = Mat4;
+
+    fn mul(self, other: Mat4) -> Mat4 {
+        mat4_mul(&self, &other)
+    }
+}
+
+impl cmp::PartialEq for Mat4 {
+    fn eq(&self, other: &Mat4) -> bool {
+        for i in 0..self.m.len() {
+            if f32::abs(self.m[i] - other.m[i]) > EPSILON {
+                return false;
+            }
+        }
+
+        true
+    }
+}
+
+///
+/// SSE versions of the `Mat4` products and inverse, used in place of the scalar
+/// code with the `simd` feature. Every x86_64 processor has SSE, so there is no
+/// need to check for it at run time. A matrix is loaded as four columns of four
+/// lanes each.
+///
+#[cfg(all(feature = "simd", target_arch = "x86_64"))]
+mod simd {
+    use std::arch::x86_64::*;
+
+    ///
+    /// The `_mm_shuffle_ps` mask that takes lanes `x` and `y` of its first
+    /// operand, followed by lanes `z` and `w` of its second.
+    ///
+    const fn mask(x: i32, y: i32, z: i32, w: i32) -> i32 {
+        x | (y << 2) | (z << 4) | (w << 6)
+    }
+
+    #[inline]
+    unsafe fn load_columns(m: &[f32; 16]) -> [__m128; 4] {
+        [_mm_loadu_ps(&m[0]), _mm_loadu_ps(&m[4]), _mm_loadu_ps(&m[8]), _mm_loadu_ps(&m[12])]
+    }
+
+    #[inline]
+    unsafe fn store_columns(columns: &[__m128; 4]) -> [f32; 16] {
+        let mut m = [0.0; 16];
+        for i in 0..4 {
+            _mm_storeu_ps(&mut m[4 * i], columns[i]);
+        }
+
+        m
+    }
+
+    ///
+    /// Combine the columns of a matrix with the components of `v` as weights.
+    /// The sums run in the same order as the scalar code, so the results match.
+    ///
+    #[inline]
+    unsafe fn combine(columns: &[__m128; 4], v: &[f32]) -> __m128 {
+        let mut sum = _mm_mul_ps(columns[0], _mm_set1_ps(v[0]));
+        sum = _mm_add_ps(sum, _mm_mul_ps(columns[1], _mm_set1_ps(v[1])));
+        sum = _mm_add_ps(sum, _mm_mul_ps(columns[2], _mm_set1_ps(v[2])));
+
+        _mm_add_ps(sum, _mm_mul_ps(columns[3], _mm_set1_ps(v[3])))
+    }
+
+    pub fn mat4_mul(a: &[f32; 16], b: &[f32; 16]) -> [f32; 16] {
+        unsafe {
+            let columns = load_columns(a);
+            store_columns(&[
+                combine(&columns, &b[0..4]),
+                combine(&columns, &b[4..8]),
+                combine(&columns, &b[8..12]),
+                combine(&columns, &b[12..16]),
+            ])
+        }
+    }
+
+    pub fn mat4_mul_vec4(m: &[f32; 16], v: &[f32; 4]) -> [f32; 4] {
+        let mut result = [0.0; 4];
+        unsafe {
+            _mm_storeu_ps(&mut result[0], combine(&load_columns(m), v));
+        }
+
+        result
+    }
+
+    // The inverse works on 2x2 blocks, each held in one vector as (m00, m01,
+    // m10, m11). These helpers multiply two blocks, where a trailing `#` below
+    // means the adjugate of a block.
+
+    // a * b
+    #[inline]
+    unsafe fn mat2_mul(a: __m128, b: __m128) -> __m128 {
+        _mm_add_ps(
+            _mm_mul_ps(a, _mm_shuffle_ps(b, b, mask(0, 3, 0, 3))),
+            _mm_mul_ps(_mm_shuffle_ps(a, a, mask(1, 0, 3, 2)), _mm_shuffle_ps(b, b, mask(2, 1, 2, 1)))
+        )
+    }
+
+    // a# * b
+    #[inline]
+    unsafe fn mat2_adj_mul(a: __m128, b: __m128) -> __m128 {
+        _mm_sub_ps(
+            _mm_mul_ps(_mm_shuffle_ps(a, a, mask(3, 3, 0, 0)), b),
+            _mm_mul_ps(_mm_shuffle_ps(a, a, mask(1, 1, 2, 2)), _mm_shuffle_ps(b, b, mask(2, 3, 0, 1)))
+        )
+    }
+
+    // a * b#
+    #[inline]
+    unsafe fn mat2_mul_adj(a: __m128, b: __m128) -> __m128 {
+        _mm_sub_ps(
+            _mm_mul_ps(a, _mm_shuffle_ps(b, b, mask(3, 0, 3, 0))),
+            _mm_mul_ps(_mm_shuffle_ps(a, a, mask(1, 0, 3, 2)), _mm_shuffle_ps(b, b, mask(2, 1, 2, 1)))
+        )
+    }
+
+    #[inline]
+    unsafe fn splat(v: __m128, lane: i32) -> __m128 {
+        match lane {
+            0 => _mm_shuffle_ps(v, v, mask(0, 0, 0, 0)),
+            1 => _mm_shuffle_ps(v, v, mask(1, 1, 1, 1)),
+            2 => _mm_shuffle_ps(v, v, mask(2, 2, 2, 2)),
+            _ => _mm_shuffle_ps(v, v, mask(3, 3, 3, 3)),
+        }
+    }
+
+    ///
+    /// Invert a matrix by splitting it into 2x2 blocks
+    ///
+    ///     | A B |
+    ///     | C D |
+    ///
+    /// and working with their determinants and adjugates, which takes far fewer
+    /// multiplies than expanding cofactors one at a time. This works on the
+    /// columns as though they were rows, which inverts the transpose, and the
+    /// transpose of that inverse is the inverse of the original matrix. Returns
+    /// `None` if the matrix has a zero determinant.
+    ///
+    pub fn mat4_inverse(m: &[f32; 16]) -> Option<[f32; 16]> {
+        unsafe {
+            let rows = load_columns(m);
+            let a = _mm_movelh_ps(rows[0], rows[1]);
+            let b = _mm_movehl_ps(rows[1], rows[0]);
+            let c = _mm_movelh_ps(rows[2], rows[3]);
+            let d = _mm_movehl_ps(rows[3], rows[2]);
+
+            // The determinants of A, B, C, and D, in that order.
+            let det_sub = _mm_sub_ps(
+                _mm_mul_ps(_mm_shuffle_ps(rows[0], rows[2], mask(0, 2, 0, 2)), _mm_shuffle_ps(rows[1], rows[3], mask(1, 3, 1, 3))),
+                _mm_mul_ps(_mm_shuffle_ps(rows[0], rows[2], mask(1, 3, 1, 3)), _mm_shuffle_ps(rows[1], rows[3], mask(0, 2, 0, 2)))
+            );
+            let det_a = splat(det_sub, 0);
+            let det_b = splat(det_sub, 1);
+            let det_c = splat(det_sub, 2);
+            let det_d = splat(det_sub, 3);
+
+            let d_c = mat2_adj_mul(d, c);
+            let a_b = mat2_adj_mul(a, b);
+            let x = _mm_sub_ps(_mm_mul_ps(det_d, a), mat2_mul(b, d_c));
+            let w = _mm_sub_ps(_mm_mul_ps(det_a, d), mat2_mul(c, a_b));
+            let y = _mm_sub_ps(_mm_mul_ps(det_b, c), mat2_mul_adj(d, a_b));
+            let z = _mm_sub_ps(_mm_mul_ps(det_c, b), mat2_mul_adj(a, d_c));
+
+            // |M| = |A||D| + |B||C| - tr((A# B)(D# C))
+            let mut trace = _mm_mul_ps(a_b, _mm_shuffle_ps(d_c, d_c, mask(0, 2, 1, 3)));
+            trace = _mm_add_ps(trace, _mm_shuffle_ps(trace, trace, mask(1, 0, 3, 2)));
+            trace = _mm_add_ps(trace, _mm_shuffle_ps(trace, trace, mask(2, 3, 0, 1)));
+            let det = _mm_sub_ps(_mm_add_ps(_mm_mul_ps(det_a, det_d), _mm_mul_ps(det_b, det_c)), trace);
+            if _mm_cvtss_f32(det) == 0.0 {
+                return None;
+            }
+
+            // Dividing by (1, -1, -1, 1) |M| also flips the signs the adjugates need.
+            let inv_det = _mm_div_ps(_mm_setr_ps(1.0, -1.0, -1.0, 1.0), det);
+            let x = _mm_mul_ps(x, inv_det);
+            let y = _mm_mul_ps(y, inv_det);
+            let z = _mm_mul_ps(z, inv_det);
+            let w = _mm_mul_ps(w, inv_det);
+
+            Some(store_columns(&[
+                _mm_shuffle_ps(x, y, mask(3, 1, 3, 1)),
+                _mm_shuffle_ps(x, y, mask(2, 0, 2, 0)),
+                _mm_shuffle_ps(z, w, mask(3, 1, 3, 1)),
+                _mm_shuffle_ps(z, w, mask(2, 0, 2, 0)),
+            ]))
+        }
+    }
+}
+
+///
+/// NEON versions of the `Mat4` products and inverse, the same as the SSE ones
+/// lane for lane. Every aarch64 processor has NEON. NEON has no general shuffle
+/// like SSE's, so each lane pattern the inverse needs is built from a fixed lane
+/// operation or two: reversing pairs, rotating, zipping, unzipping, or repeating
+/// one half of a vector.
+///
+#[cfg(all(feature = "simd", target_arch = "aarch64"))]
+mod simd {
+    use std::arch::aarch64::*;
+
+    #[inline]
+    unsafe fn load_columns(m: &[f32; 16]) -> [float32x4_t; 4] {
+        [vld1q_f32(&m[0]), vld1q_f32(&m[4]), vld1q_f32(&m[8]), vld1q_f32(&m[12])]
+    }
+
+    #[inline]
+    unsafe fn store_columns(columns: &[float32x4_t; 4]) -> [f32; 16] {
+        let mut m = [0.0; 16];
+        for i in 0..4 {
+            vst1q_f32(&mut m[4 * i], columns[i]);
+        }
+
+        m
+    }
+
+    ///
+    /// Combine the columns of a matrix with the components of `v` as weights.
+    /// Each product is added on its own rather than fused, so the results match
+    /// the scalar code.
+    ///
+    #[inline]
+    unsafe fn combine(columns: &[float32x4_t; 4], v: &[f32]) -> float32x4_t {
+        let mut sum = vmulq_f32(columns[0], vdupq_n_f32(v[0]));
+        sum = vaddq_f32(sum, vmulq_f32(columns[1], vdupq_n_f32(v[1])));
+        sum = vaddq_f32(sum, vmulq_f32(columns[2], vdupq_n_f32(v[2])));
+
+        vaddq_f32(sum, vmulq_f32(columns[3], vdupq_n_f32(v[3])))
+    }
+
+    pub fn mat4_mul(a: &[f32; 16], b: &[f32; 16]) -> [f32; 16] {
+        unsafe {
+            let columns = load_columns(a);
+            store_columns(&[
+                combine(&columns, &b[0..4]),
+                combine(&columns, &b[4..8]),
+                combine(&columns, &b[8..12]),
+                combine(&columns, &b[12..16]),
+            ])
+        }
+    }
+
+    pub fn mat4_mul_vec4(m: &[f32; 16], v: &[f32; 4]) -> [f32; 4] {
+        let mut result = [0.0; 4];
+        unsafe {
+            vst1q_f32(&mut result[0], combine(&load_columns(m), v));
+        }
+
+        result
+    }
+
+    // (v0, v1, v0, v1)
+    #[inline]
+    unsafe fn low_twice(v: float32x4_t) -> float32x4_t {
+        vcombine_f32(vget_low_f32(v), vget_low_f32(v))
+    }
+
+    // (v2, v3, v2, v3)
+    #[inline]
+    unsafe fn high_twice(v: float32x4_t) -> float32x4_t {
+        vcombine_f32(vget_high_f32(v), vget_high_f32(v))
+    }
+
+    // (v3, v0, v1, v2)
+    #[inline]
+    unsafe fn rotate_right(v: float32x4_t) -> float32x4_t {
+        vextq_f32::<3>(v, v)
+    }
+
+    // The inverse works on 2x2 blocks, each held in one vector as (m00, m01,
+    // m10, m11). These helpers multiply two blocks, where a trailing `#` below
+    // means the adjugate of a block.
+
+    // a * b = a (b0, b3, b0, b3) + (a1, a0, a3, a2) (b2, b1, b2, b1)
+    #[inline]
+    unsafe fn mat2_mul(a: float32x4_t, b: float32x4_t) -> float32x4_t {
+        // (b0, b3, b2, b1)
+        let b_0321 = vrev64q_f32(rotate_right(b));
+        vaddq_f32(
+            vmulq_f32(a, low_twice(b_0321)),
+            vmulq_f32(vrev64q_f32(a), high_twice(b_0321))
+        )
+    }
+
+    // a# * b = (a3, a3, a0, a0) b - (a1, a1, a2, a2) (b2, b3, b0, b1)
+    #[inline]
+    unsafe fn mat2_adj_mul(a: float32x4_t, b: float32x4_t) -> float32x4_t {
+        let a_3012 = rotate_right(a);
+        vsubq_f32(
+            vmulq_f32(vzip1q_f32(a_3012, a_3012), b),
+            vmulq_f32(vzip2q_f32(a_3012, a_3012), vextq_f32::<2>(b, b))
+        )
+    }
+
+    // a * b# = a (b3, b0, b3, b0) - (a1, a0, a3, a2) (b2, b1, b2, b1)
+    #[inline]
+    unsafe fn mat2_mul_adj(a: float32x4_t, b: float32x4_t) -> float32x4_t {
+        let b_3012 = rotate_right(b);
+        vsubq_f32(
+            vmulq_f32(a, low_twice(b_3012)),
+            vmulq_f32(vrev64q_f32(a), high_twice(vrev64q_f32(b_3012)))
+        )
+    }
+
+    ///
+    /// Invert a matrix by splitting it into 2x2 blocks, as the SSE version does.
+    /// Returns `None` if the matrix has a zero determinant.
+    ///
+    pub fn mat4_inverse(m: &[f32; 16]) -> Option<[f32; 16]> {
+        unsafe {
+            let rows = load_columns(m);
+            let a = vcombine_f32(vget_low_f32(rows[0]), vget_low_f32(rows[1]));
+            let b = vcombine_f32(vget_high_f32(rows[0]), vget_high_f32(rows[1]));
+            let c = vcombine_f32(vget_low_f32(rows[2]), vget_low_f32(rows[3]));
+            let d = vcombine_f32(vget_high_f32(rows[2]), vget_high_f32(rows[3]));
+
+            // The determinants of A, B, C, and D, in that order.
+            let det_sub = vsubq_f32(
+                vmulq_f32(vuzp1q_f32(rows[0], rows[2]), vuzp2q_f32(rows[1], rows[3])),
+                vmulq_f32(vuzp2q_f32(rows[0], rows[2]), vuzp1q_f32(rows[1], rows[3]))
+            );
+            let det_a = vdupq_laneq_f32::<0>(det_sub);
+            let det_b = vdupq_laneq_f32::<1>(det_sub);
+            let det_c = vdupq_laneq_f32::<2>(det_sub);
+            let det_d = vdupq_laneq_f32::<3>(det_sub);
+
+            let d_c = mat2_adj_mul(d, c);
+            let a_b = mat2_adj_mul(a, b);
+            let x = vsubq_f32(vmulq_f32(det_d, a), mat2_mul(b, d_c));
+            let w = vsubq_f32(vmulq_f32(det_a, d), mat2_mul(c, a_b));
+            let y = vsubq_f32(vmulq_f32(det_b, c), mat2_mul_adj(d, a_b));
+            let z = vsubq_f32(vmulq_f32(det_c, b), mat2_mul_adj(a, d_c));
+
+            // |M| = |A||D| + |B||C| - tr((A# B)(D# C)), where (D# C) is transposed
+            // by zipping it with itself rotated by half.
+            let mut trace = vmulq_f32(a_b, vzip1q_f32(d_c, vextq_f32::<2>(d_c, d_c)));
+            trace = vaddq_f32(trace, vrev64q_f32(trace));
+            trace = vaddq_f32(trace, vextq_f32::<2>(trace, trace));
+            let det = vsubq_f32(vaddq_f32(vmulq_f32(det_a, det_d), vmulq_f32(det_b, det_c)), trace);
+            if vgetq_lane_f32::<0>(det) == 0.0 {
+                return None;
+            }
+
+            // Dividing by (1, -1, -1, 1) |M| also flips the signs the adjugates need.
+            let signs = [1.0, -1.0, -1.0, 1.0];
+            let inv_det = vdivq_f32(vld1q_f32(signs.as_ptr()), det);
+            let x = vmulq_f32(x, inv_det);
+            let y = vmulq_f32(y, inv_det);
+            let z = vmulq_f32(z, inv_det);
+            let w = vmulq_f32(w, inv_det);
+
+            // (x3, x1, y3, y1), (x2, x0, y2, y0), and the same for z and w.
+            Some(store_columns(&[
+                vrev64q_f32(vuzp2q_f32(x, y)),
+                vrev64q_f32(vuzp1q_f32(x, y)),
+                vrev64q_f32(vuzp2q_f32(z, w)),
+                vrev64q_f32(vuzp1q_f32(z, w)),
+            ]))
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct Versor {
+    q: [f32; 4],
+}
+
+impl Versor {
+    ///
+    /// Construct a quaternion from its scalar part `w` and its vector part `(x, y, z)`.
+    ///
+    pub fn new(w: f32, x: f32, y: f32, z: f32) -> Versor {
+        Versor { q: [w, x, y, z] }
+    }
+
+    pub fn normalize(&self) -> Versor {
+        // normalize(q) = q / magnitude (q)
+        // magnitude (q) = sqrt (w*w + x*x...)
+        // only compute sqrt if interior sum != 1.0
+        let sum = self.q[0] * self.q[0] + self.q[1] * self.q[1] + self.q[2] * self.q[2] + self.q[3] * self.q[3];
+        // NB: Floats have min 6 digits of precision.
+        let threshold = 0.0001;
+        if f32::abs(1.0 - sum) < threshold {
+            return *self;
+        }
+
+        let norm = f32::sqrt(sum);
+        self / norm
+    }
+
+    pub fn dot(&self, r: &Versor) -> f32 {
+        self.q[0] * r.q[0] + self.q[1] * r.q[1] + self.q[2] * r.q[2] + self.q[3] * r.q[3]
+    }
+
+    pub fn from_axis_rad(radians: f32, x: f32, y: f32, z: f32) -> Versor {
+        Versor {
+            q: [
+                f32::cos(radians / 2.0),
+                f32::sin(radians / 2.0) * x,
+                f32::sin(radians / 2.0) * y,
+                f32::sin(radians / 2.0) * z,
+            ]
+        }
+    }
+
+    pub fn from_axis_deg(degrees: f32, x: f32, y: f32, z: f32) -> Versor {
+        Self::from_axis_rad(ONE_DEG_IN_RAD * degrees, x, y, z)
+    }
+
+    ///
+    /// Rotate a vector by a unit quaternion, computing q v q* directly rather
+    /// than through a rotation matrix. This gives the same result as
+    /// multiplying `to_mat4()` by the vector with a w of zero.
+    ///
+    pub fn rotate_vec3(&self, v: &Vec3) -> Vec3 {
+        // With u the vector part of q, q v q* = v + 2w (u x v) + 2 u x (u x v).
+        let u = vec3((self.q[1], self.q[2], self.q[3]));
+        let t = u.cross(v) * 2.0;
+
+        *v + t * self.q[0] + u.cross(&t)
+    }
+
+    ///
+    /// Construct the unit quaternion for the rotation in the upper 3x3 part of
+    /// `m`, which must be a pure rotation. This undoes `to_mat4`, up to the sign
+    /// of the quaternion.
+    ///
+    pub fn from_mat4(m: &Mat4) -> Versor {
+        // Divide by whichever of w, x, y, and z is largest, so the division
+        // stays accurate.
+        let trace = m.m[0] + m.m[5] + m.m[10];
+        let q = if trace > 0.0 {
+            let s = f32::sqrt(1.0 + trace) * 2.0;
+            [0.25 * s, (m.m[6] - m.m[9]) / s, (m.m[8] - m.m[2]) / s, (m.m[1] - m.m[4]) / s]
+        } else if m.m[0] > m.m[5] && m.m[0] > m.m[10] {
+            let s = f32::sqrt(1.0 + m.m[0] - m.m[5] - m.m[10]) * 2.0;
+            [(m.m[6] - m.m[9]) / s, 0.25 * s, (m.m[4] + m.m[1]) / s, (m.m[8] + m.m[2]) / s]
+        } else if m.m[5] > m.m[10] {
+            let s = f32::sqrt(1.0 + m.m[5] - m.m[0] - m.m[10]) * 2.0;
+            [(m.m[8] - m.m[2]) / s, (m.m[4] + m.m[1]) / s, 0.25 * s, (m.m[9] + m.m[6]) / s]
+        } else {
+            let s = f32::sqrt(1.0 + m.m[10] - m.m[0] - m.m[5]) * 2.0;
+            [(m.m[1] - m.m[4]) / s, (m.m[8] + m.m[2]) / s, (m.m[9] + m.m[6]) / s, 0.25 * s]
+        };
+
+        Versor { q: q }
+    }
+
+    pub fn to_mat4(&self) -> Mat4 {
+        let w = self.q[0];
+        let x = self.q[1];
+        let y = self.q[2];
+        let z = self.q[3];
+    
+        Mat4::new(
+            1.0 - 2.0 * y * y - 2.0 * z * z, 2.0 * x * y + 2.0 * w * z,       2.0 * x * z - 2.0 * w * y,       0.0, 
+            2.0 * x * y - 2.0 * w * z,       1.0 - 2.0 * x * x - 2.0 * z * z, 2.0 * y * z + 2.0 * w * x,       0.0, 
+            2.0 * x * z + 2.0 * w * y,       2.0 * y * z - 2.0 * w * x,       1.0 - 2.0 * x * x - 2.0 * y * y, 0.0, 
+            0.0,                             0.0,                             0.0,                             1.0
+        )
+    }
+
+    pub fn to_mut_mat4(&self, m: &mut Mat4) {
+        let w = self.q[0];
+        let x = self.q[1];
+        let y = self.q[2];
+        let z = self.q[3];
+        m.m[0] = 1.0 - 2.0 * y * y - 2.0 * z * z;
+        m.m[1] = 2.0 * x * y + 2.0 * w * z;
+        m.m[2] = 2.0 * x * z - 2.0 * w * y;
+        m.m[3] = 0.0;
+        m.m[4] = 2.0 * x * y - 2.0 * w * z;
+        m.m[5] = 1.0 - 2.0 * x * x - 2.0 * z * z;
+        m.m[6] = 2.0 * y * z + 2.0 * w * x;
+        m.m[7] = 0.0;
+        m.m[8] = 2.0 * x * z + 2.0 * w * y;
+        m.m[9] = 2.0 * y * z - 2.0 * w * x;
+        m.m[10] = 1.0 - 2.0 * x * x - 2.0 * y * y;
+        m.m[11] = 0.0;
+        m.m[12] = 0.0;
+        m.m[13] = 0.0;
+        m.m[14] = 0.0;
+        m.m[15] = 1.0;
+    }
+
+    pub fn slerp(q: &mut Versor, r: &Versor, t: f32) -> Versor {
+        // angle between q0-q1
+        let mut cos_half_theta = q.dot(r);
+        // as found here
+        // http://stackoverflow.com/questions/2886606/flipping-issue-when-interpolating-rotations-using-quaternions
+        // if dot product is negative then one quaternion should be negated, to make
+        // it take the short way around, rather than the long way
+        // yeah! and furthermore Susan, I had to recalculate the d.p. after this
+        if cos_half_theta < 0.0 {
+            q.q[0] *= -1.0;
+            q.q[1] *= -1.0;
+            q.q[2] *= -1.0;
+            q.q[3] *= -1.0;
+
+            cos_half_theta = q.dot(r);
+        }
+        // if qa=qb or qa=-qb then theta = 0 and we can return qa
+        if f32::abs(cos_half_theta) >= 1.0 {
+            return *q;
+        }
+
+        // Calculate temporary values
+        let sin_half_theta = f32::sqrt(1.0 - cos_half_theta * cos_half_theta);
+        // if theta = 180 degrees then result is not fully defined
+        // we could rotate around any axis normal to qa or qb
+        let mut result = Versor { q: [1.0, 0.0, 0.0, 0.0] };
+        if f32::abs(sin_half_theta) < 0.001 {
+            result.q[0] = (1.0 - t) * q.q[0] + t * r.q[0];
+            result.q[1] = (1.0 - t) * q.q[1] + t * r.q[1];
+            result.q[2] = (1.0 - t) * q.q[2] + t * r.q[2];
+            result.q[3] = (1.0 - t) * q.q[3] + t * r.q[3];
+
+            return result;
+        }
+        let half_theta = f32::acos(cos_half_theta);
+        let a = f32::sin((1.0 - t) * half_theta) / sin_half_theta;
+        let b = f32::sin(t * half_theta) / sin_half_theta;
+        
+        result.q[0] = q.q[0] * a + r.q[0] * b;
+        result.q[1] = q.q[1] * a + r.q[1] * b;
+        result.q[2] = q.q[2] * a + r.q[2] * b;
+        result.q[3] = q.q[3] * a + r.q[3] * b;
+
+        return result;
+    }
+}
+
+impl fmt::Display for Versor {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "[{:.2}, {:.2}, {:.2}, {:.2}]", self.q[0], self.q[1], self.q[2], self.q[3])
+    }
+}
+
+impl convert::AsRef<[f32; 4]> for Versor {
+    fn as_ref(&self) -> &[f32; 4] {
+        &self.q
+    }
+}
+
+impl ops::Div<f32> for Versor {
+    type Output = Versor;
+
+    fn div(self, other: f32) -> Versor {
+        Versor {
+            q: [
+                self.q[0] / other, 
+                self.q[1] / other, 
+                self.q[2] / other, 
+                self.q[3] / other,
+            ]
+        }
+    }
+}
+
+impl<'a> ops::Div<f32> for &'a Versor {
+    type Output = Versor;
+
+    fn div(self, other: f32) -> Versor {
+        Versor {
+            q: [
+                self.q[0] / other, 
+                self.q[1] / other, 
+                self.q[2] / other, 
+                self.q[3] / other,
+            ]
+        }
+    }
+}
+
+impl ops::Mul<f32> for Versor {
+    type Output = Versor;
+
+    fn mul(self, other: f32) -> Versor {
+        Versor {
+            q: [
+                self.q[0] * other,
+                self.q[1] * other,
+                self.q[2] * other,
+                self.q[3] * other,
+            ]
+        }
+    }
+}
+
+impl<'a> ops::Mul<&'a Versor> for Versor {
+    type Output = Versor;
+
+    fn mul(self, other: &'a Versor) -> Self::Output {
+        let result = Versor {
+            q: [
+                other.q[0] * self.q[0] - other.q[1] * self.q[1] - other.q[2] * self.q[2] - other.q[3] * self.q[3],
+                other.q[0] * self.q[1] + other.q[1] * self.q[0] - other.q[2] * self.q[3] + other.q[3] * self.q[2],
+                other.q[0] * self.q[2] + other.q[1] * self.q[3] + other.q[2] * self.q[0] - other.q[3] * self.q[1],
+                other.q[0] * self.q[3] - other.q[1] * self.q[2] + other.q[2] * self.q[1] + other.q[3] * self.q[0],
+            ]
+        };
+        // Renormalize in case of mangling.
+        result.normalize()
+    }
+}
+
+impl ops::Mul<Versor> for Versor {
+    type Output = Versor;
+
+    fn mul(self, other: Versor) -> Self::Output {
+        self * &other
+    }
+}
+
+impl<'a> ops::Mul<Versor> for &'a Versor {
+    type Output = Versor;
+
+    fn mul(self, other: Versor) -> Self::Output {
+        *self * &other
+    }
+}
+
+impl<'a, 'b> ops::Mul<&'b Versor> for &'a Versor {
+    type Output = Versor;
+
+    fn mul(self, other: &'b Versor) -> Self::Output {
+        *self * other
+    }
+}
+
+impl ops::Mul<Vec3> for Versor {
+    type Output = Vec3;
+
+    fn mul(self, other: Vec3) -> Vec3 {
+        self.rotate_vec3(&other)
+    }
+}
+
+impl<'a> ops::Mul<&'a Vec3> for Versor {
+    type Output = Vec3;
+
+    fn mul(self, other: &'a Vec3) -> Vec3 {
+        self.rotate_vec3(other)
+    }
+}
+
+impl<'a> ops::Add<&'a Versor> for Versor {
+    type Output = Versor;
+
+    fn add(self, other: &'a Versor) -> Self::Output {
+        let result = Versor {
+            q: [
+                other.q[0] + self.q[0],
+                other.q[1] + self.q[1],
+                other.q[2] + self.q[2],
+                other.q[3] + self.q[3],
+            ]
+        };
+        // Renormalize in case of mangling.
+        result.normalize()
+    }
+}
+
+
+///
+/// A three-dimensional vector in double precision. An `f32` only has about seven
+/// significant digits, so far from the origin small steps get rounded away, and
+/// a position built up a little at a time stops moving smoothly. Keep positions
+/// like that in a `DVec3`, and convert to a `Vec3` to hand them to the GPU.
+///
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct DVec3 {
+    pub v: [f64; 3],
+}
+
+impl DVec3 {
+    pub fn new(x: f64, y: f64, z: f64) -> DVec3 {
+        DVec3 { v: [x, y, z] }
+    }
+
+    pub fn zero() -> DVec3 {
+        DVec3 { v: [0.0, 0.0, 0.0] }
+    }
+
+    ///
+    /// Compute the norm (length) of a vector.
+    ///
+    pub fn norm(&self) -> f64 {
+        f64::sqrt(self.dot(self))
+    }
+
+    ///
+    /// Convert an arbitrary vector into a unit vector.
+    ///
+    pub fn normalize(&self) -> DVec3 {
+        let norm_v = self.norm();
+        if norm_v == 0.0 {
+            return DVec3::zero();
+        }
+
+        self / norm_v
+    }
+
+    ///
+    /// Compute the dot product of two vectors.
+    ///
+    pub fn dot(&self, other: &DVec3) -> f64 {
+        self.v[0] * other.v[0] + self.v[1] * other.v[1] + self.v[2] * other.v[2]
+    }
+
+    ///
+    /// Compute the cross product of two vectors.
+    ///
+    pub fn cross(&self, other: &DVec3) -> DVec3 {
+        let x = self.v[1] * other.v[2] - self.v[2] * other.v[1];
+        let y = self.v[2] * other.v[0] - self.v[0] * other.v[2];
+        let z = self.v[0] * other.v[1] - self.v[1] * other.v[0];
+
+        DVec3::new(x, y, z)
+    }
+}
+
+#[inline]
+pub fn dvec3<T: Into<DVec3>>(v: T) -> DVec3 {
+    v.into()
+}
+
+impl From<(f64, f64, f64)> for DVec3 {
+    #[inline]
+    fn from((x, y, z): (f64, f64, f64)) -> DVec3 {
+        DVec3::new(x, y, z)
+    }
+}
+
+impl From<Vec3> for DVec3 {
+    #[inline]
+    fn from(v: Vec3) -> DVec3 {
+        DVec3::new(v.v[0] as f64, v.v[1] as f64, v.v[2] as f64)
+    }
+}
+
+impl From<DVec3> for Vec3 {
+    #[inline]
+    fn from(v: DVec3) -> Vec3 {
+        Vec3::new(v.v[0] as f32, v.v[1] as f32, v.v[2] as f32)
+    }
+}
+
+impl fmt::Display for DVec3 {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "[{:.2}, {:.2}, {:.2}]", self.v[0], self.v[1], self.v[2])
+    }
+}
+
+impl<'a> ops::Add<DVec3> for &'a DVec3 {
+    type Output = DVec3;
+
+    fn add(self, other: DVec3) -> Self::Output {
+        DVec3 {
+            v: [
+                self.v[0] + other.v[0],
+                self.v[1] + other.v[1],
+                self.v[2] + other.v[2],
+            ]
+        }
+    }
+}
+
+impl ops::Add<DVec3> for DVec3 {
+    type Output = DVec3;
+
+    fn add(self, other: DVec3) -> Self::Output {
+        DVec3 {
+            v: [
+                self.v[0] + other.v[0],
+                self.v[1] + other.v[1],
+                self.v[2] + other.v[2],
+            ]
+        }
+    }
+}
+
+impl<'a> ops::Add<&'a DVec3> for DVec3 {
+    type Output = DVec3;
+
+    fn add(self, other: &'a DVec3) -> Self::Output {
+        DVec3 {
+            v: [
+                self.v[0] + other.v[0],
+                self.v[1] + other.v[1],
+                self.v[2] + other.v[2],
+            ]
+        }
+    }
+}
+
+impl<'a, 'b> ops::Add<&'b DVec3> for &'a DVec3 {
+    type Output = DVec3;
+
+    fn add(self, other: &'b DVec3) -> Self::Output {
+        DVec3 {
+            v: [
+                self.v[0] + other.v[0],
+                self.v[1] + other.v[1],
+                self.v[2] + other.v[2],
+            ]
+        }
+    }
+}
+
+impl<'a> ops::Sub<DVec3> for &'a DVec3 {
+    type Output = DVec3;
+
+    fn sub(self, other: DVec3) -> Self::Output {
+        DVec3 {
+            v: [
+                self.v[0] - other.v[0],
+                self.v[1] - other.v[1],
+                self.v[2] - other.v[2],
+            ]
+        }
+    }
+}
+
+impl ops::Sub<DVec3> for DVec3 {
+    type Output = DVec3;
+
+    fn sub(self, other: DVec3) -> Self::Output {
+        DVec3 {
+            v: [
+                self.v[0] - other.v[0],
+                self.v[1] - other.v[1],
+                self.v[2] - other.v[2],
+            ]
+        }
+    }
+}
+
+impl<'a> ops::Sub<&'a DVec3> for DVec3 {
+    type Output = DVec3;
+
+    fn sub(self, other: &'a DVec3) -> Self::Output {
+        DVec3 {
+            v: [
+                self.v[0] - other.v[0],
+                self.v[1] - other.v[1],
+                self.v[2] - other.v[2],
+            ]
+        }
+    }
+}
+
+impl<'a, 'b> ops::Sub<&'b DVec3> for &'a DVec3 {
+    type Output = DVec3;
+
+    fn sub(self, other: &'b DVec3) -> Self::Output {
+        DVec3 {
+            v: [
+                self.v[0] - other.v[0],
+                self.v[1] - other.v[1],
+                self.v[2] - other.v[2],
+            ]
+        }
+    }
+}
+
+impl ops::AddAssign<DVec3> for DVec3 {
+    fn add_assign(&mut self, other: DVec3) {
+        self.v[0] += other.v[0];
+        self.v[1] += other.v[1];
+        self.v[2] += other.v[2];
+    }
+}
+
+impl<'a> ops::AddAssign<&'a DVec3> for DVec3 {
+    fn add_assign(&mut self, other: &'a DVec3) {
+        self.v[0] += other.v[0];
+        self.v[1] += other.v[1];
+        self.v[2] += other.v[2];
+    }
+}
+
+impl ops::SubAssign<DVec3> for DVec3 {
+    fn sub_assign(&mut self, other: DVec3) {
+        self.v[0] -= other.v[0];
+        self.v[1] -= other.v[1];
+        self.v[2] -= other.v[2];
+    }
+}
+
+impl<'a> ops::SubAssign<&'a DVec3> for DVec3 {
+    fn sub_assign(&mut self, other: &'a DVec3) {
+        self.v[0] -= other.v[0];
+        self.v[1] -= other.v[1];
+        self.v[2] -= other.v[2];
+    }
+}
+
+impl ops::Mul<f64> for DVec3 {
+    type Output = DVec3;
+
+    fn mul(self, other: f64) -> Self::Output {
+        DVec3 {
+            v: [
+                self.v[0] * other,
+                self.v[1] * other,
+                self.v[2] * other,
+            ]
+        }
+    }
+}
+
+impl<'a> ops::Mul<f64> for &'a DVec3 {
+    type Output = DVec3;
+
+    fn mul(self, other: f64) -> Self::Output {
+        DVec3 {
+            v: [
+                self.v[0] * other,
+                self.v[1] * other,
+                self.v[2] * other,
+            ]
+        }
+    }
+}
+
+impl ops::Div<f64> for DVec3 {
+    type Output = DVec3;
+
+    fn div(self, other: f64) -> Self::Output {
+        DVec3 {
+            v: [
+                self.v[0] / other,
+                self.v[1] / other,
+                self.v[2] / other,
+            ]
+        }
+    }
+}
+
+impl<'a> ops::Div<f64> for &'a DVec3 {
+    type Output = DVec3;
+
+    fn div(self, other: f64) -> Self::Output {
+        DVec3 {
+            v: [
+                self.v[0] / other,
+                self.v[1] / other,
+                self.v[2] / other,
+            ]
+        }
+    }
+}
+
+impl ops::Neg for DVec3 {
+    type Output = DVec3;
+
+    fn neg(self) -> Self::Output {
+        DVec3 {
+            v: [
+                -self.v[0],
+                -self.v[1],
+                -self.v[2],
+            ]
+        }
+    }
+}
+
+impl<'a> ops::Neg for &'a DVec3 {
+    type Output = DVec3;
+
+    fn neg(self) -> Self::Output {
+        DVec3 {
+            v: [
+                -self.v[0],
+                -self.v[1],
+                -self.v[2],
+            ]
+        }
+    }
+}
+
+impl ops::Index<usize> for DVec3 {
+    type Output = f64;
+
+    #[inline]
+    fn index(&self, index: usize) -> &f64 {
+        &self.v[index]
+    }
+}
+
+impl ops::IndexMut<usize> for DVec3 {
+    #[inline]
+    fn index_mut(&mut self, index: usize) -> &mut f64 {
+        &mut self.v[index]
+    }
+}
+
+///
+/// A 4x4 matrix in double precision, in column-major order like `Mat4`. Inverting
+/// a matrix that translates a long way from the origin loses most of an `f32`'s
+/// precision, so build and invert transforms like that as a `DMat4`, and convert
+/// the result to a `Mat4` to upload it.
+///
+#[derive(Copy, Clone, Debug)]
+pub struct DMat4 {
+    pub m: [f64; 16],
+}
+
+impl DMat4 {
+    pub fn new(
+        m11: f64, m12: f64, m13: f64, m14: f64,
+        m21: f64, m22: f64, m23: f64, m24: f64,
+        m31: f64, m32: f64, m33: f64, m34: f64,
+        m41: f64, m42: f64, m43: f64, m44: f64) -> DMat4 {
+
+        DMat4 {
+            m: [
+                m11, m12, m13, m14, // Column 1
+                m21, m22, m23, m24, // Column 2
+                m31, m32, m33, m34, // Column 3
+                m41, m42, m43, m44  // Column 4
+            ]
+        }
+    }
+
+    pub fn zero() -> DMat4 {
+        DMat4 { m: [0.0; 16] }
+    }
+
+    pub fn identity() -> DMat4 {
+        DMat4::new(
+            1.0, 0.0, 0.0, 0.0,
+            0.0, 1.0, 0.0, 0.0,
+            0.0, 0.0, 1.0, 0.0,
+            0.0, 0.0, 0.0, 1.0
+        )
+    }
+
+    pub fn transpose(&self) -> DMat4 {
+        DMat4::new(
+            self.m[0], self.m[4], self.m[8],  self.m[12],
+            self.m[1], self.m[5], self.m[9],  self.m[13],
+            self.m[2], self.m[6], self.m[10], self.m[14],
+            self.m[3], self.m[7], self.m[11], self.m[15]
+        )
+    }
+
+    ///
+    /// The matrix that translates by `v`.
+    ///
+    pub fn from_translation(v: &DVec3) -> DMat4 {
+        let mut m_t = DMat4::identity();
+        m_t.m[12] = v.v[0];
+        m_t.m[13] = v.v[1];
+        m_t.m[14] = v.v[2];
+
+        m_t
+    }
+
+    ///
+    /// Translate by `v` after the transform `self` makes, the product `T * self`,
+    /// as `Mat4::then_translate` does.
+    ///
+    pub fn translate(&self, v: &DVec3) -> DMat4 {
+        DMat4::from_translation(v) * self
+    }
+
+    ///
+    /// Transform a point, including the translation.
+    ///
+    pub fn transform_point(&self, p: &DVec3) -> DVec3 {
+        let mut result = DVec3::new(self.m[12], self.m[13], self.m[14]);
+        for i in 0..3 {
+            result.v[0] += self.m[4 * i] * p.v[i];
+            result.v[1] += self.m[4 * i + 1] * p.v[i];
+            result.v[2] += self.m[4 * i + 2] * p.v[i];
+        }
+
+        result
+    }
+
+    ///
+    /// Compute the determinant the same way as `Mat4::determinant`.
+    ///
+    pub fn determinant(&self) -> f64 {
+        self.m[12] * self.m[9]  * self.m[6]  * self.m[3]  -
+        self.m[8]  * self.m[13] * self.m[6]  * self.m[3]  -
+        self.m[12] * self.m[5]  * self.m[10] * self.m[3]  +
+        self.m[4]  * self.m[13] * self.m[10] * self.m[3]  +
+        self.m[8]  * self.m[5]  * self.m[14] * self.m[3]  -
+        self.m[4]  * self.m[9]  * self.m[14] * self.m[3]  -
+        self.m[12] * self.m[9]  * self.m[2]  * self.m[7]  +
+        self.m[8]  * self.m[13] * self.m[2]  * self.m[7]  +
+        self.m[12] * self.m[1]  * self.m[10] * self.m[7]  -
+        self.m[0]  * self.m[13] * self.m[10] * self.m[7]  -
+        self.m[8]  * self.m[1]  * self.m[14] * self.m[7]  +
+        self.m[0]  * self.m[9]  * self.m[14] * self.m[7]  +
+        self.m[12] * self.m[5]  * self.m[2]  * self.m[11] -
+        self.m[4]  * self.m[13] * self.m[2]  * self.m[11] -
+        self.m[12] * self.m[1]  * self.m[6]  * self.m[11] +
+        self.m[0]  * self.m[13] * self.m[6]  * self.m[11] +
+        self.m[4]  * self.m[1]  * self.m[14] * self.m[11] -
+        self.m[0]  * self.m[5]  * self.m[14] * self.m[11] -
+        self.m[8]  * self.m[5]  * self.m[2]  * self.m[15] +
+        self.m[4]  * self.m[9]  * self.m[2]  * self.m[15] +
+        self.m[8]  * self.m[1]  * self.m[6]  * self.m[15] -
+        self.m[0]  * self.m[9]  * self.m[6]  * self.m[15] -
+        self.m[4]  * self.m[1]  * self.m[10] * self.m[15] +
+        self.m[0]  * self.m[5]  * self.m[10] * self.m[15]
+    }
+
+    pub fn is_invertible(&self) -> bool {
+        self.determinant() != 0.0
+    }
+
+    ///
+    /// Compute the inverse the same way as `Mat4::inverse`, which returns the
+    /// matrix unchanged if it has no inverse.
+    ///
+    pub fn inverse(&self) -> DMat4 {
+        let det = self.determinant();
+        if det == 0.0 {
+            eprintln!("WARNING. Matrix has zero determinant. It cannot be inverted.");
+            
+            return *self;
+        }
+
+        let inv_det = 1.0 / det;
+
+        return DMat4::new(
+            inv_det * ( self.m[9] * self.m[14] * self.m[7] - self.m[13] * self.m[10] * self.m[7] +
+                                    self.m[13] * self.m[6] * self.m[11] - self.m[5] * self.m[14] * self.m[11] -
+                                    self.m[9] * self.m[6] * self.m[15] + self.m[5] * self.m[10] * self.m[15] ),
+            inv_det * ( self.m[13] * self.m[10] * self.m[3] - self.m[9] * self.m[14] * self.m[3] -
+                                    self.m[13] * self.m[2] * self.m[11] + self.m[1] * self.m[14] * self.m[11] +
+                                    self.m[9] * self.m[2] * self.m[15] - self.m[1] * self.m[10] * self.m[15] ),
+            inv_det * ( self.m[5] * self.m[14] * self.m[3] - self.m[13] * self.m[6] * self.m[3] +
+                                    self.m[13] * self.m[2] * self.m[7] - self.m[1] * self.m[14] * self.m[7] -
+                                    self.m[5] * self.m[2] * self.m[15] + self.m[1] * self.m[6] * self.m[15] ),
+            inv_det * ( self.m[9] * self.m[6] * self.m[3] - self.m[5] * self.m[10] * self.m[3] -
+                                    self.m[9] * self.m[2] * self.m[7] + self.m[1] * self.m[10] * self.m[7] +
+                                    self.m[5] * self.m[2] * self.m[11] - self.m[1] * self.m[6] * self.m[11] ),
+            inv_det * ( self.m[12] * self.m[10] * self.m[7] - self.m[8] * self.m[14] * self.m[7] -
+                                    self.m[12] * self.m[6] * self.m[11] + self.m[4] * self.m[14] * self.m[11] +
+                                    self.m[8] * self.m[6] * self.m[15] - self.m[4] * self.m[10] * self.m[15] ),
+            inv_det * ( self.m[8] * self.m[14] * self.m[3] - self.m[12] * self.m[10] * self.m[3] +
+                                    self.m[12] * self.m[2] * self.m[11] - self.m[0] * self.m[14] * self.m[11] -
+                                    self.m[8] * self.m[2] * self.m[15] + self.m[0] * self.m[10] * self.m[15] ),
+            inv_det * ( self.m[12] * self.m[6] * self.m[3] - self.m[4] * self.m[14] * self.m[3] -
+                                    self.m[12] * self.m[2] * self.m[7] + self.m[0] * self.m[14] * self.m[7] +
+                                    self.m[4] * self.m[2] * self.m[15] - self.m[0] * self.m[6] * self.m[15] ),
+            inv_det * ( self.m[4] * self.m[10] * self.m[3] - self.m[8] * self.m[6] * self.m[3] +
+                                    self.m[8] * self.m[2] * self.m[7] - self.m[0] * self.m[10] * self.m[7] -
+                                    self.m[4] * self.m[2] * self.m[11] + self.m[0] * self.m[6] * self.m[11] ),
+            inv_det * ( self.m[8] * self.m[13] * self.m[7] - self.m[12] * self.m[9] * self.m[7] +
+                                    self.m[12] * self.m[5] * self.m[11] - self.m[4] * self.m[13] * self.m[11] -
+                                    self.m[8] * self.m[5] * self.m[15] + self.m[4] * self.m[9] * self.m[15] ),
+            inv_det * ( self.m[12] * self.m[9] * self.m[3] - self.m[8] * self.m[13] * self.m[3] -
+                                    self.m[12] * self.m[1] * self.m[11] + self.m[0] * self.m[13] * self.m[11] +
+                                    self.m[8] * self.m[1] * self.m[15] - self.m[0] * self.m[9] * self.m[15] ),
+            inv_det * ( self.m[4] * self.m[13] * self.m[3] - self.m[12] * self.m[5] * self.m[3] +
+                                    self.m[12] * self.m[1] * self.m[7] - self.m[0] * self.m[13] * self.m[7] -
+                                    self.m[4] * self.m[1] * self.m[15] + self.m[0] * self.m[5] * self.m[15] ),
+            inv_det * ( self.m[8] * self.m[5] * self.m[3] - self.m[4] * self.m[9] * self.m[3] -
+                                    self.m[8] * self.m[1] * self.m[7] + self.m[0] * self.m[9] * self.m[7] +
+                                    self.m[4] * self.m[1] * self.m[11] - self.m[0] * self.m[5] * self.m[11] ),
+            inv_det * ( self.m[12] * self.m[9] * self.m[6] - self.m[8] * self.m[13] * self.m[6] -
+                                    self.m[12] * self.m[5] * self.m[10] + self.m[4] * self.m[13] * self.m[10] +
+                                    self.m[8] * self.m[5] * self.m[14] - self.m[4] * self.m[9] * self.m[14] ),
+            inv_det * ( self.m[8] * self.m[13] * self.m[2] - self.m[12] * self.m[9] * self.m[2] +
+                                    self.m[12] * self.m[1] * self.m[10] - self.m[0] * self.m[13] * self.m[10] -
+                                    self.m[8] * self.m[1] * self.m[14] + self.m[0] * self.m[9] * self.m[14] ),
+            inv_det * ( self.m[12] * self.m[5] * self.m[2] - self.m[4] * self.m[13] * self.m[2] -
+                                    self.m[12] * self.m[1] * self.m[6] + self.m[0] * self.m[13] * self.m[6] +
+                                    self.m[4] * self.m[1] * self.m[14] - self.m[0] * self.m[5] * self.m[14] ),
+            inv_det * ( self.m[4] * self.m[9] * self.m[2] - self.m[8] * self.m[5] * self.m[2] +
+                                    self.m[8] * self.m[1] * self.m[6] - self.m[0] * self.m[9] * self.m[6] -
+                                    self.m[4] * self.m[1] * self.m[10] + self.m[0] * self.m[5] * self.m[10] ) );
+    }
+}
+
+impl From<Mat4> for DMat4 {
+    fn from(m: Mat4) -> DMat4 {
+        let mut result = DMat4::zero();
+        for i in 0..16 {
+            result.m[i] = m.m[i] as f64;
+        }
+
+        result
+    }
+}
+
+impl From<DMat4> for Mat4 {
+    fn from(m: DMat4) -> Mat4 {
+        let mut result = Mat4::zero();
+        for i in 0..16 {
+            result.m[i] = m.m[i] as f32;
+        }
+
+        result
+    }
+}
+
+impl<'a> ops::Mul<&'a DMat4> for DMat4 {
+    type Output = DMat4;
+
+    fn mul(self, other: &'a DMat4) -> DMat4 {
+        let mut mm = DMat4::zero();
+
+        mm.m[0]  = self.m[0]*other.m[0]  + self.m[4]*other.m[1]  + self.m[8]*other.m[2]   + self.m[12]*other.m[3];
+        mm.m[1]  = self.m[1]*other.m[0]  + self.m[5]*other.m[1]  + self.m[9]*other.m[2]   + self.m[13]*other.m[3];
+        mm.m[2]  = self.m[2]*other.m[0]  + self.m[6]*other.m[1]  + self.m[10]*other.m[2]  + self.m[14]*other.m[3];
+        mm.m[3]  = self.m[3]*other.m[0]  + self.m[7]*other.m[1]  + self.m[11]*other.m[2]  + self.m[15]*other.m[3];
+        mm.m[4]  = self.m[0]*other.m[4]  + self.m[4]*other.m[5]  + self.m[8]*other.m[6]   + self.m[12]*other.m[7];
+        mm.m[5]  = self.m[1]*other.m[4]  + self.m[5]*other.m[5]  + self.m[9]*other.m[6]   + self.m[13]*other.m[7];
+        mm.m[6]  = self.m[2]*other.m[4]  + self.m[6]*other.m[5]  + self.m[10]*other.m[6]  + self.m[14]*other.m[7];
+        mm.m[7]  = self.m[3]*other.m[4]  + self.m[7]*other.m[5]  + self.m[11]*other.m[6]  + self.m[15]*other.m[7];
+        mm.m[8]  = self.m[0]*other.m[8]  + self.m[4]*other.m[9]  + self.m[8]*other.m[10]  + self.m[12]*other.m[11];
+        mm.m[9]  = self.m[1]*other.m[8]  + self.m[5]*other.m[9]  + self.m[9]*other.m[10]  + self.m[13]*other.m[11];
+        mm.m[10] = self.m[2]*other.m[8]  + self.m[6]*other.m[9]  + self.m[10]*other.m[10] + self.m[14]*other.m[11];
+        mm.m[11] = self.m[3]*other.m[8]  + self.m[7]*other.m[9]  + self.m[11]*other.m[10] + self.m[15]*other.m[11];
+        mm.m[12] = self.m[0]*other.m[12] + self.m[4]*other.m[13] + self.m[8]*other.m[14]  + self.m[12]*other.m[15];
+        mm.m[13] = self.m[1]*other.m[12] + self.m[5]*other.m[13] + self.m[9]*other.m[14]  + self.m[13]*other.m[15];
+        mm.m[14] = self.m[2]*other.m[12] + self.m[6]*other.m[13] + self.m[10]*other.m[14] + self.m[14]*other.m[15];
+        mm.m[15] = self.m[3]*other.m[12] + self.m[7]*other.m[13] + self.m[11]*other.m[14] + self.m[15]*other.m[15];
+
+        mm
+    }
+}
+
+impl<'a, 'b> ops::Mul<&'a DMat4> for &'b DMat4 {
+    type Output = DMat4;
+
+    fn mul(self, other: &'a DMat4) -> DMat4 {
+        let mut mm = DMat4::zero();
+
+        mm.m[0]  = self.m[0]*other.m[0]  + self.m[4]*other.m[1]  + self.m[8]*other.m[2]   + self.m[12]*other.m[3];
+        mm.m[1]  = self.m[1]*other.m[0]  + self.m[5]*other.m[1]  + self.m[9]*other.m[2]   + self.m[13]*other.m[3];
+        mm.m[2]  = self.m[2]*other.m[0]  + self.m[6]*other.m[1]  + self.m[10]*other.m[2]  + self.m[14]*other.m[3];
+        mm.m[3]  = self.m[3]*other.m[0]  + self.m[7]*other.m[1]  + self.m[11]*other.m[2]  + self.m[15]*other.m[3];
+        mm.m[4]  = self.m[0]*other.m[4]  + self.m[4]*other.m[5]  + self.m[8]*other.m[6]   + self.m[12]*other.m[7];
+        mm.m[5]  = self.m[1]*other.m[4]  + self.m[5]*other.m[5]  + self.m[9]*other.m[6]   + self.m[13]*other.m[7];
+        mm.m[6]  = self.m[2]*other.m[4]  + self.m[6]*other.m[5]  + self.m[10]*other.m[6]  + self.m[14]*other.m[7];
+        mm.m[7]  = self.m[3]*other.m[4]  + self.m[7]*other.m[5]  + self.m[11]*other.m[6]  + self.m[15]*other.m[7];
+        mm.m[8]  = self.m[0]*other.m[8]  + self.m[4]*other.m[9]  + self.m[8]*other.m[10]  + self.m[12]*other.m[11];
+        mm.m[9]  = self.m[1]*other.m[8]  + self.m[5]*other.m[9]  + self.m[9]*other.m[10]  + self.m[13]*other.m[11];
+        mm.m[10] = self.m[2]*other.m[8]  + self.m[6]*other.m[9]  + self.m[10]*other.m[10] + self.m[14]*other.m[11];
+        mm.m[11] = self.m[3]*other.m[8]  + self.m[7]*other.m[9]  + self.m[11]*other.m[10] + self.m[15]*other.m[11];
+        mm.m[12] = self.m[0]*other.m[12] + self.m[4]*other.m[13] + self.m[8]*other.m[14]  + self.m[12]*other.m[15];
+        mm.m[13] = self.m[1]*other.m[12] + self.m[5]*other.m[13] + self.m[9]*other.m[14]  + self.m[13]*other.m[15];
+        mm.m[14] = self.m[2]*other.m[12] + self.m[6]*other.m[13] + self.m[10]*other.m[14] + self.m[14]*other.m[15];
+        mm.m[15] = self.m[3]*other.m[12] + self.m[7]*other.m[13] + self.m[11]*other.m[14] + self.m[15]*other.m[15];
+
+        mm
+    }
+}
+
+impl ops::Mul<DMat4> for DMat4 {
+    type Output = DMat4;
+
+    fn mul(self, other: DMat4) -> DMat4 {
+        let mut mm = DMat4::zero();
+
+        mm.m[0]  = self.m[0]*other.m[0]  + self.m[4]*other.m[1]  + self.m[8]*other.m[2]   + self.m[12]*other.m[3];
+        mm.m[1]  = self.m[1]*other.m[0]  + self.m[5]*other.m[1]  + self.m[9]*other.m[2]   + self.m[13]*other.m[3];
+        mm.m[2]  = self.m[2]*other.m[0]  + self.m[6]*other.m[1]  + self.m[10]*other.m[2]  + self.m[14]*other.m[3];
+        mm.m[3]  = self.m[3]*other.m[0]  + self.m[7]*other.m[1]  + self.m[11]*other.m[2]  + self.m[15]*other.m[3];
+        mm.m[4]  = self.m[0]*other.m[4]  + self.m[4]*other.m[5]  + self.m[8]*other.m[6]   + self.m[12]*other.m[7];
+        mm.m[5]  = self.m[1]*other.m[4]  + self.m[5]*other.m[5]  + self.m[9]*other.m[6]   + self.m[13]*other.m[7];
+        mm.m[6]  = self.m[2]*other.m[4]  + self.m[6]*other.m[5]  + self.m[10]*other.m[6]  + self.m[14]*other.m[7];
+        mm.m[7]  = self.m[3]*other.m[4]  + self.m[7]*other.m[5]  + self.m[11]*other.m[6]  + self.m[15]*other.m[7];
+        mm.m[8]  = self.m[0]*other.m[8]  + self.m[4]*other.m[9]  + self.m[8]*other.m[10]  + self.m[12]*other.m[11];
+        mm.m[9]  = self.m[1]*other.m[8]  + self.m[5]*other.m[9]  + self.m[9]*other.m[10]  + self.m[13]*other.m[11];
+        mm.m[10] = self.m[2]*other.m[8]  + self.m[6]*other.m[9]  + self.m[10]*other.m[10] + self.m[14]*other.m[11];
+        mm.m[11] = self.m[3]*other.m[8]  + self.m[7]*other.m[9]  + self.m[11]*other.m[10] + self.m[15]*other.m[11];
+        mm.m[12] = self.m[0]*other.m[12] + self.m[4]*other.m[13] + self.m[8]*other.m[14]  + self.m[12]*other.m[15];
+        mm.m[13] = self.m[1]*other.m[12] + self.m[5]*other.m[13] + self.m[9]*other.m[14]  + self.m[13]*other.m[15];
+        mm.m[14] = self.m[2]*other.m[12] + self.m[6]*other.m[13] + self.m[10]*other.m[14] + self.m[14]*other.m[15];
+        mm.m[15] = self.m[3]*other.m[12] + self.m[7]*other.m[13] + self.m[11]*other.m[14] + self.m[15]*other.m[15];
+
+        mm
+    }
+}
+
+impl cmp::PartialEq for DMat4 {
+    fn eq(&self, other: &DMat4) -> bool {
+        for i in 0..self.m.len() {
+            if f64::abs(self.m[i] - other.m[i]) > EPSILON as f64 {
+                return false;
+            }
+        }
+
+        true
+    }
+}
+
+///
+/// With the `glam` feature, vectors, matrices, and quaternions convert to and
+/// from their counterparts in the glam crate, so code that does its own maths
+/// with glam can pass values to these utilities and take results back without
+/// copying components by hand. Both libraries store matrices in column-major
+/// order, so a matrix converts unchanged.
+///
+#[cfg(feature = "glam")]
+impl From<glam::Vec3> for Vec3 {
+    #[inline]
+    fn from(v: glam::Vec3) -> Vec3 {
+        Vec3 { v: v.into() }
+    }
+}
+
+#[cfg(feature = "glam")]
+impl From<Vec3> for glam::Vec3 {
+    #[inline]
+    fn from(v: Vec3) -> glam::Vec3 {
+        glam::Vec3::from(v.v)
+    }
+}
+
+#[cfg(feature = "glam")]
+impl From<glam::Mat4> for Mat4 {
+    #[inline]
+    fn from(m: glam::Mat4) -> Mat4 {
+        Mat4 { m: m.to_cols_array() }
+    }
+}
+
+#[cfg(feature = "glam")]
+impl From<Mat4> for glam::Mat4 {
+    #[inline]
+    fn from(m: Mat4) -> glam::Mat4 {
+        glam::Mat4::from_cols_array(&m.m)
+    }
+}
+
+#[cfg(feature = "glam")]
+impl From<glam::Quat> for Versor {
+    #[inline]
+    fn from(q: glam::Quat) -> Versor {
+        // glam stores the scalar part last.
+        let q: [f32; 4] = q.into();
+        Versor::new(q[3], q[0], q[1], q[2])
+    }
+}
 
-    pub fn zero() -> Mat4 {
-        Mat4::new(
-            0.0, 0.0, 0.0, 0.0, 
-            0.0, 0.0, 0.0, 0.0, 
-            0.0, 0.0, 0.0, 0.0, 
-            0.0, 0.0, 0.0, 0.0
-        )
+#[cfg(feature = "glam")]
+impl From<Versor> for glam::Quat {
+    #[inline]
+    fn from(q: Versor) -> glam::Quat {
+        glam::Quat::from_xyzw(q.q[1], q.q[2], q.q[3], q.q[0])
     }
+}
 
-    pub fn identity() -> Mat4 {
+///
+/// With the `cgmath` feature, the same types convert to and from the cgmath
+/// crate's. cgmath also stores matrices by column, and keeps the scalar part of
+/// a quaternion apart from its vector part.
+///
+#[cfg(feature = "cgmath")]
+impl From<cgmath::Vector3<f32>> for Vec3 {
+    #[inline]
+    fn from(v: cgmath::Vector3<f32>) -> Vec3 {
+        Vec3 { v: [v.x, v.y, v.z] }
+    }
+}
+
+#[cfg(feature = "cgmath")]
+impl From<Vec3> for cgmath::Vector3<f32> {
+    #[inline]
+    fn from(v: Vec3) -> cgmath::Vector3<f32> {
+        cgmath::Vector3::new(v.v[0], v.v[1], v.v[2])
+    }
+}
+
+#[cfg(feature = "cgmath")]
+impl From<cgmath::Matrix4<f32>> for Mat4 {
+    #[inline]
+    fn from(m: cgmath::Matrix4<f32>) -> Mat4 {
         Mat4::new(
-            1.0, 0.0, 0.0, 0.0, 
-            0.0, 1.0, 0.0, 0.0, 
-            0.0, 0.0, 1.0, 0.0, 
-            0.0, 0.0, 0.0, 1.0
+            m.x.x, m.x.y, m.x.z, m.x.w,
+            m.y.x, m.y.y, m.y.z, m.y.w,
+            m.z.x, m.z.y, m.z.z, m.z.w,
+            m.w.x, m.w.y, m.w.z, m.w.w
         )
     }
+}
 
-    pub fn transpose(&self) -> Mat4 {
-        Mat4::new(
-            self.m[0], self.m[4], self.m[8],  self.m[12],
-            self.m[1], self.m[5], self.m[9],  self.m[13], 
-            self.m[2], self.m[6], self.m[10], self.m[14], 
-            self.m[3], self.m[7], self.m[11], self.m[15]
+#[cfg(feature = "cgmath")]
+impl From<Mat4> for cgmath::Matrix4<f32> {
+    #[inline]
+    fn from(m: Mat4) -> cgmath::Matrix4<f32> {
+        let m = m.m;
+        cgmath::Matrix4::new(
+            m[0],  m[1],  m[2],  m[3],
+            m[4],  m[5],  m[6],  m[7],
+            m[8],  m[9],  m[10], m[11],
+            m[12], m[13], m[14], m[15]
         )
     }
+}
 
-    pub fn translate(&self, v: &Vec3) -> Mat4 {
-        let mut m_t = Mat4::identity();
-        m_t.m[12] = v.v[0];
-        m_t.m[13] = v.v[1];
-        m_t.m[14] = v.v[2];
+#[cfg(feature = "cgmath")]
+impl From<cgmath::Quaternion<f32>> for Versor {
+    #[inline]
+    fn from(q: cgmath::Quaternion<f32>) -> Versor {
+        Versor::new(q.s, q.v.x, q.v.y, q.v.z)
+    }
+}
 
-        m_t * self
+#[cfg(feature = "cgmath")]
+impl From<Versor> for cgmath::Quaternion<f32> {
+    #[inline]
+    fn from(q: Versor) -> cgmath::Quaternion<f32> {
+        cgmath::Quaternion::new(q.q[0], q.q[1], q.q[2], q.q[3])
     }
+}
 
-    // Rotate around x axis by an angle in degrees.
-    pub fn rotate_x_deg(&self, deg: f32) -> Mat4 {
-        // Convert to radians.
-        let rad = deg * ONE_DEG_IN_RAD;
-        let mut m_r = Mat4::identity();
-        m_r.m[5]  =  f32::cos(rad);
-        m_r.m[9]  = -f32::sin(rad);
-        m_r.m[6]  =  f32::sin(rad);
-        m_r.m[10] =  f32::cos(rad);
-    
-        m_r * self
+mod vec2_tests {
+    use super::Vec2;
+
+    #[test]
+    fn test_arithmetic() {
+        let x = super::vec2(3.0, -1.5);
+        let y = super::vec2(0.5, 2.0);
+
+        assert_eq!(x + y, super::vec2(3.5, 0.5));
+        assert_eq!(&x - &y, super::vec2(2.5, -3.5));
+        assert_eq!(x * 2.0, super::vec2(6.0, -3.0));
+        assert_eq!(2.0 * x, x * 2.0);
+        assert_eq!(x / 2.0, super::vec2(1.5, -0.75));
+        assert_eq!(-x, super::vec2(-3.0, 1.5));
+
+        let mut z = x;
+        z += y;
+        z *= 2.0;
+        assert_eq!(z, super::vec2(7.0, 1.0));
     }
 
-    // Rotate around y axis by an angle in degrees.
-    pub fn rotate_y_deg(&self, deg: f32) -> Mat4 {
-        // Convert to radians.
-        let rad = deg * ONE_DEG_IN_RAD;
-        let mut m_r = Mat4::identity();
-        m_r.m[0]  =  f32::cos(rad);
-        m_r.m[8]  =  f32::sin(rad);
-        m_r.m[2]  = -f32::sin(rad);
-        m_r.m[10] =  f32::cos(rad);
-    
-        m_r * self
+    #[test]
+    fn test_dot_and_norm() {
+        let v = super::vec2(3.0, 4.0);
+
+        assert_eq!(v.dot(&Vec2::unit_x()), 3.0);
+        assert_eq!(v.norm(), 5.0);
+        assert_eq!(v.normalize(), super::vec2(0.6, 0.8));
+        assert_eq!(Vec2::zero().normalize(), Vec2::zero());
     }
 
-    // Rotate around z axis by an angle in degrees.
-    pub fn rotate_z_deg(&self, deg: f32) -> Mat4 {
-        // Convert to radians.
-        let rad = deg * ONE_DEG_IN_RAD;
-        let mut m_r = Mat4::identity();
-        m_r.m[0] =  f32::cos(rad);
-        m_r.m[4] = -f32::sin(rad);
-        m_r.m[1] =  f32::sin(rad);
-        m_r.m[5] =  f32::cos(rad);
-    
-        m_r * self
+    #[test]
+    fn test_indexing() {
+        let mut v = super::vec2(1.0, 2.0);
+        v[1] = 5.0;
+
+        assert_eq!(v[0], 1.0);
+        assert_eq!(v, super::vec2(1.0, 5.0));
     }
+}
 
-    // scale a matrix by [x, y, z]
-    pub fn scale(&self, v: &Vec3) -> Mat4 {
-        let mut m_s = Mat4::identity();
-        m_s.m[0]  = v.v[0];
-        m_s.m[5]  = v.v[1];
-        m_s.m[10] = v.v[2];
-    
-        m_s * self
+mod vec3_tests {
+    use std::slice::Iter;
+    use super::Vec3;
+
+    struct TestCase {
+        c: f32,
+        x: Vec3,
+        y: Vec3,
     }
 
-    /// returns a scalar value with the determinant for a 4x4 matrix
-    /// see
-    /// http://www.euclideanspace.com/maths/algebra/matrix/functions/determinant/fourD/index.htm
-    pub fn determinant(&self) -> f32 {
-        self.m[12] * self.m[9]  * self.m[6]  * self.m[3]  -
-        self.m[8]  * self.m[13] * self.m[6]  * self.m[3]  -
-        self.m[12] * self.m[5]  * self.m[10] * self.m[3]  +
-        self.m[4]  * self.m[13] * self.m[10] * self.m[3]  +
-        self.m[8]  * self.m[5]  * self.m[14] * self.m[3]  -
-        self.m[4]  * self.m[9]  * self.m[14] * self.m[3]  -
-        self.m[12] * self.m[9]  * self.m[2]  * self.m[7]  +
-        self.m[8]  * self.m[13] * self.m[2]  * self.m[7]  +
-        self.m[12] * self.m[1]  * self.m[10] * self.m[7]  -
-        self.m[0]  * self.m[13] * self.m[10] * self.m[7]  -
-        self.m[8]  * self.m[1]  * self.m[14] * self.m[7]  +
-        self.m[0]  * self.m[9]  * self.m[14] * self.m[7]  +
-        self.m[12] * self.m[5]  * self.m[2]  * self.m[11] -
-        self.m[4]  * self.m[13] * self.m[2]  * self.m[11] -
-        self.m[12] * self.m[1]  * self.m[6]  * self.m[11] +
-        self.m[0]  * self.m[13] * self.m[6]  * self.m[11] +
-        self.m[4]  * self.m[1]  * self.m[14] * self.m[11] -
-        self.m[0]  * self.m[5]  * self.m[14] * self.m[11] -
-        self.m[8]  * self.m[5]  * self.m[2]  * self.m[15] +
-        self.m[4]  * self.m[9]  * self.m[2]  * self.m[15] +
-        self.m[8]  * self.m[1]  * self.m[6]  * self.m[15] -
-        self.m[0]  * self.m[9]  * self.m[6]  * self.m[15] -
-        self.m[4]  * self.m[1]  * self.m[10] * self.m[15] +
-        self.m[0]  * self.m[5]  * self.m[10] * self.m[15]
+    struct Test {
+        tests: Vec<TestCase>,
     }
 
-    // returns a perspective function mimicking the opengl projection style.
-    pub fn perspective(fovy: f32, aspect: f32, near: f32, far: f32) -> Mat4 {
-        let fov_rad = fovy * ONE_DEG_IN_RAD;
-        let range = f32::tan(fov_rad / 2.0) * near;
-        let sx = (2.0 * near) / (range * aspect + range * aspect);
-        let sy = near / range;
-        let sz = -(far + near) / (far - near);
-        let pz = -(2.0 * far * near) / (far - near);
-        let mut m = Mat4::zero(); // make sure bottom-right corner is zero
-        m.m[0] = sx;
-        m.m[5] = sy;
-        m.m[10] = sz;
-        m.m[14] = pz;
-        m.m[11] = -1.0;
-        
-        m
+    impl Test {
+        fn iter(&self) -> TestIter {
+            TestIter {
+                inner: self.tests.iter()
+            }
+        }
+    }
+
+    struct TestIter<'a> {
+        inner: Iter<'a, TestCase>,
+    }
+
+    impl<'a> Iterator for TestIter<'a> {
+        type Item = &'a TestCase;
+
+        fn next(&mut self) -> Option<Self::Item> {
+            self.inner.next()
+        }
+    }
+
+    fn test_cases() -> Test {
+        Test {
+            tests: vec![
+                TestCase {
+                    c: 802.3435169,
+                    x: super::vec3((80.0,  23.43, 43.569)),
+                    y: super::vec3((6.741, 426.1, 23.5724)),
+                },
+                TestCase {
+                    c: 33.249539,
+                    x: super::vec3((27.6189, 13.90, 4.2219)),
+                    y: super::vec3((258.083, 31.70, 42.17))
+                },
+                TestCase {
+                    c: 7.04217,
+                    x: super::vec3((70.0,  49.0,  95.0)),
+                    y: super::vec3((89.9138, 36.84, 427.46894)),
+                },
+                TestCase {
+                    c: 61.891390,
+                    x: super::vec3((8827.1983, 89.5049494, 56.31)),
+                    y: super::vec3((89.0, 72.0, 936.5)),
+                }
+            ]
+        }
+    }
+
+    #[test]
+    fn test_addition() {
+        for test in test_cases().iter() {
+            let expected = super::vec3((test.x.v[0] + test.y.v[0], test.x.v[1] + test.y.v[1], test.x.v[2] + test.y.v[2]));
+            let result = test.x + test.y;
+            assert_eq!(result, expected);
+        }
+    }
+
+    #[test]
+    fn test_subtraction() {
+        for test in test_cases().iter() {
+            let expected = super::vec3((test.x.v[0] - test.y.v[0], test.x.v[1] - test.y.v[1], test.x.v[2] - test.y.v[2]));
+            let result = test.x - test.y;
+            assert_eq!(result, expected);
+        }
+    }
+
+    #[test]
+    fn test_scalar_multiplication() {
+        for test in test_cases().iter() {
+            let expected = super::vec3((test.c * test.x.v[0], test.c * test.x.v[1], test.c * test.x.v[2]));
+            let result = test.x * test.c;
+            assert_eq!(result, expected);
+        }
+    }
+
+    #[test]
+    fn test_scalar_division() {
+        for test in test_cases().iter() {
+            let expected = super::vec3((test.x.v[0] / test.c, test.x.v[1] / test.c, test.x.v[2] / test.c));
+            let result = test.x / test.c;
+            assert_eq!(result, expected);
+        }
+    }
+
+    #[test]
+    fn test_scalars_on_either_side() {
+        for test in test_cases().iter() {
+            assert_eq!(test.c * test.x, test.x * test.c);
+            assert_eq!(test.c + test.x, test.x + test.c);
+            assert_eq!(test.c - test.x, -(test.x - test.c));
+        }
+        assert_eq!(1.0 / super::vec3((2.0, 4.0, -0.5)), super::vec3((0.5, 0.25, -2.0)));
+    }
+
+    #[test]
+    fn test_negation_and_indexing() {
+        let mut v = super::vec3((1.0, -2.0, 3.0));
+        assert_eq!(-v, super::vec3((-1.0, 2.0, -3.0)));
+        assert_eq!(-&v + v, Vec3::zero());
+
+        v[2] *= 2.0;
+        assert_eq!(v[2], 6.0);
+        assert_eq!(v.v, [v[0], v[1], v[2]]);
+    }
+
+    #[test]
+    fn test_unit_vectors() {
+        assert_eq!(Vec3::unit_x().cross(&Vec3::unit_y()), Vec3::unit_z());
+        assert_eq!(Vec3::unit_y().norm(), 1.0);
+    }
+
+    #[test]
+    fn test_lerp_min_max_and_clamp() {
+        let a = super::vec3((0.0, 10.0, -4.0));
+        let b = super::vec3((2.0, -10.0, 4.0));
+
+        assert_eq!(a.lerp(&b, 0.0), a);
+        assert_eq!(a.lerp(&b, 1.0), b);
+        assert_eq!(a.lerp(&b, 0.25), super::vec3((0.5, 5.0, -2.0)));
+        assert_eq!(a.min(&b), super::vec3((0.0, -10.0, -4.0)));
+        assert_eq!(a.max(&b), super::vec3((2.0, 10.0, 4.0)));
+
+        let lo = super::vec3((-1.0, -1.0, -1.0));
+        let hi = super::vec3((1.0, 1.0, 1.0));
+        assert_eq!(super::vec3((0.5, 3.0, -7.0)).clamp(&lo, &hi), super::vec3((0.5, 1.0, -1.0)));
+    }
+
+    #[test]
+    fn test_vectors_multiply_and_divide_component_by_component() {
+        let a = super::vec3((1.0, -2.0, 3.0));
+        let b = super::vec3((2.0, 4.0, -0.5));
+
+        assert_eq!(a * b, super::vec3((2.0, -8.0, -1.5)));
+        assert_eq!(&a * &b, a * b);
+        assert_eq!(a / b, super::vec3((0.5, -0.5, -6.0)));
+        assert_eq!(&a / b, a / &b);
+    }
+
+    #[test]
+    fn test_a_scalar_multiplies_from_either_side() {
+        let a = super::vec3((1.0, -2.0, 3.0));
+
+        assert_eq!(-2.0 * a, a * -2.0);
+        assert_eq!(-a, a * -1.0);
+    }
+}
+
+mod vec4_tests {
+    use super::Vec4;
+
+    #[test]
+    fn test_arithmetic() {
+        let x = super::vec4((1.0, 2.0, 3.0, 4.0));
+        let y = super::vec4((0.5, -1.0, 0.0, 2.0));
+
+        assert_eq!(x + y, super::vec4((1.5, 1.0, 3.0, 6.0)));
+        assert_eq!(x - &y, super::vec4((0.5, 3.0, 3.0, 2.0)));
+        assert_eq!(0.5 * x, super::vec4((0.5, 1.0, 1.5, 2.0)));
+        assert_eq!(&x / 4.0, super::vec4((0.25, 0.5, 0.75, 1.0)));
+        assert_eq!(-y, super::vec4((-0.5, 1.0, 0.0, -2.0)));
+
+        let mut z = x;
+        z -= y;
+        z /= 0.5;
+        assert_eq!(z, super::vec4((1.0, 6.0, 6.0, 4.0)));
+    }
+
+    #[test]
+    fn test_dot_norm_and_units() {
+        let v = super::vec4((1.0, 2.0, 2.0, 4.0));
+
+        assert_eq!(v.dot(&Vec4::unit_w()), 4.0);
+        assert_eq!(v.norm(), 5.0);
+        assert_eq!(v.normalize().norm2(), 1.0);
+        assert_eq!(Vec4::unit_x() + Vec4::unit_y() + Vec4::unit_z(), super::vec4((1.0, 1.0, 1.0, 0.0)));
+    }
+
+    #[test]
+    fn test_indexing_lerp_and_clamp() {
+        let mut v = super::vec4((0.0, 0.0, 0.0, 1.0));
+        v[0] = 4.0;
+
+        assert_eq!(v[0], 4.0);
+        assert_eq!(v.lerp(&Vec4::zero(), 0.5), super::vec4((2.0, 0.0, 0.0, 0.5)));
+        assert_eq!(v.clamp(&Vec4::zero(), &super::vec4((1.0, 1.0, 1.0, 1.0))), super::vec4((1.0, 0.0, 0.0, 1.0)));
+    }
+
+    #[test]
+    fn test_vectors_multiply_and_divide_component_by_component() {
+        let a = super::vec4((1.0, -2.0, 3.0, 4.0));
+        let b = super::vec4((2.0, 4.0, -0.5, 0.25));
+
+        assert_eq!(a * b, super::vec4((2.0, -8.0, -1.5, 1.0)));
+        assert_eq!(&a * &b, a * b);
+        assert_eq!(a / b, super::vec4((0.5, -0.5, -6.0, 16.0)));
+        assert_eq!(&a / b, a / &b);
+    }
+}
+
+#[cfg(feature = "glam")]
+mod glam_tests {
+    use super::{Mat4, Vec3, Versor};
+    use glam;
+
+    #[test]
+    fn test_vec3_round_trip() {
+        let v = super::vec3((1.0, -2.0, 3.5));
+        let g: glam::Vec3 = v.into();
+
+        assert_eq!(g, glam::Vec3::new(1.0, -2.0, 3.5));
+        assert_eq!(Vec3::from(g), v);
+        assert_eq!(super::vec3(g), v);
+    }
+
+    #[test]
+    fn test_mat4_keeps_its_columns() {
+        let m = Mat4::identity().rotate_y_deg(30.0).translate(&super::vec3((4.0, 5.0, 6.0)));
+        let g: glam::Mat4 = m.into();
+
+        assert_eq!(g.transform_point3(glam::Vec3::new(0.0, 0.0, 0.0)), glam::Vec3::new(4.0, 5.0, 6.0));
+        assert_eq!(Mat4::from(g), m);
+    }
+
+    #[test]
+    fn test_versor_and_quat_rotate_the_same_way() {
+        let q = Versor::from_axis_deg(90.0, 0.0, 0.0, 1.0);
+        let g: glam::Quat = q.into();
+        let rotated = g * glam::Vec3::new(1.0, 0.0, 0.0);
+
+        assert!((Vec3::from(rotated) - q.rotate_vec3(&Vec3::unit_x())).norm() < 1e-6);
+        assert_eq!(Versor::from(g).to_mat4(), q.to_mat4());
+    }
+}
+
+#[cfg(feature = "cgmath")]
+mod cgmath_tests {
+    use super::{Mat4, Vec3, Versor};
+    use cgmath;
+
+    #[test]
+    fn test_vec3_round_trip() {
+        let v = super::vec3((1.0, -2.0, 3.5));
+        let c: cgmath::Vector3<f32> = v.into();
+
+        assert_eq!(c, cgmath::Vector3::new(1.0, -2.0, 3.5));
+        assert_eq!(Vec3::from(c), v);
+    }
+
+    #[test]
+    fn test_mat4_keeps_its_columns() {
+        let m = Mat4::identity().rotate_y_deg(30.0).translate(&super::vec3((4.0, 5.0, 6.0)));
+        let c: cgmath::Matrix4<f32> = m.into();
+
+        assert_eq!(c.w, cgmath::Vector4::new(4.0, 5.0, 6.0, 1.0));
+        assert_eq!(c * cgmath::Vector4::new(0.0, 0.0, 0.0, 1.0), cgmath::Vector4::new(4.0, 5.0, 6.0, 1.0));
+        assert_eq!(Mat4::from(c), m);
+    }
+
+    #[test]
+    fn test_versor_and_quaternion_rotate_the_same_way() {
+        let q = Versor::from_axis_deg(90.0, 0.0, 0.0, 1.0);
+        let c: cgmath::Quaternion<f32> = q.into();
+        let rotated = c * cgmath::Vector3::new(1.0, 0.0, 0.0);
+
+        assert_eq!(c.s, q.q[0]);
+        assert!((Vec3::from(rotated) - q.rotate_vec3(&Vec3::unit_x())).norm() < 1e-6);
+        assert_eq!(Versor::from(c).to_mat4(), q.to_mat4());
+    }
+}
+
+#[cfg(all(feature = "simd", any(target_arch = "x86_64", target_arch = "aarch64")))]
+mod simd_tests {
+    use super::{Mat4, Vec4};
+
+    fn assert_close(a: &[f32], b: &[f32], epsilon: f32) {
+        for i in 0..a.len() {
+            assert!((a[i] - b[i]).abs() <= epsilon, "{} is not within {} of {} at {}", a[i], epsilon, b[i], i);
+        }
+    }
+
+    fn transform() -> Mat4 {
+        Mat4::identity()
+            .rotate_x_deg(20.0)
+            .rotate_y_deg(-35.0)
+            .rotate_z_deg(70.0)
+            .translate(&super::vec3((3.0, -4.5, 12.0)))
+    }
+
+    #[test]
+    fn test_mat4_mul_matches_the_scalar_code() {
+        let a = transform();
+        let b = Mat4::perspective(67.0, 1.5, 0.1, 100.0);
+
+        assert_eq!(super::mat4_mul(&a, &b).m, super::mat4_mul_scalar(&a, &b).m);
+        assert_eq!(super::mat4_mul(&b, &a).m, super::mat4_mul_scalar(&b, &a).m);
+    }
+
+    #[test]
+    fn test_mat4_mul_vec4_matches_the_scalar_code() {
+        let m = transform();
+        let v = super::vec4((1.5, -2.0, 0.25, 1.0));
+
+        assert_eq!((m * v).v, super::mat4_mul_vec4_scalar(&m, &v).v);
+        assert_eq!((m * Vec4::zero()).v, [0.0; 4]);
+    }
+
+    #[test]
+    fn test_inverse_matches_the_scalar_code() {
+        let matrices = [
+            Mat4::identity(),
+            transform(),
+            Mat4::perspective(67.0, 1.5, 0.1, 100.0),
+            Mat4::new(
+                2.0, 0.0, 1.0, 0.5, 1.0, 3.0, 0.0, -1.0,
+                0.0, 1.0, 4.0, 2.0, -2.0, 0.5, 1.0, 5.0
+            ),
+        ];
+        for m in matrices.iter() {
+            assert_close(&m.inverse().m, &m.inverse_scalar().m, 1e-4);
+            assert_close(&(*m * m.inverse()).m, &Mat4::identity().m, 1e-4);
+        }
+    }
+
+    #[test]
+    fn test_inverse_of_a_singular_matrix_is_the_scalar_result() {
+        let m = Mat4::zero();
+
+        assert_eq!(super::simd::mat4_inverse(&m.m), None);
+        assert_eq!(m.inverse(), m.inverse_scalar());
+    }
+}
+
+mod mat4_tests {
+    use std::slice::Iter;
+    use super::{Vec3, Mat4, Versor};
+
+    struct TestCase {
+        c: f32,
+        a_mat: Mat4,
+        b_mat: Mat4,
+    }
+
+    struct Test {
+        tests: Vec<TestCase>,
+    }
+
+    impl Test {
+        fn iter(&self) -> TestIter {
+            TestIter {
+                inner: self.tests.iter()
+            }
+        }
+    }
+
+    struct TestIter<'a> {
+        inner: Iter<'a, TestCase>,
+    }
+
+    impl<'a> Iterator for TestIter<'a> {
+        type Item = &'a TestCase;
+
+        fn next(&mut self) -> Option<Self::Item> {
+            self.inner.next()
+        }
+    }
+
+    fn test_cases() -> Test {
+        Test {
+            tests: vec![
+                TestCase {
+                    c: 802.3435169,
+                    a_mat: super::mat4(
+                        80.0,   23.43,   43.569,  6.741, 
+                        426.1,  23.5724, 27.6189, 13.90,
+                        4.2219, 258.083, 31.70,   42.17, 
+                        70.0,   49.0,    95.0,    89.9138
+                    ),
+                    b_mat: super::mat4(
+                        36.84,   427.46894, 8827.1983, 89.5049494, 
+                        7.04217, 61.891390, 56.31,     89.0, 
+                        72.0,    936.5,     413.80,    50.311160,  
+                        37.6985,  311.8,    60.81,     73.8393
+                    ),
+                },
+                TestCase {
+                    c: 6.2396,
+                    a_mat: Mat4::identity(),
+                    b_mat: Mat4::identity(),
+                },
+                TestCase {
+                    c: 6.2396,
+                    a_mat: Mat4::zero(),
+                    b_mat: Mat4::zero(),
+                },
+                TestCase {
+                    c:  14.5093,
+                    a_mat: super::mat4(
+                        68.32, 0.0,    0.0,   0.0,
+                        0.0,   37.397, 0.0,   0.0,
+                        0.0,   0.0,    9.483, 0.0,
+                        0.0,   0.0,    0.0,   887.710
+                    ),
+                    b_mat: super::mat4(
+                        57.72, 0.0,       0.0,       0.0, 
+                        0.0,   9.5433127, 0.0,       0.0, 
+                        0.0,   0.0,       86.731265, 0.0,
+                        0.0,   0.0,       0.0,       269.1134546
+                    )
+                },
+            ]
+        }
+    }
+
+    #[test]
+    fn test_mat_times_identity_equals_mat() {
+        for test in test_cases().iter() {
+            let a_mat_times_identity = test.a_mat * Mat4::identity();
+            let b_mat_times_identity = test.b_mat * Mat4::identity();
+
+            assert_eq!(a_mat_times_identity, test.a_mat);
+            assert_eq!(b_mat_times_identity, test.b_mat);
+        }
+    }
+
+    #[test]
+    fn test_mat_times_zero_equals_zero() {
+        for test in test_cases().iter() {
+            let a_mat_times_zero = test.a_mat * Mat4::zero();
+            let b_mat_times_zero = test.b_mat * Mat4::zero();
+
+            assert_eq!(a_mat_times_zero, Mat4::zero());
+            assert_eq!(b_mat_times_zero, Mat4::zero());
+        }
+    }
+
+    #[test]
+    fn test_zero_times_mat_equals_zero() {
+        for test in test_cases().iter() {
+            let zero_times_a_mat = Mat4::zero() * test.a_mat;
+            let zero_times_b_mat = Mat4::zero() * test.b_mat;
+
+            assert_eq!(zero_times_a_mat, Mat4::zero());
+            assert_eq!(zero_times_b_mat, Mat4::zero());
+        }
+    }
+
+    #[test]
+    fn test_mat_times_identity_equals_identity_times_mat() {
+        for test in test_cases().iter() {
+            let a_mat_times_identity = test.a_mat * Mat4::identity();
+            let identity_times_a_mat = Mat4::identity() * test.a_mat;
+            let b_mat_times_identity = test.b_mat * Mat4::identity();
+            let identity_times_b_mat = Mat4::identity() * test.b_mat;
+
+            assert_eq!(a_mat_times_identity, identity_times_a_mat);
+            assert_eq!(b_mat_times_identity, identity_times_b_mat);
+        }
     }
 
-    pub fn as_ptr(&self) -> *const f32 {
-        self.m.as_ptr()
+    #[test]
+    fn test_mat_times_mat_inverse_equals_identity() {
+        for test in test_cases().iter() {
+            let identity = Mat4::identity();
+            if test.a_mat.is_invertible() {
+                let a_mat_inverse = test.a_mat.inverse();
+                assert_eq!(a_mat_inverse * test.a_mat, identity);
+            }
+            if test.b_mat.is_invertible() {
+                let b_mat_inverse = test.b_mat.inverse();
+                assert_eq!(b_mat_inverse * test.b_mat, identity);
+            }
+        }
     }
-}
 
-impl fmt::Display for Mat4 {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        writeln!(f, 
-            "\n[{:.2}][{:.2}][{:.2}][{:.2}]\n[{:.2}][{:.2}][{:.2}][{:.2}]\n[{:.2}][{:.2}][{:.2}][{:.2}]\n[{:.2}][{:.2}][{:.2}][{:.2}]", 
-            self.m[0], self.m[4], self.m[8],  self.m[12],
-            self.m[1], self.m[5], self.m[9],  self.m[13],
-            self.m[2], self.m[6], self.m[10], self.m[14],
-            self.m[3], self.m[7], self.m[11], self.m[15]
-        )
+    #[test]
+    fn test_mat_inverse_times_mat_equals_identity() {
+        for test in test_cases().iter() {
+            let identity = Mat4::identity();
+            if test.a_mat.is_invertible() {
+                let a_mat_inverse = test.a_mat.inverse();
+                assert_eq!(test.a_mat * a_mat_inverse, identity);
+            }
+            if test.b_mat.is_invertible() {
+                let b_mat_inverse = test.b_mat.inverse();
+                assert_eq!(test.b_mat * b_mat_inverse, identity);
+            }
+        }
     }
-}
 
-pub fn mat4(
-        m11: f32, m12: f32, m13: f32, m14: f32, 
-        m21: f32, m22: f32, m23: f32, m24: f32,
-        m31: f32, m32: f32, m33: f32, m34: f32,
-        m41: f32, m42: f32, m43: f32, m44: f32) -> Mat4 {
+    #[test]
+    fn test_mat_transpose_transpose_equals_mat() {
+        for test in test_cases().iter() {
+            let a_mat_tr_tr = test.a_mat.transpose().transpose();
+            let b_mat_tr_tr = test.b_mat.transpose().transpose();
+            
+            assert_eq!(a_mat_tr_tr, test.a_mat);
+            assert_eq!(b_mat_tr_tr, test.b_mat);
+        }
+    }
 
-    Mat4::new(
-        m11, m12, m13, m14, 
-        m21, m22, m23, m24, 
-        m31, m32, m33, m34, 
-        m41, m42, m43, m44
-    )
-}
+    #[test]
+    fn test_identity_transpose_equals_identity() {
+        let identity = Mat4::identity();
+        let identity_tr = identity.transpose();
+            
+        assert_eq!(identity, identity_tr);
+    }
 
-impl ops::Mul<Vec4> for Mat4 {
-    type Output = Vec4;
+    #[test]
+    fn test_identity_mat4_translates_vector_along_vector() {
+        let v = super::vec3((2.0, 2.0, 2.0));
+        let trans_mat = Mat4::identity().translate(&v);
+        let zero_vec4 = super::vec4((0.0, 0.0, 0.0, 1.0));
+        let zero_vec3 = super::vec3((0.0, 0.0, 0.0));
 
-    fn mul(self, other: Vec4) -> Self::Output {
-        let x = self.m[0] * other.v[0] + self.m[4] * other.v[1] + self.m[8]  * other.v[2] + self.m[12] * other.v[3];
-        let y = self.m[1] * other.v[0] + self.m[5] * other.v[1] + self.m[9]  * other.v[2] + self.m[13] * other.v[3];
-        let z = self.m[2] * other.v[0] + self.m[6] * other.v[1] + self.m[10] * other.v[2] + self.m[14] * other.v[3];
-        let w = self.m[3] * other.v[0] + self.m[7] * other.v[1] + self.m[11] * other.v[2] + self.m[15] * other.v[3];
-        
-        Vec4::new(x, y, z, w)
+        let result = trans_mat * zero_vec4;
+        assert_eq!(result, super::vec4((zero_vec3 + v, 1.0)));
     }
-}
-
-impl<'a> ops::Mul<&'a Mat4> for Mat4 {
-    type Output = Mat4;
 
-    fn mul(self, other: &'a Mat4) -> Mat4 {
-        let mut mm = Mat4::zero();
+    fn view_looking_down_and_left() -> Mat4 {
+        let cam_pos = super::vec3((3.0, 4.0, 5.0));
+        let rotation = Mat4::identity().rotate_x_deg(-30.0).rotate_y_deg(40.0);
 
-        mm.m[0]  = self.m[0]*other.m[0]  + self.m[4]*other.m[1]  + self.m[8]*other.m[2]   + self.m[12]*other.m[3];
-        mm.m[1]  = self.m[1]*other.m[0]  + self.m[5]*other.m[1]  + self.m[9]*other.m[2]   + self.m[13]*other.m[3];
-        mm.m[2]  = self.m[2]*other.m[0]  + self.m[6]*other.m[1]  + self.m[10]*other.m[2]  + self.m[14]*other.m[3];
-        mm.m[3]  = self.m[3]*other.m[0]  + self.m[7]*other.m[1]  + self.m[11]*other.m[2]  + self.m[15]*other.m[3];
-        mm.m[4]  = self.m[0]*other.m[4]  + self.m[4]*other.m[5]  + self.m[8]*other.m[6]   + self.m[12]*other.m[7];
-        mm.m[5]  = self.m[1]*other.m[4]  + self.m[5]*other.m[5]  + self.m[9]*other.m[6]   + self.m[13]*other.m[7];
-        mm.m[6]  = self.m[2]*other.m[4]  + self.m[6]*other.m[5]  + self.m[10]*other.m[6]  + self.m[14]*other.m[7];
-        mm.m[7]  = self.m[3]*other.m[4]  + self.m[7]*other.m[5]  + self.m[11]*other.m[6]  + self.m[15]*other.m[7];
-        mm.m[8]  = self.m[0]*other.m[8]  + self.m[4]*other.m[9]  + self.m[8]*other.m[10]  + self.m[12]*other.m[11];
-        mm.m[9]  = self.m[1]*other.m[8]  + self.m[5]*other.m[9]  + self.m[9]*other.m[10]  + self.m[13]*other.m[11];
-        mm.m[10] = self.m[2]*other.m[8]  + self.m[6]*other.m[9]  + self.m[10]*other.m[10] + self.m[14]*other.m[11];
-        mm.m[11] = self.m[3]*other.m[8]  + self.m[7]*other.m[9]  + self.m[11]*other.m[10] + self.m[15]*other.m[11];
-        mm.m[12] = self.m[0]*other.m[12] + self.m[4]*other.m[13] + self.m[8]*other.m[14]  + self.m[12]*other.m[15];
-        mm.m[13] = self.m[1]*other.m[12] + self.m[5]*other.m[13] + self.m[9]*other.m[14]  + self.m[13]*other.m[15];
-        mm.m[14] = self.m[2]*other.m[12] + self.m[6]*other.m[13] + self.m[10]*other.m[14] + self.m[14]*other.m[15];
-        mm.m[15] = self.m[3]*other.m[12] + self.m[7]*other.m[13] + self.m[11]*other.m[14] + self.m[15]*other.m[15];
+        rotation.inverse() * Mat4::identity().translate(&cam_pos).inverse()
+    }
 
-        mm
+    #[test]
+    fn test_spherical_billboard_faces_the_camera_head_on() {
+        let view = view_looking_down_and_left();
+        let position = super::vec3((1.0, 2.0, -3.0));
+        let model = Mat4::billboard_spherical(&view, &position, &super::vec3((2.0, 3.0, 1.0)));
+        let model_view = view * model;
+
+        // The quad's axes come out lined up with the screen, only scaled.
+        assert_eq!(model_view * super::vec4((1.0, 0.0, 0.0, 0.0)), super::vec4((2.0, 0.0, 0.0, 0.0)));
+        assert_eq!(model_view * super::vec4((0.0, 1.0, 0.0, 0.0)), super::vec4((0.0, 3.0, 0.0, 0.0)));
+        assert_eq!(model_view * super::vec4((0.0, 0.0, 1.0, 0.0)), super::vec4((0.0, 0.0, 1.0, 0.0)));
+        assert_eq!(model * super::vec4((0.0, 0.0, 0.0, 1.0)), super::vec4((position, 1.0)));
     }
-}
 
-impl<'a, 'b> ops::Mul<&'a Mat4> for &'b Mat4 {
-    type Output = Mat4;
+    #[test]
+    fn test_cylindrical_billboard_stays_upright_and_turns_to_the_camera() {
+        let view = view_looking_down_and_left();
+        let position = super::vec3((1.0, 2.0, -3.0));
+        let model = Mat4::billboard_cylindrical(&view, &position, &super::vec3((2.0, 3.0, 1.0)));
+
+        // Upright in the world, with its x-axis along the screen's x-axis.
+        assert_eq!(model * super::vec4((0.0, 1.0, 0.0, 0.0)), super::vec4((0.0, 3.0, 0.0, 0.0)));
+        let x_eye = view * model * super::vec4((1.0, 0.0, 0.0, 0.0));
+        assert!(f32::abs(x_eye.v[0] - 2.0) < 1e-5);
+        assert!(f32::abs(x_eye.v[1]) < 1e-5);
+        assert!(f32::abs(x_eye.v[2]) < 1e-5);
+        assert_eq!(model * super::vec4((0.0, 0.0, 0.0, 1.0)), super::vec4((position, 1.0)));
+    }
 
-    fn mul(self, other: &'a Mat4) -> Mat4 {
-        let mut mm = Mat4::zero();
+    fn assert_same_rotation(q: &Versor, r: &Versor) {
+        // q and -q are the same rotation.
+        assert!(f32::abs(f32::abs(q.dot(r)) - 1.0) < 1e-5, "{} != {}", q, r);
+    }
 
-        mm.m[0]  = self.m[0]*other.m[0]  + self.m[4]*other.m[1]  + self.m[8]*other.m[2]   + self.m[12]*other.m[3];
-        mm.m[1]  = self.m[1]*other.m[0]  + self.m[5]*other.m[1]  + self.m[9]*other.m[2]   + self.m[13]*other.m[3];
-        mm.m[2]  = self.m[2]*other.m[0]  + self.m[6]*other.m[1]  + self.m[10]*other.m[2]  + self.m[14]*other.m[3];
-        mm.m[3]  = self.m[3]*other.m[0]  + self.m[7]*other.m[1]  + self.m[11]*other.m[2]  + self.m[15]*other.m[3];
-        mm.m[4]  = self.m[0]*other.m[4]  + self.m[4]*other.m[5]  + self.m[8]*other.m[6]   + self.m[12]*other.m[7];
-        mm.m[5]  = self.m[1]*other.m[4]  + self.m[5]*other.m[5]  + self.m[9]*other.m[6]   + self.m[13]*other.m[7];
-        mm.m[6]  = self.m[2]*other.m[4]  + self.m[6]*other.m[5]  + self.m[10]*other.m[6]  + self.m[14]*other.m[7];
-        mm.m[7]  = self.m[3]*other.m[4]  + self.m[7]*other.m[5]  + self.m[11]*other.m[6]  + self.m[15]*other.m[7];
-        mm.m[8]  = self.m[0]*other.m[8]  + self.m[4]*other.m[9]  + self.m[8]*other.m[10]  + self.m[12]*other.m[11];
-        mm.m[9]  = self.m[1]*other.m[8]  + self.m[5]*other.m[9]  + self.m[9]*other.m[10]  + self.m[13]*other.m[11];
-        mm.m[10] = self.m[2]*other.m[8]  + self.m[6]*other.m[9]  + self.m[10]*other.m[10] + self.m[14]*other.m[11];
-        mm.m[11] = self.m[3]*other.m[8]  + self.m[7]*other.m[9]  + self.m[11]*other.m[10] + self.m[15]*other.m[11];
-        mm.m[12] = self.m[0]*other.m[12] + self.m[4]*other.m[13] + self.m[8]*other.m[14]  + self.m[12]*other.m[15];
-        mm.m[13] = self.m[1]*other.m[12] + self.m[5]*other.m[13] + self.m[9]*other.m[14]  + self.m[13]*other.m[15];
-        mm.m[14] = self.m[2]*other.m[12] + self.m[6]*other.m[13] + self.m[10]*other.m[14] + self.m[14]*other.m[15];
-        mm.m[15] = self.m[3]*other.m[12] + self.m[7]*other.m[13] + self.m[11]*other.m[14] + self.m[15]*other.m[15];
+    #[test]
+    fn test_from_trs_matches_chained_transforms() {
+        let translation = super::vec3((1.0, -2.0, 3.5));
+        let rotation = Versor::from_axis_deg(50.0, 0.0, 0.6, 0.8);
+        let scale = super::vec3((2.0, 0.5, 3.0));
+        let chained = (rotation.to_mat4() * Mat4::identity().scale(&scale)).translate(&translation);
 
-        mm
+        assert_eq!(Mat4::from_trs(translation, rotation, scale), chained);
     }
-}
 
+    #[test]
+    fn test_decompose_returns_the_trs_components() {
+        let translation = super::vec3((-4.0, 0.25, 9.0));
+        let rotation = Versor::from_axis_deg(-130.0, 0.48, 0.6, 0.64);
+        let scale = super::vec3((0.5, 1.5, 4.0));
+        let (t, r, s) = Mat4::from_trs(translation, rotation, scale).decompose();
 
-#[derive(Copy, Clone, Debug)]
-struct Versor {
-    q: [f32; 4],
-}
+        assert_eq!(t, translation);
+        assert_same_rotation(&r, &rotation);
+        assert!((s - scale).norm() < 1e-5);
+    }
 
-impl Versor {
-    pub fn normalize(&self) -> Versor {
-        // normalize(q) = q / magnitude (q)
-        // magnitude (q) = sqrt (w*w + x*x...)
-        // only compute sqrt if interior sum != 1.0
-        let sum = self.q[0] * self.q[0] + self.q[1] * self.q[1] + self.q[2] * self.q[2] + self.q[3] * self.q[3];
-        // NB: Floats have min 6 digits of precision.
-        let threshold = 0.0001;
-        if f32::abs(1.0 - sum) < threshold {
-            return *self;
+    #[test]
+    fn test_decompose_from_trs_round_trip() {
+        // Rotations about each axis by more than a half turn exercise every branch
+        // of turning a rotation matrix back into a quaternion.
+        let rotations = [
+            Versor::from_axis_deg(0.0, 1.0, 0.0, 0.0),
+            Versor::from_axis_deg(170.0, 1.0, 0.0, 0.0),
+            Versor::from_axis_deg(200.0, 0.0, 1.0, 0.0),
+            Versor::from_axis_deg(-175.0, 0.0, 0.0, 1.0),
+            Versor::from_axis_deg(75.0, 0.0, 0.8, -0.6),
+        ];
+        for rotation in rotations.iter() {
+            let m = Mat4::from_trs(super::vec3((3.0, 2.0, 1.0)), *rotation, super::vec3((1.0, 2.0, 0.25)));
+            let (t, r, s) = m.decompose();
+
+            assert_same_rotation(&r, rotation);
+            assert_eq!(Mat4::from_trs(t, r, s), m);
         }
-
-        let norm = f32::sqrt(sum);
-        self / norm
     }
 
-    pub fn dot(&self, r: &Versor) -> f32 {
-        self.q[0] * r.q[0] + self.q[1] * r.q[1] + self.q[2] * r.q[2] + self.q[3] * r.q[3]
+    #[test]
+    fn test_decompose_a_mirrored_transform() {
+        let m = Mat4::identity().scale(&super::vec3((1.0, 1.0, -2.0))).rotate_y_deg(30.0);
+        let (t, r, s) = m.decompose();
+
+        assert_eq!(t, Vec3::zero());
+        assert!(s.v[0] < 0.0);
+        assert_eq!(Mat4::from_trs(t, r, s), m);
     }
 
-    pub fn from_axis_rad(radians: f32, x: f32, y: f32, z: f32) -> Versor {
-        Versor {
-            q: [
-                f32::cos(radians / 2.0),
-                f32::sin(radians / 2.0) * x,
-                f32::sin(radians / 2.0) * y,
-                f32::sin(radians / 2.0) * z,
-            ]
-        }
+    #[test]
+    fn test_the_constructors_match_transforming_the_identity() {
+        let v = super::vec3((1.0, -2.0, 3.0));
+
+        assert_eq!(Mat4::from_translation(&v), Mat4::identity().translate(&v));
+        assert_eq!(Mat4::from_rotation_x_deg(30.0), Mat4::identity().rotate_x_deg(30.0));
+        assert_eq!(Mat4::from_rotation_y_deg(30.0), Mat4::identity().rotate_y_deg(30.0));
+        assert_eq!(Mat4::from_rotation_z_deg(30.0), Mat4::identity().rotate_z_deg(30.0));
+        assert_eq!(Mat4::from_scale(&v), Mat4::identity().scale(&v));
     }
 
-    pub fn from_axis_deg(degrees: f32, x: f32, y: f32, z: f32) -> Versor {
-        Self::from_axis_rad(ONE_DEG_IN_RAD * degrees, x, y, z)
+    #[test]
+    fn test_then_translate_moves_the_scaled_origin_and_pre_translate_scales_the_move() {
+        let scale = Mat4::from_scale(&super::vec3((2.0, 2.0, 2.0)));
+        let offset = super::vec3((1.0, 0.0, 0.0));
+        let origin = super::vec4((0.0, 0.0, 0.0, 1.0));
+
+        assert_eq!(scale.then_translate(&offset) * origin, super::vec4((1.0, 0.0, 0.0, 1.0)));
+        assert_eq!(scale.pre_translate(&offset) * origin, super::vec4((2.0, 0.0, 0.0, 1.0)));
+        assert_eq!(scale.translate(&offset), scale.then_translate(&offset));
     }
 
-    pub fn to_mat4(&self) -> Mat4 {
-        let w = self.q[0];
-        let x = self.q[1];
-        let y = self.q[2];
-        let z = self.q[3];
-    
-        Mat4::new(
-            1.0 - 2.0 * y * y - 2.0 * z * z, 2.0 * x * y + 2.0 * w * z,       2.0 * x * z - 2.0 * w * y,       0.0, 
-            2.0 * x * y - 2.0 * w * z,       1.0 - 2.0 * x * x - 2.0 * z * z, 2.0 * y * z + 2.0 * w * x,       0.0, 
-            2.0 * x * z + 2.0 * w * y,       2.0 * y * z - 2.0 * w * x,       1.0 - 2.0 * x * x - 2.0 * y * y, 0.0, 
-            0.0,                             0.0,                             0.0,                             1.0
-        )
+    ///
+    /// The depth a projection gives a point straight ahead, `distance` units away.
+    ///
+    fn depth_at(proj: &Mat4, distance: f32) -> f32 {
+        let clip = *proj * super::vec4((0.0, 0.0, -distance, 1.0));
+        clip.v[2] / clip.v[3]
     }
 
-    pub fn slerp(q: &mut Versor, r: &Versor, t: f32) -> Versor {
-        // angle between q0-q1
-        let mut cos_half_theta = q.dot(r);
-        // as found here
-        // http://stackoverflow.com/questions/2886606/flipping-issue-when-interpolating-rotations-using-quaternions
-        // if dot product is negative then one quaternion should be negated, to make
-        // it take the short way around, rather than the long way
-        // yeah! and furthermore Susan, I had to recalculate the d.p. after this
-        if cos_half_theta < 0.0 {
-            q.q[0] *= -1.0;
-            q.q[1] *= -1.0;
-            q.q[2] *= -1.0;
-            q.q[3] *= -1.0;
+    #[test]
+    fn test_an_infinite_projection_is_the_limit_of_a_finite_one() {
+        let infinite = Mat4::perspective_infinite(67.0, 1.5, 0.1);
+        let distant = Mat4::perspective(67.0, 1.5, 0.1, 1.0e7);
 
-            cos_half_theta = q.dot(r);
-        }
-        // if qa=qb or qa=-qb then theta = 0 and we can return qa
-        if f32::abs(cos_half_theta) >= 1.0 {
-            return *q;
+        for i in 0..16 {
+            assert!(f32::abs(infinite.m[i] - distant.m[i]) < 1e-4, "{} != {}", infinite, distant);
         }
+        assert!(f32::abs(depth_at(&infinite, 0.1) + 1.0) < 1e-5);
+        assert!(depth_at(&infinite, 1.0e6) < 1.0);
+    }
 
-        // Calculate temporary values
-        let sin_half_theta = f32::sqrt(1.0 - cos_half_theta * cos_half_theta);
-        // if theta = 180 degrees then result is not fully defined
-        // we could rotate around any axis normal to qa or qb
-        let mut result = Versor { q: [1.0, 0.0, 0.0, 0.0] };
-        if f32::abs(sin_half_theta) < 0.001 {
-            result.q[0] = (1.0 - t) * q.q[0] + t * r.q[0];
-            result.q[1] = (1.0 - t) * q.q[1] + t * r.q[1];
-            result.q[2] = (1.0 - t) * q.q[2] + t * r.q[2];
-            result.q[3] = (1.0 - t) * q.q[3] + t * r.q[3];
+    #[test]
+    fn test_reversed_z_puts_the_near_plane_at_one_and_infinity_at_zero() {
+        let proj = Mat4::perspective_reversed_z(67.0, 1.5, 0.1);
 
-            return result;
-        }
-        let half_theta = f32::acos(cos_half_theta);
-        let a = f32::sin((1.0 - t) * half_theta) / sin_half_theta;
-        let b = f32::sin(t * half_theta) / sin_half_theta;
-        
-        result.q[0] = q.q[0] * a + r.q[0] * b;
-        result.q[1] = q.q[1] * a + r.q[1] * b;
-        result.q[2] = q.q[2] * a + r.q[2] * b;
-        result.q[3] = q.q[3] * a + r.q[3] * b;
+        assert!(f32::abs(depth_at(&proj, 0.1) - 1.0) < 1e-6);
+        assert!(depth_at(&proj, 10.0) > depth_at(&proj, 100.0));
+        assert!(depth_at(&proj, 1.0e6) > 0.0);
+        assert!(depth_at(&proj, 1.0e6) < 1e-6);
+    }
 
-        return result;
+    #[test]
+    fn test_unproject_undoes_the_projection_and_viewport() {
+        let view = Mat4::from_rotation_y_deg(30.0).then_translate(&super::vec3((1.0, -2.0, -6.0)));
+        let view_proj = Mat4::perspective(67.0, 1.5, 0.1, 100.0) * &view;
+        let viewport = [10, 20, 600, 400];
+        let point = super::vec3((0.5, 1.0, -3.0));
+
+        let clip = view_proj * super::vec4((point, 1.0));
+        let window = super::vec3((
+            10.0 + 600.0 * (clip.v[0] / clip.v[3] + 1.0) / 2.0,
+            20.0 + 400.0 * (clip.v[1] / clip.v[3] + 1.0) / 2.0,
+            (clip.v[2] / clip.v[3] + 1.0) / 2.0
+        ));
+        let unprojected = view_proj.unproject(&window, &viewport);
+
+        assert!((unprojected - point).norm() < 1e-3, "{} != {}", unprojected, point);
     }
-}
 
-impl fmt::Display for Versor {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        writeln!(f, "[{:.2}, {:.2}, {:.2}, {:.2}]", self.q[0], self.q[1], self.q[2], self.q[3])
+    #[test]
+    fn test_unproject_with_a_zero_to_one_depth_range_undoes_reversed_z() {
+        let view = Mat4::from_translation(&super::vec3((0.0, -1.0, 0.0)));
+        let view_proj = Mat4::perspective_reversed_z(67.0, 1.0, 0.1) * &view;
+        let viewport = [0, 0, 400, 400];
+        let point = super::vec3((2.0, 3.0, -40.0));
+
+        // with glClipControl set to GL_ZERO_TO_ONE, clip space depth is the window depth
+        let clip = view_proj * super::vec4((point, 1.0));
+        let window = super::vec3((
+            400.0 * (clip.v[0] / clip.v[3] + 1.0) / 2.0,
+            400.0 * (clip.v[1] / clip.v[3] + 1.0) / 2.0,
+            clip.v[2] / clip.v[3]
+        ));
+        let unprojected = view_proj.unproject_with_clip_near(&window, &viewport, 0.0);
+
+        assert!((unprojected - point).norm() < 1e-2, "{} != {}", unprojected, point);
     }
-}
 
-impl ops::Div<f32> for Versor {
-    type Output = Versor;
+    #[test]
+    fn test_the_frustum_corners_lie_on_the_near_and_far_planes() {
+        let cam_pos = super::vec3((3.0, 2.0, 5.0));
+        let view = Mat4::from_translation(&-cam_pos);
+        let proj = Mat4::perspective(90.0, 2.0, 1.0, 1000.0);
 
-    fn div(self, other: f32) -> Versor {
-        Versor {
-            q: [
-                self.q[0] / other, 
-                self.q[1] / other, 
-                self.q[2] / other, 
-                self.q[3] / other,
-            ]
+        let corners = super::frustum_corners_world(&view, &proj);
+
+        // a 90 degree field of view is as wide as it is deep, and twice as wide again across
+        for i in 0..8 {
+            let distance = if i & 4 == 0 { 1.0 } else { 1000.0 };
+            let x = if i & 1 == 0 { -2.0 } else { 2.0 };
+            let y = if i & 2 == 0 { -1.0 } else { 1.0 };
+            let expected = cam_pos + super::vec3((x * distance, y * distance, -distance));
+            let corner = corners[i];
+
+            assert!((corner - expected).norm() < 1e-4 * distance, "{} != {}", corner, expected);
         }
     }
 }
 
-impl<'a> ops::Div<f32> for &'a Versor {
-    type Output = Versor;
+mod dmat4_tests {
+    use super::{DMat4, DVec3, Mat4, Vec3};
 
-    fn div(self, other: f32) -> Versor {
-        Versor {
-            q: [
-                self.q[0] / other, 
-                self.q[1] / other, 
-                self.q[2] / other, 
-                self.q[3] / other,
-            ]
+    // Far enough from the origin that an f32 only resolves steps of 1/64.
+    const FAR: f64 = 500_000.0;
+
+    #[test]
+    fn test_small_steps_add_up_far_from_the_origin() {
+        let mut single = super::vec3((FAR as f32, 0.0, 0.0));
+        let mut double = super::dvec3((FAR, 0.0, 0.0));
+        for _ in 0..1000 {
+            single += super::vec3((0.001, 0.0, 0.0));
+            double += super::dvec3((0.001, 0.0, 0.0));
         }
+
+        assert_eq!(single.v[0], FAR as f32);
+        assert!(f64::abs(double.v[0] - (FAR + 1.0)) < 1e-6);
+    }
+
+    #[test]
+    fn test_inverse_keeps_its_precision_far_from_the_origin() {
+        let rotation = DMat4::from(Mat4::identity().rotate_y_deg(35.0).rotate_x_deg(-20.0));
+        let model = DMat4::identity().translate(&super::dvec3((FAR, 20.0, -FAR))) * rotation;
+        let near_by = super::dvec3((FAR + 0.25, 20.5, -FAR));
+
+        let local = model.inverse().transform_point(&near_by);
+        assert!(f64::abs(local.norm() - f64::sqrt(0.25 * 0.25 + 0.5 * 0.5)) < 1e-6);
+        assert_eq!(model.inverse() * model, DMat4::identity());
+    }
+
+    #[test]
+    fn test_conversions_to_and_from_single_precision() {
+        let v = super::vec3((1.5, -2.0, 0.25));
+        let m = Mat4::identity().translate(&v).rotate_z_deg(45.0);
+
+        assert_eq!(Vec3::from(DVec3::from(v)), v);
+        assert_eq!(super::vec3(super::dvec3((FAR, 0.5, -1.0))), super::vec3((FAR as f32, 0.5, -1.0)));
+        assert_eq!(Mat4::from(DMat4::from(m)), m);
     }
 }
 
-impl ops::Mul<f32> for Versor {
-    type Output = Versor;
+mod versor_tests {
+    use super::{Vec3, Versor};
 
-    fn mul(self, other: f32) -> Versor {
-        Versor {
-            q: [
-                self.q[0] * other,
-                self.q[1] * other,
-                self.q[2] * other,
-                self.q[3] * other,
-            ]
+    fn assert_close(a: Vec3, b: Vec3) {
+        for i in 0..3 {
+            assert!(f32::abs(a.v[i] - b.v[i]) < 1e-5, "{} != {}", a, b);
         }
     }
-}
 
-impl<'a> ops::Mul<&'a Versor> for Versor {
-    type Output = Versor;
+    #[test]
+    fn test_rotate_vec3_quarter_turns() {
+        let about_z = Versor::from_axis_deg(90.0, 0.0, 0.0, 1.0);
+        let about_y = Versor::from_axis_deg(90.0, 0.0, 1.0, 0.0);
 
-    fn mul(self, other: &'a Versor) -> Self::Output {
-        let result = Versor {
-            q: [
-                other.q[0] * self.q[0] - other.q[1] * self.q[1] - other.q[2] * self.q[2] - other.q[3] * self.q[3],
-                other.q[0] * self.q[1] + other.q[1] * self.q[0] - other.q[2] * self.q[3] + other.q[3] * self.q[2],
-                other.q[0] * self.q[2] + other.q[1] * self.q[3] + other.q[2] * self.q[0] - other.q[3] * self.q[1],
-                other.q[0] * self.q[3] - other.q[1] * self.q[2] + other.q[2] * self.q[1] + other.q[3] * self.q[0],
-            ]
-        };
-        // Renormalize in case of mangling.
-        result.normalize()
+        assert_close(about_z.rotate_vec3(&super::vec3((1.0, 0.0, 0.0))), super::vec3((0.0, 1.0, 0.0)));
+        assert_close(about_y.rotate_vec3(&super::vec3((0.0, 0.0, -1.0))), super::vec3((-1.0, 0.0, 0.0)));
     }
-}
 
-impl<'a> ops::Add<&'a Versor> for Versor {
-    type Output = Versor;
+    #[test]
+    fn test_rotate_vec3_matches_the_rotation_matrix() {
+        let q = Versor::from_axis_deg(37.0, 0.0, 1.0, 0.0) * &Versor::from_axis_deg(-52.0, 0.6, 0.0, 0.8);
+        let v = super::vec3((0.3, -1.2, 2.5));
+        let expected = super::vec3(q.to_mat4() * super::vec4((v, 0.0)));
 
-    fn add(self, other: &'a Versor) -> Self::Output {
-        let result = Versor {
-            q: [
-                other.q[0] + self.q[0],
-                other.q[1] + self.q[1],
-                other.q[2] + self.q[2],
-                other.q[3] + self.q[3],
-            ]
-        };
-        // Renormalize in case of mangling.
-        result.normalize()
+        assert_close(q.rotate_vec3(&v), expected);
     }
-}
 
+    #[test]
+    fn test_rotate_vec3_keeps_the_length() {
+        let q = Versor::from_axis_deg(123.0, 0.0, 0.6, 0.8);
+        let v = super::vec3((3.0, 4.0, 12.0));
+
+        assert!(f32::abs(q.rotate_vec3(&v).norm() - 13.0) < 1e-4);
+    }
+
+    #[test]
+    fn test_multiplying_a_vector_rotates_it() {
+        let q = Versor::from_axis_deg(90.0, 1.0, 0.0, 0.0);
+        let v = super::vec3((0.0, 1.0, 0.0));
+
+        assert_close(q * v, super::vec3((0.0, 0.0, 1.0)));
+        assert_close(q * &v, q.rotate_vec3(&v));
+    }
+
+    #[test]
+    fn test_from_mat4_undoes_to_mat4() {
+        let q = Versor::from_axis_deg(-64.0, 0.36, 0.48, 0.8);
+        let r = Versor::from_mat4(&q.to_mat4());
+
+        assert!(f32::abs(q.dot(&r) - 1.0) < 1e-5);
+        assert_eq!(r.to_mat4(), q.to_mat4());
+    }
 
+    #[test]
+    fn test_quaternion_products_agree_by_value_and_by_reference() {
+        let q_yaw = Versor::from_axis_deg(30.0, 0.0, 1.0, 0.0);
+        let q_pitch = Versor::from_axis_deg(-15.0, 1.0, 0.0, 0.0);
+        let expected = q_yaw * &q_pitch;
+
+        for result in [q_yaw * q_pitch, &q_yaw * q_pitch, &q_yaw * &q_pitch].iter() {
+            assert_eq!(result.q, expected.q);
+        }
+    }
+}
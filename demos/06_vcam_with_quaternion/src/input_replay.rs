@@ -0,0 +1,343 @@
+use glfw::{Action, Key, Modifiers, MouseButton, WindowEvent};
+
+use std::cmp::Ordering;
+use std::fs::File;
+use std::io::{Read, Write};
+
+
+///
+/// The command line options that record the input of a run to a file, and play
+/// a recording back in place of the keyboard and mouse.
+///
+pub const RECORD_OPTION: &str = "--record";
+pub const REPLAY_OPTION: &str = "--replay";
+///
+/// The time step of every frame while recording or replaying, so both runs see
+/// exactly the same frames whatever the machine.
+///
+pub const REPLAY_STEP_SECONDS: f64 = 1.0 / 60.0;
+
+const RECORDING_HEADER: &str = "# input recording: frame seconds event";
+
+const KEYS: [Key; 121] = [
+    Key::Space, Key::Apostrophe, Key::Comma, Key::Minus, Key::Period, Key::Slash,
+    Key::Num0, Key::Num1, Key::Num2, Key::Num3, Key::Num4, Key::Num5, Key::Num6, Key::Num7, Key::Num8, Key::Num9,
+    Key::Semicolon, Key::Equal,
+    Key::A, Key::B, Key::C, Key::D, Key::E, Key::F, Key::G, Key::H, Key::I, Key::J, Key::K, Key::L, Key::M,
+    Key::N, Key::O, Key::P, Key::Q, Key::R, Key::S, Key::T, Key::U, Key::V, Key::W, Key::X, Key::Y, Key::Z,
+    Key::LeftBracket, Key::Backslash, Key::RightBracket, Key::GraveAccent, Key::World1, Key::World2,
+    Key::Escape, Key::Enter, Key::Tab, Key::Backspace, Key::Insert, Key::Delete,
+    Key::Right, Key::Left, Key::Down, Key::Up, Key::PageUp, Key::PageDown, Key::Home, Key::End,
+    Key::CapsLock, Key::ScrollLock, Key::NumLock, Key::PrintScreen, Key::Pause,
+    Key::F1, Key::F2, Key::F3, Key::F4, Key::F5, Key::F6, Key::F7, Key::F8, Key::F9, Key::F10, Key::F11, Key::F12,
+    Key::F13, Key::F14, Key::F15, Key::F16, Key::F17, Key::F18, Key::F19, Key::F20, Key::F21, Key::F22, Key::F23,
+    Key::F24, Key::F25,
+    Key::Kp0, Key::Kp1, Key::Kp2, Key::Kp3, Key::Kp4, Key::Kp5, Key::Kp6, Key::Kp7, Key::Kp8, Key::Kp9,
+    Key::KpDecimal, Key::KpDivide, Key::KpMultiply, Key::KpSubtract, Key::KpAdd, Key::KpEnter, Key::KpEqual,
+    Key::LeftShift, Key::LeftControl, Key::LeftAlt, Key::LeftSuper,
+    Key::RightShift, Key::RightControl, Key::RightAlt, Key::RightSuper, Key::Menu, Key::Unknown,
+];
+
+const MOUSE_BUTTONS: [MouseButton; 8] = [
+    MouseButton::Button1, MouseButton::Button2, MouseButton::Button3, MouseButton::Button4,
+    MouseButton::Button5, MouseButton::Button6, MouseButton::Button7, MouseButton::Button8,
+];
+
+const ACTIONS: [Action; 3] = [Action::Press, Action::Release, Action::Repeat];
+
+
+///
+/// Where the input of a run comes from.
+///
+#[derive(Clone, Debug, PartialEq)]
+pub enum ReplayMode {
+    ///
+    /// The keyboard and mouse, with frames as long as they really take.
+    ///
+    Live,
+    ///
+    /// The keyboard and mouse, with a fixed time step, saving the input to a file
+    /// when the window closes.
+    ///
+    Record(String),
+    ///
+    /// A recording read from a file, with a fixed time step. The window closes once
+    /// the recording runs out.
+    ///
+    Replay(String),
+}
+
+///
+/// The replay mode given on the command line, from `RECORD_OPTION` or
+/// `REPLAY_OPTION` followed by a file name.
+///
+pub fn replay_mode_from_args<I>(args: I) -> Result<ReplayMode, String>
+    where I: IntoIterator<Item = String> {
+
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        if arg == RECORD_OPTION || arg == REPLAY_OPTION {
+            let file_name = match args.next() {
+                Some(val) => val,
+                None => return Err(format!("ERROR: {} needs a file name after it", arg)),
+            };
+            return if arg == RECORD_OPTION {
+                Ok(ReplayMode::Record(file_name))
+            } else {
+                Ok(ReplayMode::Replay(file_name))
+            };
+        }
+    }
+
+    Ok(ReplayMode::Live)
+}
+
+///
+/// A key or mouse event, and the frame it arrived on.
+///
+#[derive(Clone, Debug, PartialEq)]
+pub struct RecordedEvent {
+    pub frame: u64,
+    pub event: WindowEvent,
+}
+
+///
+/// The key and mouse events of a run, frame by frame. Other window events, such
+/// as the window moving, are left out, since replaying them would not move the
+/// window.
+///
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct InputRecording {
+    events: Vec<RecordedEvent>,
+}
+
+impl InputRecording {
+    pub fn new() -> InputRecording {
+        InputRecording::default()
+    }
+
+    pub fn events(&self) -> &[RecordedEvent] {
+        &self.events
+    }
+
+    ///
+    /// Keep `event` if it comes from the keyboard or the mouse. Frames must be
+    /// recorded in order.
+    ///
+    pub fn record(&mut self, frame: u64, event: &WindowEvent) {
+        match *event {
+            WindowEvent::Key(..) | WindowEvent::MouseButton(..) |
+            WindowEvent::CursorPos(..) | WindowEvent::Scroll(..) => {
+                self.events.push(RecordedEvent { frame: frame, event: event.clone() });
+            }
+            _ => {}
+        }
+    }
+
+    ///
+    /// The events that arrived on `frame`, in the order they arrived.
+    ///
+    pub fn events_on_frame(&self, frame: u64) -> Vec<WindowEvent> {
+        let first = match self.events.binary_search_by(|recorded| {
+            if recorded.frame < frame { Ordering::Less } else { Ordering::Greater }
+        }) {
+            Ok(val) | Err(val) => val,
+        };
+
+        self.events[first..].iter()
+            .take_while(|recorded| recorded.frame == frame)
+            .map(|recorded| recorded.event.clone())
+            .collect()
+    }
+
+    ///
+    /// The frame after the last recorded event, where a replay is over.
+    ///
+    pub fn frame_count(&self) -> u64 {
+        self.events.last().map_or(0, |recorded| recorded.frame + 1)
+    }
+
+    ///
+    /// The recording as text, one event to a line.
+    ///
+    pub fn to_text(&self) -> String {
+        let mut text = String::from(RECORDING_HEADER);
+        text.push('\n');
+        for recorded in self.events.iter() {
+            let seconds = recorded.frame as f64 * REPLAY_STEP_SECONDS;
+            text.push_str(&format!("{} {:.6} {}\n", recorded.frame, seconds, event_to_text(&recorded.event)));
+        }
+
+        text
+    }
+
+    ///
+    /// Read a recording back from the text `to_text` makes. Blank lines and lines
+    /// starting with # are skipped.
+    ///
+    pub fn parse(text: &str) -> Result<InputRecording, String> {
+        let mut recording = InputRecording::new();
+        for (line_number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let error = |message: &str| format!("ERROR: input recording line {}: {}: {}", line_number + 1, message, line);
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            if fields.len() < 3 {
+                return Err(error("expected a frame, a time, and an event"));
+            }
+            let frame = match fields[0].parse::<u64>() {
+                Ok(val) => val,
+                Err(_) => return Err(error("the frame is not a whole number")),
+            };
+            if fields[1].parse::<f64>().is_err() {
+                return Err(error("the time is not a number"));
+            }
+            if frame + 1 < recording.frame_count() {
+                return Err(error("the frames are out of order"));
+            }
+            let event = match event_from_fields(&fields[2..]) {
+                Some(val) => val,
+                None => return Err(error("the event could not be read")),
+            };
+            recording.events.push(RecordedEvent { frame: frame, event: event });
+        }
+
+        Ok(recording)
+    }
+
+    pub fn save(&self, file_name: &str) -> Result<(), String> {
+        let mut file = match File::create(file_name) {
+            Ok(val) => val,
+            Err(e) => return Err(format!("ERROR: could not create input recording {}: {}", file_name, e)),
+        };
+        match file.write_all(self.to_text().as_bytes()) {
+            Ok(_) => Ok(()),
+            Err(e) => Err(format!("ERROR: could not write input recording {}: {}", file_name, e)),
+        }
+    }
+
+    pub fn load(file_name: &str) -> Result<InputRecording, String> {
+        let mut file = match File::open(file_name) {
+            Ok(val) => val,
+            Err(e) => return Err(format!("ERROR: could not open input recording {}: {}", file_name, e)),
+        };
+        let mut text = String::new();
+        if let Err(e) = file.read_to_string(&mut text) {
+            return Err(format!("ERROR: could not read input recording {}: {}", file_name, e));
+        }
+
+        InputRecording::parse(&text)
+    }
+}
+
+fn event_to_text(event: &WindowEvent) -> String {
+    match *event {
+        WindowEvent::Key(key, scancode, action, mods) => {
+            format!("key {:?} {} {:?} {}", key, scancode, action, mods.bits())
+        }
+        WindowEvent::MouseButton(button, action, mods) => {
+            format!("button {:?} {:?} {}", button, action, mods.bits())
+        }
+        WindowEvent::CursorPos(x, y) => format!("cursor {} {}", x, y),
+        WindowEvent::Scroll(x, y) => format!("scroll {} {}", x, y),
+        _ => format!("{:?}", event),
+    }
+}
+
+///
+/// Find the value in `values` whose debug name is `name`.
+///
+fn from_name<T: Copy + ::std::fmt::Debug>(values: &[T], name: &str) -> Option<T> {
+    values.iter().find(|value| format!("{:?}", value) == name).map(|&value| value)
+}
+
+fn event_from_fields(fields: &[&str]) -> Option<WindowEvent> {
+    let mods = |field: &str| field.parse::<i32>().ok().and_then(Modifiers::from_bits);
+    let number = |field: &str| field.parse::<f64>().ok();
+
+    match (fields[0], fields.len()) {
+        ("key", 5) => match (
+            from_name(&KEYS, fields[1]), fields[2].parse().ok(), from_name(&ACTIONS, fields[3]), mods(fields[4])
+        ) {
+            (Some(key), Some(scancode), Some(action), Some(mods)) => Some(WindowEvent::Key(key, scancode, action, mods)),
+            _ => None,
+        },
+        ("button", 4) => match (from_name(&MOUSE_BUTTONS, fields[1]), from_name(&ACTIONS, fields[2]), mods(fields[3])) {
+            (Some(button), Some(action), Some(mods)) => Some(WindowEvent::MouseButton(button, action, mods)),
+            _ => None,
+        },
+        ("cursor", 3) => match (number(fields[1]), number(fields[2])) {
+            (Some(x), Some(y)) => Some(WindowEvent::CursorPos(x, y)),
+            _ => None,
+        },
+        ("scroll", 3) => match (number(fields[1]), number(fields[2])) {
+            (Some(x), Some(y)) => Some(WindowEvent::Scroll(x, y)),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+mod input_replay_tests {
+    use super::{replay_mode_from_args, InputRecording, ReplayMode};
+    use glfw::{Action, Key, Modifiers, MouseButton, WindowEvent};
+
+    fn args(list: &[&str]) -> Vec<String> {
+        list.iter().map(|arg| String::from(*arg)).collect()
+    }
+
+    fn recording() -> InputRecording {
+        let mut recording = InputRecording::new();
+        recording.record(0, &WindowEvent::Key(Key::W, 17, Action::Press, Modifiers::empty()));
+        recording.record(0, &WindowEvent::CursorPos(320.5, 240.25));
+        // not input, so not recorded
+        recording.record(1, &WindowEvent::Focus(true));
+        recording.record(3, &WindowEvent::MouseButton(MouseButton::Button1, Action::Press, Modifiers::Shift));
+        recording.record(3, &WindowEvent::Scroll(0.0, -1.5));
+        recording.record(7, &WindowEvent::Key(Key::Enter, 28, Action::Release, Modifiers::Alt | Modifiers::Control));
+
+        recording
+    }
+
+    #[test]
+    fn test_the_replay_mode_comes_from_the_command_line() {
+        assert_eq!(replay_mode_from_args(args(&["demo"])), Ok(ReplayMode::Live));
+        assert_eq!(
+            replay_mode_from_args(args(&["demo", "--record", "bug.txt"])), Ok(ReplayMode::Record(String::from("bug.txt")))
+        );
+        assert_eq!(
+            replay_mode_from_args(args(&["demo", "--replay", "bug.txt"])), Ok(ReplayMode::Replay(String::from("bug.txt")))
+        );
+        assert!(replay_mode_from_args(args(&["demo", "--replay"])).is_err());
+    }
+
+    #[test]
+    fn test_only_key_and_mouse_events_are_kept_frame_by_frame() {
+        let recording = recording();
+
+        assert_eq!(recording.events().len(), 5);
+        assert_eq!(recording.events_on_frame(0).len(), 2);
+        assert!(recording.events_on_frame(1).is_empty());
+        assert_eq!(recording.events_on_frame(3)[1], WindowEvent::Scroll(0.0, -1.5));
+        assert_eq!(recording.frame_count(), 8);
+    }
+
+    #[test]
+    fn test_a_recording_survives_the_trip_through_text() {
+        let recording = recording();
+        let text = recording.to_text();
+
+        assert!(text.contains("3 0.050000 button Button1 Press 1"));
+        assert_eq!(InputRecording::parse(&text), Ok(recording));
+    }
+
+    #[test]
+    fn test_broken_lines_are_reported_with_their_line_number() {
+        let missing_event = InputRecording::parse("# header\n0 0.0\n");
+        assert!(missing_event.unwrap_err().contains("line 2"));
+        assert!(InputRecording::parse("0 0.0 key NotAKey 0 Press 0\n").is_err());
+        assert!(InputRecording::parse("5 0.0 cursor 1 2\n2 0.0 cursor 1 2\n").is_err());
+    }
+}
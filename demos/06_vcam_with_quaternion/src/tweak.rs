@@ -0,0 +1,433 @@
+use glfw::Key;
+
+use demo_app::Input;
+
+use std::fs::File;
+use std::io::{ErrorKind, Read, Write};
+
+
+///
+/// The file the tweakable values are kept in between runs, in the directory the
+/// demo runs from.
+///
+pub const TWEAKS_FILE: &str = "tweaks.txt";
+///
+/// How far Left and Right move a colour channel.
+///
+pub const COLOUR_STEP: f32 = 0.05;
+
+const CHANNEL_NAMES: [&str; 3] = ["r", "g", "b"];
+
+
+///
+/// The value of a tweakable parameter, along with how far it can go.
+///
+#[derive(Clone, Debug, PartialEq)]
+pub enum TweakValue {
+    Float { value: f32, min: f32, max: f32, step: f32 },
+    Bool(bool),
+    ///
+    /// A red, green, and blue colour, each channel from 0 to 1. Each channel is a
+    /// row of the panel of its own.
+    ///
+    Colour([f32; 3]),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct TweakVar {
+    pub name: String,
+    pub value: TweakValue,
+}
+
+///
+/// The named parameters a demo lets the user adjust while it runs, such as a fog
+/// density or a light colour. The panel lists them one to a row: Up and Down pick
+/// a row, and Left and Right adjust it. Values are looked up by name, and read
+/// with the type they were added with.
+///
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Tweaks {
+    vars: Vec<TweakVar>,
+    selected: usize,
+    changed: bool,
+    pub visible: bool,
+}
+
+impl Tweaks {
+    pub fn new() -> Tweaks {
+        Tweaks::default()
+    }
+
+    ///
+    /// A number from `min` to `max`, moved by `step` at a time.
+    ///
+    pub fn add_f32(&mut self, name: &str, value: f32, min: f32, max: f32, step: f32) {
+        let value = f32::min(f32::max(value, min), max);
+        self.add(name, TweakValue::Float { value: value, min: min, max: max, step: step });
+    }
+
+    pub fn add_bool(&mut self, name: &str, value: bool) {
+        self.add(name, TweakValue::Bool(value));
+    }
+
+    pub fn add_colour(&mut self, name: &str, value: [f32; 3]) {
+        self.add(name, TweakValue::Colour(value));
+    }
+
+    fn add(&mut self, name: &str, value: TweakValue) {
+        assert!(self.find(name).is_none(), "a tweak named {} is already registered", name);
+        self.vars.push(TweakVar { name: String::from(name), value: value });
+    }
+
+    fn find(&self, name: &str) -> Option<&TweakVar> {
+        self.vars.iter().find(|var| var.name == name)
+    }
+
+    fn find_mut(&mut self, name: &str) -> Option<&mut TweakVar> {
+        self.vars.iter_mut().find(|var| var.name == name)
+    }
+
+    ///
+    /// The value of the number `name`. Panics if there is no such number, as that
+    /// is a typo in the demo.
+    ///
+    pub fn f32(&self, name: &str) -> f32 {
+        match self.find(name) {
+            Some(&TweakVar { value: TweakValue::Float { value, .. }, .. }) => value,
+            _ => panic!("there is no number tweak named {}", name),
+        }
+    }
+
+    pub fn bool(&self, name: &str) -> bool {
+        match self.find(name) {
+            Some(&TweakVar { value: TweakValue::Bool(value), .. }) => value,
+            _ => panic!("there is no true or false tweak named {}", name),
+        }
+    }
+
+    pub fn colour(&self, name: &str) -> [f32; 3] {
+        match self.find(name) {
+            Some(&TweakVar { value: TweakValue::Colour(value), .. }) => value,
+            _ => panic!("there is no colour tweak named {}", name),
+        }
+    }
+
+    ///
+    /// Set the number `name` from elsewhere in the demo, such as a key of its own,
+    /// so the panel shows what is really in use.
+    ///
+    pub fn set_f32(&mut self, name: &str, new_value: f32) {
+        match self.find_mut(name) {
+            Some(&mut TweakVar { value: TweakValue::Float { ref mut value, min, max, .. }, .. }) => {
+                *value = f32::min(f32::max(new_value, min), max);
+            }
+            _ => panic!("there is no number tweak named {}", name),
+        }
+        self.changed = true;
+    }
+
+    pub fn set_bool(&mut self, name: &str, new_value: bool) {
+        match self.find_mut(name) {
+            Some(&mut TweakVar { value: TweakValue::Bool(ref mut value), .. }) => *value = new_value,
+            _ => panic!("there is no true or false tweak named {}", name),
+        }
+        self.changed = true;
+    }
+
+    ///
+    /// Whether anything has changed since the last call. The demo checks this
+    /// once a frame, and applies the values when it has.
+    ///
+    pub fn take_changed(&mut self) -> bool {
+        let changed = self.changed;
+        self.changed = false;
+
+        changed
+    }
+
+    ///
+    /// Each row of the panel, as the index of its variable and, for colours, the
+    /// channel.
+    ///
+    fn rows(&self) -> Vec<(usize, Option<usize>)> {
+        let mut rows = vec![];
+        for (i, var) in self.vars.iter().enumerate() {
+            match var.value {
+                TweakValue::Colour(_) => {
+                    for channel in 0..3 {
+                        rows.push((i, Some(channel)));
+                    }
+                }
+                _ => rows.push((i, None)),
+            }
+        }
+
+        rows
+    }
+
+    ///
+    /// Move the selection `rows` rows down, or up if negative, wrapping around at
+    /// either end.
+    ///
+    pub fn select(&mut self, rows: i32) {
+        let row_count = self.rows().len() as i32;
+        if row_count > 0 {
+            self.selected = ((self.selected as i32 + rows) % row_count + row_count) as usize % row_count as usize;
+        }
+    }
+
+    ///
+    /// Move the selected row `steps` steps up, or down if negative. True or false
+    /// values flip whichever way they are moved.
+    ///
+    pub fn adjust(&mut self, steps: i32) {
+        let rows = self.rows();
+        if rows.is_empty() {
+            return;
+        }
+        let (index, channel) = rows[self.selected];
+        match self.vars[index].value {
+            TweakValue::Float { ref mut value, min, max, step } => {
+                *value = f32::min(f32::max(*value + steps as f32 * step, min), max);
+            }
+            TweakValue::Bool(ref mut value) => *value = !*value,
+            TweakValue::Colour(ref mut value) => {
+                let channel = channel.unwrap_or(0);
+                value[channel] = f32::min(f32::max(value[channel] + steps as f32 * COLOUR_STEP, 0.0), 1.0);
+            }
+        }
+        self.changed = true;
+    }
+
+    ///
+    /// Adjust the panel from the arrow keys while it is visible. The arrow keys
+    /// are taken out of `input`, so the demo does not move its camera with them
+    /// as well.
+    ///
+    pub fn handle_input(&mut self, input: &mut Input) {
+        if !self.visible {
+            return;
+        }
+        let keys = [(Key::Up, -1, 0), (Key::Down, 1, 0), (Key::Left, 0, -1), (Key::Right, 0, 1)];
+        for &(key, rows, steps) in keys.iter() {
+            if input.was_pressed(key) {
+                if rows != 0 {
+                    self.select(rows);
+                } else {
+                    self.adjust(steps);
+                }
+            }
+            input.consume(key);
+        }
+    }
+
+    ///
+    /// The panel as lines of text, one row to a line, with the selected row marked.
+    ///
+    pub fn lines(&self) -> Vec<String> {
+        let rows = self.rows();
+        let names: Vec<String> = rows.iter().map(|&(index, channel)| match channel {
+            Some(channel) => format!("{} {}", self.vars[index].name, CHANNEL_NAMES[channel]),
+            None => self.vars[index].name.clone(),
+        }).collect();
+        let names_width = names.iter().map(|name| name.len()).max().unwrap_or(0);
+
+        let mut lines = vec![String::from("tweaks")];
+        for (row, &(index, channel)) in rows.iter().enumerate() {
+            let value = match (&self.vars[index].value, channel) {
+                (&TweakValue::Colour(value), Some(channel)) => format!("{:.2}", value[channel]),
+                (value, _) => describe_value(value),
+            };
+            let marker = if row == self.selected { ">" } else { " " };
+            lines.push(format!("{} {:width$}  {}", marker, names[row], value, width = names_width));
+        }
+
+        lines
+    }
+
+    ///
+    /// Every value as name and value, for the help.
+    ///
+    pub fn values(&self) -> Vec<(String, String)> {
+        self.vars.iter().map(|var| (var.name.clone(), describe_value(&var.value))).collect()
+    }
+
+    ///
+    /// The values as text, one `name = value` to a line.
+    ///
+    pub fn to_text(&self) -> String {
+        let mut text = String::new();
+        for var in self.vars.iter() {
+            text.push_str(&format!("{} = {}\n", var.name, describe_value(&var.value)));
+        }
+
+        text
+    }
+
+    ///
+    /// Set the values from the text `to_text` makes. Names the demo has not added
+    /// are skipped, so a file from an older version of the demo still loads, and
+    /// numbers are kept within their limits.
+    ///
+    pub fn apply_text(&mut self, text: &str) -> Result<(), String> {
+        for (line_number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let error = || format!("ERROR: tweaks line {}: could not read {}", line_number + 1, line);
+            let mut parts = line.splitn(2, '=');
+            let name = parts.next().unwrap_or("").trim();
+            let text_value = match parts.next() {
+                Some(val) => val.trim(),
+                None => return Err(error()),
+            };
+            let var = match self.vars.iter_mut().find(|var| var.name == name) {
+                Some(val) => val,
+                None => continue,
+            };
+            let parsed = match var.value {
+                TweakValue::Float { min, max, step, .. } => match text_value.parse::<f32>() {
+                    Ok(value) => Some(TweakValue::Float {
+                        value: f32::min(f32::max(value, min), max), min: min, max: max, step: step
+                    }),
+                    Err(_) => None,
+                },
+                TweakValue::Bool(_) => match text_value.parse::<bool>() {
+                    Ok(value) => Some(TweakValue::Bool(value)),
+                    Err(_) => None,
+                },
+                TweakValue::Colour(_) => {
+                    let channels: Vec<f32> = text_value.split_whitespace().filter_map(|c| c.parse().ok()).collect();
+                    if channels.len() == 3 {
+                        let clamp = |c: f32| f32::min(f32::max(c, 0.0), 1.0);
+                        Some(TweakValue::Colour([clamp(channels[0]), clamp(channels[1]), clamp(channels[2])]))
+                    } else {
+                        None
+                    }
+                }
+            };
+            match parsed {
+                Some(value) => var.value = value,
+                None => return Err(error()),
+            }
+        }
+        self.changed = true;
+
+        Ok(())
+    }
+
+    ///
+    /// Load the values saved by an earlier run. There being no file yet is not
+    /// an error.
+    ///
+    pub fn load(&mut self, file_name: &str) -> Result<(), String> {
+        let mut file = match File::open(file_name) {
+            Ok(val) => val,
+            Err(ref e) if e.kind() == ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(format!("ERROR: could not open tweaks file {}: {}", file_name, e)),
+        };
+        let mut text = String::new();
+        if let Err(e) = file.read_to_string(&mut text) {
+            return Err(format!("ERROR: could not read tweaks file {}: {}", file_name, e));
+        }
+
+        self.apply_text(&text)
+    }
+
+    pub fn save(&self, file_name: &str) -> Result<(), String> {
+        let mut file = match File::create(file_name) {
+            Ok(val) => val,
+            Err(e) => return Err(format!("ERROR: could not create tweaks file {}: {}", file_name, e)),
+        };
+        match file.write_all(self.to_text().as_bytes()) {
+            Ok(_) => Ok(()),
+            Err(e) => Err(format!("ERROR: could not write tweaks file {}: {}", file_name, e)),
+        }
+    }
+}
+
+fn describe_value(value: &TweakValue) -> String {
+    match *value {
+        TweakValue::Float { value, .. } => format!("{}", value),
+        TweakValue::Bool(value) => format!("{}", value),
+        TweakValue::Colour(value) => format!("{} {} {}", value[0], value[1], value[2]),
+    }
+}
+
+mod tweak_tests {
+    use super::Tweaks;
+    use demo_app::Input;
+    use glfw::{Action, Key, Modifiers, WindowEvent};
+
+    fn tweaks() -> Tweaks {
+        let mut tweaks = Tweaks::new();
+        tweaks.add_f32("fog density", 0.5, 0.0, 1.0, 0.25);
+        tweaks.add_bool("fog", true);
+        tweaks.add_colour("fog colour", [0.5, 0.5, 0.5]);
+
+        tweaks
+    }
+
+    #[test]
+    fn test_adjusting_keeps_numbers_within_their_limits() {
+        let mut tweaks = tweaks();
+        tweaks.adjust(1);
+        assert_eq!(tweaks.f32("fog density"), 0.75);
+        tweaks.adjust(5);
+        assert_eq!(tweaks.f32("fog density"), 1.0);
+        assert!(tweaks.take_changed());
+        assert!(!tweaks.take_changed());
+
+        tweaks.select(1);
+        tweaks.adjust(-1);
+        assert!(!tweaks.bool("fog"));
+    }
+
+    #[test]
+    fn test_each_colour_channel_is_a_row_of_its_own() {
+        let mut tweaks = tweaks();
+        // the green channel of the colour is the fourth row
+        tweaks.select(3);
+        tweaks.adjust(2);
+        assert_eq!(tweaks.colour("fog colour"), [0.5, 0.6, 0.5]);
+        assert_eq!(tweaks.lines()[4], "> fog colour g  0.60");
+
+        // and the selection wraps around from the last row to the first
+        tweaks.select(2);
+        assert_eq!(tweaks.lines()[1], "> fog density   0.5");
+    }
+
+    #[test]
+    fn test_the_arrow_keys_only_adjust_the_panel_while_it_is_visible() {
+        let mut tweaks = tweaks();
+        let mut input = Input::new();
+        input.handle_event(WindowEvent::Key(Key::Right, 0, Action::Press, Modifiers::empty()));
+
+        tweaks.handle_input(&mut input);
+        assert_eq!(tweaks.f32("fog density"), 0.5);
+        assert!(input.is_held(Key::Right));
+
+        tweaks.visible = true;
+        tweaks.handle_input(&mut input);
+        assert_eq!(tweaks.f32("fog density"), 0.75);
+        assert!(!input.is_held(Key::Right));
+    }
+
+    #[test]
+    fn test_values_survive_the_trip_through_text() {
+        let mut saved = tweaks();
+        saved.set_f32("fog density", 0.25);
+        saved.set_bool("fog", false);
+
+        let mut loaded = tweaks();
+        assert_eq!(loaded.apply_text(&saved.to_text()), Ok(()));
+        assert_eq!(loaded.f32("fog density"), 0.25);
+        assert!(!loaded.bool("fog"));
+
+        // unknown names are skipped, and numbers are kept within their limits
+        assert_eq!(loaded.apply_text("light = 3\nfog density = 7\n"), Ok(()));
+        assert_eq!(loaded.f32("fog density"), 1.0);
+        assert!(loaded.apply_text("fog colour = 1 2\n").is_err());
+    }
+}
@@ -1,140 +1,163 @@
 extern crate gl;
 extern crate glfw;
 extern crate chrono;
-
-#[macro_use] 
-extern crate scan_fmt;
+#[cfg(feature = "glam")]
+extern crate glam;
+#[cfg(feature = "cgmath")]
+extern crate cgmath;
+#[cfg(feature = "audio")]
+extern crate rodio;
 
 mod gl_utils;
 mod graphics_math;
 mod obj_parser;
+mod assets;
+mod colour;
+mod picking;
+mod sound;
+mod vertex_layout;
 
 
 use glfw::{Action, Context, Key, MouseButton};
-use gl::types::{GLfloat, GLsizeiptr, GLvoid};
 
-use std::mem;
-use std::ptr;
-use std::process;
 
 use gl_utils::*;
+use sound::SoundPlayer;
 
 use graphics_math as math;
 use math::{Vec3, Mat4, Versor};
+use picking::{Plane, Ray};
+use vertex_layout::VertexLayout;
 
 const MESH_FILE: &str = "src/sphere.obj";
 const VERTEX_SHADER_FILE: &str = "src/test.vert.glsl";
 const FRAGMENT_SHADER_FILE: &str = "src/test.frag.glsl";
+const CLICK_SOUND_FILE: &str = "src/click.wav";
 const NUM_SPHERES: usize = 4;
 const SPHERE_RADIUS: f32 = 1.0;
+const MARKER_RADIUS: f32 = 0.2;
+// The spheres rest on the ground.
+const GROUND_HEIGHT: f32 = -1.0;
+const GROUND_COLOUR: [f32; 3] = [0.3, 0.6, 0.3];
+const SPHERE_COLOUR: [f32; 3] = [1.0, 0.0, 0.0];
+// Clicking a marker moves it on to the next colour.
+const MARKER_COLOURS: [[f32; 3]; 4] = [
+    [1.0, 1.0, 0.0], [0.0, 1.0, 1.0], [1.0, 0.5, 0.0], [1.0, 1.0, 1.0],
+];
 
-static mut PREVIOUS_SECONDS: f64 = 0.0;
-static mut G_SELECTED_SPHERE: isize = -1;
 
+///
+/// A marker dropped on the ground by clicking it, and which colour of
+/// `MARKER_COLOURS` it is drawn in.
+///
+struct Marker {
+    position: Vec3,
+    colour: usize,
+}
 
 ///
-/// Take the mouse position on screen and return ray cast into the scene in
-/// world space coordinates.
+/// What a click on the scene landed on first.
 ///
-fn get_ray_from_mouse(proj_mat: &Mat4, view_mat: &Mat4, mouse_x: f32, mouse_y: f32) -> Vec3 {
-    // Screen space (Viewport coordinates).
-    let x = unsafe { (2.0 * mouse_x) / (G_GL_WIDTH as f32) - 1.0 };
-    let y = unsafe { 1.0 - (2.0 * mouse_y) / (G_GL_HEIGHT as f32) };
-    let z = 1.0;
-    // Normalised device coordinates.
-    let ray_nds = math::vec3((x, y, z));
-    // Clip space.
-    let ray_clip = math::vec4((ray_nds.v[0], ray_nds.v[1], -1.0, 1.0));
-    // Eye space.
-    let ray_eye = proj_mat.inverse() * ray_clip;
-    let ray_eye = math::vec4((ray_eye.v[0], ray_eye.v[1], -1.0, 0.0));
-    // World space.
-    let ray_wor = math::vec3(view_mat.inverse() * ray_eye);
-    
-    // Don't forget to normalize the vector before returning it.
-    ray_wor.normalize()
+enum Clicked {
+    Sphere(usize),
+    Marker(usize),
+    Ground(Vec3),
+    Nothing,
 }
 
 ///
-/// Determine if a ray and a sphere intersect. If the ray hits the spehere, it returns false. 
-/// It rejects intersections behind the ray caster's origin, and sets `intersection_distance` to
-/// the closest intersection.
+/// Follow the ray from the mouse into the scene. The spheres and markers stand on
+/// the ground, so they are hit before it whenever the ray passes through them.
 ///
-fn ray_sphere(
-    ray_origin_wor: Vec3, ray_direction_wor: Vec3,
-    sphere_centre_wor: Vec3, sphere_radius: f32, intersection_distance: &mut f32) -> bool {
-    
-    // Work out components of quadratic.
-    let dist_to_sphere = ray_origin_wor - sphere_centre_wor;
-    let b = ray_direction_wor.dot(&dist_to_sphere);
-    let c = dist_to_sphere.dot(&dist_to_sphere) - sphere_radius * sphere_radius;
-    let b_squared_minus_c = b * b - c;
-    // Check for "imaginary" answer. == ray completely misses sphere
-    if b_squared_minus_c < 0.0 {
-        return false;
+fn find_clicked(ray: &Ray, ground: &Plane, sphere_pos_wor: &[Vec3], markers: &[Marker]) -> Clicked {
+    let mut clicked = Clicked::Nothing;
+    let mut closest_intersection = 0.0;
+    for i in 0..NUM_SPHERES {
+        if let Some(t_dist) = picking::ray_sphere(ray, sphere_pos_wor[i], SPHERE_RADIUS) {
+            // If more than one sphere is in path of the ray, choose only the closest one.
+            let is_closer = match clicked {
+                Clicked::Nothing => true,
+                _ => t_dist < closest_intersection,
+            };
+            if is_closer {
+                clicked = Clicked::Sphere(i);
+                closest_intersection = t_dist;
+            }
+        }
     }
-    // Check whether the ray hits the sphere twice (into and out of the sphere).
-    if b_squared_minus_c > 0.0 {
-        // Get the 2 intersection distances along the ray.
-        let t_a = -b + f32::sqrt(b_squared_minus_c);
-        let t_b = -b - f32::sqrt(b_squared_minus_c);
-        *intersection_distance = t_b;
-        // If the object is behind the viewer, throw one or both away.
-        if t_a < 0.0 {
-            if t_b < 0.0 {
-                return false;
+    for (i, marker) in markers.iter().enumerate() {
+        if let Some(t_dist) = picking::ray_sphere(ray, marker.position, MARKER_RADIUS) {
+            let is_closer = match clicked {
+                Clicked::Nothing => true,
+                _ => t_dist < closest_intersection,
+            };
+            if is_closer {
+                clicked = Clicked::Marker(i);
+                closest_intersection = t_dist;
             }
-        } else if t_b < 0.0 {
-            *intersection_distance = t_a;
         }
-
-        return true;
     }
-    // Check whether the ray skims the surface (i.e. it hits at one point).
-    if b_squared_minus_c == 0.0 {
-        // If the ray hits behind the viewer, throw away the ray.
-        let t = -b + f32::sqrt(b_squared_minus_c);
-        if t < 0.0 {
-            return false;
+    if let Clicked::Nothing = clicked {
+        if let Some(point) = picking::ray_plane(ray, ground) {
+            clicked = Clicked::Ground(point);
         }
-        *intersection_distance = t;
-        return true;
     }
-    // NOTE: we could also check if the ray origin is inside the sphere radius.
-    return false;
+
+    clicked
 }
 
 ///
 /// This function gets called whenever the mouse buttons are clicked or unclicked.
+/// Clicking a sphere selects it, clicking the ground drops a marker there, and
+/// clicking a marker changes its colour.
 ///
 fn glfw_mouse_click_callback(
-    window: &mut glfw::Window, button: MouseButton, action: Action, 
-    proj_mat: &Mat4, view_mat: &Mat4, cam_pos: Vec3, sphere_pos_wor: &[Vec3]) {
+    context: &GLContext, button: MouseButton, action: Action, 
+    proj_mat: &Mat4, view_mat: &Mat4, cam_pos: Vec3, sphere_pos_wor: &[Vec3], ground: &Plane,
+    markers: &mut Vec<Marker>, sounds: &SoundPlayer, selected_sphere: &mut isize) {
     // NOTE: We could alsop query if window is out of focus here.
     // NOTE: We are not distinguishing between different mouse buttons in this callback for this demo.
     match action {
         Action::Press => {
-            let (x_pos, y_pos) = window.get_cursor_pos();
+            let (x_pos, y_pos) = context.window.get_cursor_pos();
             // Work out the ray into the scene from the mouse.
-            let ray_wor = get_ray_from_mouse(proj_mat, view_mat, x_pos as f32, y_pos as f32);
-            // Find which sphere the ray intersects, if any, in the scene.
+            let ray_wor = picking::ray_from_mouse(
+                context.width, context.height, proj_mat, view_mat, cam_pos, x_pos as f32, y_pos as f32
+            );
+            // Find what the ray hits first, if anything, in the scene.
+            let clicked = find_clicked(&ray_wor, ground, sphere_pos_wor, markers);
             let mut closest_sphere_clicked = -1;
-            let mut closest_intersection = 0.0;
-            for i in 0..NUM_SPHERES {
-                let mut t_dist = 0.0;
-                if ray_sphere(cam_pos, ray_wor, sphere_pos_wor[i], SPHERE_RADIUS, &mut t_dist) {
-                    // If more than one sphere is in path of the ray, choose only the closest one.
-                    if closest_sphere_clicked == -1 || t_dist < closest_intersection {
-                        closest_sphere_clicked = i as isize;
-                        closest_intersection = t_dist;
-                    }
+            match clicked {
+                Clicked::Sphere(i) => {
+                    closest_sphere_clicked = i as isize;
+                    println!("Sphere {} was clicked", i);
+                }
+                Clicked::Marker(i) => {
+                    markers[i].colour = (markers[i].colour + 1) % MARKER_COLOURS.len();
+                    println!("Marker {} was recoloured", i);
+                }
+                Clicked::Ground(point) => {
+                    // Stand the marker on the ground rather than sinking it halfway in.
+                    let position = point + ground.normal * MARKER_RADIUS;
+                    markers.push(Marker { position: position, colour: 0 });
+                    println!(
+                        "Marker {} was placed at ({:.2}, {:.2}, {:.2})",
+                        markers.len() - 1, point.v[0], point.v[1], point.v[2]
+                    );
+                }
+                Clicked::Nothing => {
+                    println!("Nothing was clicked");
                 }
             }
         
-            unsafe {
-                G_SELECTED_SPHERE = closest_sphere_clicked;
+            // Only click when the selection actually changes to a new sphere.
+            let newly_picked = closest_sphere_clicked != -1 && closest_sphere_clicked != *selected_sphere;
+            if newly_picked {
+                if let Err(e) = sounds.play("click") {
+                    gl_log_err(&e);
+                }
             }
-            println!("Sphere {} was clicked", closest_sphere_clicked);
+            *selected_sphere = closest_sphere_clicked;
         }
         _ => {}
     }
@@ -144,7 +167,7 @@ fn main() {
     /*--------------------------------START OPENGL--------------------------------*/
     restart_gl_log();
     // Start GL context and OS window using the GLFW helper library.
-    let (mut glfw, mut g_window, _g_events) = start_gl().unwrap();
+    let mut context = start_gl().unwrap();
     // set a function to be called when the mouse is clicked
     
     /*------------------------------CREATE GEOMETRY-------------------------------*/
@@ -152,7 +175,8 @@ fn main() {
         Ok(val) => val,
         Err(e) => {
             gl_log_err(&format!("ERROR: loading mesh file. Loader returned error\n{}", e));
-            process::exit(1);
+            gl_log_err("WARNING: using the embedded fallback mesh instead.");
+            assets::fallback_mesh()
         }
     };
 
@@ -164,34 +188,47 @@ fn main() {
     let mut vao = 0;
     unsafe {
         gl::GenVertexArrays(1, &mut vao);
-        gl::BindVertexArray(vao);
     }
-
-    let mut points_vbo = 0;
     if !vp.is_empty() {
-        unsafe {
-            gl::GenBuffers(1, &mut points_vbo);
-            gl::BindBuffer(gl::ARRAY_BUFFER, points_vbo);
-            gl::BufferData(
-                gl::ARRAY_BUFFER, (3 * g_point_count * mem::size_of::<GLfloat>()) as GLsizeiptr, 
-                vp.as_ptr() as *const GLvoid, gl::STATIC_DRAW
-            );
-            gl::VertexAttribPointer(0, 3, gl::FLOAT, gl::FALSE, 0, ptr::null());
-            gl::EnableVertexAttribArray(0);
-        }
+        VertexLayout::new().floats(0, 3).upload(vao, &vp, g_point_count);
     }
 
+    // The ground is a single quad, running from z = 0 at the far edge to z = 1 at
+    // the near edge so the shader fades it out into the distance.
+    let ground_points: [f32; 18] = [
+        -1.0, 0.0, 1.0,  1.0, 0.0, 1.0,  1.0, 0.0, 0.0,
+        -1.0, 0.0, 1.0,  1.0, 0.0, 0.0, -1.0, 0.0, 0.0,
+    ];
+    let mut ground_vao = 0;
+    unsafe {
+        gl::GenVertexArrays(1, &mut ground_vao);
+    }
+    VertexLayout::new().floats(0, 3).upload(ground_vao, &ground_points, 6);
+    let ground = Plane::from_point_normal(&math::vec3((0.0, GROUND_HEIGHT, 0.0)), &math::vec3((0.0, 1.0, 0.0)));
+    // Stretch the quad to size before moving it into place, or the move would be
+    // stretched too.
+    let ground_model_mat = Mat4::from_scale(&math::vec3((10.0, 1.0, 20.0)))
+        .then_translate(&math::vec3((0.0, GROUND_HEIGHT, -15.0)));
+
     /*-------------------------------CREATE SHADERS-------------------------------*/
-    // FIXME: Why don't the gl::GetUniformLocation calls fetch the resources when the functions are called?
     let shader_programme = create_programme_from_files(VERTEX_SHADER_FILE, FRAGMENT_SHADER_FILE);
-    let model_mat_location = unsafe { gl::GetUniformLocation(shader_programme, "model".as_ptr() as *const i8) };
+    let model_mat_location = uniform_location(shader_programme, "model");
     assert!(model_mat_location != -1);
-    let view_mat_location  = unsafe { gl::GetUniformLocation(shader_programme, "view".as_ptr() as *const i8) };
+    let view_mat_location  = uniform_location(shader_programme, "view");
     assert!(view_mat_location != -1);
-    let proj_mat_location  = unsafe { gl::GetUniformLocation(shader_programme, "proj".as_ptr() as *const i8) };
+    let proj_mat_location  = uniform_location(shader_programme, "proj");
     assert!(proj_mat_location != -1);
-    let blue_location = unsafe { gl::GetUniformLocation(shader_programme, "blue".as_ptr() as *const i8 ) };
+    let blue_location = uniform_location(shader_programme, "blue");
     assert!(blue_location != -1);
+    let colour_location = uniform_location(shader_programme, "colour");
+    assert!(colour_location != -1);
+
+    /*-------------------------------LOAD SOUNDS----------------------------------*/
+    // A missing sound is not fatal; the demo just picks spheres silently.
+    let mut sounds = SoundPlayer::new();
+    if let Err(e) = sounds.load("click", CLICK_SOUND_FILE) {
+        gl_log_err(&e);
+    }
 
     /*-------------------------------CREATE CAMERA--------------------------------*/
     const ONE_DEG_IN_RAD: f32 = math::ONE_DEG_IN_RAD; // 0.017444444
@@ -199,22 +236,22 @@ fn main() {
     let near = 0.1;                                                 // Near clipping plane
     let far = 100.0;                                                // Far clipping plane
     let fovy = 67.0;                                                // 67 Degree field of view.
-    let aspect = unsafe { G_GL_WIDTH as f32 / G_GL_HEIGHT as f32 }; // Aspect ratio
+    let aspect = context.aspect_ratio(); // Aspect ratio
     let proj_mat = Mat4::perspective(fovy, aspect, near, far);
 
     let cam_speed = 3.0;          // 1 unit per second
     let cam_heading_speed = 50.0; // 30 degrees per second
     let cam_heading = 0.0;        // y-rotation in degrees
     let mut cam_pos = math::vec3((0.0, 0.0, 5.0));
-    let mut mat_trans = Mat4::translate(&Mat4::identity(), &math::vec3((-cam_pos.v[0], -cam_pos.v[1], -cam_pos.v[2])));
-    let mut mat_rot = Mat4::identity().rotate_y_deg(-cam_heading);
+    let mut mat_trans = Mat4::from_translation(&-cam_pos);
+    let mut mat_rot = Mat4::from_rotation_y_deg(-cam_heading);
     let mut q = Versor::from_axis_deg(-cam_heading, 0.0, 1.0, 0.0);
 
     let mut view_mat = mat_rot * mat_trans;
     // Keep track of some useful vectors that can be used for keyboard movement.
-    let mut fwd = math::vec4((0.0, 0.0, -1.0, 0.0));
-    let mut rgt = math::vec4((1.0, 0.0, 0.0, 0.0));
-    let mut up  = math::vec4((0.0, 1.0, 0.0, 0.0));
+    let mut fwd = math::vec3((0.0, 0.0, -1.0));
+    let mut rgt = math::vec3((1.0, 0.0, 0.0));
+    let mut up  = math::vec3((0.0, 1.0, 0.0));
 
     /*---------------------------SET RENDERING DEFAULTS---------------------------*/
     unsafe {
@@ -231,7 +268,7 @@ fn main() {
     // Unique model matrix for each sphere.
     let mut model_mats = vec![];
     for i in 0..NUM_SPHERES {
-        model_mats.push(Mat4::translate(&Mat4::identity(), &sphere_pos_wor[i]));
+        model_mats.push(Mat4::from_translation(&sphere_pos_wor[i]));
     }
 
     unsafe {
@@ -241,28 +278,39 @@ fn main() {
         gl::CullFace(gl::BACK);       // cull back face
         gl::FrontFace(gl::CCW);       // set counter-clock-wise vertex order to mean the front
         gl::ClearColor(0.2, 0.2, 0.2, 1.0); // grey background to help spot mistakes
-        gl::Viewport(0, 0, G_GL_WIDTH as i32, G_GL_HEIGHT as i32);
+        gl::Viewport(0, 0, context.width as i32, context.height as i32);
     }
 
+    // The sphere the mouse last picked, or -1 for none.
+    let mut selected_sphere: isize = -1;
+    let mut markers: Vec<Marker> = vec![];
+    // Clicks act once per press, rather than on every frame the button is held.
+    let mut mouse_was_down = false;
+
     /*-------------------------------RENDERING LOOP-------------------------------*/
-    while !g_window.should_close() {
+    while !context.window.should_close() {
         // Update timers.
-        let current_seconds = glfw.get_time();
-        let elapsed_seconds = unsafe { current_seconds - PREVIOUS_SECONDS };
-        unsafe {
-            PREVIOUS_SECONDS = current_seconds;
-        }
+        let current_seconds = context.glfw.get_time();
+        let elapsed_seconds = current_seconds - context.elapsed_time_seconds;
+        context.elapsed_time_seconds = current_seconds;
         // Update FPS.
-        _update_fps_counter(&glfw, &mut g_window);
+        update_fps_counter(&mut context);
 
         unsafe {
             // Wipe the drawing surface clear.
             gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
 
             gl::UseProgram(shader_programme);
+            gl::BindVertexArray(ground_vao);
+            gl::Uniform1f(blue_location, 0.0);
+            gl::Uniform3fv(colour_location, 1, GROUND_COLOUR.as_ptr());
+            gl::UniformMatrix4fv(model_mat_location, 1, gl::FALSE, ground_model_mat.as_ptr());
+            gl::DrawArrays(gl::TRIANGLES, 0, 6);
+
             gl::BindVertexArray(vao);
+            gl::Uniform3fv(colour_location, 1, SPHERE_COLOUR.as_ptr());
             for i in 0..NUM_SPHERES {
-                if i as isize == G_SELECTED_SPHERE {
+                if i as isize == selected_sphere {
                     gl::Uniform1f(blue_location, 1.0);
                 } else {
                     gl::Uniform1f(blue_location, 0.0);
@@ -270,10 +318,18 @@ fn main() {
                 gl::UniformMatrix4fv(model_mat_location, 1, gl::FALSE, model_mats[i].as_ptr());
                 gl::DrawArrays(gl::TRIANGLES, 0, g_point_count as i32);
             }
+            gl::Uniform1f(blue_location, 0.0);
+            for marker in markers.iter() {
+                let marker_model_mat = Mat4::from_scale(&math::vec3((MARKER_RADIUS, MARKER_RADIUS, MARKER_RADIUS)))
+                    .then_translate(&marker.position);
+                gl::Uniform3fv(colour_location, 1, MARKER_COLOURS[marker.colour].as_ptr());
+                gl::UniformMatrix4fv(model_mat_location, 1, gl::FALSE, marker_model_mat.as_ptr());
+                gl::DrawArrays(gl::TRIANGLES, 0, g_point_count as i32);
+            }
         }
 
         // Update other events like input handling.
-        glfw.poll_events();
+        context.glfw.poll_events();
 
         // control keys
         let mut cam_moved = false;
@@ -281,113 +337,119 @@ fn main() {
         let mut cam_yaw: f32 = 0.0; // y-rotation in degrees
         let mut cam_pitch: f32 = 0.0;
         let mut cam_roll: f32 = 0.0;
-        match g_window.get_key(Key::A) {
+        match context.window.get_key(Key::A) {
             Action::Press | Action::Repeat => {
                 move_to.v[0] -= (cam_speed as f32) * (elapsed_seconds as f32);
                 cam_moved = true;
             }
             _ => {}
         }
-        match g_window.get_key(Key::D) {
+        match context.window.get_key(Key::D) {
             Action::Press | Action::Repeat => {
                 move_to.v[0] += (cam_speed as f32) * (elapsed_seconds as f32);
                 cam_moved = true;
             }
             _ => {}
         }       
-        match g_window.get_key(Key::Q) {
+        match context.window.get_key(Key::Q) {
             Action::Press | Action::Repeat => {
                 move_to.v[1] += (cam_speed as f32) * (elapsed_seconds as f32);
                 cam_moved = true;
             }
             _ => {}
         }
-        match g_window.get_key(Key::E) {
+        match context.window.get_key(Key::E) {
             Action::Press | Action::Repeat => {
                 move_to.v[1] -= (cam_speed as f32) * (elapsed_seconds as f32);
                 cam_moved = true;
             }
             _ => {}
         }
-        match g_window.get_key(Key::W) {
+        match context.window.get_key(Key::W) {
             Action::Press | Action::Repeat => {
                 move_to.v[2] -= (cam_speed as f32) * (elapsed_seconds as f32);
                 cam_moved = true;
             }
             _ => {}
         }
-        match g_window.get_key(Key::S) {
+        match context.window.get_key(Key::S) {
             Action::Press | Action::Repeat => {
                 move_to.v[2] += (cam_speed as f32) * (elapsed_seconds as f32);
                 cam_moved = true;
             }
             _ => {}
         }
-        match g_window.get_key(Key::Left) {
+        match context.window.get_key(Key::Left) {
             Action::Press | Action::Repeat => {
                 cam_yaw += (cam_heading_speed as f32) * (elapsed_seconds as f32);
                 cam_moved = true;
-                let mut q_yaw = Versor::from_axis_deg(cam_yaw, up.v[0], up.v[1], up.v[2]);
-                q = q_yaw * &q;
+                let q_yaw = Versor::from_axis_deg(cam_yaw, up.v[0], up.v[1], up.v[2]);
+                q = q_yaw * q;
             }
             _ => {}
         }
-        match g_window.get_key(Key::Right) {
+        match context.window.get_key(Key::Right) {
             Action::Press | Action::Repeat => {
                 cam_yaw -= (cam_heading_speed as f32) * (elapsed_seconds as f32);
                 cam_moved = true;
-                let mut q_yaw = Versor::from_axis_deg(cam_yaw, up.v[0], up.v[1], up.v[2]);
-                q = q_yaw * &q;
+                let q_yaw = Versor::from_axis_deg(cam_yaw, up.v[0], up.v[1], up.v[2]);
+                q = q_yaw * q;
             }
             _ => {}
         }
-        match g_window.get_key(Key::Up) {
+        match context.window.get_key(Key::Up) {
             Action::Press | Action::Repeat => {
                 cam_pitch += (cam_heading_speed as f32) * (elapsed_seconds as f32);
                 cam_moved = true;
-                let mut q_pitch = Versor::from_axis_deg(cam_pitch, rgt.v[0], rgt.v[1], rgt.v[2]);
-                q = q_pitch * &q;
+                let q_pitch = Versor::from_axis_deg(cam_pitch, rgt.v[0], rgt.v[1], rgt.v[2]);
+                q = q_pitch * q;
             }
             _ => {}
         }
-        match g_window.get_key(Key::Down) {
+        match context.window.get_key(Key::Down) {
             Action::Press | Action::Repeat => {
                 cam_pitch -= (cam_heading_speed as f32) * (elapsed_seconds as f32);
                 cam_moved = true;
-                let mut q_pitch = Versor::from_axis_deg(cam_pitch, rgt.v[0], rgt.v[1], rgt.v[2]);
-                q = q_pitch * &q;
+                let q_pitch = Versor::from_axis_deg(cam_pitch, rgt.v[0], rgt.v[1], rgt.v[2]);
+                q = q_pitch * q;
             }
             _ => {}
         }
-        match g_window.get_key(Key::Z) {
+        match context.window.get_key(Key::Z) {
             Action::Press | Action::Repeat => {
                 cam_roll -= (cam_heading_speed as f32) * (elapsed_seconds as f32);
                 cam_moved = true;
-                let mut q_roll = Versor::from_axis_deg(cam_roll, fwd.v[0], fwd.v[1], fwd.v[2]);
-                q = q_roll * &q;
+                let q_roll = Versor::from_axis_deg(cam_roll, fwd.v[0], fwd.v[1], fwd.v[2]);
+                q = q_roll * q;
             }
             _ => {}
         }
-        match g_window.get_key(Key::C) {
+        match context.window.get_key(Key::C) {
             Action::Press | Action::Repeat => {
                 cam_roll += (cam_heading_speed as f32) * (elapsed_seconds as f32);
                 cam_moved = true;
-                let mut q_roll = Versor::from_axis_deg(cam_roll, fwd.v[0], fwd.v[1], fwd.v[2]);
-                q = q_roll * &q;
+                let q_roll = Versor::from_axis_deg(cam_roll, fwd.v[0], fwd.v[1], fwd.v[2]);
+                q = q_roll * q;
             }
             _ => {}
         }
 
         // Handle mouse clicks.
-        match g_window.get_mouse_button(glfw::MouseButtonLeft) {
+        match context.window.get_mouse_button(glfw::MouseButtonLeft) {
             Action::Press => {
-                glfw_mouse_click_callback(
-                    &mut g_window, 
-                    glfw::MouseButtonLeft, Action::Press, 
-                    &proj_mat, &view_mat, cam_pos, &sphere_pos_wor
-                );
+                if !mouse_was_down {
+                    glfw_mouse_click_callback(
+                        &context, 
+                        glfw::MouseButtonLeft, Action::Press, 
+                        &proj_mat, &view_mat, cam_pos, &sphere_pos_wor, &ground,
+                        &mut markers, &sounds, &mut selected_sphere
+                    );
+                }
+                mouse_was_down = true;
+            }
+            _ => {
+                mouse_was_down = false;
             }
-            _ => {}
         }
 
         // Update view matrix.
@@ -397,14 +459,14 @@ fn main() {
             // R = quat_to_mat4( q );
             q.to_mut_mat4(&mut mat_rot);
 
-            fwd = mat_rot * math::vec4((0.0, 0.0, -1.0, 0.0));
-            rgt = mat_rot * math::vec4((1.0, 0.0, 0.0, 0.0));
-            up  = mat_rot * math::vec4((0.0, 1.0, 0.0, 0.0));
+            fwd = q.rotate_vec3(&math::vec3((0.0, 0.0, -1.0)));
+            rgt = q.rotate_vec3(&math::vec3((1.0, 0.0, 0.0)));
+            up  = q.rotate_vec3(&math::vec3((0.0, 1.0, 0.0)));
 
-            cam_pos = cam_pos + math::vec3(fwd) * -move_to.v[2];
-            cam_pos = cam_pos + math::vec3(up)  *  move_to.v[1];
-            cam_pos = cam_pos + math::vec3(rgt) *  move_to.v[0];
-            mat_trans = Mat4::translate(&Mat4::identity(), &math::vec3(cam_pos));
+            cam_pos = cam_pos + fwd * -move_to.v[2];
+            cam_pos = cam_pos + up  *  move_to.v[1];
+            cam_pos = cam_pos + rgt *  move_to.v[0];
+            mat_trans = Mat4::from_translation(&math::vec3(cam_pos));
 
             view_mat = mat_rot.inverse() * mat_trans.inverse();
             unsafe {
@@ -412,13 +474,13 @@ fn main() {
             }
         }
 
-        match g_window.get_key(Key::Escape) {
+        match context.window.get_key(Key::Escape) {
             Action::Press | Action::Repeat => {
-                g_window.set_should_close(true);
+                context.window.set_should_close(true);
             }
             _ => {}
         }
 
-        g_window.swap_buffers();
+        context.window.swap_buffers();
     }
 }
@@ -0,0 +1,214 @@
+use graphics_math as math;
+use math::{Mat4, Vec3};
+
+
+///
+/// Rays running closer to parallel with a plane than this are treated as never
+/// reaching it.
+///
+const EPSILON: f32 = 1e-6;
+
+
+///
+/// A half-line starting at `origin` and running along `direction`, which is unit
+/// length. The point `t` units along it is `origin + direction * t`.
+///
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Ray {
+    pub origin: Vec3,
+    pub direction: Vec3,
+}
+
+impl Ray {
+    ///
+    /// The ray from `origin` along `direction`. The direction does not need to be
+    /// unit length.
+    ///
+    pub fn new(origin: Vec3, direction: Vec3) -> Ray {
+        Ray {
+            origin: origin,
+            direction: direction.normalize(),
+        }
+    }
+
+    pub fn at(&self, t: f32) -> Vec3 {
+        self.origin + self.direction * t
+    }
+}
+
+///
+/// An infinite plane, made of the points `p` with `normal.dot(p) == distance`.
+///
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Plane {
+    pub normal: Vec3,
+    pub distance: f32,
+}
+
+impl Plane {
+    ///
+    /// The plane through `point` facing along `normal`. The normal does not
+    /// need to be unit length.
+    ///
+    pub fn from_point_normal(point: &Vec3, normal: &Vec3) -> Plane {
+        let normal = normal.normalize();
+        Plane {
+            normal: normal,
+            distance: normal.dot(point),
+        }
+    }
+}
+
+///
+/// Take the mouse position on screen and return the ray cast from the camera at
+/// `cam_pos` into the scene through it, in world space coordinates.
+///
+pub fn ray_from_mouse(
+    width: u32, height: u32, proj_mat: &Mat4, view_mat: &Mat4, cam_pos: Vec3, mouse_x: f32, mouse_y: f32) -> Ray {
+    let view_proj = *proj_mat * view_mat;
+    let viewport = [0, 0, width as i32, height as i32];
+    // The mouse is measured down from the top of the window, and GL measures up
+    // from the bottom.
+    let y = height as f32 - mouse_y;
+    // The points under the mouse on the near and far planes, in world space.
+    let near_wor = view_proj.unproject(&math::vec3((mouse_x, y, 0.0)), &viewport);
+    let far_wor = view_proj.unproject(&math::vec3((mouse_x, y, 1.0)), &viewport);
+
+    // The ray direction gets normalized here.
+    Ray::new(cam_pos, far_wor - near_wor)
+}
+
+///
+/// Determine if a ray and a sphere intersect, and return the distance along the
+/// ray to the closest intersection. It rejects intersections behind the ray's
+/// origin.
+///
+pub fn ray_sphere(ray: &Ray, sphere_centre_wor: Vec3, sphere_radius: f32) -> Option<f32> {
+    // Work out components of quadratic.
+    let dist_to_sphere = ray.origin - sphere_centre_wor;
+    let b = ray.direction.dot(&dist_to_sphere);
+    let c = dist_to_sphere.dot(&dist_to_sphere) - sphere_radius * sphere_radius;
+    let b_squared_minus_c = b * b - c;
+    // Check for "imaginary" answer. == ray completely misses sphere
+    if b_squared_minus_c < 0.0 {
+        return None;
+    }
+    // Check whether the ray hits the sphere twice (into and out of the sphere).
+    if b_squared_minus_c > 0.0 {
+        // Get the 2 intersection distances along the ray.
+        let t_a = -b + f32::sqrt(b_squared_minus_c);
+        let t_b = -b - f32::sqrt(b_squared_minus_c);
+        // If the object is behind the viewer, throw one or both away.
+        if t_a < 0.0 {
+            return None;
+        } else if t_b < 0.0 {
+            return Some(t_a);
+        }
+
+        return Some(t_b);
+    }
+    // The ray skims the surface (i.e. it hits at one point). If it hits behind
+    // the viewer, throw away the ray.
+    let t = -b;
+    if t < 0.0 {
+        return None;
+    }
+
+    Some(t)
+}
+
+///
+/// Find where a ray meets a plane, from either side. A ray running parallel to
+/// the plane never meets it, and neither does one pointing away from it.
+///
+pub fn ray_plane(ray: &Ray, plane: &Plane) -> Option<Vec3> {
+    let facing = plane.normal.dot(&ray.direction);
+    if f32::abs(facing) < EPSILON {
+        return None;
+    }
+    let t = (plane.distance - plane.normal.dot(&ray.origin)) / facing;
+    if t < 0.0 {
+        return None;
+    }
+
+    Some(ray.at(t))
+}
+
+mod picking_tests {
+    use super::{ray_from_mouse, ray_plane, ray_sphere, Plane, Ray};
+    use graphics_math as math;
+    use math::Mat4;
+
+    const EPSILON: f32 = 1e-5;
+
+    fn ground() -> Plane {
+        Plane::from_point_normal(&math::vec3((0.0, -1.0, 0.0)), &math::vec3((0.0, 2.0, 0.0)))
+    }
+
+    #[test]
+    fn test_a_ray_looking_down_hits_the_ground_below() {
+        let ray = Ray::new(math::vec3((1.0, 4.0, 2.0)), math::vec3((1.0, -1.0, 0.0)));
+
+        let hit = ray_plane(&ray, &ground()).unwrap();
+
+        assert!((hit - math::vec3((6.0, -1.0, 2.0))).norm() < EPSILON, "{:?}", hit);
+    }
+
+    #[test]
+    fn test_a_ray_from_below_hits_the_ground_too() {
+        let ray = Ray::new(math::vec3((0.0, -3.0, 0.0)), math::vec3((0.0, 1.0, 1.0)));
+
+        let hit = ray_plane(&ray, &ground()).unwrap();
+
+        assert!((hit - math::vec3((0.0, -1.0, 2.0))).norm() < EPSILON, "{:?}", hit);
+    }
+
+    #[test]
+    fn test_rays_parallel_to_or_leaving_the_plane_miss_it() {
+        let level = Ray::new(math::vec3((0.0, 1.0, 0.0)), math::vec3((1.0, 0.0, 0.0)));
+        let looking_up = Ray::new(math::vec3((0.0, 1.0, 0.0)), math::vec3((0.0, 1.0, -1.0)));
+
+        assert_eq!(ray_plane(&level, &ground()), None);
+        assert_eq!(ray_plane(&looking_up, &ground()), None);
+    }
+
+    #[test]
+    fn test_ray_sphere_finds_the_nearest_hit_in_front() {
+        let ray = Ray::new(math::vec3((0.0, 0.0, 5.0)), math::vec3((0.0, 0.0, -1.0)));
+        let centre = math::vec3((0.0, 0.0, 0.0));
+
+        assert!((ray_sphere(&ray, centre, 1.0).unwrap() - 4.0).abs() < EPSILON);
+        // from inside the sphere, the only hit in front is on the way out
+        let inside = Ray::new(centre, math::vec3((0.0, 0.0, -1.0)));
+        assert!((ray_sphere(&inside, centre, 1.0).unwrap() - 1.0).abs() < EPSILON);
+        // a sphere behind the ray is never hit
+        let away = Ray::new(math::vec3((0.0, 0.0, 5.0)), math::vec3((0.0, 0.0, 1.0)));
+        assert_eq!(ray_sphere(&away, centre, 1.0), None);
+    }
+
+    #[test]
+    fn test_the_mouse_ray_through_the_middle_of_the_screen_looks_ahead() {
+        let proj_mat = Mat4::perspective(67.0, 4.0 / 3.0, 0.1, 100.0);
+        let cam_pos = math::vec3((0.0, 2.0, 5.0));
+        let view_mat = Mat4::from_translation(&math::vec3((0.0, -2.0, -5.0)));
+
+        let ray = ray_from_mouse(640, 480, &proj_mat, &view_mat, cam_pos, 320.0, 240.0);
+
+        assert_eq!(ray.origin, cam_pos);
+        assert!((ray.direction - math::vec3((0.0, 0.0, -1.0))).norm() < EPSILON, "{:?}", ray.direction);
+    }
+
+    #[test]
+    fn test_the_mouse_ray_through_the_top_left_corner_runs_along_the_frustum_edge() {
+        let proj_mat = Mat4::perspective(67.0, 4.0 / 3.0, 0.1, 100.0);
+        let cam_pos = math::vec3((1.0, 2.0, 5.0));
+        let view_mat = Mat4::from_translation(&-cam_pos).rotate_y_deg(20.0);
+
+        let ray = ray_from_mouse(640, 480, &proj_mat, &view_mat, cam_pos, 0.0, 0.0);
+
+        // the near plane's top left corner is the third
+        let corner = math::frustum_corners_world(&view_mat, &proj_mat)[2];
+        let edge = (corner - cam_pos).normalize();
+        assert!((ray.direction - edge).norm() < 1e-4, "{:?} != {:?}", ray.direction, edge);
+    }
+}
@@ -0,0 +1,115 @@
+#[cfg(feature = "audio")]
+use rodio;
+#[cfg(feature = "audio")]
+use rodio::Source;
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Read;
+#[cfg(feature = "audio")]
+use std::io::Cursor;
+
+
+///
+/// Plays short sound effects by name. Sounds are loaded up front and kept in
+/// memory as encoded files, so playing one never touches the disk.
+///
+/// Sound output only happens when the demo is built with the `audio` feature.
+/// Without it, or when there is no output device, playing a loaded sound does
+/// nothing, so the demos behave the same apart from the silence.
+///
+pub struct SoundPlayer {
+    #[cfg(feature = "audio")]
+    device: Option<rodio::Device>,
+    sounds: HashMap<String, Vec<u8>>,
+}
+
+impl SoundPlayer {
+    pub fn new() -> SoundPlayer {
+        SoundPlayer {
+            #[cfg(feature = "audio")]
+            device: rodio::default_output_device(),
+            sounds: HashMap::new(),
+        }
+    }
+
+    ///
+    /// Read a sound file (WAV, Vorbis, or FLAC) and make it playable as `name`.
+    ///
+    pub fn load(&mut self, name: &str, file_name: &str) -> Result<(), String> {
+        let mut file = match File::open(file_name) {
+            Ok(val) => val,
+            Err(e) => {
+                return Err(format!("ERROR: could not open sound file {}: {}", file_name, e));
+            }
+        };
+        let mut bytes = Vec::new();
+        if let Err(e) = file.read_to_end(&mut bytes) {
+            return Err(format!("ERROR: could not read sound file {}: {}", file_name, e));
+        }
+        self.sounds.insert(String::from(name), bytes);
+
+        Ok(())
+    }
+
+    ///
+    /// Start playing the sound loaded as `name`. This returns straight away; the
+    /// sound plays in the background and overlaps any sounds already playing.
+    ///
+    pub fn play(&self, name: &str) -> Result<(), String> {
+        let bytes = match self.sounds.get(name) {
+            Some(val) => val,
+            None => return Err(format!("ERROR: no sound named {} has been loaded", name)),
+        };
+
+        self.play_bytes(name, bytes)
+    }
+
+    #[cfg(feature = "audio")]
+    fn play_bytes(&self, name: &str, bytes: &[u8]) -> Result<(), String> {
+        let device = match self.device {
+            Some(ref val) => val,
+            None => return Ok(()),
+        };
+        let source = match rodio::Decoder::new(Cursor::new(bytes.to_vec())) {
+            Ok(val) => val,
+            Err(e) => return Err(format!("ERROR: could not decode sound {}: {:?}", name, e)),
+        };
+        rodio::play_raw(device, source.convert_samples());
+
+        Ok(())
+    }
+
+    #[cfg(not(feature = "audio"))]
+    fn play_bytes(&self, _name: &str, _bytes: &[u8]) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+mod sound_tests {
+    use super::SoundPlayer;
+
+    #[test]
+    fn test_loading_a_missing_file_fails() {
+        let mut player = SoundPlayer::new();
+
+        assert!(player.load("missing", "src/no_such_sound.wav").is_err());
+        assert!(player.play("missing").is_err());
+    }
+
+    #[test]
+    fn test_playing_an_unknown_sound_fails() {
+        let player = SoundPlayer::new();
+
+        assert!(player.play("click").is_err());
+    }
+
+    #[test]
+    #[cfg(not(feature = "audio"))]
+    fn test_playing_a_loaded_sound_without_audio_is_silent() {
+        let mut player = SoundPlayer::new();
+
+        assert!(player.load("beep", "src/beep.wav").is_ok());
+        assert!(player.play("beep").is_ok());
+    }
+}
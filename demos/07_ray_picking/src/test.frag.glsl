@@ -2,10 +2,12 @@
 
 in float dist;
 uniform float blue = 0.0;
+uniform vec3 colour = vec3 (1.0, 0.0, 0.0);
 out vec4 frag_colour;
 
 void main() {
-	frag_colour = vec4 (1.0, 0.0, blue, 1.0);
+	frag_colour = vec4 (colour, 1.0);
+	frag_colour.b = max (frag_colour.b, blue);
 	// use z position to shader darker to help perception of distance
 	frag_colour.xyz *= dist;
 }
@@ -1,32 +1,27 @@
 use glfw;
 use glfw::Context;
 use gl;
-use gl::types::{GLubyte, GLuint, GLchar, GLint, GLenum};
+use gl::types::{GLubyte, GLuint, GLchar, GLint, GLenum, GLsizei};
 use chrono::prelude::Utc;
 
+use assets;
+
 use std::string::String;
-use std::ffi::CStr;
+use std::ffi::{CStr, CString};
 use std::ptr;
 use std::fs::{File, OpenOptions};
-use std::io::{Read, Write, BufReader};
+use std::io::{Read, Write};
 use std::fmt::Write as FWrite;
 use std::cell::Cell;
 use std::sync::mpsc::Receiver;
 
 
 const GL_LOG_FILE: &str = "gl.log";
-const MAX_SHADER_LENGTH: usize = 262144;
 
 // Keep track of window size for things like the viewport and the mouse cursor
 const G_GL_WIDTH_DEFAULT: u32 = 640;
 const G_GL_HEIGHT_DEFAULT: u32 = 480;
 
-pub static mut G_GL_WIDTH: u32 = 640;
-pub static mut G_GL_HEIGHT: u32 = 480;
-
-static mut PREVIOUS_SECONDS: f64 = 0.0;
-static mut FRAME_COUNT: usize = 0;
-
 #[inline]
 pub fn glubyte_ptr_to_string(cstr: *const GLubyte) -> String {
     unsafe {
@@ -35,11 +30,9 @@ pub fn glubyte_ptr_to_string(cstr: *const GLubyte) -> String {
 }
 
 // We will tell GLFW to run this function whenever the framebuffer size is changed.
-fn glfw_framebuffer_size_callback(window: &mut glfw::Window, width: u32, height: u32) {
-    unsafe {
-        G_GL_WIDTH = width;
-        G_GL_HEIGHT = height;
-    }
+fn glfw_framebuffer_size_callback(context: &mut GLContext, width: u32, height: u32) {
+    context.width = width;
+    context.height = height;
     println!("width {} height {}", width, height);
     /* Update any perspective matrices used here */
 }
@@ -151,7 +144,39 @@ pub fn log_gl_params() {
     }
 }
 
-pub fn start_gl() -> Result<(glfw::Glfw, glfw::Window, Receiver<(f64, glfw::WindowEvent)>), String> {
+///
+/// The GLFW window and its event queue, together with the state the demos keep
+/// about the window from frame to frame: the framebuffer size, and the timers
+/// behind the frame rate counter.
+///
+pub struct GLContext {
+    pub glfw: glfw::Glfw,
+    pub window: glfw::Window,
+    pub events: Receiver<(f64, glfw::WindowEvent)>,
+    pub width: u32,
+    pub height: u32,
+    pub elapsed_time_seconds: f64,
+    pub framerate_time_seconds: f64,
+    pub frame_count: u32,
+}
+
+impl GLContext {
+    ///
+    /// The framebuffer size in pixels.
+    ///
+    pub fn size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    ///
+    /// The framebuffer width divided by its height, for building projection matrices.
+    ///
+    pub fn aspect_ratio(&self) -> f32 {
+        self.width as f32 / self.height as f32
+    }
+}
+
+pub fn start_gl() -> Result<GLContext, String> {
     // Start a GL context and OS window using the GLFW helper library.
     let mut glfw = glfw::init(glfw::FAIL_ON_ERRORS).unwrap();
 
@@ -197,25 +222,32 @@ pub fn start_gl() -> Result<(glfw::Glfw, glfw::Window, Receiver<(f64, glfw::Wind
     gl_log(&format!("renderer: {}\nversion: {}\n", renderer, version));
     log_gl_params();
 
-    Ok((glfw, window, events))
+    Ok(GLContext {
+        glfw: glfw,
+        window: window,
+        events: events,
+        width: G_GL_WIDTH_DEFAULT,
+        height: G_GL_HEIGHT_DEFAULT,
+        elapsed_time_seconds: 0.0,
+        framerate_time_seconds: 0.0,
+        frame_count: 0,
+    })
 }
 
 // We will use this function to update the window title with a frame rate.
-pub fn _update_fps_counter(glfw: &glfw::Glfw, window: &mut glfw::Window) {
-    unsafe {        
-        let current_seconds = glfw.get_time();
-        let elapsed_seconds = current_seconds - PREVIOUS_SECONDS;
-        if elapsed_seconds > 0.25 {
-            PREVIOUS_SECONDS = current_seconds;
-            let fps = FRAME_COUNT as f64 / elapsed_seconds;
-            let mut title: String = String::new();
-            write!(&mut title, "OpenGL @ FPS: {:.2}", fps).unwrap();
-            window.set_title(&title);
-            FRAME_COUNT = 0;
-        }
-
-        FRAME_COUNT += 1;
+pub fn update_fps_counter(context: &mut GLContext) {
+    let current_seconds = context.glfw.get_time();
+    let elapsed_seconds = current_seconds - context.framerate_time_seconds;
+    if elapsed_seconds > 0.25 {
+        context.framerate_time_seconds = current_seconds;
+        let fps = context.frame_count as f64 / elapsed_seconds;
+        let mut title: String = String::new();
+        write!(&mut title, "OpenGL @ FPS: {:.2}", fps).unwrap();
+        context.window.set_title(&title);
+        context.frame_count = 0;
     }
+
+    context.frame_count += 1;
 }
 
 pub fn gl_type_to_string(gl_type: GLenum) -> &'static str {
@@ -237,39 +269,75 @@ pub fn gl_type_to_string(gl_type: GLenum) -> &'static str {
     }
 }
 
-pub fn parse_file_into_str(file_name: &str, shader_str: &mut [u8], max_len: usize) -> bool {
-    shader_str[0] = 0;
-    let file = File::open(file_name);
-    if file.is_err() {
-        gl_log_err(&format!("ERROR: opening file for reading: {}\n", file_name));
-        return false;
-    }
+///
+/// The location of the uniform called `name` in `program`, or -1 if it has none.
+/// GL reads the name up to a NUL byte, so it is copied into a NUL terminated
+/// string first rather than handing GL the bytes of a Rust string.
+///
+pub fn uniform_location(program: GLuint, name: &str) -> GLint {
+    let c_name = match CString::new(name) {
+        Ok(val) => val,
+        Err(_) => return -1,
+    };
+
+    unsafe { gl::GetUniformLocation(program, c_name.as_ptr()) }
+}
 
-    let file = file.unwrap();
-    let mut reader = BufReader::new(file);
+///
+/// The location of the attribute called `name` in `program`, or -1 if it has none.
+///
+pub fn attrib_location(program: GLuint, name: &str) -> GLint {
+    let c_name = match CString::new(name) {
+        Ok(val) => val,
+        Err(_) => return -1,
+    };
 
-    let bytes_read = reader.read(shader_str);
-    if bytes_read.is_err() {
-        gl_log_err(&format!("ERROR: reading shader file {}\n", file_name));
-        return false;
-    }
+    unsafe { gl::GetAttribLocation(program, c_name.as_ptr()) }
+}
 
-    let bytes_read = bytes_read.unwrap();
-    if bytes_read >= (max_len - 1) {
-        gl_log_err(&format!("WARNING: file {} too big - truncated.\n", file_name));
+///
+/// Read the whole of the shader source file `file_name`, ready to hand to GL.
+/// The source has to be UTF-8 without any NUL bytes in it, since GL would stop
+/// reading it at the first one.
+///
+pub fn read_shader_source(file_name: &str) -> Result<CString, String> {
+    let mut file = match File::open(file_name) {
+        Ok(val) => val,
+        Err(e) => return Err(format!("ERROR: opening file for reading: {}: {}", file_name, e)),
+    };
+
+    let mut bytes = Vec::new();
+    if let Err(e) = file.read_to_end(&mut bytes) {
+        return Err(format!("ERROR: reading shader file {}: {}", file_name, e));
     }
 
-    // append \0 to end of file string.
-    shader_str[bytes_read] = 0;
+    let source = match String::from_utf8(bytes) {
+        Ok(val) => val,
+        Err(e) => return Err(format!("ERROR: shader file {} is not UTF-8: {}", file_name, e.utf8_error())),
+    };
 
-    return true;
+    match CString::new(source) {
+        Ok(val) => Ok(val),
+        Err(e) => Err(format!("ERROR: shader file {} has a NUL byte at {}", file_name, e.nul_position())),
+    }
 }
 
 fn create_shader(file_name: &str, shader: &mut GLuint, gl_type: GLenum) -> bool {
     gl_log(&format!("Creating shader from {}...\n", file_name));
 
-    let mut shader_string = vec![0; MAX_SHADER_LENGTH];
-    parse_file_into_str(file_name, &mut shader_string, MAX_SHADER_LENGTH);
+    let shader_string = match read_shader_source(file_name) {
+        Ok(val) => val,
+        Err(e) => {
+            gl_log_err(&format!("{}\n", e));
+            match assets::fallback_shader_source(gl_type) {
+                Some(source) => {
+                    gl_log_err(&format!("WARNING: using the embedded fallback shader in place of {}\n", file_name));
+                    CString::new(source).unwrap()
+                }
+                None => return false,
+            }
+        }
+    };
 
     *shader = unsafe { gl::CreateShader(gl_type) };
     let p = shader_string.as_ptr() as *const GLchar;
@@ -391,84 +459,150 @@ pub fn create_programme_from_files(vert_file_name: &str, frag_file_name: &str) -
 }
 
 
-/* print absolutely everything about a shader - only useful if you get really
-stuck wondering why a shader isn't working properly */
-pub fn print_all(sp: GLuint) {
-    let mut params = -1;
+///
+/// An attribute or uniform a shader programme uses. Each element of an array is
+/// one of these, with its own name and location.
+///
+#[derive(Clone, Debug, PartialEq)]
+pub struct ActiveVariable {
+    pub name: String,
+    pub gl_type: GLenum,
+    pub location: GLint,
+}
 
-    unsafe {
-        println!("--------------------\nshader programme {} info:", sp);
-        gl::GetProgramiv(sp, gl::LINK_STATUS, &mut params);
-        println!("GL_LINK_STATUS = {}", params);
+///
+/// Absolutely everything GL reports about a shader programme. This is only useful
+/// if you get really stuck wondering why a shader isn't working properly.
+///
+#[derive(Clone, Debug)]
+pub struct ProgramInfo {
+    pub programme: GLuint,
+    pub link_status: bool,
+    pub attached_shaders: GLint,
+    pub attributes: Vec<ActiveVariable>,
+    pub uniforms: Vec<ActiveVariable>,
+    pub info_log: String,
+}
 
-        gl::GetProgramiv(sp, gl::ATTACHED_SHADERS, &mut params);
-        println!("GL_ATTACHED_SHADERS = {}", params);
+impl ProgramInfo {
+    fn report(&self) -> String {
+        let mut report = String::new();
+        writeln!(report, "--------------------\nshader programme {} info:", self.programme).unwrap();
+        writeln!(report, "GL_LINK_STATUS = {}", self.link_status).unwrap();
+        writeln!(report, "GL_ATTACHED_SHADERS = {}", self.attached_shaders).unwrap();
+        for &(title, variables) in [("GL_ACTIVE_ATTRIBUTES", &self.attributes), ("GL_ACTIVE_UNIFORMS", &self.uniforms)].iter() {
+            writeln!(report, "{} = {}", title, variables.len()).unwrap();
+            for (i, variable) in variables.iter().enumerate() {
+                writeln!(
+                    report, "  {}) type:{} name:{} location:{}",
+                    i, gl_type_to_string(variable.gl_type), variable.name, variable.location
+                ).unwrap();
+            }
+        }
+        write!(report, "Program info log for GL index {}:\n{}", self.programme, self.info_log).unwrap();
+
+        report
+    }
 
-        gl::GetProgramiv(sp, gl::ACTIVE_ATTRIBUTES, &mut params);
-        println!("GL_ACTIVE_ATTRIBUTES = {}", params);
+    ///
+    /// Write everything out to the log file.
+    ///
+    pub fn log(&self) {
+        gl_log(&self.report());
     }
+}
+
+// The longest attribute or uniform name read back from GL, NUL included.
+const MAX_VARIABLE_NAME_LENGTH: usize = 256;
+
+///
+/// The names of the elements of an attribute or uniform GL reports with `size`
+/// elements. GL reports an array once, under the name of its first element, as in
+/// `lights[0]`.
+///
+fn array_element_names(name: &str, size: GLint) -> Vec<String> {
+    if size <= 1 {
+        return vec![String::from(name)];
+    }
+
+    let base_name = if name.ends_with("[0]") { &name[..name.len() - 3] } else { name };
 
-    for i in 0..params {
-        let mut name = [0; 64];
-        let max_length = 64;
-        let mut actual_length = 0;
+    (0..size).map(|i| format!("{}[{}]", base_name, i)).collect()
+}
+
+///
+/// Read `count` attributes or uniforms. `get_active` fills in the size, type, and
+/// name of the one at an index, and gives the length of its name. Each element of
+/// an array gets its location from `location_of` under its own name.
+///
+fn active_variables<F, G>(count: GLint, get_active: F, location_of: G) -> Vec<ActiveVariable>
+    where F: Fn(GLuint, &mut GLint, &mut GLenum, &mut [u8]) -> usize,
+          G: Fn(&str) -> GLint
+{
+    let mut variables = vec![];
+    for i in 0..count {
+        let mut name = [0u8; MAX_VARIABLE_NAME_LENGTH];
         let mut size = 0;
-        let mut gl_type: GLenum = 0;
-        unsafe {
-            gl::GetActiveAttrib(sp, i as GLuint, max_length, &mut actual_length, &mut size, &mut gl_type, &mut name[0]);
-        }
-        if size > 1 {
-            for j in 0..size {
-                let mut long_name = vec![];
-                //write!(long_name, "{}[{}]", name, j);
-                let location = unsafe { gl::GetAttribLocation(sp, long_name.as_ptr() as *const i8) };
-                println!(
-                    "  {}) type:{} name:{} location:{}", 
-                    i, gl_type_to_string(gl_type), long_name.iter().map(|ch| *ch as u8 as char).collect::<String>(), location
-                );
-            }
-        } else {
-            let location = unsafe { gl::GetAttribLocation(sp, &mut name[0]) };
-            println!(
-                "  {}) type:{} name:{} location:{}",
-                i, gl_type_to_string(gl_type), name.iter().map(|ch| *ch as u8 as char).collect::<String>(), location
-            );
+        let mut gl_type = 0;
+        let length = get_active(i as GLuint, &mut size, &mut gl_type, &mut name);
+        let name = String::from_utf8_lossy(&name[..length]).into_owned();
+        for element_name in array_element_names(&name, size) {
+            let location = location_of(&element_name);
+            variables.push(ActiveVariable { name: element_name, gl_type: gl_type, location: location });
         }
     }
-    
+
+    variables
+}
+
+fn programme_info_log(sp: GLuint) -> String {
+    let mut log = [0u8; 2048];
+    let mut actual_length = 0;
     unsafe {
-        gl::GetProgramiv(sp, gl::ACTIVE_UNIFORMS, &mut params);
+        gl::GetProgramInfoLog(sp, log.len() as GLsizei, &mut actual_length, log.as_mut_ptr() as *mut GLchar);
     }
-    println!("GL_ACTIVE_UNIFORMS = {}", params);
-    for i in 0..params {
-        let mut name = [0; 64];
-        let max_length = 64;
-        let mut actual_length = 0;
-        let mut size = 0;
-        let mut gl_type: GLenum = 0;
+
+    String::from_utf8_lossy(&log[..actual_length as usize]).into_owned()
+}
+
+///
+/// Ask GL about the shader programme `sp`: whether it linked, and the type and
+/// location of every attribute and uniform it uses.
+///
+pub fn introspect(sp: GLuint) -> ProgramInfo {
+    let mut link_status = -1;
+    let mut attached_shaders = 0;
+    let mut attribute_count = 0;
+    let mut uniform_count = 0;
+    unsafe {
+        gl::GetProgramiv(sp, gl::LINK_STATUS, &mut link_status);
+        gl::GetProgramiv(sp, gl::ATTACHED_SHADERS, &mut attached_shaders);
+        gl::GetProgramiv(sp, gl::ACTIVE_ATTRIBUTES, &mut attribute_count);
+        gl::GetProgramiv(sp, gl::ACTIVE_UNIFORMS, &mut uniform_count);
+    }
+
+    let attributes = active_variables(attribute_count, |i, size, gl_type, name| {
+        let mut length = 0;
         unsafe {
-            gl::GetActiveUniform(sp, i as u32, max_length, &mut actual_length, &mut size, &mut gl_type, &mut name[0]);
+            gl::GetActiveAttrib(sp, i, name.len() as GLsizei, &mut length, size, gl_type, name.as_mut_ptr() as *mut GLchar);
         }
-        if size > 1 {
-            for j in 0..size {
-                let long_name = [0; 64];
-
-                //write!(long_name, "{}[{}]", name, j);
-                let location = unsafe { gl::GetUniformLocation(sp, long_name.as_ptr()) };
-                println!(
-                    "  {}) type:{} name:{} location:{}",
-                    i, gl_type_to_string(gl_type), long_name.iter().map(|ch| *ch as u8 as char).collect::<String>(), location
-                );
-            }
-        } else {
-            let location = unsafe { gl::GetUniformLocation(sp, &name[0]) };
-            println!(
-                "  {}) type:{} name:{} location:{}", 
-                i, gl_type_to_string(gl_type), name.iter().map(|ch| *ch as u8 as char).collect::<String>(), location
-            );
+        length as usize
+    }, |name| attrib_location(sp, name));
+    let uniforms = active_variables(uniform_count, |i, size, gl_type, name| {
+        let mut length = 0;
+        unsafe {
+            gl::GetActiveUniform(sp, i, name.len() as GLsizei, &mut length, size, gl_type, name.as_mut_ptr() as *mut GLchar);
         }
+        length as usize
+    }, |name| uniform_location(sp, name));
+
+    ProgramInfo {
+        programme: sp,
+        link_status: link_status == gl::TRUE as GLint,
+        attached_shaders: attached_shaders,
+        attributes: attributes,
+        uniforms: uniforms,
+        info_log: programme_info_log(sp),
     }
-
-    print_programme_info_log(sp);
 }
 
@@ -0,0 +1,9 @@
+#version 410
+
+in vec3 colour;
+
+out vec4 fragment_colour; // final colour of surface
+
+void main () {
+	fragment_colour = vec4 (colour, 1.0);
+}
@@ -0,0 +1,45 @@
+#version 410
+
+layout (location = 0) in vec3 vertex_position;
+layout (location = 1) in vec3 vertex_normal;
+uniform mat4 projection_mat, view_mat, model_mat;
+
+// point light properties
+uniform vec3 light_position_world;
+uniform vec3 Ls; // specular colour
+uniform vec3 Ld; // diffuse light colour
+uniform vec3 La; // ambient colour
+
+// surface reflectance
+uniform vec3 Ks; // specular reflectance
+uniform vec3 Kd; // diffuse surface reflectance
+uniform vec3 Ka; // ambient reflectance
+uniform float specular_exponent; // specular 'power'
+
+// the lighting is worked out here, once per vertex, and the resulting colour is
+// interpolated across the triangle
+out vec3 colour;
+
+void main () {
+	vec3 position_eye = vec3 (view_mat * model_mat * vec4 (vertex_position, 1.0));
+	vec3 n_eye = normalize (vec3 (view_mat * model_mat * vec4 (vertex_normal, 0.0)));
+
+	// ambient intensity
+	vec3 Ia = La * Ka;
+
+	// diffuse intensity
+	vec3 light_position_eye = vec3 (view_mat * vec4 (light_position_world, 1.0));
+	vec3 direction_to_light_eye = normalize (light_position_eye - position_eye);
+	float dot_prod = max (dot (direction_to_light_eye, n_eye), 0.0);
+	vec3 Id = Ld * Kd * dot_prod; // final diffuse intensity
+
+	// specular intensity (blinn)
+	vec3 surface_to_viewer_eye = normalize (-position_eye);
+	vec3 half_way_eye = normalize (surface_to_viewer_eye + direction_to_light_eye);
+	float dot_prod_specular = max (dot (half_way_eye, n_eye), 0.0);
+	float specular_factor = pow (dot_prod_specular, specular_exponent);
+	vec3 Is = Ls * Ks * specular_factor; // final specular intensity
+
+	colour = Is + Id + Ia;
+	gl_Position = projection_mat * vec4 (position_eye, 1.0);
+}
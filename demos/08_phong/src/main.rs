@@ -1,34 +1,101 @@
 extern crate gl;
 extern crate glfw;
 extern crate chrono;
-
-#[macro_use] 
-extern crate scan_fmt;
+#[cfg(feature = "glam")]
+extern crate glam;
+#[cfg(feature = "cgmath")]
+extern crate cgmath;
 
 mod gl_utils;
 mod graphics_math;
 mod obj_parser;
+mod assets;
+mod colour;
+mod material;
+mod vertex_layout;
 
 
 use glfw::{Action, Context, Key};
-use gl::types::{GLfloat, GLsizeiptr, GLvoid, GLsizei};
-
-use std::mem;
-use std::ptr;
+use gl::types::{GLfloat, GLint, GLsizei, GLuint};
 
 use gl_utils::*;
 
 use graphics_math as math;
 use math::Mat4;
 
-const VERTEX_SHADER_FILE: &str = "src/test.vert.glsl";
-const FRAGMENT_SHADER_FILE: &str = "src/test.frag.glsl";
+use colour::Colour;
+use obj_parser::Material;
+use material::{MaterialBinder, MaterialTextures};
+use vertex_layout::VertexLayout;
+
+const PHONG_VERTEX_SHADER_FILE: &str = "src/phong.vert.glsl";
+const PHONG_FRAGMENT_SHADER_FILE: &str = "src/phong.frag.glsl";
+const GOURAUD_VERTEX_SHADER_FILE: &str = "src/gouraud.vert.glsl";
+const GOURAUD_FRAGMENT_SHADER_FILE: &str = "src/gouraud.frag.glsl";
+const MATERIAL_FILE: &str = "src/phong.mtl";
+
+// fixed point light properties
+const LIGHT_POSITION_WORLD: [GLfloat; 3] = [0.0, 0.0, 2.0];
+const LS: Colour = Colour::WHITE; // white specular colour
+const LD: Colour = Colour { r: 0.7, g: 0.7, b: 0.7, a: 1.0 }; // dull white diffuse light colour
+const LA: Colour = Colour::DARK_GREY; // grey ambient colour
+const BACKGROUND_COLOUR: Colour = Colour::DARK_GREY;
+
+
+///
+/// A lighting shader programme and the locations of its matrix uniforms.
+///
+struct LightingProgramme {
+    sp: GLuint,
+    view_mat_location: GLint,
+    proj_mat_location: GLint,
+    model_mat_location: GLint,
+}
+
+fn get_uniform_location(sp: GLuint, name: &str) -> GLint {
+    let location = uniform_location(sp, name);
+    assert!(location != -1);
+
+    location
+}
+
+///
+/// Build a lighting shader programme and upload the camera matrices along with
+/// the light and material properties to it.
+///
+fn create_lighting_programme(
+    vert_file_name: &str, frag_file_name: &str,
+    view_mat: &Mat4, proj_mat: &Mat4, model_mat: &Mat4, material: &Material) -> LightingProgramme {
+
+    let sp = create_programme_from_files(vert_file_name, frag_file_name);
+    let programme = LightingProgramme {
+        sp: sp,
+        view_mat_location: get_uniform_location(sp, "view_mat"),
+        proj_mat_location: get_uniform_location(sp, "projection_mat"),
+        model_mat_location: get_uniform_location(sp, "model_mat"),
+    };
+
+    unsafe {
+        gl::UseProgram(sp);
+        gl::UniformMatrix4fv(programme.view_mat_location, 1, gl::FALSE, view_mat.as_ptr());
+        gl::UniformMatrix4fv(programme.proj_mat_location, 1, gl::FALSE, proj_mat.as_ptr());
+        gl::UniformMatrix4fv(programme.model_mat_location, 1, gl::FALSE, model_mat.as_ptr());
+
+        gl::Uniform3fv(get_uniform_location(sp, "light_position_world"), 1, LIGHT_POSITION_WORLD.as_ptr());
+        gl::Uniform3fv(get_uniform_location(sp, "Ls"), 1, LS.rgb().as_ptr());
+        gl::Uniform3fv(get_uniform_location(sp, "Ld"), 1, LD.rgb().as_ptr());
+        gl::Uniform3fv(get_uniform_location(sp, "La"), 1, LA.rgb().as_ptr());
+    }
+    MaterialBinder::new(sp).bind(material, &MaterialTextures::default());
+
+    programme
+}
 
 
 fn main() {
     restart_gl_log();
     // start GL context and O/S window using the GLFW helper library
-    let (mut glfw, mut g_window, mut _g_events) = start_gl().unwrap();
+    let mut context = start_gl().unwrap();
     // tell GL to only draw onto a pixel if the shape is closer to the viewer
 
     /* OTHER STUFF GOES HERE NEXT */
@@ -43,86 +110,56 @@ fn main() {
         0.0, 0.0, 1.0, 0.0, 0.0, 1.0, 0.0, 0.0, 1.0,
     ];
 
-    let mut points_vbo = 0;
-    unsafe {
-        gl::GenBuffers(1, &mut points_vbo);
-        gl::BindBuffer(gl::ARRAY_BUFFER, points_vbo);
-        gl::BufferData(
-            gl::ARRAY_BUFFER, (points.len() * mem::size_of::<GLfloat>()) as GLsizeiptr, 
-            points.as_ptr() as *const GLvoid, gl::STATIC_DRAW
-        );
-    }
-    assert!(points_vbo != 0);
-
-    let mut normals_vbo = 0;
-    unsafe {
-        gl::GenBuffers(1, &mut normals_vbo);
-        gl::BindBuffer(gl::ARRAY_BUFFER, normals_vbo);
-        gl::BufferData(
-            gl::ARRAY_BUFFER, (normals.len() * mem::size_of::<GLfloat>()) as GLsizeiptr, 
-            normals.as_ptr() as *const GLvoid, gl::STATIC_DRAW
-        );
-    }
-    assert!(normals_vbo != 0);
-
+    let point_count = points.len() / 3;
     let mut vao = 0;
     unsafe {
         gl::GenVertexArrays(1, &mut vao);
-        gl::BindVertexArray(vao);
-        gl::BindBuffer(gl::ARRAY_BUFFER, points_vbo);
-        gl::VertexAttribPointer(0, 3, gl::FLOAT, gl::FALSE, 0, ptr::null());
-        gl::BindBuffer(gl::ARRAY_BUFFER, normals_vbo);
-        gl::VertexAttribPointer(1, 3, gl::FLOAT, gl::FALSE, 0, ptr::null());
-        gl::EnableVertexAttribArray(0);
-        gl::EnableVertexAttribArray(1);
     }
     assert!(vao != 0);
-
-    let shader_programme = create_programme_from_files(VERTEX_SHADER_FILE, FRAGMENT_SHADER_FILE);
+    VertexLayout::new().floats(0, 3).upload(vao, &points, point_count);
+    VertexLayout::new().floats(1, 3).upload(vao, &normals, point_count);
 
     // input variables
     let near = 0.1;                                  // clipping plane
     let far = 100.0;                                 // clipping plane
     let fov = 67.0;                                  // 67 degrees to radians
-    let aspect = unsafe { G_GL_WIDTH as f32 / G_GL_HEIGHT as f32 }; // aspect ratio
+    let aspect = context.aspect_ratio(); // aspect ratio
     // matrix components
     let proj_mat = Mat4::perspective(fov, aspect, near, far);
 
     /* create VIEW MATRIX */
     let cam_pos = [0.0, 0.0, 2.0];   // don't start at zero, or we will be too close
     let cam_yaw = 0.0;               // y-rotation in degrees
-    let mat_trans = Mat4::identity().translate(&math::vec3((-cam_pos[0], -cam_pos[1], -cam_pos[2])));
-    let mat_rot = Mat4::identity().rotate_y_deg(-cam_yaw);
+    let mat_trans = Mat4::from_translation(&math::vec3((-cam_pos[0], -cam_pos[1], -cam_pos[2])));
+    let mat_rot = Mat4::from_rotation_y_deg(-cam_yaw);
     let view_mat = mat_rot * mat_trans;
 
     /* matrix for moving the triangle */
     let mut model_mat = Mat4::identity();
 
-    unsafe {
-        gl::UseProgram(shader_programme);
-    }
-        
-    let view_mat_location = unsafe {
-        gl::GetUniformLocation(shader_programme, "view_mat".as_ptr() as *const i8) 
-    };
-    assert!(view_mat_location != -1);
-    unsafe {    
-        gl::UniformMatrix4fv(view_mat_location, 1, gl::FALSE, view_mat.as_ptr());
-    }
-    let proj_mat_location = unsafe { 
-        gl::GetUniformLocation(shader_programme, "projection_mat".as_ptr() as *const i8)
-    };
-    assert!(proj_mat_location != -1);
-    unsafe {    
-        gl::UniformMatrix4fv(proj_mat_location, 1, gl::FALSE, proj_mat.as_ptr());
-    }    
-    let model_mat_location = unsafe { 
-        gl::GetUniformLocation(shader_programme, "model_mat".as_ptr() as *const i8)
+    /* surface properties of the triangle */
+    let material = match obj_parser::load_mtl_file(MATERIAL_FILE) {
+        Ok(ref materials) if !materials.is_empty() => materials[0].clone(),
+        Ok(_) => {
+            gl_log_err(&format!("WARNING: no materials in {}; using the default material.", MATERIAL_FILE));
+            Material::default()
+        }
+        Err(e) => {
+            gl_log_err(&format!("{}\nWARNING: using the default material.", e));
+            Material::default()
+        }
     };
-    assert!(model_mat_location != -1);
-    unsafe {
-        gl::UniformMatrix4fv(model_mat_location, 1, gl::FALSE, model_mat.as_ptr());
-    }
+
+    // Both programmes get exactly the same light and material, so the only
+    // difference on screen is where the lighting equation is evaluated.
+    let phong = create_lighting_programme(
+        PHONG_VERTEX_SHADER_FILE, PHONG_FRAGMENT_SHADER_FILE, &view_mat, &proj_mat, &model_mat, &material
+    );
+    let gouraud = create_lighting_programme(
+        GOURAUD_VERTEX_SHADER_FILE, GOURAUD_FRAGMENT_SHADER_FILE, &view_mat, &proj_mat, &model_mat, &material
+    );
+    let mut use_phong = true;
+    println!("Shading: Phong (press space to switch)");
 
     unsafe {
         gl::Enable(gl::DEPTH_TEST); // enable depth-testing
@@ -132,35 +169,47 @@ fn main() {
         gl::FrontFace(gl::CW);     // GL_CCW for counter clock-wise
     }
 
-    while !g_window.should_close() {
-        let current_seconds = glfw.get_time();
-        _update_fps_counter(&glfw, &mut g_window);
+    while !context.window.should_close() {
+        let current_seconds = context.glfw.get_time();
+        update_fps_counter(&mut context);
 
         unsafe {
             // wipe the drawing surface clear
             gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
-            gl::ClearColor(0.2, 0.2, 0.2, 1.0);
-            gl::Viewport(0, 0, G_GL_WIDTH as GLsizei, G_GL_HEIGHT as GLsizei);
+            gl::ClearColor(BACKGROUND_COLOUR.r, BACKGROUND_COLOUR.g, BACKGROUND_COLOUR.b, BACKGROUND_COLOUR.a);
+            gl::Viewport(0, 0, context.width as GLsizei, context.height as GLsizei);
 
-            gl::UseProgram(shader_programme);
+            let programme = if use_phong { &phong } else { &gouraud };
+            gl::UseProgram(programme.sp);
 
             model_mat.m[12] = f32::sin(current_seconds as f32);
-            gl::UniformMatrix4fv(model_mat_location, 1, gl::FALSE, model_mat.as_ptr());
+            gl::UniformMatrix4fv(programme.model_mat_location, 1, gl::FALSE, model_mat.as_ptr());
 
             gl::BindVertexArray(vao);
             // draw points 0-3 from the currently bound VAO with current in-use shader
             gl::DrawArrays(gl::TRIANGLES, 0, 3);
             // update other events like input handling
-            glfw.poll_events();
-            match g_window.get_key(Key::Escape) {
+            context.glfw.poll_events();
+            for (_, event) in glfw::flush_messages(&context.events) {
+                match event {
+                    glfw::WindowEvent::Key(Key::Space, _, Action::Press, _) => {
+                        use_phong = !use_phong;
+                        let name = if use_phong { "Phong" } else { "Gouraud" };
+                        println!("Shading: {}", name);
+                        gl_log(&format!("shading: {}\n", name));
+                    }
+                    _ => {}
+                }
+            }
+            match context.window.get_key(Key::Escape) {
                 Action::Press | Action::Repeat => {
-                    g_window.set_should_close(true);
+                    context.window.set_should_close(true);
                 }
                 _ => {}
             }
         }
 
         // put the stuff we've been drawing onto the display
-        g_window.swap_buffers();
+        context.window.swap_buffers();
     }
 }
@@ -0,0 +1,108 @@
+use gl;
+use gl::types::GLenum;
+use stb_image::image;
+use stb_image::image::LoadResult;
+
+use gl_utils::gl_log_err;
+use obj_parser;
+use obj_parser::ObjMesh;
+
+use std::io::Cursor;
+
+
+///
+/// A low-poly UV sphere with vertex points, texture coordinates, and normals.
+/// This gets used in place of any mesh file that cannot be loaded.
+///
+pub const FALLBACK_MESH_OBJ: &str = include_str!("fallback/sphere.obj");
+
+///
+/// A 64x64 grey checkerboard texture encoded as a PNG file. This gets used
+/// in place of any texture file that cannot be loaded.
+///
+pub const FALLBACK_TEXTURE_PNG: &[u8] = include_bytes!("fallback/checkerboard.png");
+
+///
+/// The default vertex shader. It only expects vertex points in attribute
+/// location 0 and the `view` and `proj` matrices.
+///
+pub const FALLBACK_VERTEX_SHADER: &str = include_str!("fallback/fallback.vert.glsl");
+
+///
+/// The default fragment shader. It paints everything in a hot pink checker pattern
+/// so a missing shader is easy to spot.
+///
+pub const FALLBACK_FRAGMENT_SHADER: &str = include_str!("fallback/fallback.frag.glsl");
+
+
+///
+/// Parse the embedded fallback mesh.
+///
+pub fn fallback_mesh() -> ObjMesh {
+    let mut reader = Cursor::new(FALLBACK_MESH_OBJ.as_bytes());
+    obj_parser::load_obj_mesh(&mut reader).expect("The embedded fallback mesh failed to parse.")
+}
+
+///
+/// Look up the embedded fallback shader source for a given shader type. There are only
+/// fallbacks for vertex shaders and fragment shaders.
+///
+pub fn fallback_shader_source(gl_type: GLenum) -> Option<&'static str> {
+    match gl_type {
+        gl::VERTEX_SHADER => Some(FALLBACK_VERTEX_SHADER),
+        gl::FRAGMENT_SHADER => Some(FALLBACK_FRAGMENT_SHADER),
+        _ => None,
+    }
+}
+
+///
+/// Load an image file from disk the same way `image::load_with_depth` does. If the file
+/// cannot be loaded, log a warning and decode the embedded checkerboard texture instead.
+///
+pub fn load_image_with_depth(file_name: &str, force_channels: usize) -> LoadResult {
+    match image::load_with_depth(file_name, force_channels, false) {
+        LoadResult::Error(e) => {
+            gl_log_err(&format!(
+                "WARNING: could not load {} ({}). Using the embedded checkerboard texture instead.",
+                file_name, e
+            ));
+            image::load_from_memory_with_depth(FALLBACK_TEXTURE_PNG, force_channels, false)
+        }
+        result => result,
+    }
+}
+
+mod assets_tests {
+    use gl;
+
+    #[test]
+    fn test_fallback_mesh_parses() {
+        let mesh = super::fallback_mesh();
+
+        assert!(mesh.point_count > 0);
+        assert_eq!(mesh.point_count % 3, 0);
+        assert_eq!(mesh.points.len(), 3 * mesh.point_count);
+        assert_eq!(mesh.tex_coords.len(), 2 * mesh.point_count);
+        assert_eq!(mesh.normals.len(), 3 * mesh.point_count);
+    }
+
+    #[test]
+    fn test_fallback_mesh_is_a_unit_sphere() {
+        let mesh = super::fallback_mesh();
+        for i in 0..mesh.point_count {
+            let x = mesh.points[3 * i];
+            let y = mesh.points[3 * i + 1];
+            let z = mesh.points[3 * i + 2];
+            let radius = f32::sqrt(x * x + y * y + z * z);
+
+            assert!(f32::abs(radius - 1.0) < 0.0001);
+        }
+    }
+
+    #[test]
+    fn test_fallback_shaders_exist_for_vertex_and_fragment_stages() {
+        assert!(super::fallback_shader_source(gl::VERTEX_SHADER).is_some());
+        assert!(super::fallback_shader_source(gl::FRAGMENT_SHADER).is_some());
+        assert!(super::fallback_shader_source(gl::GEOMETRY_SHADER).is_none());
+    }
+}
@@ -2,27 +2,28 @@ extern crate gl;
 extern crate glfw;
 extern crate chrono;
 extern crate stb_image;
-
-#[macro_use] 
-extern crate scan_fmt;
+#[cfg(feature = "glam")]
+extern crate glam;
+#[cfg(feature = "cgmath")]
+extern crate cgmath;
 
 mod gl_utils;
 mod graphics_math;
 mod obj_parser;
+mod assets;
+mod colour;
+mod vertex_layout;
 
 
 use glfw::{Action, Context, Key};
-use gl::types::{GLfloat, GLsizeiptr, GLvoid, GLsizei, GLuint};
-use stb_image::image;
+use gl::types::{GLfloat, GLvoid, GLsizei, GLuint};
 use stb_image::image::{LoadResult, Image};
 
-use std::mem;
-use std::ptr;
-
 use gl_utils::*;
 
 use graphics_math as math;
 use math::Mat4;
+use vertex_layout::VertexLayout;
 
 const VERTEX_SHADER_FILE: &str = "src/test.vert.glsl";
 const FRAGMENT_SHADER_FILE: &str = "src/test.frag.glsl";
@@ -31,12 +32,10 @@ const TEXTURE_FILE: &str = "src/skulluvmap.png";
 const GL_TEXTURE_MAX_ANISOTROPY_EXT: u32 = 0x84FE;
 const GL_MAX_TEXTURE_MAX_ANISOTROPY_EXT: u32 = 0x84FF;
 
-static mut PREVIOUS_SECONDS: f64 = 0.0;
-
 
 fn load_texture(file_name: &str, tex: &mut GLuint) -> bool {
     let force_channels = 4;
-    let mut image_data = match image::load_with_depth(file_name, force_channels, false) {
+    let mut image_data = match assets::load_image_with_depth(file_name, force_channels) {
         LoadResult::ImageU8(image_data) => image_data,
         LoadResult::Error(_) => {
             eprintln!("ERROR: could not load {}", file_name);
@@ -97,7 +96,7 @@ fn load_texture(file_name: &str, tex: &mut GLuint) -> bool {
 fn main() {
     restart_gl_log();
     // start GL context and O/S window using the GLFW helper library
-    let (mut glfw, mut g_window, mut _g_events) = start_gl().unwrap();
+    let mut context = start_gl().unwrap();
 
     // tell GL to only draw onto a pixel if the shape is closer to the viewer
     unsafe {
@@ -116,40 +115,14 @@ fn main() {
         0.0, 0.0, 1.0, 0.0, 1.0, 1.0, 1.0, 1.0, 0.0, 1.0, 0.0, 0.0
     ];
 
-    let mut points_vbo: GLuint = 0;
-    unsafe {
-        gl::GenBuffers(1, &mut points_vbo);
-        gl::BindBuffer(gl::ARRAY_BUFFER, points_vbo);
-        gl::BufferData(
-            gl::ARRAY_BUFFER, (points.len() * mem::size_of::<GLfloat>()) as GLsizeiptr, 
-            points.as_ptr() as *const GLvoid, gl::STATIC_DRAW
-        );
-    }
-    assert!(points_vbo != 0);
-
-    let mut texcoords_vbo: GLuint = 0;
-    unsafe {
-        gl::GenBuffers(1, &mut texcoords_vbo);
-        gl::BindBuffer(gl::ARRAY_BUFFER, texcoords_vbo);
-        gl::BufferData(
-            gl::ARRAY_BUFFER, (texcoords.len() * mem::size_of::<GLfloat>()) as GLsizeiptr, 
-            texcoords.as_ptr() as *const GLvoid, gl::STATIC_DRAW
-        );
-    }
-    assert!(texcoords_vbo != 0);
-
+    let point_count = points.len() / 3;
     let mut vao: GLuint = 0;
     unsafe {
         gl::GenVertexArrays(1, &mut vao);
-        gl::BindVertexArray(vao);
-        gl::BindBuffer(gl::ARRAY_BUFFER, points_vbo);
-        gl::VertexAttribPointer(0, 3, gl::FLOAT, gl::FALSE, 0, ptr::null());
-        gl::BindBuffer(gl::ARRAY_BUFFER, texcoords_vbo);
-        gl::VertexAttribPointer(1, 2, gl::FLOAT, gl::FALSE, 0, ptr::null()); // normalize!
-        gl::EnableVertexAttribArray(0);
-        gl::EnableVertexAttribArray(1);
     }
     assert!(vao != 0);
+    VertexLayout::new().floats(0, 3).upload(vao, &points, point_count);
+    VertexLayout::new().floats(1, 2).upload(vao, &texcoords, point_count);
 
     let shader_programme = create_programme_from_files(VERTEX_SHADER_FILE, FRAGMENT_SHADER_FILE);
 
@@ -157,7 +130,7 @@ fn main() {
     let near = 0.1;                                  // clipping plane
     let far = 100.0;                                 // clipping plane
     let fov = 67.0;                                  // convert 67 degrees to radians
-    let aspect = unsafe { G_GL_WIDTH as f32 / G_GL_HEIGHT as f32 }; // aspect ratio
+    let aspect = context.aspect_ratio(); // aspect ratio
     let proj_mat = Mat4::perspective(fov, aspect, near, far);
 
     // matrix components
@@ -165,22 +138,18 @@ fn main() {
     let cam_yaw_speed: GLfloat = 10.0;        // 10 degrees per second
     let mut cam_pos: [GLfloat; 3] = [0.0, 0.0, 2.0]; // don't start at zero, or we will be too close
     let mut cam_yaw: GLfloat = 0.0;               // y-rotation in degrees
-    let mut mat_trans = Mat4::identity().translate(&math::vec3((-cam_pos[0], -cam_pos[1], -cam_pos[2])));
-    let mut mat_rot = Mat4::identity().rotate_y_deg(-cam_yaw);
+    let mut mat_trans = Mat4::from_translation(&math::vec3((-cam_pos[0], -cam_pos[1], -cam_pos[2])));
+    let mut mat_rot = Mat4::from_rotation_y_deg(-cam_yaw);
     let mut view_mat = mat_rot * mat_trans;
 
-    let view_mat_location = unsafe {
-        gl::GetUniformLocation(shader_programme, "view".as_ptr() as *const i8)
-    };
+    let view_mat_location = uniform_location(shader_programme, "view");
     assert!(view_mat_location != -1);
     unsafe {
         gl::UseProgram(shader_programme);
         gl::UniformMatrix4fv(view_mat_location, 1, gl::FALSE, view_mat.as_ptr());
     }
 
-    let proj_mat_location = unsafe { 
-        gl::GetUniformLocation(shader_programme, "proj".as_ptr() as *const i8)
-    };
+    let proj_mat_location = uniform_location(shader_programme, "proj");
     unsafe {
         gl::UseProgram(shader_programme);
         gl::UniformMatrix4fv(proj_mat_location, 1, gl::FALSE, proj_mat.as_ptr());
@@ -198,18 +167,16 @@ fn main() {
         gl::FrontFace(gl::CCW);    // GL_CCW for counter clock-wise
     }
 
-    while !g_window.should_close() {
-        let current_seconds = glfw.get_time();
-        let elapsed_seconds = unsafe { current_seconds - PREVIOUS_SECONDS };
-        unsafe {
-            PREVIOUS_SECONDS = current_seconds;
-        }
+    while !context.window.should_close() {
+        let current_seconds = context.glfw.get_time();
+        let elapsed_seconds = current_seconds - context.elapsed_time_seconds;
+        context.elapsed_time_seconds = current_seconds;
 
-        _update_fps_counter(&glfw, &mut g_window);
+        update_fps_counter(&mut context);
         unsafe {
             // wipe the drawing surface clear
             gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
-            gl::Viewport(0, 0, G_GL_HEIGHT as i32, G_GL_HEIGHT as i32);
+            gl::Viewport(0, 0, context.height as i32, context.height as i32);
 
             gl::UseProgram(shader_programme);
             gl::BindVertexArray(vao);
@@ -218,60 +185,60 @@ fn main() {
             // update other events like input handling
         }
 
-        glfw.poll_events();
+        context.glfw.poll_events();
 
         // control keys
         let mut cam_moved = false;
-        match g_window.get_key(Key::A) {
+        match context.window.get_key(Key::A) {
             Action::Press | Action::Repeat => {
                 cam_pos[0] -= cam_speed * (elapsed_seconds as GLfloat);
                 cam_moved = true;
             }
             _ => {}
         }
-        match g_window.get_key(Key::D) {
+        match context.window.get_key(Key::D) {
             Action::Press | Action::Repeat => {
                 cam_pos[0] += cam_speed * (elapsed_seconds as GLfloat);
                 cam_moved = true;
             }
             _ => {}
         }
-        match g_window.get_key(Key::Up) {
+        match context.window.get_key(Key::Up) {
             Action::Press | Action::Repeat => {
                 cam_pos[1] += cam_speed * (elapsed_seconds as GLfloat);
                 cam_moved = true;
             }
             _ => {}
         }
-        match g_window.get_key(Key::Down) {
+        match context.window.get_key(Key::Down) {
             Action::Press | Action::Repeat => {
                 cam_pos[1] -= cam_speed * (elapsed_seconds as GLfloat);
                 cam_moved = true;
             }
             _ => {}
         }
-        match g_window.get_key(Key::W) {
+        match context.window.get_key(Key::W) {
             Action::Press | Action::Repeat => {
                 cam_pos[2] -= cam_speed * (elapsed_seconds as GLfloat);
                 cam_moved = true;
             }
             _ => {}
         }
-        match g_window.get_key(Key::S) {
+        match context.window.get_key(Key::S) {
             Action::Press | Action::Repeat => {
                 cam_pos[2] += cam_speed * (elapsed_seconds as GLfloat);
                 cam_moved = true;
             }
             _ => {}
         }
-        match g_window.get_key(Key::Left) {
+        match context.window.get_key(Key::Left) {
             Action::Press | Action::Repeat => {
                 cam_yaw += cam_yaw_speed * (elapsed_seconds as GLfloat);
                 cam_moved = true;
             }
             _ => {}
         }
-        match g_window.get_key(Key::Right) {
+        match context.window.get_key(Key::Right) {
             Action::Press | Action::Repeat => {
                 cam_yaw -= cam_yaw_speed * (elapsed_seconds as GLfloat);
                 cam_moved = true;
@@ -280,21 +247,21 @@ fn main() {
         }
         // update view matrix
         if cam_moved {
-            mat_trans = Mat4::identity().translate(&math::vec3((-cam_pos[0], -cam_pos[1], -cam_pos[2]))); // cam translation
-            mat_rot = Mat4::identity().rotate_y_deg(-cam_yaw);                 //
+            mat_trans = Mat4::from_translation(&math::vec3((-cam_pos[0], -cam_pos[1], -cam_pos[2]))); // cam translation
+            mat_rot = Mat4::from_rotation_y_deg(-cam_yaw);                 //
             view_mat = mat_rot * mat_trans;
             unsafe {
                 gl::UniformMatrix4fv(view_mat_location, 1, gl::FALSE, view_mat.as_ptr());
             }
         }
 
-        match g_window.get_key(Key::Escape) {
+        match context.window.get_key(Key::Escape) {
             Action::Press | Action::Repeat => {
-                g_window.set_should_close(true);
+                context.window.set_should_close(true);
             }
             _ => {}
         }
         // Put the stuff we've been drawing onto the display.
-        g_window.swap_buffers();
+        context.window.swap_buffers();
     }
 }
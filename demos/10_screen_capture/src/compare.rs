@@ -0,0 +1,253 @@
+use gl;
+use gl::types::{GLfloat, GLint, GLsizei, GLuint};
+
+use fullscreen_pass::FullscreenPass;
+use gl_utils::uniform_location;
+
+
+///
+/// How much brighter the heat map makes a difference, so that differences of a
+/// single step in one channel still show up.
+///
+pub const HEATMAP_GAIN: f32 = 16.0;
+
+
+///
+/// How far apart two frames are. Pixels are compared channel by channel, and a
+/// pixel differs if any of its channels do.
+///
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct DiffStats {
+    pub pixel_count: usize,
+    pub differing_pixels: usize,
+    pub max_difference: u8,
+}
+
+impl DiffStats {
+    pub fn is_identical(&self) -> bool {
+        self.differing_pixels == 0
+    }
+
+    pub fn describe(&self) -> String {
+        if self.is_identical() {
+            return format!("identical ({} pixels)", self.pixel_count);
+        }
+
+        let percent = 100.0 * self.differing_pixels as f32 / self.pixel_count as f32;
+        format!(
+            "{} of {} pixels differ ({:.2}%), by at most {} in one channel",
+            self.differing_pixels, self.pixel_count, percent, self.max_difference
+        )
+    }
+}
+
+///
+/// Compare two frames of the same size, `depth` bytes to a pixel.
+///
+pub fn diff_stats(live: &[u8], reference: &[u8], depth: usize) -> DiffStats {
+    assert_eq!(live.len(), reference.len());
+
+    let mut stats = DiffStats {
+        pixel_count: live.len() / depth,
+        differing_pixels: 0,
+        max_difference: 0,
+    };
+    for (live_pixel, reference_pixel) in live.chunks(depth).zip(reference.chunks(depth)) {
+        let mut pixel_difference = 0;
+        for (&a, &b) in live_pixel.iter().zip(reference_pixel.iter()) {
+            let difference = if a > b { a - b } else { b - a };
+            pixel_difference = u8::max(pixel_difference, difference);
+        }
+        if pixel_difference > 0 {
+            stats.differing_pixels += 1;
+            stats.max_difference = u8::max(stats.max_difference, pixel_difference);
+        }
+    }
+
+    stats
+}
+
+///
+/// Make an empty texture to copy frames from the frame buffer into.
+///
+fn create_frame_texture() -> GLuint {
+    let mut texture = 0;
+    unsafe {
+        gl::GenTextures(1, &mut texture);
+        gl::BindTexture(gl::TEXTURE_2D, texture);
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as GLint);
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as GLint);
+        // Nearest filtering, so each pixel is compared with exactly its counterpart.
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::NEAREST as GLint);
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::NEAREST as GLint);
+    }
+    assert!(texture > 0);
+
+    texture
+}
+
+///
+/// Copy the bottom-left `width` by `height` pixels of the frame buffer being drawn
+/// into a texture, resizing the texture to fit.
+///
+fn copy_frame_buffer_into(texture: GLuint, width: u32, height: u32) {
+    unsafe {
+        gl::BindTexture(gl::TEXTURE_2D, texture);
+        gl::CopyTexImage2D(gl::TEXTURE_2D, 0, gl::RGB8, 0, 0, width as GLsizei, height as GLsizei, 0);
+    }
+}
+
+///
+/// A screenshot comparison for A/B testing shader changes. Capture a reference
+/// frame, change something, and every frame after shows the live output on the
+/// left half of the window and the reference on the right, split down the middle.
+/// The heat map view instead shows the whole frame black wherever the live output
+/// matches the reference, and glowing wherever it does not, brighter the further
+/// apart they are. Refactors that should not change the output can be checked
+/// this way down to the last bit.
+///
+pub struct Comparison {
+    sp: GLuint,
+    pass: FullscreenPass,
+    heatmap_location: GLint,
+    split_location: GLint,
+    live_texture: GLuint,
+    reference_texture: GLuint,
+    reference: Option<(u32, u32)>,
+    reference_pixels: Vec<u8>,
+    pub heatmap: bool,
+}
+
+impl Comparison {
+    pub fn new(sp: GLuint) -> Comparison {
+        let location = |name: &str| {
+            let location = uniform_location(sp, name);
+            assert!(location > -1);
+            location
+        };
+        let heatmap_location = location("heatmap");
+        let split_location = location("split_x");
+        unsafe {
+            gl::UseProgram(sp);
+            gl::Uniform1i(location("live"), 0);
+            gl::Uniform1i(location("reference"), 1);
+            gl::Uniform1f(location("gain"), HEATMAP_GAIN);
+        }
+
+        Comparison {
+            sp: sp,
+            pass: FullscreenPass::new(),
+            heatmap_location: heatmap_location,
+            split_location: split_location,
+            live_texture: create_frame_texture(),
+            reference_texture: create_frame_texture(),
+            reference: None,
+            reference_pixels: vec![],
+            heatmap: false,
+        }
+    }
+
+    ///
+    /// Whether there is a reference frame to compare against.
+    ///
+    pub fn is_active(&self) -> bool {
+        self.reference.is_some()
+    }
+
+    ///
+    /// The width and height of the reference frame, if there is one.
+    ///
+    pub fn reference_size(&self) -> Option<(u32, u32)> {
+        self.reference
+    }
+
+    ///
+    /// Keep the frame just drawn as the reference, along with its `pixels` read
+    /// back from the frame buffer. Call it after drawing the scene and before
+    /// drawing the comparison.
+    ///
+    pub fn capture_reference(&mut self, width: u32, height: u32, pixels: Vec<u8>) {
+        copy_frame_buffer_into(self.reference_texture, width, height);
+        self.reference = Some((width, height));
+        self.reference_pixels = pixels;
+    }
+
+    ///
+    /// Compare the `pixels` of a live frame, read back the same way as the
+    /// reference's, with the reference.
+    ///
+    pub fn stats(&self, pixels: &[u8], depth: usize) -> Option<DiffStats> {
+        if !self.is_active() || pixels.len() != self.reference_pixels.len() {
+            return None;
+        }
+
+        Some(diff_stats(pixels, &self.reference_pixels, depth))
+    }
+
+    ///
+    /// Stop comparing, and go back to drawing only the live output.
+    ///
+    pub fn clear(&mut self) {
+        self.reference = None;
+        self.reference_pixels = vec![];
+        self.heatmap = false;
+    }
+
+    ///
+    /// Draw the comparison over the frame just drawn, which fills a `width` by
+    /// `height` frame buffer. Does nothing without a reference frame.
+    ///
+    pub fn draw(&self, width: u32, height: u32) {
+        if !self.is_active() {
+            return;
+        }
+
+        copy_frame_buffer_into(self.live_texture, width, height);
+        unsafe {
+            gl::Viewport(0, 0, width as GLsizei, height as GLsizei);
+            gl::UseProgram(self.sp);
+            gl::Uniform1i(self.heatmap_location, self.heatmap as GLint);
+            gl::Uniform1f(self.split_location, (width / 2) as GLfloat);
+        }
+        self.pass.draw(self.sp, &[self.live_texture, self.reference_texture]);
+    }
+}
+
+impl Drop for Comparison {
+    fn drop(&mut self) {
+        if gl::DeleteTextures::is_loaded() {
+            unsafe {
+                gl::DeleteTextures(1, &self.live_texture);
+                gl::DeleteTextures(1, &self.reference_texture);
+            }
+        }
+    }
+}
+
+mod compare_tests {
+    use super::diff_stats;
+
+    #[test]
+    fn test_identical_frames_have_no_differences() {
+        let frame = [10, 20, 30, 40, 50, 60];
+
+        let stats = diff_stats(&frame, &frame, 3);
+
+        assert!(stats.is_identical());
+        assert_eq!(stats.pixel_count, 2);
+        assert_eq!(stats.max_difference, 0);
+    }
+
+    #[test]
+    fn test_a_pixel_differs_if_any_channel_does() {
+        let live = [10, 20, 30, 40, 50, 60, 0, 0, 0];
+        let reference = [10, 21, 30, 40, 50, 60, 200, 0, 0];
+
+        let stats = diff_stats(&live, &reference, 3);
+
+        assert_eq!(stats.differing_pixels, 2);
+        // the difference is taken both ways round
+        assert_eq!(stats.max_difference, 200);
+        assert!(!stats.is_identical());
+    }
+}
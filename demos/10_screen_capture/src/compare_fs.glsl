@@ -0,0 +1,55 @@
+#version 410
+
+in vec2 st;
+
+// the frame just drawn, and the reference frame captured earlier.
+uniform sampler2D live;
+uniform sampler2D reference;
+// show the difference heat map instead of the two frames side by side.
+uniform bool heatmap;
+// how much to brighten differences by, so the smallest still show up.
+uniform float gain;
+// where the reference starts, in pixels from the left of the window.
+uniform float split_x;
+
+out vec4 frag_colour;
+
+// black for no difference, then blue, red, yellow, and white as it grows.
+vec3 heat (float t) {
+	vec3 blue = vec3 (0.0, 0.0, 1.0);
+	vec3 red = vec3 (1.0, 0.0, 0.0);
+	vec3 yellow = vec3 (1.0, 1.0, 0.0);
+	vec3 white = vec3 (1.0, 1.0, 1.0);
+	if (t < 0.33) {
+		return mix (blue, red, t / 0.33);
+	}
+	if (t < 0.66) {
+		return mix (red, yellow, (t - 0.33) / 0.33);
+	}
+	return mix (yellow, white, (t - 0.66) / 0.34);
+}
+
+void main() {
+	vec3 live_colour = texture (live, st).rgb;
+	vec3 reference_colour = texture (reference, st).rgb;
+
+	if (heatmap) {
+		vec3 difference = abs (live_colour - reference_colour);
+		float largest = max (max (difference.r, difference.g), difference.b);
+		if (largest == 0.0) {
+			frag_colour = vec4 (0.0, 0.0, 0.0, 1.0);
+		} else {
+			frag_colour = vec4 (heat (clamp (largest * gain, 0.0, 1.0)), 1.0);
+		}
+		return;
+	}
+
+	// a thin line down the middle marks where one frame ends and the other starts.
+	if (abs (gl_FragCoord.x - split_x) < 1.0) {
+		frag_colour = vec4 (1.0, 1.0, 1.0, 1.0);
+	} else if (gl_FragCoord.x < split_x) {
+		frag_colour = vec4 (live_colour, 1.0);
+	} else {
+		frag_colour = vec4 (reference_colour, 1.0);
+	}
+}
@@ -3,37 +3,48 @@ extern crate glfw;
 extern crate chrono;
 extern crate stb_image;
 extern crate png;
+#[cfg(feature = "glam")]
+extern crate glam;
+#[cfg(feature = "cgmath")]
+extern crate cgmath;
 
-#[macro_use] 
-extern crate scan_fmt;
-
+#[macro_use]
 mod gl_utils;
 mod graphics_math;
 mod obj_parser;
+mod colour;
+mod assets;
+mod shader_include;
+mod spirv;
+mod program_cache;
 mod screen;
+mod compare;
 mod logger;
+mod vertex_layout;
+mod fullscreen_pass;
 
 
 use glfw::{Action, Context, Key};
-use gl::types::{GLfloat, GLsizeiptr, GLvoid, GLuint};
+use gl::types::{GLfloat, GLvoid, GLuint};
 
-use stb_image::image;
 use stb_image::image::LoadResult;
 
 use gl_utils::*;
 
-use std::mem;
-use std::ptr;
 use std::process;
 
 use graphics_math as math;
 use math::Mat4;
+use vertex_layout::VertexLayout;
+use compare::Comparison;
+use fullscreen_pass::FULLSCREEN_VS_FILE;
 
 
 const GL_LOG_FILE: &str = "gl.log";
 const VERTEX_SHADER_FILE: &str = "src/test.vert.glsl";
 const FRAGMENT_SHADER_FILE: &str = "src/test.frag.glsl";
 const TEXTURE_FILE: &str = "src/skulluvmap.png";
+const COMPARE_FS_FILE: &str = "src/compare_fs.glsl";
 
 const GL_TEXTURE_MAX_ANISOTROPY_EXT: u32 = 0x84FE;
 const GL_MAX_TEXTURE_MAX_ANISOTROPY_EXT: u32 = 0x84FF;
@@ -41,7 +52,7 @@ const GL_MAX_TEXTURE_MAX_ANISOTROPY_EXT: u32 = 0x84FF;
 
 fn load_texture(file_name: &str, tex: &mut GLuint) -> bool {
     let force_channels = 4;
-    let mut image_data = match image::load_with_depth(file_name, force_channels, false) {
+    let mut image_data = match assets::load_image_with_depth(file_name, force_channels) {
         LoadResult::ImageU8(image_data) => image_data,
         LoadResult::Error(_) => {
             eprintln!("ERROR: could not load {}", file_name);
@@ -100,6 +111,8 @@ fn load_texture(file_name: &str, tex: &mut GLuint) -> bool {
 
 fn gl_capture_frame_buffer(context: &GLContext, buffer: &mut [u8]) -> bool {
     unsafe {
+        // Rows are packed tightly, whatever the width of the window.
+        gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
         gl::ReadPixels(
             0, 0, context.width as i32, context.height as i32, 
             gl::RGB, gl::UNSIGNED_BYTE, 
@@ -139,40 +152,14 @@ fn main() {
         0.0, 0.0, 1.0, 0.0, 1.0, 1.0, 1.0, 1.0, 0.0, 1.0, 0.0, 0.0
     ];
 
-    let mut points_vbo: GLuint = 0;
-    unsafe {
-        gl::GenBuffers(1, &mut points_vbo);
-        gl::BindBuffer(gl::ARRAY_BUFFER, points_vbo);
-        gl::BufferData(
-            gl::ARRAY_BUFFER, (points.len() * mem::size_of::<GLfloat>()) as GLsizeiptr, 
-            points.as_ptr() as *const GLvoid, gl::STATIC_DRAW
-        );
-    }
-    assert!(points_vbo != 0);
-
-    let mut texcoords_vbo: GLuint = 0;
-    unsafe {
-        gl::GenBuffers(1, &mut texcoords_vbo);
-        gl::BindBuffer(gl::ARRAY_BUFFER, texcoords_vbo);
-        gl::BufferData(
-            gl::ARRAY_BUFFER, (texcoords.len() * mem::size_of::<GLfloat>()) as GLsizeiptr, 
-            texcoords.as_ptr() as *const GLvoid, gl::STATIC_DRAW
-        );
-    }
-    assert!(texcoords_vbo != 0);
-
+    let point_count = points.len() / 3;
     let mut vao: GLuint = 0;
     unsafe {
         gl::GenVertexArrays(1, &mut vao);
-        gl::BindVertexArray(vao);
-        gl::BindBuffer(gl::ARRAY_BUFFER, points_vbo);
-        gl::VertexAttribPointer(0, 3, gl::FLOAT, gl::FALSE, 0, ptr::null());
-        gl::BindBuffer(gl::ARRAY_BUFFER, texcoords_vbo);
-        gl::VertexAttribPointer(1, 2, gl::FLOAT, gl::FALSE, 0, ptr::null()); // normalize!
-        gl::EnableVertexAttribArray(0);
-        gl::EnableVertexAttribArray(1);
     }
     assert!(vao != 0);
+    VertexLayout::new().floats(0, 3).upload(vao, &points, point_count);
+    VertexLayout::new().floats(1, 2).upload(vao, &texcoords, point_count);
 
     let shader_programme = create_programme_from_files(&logger, VERTEX_SHADER_FILE, FRAGMENT_SHADER_FILE);
 
@@ -188,22 +175,18 @@ fn main() {
     let cam_yaw_speed: GLfloat = 10.0;        // 10 degrees per second
     let mut cam_pos: [GLfloat; 3] = [0.0, 0.0, 2.0]; // don't start at zero, or we will be too close
     let mut cam_yaw: GLfloat = 0.0;               // y-rotation in degrees
-    let mut mat_trans = Mat4::identity().translate(&math::vec3((-cam_pos[0], -cam_pos[1], -cam_pos[2])));
-    let mut mat_rot = Mat4::identity().rotate_y_deg(-cam_yaw);
+    let mut mat_trans = Mat4::from_translation(&math::vec3((-cam_pos[0], -cam_pos[1], -cam_pos[2])));
+    let mut mat_rot = Mat4::from_rotation_y_deg(-cam_yaw);
     let mut view_mat = mat_rot * mat_trans;
 
-    let view_mat_location = unsafe {
-        gl::GetUniformLocation(shader_programme, "view".as_ptr() as *const i8)
-    };
+    let view_mat_location = uniform_location(shader_programme, "view");
     assert!(view_mat_location != -1);
     unsafe {
         gl::UseProgram(shader_programme);
         gl::UniformMatrix4fv(view_mat_location, 1, gl::FALSE, view_mat.as_ptr());
     }
 
-    let proj_mat_location = unsafe { 
-        gl::GetUniformLocation(shader_programme, "proj".as_ptr() as *const i8)
-    };
+    let proj_mat_location = uniform_location(shader_programme, "proj");
     unsafe {
         gl::UseProgram(shader_programme);
         gl::UniformMatrix4fv(proj_mat_location, 1, gl::FALSE, proj_mat.as_ptr());
@@ -221,6 +204,15 @@ fn main() {
         gl::FrontFace(gl::CCW);
     }
 
+    // A/B comparison with a reference frame. C captures the reference, H switches
+    // between the side by side view and the difference heat map, and X stops
+    // comparing.
+    let compare_programme = create_programme_from_files(&logger, FULLSCREEN_VS_FILE, COMPARE_FS_FILE);
+    let mut comparison = Comparison::new(compare_programme);
+    let mut capture_reference = false;
+    let mut report_differences = false;
+    context.window.set_framebuffer_size_polling(true);
+
     while !context.window.should_close() {
         let current_seconds = context.glfw.get_time();
         let delta_seconds = current_seconds - context.elapsed_time_seconds;
@@ -238,7 +230,54 @@ fn main() {
             gl::DrawArrays(gl::TRIANGLES, 0, 6);
         }
 
+        // The frame is read back before the comparison is drawn over it.
+        let depth = context.channel_depth as usize;
+        let frame_size = context.width as usize * context.height as usize * depth;
+        if capture_reference {
+            let mut pixels = vec![0; frame_size];
+            gl_capture_frame_buffer(&context, &mut pixels);
+            comparison.capture_reference(context.width, context.height, pixels);
+            logger.log(&format!("captured a {}x{} reference frame", context.width, context.height));
+            capture_reference = false;
+        }
+        if report_differences {
+            let mut pixels = vec![0; frame_size];
+            gl_capture_frame_buffer(&context, &mut pixels);
+            if let Some(stats) = comparison.stats(&pixels, depth) {
+                logger.log(&format!("compared with the reference: {}", stats.describe()));
+            }
+            report_differences = false;
+        }
+        comparison.draw(context.width, context.height);
+
         context.glfw.poll_events();
+        for (_, event) in glfw::flush_messages(&context.events) {
+            match event {
+                glfw::WindowEvent::FramebufferSize(width, height) => {
+                    context.width = width as u32;
+                    context.height = height as u32;
+                    // The reference no longer lines up with the frame.
+                    if comparison.is_active() && comparison.reference_size() != Some((context.width, context.height)) {
+                        comparison.clear();
+                        logger.log("the window changed size, so the reference frame was dropped");
+                    }
+                }
+                glfw::WindowEvent::Key(Key::C, _, Action::Press, _) => {
+                    capture_reference = true;
+                }
+                glfw::WindowEvent::Key(Key::H, _, Action::Press, _) => {
+                    if comparison.is_active() {
+                        comparison.heatmap = !comparison.heatmap;
+                        report_differences = comparison.heatmap;
+                        logger.log(&format!("difference heat map: {}", comparison.heatmap));
+                    }
+                }
+                glfw::WindowEvent::Key(Key::X, _, Action::Press, _) => {
+                    comparison.clear();
+                }
+                _ => {}
+            }
+        }
 
         match context.window.get_key(Key::PrintScreen) {
             Action::Press | Action::Repeat => {
@@ -314,8 +353,8 @@ fn main() {
         // Update view matrix.
         if cam_moved {
             // Camera translation.
-            mat_trans = Mat4::identity().translate(&math::vec3((-cam_pos[0], -cam_pos[1], -cam_pos[2])));
-            mat_rot = Mat4::identity().rotate_y_deg(-cam_yaw);
+            mat_trans = Mat4::from_translation(&math::vec3((-cam_pos[0], -cam_pos[1], -cam_pos[2])));
+            mat_rot = Mat4::from_rotation_y_deg(-cam_yaw);
             view_mat = mat_rot * mat_trans;
             unsafe {
                 gl::UniformMatrix4fv(view_mat_location, 1, gl::FALSE, view_mat.as_ptr());
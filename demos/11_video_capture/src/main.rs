@@ -3,40 +3,47 @@ extern crate glfw;
 extern crate chrono;
 extern crate stb_image;
 extern crate png;
-
-#[macro_use] 
-extern crate scan_fmt;
-
+#[cfg(feature = "audio")]
+extern crate rodio;
+#[cfg(feature = "glam")]
+extern crate glam;
+#[cfg(feature = "cgmath")]
+extern crate cgmath;
+
+#[macro_use]
 mod gl_utils;
 mod graphics_math;
 mod obj_parser;
+mod colour;
+mod assets;
+mod shader_include;
+mod spirv;
+mod program_cache;
 mod logger;
+mod screen;
+mod sound;
+mod vertex_layout;
 
 
 use glfw::{Action, Context, Key};
-use gl::types::{GLfloat, GLsizeiptr, GLvoid, GLuint};
+use gl::types::{GLfloat, GLvoid, GLuint};
 
-use stb_image::image;
 use stb_image::image::LoadResult;
 
-use png::HasParameters;
-
-use std::mem;
-use std::ptr;
-use std::path::Path;
-use std::fs::File;
-use std::io::BufWriter;
 use std::process;
 
 use gl_utils::*;
+use sound::SoundPlayer;
 
 use graphics_math as math;
 use math::Mat4;
+use vertex_layout::VertexLayout;
 
 const GL_LOG_FILE: &str = "gl.log";
 const VERTEX_SHADER_FILE: &str = "src/test.vert.glsl";
 const FRAGMENT_SHADER_FILE: &str = "src/test.frag.glsl";
 const TEXTURE_FILE: &str = "src/skulluvmap.png";
+const BEEP_SOUND_FILE: &str = "src/beep.wav";
 
 const GL_TEXTURE_MAX_ANISOTROPY_EXT: u32 = 0x84FE;
 const GL_MAX_TEXTURE_MAX_ANISOTROPY_EXT: u32 = 0x84FF;
@@ -45,87 +52,23 @@ const G_VIDEO_SECONDS_TOTAL: usize = 10;
 const G_VIDEO_FPS: usize = 25;
 
 
-struct FrameBufferDumper {
-    width: usize,
-    height: usize,
-    channels: usize,
-    index: Vec<(usize, usize)>,
-    data: Vec<u8>,
-}
-
-impl FrameBufferDumper {
-    fn new(
-        video_fps: usize, video_seconds_total: usize, 
-        width: usize, height: usize, channels: usize) -> FrameBufferDumper {
-        
-        FrameBufferDumper {
-            width: width,
-            height: height,
-            channels: channels,
-            index: vec![],
-            data: vec![0; video_fps * video_seconds_total * width * height * channels],
-        }
-    }
-
-    fn frame_count(&self) -> usize {
-        self.index.len()
-    }
-
-    fn size_bytes(&self) -> usize {
-        self.data.len()
-    }
-
-    fn make_new_frame(&mut self) -> &mut [u8] {
-        // There are currently not frames in the dumper's buffer.
-        let start = match self.index.is_empty() {
-            true => 0,
-            false => self.index[self.index.len() - 1].0,
-        };
-
-        let end = start + self.width * self.height * self.channels;
-        self.index.push((start, end));
-
-        &mut self.data[start..end]
-    }
-
-    fn dump_video_frame(&self, frame_number: usize) {
-        let file_name = format!("video_frame_{:03}.png", frame_number); 
-        let (start, end) = self.index[frame_number];
-
-        let path = Path::new(&file_name);
-        let file = File::create(path).unwrap();
-        let buf_writer = BufWriter::new(file);
-        let mut encoder = png::Encoder::new(buf_writer, self.width as u32, self.height as u32);
-        encoder.set(png::ColorType::RGB).set(png::BitDepth::Eight);
-        let mut png_writer = encoder.write_header().unwrap();
-
-        let result = png_writer.write_image_data(&self.data[start..end]);
-        if result.is_err() {
-            eprintln!("ERROR: could not write video frame file {}", file_name);
-        }
-    }
-
-    fn dump_video_frames(&self) {
-        for frame_number in 0..self.index.len() {
-            self.dump_video_frame(frame_number);
-        }
-    }
-}
-
-fn grab_video_frame(context: &mut GLContext, dumper: &mut FrameBufferDumper) {
-    // Copy the frame buffer contents into into a 24-bit RGB image.
+fn gl_capture_frame_buffer(width: usize, height: usize, buffer: &mut [u8]) -> bool {
+    // Copy the frame buffer contents into into a 24-bit RGB image. Rows are
+    // tightly packed, so widths that are not a multiple of four still line up.
     unsafe {
+        gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
         gl::ReadPixels(
-            0, 0, context.width as i32, context.height as i32, gl::RGB, gl::UNSIGNED_BYTE,
-            dumper.make_new_frame().as_mut_ptr() as *mut GLvoid
+            0, 0, width as i32, height as i32, gl::RGB, gl::UNSIGNED_BYTE,
+            buffer.as_mut_ptr() as *mut GLvoid
         );
     }
-}
 
+    true
+}
 
 fn load_texture(file_name: &str, tex: &mut GLuint) -> bool {
     let force_channels = 4;
-    let mut image_data = match image::load_with_depth(file_name, force_channels, false) {
+    let mut image_data = match assets::load_image_with_depth(file_name, force_channels) {
         LoadResult::ImageU8(image_data) => image_data,
         LoadResult::Error(_) => {
             eprintln!("ERROR: could not load {}", file_name);
@@ -211,40 +154,14 @@ fn main() {
         0.0, 0.0, 1.0, 0.0, 1.0, 1.0, 1.0, 1.0, 0.0, 1.0, 0.0, 0.0
     ];
 
-    let mut points_vbo: GLuint = 0;
-    unsafe {
-        gl::GenBuffers(1, &mut points_vbo);
-        gl::BindBuffer(gl::ARRAY_BUFFER, points_vbo);
-        gl::BufferData(
-            gl::ARRAY_BUFFER, (points.len() * mem::size_of::<GLfloat>()) as GLsizeiptr, 
-            points.as_ptr() as *const GLvoid, gl::STATIC_DRAW
-        );
-    }
-    assert!(points_vbo != 0);
-
-    let mut texcoords_vbo: GLuint = 0;
-    unsafe {
-        gl::GenBuffers(1, &mut texcoords_vbo);
-        gl::BindBuffer(gl::ARRAY_BUFFER, texcoords_vbo);
-        gl::BufferData(
-            gl::ARRAY_BUFFER, (texcoords.len() * mem::size_of::<GLfloat>()) as GLsizeiptr, 
-            texcoords.as_ptr() as *const GLvoid, gl::STATIC_DRAW
-        );
-    }
-    assert!(texcoords_vbo != 0);
-
+    let point_count = points.len() / 3;
     let mut vao: GLuint = 0;
     unsafe {
         gl::GenVertexArrays(1, &mut vao);
-        gl::BindVertexArray(vao);
-        gl::BindBuffer(gl::ARRAY_BUFFER, points_vbo);
-        gl::VertexAttribPointer(0, 3, gl::FLOAT, gl::FALSE, 0, ptr::null());
-        gl::BindBuffer(gl::ARRAY_BUFFER, texcoords_vbo);
-        gl::VertexAttribPointer(1, 2, gl::FLOAT, gl::FALSE, 0, ptr::null()); // normalize!
-        gl::EnableVertexAttribArray(0);
-        gl::EnableVertexAttribArray(1);
     }
     assert!(vao != 0);
+    VertexLayout::new().floats(0, 3).upload(vao, &points, point_count);
+    VertexLayout::new().floats(1, 2).upload(vao, &texcoords, point_count);
 
     let shader_programme = create_programme_from_files(&logger, VERTEX_SHADER_FILE, FRAGMENT_SHADER_FILE);
 
@@ -260,22 +177,18 @@ fn main() {
     let cam_yaw_speed: GLfloat = 10.0;        // 10 degrees per second
     let mut cam_pos: [GLfloat; 3] = [0.0, 0.0, 2.0]; // don't start at zero, or we will be too close
     let mut cam_yaw: GLfloat = 0.0;               // y-rotation in degrees
-    let mut mat_trans = Mat4::identity().translate(&math::vec3((-cam_pos[0], -cam_pos[1], -cam_pos[2])));
-    let mut mat_rot = Mat4::identity().rotate_y_deg(-cam_yaw);
+    let mut mat_trans = Mat4::from_translation(&math::vec3((-cam_pos[0], -cam_pos[1], -cam_pos[2])));
+    let mut mat_rot = Mat4::from_rotation_y_deg(-cam_yaw);
     let mut view_mat = mat_rot * mat_trans;
 
-    let view_mat_location = unsafe {
-        gl::GetUniformLocation(shader_programme, "view".as_ptr() as *const i8)
-    };
+    let view_mat_location = uniform_location(shader_programme, "view");
     assert!(view_mat_location != -1);
     unsafe {
         gl::UseProgram(shader_programme);
         gl::UniformMatrix4fv(view_mat_location, 1, gl::FALSE, view_mat.as_ptr());
     }
 
-    let proj_mat_location = unsafe { 
-        gl::GetUniformLocation(shader_programme, "proj".as_ptr() as *const i8)
-    };
+    let proj_mat_location = uniform_location(shader_programme, "proj");
     assert!(proj_mat_location != -1);
     unsafe {
         gl::UseProgram(shader_programme);
@@ -293,27 +206,30 @@ fn main() {
         gl::FrontFace(gl::CCW);    // GL_CCW for counter clock-wise
     }
 
-    // Initialize timers for video dumping.
-    let mut dump_video = false;
+    // A missing sound is not fatal; recording just starts silently.
+    let mut sounds = SoundPlayer::new();
+    if let Err(e) = sounds.load("beep", BEEP_SOUND_FILE) {
+        logger.log_err(&e);
+    }
+
+    // Initialize timers for video dumping. The recorder is only created once
+    // recording starts, so it picks up the frame buffer size at that moment.
+    let mut recorder: Option<screen::VideoRecorder> = None;
     let mut video_timer = 0.0;      // time video has been recording
     let mut video_dump_timer = 0.0; // timer for next frame grab
-    let frame_time = 0.04;          // 1/25 seconds of time
-    let mut dumper = FrameBufferDumper::new(
-        G_VIDEO_SECONDS_TOTAL, G_VIDEO_FPS,
-        context.width as usize, context.height as usize, context.channel_depth as usize
-    );
+    let frame_time = 1.0 / G_VIDEO_FPS as f64;
 
     while !context.window.should_close() {
         let current_seconds = context.glfw.get_time();
         let delta_seconds = current_seconds - context.elapsed_time_seconds;
         context.elapsed_time_seconds = current_seconds;
 
-        if dump_video {
+        if recorder.is_some() {
             // delta_seconds is seconds since last loop iteration
             video_timer += delta_seconds;
             video_dump_timer += delta_seconds;
             // only record 10s of video, then quit
-            if video_timer > 10.0 {
+            if video_timer > G_VIDEO_SECONDS_TOTAL as f64 {
                 break;
             }
         }
@@ -335,8 +251,17 @@ fn main() {
 
         match context.window.get_key(Key::PrintScreen) {
             Action::Press | Action::Repeat => {
-                dump_video = true;
-                println!("dump_video set to true.");
+                if recorder.is_none() {
+                    let (width, height) = context.window.get_framebuffer_size();
+                    recorder = Some(screen::VideoRecorder::new(
+                        width as usize, height as usize, context.channel_depth as usize,
+                        G_VIDEO_FPS, G_VIDEO_SECONDS_TOTAL
+                    ));
+                    println!("Recording {}x{} video.", width, height);
+                    if let Err(e) = sounds.play("beep") {
+                        logger.log_err(&e);
+                    }
+                }
             }
             _ => {}
         }
@@ -401,17 +326,21 @@ fn main() {
         }
         // update view matrix
         if cam_moved {
-            mat_trans = Mat4::identity().translate(&math::vec3((-cam_pos[0], -cam_pos[1], -cam_pos[2]))); // cam translation
-            mat_rot = Mat4::identity().rotate_y_deg(-cam_yaw);                 //
+            mat_trans = Mat4::from_translation(&math::vec3((-cam_pos[0], -cam_pos[1], -cam_pos[2]))); // cam translation
+            mat_rot = Mat4::from_rotation_y_deg(-cam_yaw);                 //
             view_mat = mat_rot * mat_trans;
             unsafe {
                 gl::UniformMatrix4fv(view_mat_location, 1, gl::FALSE, view_mat.as_ptr());
             }
         }
 
-        if dump_video { // check if recording mode is enabled
+        if let Some(ref mut recorder) = recorder { // check if recording mode is enabled
+            let (width, height) = (recorder.width(), recorder.height());
             while video_dump_timer > frame_time {
-                grab_video_frame(&mut context, &mut dumper); // 25 Hz so grab a frame
+                // 25 Hz so grab a frame
+                if let Err(e) = recorder.capture_frame(&|buf| { gl_capture_frame_buffer(width, height, buf) }) {
+                    eprintln!("ERROR: could not grab video frame: {}", e);
+                }
                 video_dump_timer -= frame_time;
             }
         }
@@ -426,7 +355,10 @@ fn main() {
         context.window.swap_buffers();
     }
 
-    if dump_video {
-        dumper.dump_video_frames();
+    if let Some(recorder) = recorder {
+        match recorder.write_frames() {
+            Ok(frame_count) => println!("Wrote {} video frames.", frame_count),
+            Err(e) => eprintln!("ERROR: could not write video frames: {}", e),
+        }
     }
 }
@@ -2,23 +2,44 @@ extern crate gl;
 extern crate glfw;
 extern crate chrono;
 extern crate stb_image;
-
-#[macro_use] 
-extern crate scan_fmt;
-
+#[cfg(feature = "assimp")]
+extern crate assimp;
+#[cfg(feature = "gltf")]
+extern crate gltf;
+#[cfg(feature = "glam")]
+extern crate glam;
+#[cfg(feature = "cgmath")]
+extern crate cgmath;
+
+#[macro_use]
 mod gl_utils;
 mod graphics_math;
 mod obj_parser;
+mod colour;
+mod assets;
+mod shader_include;
+mod spirv;
+mod program_cache;
 mod logger;
+mod material;
+mod model;
+mod mesh_cache;
+mod resource_cache;
+mod geometry;
+mod texture_binder;
+mod vertex_layout;
+mod vertex_packing;
+mod uniforms;
+mod streaming_buffer;
+mod overlay;
+mod fullscreen_pass;
+mod texture_inspector;
 
 
 use glfw::{Action, Context, Key};
-use gl::types::{GLfloat, GLsizeiptr, GLvoid, GLuint};
-use stb_image::image;
+use gl::types::{GLfloat, GLvoid, GLuint};
 use stb_image::image::{LoadResult, Image};
 
-use std::mem;
-use std::ptr;
 use std::process;
 
 use gl_utils::*;
@@ -26,20 +47,32 @@ use gl_utils::*;
 use graphics_math as math;
 use math::Mat4;
 
+use logger::Logger;
+use fullscreen_pass::FULLSCREEN_VS_FILE;
+use material::{MaterialBinder, MaterialLibrary};
+use model::Model;
+use obj_parser::{Material, MaterialGroup};
+use overlay::Overlay;
+use resource_cache::{Loaders, MeshBuffers, ResourceCache};
+use texture_binder::TextureBinder;
+use texture_inspector::{TextureInspector, TEXTURE_INSPECTOR_FS_FILE};
+use uniforms::Uniforms;
+use vertex_layout::VertexLayout;
+
 const GL_LOG_FILE: &str = "gl.log";
 const VERTEX_SHADER_FILE: &str = "src/test.vert.glsl";
 const FRAGMENT_SHADER_FILE: &str = "src/test.frag.glsl";
 const MESH_FILE: &str = "src/suzanne.obj";
+const AMBIENT_MAP_FILE: &str = "src/ao.png";
+const EMISSION_MAP_FILE: &str = "src/tileable9b_emiss.png";
 
 const GL_TEXTURE_MAX_ANISOTROPY_EXT: u32 = 0x84FE;
 const GL_MAX_TEXTURE_MAX_ANISOTROPY_EXT: u32 = 0x84FF;
 
-static mut PREVIOUS_SECONDS: f64 = 0.0;
-
 
 fn load_texture(file_name: &str, tex: &mut GLuint) -> bool {
     let force_channels = 4;
-    let mut image_data = match image::load_with_depth(file_name, force_channels, false) {
+    let mut image_data = match assets::load_image_with_depth(file_name, force_channels) {
         LoadResult::ImageU8(image_data) => image_data,
         LoadResult::Error(_) => {
             eprintln!("ERROR: could not load {}", file_name);
@@ -97,6 +130,89 @@ fn load_texture(file_name: &str, tex: &mut GLuint) -> bool {
     return true;
 }
 
+///
+/// Upload a model's points, normals, and any texture coordinates into a new
+/// vertex array, drawn as the triangle soup it is.
+///
+fn upload_mesh(model: &Model) -> MeshBuffers {
+    let mut vao = 0;
+    unsafe {
+        gl::GenVertexArrays(1, &mut vao);
+    }
+    assert!(vao > 0);
+
+    let point_count = model.point_count;
+    let mut buffers = vec![
+        VertexLayout::new().floats(0, 3).upload(vao, &model.points, point_count),
+        VertexLayout::new().floats(1, 3).upload(vao, &model.normals, point_count),
+    ];
+    if model.tex_coords.len() == 2 * point_count {
+        buffers.push(VertexLayout::new().floats(2, 2).upload(vao, &model.tex_coords, point_count));
+    }
+
+    MeshBuffers { vao: vao, buffers: buffers, vertex_count: point_count }
+}
+
+///
+/// Everything the demo draws the mesh with that holds the name of a GL object
+/// from the cache: the shader programme and its uniforms, the materials'
+/// textures, and the ambient and emission maps. It is made again whenever the
+/// objects in the cache are.
+///
+struct MeshBindings {
+    shader_programme: GLuint,
+    uniforms: Uniforms,
+    library: MaterialLibrary,
+    material_binder: MaterialBinder,
+    texture_binder: TextureBinder,
+}
+
+impl MeshBindings {
+    fn new(resources: &ResourceCache, logger: &Logger, materials: &[Material],
+           load_program: &dyn Fn(&str, &str) -> GLuint, load_texture_file: &dyn Fn(&str) -> GLuint) -> MeshBindings {
+        let shader_programme = resources.program(VERTEX_SHADER_FILE, FRAGMENT_SHADER_FILE, load_program).id();
+        // the camera matrices are set through the checked setters, so a debug build
+        // logs a uniform that is misspelt, optimised away, or set with the wrong type
+        let uniforms = Uniforms::new(shader_programme, Logger::from_log_file(GL_LOG_FILE));
+
+        // the diffuse and specular maps come from each material and are bound per
+        // material group when drawing; the ambient and emission maps are shared by
+        // the whole mesh, and go on the units after the ones the material binder uses.
+        let library = MaterialLibrary::new(materials.to_vec(), &|file_name: &str| {
+            resources.texture(file_name, load_texture_file).id()
+        });
+        let tex_amb = resources.texture(AMBIENT_MAP_FILE, load_texture_file).id();
+        let tex_emiss = resources.texture(EMISSION_MAP_FILE, load_texture_file).id();
+        let mut texture_binder = TextureBinder::new(shader_programme, material::HEIGHT_MAP_UNIT + 1);
+        for &(name, tex) in [("ambient_map", tex_amb), ("emission_map", tex_emiss)].iter() {
+            if let Err(e) = texture_binder.add_sampler(name, tex) {
+                logger.log_err(&e);
+            }
+        }
+
+        MeshBindings {
+            shader_programme: shader_programme,
+            uniforms: uniforms,
+            library: library,
+            material_binder: MaterialBinder::new(shader_programme),
+            texture_binder: texture_binder,
+        }
+    }
+
+    ///
+    /// Log the materials the mesh's groups name that it does not have.
+    ///
+    fn check_groups(&self, logger: &Logger, groups: &[MaterialGroup]) {
+        for group in groups.iter() {
+            if !group.material.is_empty() && !self.library.contains(&group.material) {
+                logger.log_err(&format!(
+                    "WARNING: material {} not found; using the default material.", group.material
+                ));
+            }
+        }
+    }
+}
+
 fn main() {
     let logger = restart_gl_log(GL_LOG_FILE);
     // Start a GL context and O/S window using the GLFW helper library.
@@ -126,138 +242,81 @@ fn main() {
         gl::Viewport(0, 0, context.width as i32, context.height as i32);
     }
 
+    // every mesh, texture, and shader programme below is loaded through the
+    // cache, so a file used twice is only loaded once.
+    let resources = ResourceCache::new();
+    let load_texture_file = |file_name: &str| {
+        let mut tex = 0;
+        load_texture(file_name, &mut tex);
+        tex
+    };
+
     /*------------------------------CREATE GEOMETRY------------------------------*/
-    let mesh = match obj_parser::load_obj_file(MESH_FILE) {
+    // a mesh file that fails to load is replaced by the embedded fallback mesh,
+    // which the cache keeps in its place, so it is made again along with the rest.
+    let load_mesh = |file_name: &str| -> Result<Model, String> {
+        match Model::load_cached(file_name) {
+            Ok(val) => Ok(val),
+            Err(e) => {
+                logger.log_err(&format!("ERROR: loading mesh file. Loader returned error\n{}", e));
+                logger.log_err("WARNING: using the embedded fallback mesh instead.");
+                Ok(Model::from_obj_mesh(assets::fallback_mesh(), vec![]))
+            }
+        }
+    };
+    let mesh = match resources.mesh(MESH_FILE, load_mesh, &upload_mesh) {
         Ok(val) => val,
         Err(e) => {
-            logger.log_err(&format!("ERROR: loading mesh file. Loader returned error\n{}", e));
+            logger.log_err(&e);
             process::exit(1);
         }
     };
-
-    let vp = mesh.points;
-    let vn = mesh.normals;
-    let vt = mesh.tex_coords;
-    let g_point_count = mesh.point_count;
-
-    let mut vao = 0;
-    unsafe {
-        gl::GenVertexArrays(1, &mut vao);
-        gl::BindVertexArray(vao);
-    }
-    assert!(vao > 0);
-
-    let mut points_vbo = 0;
-    unsafe {
-        gl::GenBuffers(1, &mut points_vbo);
-        gl::BindBuffer(gl::ARRAY_BUFFER, points_vbo);
-        gl::BufferData(
-            gl::ARRAY_BUFFER, (3 * g_point_count * mem::size_of::<GLfloat>()) as GLsizeiptr, 
-            vp.as_ptr() as *const GLvoid, gl::STATIC_DRAW
-        );
-        gl::VertexAttribPointer(0, 3, gl::FLOAT, gl::FALSE, 0, ptr::null());
-        gl::EnableVertexAttribArray(0);
-    }
-    assert!(points_vbo > 0);
-
-    let mut normals_vbo = 0;
-    unsafe {
-        gl::GenBuffers(1, &mut normals_vbo);
-        gl::BindBuffer(gl::ARRAY_BUFFER, normals_vbo);
-        gl::BufferData(
-            gl::ARRAY_BUFFER, (3 * g_point_count * mem::size_of::<GLfloat>()) as GLsizeiptr, 
-            vn.as_ptr() as *const GLvoid, gl::STATIC_DRAW
-        );
-        gl::VertexAttribPointer(1, 3, gl::FLOAT, gl::FALSE, 0, ptr::null());
-        gl::EnableVertexAttribArray(1);
-    }
-    assert!(normals_vbo > 0);
-
-    let mut texcoords_vbo = 0;
-    unsafe {
-        gl::GenBuffers(1, &mut texcoords_vbo);
-        gl::BindBuffer(gl::ARRAY_BUFFER, texcoords_vbo);
-        gl::BufferData(
-            gl::ARRAY_BUFFER, (2 * g_point_count * mem::size_of::<GLfloat>()) as GLsizeiptr, 
-            vp.as_ptr() as *const GLvoid, gl::STATIC_DRAW
-        );
-        gl::VertexAttribPointer(2, 2, gl::FLOAT, gl::FALSE, 0, ptr::null());
-        gl::EnableVertexAttribArray(2);
-    }
-    assert!(texcoords_vbo > 0);
+    let groups = &mesh.model().groups;
+    let materials = &mesh.model().materials;
 
     /*-------------------------------CREATE SHADERS------------------------------*/
-    let shader_programme = create_programme_from_files(&logger, VERTEX_SHADER_FILE, FRAGMENT_SHADER_FILE);
-    let view_mat_location = unsafe {
-        gl::GetUniformLocation(shader_programme, "view".as_ptr() as *const i8)
-    };
-    assert!(view_mat_location > -1);
-    let proj_mat_location = unsafe { 
-        gl::GetUniformLocation(shader_programme, "proj".as_ptr() as *const i8)
-    };
-    assert!(proj_mat_location > -1);
-
-    /* if converting to GLSL 410 do this to replace GLSL texture bindings:
-    GLint diffuse_map_loc, specular_map_loc, ambient_map_loc, emission_map_loc;
-    diffuse_map_loc = glGetUniformLocation (shader_programme, "diffuse_map");
-    specular_map_loc = glGetUniformLocation (shader_programme, "specular_map");
-    ambient_map_loc = glGetUniformLocation (shader_programme, "ambient_map");
-    emission_map_loc = glGetUniformLocation (shader_programme, "emission_map");
-    assert (diffuse_map_loc > -1);
-    assert (specular_map_loc > -1);
-    assert (ambient_map_loc > -1);
-    assert (emission_map_loc > -1);
-    glUseProgram (shader_programme);
-    glUniform1i (diffuse_map_loc, 0);
-    glUniform1i (specular_map_loc, 1);
-    glUniform1i (ambient_map_loc, 2);
-    glUniform1i (emission_map_loc, 3);
-    */
-
-    // load texture
-    let mut tex_diff = 0; 
-    let mut tex_spec = 0; 
-    let mut tex_amb = 0; 
-    let mut tex_emiss = 0;
-    unsafe {
-        gl::ActiveTexture(gl::TEXTURE0);
-        load_texture("boulder_diff.png", &mut tex_diff);
-        gl::ActiveTexture(gl::TEXTURE1);
-        load_texture("boulder_spec.png", &mut tex_spec);
-        gl::ActiveTexture(gl::TEXTURE2);
-        load_texture("ao.png", &mut tex_amb);
-        gl::ActiveTexture(gl::TEXTURE3);
-        load_texture("tileable9b_emiss.png", &mut tex_emiss);
-    }
+    let load_program = |vert: &str, frag: &str| create_programme_from_files(&logger, vert, frag);
+    let mut bindings = MeshBindings::new(&resources, &logger, materials, &load_program, &load_texture_file);
+    introspect(bindings.shader_programme).log(&logger);
+    bindings.check_groups(&logger, groups);
+    let stats = resources.stats();
+    logger.log(&format!(
+        "resources: {} textures, {} meshes, {} programmes loaded; {} loads shared",
+        stats.textures, stats.meshes, stats.programs, stats.hits
+    ));
+
+    // the texture inspector lists every texture in the cache, and draws the
+    // selected one over the scene one texel to a pixel.
+    let inspector_programme = resources.program(FULLSCREEN_VS_FILE, TEXTURE_INSPECTOR_FS_FILE, load_program);
+    let mut inspector = TextureInspector::new(inspector_programme.id());
+    let mut overlay = Overlay::new(&logger);
+    logger.log("keys: I shows the texture inspector, Tab and Shift+Tab select a texture,");
+    logger.log("      C shows one channel at a time, and [ and ] select the mip level;");
+    logger.log("      Alt+Enter toggles fullscreen and makes every GL object in the cache again");
 
     // input variables
     let near = 0.1;                                  // clipping plane
     let far = 100.0;                                 // clipping plane
     let fov = 67.0;                                  // convert 67 degrees to radians
     let aspect = context.width as f32 / context.height as f32; // aspect ratio
-    let proj_mat = Mat4::perspective(fov, aspect, near, far);
+    let mut proj_mat = Mat4::perspective(fov, aspect, near, far);
 
     // matrix components
     let cam_speed: GLfloat = 1.0;             // 1 unit per second
     let cam_yaw_speed: GLfloat = 10.0;        // 10 degrees per second
     let mut cam_pos: [GLfloat; 3] = [0.0, 0.0, 5.0]; // don't start at zero, or we will be too close
     let mut cam_yaw: GLfloat = 0.0;               // y-rotation in degrees
-    let mut mat_trans = Mat4::identity().translate(&math::vec3((-cam_pos[0], -cam_pos[1], -cam_pos[2])));
-    let mut mat_rot = Mat4::identity().rotate_y_deg(-cam_yaw);
+    let mut mat_trans = Mat4::from_translation(&math::vec3((-cam_pos[0], -cam_pos[1], -cam_pos[2])));
+    let mut mat_rot = Mat4::from_rotation_y_deg(-cam_yaw);
     let mut view_mat = mat_rot * mat_trans;
 
-    unsafe {
-        gl::UseProgram(shader_programme);
-        gl::UniformMatrix4fv(view_mat_location, 1, gl::FALSE, view_mat.as_ptr());
-        gl::UniformMatrix4fv(proj_mat_location, 1, gl::FALSE, proj_mat.as_ptr());
-    }
+    bindings.uniforms.set_mat4("view", &view_mat);
+    bindings.uniforms.set_mat4("proj", &proj_mat);
 
     while !context.window.should_close() {
         let current_seconds = context.glfw.get_time();
-        let elapsed_seconds = unsafe { current_seconds - PREVIOUS_SECONDS };
-        unsafe {
-            PREVIOUS_SECONDS = current_seconds;
-        }
+        let elapsed_seconds = current_seconds - context.elapsed_time_seconds;
+        context.elapsed_time_seconds = current_seconds;
 
         update_fps_counter(&mut context);
         unsafe {
@@ -265,15 +324,64 @@ fn main() {
             gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
             gl::Viewport(0, 0, context.width as i32, context.height as i32);
 
-            gl::UseProgram(shader_programme);
-            gl::BindVertexArray(vao);
-            // draw points 0-3 from the currently bound VAO with current in-use shader
-            gl::DrawArrays(gl::TRIANGLES, 0, g_point_count as i32);
-            // update other events like input handling
+            gl::UseProgram(bindings.shader_programme);
+            gl::BindVertexArray(mesh.vao());
+        }
+        bindings.texture_binder.bind();
+        // draw each material group with its own material and textures
+        material::draw_material_groups(&bindings.material_binder, &bindings.library, groups);
+
+        if inspector.visible {
+            inspector.draw(&mut overlay, &resources.textures(), context.width, context.height);
         }
 
         context.glfw.poll_events();
 
+        let mut toggle_fullscreen = false;
+        for (_, event) in glfw::flush_messages(&context.events) {
+            match event {
+                glfw::WindowEvent::Key(Key::I, _, Action::Press, _) => {
+                    inspector.visible = !inspector.visible;
+                }
+                glfw::WindowEvent::Key(Key::Tab, _, Action::Press, mods) => {
+                    inspector.select_next(resources.stats().textures, mods.contains(glfw::Modifiers::Shift));
+                }
+                glfw::WindowEvent::Key(Key::C, _, Action::Press, _) => {
+                    inspector.channel = inspector.channel.next();
+                }
+                glfw::WindowEvent::Key(Key::LeftBracket, _, Action::Press, _) => {
+                    inspector.change_mip_level(-1);
+                }
+                glfw::WindowEvent::Key(Key::RightBracket, _, Action::Press, _) => {
+                    inspector.change_mip_level(1);
+                }
+                glfw::WindowEvent::Key(Key::Enter, _, Action::Press, mods) if mods.contains(glfw::Modifiers::Alt) => {
+                    toggle_fullscreen = true;
+                }
+                _ => {}
+            }
+        }
+
+        // going fullscreen may recreate the context, so every GL object in the cache
+        // is made again afterwards, along with everything that holds their names
+        if toggle_fullscreen {
+            let fullscreen = context.toggle_fullscreen();
+            logger.log(&format!("fullscreen: {}", fullscreen));
+            let (width, height) = context.window.get_framebuffer_size();
+            context.width = width as u32;
+            context.height = height as u32;
+            proj_mat = Mat4::perspective(fov, width as f32 / height as f32, near, far);
+
+            let failures = resources.recreate_all(
+                &Loaders { texture: &load_texture_file, program: &load_program, mesh: &upload_mesh }
+            );
+            bindings = MeshBindings::new(&resources, &logger, materials, &load_program, &load_texture_file);
+            inspector.set_program(inspector_programme.id());
+            bindings.uniforms.set_mat4("view", &view_mat);
+            bindings.uniforms.set_mat4("proj", &proj_mat);
+            logger.log(&format!("reloaded every GL object, {} failed", failures));
+        }
+
         // control keys
         let mut cam_moved = false;
         match context.window.get_key(Key::A) {
@@ -334,12 +442,10 @@ fn main() {
         }
         // update view matrix
         if cam_moved {
-            mat_trans = Mat4::identity().translate(&math::vec3((-cam_pos[0], -cam_pos[1], -cam_pos[2]))); // cam translation
-            mat_rot = Mat4::identity().rotate_y_deg(-cam_yaw);
+            mat_trans = Mat4::from_translation(&math::vec3((-cam_pos[0], -cam_pos[1], -cam_pos[2]))); // cam translation
+            mat_rot = Mat4::from_rotation_y_deg(-cam_yaw);
             view_mat = mat_rot * mat_trans;
-            unsafe {
-                gl::UniformMatrix4fv(view_mat_location, 1, gl::FALSE, view_mat.as_ptr());
-            }
+            bindings.uniforms.set_mat4("view", &view_mat);
         }
 
         match context.window.get_key(Key::Escape) {
@@ -0,0 +1,310 @@
+use gl;
+use gl::types::{GLenum, GLsizei, GLuint};
+
+use gl_utils::glubyte_ptr_to_string;
+use logger::Logger;
+
+use std::ffi::c_void;
+use std::fs;
+use std::path::Path;
+
+
+///
+/// The directory linked programmes are cached in, under the directory the demo
+/// runs from.
+///
+pub const PROGRAM_CACHE_DIR: &str = "shader_cache";
+
+///
+/// The first bytes of every programme cache file.
+///
+const PROGRAM_CACHE_MAGIC: &[u8; 8] = b"PROGCACH";
+
+///
+/// The version of the cache layout. Bump it whenever the layout changes, so caches
+/// written by an older build are rebuilt rather than misread.
+///
+pub const PROGRAM_CACHE_VERSION: u32 = 1;
+
+fn fnv1a_hash_into(mut hash: u64, bytes: &[u8]) -> u64 {
+    for &byte in bytes {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+    }
+
+    hash
+}
+
+///
+/// The key a linked programme is cached under: a 64-bit FNV-1a hash of the
+/// source of each of its shaders, exactly as compiled, and of the driver that
+/// compiled them. A programme binary only works with the driver that made it.
+///
+pub fn cache_key(sources: &[&str], driver: &str) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for source in sources.iter().chain([driver].iter()) {
+        // The length keeps "ab" + "c" from hashing the same as "a" + "bc".
+        hash = fnv1a_hash_into(hash, &(source.len() as u64).to_le_bytes());
+        hash = fnv1a_hash_into(hash, source.as_bytes());
+    }
+
+    hash
+}
+
+pub fn cache_file_name(key: u64) -> String {
+    format!("{}/{:016x}.programcache", PROGRAM_CACHE_DIR, key)
+}
+
+///
+/// The vendor, renderer, and version strings of the GL driver.
+///
+pub fn driver_description() -> String {
+    let vendor = glubyte_ptr_to_string(unsafe { gl::GetString(gl::VENDOR) });
+    let renderer = glubyte_ptr_to_string(unsafe { gl::GetString(gl::RENDERER) });
+    let version = glubyte_ptr_to_string(unsafe { gl::GetString(gl::VERSION) });
+
+    format!("{}\n{}\n{}", vendor, renderer, version)
+}
+
+///
+/// Lay out a programme binary for the cache. The key and driver are stored with
+/// it, so a file that happens to have the right name for another programme, or
+/// that another driver wrote, is never handed to GL.
+///
+pub fn encode_program(key: u64, driver: &str, format: GLenum, binary: &[u8]) -> Vec<u8> {
+    let mut bytes = vec![];
+    bytes.extend_from_slice(PROGRAM_CACHE_MAGIC);
+    bytes.extend_from_slice(&PROGRAM_CACHE_VERSION.to_le_bytes());
+    bytes.extend_from_slice(&key.to_le_bytes());
+    bytes.extend_from_slice(&(driver.len() as u64).to_le_bytes());
+    bytes.extend_from_slice(driver.as_bytes());
+    bytes.extend_from_slice(&format.to_le_bytes());
+    bytes.extend_from_slice(&(binary.len() as u64).to_le_bytes());
+    bytes.extend_from_slice(binary);
+
+    bytes
+}
+
+///
+/// Reads values back out of a programme cache in the order they were written. The
+/// first problem is kept, and everything read after it comes back empty.
+///
+struct CacheReader<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+    error: Option<String>,
+}
+
+impl<'a> CacheReader<'a> {
+    fn new(bytes: &'a [u8]) -> CacheReader<'a> {
+        CacheReader { bytes: bytes, offset: 0, error: None }
+    }
+
+    fn fail(&mut self, error: String) {
+        if self.error.is_none() {
+            self.error = Some(error);
+        }
+    }
+
+    fn take(&mut self, len: usize) -> &'a [u8] {
+        if self.error.is_some() {
+            return &[];
+        }
+        if len > self.bytes.len() - self.offset {
+            self.fail(String::from("ERROR: the programme cache ends early."));
+            return &[];
+        }
+        let taken = &self.bytes[self.offset..self.offset + len];
+        self.offset += len;
+
+        taken
+    }
+
+    fn u32(&mut self) -> u32 {
+        let mut value = [0; 4];
+        let bytes = self.take(4);
+        if bytes.len() == 4 {
+            value.copy_from_slice(bytes);
+        }
+
+        u32::from_le_bytes(value)
+    }
+
+    fn u64(&mut self) -> u64 {
+        let mut value = [0; 8];
+        let bytes = self.take(8);
+        if bytes.len() == 8 {
+            value.copy_from_slice(bytes);
+        }
+
+        u64::from_le_bytes(value)
+    }
+
+    ///
+    /// Read a length, then that many bytes. A length longer than the rest of the
+    /// cache can only come from a damaged cache.
+    ///
+    fn bytes(&mut self) -> &'a [u8] {
+        let len = self.u64();
+        if len > (self.bytes.len() - self.offset) as u64 {
+            self.fail(String::from("ERROR: the programme cache ends early."));
+            return &[];
+        }
+
+        self.take(len as usize)
+    }
+}
+
+///
+/// Read a programme binary back out of the cache, checking it was written for
+/// this key by this driver. Returns the binary's format and the binary.
+///
+pub fn decode_program(bytes: &[u8], key: u64, driver: &str) -> Result<(GLenum, Vec<u8>), String> {
+    let mut reader = CacheReader::new(bytes);
+    if reader.take(PROGRAM_CACHE_MAGIC.len()) != PROGRAM_CACHE_MAGIC {
+        return Err(String::from("ERROR: not a programme cache file."));
+    }
+    let version = reader.u32();
+    if version != PROGRAM_CACHE_VERSION {
+        return Err(format!(
+            "ERROR: the programme cache has version {}, but version {} is needed.", version, PROGRAM_CACHE_VERSION
+        ));
+    }
+    if reader.u64() != key {
+        return Err(String::from("ERROR: the programme cache is for a different programme."));
+    }
+    if reader.bytes() != driver.as_bytes() {
+        return Err(String::from("ERROR: the programme cache was written by a different driver."));
+    }
+    let format = reader.u32();
+    let binary = reader.bytes().to_vec();
+    match reader.error {
+        Some(e) => Err(e),
+        None => Ok((format, binary)),
+    }
+}
+
+///
+/// Make a programme from the binary cached under a key, if there is one and the
+/// driver takes it. Returns `None` when the programme has to be built from its
+/// shaders instead.
+///
+pub fn load_program(logger: &Logger, key: u64, driver: &str) -> Option<GLuint> {
+    let file_name = cache_file_name(key);
+    let bytes = match fs::read(&file_name) {
+        Ok(val) => val,
+        Err(_) => return None,
+    };
+    let (format, binary) = match decode_program(&bytes, key, driver) {
+        Ok(val) => val,
+        Err(e) => {
+            logger.log_err(&format!("{} Rebuilding {}.\n", e, file_name));
+            return None;
+        }
+    };
+
+    let programme = unsafe { gl::CreateProgram() };
+    let mut params = -1;
+    unsafe {
+        gl::ProgramBinary(programme, format, binary.as_ptr() as *const c_void, binary.len() as GLsizei);
+        gl::GetProgramiv(programme, gl::LINK_STATUS, &mut params);
+    }
+    if params != gl::TRUE as i32 {
+        // Drivers may turn down binaries they made themselves, after an update.
+        logger.log_err(&format!("WARNING: the driver turned down the programme binary in {}.\n", file_name));
+        unsafe {
+            gl::DeleteProgram(programme);
+        }
+
+        return None;
+    }
+    logger.log(&format!("Loaded programme {} from {}\n", programme, file_name));
+
+    Some(programme)
+}
+
+///
+/// Cache a linked programme's binary under a key, if the driver can give one.
+///
+pub fn save_program(logger: &Logger, key: u64, driver: &str, programme: GLuint) {
+    let mut format_count = 0;
+    let mut binary_len = 0;
+    unsafe {
+        gl::GetIntegerv(gl::NUM_PROGRAM_BINARY_FORMATS, &mut format_count);
+        gl::GetProgramiv(programme, gl::PROGRAM_BINARY_LENGTH, &mut binary_len);
+    }
+    if format_count <= 0 || binary_len <= 0 {
+        return;
+    }
+
+    let mut binary = vec![0; binary_len as usize];
+    let mut written: GLsizei = 0;
+    let mut format: GLenum = 0;
+    unsafe {
+        gl::GetProgramBinary(
+            programme, binary_len, &mut written, &mut format, binary.as_mut_ptr() as *mut c_void
+        );
+    }
+    binary.truncate(written as usize);
+
+    let file_name = cache_file_name(key);
+    let bytes = encode_program(key, driver, format, &binary);
+    let result = fs::create_dir_all(Path::new(PROGRAM_CACHE_DIR)).and_then(|_| fs::write(&file_name, &bytes));
+    match result {
+        Ok(_) => {
+            logger.log(&format!("Cached programme {} in {}\n", programme, file_name));
+        }
+        Err(e) => {
+            logger.log_err(&format!("WARNING: could not write the programme cache {}: {}\n", file_name, e));
+        }
+    }
+}
+
+mod program_cache_tests {
+    use super::{cache_file_name, cache_key, decode_program, encode_program};
+
+    const DRIVER: &str = "Vendor\nRenderer\n4.1 Driver 1.0";
+
+    #[test]
+    fn test_the_key_depends_on_every_source_and_the_driver() {
+        let key = cache_key(&["vertex", "fragment"], DRIVER);
+
+        assert_eq!(key, cache_key(&["vertex", "fragment"], DRIVER));
+        assert!(key != cache_key(&["vertex", "fragment "], DRIVER));
+        assert!(key != cache_key(&["fragment", "vertex"], DRIVER));
+        assert!(key != cache_key(&["vertexf", "ragment"], DRIVER));
+        assert!(key != cache_key(&["vertex", "fragment"], "Vendor\nRenderer\n4.1 Driver 1.1"));
+    }
+
+    #[test]
+    fn test_cache_files_are_named_by_key() {
+        assert_eq!(cache_file_name(0x1234_abcd), "shader_cache/000000001234abcd.programcache");
+    }
+
+    #[test]
+    fn test_a_program_binary_round_trips_through_the_cache() {
+        let bytes = encode_program(42, DRIVER, 0x8741, &[1, 2, 3, 4, 5]);
+
+        assert_eq!(decode_program(&bytes, 42, DRIVER), Ok((0x8741, vec![1, 2, 3, 4, 5])));
+    }
+
+    #[test]
+    fn test_a_cache_for_another_key_or_driver_is_refused() {
+        let bytes = encode_program(42, DRIVER, 0x8741, &[1, 2, 3]);
+
+        assert!(decode_program(&bytes, 43, DRIVER).is_err());
+        assert!(decode_program(&bytes, 42, "Vendor\nRenderer\n4.1 Driver 1.1").is_err());
+    }
+
+    #[test]
+    fn test_damaged_caches_are_refused() {
+        let bytes = encode_program(42, DRIVER, 0x8741, &[1, 2, 3]);
+
+        for len in 0..bytes.len() {
+            assert!(decode_program(&bytes[..len], 42, DRIVER).is_err(), "{} bytes were accepted", len);
+        }
+        let mut wrong_version = bytes.clone();
+        wrong_version[8] += 1;
+        assert!(decode_program(&wrong_version, 42, DRIVER).is_err());
+    }
+}
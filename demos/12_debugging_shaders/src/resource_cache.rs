@@ -0,0 +1,694 @@
+use gl;
+use gl::types::{GLsizei, GLuint};
+
+use model::Model;
+
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::rc::Rc;
+
+
+///
+/// The functions that make each kind of GL object from what it was made from:
+/// a texture from its image file, a shader programme from its two shader files,
+/// and a mesh's vertex array from its model.
+///
+pub struct Loaders<'a> {
+    pub texture: &'a dyn Fn(&str) -> GLuint,
+    pub program: &'a dyn Fn(&str, &str) -> GLuint,
+    pub mesh: &'a dyn Fn(&Model) -> MeshBuffers,
+}
+
+///
+/// A GL object that keeps what it was made from, so it can be made again. GL
+/// objects belong to the context they were made in, and are gone with it when
+/// the window is recreated, as it may be to go fullscreen or to ask for another
+/// GL version. `invalidate` forgets an object from a context that has gone, and
+/// `upload` makes it again in the context that is current now, with whichever
+/// of the `loaders` it needs. `release` deletes an object while its context is
+/// still current.
+///
+pub trait GpuResident {
+    ///
+    /// Make the GL object in the current context, unless it is already there.
+    /// Returns whether it is there afterwards.
+    ///
+    fn upload(&self, loaders: &Loaders) -> bool;
+
+    ///
+    /// Forget the GL object without deleting it. Its name belongs to a context
+    /// that has gone, and in the new one could be another object's.
+    ///
+    fn invalidate(&self);
+
+    ///
+    /// Delete the GL object from the current context, and forget it.
+    ///
+    fn release(&self);
+
+    fn is_resident(&self) -> bool;
+}
+
+///
+/// A GL texture shared through a `ResourceCache`. The texture is deleted when the
+/// last reference to it is dropped. An id of zero means the texture failed to load,
+/// or has not been uploaded again since its context was lost.
+///
+#[derive(Debug)]
+pub struct Texture {
+    id: Cell<GLuint>,
+    file_name: String,
+}
+
+impl Texture {
+    pub fn id(&self) -> GLuint {
+        self.id.get()
+    }
+}
+
+impl GpuResident for Texture {
+    fn upload(&self, loaders: &Loaders) -> bool {
+        if self.id.get() == 0 {
+            self.id.set((loaders.texture)(&self.file_name));
+        }
+
+        self.is_resident()
+    }
+
+    fn invalidate(&self) {
+        self.id.set(0);
+    }
+
+    fn release(&self) {
+        // The functions are not loaded when there is no GL context, as in the tests.
+        let id = self.id.get();
+        if id != 0 && gl::DeleteTextures::is_loaded() {
+            unsafe {
+                gl::DeleteTextures(1, &id);
+            }
+        }
+        self.id.set(0);
+    }
+
+    fn is_resident(&self) -> bool {
+        self.id.get() != 0
+    }
+}
+
+impl Drop for Texture {
+    fn drop(&mut self) {
+        self.release();
+    }
+}
+
+///
+/// A GL shader programme shared through a `ResourceCache`. The programme is deleted
+/// when the last reference to it is dropped. An id of zero means it failed to build,
+/// or has not been built again since its context was lost.
+///
+#[derive(Debug)]
+pub struct Program {
+    id: Cell<GLuint>,
+    vertex_shader_file: String,
+    fragment_shader_file: String,
+}
+
+impl Program {
+    pub fn id(&self) -> GLuint {
+        self.id.get()
+    }
+}
+
+impl GpuResident for Program {
+    fn upload(&self, loaders: &Loaders) -> bool {
+        if self.id.get() == 0 {
+            self.id.set((loaders.program)(&self.vertex_shader_file, &self.fragment_shader_file));
+        }
+
+        self.is_resident()
+    }
+
+    fn invalidate(&self) {
+        self.id.set(0);
+    }
+
+    fn release(&self) {
+        let id = self.id.get();
+        if id != 0 && gl::DeleteProgram::is_loaded() {
+            unsafe {
+                gl::DeleteProgram(id);
+            }
+        }
+        self.id.set(0);
+    }
+
+    fn is_resident(&self) -> bool {
+        self.id.get() != 0
+    }
+}
+
+impl Drop for Program {
+    fn drop(&mut self) {
+        self.release();
+    }
+}
+
+///
+/// The GL objects a mesh is drawn from: its vertex array, the buffers behind it
+/// with any index buffer last, and how many vertices went into them, which is
+/// fewer than the model's points where they were welded.
+///
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MeshBuffers {
+    pub vao: GLuint,
+    pub buffers: Vec<GLuint>,
+    pub vertex_count: usize,
+}
+
+///
+/// A model together with its vertex array, shared through a `ResourceCache` or
+/// held on its own. The GL objects are deleted when the last reference to it is
+/// dropped. A vertex array of zero means the mesh has not been uploaded since it
+/// was made, or since its context was lost.
+///
+#[derive(Debug)]
+pub struct Mesh {
+    model: Model,
+    buffers: RefCell<MeshBuffers>,
+}
+
+impl Mesh {
+    ///
+    /// A mesh for a model, not yet uploaded.
+    ///
+    pub fn new(model: Model) -> Mesh {
+        Mesh {
+            model: model,
+            buffers: RefCell::new(MeshBuffers::default()),
+        }
+    }
+
+    pub fn model(&self) -> &Model {
+        &self.model
+    }
+
+    pub fn vao(&self) -> GLuint {
+        self.buffers.borrow().vao
+    }
+
+    pub fn vertex_count(&self) -> usize {
+        self.buffers.borrow().vertex_count
+    }
+
+    ///
+    /// Upload the mesh with `upload_mesh` alone, for a mesh uploaded before there
+    /// are any textures or programmes to go with it.
+    ///
+    pub fn upload_with(&self, upload_mesh: &dyn Fn(&Model) -> MeshBuffers) -> bool {
+        if !self.is_resident() {
+            *self.buffers.borrow_mut() = upload_mesh(&self.model);
+        }
+
+        self.is_resident()
+    }
+}
+
+impl GpuResident for Mesh {
+    fn upload(&self, loaders: &Loaders) -> bool {
+        self.upload_with(loaders.mesh)
+    }
+
+    fn invalidate(&self) {
+        *self.buffers.borrow_mut() = MeshBuffers::default();
+    }
+
+    fn release(&self) {
+        {
+            let buffers = self.buffers.borrow();
+            if buffers.vao != 0 && gl::DeleteVertexArrays::is_loaded() {
+                unsafe {
+                    gl::DeleteBuffers(buffers.buffers.len() as GLsizei, buffers.buffers.as_ptr());
+                    gl::DeleteVertexArrays(1, &buffers.vao);
+                }
+            }
+        }
+        self.invalidate();
+    }
+
+    fn is_resident(&self) -> bool {
+        self.buffers.borrow().vao != 0
+    }
+}
+
+impl Drop for Mesh {
+    fn drop(&mut self) {
+        self.release();
+    }
+}
+
+///
+/// How often a `ResourceCache` has found a resource it already had, how often it
+/// had to load one, and how many of each kind of resource it holds.
+///
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct ResourceStats {
+    pub hits: usize,
+    pub misses: usize,
+    pub textures: usize,
+    pub meshes: usize,
+    pub programs: usize,
+}
+
+///
+/// Normalise a file path so that different spellings of the same file give the
+/// same key: backslashes become forward slashes, repeated slashes and `.`
+/// components are dropped, and `..` removes the component before it where there
+/// is one. This works on the text alone, so the file does not need to exist.
+///
+pub fn normalize_path(file_name: &str) -> String {
+    let file_name = file_name.replace('\\', "/");
+    let absolute = file_name.starts_with('/');
+
+    let mut components: Vec<&str> = vec![];
+    for component in file_name.split('/') {
+        match component {
+            "" | "." => {}
+            ".." => {
+                match components.last() {
+                    Some(&last) if last != ".." => {
+                        components.pop();
+                    }
+                    // There is nothing above the root.
+                    _ if absolute => {}
+                    _ => components.push(".."),
+                }
+            }
+            _ => components.push(component),
+        }
+    }
+
+    let path = components.join("/");
+    if absolute {
+        format!("/{}", path)
+    } else if path.is_empty() {
+        String::from(".")
+    } else {
+        path
+    }
+}
+
+///
+/// Shares textures, meshes, and shader programmes between everything that uses
+/// them, so a file loaded twice under the same normalised path is only loaded
+/// once. Each resource comes back as an `Rc`, and stays alive as long as either
+/// the cache or one of its users holds it.
+///
+/// The cache takes the loading functions as arguments rather than knowing how to
+/// load anything itself, which keeps it free of image, shader, and vertex code.
+///
+pub struct ResourceCache {
+    textures: RefCell<HashMap<String, Rc<Texture>>>,
+    meshes: RefCell<HashMap<String, Rc<Mesh>>>,
+    programs: RefCell<HashMap<(String, String), Rc<Program>>>,
+    hits: Cell<usize>,
+    misses: Cell<usize>,
+}
+
+impl ResourceCache {
+    pub fn new() -> ResourceCache {
+        ResourceCache {
+            textures: RefCell::new(HashMap::new()),
+            meshes: RefCell::new(HashMap::new()),
+            programs: RefCell::new(HashMap::new()),
+            hits: Cell::new(0),
+            misses: Cell::new(0),
+        }
+    }
+
+    fn hit(&self) {
+        self.hits.set(self.hits.get() + 1);
+    }
+
+    fn miss(&self) {
+        self.misses.set(self.misses.get() + 1);
+    }
+
+    ///
+    /// Get the texture for an image file, calling `load_texture` with the normalised
+    /// path to load it the first time. A texture that failed to load is kept as
+    /// well, with an id of zero, so the file is not tried again on every lookup.
+    ///
+    pub fn texture<F>(&self, file_name: &str, load_texture: F) -> Rc<Texture>
+        where F: FnOnce(&str) -> GLuint
+    {
+        let key = normalize_path(file_name);
+        if let Some(texture) = self.textures.borrow().get(&key) {
+            self.hit();
+            return texture.clone();
+        }
+
+        self.miss();
+        let texture = Rc::new(Texture { id: Cell::new(load_texture(&key)), file_name: key.clone() });
+        self.textures.borrow_mut().insert(key, texture.clone());
+
+        texture
+    }
+
+    ///
+    /// Get the mesh for a model file, calling `load_model` with the normalised path
+    /// to load it and `upload_mesh` to upload it the first time. Unlike textures, a
+    /// mesh that failed to load is not kept, so the caller can fall back to
+    /// something else and try again later.
+    ///
+    pub fn mesh<F>(&self, file_name: &str, load_model: F, upload_mesh: &dyn Fn(&Model) -> MeshBuffers)
+        -> Result<Rc<Mesh>, String>
+        where F: FnOnce(&str) -> Result<Model, String>
+    {
+        let key = normalize_path(file_name);
+        if let Some(mesh) = self.meshes.borrow().get(&key) {
+            self.hit();
+            return Ok(mesh.clone());
+        }
+
+        self.miss();
+        let mesh = match load_model(&key) {
+            Ok(val) => Rc::new(Mesh::new(val)),
+            Err(e) => return Err(e),
+        };
+        mesh.upload_with(upload_mesh);
+        self.meshes.borrow_mut().insert(key, mesh.clone());
+
+        Ok(mesh)
+    }
+
+    ///
+    /// Get the shader programme built from a vertex and a fragment shader file,
+    /// calling `load_program` with the normalised paths to build it the first time.
+    ///
+    pub fn program<F>(&self, vertex_shader_file: &str, fragment_shader_file: &str, load_program: F) -> Rc<Program>
+        where F: FnOnce(&str, &str) -> GLuint
+    {
+        let key = (normalize_path(vertex_shader_file), normalize_path(fragment_shader_file));
+        if let Some(program) = self.programs.borrow().get(&key) {
+            self.hit();
+            return program.clone();
+        }
+
+        self.miss();
+        let program = Rc::new(Program {
+            id: Cell::new(load_program(&key.0, &key.1)),
+            vertex_shader_file: key.0.clone(),
+            fragment_shader_file: key.1.clone(),
+        });
+        self.programs.borrow_mut().insert(key, program.clone());
+
+        program
+    }
+
+    ///
+    /// Every texture the cache holds, with the normalised path it was loaded from,
+    /// in order of path. Textures that failed to load are listed too, with an id
+    /// of zero.
+    ///
+    pub fn textures(&self) -> Vec<(String, Rc<Texture>)> {
+        let mut textures: Vec<(String, Rc<Texture>)> = self.textures.borrow().iter()
+            .map(|(name, texture)| (name.clone(), texture.clone()))
+            .collect();
+        textures.sort_by(|a, b| a.0.cmp(&b.0));
+
+        textures
+    }
+
+    ///
+    /// Every GL object the cache holds, textures first, then meshes, then
+    /// programmes.
+    ///
+    fn residents(&self) -> Vec<Rc<dyn GpuResident>> {
+        let mut residents: Vec<Rc<dyn GpuResident>> = vec![];
+        for texture in self.textures.borrow().values() {
+            residents.push(texture.clone());
+        }
+        for mesh in self.meshes.borrow().values() {
+            residents.push(mesh.clone());
+        }
+        for program in self.programs.borrow().values() {
+            residents.push(program.clone());
+        }
+
+        residents
+    }
+
+    ///
+    /// Forget the GL objects of every texture, mesh, and programme the cache holds,
+    /// after the context they were made in has gone. Everything keeps its place in
+    /// the cache, and everyone holding one keeps their `Rc`, so `upload_all` brings
+    /// them all back at once.
+    ///
+    pub fn invalidate_all(&self) {
+        for resident in self.residents() {
+            resident.invalidate();
+        }
+    }
+
+    ///
+    /// Delete the GL objects of every texture, mesh, and programme the cache holds,
+    /// while their context is still current. `upload_all` makes them again.
+    ///
+    pub fn release_all(&self) {
+        for resident in self.residents() {
+            resident.release();
+        }
+    }
+
+    ///
+    /// Make every texture, mesh, and programme that is not resident again in the
+    /// current context, from the normalised paths they were first asked for with
+    /// and the models they were loaded into. Returns how many could not be made,
+    /// which includes any textures or programmes that failed the first time.
+    ///
+    pub fn upload_all(&self, loaders: &Loaders) -> usize {
+        self.residents().iter().filter(|resident| !resident.upload(loaders)).count()
+    }
+
+    ///
+    /// Delete every texture, mesh, and programme the cache holds and make them all
+    /// again in the current context, as after switching to or from fullscreen.
+    /// Returns how many could not be made, as `upload_all` does.
+    ///
+    pub fn recreate_all(&self, loaders: &Loaders) -> usize {
+        self.release_all();
+
+        self.upload_all(loaders)
+    }
+
+    pub fn stats(&self) -> ResourceStats {
+        ResourceStats {
+            hits: self.hits.get(),
+            misses: self.misses.get(),
+            textures: self.textures.borrow().len(),
+            meshes: self.meshes.borrow().len(),
+            programs: self.programs.borrow().len(),
+        }
+    }
+
+    ///
+    /// Drop the cache's references to everything it holds and reset the statistics.
+    /// Resources nothing else holds are freed straight away; the rest are freed when
+    /// their last user drops them, and are loaded afresh if asked for again.
+    ///
+    pub fn clear(&self) {
+        self.textures.borrow_mut().clear();
+        self.meshes.borrow_mut().clear();
+        self.programs.borrow_mut().clear();
+        self.hits.set(0);
+        self.misses.set(0);
+    }
+}
+
+mod resource_cache_tests {
+    use super::{normalize_path, GpuResident, Loaders, MeshBuffers, ResourceCache, ResourceStats};
+    use model::Model;
+    use obj_parser::ObjMesh;
+
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    fn triangle() -> Model {
+        let mesh = ObjMesh {
+            point_count: 3,
+            points: vec![0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0],
+            tex_coords: vec![],
+            normals: vec![0.0, 0.0, 1.0, 0.0, 0.0, 1.0, 0.0, 0.0, 1.0],
+            material_libs: vec![],
+            groups: vec![],
+        };
+
+        Model::from_obj_mesh(mesh, vec![])
+    }
+
+    // Stands in for uploading a mesh, without a GL context: the vertex array is
+    // named after the number of points, and the buffers are left out.
+    fn upload_mesh(model: &Model) -> MeshBuffers {
+        MeshBuffers { vao: model.point_count as u32, buffers: vec![], vertex_count: model.point_count }
+    }
+
+    #[test]
+    fn test_normalize_path_gives_one_spelling_per_file() {
+        assert_eq!(normalize_path("src/skull.png"), "src/skull.png");
+        assert_eq!(normalize_path("./src//skull.png"), "src/skull.png");
+        assert_eq!(normalize_path("src\\textures\\..\\skull.png"), "src/skull.png");
+        assert_eq!(normalize_path("../assets/./skull.png"), "../assets/skull.png");
+        assert_eq!(normalize_path("../../skull.png"), "../../skull.png");
+        assert_eq!(normalize_path("/data/../skull.png"), "/skull.png");
+        assert_eq!(normalize_path("/../skull.png"), "/skull.png");
+        assert_eq!(normalize_path("src/.."), ".");
+    }
+
+    #[test]
+    fn test_textures_are_loaded_once_per_normalised_path() {
+        let cache = ResourceCache::new();
+        let loads = Cell::new(0);
+        let load = |file_name: &str| {
+            assert_eq!(file_name, "src/skull.png");
+            loads.set(loads.get() + 1);
+            0
+        };
+
+        let first = cache.texture("src/skull.png", &load);
+        let second = cache.texture("./src/../src/skull.png", &load);
+
+        assert_eq!(loads.get(), 1);
+        assert!(Rc::ptr_eq(&first, &second));
+        assert_eq!(cache.stats(), ResourceStats { hits: 1, misses: 1, textures: 1, meshes: 0, programs: 0 });
+    }
+
+    #[test]
+    fn test_textures_are_listed_by_path() {
+        let cache = ResourceCache::new();
+        let skull = cache.texture("src/./skull.png", |_| 0);
+        cache.texture("src/ao.png", |_| 0);
+        cache.texture("src/skull.png", |_| panic!("the texture should come from the cache"));
+
+        let textures = cache.textures();
+        let names: Vec<&str> = textures.iter().map(|entry| entry.0.as_str()).collect();
+
+        assert_eq!(names, ["src/ao.png", "src/skull.png"]);
+        assert!(Rc::ptr_eq(&textures[1].1, &skull));
+    }
+
+    #[test]
+    fn test_meshes_are_shared_and_failures_are_not_kept() {
+        let cache = ResourceCache::new();
+
+        let failed = cache.mesh("src/suzanne.obj", |_| Err(String::from("ERROR: no such file")), &upload_mesh);
+        assert!(failed.is_err());
+        assert_eq!(cache.stats().meshes, 0);
+
+        let first = cache.mesh("src/suzanne.obj", |_| Ok(triangle()), &upload_mesh).unwrap();
+        let second = cache.mesh(
+            "src/suzanne.obj", |_| panic!("the mesh should come from the cache"), &upload_mesh
+        ).unwrap();
+
+        assert!(Rc::ptr_eq(&first, &second));
+        assert_eq!(second.model().point_count, 3);
+        assert!(second.is_resident());
+        assert_eq!(cache.stats(), ResourceStats { hits: 1, misses: 2, textures: 0, meshes: 1, programs: 0 });
+    }
+
+    #[test]
+    fn test_programs_are_keyed_by_both_shaders() {
+        let cache = ResourceCache::new();
+
+        let first = cache.program("src/test.vert.glsl", "src/test.frag.glsl", |_, _| 0);
+        let second = cache.program("src/./test.vert.glsl", "src/test.frag.glsl", |_, _| 0);
+        let other = cache.program("src/test.vert.glsl", "src/other.frag.glsl", |_, _| 0);
+
+        assert!(Rc::ptr_eq(&first, &second));
+        assert!(!Rc::ptr_eq(&first, &other));
+        assert_eq!(cache.stats().programs, 2);
+    }
+
+    #[test]
+    fn test_everything_is_uploaded_again_after_the_context_is_lost() {
+        let cache = ResourceCache::new();
+        let skull = cache.texture("src/skull.png", |_| 7);
+        let program = cache.program("src/test.vert.glsl", "src/test.frag.glsl", |_, _| 3);
+        let mesh = cache.mesh("src/suzanne.obj", |_| Ok(triangle()), &upload_mesh).unwrap();
+
+        cache.invalidate_all();
+        assert!(!skull.is_resident() && !program.is_resident() && !mesh.is_resident());
+
+        let failures = cache.upload_all(&Loaders {
+            texture: &|file_name: &str| {
+                assert_eq!(file_name, "src/skull.png");
+                8
+            },
+            program: &|vert: &str, frag: &str| {
+                assert_eq!((vert, frag), ("src/test.vert.glsl", "src/test.frag.glsl"));
+                4
+            },
+            mesh: &upload_mesh,
+        });
+
+        assert_eq!(failures, 0);
+        assert_eq!(skull.id(), 8);
+        assert_eq!(program.id(), 4);
+        assert_eq!(mesh.vao(), 3);
+        assert_eq!(cache.stats().textures, 1);
+    }
+
+    #[test]
+    fn test_uploading_leaves_resident_objects_alone_and_retries_failures() {
+        let cache = ResourceCache::new();
+        let loaded = cache.texture("src/ao.png", |_| 5);
+        let failed = cache.texture("src/missing.png", |_| 0);
+
+        let loads = Cell::new(0);
+        let failures = cache.upload_all(&Loaders {
+            texture: &|_: &str| {
+                loads.set(loads.get() + 1);
+                0
+            },
+            program: &|_: &str, _: &str| 0,
+            mesh: &upload_mesh,
+        });
+
+        assert_eq!((failures, loads.get()), (1, 1));
+        assert_eq!(loaded.id(), 5);
+        assert!(!failed.is_resident());
+    }
+
+    #[test]
+    fn test_recreating_makes_every_object_again_even_if_it_was_resident() {
+        let cache = ResourceCache::new();
+        let skull = cache.texture("src/skull.png", |_| 7);
+        let program = cache.program("src/test.vert.glsl", "src/test.frag.glsl", |_, _| 3);
+        let mesh = cache.mesh("src/suzanne.obj", |_| Ok(triangle()), &upload_mesh).unwrap();
+
+        let failures = cache.recreate_all(&Loaders {
+            texture: &|_: &str| 9,
+            program: &|_: &str, _: &str| 5,
+            mesh: &|model: &Model| MeshBuffers { vao: 6, ..upload_mesh(model) },
+        });
+
+        assert_eq!(failures, 0);
+        assert_eq!(skull.id(), 9);
+        assert_eq!(program.id(), 5);
+        assert_eq!(mesh.vao(), 6);
+        assert_eq!(cache.stats(), ResourceStats { hits: 0, misses: 3, textures: 1, meshes: 1, programs: 1 });
+    }
+
+    #[test]
+    fn test_clear_lets_go_of_everything_but_outside_references() {
+        let cache = ResourceCache::new();
+        let kept = cache.mesh("src/suzanne.obj", |_| Ok(triangle()), &upload_mesh).unwrap();
+        cache.texture("src/skull.png", |_| 0);
+
+        cache.clear();
+
+        assert_eq!(cache.stats(), ResourceStats::default());
+        assert_eq!(Rc::strong_count(&kept), 1);
+        let reloaded = cache.mesh("src/suzanne.obj", |_| Ok(triangle()), &upload_mesh).unwrap();
+        assert!(!Rc::ptr_eq(&kept, &reloaded));
+    }
+}
@@ -0,0 +1,145 @@
+use gl;
+use gl::types::{GLint, GLuint};
+
+use gl_utils::uniform_location;
+
+
+///
+/// Assigns texture units to the samplers of a shader programme, so a demo does
+/// not have to pick unit numbers by hand or rely on GLSL 420 `layout (binding = x)`.
+/// Each sampler gets the next free unit when it is added, and `bind` binds every
+/// texture to its unit before drawing.
+///
+pub struct TextureBinder {
+    sp: GLuint,
+    next_unit: u32,
+    max_units: u32,
+    samplers: Vec<(String, u32, GLuint)>,
+}
+
+impl TextureBinder {
+    ///
+    /// Make a binder for a shader programme that hands out units starting from
+    /// `first_unit`, leaving the units below it for other code, e.g. a
+    /// `MaterialBinder`. The number of units is limited by
+    /// `GL_MAX_TEXTURE_IMAGE_UNITS`, the most a fragment shader can use.
+    ///
+    pub fn new(sp: GLuint, first_unit: u32) -> TextureBinder {
+        let mut max_units = 0;
+        unsafe {
+            gl::GetIntegerv(gl::MAX_TEXTURE_IMAGE_UNITS, &mut max_units);
+        }
+
+        TextureBinder::with_unit_limit(sp, first_unit, max_units as u32)
+    }
+
+    fn with_unit_limit(sp: GLuint, first_unit: u32, max_units: u32) -> TextureBinder {
+        TextureBinder {
+            sp: sp,
+            next_unit: first_unit,
+            max_units: max_units,
+            samplers: vec![],
+        }
+    }
+
+    ///
+    /// Give a sampler a unit, reusing the unit it already has if it was added before.
+    ///
+    fn assign_unit(&mut self, name: &str, tex: GLuint) -> Result<u32, String> {
+        for sampler in self.samplers.iter_mut() {
+            if sampler.0 == name {
+                sampler.2 = tex;
+                return Ok(sampler.1);
+            }
+        }
+
+        if self.next_unit >= self.max_units {
+            return Err(format!(
+                "ERROR: no texture unit left for sampler {}; GL_MAX_TEXTURE_IMAGE_UNITS is {}",
+                name, self.max_units
+            ));
+        }
+        let unit = self.next_unit;
+        self.next_unit += 1;
+        self.samplers.push((String::from(name), unit, tex));
+
+        Ok(unit)
+    }
+
+    ///
+    /// Assign a unit to the sampler uniform called `name`, point the sampler at it,
+    /// and remember `tex` as the texture to bind there. Returns the unit, or an
+    /// error if the programme has no such sampler or there are no units left. This
+    /// leaves the binder's shader programme in use.
+    ///
+    pub fn add_sampler(&mut self, name: &str, tex: GLuint) -> Result<u32, String> {
+        let location = uniform_location(self.sp, name);
+        if location == -1 {
+            return Err(format!("ERROR: shader programme {} has no sampler called {}", self.sp, name));
+        }
+
+        let unit = match self.assign_unit(name, tex) {
+            Ok(unit) => unit,
+            Err(e) => return Err(e),
+        };
+        unsafe {
+            gl::UseProgram(self.sp);
+            gl_check!(gl::Uniform1i(location, unit as GLint));
+        }
+
+        Ok(unit)
+    }
+
+    ///
+    /// The unit a sampler was given, if it has been added.
+    ///
+    pub fn unit(&self, name: &str) -> Option<u32> {
+        self.samplers.iter().find(|sampler| sampler.0 == name).map(|sampler| sampler.1)
+    }
+
+    ///
+    /// Bind each sampler's texture to its unit.
+    ///
+    pub fn bind(&self) {
+        for &(_, unit, tex) in self.samplers.iter() {
+            unsafe {
+                gl::ActiveTexture(gl::TEXTURE0 + unit);
+                gl::BindTexture(gl::TEXTURE_2D, tex);
+            }
+        }
+    }
+}
+
+mod texture_binder_tests {
+    use super::TextureBinder;
+
+    #[test]
+    fn test_samplers_get_consecutive_units_from_the_first_unit() {
+        let mut binder = TextureBinder::with_unit_limit(0, 2, 16);
+
+        assert_eq!(binder.assign_unit("ambient_map", 1), Ok(2));
+        assert_eq!(binder.assign_unit("emission_map", 2), Ok(3));
+        assert_eq!(binder.unit("ambient_map"), Some(2));
+        assert_eq!(binder.unit("diffuse_map"), None);
+    }
+
+    #[test]
+    fn test_adding_a_sampler_again_keeps_its_unit() {
+        let mut binder = TextureBinder::with_unit_limit(0, 0, 16);
+        binder.assign_unit("diffuse_map", 1).unwrap();
+        binder.assign_unit("specular_map", 2).unwrap();
+
+        assert_eq!(binder.assign_unit("diffuse_map", 3), Ok(0));
+        assert_eq!(binder.samplers[0].2, 3);
+        assert_eq!(binder.samplers.len(), 2);
+    }
+
+    #[test]
+    fn test_running_out_of_units_is_an_error() {
+        let mut binder = TextureBinder::with_unit_limit(0, 14, 16);
+        binder.assign_unit("a", 1).unwrap();
+        binder.assign_unit("b", 2).unwrap();
+
+        assert!(binder.assign_unit("c", 3).is_err());
+    }
+}
@@ -0,0 +1,325 @@
+use gl;
+use gl::types::{GLint, GLuint};
+
+use fullscreen_pass::FullscreenPass;
+use gl_utils::uniform_location;
+use overlay;
+use overlay::Overlay;
+use resource_cache::Texture;
+
+use std::rc::Rc;
+
+
+///
+/// The fragment shader the inspector draws textures with. It goes with the full
+/// screen vertex shader.
+///
+pub const TEXTURE_INSPECTOR_FS_FILE: &str = "src/texture_inspector_fs.glsl";
+
+const TEXT_SCALE: f32 = 2.0;
+const MARGIN: f32 = 10.0;
+const TEXT_COLOUR: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+const SELECTED_COLOUR: [f32; 4] = [1.0, 0.8, 0.2, 1.0];
+const BACKGROUND_COLOUR: [f32; 4] = [0.0, 0.0, 0.0, 0.7];
+
+
+///
+/// Which channels of a texture the inspector shows. A single channel is shown
+/// as grey, so a normal map's components or a specular map packed into the alpha
+/// channel can be looked at on their own.
+///
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Channel {
+    /// The colour, blended by the alpha over a checkerboard.
+    All,
+    Red,
+    Green,
+    Blue,
+    Alpha,
+}
+
+impl Channel {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Channel::All => "RGBA",
+            Channel::Red => "R",
+            Channel::Green => "G",
+            Channel::Blue => "B",
+            Channel::Alpha => "A",
+        }
+    }
+
+    pub fn next(&self) -> Channel {
+        match *self {
+            Channel::All => Channel::Red,
+            Channel::Red => Channel::Green,
+            Channel::Green => Channel::Blue,
+            Channel::Blue => Channel::Alpha,
+            Channel::Alpha => Channel::All,
+        }
+    }
+
+    ///
+    /// The value of the `channel` uniform in the inspector's fragment shader.
+    ///
+    fn index(&self) -> GLint {
+        match *self {
+            Channel::All => 0,
+            Channel::Red => 1,
+            Channel::Green => 2,
+            Channel::Blue => 3,
+            Channel::Alpha => 4,
+        }
+    }
+}
+
+///
+/// The number of levels in a full mipmap chain for a texture of this size, from
+/// the texture itself down to 1x1.
+///
+pub fn mip_level_count(width: u32, height: u32) -> u32 {
+    let mut size = u32::max(width, height);
+    let mut count = 1;
+    while size > 1 {
+        size /= 2;
+        count += 1;
+    }
+
+    count
+}
+
+///
+/// The size of one level of a texture's mipmap chain. Each level is half the
+/// size of the one before, rounded down, but never less than one texel across.
+///
+pub fn mip_size(width: u32, height: u32, level: u32) -> (u32, u32) {
+    let level = u32::min(level, 31);
+
+    (u32::max(width >> level, 1), u32::max(height >> level, 1))
+}
+
+///
+/// The index after `index` in a list of `count` things, or the one before it
+/// going `backwards`, wrapping round at either end.
+///
+fn step(index: usize, count: usize, backwards: bool) -> usize {
+    if count == 0 {
+        0
+    } else if backwards {
+        (index % count + count - 1) % count
+    } else {
+        (index + 1) % count
+    }
+}
+
+///
+/// The size of a level of a GL texture. A level the texture does not have is
+/// 0x0.
+///
+fn level_size(tex: GLuint, level: u32) -> (u32, u32) {
+    let mut width = 0;
+    let mut height = 0;
+    unsafe {
+        gl::BindTexture(gl::TEXTURE_2D, tex);
+        gl::GetTexLevelParameteriv(gl::TEXTURE_2D, level as GLint, gl::TEXTURE_WIDTH, &mut width);
+        gl::GetTexLevelParameteriv(gl::TEXTURE_2D, level as GLint, gl::TEXTURE_HEIGHT, &mut height);
+    }
+
+    (width as u32, height as u32)
+}
+
+///
+/// A debug overlay listing the textures in a `ResourceCache`, with one of them
+/// drawn at its native resolution, one texel to a pixel, so a map that loaded
+/// blank, upside down, or with the wrong channels shows up as it is rather than
+/// as it looks on a mesh. Any level of its mipmap chain can be shown, and any
+/// one of its channels.
+///
+pub struct TextureInspector {
+    sp: GLuint,
+    pass: FullscreenPass,
+    origin_loc: GLint,
+    level_loc: GLint,
+    channel_loc: GLint,
+    pub visible: bool,
+    pub channel: Channel,
+    // The level asked for. The level drawn is the nearest one the texture has.
+    pub mip_level: u32,
+    selected: usize,
+}
+
+impl TextureInspector {
+    ///
+    /// Make an inspector drawing with `sp`, built from the full screen vertex
+    /// shader and `TEXTURE_INSPECTOR_FS_FILE`.
+    ///
+    pub fn new(sp: GLuint) -> TextureInspector {
+        let mut inspector = TextureInspector {
+            sp: 0,
+            pass: FullscreenPass::new(),
+            origin_loc: -1,
+            level_loc: -1,
+            channel_loc: -1,
+            visible: false,
+            channel: Channel::All,
+            mip_level: 0,
+            selected: 0,
+        };
+        inspector.set_program(sp);
+
+        inspector
+    }
+
+    ///
+    /// Draw with `sp` from now on, such as after the shader programme has been
+    /// made again. What is selected and shown stays as it was.
+    ///
+    pub fn set_program(&mut self, sp: GLuint) {
+        unsafe {
+            gl::UseProgram(sp);
+            gl::Uniform1i(uniform_location(sp, "tex"), 0);
+        }
+
+        self.sp = sp;
+        self.origin_loc = uniform_location(sp, "origin");
+        self.level_loc = uniform_location(sp, "level");
+        self.channel_loc = uniform_location(sp, "channel");
+    }
+
+    ///
+    /// Select the next of `count` textures, or the previous one going backwards,
+    /// wrapping round at either end.
+    ///
+    pub fn select_next(&mut self, count: usize, backwards: bool) {
+        self.selected = step(self.selected, count, backwards);
+    }
+
+    pub fn change_mip_level(&mut self, delta: i32) {
+        self.mip_level = i32::max(self.mip_level as i32 + delta, 0) as u32;
+    }
+
+    ///
+    /// Draw the list of `textures`, as given by `ResourceCache::textures`, and the
+    /// selected one under it, into a framebuffer of `width` by `height` pixels.
+    /// The texture is drawn from the top-left corner of where it goes, so one too
+    /// big for the window is cut off at the bottom and the right. This leaves the
+    /// viewport covering the whole framebuffer.
+    ///
+    pub fn draw(&mut self, overlay: &mut Overlay, textures: &[(String, Rc<Texture>)], width: u32, height: u32) {
+        if self.selected >= textures.len() {
+            self.selected = 0;
+        }
+
+        let line_height = overlay::LINE_HEIGHT * TEXT_SCALE;
+        let mut lines = vec![];
+        for (i, &(ref name, ref texture)) in textures.iter().enumerate() {
+            let marker = if i == self.selected { ">" } else { " " };
+            let line = if texture.id() == 0 {
+                format!("{} {}  failed to load", marker, name)
+            } else {
+                let size = level_size(texture.id(), 0);
+                format!("{} {}  {}x{}", marker, name, size.0, size.1)
+            };
+            lines.push(line);
+        }
+        if lines.is_empty() {
+            lines.push(String::from("  no textures loaded"));
+        }
+
+        let mut caption = String::new();
+        let mut image = None;
+        if let Some(&(_, ref texture)) = textures.get(self.selected) {
+            let base = if texture.id() != 0 { level_size(texture.id(), 0) } else { (0, 0) };
+            if base.0 > 0 && base.1 > 0 {
+                // a texture made without mipmaps only has the first level
+                let mut last_level = 0;
+                while last_level + 1 < mip_level_count(base.0, base.1) && level_size(texture.id(), last_level + 1).0 > 0 {
+                    last_level += 1;
+                }
+                self.mip_level = u32::min(self.mip_level, last_level);
+                let size = mip_size(base.0, base.1, self.mip_level);
+                caption = format!(
+                    "channel {}  mip {} of {}  {}x{}", self.channel.name(), self.mip_level, last_level + 1, size.0, size.1
+                );
+                image = Some((texture.id(), size));
+            }
+        }
+
+        let longest = lines.iter().chain(Some(&caption)).map(|line| line.len()).max().unwrap_or(0);
+        let panel_width = (longest as f32 * overlay::GLYPH_ADVANCE + 4.0) * TEXT_SCALE;
+        let panel_height = ((lines.len() + 1) as f32 * overlay::LINE_HEIGHT + 4.0) * TEXT_SCALE;
+        overlay.rect(MARGIN, MARGIN, panel_width, panel_height, BACKGROUND_COLOUR);
+        let text_x = MARGIN + 2.0 * TEXT_SCALE;
+        let mut text_y = MARGIN + 2.0 * TEXT_SCALE;
+        for (i, line) in lines.iter().enumerate() {
+            let colour = if i == self.selected { SELECTED_COLOUR } else { TEXT_COLOUR };
+            overlay.text(text_x, text_y, TEXT_SCALE, colour, line);
+            text_y += line_height;
+        }
+        overlay.text(text_x, text_y, TEXT_SCALE, TEXT_COLOUR, &caption);
+
+        if let Some((tex, size)) = image {
+            // GL counts rows up from the bottom, so the image's bottom-left corner
+            // is its height below the top of where it goes.
+            let top = (MARGIN + panel_height + MARGIN) as i32;
+            let origin = (MARGIN as i32, height as i32 - top - size.1 as i32);
+            unsafe {
+                gl::Viewport(origin.0, origin.1, size.0 as i32, size.1 as i32);
+                gl::UseProgram(self.sp);
+                gl::Uniform2i(self.origin_loc, origin.0, origin.1);
+                gl::Uniform1i(self.level_loc, self.mip_level as GLint);
+                gl::Uniform1i(self.channel_loc, self.channel.index());
+            }
+            self.pass.draw(self.sp, &[tex]);
+            unsafe {
+                gl::Viewport(0, 0, width as i32, height as i32);
+            }
+        }
+
+        overlay.draw(width, height);
+    }
+}
+
+mod texture_inspector_tests {
+    use super::{mip_level_count, mip_size, step, Channel};
+
+    #[test]
+    fn test_a_full_mip_chain_goes_down_to_one_texel() {
+        assert_eq!(mip_level_count(1, 1), 1);
+        assert_eq!(mip_level_count(256, 256), 9);
+        assert_eq!(mip_level_count(512, 64), 10);
+        assert_eq!(mip_level_count(300, 200), 9);
+    }
+
+    #[test]
+    fn test_each_mip_level_is_half_the_size_but_at_least_one_texel() {
+        assert_eq!(mip_size(512, 64, 0), (512, 64));
+        assert_eq!(mip_size(512, 64, 3), (64, 8));
+        assert_eq!(mip_size(512, 64, 7), (4, 1));
+        assert_eq!(mip_size(300, 200, 1), (150, 100));
+        assert_eq!(mip_size(300, 200, 40), (1, 1));
+    }
+
+    #[test]
+    fn test_the_selection_wraps_round_both_ways() {
+        assert_eq!(step(0, 3, false), 1);
+        assert_eq!(step(2, 3, false), 0);
+        assert_eq!(step(0, 3, true), 2);
+        assert_eq!(step(2, 3, true), 1);
+        assert_eq!(step(0, 0, false), 0);
+        assert_eq!(step(0, 0, true), 0);
+    }
+
+    #[test]
+    fn test_the_channels_cycle_back_to_all_of_them() {
+        let mut channel = Channel::All;
+        let mut names = vec![];
+        for _ in 0..5 {
+            names.push(channel.name());
+            channel = channel.next();
+        }
+
+        assert_eq!(names, ["RGBA", "R", "G", "B", "A"]);
+        assert_eq!(channel, Channel::All);
+    }
+}
@@ -0,0 +1,27 @@
+#version 410
+
+// draws one level of a texture one texel to a pixel. the viewport is exactly the
+// size of the level, with its bottom-left corner at origin, so the pixel's place
+// in the viewport is the texel to fetch.
+in vec2 st;
+
+uniform sampler2D tex;
+uniform ivec2 origin;
+uniform int level;
+// 0 for the colour over a checkerboard, or 1 to 4 for red, green, blue, or alpha
+// on its own, as grey.
+uniform int channel;
+
+out vec4 frag_colour;
+
+void main() {
+	vec4 texel = texelFetch (tex, ivec2 (gl_FragCoord.xy) - origin, level);
+	if (channel == 0) {
+		// the checkerboard shows through where the texture is transparent
+		vec2 square = floor (gl_FragCoord.xy / 8.0);
+		vec3 checker = vec3 (mod (square.x + square.y, 2.0) * 0.2 + 0.4);
+		frag_colour = vec4 (mix (checker, texel.rgb, texel.a), 1.0);
+	} else {
+		frag_colour = vec4 (vec3 (texel[channel - 1]), 1.0);
+	}
+}
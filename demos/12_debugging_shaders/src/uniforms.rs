@@ -0,0 +1,269 @@
+use gl;
+use gl::types::{GLchar, GLenum, GLint, GLsizei, GLuint};
+
+use gl_utils::{gl_type_to_string, uniform_location};
+use graphics_math::Mat4;
+use logger::Logger;
+
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+
+
+// The longest uniform name read back from GL, NUL included.
+const MAX_UNIFORM_NAME_LENGTH: usize = 256;
+
+// The GLSL types each setter writes. Booleans are set with integers, as in GL.
+const FLOAT_TYPES: &[GLenum] = &[gl::FLOAT];
+const INT_TYPES: &[GLenum] = &[gl::INT, gl::BOOL];
+const VEC3_TYPES: &[GLenum] = &[gl::FLOAT_VEC3];
+const VEC4_TYPES: &[GLenum] = &[gl::FLOAT_VEC4];
+const MAT4_TYPES: &[GLenum] = &[gl::FLOAT_MAT4];
+const SAMPLER_TYPES: &[GLenum] = &[gl::SAMPLER_2D, gl::SAMPLER_3D, gl::SAMPLER_CUBE, gl::SAMPLER_2D_SHADOW];
+
+
+///
+/// An active uniform of a shader programme, as GL reports it. `size` is the
+/// number of elements for an array and 1 otherwise.
+///
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct UniformInfo {
+    pub location: GLint,
+    pub gl_type: GLenum,
+    pub size: GLint,
+}
+
+///
+/// The name GL reports an array uniform under ends in `[0]`, but it is set by
+/// the name of the array alone just as well.
+///
+fn array_base_name(name: &str) -> Option<&str> {
+    if name.ends_with("[0]") {
+        Some(&name[..name.len() - 3])
+    } else {
+        None
+    }
+}
+
+///
+/// Check that the setter called `setter`, which writes the GLSL types in `accepted`,
+/// can set the uniform `name`, and find its location. GL quietly ignores a uniform
+/// set with the wrong type or a name the programme does not have, so both are
+/// turned into an error here that says what went wrong.
+///
+pub fn check_setter(name: &str, setter: &str, accepted: &[GLenum], info: Option<UniformInfo>) -> Result<GLint, String> {
+    let info = match info {
+        Some(val) => val,
+        None => return Err(format!(
+            "ERROR: {}(\"{}\"): the programme has no active uniform called {}. It may be misspelt, \
+             or unused by the shaders and so optimised away.", setter, name, name
+        )),
+    };
+
+    if !accepted.contains(&info.gl_type) {
+        let expected: Vec<&str> = accepted.iter().map(|&gl_type| gl_type_to_string(gl_type)).collect();
+        return Err(format!(
+            "ERROR: {}(\"{}\"): the shader declares {} as {}, but {} sets {}.",
+            setter, name, name, gl_type_to_string(info.gl_type), setter, expected.join(" or ")
+        ));
+    }
+
+    Ok(info.location)
+}
+
+///
+/// The uniforms of a shader programme, looked up by name once when it is made
+/// instead of on every call. In a debug build each setter checks the type GL
+/// reports for the uniform against the type it writes, and logs an error for a
+/// mismatch or a missing uniform instead of letting GL ignore the call. Each
+/// mistake is logged once, however often it is made. A release build skips the
+/// checks. The setters leave the programme in use.
+///
+pub struct Uniforms {
+    sp: GLuint,
+    active: HashMap<String, UniformInfo>,
+    logger: Logger,
+    reported: RefCell<HashSet<String>>,
+}
+
+impl Uniforms {
+    ///
+    /// Read the active uniforms of the shader programme `sp` from GL. Errors go to
+    /// `logger`.
+    ///
+    pub fn new(sp: GLuint, logger: Logger) -> Uniforms {
+        let mut count = 0;
+        unsafe {
+            gl::GetProgramiv(sp, gl::ACTIVE_UNIFORMS, &mut count);
+        }
+
+        let mut active = HashMap::new();
+        for i in 0..count {
+            let mut name = [0u8; MAX_UNIFORM_NAME_LENGTH];
+            let mut length = 0;
+            let mut size = 0;
+            let mut gl_type = 0;
+            unsafe {
+                gl::GetActiveUniform(
+                    sp, i as GLuint, MAX_UNIFORM_NAME_LENGTH as GLsizei,
+                    &mut length, &mut size, &mut gl_type, name.as_mut_ptr() as *mut GLchar
+                );
+            }
+            let name = String::from_utf8_lossy(&name[..length as usize]).into_owned();
+            let location = uniform_location(sp, &name);
+            active.insert(name, UniformInfo { location: location, gl_type: gl_type, size: size });
+        }
+
+        Uniforms::with_active(sp, active, logger)
+    }
+
+    fn with_active(sp: GLuint, active: HashMap<String, UniformInfo>, logger: Logger) -> Uniforms {
+        let mut active = active;
+        let array_names: Vec<(String, UniformInfo)> = active.iter()
+            .filter_map(|(name, &info)| array_base_name(name).map(|base| (String::from(base), info)))
+            .collect();
+        active.extend(array_names);
+
+        Uniforms {
+            sp: sp,
+            active: active,
+            logger: logger,
+            reported: RefCell::new(HashSet::new()),
+        }
+    }
+
+    pub fn program(&self) -> GLuint {
+        self.sp
+    }
+
+    ///
+    /// What GL reports for the uniform `name`, if the programme has it.
+    ///
+    pub fn info(&self, name: &str) -> Option<UniformInfo> {
+        self.active.get(name).cloned()
+    }
+
+    ///
+    /// The location for a setter to write to, or `None` to skip the call.
+    ///
+    fn location(&self, name: &str, setter: &str, accepted: &[GLenum]) -> Option<GLint> {
+        if !cfg!(debug_assertions) {
+            return self.info(name).map(|info| info.location);
+        }
+
+        match check_setter(name, setter, accepted, self.info(name)) {
+            Ok(location) => Some(location),
+            Err(e) => {
+                if self.reported.borrow_mut().insert(format!("{} {}", setter, name)) {
+                    self.logger.log_err(&e);
+                }
+                None
+            }
+        }
+    }
+
+    pub fn set_f32(&self, name: &str, value: f32) {
+        if let Some(location) = self.location(name, "set_f32", FLOAT_TYPES) {
+            unsafe {
+                gl::UseProgram(self.sp);
+                gl::Uniform1f(location, value);
+            }
+        }
+    }
+
+    pub fn set_i32(&self, name: &str, value: i32) {
+        if let Some(location) = self.location(name, "set_i32", INT_TYPES) {
+            unsafe {
+                gl::UseProgram(self.sp);
+                gl::Uniform1i(location, value);
+            }
+        }
+    }
+
+    pub fn set_vec3(&self, name: &str, value: &[f32; 3]) {
+        if let Some(location) = self.location(name, "set_vec3", VEC3_TYPES) {
+            unsafe {
+                gl::UseProgram(self.sp);
+                gl::Uniform3fv(location, 1, value.as_ptr());
+            }
+        }
+    }
+
+    pub fn set_vec4(&self, name: &str, value: &[f32; 4]) {
+        if let Some(location) = self.location(name, "set_vec4", VEC4_TYPES) {
+            unsafe {
+                gl::UseProgram(self.sp);
+                gl::Uniform4fv(location, 1, value.as_ptr());
+            }
+        }
+    }
+
+    pub fn set_mat4(&self, name: &str, value: &Mat4) {
+        if let Some(location) = self.location(name, "set_mat4", MAT4_TYPES) {
+            unsafe {
+                gl::UseProgram(self.sp);
+                gl::UniformMatrix4fv(location, 1, gl::FALSE, value.as_ptr());
+            }
+        }
+    }
+
+    ///
+    /// Point the sampler `name` at a texture unit.
+    ///
+    pub fn set_sampler(&self, name: &str, unit: u32) {
+        if let Some(location) = self.location(name, "set_sampler", SAMPLER_TYPES) {
+            unsafe {
+                gl::UseProgram(self.sp);
+                gl::Uniform1i(location, unit as GLint);
+            }
+        }
+    }
+}
+
+mod uniforms_tests {
+    use super::{check_setter, UniformInfo, Uniforms, MAT4_TYPES, SAMPLER_TYPES, VEC3_TYPES};
+    use logger::Logger;
+    use gl;
+    use std::collections::HashMap;
+
+    fn mat4_at(location: i32) -> Option<UniformInfo> {
+        Some(UniformInfo { location: location, gl_type: gl::FLOAT_MAT4, size: 1 })
+    }
+
+    #[test]
+    fn test_a_setter_of_the_right_type_gets_the_location() {
+        assert_eq!(check_setter("view", "set_mat4", MAT4_TYPES, mat4_at(3)), Ok(3));
+    }
+
+    #[test]
+    fn test_a_setter_of_the_wrong_type_names_both_types() {
+        let result = check_setter("view", "set_vec3", VEC3_TYPES, mat4_at(3));
+
+        let message = result.unwrap_err();
+        assert!(message.contains("mat4"), "{}", message);
+        assert!(message.contains("vec3"), "{}", message);
+    }
+
+    #[test]
+    fn test_a_missing_uniform_is_an_error() {
+        assert!(check_setter("veiw", "set_mat4", MAT4_TYPES, None).is_err());
+    }
+
+    #[test]
+    fn test_samplers_are_only_set_by_the_sampler_setter() {
+        let sampler = Some(UniformInfo { location: 5, gl_type: gl::SAMPLER_2D, size: 1 });
+
+        assert_eq!(check_setter("diffuse_map", "set_sampler", SAMPLER_TYPES, sampler), Ok(5));
+        assert!(check_setter("diffuse_map", "set_mat4", MAT4_TYPES, sampler).is_err());
+    }
+
+    #[test]
+    fn test_an_array_is_found_by_its_name_with_or_without_the_index() {
+        let mut active = HashMap::new();
+        active.insert(String::from("weights[0]"), UniformInfo { location: 7, gl_type: gl::FLOAT, size: 5 });
+        let uniforms = Uniforms::with_active(0, active, Logger::from_log_file("uniforms_tests.log"));
+
+        assert_eq!(uniforms.info("weights").map(|info| info.location), Some(7));
+        assert_eq!(uniforms.info("weights[0]").map(|info| info.size), Some(5));
+        assert_eq!(uniforms.info("weight"), None);
+    }
+}
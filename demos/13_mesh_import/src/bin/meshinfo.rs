@@ -0,0 +1,96 @@
+//
+// Print what a mesh file is made of, and warn about the problems that most often
+// explain a mesh rendering wrongly. Run it with
+//
+//     cargo run --bin meshinfo -- file.obj
+//
+// It loads meshes the same way the demo does, so with the `assimp` or `gltf`
+// features enabled it reads the same formats too.
+//
+extern crate gl;
+extern crate chrono;
+extern crate stb_image;
+#[cfg(feature = "assimp")]
+extern crate assimp;
+#[cfg(feature = "gltf")]
+extern crate gltf;
+#[cfg(feature = "glam")]
+extern crate glam;
+#[cfg(feature = "cgmath")]
+extern crate cgmath;
+
+// The shared vertex layout wraps its GL calls in `gl_check!`, which comes from
+// gl_utils, and that needs a window to build. Nothing here makes a GL call, so
+// this one only makes the call.
+macro_rules! gl_check {
+    ($call:expr) => { $call };
+}
+
+#[allow(dead_code)]
+#[path = "../graphics_math.rs"]
+mod graphics_math;
+#[allow(dead_code)]
+#[path = "../obj_parser.rs"]
+mod obj_parser;
+#[allow(dead_code)]
+#[path = "../colour.rs"]
+mod colour;
+#[allow(dead_code)]
+#[path = "../logger.rs"]
+mod logger;
+#[allow(dead_code)]
+#[path = "../assets.rs"]
+mod assets;
+#[allow(dead_code)]
+#[path = "../model.rs"]
+mod model;
+#[allow(dead_code)]
+#[path = "../mesh_cache.rs"]
+mod mesh_cache;
+#[allow(dead_code)]
+#[path = "../geometry.rs"]
+mod geometry;
+#[allow(dead_code)]
+#[path = "../vertex_layout.rs"]
+mod vertex_layout;
+#[allow(dead_code)]
+#[path = "../vertex_packing.rs"]
+mod vertex_packing;
+#[path = "../mesh_stats.rs"]
+mod mesh_stats;
+
+
+use std::env;
+use std::process;
+
+use graphics_math as math;
+
+use model::Model;
+
+
+fn main() {
+    let file_name = match env::args().nth(1) {
+        Some(val) => val,
+        None => {
+            eprintln!("usage: meshinfo <mesh file>");
+            process::exit(2);
+        }
+    };
+
+    let model = match Model::load(&file_name) {
+        Ok(val) => val,
+        Err(e) => {
+            eprintln!("ERROR: could not load {}\n{}", file_name, e);
+            process::exit(1);
+        }
+    };
+
+    let stats = mesh_stats::mesh_stats(&model);
+    println!("{}", file_name);
+    for line in stats.describe() {
+        println!("  {}", line);
+    }
+    for warning in stats.warnings() {
+        println!("{}", warning);
+    }
+}
@@ -0,0 +1,113 @@
+use gl;
+use gl::types::{GLenum, GLuint};
+
+
+///
+/// The vertex shader every full screen pass is drawn with. It makes the
+/// triangle's corners itself and passes texture coordinates to the fragment
+/// shader as `st`, from 0 at the bottom-left of the viewport to 1 at the top-right.
+///
+pub const FULLSCREEN_VS_FILE: &str = "src/fullscreen_vs.glsl";
+
+
+///
+/// The clip space position of corner `vertex_id` of the full screen triangle, at
+/// (-1, -1), (3, -1), and (-1, 3). The same sum as `fullscreen_vs.glsl`.
+///
+pub fn corner(vertex_id: i32) -> [f32; 2] {
+    [((vertex_id & 1) << 2) as f32 - 1.0, ((vertex_id & 2) << 1) as f32 - 1.0]
+}
+
+///
+/// Draws a single triangle big enough to cover the whole viewport, for passes
+/// that work on every pixel: post-processing, views of a render target's buffers,
+/// frame comparisons, and backgrounds. The corners come from the vertex shader, so
+/// there is no vertex buffer, and one triangle has no seam down the middle where
+/// the two halves of a quad would both shade the same pixels.
+///
+pub struct FullscreenPass {
+    vao: GLuint,
+}
+
+impl FullscreenPass {
+    pub fn new() -> FullscreenPass {
+        // The core profile will not draw without a vertex array bound, even an
+        // empty one.
+        let mut vao = 0;
+        unsafe {
+            gl::GenVertexArrays(1, &mut vao);
+        }
+        assert!(vao > 0);
+
+        FullscreenPass { vao: vao }
+    }
+
+    ///
+    /// Draw over the viewport with `program`, which has to use the full screen
+    /// vertex shader, with the textures in `inputs` bound to texture units 0, 1,
+    /// and so on in order. Set the program's uniforms before drawing. The depth
+    /// test is off while drawing, so nothing drawn earlier hides the pass.
+    ///
+    pub fn draw(&self, program: GLuint, inputs: &[GLuint]) {
+        unsafe {
+            let depth_test_enabled = gl::IsEnabled(gl::DEPTH_TEST) == gl::TRUE;
+            gl::Disable(gl::DEPTH_TEST);
+            gl::UseProgram(program);
+            for (i, &texture) in inputs.iter().enumerate() {
+                gl::ActiveTexture(gl::TEXTURE0 + i as GLenum);
+                gl::BindTexture(gl::TEXTURE_2D, texture);
+            }
+            gl::ActiveTexture(gl::TEXTURE0);
+            gl::BindVertexArray(self.vao);
+            gl_check!(gl::DrawArrays(gl::TRIANGLES, 0, 3));
+            if depth_test_enabled {
+                gl::Enable(gl::DEPTH_TEST);
+            }
+        }
+    }
+}
+
+impl Drop for FullscreenPass {
+    fn drop(&mut self) {
+        if gl::DeleteVertexArrays::is_loaded() {
+            unsafe {
+                gl::DeleteVertexArrays(1, &self.vao);
+            }
+        }
+    }
+}
+
+mod fullscreen_pass_tests {
+    use super::corner;
+
+    ///
+    /// Whether `point` is inside the triangle or on its edge, and which way round
+    /// the triangle winds.
+    ///
+    fn signed_areas(point: [f32; 2]) -> [f32; 3] {
+        let mut areas = [0.0; 3];
+        for i in 0..3 {
+            let a = corner(i as i32);
+            let b = corner((i as i32 + 1) % 3);
+            areas[i] = (b[0] - a[0]) * (point[1] - a[1]) - (b[1] - a[1]) * (point[0] - a[0]);
+        }
+
+        areas
+    }
+
+    #[test]
+    fn test_the_triangle_covers_the_whole_viewport() {
+        for &point in [[-1.0, -1.0], [1.0, -1.0], [1.0, 1.0], [-1.0, 1.0], [0.0, 0.0]].iter() {
+            assert!(signed_areas(point).iter().all(|&area| area >= 0.0), "{:?} is outside", point);
+        }
+    }
+
+    #[test]
+    fn test_the_triangle_winds_counter_clockwise() {
+        let (a, b, c) = (corner(0), corner(1), corner(2));
+        let area = (b[0] - a[0]) * (c[1] - a[1]) - (b[1] - a[1]) * (c[0] - a[0]);
+
+        // so it is not culled as a back face
+        assert!(area > 0.0);
+    }
+}
@@ -2,299 +2,376 @@ extern crate gl;
 extern crate glfw;
 extern crate chrono;
 extern crate stb_image;
-
-#[macro_use] 
-extern crate scan_fmt;
-
+extern crate png;
+#[cfg(feature = "assimp")]
+extern crate assimp;
+#[cfg(feature = "gltf")]
+extern crate gltf;
+#[cfg(feature = "glam")]
+extern crate glam;
+#[cfg(feature = "cgmath")]
+extern crate cgmath;
+
+#[macro_use]
 mod gl_utils;
 mod graphics_math;
 mod obj_parser;
+mod colour;
+mod assets;
+mod shader_include;
+mod spirv;
+mod program_cache;
+mod logger;
+mod model;
+mod mesh_cache;
+mod resource_cache;
+mod material;
+mod renderer;
+mod geometry;
+mod vertex_layout;
+mod vertex_packing;
+mod background;
+mod fullscreen_pass;
+mod screen;
+mod turntable;
 
 
 use glfw::{Action, Context, Key};
-use gl::types::{GLfloat, GLsizeiptr, GLvoid, GLsizei, GLuint};
-use stb_image::image;
-use stb_image::image::{LoadResult, Image};
+use gl::types::{GLfloat, GLvoid};
 
-use std::mem;
-use std::ptr;
+use std::env;
+use std::process;
 
 use gl_utils::*;
 
 use graphics_math as math;
 use math::Mat4;
 
+use model::Model;
+use logger::Logger;
+use renderer::{ProgramHandle, Renderer};
+use background::{background_from_args, Background, BackgroundStyle};
+use colour::Colour;
+use fullscreen_pass::FULLSCREEN_VS_FILE;
+use screen::VideoRecorder;
+use turntable::{turntable_from_args, Turntable};
+
+const GL_LOG_FILE: &str = "gl.log";
 const VERTEX_SHADER_FILE: &str = "src/test.vert.glsl";
 const FRAGMENT_SHADER_FILE: &str = "src/test.frag.glsl";
-const TEXTURE_FILE: &str = "src/skulluvmap.png";
-
-const GL_TEXTURE_MAX_ANISOTROPY_EXT: u32 = 0x84FE;
-const GL_MAX_TEXTURE_MAX_ANISOTROPY_EXT: u32 = 0x84FF;
-
-static mut PREVIOUS_SECONDS: f64 = 0.0;
-
-
-fn load_texture(file_name: &str, tex: &mut GLuint) -> bool {
-    let force_channels = 4;
-    let mut image_data = match image::load_with_depth(file_name, force_channels, false) {
-        LoadResult::ImageU8(image_data) => image_data,
-        LoadResult::Error(_) => {
-            eprintln!("ERROR: could not load {}", file_name);
-            return false;
-        }
-        LoadResult::ImageF32(_) => {
-            eprintln!("ERROR: Tried to load an image as byte vectors, got f32: {}", file_name);
-            return false;
-        }
-    };
-
-    let width = image_data.width;
-    let height = image_data.height;
-    let depth = image_data.depth;
-
-    // Check that the image size is a power of two.
-    if (width & (width - 1)) != 0 || (height & (height - 1)) != 0 {
-        eprintln!("WARNING: texture {} is not power-of-2 dimensions", file_name);
-    }
-
-    let width_in_bytes = width * 4;
-    let half_height = height / 2;
-    for row in 0..half_height {
-        for col in 0..width_in_bytes {
-            let temp = image_data.data[row * width_in_bytes + col];
-            image_data.data[row * width_in_bytes + col] = image_data.data[((height - row - 1) * width_in_bytes) + col];
-            image_data.data[((height - row - 1) * width_in_bytes) + col] = temp;
-        }
-    }
-
+const MESH_FILE: &str = "src/monkey2.obj";
+const BACKGROUND_FS_FILE: &str = "src/background_fs.glsl";
+///
+/// The share of the mesh's triangles kept at each level of detail, from the
+/// full mesh down. L steps through them.
+///
+const LOD_RATIOS: [f32; 4] = [1.0, 0.5, 0.25, 0.1];
+
+
+fn gl_capture_frame_buffer(width: usize, height: usize, buffer: &mut [u8]) -> bool {
+    // Copy the frame buffer contents into into a 24-bit RGB image. Rows are
+    // tightly packed, so widths that are not a multiple of four still line up.
     unsafe {
-        gl::GenTextures(1, tex);
-        gl::ActiveTexture(gl::TEXTURE0);
-        gl::BindTexture(gl::TEXTURE_2D, *tex);
-        gl::TexImage2D(
-            gl::TEXTURE_2D, 0, gl::RGBA as i32, width as i32, height as i32, 0, 
-            gl::RGBA, gl::UNSIGNED_BYTE, 
-            image_data.data.as_ptr() as *const GLvoid
+        gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
+        gl::ReadPixels(
+            0, 0, width as i32, height as i32, gl::RGB, gl::UNSIGNED_BYTE,
+            buffer.as_mut_ptr() as *mut GLvoid
         );
-        gl::GenerateMipmap(gl::TEXTURE_2D);
-        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as i32);
-        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as i32);
-        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::LINEAR as i32);
-        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::LINEAR_MIPMAP_LINEAR as i32);
     }
 
-    let mut max_aniso = 0.0;
-    // TODO: Check this against my dependencies.
-    unsafe {
-        gl::GetFloatv(GL_MAX_TEXTURE_MAX_ANISOTROPY_EXT, &mut max_aniso);
-        // Set the maximum!
-        gl::TexParameterf(gl::TEXTURE_2D, GL_TEXTURE_MAX_ANISOTROPY_EXT, max_aniso);
-    }
+    true
+}
 
-    return true;
+///
+/// Delete every GL object the renderer holds and make it again, then set the
+/// camera uniforms again in the new shader programme. Returns how many objects
+/// could not be made.
+///
+fn reload_gl_objects(
+    renderer: &mut Renderer, logger: &Logger, programme: ProgramHandle, view_mat: &Mat4, proj_mat: &Mat4
+) -> usize {
+    let failures = renderer.reload(logger);
+    renderer.set_uniform_mat4(programme, "view", view_mat);
+    renderer.set_uniform_mat4(programme, "proj", proj_mat);
+    logger.log(&format!("reloaded every GL object, {} failed", failures));
+
+    failures
 }
 
 fn main() {
-    restart_gl_log();
-    // start GL context and O/S window using the GLFW helper library
-    let (mut glfw, mut g_window, mut _g_events) = start_gl().unwrap();
-
-    // tell GL to only draw onto a pixel if the shape is closer to the viewer
-    unsafe {
-        gl::Enable(gl::DEPTH_TEST); // enable depth-testing
-        gl::DepthFunc(gl::LESS);    // depth-testing interprets a smaller value as "closer"
-    }
-
-    /* OTHER STUFF GOES HERE NEXT */
-    let points: [GLfloat; 18] = [
-        -0.5, -0.5, 0.0,  0.5, -0.5, 0.0,  0.5,  0.5, 0.0, 
-         0.5,  0.5, 0.0, -0.5,  0.5, 0.0, -0.5, -0.5, 0.0
-    ];
-
-    // 2^16 == 65536
-    let texcoords: [GLfloat; 12] = [
-        0.0, 0.0, 1.0, 0.0, 1.0, 1.0, 1.0, 1.0, 0.0, 1.0, 0.0, 0.0
-    ];
-
-    let mut points_vbo: GLuint = 0;
-    unsafe {
-        gl::GenBuffers(1, &mut points_vbo);
-        gl::BindBuffer(gl::ARRAY_BUFFER, points_vbo);
-        gl::BufferData(
-            gl::ARRAY_BUFFER, (points.len() * mem::size_of::<GLfloat>()) as GLsizeiptr, 
-            points.as_ptr() as *const GLvoid, gl::STATIC_DRAW
-        );
-    }
-    assert!(points_vbo != 0);
+    let logger = restart_gl_log(GL_LOG_FILE);
+    // Start a GL context and O/S window using the GLFW helper library.
+    let mut context = match start_gl(&logger) {
+        Ok(val) => val,
+        Err(e) => {
+            eprintln!("Failed to Initialize OpenGL context. Got error:");
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+    };
 
-    let mut texcoords_vbo: GLuint = 0;
+    // Tell GL to only draw onto a pixel if the shape is closer to the viewer.
     unsafe {
-        gl::GenBuffers(1, &mut texcoords_vbo);
-        gl::BindBuffer(gl::ARRAY_BUFFER, texcoords_vbo);
-        gl::BufferData(
-            gl::ARRAY_BUFFER, (texcoords.len() * mem::size_of::<GLfloat>()) as GLsizeiptr, 
-            texcoords.as_ptr() as *const GLvoid, gl::STATIC_DRAW
-        );
+        // Enable depth testing.
+        gl::Enable(gl::DEPTH_TEST);
+        // Depth testing interprets a smaller value as closer to the eye.
+        gl::DepthFunc(gl::LESS);
     }
-    assert!(texcoords_vbo != 0);
 
-    let mut vao: GLuint = 0;
-    unsafe {
-        gl::GenVertexArrays(1, &mut vao);
-        gl::BindVertexArray(vao);
-        gl::BindBuffer(gl::ARRAY_BUFFER, points_vbo);
-        gl::VertexAttribPointer(0, 3, gl::FLOAT, gl::FALSE, 0, ptr::null());
-        gl::BindBuffer(gl::ARRAY_BUFFER, texcoords_vbo);
-        gl::VertexAttribPointer(1, 2, gl::FLOAT, gl::FALSE, 0, ptr::null()); // normalize!
-        gl::EnableVertexAttribArray(0);
-        gl::EnableVertexAttribArray(1);
+    // the renderer owns every GL object the demo draws with, and hands out
+    // handles to them.
+    let mut renderer = Renderer::new();
+
+    /*------------------------------CREATE GEOMETRY------------------------------*/
+    let mesh = match renderer.load_model(MESH_FILE) {
+        Ok(val) => val,
+        Err(e) => {
+            logger.log_err(&format!("ERROR: loading mesh file. Loader returned error\n{}", e));
+            logger.log_err("WARNING: using the embedded fallback mesh instead.");
+            renderer.upload_model(Model::from_obj_mesh(assets::fallback_mesh(), vec![]))
+        }
+    };
+    logger.log(&format!("loaded {} with {} points", MESH_FILE, renderer.model(mesh).point_count));
+    logger.log(&format!("{}: {}", MESH_FILE, renderer.weld_report(mesh).describe()));
+
+    // the coarser levels of detail are all simplified from the one mesh.
+    let mut lods = vec![mesh];
+    for &ratio in LOD_RATIOS[1..].iter() {
+        let lod_model = renderer.model(mesh).simplified(ratio);
+        logger.log(&format!("level of detail at {} has {} triangles", ratio, lod_model.point_count / 3));
+        lods.push(renderer.upload_model(lod_model));
     }
-    assert!(vao != 0);
-
-    let shader_programme = create_programme_from_files(VERTEX_SHADER_FILE, FRAGMENT_SHADER_FILE);
+    let mut lod = 0;
+    let mut lod_key_was_down = false;
+    let mut reload_key_was_down = false;
+    let mut fullscreen_key_was_down = false;
+
+    let shader_programme = renderer.create_program(&logger, VERTEX_SHADER_FILE, FRAGMENT_SHADER_FILE);
+
+    // grey by default, to help spot mistakes.
+    let background_style = match background_from_args(env::args(), BackgroundStyle::Solid(Colour::DARK_GREY)) {
+        Ok(val) => val,
+        Err(e) => {
+            logger.log_err(&e);
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+    };
+    let background_programme = create_programme_from_files(&logger, FULLSCREEN_VS_FILE, BACKGROUND_FS_FILE);
+    let background = Background::new(background_programme, background_style);
 
     // input variables
     let near = 0.1;                                  // clipping plane
     let far = 100.0;                                 // clipping plane
     let fov = 67.0;                                  // convert 67 degrees to radians
-    let aspect = unsafe { G_GL_WIDTH as f32 / G_GL_HEIGHT as f32 }; // aspect ratio
-    let proj_mat = Mat4::perspective(fov, aspect, near, far);
+    let aspect = context.width as f32 / context.height as f32; // aspect ratio
+    let mut proj_mat = Mat4::perspective(fov, aspect, near, far);
 
     // matrix components
     let cam_speed: GLfloat = 1.0;             // 1 unit per second
     let cam_yaw_speed: GLfloat = 10.0;        // 10 degrees per second
-    let mut cam_pos: [GLfloat; 3] = [0.0, 0.0, 2.0]; // don't start at zero, or we will be too close
+    let mut cam_pos: [GLfloat; 3] = [0.0, 0.0, 5.0]; // don't start at zero, or we will be too close
     let mut cam_yaw: GLfloat = 0.0;               // y-rotation in degrees
-    let mut mat_trans = Mat4::identity().translate(&math::vec3((-cam_pos[0], -cam_pos[1], -cam_pos[2])));
-    let mut mat_rot = Mat4::identity().rotate_y_deg(-cam_yaw);
+    let mut mat_trans = Mat4::from_translation(&math::vec3((-cam_pos[0], -cam_pos[1], -cam_pos[2])));
+    let mut mat_rot = Mat4::from_rotation_y_deg(-cam_yaw);
     let mut view_mat = mat_rot * mat_trans;
 
-    let view_mat_location = unsafe {
-        gl::GetUniformLocation(shader_programme, "view".as_ptr() as *const i8)
+    renderer.set_uniform_mat4(shader_programme, "view", &view_mat);
+    renderer.set_uniform_mat4(shader_programme, "proj", &proj_mat);
+
+    // `--turntable N` orbits the camera once around the mesh over N frames,
+    // saving every frame as video_frame_000.png and on, then quits.
+    let mut turntable = match turntable_from_args(env::args()) {
+        Ok(Some(frame_count)) => Some(Turntable::new(frame_count, cam_pos[2], cam_pos[1])),
+        Ok(None) => None,
+        Err(e) => {
+            logger.log_err(&e);
+            eprintln!("{}", e);
+            process::exit(1);
+        }
     };
-    assert!(view_mat_location != -1);
-    unsafe {
-        gl::UseProgram(shader_programme);
-        gl::UniformMatrix4fv(view_mat_location, 1, gl::FALSE, view_mat.as_ptr());
-    }
-
-    let proj_mat_location = unsafe { 
-        gl::GetUniformLocation(shader_programme, "proj".as_ptr() as *const i8)
+    let mut recorder = match turntable {
+        Some(ref turntable) => {
+            let (width, height) = context.window.get_framebuffer_size();
+            // one frame a second for as many seconds as there are frames
+            Some(VideoRecorder::new(
+                width as usize, height as usize, context.channel_depth as usize, turntable.frame_count(), 1
+            ))
+        }
+        None => None,
     };
-    unsafe {
-        gl::UseProgram(shader_programme);
-        gl::UniformMatrix4fv(proj_mat_location, 1, gl::FALSE, proj_mat.as_ptr());
-    }
-    assert!(proj_mat_location != -1);
-
-    // load texture
-    let mut tex: GLuint = 0;
-    load_texture(TEXTURE_FILE, &mut tex);
-    assert!(tex != 0);
 
     unsafe {
-        gl::Enable(gl::CULL_FACE); // cull face
-        gl::CullFace(gl::BACK);    // cull back face
-        gl::FrontFace(gl::CCW);    // GL_CCW for counter clock-wise
+        // Cull face.
+        gl::Enable(gl::CULL_FACE);
+        // Cull back face.
+        gl::CullFace(gl::BACK);
+        // GL_CW for clockwise.    
+        gl::FrontFace(gl::CCW);
     }
 
-    while !g_window.should_close() {
-        let current_seconds = glfw.get_time();
-        let elapsed_seconds = unsafe { current_seconds - PREVIOUS_SECONDS };
-        unsafe {
-            PREVIOUS_SECONDS = current_seconds;
-        }
+    while !context.window.should_close() {
+        let current_seconds = context.glfw.get_time();
+        let elapsed_seconds = current_seconds - context.elapsed_time_seconds;
+        context.elapsed_time_seconds = current_seconds;
 
-        _update_fps_counter(&glfw, &mut g_window);
+        update_fps_counter(&mut context);
         unsafe {
-            // wipe the drawing surface clear
-            gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
-            gl::Viewport(0, 0, G_GL_HEIGHT as i32, G_GL_HEIGHT as i32);
-
-            gl::UseProgram(shader_programme);
-            gl::BindVertexArray(vao);
-            // draw points 0-3 from the currently bound VAO with current in-use shader
-            gl::DrawArrays(gl::TRIANGLES, 0, 6);
-            // update other events like input handling
+            gl::Viewport(0, 0, context.width as i32, context.height as i32);
+        }
+        // the turntable drives the camera while it runs, whatever keys are held
+        if let Some(ref turntable) = turntable {
+            view_mat = turntable.view_mat();
+            renderer.set_uniform_mat4(shader_programme, "view", &view_mat);
+        }
+        // Wipe the drawing surface clear.
+        background.clear();
+        // Draw the whole mesh with the shader programme, at the chosen level of detail.
+        renderer.draw(shader_programme, lods[lod]);
+
+        if let (Some(turntable), Some(recorder)) = (turntable.as_mut(), recorder.as_mut()) {
+            // grab the frame before it is swapped to the display
+            let (width, height) = (recorder.width(), recorder.height());
+            if let Err(e) = recorder.capture_frame(&|buf| { gl_capture_frame_buffer(width, height, buf) }) {
+                logger.log_err(&format!("ERROR: could not grab turntable frame: {}", e));
+            }
+            turntable.advance();
+            if turntable.is_done() {
+                context.window.set_should_close(true);
+            }
         }
 
-        glfw.poll_events();
+        context.glfw.poll_events();
 
         // control keys
         let mut cam_moved = false;
-        match g_window.get_key(Key::A) {
+        match context.window.get_key(Key::A) {
             Action::Press | Action::Repeat => {
                 cam_pos[0] -= cam_speed * (elapsed_seconds as GLfloat);
                 cam_moved = true;
             }
             _ => {}
         }
-        match g_window.get_key(Key::D) {
+        match context.window.get_key(Key::D) {
             Action::Press | Action::Repeat => {
                 cam_pos[0] += cam_speed * (elapsed_seconds as GLfloat);
                 cam_moved = true;
             }
             _ => {}
         }
-        match g_window.get_key(Key::Up) {
+        match context.window.get_key(Key::Up) {
             Action::Press | Action::Repeat => {
                 cam_pos[1] += cam_speed * (elapsed_seconds as GLfloat);
                 cam_moved = true;
             }
             _ => {}
         }
-        match g_window.get_key(Key::Down) {
+        match context.window.get_key(Key::Down) {
             Action::Press | Action::Repeat => {
                 cam_pos[1] -= cam_speed * (elapsed_seconds as GLfloat);
                 cam_moved = true;
             }
             _ => {}
         }
-        match g_window.get_key(Key::W) {
+        match context.window.get_key(Key::W) {
             Action::Press | Action::Repeat => {
                 cam_pos[2] -= cam_speed * (elapsed_seconds as GLfloat);
                 cam_moved = true;
             }
             _ => {}
         }
-        match g_window.get_key(Key::S) {
+        match context.window.get_key(Key::S) {
             Action::Press | Action::Repeat => {
                 cam_pos[2] += cam_speed * (elapsed_seconds as GLfloat);
                 cam_moved = true;
             }
             _ => {}
         }
-        match g_window.get_key(Key::Left) {
+        match context.window.get_key(Key::Left) {
             Action::Press | Action::Repeat => {
                 cam_yaw += cam_yaw_speed * (elapsed_seconds as GLfloat);
                 cam_moved = true;
             }
             _ => {}
         }
-        match g_window.get_key(Key::Right) {
+        match context.window.get_key(Key::Right) {
             Action::Press | Action::Repeat => {
                 cam_yaw -= cam_yaw_speed * (elapsed_seconds as GLfloat);
                 cam_moved = true;
             }
             _ => {}
         }
+        // step to the next level of detail once per key press
+        match context.window.get_key(Key::L) {
+            Action::Press => {
+                if !lod_key_was_down {
+                    lod = (lod + 1) % lods.len();
+                    println!(
+                        "Level of detail {}: {} triangles", lod, renderer.model(lods[lod]).point_count / 3
+                    );
+                }
+                lod_key_was_down = true;
+            }
+            _ => {
+                lod_key_was_down = false;
+            }
+        }
+        // delete every GL object and make it again, as after losing the context
+        match context.window.get_key(Key::R) {
+            Action::Press => {
+                if !reload_key_was_down {
+                    let failures = reload_gl_objects(&mut renderer, &logger, shader_programme, &view_mat, &proj_mat);
+                    println!("Reloaded every GL object, {} failed", failures);
+                }
+                reload_key_was_down = true;
+            }
+            _ => {
+                reload_key_was_down = false;
+            }
+        }
+        // Alt+Enter switches between a window and fullscreen, and makes every GL
+        // object again afterwards, as a context recreated for the new window needs
+        let alt_held = context.window.get_key(Key::LeftAlt) != Action::Release
+            || context.window.get_key(Key::RightAlt) != Action::Release;
+        match context.window.get_key(Key::Enter) {
+            Action::Press if alt_held => {
+                if !fullscreen_key_was_down {
+                    let fullscreen = context.toggle_fullscreen();
+                    logger.log(&format!("fullscreen: {}", fullscreen));
+                    let (width, height) = context.window.get_framebuffer_size();
+                    context.width = width as u32;
+                    context.height = height as u32;
+                    proj_mat = Mat4::perspective(fov, width as f32 / height as f32, near, far);
+                    reload_gl_objects(&mut renderer, &logger, shader_programme, &view_mat, &proj_mat);
+                }
+                fullscreen_key_was_down = true;
+            }
+            _ => {
+                fullscreen_key_was_down = false;
+            }
+        }
         // update view matrix
         if cam_moved {
-            mat_trans = Mat4::identity().translate(&math::vec3((-cam_pos[0], -cam_pos[1], -cam_pos[2]))); // cam translation
-            mat_rot = Mat4::identity().rotate_y_deg(-cam_yaw);                 //
+            mat_trans = Mat4::from_translation(&math::vec3((-cam_pos[0], -cam_pos[1], -cam_pos[2]))); // cam translation
+            mat_rot = Mat4::from_rotation_y_deg(-cam_yaw);
             view_mat = mat_rot * mat_trans;
-            unsafe {
-                gl::UniformMatrix4fv(view_mat_location, 1, gl::FALSE, view_mat.as_ptr());
-            }
+            renderer.set_uniform_mat4(shader_programme, "view", &view_mat);
         }
 
-        match g_window.get_key(Key::Escape) {
+        match context.window.get_key(Key::Escape) {
             Action::Press | Action::Repeat => {
-                g_window.set_should_close(true);
+                context.window.set_should_close(true);
             }
             _ => {}
         }
         // Put the stuff we've been drawing onto the display.
-        g_window.swap_buffers();
+        context.window.swap_buffers();
+    }
+
+    if let Some(recorder) = recorder {
+        match recorder.write_frames() {
+            Ok(frame_count) => println!("Wrote {} turntable frames.", frame_count),
+            Err(e) => eprintln!("ERROR: could not write turntable frames: {}", e),
+        }
     }
 }
@@ -0,0 +1,277 @@
+use geometry;
+use geometry::{Aabb, BoundingSphere};
+use model::Model;
+
+use std::collections::HashMap;
+
+
+///
+/// Triangles with less area than this fraction of the square of the bounding box
+/// diagonal are counted as degenerate. They cover no pixels, and give NaNs
+/// wherever a normal or tangent is worked out from them.
+///
+const DEGENERATE_AREA: f32 = 1e-12;
+
+///
+/// Vertices closer together than this fraction of the bounding box diagonal count
+/// as the same corner.
+///
+const WELD_DISTANCE: f32 = 1e-6;
+
+
+///
+/// What a model is made of, and the problems in it that most often explain why a
+/// mesh renders wrongly.
+///
+#[derive(Clone, Debug, PartialEq)]
+pub struct MeshStats {
+    pub vertex_count: usize,
+    pub triangle_count: usize,
+    ///
+    /// The number of distinct positions, once vertices that only differ in their
+    /// normal or texture coordinates are counted as one.
+    ///
+    pub unique_positions: usize,
+    pub bounds: Aabb,
+    pub bounding_sphere: BoundingSphere,
+    pub has_tex_coords: bool,
+    pub has_normals: bool,
+    pub group_count: usize,
+    pub material_count: usize,
+    ///
+    /// Triangles with no area, either with two corners in the same place or all
+    /// three in a line.
+    ///
+    pub degenerate_triangles: usize,
+    ///
+    /// Edges on the border of a hole, used by one triangle only. A closed mesh has
+    /// none.
+    ///
+    pub boundary_edges: usize,
+    ///
+    /// Edges shared by more than two triangles, which no real surface has.
+    ///
+    pub non_manifold_edges: usize,
+    ///
+    /// Vertices left over after the last whole triangle.
+    ///
+    pub stray_vertices: usize,
+}
+
+impl MeshStats {
+    ///
+    /// Everything worth warning about, one line each. Empty if the mesh looks sound.
+    ///
+    pub fn warnings(&self) -> Vec<String> {
+        let mut warnings = vec![];
+        if self.triangle_count == 0 {
+            warnings.push(String::from("WARNING: the mesh has no triangles"));
+        }
+        if self.stray_vertices > 0 {
+            warnings.push(format!(
+                "WARNING: {} vertices left over after the last triangle", self.stray_vertices
+            ));
+        }
+        if !self.has_normals {
+            warnings.push(String::from("WARNING: no normals, so lighting will be wrong"));
+        }
+        if self.degenerate_triangles > 0 {
+            warnings.push(format!("WARNING: {} degenerate triangles", self.degenerate_triangles));
+        }
+        if self.non_manifold_edges > 0 {
+            warnings.push(format!(
+                "WARNING: {} non-manifold edges shared by more than two triangles", self.non_manifold_edges
+            ));
+        }
+        if self.boundary_edges > 0 {
+            warnings.push(format!("WARNING: {} boundary edges, so the mesh is not closed", self.boundary_edges));
+        }
+
+        warnings
+    }
+
+    ///
+    /// A report of the counts, one line each.
+    ///
+    pub fn describe(&self) -> Vec<String> {
+        let yes_no = |value: bool| if value { "yes" } else { "no" };
+
+        vec![
+            format!("vertices:          {}", self.vertex_count),
+            format!("triangles:         {}", self.triangle_count),
+            format!("unique positions:  {}", self.unique_positions),
+            format!(
+                "bounds:            ({}, {}, {}) to ({}, {}, {})",
+                self.bounds.min.v[0], self.bounds.min.v[1], self.bounds.min.v[2],
+                self.bounds.max.v[0], self.bounds.max.v[1], self.bounds.max.v[2]
+            ),
+            format!(
+                "bounding sphere:   ({}, {}, {}) radius {}",
+                self.bounding_sphere.centre.v[0], self.bounding_sphere.centre.v[1], self.bounding_sphere.centre.v[2],
+                self.bounding_sphere.radius
+            ),
+            format!("texture coords:    {}", yes_no(self.has_tex_coords)),
+            format!("normals:           {}", yes_no(self.has_normals)),
+            format!("material groups:   {}", self.group_count),
+            format!("materials:         {}", self.material_count),
+        ]
+    }
+}
+
+fn triangle_area(a: &[f32], b: &[f32], c: &[f32]) -> f32 {
+    let ab = [b[0] - a[0], b[1] - a[1], b[2] - a[2]];
+    let ac = [c[0] - a[0], c[1] - a[1], c[2] - a[2]];
+    let cross = [
+        ab[1] * ac[2] - ab[2] * ac[1],
+        ab[2] * ac[0] - ab[0] * ac[2],
+        ab[0] * ac[1] - ab[1] * ac[0],
+    ];
+
+    0.5 * f32::sqrt(cross[0] * cross[0] + cross[1] * cross[1] + cross[2] * cross[2])
+}
+
+///
+/// Work out the statistics for `model`. Vertices count as the same corner for
+/// finding shared edges when their positions are within `WELD_DISTANCE` of the
+/// size of the mesh, so `-0.0` and `0.0`, or rounding in an exporter, do not
+/// open up seams that are not there.
+///
+pub fn mesh_stats(model: &Model) -> MeshStats {
+    let points = &model.points;
+    let vertex_count = model.point_count;
+    let triangle_count = vertex_count / 3;
+
+    let bounds = model.aabb();
+    let diagonal = (bounds.max - bounds.min).norm();
+    // a mesh squashed to a point still needs a distance to weld within
+    let scale = if diagonal > 0.0 { diagonal } else { 1.0 };
+
+    // give each distinct position an index, so triangles that touch can be found
+    let welded = geometry::weld_vertices(points, &[], WELD_DISTANCE * scale);
+    let indices = &welded.indices;
+
+    let mut degenerate_triangles = 0;
+    let mut edge_uses: HashMap<(u32, u32), usize> = HashMap::new();
+    for t in 0..triangle_count {
+        let corners = [indices[3 * t], indices[3 * t + 1], indices[3 * t + 2]];
+        let area = triangle_area(
+            &points[9 * t..9 * t + 3], &points[9 * t + 3..9 * t + 6], &points[9 * t + 6..9 * t + 9]
+        );
+        if area < DEGENERATE_AREA * scale * scale {
+            // it has no edges worth counting
+            degenerate_triangles += 1;
+            continue;
+        }
+        for k in 0..3 {
+            let (a, b) = (corners[k], corners[(k + 1) % 3]);
+            let edge = if a < b { (a, b) } else { (b, a) };
+            *edge_uses.entry(edge).or_insert(0) += 1;
+        }
+    }
+
+    MeshStats {
+        vertex_count: vertex_count,
+        triangle_count: triangle_count,
+        unique_positions: welded.vertex_count(),
+        bounds: bounds,
+        bounding_sphere: model.bounding_sphere(),
+        has_tex_coords: vertex_count > 0 && model.tex_coords.len() == 2 * vertex_count,
+        has_normals: vertex_count > 0 && model.normals.len() == 3 * vertex_count,
+        group_count: model.groups.len(),
+        material_count: model.materials.len(),
+        degenerate_triangles: degenerate_triangles,
+        boundary_edges: edge_uses.values().filter(|&&uses| uses == 1).count(),
+        non_manifold_edges: edge_uses.values().filter(|&&uses| uses > 2).count(),
+        stray_vertices: vertex_count % 3,
+    }
+}
+
+mod mesh_stats_tests {
+    use super::mesh_stats;
+    use graphics_math as math;
+    use model::Model;
+
+    fn model_from_triangles(points: Vec<f32>) -> Model {
+        Model::new(points, vec![], vec![], vec![], vec![], vec![], vec![])
+    }
+
+    ///
+    /// The four faces of a tetrahedron, wound outwards.
+    ///
+    fn tetrahedron() -> Vec<f32> {
+        let p = [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]];
+        let faces = [[0, 2, 1], [0, 1, 3], [0, 3, 2], [1, 2, 3]];
+        faces.iter().flat_map(|face| face.iter().flat_map(|&i| p[i].to_vec()).collect::<Vec<f32>>()).collect()
+    }
+
+    #[test]
+    fn test_a_closed_mesh_has_no_warnings_besides_missing_normals() {
+        let stats = mesh_stats(&model_from_triangles(tetrahedron()));
+
+        assert_eq!(stats.vertex_count, 12);
+        assert_eq!(stats.triangle_count, 4);
+        assert_eq!(stats.unique_positions, 4);
+        assert_eq!(stats.bounds.min, math::vec3((0.0, 0.0, 0.0)));
+        assert_eq!(stats.bounds.max, math::vec3((1.0, 1.0, 1.0)));
+        assert_eq!(stats.boundary_edges, 0);
+        assert_eq!(stats.non_manifold_edges, 0);
+        assert_eq!(stats.warnings(), vec![String::from("WARNING: no normals, so lighting will be wrong")]);
+    }
+
+    #[test]
+    fn test_open_and_non_manifold_edges_are_found() {
+        // take one face off, leaving a triangular hole
+        let mut points = tetrahedron();
+        points.truncate(27);
+        let open = mesh_stats(&model_from_triangles(points.clone()));
+        assert_eq!(open.boundary_edges, 3);
+
+        // a fin hanging off the edge from (0,0,0) to (1,0,0), on top of the two
+        // faces already meeting there
+        points.extend_from_slice(&[0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.5, -1.0, 0.5]);
+        let finned = mesh_stats(&model_from_triangles(points));
+        assert_eq!(finned.non_manifold_edges, 1);
+    }
+
+    #[test]
+    fn test_degenerate_triangles_and_stray_vertices_are_counted() {
+        let points = vec![
+            0.0, 0.0, 0.0,  1.0, 0.0, 0.0,  0.0, 1.0, 0.0,
+            // two corners in the same place
+            0.0, 0.0, 0.0,  0.0, 0.0, 0.0,  0.0, 1.0, 0.0,
+            // all three in a line
+            0.0, 0.0, 0.0,  1.0, 1.0, 1.0,  2.0, 2.0, 2.0,
+            // half a triangle
+            5.0, 5.0, 5.0,
+        ];
+        let stats = mesh_stats(&model_from_triangles(points));
+
+        assert_eq!(stats.triangle_count, 3);
+        assert_eq!(stats.degenerate_triangles, 2);
+        assert_eq!(stats.stray_vertices, 1);
+        assert_eq!(stats.bounds.max, math::vec3((5.0, 5.0, 5.0)));
+    }
+
+    #[test]
+    fn test_negative_zero_is_the_same_corner_as_zero() {
+        let mut points = tetrahedron();
+        for x in points[9..18].iter_mut() {
+            if *x == 0.0 {
+                *x = -0.0;
+            }
+        }
+        let stats = mesh_stats(&model_from_triangles(points));
+
+        assert_eq!(stats.unique_positions, 4);
+        assert_eq!(stats.boundary_edges, 0);
+    }
+
+    #[test]
+    fn test_degenerate_area_scales_with_the_mesh() {
+        let tiny: Vec<f32> = tetrahedron().iter().map(|x| x * 1e-7).collect();
+        let huge: Vec<f32> = tetrahedron().iter().map(|x| x * 1e7).collect();
+
+        assert_eq!(mesh_stats(&model_from_triangles(tiny)).degenerate_triangles, 0);
+        assert_eq!(mesh_stats(&model_from_triangles(huge)).degenerate_triangles, 0);
+    }
+}
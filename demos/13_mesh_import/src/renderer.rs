@@ -0,0 +1,529 @@
+use gl;
+use gl::types::{GLsizei, GLsizeiptr, GLuint, GLvoid};
+use stb_image::image::LoadResult;
+
+use assets;
+use geometry;
+use geometry::WeldReport;
+use gl_utils::create_programme_from_files;
+use graphics_math::Mat4;
+use logger::Logger;
+use material::{MaterialBinder, MaterialTextures};
+use model::Model;
+use obj_parser::{Material, MaterialGroup};
+use resource_cache::{GpuResident, Loaders, Mesh, MeshBuffers, Program, ResourceCache, Texture};
+use vertex_layout::VertexLayout;
+use gl_utils::uniform_location;
+
+use std::mem;
+use std::rc::Rc;
+
+
+///
+/// A mesh uploaded to the GPU by a `Renderer`.
+///
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct MeshHandle(usize);
+
+///
+/// A texture loaded by a `Renderer`.
+///
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct TextureHandle(usize);
+
+///
+/// A material and its textures, held by a `Renderer`.
+///
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct MaterialHandle(usize);
+
+///
+/// A shader programme built by a `Renderer`.
+///
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ProgramHandle(usize);
+
+///
+/// Every renderer starts with a plain white material, which draws whatever has
+/// no material of its own.
+///
+pub const DEFAULT_MATERIAL: MaterialHandle = MaterialHandle(0);
+
+///
+/// Vertices of an uploaded model closer than this, with all their attributes
+/// this close too, are welded into one.
+///
+const WELD_EPSILON: f32 = 1e-6;
+
+///
+/// A run of a mesh's triangles drawn with one material. `first` and `count` are
+/// in vertices of the model, which are the same as indices once it is welded.
+///
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct DrawGroup {
+    pub material: MaterialHandle,
+    pub first: usize,
+    pub count: usize,
+}
+
+///
+/// Work out which material each group of a mesh is drawn with. `materials` holds
+/// the handles of the mesh's own materials, by name; groups naming a material the
+/// mesh does not have use the default material. A mesh without any groups is
+/// drawn in one piece with the default material.
+///
+pub fn resolve_draw_groups(
+    groups: &[MaterialGroup], materials: &[(String, MaterialHandle)], point_count: usize) -> Vec<DrawGroup> {
+
+    if groups.is_empty() {
+        return vec![DrawGroup { material: DEFAULT_MATERIAL, first: 0, count: point_count }];
+    }
+
+    groups.iter().map(|group| {
+        let material = materials.iter()
+            .find(|&&(ref name, _)| *name == group.material)
+            .map_or(DEFAULT_MATERIAL, |&(_, handle)| handle);
+
+        DrawGroup { material: material, first: group.first, count: group.count }
+    }).collect()
+}
+
+///
+/// A mesh and the material groups it is drawn in. The mesh is shared with the
+/// renderer's `ResourceCache` when it came from a file.
+///
+struct GpuMesh {
+    mesh: Rc<Mesh>,
+    groups: Vec<DrawGroup>,
+}
+
+///
+/// Upload a model as an indexed mesh. Every loader gives a triangle soup, so it
+/// is welded first, to draw each shared corner once through an index buffer.
+/// A mesh lost with its context goes through here again, and is made just as it
+/// was first made.
+///
+fn upload_welded_mesh(model: &Model) -> MeshBuffers {
+    let mut vao = 0;
+    unsafe {
+        gl::GenVertexArrays(1, &mut vao);
+    }
+    assert!(vao > 0);
+
+    let welded = geometry::weld_vertices(
+        &model.points, &[(&model.normals, 3), (&model.tex_coords, 2)], WELD_EPSILON
+    );
+    let vertex_count = welded.vertex_count();
+    let mut buffers = vec![
+        VertexLayout::new().floats(0, 3).upload(vao, &welded.gather(&model.points, 3), vertex_count),
+        VertexLayout::new().floats(1, 3).upload(vao, &welded.gather(&model.normals, 3), vertex_count),
+    ];
+    if model.tex_coords.len() == 2 * model.point_count {
+        buffers.push(VertexLayout::new().floats(2, 2).upload(vao, &welded.gather(&model.tex_coords, 2), vertex_count));
+    }
+
+    // the index buffer is part of the vertex array state, so it stays bound with it
+    let mut ibo = 0;
+    unsafe {
+        gl::BindVertexArray(vao);
+        gl::GenBuffers(1, &mut ibo);
+        gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, ibo);
+        gl_check!(gl::BufferData(
+            gl::ELEMENT_ARRAY_BUFFER, (welded.indices.len() * mem::size_of::<u32>()) as GLsizeiptr,
+            welded.indices.as_ptr() as *const GLvoid, gl::STATIC_DRAW
+        ));
+    }
+    assert!(ibo > 0);
+    buffers.push(ibo);
+
+    MeshBuffers { vao: vao, buffers: buffers, vertex_count: vertex_count }
+}
+
+///
+/// Owns every GL object a demo draws with, and hands out typed handles to them
+/// in place of raw `GLuint`s, so a texture can never be bound where a buffer was
+/// meant and nothing is deleted while a handle to it is still around. Files are
+/// loaded through a `ResourceCache`, so loading the same file twice gives back
+/// the same handle.
+///
+/// Handles are only meaningful to the renderer that made them.
+///
+pub struct Renderer {
+    resources: ResourceCache,
+    meshes: Vec<GpuMesh>,
+    textures: Vec<Rc<Texture>>,
+    materials: Vec<(Material, [Option<TextureHandle>; 4])>,
+    programs: Vec<(Rc<Program>, MaterialBinder)>,
+}
+
+impl Renderer {
+    pub fn new() -> Renderer {
+        Renderer {
+            resources: ResourceCache::new(),
+            meshes: vec![],
+            textures: vec![],
+            materials: vec![(Material::default(), [None; 4])],
+            programs: vec![],
+        }
+    }
+
+    pub fn resources(&self) -> &ResourceCache {
+        &self.resources
+    }
+
+    ///
+    /// Build a shader programme from a vertex and a fragment shader file.
+    ///
+    pub fn create_program(&mut self, logger: &Logger, vertex_shader_file: &str, fragment_shader_file: &str) -> ProgramHandle {
+        let program = self.resources.program(vertex_shader_file, fragment_shader_file, |vert, frag| {
+            create_programme_from_files(logger, vert, frag)
+        });
+        if let Some(i) = self.programs.iter().position(|entry| Rc::ptr_eq(&entry.0, &program)) {
+            return ProgramHandle(i);
+        }
+
+        let binder = MaterialBinder::new(program.id());
+        self.programs.push((program, binder));
+
+        ProgramHandle(self.programs.len() - 1)
+    }
+
+    ///
+    /// Load an image file into a texture. A file that could not be loaded gives
+    /// a texture that samples as black, and an error in the log.
+    ///
+    pub fn load_texture(&mut self, file_name: &str) -> TextureHandle {
+        let texture = self.resources.texture(file_name, load_texture_file);
+        if let Some(i) = self.textures.iter().position(|entry| Rc::ptr_eq(entry, &texture)) {
+            return TextureHandle(i);
+        }
+        self.textures.push(texture);
+
+        TextureHandle(self.textures.len() - 1)
+    }
+
+    ///
+    /// Add a material, loading the texture maps it names.
+    ///
+    pub fn add_material(&mut self, material: Material) -> MaterialHandle {
+        let mut maps = [None; 4];
+        {
+            let files = [&material.diffuse_map, &material.specular_map, &material.normal_map, &material.height_map];
+            for (map, file) in maps.iter_mut().zip(files.iter()) {
+                if let Some(ref file_name) = **file {
+                    *map = Some(self.load_texture(file_name));
+                }
+            }
+        }
+        self.materials.push((material, maps));
+
+        MaterialHandle(self.materials.len() - 1)
+    }
+
+    pub fn material(&self, material: MaterialHandle) -> &Material {
+        &self.materials[material.0].0
+    }
+
+    ///
+    /// Load a model file and upload it, falling back to `Model::load_cached`'s
+    /// binary cache where it can.
+    ///
+    pub fn load_model(&mut self, file_name: &str) -> Result<MeshHandle, String> {
+        let mesh = match self.resources.mesh(file_name, Model::load_cached, &upload_welded_mesh) {
+            Ok(val) => val,
+            Err(e) => return Err(e),
+        };
+        if let Some(i) = self.meshes.iter().position(|entry| Rc::ptr_eq(&entry.mesh, &mesh)) {
+            return Ok(MeshHandle(i));
+        }
+
+        Ok(self.upload(mesh))
+    }
+
+    ///
+    /// Upload a model that did not come from a file, such as the embedded
+    /// fallback mesh.
+    ///
+    pub fn upload_model(&mut self, model: Model) -> MeshHandle {
+        self.upload(Rc::new(Mesh::new(model)))
+    }
+
+    fn upload(&mut self, mesh: Rc<Mesh>) -> MeshHandle {
+        let mut materials = vec![];
+        let groups = {
+            let model = mesh.model();
+            for material in model.materials.iter() {
+                let handle = self.add_material(material.clone());
+                materials.push((material.name.clone(), handle));
+            }
+
+            resolve_draw_groups(&model.groups, &materials, model.point_count)
+        };
+
+        mesh.upload_with(&upload_welded_mesh);
+        self.meshes.push(GpuMesh { mesh: mesh, groups: groups });
+
+        MeshHandle(self.meshes.len() - 1)
+    }
+
+    ///
+    /// The model a mesh was uploaded from.
+    ///
+    pub fn model(&self, mesh: MeshHandle) -> &Model {
+        self.meshes[mesh.0].mesh.model()
+    }
+
+    ///
+    /// How many vertices welding saved when a mesh was uploaded.
+    ///
+    pub fn weld_report(&self, mesh: MeshHandle) -> WeldReport {
+        let mesh = &self.meshes[mesh.0].mesh;
+
+        WeldReport { vertices_before: mesh.model().point_count, vertices_after: mesh.vertex_count() }
+    }
+
+    ///
+    /// Forget every GL object the renderer holds, after the context they were made
+    /// in has gone, such as when the window is recreated to go fullscreen. Nothing
+    /// is deleted, as there is nothing left to delete it from. Handles stay valid,
+    /// and `upload_all` makes the objects again.
+    ///
+    pub fn invalidate(&mut self) {
+        self.resources.invalidate_all();
+        for entry in self.meshes.iter() {
+            entry.mesh.invalidate();
+        }
+    }
+
+    ///
+    /// Make every GL object the renderer holds that is not resident again, in the
+    /// current context, from the files and models they were made from. Returns how
+    /// many textures and programmes could not be made. Uniforms live in the
+    /// programmes, so they have to be set again afterwards.
+    ///
+    pub fn upload_all(&mut self, logger: &Logger) -> usize {
+        let load_program = |vert: &str, frag: &str| create_programme_from_files(logger, vert, frag);
+        let failures = self.resources.upload_all(
+            &Loaders { texture: &load_texture_file, program: &load_program, mesh: &upload_welded_mesh }
+        );
+        self.upload_meshes();
+
+        failures
+    }
+
+    ///
+    /// Delete every GL object the renderer holds and make them all again, in the
+    /// same context. This goes through the same steps as coming back from a lost
+    /// context, and picks up any changes to the files on disk. Demos call it after
+    /// switching to or from fullscreen.
+    ///
+    pub fn reload(&mut self, logger: &Logger) -> usize {
+        for entry in self.meshes.iter() {
+            entry.mesh.release();
+        }
+        let load_program = |vert: &str, frag: &str| create_programme_from_files(logger, vert, frag);
+        let failures = self.resources.recreate_all(
+            &Loaders { texture: &load_texture_file, program: &load_program, mesh: &upload_welded_mesh }
+        );
+        self.upload_meshes();
+
+        failures
+    }
+
+    ///
+    /// Make the meshes the cache does not hold, such as levels of detail, again
+    /// where they are not resident, and look the material uniforms up afresh in
+    /// programmes that may have been made again.
+    ///
+    fn upload_meshes(&mut self) {
+        for entry in self.meshes.iter() {
+            entry.mesh.upload_with(&upload_welded_mesh);
+        }
+        for &mut (ref program, ref mut binder) in self.programs.iter_mut() {
+            *binder = MaterialBinder::new(program.id());
+        }
+    }
+
+    ///
+    /// Set a matrix uniform of a shader programme. Uniforms the programme does not
+    /// have are skipped, as GL does.
+    ///
+    pub fn set_uniform_mat4(&self, program: ProgramHandle, name: &str, matrix: &Mat4) {
+        let sp = self.programs[program.0].0.id();
+        unsafe {
+            gl::UseProgram(sp);
+            let location = uniform_location(sp, name);
+            gl_check!(gl::UniformMatrix4fv(location, 1, gl::FALSE, matrix.as_ptr()));
+        }
+    }
+
+    ///
+    /// Set a vector uniform of a shader programme. Uniforms the programme does not
+    /// have are skipped, as GL does.
+    ///
+    pub fn set_uniform_vec3(&self, program: ProgramHandle, name: &str, value: &[f32; 3]) {
+        let sp = self.programs[program.0].0.id();
+        unsafe {
+            gl::UseProgram(sp);
+            let location = uniform_location(sp, name);
+            gl_check!(gl::Uniform3fv(location, 1, value.as_ptr()));
+        }
+    }
+
+    ///
+    /// Set an integer uniform of a shader programme.
+    ///
+    pub fn set_uniform_i32(&self, program: ProgramHandle, name: &str, value: i32) {
+        let sp = self.programs[program.0].0.id();
+        unsafe {
+            gl::UseProgram(sp);
+            let location = uniform_location(sp, name);
+            gl_check!(gl::Uniform1i(location, value));
+        }
+    }
+
+    fn textures_of(&self, maps: &[Option<TextureHandle>; 4]) -> MaterialTextures {
+        let id = |map: Option<TextureHandle>| map.map_or(0, |handle| self.textures[handle.0].id());
+
+        MaterialTextures {
+            diffuse_map: id(maps[0]),
+            specular_map: id(maps[1]),
+            normal_map: id(maps[2]),
+            height_map: id(maps[3]),
+        }
+    }
+
+    ///
+    /// Draw a mesh with a shader programme, one material group at a time.
+    ///
+    pub fn draw(&self, program: ProgramHandle, mesh: MeshHandle) {
+        let (_, ref binder) = self.programs[program.0];
+        let mesh = &self.meshes[mesh.0];
+        unsafe {
+            gl::BindVertexArray(mesh.mesh.vao());
+        }
+        for group in mesh.groups.iter() {
+            let &(ref material, ref maps) = &self.materials[group.material.0];
+            binder.bind(material, &self.textures_of(maps));
+            unsafe {
+                gl_check!(gl::DrawElements(
+                    gl::TRIANGLES, group.count as GLsizei, gl::UNSIGNED_INT,
+                    (group.first * mem::size_of::<u32>()) as *const GLvoid
+                ));
+            }
+        }
+    }
+
+    ///
+    /// Draw a whole mesh with one material, in place of the materials it came with.
+    ///
+    pub fn draw_with_material(&self, program: ProgramHandle, mesh: MeshHandle, material: MaterialHandle) {
+        let (_, ref binder) = self.programs[program.0];
+        let mesh = &self.meshes[mesh.0];
+        let &(ref material, ref maps) = &self.materials[material.0];
+        binder.bind(material, &self.textures_of(maps));
+        unsafe {
+            gl::BindVertexArray(mesh.mesh.vao());
+            gl_check!(gl::DrawElements(gl::TRIANGLES, mesh.mesh.model().point_count as GLsizei, gl::UNSIGNED_INT, 0 as *const GLvoid));
+        }
+    }
+}
+
+///
+/// Load an image file into a new mipmapped GL texture, flipped so the first row
+/// of the image is at the bottom as GL expects. Returns zero if the image could
+/// not be loaded.
+///
+fn load_texture_file(file_name: &str) -> GLuint {
+    let force_channels = 4;
+    let mut image_data = match assets::load_image_with_depth(file_name, force_channels) {
+        LoadResult::ImageU8(image_data) => image_data,
+        LoadResult::Error(_) => {
+            eprintln!("ERROR: could not load {}", file_name);
+            return 0;
+        }
+        LoadResult::ImageF32(_) => {
+            eprintln!("ERROR: Tried to load an image as byte vectors, got f32: {}", file_name);
+            return 0;
+        }
+    };
+
+    let width = image_data.width;
+    let height = image_data.height;
+    let width_in_bytes = 4 * width;
+    for row in 0..(height / 2) {
+        for col in 0..width_in_bytes {
+            image_data.data.swap(row * width_in_bytes + col, (height - row - 1) * width_in_bytes + col);
+        }
+    }
+
+    let mut tex = 0;
+    unsafe {
+        gl::GenTextures(1, &mut tex);
+        gl::ActiveTexture(gl::TEXTURE0);
+        gl::BindTexture(gl::TEXTURE_2D, tex);
+        gl_check!(gl::TexImage2D(
+            gl::TEXTURE_2D, 0, gl::RGBA as i32, width as i32, height as i32, 0,
+            gl::RGBA, gl::UNSIGNED_BYTE,
+            image_data.data.as_ptr() as *const GLvoid
+        ));
+        gl_check!(gl::GenerateMipmap(gl::TEXTURE_2D));
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::REPEAT as i32);
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::REPEAT as i32);
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::LINEAR as i32);
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::LINEAR_MIPMAP_LINEAR as i32);
+    }
+
+    tex
+}
+
+mod renderer_tests {
+    use super::{resolve_draw_groups, DrawGroup, MaterialHandle, Renderer, DEFAULT_MATERIAL};
+    use colour::Colour;
+    use obj_parser::{Material, MaterialGroup};
+
+    fn group(material: &str, first: usize, count: usize) -> MaterialGroup {
+        MaterialGroup { material: String::from(material), first: first, count: count }
+    }
+
+    #[test]
+    fn test_groups_are_drawn_with_their_own_materials() {
+        let materials = vec![(String::from("skin"), MaterialHandle(3)), (String::from("eyes"), MaterialHandle(4))];
+        let groups = vec![group("eyes", 0, 6), group("skin", 6, 90)];
+
+        assert_eq!(resolve_draw_groups(&groups, &materials, 96), vec![
+            DrawGroup { material: MaterialHandle(4), first: 0, count: 6 },
+            DrawGroup { material: MaterialHandle(3), first: 6, count: 90 },
+        ]);
+    }
+
+    #[test]
+    fn test_unknown_materials_fall_back_to_the_default() {
+        let materials = vec![(String::from("skin"), MaterialHandle(1))];
+        let groups = vec![group("", 0, 3), group("missing", 3, 3)];
+        let resolved = resolve_draw_groups(&groups, &materials, 6);
+
+        assert!(resolved.iter().all(|group| group.material == DEFAULT_MATERIAL));
+    }
+
+    #[test]
+    fn test_a_mesh_without_groups_is_drawn_in_one_piece() {
+        assert_eq!(resolve_draw_groups(&[], &[], 36), vec![
+            DrawGroup { material: DEFAULT_MATERIAL, first: 0, count: 36 },
+        ]);
+    }
+
+    #[test]
+    fn test_materials_without_maps_get_their_own_handles() {
+        let mut renderer = Renderer::new();
+        let mut red = Material::default();
+        red.name = String::from("red");
+        red.kd = Colour::RED;
+
+        let handle = renderer.add_material(red.clone());
+
+        assert!(handle != DEFAULT_MATERIAL);
+        assert_eq!(renderer.material(handle), &red);
+        assert_eq!(renderer.material(DEFAULT_MATERIAL), &Material::default());
+    }
+}
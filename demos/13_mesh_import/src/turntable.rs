@@ -0,0 +1,146 @@
+use graphics_math as math;
+use math::Mat4;
+
+
+///
+/// The command line option that starts a turntable capture, followed by the
+/// number of frames to take.
+///
+pub const TURNTABLE_OPTION: &str = "--turntable";
+
+
+///
+/// The number of frames given on the command line after `TURNTABLE_OPTION`, or
+/// `None` if there is no turntable capture to do.
+///
+pub fn turntable_from_args<I>(args: I) -> Result<Option<usize>, String>
+    where I: IntoIterator<Item = String> {
+
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        if arg == TURNTABLE_OPTION {
+            return match args.next() {
+                Some(value) => match value.parse::<usize>() {
+                    Ok(frames) if frames > 0 => Ok(Some(frames)),
+                    _ => Err(format!("ERROR: {} needs a number of frames above zero, got {}", TURNTABLE_OPTION, value)),
+                },
+                None => Err(format!("ERROR: {} needs a number of frames after it", TURNTABLE_OPTION)),
+            };
+        }
+    }
+
+    Ok(None)
+}
+
+///
+/// Walks the camera once around the mesh over a fixed number of frames, always
+/// facing the middle, so each frame can be saved for a turntable render. The last
+/// frame stops one step short of a full turn, so the frames loop without the
+/// first one showing twice.
+///
+pub struct Turntable {
+    frame_count: usize,
+    frame: usize,
+    radius: f32,
+    height: f32,
+}
+
+impl Turntable {
+    ///
+    /// Orbit `radius` units from the vertical axis through the origin, at `height`
+    /// above it, starting on the +z side where the camera starts.
+    ///
+    pub fn new(frame_count: usize, radius: f32, height: f32) -> Turntable {
+        assert!(frame_count > 0);
+        Turntable {
+            frame_count: frame_count,
+            frame: 0,
+            radius: radius,
+            height: height,
+        }
+    }
+
+    pub fn frame_count(&self) -> usize {
+        self.frame_count
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.frame >= self.frame_count
+    }
+
+    ///
+    /// How far around the camera is on the current frame, in degrees.
+    ///
+    pub fn angle_deg(&self) -> f32 {
+        360.0 * self.frame as f32 / self.frame_count as f32
+    }
+
+    ///
+    /// The view matrix for the current frame, made the same way as the demo's own
+    /// from a position and a turn about the vertical.
+    ///
+    pub fn view_mat(&self) -> Mat4 {
+        let angle = self.angle_deg();
+        let radians = angle * math::ONE_DEG_IN_RAD;
+        let position = math::vec3((self.radius * f32::sin(radians), self.height, self.radius * f32::cos(radians)));
+        let mat_trans = Mat4::from_translation(&-position);
+        let mat_rot = Mat4::from_rotation_y_deg(-angle);
+
+        mat_rot * mat_trans
+    }
+
+    ///
+    /// Move on to the next frame once the current one has been captured.
+    ///
+    pub fn advance(&mut self) {
+        if !self.is_done() {
+            self.frame += 1;
+        }
+    }
+}
+
+mod turntable_tests {
+    use super::{turntable_from_args, Turntable};
+    use graphics_math as math;
+
+    fn args(list: &[&str]) -> Vec<String> {
+        list.iter().map(|arg| String::from(*arg)).collect()
+    }
+
+    #[test]
+    fn test_the_frame_count_comes_from_the_command_line() {
+        assert_eq!(turntable_from_args(args(&["mesh_import"])), Ok(None));
+        assert_eq!(turntable_from_args(args(&["mesh_import", "--turntable", "90"])), Ok(Some(90)));
+        assert!(turntable_from_args(args(&["mesh_import", "--turntable"])).is_err());
+        assert!(turntable_from_args(args(&["mesh_import", "--turntable", "0"])).is_err());
+        assert!(turntable_from_args(args(&["mesh_import", "--turntable", "lots"])).is_err());
+    }
+
+    #[test]
+    fn test_the_camera_always_faces_the_middle() {
+        let mut turntable = Turntable::new(4, 5.0, 0.0);
+        for _ in 0..4 {
+            // the origin is straight ahead of the camera, 5 units away
+            let origin = turntable.view_mat() * math::vec4((0.0, 0.0, 0.0, 1.0));
+            assert!(f32::abs(origin.v[0]) < 1e-4);
+            assert!(f32::abs(origin.v[1]) < 1e-4);
+            assert!(f32::abs(origin.v[2] + 5.0) < 1e-4);
+            turntable.advance();
+        }
+    }
+
+    #[test]
+    fn test_the_frames_cover_one_turn_without_repeating_the_first() {
+        let mut turntable = Turntable::new(8, 5.0, 0.0);
+        let mut angles = vec![];
+        while !turntable.is_done() {
+            angles.push(turntable.angle_deg());
+            turntable.advance();
+        }
+
+        assert_eq!(angles.len(), 8);
+        assert_eq!(angles[0], 0.0);
+        assert_eq!(angles[4], 180.0);
+        assert_eq!(angles[7], 315.0);
+    }
+}
@@ -2,29 +2,35 @@ extern crate gl;
 extern crate glfw;
 extern crate chrono;
 extern crate stb_image;
+#[cfg(feature = "glam")]
+extern crate glam;
+#[cfg(feature = "cgmath")]
+extern crate cgmath;
 
-#[macro_use] 
-extern crate scan_fmt;
-
+#[macro_use]
 mod gl_utils;
 mod graphics_math;
 mod obj_parser;
+mod colour;
+mod assets;
+mod shader_include;
+mod spirv;
+mod program_cache;
 mod logger;
+mod vertex_layout;
 
 
 use glfw::{Action, Context, Key};
-use gl::types::{GLfloat, GLsizeiptr, GLvoid, GLuint};
-use stb_image::image;
+use gl::types::{GLfloat, GLvoid, GLuint};
 use stb_image::image::LoadResult;
 
-use std::mem;
-use std::ptr;
 use std::process;
 
 use gl_utils::*;
 
 use graphics_math as math;
 use math::Mat4;
+use vertex_layout::VertexLayout;
 
 const GL_LOG_FILE: &str = "gl.log";
 const VERTEX_SHADER_FILE: &str = "src/test.vert.glsl";
@@ -35,12 +41,10 @@ const TEXTURE_FILE1: &str = "src/ship.png";
 const GL_TEXTURE_MAX_ANISOTROPY_EXT: u32 = 0x84FE;
 const GL_MAX_TEXTURE_MAX_ANISOTROPY_EXT: u32 = 0x84FF;
 
-static mut PREVIOUS_SECONDS: f64 = 0.0;
-
 
 fn load_texture(file_name: &str, tex: &mut GLuint) -> bool {
     let force_channels = 4;
-    let mut image_data = match image::load_with_depth(file_name, force_channels, false) {
+    let mut image_data = match assets::load_image_with_depth(file_name, force_channels) {
         LoadResult::ImageU8(image_data) => image_data,
         LoadResult::Error(_) => {
             eprintln!("ERROR: could not load {}", file_name);
@@ -127,39 +131,14 @@ fn main() {
         0.0, 0.0, 1.0, 0.0, 1.0, 1.0, 1.0, 1.0, 0.0, 1.0, 0.0, 0.0
     ];
 
-    let mut points_vbo = 0;
-    unsafe {
-        gl::GenBuffers(1, &mut points_vbo);
-        gl::BindBuffer(gl::ARRAY_BUFFER, points_vbo);
-        gl::BufferData(
-            gl::ARRAY_BUFFER, (18 * mem::size_of::<GLfloat>()) as GLsizeiptr, 
-            points.as_ptr() as *const GLvoid, gl::STATIC_DRAW
-        );
-    }
-    assert!(points_vbo > 0);
-
-    let mut texcoords_vbo = 0;
-    unsafe {
-        gl::GenBuffers(1, &mut texcoords_vbo);
-        gl::BindBuffer(gl::ARRAY_BUFFER, texcoords_vbo);
-        gl::BufferData(
-            gl::ARRAY_BUFFER, (12 * mem::size_of::<GLfloat>()) as GLsizeiptr, 
-            texcoords.as_ptr() as *const GLvoid, gl::STATIC_DRAW
-        );
-    }
-    assert!(texcoords_vbo > 0);
-
+    let point_count = points.len() / 3;
     let mut vao = 0;
     unsafe {
         gl::GenVertexArrays(1, &mut vao);
-        gl::BindVertexArray(vao);
-        gl::BindBuffer(gl::ARRAY_BUFFER, points_vbo);
-        gl::VertexAttribPointer(0, 3, gl::FLOAT, gl::FALSE, 0, ptr::null());
-        gl::BindBuffer(gl::ARRAY_BUFFER, texcoords_vbo);
-        gl::VertexAttribPointer(1, 2, gl::FLOAT, gl::FALSE, 0, ptr::null());
-        gl::EnableVertexAttribArray(0);
-        gl::EnableVertexAttribArray(1);
     }
+    assert!(vao > 0);
+    VertexLayout::new().floats(0, 3).upload(vao, &points, point_count);
+    VertexLayout::new().floats(1, 2).upload(vao, &texcoords, point_count);
 
     let shader_programme = create_programme_from_files(&logger, VERTEX_SHADER_FILE, FRAGMENT_SHADER_FILE);
 
@@ -175,25 +154,17 @@ fn main() {
     let cam_yaw_speed: GLfloat = 10.0;        // 10 degrees per second
     let mut cam_pos: [GLfloat; 3] = [0.0, 0.0, 2.0]; // don't start at zero, or we will be too close
     let mut cam_yaw: GLfloat = 0.0;               // y-rotation in degrees
-    let mut mat_trans = Mat4::identity().translate(&math::vec3((-cam_pos[0], -cam_pos[1], -cam_pos[2])));
-    let mut mat_rot = Mat4::identity().rotate_y_deg(-cam_yaw);
+    let mut mat_trans = Mat4::from_translation(&math::vec3((-cam_pos[0], -cam_pos[1], -cam_pos[2])));
+    let mut mat_rot = Mat4::from_rotation_y_deg(-cam_yaw);
     let mut view_mat = mat_rot * mat_trans;
 
-    let view_mat_location = unsafe {
-        gl::GetUniformLocation(shader_programme, "view".as_ptr() as *const i8)
-    };
+    let view_mat_location = uniform_location(shader_programme, "view");
     assert!(view_mat_location > -1);
-    let proj_mat_location = unsafe { 
-        gl::GetUniformLocation(shader_programme, "proj".as_ptr() as *const i8)
-    };
+    let proj_mat_location = uniform_location(shader_programme, "proj");
     assert!(proj_mat_location > -1);
-    let tex_a_location = unsafe {
-        gl::GetUniformLocation(shader_programme, "basic_texture".as_ptr() as *const i8)
-    };
+    let tex_a_location = uniform_location(shader_programme, "basic_texture");
     assert!(tex_a_location > -1);
-    let tex_b_location = unsafe { 
-        gl::GetUniformLocation(shader_programme, "second_texture".as_ptr() as *const i8)
-    };
+    let tex_b_location = uniform_location(shader_programme, "second_texture");
     assert!(tex_b_location > -1);
     unsafe {
         gl::UseProgram(shader_programme);
@@ -225,10 +196,8 @@ fn main() {
 
     while !context.window.should_close() {
         let current_seconds = context.glfw.get_time();
-        let elapsed_seconds = unsafe { current_seconds - PREVIOUS_SECONDS };
-        unsafe {
-            PREVIOUS_SECONDS = current_seconds;
-        }
+        let elapsed_seconds = current_seconds - context.elapsed_time_seconds;
+        context.elapsed_time_seconds = current_seconds;
 
         update_fps_counter(&mut context);
         unsafe {
@@ -305,8 +274,8 @@ fn main() {
         }
         // update view matrix
         if cam_moved {
-            mat_trans = Mat4::identity().translate(&math::vec3((-cam_pos[0], -cam_pos[1], -cam_pos[2]))); // cam translation
-            mat_rot = Mat4::identity().rotate_y_deg(-cam_yaw);
+            mat_trans = Mat4::from_translation(&math::vec3((-cam_pos[0], -cam_pos[1], -cam_pos[2]))); // cam translation
+            mat_rot = Mat4::from_rotation_y_deg(-cam_yaw);
             view_mat = mat_rot * mat_trans;
             unsafe {
                 gl::UniformMatrix4fv(view_mat_location, 1, gl::FALSE, view_mat.as_ptr());
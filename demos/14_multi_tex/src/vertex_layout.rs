@@ -0,0 +1,259 @@
+use gl;
+use gl::types::{GLenum, GLint, GLsizeiptr, GLuint, GLvoid};
+
+use std::mem;
+
+
+///
+/// One attribute in a vertex layout: the shader location it feeds, how many
+/// components it has and of what type, whether integer types are normalised to
+/// [0, 1] or [-1, 1], and its byte offset within a vertex.
+///
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct VertexAttribute {
+    pub location: GLuint,
+    pub components: GLint,
+    pub gl_type: GLenum,
+    pub normalized: bool,
+    pub offset: usize,
+}
+
+impl VertexAttribute {
+    ///
+    /// The number of bytes the attribute takes up in each vertex.
+    ///
+    pub fn size(&self) -> usize {
+        if is_packed_type(self.gl_type) {
+            return 4;
+        }
+
+        self.components as usize * type_size(self.gl_type)
+    }
+}
+
+///
+/// Whether a vertex attribute type packs all four components into a single 32-bit
+/// value, rather than giving each component its own bytes.
+///
+fn is_packed_type(gl_type: GLenum) -> bool {
+    gl_type == gl::INT_2_10_10_10_REV || gl_type == gl::UNSIGNED_INT_2_10_10_10_REV
+}
+
+///
+/// The size in bytes of one component of a vertex attribute type.
+///
+fn type_size(gl_type: GLenum) -> usize {
+    match gl_type {
+        gl::BYTE | gl::UNSIGNED_BYTE => 1,
+        gl::SHORT | gl::UNSIGNED_SHORT | gl::HALF_FLOAT => 2,
+        gl::INT | gl::UNSIGNED_INT | gl::FLOAT => 4,
+        gl::DOUBLE => 8,
+        _ => panic!("ERROR: unsupported vertex attribute type 0x{:X}", gl_type),
+    }
+}
+
+///
+/// Describes how the vertices in one vertex buffer are laid out. Attributes are
+/// declared in the order they appear in each vertex and packed with no padding, so
+/// a layout with a single attribute describes a buffer holding just that attribute,
+/// and a layout with several describes interleaved vertices. The offsets and stride
+/// passed to `glVertexAttribPointer` are worked out from the declarations rather
+/// than written out by hand.
+///
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct VertexLayout {
+    attributes: Vec<VertexAttribute>,
+}
+
+impl VertexLayout {
+    pub fn new() -> VertexLayout {
+        VertexLayout {
+            attributes: Vec::new(),
+        }
+    }
+
+    ///
+    /// Add an attribute after the ones already declared.
+    ///
+    pub fn attribute(mut self, location: GLuint, components: GLint, gl_type: GLenum, normalized: bool) -> VertexLayout {
+        let offset = self.stride();
+        self.attributes.push(VertexAttribute {
+            location: location,
+            components: components,
+            gl_type: gl_type,
+            normalized: normalized,
+            offset: offset,
+        });
+
+        self
+    }
+
+    ///
+    /// Add an attribute of `components` floats after the ones already declared.
+    ///
+    pub fn floats(self, location: GLuint, components: GLint) -> VertexLayout {
+        self.attribute(location, components, gl::FLOAT, false)
+    }
+
+    ///
+    /// Add an attribute of `components` 16-bit floats after the ones already declared.
+    ///
+    pub fn half_floats(self, location: GLuint, components: GLint) -> VertexLayout {
+        self.attribute(location, components, gl::HALF_FLOAT, false)
+    }
+
+    ///
+    /// Add an attribute of four signed normalised components packed into 32 bits,
+    /// 10 bits each for x, y, and z and 2 bits for w, after the ones already declared.
+    /// This suits unit vectors such as normals and tangents.
+    ///
+    pub fn packed_snorm(self, location: GLuint) -> VertexLayout {
+        self.attribute(location, 4, gl::INT_2_10_10_10_REV, true)
+    }
+
+    pub fn attributes(&self) -> &[VertexAttribute] {
+        &self.attributes
+    }
+
+    ///
+    /// The number of bytes from the start of one vertex to the start of the next.
+    ///
+    pub fn stride(&self) -> usize {
+        self.attributes.iter().map(|attribute| attribute.size()).sum()
+    }
+
+    ///
+    /// The number of bytes `vertex_count` vertices take up.
+    ///
+    pub fn size_of_vertices(&self, vertex_count: usize) -> usize {
+        vertex_count * self.stride()
+    }
+
+    ///
+    /// Point the attributes of `vao` at the vertices in `vbo`, and enable them.
+    ///
+    pub fn apply(&self, vao: GLuint, vbo: GLuint) {
+        self.apply_at(vao, vbo, 0);
+    }
+
+    ///
+    /// Point the attributes of `vao` at vertices starting `base_offset` bytes into
+    /// `vbo`, and enable them. This is for buffers that hold their vertices at a
+    /// different place from one frame to the next.
+    ///
+    pub fn apply_at(&self, vao: GLuint, vbo: GLuint, base_offset: usize) {
+        let stride = self.stride() as GLint;
+        unsafe {
+            gl::BindVertexArray(vao);
+            gl::BindBuffer(gl::ARRAY_BUFFER, vbo);
+            for attribute in self.attributes.iter() {
+                let normalized = if attribute.normalized { gl::TRUE } else { gl::FALSE };
+                gl_check!(gl::VertexAttribPointer(
+                    attribute.location, attribute.components, attribute.gl_type, normalized,
+                    stride, (base_offset + attribute.offset) as *const GLvoid
+                ));
+                gl::EnableVertexAttribArray(attribute.location);
+            }
+        }
+    }
+
+    ///
+    /// Copy `vertex_count` vertices from `data` into a new vertex buffer with
+    /// `usage`, and point the attributes of `vao` at it. Returns the new buffer.
+    ///
+    /// Panics if `data` is not exactly the size of `vertex_count` vertices in this
+    /// layout, which catches uploading one attribute's data into another's buffer.
+    ///
+    pub fn upload_with_usage<T: Copy>(&self, vao: GLuint, data: &[T], vertex_count: usize, usage: GLenum) -> GLuint {
+        let size = data.len() * mem::size_of::<T>();
+        assert_eq!(
+            size, self.size_of_vertices(vertex_count),
+            "ERROR: the vertex data is {} bytes, but {} vertices of {} bytes each need {} bytes",
+            size, vertex_count, self.stride(), self.size_of_vertices(vertex_count)
+        );
+
+        let mut vbo = 0;
+        unsafe {
+            gl::GenBuffers(1, &mut vbo);
+            gl::BindBuffer(gl::ARRAY_BUFFER, vbo);
+            gl_check!(gl::BufferData(gl::ARRAY_BUFFER, size as GLsizeiptr, data.as_ptr() as *const GLvoid, usage));
+        }
+        assert!(vbo > 0);
+        self.apply(vao, vbo);
+
+        vbo
+    }
+
+    ///
+    /// Copy `vertex_count` vertices from `data` into a new static vertex buffer, and
+    /// point the attributes of `vao` at it. Returns the new buffer.
+    ///
+    pub fn upload<T: Copy>(&self, vao: GLuint, data: &[T], vertex_count: usize) -> GLuint {
+        self.upload_with_usage(vao, data, vertex_count, gl::STATIC_DRAW)
+    }
+}
+
+mod vertex_layout_tests {
+    use super::VertexLayout;
+    use gl;
+
+    #[test]
+    fn test_a_single_attribute_is_tightly_packed() {
+        let layout = VertexLayout::new().floats(2, 2);
+
+        assert_eq!(layout.stride(), 8);
+        assert_eq!(layout.attributes()[0].location, 2);
+        assert_eq!(layout.attributes()[0].offset, 0);
+        assert_eq!(layout.size_of_vertices(6), 48);
+    }
+
+    #[test]
+    fn test_interleaved_attributes_follow_each_other() {
+        let layout = VertexLayout::new().floats(0, 3).floats(1, 3).floats(2, 2);
+        let offsets: Vec<usize> = layout.attributes().iter().map(|attribute| attribute.offset).collect();
+
+        assert_eq!(offsets, vec![0, 12, 24]);
+        assert_eq!(layout.stride(), 32);
+    }
+
+    #[test]
+    fn test_attribute_types_set_their_size() {
+        let layout = VertexLayout::new()
+            .floats(0, 2)
+            .attribute(1, 4, gl::UNSIGNED_BYTE, true)
+            .attribute(2, 2, gl::SHORT, false);
+
+        assert_eq!(layout.attributes()[1].size(), 4);
+        assert!(layout.attributes()[1].normalized);
+        assert_eq!(layout.attributes()[2].offset, 12);
+        assert_eq!(layout.stride(), 16);
+    }
+
+    #[test]
+    fn test_half_floats_take_two_bytes_each() {
+        let layout = VertexLayout::new().floats(0, 3).half_floats(2, 2);
+
+        assert_eq!(layout.attributes()[1].gl_type, gl::HALF_FLOAT);
+        assert_eq!(layout.attributes()[1].size(), 4);
+        assert_eq!(layout.stride(), 16);
+    }
+
+    #[test]
+    fn test_packed_attributes_fit_in_four_bytes() {
+        let layout = VertexLayout::new().packed_snorm(1).half_floats(2, 2).packed_snorm(3);
+        let offsets: Vec<usize> = layout.attributes().iter().map(|attribute| attribute.offset).collect();
+
+        assert_eq!(layout.attributes()[0].components, 4);
+        assert!(layout.attributes()[0].normalized);
+        assert_eq!(offsets, vec![0, 4, 8]);
+        assert_eq!(layout.stride(), 12);
+    }
+
+    #[test]
+    fn test_an_empty_layout_has_no_size() {
+        let layout = VertexLayout::new();
+
+        assert_eq!(layout.stride(), 0);
+        assert!(layout.attributes().is_empty());
+    }
+}
@@ -0,0 +1,204 @@
+use gl;
+use gl::types::{GLint, GLuint};
+
+use colour::Colour;
+use fullscreen_pass::FullscreenPass;
+use gl_utils::uniform_location;
+
+
+///
+/// The command line option that overrides a demo's background, as in
+/// `--background #336699` for a solid colour or `--background #88aadd/#223344`
+/// for a gradient from the top of the window to the bottom.
+///
+pub const BACKGROUND_OPTION: &str = "--background";
+
+
+///
+/// What the window is cleared to before the scene is drawn.
+///
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum BackgroundStyle {
+    Solid(Colour),
+    ///
+    /// A vertical gradient, blending from `top` at the top of the viewport to
+    /// `bottom` at the bottom.
+    ///
+    Gradient { top: Colour, bottom: Colour },
+}
+
+///
+/// Parse a colour given as `#rrggbb` in hex, or as three numbers from 0 to 1
+/// separated by commas, as in `0.2,0.2,0.2`.
+///
+pub fn parse_colour(text: &str) -> Result<Colour, String> {
+    let text = text.trim();
+    if text.starts_with('#') {
+        let hex = &text[1..];
+        if hex.len() != 6 {
+            return Err(format!("ERROR: colour {} is not in the form #rrggbb", text));
+        }
+        let mut channels = [0; 3];
+        for i in 0..3 {
+            channels[i] = match u8::from_str_radix(&hex[2 * i..2 * i + 2], 16) {
+                Ok(val) => val,
+                Err(_) => return Err(format!("ERROR: colour {} is not in the form #rrggbb", text)),
+            };
+        }
+
+        return Ok(Colour::from_rgb8(channels[0], channels[1], channels[2]));
+    }
+
+    let parts: Vec<&str> = text.split(',').collect();
+    if parts.len() != 3 {
+        return Err(format!("ERROR: colour {} needs three channels", text));
+    }
+    let mut colour = [0.0; 3];
+    for i in 0..3 {
+        colour[i] = match parts[i].trim().parse::<f32>() {
+            Ok(val) if val >= 0.0 && val <= 1.0 => val,
+            _ => return Err(format!("ERROR: colour {} has a channel outside 0 to 1", text)),
+        };
+    }
+
+    Ok(Colour::from(colour))
+}
+
+///
+/// Parse a background given as one colour, or as two colours separated by a `/`
+/// for a gradient from top to bottom.
+///
+pub fn parse_background(text: &str) -> Result<BackgroundStyle, String> {
+    let parts: Vec<&str> = text.split('/').collect();
+    match parts.len() {
+        1 => match parse_colour(parts[0]) {
+            Ok(colour) => Ok(BackgroundStyle::Solid(colour)),
+            Err(e) => Err(e),
+        },
+        2 => {
+            let top = match parse_colour(parts[0]) {
+                Ok(val) => val,
+                Err(e) => return Err(e),
+            };
+            let bottom = match parse_colour(parts[1]) {
+                Ok(val) => val,
+                Err(e) => return Err(e),
+            };
+
+            Ok(BackgroundStyle::Gradient { top: top, bottom: bottom })
+        }
+        _ => Err(format!("ERROR: background {} has more than two colours", text)),
+    }
+}
+
+///
+/// Find the background given on the command line after `BACKGROUND_OPTION`, or
+/// use `default` if there isn't one.
+///
+pub fn background_from_args<I>(args: I, default: BackgroundStyle) -> Result<BackgroundStyle, String>
+    where I: IntoIterator<Item = String> {
+
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        if arg == BACKGROUND_OPTION {
+            return match args.next() {
+                Some(value) => parse_background(&value),
+                None => Err(format!("ERROR: {} needs a colour after it", BACKGROUND_OPTION)),
+            };
+        }
+    }
+
+    Ok(default)
+}
+
+///
+/// Clears the window to a solid colour, or to a vertical gradient drawn as a full
+/// screen pass.
+///
+pub struct Background {
+    sp: GLuint,
+    pass: FullscreenPass,
+    top_location: GLint,
+    bottom_location: GLint,
+    pub style: BackgroundStyle,
+}
+
+impl Background {
+    pub fn new(sp: GLuint, style: BackgroundStyle) -> Background {
+        let location = |name: &str| {
+            let location = uniform_location(sp, name);
+            assert!(location > -1);
+            location
+        };
+
+        Background {
+            sp: sp,
+            pass: FullscreenPass::new(),
+            top_location: location("top_colour"),
+            bottom_location: location("bottom_colour"),
+            style: style,
+        }
+    }
+
+    ///
+    /// Clear the colour and depth buffers of the viewport, and draw the gradient
+    /// over it if the background has one.
+    ///
+    pub fn clear(&self) {
+        match self.style {
+            BackgroundStyle::Solid(colour) => unsafe {
+                gl::ClearColor(colour.r, colour.g, colour.b, colour.a);
+                gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
+            },
+            BackgroundStyle::Gradient { top, bottom } => {
+                unsafe {
+                    gl::Clear(gl::DEPTH_BUFFER_BIT);
+                    gl::UseProgram(self.sp);
+                    gl_check!(gl::Uniform3f(self.top_location, top.r, top.g, top.b));
+                    gl_check!(gl::Uniform3f(self.bottom_location, bottom.r, bottom.g, bottom.b));
+                }
+                self.pass.draw(self.sp, &[]);
+            }
+        }
+    }
+}
+
+mod background_tests {
+    use super::{background_from_args, parse_background, parse_colour, BackgroundStyle};
+    use colour::Colour;
+
+    fn args(text: &str) -> Vec<String> {
+        text.split_whitespace().map(String::from).collect()
+    }
+
+    #[test]
+    fn test_colours_parse_from_hex_and_from_numbers() {
+        assert_eq!(parse_colour("#ff0080").unwrap(), Colour::new(1.0, 0.0, 128.0 / 255.0));
+        assert_eq!(parse_colour("0.2, 0.5,1").unwrap(), Colour::new(0.2, 0.5, 1.0));
+        assert!(parse_colour("#ff00").is_err());
+        assert!(parse_colour("#gg0000").is_err());
+        assert!(parse_colour("0.2,0.5").is_err());
+        assert!(parse_colour("0.2,0.5,2.0").is_err());
+    }
+
+    #[test]
+    fn test_two_colours_make_a_gradient_from_top_to_bottom() {
+        let style = parse_background("#ffffff/#000000").unwrap();
+
+        assert_eq!(style, BackgroundStyle::Gradient { top: Colour::WHITE, bottom: Colour::BLACK });
+        assert_eq!(parse_background("#000000").unwrap(), BackgroundStyle::Solid(Colour::BLACK));
+        assert!(parse_background("#000000/#000000/#000000").is_err());
+    }
+
+    #[test]
+    fn test_the_command_line_overrides_the_default() {
+        let default = BackgroundStyle::Solid(Colour::DARK_GREY);
+
+        assert_eq!(background_from_args(args("demo"), default).unwrap(), default);
+        assert_eq!(
+            background_from_args(args("demo --background #ffffff"), default).unwrap(),
+            BackgroundStyle::Solid(Colour::WHITE)
+        );
+        assert!(background_from_args(args("demo --background"), default).is_err());
+    }
+}
@@ -0,0 +1,12 @@
+#version 410
+
+in vec2 st;
+
+uniform vec3 top_colour;
+uniform vec3 bottom_colour;
+
+out vec4 frag_colour;
+
+void main() {
+	frag_colour = vec4 (mix (bottom_colour, top_colour, st.t), 1.0);
+}
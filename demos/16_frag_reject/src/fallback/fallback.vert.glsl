@@ -0,0 +1,14 @@
+#version 410
+
+// Built-in fallback vertex shader. This is compiled in place of a demo's own
+// vertex shader when the shader file cannot be found on disk.
+layout (location = 0) in vec3 vertex_position;
+
+uniform mat4 view, proj;
+
+out vec3 position_local;
+
+void main() {
+	position_local = vertex_position;
+	gl_Position = proj * view * vec4 (vertex_position, 1.0);
+}
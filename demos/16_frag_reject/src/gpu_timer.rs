@@ -0,0 +1,115 @@
+use gl;
+use gl::types::{GLint, GLuint, GLuint64};
+
+
+///
+/// The number of query objects in each timer's ring buffer. Results are read back
+/// this many frames late, which is long enough that reading them never stalls
+/// waiting on the GPU.
+///
+pub const GPU_TIMER_QUERY_COUNT: usize = 4;
+
+
+///
+/// Measures how long the GPU spends on the commands issued between `begin` and
+/// `end` using `GL_TIME_ELAPSED` queries. Each frame uses the next query object in a
+/// ring buffer, and results are only collected once the GPU reports them available,
+/// so the CPU never waits on the GPU.
+///
+pub struct GpuTimer {
+    queries: [GLuint; GPU_TIMER_QUERY_COUNT],
+    pending: [bool; GPU_TIMER_QUERY_COUNT],
+    next: usize,
+    elapsed_ms: f64,
+}
+
+impl GpuTimer {
+    pub fn new() -> GpuTimer {
+        let mut queries = [0; GPU_TIMER_QUERY_COUNT];
+        unsafe {
+            gl::GenQueries(GPU_TIMER_QUERY_COUNT as i32, queries.as_mut_ptr());
+        }
+        for query in queries.iter() {
+            assert!(*query > 0);
+        }
+
+        GpuTimer {
+            queries: queries,
+            pending: [false; GPU_TIMER_QUERY_COUNT],
+            next: 0,
+            elapsed_ms: 0.0,
+        }
+    }
+
+    ///
+    /// Read back every finished query, oldest first, keeping the most recent result.
+    ///
+    fn collect(&mut self) {
+        for i in 0..GPU_TIMER_QUERY_COUNT {
+            let slot = (self.next + i) % GPU_TIMER_QUERY_COUNT;
+            if !self.pending[slot] {
+                continue;
+            }
+
+            let mut available: GLint = 0;
+            unsafe {
+                gl::GetQueryObjectiv(self.queries[slot], gl::QUERY_RESULT_AVAILABLE, &mut available);
+            }
+            if available == 0 {
+                // Later queries cannot have finished before this one.
+                break;
+            }
+
+            let mut elapsed_ns: GLuint64 = 0;
+            unsafe {
+                gl::GetQueryObjectui64v(self.queries[slot], gl::QUERY_RESULT, &mut elapsed_ns);
+            }
+            self.elapsed_ms = elapsed_ns as f64 / 1_000_000.0;
+            self.pending[slot] = false;
+        }
+    }
+
+    ///
+    /// Start timing. Every `begin` must be matched by an `end` before another timer
+    /// begins, since only one `GL_TIME_ELAPSED` query can be active at a time.
+    ///
+    pub fn begin(&mut self) {
+        self.collect();
+        if self.pending[self.next] {
+            // The GPU is more than a whole ring buffer behind. Drop the oldest
+            // result rather than waiting for it.
+            self.pending[self.next] = false;
+        }
+
+        unsafe {
+            gl::BeginQuery(gl::TIME_ELAPSED, self.queries[self.next]);
+        }
+    }
+
+    ///
+    /// Stop timing.
+    ///
+    pub fn end(&mut self) {
+        unsafe {
+            gl::EndQuery(gl::TIME_ELAPSED);
+        }
+        self.pending[self.next] = true;
+        self.next = (self.next + 1) % GPU_TIMER_QUERY_COUNT;
+    }
+
+    ///
+    /// The most recent GPU time in milliseconds. This lags a few frames behind.
+    ///
+    pub fn elapsed_ms(&mut self) -> f64 {
+        self.collect();
+        self.elapsed_ms
+    }
+}
+
+impl Drop for GpuTimer {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteQueries(GPU_TIMER_QUERY_COUNT as i32, self.queries.as_ptr());
+        }
+    }
+}
@@ -2,38 +2,66 @@ extern crate gl;
 extern crate glfw;
 extern crate chrono;
 extern crate stb_image;
+#[cfg(feature = "glam")]
+extern crate glam;
+#[cfg(feature = "cgmath")]
+extern crate cgmath;
 
-#[macro_use] 
-extern crate scan_fmt;
-
+#[macro_use]
 mod gl_utils;
 mod graphics_math;
 mod obj_parser;
+mod colour;
+mod assets;
+mod shader_include;
+mod spirv;
+mod program_cache;
 mod logger;
+mod mask;
+mod overlay;
+mod streaming_buffer;
+mod perf_stats;
+mod gpu_timer;
+mod vertex_layout;
+mod background;
+mod fullscreen_pass;
+// The time controls are shared with the other demos that have them.
+#[path = "../../shared/frame_clock.rs"]
+mod frame_clock;
 
 
 use glfw::{Action, Context, Key};
-use gl::types::{GLfloat, GLsizeiptr, GLvoid, GLuint};
-use stb_image::image;
+use gl::types::{GLfloat, GLint, GLvoid, GLuint};
 use stb_image::image::LoadResult;
 
-use std::mem;
-use std::ptr;
+use std::env;
 use std::process;
+use std::time::Instant;
 
 use gl_utils::*;
 
 use graphics_math as math;
 use math::Mat4;
+use overlay::Overlay;
+use perf_stats::{PerfStats, PERF_STATS_HISTORY};
+use gpu_timer::GpuTimer;
+use vertex_layout::VertexLayout;
+use background::{background_from_args, Background, BackgroundStyle};
+use colour::Colour;
+use fullscreen_pass::FULLSCREEN_VS_FILE;
+use frame_clock::FrameClock;
 
 const GL_LOG_FILE: &str = "gl.log";
 const VERTEX_SHADER_FILE: &str = "src/test.vert.glsl";
 const FRAGMENT_SHADER_FILE: &str = "src/test.frag.glsl";
+const BACKGROUND_FS_FILE: &str = "src/background_fs.glsl";
 const TEXTURE_FILE0: &str = "src/skulluvmap.png";
-
-const COLOR_HOT_PINK: [f32; 3] = [
-    255 as f32 / 255 as f32, 20 as f32 / 255 as f32, 147 as f32 / 255 as f32
-];
+const MASK_TEXTURE_SIZE: usize = 256;
+// The starting discard threshold, and how far each key press moves it.
+const DEFAULT_DISCARD_THRESHOLD: f32 = 0.3;
+const DISCARD_THRESHOLD_STEP: f32 = 0.05;
+// Where the second quad sits, behind and to one side of the first.
+const BACK_QUAD_OFFSET: [f32; 3] = [0.3, 0.2, -0.6];
 
 const GL_TEXTURE_MAX_ANISOTROPY_EXT: u32 = 0x84FE;
 const GL_MAX_TEXTURE_MAX_ANISOTROPY_EXT: u32 = 0x84FF;
@@ -41,7 +69,7 @@ const GL_MAX_TEXTURE_MAX_ANISOTROPY_EXT: u32 = 0x84FF;
 
 fn load_texture(file_name: &str, tex: &mut GLuint) -> bool {
     let force_channels = 4;
-    let mut image_data = match image::load_with_depth(file_name, force_channels, false) {
+    let mut image_data = match assets::load_image_with_depth(file_name, force_channels) {
         LoadResult::ImageU8(image_data) => image_data,
         LoadResult::Error(_) => {
             eprintln!("ERROR: could not load {}", file_name);
@@ -98,6 +126,26 @@ fn load_texture(file_name: &str, tex: &mut GLuint) -> bool {
     return true;
 }
 
+fn get_uniform_location(sp: GLuint, name: &str) -> GLint {
+    let location = uniform_location(sp, name);
+    assert!(location > -1);
+
+    location
+}
+
+///
+/// Switch between rejecting masked fragments with `discard` and fading them out
+/// with alpha blending, returning a description of the approach for the log.
+///
+fn set_reject_mode(sp: GLuint, location: GLint, use_blending: bool) -> &'static str {
+    unsafe {
+        gl::UseProgram(sp);
+        gl::Uniform1i(location, use_blending as GLint);
+    }
+
+    if use_blending { "alpha blend" } else { "discard" }
+}
+
 fn main() {
     let logger = restart_gl_log(GL_LOG_FILE);
     // Start a GL context and O/S window using the GLFW helper library.
@@ -128,40 +176,15 @@ fn main() {
         0.0, 0.0, 1.0, 0.0, 1.0, 1.0, 1.0, 1.0, 0.0, 1.0, 0.0, 0.0
     ];
 
-    let mut points_vbo = 0;
-    unsafe {
-        gl::GenBuffers(1, &mut points_vbo);
-        gl::BindBuffer(gl::ARRAY_BUFFER, points_vbo);
-        gl::BufferData(
-            gl::ARRAY_BUFFER, (18 * mem::size_of::<GLfloat>()) as GLsizeiptr, 
-            points.as_ptr() as *const GLvoid, gl::STATIC_DRAW
-        );
-    }
-    assert!(points_vbo > 0);
-
-    let mut texcoords_vbo = 0;
-    unsafe {
-        gl::GenBuffers(1, &mut texcoords_vbo);
-        gl::BindBuffer(gl::ARRAY_BUFFER, texcoords_vbo);
-        gl::BufferData(
-            gl::ARRAY_BUFFER, (12 * mem::size_of::<GLfloat>()) as GLsizeiptr, 
-            texcoords.as_ptr() as *const GLvoid, gl::STATIC_DRAW
-        );
-    }
-    assert!(texcoords_vbo > 0);
-
+    let point_count = points.len() / 3;
     let mut vao = 0;
     unsafe {
         gl::GenVertexArrays(1, &mut vao);
-        gl::BindVertexArray(vao);
-        gl::BindBuffer(gl::ARRAY_BUFFER, points_vbo);
-        gl::VertexAttribPointer(0, 3, gl::FLOAT, gl::FALSE, 0, ptr::null());
-        gl::BindBuffer(gl::ARRAY_BUFFER, texcoords_vbo);
-        gl::VertexAttribPointer(1, 2, gl::FLOAT, gl::FALSE, 0, ptr::null());
-        gl::EnableVertexAttribArray(0);
-        gl::EnableVertexAttribArray(1);
     }
     assert!(vao > 0);
+    VertexLayout::new().floats(0, 3).upload(vao, &points, point_count);
+    VertexLayout::new().floats(1, 2).upload(vao, &texcoords, point_count);
+    assert!(vao > 0);
 
     let shader_programme = create_programme_from_files(&logger, VERTEX_SHADER_FILE, FRAGMENT_SHADER_FILE);
 
@@ -177,34 +200,79 @@ fn main() {
     let cam_yaw_speed: GLfloat = 10.0;        // 10 degrees per second
     let mut cam_pos: [GLfloat; 3] = [0.0, 0.0, 2.0]; // don't start at zero, or we will be too close
     let mut cam_yaw: GLfloat = 0.0;               // y-rotation in degrees
-    let mut mat_trans = Mat4::identity().translate(&math::vec3((-cam_pos[0], -cam_pos[1], -cam_pos[2])));
-    let mut mat_rot = Mat4::identity().rotate_y_deg(-cam_yaw);
+    let mut mat_trans = Mat4::from_translation(&math::vec3((-cam_pos[0], -cam_pos[1], -cam_pos[2])));
+    let mut mat_rot = Mat4::from_rotation_y_deg(-cam_yaw);
     let mut view_mat = mat_rot * mat_trans;
 
-    let view_mat_location = unsafe {
-        gl::GetUniformLocation(shader_programme, "view".as_ptr() as *const i8)
-    };
+    let view_mat_location = uniform_location(shader_programme, "view");
     assert!(view_mat_location > -1);
     unsafe {
-        gl::UniformMatrix4fv(view_mat_location, 1, gl::FALSE, view_mat.as_ptr());
         gl::UseProgram(shader_programme);
+        gl::UniformMatrix4fv(view_mat_location, 1, gl::FALSE, view_mat.as_ptr());
     }
-    let proj_mat_location = unsafe { 
-        gl::GetUniformLocation(shader_programme, "proj".as_ptr() as *const i8)
-    };
+    let proj_mat_location = uniform_location(shader_programme, "proj");
     assert!(proj_mat_location > -1);
     unsafe {
-        gl::UniformMatrix4fv(proj_mat_location, 1, gl::FALSE, proj_mat.as_ptr());
         gl::UseProgram(shader_programme);
+        gl::UniformMatrix4fv(proj_mat_location, 1, gl::FALSE, proj_mat.as_ptr());
     }
 
     // Load texture.
     let mut tex = 0;
     load_texture(TEXTURE_FILE0, &mut tex);
     assert!(tex > 0);
+    let mask_tex = mask::create_mask_texture(MASK_TEXTURE_SIZE);
+    assert!(mask_tex > 0);
+
+    let model_mat_location = get_uniform_location(shader_programme, "model");
+    let time_location = get_uniform_location(shader_programme, "time");
+    let discard_threshold_location = get_uniform_location(shader_programme, "discard_threshold");
+    let use_blending_location = get_uniform_location(shader_programme, "use_blending");
+    unsafe {
+        gl::UseProgram(shader_programme);
+        gl::Uniform1i(get_uniform_location(shader_programme, "basic_texture"), 0);
+        gl::Uniform1i(get_uniform_location(shader_programme, "mask_texture"), 1);
+    }
+
+    // The quads are drawn front to back, which is the wrong order for alpha
+    // blending: the see-through parts of the front quad still hide the back one.
+    let quad_model_mats = [
+        Mat4::identity(),
+        Mat4::from_translation(&math::vec3((BACK_QUAD_OFFSET[0], BACK_QUAD_OFFSET[1], BACK_QUAD_OFFSET[2]))),
+    ];
+    let mut discard_threshold = DEFAULT_DISCARD_THRESHOLD;
+    let mut use_blending = false;
+    let mut reject_mode_text = set_reject_mode(shader_programme, use_blending_location, use_blending);
+    unsafe {
+        gl::Uniform1f(discard_threshold_location, discard_threshold);
+    }
+    logger.log("keys: [ and ] lower and raise the discard threshold, B switches between discard and alpha blending,");
+    logger.log("      F3 toggles the performance HUD");
+    logger.log("      F6 pauses time, F7 and F8 slow it down and speed it up, F10 steps a frame while paused");
+
+    // frame time statistics and the GPU time spent on the quads, drawn over the
+    // scene so the cost of each approach can be compared. F3 toggles them on and off.
+    let mut overlay = Overlay::new(&logger);
+    let mut perf_stats = PerfStats::new(PERF_STATS_HISTORY);
+    let mut show_perf_stats = true;
+    let mut quads_timer = GpuTimer::new();
+    // the simulation time the shader animates with, which the time controls
+    // pause, slow down, speed up, and step
+    let mut clock = FrameClock::new();
+
+    // hot pink by default, so anything that is not drawn stands out.
+    let background_style = match background_from_args(env::args(), BackgroundStyle::Solid(Colour::HOT_PINK)) {
+        Ok(val) => val,
+        Err(e) => {
+            logger.log_err(&e);
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+    };
+    let background_programme = create_programme_from_files(&logger, FULLSCREEN_VS_FILE, BACKGROUND_FS_FILE);
+    let background = Background::new(background_programme, background_style);
 
     unsafe {
-        gl::ClearColor(COLOR_HOT_PINK[0], COLOR_HOT_PINK[1], COLOR_HOT_PINK[2], 1.0);
         // Cull face.
         gl::Enable(gl::CULL_FACE);
         // Cull back face.
@@ -214,25 +282,92 @@ fn main() {
     }
 
     while !context.window.should_close() {
+        let frame_start = Instant::now();
         let current_seconds = context.glfw.get_time();
         let elapsed_seconds = current_seconds - context.elapsed_time_seconds;
         context.elapsed_time_seconds = current_seconds;
+        clock.tick(elapsed_seconds);
 
         update_fps_counter(&mut context);
         unsafe {
-            // wipe the drawing surface clear
-            gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
             gl::Viewport(0, 0, context.width as i32, context.height as i32);
+        }
+        // wipe the drawing surface clear
+        background.clear();
+        unsafe {
 
+            quads_timer.begin();
+            if use_blending {
+                gl::Enable(gl::BLEND);
+                gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
+            }
+            gl::ActiveTexture(gl::TEXTURE0);
+            gl::BindTexture(gl::TEXTURE_2D, tex);
+            gl::ActiveTexture(gl::TEXTURE1);
+            gl::BindTexture(gl::TEXTURE_2D, mask_tex);
             gl::UseProgram(shader_programme);
+            gl::Uniform1f(time_location, clock.seconds() as f32);
             gl::BindVertexArray(vao);
-            // draw points 0-3 from the currently bound VAO with current in-use shader
-            gl::DrawArrays(gl::TRIANGLES, 0, 6);
+            for model_mat in quad_model_mats.iter() {
+                gl::UniformMatrix4fv(model_mat_location, 1, gl::FALSE, model_mat.as_ptr());
+                // draw points 0-3 from the currently bound VAO with current in-use shader
+                gl::DrawArrays(gl::TRIANGLES, 0, 6);
+                perf_stats.count_draw(2);
+            }
+            gl::Disable(gl::BLEND);
+            quads_timer.end();
             // update other events like input handling
         }
 
+        perf_stats.set_gpu_pass_ms("quads", quads_timer.elapsed_ms());
+        if show_perf_stats {
+            let hud_bottom = perf_stats.draw_hud(&mut overlay, 10.0, 10.0);
+            let reject_text = format!(
+                "{}  threshold {:.2}  {}", reject_mode_text, discard_threshold, clock.describe()
+            );
+            overlay.text(16.0, hud_bottom + 6.0, 2.0, [1.0, 1.0, 1.0, 1.0], &reject_text);
+            overlay.draw(context.width, context.height);
+        }
+
         context.glfw.poll_events();
 
+        let mut threshold_changed = false;
+        let mut reject_mode_changed = false;
+        for (_, event) in glfw::flush_messages(&context.events) {
+            match event {
+                glfw::WindowEvent::Key(Key::LeftBracket, _, Action::Press, _) => {
+                    discard_threshold = f32::max(discard_threshold - DISCARD_THRESHOLD_STEP, 0.0);
+                    threshold_changed = true;
+                }
+                glfw::WindowEvent::Key(Key::RightBracket, _, Action::Press, _) => {
+                    discard_threshold = f32::min(discard_threshold + DISCARD_THRESHOLD_STEP, 1.0);
+                    threshold_changed = true;
+                }
+                glfw::WindowEvent::Key(Key::B, _, Action::Press, _) => {
+                    use_blending = !use_blending;
+                    reject_mode_changed = true;
+                }
+                glfw::WindowEvent::Key(Key::F3, _, Action::Press, _) => {
+                    show_perf_stats = !show_perf_stats;
+                }
+                glfw::WindowEvent::Key(key, _, Action::Press, _) => {
+                    clock.handle_key(key);
+                }
+                _ => {}
+            }
+        }
+        if threshold_changed {
+            unsafe {
+                gl::UseProgram(shader_programme);
+                gl::Uniform1f(discard_threshold_location, discard_threshold);
+            }
+            logger.log(&format!("discard threshold: {:.2}", discard_threshold));
+        }
+        if reject_mode_changed {
+            reject_mode_text = set_reject_mode(shader_programme, use_blending_location, use_blending);
+            logger.log(&format!("rejecting fragments with: {}", reject_mode_text));
+        }
+
         // control keys
         let mut cam_moved = false;
         match context.window.get_key(Key::A) {
@@ -293,10 +428,11 @@ fn main() {
         }
         // update view matrix
         if cam_moved {
-            mat_trans = Mat4::identity().translate(&math::vec3((-cam_pos[0], -cam_pos[1], -cam_pos[2]))); // cam translation
-            mat_rot = Mat4::identity().rotate_y_deg(-cam_yaw);
+            mat_trans = Mat4::from_translation(&math::vec3((-cam_pos[0], -cam_pos[1], -cam_pos[2]))); // cam translation
+            mat_rot = Mat4::from_rotation_y_deg(-cam_yaw);
             view_mat = mat_rot * mat_trans;
             unsafe {
+                gl::UseProgram(shader_programme);
                 gl::UniformMatrix4fv(view_mat_location, 1, gl::FALSE, view_mat.as_ptr());
             }
         }
@@ -307,6 +443,7 @@ fn main() {
             }
             _ => {}
         }
+        perf_stats.end_frame(perf_stats::duration_to_ms(frame_start.elapsed()));
         // Put the stuff we've been drawing onto the display.
         context.window.swap_buffers();
     }
@@ -0,0 +1,110 @@
+use gl;
+use gl::types::{GLuint, GLvoid};
+
+
+// The number of noise cells across the mask; the mask tiles seamlessly.
+const MASK_CELLS: usize = 8;
+
+
+///
+/// A pseudo random value between zero and one for a lattice point of the noise.
+///
+fn lattice_value(x: usize, y: usize) -> f32 {
+    let mut h = (x as u32).wrapping_mul(374761393) ^ (y as u32).wrapping_mul(668265263);
+    h = (h ^ (h >> 13)).wrapping_mul(1274126177);
+    h ^= h >> 16;
+
+    (h & 0xFFFF) as f32 / 65535.0
+}
+
+fn smooth(t: f32) -> f32 {
+    t * t * (3.0 - 2.0 * t)
+}
+
+///
+/// Generate a `size` by `size` single channel mask of smoothly interpolated value
+/// noise, one byte per texel. The lattice wraps around, so the mask can be scrolled
+/// across a surface without seams.
+///
+pub fn mask_pattern(size: usize) -> Vec<u8> {
+    let mut data = Vec::with_capacity(size * size);
+    let cell_size = size as f32 / MASK_CELLS as f32;
+    for row in 0..size {
+        for col in 0..size {
+            let x = col as f32 / cell_size;
+            let y = row as f32 / cell_size;
+            let (x0, y0) = (x.floor() as usize, y.floor() as usize);
+            let (x1, y1) = ((x0 + 1) % MASK_CELLS, (y0 + 1) % MASK_CELLS);
+            let (tx, ty) = (smooth(x - x0 as f32), smooth(y - y0 as f32));
+            let top = lattice_value(x0, y0) * (1.0 - tx) + lattice_value(x1, y0) * tx;
+            let bottom = lattice_value(x0, y1) * (1.0 - tx) + lattice_value(x1, y1) * tx;
+            let value = top * (1.0 - ty) + bottom * ty;
+            data.push((value * 255.0).round() as u8);
+        }
+    }
+
+    data
+}
+
+///
+/// Upload a mask pattern into a repeating single channel texture.
+///
+pub fn create_mask_texture(size: usize) -> GLuint {
+    let data = mask_pattern(size);
+    let mut tex = 0;
+    unsafe {
+        gl::GenTextures(1, &mut tex);
+        gl::BindTexture(gl::TEXTURE_2D, tex);
+        gl::PixelStorei(gl::UNPACK_ALIGNMENT, 1);
+        gl::TexImage2D(
+            gl::TEXTURE_2D, 0, gl::R8 as i32, size as i32, size as i32, 0,
+            gl::RED, gl::UNSIGNED_BYTE, data.as_ptr() as *const GLvoid
+        );
+        gl::PixelStorei(gl::UNPACK_ALIGNMENT, 4);
+        gl::GenerateMipmap(gl::TEXTURE_2D);
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::REPEAT as i32);
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::REPEAT as i32);
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::LINEAR as i32);
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::LINEAR_MIPMAP_LINEAR as i32);
+    }
+
+    tex
+}
+
+mod mask_tests {
+    use super::mask_pattern;
+
+    #[test]
+    fn test_mask_pattern_has_one_byte_per_texel() {
+        assert_eq!(mask_pattern(64).len(), 64 * 64);
+    }
+
+    #[test]
+    fn test_mask_pattern_tiles_without_seams() {
+        // Stepping across the edge of the mask should change it no more than
+        // stepping between neighbouring texels inside it.
+        let size = 64;
+        let mask = mask_pattern(size);
+        let step = |a: usize, b: usize| (mask[a] as i32 - mask[b] as i32).abs();
+        let mut largest_step = 0;
+        for row in 0..size {
+            for col in 0..size - 1 {
+                largest_step = i32::max(largest_step, step(row * size + col, row * size + col + 1));
+                largest_step = i32::max(largest_step, step(col * size + row, (col + 1) * size + row));
+            }
+        }
+        for i in 0..size {
+            assert!(step(i * size, i * size + size - 1) <= largest_step);
+            assert!(step(i, (size - 1) * size + i) <= largest_step);
+        }
+    }
+
+    #[test]
+    fn test_mask_pattern_spreads_across_thresholds() {
+        let mask = mask_pattern(64);
+        let below = mask.iter().filter(|&&value| value < 128).count();
+
+        assert!(below > mask.len() / 5);
+        assert!(below < 4 * mask.len() / 5);
+    }
+}
@@ -0,0 +1,20 @@
+#version 410
+
+// Screen-space overlay for text and flat rectangles. Vertex points are given in
+// pixels with the origin at the top-left corner of the viewport.
+layout (location = 0) in vec2 vp;
+layout (location = 1) in vec2 vt;
+layout (location = 2) in vec4 vc;
+
+uniform vec2 viewport_size;
+
+out vec2 st;
+out vec4 colour;
+
+void main() {
+	st = vt;
+	colour = vc;
+	gl_Position = vec4 (
+		vp.x / viewport_size.x * 2.0 - 1.0, 1.0 - vp.y / viewport_size.y * 2.0, 0.0, 1.0
+	);
+}
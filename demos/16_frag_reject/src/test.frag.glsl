@@ -2,12 +2,40 @@
 
 in vec2 texture_coordinates;
 uniform sampler2D basic_texture;
+// a noise mask scrolled across the surface over time
+uniform sampler2D mask_texture;
+uniform float time;
+// fragments whose mask value is below the threshold are rejected
+uniform float discard_threshold;
+// 0 rejects fragments with discard, 1 fades them out with alpha blending
+uniform int use_blending;
 out vec4 frag_colour;
 
+// how fast the mask moves across the surface, in texture coordinates per second
+const vec2 mask_scroll = vec2 (0.05, 0.02);
+// the width of the faded edge when alpha blending
+const float blend_edge = 0.05;
+
 void main() {
 	vec4 texel = texture (basic_texture, texture_coordinates);
 	frag_colour = texel;
-	if (texel.r > 0.2 && texel.g + texel.b < 1.0) {
+
+	// the first mask is the colour key on the texture itself, which never changes
+	bool colour_keyed = texel.r > 0.2 && texel.g + texel.b < 1.0;
+	// the second mask channel comes from the noise texture and moves with time
+	float mask = texture (mask_texture, texture_coordinates + mask_scroll * time).r;
+	if (colour_keyed) {
+		mask = 0.0;
+	}
+
+	if (use_blending == 1) {
+		/* blending keeps the fragment, so it still writes depth. anything drawn
+			 behind it afterwards is hidden, even where it is see-through */
+		frag_colour.a = smoothstep (discard_threshold - blend_edge, discard_threshold + blend_edge, mask);
+		if (colour_keyed) {
+			frag_colour.a = 0.0;
+		}
+	} else if (mask < discard_threshold || colour_keyed) {
 		discard;
 	}
 }
@@ -2,41 +2,57 @@ extern crate gl;
 extern crate glfw;
 extern crate chrono;
 extern crate stb_image;
+#[cfg(feature = "glam")]
+extern crate glam;
+#[cfg(feature = "cgmath")]
+extern crate cgmath;
 
-#[macro_use] 
-extern crate scan_fmt;
-
+#[macro_use]
 mod gl_utils;
 mod graphics_math;
 mod obj_parser;
+mod colour;
+mod assets;
+mod shader_include;
+mod spirv;
+mod program_cache;
 mod logger;
+mod vertex_layout;
+mod camera;
+mod spotlight;
 
 
 use glfw::{Action, Context, Key};
-use gl::types::{GLfloat, GLsizeiptr, GLvoid, GLuint};
-use stb_image::image;
+use gl::types::{GLfloat, GLvoid, GLuint};
 use stb_image::image::LoadResult;
 
-use std::mem;
-use std::ptr;
 use std::process;
 
 use gl_utils::*;
 
 use graphics_math as math;
-use math::Mat4;
+use math::{Mat4, Versor};
+use vertex_layout::VertexLayout;
+use camera::Camera;
+use spotlight::{Spotlight, SpotlightUniforms};
 
 const GL_LOG_FILE: &str = "gl.log";
 const VERTEX_SHADER_FILE: &str = "src/test.vert.glsl";
 const FRAGMENT_SHADER_FILE: &str = "src/test.frag.glsl";
 
+// Where the flashlight is held, in the camera's frame: a little below and to
+// the right of the eye, so its beam is seen from slightly off axis.
+const FLASHLIGHT_OFFSET: (GLfloat, GLfloat, GLfloat) = (0.1, -0.1, 0.0);
+const CONE_STEP_DEG: GLfloat = 2.5;
+const INTENSITY_STEP: GLfloat = 0.1;
+
 const GL_TEXTURE_MAX_ANISOTROPY_EXT: u32 = 0x84FE;
 const GL_MAX_TEXTURE_MAX_ANISOTROPY_EXT: u32 = 0x84FF;
 
 
 fn load_texture(file_name: &str, tex: &mut GLuint) -> bool {
     let force_channels = 4;
-    let mut image_data = match image::load_with_depth(file_name, force_channels, false) {
+    let mut image_data = match assets::load_image_with_depth(file_name, force_channels) {
         LoadResult::ImageU8(image_data) => image_data,
         LoadResult::Error(_) => {
             eprintln!("ERROR: could not load {}", file_name);
@@ -118,40 +134,34 @@ fn main() {
 
     let normals: [GLfloat; 9] = [0.0, 0.0, 1.0, 0.0, 0.0, 1.0, 0.0, 0.0, 1.0];
 
-    let mut points_vbo = 0;
-    unsafe {
-        gl::GenBuffers(1, &mut points_vbo);
-        gl::BindBuffer(gl::ARRAY_BUFFER, points_vbo);
-        gl::BufferData(
-            gl::ARRAY_BUFFER, (9 * mem::size_of::<GLfloat>()) as GLsizeiptr, 
-            points.as_ptr() as *const GLvoid, gl::STATIC_DRAW
-        );
-    }
-    assert!(points_vbo > 0);
-
-    let mut normals_vbo = 0;
-    unsafe {
-        gl::GenBuffers(1, &mut normals_vbo);
-        gl::BindBuffer(gl::ARRAY_BUFFER, normals_vbo);
-        gl::BufferData(
-            gl::ARRAY_BUFFER, (9 * mem::size_of::<GLfloat>()) as GLsizeiptr, 
-            normals.as_ptr() as *const GLvoid, gl::STATIC_DRAW
-        );
-    }
-    assert!(normals_vbo > 0);
-
+    let point_count = points.len() / 3;
     let mut vao = 0;
     unsafe {
         gl::GenVertexArrays(1, &mut vao);
-        gl::BindVertexArray(vao);
-        gl::BindBuffer(gl::ARRAY_BUFFER, points_vbo);
-        gl::VertexAttribPointer(0, 3, gl::FLOAT, gl::FALSE, 0, ptr::null());
-        gl::BindBuffer(gl::ARRAY_BUFFER, normals_vbo);
-        gl::VertexAttribPointer(1, 3, gl::FLOAT, gl::FALSE, 0, ptr::null());
-        gl::EnableVertexAttribArray(0);
-        gl::EnableVertexAttribArray(1);
     }
     assert!(vao > 0);
+    VertexLayout::new().floats(0, 3).upload(vao, &points, point_count);
+    VertexLayout::new().floats(1, 3).upload(vao, &normals, point_count);
+    assert!(vao > 0);
+
+    // A wall behind the triangle for the spotlight to shine on.
+    let wall_points: [GLfloat; 18] = [
+        -3.0, -3.0, -1.0, -3.0, 3.0, -1.0, 3.0, 3.0, -1.0,
+        3.0, 3.0, -1.0, 3.0, -3.0, -1.0, -3.0, -3.0, -1.0,
+    ];
+    let wall_normals: [GLfloat; 18] = [
+        0.0, 0.0, 1.0, 0.0, 0.0, 1.0, 0.0, 0.0, 1.0,
+        0.0, 0.0, 1.0, 0.0, 0.0, 1.0, 0.0, 0.0, 1.0,
+    ];
+    let wall_point_count = wall_points.len() / 3;
+    let mut wall_vao = 0;
+    unsafe {
+        gl::GenVertexArrays(1, &mut wall_vao);
+    }
+    assert!(wall_vao > 0);
+    VertexLayout::new().floats(0, 3).upload(wall_vao, &wall_points, wall_point_count);
+    VertexLayout::new().floats(1, 3).upload(wall_vao, &wall_normals, wall_point_count);
+    let wall_model_mat = Mat4::identity();
 
     let shader_programme = create_programme_from_files(&logger, VERTEX_SHADER_FILE, FRAGMENT_SHADER_FILE);
 
@@ -167,26 +177,19 @@ fn main() {
     let cam_yaw_speed: GLfloat = 10.0;        // 10 degrees per second
     let mut cam_pos: [GLfloat; 3] = [0.0, 0.0, 2.0]; // don't start at zero, or we will be too close
     let mut cam_yaw: GLfloat = 0.0;               // y-rotation in degrees
-    let mut mat_trans = Mat4::identity().translate(&math::vec3((-cam_pos[0], -cam_pos[1], -cam_pos[2])));
-    let mut mat_rot = Mat4::identity().rotate_y_deg(-cam_yaw);
-    let mut view_mat = mat_rot * mat_trans;
+    let mut camera = Camera::new(math::dvec3(math::vec3((cam_pos[0], cam_pos[1], cam_pos[2]))), Versor::from_axis_deg(cam_yaw, 0.0, 1.0, 0.0), fov);
+    let mut view_mat = camera.view_mat();
 
     let mut model_mat = Mat4::identity();
     model_mat.m[12] = 1.0;
 
-    let view_mat_location = unsafe {
-        gl::GetUniformLocation(shader_programme, "view_mat".as_ptr() as *const i8)
-    };
+    let view_mat_location = uniform_location(shader_programme, "view_mat");
     assert!(view_mat_location > -1);
 
-    let proj_mat_location = unsafe { 
-        gl::GetUniformLocation(shader_programme, "projection_mat".as_ptr() as *const i8)
-    };
+    let proj_mat_location = uniform_location(shader_programme, "projection_mat");
     assert!(proj_mat_location > -1);
 
-    let model_mat_location = unsafe {
-        gl::GetUniformLocation(shader_programme, "model_mat".as_ptr() as *const i8)
-    };
+    let model_mat_location = uniform_location(shader_programme, "model_mat");
     assert!(model_mat_location > -1);
     
     unsafe {
@@ -196,6 +199,17 @@ fn main() {
         gl::UniformMatrix4fv(model_mat_location, 1, gl::FALSE, model_mat.as_ptr());
     }
 
+    // The spotlight from the chapter, fixed in the world and shining down -z, and
+    // the flashlight mode that mounts it on the camera instead.
+    let fixed_light = Spotlight::new(math::vec3((0.0, 0.0, 2.0)), math::vec3((0.0, 0.0, -1.0)));
+    let mut light = fixed_light;
+    let mut flashlight = false;
+    let light_uniforms = SpotlightUniforms::new(shader_programme);
+    light_uniforms.upload(shader_programme, &light);
+
+    logger.log("keys: L mounts the spotlight on the camera as a flashlight, [ and ] change the cone angle,");
+    logger.log("      - and = change the light intensity; F5 saves the camera to camera.txt, and F9 restores it");
+
     unsafe {
         // Cull face.
         gl::Enable(gl::CULL_FACE);
@@ -223,13 +237,64 @@ fn main() {
 
             gl::BindVertexArray(vao);
 
-            gl::DrawArrays(gl::TRIANGLES, 0, 6);
+            gl::DrawArrays(gl::TRIANGLES, 0, point_count as i32);
+
+            gl::UniformMatrix4fv(model_mat_location, 1, gl::FALSE, wall_model_mat.as_ptr());
+            gl::BindVertexArray(wall_vao);
+            gl::DrawArrays(gl::TRIANGLES, 0, wall_point_count as i32);
         }
 
         context.glfw.poll_events();
 
-        // control keys
+        let mut light_changed = false;
         let mut cam_moved = false;
+        for (_, event) in glfw::flush_messages(&context.events) {
+            match event {
+                glfw::WindowEvent::Key(Key::L, _, Action::Press, _) => {
+                    flashlight = !flashlight;
+                    if !flashlight {
+                        light.position = fixed_light.position;
+                        light.direction = fixed_light.direction;
+                    }
+                    logger.log(if flashlight { "spotlight mounted on the camera" } else { "spotlight fixed in the world" });
+                }
+                glfw::WindowEvent::Key(Key::LeftBracket, _, Action::Press, _) => {
+                    light.change_cutoff(-CONE_STEP_DEG);
+                    light_changed = true;
+                }
+                glfw::WindowEvent::Key(Key::RightBracket, _, Action::Press, _) => {
+                    light.change_cutoff(CONE_STEP_DEG);
+                    light_changed = true;
+                }
+                glfw::WindowEvent::Key(Key::Minus, _, Action::Press, _) => {
+                    light.change_intensity(-INTENSITY_STEP);
+                    light_changed = true;
+                }
+                glfw::WindowEvent::Key(Key::Equal, _, Action::Press, _) => {
+                    light.change_intensity(INTENSITY_STEP);
+                    light_changed = true;
+                }
+                glfw::WindowEvent::Key(Key::F5, _, Action::Press, _) => {
+                    camera.save_to_camera_file();
+                }
+                glfw::WindowEvent::Key(Key::F9, _, Action::Press, _) => {
+                    if let Some(restored) = Camera::restore_from_camera_file() {
+                        // this camera only turns about the vertical axis, so a saved pitch or roll is
+                        // dropped, and the field of view stays as it is.
+                        let position = math::vec3(restored.position);
+                        cam_pos = [position.v[0], position.v[1], position.v[2]];
+                        cam_yaw = restored.yaw_deg();
+                        cam_moved = true;
+                    }
+                }
+                _ => {}
+            }
+        }
+        if light_changed {
+            logger.log(&light.describe());
+        }
+
+        // control keys
         match context.window.get_key(Key::A) {
             Action::Press | Action::Repeat => {
                 cam_pos[0] -= cam_speed * (elapsed_seconds as GLfloat);
@@ -288,14 +353,22 @@ fn main() {
         }
         // update view matrix
         if cam_moved {
-            mat_trans = Mat4::identity().translate(&math::vec3((-cam_pos[0], -cam_pos[1], -cam_pos[2]))); // cam translation
-            mat_rot = Mat4::identity().rotate_y_deg(-cam_yaw);
-            view_mat = mat_rot * mat_trans;
+            camera.position = math::dvec3(math::vec3((cam_pos[0], cam_pos[1], cam_pos[2])));
+            camera.orientation = Versor::from_axis_deg(cam_yaw, 0.0, 1.0, 0.0);
+            view_mat = camera.view_mat();
             unsafe {
                 gl::UniformMatrix4fv(view_mat_location, 1, gl::FALSE, view_mat.as_ptr());
             }
         }
 
+        // The flashlight follows the camera, so its uniforms change every frame the
+        // camera does. The shader takes them into eye space with the view matrix,
+        // where a light mounted on the camera always sits at the offset, facing -z.
+        if flashlight {
+            light.attach_to(&camera, &math::vec3(FLASHLIGHT_OFFSET));
+        }
+        light_uniforms.upload(shader_programme, &light);
+
         match context.window.get_key(Key::Escape) {
             Action::Press | Action::Repeat => {
                 context.window.set_should_close(true);
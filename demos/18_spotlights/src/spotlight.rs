@@ -0,0 +1,175 @@
+use gl;
+use gl::types::{GLint, GLuint};
+
+use camera::Camera;
+use gl_utils::uniform_location;
+use graphics_math as math;
+use math::Vec3;
+
+
+const MIN_CUTOFF_DEG: f32 = 2.0;
+const MAX_CUTOFF_DEG: f32 = 80.0;
+const MAX_INTENSITY: f32 = 4.0;
+
+
+///
+/// A spotlight: a point light that only shines inside a cone around `direction`.
+/// The light falls off linearly from full strength along the direction to
+/// nothing at `cutoff_deg` from it. The position and direction are in world
+/// space; the fragment shader moves them into eye space with the view matrix.
+///
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Spotlight {
+    pub position: Vec3,
+    pub direction: Vec3,
+    // The half angle of the cone, in degrees.
+    pub cutoff_deg: f32,
+    // Scales the diffuse and specular light.
+    pub intensity: f32,
+}
+
+impl Spotlight {
+    pub fn new(position: Vec3, direction: Vec3) -> Spotlight {
+        Spotlight {
+            position: position,
+            direction: direction,
+            cutoff_deg: 25.0,
+            intensity: 1.0,
+        }
+    }
+
+    ///
+    /// Mount the light on the camera, like a torch held at `offset` from the eye,
+    /// in the camera's own frame, and pointing wherever the camera looks. Call
+    /// it every frame the camera moves.
+    ///
+    pub fn attach_to(&mut self, camera: &Camera, offset: &Vec3) {
+        self.position = Vec3::from(camera.position) + camera.orientation.rotate_vec3(offset);
+        self.direction = camera.orientation.rotate_vec3(&math::vec3((0.0, 0.0, -1.0)));
+    }
+
+    ///
+    /// Widen or narrow the cone, keeping it between a pencil beam and a floodlight.
+    ///
+    pub fn change_cutoff(&mut self, delta_deg: f32) {
+        self.cutoff_deg = f32::min(f32::max(self.cutoff_deg + delta_deg, MIN_CUTOFF_DEG), MAX_CUTOFF_DEG);
+    }
+
+    pub fn change_intensity(&mut self, delta: f32) {
+        self.intensity = f32::min(f32::max(self.intensity + delta, 0.0), MAX_INTENSITY);
+    }
+
+    ///
+    /// The cosine of the cutoff angle, which the shader compares the dot product
+    /// of the spot direction and the direction to the fragment against.
+    ///
+    pub fn cos_cutoff(&self) -> f32 {
+        f32::cos(self.cutoff_deg * math::ONE_DEG_IN_RAD)
+    }
+
+    pub fn describe(&self) -> String {
+        format!("spotlight cone {:.0} degrees  intensity {:.2}", self.cutoff_deg, self.intensity)
+    }
+}
+
+///
+/// The locations of the spotlight's uniforms in a shader programme:
+/// `light_position_world`, `spot_direction_world`, `spot_cos_cutoff`, and
+/// `light_intensity`.
+///
+pub struct SpotlightUniforms {
+    position_loc: GLint,
+    direction_loc: GLint,
+    cos_cutoff_loc: GLint,
+    intensity_loc: GLint,
+}
+
+impl SpotlightUniforms {
+    pub fn new(sp: GLuint) -> SpotlightUniforms {
+        SpotlightUniforms {
+            position_loc: uniform_location(sp, "light_position_world"),
+            direction_loc: uniform_location(sp, "spot_direction_world"),
+            cos_cutoff_loc: uniform_location(sp, "spot_cos_cutoff"),
+            intensity_loc: uniform_location(sp, "light_intensity"),
+        }
+    }
+
+    ///
+    /// Upload the light to the shader programme, leaving it in use.
+    ///
+    pub fn upload(&self, sp: GLuint, light: &Spotlight) {
+        let direction = light.direction.normalize();
+        unsafe {
+            gl::UseProgram(sp);
+            gl::Uniform3fv(self.position_loc, 1, light.position.v.as_ptr());
+            gl::Uniform3fv(self.direction_loc, 1, direction.v.as_ptr());
+            gl::Uniform1f(self.cos_cutoff_loc, light.cos_cutoff());
+            gl::Uniform1f(self.intensity_loc, light.intensity);
+        }
+    }
+}
+
+mod spotlight_tests {
+    use super::Spotlight;
+    use camera::Camera;
+    use graphics_math as math;
+    use math::Versor;
+
+    const EPSILON: f32 = 1e-5;
+
+    fn close(a: [f32; 3], b: [f32; 3]) -> bool {
+        (0..3).all(|i| f32::abs(a[i] - b[i]) < EPSILON)
+    }
+
+    #[test]
+    fn test_a_light_on_the_camera_points_where_the_camera_looks() {
+        let camera = Camera::new(math::dvec3((1.0, 2.0, 3.0)), Versor::from_axis_deg(90.0, 0.0, 1.0, 0.0), 67.0);
+        let mut light = Spotlight::new(math::vec3((0.0, 0.0, 0.0)), math::vec3((0.0, 0.0, -1.0)));
+        light.attach_to(&camera, &math::vec3((0.0, -0.5, 0.0)));
+
+        assert!(close(light.direction.v, [-1.0, 0.0, 0.0]));
+        assert!(close(light.position.v, [1.0, 1.5, 3.0]));
+    }
+
+    #[test]
+    fn test_a_light_on_the_camera_sits_still_in_eye_space() {
+        let offset = math::vec3((0.2, -0.3, 0.0));
+        let mut light = Spotlight::new(math::vec3((0.0, 0.0, 0.0)), math::vec3((0.0, 0.0, -1.0)));
+        let cameras = [
+            Camera::new(math::dvec3((0.0, 0.0, 2.0)), Versor::from_axis_deg(0.0, 0.0, 1.0, 0.0), 67.0),
+            Camera::new(math::dvec3((-4.0, 1.0, 7.0)), Versor::from_axis_deg(35.0, 0.0, 1.0, 0.0), 67.0),
+            Camera::new(math::dvec3((3.0, -2.0, 0.5)), Versor::from_axis_deg(-20.0, 1.0, 0.0, 0.0), 67.0),
+        ];
+
+        for camera in cameras.iter() {
+            light.attach_to(camera, &offset);
+            let view_mat = camera.view_mat();
+            let p = view_mat * math::vec4((light.position, 1.0));
+            let d = view_mat * math::vec4((light.direction, 0.0));
+
+            assert!(close([p.v[0], p.v[1], p.v[2]], offset.v));
+            assert!(close([d.v[0], d.v[1], d.v[2]], [0.0, 0.0, -1.0]));
+        }
+    }
+
+    #[test]
+    fn test_the_cone_and_intensity_stay_in_range() {
+        let mut light = Spotlight::new(math::vec3((0.0, 0.0, 2.0)), math::vec3((0.0, 0.0, -1.0)));
+        light.change_cutoff(-100.0);
+        assert_eq!(light.cutoff_deg, super::MIN_CUTOFF_DEG);
+        light.change_cutoff(200.0);
+        assert_eq!(light.cutoff_deg, super::MAX_CUTOFF_DEG);
+
+        light.change_intensity(-5.0);
+        assert_eq!(light.intensity, 0.0);
+        light.change_intensity(10.0);
+        assert_eq!(light.intensity, super::MAX_INTENSITY);
+    }
+
+    #[test]
+    fn test_the_cosine_of_the_default_cone_matches_the_chapter() {
+        let light = Spotlight::new(math::vec3((0.0, 0.0, 2.0)), math::vec3((0.0, 0.0, -1.0)));
+
+        assert!(f32::abs(light.cos_cutoff() - 0.906) < 1e-3);
+    }
+}
@@ -4,8 +4,12 @@ in vec3 position_eye, normal_eye;
 
 uniform mat4 view_mat;
 
-// fixed point light properties
-vec3 light_position_world  = vec3 (0.0, 0.0, 2.0);
+// spotlight properties, in world space, updated by the programme each frame
+uniform vec3 light_position_world;
+uniform vec3 spot_direction_world;
+uniform float spot_cos_cutoff; // cosine of the half angle of the cone
+uniform float light_intensity;
+
 vec3 Ls = vec3 (1.0, 1.0, 1.0); // white specular colour
 vec3 Ld = vec3 (0.7, 0.7, 0.7); // dull white diffuse light colour
 vec3 La = vec3 (0.2, 0.2, 0.2); // grey ambient colour
@@ -37,8 +41,9 @@ void main () {
 	
 	
 	/* Spotlight */
-	vec3 spot_direction = normalize (vec3 (0.0, 0.0, -1.0));
-	float spot_arc = 0.906; // cos(25 degrees)
+	// a direction has no position, so w is 0 and the view matrix only rotates it
+	vec3 spot_direction = normalize (vec3 (view_mat * vec4 (spot_direction_world, 0.0)));
+	float spot_arc = spot_cos_cutoff;
 	float spot_dot = dot (spot_direction, -direction_to_light_eye);
 
 	// this is for a sharp cut-off of the spot
@@ -49,7 +54,7 @@ void main () {
 	
 	// this is for a linear roll-off of the spot
 	float spot_factor = (spot_dot - spot_arc) / (1.0 - spot_arc);
-	spot_factor = clamp (spot_factor, 0.0, 1.0);
+	spot_factor = clamp (spot_factor, 0.0, 1.0) * light_intensity;
 	
 	// diffuse intensity
 	float dot_prod = dot (direction_to_light_eye, normal_eye);
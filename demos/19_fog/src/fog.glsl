@@ -0,0 +1,32 @@
+// fog variables, set from the keyboard
+uniform vec3 fog_colour;
+uniform float fog_density; // thickness of exponential fog
+uniform float fog_start; // where linear fog starts to thicken
+uniform float fog_end; // where linear fog hides everything
+// 0 for linear fog, 1 for exponential fog, and 2 for exponential squared fog.
+// a shader variant can compile the equation in by defining FOG_MODE.
+#ifdef FOG_MODE
+const int fog_mode = FOG_MODE;
+#else
+uniform int fog_mode;
+#endif
+
+// blend the fog colour into a lit colour, for a point at pos_eye in eye space
+vec3 apply_fog (vec3 colour, vec3 pos_eye) {
+	// work out distance from camera to point
+	float dist = length (-pos_eye);
+	// get a fog factor (thickness of fog) based on the distance
+	float fog_fac;
+	if (fog_mode == 1) {
+		fog_fac = 1.0 - exp (-fog_density * dist);
+	} else if (fog_mode == 2) {
+		fog_fac = 1.0 - exp (-(fog_density * dist) * (fog_density * dist));
+	} else {
+		fog_fac = (dist - fog_start) / (fog_end - fog_start);
+	}
+	// constrain the fog factor between 0 and 1
+	fog_fac = clamp (fog_fac, 0.0, 1.0);
+
+	// blend the fog colour with the lighting colour, based on the fog factor
+	return mix (colour, fog_colour, fog_fac);
+}
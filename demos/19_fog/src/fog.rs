@@ -0,0 +1,257 @@
+use gl;
+use gl::types::{GLint, GLuint};
+
+use gl_utils::uniform_location;
+
+
+// The fog colours the C key cycles through. The background is cleared to the
+// fog colour so distant geometry fades into it.
+pub const FOG_COLOURS: [[f32; 3]; 4] = [
+    [0.2, 0.2, 0.2],
+    [0.5, 0.6, 0.7],
+    [0.8, 0.5, 0.3],
+    [0.9, 0.9, 0.9],
+];
+
+const MAX_FOG_DENSITY: f32 = 2.0;
+// The closest the start and end distances of linear fog can get to each other.
+const MIN_FOG_RANGE: f32 = 0.1;
+
+
+///
+/// The fog equations from the chapter. The fog factor is the fraction of the
+/// fog colour mixed into a fragment.
+///
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum FogMode {
+    Linear,
+    Exponential,
+    ExponentialSquared,
+}
+
+impl FogMode {
+    ///
+    /// The value of the `fog_mode` uniform that selects this equation.
+    ///
+    pub fn uniform_value(&self) -> GLint {
+        match *self {
+            FogMode::Linear => 0,
+            FogMode::Exponential => 1,
+            FogMode::ExponentialSquared => 2,
+        }
+    }
+
+    ///
+    /// The `#define` that compiles this equation into a shader variant of its own,
+    /// in place of choosing it with the `fog_mode` uniform.
+    ///
+    pub fn define(&self) -> (&'static str, &'static str) {
+        match *self {
+            FogMode::Linear => ("FOG_MODE", "0"),
+            FogMode::Exponential => ("FOG_MODE", "1"),
+            FogMode::ExponentialSquared => ("FOG_MODE", "2"),
+        }
+    }
+
+    pub fn next(&self) -> FogMode {
+        match *self {
+            FogMode::Linear => FogMode::Exponential,
+            FogMode::Exponential => FogMode::ExponentialSquared,
+            FogMode::ExponentialSquared => FogMode::Linear,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            FogMode::Linear => "linear",
+            FogMode::Exponential => "exp",
+            FogMode::ExponentialSquared => "exp2",
+        }
+    }
+}
+
+///
+/// The fog parameters the fragment shader reads. Linear fog uses the start and
+/// end distances, and the exponential modes use the density.
+///
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct FogSettings {
+    pub mode: FogMode,
+    pub colour_index: usize,
+    pub density: f32,
+    pub start: f32,
+    pub end: f32,
+}
+
+impl FogSettings {
+    pub fn new() -> FogSettings {
+        FogSettings {
+            mode: FogMode::Linear,
+            colour_index: 0,
+            density: 0.3,
+            start: 2.0,
+            end: 5.0,
+        }
+    }
+
+    pub fn colour(&self) -> [f32; 3] {
+        FOG_COLOURS[self.colour_index]
+    }
+
+    pub fn next_colour(&mut self) {
+        self.colour_index = (self.colour_index + 1) % FOG_COLOURS.len();
+    }
+
+    pub fn change_density(&mut self, delta: f32) {
+        self.density = f32::min(f32::max(self.density + delta, 0.0), MAX_FOG_DENSITY);
+    }
+
+    ///
+    /// Move the start of linear fog, pushing the end back if they would cross.
+    ///
+    pub fn change_start(&mut self, delta: f32) {
+        self.start = f32::max(self.start + delta, 0.0);
+        self.end = f32::max(self.end, self.start + MIN_FOG_RANGE);
+    }
+
+    ///
+    /// Move the end of linear fog, keeping it beyond the start.
+    ///
+    pub fn change_end(&mut self, delta: f32) {
+        self.end = f32::max(self.end + delta, self.start + MIN_FOG_RANGE);
+    }
+
+    ///
+    /// Work out the fog factor for a fragment `dist` units from the camera, the
+    /// same way the fragment shader does.
+    ///
+    pub fn fog_factor(&self, dist: f32) -> f32 {
+        let fog_fac = match self.mode {
+            FogMode::Linear => (dist - self.start) / (self.end - self.start),
+            FogMode::Exponential => 1.0 - f32::exp(-self.density * dist),
+            FogMode::ExponentialSquared => 1.0 - f32::exp(-(self.density * dist) * (self.density * dist)),
+        };
+
+        f32::min(f32::max(fog_fac, 0.0), 1.0)
+    }
+
+    ///
+    /// Describe the settings the current mode uses, for the HUD and the log.
+    ///
+    pub fn describe(&self) -> String {
+        match self.mode {
+            FogMode::Linear => format!("fog {}  start {:.1}  end {:.1}", self.mode.name(), self.start, self.end),
+            _ => format!("fog {}  density {:.2}", self.mode.name(), self.density),
+        }
+    }
+}
+
+///
+/// The locations of the fog uniforms in a shader programme. A shader variant with
+/// the fog equation compiled in leaves out the uniforms it does not use; their
+/// locations are -1, which GL ignores uploads to.
+///
+pub struct FogUniforms {
+    colour_loc: GLint,
+    density_loc: GLint,
+    start_loc: GLint,
+    end_loc: GLint,
+    mode_loc: GLint,
+}
+
+impl FogUniforms {
+    pub fn new(sp: GLuint) -> FogUniforms {
+        FogUniforms {
+            colour_loc: uniform_location(sp, "fog_colour"),
+            density_loc: uniform_location(sp, "fog_density"),
+            start_loc: uniform_location(sp, "fog_start"),
+            end_loc: uniform_location(sp, "fog_end"),
+            mode_loc: uniform_location(sp, "fog_mode"),
+        }
+    }
+
+    ///
+    /// Upload the fog settings to the shader programme, leaving it in use.
+    ///
+    pub fn upload(&self, sp: GLuint, settings: &FogSettings) {
+        unsafe {
+            gl::UseProgram(sp);
+            gl_check!(gl::Uniform3fv(self.colour_loc, 1, settings.colour().as_ptr()));
+            gl_check!(gl::Uniform1f(self.density_loc, settings.density));
+            gl_check!(gl::Uniform1f(self.start_loc, settings.start));
+            gl_check!(gl::Uniform1f(self.end_loc, settings.end));
+            gl_check!(gl::Uniform1i(self.mode_loc, settings.mode.uniform_value()));
+        }
+    }
+
+    ///
+    /// Upload a fog colour of its own in place of the one the settings pick, for fog
+    /// that changes colour over time, such as with the time of day. Leaves the
+    /// programme in use.
+    ///
+    pub fn upload_colour(&self, sp: GLuint, colour: [f32; 3]) {
+        unsafe {
+            gl::UseProgram(sp);
+            gl_check!(gl::Uniform3fv(self.colour_loc, 1, colour.as_ptr()));
+        }
+    }
+}
+
+mod fog_tests {
+    use super::{FogMode, FogSettings};
+
+    #[test]
+    fn test_linear_fog_ramps_between_start_and_end() {
+        let fog = FogSettings::new();
+
+        assert_eq!(fog.fog_factor(1.0), 0.0);
+        assert_eq!(fog.fog_factor(3.5), 0.5);
+        assert_eq!(fog.fog_factor(10.0), 1.0);
+    }
+
+    #[test]
+    fn test_exponential_fog_thickens_faster_when_squared() {
+        let mut fog = FogSettings::new();
+        fog.density = 0.5;
+        fog.mode = FogMode::Exponential;
+        let exp_fac = fog.fog_factor(1.0);
+        fog.mode = FogMode::ExponentialSquared;
+        let exp2_fac = fog.fog_factor(1.0);
+
+        assert!(f32::abs(exp_fac - (1.0 - f32::exp(-0.5))) < 1e-6);
+        assert!(f32::abs(exp2_fac - (1.0 - f32::exp(-0.25))) < 1e-6);
+        assert_eq!(fog.fog_factor(0.0), 0.0);
+    }
+
+    #[test]
+    fn test_linear_fog_start_never_passes_end() {
+        let mut fog = FogSettings::new();
+        fog.change_start(10.0);
+
+        assert!(fog.end > fog.start);
+
+        fog.change_end(-100.0);
+
+        assert!(fog.end > fog.start);
+        assert_eq!(fog.start, 12.0);
+    }
+
+    #[test]
+    fn test_modes_cycle_through_all_equations() {
+        let mode = FogMode::Linear;
+
+        assert_eq!(mode.next(), FogMode::Exponential);
+        assert_eq!(mode.next().next(), FogMode::ExponentialSquared);
+        assert_eq!(mode.next().next().next(), FogMode::Linear);
+    }
+
+    #[test]
+    fn test_the_define_for_a_mode_matches_its_uniform_value() {
+        for &mode in [FogMode::Linear, FogMode::Exponential, FogMode::ExponentialSquared].iter() {
+            let (name, value) = mode.define();
+
+            assert_eq!(name, "FOG_MODE");
+            assert_eq!(value, mode.uniform_value().to_string());
+        }
+    }
+}
@@ -2,33 +2,133 @@ extern crate gl;
 extern crate glfw;
 extern crate chrono;
 extern crate stb_image;
+#[cfg(feature = "assimp")]
+extern crate assimp;
+#[cfg(feature = "gltf")]
+extern crate gltf;
+#[cfg(feature = "glam")]
+extern crate glam;
+#[cfg(feature = "cgmath")]
+extern crate cgmath;
 
-#[macro_use] 
-extern crate scan_fmt;
-
+#[macro_use]
 mod gl_utils;
 mod graphics_math;
 mod obj_parser;
+mod colour;
+mod assets;
+mod shader_include;
+mod spirv;
+mod program_cache;
+mod shader_program;
 mod logger;
+mod model;
+mod mesh_cache;
+mod resource_cache;
+mod geometry;
+mod fog;
+mod overlay;
+mod streaming_buffer;
+mod perf_stats;
+mod gpu_timer;
+mod pixel_probe;
+mod vertex_layout;
+mod vertex_packing;
+// The time controls are shared with the other demos that have them.
+#[path = "../../shared/frame_clock.rs"]
+mod frame_clock;
 
 
 use glfw::{Action, Context, Key};
-use gl::types::{GLfloat, GLsizeiptr, GLvoid};
+use gl::types::{GLfloat, GLint, GLuint};
 
-use std::mem;
-use std::ptr;
 use std::process;
+use std::time::Instant;
 
 use gl_utils::*;
 
 use graphics_math as math;
 use math::Mat4;
 
+use logger::Logger;
+use model::Model;
+use fog::{FogSettings, FogUniforms};
+use shader_program::ShaderProgram;
+use overlay::Overlay;
+use perf_stats::{PerfStats, PERF_STATS_HISTORY};
+use gpu_timer::GpuTimer;
+use pixel_probe::PixelProbe;
+use vertex_layout::VertexLayout;
+use frame_clock::FrameClock;
+
 const GL_LOG_FILE: &str = "gl.log";
 const VERTEX_SHADER_FILE: &str = "src/test.vert.glsl";
 const FRAGMENT_SHADER_FILE: &str = "src/test.frag.glsl";
 const MESH_FILE: &str = "src/suzanne.obj";
+// How far each key press changes the fog density, and the linear fog distances.
+const FOG_DENSITY_STEP: f32 = 0.05;
+const FOG_DISTANCE_STEP: f32 = 0.5;
+
 
+///
+/// Upload the fog settings to the shader, and clear the background to the fog
+/// colour so the fog fades into it.
+///
+fn set_fog(sp: GLuint, uniforms: &FogUniforms, fog: &FogSettings) {
+    uniforms.upload(sp, fog);
+    let colour = fog.colour();
+    unsafe {
+        gl::ClearColor(colour[0], colour[1], colour[2], 1.0);
+    }
+}
+
+///
+/// The locations of the uniforms the demo sets. Each shader variant is a
+/// programme of its own, with locations of its own.
+///
+struct SceneUniforms {
+    view_mat_location: GLint,
+    proj_mat_location: GLint,
+    time_location: GLint,
+    fog: FogUniforms,
+}
+
+impl SceneUniforms {
+    fn new(sp: GLuint) -> SceneUniforms {
+        let get_location = |name: &str| uniform_location(sp, name);
+        let uniforms = SceneUniforms {
+            view_mat_location: get_location("view"),
+            proj_mat_location: get_location("proj"),
+            time_location: get_location("time"),
+            fog: FogUniforms::new(sp),
+        };
+        assert!(uniforms.view_mat_location > -1);
+        assert!(uniforms.proj_mat_location > -1);
+        assert!(uniforms.time_location > -1);
+
+        uniforms
+    }
+}
+
+///
+/// Switch to the shader variant with the fog equation compiled in, building it
+/// the first time, and give it the camera and the fog settings.
+///
+fn use_fog_variant(
+    logger: &Logger, program: &ShaderProgram, fog: &FogSettings,
+    view_mat: &Mat4, proj_mat: &Mat4) -> (GLuint, SceneUniforms) {
+
+    let sp = program.with_defines(logger, &[fog.mode.define()]);
+    let uniforms = SceneUniforms::new(sp);
+    unsafe {
+        gl::UseProgram(sp);
+        gl::UniformMatrix4fv(uniforms.view_mat_location, 1, gl::FALSE, view_mat.as_ptr());
+        gl::UniformMatrix4fv(uniforms.proj_mat_location, 1, gl::FALSE, proj_mat.as_ptr());
+    }
+    set_fog(sp, &uniforms.fog, fog);
+
+    (sp, uniforms)
+}
 
 fn main() {
     let logger = restart_gl_log(GL_LOG_FILE);
@@ -51,11 +151,12 @@ fn main() {
     }
 
     /*------------------------------CREATE GEOMETRY------------------------------*/
-    let mesh = match obj_parser::load_obj_file(MESH_FILE) {
+    let mesh = match Model::load_cached(MESH_FILE) {
         Ok(val) => val,
         Err(e) => {
             logger.log_err(&format!("ERROR: loading mesh file. Loader returned error\n{}", e));
-            process::exit(1);
+            logger.log_err("WARNING: using the embedded fallback mesh instead.");
+            Model::from_obj_mesh(assets::fallback_mesh(), vec![])
         }
     };
 
@@ -67,50 +168,18 @@ fn main() {
     let mut vao = 0;
     unsafe {
         gl::GenVertexArrays(1, &mut vao);
-        gl::BindVertexArray(vao);
     }
     assert!(vao > 0);
 
-    let mut points_vbo = 0;
-    unsafe {
-        gl::GenBuffers(1, &mut points_vbo);
-        gl::BindBuffer(gl::ARRAY_BUFFER, points_vbo);
-        gl::BufferData(
-            gl::ARRAY_BUFFER, (3 * g_point_count * mem::size_of::<GLfloat>()) as GLsizeiptr, 
-            g_vp.as_ptr() as *const GLvoid, gl::STATIC_DRAW
-        );
-        gl::VertexAttribPointer(0, 3, gl::FLOAT, gl::FALSE, 0, ptr::null());
-        gl::EnableVertexAttribArray(0);
-    }
-    assert!(points_vbo > 0);
-
-    let mut normals_vbo = 0;
-    unsafe {
-        gl::GenBuffers( 1, &mut normals_vbo);
-        gl::BindBuffer(gl::ARRAY_BUFFER, normals_vbo);
-        gl::BufferData(
-            gl::ARRAY_BUFFER, (3 * g_point_count * mem::size_of::<GLfloat>()) as GLsizeiptr, 
-            g_vn.as_ptr() as *const GLvoid, gl::STATIC_DRAW
-        );
-        gl::VertexAttribPointer(1, 3, gl::FLOAT, gl::FALSE, 0, ptr::null());
-        gl::EnableVertexAttribArray(1);
-    }
-    assert!(normals_vbo > 0);
-
-    let mut texcoords_vbo = 0;
-    unsafe {
-        gl::GenBuffers( 1, &mut texcoords_vbo);
-        gl::BindBuffer(gl::ARRAY_BUFFER, texcoords_vbo);
-        gl::BufferData(
-            gl::ARRAY_BUFFER, (2 * g_point_count * mem::size_of::<GLfloat>()) as GLsizeiptr, 
-            g_vt.as_ptr() as *const GLvoid, gl::STATIC_DRAW
-        );
-        gl::VertexAttribPointer(2, 2, gl::FLOAT, gl::FALSE, 0, ptr::null());
-        gl::EnableVertexAttribArray(2);
+    VertexLayout::new().floats(0, 3).upload(vao, &g_vp, g_point_count);
+    VertexLayout::new().floats(1, 3).upload(vao, &g_vn, g_point_count);
+    if g_vt.len() == 2 * g_point_count {
+        VertexLayout::new().floats(2, 2).upload(vao, &g_vt, g_point_count);
     }
-    assert!(texcoords_vbo > 0);
 
-    let shader_programme = create_programme_from_files(&logger, VERTEX_SHADER_FILE, FRAGMENT_SHADER_FILE);
+    // the fog equation is compiled into the fragment shader, so each one is a
+    // shader variant of its own. F switches between them.
+    let shader_program = ShaderProgram::new(VERTEX_SHADER_FILE, FRAGMENT_SHADER_FILE);
 
     // input variables
     let near = 0.1;                                  // clipping plane
@@ -124,30 +193,12 @@ fn main() {
     let cam_yaw_speed: GLfloat = 10.0;        // 10 degrees per second
     let mut cam_pos: [GLfloat; 3] = [0.0, 0.0, 5.0]; // don't start at zero, or we will be too close
     let mut cam_yaw: GLfloat = 0.0;               // y-rotation in degrees
-    let mut mat_trans = Mat4::identity().translate(&math::vec3((-cam_pos[0], -cam_pos[1], -cam_pos[2])));
-    let mut mat_rot = Mat4::identity().rotate_y_deg(-cam_yaw);
+    let mut mat_trans = Mat4::from_translation(&math::vec3((-cam_pos[0], -cam_pos[1], -cam_pos[2])));
+    let mut mat_rot = Mat4::from_rotation_y_deg(-cam_yaw);
     let mut view_mat = mat_rot * mat_trans;
 
-    let view_mat_location = unsafe {
-        gl::GetUniformLocation(shader_programme, "view".as_ptr() as *const i8)
-    };
-    assert!(view_mat_location > -1);
-
-    let proj_mat_location = unsafe { 
-        gl::GetUniformLocation(shader_programme, "proj".as_ptr() as *const i8)
-    };
-    assert!(proj_mat_location > -1);
-
-    let time_location = unsafe {
-        gl::GetUniformLocation(shader_programme, "time".as_ptr() as *const i8)
-    };
-    assert!(time_location > -1);
-
-    unsafe {
-        gl::UseProgram(shader_programme);
-        gl::UniformMatrix4fv(view_mat_location, 1, gl::FALSE, view_mat.as_ptr());
-        gl::UniformMatrix4fv(proj_mat_location, 1, gl::FALSE, proj_mat.as_ptr());
-    }
+    let mut fog = FogSettings::new();
+    let (mut shader_programme, mut uniforms) = use_fog_variant(&logger, &shader_program, &fog, &view_mat, &proj_mat);
 
     unsafe {
         // Cull face.
@@ -156,13 +207,31 @@ fn main() {
         gl::CullFace(gl::BACK);
         // GL_CW for clockwise.    
         gl::FrontFace(gl::CCW);
-        gl::ClearColor(0.2, 0.2, 0.2, 1.0);
     }
 
+    logger.log("keys: F cycles the fog equation, C cycles the fog colour, [ and ] change the exponential fog density,");
+    logger.log("      - and = move the start of linear fog, 9 and 0 move its end, F3 toggles the performance HUD");
+    logger.log("      P shows the colour and depth of the pixel under the cursor");
+    logger.log("      F6 pauses time, F7 and F8 slow it down and speed it up, F10 steps a frame while paused");
+
+    // frame time statistics, drawn over the scene along with the fog settings.
+    // F3 toggles them on and off.
+    let mut overlay = Overlay::new(&logger);
+    let mut perf_stats = PerfStats::new(PERF_STATS_HISTORY);
+    let mut show_perf_stats = true;
+    let mut mesh_timer = GpuTimer::new();
+    // the pixel under the cursor, read back each frame while P has it on.
+    let mut probe = PixelProbe::new();
+    // the simulation time the shader animates with, which the time controls
+    // pause, slow down, speed up, and step
+    let mut clock = FrameClock::new();
+
     while !context.window.should_close() {
+        let frame_start = Instant::now();
         let current_seconds = context.glfw.get_time();
         let elapsed_seconds = current_seconds - context.elapsed_time_seconds;
         context.elapsed_time_seconds = current_seconds;
+        clock.tick(elapsed_seconds);
 
         update_fps_counter(&mut context);
         unsafe {
@@ -170,17 +239,91 @@ fn main() {
             gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
             gl::Viewport(0, 0, context.width as i32, context.height as i32);
 
+            mesh_timer.begin();
             gl::UseProgram(shader_programme);
             gl::BindVertexArray(vao);
             // Draw points 0-3 from the currently bound VAO with current in-use shader.
-            gl::Uniform1f(time_location, current_seconds as f32);
+            gl::Uniform1f(uniforms.time_location, clock.seconds() as f32);
             gl::DrawArrays(gl::TRIANGLES, 0, g_point_count as i32);
+            perf_stats.count_draw(g_point_count / 3);
             // Update other events like input handling
             gl::DrawArrays(gl::TRIANGLES, 0, 6);
+            perf_stats.count_draw(2);
+            mesh_timer.end();
+        }
+
+        perf_stats.set_gpu_pass_ms("mesh", mesh_timer.elapsed_ms());
+        // Read the pixel back before the HUD is drawn over it.
+        probe.update(context.window.get_cursor_pos(), context.window.get_size(), (context.width, context.height));
+        let mut hud_bottom = 10.0;
+        if show_perf_stats {
+            hud_bottom = perf_stats.draw_hud(&mut overlay, 10.0, 10.0);
+            overlay.text(16.0, hud_bottom + 6.0, 2.0, [1.0, 1.0, 1.0, 1.0], &format!("{}  {}", fog.describe(), clock.describe()));
+            hud_bottom += 2.0 * overlay::LINE_HEIGHT + 12.0;
         }
+        probe.draw_hud(&mut overlay, 10.0, hud_bottom, near, far);
+        overlay.draw(context.width, context.height);
 
         context.glfw.poll_events();
 
+        let mut fog_changed = false;
+        let mut fog_mode_changed = false;
+        for (_, event) in glfw::flush_messages(&context.events) {
+            match event {
+                glfw::WindowEvent::Key(Key::F, _, Action::Press, _) => {
+                    fog.mode = fog.mode.next();
+                    fog_mode_changed = true;
+                }
+                glfw::WindowEvent::Key(Key::C, _, Action::Press, _) => {
+                    fog.next_colour();
+                    fog_changed = true;
+                }
+                glfw::WindowEvent::Key(Key::LeftBracket, _, Action::Press, _) => {
+                    fog.change_density(-FOG_DENSITY_STEP);
+                    fog_changed = true;
+                }
+                glfw::WindowEvent::Key(Key::RightBracket, _, Action::Press, _) => {
+                    fog.change_density(FOG_DENSITY_STEP);
+                    fog_changed = true;
+                }
+                glfw::WindowEvent::Key(Key::Minus, _, Action::Press, _) => {
+                    fog.change_start(-FOG_DISTANCE_STEP);
+                    fog_changed = true;
+                }
+                glfw::WindowEvent::Key(Key::Equal, _, Action::Press, _) => {
+                    fog.change_start(FOG_DISTANCE_STEP);
+                    fog_changed = true;
+                }
+                glfw::WindowEvent::Key(Key::Num9, _, Action::Press, _) => {
+                    fog.change_end(-FOG_DISTANCE_STEP);
+                    fog_changed = true;
+                }
+                glfw::WindowEvent::Key(Key::Num0, _, Action::Press, _) => {
+                    fog.change_end(FOG_DISTANCE_STEP);
+                    fog_changed = true;
+                }
+                glfw::WindowEvent::Key(Key::F3, _, Action::Press, _) => {
+                    show_perf_stats = !show_perf_stats;
+                }
+                glfw::WindowEvent::Key(Key::P, _, Action::Press, _) => {
+                    probe.enabled = !probe.enabled;
+                }
+                glfw::WindowEvent::Key(key, _, Action::Press, _) => {
+                    clock.handle_key(key);
+                }
+                _ => {}
+            }
+        }
+        if fog_mode_changed {
+            let variant = use_fog_variant(&logger, &shader_program, &fog, &view_mat, &proj_mat);
+            shader_programme = variant.0;
+            uniforms = variant.1;
+            logger.log(&format!("{} ({} shader variants built)", fog.describe(), shader_program.variant_count()));
+        } else if fog_changed {
+            set_fog(shader_programme, &uniforms.fog, &fog);
+            logger.log(&fog.describe());
+        }
+
         // control keys
         let mut cam_moved = false;
         match context.window.get_key(Key::A) {
@@ -241,11 +384,12 @@ fn main() {
         }
         // update view matrix
         if cam_moved {
-            mat_trans = Mat4::identity().translate(&math::vec3((-cam_pos[0], -cam_pos[1], -cam_pos[2]))); // cam translation
-            mat_rot = Mat4::identity().rotate_y_deg(-cam_yaw);
+            mat_trans = Mat4::from_translation(&math::vec3((-cam_pos[0], -cam_pos[1], -cam_pos[2]))); // cam translation
+            mat_rot = Mat4::from_rotation_y_deg(-cam_yaw);
             view_mat = mat_rot * mat_trans;
             unsafe {
-                gl::UniformMatrix4fv(view_mat_location, 1, gl::FALSE, view_mat.as_ptr());
+                gl::UseProgram(shader_programme);
+                gl::UniformMatrix4fv(uniforms.view_mat_location, 1, gl::FALSE, view_mat.as_ptr());
             }
         }
 
@@ -255,6 +399,7 @@ fn main() {
             }
             _ => {}
         }
+        perf_stats.end_frame(perf_stats::duration_to_ms(frame_start.elapsed()));
         // Put the stuff we've been drawing onto the display.
         context.window.swap_buffers();
     }
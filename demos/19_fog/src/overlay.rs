@@ -0,0 +1,360 @@
+use gl;
+use gl::types::{GLfloat, GLint, GLuint, GLvoid};
+
+use gl_utils::create_programme_from_files;
+use logger::Logger;
+use streaming_buffer::StreamingBuffer;
+use vertex_layout::VertexLayout;
+use gl_utils::uniform_location;
+
+use std::mem;
+
+
+const OVERLAY_VS_FILE: &str = "src/overlay_vs.glsl";
+const OVERLAY_FS_FILE: &str = "src/overlay_fs.glsl";
+
+// The font atlas is a 16x8 grid of 8x8 pixel cells. Each glyph is 5x7 pixels
+// in the top-left corner of its cell, which leaves a pixel of spacing around it.
+const ATLAS_WIDTH: usize = 128;
+const ATLAS_HEIGHT: usize = 64;
+const ATLAS_COLUMNS: usize = 16;
+const CELL_SIZE: usize = 8;
+const FIRST_GLYPH: u8 = b' ';
+const LAST_GLYPH: u8 = b'~';
+// The cell after the last glyph is filled in solid so rectangles can share the atlas.
+const SOLID_CELL: usize = (LAST_GLYPH - FIRST_GLYPH) as usize + 1;
+
+// Position, texture coordinates, and colour for each vertex.
+const FLOATS_PER_VERTEX: usize = 8;
+// Room for this many vertices before the vertex buffer has to grow. That is plenty
+// for the frame time HUD and a few lines of text.
+const INITIAL_VERTEX_CAPACITY: usize = 8192;
+
+///
+/// The horizontal distance in pixels between consecutive glyphs at a scale of 1.
+///
+pub const GLYPH_ADVANCE: f32 = 6.0;
+
+///
+/// The vertical distance in pixels between lines of text at a scale of 1.
+///
+pub const LINE_HEIGHT: f32 = 8.0;
+
+///
+/// A classic 5x7 pixel font covering printable ASCII. Each glyph is five
+/// columns, and bit 0 of each column is the top row.
+///
+const FONT_5X7: [u8; 5 * 95] = [
+    0x00, 0x00, 0x00, 0x00, 0x00, // space
+    0x00, 0x00, 0x5F, 0x00, 0x00, // !
+    0x00, 0x07, 0x00, 0x07, 0x00, // "
+    0x14, 0x7F, 0x14, 0x7F, 0x14, // #
+    0x24, 0x2A, 0x7F, 0x2A, 0x12, // $
+    0x23, 0x13, 0x08, 0x64, 0x62, // %
+    0x36, 0x49, 0x55, 0x22, 0x50, // &
+    0x00, 0x05, 0x03, 0x00, 0x00, // '
+    0x00, 0x1C, 0x22, 0x41, 0x00, // (
+    0x00, 0x41, 0x22, 0x1C, 0x00, // )
+    0x08, 0x2A, 0x1C, 0x2A, 0x08, // *
+    0x08, 0x08, 0x3E, 0x08, 0x08, // +
+    0x00, 0x50, 0x30, 0x00, 0x00, // ,
+    0x08, 0x08, 0x08, 0x08, 0x08, // -
+    0x00, 0x60, 0x60, 0x00, 0x00, // .
+    0x20, 0x10, 0x08, 0x04, 0x02, // /
+    0x3E, 0x51, 0x49, 0x45, 0x3E, // 0
+    0x00, 0x42, 0x7F, 0x40, 0x00, // 1
+    0x42, 0x61, 0x51, 0x49, 0x46, // 2
+    0x21, 0x41, 0x45, 0x4B, 0x31, // 3
+    0x18, 0x14, 0x12, 0x7F, 0x10, // 4
+    0x27, 0x45, 0x45, 0x45, 0x39, // 5
+    0x3C, 0x4A, 0x49, 0x49, 0x30, // 6
+    0x01, 0x71, 0x09, 0x05, 0x03, // 7
+    0x36, 0x49, 0x49, 0x49, 0x36, // 8
+    0x06, 0x49, 0x49, 0x29, 0x1E, // 9
+    0x00, 0x36, 0x36, 0x00, 0x00, // :
+    0x00, 0x56, 0x36, 0x00, 0x00, // ;
+    0x08, 0x14, 0x22, 0x41, 0x00, // <
+    0x14, 0x14, 0x14, 0x14, 0x14, // =
+    0x00, 0x41, 0x22, 0x14, 0x08, // >
+    0x02, 0x01, 0x51, 0x09, 0x06, // ?
+    0x32, 0x49, 0x79, 0x41, 0x3E, // @
+    0x7E, 0x11, 0x11, 0x11, 0x7E, // A
+    0x7F, 0x49, 0x49, 0x49, 0x36, // B
+    0x3E, 0x41, 0x41, 0x41, 0x22, // C
+    0x7F, 0x41, 0x41, 0x22, 0x1C, // D
+    0x7F, 0x49, 0x49, 0x49, 0x41, // E
+    0x7F, 0x09, 0x09, 0x09, 0x01, // F
+    0x3E, 0x41, 0x49, 0x49, 0x7A, // G
+    0x7F, 0x08, 0x08, 0x08, 0x7F, // H
+    0x00, 0x41, 0x7F, 0x41, 0x00, // I
+    0x20, 0x40, 0x41, 0x3F, 0x01, // J
+    0x7F, 0x08, 0x14, 0x22, 0x41, // K
+    0x7F, 0x40, 0x40, 0x40, 0x40, // L
+    0x7F, 0x02, 0x0C, 0x02, 0x7F, // M
+    0x7F, 0x04, 0x08, 0x10, 0x7F, // N
+    0x3E, 0x41, 0x41, 0x41, 0x3E, // O
+    0x7F, 0x09, 0x09, 0x09, 0x06, // P
+    0x3E, 0x41, 0x51, 0x21, 0x5E, // Q
+    0x7F, 0x09, 0x19, 0x29, 0x46, // R
+    0x46, 0x49, 0x49, 0x49, 0x31, // S
+    0x01, 0x01, 0x7F, 0x01, 0x01, // T
+    0x3F, 0x40, 0x40, 0x40, 0x3F, // U
+    0x1F, 0x20, 0x40, 0x20, 0x1F, // V
+    0x3F, 0x40, 0x38, 0x40, 0x3F, // W
+    0x63, 0x14, 0x08, 0x14, 0x63, // X
+    0x07, 0x08, 0x70, 0x08, 0x07, // Y
+    0x61, 0x51, 0x49, 0x45, 0x43, // Z
+    0x00, 0x7F, 0x41, 0x41, 0x00, // [
+    0x02, 0x04, 0x08, 0x10, 0x20, // backslash
+    0x00, 0x41, 0x41, 0x7F, 0x00, // ]
+    0x04, 0x02, 0x01, 0x02, 0x04, // ^
+    0x40, 0x40, 0x40, 0x40, 0x40, // _
+    0x00, 0x01, 0x02, 0x04, 0x00, // `
+    0x20, 0x54, 0x54, 0x54, 0x78, // a
+    0x7F, 0x48, 0x44, 0x44, 0x38, // b
+    0x38, 0x44, 0x44, 0x44, 0x20, // c
+    0x38, 0x44, 0x44, 0x48, 0x7F, // d
+    0x38, 0x54, 0x54, 0x54, 0x18, // e
+    0x08, 0x7E, 0x09, 0x01, 0x02, // f
+    0x0C, 0x52, 0x52, 0x52, 0x3E, // g
+    0x7F, 0x08, 0x04, 0x04, 0x78, // h
+    0x00, 0x44, 0x7D, 0x40, 0x00, // i
+    0x20, 0x40, 0x44, 0x3D, 0x00, // j
+    0x7F, 0x10, 0x28, 0x44, 0x00, // k
+    0x00, 0x41, 0x7F, 0x40, 0x00, // l
+    0x7C, 0x04, 0x18, 0x04, 0x78, // m
+    0x7C, 0x08, 0x04, 0x04, 0x78, // n
+    0x38, 0x44, 0x44, 0x44, 0x38, // o
+    0x7C, 0x14, 0x14, 0x14, 0x08, // p
+    0x08, 0x14, 0x14, 0x18, 0x7C, // q
+    0x7C, 0x08, 0x04, 0x04, 0x08, // r
+    0x48, 0x54, 0x54, 0x54, 0x20, // s
+    0x04, 0x3F, 0x44, 0x40, 0x20, // t
+    0x3C, 0x40, 0x40, 0x20, 0x7C, // u
+    0x1C, 0x20, 0x40, 0x20, 0x1C, // v
+    0x3C, 0x40, 0x30, 0x40, 0x3C, // w
+    0x44, 0x28, 0x10, 0x28, 0x44, // x
+    0x0C, 0x50, 0x50, 0x50, 0x3C, // y
+    0x44, 0x64, 0x54, 0x4C, 0x44, // z
+    0x00, 0x08, 0x36, 0x41, 0x00, // {
+    0x00, 0x00, 0x7F, 0x00, 0x00, // |
+    0x00, 0x41, 0x36, 0x08, 0x00, // }
+    0x08, 0x04, 0x08, 0x10, 0x08, // ~
+];
+
+
+///
+/// Expand the 5x7 font into a single channel texture atlas.
+///
+fn font_atlas_pixels() -> Vec<u8> {
+    let mut pixels = vec![0; ATLAS_WIDTH * ATLAS_HEIGHT];
+    for glyph in 0..SOLID_CELL {
+        let x0 = (glyph % ATLAS_COLUMNS) * CELL_SIZE;
+        let y0 = (glyph / ATLAS_COLUMNS) * CELL_SIZE;
+        for col in 0..5 {
+            let bits = FONT_5X7[5 * glyph + col];
+            for row in 0..7 {
+                if (bits >> row) & 1 == 1 {
+                    pixels[(y0 + row) * ATLAS_WIDTH + x0 + col] = 255;
+                }
+            }
+        }
+    }
+
+    let x0 = (SOLID_CELL % ATLAS_COLUMNS) * CELL_SIZE;
+    let y0 = (SOLID_CELL / ATLAS_COLUMNS) * CELL_SIZE;
+    for row in 0..CELL_SIZE {
+        for col in 0..CELL_SIZE {
+            pixels[(y0 + row) * ATLAS_WIDTH + x0 + col] = 255;
+        }
+    }
+
+    pixels
+}
+
+///
+/// Look up the texture coordinates of the top-left corner of a cell in the atlas.
+///
+fn cell_tex_coords(cell: usize) -> (f32, f32) {
+    let s = ((cell % ATLAS_COLUMNS) * CELL_SIZE) as f32 / ATLAS_WIDTH as f32;
+    let t = ((cell / ATLAS_COLUMNS) * CELL_SIZE) as f32 / ATLAS_HEIGHT as f32;
+
+    (s, t)
+}
+
+///
+/// An `Overlay` batches up screen-space text and flat coloured rectangles, and
+/// draws them over the top of the scene in a single draw call. Coordinates are
+/// in pixels, measured from the top-left corner of the viewport.
+///
+pub struct Overlay {
+    sp: GLuint,
+    viewport_size_loc: GLint,
+    font_tex: GLuint,
+    vao: GLuint,
+    vertex_layout: VertexLayout,
+    vertex_stream: StreamingBuffer,
+    vertices: Vec<GLfloat>,
+}
+
+impl Overlay {
+    pub fn new(logger: &Logger) -> Overlay {
+        let sp = create_programme_from_files(logger, OVERLAY_VS_FILE, OVERLAY_FS_FILE);
+        let viewport_size_loc = uniform_location(sp, "viewport_size");
+        assert!(viewport_size_loc > -1);
+
+        let pixels = font_atlas_pixels();
+        let mut font_tex = 0;
+        unsafe {
+            gl::GenTextures(1, &mut font_tex);
+            gl::ActiveTexture(gl::TEXTURE0);
+            gl::BindTexture(gl::TEXTURE_2D, font_tex);
+            gl::PixelStorei(gl::UNPACK_ALIGNMENT, 1);
+            gl_check!(gl::TexImage2D(
+                gl::TEXTURE_2D, 0, gl::R8 as i32, ATLAS_WIDTH as i32, ATLAS_HEIGHT as i32, 0,
+                gl::RED, gl::UNSIGNED_BYTE,
+                pixels.as_ptr() as *const GLvoid
+            ));
+            gl::PixelStorei(gl::UNPACK_ALIGNMENT, 4);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as i32);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as i32);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::NEAREST as i32);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::NEAREST as i32);
+        }
+        assert!(font_tex > 0);
+
+        let vertex_stream = StreamingBuffer::new(
+            gl::ARRAY_BUFFER, INITIAL_VERTEX_CAPACITY * FLOATS_PER_VERTEX * mem::size_of::<GLfloat>()
+        );
+        logger.log(&format!("overlay: streaming vertices with {}", vertex_stream.mode().name()));
+        let mut vao = 0;
+        unsafe {
+            gl::GenVertexArrays(1, &mut vao);
+        }
+        assert!(vao > 0);
+
+        Overlay {
+            sp: sp,
+            viewport_size_loc: viewport_size_loc,
+            font_tex: font_tex,
+            vao: vao,
+            vertex_layout: VertexLayout::new().floats(0, 2).floats(1, 2).floats(2, 4),
+            vertex_stream: vertex_stream,
+            vertices: vec![],
+        }
+    }
+
+    fn push_quad(&mut self, x: f32, y: f32, w: f32, h: f32, st0: (f32, f32), st1: (f32, f32), colour: [f32; 4]) {
+        let corners = [
+            (x,     y,     st0.0, st0.1),
+            (x,     y + h, st0.0, st1.1),
+            (x + w, y + h, st1.0, st1.1),
+            (x,     y,     st0.0, st0.1),
+            (x + w, y + h, st1.0, st1.1),
+            (x + w, y,     st1.0, st0.1),
+        ];
+        for &(px, py, s, t) in corners.iter() {
+            self.vertices.extend_from_slice(&[px, py, s, t, colour[0], colour[1], colour[2], colour[3]]);
+        }
+    }
+
+    ///
+    /// Queue up a line of text. The text wraps onto a new line at each `\n`, and
+    /// any character outside of printable ASCII is drawn as a `?`. The colour can
+    /// be a `Colour` or a plain RGBA array.
+    ///
+    pub fn text<C: Into<[f32; 4]>>(&mut self, x: f32, y: f32, scale: f32, colour: C, text: &str) {
+        let colour = colour.into();
+        let mut pen_x = x;
+        let mut pen_y = y;
+        for ch in text.bytes() {
+            if ch == b'\n' {
+                pen_x = x;
+                pen_y += LINE_HEIGHT * scale;
+                continue;
+            }
+
+            let ch = if ch >= FIRST_GLYPH && ch <= LAST_GLYPH { ch } else { b'?' };
+            if ch != b' ' {
+                let st0 = cell_tex_coords((ch - FIRST_GLYPH) as usize);
+                let st1 = (
+                    st0.0 + GLYPH_ADVANCE / ATLAS_WIDTH as f32, st0.1 + LINE_HEIGHT / ATLAS_HEIGHT as f32
+                );
+                self.push_quad(pen_x, pen_y, GLYPH_ADVANCE * scale, LINE_HEIGHT * scale, st0, st1, colour);
+            }
+            pen_x += GLYPH_ADVANCE * scale;
+        }
+    }
+
+    ///
+    /// Queue up a flat coloured rectangle.
+    ///
+    pub fn rect<C: Into<[f32; 4]>>(&mut self, x: f32, y: f32, w: f32, h: f32, colour: C) {
+        let colour = colour.into();
+        let (s, t) = cell_tex_coords(SOLID_CELL);
+        let half_cell_s = 0.5 * CELL_SIZE as f32 / ATLAS_WIDTH as f32;
+        let half_cell_t = 0.5 * CELL_SIZE as f32 / ATLAS_HEIGHT as f32;
+        let centre = (s + half_cell_s, t + half_cell_t);
+        self.push_quad(x, y, w, h, centre, centre, colour);
+    }
+
+    ///
+    /// Draw everything queued up since the last call over the top of the scene,
+    /// then clear the queue. Depth testing is switched off while drawing and restored
+    /// afterwards.
+    ///
+    pub fn draw(&mut self, viewport_width: u32, viewport_height: u32) {
+        if self.vertices.is_empty() {
+            return;
+        }
+
+        // The vertices land somewhere different in the stream each frame, so point the
+        // attributes at wherever they went this time.
+        let offset = self.vertex_stream.upload(&self.vertices);
+        self.vertex_layout.apply_at(self.vao, self.vertex_stream.buffer(), offset);
+        unsafe {
+            let depth_test_enabled = gl::IsEnabled(gl::DEPTH_TEST) == gl::TRUE;
+            gl::Disable(gl::DEPTH_TEST);
+            gl::Enable(gl::BLEND);
+            gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
+
+            gl::UseProgram(self.sp);
+            gl_check!(gl::Uniform2f(self.viewport_size_loc, viewport_width as f32, viewport_height as f32));
+            gl::ActiveTexture(gl::TEXTURE0);
+            gl::BindTexture(gl::TEXTURE_2D, self.font_tex);
+            gl_check!(gl::DrawArrays(gl::TRIANGLES, 0, (self.vertices.len() / FLOATS_PER_VERTEX) as i32));
+
+            gl::Disable(gl::BLEND);
+            if depth_test_enabled {
+                gl::Enable(gl::DEPTH_TEST);
+            }
+        }
+
+        self.vertices.clear();
+    }
+}
+
+mod overlay_tests {
+    #[test]
+    fn test_font_atlas_has_solid_cell() {
+        let pixels = super::font_atlas_pixels();
+        let (s, t) = super::cell_tex_coords(super::SOLID_CELL);
+        let x0 = (s * super::ATLAS_WIDTH as f32) as usize;
+        let y0 = (t * super::ATLAS_HEIGHT as f32) as usize;
+        for row in 0..super::CELL_SIZE {
+            for col in 0..super::CELL_SIZE {
+                assert_eq!(pixels[(y0 + row) * super::ATLAS_WIDTH + x0 + col], 255);
+            }
+        }
+    }
+
+    #[test]
+    fn test_space_glyph_is_empty() {
+        let pixels = super::font_atlas_pixels();
+        for row in 0..super::CELL_SIZE {
+            for col in 0..super::CELL_SIZE {
+                assert_eq!(pixels[row * super::ATLAS_WIDTH + col], 0);
+            }
+        }
+    }
+}
@@ -0,0 +1,210 @@
+use gl;
+use gl::types::{GLfloat, GLint, GLvoid};
+
+use overlay::{Overlay, GLYPH_ADVANCE, LINE_HEIGHT};
+
+
+const PROBE_BACKGROUND_COLOUR: [f32; 4] = [0.0, 0.0, 0.0, 0.6];
+const PROBE_TEXT_COLOUR: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+
+
+///
+/// Turn a depth buffer value back into the distance from the camera along its
+/// view direction, undoing the perspective divide for a projection with clipping
+/// planes at `near` and `far`.
+///
+pub fn linearize_depth(depth: f32, near: f32, far: f32) -> f32 {
+    let z_ndc = 2.0 * depth - 1.0;
+
+    (2.0 * near * far) / (far + near - z_ndc * (far - near))
+}
+
+///
+/// The pixel under the cursor, counting up from the bottom-left of the frame
+/// buffer as GL does. The cursor is measured in window coordinates from the
+/// top-left, and on high DPI screens the frame buffer has more pixels than the
+/// window has coordinates, so it is scaled to fit. Returns `None` when the
+/// cursor is outside the window.
+///
+pub fn cursor_to_pixel(
+    cursor: (f64, f64), window_size: (i32, i32), framebuffer_size: (u32, u32)) -> Option<(u32, u32)> {
+
+    let (window_width, window_height) = window_size;
+    let (framebuffer_width, framebuffer_height) = framebuffer_size;
+    if window_width <= 0 || window_height <= 0 || framebuffer_width == 0 || framebuffer_height == 0 {
+        return None;
+    }
+    let (cursor_x, cursor_y) = cursor;
+    if cursor_x < 0.0 || cursor_y < 0.0 || cursor_x >= window_width as f64 || cursor_y >= window_height as f64 {
+        return None;
+    }
+
+    let x = (cursor_x * framebuffer_width as f64 / window_width as f64) as u32;
+    let y = (cursor_y * framebuffer_height as f64 / window_height as f64) as u32;
+
+    Some((u32::min(x, framebuffer_width - 1), framebuffer_height - 1 - u32::min(y, framebuffer_height - 1)))
+}
+
+///
+/// The colour and depth of one pixel, read back from the frame buffer.
+///
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct PixelSample {
+    pub x: u32,
+    pub y: u32,
+    pub rgba: [u8; 4],
+    pub depth: f32,
+}
+
+impl PixelSample {
+    ///
+    /// The distance to whatever was drawn at the pixel, or `None` if nothing was
+    /// and the depth buffer still holds its cleared value.
+    ///
+    pub fn linear_depth(&self, near: f32, far: f32) -> Option<f32> {
+        if self.depth >= 1.0 {
+            return None;
+        }
+
+        Some(linearize_depth(self.depth, near, far))
+    }
+
+    pub fn describe(&self, near: f32, far: f32) -> Vec<String> {
+        let r = self.rgba[0];
+        let g = self.rgba[1];
+        let b = self.rgba[2];
+        let a = self.rgba[3];
+        let distance = match self.linear_depth(near, far) {
+            Some(distance) => format!("{:.3}", distance),
+            None => String::from("background"),
+        };
+
+        vec![
+            format!("pixel ({}, {})", self.x, self.y),
+            format!("rgba {:3} {:3} {:3} {:3}  #{:02x}{:02x}{:02x}{:02x}", r, g, b, a, r, g, b, a),
+            format!("depth {:.6}  linear {}", self.depth, distance),
+        ]
+    }
+}
+
+///
+/// Read the colour and depth of one pixel of the frame buffer being drawn.
+///
+pub fn read_pixel(x: u32, y: u32) -> PixelSample {
+    let mut rgba = [0_u8; 4];
+    let mut depth: GLfloat = 0.0;
+    unsafe {
+        gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
+        gl::ReadPixels(
+            x as GLint, y as GLint, 1, 1, gl::RGBA, gl::UNSIGNED_BYTE, rgba.as_mut_ptr() as *mut GLvoid
+        );
+        gl::ReadPixels(
+            x as GLint, y as GLint, 1, 1, gl::DEPTH_COMPONENT, gl::FLOAT, &mut depth as *mut GLfloat as *mut GLvoid
+        );
+    }
+
+    PixelSample { x: x, y: y, rgba: rgba, depth: depth }
+}
+
+///
+/// Shows the colour and depth of the pixel under the cursor in the HUD, to help
+/// track down where a shader writes something unexpected. Sample the frame after
+/// drawing the scene and before drawing the HUD over it, or the probe reads back
+/// the HUD.
+///
+pub struct PixelProbe {
+    pub enabled: bool,
+    sample: Option<PixelSample>,
+}
+
+impl PixelProbe {
+    pub fn new() -> PixelProbe {
+        PixelProbe {
+            enabled: false,
+            sample: None,
+        }
+    }
+
+    ///
+    /// Read back the pixel under the cursor, if the probe is on and the cursor is
+    /// in the window.
+    ///
+    pub fn update(&mut self, cursor: (f64, f64), window_size: (i32, i32), framebuffer_size: (u32, u32)) {
+        self.sample = if self.enabled {
+            cursor_to_pixel(cursor, window_size, framebuffer_size).map(|(x, y)| read_pixel(x, y))
+        } else {
+            None
+        };
+    }
+
+    ///
+    /// Add the last sample to the HUD at `x`, `y`, with a swatch of its colour.
+    /// Returns the bottom edge of what was drawn.
+    ///
+    pub fn draw_hud(&self, overlay: &mut Overlay, x: f32, y: f32, near: f32, far: f32) -> f32 {
+        let sample = match self.sample {
+            Some(sample) => sample,
+            None => return y,
+        };
+
+        let scale = 2.0;
+        let line = LINE_HEIGHT * scale;
+        let padding = 6.0;
+        let lines = sample.describe(near, far);
+        let swatch_size = lines.len() as f32 * line;
+        let longest_line = lines.iter().map(|line| line.len()).max().unwrap_or(0);
+        let width = longest_line as f32 * GLYPH_ADVANCE * scale + swatch_size + 3.0 * padding;
+        let height = swatch_size + 2.0 * padding;
+        overlay.rect(x, y, width, height, PROBE_BACKGROUND_COLOUR);
+
+        let colour = [
+            sample.rgba[0] as f32 / 255.0, sample.rgba[1] as f32 / 255.0, sample.rgba[2] as f32 / 255.0, 1.0
+        ];
+        overlay.rect(x + padding, y + padding, swatch_size, swatch_size, colour);
+        let mut text_y = y + padding;
+        for text in lines.iter() {
+            overlay.text(x + swatch_size + 2.0 * padding, text_y, scale, PROBE_TEXT_COLOUR, text);
+            text_y += line;
+        }
+
+        y + height
+    }
+}
+
+mod pixel_probe_tests {
+    use super::{cursor_to_pixel, linearize_depth, PixelSample};
+
+    #[test]
+    fn test_linearize_depth_maps_the_clipping_planes_back() {
+        assert!(f32::abs(linearize_depth(0.0, 0.1, 100.0) - 0.1) < 1e-5);
+        assert!(f32::abs(linearize_depth(1.0, 0.1, 100.0) - 100.0) < 1e-2);
+    }
+
+    #[test]
+    fn test_linearize_depth_undoes_the_projection() {
+        // a point 5 units in front of the camera, through the same projection
+        let (near, far, z_eye) = (0.1, 100.0, -5.0);
+        let z_clip = -(far + near) / (far - near) * z_eye - 2.0 * far * near / (far - near);
+        let depth = 0.5 * (z_clip / -z_eye) + 0.5;
+
+        assert!(f32::abs(linearize_depth(depth, near, far) - 5.0) < 1e-3);
+    }
+
+    #[test]
+    fn test_the_cleared_depth_is_the_background() {
+        let sample = PixelSample { x: 0, y: 0, rgba: [0, 0, 0, 255], depth: 1.0 };
+
+        assert_eq!(sample.linear_depth(0.1, 100.0), None);
+        assert!(sample.describe(0.1, 100.0)[2].contains("background"));
+    }
+
+    #[test]
+    fn test_the_cursor_is_flipped_and_scaled_to_the_frame_buffer() {
+        // the top-left of the window is the top row of the frame buffer
+        assert_eq!(cursor_to_pixel((0.0, 0.0), (640, 480), (640, 480)), Some((0, 479)));
+        // a high DPI frame buffer has twice the pixels in each direction
+        assert_eq!(cursor_to_pixel((100.0, 100.0), (640, 480), (1280, 960)), Some((200, 759)));
+        assert_eq!(cursor_to_pixel((-1.0, 10.0), (640, 480), (640, 480)), None);
+        assert_eq!(cursor_to_pixel((10.0, 480.0), (640, 480), (640, 480)), None);
+    }
+}
@@ -4,21 +4,11 @@ in vec3 normal;
 in vec3 pos_eye;
 out vec4 frag_colour;
 
-// required fog variables
-vec3 fog_colour = vec3 (0.2, 0.2, 0.2);
-float min_fog_radius = 2.0;
-float max_fog_radius = 5.0;
+#include "fog.glsl"
 
 void main() {
 	frag_colour = vec4 (normal, 1.0);
 	
-	// work out distance from camera to point
-	float dist = length (-pos_eye);
-	// get a fog factor (thickness of fog) based on the distance
-	float fog_fac = (dist - min_fog_radius) / (max_fog_radius - min_fog_radius);
-	// constrain the fog factor between 0 and 1
-	fog_fac = clamp (fog_fac, 0.0, 1.0);
-
-	// blend the fog colour with the lighting colour, based on the fog factor
-	frag_colour.rgb = mix (frag_colour.rgb, fog_colour, fog_fac);
+	// blend the fog colour with the lighting colour, based on distance from the camera
+	frag_colour.rgb = apply_fog (frag_colour.rgb, pos_eye);
 }
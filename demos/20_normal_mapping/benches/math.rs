@@ -0,0 +1,53 @@
+//
+// Benchmarks for the shared maths code. Compare the scalar and SIMD matrix code
+// by running
+//
+//     cargo bench --bench math
+//     cargo bench --bench math --features simd
+//
+#[cfg(feature = "glam")]
+extern crate glam;
+#[cfg(feature = "cgmath")]
+extern crate cgmath;
+
+#[macro_use]
+extern crate criterion;
+
+#[allow(dead_code)]
+#[path = "../src/graphics_math.rs"]
+mod graphics_math;
+
+use criterion::{black_box, Criterion};
+use graphics_math as math;
+use math::{Mat4, Versor};
+
+
+fn model_view() -> Mat4 {
+    Mat4::from_rotation_x_deg(20.0)
+        .rotate_y_deg(-35.0)
+        .rotate_z_deg(70.0)
+        .then_translate(&math::vec3((3.0, -4.5, 12.0)))
+}
+
+fn mat4_benchmarks(c: &mut Criterion) {
+    let m = model_view();
+    let proj = Mat4::perspective(67.0, 1.5, 0.1, 100.0);
+    let v = math::vec4((1.5, -2.0, 0.25, 1.0));
+
+    c.bench_function("mat4 * mat4", |b| b.iter(|| black_box(proj) * black_box(m)));
+    c.bench_function("mat4 * vec4", |b| b.iter(|| black_box(m) * black_box(v)));
+    c.bench_function("mat4 inverse", |b| b.iter(|| black_box(m).inverse()));
+    c.bench_function("mat4 transpose", |b| b.iter(|| black_box(m).transpose()));
+}
+
+fn versor_benchmarks(c: &mut Criterion) {
+    let q = Versor::from_axis_deg(35.0, 0.0, 1.0, 0.0);
+    let r = Versor::from_axis_deg(-70.0, 1.0, 0.0, 0.0);
+    let unnormalized = q * 3.0;
+
+    c.bench_function("versor normalize", |b| b.iter(|| black_box(unnormalized).normalize()));
+    c.bench_function("versor * versor", |b| b.iter(|| black_box(q) * &black_box(r)));
+}
+
+criterion_group!(benches, mat4_benchmarks, versor_benchmarks);
+criterion_main!(benches);
@@ -0,0 +1,184 @@
+//
+// Benchmarks for loading meshes: parsing suzanne.obj and generating its tangents.
+//
+#[cfg(feature = "glam")]
+extern crate glam;
+#[cfg(feature = "cgmath")]
+extern crate cgmath;
+
+#[macro_use]
+extern crate criterion;
+
+#[allow(dead_code)]
+#[path = "../src/graphics_math.rs"]
+mod graphics_math;
+#[allow(dead_code)]
+#[path = "../src/geometry.rs"]
+mod geometry;
+#[allow(dead_code)]
+#[path = "../src/obj_parser.rs"]
+mod obj_parser;
+#[allow(dead_code)]
+#[path = "../src/colour.rs"]
+mod colour;
+
+use criterion::{black_box, Criterion};
+// The shared modules refer to the maths module by this name.
+use graphics_math as math;
+
+use std::fmt::Write;
+use std::io::Cursor;
+
+
+// Read the mesh into memory up front, so the parsing benchmark does not time the disk.
+const SUZANNE: &[u8] = include_bytes!("../src/suzanne.obj");
+
+///
+/// A flat grid of `quads_per_side` by `quads_per_side` quads, split into two
+/// triangles each, written out as a Wavefront *.obj file.
+///
+fn grid_obj(quads_per_side: usize) -> String {
+    let side = quads_per_side + 1;
+    let mut obj = String::new();
+    for row in 0..side {
+        for column in 0..side {
+            writeln!(obj, "v {} 0.0 {}", column as f32 * 0.01, row as f32 * -0.01).unwrap();
+            writeln!(obj, "vt {} {}", column as f32 / quads_per_side as f32, row as f32 / quads_per_side as f32).unwrap();
+        }
+    }
+    writeln!(obj, "vn 0.0 1.0 0.0").unwrap();
+    for row in 0..quads_per_side {
+        for column in 0..quads_per_side {
+            let corner = row * side + column + 1;
+            let (a, b, c, d) = (corner, corner + 1, corner + side + 1, corner + side);
+            writeln!(obj, "f {}/{}/1 {}/{}/1 {}/{}/1", a, a, b, b, c, c).unwrap();
+            writeln!(obj, "f {}/{}/1 {}/{}/1 {}/{}/1", a, a, c, c, d, d).unwrap();
+        }
+    }
+
+    obj
+}
+
+///
+/// The OBJ parser as it was before it read lines in place: it counts the file
+/// first, then reads it again a line at a time, splitting each line into owned
+/// strings and parsing every field with `str::parse`, as `scan_fmt` did. It is
+/// kept here only as a baseline for the parser in `obj_parser`, and reads only
+/// the `v`, `vt`, `vn`, and `f v/vt/vn` statements the grid is made of.
+///
+mod str_parse_obj_parser {
+    use std::io::{BufRead, Seek, SeekFrom};
+
+    pub struct Mesh {
+        pub points: Vec<f32>,
+        pub tex_coords: Vec<f32>,
+        pub normals: Vec<f32>,
+    }
+
+    fn fields(line: &str) -> Vec<String> {
+        line.split(|ch: char| ch.is_whitespace() || ch == '/')
+            .filter(|field| !field.is_empty())
+            .skip(1)
+            .map(String::from)
+            .collect()
+    }
+
+    fn parse_floats(line: &str, count: usize) -> Result<Vec<f32>, String> {
+        let values = fields(line).iter().map(|field| field.parse::<f32>().ok()).collect::<Vec<_>>();
+        if values.len() != count || values.iter().any(|value| value.is_none()) {
+            return Err(format!("Invalid vertex declaration: {}", line));
+        }
+
+        Ok(values.into_iter().map(|value| value.unwrap()).collect())
+    }
+
+    pub fn load_obj_mesh<T: BufRead + Seek>(reader: &mut T) -> Result<Mesh, String> {
+        let (mut vp_count, mut vt_count, mut vn_count) = (0, 0, 0);
+        for line in reader.lines().map(|st| st.unwrap()) {
+            if line.starts_with("v ") {
+                vp_count += 1;
+            } else if line.starts_with("vt ") {
+                vt_count += 1;
+            } else if line.starts_with("vn ") {
+                vn_count += 1;
+            }
+        }
+        reader.seek(SeekFrom::Start(0)).unwrap();
+
+        let mut vp = Vec::with_capacity(3 * vp_count);
+        let mut vt = Vec::with_capacity(2 * vt_count);
+        let mut vn = Vec::with_capacity(3 * vn_count);
+        let mut mesh = Mesh { points: vec![], tex_coords: vec![], normals: vec![] };
+        for line in reader.lines().map(|st| st.unwrap()) {
+            if line.starts_with("v ") {
+                vp.extend(parse_floats(&line, 3)?);
+            } else if line.starts_with("vt ") {
+                vt.extend(parse_floats(&line, 2)?);
+            } else if line.starts_with("vn ") {
+                vn.extend(parse_floats(&line, 3)?);
+            } else if line.starts_with("f ") {
+                let indices = fields(&line).iter().map(|field| field.parse::<usize>().ok()).collect::<Vec<_>>();
+                if indices.len() != 9 || indices.iter().any(|index| index.map_or(true, |index| index == 0)) {
+                    return Err(format!("Invalid mesh face declaration: {}", line));
+                }
+                for corner in indices.chunks(3) {
+                    let (p, t, n) = (corner[0].unwrap() - 1, corner[1].unwrap() - 1, corner[2].unwrap() - 1);
+                    if 3 * p >= vp.len() || 2 * t >= vt.len() || 3 * n >= vn.len() {
+                        return Err(format!("Invalid index in face: {}", line));
+                    }
+                    mesh.points.extend_from_slice(&vp[3 * p..3 * p + 3]);
+                    mesh.tex_coords.extend_from_slice(&vt[2 * t..2 * t + 2]);
+                    mesh.normals.extend_from_slice(&vn[3 * n..3 * n + 3]);
+                }
+            }
+        }
+
+        Ok(mesh)
+    }
+}
+
+fn obj_benchmarks(c: &mut Criterion) {
+    c.bench_function("parse suzanne.obj", |b| {
+        b.iter(|| obj_parser::load_obj_mesh(&mut Cursor::new(black_box(SUZANNE))).unwrap())
+    });
+
+    // Just over a million triangles.
+    let grid = grid_obj(708);
+    let mesh = obj_parser::load_obj_mesh(&mut Cursor::new(grid.as_bytes())).unwrap();
+    let baseline = str_parse_obj_parser::load_obj_mesh(&mut Cursor::new(grid.as_bytes())).unwrap();
+    assert!(mesh.points == baseline.points && mesh.tex_coords == baseline.tex_coords);
+    let mut group = c.benchmark_group("large meshes");
+    group.sample_size(10);
+    group.bench_function("parse 1M triangle grid", |b| {
+        b.iter(|| obj_parser::load_obj_mesh(&mut Cursor::new(black_box(grid.as_bytes()))).unwrap())
+    });
+    group.bench_function("parse 1M triangle grid with str::parse", |b| {
+        b.iter(|| str_parse_obj_parser::load_obj_mesh(&mut Cursor::new(black_box(grid.as_bytes()))).unwrap())
+    });
+    group.finish();
+}
+
+fn tangent_benchmarks(c: &mut Criterion) {
+    let mesh = obj_parser::load_obj_mesh(&mut Cursor::new(SUZANNE)).unwrap();
+    assert_eq!(mesh.tex_coords.len(), 2 * mesh.point_count);
+    let indices = geometry::shared_vertex_indices(&mesh.points, &mesh.tex_coords, &mesh.normals);
+
+    c.bench_function("suzanne shared vertex indices", |b| {
+        b.iter(|| geometry::shared_vertex_indices(
+            black_box(&mesh.points), black_box(&mesh.tex_coords), black_box(&mesh.normals)
+        ))
+    });
+    c.bench_function("suzanne indexed tangents", |b| {
+        b.iter(|| geometry::compute_tangents(
+            black_box(&mesh.points), black_box(&mesh.tex_coords), black_box(&mesh.normals), black_box(&indices)
+        ))
+    });
+    c.bench_function("suzanne unindexed tangents", |b| {
+        b.iter(|| geometry::compute_unindexed_tangents(
+            black_box(&mesh.points), black_box(&mesh.tex_coords), black_box(&mesh.normals)
+        ))
+    });
+}
+
+criterion_group!(benches, obj_benchmarks, tangent_benchmarks);
+criterion_main!(benches);
@@ -0,0 +1,176 @@
+use gl;
+use gl::types::{GLfloat, GLint, GLsizeiptr, GLuint, GLvoid};
+
+use colour::Colour;
+use gl_utils::create_programme_from_files;
+use graphics_math as math;
+use logger::Logger;
+use math::Mat4;
+use vertex_layout::VertexLayout;
+use gl_utils::uniform_location;
+
+use std::mem;
+
+
+const DEBUG_DRAW_VS_FILE: &str = "src/debug_draw_vs.glsl";
+const DEBUG_DRAW_FS_FILE: &str = "src/debug_draw_fs.glsl";
+
+// Position and colour for each vertex.
+const FLOATS_PER_VERTEX: usize = 6;
+
+pub const TANGENT_COLOUR: Colour = Colour::RED;
+pub const BITANGENT_COLOUR: Colour = Colour::GREEN;
+pub const NORMAL_COLOUR: Colour = Colour::BLUE;
+
+
+///
+/// Build the line vertices showing the tangent space at each vertex of a mesh:
+/// the tangent in red, the bitangent in green, and the normal in blue. The
+/// tangents have four components, with the handedness of the tangent space in `w`.
+///
+pub fn tangent_frame_lines(points: &[f32], normals: &[f32], tangents: &[f32], length: f32) -> Vec<GLfloat> {
+    let point_count = points.len() / 3;
+    let mut vertices = Vec::with_capacity(3 * 2 * FLOATS_PER_VERTEX * point_count);
+    for v_i in 0..point_count {
+        let p = math::vec3((points[3 * v_i], points[3 * v_i + 1], points[3 * v_i + 2]));
+        let n = math::vec3((normals[3 * v_i], normals[3 * v_i + 1], normals[3 * v_i + 2]));
+        let t = math::vec3((tangents[4 * v_i], tangents[4 * v_i + 1], tangents[4 * v_i + 2]));
+        // The same bitangent the vertex shader works out.
+        let b = n.cross(&t) * tangents[4 * v_i + 3];
+        let frame = [(t, TANGENT_COLOUR), (b, BITANGENT_COLOUR), (n, NORMAL_COLOUR)];
+        for &(axis, colour) in frame.iter() {
+            let end = p + axis * length;
+            vertices.extend_from_slice(&[p.v[0], p.v[1], p.v[2], colour.r, colour.g, colour.b]);
+            vertices.extend_from_slice(&[end.v[0], end.v[1], end.v[2], colour.r, colour.g, colour.b]);
+        }
+    }
+
+    vertices
+}
+
+///
+/// A `DebugDraw` holds a set of coloured line segments in world space and draws
+/// them over a scene. Unlike the overlay, the lines are kept between frames until
+/// `clear` is called, and are only copied to the GPU again when they change.
+///
+pub struct DebugDraw {
+    sp: GLuint,
+    view_mat_loc: GLint,
+    proj_mat_loc: GLint,
+    vao: GLuint,
+    vbo: GLuint,
+    vertices: Vec<GLfloat>,
+    uploaded_vertex_count: usize,
+    dirty: bool,
+}
+
+impl DebugDraw {
+    pub fn new(logger: &Logger) -> DebugDraw {
+        let sp = create_programme_from_files(logger, DEBUG_DRAW_VS_FILE, DEBUG_DRAW_FS_FILE);
+        let view_mat_loc = uniform_location(sp, "view");
+        assert!(view_mat_loc > -1);
+        let proj_mat_loc = uniform_location(sp, "proj");
+        assert!(proj_mat_loc > -1);
+
+        let mut vbo = 0;
+        let mut vao = 0;
+        unsafe {
+            gl::GenBuffers(1, &mut vbo);
+            gl::GenVertexArrays(1, &mut vao);
+        }
+        VertexLayout::new().floats(0, 3).floats(1, 3).apply(vao, vbo);
+        assert!(vbo > 0);
+        assert!(vao > 0);
+
+        DebugDraw {
+            sp: sp,
+            view_mat_loc: view_mat_loc,
+            proj_mat_loc: proj_mat_loc,
+            vao: vao,
+            vbo: vbo,
+            vertices: vec![],
+            uploaded_vertex_count: 0,
+            dirty: false,
+        }
+    }
+
+    ///
+    /// Add a line segment from `from` to `to`. The lines are opaque, so the
+    /// colour's alpha is left out.
+    ///
+    pub fn line(&mut self, from: [f32; 3], to: [f32; 3], colour: Colour) {
+        self.vertices.extend_from_slice(&[from[0], from[1], from[2], colour.r, colour.g, colour.b]);
+        self.vertices.extend_from_slice(&[to[0], to[1], to[2], colour.r, colour.g, colour.b]);
+        self.dirty = true;
+    }
+
+    ///
+    /// Add line vertices built elsewhere, such as by `tangent_frame_lines`.
+    ///
+    pub fn lines(&mut self, vertices: &[GLfloat]) {
+        self.vertices.extend_from_slice(vertices);
+        self.dirty = true;
+    }
+
+    ///
+    /// Remove all of the line segments.
+    ///
+    pub fn clear(&mut self) {
+        self.vertices.clear();
+        self.dirty = true;
+    }
+
+    ///
+    /// Draw the line segments with the given camera. The lines are depth tested
+    /// against the scene, so they should be drawn after it.
+    ///
+    pub fn draw(&mut self, view_mat: &Mat4, proj_mat: &Mat4) {
+        unsafe {
+            if self.dirty {
+                gl::BindBuffer(gl::ARRAY_BUFFER, self.vbo);
+                gl::BufferData(
+                    gl::ARRAY_BUFFER, (self.vertices.len() * mem::size_of::<GLfloat>()) as GLsizeiptr,
+                    self.vertices.as_ptr() as *const GLvoid, gl::STATIC_DRAW
+                );
+                self.uploaded_vertex_count = self.vertices.len() / FLOATS_PER_VERTEX;
+                self.dirty = false;
+            }
+            if self.uploaded_vertex_count == 0 {
+                return;
+            }
+
+            gl::UseProgram(self.sp);
+            gl::UniformMatrix4fv(self.view_mat_loc, 1, gl::FALSE, view_mat.as_ptr());
+            gl::UniformMatrix4fv(self.proj_mat_loc, 1, gl::FALSE, proj_mat.as_ptr());
+            gl::BindVertexArray(self.vao);
+            gl::DrawArrays(gl::LINES, 0, self.uploaded_vertex_count as i32);
+        }
+    }
+}
+
+mod debug_draw_tests {
+    use super::{tangent_frame_lines, FLOATS_PER_VERTEX};
+
+    #[test]
+    fn test_tangent_frame_lines_has_three_lines_per_vertex() {
+        let points = [0.0, 0.0, 0.0, 1.0, 0.0, 0.0];
+        let normals = [0.0, 0.0, 1.0, 0.0, 0.0, 1.0];
+        let tangents = [1.0, 0.0, 0.0, 1.0, 1.0, 0.0, 0.0, 1.0];
+        let vertices = tangent_frame_lines(&points, &normals, &tangents, 0.5);
+
+        assert_eq!(vertices.len(), 2 * 3 * 2 * FLOATS_PER_VERTEX);
+    }
+
+    #[test]
+    fn test_tangent_frame_lines_follow_handedness() {
+        let points = [0.0, 0.0, 0.0];
+        let normals = [0.0, 0.0, 1.0];
+        let right_handed = tangent_frame_lines(&points, &normals, &[1.0, 0.0, 0.0, 1.0], 1.0);
+        let left_handed = tangent_frame_lines(&points, &normals, &[1.0, 0.0, 0.0, -1.0], 1.0);
+
+        // The end of the bitangent line is the fourth vertex.
+        let end = 3 * FLOATS_PER_VERTEX;
+        assert_eq!(&right_handed[end..end + 3], &[0.0, 1.0, 0.0]);
+        assert_eq!(&left_handed[end..end + 3], &[0.0, -1.0, 0.0]);
+    }
+}
@@ -0,0 +1,14 @@
+#version 410
+
+// World-space debug lines, coloured per vertex.
+layout (location = 0) in vec3 vp;
+layout (location = 1) in vec3 vc;
+
+uniform mat4 view, proj;
+
+out vec3 colour;
+
+void main() {
+	colour = vc;
+	gl_Position = proj * view * vec4 (vp, 1.0);
+}
@@ -3,182 +3,123 @@ extern crate glfw;
 extern crate chrono;
 extern crate stb_image;
 extern crate png;
+#[cfg(feature = "assimp")]
 extern crate assimp;
-
-#[macro_use] 
-extern crate scan_fmt;
-
+#[cfg(feature = "gltf")]
+extern crate gltf;
+#[cfg(feature = "glam")]
+extern crate glam;
+#[cfg(feature = "cgmath")]
+extern crate cgmath;
+
+#[macro_use]
 mod gl_utils;
 mod graphics_math;
 mod obj_parser;
+mod colour;
+mod assets;
+mod shader_include;
+mod spirv;
+mod program_cache;
+mod shader_program;
 mod logger;
+mod material;
+mod model;
+mod mesh_cache;
+mod resource_cache;
+mod debug_draw;
+mod geometry;
+mod overlay;
+mod streaming_buffer;
+mod perf_stats;
+mod gpu_timer;
+mod vertex_layout;
+mod vertex_packing;
+mod normal_bake;
+// The demo driver and its clock are shared with the other demos that use them.
+#[path = "../../shared/demo_app.rs"]
+mod demo_app;
+#[path = "../../shared/frame_clock.rs"]
+mod frame_clock;
+mod camera;
+mod input_replay;
+mod help_overlay;
+mod tweak;
+
+
+use glfw::Key;
+use gl::types::{GLfloat, GLint, GLvoid, GLuint};
 
-
-use glfw::{Action, Context, Key};
-use gl::types::{GLfloat, GLsizeiptr, GLvoid, GLuint};
-
-use std::mem;
-use std::ptr;
 use std::process;
+use std::time::Instant;
 
-use stb_image::image;
 use stb_image::image::LoadResult;
 
 use gl_utils::*;
 
 use graphics_math as math;
-use math::Mat4;
-
-use assimp::import as ai;
+use math::{Mat4, Versor};
+
+use colour::Colour;
+use obj_parser::Material;
+use material::{MaterialBinder, MaterialTextures};
+use model::{Model, PackedVertex, Vertex, VertexFormat};
+use debug_draw::DebugDraw;
+use overlay::Overlay;
+use perf_stats::{PerfStats, PERF_STATS_HISTORY};
+use gpu_timer::GpuTimer;
+use shader_program::ShaderProgram;
+use vertex_layout::VertexLayout;
+use logger::Logger;
+use demo_app::{run_demo, DemoApp, Input, KeyBindings};
+use frame_clock::FrameClock;
+use camera::Camera;
+use tweak::Tweaks;
 
 
 const GL_LOG_FILE: &str = "gl.log";
 const VERTEX_SHADER_FILE: &str = "src/test.vert.glsl";
 const FRAGMENT_SHADER_FILE: &str = "src/test.frag.glsl";
 const MESH_FILE: &str = "src/suzanne.obj";
+const MATERIAL_FILE: &str = "src/suzanne.mtl";
 const NMAP_IMG_FILE: &str = "src/brickwork_normal-map.png";
+// The smallest width and height of the object-space normal map baked at load time.
+const OBJECT_SPACE_MAP_SIZE: usize = 1024;
+const HEIGHT_MAP_IMG_FILE: &str = "src/brickwork_height-map.png";
+// The length of the lines showing the tangent space at each vertex.
+const TBN_LINE_LENGTH: f32 = 0.05;
+// The depth of the parallax occlusion mapped surface, and how far each key press changes it.
+const DEFAULT_HEIGHT_SCALE: f32 = 0.05;
+const HEIGHT_SCALE_STEP: f32 = 0.01;
+const MAX_HEIGHT_SCALE: f32 = 0.2;
+// The range of layers parallax occlusion mapping steps through, doubled or halved by key presses.
+const DEFAULT_POM_MIN_SAMPLES: i32 = 8;
+const DEFAULT_POM_MAX_SAMPLES: i32 = 32;
+const MAX_POM_SAMPLES: i32 = 256;
+// The camera's vertical field of view in degrees, and its clipping planes.
+const FOVY: f32 = 67.0;
+const NEAR: f32 = 0.1;
+const FAR: f32 = 100.0;
 
 const GL_TEXTURE_MAX_ANISOTROPY_EXT: u32 = 0x84FE;
 const GL_MAX_TEXTURE_MAX_ANISOTROPY_EXT: u32 = 0x84FF;
 
 
-fn calc_tangent_space() -> ai::structs::CalcTangentSpace {
-    ai::structs::CalcTangentSpace {
-        enable: true,
-        max_smoothing_angle: 45.0,
-        texture_channel: 0,
-    }
-}
-
-struct AiMesh {
-    vp: Vec<f32>,
-    vn: Vec<f32>,
-    vt: Vec<f32>,
-    vtans: Vec<f32>,
-    point_count: u32,
-}
-
-fn load_mesh(file_name: &str) -> Result<AiMesh, String> {
-    let mut importer = ai::Importer::new();
-    importer.calc_tangent_space(|calc| {});
-    let scene = match importer.read_file(file_name) {
-        Ok(val) => val,
-        Err(_) => {
-            eprintln!("ERROR: reading mesh {}", file_name);
-            return Err(format!("ERROR: reading mesh {}", file_name));
-        }
-    };
-
-    println!("  {} animations", scene.num_animations());
-    println!("  {} cameras", scene.num_cameras());
-    println!("  {} lights", scene.num_lights());
-    println!("  {} materials", scene.num_materials());
-    println!("  {} meshes", scene.num_meshes());
-    println!("  {} textures", scene.num_textures());
-
-    // get first mesh only
-    let mesh = match scene.mesh(0) {
-        Some(val) => val,
-        None => {
-            eprintln!("ERROR: scene \"{}\" has not meshes.", file_name);
-            return Err(format!("ERROR: scene \"{}\" has not meshes.", file_name));
-        }
-    };
-    println!("    {} vertices in mesh[0]", mesh.num_vertices());
-    
-    let mut g_vp: Vec<GLfloat> = vec![];
-    let mut g_vn: Vec<GLfloat> = vec![];
-    let mut g_vt: Vec<GLfloat> = vec![];
-    let mut g_vtans: Vec<GLfloat> = vec![];
-    let g_point_count = mesh.num_vertices();
-
-    // allocate memory for vertex points
-    if mesh.has_positions() {
-        println!("mesh has positions");
-        g_vp = vec![0.0; 3 * (g_point_count as usize) * mem::size_of::<GLfloat>()];
-    }
-    if mesh.has_normals() {
-        println!("mesh has normals");
-        g_vn = vec![0.0; 3 * (g_point_count as usize) * mem::size_of::<GLfloat>()];
-    }
-    if mesh.has_texture_coords(0) {
-        println!("mesh has texture coords");
-        g_vt = vec![0.0; 2 * (g_point_count as usize) * mem::size_of::<GLfloat>()];
-    }
-    if mesh.has_tangents_and_bitangents() {
-        println!("mesh has tangents");
-        g_vtans = vec![0.0; 4 * (g_point_count as usize) * mem::size_of::<GLfloat>()];
-    }
-
-    for v_i in 0..mesh.num_vertices() as usize {
-        if mesh.has_positions() {
-            let vp = mesh.get_vertex(v_i as u32).unwrap();
-            g_vp[3 * v_i] = vp.x;
-            g_vp[3 * v_i + 1] = vp.y;
-            g_vp[3 * v_i + 2] = vp.z;
-        }
-        if mesh.has_normals() {
-            let vn = mesh.get_normal(v_i as u32).unwrap();
-            g_vn[3 * v_i] = vn.x;
-            g_vn[3 * v_i + 1] = vn.y;
-            g_vn[3 * v_i + 2] = vn.z;
-        }
-        if mesh.has_texture_coords(0) {
-            let vt = mesh.get_texture_coord(0, v_i as u32).unwrap();
-            g_vt[2 * v_i] = vt.x;
-            g_vt[2 * v_i + 1] = vt.y;
-        }
-        if mesh.has_tangents_and_bitangents() {
-            let tangent = mesh.get_tangent(v_i as u32).unwrap();
-            let bitangent = mesh.get_bitangent(v_i as u32).unwrap();
-            let normal = mesh.get_normal(v_i as u32).unwrap();
-
-            // put the three vectors into my vec3 struct format for doing maths
-            let t = math::vec3((tangent.x, tangent.y, tangent.z));
-            let n = math::vec3((normal.x, normal.y, normal.z));
-            let b = math::vec3((bitangent.x, bitangent.y, bitangent.z));
-            // orthogonalise and normalise the tangent so we can use it in something
-            // approximating a T,N,B inverse matrix
-            let t_i = (t - n * n.dot(&t)).normalize();
-
-            // get determinant of T,B,N 3x3 matrix by dot*cross method
-            let mut det = (n.cross(&t)).dot(&b);
-            if det < 0.0 {
-                det = -1.0;
-            } else {
-                det = 1.0;
-            }
-
-            // push back 4d vector for inverse tangent with determinant
-            g_vtans[4 * v_i] = t_i.v[0];
-            g_vtans[4 * v_i + 1] = t_i.v[1];
-            g_vtans[4 * v_i + 2] = t_i.v[2];
-            g_vtans[4 * v_i + 3] = det;
-        }
-    }
-
-    println!("mesh loaded");
-
-    return Ok(AiMesh {
-        vp: g_vp,
-        vn: g_vn,
-        vt: g_vt,
-        vtans: g_vtans,
-        point_count: g_point_count,
-    });
-}
-
-fn load_texture(file_name: &str, tex: &mut GLuint) -> bool {
+///
+/// Load an image file as RGBA bytes, flipped so the bottom row comes first as GL
+/// reads it. Returns the bytes, the width, and the height.
+///
+fn load_image(file_name: &str) -> Option<(Vec<u8>, usize, usize)> {
     let force_channels = 4;
-    let mut image_data = match image::load_with_depth(file_name, force_channels, false) {
+    let mut image_data = match assets::load_image_with_depth(file_name, force_channels) {
         LoadResult::ImageU8(image_data) => image_data,
         LoadResult::Error(_) => {
             eprintln!("ERROR: could not load {}", file_name);
-            return false;
+            return None;
         }
         LoadResult::ImageF32(_) => {
             eprintln!("ERROR: Tried to load an image as byte vectors, got f32: {}", file_name);
-            return false;
+            return None;
         }
     };
 
@@ -200,6 +141,15 @@ fn load_texture(file_name: &str, tex: &mut GLuint) -> bool {
         }
     }
 
+    Some((image_data.data, width, height))
+}
+
+///
+/// Upload RGBA bytes, bottom row first, into a new texture. Without mipmaps the
+/// texture is only ever filtered between neighbouring texels.
+///
+fn upload_texture(data: &[u8], width: usize, height: usize, mipmaps: bool, tex: &mut GLuint) {
+    let min_filter = if mipmaps { gl::LINEAR_MIPMAP_LINEAR } else { gl::LINEAR };
     unsafe {
         gl::GenTextures(1, tex);
         gl::ActiveTexture(gl::TEXTURE0);
@@ -207,13 +157,15 @@ fn load_texture(file_name: &str, tex: &mut GLuint) -> bool {
         gl::TexImage2D(
             gl::TEXTURE_2D, 0, gl::RGBA as i32, width as i32, height as i32, 0, 
             gl::RGBA, gl::UNSIGNED_BYTE, 
-            image_data.data.as_ptr() as *const GLvoid
+            data.as_ptr() as *const GLvoid
         );
-        gl::GenerateMipmap(gl::TEXTURE_2D);
+        if mipmaps {
+            gl::GenerateMipmap(gl::TEXTURE_2D);
+        }
         gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as i32);
         gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as i32);
         gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::LINEAR as i32);
-        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::LINEAR_MIPMAP_LINEAR as i32);
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, min_filter as i32);
     }
 
     let mut max_aniso = 0.0;
@@ -223,253 +175,788 @@ fn load_texture(file_name: &str, tex: &mut GLuint) -> bool {
         // Set the maximum!
         gl::TexParameterf(gl::TEXTURE_2D, GL_TEXTURE_MAX_ANISOTROPY_EXT, max_aniso);
     }
-
-    return true;
 }
 
-fn main() {
-    let logger = restart_gl_log(GL_LOG_FILE);
-    // Start a GL context and O/S window using the GLFW helper library.
-    let mut context = match start_gl(&logger) {
-        Ok(val) => val,
-        Err(e) => {
-            eprintln!("Failed to Initialize OpenGL context. Got error:");
-            eprintln!("{}", e);
-            process::exit(1);
+fn load_texture(file_name: &str, tex: &mut GLuint) -> bool {
+    match load_image(file_name) {
+        Some((data, width, height)) => {
+            upload_texture(&data, width, height, true, tex);
+            true
         }
+        None => false,
+    }
+}
+
+///
+/// Pick the defines for the shader variant that lights the surface the way the
+/// keys ask for, and describe it for the log. The object-space map is baked from
+/// the tangent-space one at load time, so the two should light the mesh alike.
+/// Parallax occlusion mapping only works with the tangent-space map, so it is
+/// left out of the other variants.
+///
+fn normal_map_defines(
+    normal_mapping: bool, object_space: bool, parallax: bool) -> ([(&'static str, &'static str); 3], &'static str) {
+
+    let (use_normal_map, object_space_normal_map, use_parallax, description) = if !normal_mapping {
+        ("0", "0", "0", "off")
+    } else if object_space {
+        ("1", "1", "0", "object space, baked at load time")
+    } else if parallax {
+        ("1", "0", "1", "tangent space with parallax occlusion")
+    } else {
+        ("1", "0", "0", "tangent space")
     };
+    let defines = [
+        ("USE_NORMAL_MAP", use_normal_map),
+        ("OBJECT_SPACE_NORMAL_MAP", object_space_normal_map),
+        ("USE_PARALLAX", use_parallax),
+    ];
 
-    // Tell GL to only draw onto a pixel if the shape is closer to the viewer.
-    unsafe {
-        // Enable depth testing.
-        gl::Enable(gl::DEPTH_TEST);
-        // Depth testing interprets a smaller value as closer to the eye.
-        gl::DepthFunc(gl::LESS);
+    (defines, description)
+}
+
+///
+/// The locations of the transformation matrices in a shader programme. Each
+/// shader variant is a programme of its own, with locations of its own.
+///
+struct MatrixUniforms {
+    model_mat_location: GLint,
+    view_mat_location: GLint,
+    proj_mat_location: GLint,
+}
+
+impl MatrixUniforms {
+    fn new(sp: GLuint) -> MatrixUniforms {
+        let get_location = |name: &str| uniform_location(sp, name);
+        let uniforms = MatrixUniforms {
+            model_mat_location: get_location("model"),
+            view_mat_location: get_location("view"),
+            proj_mat_location: get_location("proj"),
+        };
+        assert!(uniforms.model_mat_location > -1);
+        assert!(uniforms.view_mat_location > -1);
+        assert!(uniforms.proj_mat_location > -1);
+
+        uniforms
     }
 
-    /*------------------------------CREATE GEOMETRY------------------------------*/
-    let mesh = match load_mesh(MESH_FILE) {
-        Ok(val) => val,
-        Err(e) => {
-            logger.log_err(&format!("ERROR: loading mesh file. Loader returned error\n{}", e));
-            process::exit(1);
+    fn upload(&self, sp: GLuint, model_mat: &Mat4, view_mat: &Mat4, proj_mat: &Mat4) {
+        unsafe {
+            gl::UseProgram(sp);
+            gl::UniformMatrix4fv(self.model_mat_location, 1, gl::FALSE, model_mat.as_ptr());
+            gl::UniformMatrix4fv(self.view_mat_location, 1, gl::FALSE, view_mat.as_ptr());
+            gl::UniformMatrix4fv(self.proj_mat_location, 1, gl::FALSE, proj_mat.as_ptr());
         }
-    };
+    }
+}
 
-    let g_vp = mesh.vp;
-    let g_vn = mesh.vn;
-    let g_vt = mesh.vt;
-    let g_vtans = mesh.vtans;
-    let g_point_count = mesh.point_count as usize;
+///
+/// The current settings of parallax occlusion mapping.
+///
+struct ParallaxSettings {
+    height_scale: f32,
+    min_samples: i32,
+    max_samples: i32,
+}
+
+impl ParallaxSettings {
+    fn new() -> ParallaxSettings {
+        ParallaxSettings {
+            height_scale: DEFAULT_HEIGHT_SCALE,
+            min_samples: DEFAULT_POM_MIN_SAMPLES,
+            max_samples: DEFAULT_POM_MAX_SAMPLES,
+        }
+    }
+
+    ///
+    /// Upload the settings to the shader programme. The variants without parallax
+    /// occlusion mapping have none of these uniforms, and GL ignores the uploads.
+    ///
+    fn upload(&self, sp: GLuint) {
+        let get_location = |name: &str| uniform_location(sp, name);
+        unsafe {
+            gl::UseProgram(sp);
+            gl::Uniform1f(get_location("height_scale"), self.height_scale);
+            gl::Uniform1i(get_location("pom_min_samples"), self.min_samples);
+            gl::Uniform1i(get_location("pom_max_samples"), self.max_samples);
+        }
+    }
+
+    fn change_height_scale(&mut self, delta: f32) {
+        self.height_scale = f32::min(f32::max(self.height_scale + delta, 0.0), MAX_HEIGHT_SCALE);
+    }
 
+    ///
+    /// Double or halve the number of layers, keeping the head on count no larger
+    /// than the edge on count.
+    ///
+    fn change_min_samples(&mut self, more: bool) {
+        let samples = if more { 2 * self.min_samples } else { self.min_samples / 2 };
+        self.min_samples = i32::min(i32::max(samples, 1), self.max_samples);
+    }
+
+    fn change_max_samples(&mut self, more: bool) {
+        let samples = if more { 2 * self.max_samples } else { self.max_samples / 2 };
+        self.max_samples = i32::min(i32::max(samples, 1), MAX_POM_SAMPLES);
+        self.min_samples = i32::min(self.min_samples, self.max_samples);
+    }
+
+    fn describe(&self) -> String {
+        format!(
+            "height scale {:.2}  samples {}-{}", self.height_scale, self.min_samples, self.max_samples
+        )
+    }
+}
+
+///
+/// Upload each of a model's attributes into a vertex buffer of its own. Models
+/// without texture coordinates or tangents leave those attributes disabled.
+/// Returns the vertex array and the number of bytes uploaded.
+///
+fn make_separate_vao(mesh: &Model) -> (GLuint, usize) {
     let mut vao = 0;
     unsafe {
         gl::GenVertexArrays(1, &mut vao);
-        gl::BindVertexArray(vao);
     }
     assert!(vao > 0);
 
-    let mut points_vbo = 0;
-    unsafe {
-        gl::GenBuffers(1, &mut points_vbo);
-        gl::BindBuffer(gl::ARRAY_BUFFER, points_vbo);
-        gl::BufferData(
-            gl::ARRAY_BUFFER, (3 * g_point_count * mem::size_of::<GLfloat>()) as GLsizeiptr, 
-            g_vp.as_ptr() as *const GLvoid, gl::STATIC_DRAW
-        );
-        gl::VertexAttribPointer(0, 3, gl::FLOAT, gl::FALSE, 0, ptr::null());
-        gl::EnableVertexAttribArray(0);
+    let mut layouts = vec![
+        (VertexLayout::new().floats(0, 3), &mesh.points),
+        (VertexLayout::new().floats(1, 3), &mesh.normals),
+    ];
+    if mesh.tex_coords.len() == 2 * mesh.point_count {
+        layouts.push((VertexLayout::new().floats(2, 2), &mesh.tex_coords));
     }
-    assert!(points_vbo > 0);
+    if mesh.tangents.len() == 4 * mesh.point_count {
+        layouts.push((VertexLayout::new().floats(3, 4), &mesh.tangents));
+    }
+    let mut size = 0;
+    for &(ref layout, data) in layouts.iter() {
+        layout.upload(vao, data, mesh.point_count);
+        size += layout.size_of_vertices(mesh.point_count);
+    }
+
+    (vao, size)
+}
 
-    let mut normals_vbo = 0;
+///
+/// Upload interleaved vertices into a single vertex buffer, laid out the way the
+/// vertex type describes itself.
+///
+fn make_interleaved_vao<V: VertexFormat>(vertices: &[V]) -> GLuint {
+    let mut vao = 0;
     unsafe {
-        gl::GenBuffers( 1, &mut normals_vbo);
-        gl::BindBuffer(gl::ARRAY_BUFFER, normals_vbo);
-        gl::BufferData(
-            gl::ARRAY_BUFFER, (3 * g_point_count * mem::size_of::<GLfloat>()) as GLsizeiptr, 
-            g_vn.as_ptr() as *const GLvoid, gl::STATIC_DRAW
-        );
-        gl::VertexAttribPointer(1, 3, gl::FLOAT, gl::FALSE, 0, ptr::null());
-        gl::EnableVertexAttribArray(1);
+        gl::GenVertexArrays(1, &mut vao);
     }
-    assert!(normals_vbo > 0);
+    assert!(vao > 0);
+    V::layout().upload(vao, vertices, vertices.len());
 
-    let mut texcoords_vbo = 0;
-    unsafe {
-        gl::GenBuffers( 1, &mut texcoords_vbo);
-        gl::BindBuffer(gl::ARRAY_BUFFER, texcoords_vbo);
-        gl::BufferData(
-            gl::ARRAY_BUFFER, (2 * g_point_count * mem::size_of::<GLfloat>()) as GLsizeiptr, 
-            g_vt.as_ptr() as *const GLvoid, gl::STATIC_DRAW
-        );
-        gl::VertexAttribPointer(2, 2, gl::FLOAT, gl::FALSE, 0, ptr::null());
-        gl::EnableVertexAttribArray(2);
+    vao
+}
+
+///
+/// The ways the demo can store the mesh's vertices. I cycles through them.
+///
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum VertexStorage {
+    Separate,
+    Interleaved,
+    Packed,
+}
+
+impl VertexStorage {
+    fn next(&self) -> VertexStorage {
+        match *self {
+            VertexStorage::Separate => VertexStorage::Interleaved,
+            VertexStorage::Interleaved => VertexStorage::Packed,
+            VertexStorage::Packed => VertexStorage::Separate,
+        }
     }
-    assert!(texcoords_vbo > 0);
 
-    let mut tangents_vbo = 0;
-    unsafe {
-        gl::GenBuffers(1, &mut tangents_vbo);
-        gl::BindBuffer(gl::ARRAY_BUFFER, tangents_vbo);
-        gl::BufferData(
-            gl::ARRAY_BUFFER, (4 * g_point_count * mem::size_of::<GLfloat>()) as GLsizeiptr, 
-            g_vtans.as_ptr() as *const GLvoid, gl::STATIC_DRAW
-        );
-        gl::VertexAttribPointer(3, 4, gl::FLOAT, gl::FALSE, 0, ptr::null());
-        gl::EnableVertexAttribArray(3);
-    }
-    assert!(tangents_vbo > 0);
-    println!("Created tangents_vbo.");
-    let shader_programme = create_programme_from_files(&logger, VERTEX_SHADER_FILE, FRAGMENT_SHADER_FILE);
-
-    // input variables
-    let near = 0.1;                                  // clipping plane
-    let far = 100.0;                                 // clipping plane
-    let fov = 67.0;                                  // convert 67 degrees to radians
-    let aspect = context.width as f32 / context.height as f32; // aspect ratio
-    let proj_mat = Mat4::perspective(fov, aspect, near, far);
-
-    // matrix components
-    let cam_speed: GLfloat = 1.0;             // 1 unit per second
-    let cam_yaw_speed: GLfloat = 10.0;        // 10 degrees per second
-    let mut cam_pos: [GLfloat; 3] = [0.0, 0.0, 5.0]; // don't start at zero, or we will be too close
-    let mut cam_yaw: GLfloat = 0.0;               // y-rotation in degrees
-    let mut mat_trans = Mat4::identity().translate(&math::vec3((-cam_pos[0], -cam_pos[1], -cam_pos[2])));
-    let mut mat_rot = Mat4::identity().rotate_y_deg(-cam_yaw);
-    let mut view_mat = mat_rot * mat_trans;
-
-    let model_mat_location = unsafe {
-        gl::GetUniformLocation(shader_programme, "model".as_ptr() as *const i8)
-    };
-    assert!(model_mat_location > -1);
+    fn describe(&self) -> &'static str {
+        match *self {
+            VertexStorage::Separate => "in 4 separate buffers",
+            VertexStorage::Interleaved => "interleaved in 1 buffer",
+            VertexStorage::Packed => "packed and interleaved in 1 buffer",
+        }
+    }
+}
 
-    let view_mat_location = unsafe {
-        gl::GetUniformLocation(shader_programme, "view".as_ptr() as *const i8)
-    };
-    assert!(view_mat_location > -1);
+///
+/// How far packing moves the vertices: the largest angle in degrees between a
+/// normal and its packed version, and the largest difference in a texture
+/// coordinate.
+///
+fn packing_error(vertices: &[Vertex], packed: &[PackedVertex]) -> (f32, f32) {
+    let mut normal_error: f32 = 0.0;
+    let mut uv_error: f32 = 0.0;
+    for (vertex, packed) in vertices.iter().zip(packed.iter()) {
+        let unpacked = packed.unpack();
+        let a = math::vec3((vertex.normal[0], vertex.normal[1], vertex.normal[2]));
+        let b = math::vec3((unpacked.normal[0], unpacked.normal[1], unpacked.normal[2]));
+        if a.norm() > 0.0 && b.norm() > 0.0 {
+            let cos_angle = f32::min(f32::max(a.normalize().dot(&b.normalize()), -1.0), 1.0);
+            normal_error = f32::max(normal_error, cos_angle.acos() * math::ONE_RAD_IN_DEG);
+        }
+        for i in 0..2 {
+            uv_error = f32::max(uv_error, (vertex.uv[i] - unpacked.uv[i]).abs());
+        }
+    }
 
-    let proj_mat_location = unsafe { 
-        gl::GetUniformLocation(shader_programme, "proj".as_ptr() as *const i8)
-    };
-    assert!(proj_mat_location > -1);
+    (normal_error, uv_error)
+}
 
-    let model_mat = Mat4::identity();
-    unsafe {
-        gl::UseProgram(shader_programme);
-        gl::UniformMatrix4fv(model_mat_location, 1, gl::FALSE, model_mat.as_ptr());
-        gl::UniformMatrix4fv(view_mat_location, 1, gl::FALSE, view_mat.as_ptr());
-        gl::UniformMatrix4fv(proj_mat_location, 1, gl::FALSE, proj_mat.as_ptr());
+///
+/// A mesh lit with normal maps and parallax occlusion mapping, with keys to
+/// switch between the ways of lighting it and of storing its vertices.
+///
+struct NormalMappingDemo {
+    logger: Logger,
+
+    separate_vao: GLuint,
+    interleaved_vao: GLuint,
+    packed_vao: GLuint,
+    // the mesh laid out over the baked object-space normal map, and the map
+    object_space_vao: GLuint,
+    baked_normal_map: GLuint,
+    separate_bytes: usize,
+    interleaved_bytes: usize,
+    packed_bytes: usize,
+    normal_error: f32,
+    vertex_storage: VertexStorage,
+    point_count: usize,
+
+    shader_program: ShaderProgram,
+    shader_programme: GLuint,
+    normal_mapping: bool,
+    object_space_normal_map: bool,
+    parallax: bool,
+    normal_map_mode_text: &'static str,
+    matrix_uniforms: MatrixUniforms,
+    parallax_settings: ParallaxSettings,
+    material: Material,
+    textures: MaterialTextures,
+    material_binder: MaterialBinder,
+
+    // lines showing the tangent, bitangent, and normal at each vertex
+    debug_draw: DebugDraw,
+    show_tangent_space: bool,
+    // the parallax height scale, the material, and whether the tangent space shows
+    tweaks: Tweaks,
+
+    model_mat: Mat4,
+    view_mat: Mat4,
+    proj_mat: Mat4,
+    cam_speed: GLfloat,
+    cam_yaw_speed: GLfloat,
+    cam_pos: [GLfloat; 3],
+    cam_yaw: GLfloat,
+    fovy: GLfloat,
+
+    overlay: Overlay,
+    perf_stats: PerfStats,
+    show_perf_stats: bool,
+    mesh_timer: GpuTimer,
+    frame_start: Instant,
+}
+
+impl NormalMappingDemo {
+    ///
+    /// The textures the material is drawn with: the baked object-space normal map
+    /// in place of the tangent-space one when the shader reads it in object space.
+    ///
+    fn bound_textures(&self) -> MaterialTextures {
+        let mut textures = self.textures;
+        if self.normal_mapping && self.object_space_normal_map {
+            textures.normal_map = self.baked_normal_map;
+        }
+
+        textures
     }
 
-    // load normal map image into texture
-    let mut nmap_tex = 0;
-    load_texture(NMAP_IMG_FILE, &mut nmap_tex);
+    ///
+    /// Rebuild the view matrix from the camera's position and heading, and upload it.
+    ///
+    fn update_view_mat(&mut self) {
+        let cam_pos = self.cam_pos;
+        let mat_trans = Mat4::from_translation(&math::vec3((-cam_pos[0], -cam_pos[1], -cam_pos[2]))); // cam translation
+        let mat_rot = Mat4::from_rotation_y_deg(-self.cam_yaw);
+        self.view_mat = mat_rot * mat_trans;
+        unsafe {
+            gl::UseProgram(self.shader_programme);
+            gl::UniformMatrix4fv(self.matrix_uniforms.view_mat_location, 1, gl::FALSE, self.view_mat.as_ptr());
+        }
+    }
 
-    unsafe {
-        // Cull face.
-        gl::Enable(gl::CULL_FACE);
-        // Cull back face.
-        gl::CullFace(gl::BACK);
-        // GL_CW for clockwise.    
-        gl::FrontFace(gl::CCW);
+    ///
+    /// Build the shader variant for the current lighting mode, and hand it the
+    /// matrices, parallax settings, and material the last one had.
+    ///
+    fn rebuild_shader_variant(&mut self) {
+        let (defines, description) = normal_map_defines(
+            self.normal_mapping, self.object_space_normal_map, self.parallax
+        );
+        self.shader_programme = self.shader_program.with_defines(&self.logger, &defines);
+        self.matrix_uniforms = MatrixUniforms::new(self.shader_programme);
+        self.matrix_uniforms.upload(self.shader_programme, &self.model_mat, &self.view_mat, &self.proj_mat);
+        self.parallax_settings.upload(self.shader_programme);
+        self.material_binder = MaterialBinder::new(self.shader_programme);
+        self.material_binder.bind(&self.material, &self.bound_textures());
+        self.normal_map_mode_text = description;
+        self.logger.log(&format!(
+            "normal mapping: {} ({} shader variants built)", self.normal_map_mode_text, self.shader_program.variant_count()
+        ));
     }
+}
+
+impl DemoApp for NormalMappingDemo {
+    fn init(context: &mut GLContext) -> Result<NormalMappingDemo, String> {
+        let logger = Logger::from_log_file(GL_LOG_FILE);
+
+        // Tell GL to only draw onto a pixel if the shape is closer to the viewer.
+        unsafe {
+            // Enable depth testing.
+            gl::Enable(gl::DEPTH_TEST);
+            // Depth testing interprets a smaller value as closer to the eye.
+            gl::DepthFunc(gl::LESS);
+        }
+
+        /*------------------------------CREATE GEOMETRY------------------------------*/
+        let mesh = match Model::load_cached(MESH_FILE) {
+            Ok(val) => val,
+            Err(e) => {
+                logger.log_err(&format!("ERROR: loading mesh file. Loader returned error\n{}", e));
+                logger.log_err("WARNING: using the embedded fallback mesh instead.");
+                Model::from_obj_mesh(assets::fallback_mesh(), vec![])
+            }
+        };
+
+        // the same mesh three times: once with each attribute in a vertex buffer of its
+        // own, once with all of them interleaved in a single buffer, and once
+        // interleaved with the normals and tangents packed into 32 bits each and the
+        // texture coordinates in half floats. I cycles through them, so the GPU time
+        // spent on the mesh, and how packing looks, can be compared.
+        let separate_start = Instant::now();
+        let (separate_vao, separate_bytes) = make_separate_vao(&mesh);
+        let separate_upload_ms = perf_stats::duration_to_ms(separate_start.elapsed());
+        let vertices = mesh.interleaved_vertices();
+        let interleaved_start = Instant::now();
+        let interleaved_vao = make_interleaved_vao(&vertices);
+        let interleaved_upload_ms = perf_stats::duration_to_ms(interleaved_start.elapsed());
+        let packed_vertices = mesh.packed_vertices();
+        let packed_start = Instant::now();
+        let packed_vao = make_interleaved_vao(&packed_vertices);
+        let packed_upload_ms = perf_stats::duration_to_ms(packed_start.elapsed());
+        let interleaved_bytes = Vertex::layout().size_of_vertices(vertices.len());
+        let packed_bytes = PackedVertex::layout().size_of_vertices(packed_vertices.len());
+        logger.log(&format!(
+            "uploaded {} vertices: 4 separate buffers ({} bytes) in {:.3} ms, 1 interleaved buffer ({} bytes) in {:.3} ms, \
+            1 packed buffer ({} bytes) in {:.3} ms",
+            mesh.point_count, separate_bytes, separate_upload_ms, interleaved_bytes, interleaved_upload_ms,
+            packed_bytes, packed_upload_ms
+        ));
+        let (normal_error, uv_error) = packing_error(&vertices, &packed_vertices);
+        logger.log(&format!(
+            "packing moves normals by up to {:.3} degrees, and texture coordinates by up to {:.6}",
+            normal_error, uv_error
+        ));
+
+        let g_vp = &mesh.points;
+        let g_vn = &mesh.normals;
+        let g_vtans = &mesh.tangents;
+        let g_point_count = mesh.point_count;
+        // the way the surface is lit is compiled into the fragment shader, so each
+        // lighting mode is a shader variant of its own. N, M, and P switch between them.
+        let shader_program = ShaderProgram::new(VERTEX_SHADER_FILE, FRAGMENT_SHADER_FILE);
+        let (normal_mapping, object_space_normal_map, parallax) = (true, false, false);
+        let (defines, normal_map_mode_text) = normal_map_defines(normal_mapping, object_space_normal_map, parallax);
+        let shader_programme = shader_program.with_defines(&logger, &defines);
+
+        // lines showing the tangent, bitangent, and normal at each vertex
+        let mut debug_draw = DebugDraw::new(&logger);
+        if g_vtans.len() == 4 * g_point_count {
+            debug_draw.lines(&debug_draw::tangent_frame_lines(g_vp, g_vn, g_vtans, TBN_LINE_LENGTH));
+        } else {
+            logger.log_err("WARNING: the mesh has no tangents, so there is no tangent space to show.");
+        }
+
+        // input variables
+        let proj_mat = Mat4::perspective(FOVY, context.width as f32 / context.height as f32, NEAR, FAR);
+
+        // matrix components
+        let cam_pos: [GLfloat; 3] = [0.0, 0.0, 5.0]; // don't start at zero, or we will be too close
+        let cam_yaw: GLfloat = 0.0;               // y-rotation in degrees
+        let mat_trans = Mat4::from_translation(&math::vec3((-cam_pos[0], -cam_pos[1], -cam_pos[2])));
+        let mat_rot = Mat4::from_rotation_y_deg(-cam_yaw);
+        let view_mat = mat_rot * mat_trans;
+
+        let model_mat = Mat4::identity();
+        let matrix_uniforms = MatrixUniforms::new(shader_programme);
+        matrix_uniforms.upload(shader_programme, &model_mat, &view_mat, &proj_mat);
+
+        let parallax_settings = ParallaxSettings::new();
+        parallax_settings.upload(shader_programme);
+        logger.log("keys: N toggles normal mapping, M switches between tangent-space and object-space normal maps,");
+        logger.log("      T shows the tangent (red), bitangent (green), and normal (blue) at each vertex,");
+        logger.log("      P toggles parallax occlusion mapping, [ and ] change its height scale,");
+        logger.log("      9 and 0 halve and double its head on samples, - and = halve and double its edge on samples,");
+        logger.log("      I cycles through interleaved, packed, and separate vertex buffers, F3 toggles the performance HUD");
+
+        // load the surface properties, and the normal map image into a texture
+        let material = match obj_parser::load_mtl_file(MATERIAL_FILE) {
+            Ok(ref materials) if !materials.is_empty() => materials[0].clone(),
+            Ok(_) => {
+                logger.log_err(&format!("WARNING: no materials in {}; using the default material.", MATERIAL_FILE));
+                Material::default()
+            }
+            Err(e) => {
+                logger.log_err(&e);
+                logger.log_err("WARNING: using the default material.");
+                Material::default()
+            }
+        };
+        let nmap_file = match material.normal_map {
+            Some(ref file_name) => file_name.clone(),
+            None => String::from(NMAP_IMG_FILE),
+        };
+        let height_map_file = match material.height_map {
+            Some(ref file_name) => file_name.clone(),
+            None => String::from(HEIGHT_MAP_IMG_FILE),
+        };
+        let mut textures = MaterialTextures::default();
+        let normal_map_image = load_image(&nmap_file);
+        if let Some((ref data, width, height)) = normal_map_image {
+            upload_texture(data, width, height, true, &mut textures.normal_map);
+        }
+        load_texture(&height_map_file, &mut textures.height_map);
+
+        // the monkey's faces all share the whole brick texture, so the object-space
+        // map is baked from the tangent-space one over a layout of its own, with
+        // the mesh drawn from its own vertex buffers to match. Mipmaps would blend
+        // triangles that are next to each other in the layout but not on the mesh.
+        // A mesh with more triangles than fit the map at its usual size gets a bigger one.
+        let map_size = match normal_bake::atlas_size(mesh.point_count / 3, OBJECT_SPACE_MAP_SIZE) {
+            Ok(size) => size,
+            Err(e) => {
+                logger.log_err(&e);
+                normal_bake::MAX_ATLAS_SIZE
+            }
+        };
+        let atlas_st = normal_bake::atlas_tex_coords(mesh.point_count / 3, map_size);
+        let baked_texels = match normal_map_image {
+            Some((ref data, width, height)) => {
+                normal_bake::bake_object_space_normal_map(&mesh, &atlas_st, data, width, height, map_size)
+            }
+            None => normal_bake::bake_object_space_normal_map(&mesh, &atlas_st, &[], 0, 0, map_size),
+        };
+        let mut baked_normal_map = 0;
+        upload_texture(&baked_texels, map_size, map_size, false, &mut baked_normal_map);
+        let object_space_mesh = Model::new(
+            mesh.points.clone(), atlas_st, mesh.normals.clone(), mesh.tangents.clone(), vec![], vec![], vec![]
+        );
+        let (object_space_vao, _) = make_separate_vao(&object_space_mesh);
+        logger.log(&format!("baked a {}x{} object-space normal map", map_size, map_size));
+        let mut material_binder = MaterialBinder::new(shader_programme);
+        material_binder.bind(&material, &textures);
+
+        let mut tweaks = Tweaks::new();
+        tweaks.add_f32("parallax height scale", DEFAULT_HEIGHT_SCALE, 0.0, MAX_HEIGHT_SCALE, HEIGHT_SCALE_STEP);
+        tweaks.add_colour("diffuse colour", material.kd.rgb());
+        tweaks.add_f32("shininess", material.shininess, 1.0, 256.0, 8.0);
+        tweaks.add_bool("show tangent space", false);
 
-    while !context.window.should_close() {
-        let current_seconds = context.glfw.get_time();
-        let elapsed_seconds = current_seconds - context.elapsed_time_seconds;
-        context.elapsed_time_seconds = current_seconds;
+        unsafe {
+            // Cull face.
+            gl::Enable(gl::CULL_FACE);
+            // Cull back face.
+            gl::CullFace(gl::BACK);
+            // GL_CW for clockwise.
+            gl::FrontFace(gl::CCW);
+        }
+
+        // frame time statistics and the GPU time spent on the mesh, drawn over the
+        // scene so the cost of parallax occlusion mapping can be compared with plain
+        // normal mapping. F3 toggles them on and off.
+        let overlay = Overlay::new(&logger);
+
+        Ok(NormalMappingDemo {
+            logger: logger,
+            separate_vao: separate_vao,
+            interleaved_vao: interleaved_vao,
+            packed_vao: packed_vao,
+            object_space_vao: object_space_vao,
+            baked_normal_map: baked_normal_map,
+            separate_bytes: separate_bytes,
+            interleaved_bytes: interleaved_bytes,
+            packed_bytes: packed_bytes,
+            normal_error: normal_error,
+            vertex_storage: VertexStorage::Interleaved,
+            point_count: g_point_count,
+            shader_program: shader_program,
+            shader_programme: shader_programme,
+            normal_mapping: normal_mapping,
+            object_space_normal_map: object_space_normal_map,
+            parallax: parallax,
+            normal_map_mode_text: normal_map_mode_text,
+            matrix_uniforms: matrix_uniforms,
+            parallax_settings: parallax_settings,
+            material: material,
+            textures: textures,
+            material_binder: material_binder,
+            debug_draw: debug_draw,
+            show_tangent_space: false,
+            tweaks: tweaks,
+            model_mat: model_mat,
+            view_mat: view_mat,
+            proj_mat: proj_mat,
+            cam_speed: 1.0,      // 1 unit per second
+            cam_yaw_speed: 10.0, // 10 degrees per second
+            cam_pos: cam_pos,
+            cam_yaw: cam_yaw,
+            fovy: FOVY,
+            overlay: overlay,
+            perf_stats: PerfStats::new(PERF_STATS_HISTORY),
+            show_perf_stats: true,
+            mesh_timer: GpuTimer::new(),
+            frame_start: Instant::now(),
+        })
+    }
 
-        update_fps_counter(&mut context);
+    fn render(&mut self, context: &mut GLContext) {
+        self.frame_start = Instant::now();
         unsafe {
             // Wipe the drawing surface clear.
             gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
-            gl::Viewport(0, 0, context.width as i32, context.height as i32);
 
-            gl::UseProgram(shader_programme);
-            gl::BindVertexArray(vao);
+            self.mesh_timer.begin();
+            gl::UseProgram(self.shader_programme);
+            // the baked map has a layout of its own, which only its own vertices follow
+            gl::BindVertexArray(match self.vertex_storage {
+                _ if self.normal_mapping && self.object_space_normal_map => self.object_space_vao,
+                VertexStorage::Separate => self.separate_vao,
+                VertexStorage::Interleaved => self.interleaved_vao,
+                VertexStorage::Packed => self.packed_vao,
+            });
+
+            gl::DrawArrays(gl::TRIANGLES, 0, self.point_count as i32);
+            self.perf_stats.count_draw(self.point_count / 3);
+            self.mesh_timer.end();
+        }
+        if self.show_tangent_space {
+            self.debug_draw.draw(&self.view_mat, &self.proj_mat);
+            self.perf_stats.count_draw(0);
+        }
 
-            // Update other events like input handling
-            gl::DrawArrays(gl::TRIANGLES, 0, g_point_count as i32);
+        self.perf_stats.set_gpu_pass_ms("mesh", self.mesh_timer.elapsed_ms());
+        if self.show_perf_stats {
+            let hud_bottom = self.perf_stats.draw_hud(&mut self.overlay, 10.0, 10.0);
+            let mode_text = format!("normal mapping {}", self.normal_map_mode_text);
+            self.overlay.text(16.0, hud_bottom + 6.0, 2.0, Colour::WHITE, &mode_text);
+            let parallax_text = format!("parallax {}", self.parallax_settings.describe());
+            self.overlay.text(
+                16.0, hud_bottom + 6.0 + 2.0 * overlay::LINE_HEIGHT, 2.0, Colour::WHITE, &parallax_text
+            );
+            let vertices_text = match self.vertex_storage {
+                VertexStorage::Separate => {
+                    format!("vertices {}  {} KB", self.vertex_storage.describe(), self.separate_bytes / 1024)
+                }
+                VertexStorage::Interleaved => {
+                    format!("vertices {}  {} KB", self.vertex_storage.describe(), self.interleaved_bytes / 1024)
+                }
+                VertexStorage::Packed => format!(
+                    "vertices {}  {} KB  normals within {:.2} deg", self.vertex_storage.describe(),
+                    self.packed_bytes / 1024, self.normal_error
+                ),
+            };
+            self.overlay.text(
+                16.0, hud_bottom + 6.0 + 4.0 * overlay::LINE_HEIGHT, 2.0, Colour::WHITE, &vertices_text
+            );
+            self.overlay.draw(context.width, context.height);
         }
+    }
 
-        context.glfw.poll_events();
+    fn update(&mut self, _context: &mut GLContext, clock: &FrameClock, input: &Input) {
+        if input.was_pressed(Key::N) {
+            self.normal_mapping = !self.normal_mapping;
+        }
+        if input.was_pressed(Key::M) {
+            self.object_space_normal_map = !self.object_space_normal_map;
+        }
+        if input.was_pressed(Key::P) {
+            self.parallax = !self.parallax;
+        }
+        if input.was_pressed(Key::N) || input.was_pressed(Key::M) || input.was_pressed(Key::P) {
+            self.rebuild_shader_variant();
+        }
+        if input.was_pressed(Key::T) {
+            self.show_tangent_space = !self.show_tangent_space;
+            self.tweaks.set_bool("show tangent space", self.show_tangent_space);
+        }
 
-        // control keys
-        let mut cam_moved = false;
-        match context.window.get_key(Key::A) {
-            Action::Press | Action::Repeat => {
-                cam_pos[0] -= cam_speed * (elapsed_seconds as GLfloat);
-                cam_moved = true;
-            }
-            _ => {}
+        let mut parallax_settings_changed = false;
+        if input.was_pressed(Key::LeftBracket) {
+            self.parallax_settings.change_height_scale(-HEIGHT_SCALE_STEP);
+            parallax_settings_changed = true;
         }
-        match context.window.get_key(Key::D) {
-            Action::Press | Action::Repeat => {
-                cam_pos[0] += cam_speed * (elapsed_seconds as GLfloat);
-                cam_moved = true;
-            }
-            _ => {}
+        if input.was_pressed(Key::RightBracket) {
+            self.parallax_settings.change_height_scale(HEIGHT_SCALE_STEP);
+            parallax_settings_changed = true;
         }
-        match context.window.get_key(Key::Up) {
-            Action::Press | Action::Repeat => {
-                cam_pos[1] += cam_speed * (elapsed_seconds as GLfloat);
-                cam_moved = true;
-            }
-            _ => {}
+        if input.was_pressed(Key::Num9) {
+            self.parallax_settings.change_min_samples(false);
+            parallax_settings_changed = true;
         }
-        match context.window.get_key(Key::Down) {
-            Action::Press | Action::Repeat => {
-                cam_pos[1] -= cam_speed * (elapsed_seconds as GLfloat);
-                cam_moved = true;
-            }
-            _ => {}
+        if input.was_pressed(Key::Num0) {
+            self.parallax_settings.change_min_samples(true);
+            parallax_settings_changed = true;
         }
-        match context.window.get_key(Key::W) {
-            Action::Press | Action::Repeat => {
-                cam_pos[2] -= cam_speed * (elapsed_seconds as GLfloat);
-                cam_moved = true;
-            }
-            _ => {}
+        if input.was_pressed(Key::Minus) {
+            self.parallax_settings.change_max_samples(false);
+            parallax_settings_changed = true;
         }
-        match context.window.get_key(Key::S) {
-            Action::Press | Action::Repeat => {
-                cam_pos[2] += cam_speed * (elapsed_seconds as GLfloat);
-                cam_moved = true;
-            }
-            _ => {}
+        if input.was_pressed(Key::Equal) {
+            self.parallax_settings.change_max_samples(true);
+            parallax_settings_changed = true;
         }
-        match context.window.get_key(Key::Left) {
-            Action::Press | Action::Repeat => {
-                cam_yaw += cam_yaw_speed * (elapsed_seconds as GLfloat);
-                cam_moved = true;
-            }
-            _ => {}
+        if parallax_settings_changed {
+            self.tweaks.set_f32("parallax height scale", self.parallax_settings.height_scale);
+            self.parallax_settings.upload(self.shader_programme);
+            self.logger.log(&format!("parallax occlusion mapping: {}", self.parallax_settings.describe()));
         }
-        match context.window.get_key(Key::Right) {
-            Action::Press | Action::Repeat => {
-                cam_yaw -= cam_yaw_speed * (elapsed_seconds as GLfloat);
+
+        if input.was_pressed(Key::F3) {
+            self.show_perf_stats = !self.show_perf_stats;
+        }
+        if input.was_pressed(Key::I) {
+            self.vertex_storage = self.vertex_storage.next();
+            self.logger.log(&format!("vertices: {}", self.vertex_storage.describe()));
+        }
+
+        // control keys
+        let step = self.cam_speed * (clock.real_dt() as GLfloat);
+        let turn = self.cam_yaw_speed * (clock.real_dt() as GLfloat);
+        let moves = [
+            (Key::A, 0, -step), (Key::D, 0, step),
+            (Key::Up, 1, step), (Key::Down, 1, -step),
+            (Key::W, 2, -step), (Key::S, 2, step),
+        ];
+        let mut cam_moved = false;
+        for &(key, axis, distance) in moves.iter() {
+            if input.is_held(key) {
+                self.cam_pos[axis] += distance;
                 cam_moved = true;
             }
-            _ => {}
+        }
+        if input.is_held(Key::Left) {
+            self.cam_yaw += turn;
+            cam_moved = true;
+        }
+        if input.is_held(Key::Right) {
+            self.cam_yaw -= turn;
+            cam_moved = true;
         }
         // update view matrix
         if cam_moved {
-            mat_trans = Mat4::identity().translate(&math::vec3((-cam_pos[0], -cam_pos[1], -cam_pos[2]))); // cam translation
-            mat_rot = Mat4::identity().rotate_y_deg(-cam_yaw);
-            view_mat = mat_rot * mat_trans;
-            unsafe {
-                gl::UniformMatrix4fv(view_mat_location, 1, gl::FALSE, view_mat.as_ptr());
-            }
+            self.update_view_mat();
         }
 
-        match context.window.get_key(Key::Escape) {
-            Action::Press | Action::Repeat => {
-                context.window.set_should_close(true);
-            }
-            _ => {}
+        if self.tweaks.take_changed() {
+            self.parallax_settings.height_scale = self.tweaks.f32("parallax height scale");
+            self.parallax_settings.upload(self.shader_programme);
+            self.material.kd = Colour::from(self.tweaks.colour("diffuse colour"));
+            self.material.shininess = self.tweaks.f32("shininess");
+            self.material_binder.bind(&self.material, &self.bound_textures());
+            self.show_tangent_space = self.tweaks.bool("show tangent space");
+        }
+
+        self.perf_stats.end_frame(perf_stats::duration_to_ms(self.frame_start.elapsed()));
+    }
+
+    fn resize(&mut self, _context: &mut GLContext, width: u32, height: u32) {
+        self.proj_mat = Mat4::perspective(self.fovy, width as f32 / height as f32, NEAR, FAR);
+        self.matrix_uniforms.upload(self.shader_programme, &self.model_mat, &self.view_mat, &self.proj_mat);
+    }
+
+    fn shutdown(&mut self, _context: &mut GLContext) {
+        let vaos = [self.separate_vao, self.interleaved_vao, self.packed_vao, self.object_space_vao];
+        let textures = [self.textures.normal_map, self.textures.height_map, self.baked_normal_map];
+        unsafe {
+            gl::DeleteVertexArrays(vaos.len() as i32, vaos.as_ptr());
+            gl::DeleteTextures(textures.len() as i32, textures.as_ptr());
+        }
+    }
+
+    fn bindings(&self, bindings: &mut KeyBindings) {
+        bindings.bind(Key::N, "toggle normal mapping");
+        bindings.bind(Key::M, "switch between tangent-space and object-space normal maps");
+        bindings.bind(Key::T, "show the tangent, bitangent, and normal at each vertex");
+        bindings.bind(Key::P, "toggle parallax occlusion mapping");
+        bindings.bind_keys(&[Key::LeftBracket, Key::RightBracket], "change the parallax height scale");
+        bindings.bind_keys(&[Key::Num9, Key::Num0], "halve and double the head on parallax samples");
+        bindings.bind_keys(&[Key::Minus, Key::Equal], "halve and double the edge on parallax samples");
+        bindings.bind(Key::I, "cycle through interleaved, packed, and separate vertex buffers");
+        bindings.bind(Key::F3, "toggle the performance HUD");
+        bindings.bind_keys(&[Key::W, Key::A, Key::S, Key::D], "move forwards, left, backwards, and right");
+        bindings.bind_keys(&[Key::Up, Key::Down], "move up and down");
+        bindings.bind_keys(&[Key::Left, Key::Right], "turn");
+    }
+
+    fn help_values(&self) -> Vec<(String, String)> {
+        vec![
+            (String::from("normal mapping"), String::from(self.normal_map_mode_text)),
+            (String::from("parallax"), self.parallax_settings.describe()),
+            (String::from("vertices"), String::from(self.vertex_storage.describe())),
+        ]
+    }
+
+    fn draw_help(&mut self, context: &mut GLContext, lines: &[String]) {
+        help_overlay::draw_help(&mut self.overlay, lines, context.width, context.height);
+        self.overlay.draw(context.width, context.height);
+    }
+
+    fn tweaks(&mut self) -> Option<&mut Tweaks> {
+        Some(&mut self.tweaks)
+    }
+
+    fn draw_tweaks(&mut self, context: &mut GLContext, lines: &[String]) {
+        help_overlay::draw_tweaks(&mut self.overlay, lines, context.height);
+        self.overlay.draw(context.width, context.height);
+    }
+
+    fn camera(&self) -> Option<Camera> {
+        let orientation = Versor::from_axis_deg(self.cam_yaw, 0.0, 1.0, 0.0);
+
+        Some(Camera::new(math::dvec3(math::vec3((self.cam_pos[0], self.cam_pos[1], self.cam_pos[2]))), orientation, self.fovy))
+    }
+
+    fn set_camera(&mut self, context: &mut GLContext, camera: &Camera) {
+        let position = math::vec3(camera.position);
+        self.cam_pos = [position.v[0], position.v[1], position.v[2]];
+        // this camera only turns about the vertical axis, so a saved pitch or roll is dropped.
+        self.cam_yaw = camera.yaw_deg();
+        self.update_view_mat();
+
+        self.fovy = camera.fovy;
+        let (width, height) = (context.width, context.height);
+        self.resize(context, width, height);
+    }
+}
+
+fn main() {
+    let logger = restart_gl_log(GL_LOG_FILE);
+    // Start a GL context and O/S window using the GLFW helper library.
+    let context = match start_gl(&logger) {
+        Ok(val) => val,
+        Err(e) => {
+            eprintln!("Failed to Initialize OpenGL context. Got error:");
+            eprintln!("{}", e);
+            process::exit(1);
         }
-        // Put the stuff we've been drawing onto the display.
-        context.window.swap_buffers();
+    };
+
+    if let Err(e) = run_demo::<NormalMappingDemo>(context) {
+        logger.log_err(&e);
+        eprintln!("{}", e);
+        process::exit(1);
     }
 }
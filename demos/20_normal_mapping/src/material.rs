@@ -0,0 +1,234 @@
+use gl;
+use gl::types::{GLint, GLsizei, GLuint};
+
+use obj_parser::{Material, MaterialGroup};
+use gl_utils::uniform_location;
+
+use std::collections::HashMap;
+
+
+// The texture units each kind of material map is bound to.
+pub const DIFFUSE_MAP_UNIT: u32 = 0;
+pub const SPECULAR_MAP_UNIT: u32 = 1;
+pub const NORMAL_MAP_UNIT: u32 = 2;
+pub const HEIGHT_MAP_UNIT: u32 = 3;
+
+
+///
+/// The plane through `point` facing along the unit vector `normal`, as the
+/// `clip_plane` uniform takes it: the normal followed by the plane's offset, so
+/// that a point's dot product with it is its signed distance from the plane.
+///
+pub fn clip_plane(point: &[f32; 3], normal: &[f32; 3]) -> [f32; 4] {
+    let offset = -(point[0] * normal[0] + point[1] * normal[1] + point[2] * normal[2]);
+
+    [normal[0], normal[1], normal[2], offset]
+}
+
+///
+/// How far `point` is from `plane`, on the side its normal faces. This is what a
+/// vertex shader writes to `gl_ClipDistance[0]`, and the parts of a triangle
+/// where it is negative are clipped away.
+///
+pub fn clip_distance(plane: &[f32; 4], point: &[f32; 3]) -> f32 {
+    plane[0] * point[0] + plane[1] * point[1] + plane[2] * point[2] + plane[3]
+}
+
+///
+/// The GL textures loaded for a material's texture maps. A handle of zero
+/// means the material does not use that map.
+///
+#[derive(Copy, Clone, Debug, Default)]
+pub struct MaterialTextures {
+    pub diffuse_map: GLuint,
+    pub specular_map: GLuint,
+    pub normal_map: GLuint,
+    pub height_map: GLuint,
+}
+
+///
+/// Uploads materials to a shader programme. The programme only needs to declare
+/// the `Ka`, `Kd`, `Ks`, and `specular_exponent` uniforms and the `diffuse_map`,
+/// `specular_map`, `normal_map`, and `height_map` samplers it actually uses; the
+/// rest are skipped. A programme that declares a `clip_plane` uniform can also
+/// have part of the scene clipped away.
+///
+pub struct MaterialBinder {
+    sp: GLuint,
+    ka_location: GLint,
+    kd_location: GLint,
+    ks_location: GLint,
+    shininess_location: GLint,
+    clip_plane_location: GLint,
+}
+
+impl MaterialBinder {
+    pub fn new(sp: GLuint) -> MaterialBinder {
+        let samplers = [
+            ("diffuse_map", DIFFUSE_MAP_UNIT),
+            ("specular_map", SPECULAR_MAP_UNIT),
+            ("normal_map", NORMAL_MAP_UNIT),
+            ("height_map", HEIGHT_MAP_UNIT),
+        ];
+        unsafe {
+            gl::UseProgram(sp);
+            for &(name, unit) in samplers.iter() {
+                let location = uniform_location(sp, name);
+                if location != -1 {
+                    gl_check!(gl::Uniform1i(location, unit as GLint));
+                }
+            }
+        }
+
+        MaterialBinder {
+            sp: sp,
+            ka_location: uniform_location(sp, "Ka"),
+            kd_location: uniform_location(sp, "Kd"),
+            ks_location: uniform_location(sp, "Ks"),
+            shininess_location: uniform_location(sp, "specular_exponent"),
+            clip_plane_location: uniform_location(sp, "clip_plane"),
+        }
+    }
+
+    ///
+    /// Upload the surface properties of a material and bind its textures. This
+    /// leaves the binder's shader programme in use.
+    ///
+    pub fn bind(&self, material: &Material, textures: &MaterialTextures) {
+        let maps = [
+            (textures.diffuse_map, DIFFUSE_MAP_UNIT),
+            (textures.specular_map, SPECULAR_MAP_UNIT),
+            (textures.normal_map, NORMAL_MAP_UNIT),
+            (textures.height_map, HEIGHT_MAP_UNIT),
+        ];
+        unsafe {
+            gl::UseProgram(self.sp);
+            gl_check!(gl::Uniform3fv(self.ka_location, 1, material.ka.rgb().as_ptr()));
+            gl_check!(gl::Uniform3fv(self.kd_location, 1, material.kd.rgb().as_ptr()));
+            gl_check!(gl::Uniform3fv(self.ks_location, 1, material.ks.rgb().as_ptr()));
+            gl_check!(gl::Uniform1f(self.shininess_location, material.shininess));
+            for &(tex, unit) in maps.iter() {
+                if tex != 0 {
+                    gl::ActiveTexture(gl::TEXTURE0 + unit);
+                    gl::BindTexture(gl::TEXTURE_2D, tex);
+                }
+            }
+        }
+    }
+
+    ///
+    /// Clip away everything behind `plane`, or nothing if it is `None`. The
+    /// programme's vertex shader has to write `gl_ClipDistance[0]` as the dot
+    /// product of the world space position with `clip_plane`. The clip distance
+    /// stays enabled for every draw until this is called again with `None`. This
+    /// leaves the binder's shader programme in use.
+    ///
+    pub fn set_clip_plane(&self, plane: Option<[f32; 4]>) {
+        unsafe {
+            gl::UseProgram(self.sp);
+            match plane {
+                Some(plane) => {
+                    gl_check!(gl::Uniform4fv(self.clip_plane_location, 1, plane.as_ptr()));
+                    gl::Enable(gl::CLIP_DISTANCE0);
+                }
+                None => {
+                    gl::Disable(gl::CLIP_DISTANCE0);
+                }
+            }
+        }
+    }
+}
+
+///
+/// The materials a mesh uses, each paired with its loaded textures. A texture
+/// shared by several materials is only loaded once.
+///
+pub struct MaterialLibrary {
+    materials: Vec<(Material, MaterialTextures)>,
+    default_material: (Material, MaterialTextures),
+}
+
+impl MaterialLibrary {
+    ///
+    /// Build a library from parsed materials. The `load_texture` function loads
+    /// an image file into a GL texture, returning zero if it could not.
+    ///
+    pub fn new<F>(materials: Vec<Material>, load_texture: &F) -> MaterialLibrary
+        where F: Fn(&str) -> GLuint
+    {
+        let mut loaded: HashMap<String, GLuint> = HashMap::new();
+        let mut load = |map: &Option<String>| -> GLuint {
+            match *map {
+                Some(ref file_name) => {
+                    *loaded.entry(file_name.clone()).or_insert_with(|| load_texture(file_name))
+                }
+                None => 0,
+            }
+        };
+
+        let mut library = vec![];
+        for material in materials.into_iter() {
+            let textures = MaterialTextures {
+                diffuse_map: load(&material.diffuse_map),
+                specular_map: load(&material.specular_map),
+                normal_map: load(&material.normal_map),
+                height_map: load(&material.height_map),
+            };
+            library.push((material, textures));
+        }
+
+        MaterialLibrary {
+            materials: library,
+            default_material: (Material::default(), MaterialTextures::default()),
+        }
+    }
+
+    ///
+    /// Look up a material by name, falling back to the default material for
+    /// names the library does not have.
+    ///
+    pub fn find(&self, name: &str) -> &(Material, MaterialTextures) {
+        for entry in self.materials.iter() {
+            if entry.0.name == name {
+                return entry;
+            }
+        }
+
+        &self.default_material
+    }
+
+    ///
+    /// Whether the library has a material with this name.
+    ///
+    pub fn contains(&self, name: &str) -> bool {
+        self.materials.iter().any(|entry| entry.0.name == name)
+    }
+}
+
+///
+/// Draw a mesh one material group at a time, binding each group's material
+/// first. The mesh's vertex array must already be bound.
+///
+pub fn draw_material_groups(binder: &MaterialBinder, library: &MaterialLibrary, groups: &[MaterialGroup]) {
+    for group in groups.iter() {
+        let &(ref material, ref textures) = library.find(&group.material);
+        binder.bind(material, textures);
+        unsafe {
+            gl_check!(gl::DrawArrays(gl::TRIANGLES, group.first as GLint, group.count as GLsizei));
+        }
+    }
+}
+
+mod material_tests {
+    use super::{clip_distance, clip_plane};
+
+    #[test]
+    fn test_points_in_front_of_a_clip_plane_are_kept() {
+        let plane = clip_plane(&[0.0, 2.0, 0.0], &[0.0, 1.0, 0.0]);
+
+        assert_eq!(plane, [0.0, 1.0, 0.0, -2.0]);
+        assert_eq!(clip_distance(&plane, &[5.0, 3.0, -1.0]), 1.0);
+        assert_eq!(clip_distance(&plane, &[0.0, 2.0, 7.0]), 0.0);
+        assert!(clip_distance(&plane, &[0.0, 0.0, 0.0]) < 0.0);
+    }
+}
@@ -0,0 +1,361 @@
+use graphics_math as math;
+use math::Vec3;
+
+use model::Model;
+
+
+///
+/// How far in from the edge of its cell each triangle is laid out in the atlas,
+/// in texels, so filtering never reads a texel of the triangle next to it.
+///
+const ATLAS_PADDING: f32 = 2.0;
+///
+/// The smallest cell the atlas lays two triangles out in: the padding around and
+/// between them, and two texels along each of their sides.
+///
+const MIN_ATLAS_CELL_SIZE: f32 = 4.0 * ATLAS_PADDING + 2.0;
+///
+/// The largest texture `atlas_size` grows the atlas to.
+///
+pub const MAX_ATLAS_SIZE: usize = 8192;
+///
+/// How many times the texels around each baked triangle are filled in from the
+/// texels next to them, so the edges of a triangle do not filter in the colour
+/// of a texel nothing was baked into. It is enough to reach the corners of the
+/// padding.
+///
+const DILATION_PASSES: usize = 4;
+///
+/// The texel written where nothing was baked: a normal straight along +z.
+///
+const FLAT_NORMAL_TEXEL: [u8; 4] = [128, 128, 255, 255];
+
+
+///
+/// Lay each triangle of an unindexed mesh out on its own in a square texture of
+/// `size` texels, two to a cell of a grid, and return the texture coordinates of
+/// every vertex. A mesh's own texture coordinates can overlap, as the monkey's
+/// do, with every face mapped over the whole brick texture, but an object-space
+/// map needs a texel of its own for each point on the surface.
+///
+/// Cells too small for the full padding, in a texture smaller than `atlas_size`
+/// asks for, have the padding cut down to a fifth of the cell, so the triangles
+/// still face the right way, if with little room to filter.
+///
+pub fn atlas_tex_coords(triangle_count: usize, size: usize) -> Vec<f32> {
+    let cells_per_row = atlas_cells_per_row(triangle_count);
+    let cell_size = size as f32 / cells_per_row as f32;
+    let padding = f32::min(ATLAS_PADDING, cell_size / 5.0);
+    let (near, far) = (padding, cell_size - padding);
+
+    let mut tex_coords = Vec::with_capacity(6 * triangle_count);
+    for triangle in 0..triangle_count {
+        let cell = triangle / 2;
+        let x = (cell % cells_per_row) as f32 * cell_size;
+        let y = (cell / cells_per_row) as f32 * cell_size;
+        // the first triangle of a cell takes the corner below its diagonal, and the
+        // second the corner above it, with a gap between the two.
+        let corners = if triangle % 2 == 0 {
+            [(near, near), (far - padding, near), (near, far - padding)]
+        } else {
+            [(far, far), (near + padding, far), (far, near + padding)]
+        };
+        for &(s, t) in corners.iter() {
+            tex_coords.push((x + s) / size as f32);
+            tex_coords.push((y + t) / size as f32);
+        }
+    }
+
+    tex_coords
+}
+
+fn atlas_cells_per_row(triangle_count: usize) -> usize {
+    let cell_count = (triangle_count + 1) / 2;
+    usize::max(f32::ceil(f32::sqrt(cell_count as f32)) as usize, 1)
+}
+
+///
+/// The size of square texture `atlas_tex_coords` needs to give each cell of
+/// `triangle_count` triangles room for its padding: `min_size`, doubled until
+/// the cells are big enough. Returns an error with the largest size it tried,
+/// `MAX_ATLAS_SIZE`, if even that is too small.
+///
+pub fn atlas_size(triangle_count: usize, min_size: usize) -> Result<usize, String> {
+    let cells_per_row = atlas_cells_per_row(triangle_count);
+    let mut size = usize::max(min_size, 1);
+    while (size as f32 / cells_per_row as f32) < MIN_ATLAS_CELL_SIZE {
+        if size >= MAX_ATLAS_SIZE {
+            return Err(format!(
+                "ERROR: {} triangles do not fit a {}x{} normal map atlas with {} texels of padding",
+                triangle_count, MAX_ATLAS_SIZE, MAX_ATLAS_SIZE, ATLAS_PADDING
+            ));
+        }
+        size = usize::min(2 * size, MAX_ATLAS_SIZE);
+    }
+
+    Ok(size)
+}
+
+fn vertex_vec3(data: &[f32], i: usize) -> Vec3 {
+    math::vec3((data[3 * i], data[3 * i + 1], data[3 * i + 2]))
+}
+
+///
+/// The barycentric weights of a point in a triangle in texture space, or `None`
+/// if the point is outside it or the triangle has no area.
+///
+fn barycentric(p: (f32, f32), a: (f32, f32), b: (f32, f32), c: (f32, f32)) -> Option<[f32; 3]> {
+    let area = (b.0 - a.0) * (c.1 - a.1) - (c.0 - a.0) * (b.1 - a.1);
+    if f32::abs(area) < math::EPSILON {
+        return None;
+    }
+    let wa = ((b.0 - p.0) * (c.1 - p.1) - (c.0 - p.0) * (b.1 - p.1)) / area;
+    let wb = ((c.0 - p.0) * (a.1 - p.1) - (a.0 - p.0) * (c.1 - p.1)) / area;
+    let wc = 1.0 - wa - wb;
+    let inside = -1e-4;
+    if wa < inside || wb < inside || wc < inside {
+        return None;
+    }
+
+    Some([wa, wb, wc])
+}
+
+///
+/// Read the normal at texture coordinates `(s, t)` from an RGBA tangent-space map
+/// stored bottom row first, clamping at the edges as the texture does.
+///
+fn sample_normal(map: &[u8], width: usize, height: usize, s: f32, t: f32) -> Vec3 {
+    let x = f32::min(f32::max(s * width as f32, 0.0), (width - 1) as f32) as usize;
+    let y = f32::min(f32::max(t * height as f32, 0.0), (height - 1) as f32) as usize;
+    let texel = &map[4 * (y * width + x)..];
+
+    math::vec3((
+        texel[0] as f32 / 255.0 * 2.0 - 1.0,
+        texel[1] as f32 / 255.0 * 2.0 - 1.0,
+        texel[2] as f32 / 255.0 * 2.0 - 1.0,
+    )).normalize()
+}
+
+///
+/// Give every texel nothing was baked into the colour of a baked texel next to
+/// it, a few texels deep, and the flat normal after that.
+///
+fn dilate(texels: &mut [u8], baked: &mut [bool], size: usize) {
+    for _ in 0..DILATION_PASSES {
+        let was_baked = baked.to_vec();
+        for y in 0..size {
+            for x in 0..size {
+                if was_baked[y * size + x] {
+                    continue;
+                }
+                let neighbours = [(x.wrapping_sub(1), y), (x + 1, y), (x, y.wrapping_sub(1)), (x, y + 1)];
+                for &(nx, ny) in neighbours.iter() {
+                    if nx < size && ny < size && was_baked[ny * size + nx] {
+                        let (from, to) = (4 * (ny * size + nx), 4 * (y * size + x));
+                        for k in 0..4 {
+                            texels[to + k] = texels[from + k];
+                        }
+                        baked[y * size + x] = true;
+                        break;
+                    }
+                }
+            }
+        }
+    }
+    for (i, &was_baked) in baked.iter().enumerate() {
+        if !was_baked {
+            texels[4 * i..4 * i + 4].copy_from_slice(&FLAT_NORMAL_TEXEL);
+        }
+    }
+}
+
+///
+/// Bake a square RGBA object-space normal map of `size` texels for `model` from
+/// its tangent-space map, laid out over the model by `atlas_st` as
+/// `atlas_tex_coords` lays it out. Each texel reads the tangent-space map at the
+/// model's own texture coordinates for that point, and turns it into object
+/// space with the tangent frame there, the same way the tangent-space shader
+/// does, so both light the model alike. Rows are stored bottom first, as GL
+/// reads them. A model without tangents has no tangent space to bake from, and
+/// gets a map that is flat throughout.
+///
+pub fn bake_object_space_normal_map(
+    model: &Model, atlas_st: &[f32], tangent_map: &[u8], map_width: usize, map_height: usize,
+    size: usize) -> Vec<u8> {
+
+    let mut texels = vec![0; 4 * size * size];
+    let mut baked = vec![false; size * size];
+    let has_tangent_space = model.tex_coords.len() == 2 * model.point_count
+        && model.tangents.len() == 4 * model.point_count
+        && tangent_map.len() >= 4 * map_width * map_height
+        && map_width > 0 && map_height > 0;
+    if has_tangent_space {
+        for triangle in 0..model.point_count / 3 {
+            let v = [3 * triangle, 3 * triangle + 1, 3 * triangle + 2];
+            let corner = |i: usize| (atlas_st[2 * v[i]] * size as f32, atlas_st[2 * v[i] + 1] * size as f32);
+            let (a, b, c) = (corner(0), corner(1), corner(2));
+            let min_x = f32::max(f32::floor(f32::min(a.0, f32::min(b.0, c.0))), 0.0) as usize;
+            let min_y = f32::max(f32::floor(f32::min(a.1, f32::min(b.1, c.1))), 0.0) as usize;
+            let max_x = usize::min(f32::ceil(f32::max(a.0, f32::max(b.0, c.0))) as usize, size);
+            let max_y = usize::min(f32::ceil(f32::max(a.1, f32::max(b.1, c.1))) as usize, size);
+            for y in min_y..max_y {
+                for x in min_x..max_x {
+                    let weights = match barycentric((x as f32 + 0.5, y as f32 + 0.5), a, b, c) {
+                        Some(weights) => weights,
+                        None => continue,
+                    };
+                    let mut normal = Vec3::zero();
+                    let mut tangent = Vec3::zero();
+                    let mut handedness = 0.0;
+                    let (mut s, mut t) = (0.0, 0.0);
+                    for k in 0..3 {
+                        let i = v[k];
+                        normal = normal + vertex_vec3(&model.normals, i) * weights[k];
+                        let tan = &model.tangents[4 * i..4 * i + 4];
+                        tangent = tangent + math::vec3((tan[0], tan[1], tan[2])) * weights[k];
+                        handedness += tan[3] * weights[k];
+                        s += model.tex_coords[2 * i] * weights[k];
+                        t += model.tex_coords[2 * i + 1] * weights[k];
+                    }
+                    let normal = normal.normalize();
+                    let tangent = tangent.normalize();
+                    let bitangent = normal.cross(&tangent) * if handedness < 0.0 { -1.0 } else { 1.0 };
+
+                    let n = sample_normal(tangent_map, map_width, map_height, s, t);
+                    let object_normal = (tangent * n.v[0] + bitangent * n.v[1] + normal * n.v[2]).normalize();
+                    let texel = 4 * (y * size + x);
+                    for k in 0..3 {
+                        texels[texel + k] = f32::round((object_normal.v[k] * 0.5 + 0.5) * 255.0) as u8;
+                    }
+                    texels[texel + 3] = 255;
+                    baked[y * size + x] = true;
+                }
+            }
+        }
+    }
+    dilate(&mut texels, &mut baked, size);
+
+    texels
+}
+
+mod normal_bake_tests {
+    use super::{atlas_size, atlas_tex_coords, bake_object_space_normal_map, MAX_ATLAS_SIZE};
+    use model::Model;
+
+    // two triangles making a unit square facing `normal`, with `tangent` along s
+    fn square(normal: [f32; 3], tangent: [f32; 4]) -> Model {
+        let points = vec![
+            0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0,
+            1.0, 1.0, 0.0, 0.0, 1.0, 0.0, 1.0, 0.0, 0.0,
+        ];
+        let tex_coords = vec![0.0, 0.0, 1.0, 0.0, 0.0, 1.0, 1.0, 1.0, 0.0, 1.0, 1.0, 0.0];
+        let mut normals = vec![];
+        let mut tangents = vec![];
+        for _ in 0..6 {
+            normals.extend_from_slice(&normal);
+            tangents.extend_from_slice(&tangent);
+        }
+
+        Model::new(points, tex_coords, normals, tangents, vec![], vec![], vec![])
+    }
+
+    fn texel(map: &[u8], size: usize, x: usize, y: usize) -> [u8; 3] {
+        let i = 4 * (y * size + x);
+        [map[i], map[i + 1], map[i + 2]]
+    }
+
+    fn close(texel: [u8; 3], expected: [u8; 3]) -> bool {
+        (0..3).all(|k| (texel[k] as i32 - expected[k] as i32).abs() <= 1)
+    }
+
+    #[test]
+    fn test_atlas_triangles_stay_inside_the_texture_and_apart() {
+        let size = 64;
+        let tex_coords = atlas_tex_coords(5, size);
+        assert_eq!(tex_coords.len(), 30);
+        assert!(tex_coords.iter().all(|&st| st > 0.0 && st < 1.0));
+
+        // the first two triangles share a cell, either side of its diagonal
+        let diagonal = |triangle: usize| -> Vec<f32> {
+            (0..3).map(|v| {
+                let i = 2 * (3 * triangle + v);
+                tex_coords[i] + tex_coords[i + 1]
+            }).collect()
+        };
+        let below = diagonal(0).iter().cloned().fold(0.0, f32::max);
+        let above = diagonal(1).iter().cloned().fold(2.0, f32::min);
+        assert!(above - below > 2.0 / size as f32);
+        // and the third starts a cell of its own
+        assert!(tex_coords[12] > tex_coords[2]);
+    }
+
+    #[test]
+    fn test_the_atlas_grows_until_its_cells_fit_the_padding() {
+        // two triangles to a cell, so 2000 triangles need a grid 32 cells across
+        assert_eq!(atlas_size(8, 64), Ok(64));
+        assert_eq!(atlas_size(2000, 64), Ok(512));
+        assert!(atlas_size(200000000, 64).is_err());
+        assert!(MAX_ATLAS_SIZE >= 512);
+    }
+
+    #[test]
+    fn test_cells_too_small_for_the_padding_still_hold_their_triangles() {
+        // 32 cells across 64 texels leaves 2 texels a cell, less than the padding
+        let tex_coords = atlas_tex_coords(2000, 64);
+        for triangle in 0..2 {
+            let st = &tex_coords[6 * triangle..6 * triangle + 6];
+            assert!(st.iter().all(|&st| st > 0.0 && st < 2.0 / 64.0));
+            // each triangle keeps its winding, rather than turning inside out
+            let area = (st[2] - st[0]) * (st[5] - st[1]) - (st[4] - st[0]) * (st[3] - st[1]);
+            assert!(area > 0.0);
+        }
+    }
+
+    #[test]
+    fn test_a_flat_map_bakes_to_the_surface_normal() {
+        let size = 16;
+        let model = square([1.0, 0.0, 0.0], [0.0, 0.0, -1.0, 1.0]);
+        let flat = [128, 128, 255, 255];
+        let atlas = atlas_tex_coords(2, size);
+        let map = bake_object_space_normal_map(&model, &atlas, &flat, 1, 1, size);
+
+        assert!(close(texel(&map, size, 4, 4), [255, 128, 128]));
+        assert!(close(texel(&map, size, 11, 11), [255, 128, 128]));
+    }
+
+    #[test]
+    fn test_tangent_space_normals_turn_with_the_tangent_frame() {
+        let size = 16;
+        // a normal leaning all the way along the tangent, on a square whose
+        // tangent runs along +y
+        let model = square([0.0, 0.0, 1.0], [0.0, 1.0, 0.0, 1.0]);
+        let along_tangent = [255, 128, 128, 255];
+        let atlas = atlas_tex_coords(2, size);
+        let map = bake_object_space_normal_map(&model, &atlas, &along_tangent, 1, 1, size);
+
+        assert!(close(texel(&map, size, 4, 4), [128, 255, 128]));
+    }
+
+    #[test]
+    fn test_the_edges_around_a_triangle_are_filled_from_it() {
+        let size = 16;
+        let model = square([0.0, 1.0, 0.0], [1.0, 0.0, 0.0, 1.0]);
+        let flat = [128, 128, 255, 255];
+        let atlas = atlas_tex_coords(2, size);
+        let map = bake_object_space_normal_map(&model, &atlas, &flat, 1, 1, size);
+
+        // the corner of the cell is inside the padding, where nothing is baked
+        assert!(close(texel(&map, size, 0, 0), [128, 255, 128]));
+    }
+
+    #[test]
+    fn test_a_model_without_tangents_bakes_a_flat_map() {
+        let size = 4;
+        let mut model = square([1.0, 0.0, 0.0], [0.0, 0.0, -1.0, 1.0]);
+        model.tangents.clear();
+        let atlas = atlas_tex_coords(2, size);
+        let map = bake_object_space_normal_map(&model, &atlas, &[128, 128, 255, 255], 1, 1, size);
+
+        assert!(map.chunks(4).all(|texel| texel == [128, 128, 255, 255]));
+    }
+}
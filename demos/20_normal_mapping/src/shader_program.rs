@@ -0,0 +1,137 @@
+use gl;
+use gl::types::GLuint;
+
+use gl_utils;
+use gl_utils::ProgramInfo;
+use logger::Logger;
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+
+///
+/// A shader programme that comes in variants, one for each set of `#define`s its
+/// shaders are compiled with. Features the shaders turn on and off with `#if`
+/// cost nothing when they are off, unlike branching on a uniform. Each variant is
+/// built the first time it is asked for and kept, so switching back and forth
+/// between variants only compiles each of them once.
+///
+pub struct ShaderProgram {
+    vertex_shader_file: String,
+    fragment_shader_file: String,
+    variants: RefCell<HashMap<Vec<(String, String)>, GLuint>>,
+}
+
+impl ShaderProgram {
+    pub fn new(vertex_shader_file: &str, fragment_shader_file: &str) -> ShaderProgram {
+        ShaderProgram {
+            vertex_shader_file: String::from(vertex_shader_file),
+            fragment_shader_file: String::from(fragment_shader_file),
+            variants: RefCell::new(HashMap::new()),
+        }
+    }
+
+    ///
+    /// Get the variant for a set of defines, calling `build` with the defines in
+    /// a fixed order to make it the first time. The order the defines are given in
+    /// does not matter.
+    ///
+    fn variant<F>(&self, defines: &[(&str, &str)], build: F) -> GLuint
+        where F: FnOnce(&[(&str, &str)]) -> GLuint
+    {
+        let mut key: Vec<(String, String)> = defines.iter()
+            .map(|&(name, value)| (String::from(name), String::from(value)))
+            .collect();
+        key.sort();
+        if let Some(&programme) = self.variants.borrow().get(&key) {
+            return programme;
+        }
+
+        let programme = {
+            let sorted_defines: Vec<(&str, &str)> = key.iter()
+                .map(|&(ref name, ref value)| (name.as_str(), value.as_str()))
+                .collect();
+            build(&sorted_defines)
+        };
+        self.variants.borrow_mut().insert(key, programme);
+
+        programme
+    }
+
+    ///
+    /// Get the variant of the programme with a `#define` for each name and value,
+    /// compiling it if this is the first time it has been asked for.
+    ///
+    pub fn with_defines(&self, logger: &Logger, defines: &[(&str, &str)]) -> GLuint {
+        self.variant(defines, |sorted_defines| {
+            gl_utils::create_programme_from_files_with_defines(
+                logger, &self.vertex_shader_file, &self.fragment_shader_file, sorted_defines
+            )
+        })
+    }
+
+    ///
+    /// Ask GL about the variant of the programme for a set of defines, building it
+    /// if need be, and write everything it reports to the log.
+    ///
+    pub fn introspect(&self, logger: &Logger, defines: &[(&str, &str)]) -> ProgramInfo {
+        let info = gl_utils::introspect(self.with_defines(logger, defines));
+        info.log(logger);
+
+        info
+    }
+
+    pub fn variant_count(&self) -> usize {
+        self.variants.borrow().len()
+    }
+}
+
+impl Drop for ShaderProgram {
+    fn drop(&mut self) {
+        // The functions are not loaded when there is no GL context, as in the tests.
+        if gl::DeleteProgram::is_loaded() {
+            for &programme in self.variants.borrow().values() {
+                unsafe {
+                    gl::DeleteProgram(programme);
+                }
+            }
+        }
+    }
+}
+
+mod shader_program_tests {
+    use super::ShaderProgram;
+
+    use std::cell::Cell;
+
+    #[test]
+    fn test_each_set_of_defines_is_built_once_whatever_its_order() {
+        let program = ShaderProgram::new("src/test.vert.glsl", "src/test.frag.glsl");
+        let builds = Cell::new(0);
+        let build = |defines: &[(&str, &str)]| {
+            assert_eq!(defines, &[("FOG_MODE", "1"), ("USE_FOG", "1")]);
+            builds.set(builds.get() + 1);
+            7
+        };
+
+        let first = program.variant(&[("USE_FOG", "1"), ("FOG_MODE", "1")], &build);
+        let second = program.variant(&[("FOG_MODE", "1"), ("USE_FOG", "1")], &build);
+
+        assert_eq!((first, second), (7, 7));
+        assert_eq!(builds.get(), 1);
+        assert_eq!(program.variant_count(), 1);
+    }
+
+    #[test]
+    fn test_different_values_are_different_variants() {
+        let program = ShaderProgram::new("src/test.vert.glsl", "src/test.frag.glsl");
+
+        let linear = program.variant(&[("FOG_MODE", "0")], |_| 1);
+        let exponential = program.variant(&[("FOG_MODE", "1")], |_| 2);
+        let plain = program.variant(&[], |_| 3);
+
+        assert_eq!((linear, exponential, plain), (1, 2, 3));
+        assert_eq!(program.variant(&[("FOG_MODE", "0")], |_| panic!("the variant should be kept")), 1);
+        assert_eq!(program.variant_count(), 3);
+    }
+}
@@ -4,34 +4,113 @@
 in vec2 st;
 in vec3 view_dir_tan;
 in vec3 light_dir_tan;
+// the same directions, and the vertex normal, in local (object) space
+in vec3 view_dir_loc;
+in vec3 light_dir_loc;
+in vec3 normal_loc;
 
-// the normal map texture
+// the normal map texture, and the height map that goes with it
 uniform sampler2D normal_map;
+uniform sampler2D height_map;
+/* the lighting is picked when the shader is compiled. USE_NORMAL_MAP 0 lights
+	 the surface with the interpolated vertex normals, and 1 with the normal map.
+	 OBJECT_SPACE_NORMAL_MAP 1 reads the map as an object-space map instead of a
+	 tangent-space one, and USE_PARALLAX 1 has parallax occlusion mapping shift
+	 the texture coordinates before a tangent-space map is read */
+#ifndef USE_NORMAL_MAP
+#define USE_NORMAL_MAP 1
+#endif
+#ifndef OBJECT_SPACE_NORMAL_MAP
+#define OBJECT_SPACE_NORMAL_MAP 0
+#endif
+#ifndef USE_PARALLAX
+#define USE_PARALLAX 0
+#endif
+// how deep the height map's lowest point is, in texture coordinate units
+uniform float height_scale;
+/* the number of layers the height map is stepped through when the surface is
+	 seen head on, and when it is seen edge on */
+uniform int pom_min_samples;
+uniform int pom_max_samples;
+
+// surface reflectance
+uniform vec3 Ka; // ambient reflectance
+uniform vec3 Kd; // diffuse surface reflectance
+uniform vec3 Ks; // specular reflectance
+uniform float specular_exponent; // specular 'power'
 
 // output colour
 out vec4 frag_colour;
 
 in vec4 test_tan;
 
+/* march the view ray down into the surface through layers of equal depth until
+	 it passes under the height map, then interpolate between the last two layers
+	 to find where it hit. view_dir is the tangent-space direction to the eye */
+vec2 parallax_occlusion_st (vec2 st, vec3 view_dir) {
+	// a surface seen at a glancing angle needs more layers to avoid stair steps
+	float sample_count = mix (float (pom_max_samples), float (pom_min_samples), abs (view_dir.z));
+	float layer_depth = 1.0 / sample_count;
+	vec2 st_step = view_dir.xy / max (view_dir.z, 0.05) * height_scale / sample_count;
+
+	float current_layer_depth = 0.0;
+	vec2 current_st = st;
+	// the height map stores heights, so the depth below the top is one minus it
+	float current_depth = 1.0 - texture (height_map, current_st).r;
+	for (int i = 0; i < pom_max_samples; i++) {
+		if (current_layer_depth >= current_depth) {
+			break;
+		}
+		current_st -= st_step;
+		current_depth = 1.0 - texture (height_map, current_st).r;
+		current_layer_depth += layer_depth;
+	}
+
+	vec2 previous_st = current_st + st_step;
+	float after_depth = current_depth - current_layer_depth;
+	float before_depth = 1.0 - texture (height_map, previous_st).r - current_layer_depth + layer_depth;
+	float weight = after_depth / (after_depth - before_depth);
+	return mix (current_st, previous_st, weight);
+}
+
 void main() {
-	vec3 Ia = vec3 (0.2, 0.2, 0.2);
+	vec3 Ia = vec3 (0.2, 0.2, 0.2) * Ka;
+
+	vec2 surface_st = st;
+#if USE_PARALLAX
+	surface_st = parallax_occlusion_st (st, normalize (view_dir_tan));
+#endif
 	
 	// sample the normal map and covert from 0:1 range to -1:1 range
-	vec3 normal_tan = texture (normal_map, st).rgb;
-	normal_tan = normalize (normal_tan * 2.0 - 1.0);
+	vec3 normal_map_sample = texture (normal_map, surface_st).rgb;
+	normal_map_sample = normalize (normal_map_sample * 2.0 - 1.0);
+
+	/* pick the normal, and the space the lighting is done in. a tangent-space
+		 map needs the light and view directions in tangent space, but an
+		 object-space map or the vertex normal can use them in local space */
+	vec3 normal = normalize (normal_loc);
+	vec3 light_dir = light_dir_loc;
+	vec3 view_dir = view_dir_loc;
+#if USE_NORMAL_MAP
+	normal = normal_map_sample;
+#if !OBJECT_SPACE_NORMAL_MAP
+	light_dir = light_dir_tan;
+	view_dir = view_dir_tan;
+#endif
+#endif
 
-	// diffuse light equation done in tangent space
-	vec3 direction_to_light_tan = normalize (-light_dir_tan);
-	float dot_prod = dot (direction_to_light_tan, normal_tan);
+	// diffuse light equation
+	vec3 direction_to_light = normalize (-light_dir);
+	float dot_prod = dot (direction_to_light, normal);
 	dot_prod = max (dot_prod, 0.0);
-	vec3 Id = vec3 (0.7, 0.7, 0.7) * vec3 (1.0, 0.5, 0.0) * dot_prod;
+	vec3 Id = vec3 (0.7, 0.7, 0.7) * Kd * dot_prod;
 
-	// specular light equation done in tangent space
-	vec3 reflection_tan = reflect (normalize (light_dir_tan), normal_tan);
-	float dot_prod_specular = dot (reflection_tan, normalize (view_dir_tan));
+	// specular light equation
+	vec3 reflection = reflect (normalize (light_dir), normal);
+	float dot_prod_specular = dot (reflection, normalize (view_dir));
 	dot_prod_specular = max (dot_prod_specular, 0.0);
-	float specular_factor = pow (dot_prod_specular, 100.0);
-	vec3 Is = vec3 (1.0, 1.0, 1.0) * vec3 (0.5, 0.5, 0.5) * specular_factor;
+	float specular_factor = pow (dot_prod_specular, specular_exponent);
+	vec3 Is = vec3 (1.0, 1.0, 1.0) * Ks * specular_factor;
 
 	// phong light output
 	frag_colour.rgb = Is + Id + Ia;
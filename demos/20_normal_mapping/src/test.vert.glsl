@@ -12,6 +12,10 @@ out vec4 test_tan;
 out vec2 st;
 out vec3 view_dir_tan;
 out vec3 light_dir_tan;
+// the same directions, and the vertex normal, in local (object) space
+out vec3 view_dir_loc;
+out vec3 light_dir_loc;
+out vec3 normal_loc;
 
 void main() {
 	gl_Position = proj * view * vec4 (vertex_position, 1.0);
@@ -31,9 +35,10 @@ void main() {
 	
 	/* transform our camera and light uniforms into local space */
 	vec3 cam_pos_loc = vec3 (inverse (model) * vec4 (cam_pos_wor, 1.0));
-	vec3 light_dir_loc = vec3 (inverse (model) * vec4 (light_dir_wor, 0.0));
+	light_dir_loc = vec3 (inverse (model) * vec4 (light_dir_wor, 0.0));
 	// ...and work out view _direction_ in local space
-	vec3 view_dir_loc = normalize (cam_pos_loc - vertex_position);
+	view_dir_loc = normalize (cam_pos_loc - vertex_position);
+	normal_loc = vertex_normal;
 	
 	/* this [dot,dot,dot] is the same as making a 3x3 inverse tangent matrix, and
 		 doing a matrix*vector multiplication.
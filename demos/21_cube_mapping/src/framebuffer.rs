@@ -0,0 +1,226 @@
+use gl;
+use gl::types::{GLenum, GLint, GLsizei, GLuint};
+
+use graphics_math as math;
+use math::{Mat4, Vec3};
+
+use std::ptr;
+
+
+///
+/// One face of a cube map, and the direction a camera must look, and which way is
+/// up for it, to render that face. The up vectors follow the cube map convention,
+/// where the side faces are stored upside down, so a face rendered with them can be
+/// sampled by direction without any flipping.
+///
+pub struct CubeMapFace {
+    pub target: GLenum,
+    pub forward: [f32; 3],
+    pub up: [f32; 3],
+}
+
+pub const CUBE_MAP_FACES: [CubeMapFace; 6] = [
+    CubeMapFace {
+        target: gl::TEXTURE_CUBE_MAP_POSITIVE_X, forward: [1.0, 0.0, 0.0], up: [0.0, -1.0, 0.0]
+    },
+    CubeMapFace {
+        target: gl::TEXTURE_CUBE_MAP_NEGATIVE_X, forward: [-1.0, 0.0, 0.0], up: [0.0, -1.0, 0.0]
+    },
+    CubeMapFace {
+        target: gl::TEXTURE_CUBE_MAP_POSITIVE_Y, forward: [0.0, 1.0, 0.0], up: [0.0, 0.0, 1.0]
+    },
+    CubeMapFace {
+        target: gl::TEXTURE_CUBE_MAP_NEGATIVE_Y, forward: [0.0, -1.0, 0.0], up: [0.0, 0.0, -1.0]
+    },
+    CubeMapFace {
+        target: gl::TEXTURE_CUBE_MAP_POSITIVE_Z, forward: [0.0, 0.0, 1.0], up: [0.0, -1.0, 0.0]
+    },
+    CubeMapFace {
+        target: gl::TEXTURE_CUBE_MAP_NEGATIVE_Z, forward: [0.0, 0.0, -1.0], up: [0.0, -1.0, 0.0]
+    },
+];
+
+///
+/// The view matrix of a camera at `eye` looking out through a cube map face.
+///
+pub fn face_view(eye: &Vec3, face: &CubeMapFace) -> Mat4 {
+    let f = math::vec3((face.forward[0], face.forward[1], face.forward[2]));
+    let u = math::vec3((face.up[0], face.up[1], face.up[2]));
+    let r = f.cross(&u);
+
+    // The rows of the rotation are the camera's axes, with the camera looking down -z.
+    let rotation = Mat4::new(
+        r.v[0], u.v[0], -f.v[0], 0.0,
+        r.v[1], u.v[1], -f.v[1], 0.0,
+        r.v[2], u.v[2], -f.v[2], 0.0,
+        0.0,    0.0,    0.0,     1.0
+    );
+
+    rotation * Mat4::from_translation(&-eye)
+}
+
+///
+/// The projection matrix for rendering a cube map face: a square 90 degree frustum,
+/// so the six faces meet exactly at their edges.
+///
+pub fn face_projection(near: f32, far: f32) -> Mat4 {
+    Mat4::perspective(90.0, 1.0, near, far)
+}
+
+///
+/// A framebuffer that renders into the faces of a cube map texture, one face at a
+/// time, with a depth buffer shared by all the faces. The texture can be sampled
+/// like any other cube map once the faces have been drawn.
+///
+pub struct CubeMapFramebuffer {
+    fbo: GLuint,
+    tex: GLuint,
+    depth_rb: GLuint,
+    size: u32,
+}
+
+impl CubeMapFramebuffer {
+    ///
+    /// Make a cube map with square faces `size` texels wide, and a framebuffer to
+    /// render into it. Returns an error if the driver cannot render to it.
+    ///
+    pub fn new(size: u32) -> Result<CubeMapFramebuffer, String> {
+        let mut tex = 0;
+        unsafe {
+            gl::GenTextures(1, &mut tex);
+            gl::BindTexture(gl::TEXTURE_CUBE_MAP, tex);
+            for face in CUBE_MAP_FACES.iter() {
+                gl_check!(gl::TexImage2D(
+                    face.target, 0, gl::RGBA as GLint, size as GLsizei, size as GLsizei, 0,
+                    gl::RGBA, gl::UNSIGNED_BYTE, ptr::null()
+                ));
+            }
+            gl::TexParameteri(gl::TEXTURE_CUBE_MAP, gl::TEXTURE_MAG_FILTER, gl::LINEAR as GLint);
+            gl::TexParameteri(gl::TEXTURE_CUBE_MAP, gl::TEXTURE_MIN_FILTER, gl::LINEAR as GLint);
+            gl::TexParameteri(gl::TEXTURE_CUBE_MAP, gl::TEXTURE_WRAP_R, gl::CLAMP_TO_EDGE as GLint);
+            gl::TexParameteri(gl::TEXTURE_CUBE_MAP, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as GLint);
+            gl::TexParameteri(gl::TEXTURE_CUBE_MAP, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as GLint);
+        }
+
+        let mut depth_rb = 0;
+        unsafe {
+            gl::GenRenderbuffers(1, &mut depth_rb);
+            gl::BindRenderbuffer(gl::RENDERBUFFER, depth_rb);
+            gl_check!(gl::RenderbufferStorage(gl::RENDERBUFFER, gl::DEPTH_COMPONENT24, size as GLsizei, size as GLsizei));
+        }
+
+        let mut fbo = 0;
+        unsafe {
+            gl::GenFramebuffers(1, &mut fbo);
+            gl::BindFramebuffer(gl::FRAMEBUFFER, fbo);
+            gl_check!(gl::FramebufferTexture2D(
+                gl::FRAMEBUFFER, gl::COLOR_ATTACHMENT0, CUBE_MAP_FACES[0].target, tex, 0
+            ));
+            gl_check!(gl::FramebufferRenderbuffer(gl::FRAMEBUFFER, gl::DEPTH_ATTACHMENT, gl::RENDERBUFFER, depth_rb));
+        }
+        let status = unsafe { gl::CheckFramebufferStatus(gl::FRAMEBUFFER) };
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+        }
+
+        let framebuffer = CubeMapFramebuffer {
+            fbo: fbo,
+            tex: tex,
+            depth_rb: depth_rb,
+            size: size,
+        };
+        if status != gl::FRAMEBUFFER_COMPLETE {
+            return Err(format!(
+                "ERROR: cube map framebuffer is incomplete. Status: 0x{:x}", status
+            ));
+        }
+
+        Ok(framebuffer)
+    }
+
+    ///
+    /// The cube map texture the faces are rendered into.
+    ///
+    pub fn texture(&self) -> GLuint {
+        self.tex
+    }
+
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+
+    ///
+    /// Bind the framebuffer, attach one face of the cube map to it, and set the
+    /// viewport to cover the face. Draw calls after this render into that face.
+    ///
+    pub fn bind_face(&self, face: &CubeMapFace) {
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, self.fbo);
+            gl_check!(gl::FramebufferTexture2D(gl::FRAMEBUFFER, gl::COLOR_ATTACHMENT0, face.target, self.tex, 0));
+            gl::Viewport(0, 0, self.size as GLsizei, self.size as GLsizei);
+        }
+    }
+
+    ///
+    /// Go back to drawing to the window. The caller has to restore the viewport.
+    ///
+    pub fn unbind(&self) {
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+        }
+    }
+}
+
+impl Drop for CubeMapFramebuffer {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteFramebuffers(1, &self.fbo);
+            gl::DeleteRenderbuffers(1, &self.depth_rb);
+            gl::DeleteTextures(1, &self.tex);
+        }
+    }
+}
+
+mod framebuffer_tests {
+    use super::{face_view, CUBE_MAP_FACES};
+    use graphics_math as math;
+
+    const EPSILON: f32 = 1e-5;
+
+    fn transform(face: usize, eye: (f32, f32, f32), point: (f32, f32, f32)) -> math::Vec4 {
+        let view = face_view(&math::vec3(eye), &CUBE_MAP_FACES[face]);
+        view * math::vec4((point.0, point.1, point.2, 1.0))
+    }
+
+    #[test]
+    fn test_face_view_looks_down_the_face_direction() {
+        for (i, face) in CUBE_MAP_FACES.iter().enumerate() {
+            let ahead = (face.forward[0] + 1.0, face.forward[1] + 2.0, face.forward[2] + 3.0);
+            let p = transform(i, (1.0, 2.0, 3.0), ahead);
+
+            assert!(p.v[0].abs() < EPSILON, "face {}: {:?}", i, p);
+            assert!(p.v[1].abs() < EPSILON, "face {}: {:?}", i, p);
+            assert!((p.v[2] + 1.0).abs() < EPSILON, "face {}: {:?}", i, p);
+        }
+    }
+
+    #[test]
+    fn test_face_view_matches_the_cube_map_texture_coordinates() {
+        // The +x face is addressed with s = -z and t = -y, so a point towards -z
+        // must land on the right of the face, and a point towards -y at the top.
+        let right = transform(0, (0.0, 0.0, 0.0), (1.0, 0.0, -0.5));
+        let top = transform(0, (0.0, 0.0, 0.0), (1.0, -0.5, 0.0));
+
+        assert!(right.v[0] > 0.0);
+        assert!(top.v[1] > 0.0);
+    }
+
+    #[test]
+    fn test_face_views_are_rotations() {
+        for (i, face) in CUBE_MAP_FACES.iter().enumerate() {
+            let view = face_view(&math::vec3((0.0, 0.0, 0.0)), face);
+
+            assert!((view.determinant() - 1.0).abs() < EPSILON, "face {}", i);
+        }
+    }
+}
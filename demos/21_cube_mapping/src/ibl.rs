@@ -0,0 +1,350 @@
+use gl;
+use gl::types::{GLint, GLsizei, GLuint};
+
+use graphics_math as math;
+use math::Vec3;
+
+use framebuffer::{self, CUBE_MAP_FACES};
+use gl_utils::uniform_location;
+
+use std::f32::consts::PI;
+use std::ptr;
+
+
+///
+/// The width of the sharpest level of the prefiltered environment map. Each level
+/// down is half as wide and blurred for a rougher surface, and rough reflections
+/// need far fewer texels than sharp ones.
+///
+pub const PREFILTER_SIZE: u32 = 128;
+///
+/// The number of mip levels of the prefiltered map, from a roughness of 0 at the
+/// top level to 1 at the bottom one.
+///
+pub const PREFILTER_MIP_LEVELS: u32 = 5;
+///
+/// The width and height of the BRDF lookup table, and how many directions are
+/// sampled for each of its texels.
+///
+pub const BRDF_LUT_SIZE: usize = 32;
+pub const BRDF_SAMPLE_COUNT: u32 = 256;
+
+
+///
+/// The roughness that mip `level` of a prefiltered map with `levels` levels is
+/// blurred for. The shader picks a level the other way round, with
+/// `roughness * (levels - 1)`.
+///
+pub fn mip_roughness(level: u32, levels: u32) -> f32 {
+    if levels <= 1 {
+        return 0.0;
+    }
+
+    level as f32 / (levels - 1) as f32
+}
+
+///
+/// The width of mip `level` of a texture `size` texels wide.
+///
+pub fn mip_size(size: u32, level: u32) -> u32 {
+    u32::max(size >> level, 1)
+}
+
+///
+/// Sample `i` of a Hammersley set of `count` points, spread evenly over the unit
+/// square without the clumps random points fall into. The same sum as
+/// `prefilter_fs.glsl`.
+///
+pub fn hammersley(i: u32, count: u32) -> (f32, f32) {
+    // Mirror the bits of i about the binary point.
+    let radical_inverse = i.reverse_bits() as f32 * 2.328_306_4e-10;
+
+    (i as f32 / count as f32, radical_inverse)
+}
+
+///
+/// A half vector around the normal +z, picked more often where the GGX
+/// distribution of microfacet normals for `roughness` is denser, from a point on
+/// the unit square.
+///
+pub fn importance_sample_ggx(xi: (f32, f32), roughness: f32) -> Vec3 {
+    let a = roughness * roughness;
+    let phi = 2.0 * PI * xi.0;
+    let cos_theta = f32::sqrt((1.0 - xi.1) / (1.0 + (a * a - 1.0) * xi.1));
+    let sin_theta = f32::sqrt(1.0 - cos_theta * cos_theta);
+
+    math::vec3((f32::cos(phi) * sin_theta, f32::sin(phi) * sin_theta, cos_theta))
+}
+
+///
+/// How much of a surface of the given roughness is neither hidden from the viewer
+/// nor shadowed from the light by its own microfacets, for image based lighting.
+///
+pub fn geometry_smith(n_dot_v: f32, n_dot_l: f32, roughness: f32) -> f32 {
+    let k = roughness * roughness / 2.0;
+    let schlick_ggx = |n_dot_x: f32| n_dot_x / (n_dot_x * (1.0 - k) + k);
+
+    schlick_ggx(n_dot_v) * schlick_ggx(n_dot_l)
+}
+
+///
+/// The split sum approximation's second half: the scale and bias to the Fresnel
+/// reflectance at normal incidence that give a surface's total specular reflection
+/// of an evenly white environment, seen at `n_dot_v`.
+///
+pub fn integrate_brdf(n_dot_v: f32, roughness: f32, sample_count: u32) -> (f32, f32) {
+    let n_dot_v = f32::max(n_dot_v, 1e-4);
+    let v = math::vec3((f32::sqrt(1.0 - n_dot_v * n_dot_v), 0.0, n_dot_v));
+    let mut scale = 0.0;
+    let mut bias = 0.0;
+    for i in 0..sample_count {
+        let h = importance_sample_ggx(hammersley(i, sample_count), roughness);
+        let v_dot_h = v.dot(&h);
+        let l = h * (2.0 * v_dot_h) - v;
+        let n_dot_l = f32::max(l.v[2], 0.0);
+        let n_dot_h = f32::max(h.v[2], 0.0);
+        let v_dot_h = f32::max(v_dot_h, 0.0);
+        if n_dot_l > 0.0 {
+            let g = geometry_smith(n_dot_v, n_dot_l, roughness);
+            let g_vis = g * v_dot_h / (n_dot_h * n_dot_v);
+            let fc = f32::powi(1.0 - v_dot_h, 5);
+            scale += (1.0 - fc) * g_vis;
+            bias += fc * g_vis;
+        }
+    }
+
+    (scale / sample_count as f32, bias / sample_count as f32)
+}
+
+///
+/// The BRDF lookup table, `size` texels on a side, with `n_dot_v` across and
+/// roughness up. Each texel holds the scale and then the bias from
+/// `integrate_brdf`, both taken at the centre of the texel.
+///
+pub fn brdf_lut(size: usize, sample_count: u32) -> Vec<f32> {
+    let mut lut = Vec::with_capacity(2 * size * size);
+    for row in 0..size {
+        let roughness = (row as f32 + 0.5) / size as f32;
+        for column in 0..size {
+            let n_dot_v = (column as f32 + 0.5) / size as f32;
+            let (scale, bias) = integrate_brdf(n_dot_v, roughness, sample_count);
+            lut.push(scale);
+            lut.push(bias);
+        }
+    }
+
+    lut
+}
+
+///
+/// The two textures specular image based lighting needs besides the environment
+/// itself. The prefiltered map is the environment blurred for a rougher surface at
+/// each mip level down, so a shader reads a rough reflection with a single lookup
+/// at the right level. The BRDF lookup table holds the rest of the split sum, and
+/// only depends on the BRDF, so it is worked out once on the CPU.
+///
+pub struct SpecularIbl {
+    prefilter_sp: GLuint,
+    view_location: GLint,
+    proj_location: GLint,
+    roughness_location: GLint,
+    cube_vao: GLuint,
+    fbo: GLuint,
+    prefiltered_tex: GLuint,
+    brdf_lut_tex: GLuint,
+}
+
+impl SpecularIbl {
+    ///
+    /// Make the prefiltered map and the BRDF lookup table. The prefilter shader
+    /// programme draws the environment over the inside of `cube_vao`, the sky box's
+    /// cube. Returns an error if the driver cannot render to the prefiltered map.
+    ///
+    pub fn new(prefilter_sp: GLuint, cube_vao: GLuint) -> Result<SpecularIbl, String> {
+        let location = |name: &str| {
+            let location = uniform_location(prefilter_sp, name);
+            assert!(location > -1);
+            location
+        };
+
+        let mut prefiltered_tex = 0;
+        unsafe {
+            gl::GenTextures(1, &mut prefiltered_tex);
+            gl::BindTexture(gl::TEXTURE_CUBE_MAP, prefiltered_tex);
+            for level in 0..PREFILTER_MIP_LEVELS {
+                let size = mip_size(PREFILTER_SIZE, level) as GLsizei;
+                for face in CUBE_MAP_FACES.iter() {
+                    gl_check!(gl::TexImage2D(
+                        face.target, level as GLint, gl::RGBA16F as GLint, size, size, 0,
+                        gl::RGBA, gl::FLOAT, ptr::null()
+                    ));
+                }
+            }
+            gl::TexParameteri(gl::TEXTURE_CUBE_MAP, gl::TEXTURE_MAX_LEVEL, (PREFILTER_MIP_LEVELS - 1) as GLint);
+            gl::TexParameteri(gl::TEXTURE_CUBE_MAP, gl::TEXTURE_MAG_FILTER, gl::LINEAR as GLint);
+            gl::TexParameteri(gl::TEXTURE_CUBE_MAP, gl::TEXTURE_MIN_FILTER, gl::LINEAR_MIPMAP_LINEAR as GLint);
+            gl::TexParameteri(gl::TEXTURE_CUBE_MAP, gl::TEXTURE_WRAP_R, gl::CLAMP_TO_EDGE as GLint);
+            gl::TexParameteri(gl::TEXTURE_CUBE_MAP, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as GLint);
+            gl::TexParameteri(gl::TEXTURE_CUBE_MAP, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as GLint);
+            // Blend across the seams between faces, or the blurry levels show them.
+            gl::Enable(gl::TEXTURE_CUBE_MAP_SEAMLESS);
+        }
+
+        let lut = brdf_lut(BRDF_LUT_SIZE, BRDF_SAMPLE_COUNT);
+        let mut brdf_lut_tex = 0;
+        unsafe {
+            gl::GenTextures(1, &mut brdf_lut_tex);
+            gl::BindTexture(gl::TEXTURE_2D, brdf_lut_tex);
+            gl_check!(gl::TexImage2D(
+                gl::TEXTURE_2D, 0, gl::RG16F as GLint, BRDF_LUT_SIZE as GLsizei, BRDF_LUT_SIZE as GLsizei, 0,
+                gl::RG, gl::FLOAT, lut.as_ptr() as *const _
+            ));
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::LINEAR as GLint);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::LINEAR as GLint);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as GLint);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as GLint);
+        }
+
+        // No depth buffer: the cube is all there is to draw.
+        let mut fbo = 0;
+        unsafe {
+            gl::GenFramebuffers(1, &mut fbo);
+            gl::BindFramebuffer(gl::FRAMEBUFFER, fbo);
+            gl_check!(gl::FramebufferTexture2D(
+                gl::FRAMEBUFFER, gl::COLOR_ATTACHMENT0, CUBE_MAP_FACES[0].target, prefiltered_tex, 0
+            ));
+        }
+        let status = unsafe { gl::CheckFramebufferStatus(gl::FRAMEBUFFER) };
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+        }
+
+        let ibl = SpecularIbl {
+            prefilter_sp: prefilter_sp,
+            view_location: location("V"),
+            proj_location: location("P"),
+            roughness_location: location("roughness"),
+            cube_vao: cube_vao,
+            fbo: fbo,
+            prefiltered_tex: prefiltered_tex,
+            brdf_lut_tex: brdf_lut_tex,
+        };
+        if status != gl::FRAMEBUFFER_COMPLETE {
+            return Err(format!("ERROR: prefiltered map framebuffer is incomplete. Status: 0x{:x}", status));
+        }
+
+        Ok(ibl)
+    }
+
+    ///
+    /// Blur the environment cube map `source` into each level of the prefiltered
+    /// map. Call again whenever the environment changes. The caller has to restore
+    /// the viewport afterwards.
+    ///
+    pub fn prefilter(&self, source: GLuint) {
+        let proj = framebuffer::face_projection(0.1, 100.0);
+        let origin = math::vec3((0.0, 0.0, 0.0));
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, self.fbo);
+            gl::UseProgram(self.prefilter_sp);
+            gl_check!(gl::UniformMatrix4fv(self.proj_location, 1, gl::FALSE, proj.as_ptr()));
+            gl::ActiveTexture(gl::TEXTURE0);
+            gl::BindTexture(gl::TEXTURE_CUBE_MAP, source);
+            gl::BindVertexArray(self.cube_vao);
+            gl::DepthMask(gl::FALSE);
+        }
+        for level in 0..PREFILTER_MIP_LEVELS {
+            let size = mip_size(PREFILTER_SIZE, level) as GLsizei;
+            unsafe {
+                gl::Viewport(0, 0, size, size);
+                gl_check!(gl::Uniform1f(self.roughness_location, mip_roughness(level, PREFILTER_MIP_LEVELS)));
+            }
+            for face in CUBE_MAP_FACES.iter() {
+                let view = framebuffer::face_view(&origin, face);
+                unsafe {
+                    gl_check!(gl::FramebufferTexture2D(
+                        gl::FRAMEBUFFER, gl::COLOR_ATTACHMENT0, face.target, self.prefiltered_tex, level as GLint
+                    ));
+                    gl_check!(gl::UniformMatrix4fv(self.view_location, 1, gl::FALSE, view.as_ptr()));
+                    gl_check!(gl::DrawArrays(gl::TRIANGLES, 0, 36));
+                }
+            }
+        }
+        unsafe {
+            gl::DepthMask(gl::TRUE);
+            gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+        }
+    }
+
+    pub fn prefiltered_texture(&self) -> GLuint {
+        self.prefiltered_tex
+    }
+
+    pub fn brdf_lut_texture(&self) -> GLuint {
+        self.brdf_lut_tex
+    }
+
+    ///
+    /// The level of detail of the roughest level, for the shader to scale
+    /// roughness by.
+    ///
+    pub fn max_lod(&self) -> f32 {
+        (PREFILTER_MIP_LEVELS - 1) as f32
+    }
+}
+
+impl Drop for SpecularIbl {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteFramebuffers(1, &self.fbo);
+            gl::DeleteTextures(1, &self.prefiltered_tex);
+            gl::DeleteTextures(1, &self.brdf_lut_tex);
+        }
+    }
+}
+
+mod ibl_tests {
+    use super::{brdf_lut, hammersley, importance_sample_ggx, integrate_brdf, mip_roughness, mip_size};
+
+    #[test]
+    fn test_the_mip_levels_run_from_smooth_to_rough() {
+        assert_eq!(mip_roughness(0, 5), 0.0);
+        assert_eq!(mip_roughness(2, 5), 0.5);
+        assert_eq!(mip_roughness(4, 5), 1.0);
+        assert_eq!(mip_size(128, 4), 8);
+        assert_eq!(mip_size(4, 5), 1);
+    }
+
+    #[test]
+    fn test_the_hammersley_points_stay_on_the_unit_square() {
+        assert_eq!(hammersley(0, 4), (0.0, 0.0));
+        assert_eq!(hammersley(1, 4), (0.25, 0.5));
+        assert_eq!(hammersley(2, 4), (0.5, 0.25));
+    }
+
+    #[test]
+    fn test_a_smooth_surface_samples_only_the_normal() {
+        let h = importance_sample_ggx((0.3, 0.7), 0.0);
+
+        assert!(f32::abs(h.v[2] - 1.0) < 1e-6);
+        // and rougher surfaces stray from it, but never below the surface
+        let h = importance_sample_ggx((0.3, 0.7), 0.8);
+        assert!(h.v[2] > 0.0 && h.v[2] < 1.0);
+        assert!(f32::abs(h.norm() - 1.0) < 1e-5);
+    }
+
+    #[test]
+    fn test_a_smooth_surface_seen_head_on_reflects_everything() {
+        let (scale, bias) = integrate_brdf(1.0, 0.0, 64);
+
+        assert!(f32::abs(scale + bias - 1.0) < 1e-3, "{} + {}", scale, bias);
+    }
+
+    #[test]
+    fn test_the_lookup_table_holds_a_scale_and_bias_per_texel() {
+        let lut = brdf_lut(8, 32);
+
+        assert_eq!(lut.len(), 2 * 8 * 8);
+        assert!(lut.iter().all(|&value| value >= 0.0 && value <= 1.0 + 1e-3));
+    }
+}
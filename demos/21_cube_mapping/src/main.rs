@@ -3,25 +3,41 @@ extern crate glfw;
 extern crate chrono;
 extern crate stb_image;
 extern crate png;
+#[cfg(feature = "assimp")]
 extern crate assimp;
-
-#[macro_use] 
-extern crate scan_fmt;
-
+#[cfg(feature = "gltf")]
+extern crate gltf;
+#[cfg(feature = "glam")]
+extern crate glam;
+#[cfg(feature = "cgmath")]
+extern crate cgmath;
+
+#[macro_use]
 mod gl_utils;
 mod graphics_math;
 mod obj_parser;
+mod colour;
+mod assets;
+mod shader_include;
+mod spirv;
+mod program_cache;
 mod logger;
+mod model;
+mod mesh_cache;
+mod resource_cache;
+mod geometry;
+mod framebuffer;
+mod vertex_layout;
+mod vertex_packing;
+mod ibl;
+mod pbr_material;
 
 
 use glfw::{Action, Context, Key};
-use gl::types::{GLenum, GLfloat, GLsizeiptr, GLvoid, GLuint};
+use gl::types::{GLenum, GLfloat, GLint, GLvoid, GLuint};
 
-use std::mem;
-use std::ptr;
 use std::process;
 
-use stb_image::image;
 use stb_image::image::LoadResult;
 
 use gl_utils::*;
@@ -29,6 +45,12 @@ use gl_utils::*;
 use graphics_math as math;
 use math::{Vec3, Mat4, Versor};
 
+use model::Model;
+use framebuffer::{CubeMapFramebuffer, CUBE_MAP_FACES};
+use ibl::SpecularIbl;
+use pbr_material::PbrMaterial;
+use vertex_layout::VertexLayout;
+
 
 const GL_LOG_FILE: &str = "gl.log";
 const MESH_FILE: &str = "src/suzanne.obj";
@@ -47,6 +69,193 @@ const TOP: &str = "src/posy.jpg";
 const BOTTOM: &str = "src/negy.jpg";
 const LEFT: &str = "src/negx.jpg";
 const RIGHT: &str = "src/posx.jpg";
+const SPHERE_VERT_FILE: &str = "src/sphere_vs.glsl";
+const SPHERE_FRAG_FILE: &str = "src/sphere_fs.glsl";
+/* the monkey can also be shaded as a metallic-roughness material lit by the
+environment, with the rough reflections read from a prefiltered copy of it. */
+const PBR_FRAG_FILE: &str = "src/pbr_fs.glsl";
+const PREFILTER_FRAG_FILE: &str = "src/prefilter_fs.glsl";
+const MONKEY_ALBEDO: [f32; 3] = [1.0, 0.78, 0.34];
+/* how far each key press moves the metallic and roughness sliders. */
+const MATERIAL_STEP: f32 = 0.05;
+
+/* the environment cube map is re-rendered from the monkey's position at this
+resolution. reflections are distorted anyway, so it can be far below the window's. */
+const ENV_MAP_SIZE: u32 = 256;
+const ENV_MAP_NEAR: f32 = 0.1;
+const ENV_MAP_FAR: f32 = 100.0;
+/* re-render the environment every this many frames. */
+const DEFAULT_CAPTURE_INTERVAL: u32 = 2;
+const MAX_CAPTURE_INTERVAL: u32 = 60;
+
+/* a sphere circling the monkey, so there is something moving to see in the reflection. */
+struct OrbitingSphere {
+    radius: f32,
+    height: f32,
+    degrees_per_second: f32,
+    phase_deg: f32,
+    scale: f32,
+    colour: [f32; 3],
+}
+
+impl OrbitingSphere {
+    fn model_matrix(&self, seconds: f64) -> Mat4 {
+        let angle = self.phase_deg + self.degrees_per_second * seconds as f32;
+        Mat4::from_scale(&math::vec3((self.scale, self.scale, self.scale)))
+            .then_translate(&math::vec3((self.radius, self.height, 0.0)))
+            .rotate_y_deg(angle)
+    }
+}
+
+const SPHERES: [OrbitingSphere; 3] = [
+    OrbitingSphere {
+        radius: 2.0, height: 0.0, degrees_per_second: 40.0, phase_deg: 0.0, scale: 0.4, colour: [0.9, 0.2, 0.2]
+    },
+    OrbitingSphere {
+        radius: 2.6, height: 0.8, degrees_per_second: -25.0, phase_deg: 120.0, scale: 0.3, colour: [0.2, 0.8, 0.3]
+    },
+    OrbitingSphere {
+        radius: 3.2, height: -0.6, degrees_per_second: 15.0, phase_deg: 240.0, scale: 0.5, colour: [0.2, 0.4, 0.9]
+    },
+];
+
+/* the sky-box shader and the big cube it is drawn on. */
+struct Skybox {
+    sp: GLuint,
+    view_location: GLint,
+    proj_location: GLint,
+    vao: GLuint,
+}
+
+impl Skybox {
+    /* the view matrix should NOT contain camera translation. */
+    fn draw(&self, texture: GLuint, view_rotation: &Mat4, proj: &Mat4) {
+        unsafe {
+            gl::DepthMask(gl::FALSE);
+            gl::UseProgram(self.sp);
+            gl::UniformMatrix4fv(self.view_location, 1, gl::FALSE, view_rotation.as_ptr());
+            gl::UniformMatrix4fv(self.proj_location, 1, gl::FALSE, proj.as_ptr());
+            gl::ActiveTexture(gl::TEXTURE0);
+            gl::BindTexture(gl::TEXTURE_CUBE_MAP, texture);
+            gl::BindVertexArray(self.vao);
+            gl::DrawArrays(gl::TRIANGLES, 0, 36);
+            gl::DepthMask(gl::TRUE);
+        }
+    }
+}
+
+/* the shader and mesh the orbiting spheres are drawn with. */
+struct SphereRenderer {
+    sp: GLuint,
+    model_location: GLint,
+    view_location: GLint,
+    proj_location: GLint,
+    colour_location: GLint,
+    vao: GLuint,
+    point_count: usize,
+}
+
+impl SphereRenderer {
+    fn draw(&self, spheres: &[OrbitingSphere], seconds: f64, view: &Mat4, proj: &Mat4) {
+        unsafe {
+            gl::UseProgram(self.sp);
+            gl::UniformMatrix4fv(self.view_location, 1, gl::FALSE, view.as_ptr());
+            gl::UniformMatrix4fv(self.proj_location, 1, gl::FALSE, proj.as_ptr());
+            gl::BindVertexArray(self.vao);
+        }
+        for sphere in spheres.iter() {
+            let model_mat = sphere.model_matrix(seconds);
+            unsafe {
+                gl::UniformMatrix4fv(self.model_location, 1, gl::FALSE, model_mat.as_ptr());
+                gl::Uniform3f(self.colour_location, sphere.colour[0], sphere.colour[1], sphere.colour[2]);
+                gl::DrawArrays(gl::TRIANGLES, 0, self.point_count as i32);
+            }
+        }
+    }
+}
+
+/* the monkey's PBR shader, with its material's uniforms. */
+struct PbrShader {
+    sp: GLuint,
+    model_location: GLint,
+    view_location: GLint,
+    proj_location: GLint,
+    albedo_location: GLint,
+    metallic_location: GLint,
+    roughness_location: GLint,
+    max_lod_location: GLint,
+}
+
+impl PbrShader {
+    fn new(sp: GLuint) -> PbrShader {
+        let location = |name: &str| {
+            let location = uniform_location(sp, name);
+            assert!(location > -1);
+            location
+        };
+        unsafe {
+            gl::UseProgram(sp);
+            gl::Uniform1i(location("prefiltered_map"), 0);
+            gl::Uniform1i(location("brdf_lut"), 1);
+        }
+
+        PbrShader {
+            sp: sp,
+            model_location: location("M"),
+            view_location: location("V"),
+            proj_location: location("P"),
+            albedo_location: location("albedo"),
+            metallic_location: location("metallic"),
+            roughness_location: location("roughness"),
+            max_lod_location: location("max_lod"),
+        }
+    }
+
+    fn set_camera(&self, view: &Mat4, proj: &Mat4) {
+        unsafe {
+            gl::UseProgram(self.sp);
+            gl::UniformMatrix4fv(self.view_location, 1, gl::FALSE, view.as_ptr());
+            gl::UniformMatrix4fv(self.proj_location, 1, gl::FALSE, proj.as_ptr());
+        }
+    }
+
+    fn draw(&self, vao: GLuint, point_count: usize, model: &Mat4, material: &PbrMaterial, ibl: &SpecularIbl) {
+        unsafe {
+            gl::UseProgram(self.sp);
+            gl::UniformMatrix4fv(self.model_location, 1, gl::FALSE, model.as_ptr());
+            gl::Uniform3f(self.albedo_location, material.albedo[0], material.albedo[1], material.albedo[2]);
+            gl::Uniform1f(self.metallic_location, material.metallic);
+            gl::Uniform1f(self.roughness_location, material.roughness);
+            gl::Uniform1f(self.max_lod_location, ibl.max_lod());
+            gl::ActiveTexture(gl::TEXTURE0);
+            gl::BindTexture(gl::TEXTURE_CUBE_MAP, ibl.prefiltered_texture());
+            gl::ActiveTexture(gl::TEXTURE1);
+            gl::BindTexture(gl::TEXTURE_2D, ibl.brdf_lut_texture());
+            gl::ActiveTexture(gl::TEXTURE0);
+            gl::BindVertexArray(vao);
+            gl::DrawArrays(gl::TRIANGLES, 0, point_count as i32);
+        }
+    }
+}
+
+/* render the scene around `eye` into the six faces of the environment cube map.
+the monkey itself is left out, since it cannot see its own reflection. */
+fn capture_environment(
+    env_map: &CubeMapFramebuffer, eye: &Vec3, skybox: &Skybox, sky_texture: GLuint,
+    sphere_renderer: &SphereRenderer, seconds: f64) {
+
+    let proj = framebuffer::face_projection(ENV_MAP_NEAR, ENV_MAP_FAR);
+    let origin = math::vec3((0.0, 0.0, 0.0));
+    for face in CUBE_MAP_FACES.iter() {
+        env_map.bind_face(face);
+        unsafe {
+            gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
+        }
+        skybox.draw(sky_texture, &framebuffer::face_view(&origin, face), &proj);
+        sphere_renderer.draw(&SPHERES, seconds, &framebuffer::face_view(eye, face), &proj);
+    }
+    env_map.unbind();
+}
 
 /* big cube. returns Vertex Array Object */
 fn make_big_cube() -> GLuint {
@@ -70,24 +279,12 @@ fn make_big_cube() -> GLuint {
          10.0, -10.0, -10.0, -10.0, -10.0,  10.0,  10.0, -10.0,  10.0
     ];
 
-    let mut vbo = 0;
-    unsafe {
-        gl::GenBuffers(1, &mut vbo);
-        gl::BindBuffer(gl::ARRAY_BUFFER, vbo);
-        gl::BufferData(
-            gl::ARRAY_BUFFER, (3 * 36 * mem::size_of::<GLfloat>()) as GLsizeiptr,
-            points.as_ptr() as *const GLvoid, gl::STATIC_DRAW
-        );
-    }
-
     let mut vao = 0;
     unsafe {
         gl::GenVertexArrays(1, &mut vao);
-        gl::BindVertexArray(vao);
-        gl::EnableVertexAttribArray(0);
-        gl::BindBuffer(gl::ARRAY_BUFFER, vbo);
-        gl::VertexAttribPointer(0, 3, gl::FLOAT, gl::FALSE, 0, ptr::null());
     }
+    assert!(vao > 0);
+    VertexLayout::new().floats(0, 3).upload(vao, &points, 36);
 
     vao
 }
@@ -100,7 +297,7 @@ fn load_cube_map_side(texture: GLuint, side_target: GLenum, file_name: &str) ->
     }
 
     let force_channels = 4;
-    let image_data = match image::load_with_depth(file_name, force_channels, false) {
+    let image_data = match assets::load_image_with_depth(file_name, force_channels) {
         LoadResult::ImageU8(image_data) => image_data,
         LoadResult::Error(_) => {
             eprintln!("ERROR: could not load {}", file_name);
@@ -185,11 +382,12 @@ fn main() {
     assert!(cube_map_texture > 0);
 
     /*------------------------------CREATE GEOMETRY------------------------------*/
-    let mesh = match obj_parser::load_obj_file(MESH_FILE) {
+    let mesh = match Model::load_cached(MESH_FILE) {
         Ok(val) => val,
         Err(e) => {
             logger.log_err(&format!("ERROR: loading mesh file. Loader returned error\n{}", e));
-            process::exit(1);
+            logger.log_err("WARNING: using the embedded fallback mesh instead.");
+            Model::from_obj_mesh(assets::fallback_mesh(), vec![])
         }
     };
 
@@ -200,65 +398,112 @@ fn main() {
     let mut vao = 0;
     unsafe {
         gl::GenVertexArrays(1, &mut vao);
-        gl::BindVertexArray(vao);
     }
     assert!(vao > 0);
 
-    let mut points_vbo = 0;
-    unsafe {
-        gl::GenBuffers(1, &mut points_vbo);
-        gl::BindBuffer(gl::ARRAY_BUFFER, points_vbo);
-        gl::BufferData(
-            gl::ARRAY_BUFFER, (3 * g_point_count * mem::size_of::<GLfloat>()) as GLsizeiptr, 
-            g_vp.as_ptr() as *const GLvoid, gl::STATIC_DRAW
-        );
-        gl::VertexAttribPointer(0, 3, gl::FLOAT, gl::FALSE, 0, ptr::null());
-        gl::EnableVertexAttribArray(0);
-    }
-    assert!(points_vbo > 0);
+    VertexLayout::new().floats(0, 3).upload(vao, &g_vp, g_point_count);
+    VertexLayout::new().floats(1, 3).upload(vao, &g_vn, g_point_count);
 
-    let mut normals_vbo = 0;
+    // the orbiting spheres use the embedded unit sphere
+    let sphere_mesh = assets::fallback_mesh();
+    let mut sphere_vao = 0;
     unsafe {
-        gl::GenBuffers( 1, &mut normals_vbo);
-        gl::BindBuffer(gl::ARRAY_BUFFER, normals_vbo);
-        gl::BufferData(
-            gl::ARRAY_BUFFER, (3 * g_point_count * mem::size_of::<GLfloat>()) as GLsizeiptr, 
-            g_vn.as_ptr() as *const GLvoid, gl::STATIC_DRAW
-        );
-        gl::VertexAttribPointer(1, 3, gl::FLOAT, gl::FALSE, 0, ptr::null());
-        gl::EnableVertexAttribArray(1);
+        gl::GenVertexArrays(1, &mut sphere_vao);
     }
-    assert!(normals_vbo > 0);
+    assert!(sphere_vao > 0);
+
+    VertexLayout::new().floats(0, 3).upload(sphere_vao, &sphere_mesh.points, sphere_mesh.point_count);
+    VertexLayout::new().floats(1, 3).upload(sphere_vao, &sphere_mesh.normals, sphere_mesh.point_count);
 
     /*-------------------------------CREATE SHADERS-------------------------------*/
     let monkey_sp = create_programme_from_files(&logger, MONKEY_VERT_FILE, MONKEY_FRAG_FILE);
     assert!(monkey_sp > 0);
 
-    let monkey_M_location = unsafe {
-        gl::GetUniformLocation(monkey_sp, "M".as_ptr() as *const i8)
-    };
+    let monkey_M_location = uniform_location(monkey_sp, "M");
     //assert!(monkey_M_location > -1);
-    let monkey_V_location = unsafe { 
-        gl::GetUniformLocation(monkey_sp, "V".as_ptr() as *const i8)
-    };
+    let monkey_V_location = uniform_location(monkey_sp, "V");
     assert!(monkey_V_location > -1);
-    let monkey_P_location = unsafe { 
-        gl::GetUniformLocation(monkey_sp, "P".as_ptr() as *const i8)
-    };
+    let monkey_P_location = uniform_location(monkey_sp, "P");
     assert!(monkey_P_location > -1);
 
     // cube-map shaders
     let cube_sp = create_programme_from_files(&logger, CUBE_VERT_FILE, CUBE_FRAG_FILE);
     assert!(cube_sp > 0);
     // note that this view matrix should NOT contain camera translation.
-    let cube_V_location = unsafe {
-        gl::GetUniformLocation(cube_sp, "V".as_ptr() as *const i8)
-    };
+    let cube_V_location = uniform_location(cube_sp, "V");
     assert!(cube_V_location > -1);
-    let cube_P_location = unsafe {
-        gl::GetUniformLocation(cube_sp, "P".as_ptr() as *const i8)
-    };
+    let cube_P_location = uniform_location(cube_sp, "P");
     assert!(cube_P_location > -1);
+    let skybox = Skybox {
+        sp: cube_sp,
+        view_location: cube_V_location,
+        proj_location: cube_P_location,
+        vao: cube_vao,
+    };
+
+    // shaders for the orbiting spheres
+    let sphere_sp = create_programme_from_files(&logger, SPHERE_VERT_FILE, SPHERE_FRAG_FILE);
+    assert!(sphere_sp > 0);
+    let sphere_M_location = uniform_location(sphere_sp, "M");
+    assert!(sphere_M_location > -1);
+    let sphere_V_location = uniform_location(sphere_sp, "V");
+    assert!(sphere_V_location > -1);
+    let sphere_P_location = uniform_location(sphere_sp, "P");
+    assert!(sphere_P_location > -1);
+    let sphere_colour_location = uniform_location(sphere_sp, "colour");
+    assert!(sphere_colour_location > -1);
+    let sphere_renderer = SphereRenderer {
+        sp: sphere_sp,
+        model_location: sphere_M_location,
+        view_location: sphere_V_location,
+        proj_location: sphere_P_location,
+        colour_location: sphere_colour_location,
+        vao: sphere_vao,
+        point_count: sphere_mesh.point_count,
+    };
+
+    /*---------------------------DYNAMIC ENVIRONMENT MAP--------------------------*/
+    // the monkey reflects this cube map, re-rendered from its position every few
+    // frames, instead of the static one loaded from the images.
+    let env_map = match CubeMapFramebuffer::new(ENV_MAP_SIZE) {
+        Ok(val) => {
+            logger.log(&format!("environment cube map faces are {0}x{0}", val.size()));
+            Some(val)
+        }
+        Err(e) => {
+            logger.log_err(&e);
+            logger.log_err("WARNING: reflecting the static cube map only.");
+            None
+        }
+    };
+    /*-----------------------------SPECULAR IBL--------------------------------*/
+    // the environment prefiltered for each roughness, and the BRDF lookup table.
+    let prefilter_sp = create_programme_from_files(&logger, CUBE_VERT_FILE, PREFILTER_FRAG_FILE);
+    assert!(prefilter_sp > 0);
+    let ibl = match SpecularIbl::new(prefilter_sp, cube_vao) {
+        Ok(val) => Some(val),
+        Err(e) => {
+            logger.log_err(&e);
+            logger.log_err("WARNING: PBR shading is not available.");
+            None
+        }
+    };
+    let pbr_sp = create_programme_from_files(&logger, MONKEY_VERT_FILE, PBR_FRAG_FILE);
+    assert!(pbr_sp > 0);
+    let pbr_shader = PbrShader::new(pbr_sp);
+    let mut material = PbrMaterial::new(MONKEY_ALBEDO, 1.0, 0.3);
+    let mut pbr_shading = ibl.is_some();
+    // the environment the prefiltered map was last made from.
+    let mut prefiltered_source = 0;
+
+    let mut dynamic_reflections = env_map.is_some();
+    let mut capture_interval = DEFAULT_CAPTURE_INTERVAL;
+    // start due, so the first frame captures the environment
+    let mut frames_since_capture = capture_interval;
+    logger.log("keys: R toggles the dynamic environment map,");
+    logger.log("      [ and ] change how many frames pass between captures,");
+    logger.log("      P toggles PBR shading, , and . lower and raise metallic,");
+    logger.log("      - and = lower and raise roughness");
 
 
     /*-------------------------------CREATE CAMERA--------------------------------*/
@@ -274,24 +519,22 @@ fn main() {
     let cam_heading_speed: GLfloat = 50.0;        // 30 degrees per second
     let mut cam_pos: Vec3 = math::vec3((0.0, 0.0, 5.0)); // don't start at zero, or we will be too close
     let mut cam_heading: GLfloat = 0.0;               // y-rotation in degrees
-    let mut mat_trans = Mat4::identity().translate(&math::vec3((-cam_pos.v[0], -cam_pos.v[1], -cam_pos.v[2])));
-    let mut mat_rot = Mat4::identity().rotate_y_deg(-cam_heading);
+    let mut mat_trans = Mat4::from_translation(&-cam_pos);
+    let mut mat_rot = Mat4::from_rotation_y_deg(-cam_heading);
     let mut q = Versor::from_axis_deg(-cam_heading, 0.0, 1.0, 0.0);
     let mut view_mat = mat_rot * mat_trans;
 
-    let mut fwd = math::vec4((0.0, 0.0, -1.0, 0.0));
-    let mut rgt = math::vec4((1.0, 0.0, 0.0, 0.0));
-    let mut up = math::vec4((0.0, 1.0, 0.0, 0.0));
+    let mut fwd = math::vec3((0.0, 0.0, -1.0));
+    let mut rgt = math::vec3((1.0, 0.0, 0.0));
+    let mut up = math::vec3((0.0, 1.0, 0.0));
 
     /*---------------------------SET RENDERING DEFAULTS---------------------------*/
     unsafe {
         gl::UseProgram(monkey_sp);
         gl::UniformMatrix4fv(monkey_V_location, 1, gl::FALSE, view_mat.as_ptr());
         gl::UniformMatrix4fv(monkey_P_location, 1, gl::FALSE, proj_mat.as_ptr());
-        gl::UseProgram(cube_sp);
-        gl::UniformMatrix4fv(cube_V_location, 1, gl::FALSE, mat_rot.as_ptr());
-        gl::UniformMatrix4fv(cube_P_location, 1, gl::FALSE, proj_mat.as_ptr());
     }
+    pbr_shader.set_camera(&view_mat, &proj_mat);
 
     // unique model matrix for each sphere
     let mut model_mat = Mat4::identity();
@@ -314,28 +557,101 @@ fn main() {
         context.elapsed_time_seconds = current_seconds;
         update_fps_counter(&mut context);
 
+        // re-render the environment around the monkey
+        let mut captured = false;
+        let reflection_texture = match env_map {
+            Some(ref env_map) if dynamic_reflections => {
+                frames_since_capture += 1;
+                if frames_since_capture >= capture_interval {
+                    let monkey_pos = math::vec3((model_mat.m[12], model_mat.m[13], model_mat.m[14]));
+                    capture_environment(
+                        env_map, &monkey_pos, &skybox, cube_map_texture, &sphere_renderer, current_seconds
+                    );
+                    frames_since_capture = 0;
+                    captured = true;
+                }
+                env_map.texture()
+            }
+            _ => cube_map_texture,
+        };
+
+        // and blur it again for each roughness whenever it changes
+        if let Some(ref ibl) = ibl {
+            if pbr_shading && (captured || prefiltered_source != reflection_texture) {
+                ibl.prefilter(reflection_texture);
+                prefiltered_source = reflection_texture;
+            }
+        }
+
         unsafe {
+            gl::Viewport(0, 0, context.width as i32, context.height as i32);
             // Wipe the drawing surface clear.
             gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
-            
-            // render a sky-box using the cube-map texture
-            gl::DepthMask(gl::FALSE);
-            gl::UseProgram(cube_sp);
-            gl::ActiveTexture(gl::TEXTURE0);
-            gl::BindTexture(gl::TEXTURE_CUBE_MAP, cube_map_texture);
-            gl::BindVertexArray(cube_vao);
-            gl::DrawArrays(gl::TRIANGLES, 0, 36);
-            gl::DepthMask(gl::TRUE);
+        }
 
-            gl::UseProgram(monkey_sp);
-            gl::BindVertexArray(vao);
-            gl::UniformMatrix4fv(monkey_M_location, 1, gl::FALSE, model_mat.as_ptr());
-            gl::DrawArrays(gl::TRIANGLES, 0, g_point_count as i32);
-            // update other events like input handling
+        // render a sky-box using the cube-map texture
+        skybox.draw(cube_map_texture, &mat_rot.inverse(), &proj_mat);
+        sphere_renderer.draw(&SPHERES, current_seconds, &view_mat, &proj_mat);
+
+        match ibl {
+            Some(ref ibl) if pbr_shading => {
+                pbr_shader.draw(vao, g_point_count, &model_mat, &material, ibl);
+            }
+            _ => unsafe {
+                gl::UseProgram(monkey_sp);
+                gl::ActiveTexture(gl::TEXTURE0);
+                gl::BindTexture(gl::TEXTURE_CUBE_MAP, reflection_texture);
+                gl::BindVertexArray(vao);
+                gl::UniformMatrix4fv(monkey_M_location, 1, gl::FALSE, model_mat.as_ptr());
+                gl::DrawArrays(gl::TRIANGLES, 0, g_point_count as i32);
+            },
         }
 
         context.glfw.poll_events();
 
+        for (_, event) in glfw::flush_messages(&context.events) {
+            match event {
+                glfw::WindowEvent::Key(Key::R, _, Action::Press, _) => {
+                    if env_map.is_some() {
+                        dynamic_reflections = !dynamic_reflections;
+                        frames_since_capture = capture_interval;
+                        logger.log(&format!("dynamic environment map: {}", dynamic_reflections));
+                    }
+                }
+                glfw::WindowEvent::Key(Key::LeftBracket, _, Action::Press, _) => {
+                    capture_interval = u32::max(capture_interval - 1, 1);
+                    logger.log(&format!("capturing the environment every {} frames", capture_interval));
+                }
+                glfw::WindowEvent::Key(Key::RightBracket, _, Action::Press, _) => {
+                    capture_interval = u32::min(capture_interval + 1, MAX_CAPTURE_INTERVAL);
+                    logger.log(&format!("capturing the environment every {} frames", capture_interval));
+                }
+                glfw::WindowEvent::Key(Key::P, _, Action::Press, _) => {
+                    if ibl.is_some() {
+                        pbr_shading = !pbr_shading;
+                        logger.log(&format!("PBR shading: {}", pbr_shading));
+                    }
+                }
+                glfw::WindowEvent::Key(Key::Comma, _, Action::Press, _) => {
+                    material.change_metallic(-MATERIAL_STEP);
+                    logger.log(&material.describe());
+                }
+                glfw::WindowEvent::Key(Key::Period, _, Action::Press, _) => {
+                    material.change_metallic(MATERIAL_STEP);
+                    logger.log(&material.describe());
+                }
+                glfw::WindowEvent::Key(Key::Minus, _, Action::Press, _) => {
+                    material.change_roughness(-MATERIAL_STEP);
+                    logger.log(&material.describe());
+                }
+                glfw::WindowEvent::Key(Key::Equal, _, Action::Press, _) => {
+                    material.change_roughness(MATERIAL_STEP);
+                    logger.log(&material.describe());
+                }
+                _ => {}
+            }
+        }
+
         // control keys
         let mut cam_moved = false;
         let mut move_to = math::vec3((0.0, 0.0, 0.0));
@@ -389,7 +705,7 @@ fn main() {
                 cam_yaw += cam_heading_speed * (elapsed_seconds as GLfloat);
                 cam_moved = true;
                 let q_yaw = Versor::from_axis_deg(cam_yaw, up.v[0], up.v[1], up.v[2]);
-                q = q_yaw * &q;
+                q = q_yaw * q;
             }
             _ => {}
         }
@@ -398,7 +714,7 @@ fn main() {
                 cam_yaw -= cam_heading_speed * (elapsed_seconds as GLfloat);
                 cam_moved = true;
                 let q_yaw = Versor::from_axis_deg(cam_yaw, up.v[0], up.v[1], up.v[2]);
-                q = q_yaw * &q;
+                q = q_yaw * q;
             }
             _ => {}
         }
@@ -407,7 +723,7 @@ fn main() {
                 cam_pitch += cam_heading_speed * (elapsed_seconds as GLfloat);
                 cam_moved = true;
                 let q_pitch = Versor::from_axis_deg(cam_pitch, rgt.v[0], rgt.v[1], rgt.v[2]);
-                q = q_pitch * &q;
+                q = q_pitch * q;
             }
             _ => {}
         }
@@ -416,7 +732,7 @@ fn main() {
                 cam_pitch -= cam_heading_speed * (elapsed_seconds as GLfloat);
                 cam_moved = true;
                 let q_pitch = Versor::from_axis_deg(cam_pitch, rgt.v[0], rgt.v[1], rgt.v[2]);
-                q = q_pitch * &q;
+                q = q_pitch * q;
             }
             _ => {}
         }
@@ -425,7 +741,7 @@ fn main() {
                 cam_roll -= cam_heading_speed * (elapsed_seconds as GLfloat);
                 cam_moved = true;
                 let q_roll = Versor::from_axis_deg(cam_roll, fwd.v[0], fwd.v[1], fwd.v[2]);
-                q = q_roll * &q;
+                q = q_roll * q;
             }
             _ => {}
         }
@@ -434,7 +750,7 @@ fn main() {
                 cam_roll += cam_heading_speed * (elapsed_seconds as GLfloat);
                 cam_moved = true;
                 let q_roll = Versor::from_axis_deg(cam_roll, fwd.v[0], fwd.v[1], fwd.v[2]);
-                q = q_roll * &q;        
+                q = q_roll * q;        
             }
             _ => {}
         }
@@ -445,24 +761,21 @@ fn main() {
 
             // re-calculate local axes so can move fwd in dir cam is pointing
             mat_rot = q.to_mat4();
-            fwd = mat_rot * math::vec4((0.0, 0.0, -1.0, 0.0));
-            rgt = mat_rot * math::vec4((1.0, 0.0,  0.0, 0.0));
-            up  = mat_rot * math::vec4((0.0, 1.0,  0.0, 0.0));
+            fwd = q.rotate_vec3(&math::vec3((0.0, 0.0, -1.0)));
+            rgt = q.rotate_vec3(&math::vec3((1.0, 0.0,  0.0)));
+            up  = q.rotate_vec3(&math::vec3((0.0, 1.0,  0.0)));
 
-            cam_pos = cam_pos + math::vec3(&fwd) * (-move_to.v[2]);
-            cam_pos = cam_pos + math::vec3(&up) * (move_to.v[1]);
-            cam_pos = cam_pos + math::vec3(&rgt) * (move_to.v[0]);
-            mat_trans = Mat4::identity().translate(&math::vec3(cam_pos));
+            cam_pos = cam_pos + fwd * (-move_to.v[2]);
+            cam_pos = cam_pos + up * (move_to.v[1]);
+            cam_pos = cam_pos + rgt * (move_to.v[0]);
+            mat_trans = Mat4::from_translation(&math::vec3(cam_pos));
 
             view_mat = mat_rot.inverse() * mat_trans.inverse();
             unsafe {
                 gl::UseProgram( monkey_sp );
                 gl::UniformMatrix4fv( monkey_V_location, 1, gl::FALSE, view_mat.as_ptr());
-
-                // cube-map view matrix has rotation, but not translation
-                gl::UseProgram(cube_sp);
-                gl::UniformMatrix4fv(cube_V_location, 1, gl::FALSE, mat_rot.inverse().as_ptr());
             }
+            pbr_shader.set_camera(&view_mat, &proj_mat);
         }
 
         match context.window.get_key(Key::Escape) {
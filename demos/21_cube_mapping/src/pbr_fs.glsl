@@ -0,0 +1,46 @@
+#version 410
+
+in vec3 pos_eye;
+in vec3 n_eye;
+// the environment blurred for rougher surfaces at each mip level down, and the
+// scale and bias to the fresnel term for each n.v and roughness.
+uniform samplerCube prefiltered_map;
+uniform sampler2D brdf_lut;
+uniform float max_lod;
+uniform vec3 albedo;
+uniform float metallic;
+uniform float roughness;
+uniform mat4 V; // view matrix
+out vec4 frag_colour;
+
+// schlick's fresnel, held back on rough surfaces, whose microfacets mostly face
+// away from a grazing viewer.
+vec3 fresnel_schlick_roughness (float cos_theta, vec3 f0) {
+	return f0 + (max (vec3 (1.0 - roughness), f0) - f0) * pow (1.0 - cos_theta, 5.0);
+}
+
+void main () {
+	vec3 n = normalize (n_eye);
+	vec3 v = normalize (-pos_eye);
+	float n_dot_v = max (dot (n, v), 0.0);
+	// convert from eye to world space to look up the environment.
+	mat4 inv_V = inverse (V);
+	vec3 n_world = vec3 (inv_V * vec4 (n, 0.0));
+	vec3 r_world = vec3 (inv_V * vec4 (reflect (-v, n), 0.0));
+
+	// dielectrics all reflect about 4% head on, and metals tint it with their colour.
+	vec3 f0 = mix (vec3 (0.04), albedo, metallic);
+	vec3 f = fresnel_schlick_roughness (n_dot_v, f0);
+
+	vec3 prefiltered = textureLod (prefiltered_map, r_world, roughness * max_lod).rgb;
+	vec2 brdf = texture (brdf_lut, vec2 (n_dot_v, roughness)).rg;
+	vec3 specular = prefiltered * (f * brdf.x + brdf.y);
+
+	// the roughest level is blurred enough to stand in for the diffuse light
+	// arriving from around the normal. metals have no diffuse.
+	vec3 irradiance = textureLod (prefiltered_map, n_world, max_lod).rgb;
+	vec3 k_d = (1.0 - f) * (1.0 - metallic);
+	vec3 diffuse = k_d * albedo * irradiance;
+
+	frag_colour = vec4 (diffuse + specular, 1.0);
+}
@@ -0,0 +1,55 @@
+///
+/// The parameters of a metallic-roughness material, as the PBR shaders take them.
+///
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct PbrMaterial {
+    ///
+    /// The diffuse colour of a dielectric, or the reflected colour of a metal.
+    ///
+    pub albedo: [f32; 3],
+    ///
+    /// From 0 for a dielectric to 1 for a bare metal.
+    ///
+    pub metallic: f32,
+    ///
+    /// From 0 for a mirror finish to 1 for a fully matte one.
+    ///
+    pub roughness: f32,
+}
+
+impl PbrMaterial {
+    pub fn new(albedo: [f32; 3], metallic: f32, roughness: f32) -> PbrMaterial {
+        PbrMaterial {
+            albedo: albedo,
+            metallic: f32::min(f32::max(metallic, 0.0), 1.0),
+            roughness: f32::min(f32::max(roughness, 0.0), 1.0),
+        }
+    }
+
+    pub fn change_metallic(&mut self, step: f32) {
+        self.metallic = f32::min(f32::max(self.metallic + step, 0.0), 1.0);
+    }
+
+    pub fn change_roughness(&mut self, step: f32) {
+        self.roughness = f32::min(f32::max(self.roughness + step, 0.0), 1.0);
+    }
+
+    pub fn describe(&self) -> String {
+        format!("metallic {:.2}, roughness {:.2}", self.metallic, self.roughness)
+    }
+}
+
+mod pbr_material_tests {
+    use super::PbrMaterial;
+
+    #[test]
+    fn test_the_parameters_stay_between_zero_and_one() {
+        let mut material = PbrMaterial::new([1.0, 1.0, 1.0], 2.0, -1.0);
+        assert_eq!((material.metallic, material.roughness), (1.0, 0.0));
+
+        material.change_metallic(-0.25);
+        material.change_roughness(5.0);
+        assert_eq!((material.metallic, material.roughness), (0.75, 1.0));
+        assert_eq!(material.describe(), "metallic 0.75, roughness 1.00");
+    }
+}
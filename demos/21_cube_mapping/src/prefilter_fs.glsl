@@ -0,0 +1,52 @@
+#version 410
+
+in vec3 texcoords;
+uniform samplerCube cube_texture;
+// the roughness this mip level is blurred for.
+uniform float roughness;
+out vec4 frag_colour;
+
+const uint SAMPLE_COUNT = 128u;
+const float PI = 3.14159265359;
+
+// point i of a hammersley set, spread evenly over the unit square. keep in step
+// with hammersley in ibl.rs.
+vec2 hammersley (uint i, uint count) {
+	uint bits = bitfieldReverse (i);
+	return vec2 (float (i) / float (count), float (bits) * 2.3283064365386963e-10);
+}
+
+// a half vector around n, picked more often where the ggx distribution for the
+// roughness is denser. keep in step with importance_sample_ggx in ibl.rs.
+vec3 importance_sample_ggx (vec2 xi, vec3 n) {
+	float a = roughness * roughness;
+	float phi = 2.0 * PI * xi.x;
+	float cos_theta = sqrt ((1.0 - xi.y) / (1.0 + (a * a - 1.0) * xi.y));
+	float sin_theta = sqrt (1.0 - cos_theta * cos_theta);
+	vec3 h = vec3 (cos (phi) * sin_theta, sin (phi) * sin_theta, cos_theta);
+
+	vec3 up = abs (n.z) < 0.999 ? vec3 (0.0, 0.0, 1.0) : vec3 (1.0, 0.0, 0.0);
+	vec3 tangent = normalize (cross (up, n));
+	vec3 bitangent = cross (n, tangent);
+	return normalize (tangent * h.x + bitangent * h.y + n * h.z);
+}
+
+void main () {
+	// assume the viewer looks straight down the normal, so the reflection is the
+	// normal too. that loses the stretched highlights at grazing angles, but makes
+	// the blur depend on direction alone.
+	vec3 n = normalize (texcoords);
+	vec3 v = n;
+	vec3 colour = vec3 (0.0);
+	float total_weight = 0.0;
+	for (uint i = 0u; i < SAMPLE_COUNT; i++) {
+		vec3 h = importance_sample_ggx (hammersley (i, SAMPLE_COUNT), n);
+		vec3 l = normalize (2.0 * dot (v, h) * h - v);
+		float n_dot_l = dot (n, l);
+		if (n_dot_l > 0.0) {
+			colour += texture (cube_texture, l).rgb * n_dot_l;
+			total_weight += n_dot_l;
+		}
+	}
+	frag_colour = vec4 (colour / total_weight, 1.0);
+}
@@ -0,0 +1,14 @@
+#version 410
+
+in vec3 n_world;
+uniform vec3 colour;
+out vec4 frag_colour;
+
+/* a fixed light from above, so the spheres look the same from every face of the
+environment capture */
+const vec3 light_dir_world = normalize (vec3 (0.3, 1.0, 0.5));
+
+void main () {
+	float diffuse = max (dot (normalize (n_world), light_dir_world), 0.0);
+	frag_colour = vec4 (colour * (0.25 + 0.75 * diffuse), 1.0);
+}
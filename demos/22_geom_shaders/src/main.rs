@@ -3,25 +3,38 @@ extern crate glfw;
 extern crate chrono;
 extern crate stb_image;
 extern crate png;
+#[cfg(feature = "assimp")]
 extern crate assimp;
-
-#[macro_use] 
-extern crate scan_fmt;
-
+#[cfg(feature = "gltf")]
+extern crate gltf;
+#[cfg(feature = "glam")]
+extern crate glam;
+#[cfg(feature = "cgmath")]
+extern crate cgmath;
+
+#[macro_use]
 mod gl_utils;
 mod graphics_math;
 mod obj_parser;
+mod colour;
+mod assets;
+mod shader_include;
+mod spirv;
+mod program_cache;
 mod logger;
+mod model;
+mod mesh_cache;
+mod resource_cache;
+mod geometry;
+mod vertex_layout;
+mod vertex_packing;
 
 
 use glfw::{Action, Context, Key};
-use gl::types::{GLenum, GLfloat, GLsizeiptr, GLvoid, GLuint};
+use gl::types::{GLenum, GLfloat, GLvoid, GLuint};
 
-use std::mem;
-use std::ptr;
 use std::process;
 
-use stb_image::image;
 use stb_image::image::LoadResult;
 
 use gl_utils::*;
@@ -29,6 +42,9 @@ use gl_utils::*;
 use graphics_math as math;
 use math::{Vec3, Mat4, Versor};
 
+use model::Model;
+use vertex_layout::VertexLayout;
+
 
 const GL_LOG_FILE: &str = "gl.log";
 const MESH_FILE: &str = "src/suzanne.obj";
@@ -70,24 +86,12 @@ fn make_big_cube() -> GLuint {
          10.0, -10.0, -10.0, -10.0, -10.0,  10.0,  10.0, -10.0,  10.0
     ];
 
-    let mut vbo = 0;
-    unsafe {
-        gl::GenBuffers(1, &mut vbo);
-        gl::BindBuffer(gl::ARRAY_BUFFER, vbo);
-        gl::BufferData(
-            gl::ARRAY_BUFFER, (3 * 36 * mem::size_of::<GLfloat>()) as GLsizeiptr,
-            points.as_ptr() as *const GLvoid, gl::STATIC_DRAW
-        );
-    }
-
     let mut vao = 0;
     unsafe {
         gl::GenVertexArrays(1, &mut vao);
-        gl::BindVertexArray(vao);
-        gl::EnableVertexAttribArray(0);
-        gl::BindBuffer(gl::ARRAY_BUFFER, vbo);
-        gl::VertexAttribPointer(0, 3, gl::FLOAT, gl::FALSE, 0, ptr::null());
     }
+    assert!(vao > 0);
+    VertexLayout::new().floats(0, 3).upload(vao, &points, 36);
 
     vao
 }
@@ -100,7 +104,7 @@ fn load_cube_map_side(texture: GLuint, side_target: GLenum, file_name: &str) ->
     }
 
     let force_channels = 4;
-    let image_data = match image::load_with_depth(file_name, force_channels, false) {
+    let image_data = match assets::load_image_with_depth(file_name, force_channels) {
         LoadResult::ImageU8(image_data) => image_data,
         LoadResult::Error(_) => {
             eprintln!("ERROR: could not load {}", file_name);
@@ -185,11 +189,12 @@ fn main() {
     assert!(cube_map_texture > 0);
 
     /*------------------------------CREATE GEOMETRY------------------------------*/
-    let mesh = match obj_parser::load_obj_file(MESH_FILE) {
+    let mesh = match Model::load_cached(MESH_FILE) {
         Ok(val) => val,
         Err(e) => {
             logger.log_err(&format!("ERROR: loading mesh file. Loader returned error\n{}", e));
-            process::exit(1);
+            logger.log_err("WARNING: using the embedded fallback mesh instead.");
+            Model::from_obj_mesh(assets::fallback_mesh(), vec![])
         }
     };
 
@@ -200,64 +205,30 @@ fn main() {
     let mut vao = 0;
     unsafe {
         gl::GenVertexArrays(1, &mut vao);
-        gl::BindVertexArray(vao);
     }
     assert!(vao > 0);
 
-    let mut points_vbo = 0;
-    unsafe {
-        gl::GenBuffers(1, &mut points_vbo);
-        gl::BindBuffer(gl::ARRAY_BUFFER, points_vbo);
-        gl::BufferData(
-            gl::ARRAY_BUFFER, (3 * g_point_count * mem::size_of::<GLfloat>()) as GLsizeiptr, 
-            g_vp.as_ptr() as *const GLvoid, gl::STATIC_DRAW
-        );
-        gl::VertexAttribPointer(0, 3, gl::FLOAT, gl::FALSE, 0, ptr::null());
-        gl::EnableVertexAttribArray(0);
-    }
-    assert!(points_vbo > 0);
-
-    let mut normals_vbo = 0;
-    unsafe {
-        gl::GenBuffers( 1, &mut normals_vbo);
-        gl::BindBuffer(gl::ARRAY_BUFFER, normals_vbo);
-        gl::BufferData(
-            gl::ARRAY_BUFFER, (3 * g_point_count * mem::size_of::<GLfloat>()) as GLsizeiptr, 
-            g_vn.as_ptr() as *const GLvoid, gl::STATIC_DRAW
-        );
-        gl::VertexAttribPointer(1, 3, gl::FLOAT, gl::FALSE, 0, ptr::null());
-        gl::EnableVertexAttribArray(1);
-    }
-    assert!(normals_vbo > 0);
+    VertexLayout::new().floats(0, 3).upload(vao, &g_vp, g_point_count);
+    VertexLayout::new().floats(1, 3).upload(vao, &g_vn, g_point_count);
 
     /*-------------------------------CREATE SHADERS-------------------------------*/
     let monkey_sp = create_programme_from_files(&logger, MONKEY_VERT_FILE, MONKEY_FRAG_FILE);
     assert!(monkey_sp > 0);
 
-    let monkey_M_location = unsafe {
-        gl::GetUniformLocation(monkey_sp, "M".as_ptr() as *const i8)
-    };
+    let monkey_M_location = uniform_location(monkey_sp, "M");
     //assert!(monkey_M_location > -1);
-    let monkey_V_location = unsafe { 
-        gl::GetUniformLocation(monkey_sp, "V".as_ptr() as *const i8)
-    };
+    let monkey_V_location = uniform_location(monkey_sp, "V");
     assert!(monkey_V_location > -1);
-    let monkey_P_location = unsafe { 
-        gl::GetUniformLocation(monkey_sp, "P".as_ptr() as *const i8)
-    };
+    let monkey_P_location = uniform_location(monkey_sp, "P");
     assert!(monkey_P_location > -1);
 
     // cube-map shaders
     let cube_sp = create_programme_from_files(&logger, CUBE_VERT_FILE, CUBE_FRAG_FILE);
     assert!(cube_sp > 0);
     // note that this view matrix should NOT contain camera translation.
-    let cube_V_location = unsafe {
-        gl::GetUniformLocation(cube_sp, "V".as_ptr() as *const i8)
-    };
+    let cube_V_location = uniform_location(cube_sp, "V");
     assert!(cube_V_location > -1);
-    let cube_P_location = unsafe {
-        gl::GetUniformLocation(cube_sp, "P".as_ptr() as *const i8)
-    };
+    let cube_P_location = uniform_location(cube_sp, "P");
     assert!(cube_P_location > -1);
 
 
@@ -274,14 +245,14 @@ fn main() {
     let cam_heading_speed: GLfloat = 50.0;        // 30 degrees per second
     let mut cam_pos: Vec3 = math::vec3((0.0, 0.0, 5.0)); // don't start at zero, or we will be too close
     let mut cam_heading: GLfloat = 0.0;               // y-rotation in degrees
-    let mut mat_trans = Mat4::identity().translate(&math::vec3((-cam_pos.v[0], -cam_pos.v[1], -cam_pos.v[2])));
-    let mut mat_rot = Mat4::identity().rotate_y_deg(-cam_heading);
+    let mut mat_trans = Mat4::from_translation(&-cam_pos);
+    let mut mat_rot = Mat4::from_rotation_y_deg(-cam_heading);
     let mut q = Versor::from_axis_deg(-cam_heading, 0.0, 1.0, 0.0);
     let mut view_mat = mat_rot * mat_trans;
 
-    let mut fwd = math::vec4((0.0, 0.0, -1.0, 0.0));
-    let mut rgt = math::vec4((1.0, 0.0, 0.0, 0.0));
-    let mut up = math::vec4((0.0, 1.0, 0.0, 0.0));
+    let mut fwd = math::vec3((0.0, 0.0, -1.0));
+    let mut rgt = math::vec3((1.0, 0.0, 0.0));
+    let mut up = math::vec3((0.0, 1.0, 0.0));
 
     /*---------------------------SET RENDERING DEFAULTS---------------------------*/
     unsafe {
@@ -389,7 +360,7 @@ fn main() {
                 cam_yaw += cam_heading_speed * (elapsed_seconds as GLfloat);
                 cam_moved = true;
                 let q_yaw = Versor::from_axis_deg(cam_yaw, up.v[0], up.v[1], up.v[2]);
-                q = q_yaw * &q;
+                q = q_yaw * q;
             }
             _ => {}
         }
@@ -398,7 +369,7 @@ fn main() {
                 cam_yaw -= cam_heading_speed * (elapsed_seconds as GLfloat);
                 cam_moved = true;
                 let q_yaw = Versor::from_axis_deg(cam_yaw, up.v[0], up.v[1], up.v[2]);
-                q = q_yaw * &q;
+                q = q_yaw * q;
             }
             _ => {}
         }
@@ -407,7 +378,7 @@ fn main() {
                 cam_pitch += cam_heading_speed * (elapsed_seconds as GLfloat);
                 cam_moved = true;
                 let q_pitch = Versor::from_axis_deg(cam_pitch, rgt.v[0], rgt.v[1], rgt.v[2]);
-                q = q_pitch * &q;
+                q = q_pitch * q;
             }
             _ => {}
         }
@@ -416,7 +387,7 @@ fn main() {
                 cam_pitch -= cam_heading_speed * (elapsed_seconds as GLfloat);
                 cam_moved = true;
                 let q_pitch = Versor::from_axis_deg(cam_pitch, rgt.v[0], rgt.v[1], rgt.v[2]);
-                q = q_pitch * &q;
+                q = q_pitch * q;
             }
             _ => {}
         }
@@ -425,7 +396,7 @@ fn main() {
                 cam_roll -= cam_heading_speed * (elapsed_seconds as GLfloat);
                 cam_moved = true;
                 let q_roll = Versor::from_axis_deg(cam_roll, fwd.v[0], fwd.v[1], fwd.v[2]);
-                q = q_roll * &q;
+                q = q_roll * q;
             }
             _ => {}
         }
@@ -434,7 +405,7 @@ fn main() {
                 cam_roll += cam_heading_speed * (elapsed_seconds as GLfloat);
                 cam_moved = true;
                 let q_roll = Versor::from_axis_deg(cam_roll, fwd.v[0], fwd.v[1], fwd.v[2]);
-                q = q_roll * &q;        
+                q = q_roll * q;        
             }
             _ => {}
         }
@@ -445,14 +416,14 @@ fn main() {
 
             // re-calculate local axes so can move fwd in dir cam is pointing
             mat_rot = q.to_mat4();
-            fwd = mat_rot * math::vec4((0.0, 0.0, -1.0, 0.0));
-            rgt = mat_rot * math::vec4((1.0, 0.0,  0.0, 0.0));
-            up  = mat_rot * math::vec4((0.0, 1.0,  0.0, 0.0));
-
-            cam_pos = cam_pos + math::vec3(&fwd) * (-move_to.v[2]);
-            cam_pos = cam_pos + math::vec3(&up) * (move_to.v[1]);
-            cam_pos = cam_pos + math::vec3(&rgt) * (move_to.v[0]);
-            mat_trans = Mat4::identity().translate(&math::vec3(cam_pos));
+            fwd = q.rotate_vec3(&math::vec3((0.0, 0.0, -1.0)));
+            rgt = q.rotate_vec3(&math::vec3((1.0, 0.0,  0.0)));
+            up  = q.rotate_vec3(&math::vec3((0.0, 1.0,  0.0)));
+
+            cam_pos = cam_pos + fwd * (-move_to.v[2]);
+            cam_pos = cam_pos + up * (move_to.v[1]);
+            cam_pos = cam_pos + rgt * (move_to.v[0]);
+            mat_trans = Mat4::from_translation(&math::vec3(cam_pos));
 
             view_mat = mat_rot.inverse() * mat_trans.inverse();
             unsafe {
@@ -0,0 +1,684 @@
+use colour::Colour;
+use graphics_math::Mat4;
+use model::{Model, ModelNode};
+use obj_parser::{Material, MaterialGroup};
+
+use std::fs;
+use std::path::Path;
+use std::str;
+use std::time::UNIX_EPOCH;
+
+
+///
+/// The first bytes of every mesh cache file.
+///
+const MESH_CACHE_MAGIC: &[u8; 8] = b"MESHCACH";
+
+///
+/// The version of the cache layout. Bump it whenever the layout changes, so caches
+/// written by an older build are rebuilt rather than misread.
+///
+pub const MESH_CACHE_VERSION: u32 = 2;
+
+///
+/// The name of the cache file for a mesh file. It sits next to the mesh.
+///
+pub fn cache_file_name(file_name: &str) -> String {
+    format!("{}.meshcache", file_name)
+}
+
+///
+/// What a cache records about the mesh file it was built from: its size, its
+/// modification time in nanoseconds since the Unix epoch, and a hash of its
+/// contents.
+///
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct SourceStamp {
+    pub size: u64,
+    pub modified: u64,
+    pub hash: u64,
+}
+
+///
+/// What a cache records about a file the mesh file pulls in, such as a material
+/// library or a glTF buffer: its name, and its stamp, or `None` if the file was
+/// missing when the cache was written.
+///
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DependencyStamp {
+    pub file_name: String,
+    pub stamp: Option<SourceStamp>,
+}
+
+///
+/// The 64-bit FNV-1a hash of `bytes`.
+///
+pub fn fnv1a_hash(bytes: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for &byte in bytes {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+    }
+
+    hash
+}
+
+///
+/// The size of a file and its modification time in nanoseconds since the Unix
+/// epoch.
+///
+fn size_and_modified(file_name: &str) -> Result<(u64, u64), String> {
+    let metadata = match fs::metadata(file_name) {
+        Ok(metadata) => metadata,
+        Err(_) => return Err(format!("ERROR: file not found: {}", file_name)),
+    };
+    let modified = match metadata.modified().map(|time| time.duration_since(UNIX_EPOCH)) {
+        Ok(Ok(since_epoch)) => since_epoch.as_secs() * 1_000_000_000 + since_epoch.subsec_nanos() as u64,
+        _ => 0,
+    };
+
+    Ok((metadata.len(), modified))
+}
+
+///
+/// Stamp a mesh file, reading the whole file to hash it.
+///
+pub fn source_stamp(file_name: &str) -> Result<SourceStamp, String> {
+    let (size, modified) = match size_and_modified(file_name) {
+        Ok(val) => val,
+        Err(e) => return Err(e),
+    };
+    let contents = match fs::read(file_name) {
+        Ok(contents) => contents,
+        Err(e) => return Err(format!("ERROR: could not read {}: {}", file_name, e)),
+    };
+
+    Ok(SourceStamp { size: size, modified: modified, hash: fnv1a_hash(&contents) })
+}
+
+///
+/// Stamp each of the files a mesh file pulls in. A file that cannot be read is
+/// stamped as missing, so the cache goes stale once it can be.
+///
+pub fn dependency_stamps(file_names: &[String]) -> Vec<DependencyStamp> {
+    file_names.iter().map(|file_name| {
+        DependencyStamp { file_name: file_name.clone(), stamp: source_stamp(file_name).ok() }
+    }).collect()
+}
+
+///
+/// The stamp `file_name` has now if it still has the contents `stamp` records,
+/// or `None` if it has changed or gone. The file is only hashed when its size
+/// matches but its modification time does not, as it does after a fresh checkout.
+///
+fn current_stamp(file_name: &str, stamp: &SourceStamp) -> Option<SourceStamp> {
+    let (size, modified) = match size_and_modified(file_name) {
+        Ok(val) => val,
+        Err(_) => return None,
+    };
+    if size != stamp.size {
+        return None;
+    }
+    if modified == stamp.modified {
+        return Some(*stamp);
+    }
+
+    match source_stamp(file_name) {
+        Ok(current) if current.hash == stamp.hash => Some(current),
+        _ => None,
+    }
+}
+
+fn put_u8(bytes: &mut Vec<u8>, value: u8) {
+    bytes.push(value);
+}
+
+fn put_u32(bytes: &mut Vec<u8>, value: u32) {
+    bytes.extend_from_slice(&value.to_le_bytes());
+}
+
+fn put_u64(bytes: &mut Vec<u8>, value: u64) {
+    bytes.extend_from_slice(&value.to_le_bytes());
+}
+
+fn put_f32s(bytes: &mut Vec<u8>, values: &[f32]) {
+    for value in values.iter() {
+        bytes.extend_from_slice(&value.to_le_bytes());
+    }
+}
+
+fn put_array(bytes: &mut Vec<u8>, values: &[f32]) {
+    put_u64(bytes, values.len() as u64);
+    put_f32s(bytes, values);
+}
+
+fn put_string(bytes: &mut Vec<u8>, value: &str) {
+    put_u64(bytes, value.len() as u64);
+    bytes.extend_from_slice(value.as_bytes());
+}
+
+fn put_stamp(bytes: &mut Vec<u8>, stamp: &SourceStamp) {
+    put_u64(bytes, stamp.size);
+    put_u64(bytes, stamp.modified);
+    put_u64(bytes, stamp.hash);
+}
+
+fn put_optional_string(bytes: &mut Vec<u8>, value: &Option<String>) {
+    match *value {
+        Some(ref value) => {
+            put_u8(bytes, 1);
+            put_string(bytes, value);
+        }
+        None => put_u8(bytes, 0),
+    }
+}
+
+///
+/// Write a model out in the cache layout. Everything is little-endian: a header
+/// with the magic bytes, the layout version, the source stamp, and the stamps of
+/// the files the source pulls in, followed by the vertex attributes as raw arrays
+/// of floats, then the groups, materials, and nodes.
+///
+pub fn encode_model(model: &Model, stamp: &SourceStamp, dependencies: &[DependencyStamp]) -> Vec<u8> {
+    let floats = model.points.len() + model.tex_coords.len() + model.normals.len() + model.tangents.len();
+    let mut bytes = Vec::with_capacity(64 + 4 * floats);
+    bytes.extend_from_slice(MESH_CACHE_MAGIC);
+    put_u32(&mut bytes, MESH_CACHE_VERSION);
+    put_stamp(&mut bytes, stamp);
+    put_u64(&mut bytes, dependencies.len() as u64);
+    for dependency in dependencies.iter() {
+        put_string(&mut bytes, &dependency.file_name);
+        match dependency.stamp {
+            Some(ref stamp) => {
+                put_u8(&mut bytes, 1);
+                put_stamp(&mut bytes, stamp);
+            }
+            None => put_u8(&mut bytes, 0),
+        }
+    }
+
+    put_u64(&mut bytes, model.point_count as u64);
+    put_array(&mut bytes, &model.points);
+    put_array(&mut bytes, &model.tex_coords);
+    put_array(&mut bytes, &model.normals);
+    put_array(&mut bytes, &model.tangents);
+
+    put_u64(&mut bytes, model.groups.len() as u64);
+    for group in model.groups.iter() {
+        put_string(&mut bytes, &group.material);
+        put_u64(&mut bytes, group.first as u64);
+        put_u64(&mut bytes, group.count as u64);
+    }
+
+    put_u64(&mut bytes, model.materials.len() as u64);
+    for material in model.materials.iter() {
+        put_string(&mut bytes, &material.name);
+        put_f32s(&mut bytes, &material.ka.rgb());
+        put_f32s(&mut bytes, &material.kd.rgb());
+        put_f32s(&mut bytes, &material.ks.rgb());
+        put_f32s(&mut bytes, &[material.shininess]);
+        put_optional_string(&mut bytes, &material.diffuse_map);
+        put_optional_string(&mut bytes, &material.specular_map);
+        put_optional_string(&mut bytes, &material.normal_map);
+        put_optional_string(&mut bytes, &material.height_map);
+    }
+
+    put_u64(&mut bytes, model.nodes.len() as u64);
+    for node in model.nodes.iter() {
+        put_string(&mut bytes, &node.name);
+        match node.parent {
+            Some(parent) => {
+                put_u8(&mut bytes, 1);
+                put_u64(&mut bytes, parent as u64);
+            }
+            None => put_u8(&mut bytes, 0),
+        }
+        put_f32s(&mut bytes, &node.transform.m);
+        put_u64(&mut bytes, node.groups.len() as u64);
+        for &group in node.groups.iter() {
+            put_u64(&mut bytes, group as u64);
+        }
+    }
+
+    bytes
+}
+
+///
+/// Reads the values `encode_model` wrote, in the same order. Once a read fails,
+/// on a cache that ends early or is otherwise damaged, the reader keeps the first
+/// error and every later read returns zero or empty, so a whole model can be read
+/// before checking for errors once.
+///
+struct CacheReader<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+    error: Option<String>,
+}
+
+impl<'a> CacheReader<'a> {
+    fn new(bytes: &'a [u8]) -> CacheReader<'a> {
+        CacheReader { bytes: bytes, offset: 0, error: None }
+    }
+
+    fn fail(&mut self, error: String) {
+        if self.error.is_none() {
+            self.error = Some(error);
+        }
+    }
+
+    fn take(&mut self, len: usize) -> &'a [u8] {
+        if self.error.is_some() {
+            return &[];
+        }
+        if len > self.bytes.len() - self.offset {
+            self.fail(String::from("ERROR: the mesh cache ends early."));
+            return &[];
+        }
+        let taken = &self.bytes[self.offset..self.offset + len];
+        self.offset += len;
+
+        taken
+    }
+
+    fn u8(&mut self) -> u8 {
+        match self.take(1) {
+            &[value] => value,
+            _ => 0,
+        }
+    }
+
+    fn u32(&mut self) -> u32 {
+        let mut value = [0; 4];
+        let bytes = self.take(4);
+        if bytes.len() == 4 {
+            value.copy_from_slice(bytes);
+        }
+
+        u32::from_le_bytes(value)
+    }
+
+    fn u64(&mut self) -> u64 {
+        let mut value = [0; 8];
+        let bytes = self.take(8);
+        if bytes.len() == 8 {
+            value.copy_from_slice(bytes);
+        }
+
+        u64::from_le_bytes(value)
+    }
+
+    ///
+    /// Read a count or an index. A value larger than the whole cache can only come
+    /// from a damaged cache, and would otherwise ask for an absurd allocation.
+    ///
+    fn usize(&mut self) -> usize {
+        let value = self.u64();
+        if value > self.bytes.len() as u64 {
+            self.fail(format!("ERROR: the mesh cache has an impossible count {}.", value));
+            return 0;
+        }
+
+        value as usize
+    }
+
+    fn f32s(&mut self, values: &mut [f32]) {
+        let bytes = self.take(4 * values.len());
+        for (value, chunk) in values.iter_mut().zip(bytes.chunks(4)) {
+            *value = f32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+        }
+    }
+
+    fn stamp(&mut self) -> SourceStamp {
+        SourceStamp { size: self.u64(), modified: self.u64(), hash: self.u64() }
+    }
+
+    fn colour(&mut self) -> Colour {
+        let mut rgb = [0.0; 3];
+        self.f32s(&mut rgb);
+
+        Colour::from(rgb)
+    }
+
+    fn array(&mut self) -> Vec<f32> {
+        let mut values = vec![0.0; self.usize()];
+        self.f32s(&mut values);
+
+        values
+    }
+
+    fn string(&mut self) -> String {
+        let len = self.usize();
+        match str::from_utf8(self.take(len)) {
+            Ok(value) => String::from(value),
+            Err(_) => {
+                self.fail(String::from("ERROR: the mesh cache has a name that is not UTF-8."));
+                String::new()
+            }
+        }
+    }
+
+    fn optional_string(&mut self) -> Option<String> {
+        match self.u8() {
+            0 => None,
+            _ => Some(self.string()),
+        }
+    }
+}
+
+///
+/// Read back a model written by `encode_model`, together with the stamps of the
+/// mesh file it was built from and of the files that pulled in. A cache with a
+/// different layout version is rejected.
+///
+pub fn decode_model(bytes: &[u8]) -> Result<(SourceStamp, Vec<DependencyStamp>, Model), String> {
+    let mut reader = CacheReader::new(bytes);
+    if reader.take(MESH_CACHE_MAGIC.len()) != MESH_CACHE_MAGIC {
+        return Err(String::from("ERROR: not a mesh cache file."));
+    }
+    let version = reader.u32();
+    if version != MESH_CACHE_VERSION {
+        return Err(format!(
+            "ERROR: the mesh cache has version {}, but version {} is needed.", version, MESH_CACHE_VERSION
+        ));
+    }
+
+    let stamp = reader.stamp();
+    let mut dependencies = vec![];
+    for _ in 0..reader.usize() {
+        let file_name = reader.string();
+        let stamp = match reader.u8() {
+            0 => None,
+            _ => Some(reader.stamp()),
+        };
+        dependencies.push(DependencyStamp { file_name: file_name, stamp: stamp });
+    }
+    let point_count = reader.usize();
+    let points = reader.array();
+    let tex_coords = reader.array();
+    let normals = reader.array();
+    let tangents = reader.array();
+
+    let mut groups = vec![];
+    for _ in 0..reader.usize() {
+        groups.push(MaterialGroup { material: reader.string(), first: reader.usize(), count: reader.usize() });
+    }
+
+    let mut materials = vec![];
+    for _ in 0..reader.usize() {
+        let mut material = Material::new(&reader.string());
+        material.ka = reader.colour();
+        material.kd = reader.colour();
+        material.ks = reader.colour();
+        let mut shininess = [0.0];
+        reader.f32s(&mut shininess);
+        material.shininess = shininess[0];
+        material.diffuse_map = reader.optional_string();
+        material.specular_map = reader.optional_string();
+        material.normal_map = reader.optional_string();
+        material.height_map = reader.optional_string();
+        materials.push(material);
+    }
+
+    let mut nodes = vec![];
+    for _ in 0..reader.usize() {
+        let name = reader.string();
+        let parent = match reader.u8() {
+            0 => None,
+            _ => Some(reader.usize()),
+        };
+        let mut transform = Mat4::identity();
+        reader.f32s(&mut transform.m);
+        let groups = (0..reader.usize()).map(|_| reader.usize()).collect();
+        nodes.push(ModelNode { name: name, parent: parent, transform: transform, groups: groups });
+    }
+
+    if let Some(e) = reader.error {
+        return Err(e);
+    }
+
+    if point_count != points.len() / 3 {
+        return Err(String::from("ERROR: the mesh cache's point count does not match its points."));
+    }
+    let model = Model::new(points, tex_coords, normals, tangents, groups, materials, nodes);
+
+    Ok((stamp, dependencies, model))
+}
+
+///
+/// Write a model to the cache file for the mesh file `file_name`, stamped with
+/// the mesh file as it is now, and with the files in `dependencies` that it pulls
+/// in, such as its material libraries.
+///
+pub fn write_cache(model: &Model, file_name: &str, dependencies: &[String]) -> Result<(), String> {
+    let stamp = match source_stamp(file_name) {
+        Ok(stamp) => stamp,
+        Err(e) => return Err(e),
+    };
+    let cache_file = cache_file_name(file_name);
+    match fs::write(&cache_file, encode_model(model, &stamp, &dependency_stamps(dependencies))) {
+        Ok(()) => Ok(()),
+        Err(e) => Err(format!("ERROR: could not write mesh cache {}: {}", cache_file, e)),
+    }
+}
+
+///
+/// Read the model cached for the mesh file `file_name`, if there is a cache and
+/// it is up to date. It is up to date if the mesh file, and every file the cache
+/// lists it as pulling in, has the same size and modification time as when the
+/// cache was written. Where only the modification time differs, as it does after
+/// a fresh checkout, the file is hashed to decide, and a cache that is still good
+/// is stamped with the new times. A file that was missing must still be missing.
+///
+pub fn read_cache(file_name: &str) -> Option<Model> {
+    let cache_file = cache_file_name(file_name);
+    let bytes = match fs::read(&cache_file) {
+        Ok(bytes) => bytes,
+        Err(_) => return None,
+    };
+    let (stamp, dependencies, model) = match decode_model(&bytes) {
+        Ok(val) => val,
+        Err(_) => return None,
+    };
+    let current = match current_stamp(file_name, &stamp) {
+        Some(current) => current,
+        None => return None,
+    };
+    let mut current_dependencies = Vec::with_capacity(dependencies.len());
+    for dependency in dependencies.iter() {
+        let current = match dependency.stamp {
+            Some(ref stamp) => match current_stamp(&dependency.file_name, stamp) {
+                Some(current) => Some(current),
+                None => return None,
+            },
+            None if Path::new(&dependency.file_name).exists() => return None,
+            None => None,
+        };
+        current_dependencies.push(DependencyStamp { file_name: dependency.file_name.clone(), stamp: current });
+    }
+    if current != stamp || current_dependencies != dependencies {
+        // If this fails, the next load hashes the touched files again.
+        let _ = fs::write(&cache_file, encode_model(&model, &current, &current_dependencies));
+    }
+
+    Some(model)
+}
+
+mod mesh_cache_tests {
+    use super::{DependencyStamp, SourceStamp};
+    use colour::Colour;
+    use graphics_math::Mat4;
+    use model::{Model, ModelNode};
+    use obj_parser::{Material, MaterialGroup};
+    use std::env;
+    use std::fs;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    fn test_model() -> Model {
+        let mut bricks = Material::new("bricks");
+        bricks.kd = Colour::new(0.5, 0.25, 0.125);
+        bricks.diffuse_map = Some(String::from("textures/bricks.png"));
+        bricks.height_map = Some(String::from("textures/bricks_height.png"));
+
+        Model::new(
+            vec![0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, -1.5],
+            vec![0.0, 0.0, 1.0, 0.0, 0.0, 1.0],
+            vec![0.0, 0.0, 1.0, 0.0, 0.0, 1.0, 0.0, 0.0, 1.0],
+            vec![],
+            vec![MaterialGroup { material: String::from("bricks"), first: 0, count: 3 }],
+            vec![bricks, Material::default()],
+            vec![
+                ModelNode { name: String::from("root"), parent: None, transform: Mat4::identity(), groups: vec![] },
+                ModelNode {
+                    name: String::from("wall"),
+                    parent: Some(0),
+                    transform: Mat4::from_translation(&::graphics_math::vec3((1.0, 2.0, 3.0))),
+                    groups: vec![0],
+                },
+            ],
+        )
+    }
+
+    fn test_stamp() -> SourceStamp {
+        SourceStamp { size: 1234, modified: 1_500_000_000_123_456_789, hash: 0xdead_beef_0bad_f00d }
+    }
+
+    fn test_dependencies() -> Vec<DependencyStamp> {
+        vec![
+            DependencyStamp {
+                file_name: String::from("bricks.mtl"),
+                stamp: Some(SourceStamp { size: 56, modified: 1_600_000_000_000_000_000, hash: 0x0123_4567_89ab_cdef }),
+            },
+            DependencyStamp { file_name: String::from("missing.mtl"), stamp: None },
+        ]
+    }
+
+    ///
+    /// A mesh file of its own for each test, so tests running at the same time do
+    /// not share cache files.
+    ///
+    fn temp_mesh_file(name: &str, contents: &str) -> String {
+        let file_name = String::from(temp_file_name(&format!("{}.obj", name)).to_string_lossy());
+        fs::write(&file_name, contents).unwrap();
+        let _ = fs::remove_file(super::cache_file_name(&file_name));
+
+        file_name
+    }
+
+    fn temp_file_name(name: &str) -> ::std::path::PathBuf {
+        env::temp_dir().join(format!("mesh_cache_tests_{}_{}", ::std::process::id(), name))
+    }
+
+    fn remove_mesh_file(file_name: &str) {
+        let _ = fs::remove_file(file_name);
+        let _ = fs::remove_file(super::cache_file_name(file_name));
+    }
+
+    const TRIANGLE: &str = "v 0.0 0.0 0.0\nv 1.0 0.0 0.0\nv 0.0 1.0 0.0\nvn 0.0 0.0 1.0\nf 1//1 2//1 3//1\n";
+
+    #[test]
+    fn test_models_round_trip() {
+        let model = test_model();
+        let bytes = super::encode_model(&model, &test_stamp(), &test_dependencies());
+
+        assert_eq!(super::decode_model(&bytes), Ok((test_stamp(), test_dependencies(), model)));
+    }
+
+    #[test]
+    fn test_header_is_little_endian() {
+        let bytes = super::encode_model(&test_model(), &test_stamp(), &[]);
+
+        assert_eq!(&bytes[0..8], b"MESHCACH");
+        assert_eq!(&bytes[8..12], &[super::MESH_CACHE_VERSION as u8, 0, 0, 0]);
+        assert_eq!(&bytes[12..20], &[0xd2, 0x04, 0, 0, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn test_damaged_caches_are_rejected() {
+        let bytes = super::encode_model(&test_model(), &test_stamp(), &test_dependencies());
+        for len in 0..bytes.len() {
+            assert!(super::decode_model(&bytes[..len]).is_err(), "{} bytes", len);
+        }
+
+        let mut other_version = bytes.clone();
+        other_version[8] += 1;
+        assert!(super::decode_model(&other_version).is_err());
+
+        let mut huge_count = bytes.clone();
+        // The count of dependencies comes right after the source stamp.
+        huge_count[36..44].copy_from_slice(&[0xff; 8]);
+        assert!(super::decode_model(&huge_count).is_err());
+    }
+
+    #[test]
+    fn test_load_cached_writes_and_then_reads_the_cache() {
+        let file_name = temp_mesh_file("reads", TRIANGLE);
+        let model = Model::load_cached(&file_name).unwrap();
+        let cached = super::read_cache(&file_name);
+        remove_mesh_file(&file_name);
+
+        assert_eq!(model.point_count, 3);
+        assert_eq!(cached, Some(model));
+    }
+
+    #[test]
+    fn test_changing_the_mesh_file_invalidates_the_cache() {
+        let file_name = temp_mesh_file("changes", TRIANGLE);
+        Model::load_cached(&file_name).unwrap();
+        fs::write(&file_name, TRIANGLE.replace("v 0.0 1.0 0.0", "v 0.0 2.0 0.0")).unwrap();
+        let cached = super::read_cache(&file_name);
+        let reloaded = Model::load_cached(&file_name).unwrap();
+        remove_mesh_file(&file_name);
+
+        assert_eq!(cached, None);
+        assert_eq!(reloaded.points[7], 2.0);
+    }
+
+    #[test]
+    fn test_touching_the_mesh_file_keeps_the_cache() {
+        let file_name = temp_mesh_file("touches", TRIANGLE);
+        let model = Model::load_cached(&file_name).unwrap();
+        let new_time = UNIX_EPOCH + Duration::from_secs(1_000_000_000);
+        fs::OpenOptions::new().write(true).open(&file_name).unwrap().set_modified(new_time).unwrap();
+        let cached = super::read_cache(&file_name);
+        let bytes = fs::read(super::cache_file_name(&file_name)).unwrap();
+        remove_mesh_file(&file_name);
+
+        assert_eq!(cached, Some(model));
+        assert_eq!(super::decode_model(&bytes).unwrap().0.modified, 1_000_000_000 * 1_000_000_000);
+    }
+
+    #[test]
+    fn test_changing_only_the_material_library_invalidates_the_cache() {
+        let mtl_file_name = temp_file_name("materials.mtl");
+        let mtl_lib = String::from(mtl_file_name.file_name().unwrap().to_string_lossy());
+        fs::write(&mtl_file_name, "newmtl red\nKd 1.0 0.0 0.0\n").unwrap();
+        let file_name = temp_mesh_file("mtl", &format!("mtllib {}\nusemtl red\n{}", mtl_lib, TRIANGLE));
+        let model = Model::load_cached(&file_name).unwrap();
+        fs::write(&mtl_file_name, "newmtl red\nKd 0.5 0.0 0.0\n").unwrap();
+        let cached = super::read_cache(&file_name);
+        let reloaded = Model::load_cached(&file_name).unwrap();
+        remove_mesh_file(&file_name);
+        let _ = fs::remove_file(&mtl_file_name);
+
+        assert_eq!(model.materials[0].kd, Colour::new(1.0, 0.0, 0.0));
+        assert_eq!(cached, None);
+        assert_eq!(reloaded.materials[0].kd, Colour::new(0.5, 0.0, 0.0));
+    }
+
+    #[test]
+    fn test_a_material_library_turning_up_invalidates_the_cache() {
+        let mtl_file_name = temp_file_name("late.mtl");
+        let mtl_lib = String::from(mtl_file_name.file_name().unwrap().to_string_lossy());
+        let _ = fs::remove_file(&mtl_file_name);
+        let file_name = temp_mesh_file("late", &format!("mtllib {}\nusemtl red\n{}", mtl_lib, TRIANGLE));
+        Model::load_cached(&file_name).unwrap();
+        let cached_before = super::read_cache(&file_name);
+        fs::write(&mtl_file_name, "newmtl red\nKd 1.0 0.0 0.0\n").unwrap();
+        let cached_after = super::read_cache(&file_name);
+        remove_mesh_file(&file_name);
+        let _ = fs::remove_file(&mtl_file_name);
+
+        assert!(cached_before.is_some());
+        assert_eq!(cached_after, None);
+    }
+}
@@ -0,0 +1,178 @@
+///
+/// Convert a 32-bit float to the nearest 16-bit float, in the bit layout
+/// `GL_HALF_FLOAT` attributes read: 1 sign bit, 5 exponent bits, and 10 mantissa
+/// bits. Values too large for a half float become infinity, and values too small
+/// become zero or a subnormal.
+///
+pub fn f32_to_half(value: f32) -> u16 {
+    let bits = value.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exponent = ((bits >> 23) & 0xff) as i32;
+    let mantissa = bits & 0x007f_ffff;
+
+    if exponent == 0xff {
+        // Infinity stays infinity, and NaN stays NaN.
+        let nan_bit = if mantissa != 0 { 0x0200 } else { 0 };
+        return sign | 0x7c00 | nan_bit;
+    }
+
+    let half_exponent = exponent - 127 + 15;
+    if half_exponent >= 0x1f {
+        return sign | 0x7c00;
+    }
+
+    if half_exponent <= 0 {
+        if half_exponent < -10 {
+            return sign;
+        }
+        // A subnormal half float, with the implicit leading one made explicit.
+        let mantissa = mantissa | 0x0080_0000;
+        let shift = (14 - half_exponent) as u32;
+        let half_mantissa = mantissa >> shift;
+
+        return sign | round_to_nearest_even(half_mantissa, mantissa, shift) as u16;
+    }
+
+    let half = ((half_exponent as u32) << 10) | (mantissa >> 13);
+
+    // Rounding up can carry into the exponent, which is still the right answer.
+    sign | round_to_nearest_even(half, mantissa, 13) as u16
+}
+
+///
+/// Round `truncated`, which is `bits` shifted right by `shift`, to the nearest value
+/// using the bits shifted out, with ties going to the even value.
+///
+fn round_to_nearest_even(truncated: u32, bits: u32, shift: u32) -> u32 {
+    let halfway = 1 << (shift - 1);
+    let rest = bits & ((1 << shift) - 1);
+    if rest > halfway || (rest == halfway && truncated & 1 == 1) {
+        truncated + 1
+    } else {
+        truncated
+    }
+}
+
+///
+/// Convert a 16-bit float back to a 32-bit float. Every half float has an exact
+/// 32-bit float equivalent.
+///
+pub fn half_to_f32(half: u16) -> f32 {
+    let sign = ((half as u32) & 0x8000) << 16;
+    let exponent = ((half >> 10) & 0x1f) as u32;
+    let mantissa = (half & 0x03ff) as u32;
+
+    if exponent == 0 {
+        // Zero or a subnormal, which is the mantissa in units of 2^-24.
+        let value = mantissa as f32 / 16_777_216.0;
+        return if sign != 0 { -value } else { value };
+    }
+    if exponent == 0x1f {
+        return f32::from_bits(sign | 0x7f80_0000 | (mantissa << 13));
+    }
+
+    f32::from_bits(sign | ((exponent + 127 - 15) << 23) | (mantissa << 13))
+}
+
+///
+/// Convert a value in [-1, 1] to a signed normalised integer `bits` wide, masked
+/// to those bits. Values outside the range are clamped.
+///
+fn pack_snorm(value: f32, bits: u32) -> u32 {
+    let max = ((1 << (bits - 1)) - 1) as f32;
+    let clamped = f32::min(f32::max(value, -1.0), 1.0);
+    let scaled = (clamped * max).round() as i32;
+
+    (scaled as u32) & ((1 << bits) - 1)
+}
+
+///
+/// Read the signed normalised integer `bits` wide starting at bit `offset` of
+/// `packed`. The most negative integer reads as -1, the same as the one above it,
+/// following the OpenGL 4.2 conversion rule.
+///
+fn unpack_snorm(packed: u32, offset: u32, bits: u32) -> f32 {
+    let max = ((1 << (bits - 1)) - 1) as f32;
+    let value = ((packed << (32 - offset - bits)) as i32) >> (32 - bits);
+
+    f32::max(value as f32 / max, -1.0)
+}
+
+///
+/// Pack four values in [-1, 1] into the bit layout `GL_INT_2_10_10_10_REV`
+/// attributes read: x in the lowest 10 bits, then y and z, then w in the top 2 bits.
+/// The 2 bits of w can only hold -1, 0, and 1, which is enough for a tangent's
+/// handedness.
+///
+pub fn pack_snorm_2_10_10_10_rev(v: [f32; 4]) -> u32 {
+    pack_snorm(v[0], 10) | (pack_snorm(v[1], 10) << 10) | (pack_snorm(v[2], 10) << 20) | (pack_snorm(v[3], 2) << 30)
+}
+
+///
+/// Unpack four values from the `GL_INT_2_10_10_10_REV` bit layout, the way the GPU
+/// would.
+///
+pub fn unpack_snorm_2_10_10_10_rev(packed: u32) -> [f32; 4] {
+    [
+        unpack_snorm(packed, 0, 10),
+        unpack_snorm(packed, 10, 10),
+        unpack_snorm(packed, 20, 10),
+        unpack_snorm(packed, 30, 2),
+    ]
+}
+
+mod vertex_packing_tests {
+    use super::{f32_to_half, half_to_f32, pack_snorm_2_10_10_10_rev, unpack_snorm_2_10_10_10_rev};
+
+    #[test]
+    fn test_half_floats_hold_small_integers_and_fractions_exactly() {
+        for &value in [0.0, 1.0, -2.0, 0.5, 0.25, 1024.0, -0.125].iter() {
+            assert_eq!(half_to_f32(f32_to_half(value)), value);
+        }
+        assert_eq!(f32_to_half(1.0), 0x3c00);
+        assert_eq!(f32_to_half(-2.0), 0xc000);
+    }
+
+    #[test]
+    fn test_half_floats_round_to_the_nearest_value() {
+        // Between 1 and 2 half floats are 2^-10 apart.
+        let step = 1.0 / 1024.0;
+        assert_eq!(half_to_f32(f32_to_half(1.0 + 0.4 * step)), 1.0);
+        assert_eq!(half_to_f32(f32_to_half(1.0 + 0.6 * step)), 1.0 + step);
+        // A tie goes to the even mantissa.
+        assert_eq!(half_to_f32(f32_to_half(1.0 + 0.5 * step)), 1.0);
+        assert_eq!(half_to_f32(f32_to_half(1.0 + 1.5 * step)), 1.0 + 2.0 * step);
+    }
+
+    #[test]
+    fn test_half_floats_out_of_range() {
+        assert_eq!(f32_to_half(100_000.0), 0x7c00);
+        assert_eq!(half_to_f32(f32_to_half(-100_000.0)), -::std::f32::INFINITY);
+        assert!(half_to_f32(f32_to_half(::std::f32::NAN)).is_nan());
+        // The smallest subnormal half float, and a value too small for any.
+        let smallest = 1.0 / 16_777_216.0;
+        assert_eq!(f32_to_half(smallest), 0x0001);
+        assert_eq!(half_to_f32(0x0001), smallest);
+        assert_eq!(f32_to_half(smallest / 4.0), 0x0000);
+    }
+
+    #[test]
+    fn test_packed_snorm_bit_layout() {
+        assert_eq!(pack_snorm_2_10_10_10_rev([1.0, 0.0, 0.0, 0.0]), 0x0000_01ff);
+        assert_eq!(pack_snorm_2_10_10_10_rev([0.0, 1.0, 0.0, 0.0]), 0x0007_fc00);
+        assert_eq!(pack_snorm_2_10_10_10_rev([0.0, 0.0, -1.0, 0.0]), 0x2010_0000);
+        assert_eq!(pack_snorm_2_10_10_10_rev([0.0, 0.0, 0.0, -1.0]), 0xc000_0000);
+    }
+
+    #[test]
+    fn test_packed_snorm_round_trip() {
+        let v = [0.6, -0.48, 0.64, 1.0];
+        let unpacked = unpack_snorm_2_10_10_10_rev(pack_snorm_2_10_10_10_rev(v));
+
+        for i in 0..3 {
+            assert!((unpacked[i] - v[i]).abs() <= 0.5 / 511.0);
+        }
+        assert_eq!(unpacked[3], 1.0);
+        assert_eq!(unpack_snorm_2_10_10_10_rev(pack_snorm_2_10_10_10_rev([-2.0, 2.0, 0.0, -1.0])), [-1.0, 1.0, 0.0, -1.0]);
+    }
+}
@@ -0,0 +1,276 @@
+use graphics_math as math;
+use math::Vec3;
+
+use collision;
+use collision::{Capsule, Plane};
+
+
+///
+/// Height of the eyes above the feet, in world units.
+///
+const EYE_HEIGHT: f32 = 1.6;
+
+///
+/// Radius of the capsule standing in for the character's body.
+///
+const BODY_RADIUS: f32 = 0.3;
+
+///
+/// Downward acceleration while walking, in units per second squared.
+///
+const GRAVITY: f32 = 9.81;
+
+///
+/// Upward speed at the start of a jump. With `GRAVITY` this clears about a
+/// metre.
+///
+const JUMP_SPEED: f32 = 4.5;
+
+///
+/// Directions shorter than this have no usable heading left once they are
+/// flattened onto the ground.
+///
+const EPSILON: f32 = 1e-6;
+
+
+///
+/// How the character controller moves the camera.
+///
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum MoveMode {
+    /// Move freely along the camera axes, through the ground if need be.
+    Fly,
+    /// Move across the ground under gravity, and jump.
+    Walk,
+}
+
+impl MoveMode {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            MoveMode::Fly => "fly",
+            MoveMode::Walk => "walk",
+        }
+    }
+}
+
+///
+/// Moves a first person camera either as a free flying camera or as a person
+/// walking on the ground. `position` is the position of the eyes, and the body is
+/// an upright capsule reaching from there down to the feet.
+///
+#[derive(Copy, Clone, Debug)]
+pub struct CharacterController {
+    pub mode: MoveMode,
+    pub position: Vec3,
+    pub vertical_speed: f32,
+    pub on_ground: bool,
+}
+
+impl CharacterController {
+    pub fn new(position: Vec3) -> CharacterController {
+        CharacterController {
+            mode: MoveMode::Fly,
+            position: position,
+            vertical_speed: 0.0,
+            on_ground: false,
+        }
+    }
+
+    ///
+    /// Switch between flying and walking. A character that starts walking in mid
+    /// air falls from where it is.
+    ///
+    pub fn toggle_mode(&mut self) -> MoveMode {
+        self.mode = match self.mode {
+            MoveMode::Fly => MoveMode::Walk,
+            MoveMode::Walk => MoveMode::Fly,
+        };
+        self.vertical_speed = 0.0;
+        self.on_ground = false;
+
+        self.mode
+    }
+
+    ///
+    /// Move the character somewhere else entirely, for instance to a saved
+    /// camera position. It keeps none of its vertical speed.
+    ///
+    pub fn teleport(&mut self, position: Vec3) {
+        self.position = position;
+        self.vertical_speed = 0.0;
+        self.on_ground = false;
+    }
+
+    ///
+    /// Start a jump. This only works while walking with both feet on the ground.
+    ///
+    pub fn jump(&mut self) -> bool {
+        if self.mode != MoveMode::Walk || !self.on_ground {
+            return false;
+        }
+        self.vertical_speed = JUMP_SPEED;
+        self.on_ground = false;
+
+        true
+    }
+
+    ///
+    /// The capsule the character's body occupies.
+    ///
+    pub fn body(&self) -> Capsule {
+        let feet_to_eyes = math::vec3((0.0, EYE_HEIGHT - BODY_RADIUS, 0.0));
+        Capsule::new(self.position - feet_to_eyes, self.position, BODY_RADIUS)
+    }
+
+    ///
+    /// Advance the character by one frame. `fwd`, `rgt`, and `up` are the camera
+    /// axes in world space, and `move_to` is how far the keys ask to move along
+    /// them this frame, in camera space with negative z going forwards.
+    ///
+    /// Flying moves along all three camera axes. Walking only moves across the
+    /// ground, so looking up or down does not change the walking speed, and
+    /// gravity, jumps, and the ground take care of the height. Returns true if the
+    /// character moved.
+    ///
+    pub fn step(
+        &mut self, fwd: &Vec3, rgt: &Vec3, up: &Vec3, move_to: &Vec3,
+        elapsed_seconds: f32, ground: &Plane) -> bool {
+
+        let start = self.position;
+        match self.mode {
+            MoveMode::Fly => {
+                self.position += *fwd * -move_to.v[2];
+                self.position += *up * move_to.v[1];
+                self.position += *rgt * move_to.v[0];
+            }
+            MoveMode::Walk => {
+                self.position += flatten(fwd) * -move_to.v[2];
+                self.position += flatten(rgt) * move_to.v[0];
+
+                self.vertical_speed -= GRAVITY * elapsed_seconds;
+                self.position.v[1] += self.vertical_speed * elapsed_seconds;
+
+                self.on_ground = false;
+                if let Some(contact) = collision::capsule_plane(&self.body(), ground) {
+                    self.position += contact.normal * contact.depth;
+                    if self.vertical_speed < 0.0 {
+                        self.vertical_speed = 0.0;
+                    }
+                    self.on_ground = true;
+                }
+            }
+        }
+
+        self.position != start
+    }
+}
+
+///
+/// The direction of `v` across the ground, at unit length. This is zero if `v`
+/// points straight up or down.
+///
+fn flatten(v: &Vec3) -> Vec3 {
+    let flat = math::vec3((v.v[0], 0.0, v.v[2]));
+    let length = flat.norm();
+    if length < EPSILON {
+        return Vec3::zero();
+    }
+
+    flat / length
+}
+
+mod character_tests {
+    use super::{CharacterController, MoveMode, EYE_HEIGHT};
+    use collision::Plane;
+    use graphics_math as math;
+    use math::Vec3;
+
+    const EPSILON: f32 = 1e-4;
+    const FRAME_SECONDS: f32 = 1.0 / 60.0;
+
+    fn ground() -> Plane {
+        Plane::from_point_normal(&math::vec3((0.0, -1.0, 0.0)), &math::vec3((0.0, 1.0, 0.0)))
+    }
+
+    fn walker(height: f32) -> CharacterController {
+        let mut character = CharacterController::new(math::vec3((0.0, height, 0.0)));
+        character.toggle_mode();
+        character
+    }
+
+    fn step(character: &mut CharacterController, fwd: Vec3, move_to: Vec3) -> bool {
+        let rgt = math::vec3((1.0, 0.0, 0.0));
+        let up = math::vec3((0.0, 1.0, 0.0));
+        character.step(&fwd, &rgt, &up, &move_to, FRAME_SECONDS, &ground())
+    }
+
+    fn level() -> Vec3 {
+        math::vec3((0.0, 0.0, -1.0))
+    }
+
+    #[test]
+    fn test_walking_character_falls_and_lands_on_the_ground() {
+        let mut character = walker(5.0);
+        for _ in 0..300 {
+            step(&mut character, level(), Vec3::zero());
+        }
+
+        assert!(character.on_ground);
+        assert_eq!(character.vertical_speed, 0.0);
+        assert!((character.position.v[1] - (EYE_HEIGHT - 1.0)).abs() < EPSILON);
+    }
+
+    #[test]
+    fn test_flying_character_ignores_gravity() {
+        let mut character = CharacterController::new(math::vec3((0.0, 5.0, 0.0)));
+
+        assert_eq!(character.mode, MoveMode::Fly);
+        assert!(!step(&mut character, level(), Vec3::zero()));
+        assert_eq!(character.position, math::vec3((0.0, 5.0, 0.0)));
+    }
+
+    #[test]
+    fn test_flying_character_passes_through_the_ground() {
+        let mut character = CharacterController::new(math::vec3((0.0, 0.0, 0.0)));
+        step(&mut character, level(), math::vec3((0.0, -3.0, 0.0)));
+
+        assert_eq!(character.position, math::vec3((0.0, -3.0, 0.0)));
+    }
+
+    #[test]
+    fn test_walking_looking_down_moves_level_at_full_speed() {
+        let mut character = walker(EYE_HEIGHT - 1.0);
+        step(&mut character, level(), Vec3::zero());
+        let looking_down = math::vec3((0.0, -1.0, -1.0)).normalize();
+        step(&mut character, looking_down, math::vec3((0.0, 0.0, -0.5)));
+
+        assert!((character.position.v[2] + 0.5).abs() < EPSILON);
+        assert!((character.position.v[1] - (EYE_HEIGHT - 1.0)).abs() < EPSILON);
+    }
+
+    #[test]
+    fn test_jumping_needs_the_ground() {
+        let mut character = walker(5.0);
+        assert!(!character.jump());
+
+        for _ in 0..300 {
+            step(&mut character, level(), Vec3::zero());
+        }
+        assert!(character.jump());
+        step(&mut character, level(), Vec3::zero());
+
+        assert!(!character.on_ground);
+        assert!(character.position.v[1] > EYE_HEIGHT - 1.0);
+        assert!(!character.jump());
+    }
+
+    #[test]
+    fn test_switching_modes_clears_vertical_speed() {
+        let mut character = walker(5.0);
+        step(&mut character, level(), Vec3::zero());
+        assert!(character.vertical_speed < 0.0);
+
+        assert_eq!(character.toggle_mode(), MoveMode::Fly);
+        assert_eq!(character.vertical_speed, 0.0);
+    }
+}
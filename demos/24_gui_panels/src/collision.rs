@@ -0,0 +1,443 @@
+use graphics_math as math;
+use math::Vec3;
+
+
+///
+/// Distances shorter than this are treated as zero, so a direction is never
+/// taken from a vector too short to have one.
+///
+const EPSILON: f32 = 1e-6;
+
+
+///
+/// A sphere, given by its centre and radius.
+///
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Sphere {
+    pub centre: Vec3,
+    pub radius: f32,
+}
+
+impl Sphere {
+    pub fn new(centre: Vec3, radius: f32) -> Sphere {
+        Sphere {
+            centre: centre,
+            radius: radius,
+        }
+    }
+
+    ///
+    /// The smallest axis-aligned box containing the sphere.
+    ///
+    pub fn bounds(&self) -> Aabb {
+        let r = math::vec3((self.radius, self.radius, self.radius));
+        Aabb::new(self.centre - r, self.centre + r)
+    }
+}
+
+///
+/// An axis-aligned bounding box, given by its minimum and maximum corners.
+///
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Aabb {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+impl Aabb {
+    pub fn new(min: Vec3, max: Vec3) -> Aabb {
+        Aabb {
+            min: min,
+            max: max,
+        }
+    }
+
+    ///
+    /// The box with its centre at `centre`, reaching `half_extents` out from it
+    /// along each axis.
+    ///
+    pub fn from_centre(centre: Vec3, half_extents: Vec3) -> Aabb {
+        Aabb::new(centre - half_extents, centre + half_extents)
+    }
+
+    pub fn centre(&self) -> Vec3 {
+        (self.min + self.max) * 0.5
+    }
+
+    pub fn half_extents(&self) -> Vec3 {
+        (self.max - self.min) * 0.5
+    }
+
+    ///
+    /// The smallest box containing both boxes.
+    ///
+    pub fn union(&self, other: &Aabb) -> Aabb {
+        let mut union = *self;
+        for i in 0..3 {
+            union.min.v[i] = f32::min(self.min.v[i], other.min.v[i]);
+            union.max.v[i] = f32::max(self.max.v[i], other.max.v[i]);
+        }
+
+        union
+    }
+
+    ///
+    /// The point in or on the box closest to `p`. This is `p` itself if it is
+    /// inside the box.
+    ///
+    pub fn closest_point(&self, p: &Vec3) -> Vec3 {
+        let mut closest = *p;
+        for i in 0..3 {
+            closest.v[i] = f32::min(f32::max(p.v[i], self.min.v[i]), self.max.v[i]);
+        }
+
+        closest
+    }
+}
+
+///
+/// An infinite plane, made of the points `p` with `normal.dot(p) == distance`.
+/// Everything on the side the normal points away from counts as solid, so a
+/// shape that has sunk right through the plane is still pushed back out.
+///
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Plane {
+    pub normal: Vec3,
+    pub distance: f32,
+}
+
+impl Plane {
+    ///
+    /// The plane through `point` facing along `normal`. The normal does not
+    /// need to be unit length.
+    ///
+    pub fn from_point_normal(point: &Vec3, normal: &Vec3) -> Plane {
+        let normal = normal.normalize();
+        Plane {
+            normal: normal,
+            distance: normal.dot(point),
+        }
+    }
+
+    ///
+    /// How far `p` is in front of the plane. This is negative behind it.
+    ///
+    pub fn signed_distance(&self, p: &Vec3) -> f32 {
+        self.normal.dot(p) - self.distance
+    }
+}
+
+///
+/// A capsule: every point within `radius` of the line segment from `a` to `b`.
+/// Standing upright, it is the usual stand-in for a person.
+///
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Capsule {
+    pub a: Vec3,
+    pub b: Vec3,
+    pub radius: f32,
+}
+
+impl Capsule {
+    pub fn new(a: Vec3, b: Vec3, radius: f32) -> Capsule {
+        Capsule {
+            a: a,
+            b: b,
+            radius: radius,
+        }
+    }
+}
+
+///
+/// How two overlapping shapes touch. `normal` is the unit direction to move the
+/// first shape to separate it from the second, and `depth` is how far it has to
+/// move along it.
+///
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Contact {
+    pub normal: Vec3,
+    pub depth: f32,
+}
+
+///
+/// The contact between two spheres, or `None` if they do not overlap. Spheres
+/// that only touch do not overlap. If the centres coincide there is no best way
+/// to separate them, so they are separated upwards.
+///
+pub fn sphere_sphere(a: &Sphere, b: &Sphere) -> Option<Contact> {
+    let d = a.centre - b.centre;
+    let dist2 = d.norm2();
+    let radii = a.radius + b.radius;
+    if dist2 >= radii * radii {
+        return None;
+    }
+
+    let dist = f32::sqrt(dist2);
+    let normal = if dist > EPSILON {
+        d / dist
+    } else {
+        math::vec3((0.0, 1.0, 0.0))
+    };
+
+    Some(Contact { normal: normal, depth: radii - dist })
+}
+
+///
+/// The contact between a sphere and a box, or `None` if they do not overlap. A
+/// sphere whose centre is inside the box is pushed out through the nearest face.
+///
+pub fn sphere_aabb(s: &Sphere, b: &Aabb) -> Option<Contact> {
+    let closest = b.closest_point(&s.centre);
+    let d = s.centre - closest;
+    let dist2 = d.norm2();
+    if dist2 >= s.radius * s.radius {
+        return None;
+    }
+
+    let dist = f32::sqrt(dist2);
+    if dist > EPSILON {
+        return Some(Contact { normal: d / dist, depth: s.radius - dist });
+    }
+
+    // The centre is inside the box, so find the face it is nearest to.
+    let mut normal = Vec3::zero();
+    let mut face_dist = f32::INFINITY;
+    for i in 0..3 {
+        let to_min = s.centre.v[i] - b.min.v[i];
+        let to_max = b.max.v[i] - s.centre.v[i];
+        if to_min < face_dist {
+            face_dist = to_min;
+            normal = Vec3::zero();
+            normal.v[i] = -1.0;
+        }
+        if to_max < face_dist {
+            face_dist = to_max;
+            normal = Vec3::zero();
+            normal.v[i] = 1.0;
+        }
+    }
+
+    Some(Contact { normal: normal, depth: face_dist + s.radius })
+}
+
+///
+/// The contact between two boxes, or `None` if they do not overlap. The boxes
+/// are separated along the axis they overlap least on.
+///
+pub fn aabb_aabb(a: &Aabb, b: &Aabb) -> Option<Contact> {
+    let mut normal = Vec3::zero();
+    let mut depth = f32::INFINITY;
+    for i in 0..3 {
+        let overlap = f32::min(a.max.v[i], b.max.v[i]) - f32::max(a.min.v[i], b.min.v[i]);
+        if overlap <= 0.0 {
+            return None;
+        }
+        if overlap < depth {
+            depth = overlap;
+            normal = Vec3::zero();
+            normal.v[i] = if a.min.v[i] + a.max.v[i] < b.min.v[i] + b.max.v[i] { -1.0 } else { 1.0 };
+        }
+    }
+
+    Some(Contact { normal: normal, depth: depth })
+}
+
+///
+/// The contact between a capsule and a plane, or `None` if they do not overlap.
+/// The end of the capsule deepest behind the plane decides how far it has to
+/// move, and it always moves out along the plane normal.
+///
+pub fn capsule_plane(c: &Capsule, p: &Plane) -> Option<Contact> {
+    let deepest = f32::min(p.signed_distance(&c.a), p.signed_distance(&c.b));
+    if deepest >= c.radius {
+        return None;
+    }
+
+    Some(Contact { normal: p.normal, depth: c.radius - deepest })
+}
+
+///
+/// Sweep a sphere along `velocity` for one step, and find the fraction of the
+/// step, from 0 to 1, at which it first touches `other`. Returns `None` if the
+/// spheres do not meet during the step, or are moving apart. Spheres that
+/// already overlap meet at 0. Unlike testing only where the sphere ends up, this
+/// cannot miss a collision because the sphere moved right through the other one.
+///
+pub fn sweep_sphere_sphere(moving: &Sphere, velocity: &Vec3, other: &Sphere) -> Option<f32> {
+    // Solve |s + velocity * t| = radii for t, where s points from other to moving.
+    let s = moving.centre - other.centre;
+    let radii = moving.radius + other.radius;
+    let c = s.dot(&s) - radii * radii;
+    if c < 0.0 {
+        return Some(0.0);
+    }
+
+    let a = velocity.dot(velocity);
+    let half_b = velocity.dot(&s);
+    if a < EPSILON || half_b >= 0.0 {
+        return None;
+    }
+
+    let discriminant = half_b * half_b - a * c;
+    if discriminant < 0.0 {
+        return None;
+    }
+
+    let t = (-half_b - f32::sqrt(discriminant)) / a;
+    if t <= 1.0 {
+        Some(t)
+    } else {
+        None
+    }
+}
+
+mod collision_tests {
+    use super::{
+        aabb_aabb, capsule_plane, sphere_aabb, sphere_sphere, sweep_sphere_sphere,
+        Aabb, Capsule, Plane, Sphere
+    };
+    use graphics_math as math;
+
+    const EPSILON: f32 = 1e-5;
+
+    fn sphere(x: f32, y: f32, z: f32, radius: f32) -> Sphere {
+        Sphere::new(math::vec3((x, y, z)), radius)
+    }
+
+    fn unit_box() -> Aabb {
+        Aabb::new(math::vec3((-1.0, -1.0, -1.0)), math::vec3((1.0, 1.0, 1.0)))
+    }
+
+    #[test]
+    fn test_overlapping_spheres_are_pushed_apart_along_the_centres() {
+        let contact = sphere_sphere(&sphere(1.5, 0.0, 0.0, 1.0), &sphere(0.0, 0.0, 0.0, 1.0)).unwrap();
+
+        assert_eq!(contact.normal, math::vec3((1.0, 0.0, 0.0)));
+        assert!((contact.depth - 0.5).abs() < EPSILON);
+    }
+
+    #[test]
+    fn test_touching_spheres_do_not_overlap() {
+        assert!(sphere_sphere(&sphere(2.0, 0.0, 0.0, 1.0), &sphere(0.0, 0.0, 0.0, 1.0)).is_none());
+    }
+
+    #[test]
+    fn test_concentric_spheres_are_separated_upwards() {
+        let contact = sphere_sphere(&sphere(0.0, 0.0, 0.0, 1.0), &sphere(0.0, 0.0, 0.0, 0.5)).unwrap();
+
+        assert_eq!(contact.normal, math::vec3((0.0, 1.0, 0.0)));
+        assert!((contact.depth - 1.5).abs() < EPSILON);
+    }
+
+    #[test]
+    fn test_sphere_against_a_box_face() {
+        let contact = sphere_aabb(&sphere(0.0, 0.0, 1.25, 0.5), &unit_box()).unwrap();
+
+        assert_eq!(contact.normal, math::vec3((0.0, 0.0, 1.0)));
+        assert!((contact.depth - 0.25).abs() < EPSILON);
+    }
+
+    #[test]
+    fn test_sphere_near_a_box_corner_misses_it() {
+        // Inside the bounds of the box grown by the radius, but not near enough
+        // to the corner itself.
+        assert!(sphere_aabb(&sphere(1.4, 1.4, 1.4, 0.5), &unit_box()).is_none());
+    }
+
+    #[test]
+    fn test_sphere_centred_inside_a_box_leaves_through_the_nearest_face() {
+        let contact = sphere_aabb(&sphere(0.0, -0.75, 0.0, 0.5), &unit_box()).unwrap();
+
+        assert_eq!(contact.normal, math::vec3((0.0, -1.0, 0.0)));
+        assert!((contact.depth - 0.75).abs() < EPSILON);
+    }
+
+    #[test]
+    fn test_boxes_are_separated_along_the_least_overlapping_axis() {
+        let a = Aabb::from_centre(math::vec3((1.5, 0.5, 0.0)), math::vec3((1.0, 1.0, 1.0)));
+        let contact = aabb_aabb(&a, &unit_box()).unwrap();
+
+        assert_eq!(contact.normal, math::vec3((1.0, 0.0, 0.0)));
+        assert!((contact.depth - 0.5).abs() < EPSILON);
+    }
+
+    #[test]
+    fn test_boxes_apart_on_one_axis_do_not_overlap() {
+        let a = Aabb::from_centre(math::vec3((0.0, 0.0, 3.0)), math::vec3((1.0, 1.0, 1.0)));
+
+        assert!(aabb_aabb(&a, &unit_box()).is_none());
+    }
+
+    #[test]
+    fn test_sweep_finds_the_first_moment_of_contact() {
+        let moving = sphere(-5.0, 0.0, 0.0, 1.0);
+        let velocity = math::vec3((10.0, 0.0, 0.0));
+        let t = sweep_sphere_sphere(&moving, &velocity, &sphere(0.0, 0.0, 0.0, 1.0)).unwrap();
+
+        assert!((t - 0.3).abs() < EPSILON);
+    }
+
+    #[test]
+    fn test_sweep_catches_a_sphere_passed_right_through() {
+        // Without the sweep, both the start and the end would miss the small sphere.
+        let moving = sphere(-5.0, 0.0, 0.0, 0.5);
+        let velocity = math::vec3((10.0, 0.0, 0.0));
+
+        assert!(sweep_sphere_sphere(&moving, &velocity, &sphere(0.0, 0.0, 0.0, 0.25)).is_some());
+        assert!(sphere_sphere(&sphere(5.0, 0.0, 0.0, 0.5), &sphere(0.0, 0.0, 0.0, 0.25)).is_none());
+    }
+
+    #[test]
+    fn test_sweep_misses_when_moving_apart_or_falling_short() {
+        let other = sphere(0.0, 0.0, 0.0, 1.0);
+        let moving = sphere(-5.0, 0.0, 0.0, 1.0);
+
+        assert!(sweep_sphere_sphere(&moving, &math::vec3((-10.0, 0.0, 0.0)), &other).is_none());
+        assert!(sweep_sphere_sphere(&moving, &math::vec3((2.0, 0.0, 0.0)), &other).is_none());
+        assert!(sweep_sphere_sphere(&moving, &math::vec3((10.0, 6.0, 0.0)), &other).is_none());
+    }
+
+    #[test]
+    fn test_sweep_of_overlapping_spheres_starts_at_zero() {
+        let moving = sphere(0.5, 0.0, 0.0, 1.0);
+
+        assert_eq!(sweep_sphere_sphere(&moving, &math::vec3((1.0, 0.0, 0.0)), &sphere(0.0, 0.0, 0.0, 1.0)), Some(0.0));
+    }
+
+    fn upright_capsule(bottom: f32, top: f32) -> Capsule {
+        Capsule::new(math::vec3((0.0, bottom, 0.0)), math::vec3((0.0, top, 0.0)), 0.5)
+    }
+
+    fn ground() -> Plane {
+        Plane::from_point_normal(&math::vec3((0.0, -1.0, 0.0)), &math::vec3((0.0, 2.0, 0.0)))
+    }
+
+    #[test]
+    fn test_plane_signed_distance() {
+        let plane = ground();
+
+        assert!((plane.signed_distance(&math::vec3((3.0, 1.0, -2.0))) - 2.0).abs() < EPSILON);
+        assert!((plane.signed_distance(&math::vec3((0.0, -1.5, 0.0))) + 0.5).abs() < EPSILON);
+    }
+
+    #[test]
+    fn test_capsule_resting_on_a_plane_is_pushed_up() {
+        let contact = capsule_plane(&upright_capsule(-0.75, 1.0), &ground()).unwrap();
+
+        assert_eq!(contact.normal, math::vec3((0.0, 1.0, 0.0)));
+        assert!((contact.depth - 0.25).abs() < EPSILON);
+    }
+
+    #[test]
+    fn test_capsule_above_a_plane_does_not_overlap() {
+        assert!(capsule_plane(&upright_capsule(-0.5, 1.0), &ground()).is_none());
+    }
+
+    #[test]
+    fn test_capsule_sunk_through_a_plane_is_pushed_all_the_way_out() {
+        let contact = capsule_plane(&upright_capsule(-4.0, -3.0), &ground()).unwrap();
+
+        assert_eq!(contact.normal, math::vec3((0.0, 1.0, 0.0)));
+        assert!((contact.depth - 3.5).abs() < EPSILON);
+    }
+}
@@ -1,27 +1,102 @@
 use glfw;
 use glfw::Context;
 use gl;
-use gl::types::{GLubyte, GLuint, GLchar, GLint, GLenum};
+use gl::types::{GLubyte, GLuint, GLchar, GLint, GLenum, GLsizei};
 
 use logger::Logger;
+use assets;
+use shader_include;
+use program_cache;
+use spirv;
 
 use std::string::String;
-use std::ffi::CStr;
+use std::ffi::{CStr, CString};
 use std::ptr;
 use std::fs::File;
-use std::io::{Read, BufReader};
+use std::io::Read;
+use std::fmt;
 use std::fmt::Write as FWrite;
-use std::cell::Cell;
+use std::cell::{Cell, RefCell};
 use std::sync::mpsc::Receiver;
 
 
-const MAX_SHADER_LENGTH: usize = 262144;
-
 // Keep track of window size for things like the viewport and the mouse cursor
 const G_GL_WIDTH_DEFAULT: u32 = 640;
 const G_GL_HEIGHT_DEFAULT: u32 = 480;
 const G_GL_CHANNEL_DEPTH_DEFAULT: u32 = 3;
 
+// The log file `gl_check!` writes GL errors to, which is the one every demo logs to.
+const GL_CHECK_LOG_FILE: &str = "gl.log";
+// GL keeps one flag per kind of error, so there are only ever a few to read. A
+// lost context can report errors for ever, though, so stop after this many.
+const MAX_GL_ERRORS_PER_CHECK: usize = 8;
+
+
+///
+/// Make a GL call and, in a debug build, check `glGetError` straight after it,
+/// logging any errors with their names, the call, and the file and line it was
+/// made from. GL does nothing about a call that goes wrong but set a flag, so
+/// otherwise the first sign of a mistake is usually a black screen some time
+/// later. The call's value is passed through, so calls that return something
+/// can be wrapped too. A release build makes the call and nothing else.
+///
+/// The macro is only visible to the modules declared after `gl_utils`, which is
+/// declared first, with `#[macro_use]`, for that reason.
+///
+macro_rules! gl_check {
+    ($call:expr) => {{
+        let result = $call;
+        if cfg!(debug_assertions) {
+            $crate::gl_utils::check_gl_errors(stringify!($call), file!(), line!());
+        }
+        result
+    }};
+}
+
+///
+/// The name of a value returned by `glGetError`, as it is spelt in the GL
+/// specification.
+///
+pub fn gl_error_name(error: GLenum) -> &'static str {
+    match error {
+        gl::NO_ERROR => "GL_NO_ERROR",
+        gl::INVALID_ENUM => "GL_INVALID_ENUM",
+        gl::INVALID_VALUE => "GL_INVALID_VALUE",
+        gl::INVALID_OPERATION => "GL_INVALID_OPERATION",
+        gl::INVALID_FRAMEBUFFER_OPERATION => "GL_INVALID_FRAMEBUFFER_OPERATION",
+        gl::OUT_OF_MEMORY => "GL_OUT_OF_MEMORY",
+        gl::STACK_UNDERFLOW => "GL_STACK_UNDERFLOW",
+        gl::STACK_OVERFLOW => "GL_STACK_OVERFLOW",
+        _ => "an unknown GL error",
+    }
+}
+
+///
+/// Read every error GL has recorded since it was last asked, and log each one as
+/// coming from `call` at `file` and `line`. Returns how many there were. This is
+/// what `gl_check!` calls after the call it wraps. Without a GL context, as in the
+/// tests, there is nothing to check.
+///
+pub fn check_gl_errors(call: &str, file: &str, line: u32) -> usize {
+    if !gl::GetError::is_loaded() {
+        return 0;
+    }
+
+    // Most checks find nothing, so the log file is only opened for the first error.
+    let mut logger = None;
+    let mut error_count = 0;
+    while error_count < MAX_GL_ERRORS_PER_CHECK {
+        let error = unsafe { gl::GetError() };
+        if error == gl::NO_ERROR {
+            break;
+        }
+        let logger = logger.get_or_insert_with(|| Logger::from_log_file(GL_CHECK_LOG_FILE));
+        logger.log_err(&format!("ERROR: {} at {}:{} in {}", gl_error_name(error), file, line, call));
+        error_count += 1;
+    }
+
+    error_count
+}
 
 #[inline]
 pub fn glubyte_ptr_to_string(cstr: *const GLubyte) -> String {
@@ -49,17 +124,6 @@ pub fn gl_type_to_string(gl_type: GLenum) -> &'static str {
     }
 }
 
-///
-/// A callback that GLFW runs whenever the framebuffer size changes.
-///
-fn glfw_framebuffer_size_callback(context: &mut GLContext, width: u32, height: u32) {
-    context.width = width;
-    context.height = height;
-    println!("width {} height {}", width, height);
-    /* TODO: Update any perspective matrices used here */
-}
-
-
 /// 
 /// A callback for that tells GLFW what to do whenever it finds an error.
 ///
@@ -126,6 +190,64 @@ pub fn log_gl_params(logger: &Logger) {
     }
 }
 
+///
+/// How often the front and back buffers get swapped.
+///
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SwapInterval {
+    /// Swap as soon as a frame is finished. Vsync is off.
+    Immediate,
+    /// Wait for the vertical blank before swapping. Vsync is on.
+    VSync,
+    /// Wait for the vertical blank unless the frame is late, in which case swap immediately.
+    Adaptive,
+}
+
+impl SwapInterval {
+    ///
+    /// The next mode in the cycle off, on, adaptive.
+    ///
+    pub fn next(self) -> SwapInterval {
+        match self {
+            SwapInterval::Immediate => SwapInterval::VSync,
+            SwapInterval::VSync => SwapInterval::Adaptive,
+            SwapInterval::Adaptive => SwapInterval::Immediate,
+        }
+    }
+}
+
+impl fmt::Display for SwapInterval {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SwapInterval::Immediate => write!(f, "off"),
+            SwapInterval::VSync => write!(f, "on"),
+            SwapInterval::Adaptive => write!(f, "adaptive"),
+        }
+    }
+}
+
+///
+/// The settings used to create a new OpenGL context.
+///
+#[derive(Copy, Clone, Debug)]
+pub struct GlConfig {
+    pub swap_interval: SwapInterval,
+    ///
+    /// Ask for a window whose framebuffer can convert linear colours to sRGB as
+    /// they are written, when `GL_FRAMEBUFFER_SRGB` is enabled.
+    ///
+    pub srgb_framebuffer: bool,
+}
+
+impl Default for GlConfig {
+    fn default() -> GlConfig {
+        GlConfig {
+            swap_interval: SwapInterval::VSync,
+            srgb_framebuffer: false,
+        }
+    }
+}
+
 ///
 /// A record for storing all the OpenGL state machine state needed on
 /// the CPU side of an OpenGL graphics application.
@@ -140,12 +262,99 @@ pub struct GLContext {
     pub elapsed_time_seconds: f64,
     pub framerate_time_seconds: f64,
     pub frame_count: u32,
+    pub swap_interval: SwapInterval,
+    pub fullscreen: bool,
+    // Where the window was before going fullscreen, so it can be put back.
+    pub windowed_pos: (i32, i32),
+    pub windowed_size: (u32, u32),
+}
+
+impl GLContext {
+    ///
+    /// Set how often the buffers get swapped, and return the mode that actually took
+    /// effect. Adaptive vsync needs the swap control tear extension; without it this
+    /// falls back to plain vsync.
+    ///
+    pub fn set_swap_interval(&mut self, swap_interval: SwapInterval) -> SwapInterval {
+        let swap_interval = match swap_interval {
+            SwapInterval::Adaptive => {
+                if self.glfw.extension_supported("WGL_EXT_swap_control_tear") ||
+                   self.glfw.extension_supported("GLX_EXT_swap_control_tear") {
+                    SwapInterval::Adaptive
+                } else {
+                    SwapInterval::VSync
+                }
+            }
+            other => other,
+        };
+
+        let glfw_swap_interval = match swap_interval {
+            SwapInterval::Immediate => glfw::SwapInterval::None,
+            SwapInterval::VSync => glfw::SwapInterval::Sync(1),
+            SwapInterval::Adaptive => glfw::SwapInterval::Adaptive,
+        };
+        self.glfw.set_swap_interval(glfw_swap_interval);
+        self.swap_interval = swap_interval;
+
+        swap_interval
+    }
+
+    ///
+    /// Switch between a window and fullscreen on the primary monitor. The framebuffer
+    /// changes size, so callers should read the new size back with
+    /// `window.get_framebuffer_size` and update their viewport and projection matrices.
+    /// Returns whether the window is now fullscreen.
+    ///
+    pub fn toggle_fullscreen(&mut self) -> bool {
+        if self.fullscreen {
+            let (x, y) = self.windowed_pos;
+            let (width, height) = self.windowed_size;
+            self.window.set_monitor(glfw::WindowMode::Windowed, x, y, width, height, None);
+            self.fullscreen = false;
+        } else {
+            let (width, height) = self.window.get_size();
+            self.windowed_pos = self.window.get_pos();
+            self.windowed_size = (width as u32, height as u32);
+
+            // `with_primary_monitor` only lends the monitor out to a shared closure,
+            // so the window is mutated through a `RefCell`.
+            let window = RefCell::new(&mut self.window);
+            self.fullscreen = self.glfw.with_primary_monitor(|_, monitor| {
+                let monitor = match monitor {
+                    Some(monitor) => monitor,
+                    None => return false,
+                };
+                let mode = match monitor.get_video_mode() {
+                    Some(mode) => mode,
+                    None => return false,
+                };
+                window.borrow_mut().set_monitor(
+                    glfw::WindowMode::FullScreen(monitor), 0, 0, mode.width, mode.height, Some(mode.refresh_rate)
+                );
+
+                true
+            });
+        }
+
+        // Some drivers reset the swap interval when the window changes monitor.
+        let swap_interval = self.swap_interval;
+        self.set_swap_interval(swap_interval);
+
+        self.fullscreen
+    }
 }
 
 ///
-/// Initialize a new OpenGL context and load a new GLFW window. 
+/// Initialize a new OpenGL context and load a new GLFW window with the default settings.
 ///
 pub fn start_gl(logger: &Logger) -> Result<GLContext, String> {
+    start_gl_with_config(logger, &GlConfig::default())
+}
+
+///
+/// Initialize a new OpenGL context and load a new GLFW window. 
+///
+pub fn start_gl_with_config(logger: &Logger, config: &GlConfig) -> Result<GLContext, String> {
     // Start a GL context and OS window using the GLFW helper library.
     let mut glfw = glfw::init(glfw::FAIL_ON_ERRORS).unwrap();
 
@@ -161,6 +370,7 @@ pub fn start_gl(logger: &Logger) -> Result<GLContext, String> {
 
     // Set anti-aliasing factor to make diagonal edges appear less jagged.
     glfw.window_hint(glfw::WindowHint::Samples(Some(4)));
+    glfw.window_hint(glfw::WindowHint::SRgbCapable(config.srgb_framebuffer));
 
     let (mut window, events) = glfw.create_window(
         G_GL_WIDTH_DEFAULT, G_GL_HEIGHT_DEFAULT, "GUI Panels", glfw::WindowMode::Windowed
@@ -171,10 +381,11 @@ pub fn start_gl(logger: &Logger) -> Result<GLContext, String> {
     window.set_key_polling(true);
     window.set_size_polling(true);
     window.set_refresh_polling(true);
-    window.set_size_polling(true);
+    window.set_framebuffer_size_polling(true);
 
     // Load the OpenGl function pointers.
     gl::load_with(|symbol| { window.get_proc_address(symbol) as *const _ });
+    spirv::load_with(|symbol| { window.get_proc_address(symbol) as *const _ });
 
     // Get renderer and version info.
     let renderer = glubyte_ptr_to_string(unsafe { gl::GetString(gl::RENDERER) });
@@ -182,9 +393,10 @@ pub fn start_gl(logger: &Logger) -> Result<GLContext, String> {
     println!("Renderer: {}", renderer);
     println!("OpenGL version supported: {}", version);
     logger.log(&format!("renderer: {}\nversion: {}\n", renderer, version));
+    logger.log(&format!("SPIR-V shaders: {}\n", if spirv::is_supported() { "supported" } else { "not supported" }));
     log_gl_params(logger);
 
-    Ok(GLContext {
+    let mut context = GLContext {
         glfw: glfw, 
         window: window, 
         events: events,
@@ -194,7 +406,15 @@ pub fn start_gl(logger: &Logger) -> Result<GLContext, String> {
         elapsed_time_seconds: 0.0,
         framerate_time_seconds: 0.0,
         frame_count: 0,
-    })
+        swap_interval: config.swap_interval,
+        fullscreen: false,
+        windowed_pos: (0, 0),
+        windowed_size: (G_GL_WIDTH_DEFAULT, G_GL_HEIGHT_DEFAULT),
+    };
+    let swap_interval = context.set_swap_interval(config.swap_interval);
+    logger.log(&format!("vsync: {}\n", swap_interval));
+
+    Ok(context)
 }
 
 ///
@@ -214,46 +434,187 @@ pub fn update_fps_counter(context: &mut GLContext) {
     context.frame_count += 1;
 }
 
-pub fn parse_file_into_str(logger: &Logger, file_name: &str, shader_str: &mut [u8], max_len: usize) -> bool {
-    shader_str[0] = 0;
-    let file = File::open(file_name);
-    if file.is_err() {
-        logger.log_err(&format!("ERROR: opening file for reading: {}\n", file_name));
-        return false;
+///
+/// Runs a simulation in steps of a fixed length, however long the frames take, so
+/// it behaves the same at 30, 60, or uncapped frames per second. Each frame adds
+/// its time to an accumulator, and every whole step's worth of time in it is one
+/// step to run. The time left over carries over to the next frame.
+///
+#[derive(Copy, Clone, Debug)]
+pub struct FixedStep {
+    step_seconds: f64,
+    max_steps_per_frame: u32,
+    accumulator_seconds: f64,
+}
+
+impl FixedStep {
+    ///
+    /// Step `steps_per_second` times per simulated second. A frame runs at most
+    /// `max_steps_per_frame` steps; if more than that are due, the rest are
+    /// dropped and the simulation falls behind real time. Otherwise a frame slow
+    /// enough to need many steps would make the next frame need even more.
+    ///
+    pub fn new(steps_per_second: f64, max_steps_per_frame: u32) -> FixedStep {
+        FixedStep {
+            step_seconds: 1.0 / steps_per_second,
+            max_steps_per_frame: max_steps_per_frame,
+            accumulator_seconds: 0.0,
+        }
     }
 
-    let file = file.unwrap();
-    let mut reader = BufReader::new(file);
+    pub fn step_seconds(&self) -> f64 {
+        self.step_seconds
+    }
 
-    let bytes_read = reader.read(shader_str);
-    if bytes_read.is_err() {
-        logger.log_err(&format!("ERROR: reading shader file {}\n", file_name));
-        return false;
+    ///
+    /// Add the time a frame took, and return how many steps to run for it.
+    ///
+    pub fn advance(&mut self, elapsed_seconds: f64) -> u32 {
+        self.accumulator_seconds += elapsed_seconds;
+        let mut steps = 0;
+        while self.accumulator_seconds >= self.step_seconds {
+            if steps == self.max_steps_per_frame {
+                self.accumulator_seconds = 0.0;
+                break;
+            }
+            self.accumulator_seconds -= self.step_seconds;
+            steps += 1;
+        }
+
+        steps
     }
 
-    let bytes_read = bytes_read.unwrap();
-    if bytes_read >= (max_len - 1) {
-        logger.log_err(&format!("WARNING: file {} too big - truncated.\n", file_name));
+    ///
+    /// How far the time left over is into the next step, from 0 up to but not
+    /// including 1. Rendering each object this far from where it was before the
+    /// last step to where it is now keeps motion smooth when the frame rate and
+    /// the step rate do not match.
+    ///
+    pub fn alpha(&self) -> f64 {
+        self.accumulator_seconds / self.step_seconds
     }
 
-    // append \0 to end of file string.
-    shader_str[bytes_read] = 0;
+    ///
+    /// Throw away the time left over, e.g. when the simulation is restarted.
+    ///
+    pub fn reset(&mut self) {
+        self.accumulator_seconds = 0.0;
+    }
+}
 
-    return true;
+///
+/// The location of the uniform called `name` in `program`, or -1 if it has none.
+/// GL reads the name up to a NUL byte, so it is copied into a NUL terminated
+/// string first rather than handing GL the bytes of a Rust string.
+///
+pub fn uniform_location(program: GLuint, name: &str) -> GLint {
+    let c_name = match CString::new(name) {
+        Ok(val) => val,
+        Err(_) => return -1,
+    };
+
+    unsafe { gl::GetUniformLocation(program, c_name.as_ptr()) }
+}
+
+///
+/// The location of the attribute called `name` in `program`, or -1 if it has none.
+///
+pub fn attrib_location(program: GLuint, name: &str) -> GLint {
+    let c_name = match CString::new(name) {
+        Ok(val) => val,
+        Err(_) => return -1,
+    };
+
+    unsafe { gl::GetAttribLocation(program, c_name.as_ptr()) }
+}
+
+///
+/// Read the whole of the shader source file `file_name`, ready to hand to GL.
+/// The source has to be UTF-8 without any NUL bytes in it, since GL would stop
+/// reading it at the first one.
+///
+pub fn read_shader_source(file_name: &str) -> Result<CString, String> {
+    let mut file = match File::open(file_name) {
+        Ok(val) => val,
+        Err(e) => return Err(format!("ERROR: opening file for reading: {}: {}", file_name, e)),
+    };
+
+    let mut bytes = Vec::new();
+    if let Err(e) = file.read_to_end(&mut bytes) {
+        return Err(format!("ERROR: reading shader file {}: {}", file_name, e));
+    }
+
+    let source = match String::from_utf8(bytes) {
+        Ok(val) => val,
+        Err(e) => return Err(format!("ERROR: shader file {} is not UTF-8: {}", file_name, e.utf8_error())),
+    };
+
+    match CString::new(source) {
+        Ok(val) => Ok(val),
+        Err(e) => Err(format!("ERROR: shader file {} has a NUL byte at {}", file_name, e.nul_position())),
+    }
+}
+
+fn create_shader(
+    logger: &Logger, file_name: &str, defines: &[(&str, &str)], shader: &mut GLuint, gl_type: GLenum) -> bool {
+
+    // A pre-compiled SPIR-V shader takes the place of the source, unless the
+    // source is being compiled with defines the SPIR-V was not built with.
+    if defines.is_empty() {
+        if let Some(spirv_shader) = spirv::create_shader_from_spirv(logger, file_name, gl_type) {
+            *shader = spirv_shader;
+            return true;
+        }
+    }
+
+    let (source, file_table) = shader_source(logger, file_name, defines, gl_type);
+
+    compile_shader(logger, &source, &file_table, shader, gl_type)
 }
 
-fn create_shader(logger: &Logger, file_name: &str, shader: &mut GLuint, gl_type: GLenum) -> bool {
+///
+/// Read a shader's source the way it is compiled, with its includes expanded and
+/// its defines put in, or the embedded fallback shader if the file cannot be read.
+/// Returns the source and, for a shader with includes, the table of the files the
+/// compiler's errors refer to.
+///
+fn shader_source(logger: &Logger, file_name: &str, defines: &[(&str, &str)], gl_type: GLenum) -> (String, String) {
     logger.log(&format!("Creating shader from {}...\n", file_name));
 
-    let mut shader_string = vec![0; MAX_SHADER_LENGTH];
-    parse_file_into_str(logger, file_name, &mut shader_string, MAX_SHADER_LENGTH);
+    let mut file_table = String::new();
+    let shader_string = match shader_include::load_shader_source(file_name) {
+        Ok(shader_source) => {
+            if shader_source.files.len() > 1 {
+                file_table = shader_source.file_table();
+            }
+            shader_source.text
+        }
+        Err(e) => {
+            logger.log_err(&format!("{}\n", e));
+            match assets::fallback_shader_source(gl_type) {
+                Some(source) => {
+                    logger.log_err(&format!("WARNING: using the embedded fallback shader in place of {}\n", file_name));
+                    String::from(source)
+                }
+                None => String::new(),
+            }
+        }
+    };
+    let shader_string = shader_include::insert_defines(&shader_string, defines);
+
+    (shader_string, file_table)
+}
+
+fn compile_shader(logger: &Logger, source: &str, file_table: &str, shader: &mut GLuint, gl_type: GLenum) -> bool {
+    let mut shader_string = String::from(source);
+    shader_string.push('\0');
 
     *shader = unsafe { gl::CreateShader(gl_type) };
     let p = shader_string.as_ptr() as *const GLchar;
     
     unsafe {
         gl::ShaderSource(*shader, 1, &p, ptr::null());
-        gl::CompileShader(*shader);
+        gl_check!(gl::CompileShader(*shader));
     }
     // Check for compile errors.
     let mut params = -1;
@@ -263,6 +624,10 @@ fn create_shader(logger: &Logger, file_name: &str, shader: &mut GLuint, gl_type:
 
     if params != gl::TRUE as i32 {
         logger.log_err(&format!("ERROR: GL shader index {} did not compile\n", *shader));
+        if !file_table.is_empty() {
+            // The file numbers in the info log refer to this table.
+            logger.log_err(&format!("shader source files:\n{}", file_table));
+        }
         print_shader_info_log(*shader);
         
         return false;
@@ -342,11 +707,13 @@ pub fn create_programme(logger: &Logger, vertex_shader: GLuint, fragment_shader:
             "Created programme {}. attaching shaders {} and {}...\n", 
             programme, vertex_shader, fragment_shader)
         );
-        gl::AttachShader(*programme, vertex_shader);
-        gl::AttachShader(*programme, fragment_shader);
+        gl_check!(gl::AttachShader(*programme, vertex_shader));
+        gl_check!(gl::AttachShader(*programme, fragment_shader));
+        // Ask the driver to keep the linked binary, so it can be cached.
+        gl::ProgramParameteri(*programme, gl::PROGRAM_BINARY_RETRIEVABLE_HINT, gl::TRUE as GLint);
 
         // Link the shader programme. If binding input attributes do that before linking.
-        gl::LinkProgram(*programme);
+        gl_check!(gl::LinkProgram(*programme));
         let mut params = -1;
         gl::GetProgramiv(*programme, gl::LINK_STATUS, &mut params);
         if params != gl::TRUE as i32 {
@@ -369,98 +736,331 @@ pub fn create_programme(logger: &Logger, vertex_shader: GLuint, fragment_shader:
 /// Compile and link a shader program.
 ///
 pub fn create_programme_from_files(logger: &Logger, vert_file_name: &str, frag_file_name: &str) -> GLuint {
+    create_programme_from_files_with_defines(logger, vert_file_name, frag_file_name, &[])
+}
+
+///
+/// Build a variant of a shader programme with a `#define` for each name and value
+/// put at the top of both shaders, so features can be compiled in or out.
+///
+/// A programme built from source is cached as a binary in `shader_cache`, keyed
+/// by its shaders' source and the driver, and later runs load the binary instead
+/// of compiling the shaders again. Deleting the directory clears the cache.
+///
+pub fn create_programme_from_files_with_defines(
+    logger: &Logger, vert_file_name: &str, frag_file_name: &str, defines: &[(&str, &str)]) -> GLuint {
+
     let mut vertex_shader: GLuint = 0;
     let mut fragment_shader: GLuint = 0;
     let mut programme: GLuint = 0;
     
-    create_shader(logger, vert_file_name, &mut vertex_shader, gl::VERTEX_SHADER);
-    create_shader(logger, frag_file_name, &mut fragment_shader, gl::FRAGMENT_SHADER);
-    create_programme(logger, vertex_shader, fragment_shader, &mut programme);
+    // SPIR-V shaders skip the compiler already, so there is nothing to cache.
+    if defines.is_empty() && (spirv::has_spirv(vert_file_name) || spirv::has_spirv(frag_file_name)) {
+        create_shader(logger, vert_file_name, defines, &mut vertex_shader, gl::VERTEX_SHADER);
+        create_shader(logger, frag_file_name, defines, &mut fragment_shader, gl::FRAGMENT_SHADER);
+        create_programme(logger, vertex_shader, fragment_shader, &mut programme);
+
+        return programme;
+    }
+
+    let (vertex_source, vertex_file_table) = shader_source(logger, vert_file_name, defines, gl::VERTEX_SHADER);
+    let (fragment_source, fragment_file_table) = shader_source(logger, frag_file_name, defines, gl::FRAGMENT_SHADER);
+    let driver = program_cache::driver_description();
+    let cache_key = program_cache::cache_key(&[&vertex_source, &fragment_source], &driver);
+    if let Some(cached_programme) = program_cache::load_program(logger, cache_key, &driver) {
+        return cached_programme;
+    }
+
+    compile_shader(logger, &vertex_source, &vertex_file_table, &mut vertex_shader, gl::VERTEX_SHADER);
+    compile_shader(logger, &fragment_source, &fragment_file_table, &mut fragment_shader, gl::FRAGMENT_SHADER);
+    if create_programme(logger, vertex_shader, fragment_shader, &mut programme) {
+        program_cache::save_program(logger, cache_key, &driver, programme);
+    }
     
     programme
 }
 
 
 ///
-/// Print absolutely everything about a shader. This is only useful if you get really
-/// stuck wondering why a shader isn't working properly.
+/// An attribute or uniform a shader programme uses. Each element of an array is
+/// one of these, with its own name and location.
 ///
-pub fn print_all(sp: GLuint) {
-    let mut params = -1;
+#[derive(Clone, Debug, PartialEq)]
+pub struct ActiveVariable {
+    pub name: String,
+    pub gl_type: GLenum,
+    pub location: GLint,
+}
 
-    unsafe {
-        println!("--------------------\nshader programme {} info:", sp);
-        gl::GetProgramiv(sp, gl::LINK_STATUS, &mut params);
-        println!("GL_LINK_STATUS = {}", params);
+///
+/// Absolutely everything GL reports about a shader programme. This is only useful
+/// if you get really stuck wondering why a shader isn't working properly.
+///
+#[derive(Clone, Debug)]
+pub struct ProgramInfo {
+    pub programme: GLuint,
+    pub link_status: bool,
+    pub attached_shaders: GLint,
+    pub attributes: Vec<ActiveVariable>,
+    pub uniforms: Vec<ActiveVariable>,
+    pub info_log: String,
+}
+
+impl ProgramInfo {
+    fn report(&self) -> String {
+        let mut report = String::new();
+        writeln!(report, "--------------------\nshader programme {} info:", self.programme).unwrap();
+        writeln!(report, "GL_LINK_STATUS = {}", self.link_status).unwrap();
+        writeln!(report, "GL_ATTACHED_SHADERS = {}", self.attached_shaders).unwrap();
+        for &(title, variables) in [("GL_ACTIVE_ATTRIBUTES", &self.attributes), ("GL_ACTIVE_UNIFORMS", &self.uniforms)].iter() {
+            writeln!(report, "{} = {}", title, variables.len()).unwrap();
+            for (i, variable) in variables.iter().enumerate() {
+                writeln!(
+                    report, "  {}) type:{} name:{} location:{}",
+                    i, gl_type_to_string(variable.gl_type), variable.name, variable.location
+                ).unwrap();
+            }
+        }
+        write!(report, "Program info log for GL index {}:\n{}", self.programme, self.info_log).unwrap();
+
+        report
+    }
+
+    ///
+    /// Write everything out to the log.
+    ///
+    pub fn log(&self, logger: &Logger) {
+        logger.log(&self.report());
+    }
+}
 
-        gl::GetProgramiv(sp, gl::ATTACHED_SHADERS, &mut params);
-        println!("GL_ATTACHED_SHADERS = {}", params);
+// The longest attribute or uniform name read back from GL, NUL included.
+const MAX_VARIABLE_NAME_LENGTH: usize = 256;
 
-        gl::GetProgramiv(sp, gl::ACTIVE_ATTRIBUTES, &mut params);
-        println!("GL_ACTIVE_ATTRIBUTES = {}", params);
+///
+/// The names of the elements of an attribute or uniform GL reports with `size`
+/// elements. GL reports an array once, under the name of its first element, as in
+/// `lights[0]`.
+///
+fn array_element_names(name: &str, size: GLint) -> Vec<String> {
+    if size <= 1 {
+        return vec![String::from(name)];
     }
 
-    for i in 0..params {
-        let mut name = [0; 64];
-        let max_length = 64;
-        let mut actual_length = 0;
+    let base_name = if name.ends_with("[0]") { &name[..name.len() - 3] } else { name };
+
+    (0..size).map(|i| format!("{}[{}]", base_name, i)).collect()
+}
+
+///
+/// Read `count` attributes or uniforms. `get_active` fills in the size, type, and
+/// name of the one at an index, and gives the length of its name. Each element of
+/// an array gets its location from `location_of` under its own name.
+///
+fn active_variables<F, G>(count: GLint, get_active: F, location_of: G) -> Vec<ActiveVariable>
+    where F: Fn(GLuint, &mut GLint, &mut GLenum, &mut [u8]) -> usize,
+          G: Fn(&str) -> GLint
+{
+    let mut variables = vec![];
+    for i in 0..count {
+        let mut name = [0u8; MAX_VARIABLE_NAME_LENGTH];
         let mut size = 0;
-        let mut gl_type: GLenum = 0;
-        unsafe {
-            gl::GetActiveAttrib(sp, i as GLuint, max_length, &mut actual_length, &mut size, &mut gl_type, &mut name[0]);
-        }
-        if size > 1 {
-            for j in 0..size {
-                let mut long_name = vec![];
-                //write!(long_name, "{}[{}]", name, j);
-                let location = unsafe { gl::GetAttribLocation(sp, long_name.as_ptr() as *const i8) };
-                println!(
-                    "  {}) type:{} name:{} location:{}", 
-                    i, gl_type_to_string(gl_type), long_name.iter().map(|ch| *ch as u8 as char).collect::<String>(), location
-                );
-            }
-        } else {
-            let location = unsafe { gl::GetAttribLocation(sp, &mut name[0]) };
-            println!(
-                "  {}) type:{} name:{} location:{}",
-                i, gl_type_to_string(gl_type), name.iter().map(|ch| *ch as u8 as char).collect::<String>(), location
-            );
+        let mut gl_type = 0;
+        let length = get_active(i as GLuint, &mut size, &mut gl_type, &mut name);
+        let name = String::from_utf8_lossy(&name[..length]).into_owned();
+        for element_name in array_element_names(&name, size) {
+            let location = location_of(&element_name);
+            variables.push(ActiveVariable { name: element_name, gl_type: gl_type, location: location });
         }
     }
-    
+
+    variables
+}
+
+fn programme_info_log(sp: GLuint) -> String {
+    let mut log = [0u8; 2048];
+    let mut actual_length = 0;
     unsafe {
-        gl::GetProgramiv(sp, gl::ACTIVE_UNIFORMS, &mut params);
+        gl::GetProgramInfoLog(sp, log.len() as GLsizei, &mut actual_length, log.as_mut_ptr() as *mut GLchar);
     }
-    println!("GL_ACTIVE_UNIFORMS = {}", params);
-    for i in 0..params {
-        let mut name = [0; 64];
-        let max_length = 64;
-        let mut actual_length = 0;
-        let mut size = 0;
-        let mut gl_type: GLenum = 0;
+
+    String::from_utf8_lossy(&log[..actual_length as usize]).into_owned()
+}
+
+///
+/// Ask GL about the shader programme `sp`: whether it linked, and the type and
+/// location of every attribute and uniform it uses.
+///
+pub fn introspect(sp: GLuint) -> ProgramInfo {
+    let mut link_status = -1;
+    let mut attached_shaders = 0;
+    let mut attribute_count = 0;
+    let mut uniform_count = 0;
+    unsafe {
+        gl::GetProgramiv(sp, gl::LINK_STATUS, &mut link_status);
+        gl::GetProgramiv(sp, gl::ATTACHED_SHADERS, &mut attached_shaders);
+        gl::GetProgramiv(sp, gl::ACTIVE_ATTRIBUTES, &mut attribute_count);
+        gl::GetProgramiv(sp, gl::ACTIVE_UNIFORMS, &mut uniform_count);
+    }
+
+    let attributes = active_variables(attribute_count, |i, size, gl_type, name| {
+        let mut length = 0;
         unsafe {
-            gl::GetActiveUniform(sp, i as u32, max_length, &mut actual_length, &mut size, &mut gl_type, &mut name[0]);
+            gl::GetActiveAttrib(sp, i, name.len() as GLsizei, &mut length, size, gl_type, name.as_mut_ptr() as *mut GLchar);
         }
-        if size > 1 {
-            for j in 0..size {
-                let long_name = [0; 64];
-
-                //write!(long_name, "{}[{}]", name, j);
-                let location = unsafe { gl::GetUniformLocation(sp, long_name.as_ptr()) };
-                println!(
-                    "  {}) type:{} name:{} location:{}",
-                    i, gl_type_to_string(gl_type), long_name.iter().map(|ch| *ch as u8 as char).collect::<String>(), location
-                );
-            }
-        } else {
-            let location = unsafe { gl::GetUniformLocation(sp, &name[0]) };
-            println!(
-                "  {}) type:{} name:{} location:{}", 
-                i, gl_type_to_string(gl_type), name.iter().map(|ch| *ch as u8 as char).collect::<String>(), location
-            );
+        length as usize
+    }, |name| attrib_location(sp, name));
+    let uniforms = active_variables(uniform_count, |i, size, gl_type, name| {
+        let mut length = 0;
+        unsafe {
+            gl::GetActiveUniform(sp, i, name.len() as GLsizei, &mut length, size, gl_type, name.as_mut_ptr() as *mut GLchar);
         }
-    }
+        length as usize
+    }, |name| uniform_location(sp, name));
 
-    print_programme_info_log(sp);
+    ProgramInfo {
+        programme: sp,
+        link_status: link_status == gl::TRUE as GLint,
+        attached_shaders: attached_shaders,
+        attributes: attributes,
+        uniforms: uniforms,
+        info_log: programme_info_log(sp),
+    }
 }
 
+mod gl_utils_tests {
+    use super::{active_variables, array_element_names, check_gl_errors, gl_error_name, FixedStep};
+    use gl;
+    use gl::types::GLint;
+    use std::env;
+    use std::fs;
+
+    ///
+    /// Run a simulation that counts its steps for two seconds at a frame rate.
+    ///
+    fn steps_at_frame_rate(frames_per_second: f64) -> u32 {
+        let mut fixed_step = FixedStep::new(120.0, 10);
+        let frame_count = (2.0 * frames_per_second) as usize;
+        let mut steps = 0;
+        for _ in 0..frame_count {
+            steps += fixed_step.advance(1.0 / frames_per_second);
+        }
+
+        steps
+    }
+
+    #[test]
+    fn test_the_step_count_does_not_depend_on_the_frame_rate() {
+        // Rounding in the accumulator can lose a step at the very end.
+        for &frames_per_second in [30.0, 60.0, 144.0, 1000.0].iter() {
+            let steps = steps_at_frame_rate(frames_per_second);
+            assert!(steps == 239 || steps == 240, "{} fps ran {} steps", frames_per_second, steps);
+        }
+    }
+
+    #[test]
+    fn test_alpha_is_the_fraction_of_a_step_left_over() {
+        let mut fixed_step = FixedStep::new(100.0, 10);
+
+        assert_eq!(fixed_step.advance(0.025), 2);
+        assert!((fixed_step.alpha() - 0.5).abs() < 1e-9);
+        assert_eq!(fixed_step.advance(0.004), 0);
+        assert!((fixed_step.alpha() - 0.9).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_a_long_frame_runs_at_most_the_maximum_steps_and_drops_the_rest() {
+        let mut fixed_step = FixedStep::new(100.0, 5);
+
+        assert_eq!(fixed_step.advance(1.0), 5);
+        assert_eq!(fixed_step.alpha(), 0.0);
+        assert_eq!(fixed_step.advance(0.01), 1);
+    }
+
+    ///
+    /// A shader file of its own for each test, so tests running at the same time
+    /// do not share files.
+    ///
+    fn temp_shader_file(name: &str, contents: &[u8]) -> String {
+        let file_name = env::temp_dir().join(format!("gl_utils_tests_{}_{}.glsl", name, ::std::process::id()));
+        let file_name = String::from(file_name.to_string_lossy());
+        fs::write(&file_name, contents).unwrap();
+
+        file_name
+    }
+
+    #[test]
+    fn test_shader_source_is_read_whole_however_long() {
+        // far longer than the fixed buffers shaders used to be read into
+        let mut source = String::from("#version 410\n");
+        for i in 0..1000 {
+            source.push_str(&format!("// line {}\n", i));
+        }
+        let file_name = temp_shader_file("long", source.as_bytes());
+        let result = super::read_shader_source(&file_name);
+        let _ = fs::remove_file(&file_name);
+
+        assert_eq!(result.unwrap().to_str(), Ok(source.as_str()));
+    }
+
+    #[test]
+    fn test_shader_source_with_a_nul_byte_is_an_error() {
+        let file_name = temp_shader_file("nul", b"#version 410\n\0void main() {}\n");
+        let result = super::read_shader_source(&file_name);
+        let _ = fs::remove_file(&file_name);
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_shader_source_that_is_not_utf8_is_an_error() {
+        let file_name = temp_shader_file("utf8", b"#version 410\n// \xff\n");
+        let result = super::read_shader_source(&file_name);
+        let _ = fs::remove_file(&file_name);
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_missing_shader_source_is_an_error() {
+        assert!(super::read_shader_source("no_such_shader.glsl").is_err());
+    }
+
+    #[test]
+    fn test_each_element_of_an_array_gets_its_own_name_and_location() {
+        let reported: [(&str, GLint); 2] = [("model", 1), ("lights[0]", 3)];
+        let variables = active_variables(2, |i, size, gl_type, name| {
+            let (reported_name, reported_size) = reported[i as usize];
+            *size = reported_size;
+            *gl_type = gl::FLOAT_VEC3;
+            name[..reported_name.len()].copy_from_slice(reported_name.as_bytes());
+            reported_name.len()
+        }, |name| if name == "lights[2]" { 7 } else { 1 });
+
+        let names: Vec<&str> = variables.iter().map(|variable| variable.name.as_str()).collect();
+        assert_eq!(names, vec!["model", "lights[0]", "lights[1]", "lights[2]"]);
+        assert_eq!(variables[3].location, 7);
+        assert!(variables.iter().all(|variable| variable.gl_type == gl::FLOAT_VEC3));
+    }
+
+    #[test]
+    fn test_a_variable_that_is_not_an_array_keeps_its_name() {
+        assert_eq!(array_element_names("view", 1), vec![String::from("view")]);
+        assert_eq!(array_element_names("weights[0]", 1), vec![String::from("weights[0]")]);
+    }
+
+    #[test]
+    fn test_gl_errors_are_named_as_in_the_specification() {
+        assert_eq!(gl_error_name(gl::INVALID_OPERATION), "GL_INVALID_OPERATION");
+        assert_eq!(gl_error_name(gl::INVALID_FRAMEBUFFER_OPERATION), "GL_INVALID_FRAMEBUFFER_OPERATION");
+        assert_eq!(gl_error_name(gl::NO_ERROR), "GL_NO_ERROR");
+        assert_eq!(gl_error_name(0x1234), "an unknown GL error");
+    }
+
+    #[test]
+    fn test_gl_check_passes_the_value_of_the_call_through() {
+        // There is no GL context in the tests, so there are no errors to find.
+        assert_eq!(check_gl_errors("gl::Flush()", file!(), line!()), 0);
+        assert_eq!(gl_check!(1 + 1), 2);
+    }
+}
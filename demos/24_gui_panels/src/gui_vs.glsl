@@ -2,11 +2,13 @@
 
 in vec2 vp;
 uniform vec2 gui_scale;
+// where the middle of the panel goes, in clip space
+uniform vec2 gui_offset;
 out vec2 st;
 
 
 void main () {
     st = (vp + 1.0) * 0.5;
-    gl_Position = vec4 (vp * gui_scale, 0.0, 1.0);
+    gl_Position = vec4 (vp * gui_scale + gui_offset, 0.0, 1.0);
 }
 
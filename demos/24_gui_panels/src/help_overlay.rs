@@ -0,0 +1,67 @@
+use overlay::{Overlay, GLYPH_ADVANCE, LINE_HEIGHT};
+
+
+const HELP_SCALE: f32 = 2.0;
+const HELP_PADDING: f32 = 10.0;
+// The gap between the tweak panel and the edges of the viewport.
+const TWEAKS_MARGIN: f32 = 10.0;
+const HELP_BACKGROUND_COLOUR: [f32; 4] = [0.0, 0.0, 0.0, 0.75];
+const HELP_TEXT_COLOUR: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+
+
+///
+/// The size in pixels of the panel `lines` are drawn in, padding included.
+///
+pub fn help_panel_size(lines: &[String]) -> (f32, f32) {
+    let longest_line = lines.iter().map(|line| line.len()).max().unwrap_or(0);
+    let width = longest_line as f32 * GLYPH_ADVANCE * HELP_SCALE + 2.0 * HELP_PADDING;
+    let height = lines.len() as f32 * LINE_HEIGHT * HELP_SCALE + 2.0 * HELP_PADDING;
+
+    (width, height)
+}
+
+///
+/// Queue up the help on a dark panel in the middle of the viewport, over
+/// everything else. Help too big for the viewport starts from its top left corner.
+///
+pub fn draw_help(overlay: &mut Overlay, lines: &[String], viewport_width: u32, viewport_height: u32) {
+    let (width, height) = help_panel_size(lines);
+    let x = f32::max(0.5 * (viewport_width as f32 - width), 0.0);
+    let y = f32::max(0.5 * (viewport_height as f32 - height), 0.0);
+    draw_panel(overlay, lines, x, y);
+}
+
+///
+/// Queue up the tweak panel in the bottom left corner of the viewport, out of the
+/// way of the HUD.
+///
+pub fn draw_tweaks(overlay: &mut Overlay, lines: &[String], viewport_height: u32) {
+    let (_, height) = help_panel_size(lines);
+    let y = f32::max(viewport_height as f32 - height - TWEAKS_MARGIN, 0.0);
+    draw_panel(overlay, lines, TWEAKS_MARGIN, y);
+}
+
+fn draw_panel(overlay: &mut Overlay, lines: &[String], x: f32, y: f32) {
+    let (width, height) = help_panel_size(lines);
+    overlay.rect(x, y, width, height, HELP_BACKGROUND_COLOUR);
+
+    let mut text_y = y + HELP_PADDING;
+    for line in lines.iter() {
+        overlay.text(x + HELP_PADDING, text_y, HELP_SCALE, HELP_TEXT_COLOUR, line);
+        text_y += LINE_HEIGHT * HELP_SCALE;
+    }
+}
+
+mod help_overlay_tests {
+    use super::help_panel_size;
+
+    #[test]
+    fn test_the_panel_fits_the_longest_line() {
+        let lines = vec![String::from("keys"), String::from("  F1  help"), String::new()];
+        let (width, height) = help_panel_size(&lines);
+
+        // 10 glyphs 12 pixels apart, 3 lines 16 pixels apart, and 10 pixels of padding around them
+        assert_eq!(width, 140.0);
+        assert_eq!(height, 68.0);
+    }
+}
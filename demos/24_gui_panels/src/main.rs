@@ -4,31 +4,70 @@ extern crate chrono;
 extern crate stb_image;
 extern crate png;
 extern crate assimp;
+#[cfg(feature = "glam")]
+extern crate glam;
+#[cfg(feature = "cgmath")]
+extern crate cgmath;
 
-#[macro_use] 
-extern crate scan_fmt;
-
+#[macro_use]
 mod gl_utils;
 mod graphics_math;
 mod obj_parser;
+mod colour;
+mod assets;
+mod shader_include;
+mod spirv;
+mod program_cache;
 mod logger;
+mod camera;
+mod overlay;
+mod streaming_buffer;
+mod perf_stats;
+mod gpu_timer;
+mod collision;
+mod character;
+mod draw_list;
+mod vertex_layout;
+mod render_target;
+mod security_camera;
+mod minimap;
+// The demo driver and its clock are shared with the other demos that use them.
+#[path = "../../shared/demo_app.rs"]
+mod demo_app;
+#[path = "../../shared/frame_clock.rs"]
+mod frame_clock;
+mod input_replay;
+mod help_overlay;
+mod tweak;
+
+
+use glfw::{Key, Modifiers};
+use gl::types::{GLchar, GLfloat, GLint, GLvoid, GLuint};
 
-
-use glfw::{Action, Context, Key};
-use gl::types::{GLchar, GLfloat, GLint, GLsizeiptr, GLvoid, GLuint};
-
-use std::mem;
 use std::ptr;
 use std::process;
+use std::time::Instant;
 
-use stb_image::image;
 use stb_image::image::LoadResult;
 
 use gl_utils::*;
 
 use graphics_math as math;
-use math::{Mat4, Versor};
+use math::{Mat4, Vec3, Versor};
 use logger::Logger;
+use camera::Camera;
+use overlay::Overlay;
+use perf_stats::{PerfStats, PERF_STATS_HISTORY};
+use gpu_timer::GpuTimer;
+use collision::Plane;
+use character::CharacterController;
+use draw_list::{DrawCommand, DrawList, Uniform};
+use vertex_layout::VertexLayout;
+use security_camera::{SecurityCamera, FEED_HEIGHT, FEED_MARGIN, FEED_WIDTH};
+use minimap::{Minimap, MINIMAP_EXTENT, MINIMAP_SIZE};
+use demo_app::{run_demo, DemoApp, Input, KeyBindings};
+use frame_clock::FrameClock;
+use tweak::Tweaks;
 
 
 const GL_LOG_FILE: &str = "gl.log";
@@ -36,6 +75,10 @@ const GP_VS_FILE: &str = "src/gp_vs.glsl";
 const GP_FS_FILE: &str = "src/gp_fs.glsl";
 const GUI_VS_FILE: &str = "src/gui_vs.glsl";
 const GUI_FS_FILE: &str = "src/gui_fs.glsl";
+const MARKER_VS_FILE: &str = "src/marker_vs.glsl";
+const MARKER_FS_FILE: &str = "src/marker_fs.glsl";
+// the ground plane shader puts the floor at this height
+const GROUND_HEIGHT: f32 = -1.0;
 
 const GL_TEXTURE_MAX_ANISOTROPY_EXT: u32 = 0x84FE;
 const GL_MAX_TEXTURE_MAX_ANISOTROPY_EXT: u32 = 0x84FF;
@@ -44,30 +87,47 @@ const GL_MAX_TEXTURE_MAX_ANISOTROPY_EXT: u32 = 0x84FF;
 struct AppState {
     g_viewport_width: u32,
     g_viewport_height: u32,
+    // the gui panel's size as a fraction of the viewport, so it stays the same
+    // number of pixels whatever size the window is
+    gui_scale: [f32; 2],
 
     // virtual camera view matrix
     view_mat: Mat4,
     // virtual camera projection matrix
     proj_mat: Mat4,
+    // vertical field of view of the virtual camera in degrees
+    fovy: f32,
 
     gp_sp: GLuint,           // ground plane shader programme
     gp_view_mat_loc: GLint,  // view matrix location in gp_sp
     gp_proj_mat_loc: GLint,  // projection matrix location in gp_sp
     gui_sp: GLuint,          // 2d GUI panel shader programme
     gui_scale_loc: GLint,    // scale factors for gui shader   
+    gui_offset_loc: GLint,   // panel position for gui shader
+    marker_sp: GLuint,          // minimap marker shader programme
+    marker_pos_loc: GLint,      // marker position in marker_sp
+    marker_scale_loc: GLint,    // marker size in marker_sp
+    marker_heading_loc: GLint,  // marker heading in marker_sp
 }
 
 fn init_app_state() -> AppState {
     AppState {
         g_viewport_width: 640,
         g_viewport_height: 480,
+        gui_scale: [1.0, 1.0],
         view_mat: Mat4::identity(),
         proj_mat: Mat4::identity(),
+        fovy: 67.0,
         gp_sp: 0,
         gp_view_mat_loc: -1,
         gp_proj_mat_loc: -1,
         gui_sp: 0,
         gui_scale_loc: -1,
+        gui_offset_loc: -1,
+        marker_sp: 0,
+        marker_pos_loc: -1,
+        marker_scale_loc: -1,
+        marker_heading_loc: -1,
     }
 }
 
@@ -75,15 +135,21 @@ fn create_ground_plane_shaders(logger: &Logger, app: &mut AppState) {
     // Here I used negative y from the buffer as the z value so that it was on
     // the floor but also that the 'front' was on the top side. also note how I
     // work out the texture coordinates, st, from the vertex point position.
-    let mut gp_vs_str = vec![0; 1024];
-    let mut gp_fs_str = vec![0; 1024];
-    if !parse_file_into_str(logger, GP_VS_FILE, &mut gp_vs_str, 1024) {
-        panic!("Failed to parse ground plane vertex shader file.");
-    }
+    let gp_vs_str = match read_shader_source(GP_VS_FILE) {
+        Ok(val) => val,
+        Err(e) => {
+            logger.log_err(&e);
+            panic!("Failed to parse ground plane vertex shader file.");
+        }
+    };
 
-    if !parse_file_into_str(logger, GP_FS_FILE, &mut gp_fs_str, 1024) {
-        panic!("Failed to parse ground plane fragment shader file.");
-    }
+    let gp_fs_str = match read_shader_source(GP_FS_FILE) {
+        Ok(val) => val,
+        Err(e) => {
+            logger.log_err(&e);
+            panic!("Failed to parse ground plane fragment shader file.");
+        }
+    };
     
     unsafe {
         let gp_vs = gl::CreateShader(gl::VERTEX_SHADER);
@@ -103,10 +169,10 @@ fn create_ground_plane_shaders(logger: &Logger, app: &mut AppState) {
         assert!(gp_sp > 0);
 
         // Get uniform locations of camera view and projection matrices.
-        let gp_view_mat_loc = gl::GetUniformLocation(gp_sp, "view".as_ptr() as *const i8);
+        let gp_view_mat_loc = uniform_location(gp_sp, "view");
         assert!(gp_view_mat_loc > -1);
 
-        let gp_proj_mat_loc = gl::GetUniformLocation(gp_sp, "proj".as_ptr() as *const i8);
+        let gp_proj_mat_loc = uniform_location(gp_sp, "proj");
         assert!(gp_proj_mat_loc > -1);
 
         // Set defaults for matrices
@@ -122,15 +188,21 @@ fn create_ground_plane_shaders(logger: &Logger, app: &mut AppState) {
 
 fn create_gui_shaders(logger: &Logger, app: &mut AppState) {
     // Note that I scaled down the size to 0.5 * the viewport size here.
-    let mut gui_vs_str = vec![0; 1024];
-    let mut gui_fs_str = vec![0; 1024];
-    if !parse_file_into_str(logger, GUI_VS_FILE, &mut gui_vs_str, 1024) {
-        panic!("Failed to parse gui vertex shader file.");
-    }
+    let gui_vs_str = match read_shader_source(GUI_VS_FILE) {
+        Ok(val) => val,
+        Err(e) => {
+            logger.log_err(&e);
+            panic!("Failed to parse gui vertex shader file.");
+        }
+    };
 
-    if !parse_file_into_str(logger, GUI_FS_FILE, &mut gui_fs_str, 1024) {
-        panic!("Failed to parse gui fragment shader file.");
-    }   
+    let gui_fs_str = match read_shader_source(GUI_FS_FILE) {
+        Ok(val) => val,
+        Err(e) => {
+            logger.log_err(&e);
+            panic!("Failed to parse gui fragment shader file.");
+        }
+    };
 
     unsafe {
         let gui_vs = gl::CreateShader(gl::VERTEX_SHADER);
@@ -148,17 +220,68 @@ fn create_gui_shaders(logger: &Logger, app: &mut AppState) {
         gl::AttachShader(gui_sp, gui_fs);
         gl::LinkProgram(gui_sp);
         assert!(gui_sp > 0);
-        let gui_scale_loc = gl::GetUniformLocation(gui_sp, "gui_scale".as_ptr() as *const i8);
+        let gui_scale_loc = uniform_location(gui_sp, "gui_scale");
         assert!(gui_scale_loc > -1);
+        let gui_offset_loc = uniform_location(gui_sp, "gui_offset");
+        assert!(gui_offset_loc > -1);
 
         app.gui_sp = gui_sp;
         app.gui_scale_loc = gui_scale_loc;
+        app.gui_offset_loc = gui_offset_loc;
+    }
+}
+
+fn create_marker_shaders(logger: &Logger, app: &mut AppState) {
+    // The marker is an arrow drawn over the minimap, turned to the camera's heading.
+    let marker_vs_str = match read_shader_source(MARKER_VS_FILE) {
+        Ok(val) => val,
+        Err(e) => {
+            logger.log_err(&e);
+            panic!("Failed to parse marker vertex shader file.");
+        }
+    };
+
+    let marker_fs_str = match read_shader_source(MARKER_FS_FILE) {
+        Ok(val) => val,
+        Err(e) => {
+            logger.log_err(&e);
+            panic!("Failed to parse marker fragment shader file.");
+        }
+    };
+
+    unsafe {
+        let marker_vs = gl::CreateShader(gl::VERTEX_SHADER);
+        gl::ShaderSource(marker_vs, 1, &(marker_vs_str.as_ptr() as *const GLchar), ptr::null());
+        gl::CompileShader(marker_vs);
+        assert!(marker_vs > 0);
+
+        let marker_fs = gl::CreateShader(gl::FRAGMENT_SHADER);
+        gl::ShaderSource(marker_fs, 1, &(marker_fs_str.as_ptr() as *const GLchar), ptr::null());
+        gl::CompileShader(marker_fs);
+        assert!(marker_fs > 0);
+
+        let marker_sp = gl::CreateProgram();
+        gl::AttachShader(marker_sp, marker_vs);
+        gl::AttachShader(marker_sp, marker_fs);
+        gl::LinkProgram(marker_sp);
+        assert!(marker_sp > 0);
+        let marker_pos_loc = uniform_location(marker_sp, "marker_pos");
+        assert!(marker_pos_loc > -1);
+        let marker_scale_loc = uniform_location(marker_sp, "marker_scale");
+        assert!(marker_scale_loc > -1);
+        let marker_heading_loc = uniform_location(marker_sp, "marker_heading");
+        assert!(marker_heading_loc > -1);
+
+        app.marker_sp = marker_sp;
+        app.marker_pos_loc = marker_pos_loc;
+        app.marker_scale_loc = marker_scale_loc;
+        app.marker_heading_loc = marker_heading_loc;
     }
 }
 
 fn load_texture(file_name: &str, tex: &mut GLuint) -> bool {
     let force_channels = 4;
-    let mut image_data = match image::load_with_depth(file_name, force_channels, false) {
+    let mut image_data = match assets::load_image_with_depth(file_name, force_channels) {
         LoadResult::ImageU8(image_data) => image_data,
         LoadResult::Error(_) => {
             eprintln!("ERROR: could not load {}", file_name);
@@ -215,271 +338,512 @@ fn load_texture(file_name: &str, tex: &mut GLuint) -> bool {
     return true;
 }
 
-/* we will tell GLFW to run this function whenever the window is resized */
-fn glfw_framebuffer_size_callback(context: &mut GLContext, app: &mut AppState, width: u32, height: u32) {
-    context.width = width;
-    context.height = height;
-    /* update any perspective matrices used here */
-    app.proj_mat = Mat4::perspective(67.0, context.width as f32 / context.height as f32, 0.1, 100.0);
+/* recompute the projection matrix and send it to the ground plane shader */
+fn update_proj_mat(app: &mut AppState, width: u32, height: u32) {
+    app.proj_mat = Mat4::perspective(app.fovy, width as f32 / height as f32, 0.1, 100.0);
     unsafe {
-        gl::Viewport(0, 0, context.width as i32, context.height as i32);
+        gl::UseProgram(app.gp_sp);
+        gl::UniformMatrix4fv(app.gp_proj_mat_loc, 1, gl::FALSE, app.proj_mat.as_ptr());
     }
 }
 
-fn main() {
-    let logger = restart_gl_log(GL_LOG_FILE);
-    // Start a GL context and O/S window using the GLFW helper library.
-    let mut context = match start_gl(&logger) {
-        Ok(val) => val,
-        Err(e) => {
-            eprintln!("Failed to Initialize OpenGL context. Got error:");
-            eprintln!("{}", e);
-            process::exit(1);
-        }
-    };
+/* fit everything sized by the viewport to a new one: the gui scale factors that
+ * keep a `panel_width` by `panel_height` pixel panel that size, and the projection */
+fn update_viewport(app: &mut AppState, panel_width: f32, panel_height: f32, width: u32, height: u32) {
+    app.g_viewport_width = width;
+    app.g_viewport_height = height;
+    app.gui_scale = [panel_width / (width as f32), panel_height / (height as f32)];
+    update_proj_mat(app, width, height);
+}
 
-    let mut app = init_app_state();
+///
+/// A ground plane seen by a camera flying or walking over it, with GUI panels
+/// drawn on top: a texture, a security camera's picture, and a minimap.
+///
+struct GuiPanelsDemo {
+    logger: Logger,
+    app: AppState,
+
+    vao: GLuint,
+    marker_vao: GLuint,
+    gp_tex: GLuint,
+    gui_tex: GLuint,
+    // absolute panel dimensions in pixels
+    panel_width: f32,
+    panel_height: f32,
 
-    // create a 2d panel. from 2 triangles = 6 xy coords.
-    let points: [f32; 12] = [
-        -1.0, -1.0, 1.0, -1.0, -1.0, 1.0, -1.0, 1.0, 1.0, -1.0, 1.0, 1.0
-    ];
+    // keep track of some useful vectors that can be used for keyboard movement
+    fwd: Vec3,
+    rgt: Vec3,
+    up: Vec3,
+    q: Versor,
+    mat_rot_inv: Mat4,
+    // set when the driver restores a saved camera, for the next update to look
+    // from it
+    camera_restored: bool,
+    // the field of view and the camera's speed and turning speed
+    tweaks: Tweaks,
+    character: CharacterController,
+    ground: Plane,
+
+    security_camera: SecurityCamera,
+    minimap: Minimap,
+
+    overlay: Overlay,
+    perf_stats: PerfStats,
+    show_perf_stats: bool,
+    // GPU time spent drawing the ground plane and the GUI panel.
+    scene_timer: GpuTimer,
+    // GPU time spent drawing the scene again for the security camera.
+    security_timer: GpuTimer,
+    // the draws for each frame, sorted to cut down on state changes.
+    draw_list: DrawList,
+    frame_start: Instant,
+    // the state of the time controls, for the HUD
+    time_text: String,
+}
 
-    // for the ground plane we can just re-use panel points but y is now z
-    let mut vp_vbo = 0;
-    unsafe {
-        gl::GenBuffers(1, &mut vp_vbo);
-        gl::BindBuffer(gl::ARRAY_BUFFER, vp_vbo);
-        gl::BufferData(
-            gl::ARRAY_BUFFER, (points.len() * mem::size_of::<GLfloat>()) as GLsizeiptr, 
-            points.as_ptr() as *const GLvoid, gl::STATIC_DRAW
-        );
-    }
-    assert!(vp_vbo > 0);
+impl DemoApp for GuiPanelsDemo {
+    fn init(context: &mut GLContext) -> Result<GuiPanelsDemo, String> {
+        let logger = Logger::from_log_file(GL_LOG_FILE);
+        let mut app = init_app_state();
 
-    let mut vao = 0;
-    unsafe {
-        gl::GenVertexArrays(1, &mut vao);
-        gl::BindVertexArray(vao);
-        // NOTE: vertex buffer is already bound
-        gl::VertexAttribPointer(0, 2, gl::FLOAT, gl::FALSE, 0, ptr::null());
-        gl::EnableVertexAttribArray(0);
-    }
-    assert!(vao > 0);
+        // create a 2d panel. from 2 triangles = 6 xy coords.
+        let points: [f32; 12] = [
+            -1.0, -1.0, 1.0, -1.0, -1.0, 1.0, -1.0, 1.0, 1.0, -1.0, 1.0, 1.0
+        ];
 
-    // create a 3d camera to move in 3d so that we can tell that the panel is 2d
-    // keep track of some useful vectors that can be used for keyboard movement
-    let mut fwd = math::vec4((0.0, 0.0, -1.0, 0.0));
-    let mut rgt = math::vec4((1.0, 0.0,  0.0, 0.0));
-    let mut up  = math::vec4((0.0, 1.0,  0.0, 0.0));
-    let mut cam_pos = math::vec3((0.0, 1.0, 5.0));
-    let mut mat_trans_inv = Mat4::identity().translate(&cam_pos);
-
-    // point slightly downwards to see the plane
-    let mut q = Versor::from_axis_deg(0.0, 1.0, 0.0, 0.0);
-    let mut mat_rot_inv = q.to_mat4();
-    // combine the inverse rotation and transformation to make a view matrix
-    let mut view = mat_rot_inv.inverse() * mat_trans_inv.inverse();
-    // projection matrix
-    let mut proj = Mat4::perspective(67.0, context.width as f32 / context.height as f32, 0.1, 100.0);
-    let cam_speed = 3.0;          // 1 unit per second
-    let cam_heading_speed = 50.0; // 30 degrees per second
-
-    app.view_mat = view;
-    app.proj_mat = proj;
-    create_ground_plane_shaders(&logger, &mut app);
-    create_gui_shaders(&logger, &mut app);
-
-    // textures for ground plane and gui
-    let mut gp_tex = 0;
-    load_texture("src/tile2-diamonds256x256.png", &mut gp_tex);
-    assert!(gp_tex > 0);
-
-    let mut gui_tex = 0;
-    load_texture("src/skulluvmap.png", &mut gui_tex);
-    assert!(gui_tex > 0);
+        // for the ground plane we can just re-use panel points but y is now z
+        let mut vao = 0;
+        unsafe {
+            gl::GenVertexArrays(1, &mut vao);
+        }
+        assert!(vao > 0);
+        VertexLayout::new().floats(0, 2).upload(vao, &points, 6);
 
-    unsafe {
-        // rendering defaults
-        gl::DepthFunc(gl::LESS);   // set depth function but don't enable yet
-        gl::Enable(gl::CULL_FACE); // cull face
-        gl::CullFace(gl::BACK);    // cull back face
-        gl::FrontFace(gl::CCW);    // GL_CCW for counter clock-wise
-    }
+        // an arrow pointing up for the minimap marker, turned by its shader
+        let marker_points: [f32; 6] = [0.0, 1.0, -0.6, -0.8, 0.6, -0.8];
+        let mut marker_vao = 0;
+        unsafe {
+            gl::GenVertexArrays(1, &mut marker_vao);
+        }
+        assert!(marker_vao > 0);
+        VertexLayout::new().floats(0, 2).upload(marker_vao, &marker_points, 3);
+
+        // create a 3d camera to move in 3d so that we can tell that the panel is 2d
+        let cam_pos = math::vec3((0.0, 1.0, 5.0));
+        let mat_trans_inv = Mat4::from_translation(&cam_pos);
+        // the character controller moves the camera, either flying freely or
+        // walking on the ground plane. F switches between the two.
+        let character = CharacterController::new(cam_pos);
+        let ground = Plane::from_point_normal(&math::vec3((0.0, GROUND_HEIGHT, 0.0)), &math::vec3((0.0, 1.0, 0.0)));
+
+        // point slightly downwards to see the plane
+        let q = Versor::from_axis_deg(0.0, 1.0, 0.0, 0.0);
+        let mat_rot_inv = q.to_mat4();
+        // combine the inverse rotation and transformation to make a view matrix
+        let view = mat_rot_inv.inverse() * mat_trans_inv.inverse();
+
+        app.view_mat = view;
+        create_ground_plane_shaders(&logger, &mut app);
+        create_gui_shaders(&logger, &mut app);
+        create_marker_shaders(&logger, &mut app);
+        // the projection matrix and the gui scale factors, remade whenever the
+        // framebuffer changes size
+        let (panel_width, panel_height) = (256.0, 256.0);
+        update_viewport(&mut app, panel_width, panel_height, context.width, context.height);
+
+        // textures for ground plane and gui
+        let mut gp_tex = 0;
+        load_texture("src/tile2-diamonds256x256.png", &mut gp_tex);
+        assert!(gp_tex > 0);
+
+        let mut gui_tex = 0;
+        load_texture("src/skulluvmap.png", &mut gui_tex);
+        assert!(gui_tex > 0);
 
-    // absolute panel dimensions in pixels
-    let panel_width = 256.0;
-    let panel_height = 256.0;
-    unsafe {
-        gl::Viewport(0, 0, context.width as i32, context.height as i32);
+        unsafe {
+            // rendering defaults
+            gl::DepthFunc(gl::LESS);   // set depth function but don't enable yet
+            gl::Enable(gl::CULL_FACE); // cull face
+            gl::CullFace(gl::BACK);    // cull back face
+            gl::FrontFace(gl::CCW);    // GL_CCW for counter clock-wise
+        }
+
+        // a fixed camera up in the corner of the room, looking back across the floor.
+        // it renders the scene into a texture every frame, shown in a GUI panel at the
+        // top right of the window like a security monitor.
+        let security_camera = match SecurityCamera::new(math::vec3((6.0, 3.0, 6.0)), 45.0, -25.0, 67.0) {
+            Ok(val) => val,
+            Err(e) => return Err(e),
+        };
+
+        // a map of the floor from straight above, shown in the bottom right corner
+        // with an arrow for where the camera is and which way it faces.
+        let minimap = match Minimap::new(MINIMAP_EXTENT) {
+            Ok(val) => val,
+            Err(e) => return Err(e),
+        };
+
+        // frame time statistics, drawn over the scene. F3 toggles them on and off.
+        let overlay = Overlay::new(&logger);
+
+        let mut tweaks = Tweaks::new();
+        tweaks.add_f32("field of view", app.fovy, 30.0, 120.0, 5.0);   // degrees
+        tweaks.add_f32("camera speed", 3.0, 0.5, 20.0, 0.5);           // units per second
+        tweaks.add_f32("camera turn speed", 50.0, 10.0, 360.0, 10.0);  // degrees per second
+
+        Ok(GuiPanelsDemo {
+            logger: logger,
+            app: app,
+            vao: vao,
+            marker_vao: marker_vao,
+            gp_tex: gp_tex,
+            gui_tex: gui_tex,
+            panel_width: panel_width,
+            panel_height: panel_height,
+            fwd: math::vec3((0.0, 0.0, -1.0)),
+            rgt: math::vec3((1.0, 0.0,  0.0)),
+            up: math::vec3((0.0, 1.0,  0.0)),
+            q: q,
+            mat_rot_inv: mat_rot_inv,
+            camera_restored: false,
+            tweaks: tweaks,
+            character: character,
+            ground: ground,
+            security_camera: security_camera,
+            minimap: minimap,
+            overlay: overlay,
+            perf_stats: PerfStats::new(PERF_STATS_HISTORY),
+            show_perf_stats: true,
+            scene_timer: GpuTimer::new(),
+            security_timer: GpuTimer::new(),
+            draw_list: DrawList::new(),
+            frame_start: Instant::now(),
+            time_text: FrameClock::new().describe(),
+        })
     }
 
-    /*-------------------------------RENDERING LOOP-------------------------------*/
-    while !context.window.should_close() {
-        // Update timers.
-        let current_seconds = context.glfw.get_time();
-        let elapsed_seconds = current_seconds - context.elapsed_time_seconds;
-        context.elapsed_time_seconds = current_seconds;
-        update_fps_counter(&mut context);
+    fn render(&mut self, context: &mut GLContext) {
+        self.frame_start = Instant::now();
+        let app = &self.app;
+        let (vao, gp_tex) = (self.vao, self.gp_tex);
+
+        // render the scene from the security camera first, so its picture is ready
+        // for the panel. the ground plane takes the camera's matrices as uniforms,
+        // and the main view sets its own back below.
+        {
+            let draw_list = &mut self.draw_list;
+            let perf_stats = &mut self.perf_stats;
+            self.security_timer.begin();
+            self.security_camera.render(|view, proj| {
+                draw_list.clear();
+                draw_list.push(
+                    DrawCommand::new(app.gp_sp, vao, 6)
+                        .with_texture(gp_tex)
+                        .with_uniform(Uniform::Mat4(app.gp_view_mat_loc, *view))
+                        .with_uniform(Uniform::Mat4(app.gp_proj_mat_loc, *proj))
+                );
+                perf_stats.count_draw(2);
+                draw_list.execute();
+            });
+            self.security_timer.end();
+
+            // and the minimap, looking down on the floor with an orthographic projection
+            self.minimap.render(|view, proj| {
+                draw_list.clear();
+                draw_list.push(
+                    DrawCommand::new(app.gp_sp, vao, 6)
+                        .with_texture(gp_tex)
+                        .with_uniform(Uniform::Mat4(app.gp_view_mat_loc, *view))
+                        .with_uniform(Uniform::Mat4(app.gp_proj_mat_loc, *proj))
+                );
+                perf_stats.count_draw(2);
+                draw_list.execute();
+            });
+        }
 
         unsafe {
+            gl::Viewport(0, 0, context.width as i32, context.height as i32);
             // wipe the drawing surface clear
             gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
-
-            // draw ground plane. note: depth test is enabled here
-            gl::Enable(gl::DEPTH_TEST);
-            gl::ActiveTexture(gl::TEXTURE0);
-            gl::BindTexture(gl::TEXTURE_2D, gp_tex);
-            gl::UseProgram(app.gp_sp);
-            gl::BindVertexArray(vao);
-            gl::DrawArrays(gl::TRIANGLES, 0, 6);
-
-            // draw GUI panel. note: depth test is disabled here and drawn AFTER scene
-            gl::Disable(gl::DEPTH_TEST);
-            gl::ActiveTexture(gl::TEXTURE0);
-            gl::BindTexture(gl::TEXTURE_2D, gui_tex);
-            gl::UseProgram(app.gui_sp);
-            // resize panel to size in pixels
-            let x_scale = panel_width / (context.width as f32);
-            let y_scale = panel_height / (context.height as f32);
-            gl::Uniform2f(app.gui_scale_loc, x_scale, y_scale);
-            gl::BindVertexArray(vao);
-            gl::DrawArrays(gl::TRIANGLES, 0, 6);
         }
 
-        context.glfw.poll_events();
+        // draw ground plane. note: depth test is enabled here
+        self.draw_list.clear();
+        self.draw_list.push(
+            DrawCommand::new(app.gp_sp, vao, 6)
+                .with_texture(gp_tex)
+                .with_uniform(Uniform::Mat4(app.gp_view_mat_loc, app.view_mat))
+                .with_uniform(Uniform::Mat4(app.gp_proj_mat_loc, app.proj_mat))
+        );
+        self.perf_stats.count_draw(2);
+
+        // draw GUI panel. note: depth test is disabled here and it goes in a later
+        // layer, so it is drawn AFTER the scene. the gui scale keeps it its size in pixels
+        self.draw_list.push(
+            DrawCommand::new(app.gui_sp, vao, 6)
+                .with_layer(1)
+                .with_depth_test(false)
+                .with_texture(self.gui_tex)
+                .with_uniform(Uniform::Vec2(app.gui_scale_loc, app.gui_scale))
+                .with_uniform(Uniform::Vec2(app.gui_offset_loc, [0.0, 0.0]))
+        );
+        self.perf_stats.count_draw(2);
+
+        // the security camera's picture goes in a second panel, in the top right
+        // corner at one texel to a pixel.
+        let feed_width = FEED_WIDTH as f32;
+        let feed_height = FEED_HEIGHT as f32;
+        let feed_offset = security_camera::corner_panel_offset(
+            feed_width, feed_height, FEED_MARGIN, context.width, context.height
+        );
+        self.draw_list.push(
+            DrawCommand::new(app.gui_sp, vao, 6)
+                .with_layer(1)
+                .with_depth_test(false)
+                .with_texture(self.security_camera.texture())
+                .with_uniform(Uniform::Vec2(
+                    app.gui_scale_loc, [feed_width / (context.width as f32), feed_height / (context.height as f32)]
+                ))
+                .with_uniform(Uniform::Vec2(app.gui_offset_loc, feed_offset))
+        );
+        self.perf_stats.count_draw(2);
+
+        // the minimap goes in the bottom right corner, which is the top right
+        // corner's offset mirrored down. the marker goes in the layer above it.
+        let map_size = MINIMAP_SIZE as f32;
+        let map_scale = [map_size / (context.width as f32), map_size / (context.height as f32)];
+        let map_offset = security_camera::corner_panel_offset(
+            map_size, map_size, FEED_MARGIN, context.width, context.height
+        );
+        let map_offset = [map_offset[0], -map_offset[1]];
+        self.draw_list.push(
+            DrawCommand::new(app.gui_sp, vao, 6)
+                .with_layer(1)
+                .with_depth_test(false)
+                .with_texture(self.minimap.texture())
+                .with_uniform(Uniform::Vec2(app.gui_scale_loc, map_scale))
+                .with_uniform(Uniform::Vec2(app.gui_offset_loc, map_offset))
+        );
+        self.perf_stats.count_draw(2);
+
+        let on_map = minimap::map_position(&self.minimap.view_proj_mat(), &self.character.position);
+        let marker_pos = [
+            map_offset[0] + (2.0 * on_map[0] - 1.0) * map_scale[0],
+            map_offset[1] + (2.0 * on_map[1] - 1.0) * map_scale[1],
+        ];
+        let marker_size = 8.0;
+        self.draw_list.push(
+            DrawCommand::new(app.marker_sp, self.marker_vao, 3)
+                .with_layer(2)
+                .with_depth_test(false)
+                .with_uniform(Uniform::Vec2(app.marker_pos_loc, marker_pos))
+                .with_uniform(Uniform::Vec2(
+                    app.marker_scale_loc, [marker_size / (context.width as f32), marker_size / (context.height as f32)]
+                ))
+                .with_uniform(Uniform::Float(app.marker_heading_loc, minimap::marker_heading(&self.fwd)))
+        );
+        self.perf_stats.count_draw(1);
+
+        let unsorted_stats = self.draw_list.state_changes();
+        self.scene_timer.begin();
+        let draw_stats = self.draw_list.execute();
+        self.scene_timer.end();
+
+        self.perf_stats.set_gpu_pass_ms("security", self.security_timer.elapsed_ms());
+        self.perf_stats.set_gpu_pass_ms("scene", self.scene_timer.elapsed_ms());
+        if self.show_perf_stats {
+            let hud_bottom = self.perf_stats.draw_hud(&mut self.overlay, 10.0, 10.0);
+            let vsync_text = format!("vsync {}", context.swap_interval);
+            self.overlay.text(16.0, hud_bottom + 6.0, 2.0, [1.0, 1.0, 1.0, 1.0], &vsync_text);
+            let mode_text = format!("{} mode", self.character.mode.name());
+            self.overlay.text(16.0, hud_bottom + 26.0, 2.0, [1.0, 1.0, 1.0, 1.0], &mode_text);
+            let state_text = format!(
+                "state changes {} sorted, {} unsorted",
+                draw_stats.state_changes(), unsorted_stats.state_changes()
+            );
+            self.overlay.text(16.0, hud_bottom + 46.0, 2.0, [1.0, 1.0, 1.0, 1.0], &state_text);
+            self.overlay.text(16.0, hud_bottom + 66.0, 2.0, [1.0, 1.0, 1.0, 1.0], &self.time_text);
+            self.overlay.draw(context.width, context.height);
+        }
+    }
 
+    fn update(&mut self, context: &mut GLContext, clock: &FrameClock, input: &Input) {
         // control keys
-        let mut cam_moved = false;
+        let mut cam_moved = self.camera_restored;
+        self.camera_restored = false;
         let mut move_to = math::vec3((0.0, 0.0, 0.0));
-        let mut cam_yaw = 0.0; // y-rotation in degrees
-        let mut cam_pitch = 0.0;
-        let mut cam_roll = 0.0;
-        match context.window.get_key(Key::A) {
-            Action::Press | Action::Repeat => {
-                move_to.v[0] -= cam_speed * (elapsed_seconds as GLfloat);
-                cam_moved = true;
-            }
-            _ => {}
-        }
-        match context.window.get_key(Key::D) {
-            Action::Press | Action::Repeat => {
-                move_to.v[0] += cam_speed * (elapsed_seconds as GLfloat);
-                cam_moved = true;
-            }
-            _ => {}
-        }
-        match context.window.get_key(Key::Q) {
-            Action::Press | Action::Repeat => {
-                move_to.v[1] += cam_speed * (elapsed_seconds as GLfloat);
-                cam_moved = true;
-            }
-            _ => {}
-        }
-        match context.window.get_key(Key::E) {
-            Action::Press | Action::Repeat => {
-                move_to.v[1] -= cam_speed * (elapsed_seconds as GLfloat);
-                cam_moved = true;
-            }
-            _ => {}
-        }
-        match context.window.get_key(Key::W) {
-            Action::Press | Action::Repeat => {
-                move_to.v[2] -= cam_speed * (elapsed_seconds as GLfloat);
-                cam_moved = true;
-            }
-            _ => {}
-        }
-        match context.window.get_key(Key::S) {
-            Action::Press | Action::Repeat => {
-                move_to.v[2] += cam_speed * (elapsed_seconds as GLfloat);
-                cam_moved = true;
-            }
-            _ => {}
+
+        // F3 toggles the HUD, V cycles vsync, and Alt+Enter switches between
+        // windowed and fullscreen. F switches between flying and walking, and
+        // Space jumps while walking. The driver saves and restores the camera.
+        if input.was_pressed(Key::F3) {
+            self.show_perf_stats = !self.show_perf_stats;
         }
-        match context.window.get_key(Key::Left) {
-            Action::Press | Action::Repeat => {
-                cam_yaw += cam_heading_speed * (elapsed_seconds as GLfloat);
-                cam_moved = true;
-                let q_yaw = Versor::from_axis_deg(cam_yaw, up.v[0], up.v[1], up.v[2]);
-                q = q_yaw * &q;
-            }
-            _ => {}
+        if input.was_pressed(Key::F) {
+            let mode = self.character.toggle_mode();
+            self.logger.log(&format!("movement mode: {}", mode.name()));
         }
-        match context.window.get_key(Key::Right) {
-            Action::Press | Action::Repeat => {
-                cam_yaw -= cam_heading_speed * (elapsed_seconds as GLfloat);
-                cam_moved = true;
-                let q_yaw = Versor::from_axis_deg(cam_yaw, up.v[0], up.v[1], up.v[2]);
-                q = q_yaw * &q;
-            }
-            _ => {}
+        if input.was_pressed(Key::Space) {
+            self.character.jump();
         }
-        match context.window.get_key(Key::Up) {
-            Action::Press | Action::Repeat => {
-                cam_pitch += cam_heading_speed * (elapsed_seconds as GLfloat);
-                cam_moved = true;
-                let q_pitch = Versor::from_axis_deg(cam_pitch, rgt.v[0], rgt.v[1], rgt.v[2]);
-                q = q_pitch * &q;
-            }
-            _ => {}
+
+        if input.was_pressed(Key::V) {
+            // cycle vsync off, on, and adaptive to compare frame times
+            let swap_interval = context.swap_interval.next();
+            let swap_interval = context.set_swap_interval(swap_interval);
+            self.logger.log(&format!("vsync: {}", swap_interval));
         }
-        match context.window.get_key(Key::Down) {
-            Action::Press | Action::Repeat => {
-                cam_pitch -= cam_heading_speed * (elapsed_seconds as GLfloat);
-                cam_moved = true;
-                let q_pitch = Versor::from_axis_deg(cam_pitch, rgt.v[0], rgt.v[1], rgt.v[2]);
-                q = q_pitch * &q;
-            }
-            _ => {}
+
+        if input.was_pressed_with(Key::Enter, Modifiers::Alt) {
+            // the camera is untouched; the driver sees the new framebuffer size on
+            // the next frame, and only the viewport and projection follow it
+            let fullscreen = context.toggle_fullscreen();
+            self.logger.log(&format!("fullscreen: {}", fullscreen));
         }
-        match context.window.get_key(Key::Z) {
-            Action::Press | Action::Repeat => {
-                cam_roll -= cam_heading_speed * (elapsed_seconds as GLfloat);
+
+        // the keys move the camera in real time, so it can still look around while
+        // time is paused, but gravity and jumps follow simulation time
+        let step = self.tweaks.f32("camera speed") * (clock.real_dt() as GLfloat);
+        let turn = self.tweaks.f32("camera turn speed") * (clock.real_dt() as GLfloat);
+        let moves = [
+            (Key::A, 0, -step), (Key::D, 0, step),
+            (Key::Q, 1, step), (Key::E, 1, -step),
+            (Key::W, 2, -step), (Key::S, 2, step),
+        ];
+        for &(key, axis, distance) in moves.iter() {
+            if input.is_held(key) {
+                move_to.v[axis] += distance;
                 cam_moved = true;
-                let q_roll = Versor::from_axis_deg(cam_roll, fwd.v[0], fwd.v[1], fwd.v[2]);
-                q = q_roll * &q;
             }
-            _ => {}
         }
-        match context.window.get_key(Key::C) {
-            Action::Press | Action::Repeat => {
-                cam_roll += cam_heading_speed * (elapsed_seconds as GLfloat);
+
+        // yaw about the up axis, pitch about the right axis, and roll about the
+        // forward axis, each from wherever the camera points now.
+        let turns = [
+            (Key::Left, turn, self.up), (Key::Right, -turn, self.up),
+            (Key::Up, turn, self.rgt), (Key::Down, -turn, self.rgt),
+            (Key::Z, -turn, self.fwd), (Key::C, turn, self.fwd),
+        ];
+        for &(key, degrees, axis) in turns.iter() {
+            if input.is_held(key) {
+                let q_turn = Versor::from_axis_deg(degrees, axis.v[0], axis.v[1], axis.v[2]);
+                self.q = q_turn * self.q;
                 cam_moved = true;
-                let q_roll = Versor::from_axis_deg(cam_roll, fwd.v[0], fwd.v[1], fwd.v[2]);
-                q = q_roll * &q;        
             }
-            _ => {}
         }
 
         // update view matrix
         if cam_moved {
             // re-calculate local axes so can move fwd in dir cam is pointing
-            mat_rot_inv = q.to_mat4();
-            fwd = mat_rot_inv * math::vec4((0.0, 0.0, -1.0, 0.0));
-            rgt = mat_rot_inv * math::vec4((1.0, 0.0,  0.0, 0.0));
-            up  = mat_rot_inv * math::vec4((0.0, 1.0,  0.0, 0.0));
-
-            cam_pos = cam_pos + math::vec3(fwd) * -move_to.v[2];
-            cam_pos = cam_pos + math::vec3(up)  *  move_to.v[1];
-            cam_pos = cam_pos + math::vec3(rgt) *  move_to.v[0];
-            mat_trans_inv = Mat4::identity().translate(&cam_pos);
-
-            view = mat_rot_inv.inverse() * mat_trans_inv.inverse();
-            app.view_mat = view;
+            self.mat_rot_inv = self.q.to_mat4();
+            self.fwd = self.q.rotate_vec3(&math::vec3((0.0, 0.0, -1.0)));
+            self.rgt = self.q.rotate_vec3(&math::vec3((1.0, 0.0,  0.0)));
+            self.up  = self.q.rotate_vec3(&math::vec3((0.0, 1.0,  0.0)));
+        }
+
+        // a walking character keeps moving under gravity with no keys held down
+        if self.character.step(&self.fwd, &self.rgt, &self.up, &move_to, clock.dt() as GLfloat, &self.ground) {
+            cam_moved = true;
+        }
+
+        if cam_moved {
+            let mat_trans_inv = Mat4::from_translation(&self.character.position);
+
+            let view = self.mat_rot_inv.inverse() * mat_trans_inv.inverse();
+            self.app.view_mat = view;
             unsafe {
-                gl::UseProgram(app.gp_sp);
-                gl::UniformMatrix4fv(app.gp_view_mat_loc, 1, gl::FALSE, view.as_ptr());
+                gl::UseProgram(self.app.gp_sp);
+                gl::UniformMatrix4fv(self.app.gp_view_mat_loc, 1, gl::FALSE, view.as_ptr());
             }
         }
 
-        match context.window.get_key(Key::Escape) {
-            Action::Press | Action::Repeat => {
-                context.window.set_should_close(true);
-            }
-            _ => {}
+        if self.tweaks.take_changed() {
+            self.app.fovy = self.tweaks.f32("field of view");
+            update_proj_mat(&mut self.app, context.width, context.height);
+        }
+
+        self.time_text = clock.describe();
+        self.perf_stats.end_frame(perf_stats::duration_to_ms(self.frame_start.elapsed()));
+    }
+
+    fn resize(&mut self, _context: &mut GLContext, width: u32, height: u32) {
+        update_viewport(&mut self.app, self.panel_width, self.panel_height, width, height);
+    }
+
+    fn shutdown(&mut self, _context: &mut GLContext) {
+        let vaos = [self.vao, self.marker_vao];
+        let textures = [self.gp_tex, self.gui_tex];
+        unsafe {
+            gl::DeleteVertexArrays(vaos.len() as i32, vaos.as_ptr());
+            gl::DeleteTextures(textures.len() as i32, textures.as_ptr());
         }
-        // Put the stuff we've been drawing onto the display.
-        context.window.swap_buffers();
+    }
+
+    fn camera(&self) -> Option<Camera> {
+        Some(Camera::new(math::dvec3(self.character.position), self.q, self.app.fovy))
+    }
+
+    fn set_camera(&mut self, context: &mut GLContext, camera: &Camera) {
+        self.character.teleport(math::vec3(camera.position));
+        self.q = camera.orientation;
+        self.camera_restored = true;
+        self.app.fovy = camera.fovy;
+        self.tweaks.set_f32("field of view", camera.fovy);
+        update_proj_mat(&mut self.app, context.width, context.height);
+    }
+
+    fn bindings(&self, bindings: &mut KeyBindings) {
+        bindings.bind(Key::F3, "toggle the performance HUD");
+        bindings.bind(Key::F, "switch between flying and walking");
+        bindings.bind(Key::Space, "jump while walking");
+        bindings.bind(Key::V, "cycle vsync off, on, and adaptive");
+        bindings.bind_with(Key::Enter, Modifiers::Alt, "switch between windowed and fullscreen");
+        bindings.bind_keys(&[Key::W, Key::A, Key::S, Key::D], "move forwards, left, backwards, and right");
+        bindings.bind_keys(&[Key::Q, Key::E], "move up and down");
+        bindings.bind_keys(&[Key::Left, Key::Right], "yaw");
+        bindings.bind_keys(&[Key::Up, Key::Down], "pitch");
+        bindings.bind_keys(&[Key::Z, Key::C], "roll");
+    }
+
+    fn help_values(&self) -> Vec<(String, String)> {
+        vec![
+            (String::from("movement"), String::from(self.character.mode.name())),
+            (String::from("time"), self.time_text.clone()),
+        ]
+    }
+
+    fn draw_help(&mut self, context: &mut GLContext, lines: &[String]) {
+        help_overlay::draw_help(&mut self.overlay, lines, context.width, context.height);
+        self.overlay.draw(context.width, context.height);
+    }
+
+    fn tweaks(&mut self) -> Option<&mut Tweaks> {
+        Some(&mut self.tweaks)
+    }
+
+    fn draw_tweaks(&mut self, context: &mut GLContext, lines: &[String]) {
+        help_overlay::draw_tweaks(&mut self.overlay, lines, context.height);
+        self.overlay.draw(context.width, context.height);
+    }
+}
+
+fn main() {
+    let logger = restart_gl_log(GL_LOG_FILE);
+    // Start a GL context and O/S window using the GLFW helper library.
+    let context = match start_gl(&logger) {
+        Ok(val) => val,
+        Err(e) => {
+            eprintln!("Failed to Initialize OpenGL context. Got error:");
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+    };
+
+    if let Err(e) = run_demo::<GuiPanelsDemo>(context) {
+        logger.log_err(&e);
+        eprintln!("{}", e);
+        process::exit(1);
     }
 }
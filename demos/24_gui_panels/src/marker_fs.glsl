@@ -0,0 +1,8 @@
+#version 410
+
+out vec4 frag_colour;
+
+
+void main () {
+    frag_colour = vec4 (1.0, 0.2, 0.1, 1.0);
+}
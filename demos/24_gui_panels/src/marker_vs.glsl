@@ -0,0 +1,16 @@
+#version 410
+
+in vec2 vp;
+// where the marker goes, in clip space
+uniform vec2 marker_pos;
+uniform vec2 marker_scale;
+// anticlockwise turn in radians from pointing straight up
+uniform float marker_heading;
+
+
+void main () {
+    float c = cos (marker_heading);
+    float s = sin (marker_heading);
+    vec2 p = vec2 (c * vp.x - s * vp.y, s * vp.x + c * vp.y);
+    gl_Position = vec4 (marker_pos + p * marker_scale, 0.0, 1.0);
+}
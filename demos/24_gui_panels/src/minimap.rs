@@ -0,0 +1,167 @@
+use gl;
+use gl::types::GLuint;
+
+use graphics_math as math;
+use math::{Mat4, Vec3, Versor};
+
+use render_target::RenderTarget;
+use security_camera::fixed_view_mat;
+
+
+///
+/// Size of the minimap's picture, in pixels. It is square, and the GUI panel
+/// shows it at this size.
+///
+pub const MINIMAP_SIZE: u32 = 160;
+
+///
+/// How far the minimap reaches from the middle of the floor along each axis, in
+/// world units. The ground plane is 20 units across.
+///
+pub const MINIMAP_EXTENT: f32 = 10.0;
+
+///
+/// Height the minimap looks down from. Anything higher is left off the map.
+///
+const MINIMAP_HEIGHT: f32 = 10.0;
+
+
+///
+/// An orthographic projection of the box from `left` to `right`, `bottom` to
+/// `top`, and `near` to `far` in front of the viewer. Parallel lines stay
+/// parallel, so the floor keeps its proportions everywhere on the map.
+///
+pub fn ortho_mat(left: f32, right: f32, bottom: f32, top: f32, near: f32, far: f32) -> Mat4 {
+    Mat4::new(
+        2.0 / (right - left), 0.0, 0.0, 0.0,
+        0.0, 2.0 / (top - bottom), 0.0, 0.0,
+        0.0, 0.0, -2.0 / (far - near), 0.0,
+        -(right + left) / (right - left), -(top + bottom) / (top - bottom), -(far + near) / (far - near), 1.0
+    )
+}
+
+///
+/// The view from straight above the middle of the floor, with -z at the top of
+/// the map and +x to the right.
+///
+pub fn minimap_view_mat() -> Mat4 {
+    let position = math::vec3((0.0, MINIMAP_HEIGHT, 0.0));
+    let orientation = Versor::from_axis_deg(-90.0, 1.0, 0.0, 0.0);
+
+    fixed_view_mat(&position, &orientation)
+}
+
+///
+/// The projection that fits the square `extent` units either side of the middle
+/// of the floor onto the whole map.
+///
+pub fn minimap_proj_mat(extent: f32) -> Mat4 {
+    ortho_mat(-extent, extent, -extent, extent, 0.1, 2.0 * MINIMAP_HEIGHT)
+}
+
+///
+/// Where `position` is on a map drawn with `view_proj`, from 0 to 1 across and up.
+/// Anything off the map is kept on its edge, so the marker never goes missing.
+///
+pub fn map_position(view_proj: &Mat4, position: &Vec3) -> [f32; 2] {
+    let clip = *view_proj * math::vec4((*position, 1.0));
+    let u = f32::min(f32::max(0.5 * clip.v[0] + 0.5, 0.0), 1.0);
+    let v = f32::min(f32::max(0.5 * clip.v[1] + 0.5, 0.0), 1.0);
+
+    [u, v]
+}
+
+///
+/// The angle in radians to turn an arrow pointing up the map, anticlockwise, so
+/// it points the way `forward` faces. Looking up or down only changes the length
+/// of the arrow's shadow on the floor, not its heading.
+///
+pub fn marker_heading(forward: &Vec3) -> f32 {
+    f32::atan2(-forward.v[0], -forward.v[2])
+}
+
+///
+/// A small map of the floor seen from straight above, rendered with an
+/// orthographic projection into a texture every frame, for a GUI panel to show
+/// with a marker for the camera on top.
+///
+pub struct Minimap {
+    pub extent: f32,
+    target: RenderTarget,
+}
+
+impl Minimap {
+    ///
+    /// Make the minimap's render target. Returns an error if the driver cannot
+    /// render to it.
+    ///
+    pub fn new(extent: f32) -> Result<Minimap, String> {
+        let target = match RenderTarget::new(MINIMAP_SIZE, MINIMAP_SIZE) {
+            Ok(val) => val,
+            Err(e) => return Err(e),
+        };
+
+        Ok(Minimap {
+            extent: extent,
+            target: target,
+        })
+    }
+
+    pub fn view_proj_mat(&self) -> Mat4 {
+        minimap_proj_mat(self.extent) * minimap_view_mat()
+    }
+
+    ///
+    /// Render the map into its texture. `draw` is called once, with the view and
+    /// projection matrices looking down on the floor, to draw the scene. The
+    /// caller has to restore the viewport afterwards.
+    ///
+    pub fn render<F>(&self, mut draw: F) where F: FnMut(&Mat4, &Mat4) {
+        self.target.bind();
+        unsafe {
+            gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
+        }
+        draw(&minimap_view_mat(), &minimap_proj_mat(self.extent));
+        self.target.unbind();
+    }
+
+    pub fn texture(&self) -> GLuint {
+        self.target.colour_texture()
+    }
+}
+
+mod minimap_tests {
+    use super::{map_position, marker_heading, minimap_proj_mat, minimap_view_mat};
+    use graphics_math as math;
+
+    #[test]
+    fn test_the_map_shows_the_floor_with_negative_z_at_the_top() {
+        let view_proj = minimap_proj_mat(10.0) * minimap_view_mat();
+
+        let middle = map_position(&view_proj, &math::vec3((0.0, -1.0, 0.0)));
+        let top_right = map_position(&view_proj, &math::vec3((10.0, -1.0, -10.0)));
+        let bottom_left = map_position(&view_proj, &math::vec3((-5.0, -1.0, 5.0)));
+        assert!(f32::abs(middle[0] - 0.5) < 1e-5 && f32::abs(middle[1] - 0.5) < 1e-5);
+        assert!(f32::abs(top_right[0] - 1.0) < 1e-5 && f32::abs(top_right[1] - 1.0) < 1e-5);
+        assert!(f32::abs(bottom_left[0] - 0.25) < 1e-5 && f32::abs(bottom_left[1] - 0.25) < 1e-5);
+    }
+
+    #[test]
+    fn test_positions_off_the_map_stay_on_its_edge() {
+        let view_proj = minimap_proj_mat(10.0) * minimap_view_mat();
+        let position = map_position(&view_proj, &math::vec3((40.0, 0.0, 3.0)));
+
+        assert_eq!(position[0], 1.0);
+        assert!(position[1] > 0.0 && position[1] < 1.0);
+    }
+
+    #[test]
+    fn test_the_marker_points_the_way_the_camera_faces() {
+        let up_the_map = marker_heading(&math::vec3((0.0, 0.0, -1.0)));
+        let right = marker_heading(&math::vec3((1.0, -0.5, 0.0)));
+
+        assert!(f32::abs(up_the_map) < 1e-5);
+        // a quarter turn clockwise
+        assert!(f32::abs(right + 0.5 * ::std::f32::consts::PI) < 1e-5);
+    }
+}
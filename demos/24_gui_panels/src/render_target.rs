@@ -0,0 +1,222 @@
+use gl;
+use gl::types::{GLenum, GLint, GLsizei, GLuint};
+
+use std::ptr;
+
+
+///
+/// The formats a render target's colour attachments can have.
+///
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum TargetFormat {
+    ///
+    /// Eight bits a channel, for colours.
+    ///
+    Rgba8,
+    ///
+    /// Half floats, for values outside 0 to 1 such as normals.
+    ///
+    Rgba16F,
+    ///
+    /// A single half float channel, for counting with additive blending.
+    ///
+    R16F,
+}
+
+impl TargetFormat {
+    ///
+    /// The internal format, format, and type to allocate the texture with.
+    ///
+    fn gl_formats(&self) -> (GLenum, GLenum, GLenum) {
+        match *self {
+            TargetFormat::Rgba8 => (gl::RGBA8, gl::RGBA, gl::UNSIGNED_BYTE),
+            TargetFormat::Rgba16F => (gl::RGBA16F, gl::RGBA, gl::FLOAT),
+            TargetFormat::R16F => (gl::R16F, gl::RED, gl::FLOAT),
+        }
+    }
+}
+
+///
+/// Make a texture for a render target to draw into, with no mipmaps. Texels are
+/// read back one for one by the passes that follow, so there is no filtering.
+///
+fn create_target_texture() -> GLuint {
+    let mut tex = 0;
+    unsafe {
+        gl::GenTextures(1, &mut tex);
+        gl::BindTexture(gl::TEXTURE_2D, tex);
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::NEAREST as GLint);
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::NEAREST as GLint);
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as GLint);
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as GLint);
+    }
+    assert!(tex > 0);
+
+    tex
+}
+
+///
+/// An off-screen framebuffer the scene is rendered into instead of the window, so
+/// that post-processing passes can read what was drawn. The colours and the depth
+/// buffer are all textures, so any of them can be sampled afterwards. A render
+/// target can have several colour attachments, which a fragment shader writes to
+/// all at once through outputs at locations 0, 1, and so on.
+///
+pub struct RenderTarget {
+    fbo: GLuint,
+    colour_texs: Vec<(GLuint, TargetFormat)>,
+    depth_tex: GLuint,
+    width: u32,
+    height: u32,
+}
+
+impl RenderTarget {
+    ///
+    /// Make a render target `width` by `height` pixels in size, with one colour
+    /// attachment. Returns an error if the driver cannot render to it.
+    ///
+    pub fn new(width: u32, height: u32) -> Result<RenderTarget, String> {
+        RenderTarget::with_formats(width, height, &[TargetFormat::Rgba8])
+    }
+
+    ///
+    /// Make a render target with a colour attachment of each format in `formats`,
+    /// in order.
+    ///
+    pub fn with_formats(width: u32, height: u32, formats: &[TargetFormat]) -> Result<RenderTarget, String> {
+        assert!(!formats.is_empty());
+        let mut fbo = 0;
+        unsafe {
+            gl::GenFramebuffers(1, &mut fbo);
+        }
+
+        let mut target = RenderTarget {
+            fbo: fbo,
+            colour_texs: formats.iter().map(|&format| (create_target_texture(), format)).collect(),
+            depth_tex: create_target_texture(),
+            width: 0,
+            height: 0,
+        };
+        match target.resize(width, height) {
+            Ok(_) => Ok(target),
+            Err(e) => Err(e),
+        }
+    }
+
+    ///
+    /// Make the textures a new size, after the window changes size. What was
+    /// drawn into them is lost.
+    ///
+    pub fn resize(&mut self, width: u32, height: u32) -> Result<(), String> {
+        // GL cannot make a texture with no texels, as a minimised window has.
+        let width = u32::max(width, 1);
+        let height = u32::max(height, 1);
+        let mut draw_buffers = vec![];
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, self.fbo);
+            for (i, &(tex, format)) in self.colour_texs.iter().enumerate() {
+                let (internal_format, pixel_format, pixel_type) = format.gl_formats();
+                gl::BindTexture(gl::TEXTURE_2D, tex);
+                gl_check!(gl::TexImage2D(
+                    gl::TEXTURE_2D, 0, internal_format as GLint, width as GLsizei, height as GLsizei, 0,
+                    pixel_format, pixel_type, ptr::null()
+                ));
+                let attachment = gl::COLOR_ATTACHMENT0 + i as GLenum;
+                gl_check!(gl::FramebufferTexture2D(gl::FRAMEBUFFER, attachment, gl::TEXTURE_2D, tex, 0));
+                draw_buffers.push(attachment);
+            }
+            gl::BindTexture(gl::TEXTURE_2D, self.depth_tex);
+            gl_check!(gl::TexImage2D(
+                gl::TEXTURE_2D, 0, gl::DEPTH_COMPONENT24 as GLint, width as GLsizei, height as GLsizei, 0,
+                gl::DEPTH_COMPONENT, gl::FLOAT, ptr::null()
+            ));
+            gl_check!(gl::FramebufferTexture2D(gl::FRAMEBUFFER, gl::DEPTH_ATTACHMENT, gl::TEXTURE_2D, self.depth_tex, 0));
+            gl_check!(gl::DrawBuffers(draw_buffers.len() as GLsizei, draw_buffers.as_ptr()));
+        }
+        let status = unsafe { gl::CheckFramebufferStatus(gl::FRAMEBUFFER) };
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+        }
+        self.width = width;
+        self.height = height;
+
+        if status != gl::FRAMEBUFFER_COMPLETE {
+            return Err(format!("ERROR: render target is incomplete. Status: 0x{:x}", status));
+        }
+
+        Ok(())
+    }
+
+    ///
+    /// The texture of the first colour attachment, which the scene's colours are
+    /// rendered into.
+    ///
+    pub fn colour_texture(&self) -> GLuint {
+        self.colour_texs[0].0
+    }
+
+    ///
+    /// The texture of colour attachment `i`, written by the fragment shader's
+    /// output at location `i`.
+    ///
+    pub fn attachment(&self, i: usize) -> GLuint {
+        self.colour_texs[i].0
+    }
+
+    ///
+    /// The texture holding the depth buffer, with the window space depth of each
+    /// pixel from 0 at the near clipping plane to 1 at the far one.
+    ///
+    pub fn depth_texture(&self) -> GLuint {
+        self.depth_tex
+    }
+
+    pub fn size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    ///
+    /// Clear colour attachment `i` alone to `colour`, for attachments that need a
+    /// different clear colour from the rest. The target has to be bound.
+    ///
+    pub fn clear_attachment(&self, i: usize, colour: [f32; 4]) {
+        assert!(i < self.colour_texs.len());
+        unsafe {
+            gl::ClearBufferfv(gl::COLOR, i as GLint, colour.as_ptr());
+        }
+    }
+
+    ///
+    /// Bind the framebuffer and set the viewport to cover it. Draw calls after this
+    /// render into the target's textures.
+    ///
+    pub fn bind(&self) {
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, self.fbo);
+            gl::Viewport(0, 0, self.width as GLsizei, self.height as GLsizei);
+        }
+    }
+
+    ///
+    /// Go back to drawing to the window. The caller has to restore the viewport.
+    ///
+    pub fn unbind(&self) {
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+        }
+    }
+}
+
+impl Drop for RenderTarget {
+    fn drop(&mut self) {
+        if gl::DeleteFramebuffers::is_loaded() {
+            unsafe {
+                gl::DeleteFramebuffers(1, &self.fbo);
+                for &(tex, _) in self.colour_texs.iter() {
+                    gl::DeleteTextures(1, &tex);
+                }
+                gl::DeleteTextures(1, &self.depth_tex);
+            }
+        }
+    }
+}
@@ -0,0 +1,150 @@
+use gl;
+use gl::types::GLuint;
+
+use graphics_math::{Mat4, Vec3, Versor};
+
+use render_target::RenderTarget;
+
+
+///
+/// Size of the security camera's picture, in pixels. The GUI panel shows it at
+/// this size, one texel to a pixel.
+///
+pub const FEED_WIDTH: u32 = 256;
+pub const FEED_HEIGHT: u32 = 192;
+
+///
+/// Gap between the feed's panel and the edges of the window, in pixels.
+///
+pub const FEED_MARGIN: f32 = 16.0;
+
+
+///
+/// The offset in clip space that moves a GUI panel `panel_width` by `panel_height`
+/// pixels from the middle of the window into its top right corner, `margin` pixels
+/// in from both edges.
+///
+pub fn corner_panel_offset(
+    panel_width: f32, panel_height: f32, margin: f32, width: u32, height: u32) -> [f32; 2] {
+
+    let x = 1.0 - (panel_width + 2.0 * margin) / (width as f32);
+    let y = 1.0 - (panel_height + 2.0 * margin) / (height as f32);
+
+    [x, y]
+}
+
+///
+/// The view matrix of a camera at `position` turned by `orientation`, made the
+/// same way as the main camera's.
+///
+pub fn fixed_view_mat(position: &Vec3, orientation: &Versor) -> Mat4 {
+    let mat_rot_inv = orientation.to_mat4();
+    let mat_trans_inv = Mat4::from_translation(position);
+
+    mat_rot_inv.inverse() * mat_trans_inv.inverse()
+}
+
+///
+/// A camera bolted to one spot, looking at the scene from a fixed heading and
+/// angle. Every frame it renders the scene into a texture of its own, which a GUI
+/// panel then shows like a monitor.
+///
+pub struct SecurityCamera {
+    pub position: Vec3,
+    pub orientation: Versor,
+    pub fovy: f32,
+    target: RenderTarget,
+}
+
+impl SecurityCamera {
+    ///
+    /// Put a camera at `position`, turned `yaw` degrees about the vertical and
+    /// tilted `pitch` degrees up. Returns an error if the driver cannot render to
+    /// its texture.
+    ///
+    pub fn new(position: Vec3, yaw: f32, pitch: f32, fovy: f32) -> Result<SecurityCamera, String> {
+        let target = match RenderTarget::new(FEED_WIDTH, FEED_HEIGHT) {
+            Ok(val) => val,
+            Err(e) => return Err(e),
+        };
+        let q_yaw = Versor::from_axis_deg(yaw, 0.0, 1.0, 0.0);
+        let q_pitch = Versor::from_axis_deg(pitch, 1.0, 0.0, 0.0);
+
+        Ok(SecurityCamera {
+            position: position,
+            orientation: q_yaw * q_pitch,
+            fovy: fovy,
+            target: target,
+        })
+    }
+
+    pub fn view_mat(&self) -> Mat4 {
+        fixed_view_mat(&self.position, &self.orientation)
+    }
+
+    ///
+    /// The projection for the feed's own aspect ratio, whatever shape the window is.
+    ///
+    pub fn proj_mat(&self) -> Mat4 {
+        Mat4::perspective(self.fovy, FEED_WIDTH as f32 / FEED_HEIGHT as f32, 0.1, 100.0)
+    }
+
+    ///
+    /// Render what the camera sees into its texture. `draw` is called once, with
+    /// the camera's view and projection matrices, to draw the scene. The caller has
+    /// to restore the viewport afterwards.
+    ///
+    pub fn render<F>(&self, mut draw: F) where F: FnMut(&Mat4, &Mat4) {
+        self.target.bind();
+        unsafe {
+            gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
+        }
+        draw(&self.view_mat(), &self.proj_mat());
+        self.target.unbind();
+    }
+
+    ///
+    /// The camera's latest picture, for a GUI panel to show.
+    ///
+    pub fn texture(&self) -> GLuint {
+        self.target.colour_texture()
+    }
+}
+
+mod security_camera_tests {
+    use super::{corner_panel_offset, fixed_view_mat};
+    use graphics_math as math;
+    use math::Versor;
+
+    #[test]
+    fn test_a_corner_panel_stops_short_of_the_edges_by_the_margin() {
+        // a 200 by 100 pixel panel in an 800 by 600 window
+        let offset = corner_panel_offset(200.0, 100.0, 10.0, 800, 600);
+        let x_scale = 200.0 / 800.0;
+        let y_scale = 100.0 / 600.0;
+
+        // the panel's right and top edges, in pixels from the window's corner
+        let right_gap = (1.0 - (offset[0] + x_scale)) * 0.5 * 800.0;
+        let top_gap = (1.0 - (offset[1] + y_scale)) * 0.5 * 600.0;
+        assert!(f32::abs(right_gap - 10.0) < 1e-3);
+        assert!(f32::abs(top_gap - 10.0) < 1e-3);
+    }
+
+    #[test]
+    fn test_the_view_puts_the_camera_at_the_origin_looking_down_negative_z() {
+        let position = math::vec3((4.0, 3.0, 4.0));
+        let q_yaw = Versor::from_axis_deg(45.0, 0.0, 1.0, 0.0);
+        let q_pitch = Versor::from_axis_deg(-30.0, 1.0, 0.0, 0.0);
+        let orientation = q_yaw * q_pitch;
+        let view = fixed_view_mat(&position, &orientation);
+
+        let eye = view * math::vec4((position, 1.0));
+        let ahead = orientation.rotate_vec3(&math::vec3((0.0, 0.0, -1.0)));
+        let seen = view * math::vec4((position + ahead, 1.0));
+        for i in 0..3 {
+            assert!(f32::abs(eye.v[i]) < 1e-5);
+        }
+        assert!(f32::abs(seen.v[0]) < 1e-5 && f32::abs(seen.v[1]) < 1e-5);
+        assert!(f32::abs(seen.v[2] + 1.0) < 1e-5);
+    }
+}
@@ -4,24 +4,30 @@ extern crate chrono;
 extern crate stb_image;
 extern crate png;
 extern crate assimp;
+#[cfg(feature = "glam")]
+extern crate glam;
+#[cfg(feature = "cgmath")]
+extern crate cgmath;
 
-#[macro_use] 
-extern crate scan_fmt;
-
+#[macro_use]
 mod gl_utils;
 mod graphics_math;
 mod obj_parser;
+mod colour;
+mod assets;
+mod shader_include;
+mod spirv;
+mod program_cache;
 mod logger;
+mod vertex_layout;
 
 
 use glfw::{Action, Context, Key};
-use gl::types::{GLchar, GLfloat, GLint, GLsizeiptr, GLvoid, GLuint};
+use gl::types::{GLchar, GLfloat, GLint, GLvoid, GLuint};
 
-use std::mem;
 use std::ptr;
 use std::process;
 
-use stb_image::image;
 use stb_image::image::LoadResult;
 
 use gl_utils::*;
@@ -29,6 +35,7 @@ use gl_utils::*;
 use graphics_math as math;
 use math::{Mat4, Versor};
 use logger::Logger;
+use vertex_layout::VertexLayout;
 
 
 const GL_LOG_FILE: &str = "gl.log";
@@ -75,15 +82,21 @@ fn create_ground_plane_shaders(logger: &Logger, app: &mut AppState) {
     // Here I used negative y from the buffer as the z value so that it was on
     // the floor but also that the 'front' was on the top side. also note how I
     // work out the texture coordinates, st, from the vertex point position.
-    let mut gp_vs_str = vec![0; 1024];
-    let mut gp_fs_str = vec![0; 1024];
-    if !parse_file_into_str(logger, GP_VS_FILE, &mut gp_vs_str, 1024) {
-        panic!("Failed to parse ground plane vertex shader file.");
-    }
+    let gp_vs_str = match read_shader_source(GP_VS_FILE) {
+        Ok(val) => val,
+        Err(e) => {
+            logger.log_err(&e);
+            panic!("Failed to parse ground plane vertex shader file.");
+        }
+    };
 
-    if !parse_file_into_str(logger, GP_FS_FILE, &mut gp_fs_str, 1024) {
-        panic!("Failed to parse ground plane fragment shader file.");
-    }
+    let gp_fs_str = match read_shader_source(GP_FS_FILE) {
+        Ok(val) => val,
+        Err(e) => {
+            logger.log_err(&e);
+            panic!("Failed to parse ground plane fragment shader file.");
+        }
+    };
     
     unsafe {
         let gp_vs = gl::CreateShader(gl::VERTEX_SHADER);
@@ -103,10 +116,10 @@ fn create_ground_plane_shaders(logger: &Logger, app: &mut AppState) {
         assert!(gp_sp > 0);
 
         // Get uniform locations of camera view and projection matrices.
-        let gp_view_mat_loc = gl::GetUniformLocation(gp_sp, "view".as_ptr() as *const i8);
+        let gp_view_mat_loc = uniform_location(gp_sp, "view");
         assert!(gp_view_mat_loc > -1);
 
-        let gp_proj_mat_loc = gl::GetUniformLocation(gp_sp, "proj".as_ptr() as *const i8);
+        let gp_proj_mat_loc = uniform_location(gp_sp, "proj");
         assert!(gp_proj_mat_loc > -1);
 
         // Set defaults for matrices
@@ -122,15 +135,21 @@ fn create_ground_plane_shaders(logger: &Logger, app: &mut AppState) {
 
 fn create_gui_shaders(logger: &Logger, app: &mut AppState) {
     // Note that I scaled down the size to 0.5 * the viewport size here.
-    let mut gui_vs_str = vec![0; 1024];
-    let mut gui_fs_str = vec![0; 1024];
-    if !parse_file_into_str(logger, GUI_VS_FILE, &mut gui_vs_str, 1024) {
-        panic!("Failed to parse gui vertex shader file.");
-    }
+    let gui_vs_str = match read_shader_source(GUI_VS_FILE) {
+        Ok(val) => val,
+        Err(e) => {
+            logger.log_err(&e);
+            panic!("Failed to parse gui vertex shader file.");
+        }
+    };
 
-    if !parse_file_into_str(logger, GUI_FS_FILE, &mut gui_fs_str, 1024) {
-        panic!("Failed to parse gui fragment shader file.");
-    }   
+    let gui_fs_str = match read_shader_source(GUI_FS_FILE) {
+        Ok(val) => val,
+        Err(e) => {
+            logger.log_err(&e);
+            panic!("Failed to parse gui fragment shader file.");
+        }
+    };
 
     unsafe {
         let gui_vs = gl::CreateShader(gl::VERTEX_SHADER);
@@ -148,7 +167,7 @@ fn create_gui_shaders(logger: &Logger, app: &mut AppState) {
         gl::AttachShader(gui_sp, gui_fs);
         gl::LinkProgram(gui_sp);
         assert!(gui_sp > 0);
-        let gui_scale_loc = gl::GetUniformLocation(gui_sp, "gui_scale".as_ptr() as *const i8);
+        let gui_scale_loc = uniform_location(gui_sp, "gui_scale");
         assert!(gui_scale_loc > -1);
 
         app.gui_sp = gui_sp;
@@ -158,7 +177,7 @@ fn create_gui_shaders(logger: &Logger, app: &mut AppState) {
 
 fn load_texture(file_name: &str, tex: &mut GLuint) -> bool {
     let force_channels = 4;
-    let mut image_data = match image::load_with_depth(file_name, force_channels, false) {
+    let mut image_data = match assets::load_image_with_depth(file_name, force_channels) {
         LoadResult::ImageU8(image_data) => image_data,
         LoadResult::Error(_) => {
             eprintln!("ERROR: could not load {}", file_name);
@@ -246,34 +265,20 @@ fn main() {
     ];
 
     // for the ground plane we can just re-use panel points but y is now z
-    let mut vp_vbo = 0;
-    unsafe {
-        gl::GenBuffers(1, &mut vp_vbo);
-        gl::BindBuffer(gl::ARRAY_BUFFER, vp_vbo);
-        gl::BufferData(
-            gl::ARRAY_BUFFER, (points.len() * mem::size_of::<GLfloat>()) as GLsizeiptr, 
-            points.as_ptr() as *const GLvoid, gl::STATIC_DRAW
-        );
-    }
-    assert!(vp_vbo > 0);
-
     let mut vao = 0;
     unsafe {
         gl::GenVertexArrays(1, &mut vao);
-        gl::BindVertexArray(vao);
-        // NOTE: vertex buffer is already bound
-        gl::VertexAttribPointer(0, 2, gl::FLOAT, gl::FALSE, 0, ptr::null());
-        gl::EnableVertexAttribArray(0);
     }
     assert!(vao > 0);
+    VertexLayout::new().floats(0, 2).upload(vao, &points, 6);
 
     // create a 3d camera to move in 3d so that we can tell that the panel is 2d
     // keep track of some useful vectors that can be used for keyboard movement
-    let mut fwd = math::vec4((0.0, 0.0, -1.0, 0.0));
-    let mut rgt = math::vec4((1.0, 0.0,  0.0, 0.0));
-    let mut up  = math::vec4((0.0, 1.0,  0.0, 0.0));
+    let mut fwd = math::vec3((0.0, 0.0, -1.0));
+    let mut rgt = math::vec3((1.0, 0.0,  0.0));
+    let mut up  = math::vec3((0.0, 1.0,  0.0));
     let mut cam_pos = math::vec3((0.0, 1.0, 5.0));
-    let mut mat_trans_inv = Mat4::identity().translate(&cam_pos);
+    let mut mat_trans_inv = Mat4::from_translation(&cam_pos);
 
     // point slightly downwards to see the plane
     let mut q = Versor::from_axis_deg(0.0, 1.0, 0.0, 0.0);
@@ -402,7 +407,7 @@ fn main() {
                 cam_yaw += cam_heading_speed * (elapsed_seconds as GLfloat);
                 cam_moved = true;
                 let q_yaw = Versor::from_axis_deg(cam_yaw, up.v[0], up.v[1], up.v[2]);
-                q = q_yaw * &q;
+                q = q_yaw * q;
             }
             _ => {}
         }
@@ -411,7 +416,7 @@ fn main() {
                 cam_yaw -= cam_heading_speed * (elapsed_seconds as GLfloat);
                 cam_moved = true;
                 let q_yaw = Versor::from_axis_deg(cam_yaw, up.v[0], up.v[1], up.v[2]);
-                q = q_yaw * &q;
+                q = q_yaw * q;
             }
             _ => {}
         }
@@ -420,7 +425,7 @@ fn main() {
                 cam_pitch += cam_heading_speed * (elapsed_seconds as GLfloat);
                 cam_moved = true;
                 let q_pitch = Versor::from_axis_deg(cam_pitch, rgt.v[0], rgt.v[1], rgt.v[2]);
-                q = q_pitch * &q;
+                q = q_pitch * q;
             }
             _ => {}
         }
@@ -429,7 +434,7 @@ fn main() {
                 cam_pitch -= cam_heading_speed * (elapsed_seconds as GLfloat);
                 cam_moved = true;
                 let q_pitch = Versor::from_axis_deg(cam_pitch, rgt.v[0], rgt.v[1], rgt.v[2]);
-                q = q_pitch * &q;
+                q = q_pitch * q;
             }
             _ => {}
         }
@@ -438,7 +443,7 @@ fn main() {
                 cam_roll -= cam_heading_speed * (elapsed_seconds as GLfloat);
                 cam_moved = true;
                 let q_roll = Versor::from_axis_deg(cam_roll, fwd.v[0], fwd.v[1], fwd.v[2]);
-                q = q_roll * &q;
+                q = q_roll * q;
             }
             _ => {}
         }
@@ -447,7 +452,7 @@ fn main() {
                 cam_roll += cam_heading_speed * (elapsed_seconds as GLfloat);
                 cam_moved = true;
                 let q_roll = Versor::from_axis_deg(cam_roll, fwd.v[0], fwd.v[1], fwd.v[2]);
-                q = q_roll * &q;        
+                q = q_roll * q;        
             }
             _ => {}
         }
@@ -456,14 +461,14 @@ fn main() {
         if cam_moved {
             // re-calculate local axes so can move fwd in dir cam is pointing
             mat_rot_inv = q.to_mat4();
-            fwd = mat_rot_inv * math::vec4((0.0, 0.0, -1.0, 0.0));
-            rgt = mat_rot_inv * math::vec4((1.0, 0.0,  0.0, 0.0));
-            up  = mat_rot_inv * math::vec4((0.0, 1.0,  0.0, 0.0));
-
-            cam_pos = cam_pos + math::vec3(fwd) * -move_to.v[2];
-            cam_pos = cam_pos + math::vec3(up)  *  move_to.v[1];
-            cam_pos = cam_pos + math::vec3(rgt) *  move_to.v[0];
-            mat_trans_inv = Mat4::identity().translate(&cam_pos);
+            fwd = q.rotate_vec3(&math::vec3((0.0, 0.0, -1.0)));
+            rgt = q.rotate_vec3(&math::vec3((1.0, 0.0,  0.0)));
+            up  = q.rotate_vec3(&math::vec3((0.0, 1.0,  0.0)));
+
+            cam_pos = cam_pos + fwd * -move_to.v[2];
+            cam_pos = cam_pos + up  *  move_to.v[1];
+            cam_pos = cam_pos + rgt *  move_to.v[0];
+            mat_trans_inv = Mat4::from_translation(&cam_pos);
 
             view = mat_rot_inv.inverse() * mat_trans_inv.inverse();
             app.view_mat = view;
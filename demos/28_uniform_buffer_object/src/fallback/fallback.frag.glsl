@@ -0,0 +1,13 @@
+#version 410
+
+// Built-in fallback fragment shader. Paints geometry in a hot pink checker
+// pattern so that missing shaders are obvious on screen.
+in vec3 position_local;
+
+out vec4 frag_colour;
+
+void main() {
+	vec3 cell = floor (position_local * 4.0);
+	float checker = mod (cell.x + cell.y + cell.z, 2.0);
+	frag_colour = mix (vec4 (1.0, 0.0, 1.0, 1.0), vec4 (0.5, 0.0, 0.5, 1.0), checker);
+}
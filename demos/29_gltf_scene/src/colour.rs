@@ -0,0 +1,205 @@
+use std::convert::From;
+
+
+///
+/// Decode an sRGB encoded value, from 0 to 1, to linear light. The same curve the
+/// GPU applies when it reads an sRGB texture.
+///
+pub fn srgb_to_linear(value: f32) -> f32 {
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        f32::powf((value + 0.055) / 1.055, 2.4)
+    }
+}
+
+///
+/// Encode linear light as sRGB, the curve the GPU applies when it writes to an
+/// sRGB framebuffer with `GL_FRAMEBUFFER_SRGB` enabled.
+///
+pub fn linear_to_srgb(value: f32) -> f32 {
+    if value <= 0.0031308 {
+        value * 12.92
+    } else {
+        1.055 * f32::powf(value, 1.0 / 2.4) - 0.055
+    }
+}
+
+///
+/// A colour with red, green, blue, and alpha channels from 0 to 1. Lighting and
+/// blending only add up right in linear light, but colours picked by eye, as from
+/// a paint program or a web page, are usually sRGB encoded. `to_linear` and
+/// `to_srgb` convert between the two; alpha is never encoded.
+///
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Colour {
+    pub r: f32,
+    pub g: f32,
+    pub b: f32,
+    pub a: f32,
+}
+
+impl Colour {
+    pub const BLACK: Colour = Colour { r: 0.0, g: 0.0, b: 0.0, a: 1.0 };
+    pub const WHITE: Colour = Colour { r: 1.0, g: 1.0, b: 1.0, a: 1.0 };
+    pub const RED: Colour = Colour { r: 1.0, g: 0.0, b: 0.0, a: 1.0 };
+    pub const GREEN: Colour = Colour { r: 0.0, g: 1.0, b: 0.0, a: 1.0 };
+    pub const BLUE: Colour = Colour { r: 0.0, g: 0.0, b: 1.0, a: 1.0 };
+    pub const TRANSPARENT: Colour = Colour { r: 0.0, g: 0.0, b: 0.0, a: 0.0 };
+    ///
+    /// The grey the demos clear to, so that anything drawn black by mistake still
+    /// shows up.
+    ///
+    pub const DARK_GREY: Colour = Colour { r: 0.2, g: 0.2, b: 0.2, a: 1.0 };
+    ///
+    /// `#ff1493`, a colour no scene is likely to be made of, for a background that
+    /// shows up every gap.
+    ///
+    pub const HOT_PINK: Colour = Colour { r: 255.0 / 255.0, g: 20.0 / 255.0, b: 147.0 / 255.0, a: 1.0 };
+
+    ///
+    /// An opaque colour.
+    ///
+    pub fn new(r: f32, g: f32, b: f32) -> Colour {
+        Colour { r: r, g: g, b: b, a: 1.0 }
+    }
+
+    ///
+    /// An opaque colour from channels from 0 to 255, as colours are written in hex.
+    /// The channels are only scaled, not decoded.
+    ///
+    pub fn from_rgb8(r: u8, g: u8, b: u8) -> Colour {
+        Colour::new(r as f32 / 255.0, g as f32 / 255.0, b as f32 / 255.0)
+    }
+
+    ///
+    /// An opaque colour from a hue in degrees, with red at 0, green at 120, and blue
+    /// at 240, and a saturation and value from 0 to 1. Spacing hues out evenly is an
+    /// easy way to give a handful of things colours that are told apart at a glance.
+    ///
+    pub fn from_hsv(hue: f32, saturation: f32, value: f32) -> Colour {
+        let hue = ((hue % 360.0) + 360.0) % 360.0;
+        let chroma = value * saturation;
+        let sector = hue / 60.0;
+        let x = chroma * (1.0 - f32::abs(sector % 2.0 - 1.0));
+        let (r, g, b) = match sector as u32 {
+            0 => (chroma, x, 0.0),
+            1 => (x, chroma, 0.0),
+            2 => (0.0, chroma, x),
+            3 => (0.0, x, chroma),
+            4 => (x, 0.0, chroma),
+            _ => (chroma, 0.0, x),
+        };
+        let m = value - chroma;
+
+        Colour::new(r + m, g + m, b + m)
+    }
+
+    pub fn with_alpha(&self, a: f32) -> Colour {
+        Colour { r: self.r, g: self.g, b: self.b, a: a }
+    }
+
+    ///
+    /// Decode an sRGB encoded colour to linear light.
+    ///
+    pub fn to_linear(&self) -> Colour {
+        Colour { r: srgb_to_linear(self.r), g: srgb_to_linear(self.g), b: srgb_to_linear(self.b), a: self.a }
+    }
+
+    ///
+    /// Encode a colour in linear light as sRGB.
+    ///
+    pub fn to_srgb(&self) -> Colour {
+        Colour { r: linear_to_srgb(self.r), g: linear_to_srgb(self.g), b: linear_to_srgb(self.b), a: self.a }
+    }
+
+    ///
+    /// The red, green, and blue channels, as a `vec3` uniform takes them.
+    ///
+    pub fn rgb(&self) -> [f32; 3] {
+        [self.r, self.g, self.b]
+    }
+
+    pub fn rgba(&self) -> [f32; 4] {
+        [self.r, self.g, self.b, self.a]
+    }
+}
+
+impl From<[f32; 3]> for Colour {
+    fn from(rgb: [f32; 3]) -> Colour {
+        Colour::new(rgb[0], rgb[1], rgb[2])
+    }
+}
+
+impl From<[f32; 4]> for Colour {
+    fn from(rgba: [f32; 4]) -> Colour {
+        Colour { r: rgba[0], g: rgba[1], b: rgba[2], a: rgba[3] }
+    }
+}
+
+impl From<Colour> for [f32; 3] {
+    fn from(colour: Colour) -> [f32; 3] {
+        colour.rgb()
+    }
+}
+
+impl From<Colour> for [f32; 4] {
+    fn from(colour: Colour) -> [f32; 4] {
+        colour.rgba()
+    }
+}
+
+mod colour_tests {
+    use super::{linear_to_srgb, srgb_to_linear, Colour};
+
+    const EPSILON: f32 = 1e-5;
+
+    fn assert_close(a: Colour, b: Colour) {
+        let (a, b) = (a.rgba(), b.rgba());
+        for i in 0..4 {
+            assert!(f32::abs(a[i] - b[i]) < EPSILON, "{:?} != {:?}", a, b);
+        }
+    }
+
+    #[test]
+    fn test_srgb_round_trips_through_linear() {
+        for i in 0..256 {
+            let value = i as f32 / 255.0;
+            assert!(f32::abs(linear_to_srgb(srgb_to_linear(value)) - value) < EPSILON, "{}", value);
+        }
+        // sRGB mid grey is much less than half as bright in linear light
+        assert!(f32::abs(srgb_to_linear(0.5) - 0.214) < 1e-3);
+    }
+
+    #[test]
+    fn test_alpha_is_left_alone_by_the_colour_space() {
+        let colour = Colour::new(0.5, 0.25, 1.0).with_alpha(0.5);
+
+        assert_eq!(colour.to_linear().a, 0.5);
+        assert_close(colour.to_linear().to_srgb(), colour);
+    }
+
+    #[test]
+    fn test_hues_go_round_red_green_blue() {
+        assert_close(Colour::from_hsv(0.0, 1.0, 1.0), Colour::RED);
+        assert_close(Colour::from_hsv(120.0, 1.0, 1.0), Colour::GREEN);
+        assert_close(Colour::from_hsv(240.0, 1.0, 1.0), Colour::BLUE);
+        assert_close(Colour::from_hsv(360.0, 1.0, 1.0), Colour::RED);
+        assert_close(Colour::from_hsv(-60.0, 1.0, 1.0), Colour::new(1.0, 0.0, 1.0));
+        assert_close(Colour::from_hsv(60.0, 1.0, 0.5), Colour::new(0.5, 0.5, 0.0));
+        // no saturation leaves a grey as bright as the value
+        assert_close(Colour::from_hsv(200.0, 0.0, 0.2), Colour::DARK_GREY);
+    }
+
+    #[test]
+    fn test_colours_convert_to_and_from_arrays() {
+        let colour = Colour::from_rgb8(255, 20, 147);
+        let rgba: [f32; 4] = colour.into();
+        let rgb: [f32; 3] = colour.into();
+
+        assert_eq!(colour, Colour::HOT_PINK);
+        assert_eq!(rgba, [1.0, 20.0 / 255.0, 147.0 / 255.0, 1.0]);
+        assert_eq!(Colour::from(rgb), colour);
+        assert_eq!(Colour::from([0.0, 0.0, 0.0, 0.0]), Colour::TRANSPARENT);
+    }
+}
@@ -0,0 +1,1443 @@
+use graphics_math as math;
+use math::Vec3;
+
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap};
+
+
+///
+/// Creases sharper than this, in degrees, keep a hard edge when normals are
+/// worked out for a mesh that has none. Gentler ones are smoothed over.
+///
+pub const DEFAULT_SMOOTH_ANGLE_DEG: f32 = 60.0;
+
+
+fn vertex_vec3(data: &[f32], i: usize) -> Vec3 {
+    math::vec3((data[3 * i], data[3 * i + 1], data[3 * i + 2]))
+}
+
+fn vertex_uv(data: &[f32], i: usize) -> (f32, f32) {
+    (data[2 * i], data[2 * i + 1])
+}
+
+///
+/// The angle between two edges leaving a vertex of a triangle.
+///
+fn corner_angle(edge1: Vec3, edge2: Vec3) -> f32 {
+    let denom = edge1.norm() * edge2.norm();
+    if denom > math::EPSILON {
+        let cos_angle = edge1.dot(&edge2) / denom;
+        f32::acos(f32::max(f32::min(cos_angle, 1.0), -1.0))
+    } else {
+        0.0
+    }
+}
+
+///
+/// Orthogonalise a tangent against the normal and work out the handedness of
+/// the tangent space, packing both into a four component vector. The bitangent
+/// can be rebuilt in a shader as `cross(normal, tangent.xyz) * tangent.w`.
+///
+pub fn tangent_with_handedness(n: Vec3, t: Vec3, b: Vec3) -> [f32; 4] {
+    let t_i = t - n * n.dot(&t);
+    let t_i = if t_i.norm() > math::EPSILON {
+        t_i.normalize()
+    } else {
+        // There is no usable tangent, so pick any direction perpendicular to the normal.
+        let axis = if f32::abs(n.v[0]) < 0.9 { math::vec3((1.0, 0.0, 0.0)) } else { math::vec3((0.0, 1.0, 0.0)) };
+        n.cross(&axis).normalize()
+    };
+    let det = if (n.cross(&t_i)).dot(&b) < 0.0 { -1.0 } else { 1.0 };
+
+    [t_i.v[0], t_i.v[1], t_i.v[2], det]
+}
+
+///
+/// Work out per vertex tangents for an indexed triangle mesh, four components
+/// per vertex with the handedness in `w`. As in MikkTSpace, each triangle's
+/// tangent and bitangent directions are added onto its vertices weighted by the
+/// angle of the triangle at that vertex, and the sums are orthogonalised against
+/// the vertex normals. Vertices no triangle refers to get an arbitrary tangent.
+///
+pub fn compute_tangents(positions: &[f32], uvs: &[f32], normals: &[f32], indices: &[u32]) -> Vec<f32> {
+    let vertex_count = positions.len() / 3;
+    let mut tangent_sums = vec![math::vec3((0.0, 0.0, 0.0)); vertex_count];
+    let mut bitangent_sums = vec![math::vec3((0.0, 0.0, 0.0)); vertex_count];
+    for tri in indices.chunks(3) {
+        if tri.len() < 3 {
+            break;
+        }
+        let corners = [tri[0] as usize, tri[1] as usize, tri[2] as usize];
+        let p0 = vertex_vec3(positions, corners[0]);
+        let edge1 = vertex_vec3(positions, corners[1]) - p0;
+        let edge2 = vertex_vec3(positions, corners[2]) - p0;
+        let (u0, v0) = vertex_uv(uvs, corners[0]);
+        let (u1, v1) = vertex_uv(uvs, corners[1]);
+        let (u2, v2) = vertex_uv(uvs, corners[2]);
+        let (du1, dv1) = (u1 - u0, v1 - v0);
+        let (du2, dv2) = (u2 - u0, v2 - v0);
+
+        let det = du1 * dv2 - du2 * dv1;
+        if f32::abs(det) <= math::EPSILON {
+            // The texture coordinates are degenerate, so this triangle says nothing
+            // about the direction of the tangent.
+            continue;
+        }
+        let r = 1.0 / det;
+        let t = (edge1 * dv2 - edge2 * dv1) * r;
+        let b = (edge2 * du1 - edge1 * du2) * r;
+        if t.norm() <= math::EPSILON || b.norm() <= math::EPSILON {
+            continue;
+        }
+        let t = t.normalize();
+        let b = b.normalize();
+
+        for k in 0..3 {
+            let here = vertex_vec3(positions, corners[k]);
+            let next = vertex_vec3(positions, corners[(k + 1) % 3]);
+            let prev = vertex_vec3(positions, corners[(k + 2) % 3]);
+            let angle = corner_angle(next - here, prev - here);
+            tangent_sums[corners[k]] = tangent_sums[corners[k]] + t * angle;
+            bitangent_sums[corners[k]] = bitangent_sums[corners[k]] + b * angle;
+        }
+    }
+
+    let mut tangents = vec![0.0; 4 * vertex_count];
+    for v_i in 0..vertex_count {
+        let tangent = tangent_with_handedness(vertex_vec3(normals, v_i), tangent_sums[v_i], bitangent_sums[v_i]);
+        tangents[4 * v_i..4 * v_i + 4].copy_from_slice(&tangent);
+    }
+
+    tangents
+}
+
+///
+/// Map each vertex of an unindexed mesh to the first vertex with exactly the
+/// same position, texture coordinates and normal, so the triangles can be treated
+/// as an indexed mesh.
+///
+pub fn shared_vertex_indices(positions: &[f32], uvs: &[f32], normals: &[f32]) -> Vec<u32> {
+    let vertex_count = positions.len() / 3;
+    let mut first_vertex: HashMap<[u32; 8], u32> = HashMap::new();
+    let mut indices = Vec::with_capacity(vertex_count);
+    for v_i in 0..vertex_count {
+        let key = [
+            positions[3 * v_i].to_bits(), positions[3 * v_i + 1].to_bits(), positions[3 * v_i + 2].to_bits(),
+            uvs[2 * v_i].to_bits(), uvs[2 * v_i + 1].to_bits(),
+            normals[3 * v_i].to_bits(), normals[3 * v_i + 1].to_bits(), normals[3 * v_i + 2].to_bits(),
+        ];
+        indices.push(*first_vertex.entry(key).or_insert(v_i as u32));
+    }
+
+    indices
+}
+
+///
+/// Map each vertex of an unindexed mesh to the first vertex in exactly the same
+/// place, whatever its other attributes, so triangles that touch can be found.
+///
+pub fn shared_position_indices(positions: &[f32]) -> Vec<u32> {
+    let vertex_count = positions.len() / 3;
+    let mut first_vertex: HashMap<[u32; 3], u32> = HashMap::new();
+    let mut indices = Vec::with_capacity(vertex_count);
+    for v_i in 0..vertex_count {
+        let key = [positions[3 * v_i].to_bits(), positions[3 * v_i + 1].to_bits(), positions[3 * v_i + 2].to_bits()];
+        indices.push(*first_vertex.entry(key).or_insert(v_i as u32));
+    }
+
+    indices
+}
+
+///
+/// Work out normals for an indexed triangle mesh, one for each corner of each
+/// triangle in the order of `indices`, since a corner on a hard edge needs a
+/// different normal from the other corners of its vertex. Each corner gets the
+/// face normals of the triangles around its vertex added up, weighted by their
+/// areas and by their angles at the vertex, leaving out any triangle that meets
+/// the corner's own triangle at more than `smooth_angle_deg`. A smoothing angle
+/// of zero gives flat shading. The corners of a triangle with no area take
+/// every triangle around them.
+///
+pub fn compute_normals(positions: &[f32], indices: &[u32], smooth_angle_deg: f32) -> Vec<f32> {
+    let vertex_count = positions.len() / 3;
+    let triangle_count = indices.len() / 3;
+    // leave some room for rounding, so coplanar triangles always smooth together
+    let cos_limit = f32::cos(smooth_angle_deg * math::ONE_DEG_IN_RAD) - 1e-5;
+
+    let mut face_normals = Vec::with_capacity(triangle_count);
+    let mut corner_weights = Vec::with_capacity(3 * triangle_count);
+    let mut corners_around: Vec<Vec<usize>> = vec![vec![]; vertex_count];
+    for t in 0..triangle_count {
+        let corners = [indices[3 * t] as usize, indices[3 * t + 1] as usize, indices[3 * t + 2] as usize];
+        let p0 = vertex_vec3(positions, corners[0]);
+        let cross = (vertex_vec3(positions, corners[1]) - p0).cross(&(vertex_vec3(positions, corners[2]) - p0));
+        let area = 0.5 * cross.norm();
+        face_normals.push(if area > math::EPSILON { Some(cross.normalize()) } else { None });
+
+        for k in 0..3 {
+            let here = vertex_vec3(positions, corners[k]);
+            let next = vertex_vec3(positions, corners[(k + 1) % 3]);
+            let prev = vertex_vec3(positions, corners[(k + 2) % 3]);
+            corner_weights.push(area * corner_angle(next - here, prev - here));
+            corners_around[corners[k]].push(3 * t + k);
+        }
+    }
+
+    let mut normals = vec![0.0; 3 * 3 * triangle_count];
+    for corner in 0..(3 * triangle_count) {
+        let own_normal = face_normals[corner / 3];
+        let mut sum = math::vec3((0.0, 0.0, 0.0));
+        for &other in corners_around[indices[corner] as usize].iter() {
+            let other_normal = match face_normals[other / 3] {
+                Some(n) => n,
+                None => continue,
+            };
+            let smooth = match own_normal {
+                Some(n) => n.dot(&other_normal) >= cos_limit,
+                None => true,
+            };
+            if smooth {
+                sum = sum + other_normal * corner_weights[other];
+            }
+        }
+        let normal = if sum.norm() > math::EPSILON {
+            sum.normalize()
+        } else {
+            own_normal.unwrap_or(math::vec3((0.0, 0.0, 1.0)))
+        };
+        normals[3 * corner..3 * corner + 3].copy_from_slice(&normal.v);
+    }
+
+    normals
+}
+
+///
+/// Work out normals for an unindexed triangle list the way `compute_normals`
+/// does, treating vertices in the same place as one.
+///
+pub fn compute_unindexed_normals(positions: &[f32], smooth_angle_deg: f32) -> Vec<f32> {
+    let indices = shared_position_indices(positions);
+    let triangle_indices = &indices[0..(indices.len() - indices.len() % 3)];
+    let mut normals = compute_normals(positions, triangle_indices, smooth_angle_deg);
+    // vertices left over after the last whole triangle face the default way
+    for _ in triangle_indices.len()..indices.len() {
+        normals.extend_from_slice(&[0.0, 0.0, 1.0]);
+    }
+
+    normals
+}
+
+///
+/// Work out per vertex tangents for an unindexed triangle list, averaging the
+/// tangents of vertices that are repeated between triangles.
+///
+pub fn compute_unindexed_tangents(positions: &[f32], uvs: &[f32], normals: &[f32]) -> Vec<f32> {
+    let indices = shared_vertex_indices(positions, uvs, normals);
+    let mut tangents = compute_tangents(positions, uvs, normals, &indices);
+    // Only the first copy of each repeated vertex has its tangent, so copy it to the others.
+    for (v_i, &shared_i) in indices.iter().enumerate() {
+        let shared_i = shared_i as usize;
+        if shared_i != v_i {
+            for k in 0..4 {
+                tangents[4 * v_i + k] = tangents[4 * shared_i + k];
+            }
+        }
+    }
+
+    tangents
+}
+
+///
+/// How many vertices a mesh had before and after welding.
+///
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct WeldReport {
+    pub vertices_before: usize,
+    pub vertices_after: usize,
+}
+
+impl WeldReport {
+    ///
+    /// The share of the vertices that were welded away, from 0 to 1.
+    ///
+    pub fn reduction(&self) -> f32 {
+        if self.vertices_before == 0 {
+            0.0
+        } else {
+            1.0 - self.vertices_after as f32 / self.vertices_before as f32
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        format!(
+            "welded {} vertices into {}, {:.1}% fewer",
+            self.vertices_before, self.vertices_after, 100.0 * self.reduction()
+        )
+    }
+}
+
+///
+/// A triangle soup turned into an indexed mesh by `weld_vertices`. There is one
+/// index for each vertex of the soup, in the same order, so ranges of vertices
+/// such as material groups become the same ranges of indices.
+///
+#[derive(Clone, Debug, PartialEq)]
+pub struct WeldedVertices {
+    pub indices: Vec<u32>,
+    ///
+    /// The vertex of the soup each welded vertex was taken from.
+    ///
+    pub sources: Vec<u32>,
+}
+
+impl WeldedVertices {
+    pub fn vertex_count(&self) -> usize {
+        self.sources.len()
+    }
+
+    ///
+    /// Pick the welded vertices' values out of an attribute of the soup, with
+    /// `stride` floats to a vertex. An attribute that does not cover every vertex
+    /// of the soup, such as missing texture coordinates, gives an empty array.
+    ///
+    pub fn gather(&self, data: &[f32], stride: usize) -> Vec<f32> {
+        if data.len() != stride * self.indices.len() {
+            return vec![];
+        }
+
+        self.sources.iter()
+            .flat_map(|&v_i| data[stride * v_i as usize..stride * (v_i as usize + 1)].iter().cloned())
+            .collect()
+    }
+
+    pub fn report(&self) -> WeldReport {
+        WeldReport {
+            vertices_before: self.indices.len(),
+            vertices_after: self.vertex_count(),
+        }
+    }
+}
+
+///
+/// Weld the vertices of a triangle soup that are within `epsilon` of each other,
+/// so it can be drawn with an index buffer and each shared corner goes through
+/// the vertex shader once. `attributes` are the soup's other arrays, each with
+/// the number of floats to a vertex; vertices are only welded if these are all
+/// within `epsilon` too, so hard edges and texture seams stay where they are.
+/// Attributes that do not cover every vertex are left out of the comparison.
+///
+/// Each vertex is welded onto the first earlier one close enough, found through
+/// a grid of cells `epsilon` wide, so vertices in a chain less than `epsilon`
+/// apart are not all pulled onto one.
+///
+pub fn weld_vertices(positions: &[f32], attributes: &[(&[f32], usize)], epsilon: f32) -> WeldedVertices {
+    assert!(epsilon > 0.0);
+    let vertex_count = positions.len() / 3;
+    let attributes: Vec<&(&[f32], usize)> = attributes.iter()
+        .filter(|&&(data, stride)| data.len() == stride * vertex_count)
+        .collect();
+
+    let cell_of = |p: &Vec3| {
+        let cell = |x: f32| f32::floor(x / epsilon) as i64;
+        (cell(p.v[0]), cell(p.v[1]), cell(p.v[2]))
+    };
+    let matches = |a: usize, b: usize| {
+        attributes.iter().all(|&&(data, stride)| {
+            (0..stride).all(|k| f32::abs(data[stride * a + k] - data[stride * b + k]) <= epsilon)
+        })
+    };
+
+    let mut cells: HashMap<(i64, i64, i64), Vec<u32>> = HashMap::new();
+    let mut indices = Vec::with_capacity(vertex_count);
+    let mut sources: Vec<u32> = vec![];
+    for v_i in 0..vertex_count {
+        let p = vertex_vec3(positions, v_i);
+        let (x, y, z) = cell_of(&p);
+
+        let mut found = None;
+        'search: for dx in -1..2 {
+            for dy in -1..2 {
+                for dz in -1..2 {
+                    let welded = match cells.get(&(x + dx, y + dy, z + dz)) {
+                        Some(welded) => welded,
+                        None => continue,
+                    };
+                    for &w_i in welded.iter() {
+                        let source = sources[w_i as usize] as usize;
+                        if (vertex_vec3(positions, source) - p).norm() <= epsilon && matches(source, v_i) {
+                            found = Some(w_i);
+                            break 'search;
+                        }
+                    }
+                }
+            }
+        }
+
+        let index = match found {
+            Some(w_i) => w_i,
+            None => {
+                let w_i = sources.len() as u32;
+                sources.push(v_i as u32);
+                cells.entry((x, y, z)).or_insert(vec![]).push(w_i);
+                w_i
+            }
+        };
+        indices.push(index);
+    }
+
+    WeldedVertices {
+        indices: indices,
+        sources: sources,
+    }
+}
+
+///
+/// An axis aligned bounding box.
+///
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Aabb {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+impl Aabb {
+    pub fn centre(&self) -> Vec3 {
+        (self.min + self.max) * 0.5
+    }
+
+    pub fn half_extents(&self) -> Vec3 {
+        (self.max - self.min) * 0.5
+    }
+
+    pub fn contains(&self, p: &Vec3) -> bool {
+        (0..3).all(|i| p.v[i] >= self.min.v[i] && p.v[i] <= self.max.v[i])
+    }
+}
+
+///
+/// A sphere around a set of points.
+///
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct BoundingSphere {
+    pub centre: Vec3,
+    pub radius: f32,
+}
+
+impl BoundingSphere {
+    pub fn contains(&self, p: &Vec3) -> bool {
+        (*p - self.centre).norm() <= self.radius
+    }
+}
+
+///
+/// The smallest axis aligned box around a list of points, three floats each. No
+/// points give an empty box at the origin.
+///
+pub fn points_aabb(positions: &[f32]) -> Aabb {
+    let vertex_count = positions.len() / 3;
+    if vertex_count == 0 {
+        return Aabb { min: math::vec3((0.0, 0.0, 0.0)), max: math::vec3((0.0, 0.0, 0.0)) };
+    }
+
+    let mut min = vertex_vec3(positions, 0);
+    let mut max = min;
+    for v_i in 1..vertex_count {
+        let p = vertex_vec3(positions, v_i);
+        for i in 0..3 {
+            min.v[i] = f32::min(min.v[i], p.v[i]);
+            max.v[i] = f32::max(max.v[i], p.v[i]);
+        }
+    }
+
+    Aabb { min: min, max: max }
+}
+
+///
+/// A sphere around a list of points, three floats each, found with Ritter's
+/// method. It starts from two points far apart and grows to take in any point
+/// left outside, which is quick and comes out at most a few percent larger than
+/// the smallest sphere. No points give a sphere of no size at the origin.
+///
+pub fn points_bounding_sphere(positions: &[f32]) -> BoundingSphere {
+    let vertex_count = positions.len() / 3;
+    if vertex_count == 0 {
+        return BoundingSphere { centre: math::vec3((0.0, 0.0, 0.0)), radius: 0.0 };
+    }
+
+    let farthest_from = |from: Vec3| {
+        (0..vertex_count)
+            .map(|v_i| vertex_vec3(positions, v_i))
+            .fold(from, |best, p| if (p - from).norm() > (best - from).norm() { p } else { best })
+    };
+    let a = farthest_from(vertex_vec3(positions, 0));
+    let b = farthest_from(a);
+    let mut centre = (a + b) * 0.5;
+    let mut radius = (b - a).norm() * 0.5;
+    for v_i in 0..vertex_count {
+        let p = vertex_vec3(positions, v_i);
+        let distance = (p - centre).norm();
+        if distance > radius {
+            // grow just enough to reach p, keeping the far side where it was
+            let new_radius = 0.5 * (radius + distance);
+            centre = centre + (p - centre) * ((new_radius - radius) / distance);
+            radius = new_radius;
+        }
+    }
+
+    BoundingSphere { centre: centre, radius: radius }
+}
+
+///
+/// An indexed triangle mesh with positions, normals, and texture coordinates
+/// in separate arrays, ready to be copied into vertex buffers.
+///
+pub struct GridMesh {
+    pub positions: Vec<f32>,
+    pub normals: Vec<f32>,
+    pub uvs: Vec<f32>,
+    pub indices: Vec<u32>,
+}
+
+///
+/// Build a grid of triangles from a heightfield: `columns` by `rows` heights
+/// stored row by row, with the rows running along +z and the columns along +x,
+/// `spacing` apart. The grid is centred on the origin in x and z, and its
+/// texture coordinates run from 0 to 1 across it. The triangles face up.
+///
+/// Each normal comes from the slope of the heights either side of its vertex,
+/// so the shading is smooth without having to average face normals. Vertices on
+/// the edge of the grid use the one neighbour they have on that side.
+///
+pub fn heightfield_grid(heights: &[f32], columns: usize, rows: usize, spacing: f32) -> GridMesh {
+    assert!(columns >= 2 && rows >= 2);
+    assert_eq!(heights.len(), columns * rows);
+
+    let height = |i: usize, j: usize| heights[j * columns + i];
+    let x_offset = 0.5 * (columns - 1) as f32 * spacing;
+    let z_offset = 0.5 * (rows - 1) as f32 * spacing;
+
+    let vertex_count = columns * rows;
+    let mut positions = Vec::with_capacity(3 * vertex_count);
+    let mut normals = Vec::with_capacity(3 * vertex_count);
+    let mut uvs = Vec::with_capacity(2 * vertex_count);
+    for j in 0..rows {
+        for i in 0..columns {
+            positions.push(i as f32 * spacing - x_offset);
+            positions.push(height(i, j));
+            positions.push(j as f32 * spacing - z_offset);
+
+            let (left, right) = (i.saturating_sub(1), usize::min(i + 1, columns - 1));
+            let (back, front) = (j.saturating_sub(1), usize::min(j + 1, rows - 1));
+            let dh_dx = (height(right, j) - height(left, j)) / ((right - left) as f32 * spacing);
+            let dh_dz = (height(i, front) - height(i, back)) / ((front - back) as f32 * spacing);
+            let normal = math::vec3((-dh_dx, 1.0, -dh_dz)).normalize();
+            normals.extend_from_slice(&normal.v);
+
+            uvs.push(i as f32 / (columns - 1) as f32);
+            uvs.push(j as f32 / (rows - 1) as f32);
+        }
+    }
+
+    let mut indices = Vec::with_capacity(6 * (columns - 1) * (rows - 1));
+    for j in 0..(rows - 1) {
+        for i in 0..(columns - 1) {
+            let v00 = (j * columns + i) as u32;
+            let v10 = v00 + 1;
+            let v01 = v00 + columns as u32;
+            let v11 = v01 + 1;
+            indices.extend_from_slice(&[v00, v01, v10, v10, v01, v11]);
+        }
+    }
+
+    GridMesh {
+        positions: positions,
+        normals: normals,
+        uvs: uvs,
+        indices: indices,
+    }
+}
+
+///
+/// The sum of squared distances from a point to a set of planes, kept as the
+/// upper triangle of a symmetric 4x4 matrix, as in Garland and Heckbert's
+/// quadric error metric. Adding two quadrics measures the distance to the
+/// planes of both. Doubles are used because the error of a point on all the
+/// planes comes from cancelling large terms.
+///
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+struct Quadric {
+    // aa, ab, ac, ad, bb, bc, bd, cc, cd, dd for the plane ax + by + cz + d = 0
+    m: [f64; 10],
+}
+
+impl Quadric {
+    ///
+    /// The quadric of the plane through `point` with unit normal `normal`,
+    /// scaled by `weight`.
+    ///
+    fn from_plane(normal: Vec3, point: Vec3, weight: f32) -> Quadric {
+        let (a, b, c) = (normal.v[0] as f64, normal.v[1] as f64, normal.v[2] as f64);
+        let d = -(normal.dot(&point) as f64);
+        let w = weight as f64;
+
+        Quadric {
+            m: [
+                w * a * a, w * a * b, w * a * c, w * a * d,
+                w * b * b, w * b * c, w * b * d,
+                w * c * c, w * c * d,
+                w * d * d,
+            ],
+        }
+    }
+
+    fn add(&self, other: &Quadric) -> Quadric {
+        let mut m = self.m;
+        for i in 0..10 {
+            m[i] += other.m[i];
+        }
+
+        Quadric { m: m }
+    }
+
+    fn error(&self, p: Vec3) -> f64 {
+        let (x, y, z) = (p.v[0] as f64, p.v[1] as f64, p.v[2] as f64);
+        let m = &self.m;
+
+        m[0] * x * x + 2.0 * m[1] * x * y + 2.0 * m[2] * x * z + 2.0 * m[3] * x
+            + m[4] * y * y + 2.0 * m[5] * y * z + 2.0 * m[6] * y
+            + m[7] * z * z + 2.0 * m[8] * z
+            + m[9]
+    }
+
+    ///
+    /// The point with the least error, or `None` if there is no single one, as
+    /// when all the planes are parallel.
+    ///
+    fn optimal_point(&self) -> Option<Vec3> {
+        let m = &self.m;
+        let (a00, a01, a02, a11, a12, a22) = (m[0], m[1], m[2], m[4], m[5], m[7]);
+        let (b0, b1, b2) = (-m[3], -m[6], -m[8]);
+
+        // Cramer's rule on the symmetric 3x3 system
+        let det = a00 * (a11 * a22 - a12 * a12) - a01 * (a01 * a22 - a12 * a02) + a02 * (a01 * a12 - a11 * a02);
+        if f64::abs(det) < 1e-12 {
+            return None;
+        }
+        let x = b0 * (a11 * a22 - a12 * a12) - a01 * (b1 * a22 - a12 * b2) + a02 * (b1 * a12 - a11 * b2);
+        let y = a00 * (b1 * a22 - a12 * b2) - b0 * (a01 * a22 - a12 * a02) + a02 * (a01 * b2 - b1 * a02);
+        let z = a00 * (a11 * b2 - b1 * a12) - a01 * (a01 * b2 - b1 * a02) + b0 * (a01 * a12 - a11 * a02);
+
+        Some(math::vec3(((x / det) as f32, (y / det) as f32, (z / det) as f32)))
+    }
+}
+
+///
+/// How much more it costs to move a vertex off the border of a hole than off a
+/// surface, so open meshes keep their outlines.
+///
+const BOUNDARY_WEIGHT: f32 = 1000.0;
+
+///
+/// An edge that could be collapsed, with where the merged vertex would go and
+/// what it would cost. The versions of its ends go stale when either end moves,
+/// and the candidate is then skipped.
+///
+struct Collapse {
+    cost: f64,
+    keep: usize,
+    remove: usize,
+    position: Vec3,
+    keep_version: u32,
+    remove_version: u32,
+}
+
+impl PartialEq for Collapse {
+    fn eq(&self, other: &Collapse) -> bool {
+        self.cost == other.cost
+    }
+}
+
+impl Eq for Collapse {}
+
+impl PartialOrd for Collapse {
+    fn partial_cmp(&self, other: &Collapse) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Collapse {
+    // reversed, so the binary heap hands out the cheapest collapse first
+    fn cmp(&self, other: &Collapse) -> Ordering {
+        other.cost.partial_cmp(&self.cost).unwrap_or(Ordering::Equal)
+    }
+}
+
+///
+/// An indexed triangle mesh as made by `simplify`: positions, and texture
+/// coordinates if `simplify_with_uvs` was given some.
+///
+#[derive(Clone, Debug, PartialEq)]
+pub struct SimplifiedMesh {
+    pub positions: Vec<f32>,
+    pub indices: Vec<u32>,
+    /// Two for each entry of `indices`, since a vertex on a seam has different
+    /// texture coordinates on either side of it. Empty if the mesh had none.
+    pub corner_uvs: Vec<f32>,
+}
+
+///
+/// The state of a mesh part of the way through `simplify`.
+///
+struct Simplifier {
+    points: Vec<Vec3>,
+    quadrics: Vec<Quadric>,
+    versions: Vec<u32>,
+    triangles: Vec<[usize; 3]>,
+    alive: Vec<bool>,
+    alive_count: usize,
+    triangles_around: Vec<Vec<usize>>,
+    has_uvs: bool,
+    // the texture coordinates of each vertex off a seam, and of each corner of
+    // each triangle for the vertices on one
+    uvs: Vec<[f32; 2]>,
+    corner_uvs: Vec<[[f32; 2]; 3]>,
+    on_seam: Vec<bool>,
+}
+
+impl Simplifier {
+    fn new(positions: &[f32], indices: &[u32], corner_uvs: &[f32]) -> Simplifier {
+        let vertex_count = positions.len() / 3;
+        let points: Vec<Vec3> = (0..vertex_count).map(|v_i| vertex_vec3(positions, v_i)).collect();
+        let has_uvs = !indices.is_empty() && corner_uvs.len() == 2 * indices.len();
+        let corner_uv = |i: usize| if has_uvs { [corner_uvs[2 * i], corner_uvs[2 * i + 1]] } else { [0.0, 0.0] };
+        let mut triangles: Vec<[usize; 3]> = vec![];
+        let mut triangle_uvs: Vec<[[f32; 2]; 3]> = vec![];
+        for (t, tri) in indices.chunks(3).enumerate() {
+            if tri.len() == 3 && tri[0] != tri[1] && tri[1] != tri[2] && tri[2] != tri[0] {
+                triangles.push([tri[0] as usize, tri[1] as usize, tri[2] as usize]);
+                triangle_uvs.push([corner_uv(3 * t), corner_uv(3 * t + 1), corner_uv(3 * t + 2)]);
+            }
+        }
+
+        // A vertex whose corners do not all have the same texture coordinates is on
+        // a seam, and stays where it is, so the texture still lines up either side.
+        let mut uvs: Vec<Option<[f32; 2]>> = vec![None; vertex_count];
+        let mut on_seam = vec![false; vertex_count];
+        if has_uvs {
+            for (tri, tri_uvs) in triangles.iter().zip(triangle_uvs.iter()) {
+                for k in 0..3 {
+                    match uvs[tri[k]] {
+                        None => uvs[tri[k]] = Some(tri_uvs[k]),
+                        Some(uv) if uv != tri_uvs[k] => on_seam[tri[k]] = true,
+                        Some(_) => {}
+                    }
+                }
+            }
+        }
+
+        let mut quadrics = vec![Quadric::default(); vertex_count];
+        let mut triangles_around: Vec<Vec<usize>> = vec![vec![]; vertex_count];
+        let mut edge_uses: HashMap<(usize, usize), (usize, usize)> = HashMap::new();
+        for (t, tri) in triangles.iter().enumerate() {
+            let cross = (points[tri[1]] - points[tri[0]]).cross(&(points[tri[2]] - points[tri[0]]));
+            let area = 0.5 * cross.norm();
+            if area > math::EPSILON {
+                let plane = Quadric::from_plane(cross.normalize(), points[tri[0]], area);
+                for &v_i in tri.iter() {
+                    quadrics[v_i] = quadrics[v_i].add(&plane);
+                }
+            }
+            for k in 0..3 {
+                triangles_around[tri[k]].push(t);
+                let (a, b) = (tri[k], tri[(k + 1) % 3]);
+                let edge = if a < b { (a, b) } else { (b, a) };
+                edge_uses.entry(edge).or_insert((0, t)).0 += 1;
+            }
+        }
+
+        // A plane at right angles to the triangle along each edge of a hole holds
+        // the edge's vertices to the outline.
+        for (&(a, b), &(uses, t)) in edge_uses.iter() {
+            if uses != 1 {
+                continue;
+            }
+            let tri = triangles[t];
+            let face = (points[tri[1]] - points[tri[0]]).cross(&(points[tri[2]] - points[tri[0]]));
+            let edge = points[b] - points[a];
+            let normal = edge.cross(&face);
+            if normal.norm() > math::EPSILON {
+                let weight = BOUNDARY_WEIGHT * edge.dot(&edge);
+                let plane = Quadric::from_plane(normal.normalize(), points[a], weight);
+                quadrics[a] = quadrics[a].add(&plane);
+                quadrics[b] = quadrics[b].add(&plane);
+            }
+        }
+
+        let triangle_count = triangles.len();
+        Simplifier {
+            points: points,
+            quadrics: quadrics,
+            versions: vec![0; vertex_count],
+            triangles: triangles,
+            alive: vec![true; triangle_count],
+            alive_count: triangle_count,
+            triangles_around: triangles_around,
+            has_uvs: has_uvs,
+            uvs: uvs.iter().map(|uv| uv.unwrap_or([0.0, 0.0])).collect(),
+            corner_uvs: triangle_uvs,
+            on_seam: on_seam,
+        }
+    }
+
+    ///
+    /// The vertices sharing a live triangle with `v_i`, in order, each once.
+    ///
+    fn neighbours(&self, v_i: usize) -> Vec<usize> {
+        let mut neighbours: Vec<usize> = self.triangles_around[v_i].iter()
+            .filter(|&&t| self.alive[t])
+            .flat_map(|&t| self.triangles[t].iter().cloned())
+            .filter(|&other| other != v_i)
+            .collect();
+        neighbours.sort();
+        neighbours.dedup();
+
+        neighbours
+    }
+
+    ///
+    /// Work out where to put the vertex left by merging `keep` and `remove`, and
+    /// what it costs. The best place may not be unique, as on a flat patch, and
+    /// then the best of the two ends and the middle of the edge is taken.
+    ///
+    fn candidate(&self, keep: usize, remove: usize) -> Collapse {
+        let quadric = self.quadrics[keep].add(&self.quadrics[remove]);
+        let position = match quadric.optimal_point() {
+            Some(p) => p,
+            None => {
+                let (a, b) = (self.points[keep], self.points[remove]);
+                let choices = [a, b, (a + b) * 0.5];
+                let mut best = choices[0];
+                for &p in choices[1..].iter() {
+                    if quadric.error(p) < quadric.error(best) {
+                        best = p;
+                    }
+                }
+                best
+            }
+        };
+
+        Collapse {
+            cost: quadric.error(position),
+            keep: keep,
+            remove: remove,
+            position: position,
+            keep_version: self.versions[keep],
+            remove_version: self.versions[remove],
+        }
+    }
+
+    ///
+    /// Whether merging the ends of an edge keeps the mesh a sound surface. The
+    /// ends may only share the neighbours across the triangles on the edge, or
+    /// the merge would pinch the surface or stack two triangles on each other,
+    /// and no triangle left around them may turn over. Nor may a vertex on a
+    /// texture seam be moved or merged away.
+    ///
+    fn can_collapse(&self, collapse: &Collapse) -> bool {
+        let (keep, remove) = (collapse.keep, collapse.remove);
+        if self.on_seam[keep] || self.on_seam[remove] {
+            return false;
+        }
+        let mut opposite: Vec<usize> = self.triangles_around[keep].iter()
+            .filter(|&&t| self.alive[t] && self.triangles[t].contains(&remove))
+            .flat_map(|&t| self.triangles[t].iter().cloned())
+            .filter(|&v_i| v_i != keep && v_i != remove)
+            .collect();
+        opposite.sort();
+        opposite.dedup();
+        if opposite.is_empty() {
+            // the vertices are no longer joined
+            return false;
+        }
+
+        let keep_neighbours = self.neighbours(keep);
+        let shared: Vec<usize> = self.neighbours(remove).into_iter()
+            .filter(|v_i| keep_neighbours.binary_search(v_i).is_ok())
+            .collect();
+        if shared != opposite {
+            return false;
+        }
+
+        for &v_i in [keep, remove].iter() {
+            for &t in self.triangles_around[v_i].iter() {
+                let tri = self.triangles[t];
+                if !self.alive[t] || (tri.contains(&keep) && tri.contains(&remove)) {
+                    continue;
+                }
+                let corner = |k: usize| if tri[k] == v_i { collapse.position } else { self.points[tri[k]] };
+                let before = (self.points[tri[1]] - self.points[tri[0]]).cross(&(self.points[tri[2]] - self.points[tri[0]]));
+                let after = (corner(1) - corner(0)).cross(&(corner(2) - corner(0)));
+                if after.norm() <= math::EPSILON || before.dot(&after) <= 0.0 {
+                    return false;
+                }
+            }
+        }
+
+        true
+    }
+
+    ///
+    /// Merge `remove` into `keep` at the collapse's position, dropping the
+    /// triangles on the edge between them. The merged vertex takes the texture
+    /// coordinates from along the edge, as far as the position is along it.
+    ///
+    fn collapse(&mut self, collapse: &Collapse) {
+        let (keep, remove) = (collapse.keep, collapse.remove);
+        if self.has_uvs {
+            let edge = self.points[remove] - self.points[keep];
+            let length_sq = edge.dot(&edge);
+            let along = if length_sq > math::EPSILON {
+                (edge.dot(&(collapse.position - self.points[keep])) / length_sq).clamp(0.0, 1.0)
+            } else {
+                0.0
+            };
+            for k in 0..2 {
+                self.uvs[keep][k] += along * (self.uvs[remove][k] - self.uvs[keep][k]);
+            }
+        }
+        self.points[keep] = collapse.position;
+        self.quadrics[keep] = self.quadrics[keep].add(&self.quadrics[remove]);
+        self.versions[keep] += 1;
+        self.versions[remove] += 1;
+
+        let moved = ::std::mem::replace(&mut self.triangles_around[remove], vec![]);
+        for t in moved {
+            if !self.alive[t] {
+                continue;
+            }
+            if self.triangles[t].contains(&keep) {
+                self.alive[t] = false;
+                self.alive_count -= 1;
+            } else {
+                for corner in self.triangles[t].iter_mut() {
+                    if *corner == remove {
+                        *corner = keep;
+                    }
+                }
+                self.triangles_around[keep].push(t);
+            }
+        }
+        let alive = &self.alive;
+        self.triangles_around[keep].retain(|&t| alive[t]);
+    }
+
+    ///
+    /// The live triangles, with the vertices they still use numbered again from
+    /// zero in their original order.
+    ///
+    fn finish(&self) -> SimplifiedMesh {
+        let mut used = vec![false; self.points.len()];
+        for (t, tri) in self.triangles.iter().enumerate() {
+            if self.alive[t] {
+                for &v_i in tri.iter() {
+                    used[v_i] = true;
+                }
+            }
+        }
+
+        let mut new_index = vec![0; self.points.len()];
+        let mut positions = vec![];
+        for v_i in (0..self.points.len()).filter(|&v_i| used[v_i]) {
+            new_index[v_i] = (positions.len() / 3) as u32;
+            positions.extend_from_slice(&self.points[v_i].v);
+        }
+        let indices = self.triangles.iter().enumerate()
+            .filter(|&(t, _)| self.alive[t])
+            .flat_map(|(_, tri)| tri.iter().map(|&v_i| new_index[v_i]).collect::<Vec<u32>>())
+            .collect();
+        let mut corner_uvs = vec![];
+        if self.has_uvs {
+            for (t, tri) in self.triangles.iter().enumerate().filter(|&(t, _)| self.alive[t]) {
+                for (&v_i, corner_uv) in tri.iter().zip(self.corner_uvs[t].iter()) {
+                    let uv = if self.on_seam[v_i] { corner_uv } else { &self.uvs[v_i] };
+                    corner_uvs.extend_from_slice(uv);
+                }
+            }
+        }
+
+        SimplifiedMesh {
+            positions: positions,
+            indices: indices,
+            corner_uvs: corner_uvs,
+        }
+    }
+}
+
+///
+/// Simplify an indexed triangle mesh down to about `target_ratio` of its
+/// triangles, for a distant level of detail. Edges are collapsed one at a time,
+/// cheapest first, by Garland and Heckbert's quadric error metric: each vertex
+/// keeps the planes of the triangles it started on, and a merged vertex goes
+/// where it is closest to all of them. Borders of holes are held in place.
+///
+/// No collapse is made that would turn a triangle over or break up the
+/// surface, so the result can stop short of the target on a mesh that has
+/// nothing left to give. Triangles must share vertices to be simplified, so
+/// weld an unindexed mesh first with `shared_position_indices`.
+///
+pub fn simplify(positions: &[f32], indices: &[u32], target_ratio: f32) -> SimplifiedMesh {
+    simplify_with_uvs(positions, indices, &[], target_ratio)
+}
+
+///
+/// Simplify a mesh as `simplify` does, carrying along texture coordinates, two
+/// for each entry of `indices`. A vertex welded from corners with different
+/// texture coordinates is on a seam and is left where it is; the rest have
+/// theirs moved along with them as edges collapse.
+///
+pub fn simplify_with_uvs(positions: &[f32], indices: &[u32], corner_uvs: &[f32], target_ratio: f32) -> SimplifiedMesh {
+    let mut simplifier = Simplifier::new(positions, indices, corner_uvs);
+    let ratio = f32::max(f32::min(target_ratio, 1.0), 0.0);
+    let target = f32::ceil(ratio * simplifier.alive_count as f32) as usize;
+
+    let mut edges: Vec<(usize, usize)> = simplifier.triangles.iter()
+        .flat_map(|tri| (0..3).map(move |k| (tri[k], tri[(k + 1) % 3])))
+        .map(|(a, b)| if a < b { (a, b) } else { (b, a) })
+        .collect();
+    edges.sort();
+    edges.dedup();
+    let mut heap: BinaryHeap<Collapse> = edges.into_iter().map(|(a, b)| simplifier.candidate(a, b)).collect();
+
+    while simplifier.alive_count > target {
+        let collapse = match heap.pop() {
+            Some(collapse) => collapse,
+            None => break,
+        };
+        if collapse.keep_version != simplifier.versions[collapse.keep]
+            || collapse.remove_version != simplifier.versions[collapse.remove] {
+            continue;
+        }
+        if !simplifier.can_collapse(&collapse) {
+            continue;
+        }
+
+        simplifier.collapse(&collapse);
+        for neighbour in simplifier.neighbours(collapse.keep) {
+            heap.push(simplifier.candidate(collapse.keep, neighbour));
+        }
+    }
+
+    simplifier.finish()
+}
+
+mod geometry_tests {
+    use super::{
+        compute_normals, compute_tangents, compute_unindexed_normals, compute_unindexed_tangents, heightfield_grid,
+        points_aabb, points_bounding_sphere, shared_vertex_indices, simplify, simplify_with_uvs, weld_vertices, SimplifiedMesh, WeldReport,
+    };
+    use graphics_math as math;
+
+    use std::collections::HashMap;
+
+    // A unit quad in the xy-plane facing +z, with texture coordinates matching x and y.
+    const QUAD_POSITIONS: [f32; 12] = [0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 1.0, 1.0, 0.0, 0.0, 1.0, 0.0];
+    const QUAD_NORMALS: [f32; 12] = [0.0, 0.0, 1.0, 0.0, 0.0, 1.0, 0.0, 0.0, 1.0, 0.0, 0.0, 1.0];
+    const QUAD_INDICES: [u32; 6] = [0, 1, 2, 0, 2, 3];
+
+    #[test]
+    fn test_tangents_follow_increasing_u() {
+        let uvs = [0.0, 0.0, 1.0, 0.0, 1.0, 1.0, 0.0, 1.0];
+        let tangents = compute_tangents(&QUAD_POSITIONS, &uvs, &QUAD_NORMALS, &QUAD_INDICES);
+
+        for v_i in 0..4 {
+            assert_eq!(&tangents[4 * v_i..4 * v_i + 4], &[1.0, 0.0, 0.0, 1.0]);
+        }
+    }
+
+    #[test]
+    fn test_mirrored_texture_coordinates_flip_handedness() {
+        let uvs = [1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 1.0, 1.0];
+        let tangents = compute_tangents(&QUAD_POSITIONS, &uvs, &QUAD_NORMALS, &QUAD_INDICES);
+
+        for v_i in 0..4 {
+            assert_eq!(&tangents[4 * v_i..4 * v_i + 4], &[-1.0, 0.0, 0.0, -1.0]);
+        }
+    }
+
+    #[test]
+    fn test_shared_vertex_indices_weld_identical_vertices() {
+        let positions = [0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 1.0, 1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 1.0, 0.0, 0.0, 1.0, 0.0];
+        let uvs = [0.0, 0.0, 1.0, 0.0, 1.0, 1.0, 0.0, 0.0, 1.0, 1.0, 0.0, 1.0];
+        let normals = [0.0, 0.0, 1.0, 0.0, 0.0, 1.0, 0.0, 0.0, 1.0, 0.0, 0.0, 1.0, 0.0, 0.0, 1.0, 0.0, 0.0, 1.0];
+        let indices = shared_vertex_indices(&positions, &uvs, &normals);
+
+        assert_eq!(indices, vec![0, 1, 2, 0, 2, 5]);
+
+        let tangents = compute_unindexed_tangents(&positions, &uvs, &normals);
+        for v_i in 0..6 {
+            assert_eq!(&tangents[4 * v_i..4 * v_i + 4], &[1.0, 0.0, 0.0, 1.0]);
+        }
+    }
+
+    #[test]
+    fn test_shared_vertices_average_their_tangents() {
+        // Two triangles meeting at vertex 0, whose texture coordinates run along
+        // the x-axis in one and the y-axis in the other.
+        let positions = [0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0, -1.0, 0.0, 0.0, 0.0, -1.0, 0.0];
+        let uvs = [0.0, 0.0, 1.0, 0.0, 0.0, 1.0, 0.0, 1.0, -1.0, 0.0];
+        let normals = [0.0, 0.0, 1.0, 0.0, 0.0, 1.0, 0.0, 0.0, 1.0, 0.0, 0.0, 1.0, 0.0, 0.0, 1.0];
+        let indices = [0, 1, 2, 0, 3, 4];
+        let tangents = compute_tangents(&positions, &uvs, &normals, &indices);
+        let expected = f32::sqrt(0.5);
+
+        assert!(f32::abs(tangents[0] - expected) < 1e-6);
+        assert!(f32::abs(tangents[1] - expected) < 1e-6);
+        assert_eq!(tangents[2], 0.0);
+    }
+
+    #[test]
+    fn test_flat_heightfield_grid_is_centred_and_faces_up() {
+        let grid = heightfield_grid(&[2.0; 6], 3, 2, 0.5);
+
+        assert_eq!(grid.positions.len(), 18);
+        assert_eq!(&grid.positions[0..3], &[-0.5, 2.0, -0.25]);
+        assert_eq!(&grid.positions[15..18], &[0.5, 2.0, 0.25]);
+        assert_eq!(&grid.uvs[10..12], &[1.0, 1.0]);
+        for v_i in 0..6 {
+            assert_eq!(&grid.normals[3 * v_i..3 * v_i + 3], &[0.0, 1.0, 0.0]);
+        }
+
+        // Two triangles per cell, each wound counter-clockwise seen from above.
+        assert_eq!(grid.indices.len(), 12);
+        for tri in grid.indices.chunks(3) {
+            let corner = |k: usize| {
+                let v_i = tri[k] as usize;
+                math::vec3((grid.positions[3 * v_i], grid.positions[3 * v_i + 1], grid.positions[3 * v_i + 2]))
+            };
+            let normal = (corner(1) - corner(0)).cross(&(corner(2) - corner(0)));
+            assert!(normal.v[1] > 0.0);
+        }
+    }
+
+    #[test]
+    fn test_heightfield_normals_lean_away_from_the_slope() {
+        // Heights rising by one for each step along x.
+        let heights = [0.0, 1.0, 2.0, 0.0, 1.0, 2.0];
+        let grid = heightfield_grid(&heights, 3, 2, 1.0);
+        let expected = f32::sqrt(0.5);
+
+        for v_i in 0..6 {
+            assert!(f32::abs(grid.normals[3 * v_i] + expected) < 1e-6);
+            assert!(f32::abs(grid.normals[3 * v_i + 1] - expected) < 1e-6);
+            assert_eq!(grid.normals[3 * v_i + 2], 0.0);
+        }
+    }
+
+    // Two triangles folded along the x-axis: one flat on the ground facing up,
+    // and one standing up facing +z. Vertices 0 and 1 are on the fold.
+    const FOLD_POSITIONS: [f32; 12] = [0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, -1.0, 0.0, 1.0, 0.0];
+    const FOLD_INDICES: [u32; 6] = [0, 1, 2, 0, 1, 3];
+
+    #[test]
+    fn test_a_crease_sharper_than_the_smoothing_angle_stays_hard() {
+        let normals = compute_normals(&FOLD_POSITIONS, &FOLD_INDICES, 60.0);
+
+        assert_eq!(normals.len(), 18);
+        for corner in 0..3 {
+            assert_eq!(&normals[3 * corner..3 * corner + 3], &[0.0, 1.0, 0.0]);
+        }
+        for corner in 3..6 {
+            assert_eq!(&normals[3 * corner..3 * corner + 3], &[0.0, 0.0, 1.0]);
+        }
+    }
+
+    #[test]
+    fn test_a_gentle_crease_is_smoothed_over() {
+        let normals = compute_normals(&FOLD_POSITIONS, &FOLD_INDICES, 100.0);
+        let expected = f32::sqrt(0.5);
+
+        // the corners on the fold lean halfway between the two faces, and the
+        // corners off it keep their own face's normal
+        for &corner in [0, 1, 3, 4].iter() {
+            assert!(f32::abs(normals[3 * corner] - 0.0) < 1e-6);
+            assert!(f32::abs(normals[3 * corner + 1] - expected) < 1e-6);
+            assert!(f32::abs(normals[3 * corner + 2] - expected) < 1e-6);
+        }
+        assert_eq!(&normals[6..9], &[0.0, 1.0, 0.0]);
+        assert_eq!(&normals[15..18], &[0.0, 0.0, 1.0]);
+    }
+
+    #[test]
+    fn test_bigger_triangles_pull_harder_on_smooth_normals() {
+        // the standing triangle from the fold, made four times as tall
+        let positions = [0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, -1.0, 0.0, 4.0, 0.0];
+        let normals = compute_normals(&positions, &FOLD_INDICES, 100.0);
+
+        // the same angle at vertex 0, but four times the area
+        assert!(normals[2] > normals[1]);
+    }
+
+    #[test]
+    fn test_unindexed_normals_find_the_triangles_that_touch() {
+        // the fold again, with the two vertices on it repeated
+        let positions = [
+            0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, -1.0,
+            0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0,
+        ];
+        let normals = compute_unindexed_normals(&positions, 100.0);
+        let indexed = compute_normals(&FOLD_POSITIONS, &FOLD_INDICES, 100.0);
+
+        assert_eq!(normals, indexed);
+    }
+
+    #[test]
+    fn test_the_bounds_of_a_heightfield_grid_take_in_every_point() {
+        let heights = [0.0, 1.0, 2.0, 0.5, -1.0, 0.0];
+        let grid = heightfield_grid(&heights, 3, 2, 1.0);
+        let aabb = points_aabb(&grid.positions);
+        let sphere = points_bounding_sphere(&grid.positions);
+
+        assert_eq!(aabb.min, math::vec3((-1.0, -1.0, -0.5)));
+        assert_eq!(aabb.max, math::vec3((1.0, 2.0, 0.5)));
+        for v_i in 0..6 {
+            let p = math::vec3((grid.positions[3 * v_i], grid.positions[3 * v_i + 1], grid.positions[3 * v_i + 2]));
+            assert!(aabb.contains(&p));
+            assert!((p - sphere.centre).norm() <= sphere.radius + 1e-5);
+        }
+    }
+
+    #[test]
+    fn test_no_points_give_empty_bounds_at_the_origin() {
+        assert_eq!(points_aabb(&[]).half_extents(), math::vec3((0.0, 0.0, 0.0)));
+        assert_eq!(points_bounding_sphere(&[]).radius, 0.0);
+    }
+
+    ///
+    /// A sphere of radius one made by splitting each face of an octahedron into
+    /// four `subdivisions` times, with shared vertices and the faces wound outwards.
+    ///
+    fn octahedron_sphere(subdivisions: usize) -> (Vec<f32>, Vec<u32>) {
+        let mut points = vec![
+            math::vec3((1.0, 0.0, 0.0)), math::vec3((-1.0, 0.0, 0.0)),
+            math::vec3((0.0, 1.0, 0.0)), math::vec3((0.0, -1.0, 0.0)),
+            math::vec3((0.0, 0.0, 1.0)), math::vec3((0.0, 0.0, -1.0)),
+        ];
+        let mut triangles = vec![];
+        for &x in [0, 1].iter() {
+            for &y in [2, 3].iter() {
+                for &z in [4, 5].iter() {
+                    // each axis turned around flips the winding
+                    let flips = (x == 1) as usize + (y == 3) as usize + (z == 5) as usize;
+                    triangles.push(if flips % 2 == 0 { [x, y, z] } else { [x, z, y] });
+                }
+            }
+        }
+
+        for _ in 0..subdivisions {
+            let mut middles: HashMap<(usize, usize), usize> = HashMap::new();
+            let mut split = vec![];
+            for tri in triangles.iter() {
+                let mut middle = |a: usize, b: usize| {
+                    let key = if a < b { (a, b) } else { (b, a) };
+                    if let Some(&m) = middles.get(&key) {
+                        return m;
+                    }
+                    points.push(((points[a] + points[b]) * 0.5).normalize());
+                    middles.insert(key, points.len() - 1);
+                    points.len() - 1
+                };
+                let (ab, bc, ca) = (middle(tri[0], tri[1]), middle(tri[1], tri[2]), middle(tri[2], tri[0]));
+                split.extend_from_slice(&[[tri[0], ab, ca], [ab, tri[1], bc], [ca, bc, tri[2]], [ab, bc, ca]]);
+            }
+            triangles = split;
+        }
+
+        let positions = points.iter().flat_map(|p| p.v.to_vec()).collect();
+        let indices = triangles.iter().flat_map(|tri| tri.iter().map(|&v_i| v_i as u32)).collect();
+
+        (positions, indices)
+    }
+
+    ///
+    /// Check that every triangle has three different vertices that exist, and
+    /// that each edge is crossed exactly once each way, so the surface is closed
+    /// with its triangles all wound the same way.
+    ///
+    fn assert_closed_surface(mesh: &SimplifiedMesh) {
+        let vertex_count = (mesh.positions.len() / 3) as u32;
+        let mut directed_edges: HashMap<(u32, u32), usize> = HashMap::new();
+        for tri in mesh.indices.chunks(3) {
+            assert!(tri.iter().all(|&v_i| v_i < vertex_count));
+            assert!(tri[0] != tri[1] && tri[1] != tri[2] && tri[2] != tri[0]);
+            for k in 0..3 {
+                *directed_edges.entry((tri[k], tri[(k + 1) % 3])).or_insert(0) += 1;
+            }
+        }
+        for (&(a, b), &uses) in directed_edges.iter() {
+            assert_eq!(uses, 1);
+            assert_eq!(directed_edges.get(&(b, a)), Some(&1));
+        }
+
+        // a closed surface with no handles has V - E + F = 2
+        let edge_count = directed_edges.len() / 2;
+        let triangle_count = mesh.indices.len() / 3;
+        assert_eq!(vertex_count as usize + triangle_count, edge_count + 2);
+    }
+
+    #[test]
+    fn test_simplifying_a_sphere_meets_the_target_and_keeps_it_closed() {
+        let (positions, indices) = octahedron_sphere(3);
+        assert_eq!(indices.len() / 3, 512);
+
+        for &ratio in [0.5, 0.25, 0.1].iter() {
+            let simplified = simplify(&positions, &indices, ratio);
+            let target = f32::ceil(ratio * 512.0) as usize;
+            let triangle_count = simplified.indices.len() / 3;
+            // each collapse takes away two triangles
+            assert!(triangle_count <= target && triangle_count + 2 > target);
+            assert_closed_surface(&simplified);
+
+            for p in simplified.positions.chunks(3) {
+                let radius = f32::sqrt(p[0] * p[0] + p[1] * p[1] + p[2] * p[2]);
+                assert!(f32::abs(radius - 1.0) < 0.2);
+            }
+        }
+    }
+
+    #[test]
+    fn test_a_ratio_of_one_leaves_the_mesh_as_it_is() {
+        let (positions, indices) = octahedron_sphere(1);
+        let simplified = simplify(&positions, &indices, 1.0);
+
+        assert_eq!(simplified.positions, positions);
+        assert_eq!(simplified.indices, indices);
+    }
+
+    #[test]
+    fn test_simplifying_a_flat_grid_keeps_it_flat_and_keeps_its_outline() {
+        let grid = heightfield_grid(&[0.0; 81], 9, 9, 1.0);
+        let simplified = simplify(&grid.positions, &grid.indices, 0.1);
+
+        assert!(simplified.indices.len() / 3 <= 13);
+        assert_eq!(points_aabb(&simplified.positions), points_aabb(&grid.positions));
+        for tri in simplified.indices.chunks(3) {
+            let p = |k: usize| math::vec3((
+                simplified.positions[3 * tri[k] as usize],
+                simplified.positions[3 * tri[k] as usize + 1],
+                simplified.positions[3 * tri[k] as usize + 2],
+            ));
+            for k in 0..3 {
+                assert!(f32::abs(p(k).v[1]) < 1e-5);
+            }
+            // still facing up
+            assert!((p(1) - p(0)).cross(&(p(2) - p(0))).v[1] > 0.0);
+        }
+    }
+
+    ///
+    /// Texture coordinates for each corner of a 9 by 9 flat grid, matching its
+    /// positions. With a seam, the triangles right of the middle column are moved
+    /// along by 1 in u.
+    ///
+    fn grid_corner_uvs(positions: &[f32], indices: &[u32], seam: bool) -> Vec<f32> {
+        let mut corner_uvs = vec![];
+        for tri in indices.chunks(3) {
+            let right = tri.iter().all(|&v_i| positions[3 * v_i as usize] >= 0.0);
+            for &v_i in tri.iter() {
+                let (x, z) = (positions[3 * v_i as usize], positions[3 * v_i as usize + 2]);
+                let u = (x + 4.0) / 8.0 + if seam && right { 1.0 } else { 0.0 };
+                corner_uvs.extend_from_slice(&[u, (z + 4.0) / 8.0]);
+            }
+        }
+
+        corner_uvs
+    }
+
+    #[test]
+    fn test_texture_coordinates_follow_the_collapses() {
+        let grid = heightfield_grid(&[0.0; 81], 9, 9, 1.0);
+        let corner_uvs = grid_corner_uvs(&grid.positions, &grid.indices, false);
+        let simplified = simplify_with_uvs(&grid.positions, &grid.indices, &corner_uvs, 0.1);
+
+        assert!(simplified.indices.len() / 3 <= 13);
+        assert_eq!(simplified.corner_uvs, grid_corner_uvs(&simplified.positions, &simplified.indices, false));
+        assert!(simplify(&grid.positions, &grid.indices, 0.1).corner_uvs.is_empty());
+    }
+
+    #[test]
+    fn test_vertices_on_a_texture_seam_stay_put() {
+        let grid = heightfield_grid(&[0.0; 81], 9, 9, 1.0);
+        let corner_uvs = grid_corner_uvs(&grid.positions, &grid.indices, true);
+        let simplified = simplify_with_uvs(&grid.positions, &grid.indices, &corner_uvs, 0.1);
+
+        let seam_vertices = simplified.positions.chunks(3).filter(|p| p[0] == 0.0).count();
+        assert_eq!(seam_vertices, 9);
+        assert_eq!(simplified.corner_uvs, grid_corner_uvs(&simplified.positions, &simplified.indices, true));
+    }
+
+    ///
+    /// A unit cube as a triangle soup of 12 triangles, with a flat normal on
+    /// each face.
+    ///
+    fn cube_soup() -> (Vec<f32>, Vec<f32>) {
+        let corner = |i: usize| [(i & 1) as f32, ((i >> 1) & 1) as f32, ((i >> 2) & 1) as f32];
+        let faces: [([usize; 4], [f32; 3]); 6] = [
+            ([0, 2, 6, 4], [-1.0, 0.0, 0.0]), ([1, 5, 7, 3], [1.0, 0.0, 0.0]),
+            ([0, 4, 5, 1], [0.0, -1.0, 0.0]), ([2, 3, 7, 6], [0.0, 1.0, 0.0]),
+            ([0, 1, 3, 2], [0.0, 0.0, -1.0]), ([4, 6, 7, 5], [0.0, 0.0, 1.0]),
+        ];
+        let mut positions = vec![];
+        let mut normals = vec![];
+        for &(quad, normal) in faces.iter() {
+            for &k in [0, 1, 2, 0, 2, 3].iter() {
+                positions.extend_from_slice(&corner(quad[k]));
+                normals.extend_from_slice(&normal);
+            }
+        }
+
+        (positions, normals)
+    }
+
+    #[test]
+    fn test_welding_keeps_hard_edges_apart() {
+        let (positions, normals) = cube_soup();
+        let by_position = weld_vertices(&positions, &[], 1e-5);
+        let by_position_and_normal = weld_vertices(&positions, &[(&normals, 3)], 1e-5);
+
+        assert_eq!(by_position.vertex_count(), 8);
+        // each corner of the cube is on three faces with different normals
+        assert_eq!(by_position_and_normal.vertex_count(), 24);
+        assert_eq!(by_position_and_normal.indices.len(), 36);
+        assert_eq!(by_position_and_normal.report().vertices_before, 36);
+        assert!(f32::abs(by_position_and_normal.report().reduction() - 1.0 / 3.0) < 1e-6);
+    }
+
+    #[test]
+    fn test_welded_vertices_rebuild_the_soup() {
+        let (positions, normals) = cube_soup();
+        let welded = weld_vertices(&positions, &[(&normals, 3)], 1e-5);
+        let welded_positions = welded.gather(&positions, 3);
+        let welded_normals = welded.gather(&normals, 3);
+
+        for (v_i, &index) in welded.indices.iter().enumerate() {
+            let w_i = index as usize;
+            assert_eq!(&welded_positions[3 * w_i..3 * w_i + 3], &positions[3 * v_i..3 * v_i + 3]);
+            assert_eq!(&welded_normals[3 * w_i..3 * w_i + 3], &normals[3 * v_i..3 * v_i + 3]);
+        }
+        // an attribute the soup does not have stays missing
+        assert!(welded.gather(&[], 2).is_empty());
+    }
+
+    #[test]
+    fn test_only_vertices_within_epsilon_are_welded() {
+        let positions = [
+            0.0, 0.0, 0.0,  1.0, 0.0, 0.0,  0.0, 1.0, 0.0,
+            // a hair away from the first triangle's corners, across a cell boundary
+            -0.0004, 0.0, 0.0,  1.0, 0.0004, 0.0,  0.0, 1.01, 0.0,
+        ];
+        let welded = weld_vertices(&positions, &[], 1e-3);
+
+        assert_eq!(welded.indices, vec![0, 1, 2, 0, 1, 3]);
+        assert_eq!(welded.report(), WeldReport { vertices_before: 6, vertices_after: 4 });
+    }
+}
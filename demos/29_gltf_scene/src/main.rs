@@ -0,0 +1,352 @@
+extern crate gl;
+extern crate glfw;
+extern crate chrono;
+extern crate stb_image;
+#[cfg(feature = "assimp")]
+extern crate assimp;
+#[cfg(feature = "gltf")]
+extern crate gltf;
+#[cfg(feature = "glam")]
+extern crate glam;
+#[cfg(feature = "cgmath")]
+extern crate cgmath;
+extern crate ron;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+extern crate serde_json;
+
+#[macro_use]
+mod gl_utils;
+mod graphics_math;
+mod obj_parser;
+mod colour;
+mod assets;
+mod shader_include;
+mod spirv;
+mod program_cache;
+mod logger;
+mod material;
+mod model;
+mod mesh_cache;
+mod resource_cache;
+mod renderer;
+mod geometry;
+mod vertex_layout;
+mod vertex_packing;
+mod scene_file;
+mod skybox;
+
+
+use glfw::{Action, Context, Key};
+use gl::types::{GLfloat};
+
+use std::collections::HashMap;
+use std::env;
+use std::process;
+
+use gl_utils::*;
+use logger::Logger;
+
+use colour::Colour;
+use graphics_math as math;
+use math::Mat4;
+
+use model::Model;
+use renderer::{MeshHandle, ProgramHandle, Renderer};
+use scene_file::SceneFile;
+
+const GL_LOG_FILE: &str = "gl.log";
+const VERTEX_SHADER_FILE: &str = "src/test.vert.glsl";
+const FRAGMENT_SHADER_FILE: &str = "src/test.frag.glsl";
+const SCENE_FILE: &str = "src/scene.ron";
+
+
+///
+/// Write the node hierarchy of a model to the log, one node per line, indented
+/// by depth.
+///
+fn log_node_hierarchy(logger: &Logger, model: &Model, parent: Option<usize>, depth: usize) {
+    for (node_i, node) in model.nodes.iter().enumerate() {
+        if node.parent == parent {
+            let mut triangles = 0;
+            for &group_i in node.groups.iter() {
+                triangles += model.groups[group_i].count / 3;
+            }
+            logger.log(&format!("{}{} ({} triangles)", "  ".repeat(depth), node.name, triangles));
+            log_node_hierarchy(logger, model, Some(node_i), depth + 1);
+        }
+    }
+}
+
+///
+/// Set the scene's lights and the camera matrices in the shader programme, as
+/// at the start and again whenever the programme has been made again.
+///
+fn set_scene_uniforms(
+    renderer: &Renderer, programme: ProgramHandle, scene: &SceneFile, view_mat: &Mat4, proj_mat: &Mat4
+) {
+    renderer.set_uniform_i32(programme, "light_count", scene.lights.len() as i32);
+    for (i, light) in scene.lights.iter().enumerate() {
+        renderer.set_uniform_vec3(programme, &format!("light_position_world[{}]", i), &light.position);
+        renderer.set_uniform_vec3(programme, &format!("La[{}]", i), &light.ambient);
+        renderer.set_uniform_vec3(programme, &format!("Ld[{}]", i), &light.diffuse);
+        renderer.set_uniform_vec3(programme, &format!("Ls[{}]", i), &light.specular);
+    }
+    renderer.set_uniform_mat4(programme, "view", view_mat);
+    renderer.set_uniform_mat4(programme, "proj", proj_mat);
+}
+
+fn main() {
+    let logger = restart_gl_log(GL_LOG_FILE);
+    // Start a GL context and O/S window using the GLFW helper library.
+    let mut context = match start_gl(&logger) {
+        Ok(val) => val,
+        Err(e) => {
+            eprintln!("Failed to Initialize OpenGL context. Got error:");
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+    };
+
+    // Tell GL to only draw onto a pixel if the shape is closer to the viewer.
+    unsafe {
+        // Enable depth testing.
+        gl::Enable(gl::DEPTH_TEST);
+        // Depth testing interprets a smaller value as closer to the eye.
+        gl::DepthFunc(gl::LESS);
+    }
+
+    // the scene file to draw can be given on the command line.
+    let scene_path = env::args().nth(1).unwrap_or(String::from(SCENE_FILE));
+    let scene = match SceneFile::load(&scene_path) {
+        Ok(val) => val,
+        Err(e) => {
+            logger.log_err(&format!("ERROR: loading scene file {}. Loader returned error\n{}", scene_path, e));
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+    };
+    logger.log(&format!(
+        "loaded {} with {} objects, {} materials and {} lights",
+        scene_path, scene.objects.len(), scene.materials.len(), scene.lights.len()
+    ));
+
+    // the renderer owns every GL object the demo draws with, and hands out
+    // handles to them.
+    let mut renderer = Renderer::new();
+
+    /*------------------------------CREATE GEOMETRY------------------------------*/
+    // each mesh file is loaded once, however many objects use it.
+    let mut meshes: HashMap<String, MeshHandle> = HashMap::new();
+    let mut object_meshes = vec![];
+    for object in scene.objects.iter() {
+        if let Some(&mesh) = meshes.get(&object.mesh) {
+            object_meshes.push(mesh);
+            continue;
+        }
+        let mesh = match renderer.load_model(&object.mesh) {
+            Ok(val) => val,
+            Err(e) => {
+                logger.log_err(&format!("ERROR: loading mesh file {}. Loader returned error\n{}", object.mesh, e));
+                logger.log_err("WARNING: using the embedded fallback mesh instead.");
+                renderer.upload_model(Model::from_obj_mesh(assets::fallback_mesh(), vec![]))
+            }
+        };
+        {
+            let model = renderer.model(mesh);
+            logger.log(&format!("loaded {} with {} points in {} nodes", object.mesh, model.point_count, model.nodes.len()));
+            log_node_hierarchy(&logger, model, None, 1);
+            logger.log(&format!("{}: {}", object.mesh, renderer.weld_report(mesh).describe()));
+        }
+        meshes.insert(object.mesh.clone(), mesh);
+        object_meshes.push(mesh);
+    }
+
+    // the scene's own materials, by name.
+    let mut materials = HashMap::new();
+    for material in scene.materials.iter() {
+        let handle = renderer.add_material(material.to_material());
+        materials.insert(material.name.clone(), handle);
+    }
+
+    let skybox = match scene.skybox {
+        Some(ref desc) => match desc.to_skybox(&logger) {
+            Ok(val) => Some(val),
+            Err(e) => {
+                logger.log_err(&format!("{}\nWARNING: drawing the scene without its sky box.", e));
+                None
+            }
+        },
+        None => None,
+    };
+
+    let shader_programme = renderer.create_program(&logger, VERTEX_SHADER_FILE, FRAGMENT_SHADER_FILE);
+    let stats = renderer.resources().stats();
+    logger.log(&format!(
+        "resources: {} textures, {} meshes, {} programmes loaded; {} loads shared",
+        stats.textures, stats.meshes, stats.programs, stats.hits
+    ));
+    logger.log("keys: Alt+Enter toggles fullscreen and makes every GL object again");
+
+    // input variables
+    let near = scene.camera.near;                    // clipping plane
+    let far = scene.camera.far;                      // clipping plane
+    let fov = scene.camera.fovy;                     // field of view in degrees
+    let aspect = context.width as f32 / context.height as f32; // aspect ratio
+    let mut proj_mat = Mat4::perspective(fov, aspect, near, far);
+
+    // matrix components
+    let cam_speed: GLfloat = 1.0;             // 1 unit per second
+    let cam_yaw_speed: GLfloat = 10.0;        // 10 degrees per second
+    let mut cam_pos: [GLfloat; 3] = scene.camera.position;
+    let mut cam_yaw: GLfloat = scene.camera.yaw;  // y-rotation in degrees
+    let mut mat_trans = Mat4::from_translation(&math::vec3((-cam_pos[0], -cam_pos[1], -cam_pos[2])));
+    let mut mat_rot = Mat4::from_rotation_y_deg(-cam_yaw);
+    let mut view_mat = mat_rot * mat_trans;
+    let mut fullscreen_key_was_down = false;
+
+    set_scene_uniforms(&renderer, shader_programme, &scene, &view_mat, &proj_mat);
+
+    let background = Colour::from(scene.background);
+    unsafe {
+        // Cull face.
+        gl::Enable(gl::CULL_FACE);
+        // Cull back face.
+        gl::CullFace(gl::BACK);
+        // GL_CW for clockwise.    
+        gl::FrontFace(gl::CCW);
+        gl::ClearColor(background.r, background.g, background.b, background.a);
+    }
+
+    while !context.window.should_close() {
+        let current_seconds = context.glfw.get_time();
+        let elapsed_seconds = current_seconds - context.elapsed_time_seconds;
+        context.elapsed_time_seconds = current_seconds;
+
+        update_fps_counter(&mut context);
+        unsafe {
+            // Wipe the drawing surface clear.
+            gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
+            gl::Viewport(0, 0, context.width as i32, context.height as i32);
+        }
+        // The sky box goes first, behind everything.
+        if let Some(ref skybox) = skybox {
+            unsafe {
+                gl::Disable(gl::CULL_FACE);
+            }
+            skybox.draw(&view_mat, &proj_mat);
+            unsafe {
+                gl::Enable(gl::CULL_FACE);
+            }
+        }
+        for (object, &mesh) in scene.objects.iter().zip(object_meshes.iter()) {
+            renderer.set_uniform_mat4(shader_programme, "model", &object.model_mat());
+            match object.material {
+                Some(ref name) => renderer.draw_with_material(shader_programme, mesh, materials[name]),
+                // Draw each material group with its own material.
+                None => renderer.draw(shader_programme, mesh),
+            }
+        }
+
+        context.glfw.poll_events();
+
+        // control keys
+        let mut cam_moved = false;
+        match context.window.get_key(Key::A) {
+            Action::Press | Action::Repeat => {
+                cam_pos[0] -= cam_speed * (elapsed_seconds as GLfloat);
+                cam_moved = true;
+            }
+            _ => {}
+        }
+        match context.window.get_key(Key::D) {
+            Action::Press | Action::Repeat => {
+                cam_pos[0] += cam_speed * (elapsed_seconds as GLfloat);
+                cam_moved = true;
+            }
+            _ => {}
+        }
+        match context.window.get_key(Key::Up) {
+            Action::Press | Action::Repeat => {
+                cam_pos[1] += cam_speed * (elapsed_seconds as GLfloat);
+                cam_moved = true;
+            }
+            _ => {}
+        }
+        match context.window.get_key(Key::Down) {
+            Action::Press | Action::Repeat => {
+                cam_pos[1] -= cam_speed * (elapsed_seconds as GLfloat);
+                cam_moved = true;
+            }
+            _ => {}
+        }
+        match context.window.get_key(Key::W) {
+            Action::Press | Action::Repeat => {
+                cam_pos[2] -= cam_speed * (elapsed_seconds as GLfloat);
+                cam_moved = true;
+            }
+            _ => {}
+        }
+        match context.window.get_key(Key::S) {
+            Action::Press | Action::Repeat => {
+                cam_pos[2] += cam_speed * (elapsed_seconds as GLfloat);
+                cam_moved = true;
+            }
+            _ => {}
+        }
+        match context.window.get_key(Key::Left) {
+            Action::Press | Action::Repeat => {
+                cam_yaw += cam_yaw_speed * (elapsed_seconds as GLfloat);
+                cam_moved = true;
+            }
+            _ => {}
+        }
+        match context.window.get_key(Key::Right) {
+            Action::Press | Action::Repeat => {
+                cam_yaw -= cam_yaw_speed * (elapsed_seconds as GLfloat);
+                cam_moved = true;
+            }
+            _ => {}
+        }
+        // update view matrix
+        if cam_moved {
+            mat_trans = Mat4::from_translation(&math::vec3((-cam_pos[0], -cam_pos[1], -cam_pos[2]))); // cam translation
+            mat_rot = Mat4::from_rotation_y_deg(-cam_yaw);
+            view_mat = mat_rot * mat_trans;
+            renderer.set_uniform_mat4(shader_programme, "view", &view_mat);
+        }
+        // Alt+Enter switches between a window and fullscreen, and makes every GL
+        // object again afterwards, as a context recreated for the new window needs
+        let alt_held = context.window.get_key(Key::LeftAlt) != Action::Release
+            || context.window.get_key(Key::RightAlt) != Action::Release;
+        match context.window.get_key(Key::Enter) {
+            Action::Press if alt_held => {
+                if !fullscreen_key_was_down {
+                    let fullscreen = context.toggle_fullscreen();
+                    logger.log(&format!("fullscreen: {}", fullscreen));
+                    let (width, height) = context.window.get_framebuffer_size();
+                    context.width = width as u32;
+                    context.height = height as u32;
+                    proj_mat = Mat4::perspective(fov, width as f32 / height as f32, near, far);
+                    let failures = renderer.reload(&logger);
+                    set_scene_uniforms(&renderer, shader_programme, &scene, &view_mat, &proj_mat);
+                    logger.log(&format!("reloaded every GL object, {} failed", failures));
+                }
+                fullscreen_key_was_down = true;
+            }
+            _ => {
+                fullscreen_key_was_down = false;
+            }
+        }
+
+        match context.window.get_key(Key::Escape) {
+            Action::Press | Action::Repeat => {
+                context.window.set_should_close(true);
+            }
+            _ => {}
+        }
+        // Put the stuff we've been drawing onto the display.
+        context.window.swap_buffers();
+    }
+}
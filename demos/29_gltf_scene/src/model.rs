@@ -0,0 +1,938 @@
+use graphics_math as math;
+use math::{Mat4, Vec3};
+
+use geometry;
+use geometry::{Aabb, BoundingSphere};
+use mesh_cache;
+use obj_parser;
+use obj_parser::{Material, MaterialGroup, ObjMesh};
+use vertex_layout::VertexLayout;
+use vertex_packing;
+
+#[cfg(feature = "assimp")]
+use assimp::import as ai;
+
+#[cfg(feature = "gltf")]
+use gltf;
+#[cfg(feature = "gltf")]
+use colour::Colour;
+
+use std::path::Path;
+
+
+///
+/// A `Model` is a mesh ready to be copied into vertex buffers, together with
+/// the materials its groups refer to. It is the same whichever loader produced
+/// it. Every attribute is stored per vertex and the vertices are not indexed,
+/// so the model can be drawn with `glDrawArrays`.
+///
+/// The model's bounding box and bounding sphere are worked out once, when it is
+/// made, for culling, picking, and framing it in view. Anything that moves its
+/// points has to call `update_bounds` afterwards.
+///
+#[derive(Clone, Debug, PartialEq)]
+pub struct Model {
+    pub point_count: usize,
+    pub points: Vec<f32>,
+    pub tex_coords: Vec<f32>,
+    pub normals: Vec<f32>,
+    /// Four components per vertex: the tangent, and the handedness of the
+    /// tangent space in `w`. Empty if the model has no texture coordinates.
+    pub tangents: Vec<f32>,
+    pub groups: Vec<MaterialGroup>,
+    pub materials: Vec<Material>,
+    /// The scene graph the model was built from. Only glTF files have one;
+    /// the other loaders leave it empty.
+    pub nodes: Vec<ModelNode>,
+    aabb: Aabb,
+    bounding_sphere: BoundingSphere,
+}
+
+///
+/// A node in a model's scene graph. The vertices of a node's mesh have already
+/// been transformed into model space, so the hierarchy is only needed by demos
+/// that want to animate parts of a model on their own.
+///
+#[derive(Clone, Debug, PartialEq)]
+pub struct ModelNode {
+    pub name: String,
+    /// The index of the parent node in `Model::nodes`, or `None` for a root node.
+    pub parent: Option<usize>,
+    /// The node's transform relative to its parent.
+    pub transform: Mat4,
+    /// The indices into `Model::groups` of the node's mesh.
+    pub groups: Vec<usize>,
+}
+
+///
+/// One vertex of a model with all of its attributes side by side, so a whole
+/// model fits in a single vertex buffer and each vertex is read from one place.
+/// Attributes a model does not have are left at zero.
+///
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Vertex {
+    pub pos: [f32; 3],
+    pub normal: [f32; 3],
+    pub uv: [f32; 2],
+    pub tangent: [f32; 4],
+}
+
+///
+/// A vertex type that describes its own layout, so the code setting up vertex
+/// attribute pointers reads the offsets and stride from the type rather than
+/// repeating them by hand.
+///
+pub trait VertexFormat: Copy {
+    fn layout() -> VertexLayout;
+}
+
+impl VertexFormat for Vertex {
+    fn layout() -> VertexLayout {
+        VertexLayout::new().floats(0, 3).floats(1, 3).floats(2, 2).floats(3, 4)
+    }
+}
+
+///
+/// A model vertex in half the memory of a `Vertex`. The normal and tangent are
+/// packed into 10 bits per component with the tangent's handedness in the top 2
+/// bits, and the texture coordinates are half floats. The position stays a full
+/// float, since rounding it would move the surface.
+///
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct PackedVertex {
+    pub pos: [f32; 3],
+    pub normal: u32,
+    pub uv: [u16; 2],
+    pub tangent: u32,
+}
+
+impl PackedVertex {
+    pub fn pack(vertex: &Vertex) -> PackedVertex {
+        let n = vertex.normal;
+        PackedVertex {
+            pos: vertex.pos,
+            normal: vertex_packing::pack_snorm_2_10_10_10_rev([n[0], n[1], n[2], 0.0]),
+            uv: [vertex_packing::f32_to_half(vertex.uv[0]), vertex_packing::f32_to_half(vertex.uv[1])],
+            tangent: vertex_packing::pack_snorm_2_10_10_10_rev(vertex.tangent),
+        }
+    }
+
+    ///
+    /// The vertex the GPU sees when it reads this one back.
+    ///
+    pub fn unpack(&self) -> Vertex {
+        let n = vertex_packing::unpack_snorm_2_10_10_10_rev(self.normal);
+        Vertex {
+            pos: self.pos,
+            normal: [n[0], n[1], n[2]],
+            uv: [vertex_packing::half_to_f32(self.uv[0]), vertex_packing::half_to_f32(self.uv[1])],
+            tangent: vertex_packing::unpack_snorm_2_10_10_10_rev(self.tangent),
+        }
+    }
+}
+
+impl VertexFormat for PackedVertex {
+    fn layout() -> VertexLayout {
+        VertexLayout::new().floats(0, 3).packed_snorm(1).half_floats(2, 2).packed_snorm(3)
+    }
+}
+
+fn file_extension(file_name: &str) -> String {
+    match Path::new(file_name).extension() {
+        Some(ext) => ext.to_string_lossy().to_lowercase(),
+        None => String::new(),
+    }
+}
+
+fn is_obj_file(file_name: &str) -> bool {
+    file_extension(file_name) == "obj"
+}
+
+fn is_gltf_file(file_name: &str) -> bool {
+    let ext = file_extension(file_name);
+    ext == "gltf" || ext == "glb"
+}
+
+///
+/// Transform a direction by the upper 3x3 part of a matrix and normalise it.
+///
+fn transform_direction(mat: &Mat4, v: [f32; 3]) -> [f32; 3] {
+    let v_t = Vec3::from(*mat * math::vec4((v[0], v[1], v[2], 0.0)));
+    if v_t.norm() > math::EPSILON {
+        v_t.normalize().v
+    } else {
+        v
+    }
+}
+
+impl Model {
+    ///
+    /// Make a model from its attribute arrays, and work out its bounds.
+    ///
+    pub fn new(
+        points: Vec<f32>, tex_coords: Vec<f32>, normals: Vec<f32>, tangents: Vec<f32>,
+        groups: Vec<MaterialGroup>, materials: Vec<Material>, nodes: Vec<ModelNode>) -> Model {
+
+        let mut model = Model {
+            point_count: points.len() / 3,
+            points: points,
+            tex_coords: tex_coords,
+            normals: normals,
+            tangents: tangents,
+            groups: groups,
+            materials: materials,
+            nodes: nodes,
+            aabb: geometry::points_aabb(&[]),
+            bounding_sphere: geometry::points_bounding_sphere(&[]),
+        };
+        model.update_bounds();
+
+        model
+    }
+
+    ///
+    /// The smallest axis aligned box around the model's points, in model space.
+    ///
+    pub fn aabb(&self) -> Aabb {
+        self.aabb
+    }
+
+    ///
+    /// A sphere around the model's points, in model space. It is a little larger
+    /// than the smallest one there is.
+    ///
+    pub fn bounding_sphere(&self) -> BoundingSphere {
+        self.bounding_sphere
+    }
+
+    ///
+    /// Work the bounds out again after the points have changed.
+    ///
+    pub fn update_bounds(&mut self) {
+        self.aabb = geometry::points_aabb(&self.points);
+        self.bounding_sphere = geometry::points_bounding_sphere(&self.points);
+    }
+
+    ///
+    /// Build a model from a mesh read by the internal OBJ parser. Normals are
+    /// worked out from the triangles when the mesh has none, and tangents from the
+    /// texture coordinates when the mesh has them.
+    ///
+    pub fn from_obj_mesh(mut mesh: ObjMesh, materials: Vec<Material>) -> Model {
+        if mesh.normals.len() != 3 * mesh.point_count {
+            mesh.normals = geometry::compute_unindexed_normals(&mesh.points, geometry::DEFAULT_SMOOTH_ANGLE_DEG);
+        }
+        let tangents = if mesh.tex_coords.len() == 2 * mesh.point_count {
+            geometry::compute_unindexed_tangents(&mesh.points, &mesh.tex_coords, &mesh.normals)
+        } else {
+            vec![]
+        };
+
+        Model::new(mesh.points, mesh.tex_coords, mesh.normals, tangents, mesh.groups, materials, vec![])
+    }
+
+    ///
+    /// A copy of the model cut down to about `target_ratio` of its triangles by
+    /// `geometry::simplify_with_uvs`, for a distant level of detail. Vertices in
+    /// the same place are welded first so the triangles can be collapsed into
+    /// each other. Texture coordinates are carried through the collapses, with the
+    /// vertices on seams left where they are; normals and tangents are worked out
+    /// again from the result. The whole model is drawn with the material of its
+    /// first group.
+    ///
+    pub fn simplified(&self, target_ratio: f32) -> Model {
+        let welded = geometry::shared_position_indices(&self.points);
+        let triangle_indices = &welded[0..(welded.len() - welded.len() % 3)];
+        let corner_uvs = if self.tex_coords.len() == 2 * self.point_count {
+            &self.tex_coords[0..2 * triangle_indices.len()]
+        } else {
+            &[]
+        };
+        let mesh = geometry::simplify_with_uvs(&self.points, triangle_indices, corner_uvs, target_ratio);
+
+        let points: Vec<f32> = mesh.indices.iter()
+            .flat_map(|&v_i| mesh.positions[3 * v_i as usize..3 * v_i as usize + 3].to_vec())
+            .collect();
+        let normals = geometry::compute_unindexed_normals(&points, geometry::DEFAULT_SMOOTH_ANGLE_DEG);
+        let tangents = if mesh.corner_uvs.is_empty() {
+            vec![]
+        } else {
+            geometry::compute_unindexed_tangents(&points, &mesh.corner_uvs, &normals)
+        };
+        let groups = match self.groups.first() {
+            Some(group) => vec![MaterialGroup {
+                material: group.material.clone(),
+                first: 0,
+                count: points.len() / 3,
+            }],
+            None => vec![],
+        };
+
+        Model::new(points, mesh.corner_uvs, normals, tangents, groups, self.materials.clone(), vec![])
+    }
+
+    ///
+    /// Gather the separate attribute arrays into one interleaved vertex per point.
+    ///
+    pub fn interleaved_vertices(&self) -> Vec<Vertex> {
+        let has_normals = self.normals.len() == 3 * self.point_count;
+        let has_tex_coords = self.tex_coords.len() == 2 * self.point_count;
+        let has_tangents = self.tangents.len() == 4 * self.point_count;
+
+        (0..self.point_count).map(|i| {
+            let mut vertex = Vertex::default();
+            vertex.pos.copy_from_slice(&self.points[3 * i..3 * i + 3]);
+            if has_normals {
+                vertex.normal.copy_from_slice(&self.normals[3 * i..3 * i + 3]);
+            }
+            if has_tex_coords {
+                vertex.uv.copy_from_slice(&self.tex_coords[2 * i..2 * i + 2]);
+            }
+            if has_tangents {
+                vertex.tangent.copy_from_slice(&self.tangents[4 * i..4 * i + 4]);
+            }
+
+            vertex
+        }).collect()
+    }
+
+    ///
+    /// Gather the attributes into one packed vertex per point.
+    ///
+    pub fn packed_vertices(&self) -> Vec<PackedVertex> {
+        self.interleaved_vertices().iter().map(PackedVertex::pack).collect()
+    }
+
+    fn empty() -> Model {
+        Model::new(vec![], vec![], vec![], vec![], vec![], vec![], vec![])
+    }
+
+    ///
+    /// Load a model from a file. With the `gltf` feature enabled, glTF files
+    /// are read by a pure Rust loader. With the `assimp` feature enabled, anything
+    /// assimp understands can be loaded, including glTF, FBX and COLLADA files,
+    /// and OBJ files fall back to the internal parser if assimp fails on them.
+    /// Without either, only OBJ files can be loaded.
+    ///
+    pub fn load(file_name: &str) -> Result<Model, String> {
+        let result = if is_gltf_file(file_name) && cfg!(feature = "gltf") {
+            Model::load_gltf(file_name)
+        } else {
+            Model::load_with_assimp(file_name)
+        };
+
+        match result {
+            Ok(model) => Ok(model),
+            Err(e) => {
+                if is_obj_file(file_name) {
+                    Model::load_obj(file_name)
+                } else {
+                    Err(e)
+                }
+            }
+        }
+    }
+
+    ///
+    /// Load a model the way `load` does, but keep a binary copy of it in a cache
+    /// file next to the mesh file, and read that instead on later runs for as long
+    /// as the mesh file and the files it pulls in are unchanged. Those are the
+    /// material libraries of an OBJ file and the buffers of a glTF file. Other
+    /// formats are not cached, since which files they pull in is not known.
+    ///
+    pub fn load_cached(file_name: &str) -> Result<Model, String> {
+        if let Some(model) = mesh_cache::read_cache(file_name) {
+            return Ok(model);
+        }
+
+        let model = match Model::load(file_name) {
+            Ok(model) => model,
+            Err(e) => return Err(e),
+        };
+        let dependencies = match Model::dependency_files(file_name) {
+            Some(dependencies) => dependencies,
+            None => return Ok(model),
+        };
+        if let Err(e) = mesh_cache::write_cache(&model, file_name, &dependencies) {
+            eprintln!("{}\nWARNING: {} will be parsed again next time.", e, file_name);
+        }
+
+        Ok(model)
+    }
+
+    ///
+    /// The files besides `file_name` that loading it reads, or `None` if they
+    /// cannot be worked out for its format.
+    ///
+    fn dependency_files(file_name: &str) -> Option<Vec<String>> {
+        if is_obj_file(file_name) {
+            obj_parser::obj_material_lib_files(file_name).ok()
+        } else if is_gltf_file(file_name) {
+            gltf_buffer_files(file_name)
+        } else {
+            None
+        }
+    }
+
+    fn load_obj(file_name: &str) -> Result<Model, String> {
+        let mesh = match obj_parser::load_obj_file(file_name) {
+            Ok(mesh) => mesh,
+            Err(e) => return Err(e),
+        };
+        let materials = match obj_parser::load_obj_materials(&mesh, file_name) {
+            Ok(materials) => materials,
+            Err(e) => {
+                eprintln!("{}\nWARNING: {} will use the default material.", e, file_name);
+                vec![]
+            }
+        };
+
+        Ok(Model::from_obj_mesh(mesh, materials))
+    }
+
+    ///
+    /// Load every mesh in a scene with assimp, one material group per mesh. The
+    /// assimp bindings do not expose material properties, so each group gets its
+    /// own default material named after the scene's material index.
+    ///
+    #[cfg(feature = "assimp")]
+    fn load_with_assimp(file_name: &str) -> Result<Model, String> {
+        let mut importer = ai::Importer::new();
+        importer.triangulate(true);
+        importer.calc_tangent_space(|args| args.enable = true);
+        let scene = match importer.read_file(file_name) {
+            Ok(val) => val,
+            Err(_) => return Err(format!("ERROR: reading mesh {}", file_name)),
+        };
+
+        let mut model = Model::empty();
+        for mesh_i in 0..scene.num_meshes() as usize {
+            let mesh = match scene.mesh(mesh_i) {
+                Some(val) => val,
+                None => continue,
+            };
+            let material = format!("material_{}", mesh.material_index);
+            if !model.materials.iter().any(|m| m.name == material) {
+                model.materials.push(Material::new(&material));
+            }
+            model.groups.push(MaterialGroup {
+                material: material,
+                first: model.point_count,
+                count: mesh.num_vertices() as usize,
+            });
+
+            let mut points = Vec::with_capacity(3 * mesh.num_vertices() as usize);
+            for v_i in 0..mesh.num_vertices() {
+                match mesh.get_vertex(v_i) {
+                    Some(vp) => points.extend_from_slice(&[vp.x, vp.y, vp.z]),
+                    None => points.extend_from_slice(&[0.0, 0.0, 0.0]),
+                }
+            }
+            // meshes without normals get them worked out from their triangles
+            let computed_normals = if mesh.has_normals() {
+                vec![]
+            } else {
+                geometry::compute_unindexed_normals(&points, geometry::DEFAULT_SMOOTH_ANGLE_DEG)
+            };
+            model.points.extend_from_slice(&points);
+
+            for v_i in 0..mesh.num_vertices() {
+                let i = v_i as usize;
+                let vn = match mesh.get_normal(v_i) {
+                    Some(vn) => [vn.x, vn.y, vn.z],
+                    None if !computed_normals.is_empty() => {
+                        [computed_normals[3 * i], computed_normals[3 * i + 1], computed_normals[3 * i + 2]]
+                    }
+                    None => [0.0, 0.0, 1.0],
+                };
+                let n = math::vec3((vn[0], vn[1], vn[2]));
+                model.normals.extend_from_slice(&vn);
+                match mesh.get_texture_coord(0, v_i) {
+                    Some(vt) => model.tex_coords.extend_from_slice(&[vt.x, vt.y]),
+                    None => model.tex_coords.extend_from_slice(&[0.0, 0.0]),
+                }
+                let tangent = match (mesh.get_tangent(v_i), mesh.get_bitangent(v_i)) {
+                    (Some(t), Some(b)) => {
+                        geometry::tangent_with_handedness(n, math::vec3((t.x, t.y, t.z)), math::vec3((b.x, b.y, b.z)))
+                    }
+                    _ => geometry::tangent_with_handedness(n, math::vec3((0.0, 0.0, 0.0)), math::vec3((0.0, 0.0, 0.0))),
+                };
+                model.tangents.extend_from_slice(&tangent);
+            }
+            model.point_count += mesh.num_vertices() as usize;
+        }
+
+        if model.point_count == 0 {
+            return Err(format!("ERROR: scene \"{}\" has no meshes.", file_name));
+        }
+        model.update_bounds();
+
+        Ok(model)
+    }
+
+    #[cfg(not(feature = "assimp"))]
+    fn load_with_assimp(file_name: &str) -> Result<Model, String> {
+        Err(format!(
+            "ERROR: cannot load {} without the assimp feature. Only OBJ files are supported.", file_name
+        ))
+    }
+
+    #[cfg(feature = "gltf")]
+    fn load_gltf(file_name: &str) -> Result<Model, String> {
+        let (document, buffers, _) = match gltf::import(file_name) {
+            Ok(val) => val,
+            Err(e) => return Err(format!("ERROR: reading glTF file {}\n{}", file_name, e)),
+        };
+        let base_dir = match Path::new(file_name).parent() {
+            Some(dir) => dir,
+            None => Path::new(""),
+        };
+
+        Model::from_gltf(&document, &buffers, base_dir)
+    }
+
+    #[cfg(not(feature = "gltf"))]
+    fn load_gltf(file_name: &str) -> Result<Model, String> {
+        Err(format!("ERROR: cannot load {} without the gltf feature.", file_name))
+    }
+
+    ///
+    /// Build a model from the default scene of a glTF document, falling back to
+    /// the first scene if there is no default. Texture maps are resolved relative
+    /// to `base_dir`; images embedded in the file are not supported.
+    ///
+    #[cfg(feature = "gltf")]
+    pub fn from_gltf(document: &gltf::Document, buffers: &[gltf::buffer::Data], base_dir: &Path) -> Result<Model, String> {
+        let scene = match document.default_scene() {
+            Some(scene) => scene,
+            None => match document.scenes().next() {
+                Some(scene) => scene,
+                None => return Err(String::from("ERROR: glTF document has no scenes.")),
+            },
+        };
+
+        let mut model = Model::empty();
+        for material in document.materials() {
+            model.materials.push(gltf_material(&material, base_dir));
+        }
+        for node in scene.nodes() {
+            model.add_gltf_node(&node, None, &Mat4::identity(), buffers);
+        }
+
+        if model.point_count == 0 {
+            return Err(String::from("ERROR: glTF scene has no triangles."));
+        }
+        model.update_bounds();
+
+        Ok(model)
+    }
+
+    #[cfg(feature = "gltf")]
+    fn add_gltf_node(&mut self, node: &gltf::Node, parent: Option<usize>, parent_transform: &Mat4, buffers: &[gltf::buffer::Data]) {
+        // glTF stores rotations with the scalar part last.
+        let (t, r, s) = node.transform().decomposed();
+        let transform = Mat4::from_trs(
+            math::vec3((t[0], t[1], t[2])), math::Versor::new(r[3], r[0], r[1], r[2]), math::vec3((s[0], s[1], s[2]))
+        );
+        let model_transform = parent_transform * &transform;
+
+        let node_i = self.nodes.len();
+        self.nodes.push(ModelNode {
+            name: node.name().unwrap_or("").to_string(),
+            parent: parent,
+            transform: transform,
+            groups: vec![],
+        });
+
+        if let Some(mesh) = node.mesh() {
+            for primitive in mesh.primitives() {
+                if primitive.mode() != gltf::mesh::Mode::Triangles {
+                    eprintln!("WARNING: skipping a glTF primitive that is not made of triangles.");
+                    continue;
+                }
+                if self.add_gltf_primitive(&primitive, &model_transform, buffers) {
+                    let group_i = self.groups.len() - 1;
+                    self.nodes[node_i].groups.push(group_i);
+                }
+            }
+        }
+
+        for child in node.children() {
+            self.add_gltf_node(&child, Some(node_i), &model_transform, buffers);
+        }
+    }
+
+    ///
+    /// Append a primitive's triangles, transformed into model space, as a new
+    /// material group. Returns false if the primitive has no positions.
+    ///
+    #[cfg(feature = "gltf")]
+    fn add_gltf_primitive(&mut self, primitive: &gltf::Primitive, transform: &Mat4, buffers: &[gltf::buffer::Data]) -> bool {
+        let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()]));
+        let positions: Vec<[f32; 3]> = match reader.read_positions() {
+            Some(positions) => positions.collect(),
+            None => return false,
+        };
+        let normals: Option<Vec<[f32; 3]>> = reader.read_normals().map(|normals| normals.collect());
+        let tex_coords: Option<Vec<[f32; 2]>> = reader.read_tex_coords(0).map(|tex_coords| tex_coords.into_f32().collect());
+        let tangents: Option<Vec<[f32; 4]>> = reader.read_tangents().map(|tangents| tangents.collect());
+        let indices: Vec<u32> = match reader.read_indices() {
+            Some(indices) => indices.into_u32().collect(),
+            None => (0..positions.len() as u32).collect(),
+        };
+        let normal_transform = transform.inverse().transpose();
+
+        let first = self.point_count;
+        let count = indices.len() - indices.len() % 3;
+        let mut points = Vec::with_capacity(3 * count);
+        let mut vertex_normals = Vec::with_capacity(3 * count);
+        let mut vertex_tex_coords = Vec::with_capacity(2 * count);
+        let mut vertex_tangents = Vec::with_capacity(4 * count);
+        for &index in indices[0..count].iter() {
+            let i = index as usize;
+            let p = *transform * math::vec4((positions[i][0], positions[i][1], positions[i][2], 1.0));
+            points.extend_from_slice(&[p.v[0], p.v[1], p.v[2]]);
+            if let Some(ref normals) = normals {
+                vertex_normals.extend_from_slice(&transform_direction(&normal_transform, normals[i]));
+            }
+            match tex_coords {
+                Some(ref tex_coords) => vertex_tex_coords.extend_from_slice(&tex_coords[i]),
+                None => vertex_tex_coords.extend_from_slice(&[0.0, 0.0]),
+            }
+            if let Some(ref tangents) = tangents {
+                let t = transform_direction(transform, [tangents[i][0], tangents[i][1], tangents[i][2]]);
+                vertex_tangents.extend_from_slice(&[t[0], t[1], t[2], tangents[i][3]]);
+            }
+        }
+        if normals.is_none() {
+            vertex_normals = geometry::compute_unindexed_normals(&points, geometry::DEFAULT_SMOOTH_ANGLE_DEG);
+        }
+        if tangents.is_none() {
+            vertex_tangents = geometry::compute_unindexed_tangents(&points, &vertex_tex_coords, &vertex_normals);
+        }
+
+        let material = match primitive.material().index() {
+            Some(material_i) => self.materials[material_i].name.clone(),
+            None => String::new(),
+        };
+        self.points.extend_from_slice(&points);
+        self.normals.extend_from_slice(&vertex_normals);
+        self.tex_coords.extend_from_slice(&vertex_tex_coords);
+        self.tangents.extend_from_slice(&vertex_tangents);
+        self.groups.push(MaterialGroup { material: material, first: first, count: count });
+        self.point_count += count;
+
+        true
+    }
+}
+
+///
+/// The external buffer files a glTF file refers to, relative to the glTF file.
+/// Buffers in a `.glb` file's own binary chunk, or in data URIs, have no file.
+///
+#[cfg(feature = "gltf")]
+fn gltf_buffer_files(file_name: &str) -> Option<Vec<String>> {
+    let document = match gltf::Gltf::open(file_name) {
+        Ok(gltf) => gltf.document,
+        Err(_) => return None,
+    };
+    let base_dir = match Path::new(file_name).parent() {
+        Some(dir) => dir,
+        None => Path::new(""),
+    };
+    let files = document.buffers().filter_map(|buffer| match buffer.source() {
+        gltf::buffer::Source::Uri(uri) if !uri.starts_with("data:") => {
+            Some(String::from(base_dir.join(uri).to_string_lossy()))
+        }
+        _ => None,
+    }).collect();
+
+    Some(files)
+}
+
+#[cfg(not(feature = "gltf"))]
+fn gltf_buffer_files(_file_name: &str) -> Option<Vec<String>> {
+    None
+}
+
+///
+/// Convert a glTF metallic-roughness material into the Phong material the demos
+/// use. Rough surfaces get a broad, dim highlight, and metals tint their
+/// highlight with the base colour.
+///
+#[cfg(feature = "gltf")]
+fn gltf_material(material: &gltf::Material, base_dir: &Path) -> Material {
+    let name = match material.name() {
+        Some(name) => name.to_string(),
+        None => format!("material_{}", material.index().unwrap_or(0)),
+    };
+    let pbr = material.pbr_metallic_roughness();
+    let base_colour = Colour::from(pbr.base_color_factor());
+    let metallic = pbr.metallic_factor();
+    let roughness = f32::max(pbr.roughness_factor(), 0.05);
+    let diffuse = |channel: f32| channel * (1.0 - metallic);
+    let specular = |channel: f32| (0.04 * (1.0 - metallic) + channel * metallic) * (1.0 - roughness);
+
+    let mut result = Material::new(&name);
+    result.kd = Colour::new(diffuse(base_colour.r), diffuse(base_colour.g), diffuse(base_colour.b));
+    result.ka = base_colour.with_alpha(1.0);
+    result.ks = Colour::new(specular(base_colour.r), specular(base_colour.g), specular(base_colour.b));
+    // Match the width of the highlight to a Blinn-Phong lobe with the same roughness.
+    result.shininess = f32::max(2.0 / (roughness * roughness * roughness * roughness) - 2.0, 1.0);
+    result.diffuse_map = pbr.base_color_texture().and_then(|info| gltf_texture_file(&info.texture(), base_dir));
+    result.normal_map = material.normal_texture().and_then(|info| gltf_texture_file(&info.texture(), base_dir));
+
+    result
+}
+
+#[cfg(feature = "gltf")]
+fn gltf_texture_file(texture: &gltf::Texture, base_dir: &Path) -> Option<String> {
+    match texture.source().source() {
+        gltf::image::Source::Uri { uri, .. } if !uri.starts_with("data:") => {
+            Some(base_dir.join(uri).to_string_lossy().into_owned())
+        }
+        _ => {
+            eprintln!("WARNING: skipping a glTF texture embedded in the file.");
+            None
+        }
+    }
+}
+
+mod model_tests {
+    use super::{Model, PackedVertex, Vertex, VertexFormat};
+    use assets;
+    use graphics_math as math;
+    use obj_parser;
+    use std::io::{BufReader, Cursor};
+    use std::mem;
+
+    fn quad_model() -> Model {
+        let obj_file = "\
+            v 0.0 0.0 0.0\n\
+            v 1.0 0.0 0.0\n\
+            v 1.0 1.0 0.0\n\
+            v 0.0 1.0 0.0\n\
+            vt 0.0 0.0\n\
+            vt 1.0 0.0\n\
+            vt 1.0 1.0\n\
+            vt 0.0 1.0\n\
+            vn 0.0 0.0 1.0\n\
+            usemtl bricks\n\
+            f 1/1/1 2/2/1 3/3/1\n\
+            f 1/1/1 3/3/1 4/4/1\n\
+        ";
+        let mut reader = BufReader::new(Cursor::new(obj_file.as_bytes()));
+        let mesh = obj_parser::load_obj_mesh(&mut reader).unwrap();
+
+        Model::from_obj_mesh(mesh, vec![])
+    }
+
+    #[test]
+    fn test_model_from_obj_mesh_keeps_vertices_and_groups() {
+        let model = quad_model();
+
+        assert_eq!(model.point_count, 6);
+        assert_eq!(model.points.len(), 18);
+        assert_eq!(model.groups.len(), 1);
+        assert_eq!(model.groups[0].material, "bricks");
+        assert_eq!(model.groups[0].count, 6);
+    }
+
+    #[test]
+    fn test_model_tangents_follow_texture_coordinates() {
+        let model = quad_model();
+
+        assert_eq!(model.tangents.len(), 4 * model.point_count);
+        for v_i in 0..model.point_count {
+            assert_eq!(&model.tangents[4 * v_i..4 * v_i + 4], &[1.0, 0.0, 0.0, 1.0]);
+        }
+    }
+
+    #[test]
+    fn test_model_bounds_fit_the_quad() {
+        let model = quad_model();
+        let aabb = model.aabb();
+        let sphere = model.bounding_sphere();
+
+        assert_eq!(aabb.min, math::vec3((0.0, 0.0, 0.0)));
+        assert_eq!(aabb.max, math::vec3((1.0, 1.0, 0.0)));
+        // the quad's diagonal is the sphere's diameter
+        assert!((sphere.centre - math::vec3((0.5, 0.5, 0.0))).norm() < 1e-5);
+        assert!(f32::abs(sphere.radius - f32::sqrt(0.5)) < 1e-5);
+    }
+
+    #[test]
+    fn test_model_bounds_fit_the_fallback_sphere() {
+        let model = Model::from_obj_mesh(assets::fallback_mesh(), vec![]);
+        let aabb = model.aabb();
+        let sphere = model.bounding_sphere();
+
+        assert!((aabb.centre()).norm() < 1e-4);
+        assert!((aabb.half_extents() - math::vec3((1.0, 1.0, 1.0))).norm() < 1e-4);
+        // no more than a few percent bigger than the unit sphere
+        assert!(sphere.radius >= 1.0 - 1e-4 && sphere.radius < 1.05);
+        for v_i in 0..model.point_count {
+            let p = math::vec3((model.points[3 * v_i], model.points[3 * v_i + 1], model.points[3 * v_i + 2]));
+            assert!(aabb.contains(&p));
+            assert!((p - sphere.centre).norm() <= sphere.radius + 1e-5);
+        }
+    }
+
+    #[test]
+    fn test_model_bounds_follow_moved_points() {
+        let mut model = quad_model();
+        for p in model.points.iter_mut() {
+            *p *= 2.0;
+        }
+        model.update_bounds();
+
+        assert_eq!(model.aabb().max, math::vec3((2.0, 2.0, 0.0)));
+    }
+
+    #[test]
+    fn test_a_simplified_model_has_fewer_triangles_and_keeps_its_texture_coordinates() {
+        let model = Model::from_obj_mesh(assets::fallback_mesh(), vec![]);
+        let simplified = model.simplified(0.25);
+        let target = f32::ceil(0.25 * (model.point_count / 3) as f32) as usize;
+
+        assert!(simplified.point_count / 3 <= target);
+        assert!(simplified.point_count > 0);
+        assert_eq!(simplified.normals.len(), simplified.points.len());
+        assert!(model.tex_coords.len() == 2 * model.point_count);
+        assert_eq!(simplified.tex_coords.len(), 2 * simplified.point_count);
+        assert_eq!(simplified.tangents.len(), 4 * simplified.point_count);
+        assert!(simplified.tex_coords.iter().all(|st| (0.0..=1.0).contains(st)));
+        assert_eq!(simplified.groups.len(), model.groups.len());
+        // the merged vertices stay close to the surface they came from
+        let bounds = simplified.aabb();
+        for i in 0..3 {
+            assert!(bounds.min.v[i] >= model.aabb().min.v[i] - 0.1);
+            assert!(bounds.max.v[i] <= model.aabb().max.v[i] + 0.1);
+        }
+    }
+
+    #[test]
+    fn test_interleaved_vertices_match_the_separate_arrays() {
+        let model = quad_model();
+        let vertices = model.interleaved_vertices();
+
+        assert_eq!(vertices.len(), model.point_count);
+        assert_eq!(vertices[1], Vertex {
+            pos: [1.0, 0.0, 0.0],
+            normal: [0.0, 0.0, 1.0],
+            uv: [1.0, 0.0],
+            tangent: [1.0, 0.0, 0.0, 1.0],
+        });
+    }
+
+    #[test]
+    fn test_interleaved_vertices_leave_missing_attributes_at_zero() {
+        let mut model = quad_model();
+        model.tex_coords.clear();
+        model.tangents.clear();
+        let vertices = model.interleaved_vertices();
+
+        assert_eq!(vertices[2].pos, [1.0, 1.0, 0.0]);
+        assert_eq!(vertices[2].uv, [0.0, 0.0]);
+        assert_eq!(vertices[2].tangent, [0.0, 0.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn test_vertex_layout_matches_the_struct_layout() {
+        let vertex = Vertex::default();
+        let base = &vertex as *const Vertex as usize;
+        let field_offsets = vec![
+            &vertex.pos as *const _ as usize - base,
+            &vertex.normal as *const _ as usize - base,
+            &vertex.uv as *const _ as usize - base,
+            &vertex.tangent as *const _ as usize - base,
+        ];
+        let layout = Vertex::layout();
+        let offsets: Vec<usize> = layout.attributes().iter().map(|attribute| attribute.offset).collect();
+
+        assert_eq!(layout.stride(), mem::size_of::<Vertex>());
+        assert_eq!(offsets, field_offsets);
+    }
+
+    #[test]
+    fn test_packed_vertices_are_half_the_size() {
+        let vertex = PackedVertex::default();
+        let base = &vertex as *const PackedVertex as usize;
+        let field_offsets = vec![
+            &vertex.pos as *const _ as usize - base,
+            &vertex.normal as *const _ as usize - base,
+            &vertex.uv as *const _ as usize - base,
+            &vertex.tangent as *const _ as usize - base,
+        ];
+        let layout = PackedVertex::layout();
+        let offsets: Vec<usize> = layout.attributes().iter().map(|attribute| attribute.offset).collect();
+
+        assert_eq!(layout.stride(), mem::size_of::<PackedVertex>());
+        assert_eq!(offsets, field_offsets);
+        assert_eq!(2 * mem::size_of::<PackedVertex>(), mem::size_of::<Vertex>());
+    }
+
+    #[test]
+    fn test_packed_vertices_unpack_close_to_the_originals() {
+        let model = quad_model();
+        let vertices = model.interleaved_vertices();
+        let packed = model.packed_vertices();
+
+        assert_eq!(packed.len(), vertices.len());
+        for (vertex, packed) in vertices.iter().zip(packed.iter()) {
+            let unpacked = packed.unpack();
+            assert_eq!(unpacked.pos, vertex.pos);
+            assert_eq!(unpacked.uv, vertex.uv);
+            assert_eq!(unpacked.normal, vertex.normal);
+            assert_eq!(unpacked.tangent, vertex.tangent);
+        }
+    }
+
+    #[test]
+    fn test_model_load_rejects_missing_files() {
+        assert!(Model::load("does_not_exist.obj").is_err());
+    }
+
+    #[cfg(feature = "gltf")]
+    #[test]
+    fn test_model_from_gltf_applies_node_transforms() {
+        use gltf;
+        use std::path::Path;
+
+        let gltf_file = r#"{
+            "asset": { "version": "2.0" },
+            "scene": 0,
+            "scenes": [{ "nodes": [0] }],
+            "nodes": [
+                { "name": "parent", "translation": [0.0, 0.0, -2.0], "children": [1] },
+                { "name": "child", "translation": [1.0, 0.0, 0.0], "mesh": 0 }
+            ],
+            "meshes": [{ "primitives": [{ "attributes": { "POSITION": 0 }, "material": 0 }] }],
+            "materials": [{ "name": "gold", "pbrMetallicRoughness": { "baseColorFactor": [1.0, 0.8, 0.2, 1.0] } }],
+            "buffers": [{ "byteLength": 36 }],
+            "bufferViews": [{ "buffer": 0, "byteLength": 36 }],
+            "accessors": [{
+                "bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3",
+                "min": [0.0, 0.0, 0.0], "max": [1.0, 1.0, 0.0]
+            }]
+        }"#;
+        let positions: [f32; 9] = [0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0];
+        let mut data = vec![];
+        for p in positions.iter() {
+            data.extend_from_slice(&p.to_bits().to_le_bytes());
+        }
+        let document = gltf::Gltf::from_slice(gltf_file.as_bytes()).unwrap().document;
+        let model = Model::from_gltf(&document, &[gltf::buffer::Data(data)], Path::new("")).unwrap();
+
+        assert_eq!(model.point_count, 3);
+        assert_eq!(model.points, vec![1.0, 0.0, -2.0, 2.0, 0.0, -2.0, 1.0, 1.0, -2.0]);
+        assert_eq!(&model.normals[0..3], &[0.0, 0.0, 1.0]);
+        assert_eq!(model.nodes.len(), 2);
+        assert_eq!(model.nodes[1].parent, Some(0));
+        assert_eq!(model.nodes[1].groups, vec![0]);
+        assert_eq!(model.groups[0].material, "gold");
+        assert_eq!(model.materials[0].name, "gold");
+    }
+}
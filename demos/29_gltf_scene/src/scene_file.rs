@@ -0,0 +1,450 @@
+use ron;
+use serde_json;
+
+use colour::Colour;
+use graphics_math as math;
+use math::Mat4;
+use logger::Logger;
+use obj_parser::Material;
+use skybox::{ProceduralSky, Skybox};
+
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+
+///
+/// The most lights a scene can have. The shader has room for this many.
+///
+pub const MAX_LIGHTS: usize = 4;
+
+
+fn default_fovy() -> f32 {
+    67.0
+}
+
+fn default_near() -> f32 {
+    0.1
+}
+
+fn default_far() -> f32 {
+    100.0
+}
+
+fn default_background() -> [f32; 3] {
+    [0.2, 0.2, 0.2]
+}
+
+fn default_scale() -> [f32; 3] {
+    [1.0, 1.0, 1.0]
+}
+
+fn default_ka() -> [f32; 3] {
+    [1.0, 1.0, 1.0]
+}
+
+fn default_kd() -> [f32; 3] {
+    [0.8, 0.8, 0.8]
+}
+
+fn default_ks() -> [f32; 3] {
+    [1.0, 1.0, 1.0]
+}
+
+fn default_shininess() -> f32 {
+    100.0
+}
+
+fn default_light_ambient() -> [f32; 3] {
+    [0.2, 0.2, 0.2]
+}
+
+fn default_light_diffuse() -> [f32; 3] {
+    [0.8, 0.8, 0.8]
+}
+
+fn default_zenith() -> [f32; 3] {
+    [0.15, 0.35, 0.75]
+}
+
+fn default_horizon() -> [f32; 3] {
+    [0.65, 0.8, 0.95]
+}
+
+fn default_ground() -> [f32; 3] {
+    [0.3, 0.28, 0.25]
+}
+
+fn default_sun_colour() -> [f32; 3] {
+    [1.0, 0.95, 0.85]
+}
+
+fn default_sun_radius_deg() -> f32 {
+    1.0
+}
+
+///
+/// Where the camera starts and how it sees. The camera looks down the negative z
+/// axis, turned `yaw` degrees to the left.
+///
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CameraDesc {
+    pub position: [f32; 3],
+    #[serde(default)]
+    pub yaw: f32,
+    #[serde(default = "default_fovy")]
+    pub fovy: f32,
+    #[serde(default = "default_near")]
+    pub near: f32,
+    #[serde(default = "default_far")]
+    pub far: f32,
+}
+
+impl Default for CameraDesc {
+    fn default() -> CameraDesc {
+        CameraDesc {
+            position: [0.0, 0.0, 3.0],
+            yaw: 0.0,
+            fovy: default_fovy(),
+            near: default_near(),
+            far: default_far(),
+        }
+    }
+}
+
+///
+/// The sky behind the scene: either the six images of a cube map, named for
+/// where they appear from a camera looking down the negative z axis, or a sky
+/// drawn by the shader with the sun towards `sun_direction`, which needs no
+/// images at all. The procedural sky's colours default to a clear day.
+///
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum SkyboxDesc {
+    CubeMap {
+        front: String,
+        back: String,
+        top: String,
+        bottom: String,
+        left: String,
+        right: String,
+    },
+    Procedural {
+        sun_direction: [f32; 3],
+        #[serde(default = "default_zenith")]
+        zenith: [f32; 3],
+        #[serde(default = "default_horizon")]
+        horizon: [f32; 3],
+        #[serde(default = "default_ground")]
+        ground: [f32; 3],
+        #[serde(default = "default_sun_colour")]
+        sun: [f32; 3],
+        #[serde(default = "default_sun_radius_deg")]
+        sun_radius_deg: f32,
+    },
+}
+
+impl SkyboxDesc {
+    ///
+    /// Make the sky box this describes. Only a cube map can fail, when one of its
+    /// images cannot be loaded.
+    ///
+    pub fn to_skybox(&self, logger: &Logger) -> Result<Skybox, String> {
+        match *self {
+            SkyboxDesc::CubeMap { ref front, ref back, ref top, ref bottom, ref left, ref right } => {
+                Skybox::cube_map(logger, &[front, back, top, bottom, left, right])
+            }
+            SkyboxDesc::Procedural { sun_direction, zenith, horizon, ground, sun, sun_radius_deg } => {
+                let mut sky = ProceduralSky::new(math::vec3((sun_direction[0], sun_direction[1], sun_direction[2])));
+                sky.zenith = Colour::from(zenith);
+                sky.horizon = Colour::from(horizon);
+                sky.ground = Colour::from(ground);
+                sky.sun = Colour::from(sun);
+                sky.sun_radius_deg = sun_radius_deg;
+
+                Ok(Skybox::procedural(logger, sky))
+            }
+        }
+    }
+}
+
+///
+/// A point light, and the colours it lights the scene with.
+///
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct LightDesc {
+    pub position: [f32; 3],
+    #[serde(default = "default_light_ambient")]
+    pub ambient: [f32; 3],
+    #[serde(default = "default_light_diffuse")]
+    pub diffuse: [f32; 3],
+    #[serde(default = "default_ks")]
+    pub specular: [f32; 3],
+}
+
+///
+/// A named material that objects can be drawn with in place of the materials
+/// their meshes came with. Anything left out matches `Material::new`.
+///
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct MaterialDesc {
+    pub name: String,
+    #[serde(default = "default_ka")]
+    pub ka: [f32; 3],
+    #[serde(default = "default_kd")]
+    pub kd: [f32; 3],
+    #[serde(default = "default_ks")]
+    pub ks: [f32; 3],
+    #[serde(default = "default_shininess")]
+    pub shininess: f32,
+    #[serde(default)]
+    pub diffuse_map: Option<String>,
+}
+
+impl MaterialDesc {
+    pub fn to_material(&self) -> Material {
+        let mut material = Material::new(&self.name);
+        material.ka = Colour::from(self.ka);
+        material.kd = Colour::from(self.kd);
+        material.ks = Colour::from(self.ks);
+        material.shininess = self.shininess;
+        material.diffuse_map = self.diffuse_map.clone();
+
+        material
+    }
+}
+
+///
+/// One mesh placed in the scene. The rotation is in degrees about the x, y, and
+/// z axes, applied in that order, after scaling and before moving the mesh into
+/// place. An object without a material is drawn with its mesh's own materials.
+///
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ObjectDesc {
+    pub mesh: String,
+    #[serde(default)]
+    pub position: [f32; 3],
+    #[serde(default)]
+    pub rotation: [f32; 3],
+    #[serde(default = "default_scale")]
+    pub scale: [f32; 3],
+    #[serde(default)]
+    pub material: Option<String>,
+}
+
+impl ObjectDesc {
+    pub fn model_mat(&self) -> Mat4 {
+        Mat4::from_scale(&math::vec3((self.scale[0], self.scale[1], self.scale[2])))
+            .rotate_x_deg(self.rotation[0])
+            .rotate_y_deg(self.rotation[1])
+            .rotate_z_deg(self.rotation[2])
+            .then_translate(&math::vec3((self.position[0], self.position[1], self.position[2])))
+    }
+}
+
+///
+/// A whole scene described in a file: the meshes in it and where they are, the
+/// materials they are drawn with, the lights, the camera, and the background.
+/// Scene files are written in RON or JSON, picked by the file extension, so a
+/// new scene can be put together without touching the demo's code.
+///
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SceneFile {
+    #[serde(default)]
+    pub camera: CameraDesc,
+    #[serde(default = "default_background")]
+    pub background: [f32; 3],
+    #[serde(default)]
+    pub skybox: Option<SkyboxDesc>,
+    #[serde(default)]
+    pub lights: Vec<LightDesc>,
+    #[serde(default)]
+    pub materials: Vec<MaterialDesc>,
+    pub objects: Vec<ObjectDesc>,
+}
+
+impl SceneFile {
+    pub fn from_json(text: &str) -> Result<SceneFile, String> {
+        let scene: SceneFile = match serde_json::from_str(text) {
+            Ok(val) => val,
+            Err(e) => return Err(format!("ERROR: invalid scene JSON: {}", e)),
+        };
+
+        match scene.validate() {
+            Ok(_) => Ok(scene),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub fn from_ron(text: &str) -> Result<SceneFile, String> {
+        let scene: SceneFile = match ron::de::from_str(text) {
+            Ok(val) => val,
+            Err(e) => return Err(format!("ERROR: invalid scene RON: {}", e)),
+        };
+
+        match scene.validate() {
+            Ok(_) => Ok(scene),
+            Err(e) => Err(e),
+        }
+    }
+
+    ///
+    /// Load a scene file, reading it as RON or JSON by its extension.
+    ///
+    pub fn load(path: &str) -> Result<SceneFile, String> {
+        let extension = match Path::new(path).extension() {
+            Some(ext) => ext.to_string_lossy().to_lowercase(),
+            None => String::new(),
+        };
+        if extension != "ron" && extension != "json" {
+            return Err(format!("ERROR: scene file {} is neither .ron nor .json", path));
+        }
+
+        let mut file = match File::open(path) {
+            Ok(handle) => handle,
+            Err(_) => return Err(format!("ERROR: file not found: {}", path)),
+        };
+        let mut text = String::new();
+        if let Err(e) = file.read_to_string(&mut text) {
+            return Err(format!("ERROR: could not read scene file {}: {}", path, e));
+        }
+
+        if extension == "ron" {
+            SceneFile::from_ron(&text)
+        } else {
+            SceneFile::from_json(&text)
+        }
+    }
+
+    ///
+    /// Check the parts of the scene that refer to each other, and the limits the
+    /// renderer has, so mistakes in a scene file show up as one clear error.
+    ///
+    pub fn validate(&self) -> Result<(), String> {
+        if self.camera.near <= 0.0 || self.camera.far <= self.camera.near {
+            return Err(format!(
+                "ERROR: the camera clipping planes must have 0 < near < far, not near {} and far {}",
+                self.camera.near, self.camera.far
+            ));
+        }
+        if self.lights.len() > MAX_LIGHTS {
+            return Err(format!("ERROR: the scene has {} lights, but at most {} are supported", self.lights.len(), MAX_LIGHTS));
+        }
+        for (i, material) in self.materials.iter().enumerate() {
+            if self.materials[..i].iter().any(|other| other.name == material.name) {
+                return Err(format!("ERROR: the material {} is defined more than once", material.name));
+            }
+        }
+        for object in self.objects.iter() {
+            if let Some(ref name) = object.material {
+                if !self.materials.iter().any(|material| material.name == *name) {
+                    return Err(format!("ERROR: the object with mesh {} uses an undefined material {}", object.mesh, name));
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+mod scene_file_tests {
+    use super::{SceneFile, SkyboxDesc, MAX_LIGHTS};
+    use colour::Colour;
+
+    const RON_SCENE: &str = r#"
+        (
+            camera: (position: (0.0, 1.0, 5.0), yaw: 10.0),
+            lights: [(position: (2.0, 3.0, 4.0))],
+            materials: [(name: "red", kd: (0.8, 0.1, 0.1))],
+            objects: [
+                (mesh: "src/scene.gltf"),
+                (mesh: "src/scene.gltf", position: (2.0, 0.0, 0.0), scale: (0.5, 0.5, 0.5), material: Some("red")),
+            ],
+        )
+    "#;
+
+    const JSON_SCENE: &str = r#"
+        {
+            "camera": { "position": [0.0, 1.0, 5.0], "yaw": 10.0 },
+            "lights": [ { "position": [2.0, 3.0, 4.0] } ],
+            "materials": [ { "name": "red", "kd": [0.8, 0.1, 0.1] } ],
+            "objects": [
+                { "mesh": "src/scene.gltf" },
+                { "mesh": "src/scene.gltf", "position": [2.0, 0.0, 0.0], "scale": [0.5, 0.5, 0.5], "material": "red" }
+            ]
+        }
+    "#;
+
+    #[test]
+    fn test_ron_and_json_describe_the_same_scene() {
+        let from_ron = SceneFile::from_ron(RON_SCENE).unwrap();
+        let from_json = SceneFile::from_json(JSON_SCENE).unwrap();
+
+        assert_eq!(from_ron, from_json);
+    }
+
+    #[test]
+    fn test_left_out_fields_take_their_defaults() {
+        let scene = SceneFile::from_json(JSON_SCENE).unwrap();
+
+        assert_eq!(scene.camera.fovy, 67.0);
+        assert_eq!(scene.background, [0.2, 0.2, 0.2]);
+        assert_eq!(scene.skybox, None);
+        assert_eq!(scene.objects[0].scale, [1.0, 1.0, 1.0]);
+        assert_eq!(scene.objects[0].material, None);
+        assert_eq!(scene.materials[0].to_material().ks, Colour::WHITE);
+        assert_eq!(scene.lights[0].diffuse, [0.8, 0.8, 0.8]);
+    }
+
+    #[test]
+    fn test_the_sky_is_a_cube_map_or_procedural() {
+        let cube_map = r#"(
+            skybox: Some(CubeMap(front: "f.jpg", back: "b.jpg", top: "t.jpg", bottom: "u.jpg", left: "l.jpg", right: "r.jpg")),
+            objects: [],
+        )"#;
+        let procedural = r#"{ "skybox": { "Procedural": { "sun_direction": [0.0, 1.0, 0.0] } }, "objects": [] }"#;
+
+        match SceneFile::from_ron(cube_map).unwrap().skybox {
+            Some(SkyboxDesc::CubeMap { ref top, .. }) => assert_eq!(top, "t.jpg"),
+            ref other => panic!("expected a cube map, got {:?}", other),
+        }
+        match SceneFile::from_json(procedural).unwrap().skybox {
+            Some(SkyboxDesc::Procedural { sun_direction, sun_radius_deg, .. }) => {
+                assert_eq!(sun_direction, [0.0, 1.0, 0.0]);
+                assert_eq!(sun_radius_deg, 1.0);
+            }
+            ref other => panic!("expected a procedural sky, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_objects_must_use_defined_materials() {
+        let text = r#"{ "objects": [ { "mesh": "a.obj", "material": "missing" } ] }"#;
+
+        assert!(SceneFile::from_json(text).is_err());
+    }
+
+    #[test]
+    fn test_too_many_lights_is_an_error() {
+        let light = r#"{ "position": [0.0, 0.0, 0.0] }"#;
+        let lights = vec![light; MAX_LIGHTS + 1].join(", ");
+        let text = format!(r#"{{ "lights": [{}], "objects": [] }}"#, lights);
+
+        assert!(SceneFile::from_json(&text).is_err());
+    }
+
+    #[test]
+    fn test_the_camera_needs_sensible_clipping_planes() {
+        let text = r#"{ "camera": { "position": [0.0, 0.0, 0.0], "near": 1.0, "far": 0.5 }, "objects": [] }"#;
+
+        assert!(SceneFile::from_json(text).is_err());
+    }
+
+    #[test]
+    fn test_the_sample_scenes_load() {
+        let scene = SceneFile::load("src/scene.ron").unwrap();
+
+        assert!(!scene.objects.is_empty());
+        assert!(SceneFile::load("src/scene.json").is_ok());
+        assert!(SceneFile::load("src/scene.gltf").is_err());
+    }
+}
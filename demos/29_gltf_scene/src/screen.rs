@@ -0,0 +1,278 @@
+use png;
+use png::HasParameters;
+
+use chrono::prelude::Utc;
+
+use std::path::Path;
+use std::fmt;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::error;
+
+
+pub struct CaptureResult {
+    bytes_written: usize,
+    path: String,
+}
+
+
+#[derive(Copy, Clone, Debug)]
+pub enum CaptureError {
+    CouldNotCaptureFromFrameBuffer,
+    CouldNotWriteImageData,
+    RecordingFull,
+}
+
+impl fmt::Display for CaptureError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            CaptureError::CouldNotCaptureFromFrameBuffer => {
+                write!(f,
+                    "The frame buffer map provided reported failing to read out 
+                    the frame buffer data from the graphics device."
+                )
+            }
+            CaptureError::CouldNotWriteImageData => {
+                write!(f, "We could not write out the screenshot to an image file.")
+            }
+            CaptureError::RecordingFull => {
+                write!(f, "The video recording has no room left for another frame.")
+            }
+        }
+    }
+}
+
+impl error::Error for CaptureError {
+    fn description(&self) -> &str { "" }
+}
+
+pub fn capture<F>(height: usize, width: usize, depth: usize, capture_func: &F) -> Result<CaptureResult, CaptureError>
+    where F: Fn(&mut [u8]) -> bool
+{
+    let mut image_buffer: Vec<u8> = vec![0; (height * width * depth) as usize];
+    
+    // Capture the buffer data from the source and write it into the 
+    // image buffer.
+    let result = capture_func(&mut image_buffer);
+    if !result {
+        return Err(CaptureError::CouldNotCaptureFromFrameBuffer);
+    }
+
+    flip_rows(&mut image_buffer, height, width, depth);
+
+    let date = Utc::now();
+    let name = format!("screenshot_{}.png", date);
+    
+    let path = Path::new(&name);
+    match write_png(path, height, width, depth, &image_buffer) {
+        Ok(_) => Ok(CaptureResult { 
+            bytes_written: height * width * depth, 
+            path: String::from(path.to_str().unwrap()) 
+        }),
+        Err(e) => Err(e),
+    }
+}
+
+///
+/// OpenGL reads the frame buffer bottom row first, while image files store the
+/// top row first, so swap the rows around before encoding.
+///
+fn flip_rows(image_buffer: &mut [u8], height: usize, width: usize, depth: usize) {
+    let width_in_bytes = depth * width;
+    let half_height = height / 2;
+    for row in 0..half_height {
+        for col in 0..width_in_bytes {
+            let temp = image_buffer[row * width_in_bytes + col];
+            image_buffer[row * width_in_bytes + col] = image_buffer[((height - row - 1) * width_in_bytes) + col];
+            image_buffer[((height - row - 1) * width_in_bytes) + col] = temp;
+        }
+    }
+}
+
+fn encode_png<W: Write>(
+    writer: W, height: usize, width: usize, depth: usize, image_buffer: &[u8]) -> Result<(), CaptureError> {
+
+    let color_type = match depth {
+        4 => png::ColorType::RGBA,
+        _ => png::ColorType::RGB,
+    };
+    let mut encoder = png::Encoder::new(writer, width as u32, height as u32);
+    encoder.set(color_type).set(png::BitDepth::Eight);
+    let mut png_writer = match encoder.write_header() {
+        Ok(png_writer) => png_writer,
+        Err(_) => return Err(CaptureError::CouldNotWriteImageData),
+    };
+
+    match png_writer.write_image_data(image_buffer) {
+        Ok(_) => Ok(()),
+        Err(_) => Err(CaptureError::CouldNotWriteImageData),
+    }
+}
+
+fn write_png(path: &Path, height: usize, width: usize, depth: usize, image_buffer: &[u8]) -> Result<(), CaptureError> {
+    let file = match File::create(path) {
+        Ok(handle) => handle,
+        Err(_) => return Err(CaptureError::CouldNotWriteImageData),
+    };
+
+    encode_png(BufWriter::new(file), height, width, depth, image_buffer)
+}
+
+
+///
+/// Records a fixed length run of frames from the frame buffer into memory, and
+/// writes them out as numbered image files once recording is done. The frame size
+/// is fixed when the recorder is created, so it should be created from the size of
+/// the frame buffer at the moment recording starts.
+///
+pub struct VideoRecorder {
+    width: usize,
+    height: usize,
+    depth: usize,
+    max_frames: usize,
+    frame_count: usize,
+    data: Vec<u8>,
+}
+
+impl VideoRecorder {
+    pub fn new(width: usize, height: usize, depth: usize, fps: usize, seconds_total: usize) -> VideoRecorder {
+        VideoRecorder {
+            width: width,
+            height: height,
+            depth: depth,
+            max_frames: fps * seconds_total,
+            frame_count: 0,
+            data: vec![],
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    pub fn frame_count(&self) -> usize {
+        self.frame_count
+    }
+
+    ///
+    /// The size of a single frame in bytes.
+    ///
+    pub fn frame_size_bytes(&self) -> usize {
+        self.width * self.height * self.depth
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.frame_count >= self.max_frames
+    }
+
+    ///
+    /// Grab the next frame. The capture function is handed a buffer exactly one
+    /// frame in size to read the frame buffer into.
+    ///
+    pub fn capture_frame<F>(&mut self, capture_func: &F) -> Result<(), CaptureError>
+        where F: Fn(&mut [u8]) -> bool
+    {
+        if self.is_full() {
+            return Err(CaptureError::RecordingFull);
+        }
+
+        let start = self.frame_count * self.frame_size_bytes();
+        let end = start + self.frame_size_bytes();
+        self.data.resize(end, 0);
+        if !capture_func(&mut self.data[start..end]) {
+            self.data.truncate(start);
+            return Err(CaptureError::CouldNotCaptureFromFrameBuffer);
+        }
+        flip_rows(&mut self.data[start..end], self.height, self.width, self.depth);
+        self.frame_count += 1;
+
+        Ok(())
+    }
+
+    ///
+    /// The image data of a recorded frame, top row first.
+    ///
+    pub fn frame(&self, frame_number: usize) -> &[u8] {
+        let start = frame_number * self.frame_size_bytes();
+        &self.data[start..(start + self.frame_size_bytes())]
+    }
+
+    ///
+    /// Encode a recorded frame as a PNG image.
+    ///
+    pub fn encode_frame<W: Write>(&self, frame_number: usize, writer: W) -> Result<(), CaptureError> {
+        encode_png(writer, self.height, self.width, self.depth, self.frame(frame_number))
+    }
+
+    ///
+    /// Write every recorded frame out to `video_frame_000.png`, `video_frame_001.png`,
+    /// and so on. Returns the number of frames written.
+    ///
+    pub fn write_frames(&self) -> Result<usize, CaptureError> {
+        for frame_number in 0..self.frame_count {
+            let file_name = format!("video_frame_{:03}.png", frame_number);
+            let file = match File::create(Path::new(&file_name)) {
+                Ok(handle) => handle,
+                Err(_) => return Err(CaptureError::CouldNotWriteImageData),
+            };
+            if let Err(e) = self.encode_frame(frame_number, BufWriter::new(file)) {
+                return Err(e);
+            }
+        }
+
+        Ok(self.frame_count)
+    }
+}
+
+mod screen_tests {
+    use super::VideoRecorder;
+    use png;
+
+    #[test]
+    fn test_recorded_frames_match_frame_buffer_size() {
+        let (width, height, depth) = (8, 6, 3);
+        let mut recorder = VideoRecorder::new(width, height, depth, 25, 1);
+        recorder.capture_frame(&|buf: &mut [u8]| {
+            assert_eq!(buf.len(), width * height * depth);
+            true
+        }).unwrap();
+
+        let mut png_data = vec![];
+        recorder.encode_frame(0, &mut png_data).unwrap();
+        let decoder = png::Decoder::new(&png_data[..]);
+        let (info, _) = decoder.read_info().unwrap();
+
+        assert_eq!(recorder.frame_count(), 1);
+        assert_eq!(info.width as usize, width);
+        assert_eq!(info.height as usize, height);
+        assert_eq!(info.buffer_size(), recorder.frame_size_bytes());
+    }
+
+    #[test]
+    fn test_recorded_frames_are_flipped_top_row_first() {
+        let mut recorder = VideoRecorder::new(1, 2, 3, 25, 1);
+        recorder.capture_frame(&|buf: &mut [u8]| {
+            buf.copy_from_slice(&[1, 1, 1, 2, 2, 2]);
+            true
+        }).unwrap();
+
+        assert_eq!(recorder.frame(0), &[2, 2, 2, 1, 1, 1]);
+    }
+
+    #[test]
+    fn test_recorder_stops_when_full() {
+        let mut recorder = VideoRecorder::new(2, 2, 3, 2, 1);
+        assert!(recorder.capture_frame(&|_: &mut [u8]| true).is_ok());
+        assert!(recorder.capture_frame(&|_: &mut [u8]| true).is_ok());
+        assert!(recorder.capture_frame(&|_: &mut [u8]| true).is_err());
+        assert_eq!(recorder.frame_count(), 2);
+    }
+}
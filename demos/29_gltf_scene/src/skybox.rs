@@ -0,0 +1,312 @@
+use gl;
+use gl::types::{GLenum, GLfloat, GLint, GLsizei, GLuint, GLvoid};
+use stb_image::image::LoadResult;
+
+use assets;
+use colour::Colour;
+use gl_utils::create_programme_from_files;
+use graphics_math as math;
+use math::{Mat4, Vec3};
+use logger::Logger;
+use vertex_layout::VertexLayout;
+use gl_utils::uniform_location;
+
+
+const SKYBOX_VS_FILE: &str = "src/skybox_vs.glsl";
+const SKYBOX_FS_FILE: &str = "src/skybox_fs.glsl";
+const SKYDOME_FS_FILE: &str = "src/skydome_fs.glsl";
+
+
+///
+/// Load an image file into one side of a cube map texture.
+///
+fn load_cube_map_side(side_target: GLenum, file_name: &str) -> Result<(), String> {
+    let force_channels = 4;
+    let image_data = match assets::load_image_with_depth(file_name, force_channels) {
+        LoadResult::ImageU8(image_data) => image_data,
+        LoadResult::Error(_) => {
+            return Err(format!("ERROR: could not load {}", file_name));
+        }
+        LoadResult::ImageF32(_) => {
+            return Err(format!("ERROR: Tried to load an image as byte vectors, got f32: {}", file_name));
+        }
+    };
+
+    unsafe {
+        gl_check!(gl::TexImage2D(
+            side_target, 0, gl::RGBA as GLint, image_data.width as GLsizei, image_data.height as GLsizei, 0,
+            gl::RGBA, gl::UNSIGNED_BYTE,
+            image_data.data.as_ptr() as *const GLvoid
+        ));
+    }
+
+    Ok(())
+}
+
+///
+/// Load all six sides of a cube map from images, given in the order front, back,
+/// top, bottom, left, and right, as seen from a camera looking down the negative
+/// z axis. Returns an error naming the first image that could not be loaded.
+///
+fn create_cube_map(files: &[&str; 6]) -> Result<GLuint, String> {
+    let mut texture = 0;
+    unsafe {
+        gl::ActiveTexture(gl::TEXTURE0);
+        gl::GenTextures(1, &mut texture);
+        gl::BindTexture(gl::TEXTURE_CUBE_MAP, texture);
+    }
+
+    let sides = [
+        (gl::TEXTURE_CUBE_MAP_NEGATIVE_Z, files[0]),
+        (gl::TEXTURE_CUBE_MAP_POSITIVE_Z, files[1]),
+        (gl::TEXTURE_CUBE_MAP_POSITIVE_Y, files[2]),
+        (gl::TEXTURE_CUBE_MAP_NEGATIVE_Y, files[3]),
+        (gl::TEXTURE_CUBE_MAP_NEGATIVE_X, files[4]),
+        (gl::TEXTURE_CUBE_MAP_POSITIVE_X, files[5]),
+    ];
+    for &(side_target, file_name) in sides.iter() {
+        if let Err(e) = load_cube_map_side(side_target, file_name) {
+            unsafe {
+                gl::DeleteTextures(1, &texture);
+            }
+            return Err(e);
+        }
+    }
+
+    unsafe {
+        gl::TexParameteri(gl::TEXTURE_CUBE_MAP, gl::TEXTURE_MAG_FILTER, gl::LINEAR as GLint);
+        gl::TexParameteri(gl::TEXTURE_CUBE_MAP, gl::TEXTURE_MIN_FILTER, gl::LINEAR as GLint);
+        gl::TexParameteri(gl::TEXTURE_CUBE_MAP, gl::TEXTURE_WRAP_R, gl::CLAMP_TO_EDGE as GLint);
+        gl::TexParameteri(gl::TEXTURE_CUBE_MAP, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as GLint);
+        gl::TexParameteri(gl::TEXTURE_CUBE_MAP, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as GLint);
+    }
+
+    Ok(texture)
+}
+
+///
+/// A cube ten units across around the camera, to draw the sky box on.
+///
+fn make_big_cube() -> GLuint {
+    let points: [GLfloat; 108] = [
+        -10.0,  10.0, -10.0, -10.0, -10.0, -10.0,  10.0, -10.0, -10.0,
+         10.0, -10.0, -10.0,  10.0,  10.0, -10.0, -10.0,  10.0, -10.0,
+
+        -10.0, -10.0,  10.0, -10.0, -10.0, -10.0, -10.0,  10.0, -10.0,
+        -10.0,  10.0, -10.0, -10.0,  10.0,  10.0, -10.0, -10.0,  10.0,
+
+         10.0, -10.0, -10.0,  10.0, -10.0,  10.0,  10.0,  10.0,  10.0,
+         10.0,  10.0,  10.0,  10.0,  10.0, -10.0,  10.0, -10.0, -10.0,
+
+        -10.0, -10.0,  10.0, -10.0,  10.0,  10.0,  10.0,  10.0,  10.0,
+         10.0,  10.0,  10.0,  10.0, -10.0,  10.0, -10.0, -10.0,  10.0,
+
+        -10.0,  10.0, -10.0,  10.0,  10.0, -10.0,  10.0,  10.0,  10.0,
+         10.0,  10.0,  10.0, -10.0,  10.0,  10.0, -10.0,  10.0, -10.0,
+
+        -10.0, -10.0, -10.0, -10.0, -10.0,  10.0,  10.0, -10.0, -10.0,
+         10.0, -10.0, -10.0, -10.0, -10.0,  10.0,  10.0, -10.0,  10.0
+    ];
+
+    let mut vao = 0;
+    unsafe {
+        gl::GenVertexArrays(1, &mut vao);
+    }
+    assert!(vao > 0);
+    VertexLayout::new().floats(0, 3).upload(vao, &points, 36);
+
+    vao
+}
+
+///
+/// The view matrix with the camera's movement taken out, leaving only its turn,
+/// so the sky box stays put around the camera however far it goes.
+///
+pub fn view_rotation(view_mat: &Mat4) -> Mat4 {
+    let mut rotation = *view_mat;
+    rotation.m[12] = 0.0;
+    rotation.m[13] = 0.0;
+    rotation.m[14] = 0.0;
+
+    rotation
+}
+
+///
+/// A sky worked out in the shader from the direction of each pixel, with no
+/// images to load: a gradient from the horizon up to the zenith, a disc for the
+/// sun, and an orange tint along the horizon while the sun is low. The sky
+/// darkens to night once the sun goes below the horizon. The colours are those
+/// of the sky with the sun high.
+///
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ProceduralSky {
+    // the direction towards the sun, in world space
+    pub sun_direction: Vec3,
+    pub zenith: Colour,
+    pub horizon: Colour,
+    pub ground: Colour,
+    pub sun: Colour,
+    // the angle from the middle of the sun's disc to its edge, in degrees
+    pub sun_radius_deg: f32,
+}
+
+impl ProceduralSky {
+    ///
+    /// A clear blue day sky with the sun towards `sun_direction`.
+    ///
+    pub fn new(sun_direction: Vec3) -> ProceduralSky {
+        ProceduralSky {
+            sun_direction: sun_direction.normalize(),
+            zenith: Colour::new(0.15, 0.35, 0.75),
+            horizon: Colour::new(0.65, 0.8, 0.95),
+            ground: Colour::new(0.3, 0.28, 0.25),
+            sun: Colour::new(1.0, 0.95, 0.85),
+            sun_radius_deg: 1.0,
+        }
+    }
+}
+
+///
+/// The uniforms of the procedural sky's shader.
+///
+struct SkydomeUniforms {
+    sun_direction: GLint,
+    zenith_colour: GLint,
+    horizon_colour: GLint,
+    ground_colour: GLint,
+    sun_colour: GLint,
+    sun_cos_radius: GLint,
+}
+
+impl SkydomeUniforms {
+    fn new(sp: GLuint) -> SkydomeUniforms {
+        SkydomeUniforms {
+            sun_direction: uniform_location(sp, "sun_direction"),
+            zenith_colour: uniform_location(sp, "zenith_colour"),
+            horizon_colour: uniform_location(sp, "horizon_colour"),
+            ground_colour: uniform_location(sp, "ground_colour"),
+            sun_colour: uniform_location(sp, "sun_colour"),
+            sun_cos_radius: uniform_location(sp, "sun_cos_radius"),
+        }
+    }
+
+    fn set(&self, sky: &ProceduralSky) {
+        let sun_direction = sky.sun_direction.normalize();
+        unsafe {
+            gl_check!(gl::Uniform3fv(self.sun_direction, 1, sun_direction.v.as_ptr()));
+            gl_check!(gl::Uniform3fv(self.zenith_colour, 1, sky.zenith.rgb().as_ptr()));
+            gl_check!(gl::Uniform3fv(self.horizon_colour, 1, sky.horizon.rgb().as_ptr()));
+            gl_check!(gl::Uniform3fv(self.ground_colour, 1, sky.ground.rgb().as_ptr()));
+            gl_check!(gl::Uniform3fv(self.sun_colour, 1, sky.sun.rgb().as_ptr()));
+            gl_check!(gl::Uniform1f(self.sun_cos_radius, f32::cos(sky.sun_radius_deg * math::ONE_DEG_IN_RAD)));
+        }
+    }
+}
+
+///
+/// What the sky box shows: the six images of a cube map, or a sky drawn from
+/// scratch in the shader.
+///
+enum SkyboxStyle {
+    CubeMap(GLuint),
+    Procedural(ProceduralSky, SkydomeUniforms),
+}
+
+///
+/// A cube map drawn behind everything else in the scene.
+///
+pub struct Skybox {
+    sp: GLuint,
+    view_location: GLint,
+    proj_location: GLint,
+    vao: GLuint,
+    style: SkyboxStyle,
+}
+
+impl Skybox {
+    fn with_style(sp: GLuint, style: SkyboxStyle) -> Skybox {
+        Skybox {
+            sp: sp,
+            view_location: uniform_location(sp, "V"),
+            proj_location: uniform_location(sp, "P"),
+            vao: make_big_cube(),
+            style: style,
+        }
+    }
+
+    ///
+    /// Load the sky box's images, in the order `create_cube_map` takes them, and
+    /// build its shader. Returns an error if any image could not be loaded.
+    ///
+    pub fn cube_map(logger: &Logger, files: &[&str; 6]) -> Result<Skybox, String> {
+        let texture = match create_cube_map(files) {
+            Ok(val) => val,
+            Err(e) => return Err(e),
+        };
+        let sp = create_programme_from_files(logger, SKYBOX_VS_FILE, SKYBOX_FS_FILE);
+
+        Ok(Skybox::with_style(sp, SkyboxStyle::CubeMap(texture)))
+    }
+
+    ///
+    /// Build the shader for a procedural sky. There is nothing to load, so this
+    /// cannot fail the way a cube map can.
+    ///
+    pub fn procedural(logger: &Logger, sky: ProceduralSky) -> Skybox {
+        let sp = create_programme_from_files(logger, SKYBOX_VS_FILE, SKYDOME_FS_FILE);
+        let uniforms = SkydomeUniforms::new(sp);
+
+        Skybox::with_style(sp, SkyboxStyle::Procedural(sky, uniforms))
+    }
+
+    ///
+    /// The settings of a procedural sky, to move the sun or change its colours
+    /// from one frame to the next. A cube map has none.
+    ///
+    pub fn procedural_sky_mut(&mut self) -> Option<&mut ProceduralSky> {
+        match self.style {
+            SkyboxStyle::Procedural(ref mut sky, _) => Some(sky),
+            SkyboxStyle::CubeMap(_) => None,
+        }
+    }
+
+    ///
+    /// Draw the sky box. It does not write depth, so draw it first and everything
+    /// else lands in front of it.
+    ///
+    pub fn draw(&self, view_mat: &Mat4, proj_mat: &Mat4) {
+        let view_rotation = view_rotation(view_mat);
+        unsafe {
+            gl::DepthMask(gl::FALSE);
+            gl::UseProgram(self.sp);
+            gl_check!(gl::UniformMatrix4fv(self.view_location, 1, gl::FALSE, view_rotation.as_ptr()));
+            gl_check!(gl::UniformMatrix4fv(self.proj_location, 1, gl::FALSE, proj_mat.as_ptr()));
+        }
+        match self.style {
+            SkyboxStyle::CubeMap(texture) => unsafe {
+                gl::ActiveTexture(gl::TEXTURE0);
+                gl::BindTexture(gl::TEXTURE_CUBE_MAP, texture);
+            },
+            SkyboxStyle::Procedural(ref sky, ref uniforms) => uniforms.set(sky),
+        }
+        unsafe {
+            gl::BindVertexArray(self.vao);
+            gl_check!(gl::DrawArrays(gl::TRIANGLES, 0, 36));
+            gl::DepthMask(gl::TRUE);
+        }
+    }
+}
+
+impl Drop for Skybox {
+    fn drop(&mut self) {
+        if gl::DeleteTextures::is_loaded() {
+            unsafe {
+                if let SkyboxStyle::CubeMap(texture) = self.style {
+                    gl::DeleteTextures(1, &texture);
+                }
+                gl::DeleteVertexArrays(1, &self.vao);
+                gl::DeleteProgram(self.sp);
+            }
+        }
+    }
+}
@@ -0,0 +1,54 @@
+#version 410
+
+in vec3 texcoords;
+// the direction towards the sun, in world space
+uniform vec3 sun_direction;
+// the colour straight up, at the horizon, and below it, with the sun high
+uniform vec3 zenith_colour, horizon_colour, ground_colour;
+uniform vec3 sun_colour;
+// the cosine of the angle from the middle of the sun's disc to its edge
+uniform float sun_cos_radius;
+out vec4 frag_colour;
+
+// the colour the low sun turns the sky near the horizon, and the sky at night
+vec3 sunset_colour = vec3 (1.0, 0.45, 0.15);
+vec3 night_colour = vec3 (0.01, 0.015, 0.04);
+
+void main () {
+	vec3 dir = normalize (texcoords);
+	vec3 sun = normalize (sun_direction);
+	float cos_sun = dot (dir, sun);
+
+	// blend from the horizon up to the zenith, quickly at first as a real sky does
+	float up = max (dir.y, 0.0);
+	vec3 sky = mix (horizon_colour, zenith_colour, sqrt (up));
+
+	// light scattered by the air is brightest towards and away from the sun, in
+	// the shape of Rayleigh scattering's phase function.
+	float rayleigh = 0.75 * (1.0 + cos_sun * cos_sun);
+	sky *= 0.6 + 0.4 * rayleigh;
+
+	// the closer the sun is to the horizon, the longer its light's path through
+	// the air, and the more blue is scattered out of it, leaving the horizon on
+	// the sun's side orange.
+	float low_sun = 1.0 - smoothstep (0.0, 0.35, sun.y);
+	float near_horizon = pow (1.0 - up, 4.0);
+	float sun_side = 0.5 + 0.5 * cos_sun;
+	sky = mix (sky, sunset_colour, low_sun * near_horizon * sun_side * smoothstep (-0.2, 0.0, sun.y));
+
+	// fade to the ground colour just below the horizon
+	sky = mix (sky, ground_colour, smoothstep (0.0, -0.05, dir.y));
+
+	// darken towards night once the sun has set
+	float daylight = smoothstep (-0.15, 0.1, sun.y);
+	sky = mix (night_colour, sky, daylight);
+
+	// the sun's disc, with a soft edge, and a glow around it
+	float edge = 0.2 * (1.0 - sun_cos_radius);
+	float disc = smoothstep (sun_cos_radius - edge, sun_cos_radius + edge, cos_sun);
+	float glow = pow (max (cos_sun, 0.0), 256.0) * 0.5;
+	float above_horizon = smoothstep (-0.02, 0.0, dir.y);
+	sky += sun_colour * (disc + glow) * above_horizon;
+
+	frag_colour = vec4 (sky, 1.0);
+}
@@ -0,0 +1,49 @@
+#version 410
+
+// keep in step with MAX_LIGHTS in scene_file.rs
+#define MAX_LIGHTS 4
+
+in vec3 position_eye, normal_eye;
+
+uniform mat4 view;
+
+// point lights from the scene file
+uniform int light_count;
+uniform vec3 light_position_world[MAX_LIGHTS];
+uniform vec3 Ls[MAX_LIGHTS]; // specular colour
+uniform vec3 Ld[MAX_LIGHTS]; // diffuse light colour
+uniform vec3 La[MAX_LIGHTS]; // ambient colour
+
+// surface reflectance from the current material
+uniform vec3 Ka, Kd, Ks;
+uniform float specular_exponent; // specular 'power'
+
+out vec4 fragment_colour; // final colour of surface
+
+void main() {
+	vec3 n_eye = normalize (normal_eye);
+	vec3 surface_to_viewer_eye = normalize (-position_eye);
+
+	vec3 colour = vec3 (0.0, 0.0, 0.0);
+	for (int i = 0; i < light_count; i++) {
+		// ambient intensity
+		vec3 Ia = La[i] * Ka;
+
+		// diffuse intensity
+		vec3 light_position_eye = vec3 (view * vec4 (light_position_world[i], 1.0));
+		vec3 direction_to_light_eye = normalize (light_position_eye - position_eye);
+		float dot_prod = max (dot (direction_to_light_eye, n_eye), 0.0);
+		vec3 Id = Ld[i] * Kd * dot_prod; // final diffuse intensity
+
+		// specular intensity, blinn-phong
+		vec3 half_way_eye = normalize (surface_to_viewer_eye + direction_to_light_eye);
+		float dot_prod_specular = max (dot (half_way_eye, n_eye), 0.0);
+		float specular_factor = pow (dot_prod_specular, specular_exponent);
+		vec3 Is = Ls[i] * Ks * specular_factor; // final specular intensity
+
+		colour += Is + Id + Ia;
+	}
+
+	// final colour
+	fragment_colour = vec4 (colour, 1.0);
+}
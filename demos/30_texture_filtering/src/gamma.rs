@@ -0,0 +1,143 @@
+use gl;
+use gl::types::GLint;
+
+use colour::{linear_to_srgb, srgb_to_linear};
+use texture::ColourSpace;
+
+
+///
+/// The two stages of the pipeline where gamma matters: reading the texture, and
+/// writing the framebuffer. Filtering and blending in between add light up, which
+/// is only right in linear light, so the texture has to be decoded on the way in
+/// and the result encoded again on the way out. Either stage alone leaves the
+/// picture too dark or too washed out; neither leaves it looking right up close
+/// but wrong wherever texels are averaged, as in the distance.
+///
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct GammaSettings {
+    pub texture: ColourSpace,
+    pub srgb_framebuffer: bool,
+}
+
+impl GammaSettings {
+    ///
+    /// Neither stage corrected, as the demo has always drawn.
+    ///
+    pub fn new() -> GammaSettings {
+        GammaSettings {
+            texture: ColourSpace::Linear,
+            srgb_framebuffer: false,
+        }
+    }
+
+    ///
+    /// The value the display gets for a texture byte value `stored`, from 0 to 1.
+    ///
+    pub fn displayed(&self, stored: f32) -> f32 {
+        self.displayed_average(stored, stored)
+    }
+
+    ///
+    /// The value the display gets where the filter averages two texels with byte
+    /// values `a` and `b` half and half, as a mipmap level or a sample halfway
+    /// between texels does.
+    ///
+    pub fn displayed_average(&self, a: f32, b: f32) -> f32 {
+        let read = |value: f32| match self.texture {
+            ColourSpace::Linear => value,
+            ColourSpace::Srgb => srgb_to_linear(value),
+        };
+        let average = 0.5 * (read(a) + read(b));
+
+        if self.srgb_framebuffer { linear_to_srgb(average) } else { average }
+    }
+
+    ///
+    /// Whether both stages are corrected, or neither: the two ways of drawing that
+    /// leave an unfiltered texel on screen as it was painted.
+    ///
+    pub fn is_balanced(&self) -> bool {
+        (self.texture == ColourSpace::Srgb) == self.srgb_framebuffer
+    }
+
+    ///
+    /// Turn the framebuffer's conversion to sRGB on or off for the draws that follow.
+    ///
+    pub fn apply(&self) {
+        unsafe {
+            if self.srgb_framebuffer {
+                gl::Enable(gl::FRAMEBUFFER_SRGB);
+            } else {
+                gl::Disable(gl::FRAMEBUFFER_SRGB);
+            }
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        let verdict = match (self.texture, self.srgb_framebuffer) {
+            (ColourSpace::Srgb, true) => "correct",
+            (ColourSpace::Linear, false) => "filtered in gamma space",
+            (ColourSpace::Srgb, false) => "too dark",
+            (ColourSpace::Linear, true) => "washed out",
+        };
+        format!(
+            "texture {}  framebuffer {}  ({})",
+            self.texture.name(), if self.srgb_framebuffer { "sRGB" } else { "raw" }, verdict
+        )
+    }
+}
+
+///
+/// Whether the window's framebuffer actually encodes to sRGB when asked to. The
+/// driver may not give one, however the window was asked for.
+///
+pub fn framebuffer_is_srgb() -> bool {
+    let mut encoding: GLint = 0;
+    unsafe {
+        gl::GetFramebufferAttachmentParameteriv(
+            gl::FRAMEBUFFER, gl::BACK_LEFT, gl::FRAMEBUFFER_ATTACHMENT_COLOR_ENCODING, &mut encoding
+        );
+    }
+
+    encoding as u32 == gl::SRGB
+}
+
+mod gamma_tests {
+    use super::GammaSettings;
+    use colour::{linear_to_srgb, srgb_to_linear};
+    use texture::ColourSpace;
+
+    #[test]
+    fn test_the_srgb_curves_undo_each_other() {
+        for i in 0..11 {
+            let value = i as f32 / 10.0;
+            assert!(f32::abs(linear_to_srgb(srgb_to_linear(value)) - value) < 1e-5);
+        }
+        // Half the byte range is only about a fifth of the light.
+        assert!(f32::abs(srgb_to_linear(0.5) - 0.214) < 1e-3);
+    }
+
+    #[test]
+    fn test_balanced_pipelines_show_a_single_texel_as_it_was_painted() {
+        let correct = GammaSettings { texture: ColourSpace::Srgb, srgb_framebuffer: true };
+        let uncorrected = GammaSettings::new();
+        assert!(correct.is_balanced() && uncorrected.is_balanced());
+        assert!(f32::abs(correct.displayed(0.3) - 0.3) < 1e-5);
+        assert_eq!(uncorrected.displayed(0.3), 0.3);
+
+        let dark = GammaSettings { texture: ColourSpace::Srgb, srgb_framebuffer: false };
+        let washed_out = GammaSettings { texture: ColourSpace::Linear, srgb_framebuffer: true };
+        assert!(dark.displayed(0.3) < 0.3 && washed_out.displayed(0.3) > 0.3);
+        assert_eq!(dark.describe(), "texture sRGB  framebuffer raw  (too dark)");
+    }
+
+    #[test]
+    fn test_only_the_correct_pipeline_averages_black_and_white_to_half_the_light() {
+        let correct = GammaSettings { texture: ColourSpace::Srgb, srgb_framebuffer: true };
+        let uncorrected = GammaSettings::new();
+
+        // Half the light looks about three quarters of the way to white.
+        assert!(f32::abs(correct.displayed_average(0.0, 1.0) - linear_to_srgb(0.5)) < 1e-5);
+        assert_eq!(uncorrected.displayed_average(0.0, 1.0), 0.5);
+    }
+}
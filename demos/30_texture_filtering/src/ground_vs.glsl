@@ -0,0 +1,13 @@
+#version 410
+
+layout (location = 0) in vec3 vertex_position;
+layout (location = 1) in vec2 vt; // per-vertex texture co-ords
+
+uniform mat4 view, proj;
+
+out vec2 st;
+
+void main() {
+	st = vt;
+	gl_Position = proj * view * vec4 (vertex_position, 1.0);
+}
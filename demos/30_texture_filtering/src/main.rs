@@ -0,0 +1,401 @@
+extern crate gl;
+extern crate glfw;
+extern crate chrono;
+extern crate stb_image;
+#[cfg(feature = "glam")]
+extern crate glam;
+#[cfg(feature = "cgmath")]
+extern crate cgmath;
+
+#[macro_use]
+mod gl_utils;
+mod graphics_math;
+mod obj_parser;
+mod colour;
+mod assets;
+mod shader_include;
+mod spirv;
+mod program_cache;
+mod logger;
+mod overlay;
+mod streaming_buffer;
+mod texture;
+mod gamma;
+mod vertex_layout;
+
+
+use glfw::{Action, Context, Key};
+use gl::types::{GLfloat};
+
+use std::process;
+
+use gl_utils::*;
+
+use graphics_math as math;
+use math::Mat4;
+
+use overlay::Overlay;
+use gamma::GammaSettings;
+use texture::{ColourSpace, MagFilter, MinFilter, Sampler, SamplerSettings, Texture};
+use vertex_layout::VertexLayout;
+
+
+const GL_LOG_FILE: &str = "gl.log";
+const VERTEX_SHADER_FILE: &str = "src/ground_vs.glsl";
+const FRAGMENT_SHADER_FILE: &str = "src/ground_fs.glsl";
+const TEXTURE_FILE: &str = "src/tile2-diamonds256x256.png";
+
+// The ground plane is a long strip running away from the camera, so the texture
+// is seen at every distance and angle from right underfoot to the horizon.
+const GROUND_HALF_WIDTH: f32 = 20.0;
+const GROUND_NEAR_Z: f32 = 10.0;
+const GROUND_FAR_Z: f32 = -390.0;
+// The size of one repeat of the texture on the ground, in world units.
+const GROUND_TILE_SIZE: f32 = 2.0;
+
+const OVERLAY_TEXT_COLOUR: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+const OVERLAY_BACKGROUND_COLOUR: [f32; 4] = [0.0, 0.0, 0.0, 0.6];
+
+
+///
+/// Build the two triangles of the ground plane, interleaving each position with
+/// its texture coordinates.
+///
+fn ground_plane_vertices() -> Vec<GLfloat> {
+    let corners = [
+        (-GROUND_HALF_WIDTH, GROUND_NEAR_Z), (GROUND_HALF_WIDTH, GROUND_NEAR_Z), (GROUND_HALF_WIDTH, GROUND_FAR_Z),
+        (GROUND_HALF_WIDTH, GROUND_FAR_Z), (-GROUND_HALF_WIDTH, GROUND_FAR_Z), (-GROUND_HALF_WIDTH, GROUND_NEAR_Z),
+    ];
+    let mut vertices = Vec::with_capacity(5 * corners.len());
+    for &(x, z) in corners.iter() {
+        vertices.extend_from_slice(&[x, 0.0, z, x / GROUND_TILE_SIZE, -z / GROUND_TILE_SIZE]);
+    }
+
+    vertices
+}
+
+///
+/// Describe each side's sampler and gamma settings for the overlay and the log,
+/// marking the one the keys change.
+///
+fn describe_filtering(samplers: &[Sampler; 2], gamma: &[GammaSettings; 2], selected: usize, compare: bool) -> Vec<String> {
+    let sides = ["left ", "right"];
+    let mut lines = vec![];
+    for (i, sampler) in samplers.iter().enumerate() {
+        if !compare && i != selected {
+            continue;
+        }
+        let marker = if i == selected { ">" } else { " " };
+        lines.push(format!("{} {}  {}", marker, sides[i], sampler.settings().describe()));
+        lines.push(format!("         {}", gamma[i].describe()));
+    }
+
+    lines
+}
+
+fn main() {
+    let logger = restart_gl_log(GL_LOG_FILE);
+    // Start a GL context and O/S window using the GLFW helper library.
+    // Ask for a window that can encode to sRGB, so the framebuffer's half of the
+    // gamma correction can be switched on and off.
+    let config = GlConfig { srgb_framebuffer: true, ..GlConfig::default() };
+    let mut context = match start_gl_with_config(&logger, &config) {
+        Ok(val) => val,
+        Err(e) => {
+            eprintln!("Failed to Initialize OpenGL context. Got error:");
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+    };
+
+    // Tell GL to only draw onto a pixel if the shape is closer to the viewer.
+    unsafe {
+        // Enable depth testing.
+        gl::Enable(gl::DEPTH_TEST);
+        // Depth testing interprets a smaller value as closer to the eye.
+        gl::DepthFunc(gl::LESS);
+    }
+
+    /*------------------------------CREATE GEOMETRY------------------------------*/
+    let vertices = ground_plane_vertices();
+    let mut vao = 0;
+    unsafe {
+        gl::GenVertexArrays(1, &mut vao);
+    }
+    assert!(vao > 0);
+    VertexLayout::new().floats(0, 3).floats(1, 2).upload(vao, &vertices, vertices.len() / 5);
+
+    let shader_programme = create_programme_from_files(&logger, VERTEX_SHADER_FILE, FRAGMENT_SHADER_FILE);
+
+    // input variables
+    let near = 0.1;                                  // clipping plane
+    let far = 500.0;                                 // clipping plane
+    let fov = 67.0;                                  // convert 67 degrees to radians
+    let aspect = context.width as f32 / context.height as f32; // aspect ratio
+    let proj_mat = Mat4::perspective(fov, aspect, near, far);
+
+    // matrix components
+    let cam_speed: GLfloat = 5.0;             // 5 units per second
+    let cam_yaw_speed: GLfloat = 30.0;        // 30 degrees per second
+    let mut cam_pos: [GLfloat; 3] = [0.0, 1.0, 5.0]; // just above the ground, looking along it
+    let mut cam_yaw: GLfloat = 0.0;               // y-rotation in degrees
+    let mut mat_trans = Mat4::from_translation(&math::vec3((-cam_pos[0], -cam_pos[1], -cam_pos[2])));
+    let mut mat_rot = Mat4::from_rotation_y_deg(-cam_yaw);
+    let mut view_mat = mat_rot * mat_trans;
+
+    let view_mat_location = uniform_location(shader_programme, "view");
+    assert!(view_mat_location > -1);
+
+    let proj_mat_location = uniform_location(shader_programme, "proj");
+    assert!(proj_mat_location > -1);
+
+    unsafe {
+        gl::UseProgram(shader_programme);
+        gl::UniformMatrix4fv(view_mat_location, 1, gl::FALSE, view_mat.as_ptr());
+        gl::UniformMatrix4fv(proj_mat_location, 1, gl::FALSE, proj_mat.as_ptr());
+    }
+
+    // The image is loaded twice, once read back as it is stored and once decoded
+    // from sRGB, so each side can pick either.
+    let mut ground_textures = vec![];
+    for &colour_space in [ColourSpace::Linear, ColourSpace::Srgb].iter() {
+        match Texture::load_with_colour_space(TEXTURE_FILE, colour_space) {
+            Ok(texture) => ground_textures.push(texture),
+            Err(e) => {
+                logger.log_err(&e);
+                process::exit(1);
+            }
+        }
+    }
+    if !gamma::framebuffer_is_srgb() {
+        logger.log_err("WARNING: the window's framebuffer does not encode to sRGB; B will have no effect");
+    }
+    let anisotropy_levels = texture::anisotropy_levels(texture::max_anisotropy());
+    logger.log(&format!("anisotropy levels supported: {:?}", anisotropy_levels));
+    logger.log("keys: N cycles the minification filter, M cycles the magnification filter,");
+    logger.log("      F cycles the anisotropy level, Tab picks the side the keys change,");
+    logger.log("      G toggles between reading the texture as linear or sRGB, B toggles the");
+    logger.log("      framebuffer's gamma correction, C toggles the side by side comparison,");
+    logger.log("      O toggles the overlay");
+
+    // The same texture is drawn through two samplers, one for each half of the
+    // window, so two ways of filtering it can be seen side by side. The left side
+    // starts out with the nearest texel and no mipmaps, and the right with
+    // trilinear filtering.
+    let mut samplers = [
+        Sampler::new(SamplerSettings {
+            min_filter: MinFilter::Nearest, mag_filter: MagFilter::Nearest, ..SamplerSettings::new()
+        }),
+        Sampler::new(SamplerSettings::new()),
+    ];
+    let mut anisotropy_indices = [0, 0];
+    // Each side also has its own gamma settings. Both start with neither stage
+    // corrected, which is how the texture looked before either could be.
+    let mut gamma = [GammaSettings::new(), GammaSettings::new()];
+    let mut selected = 1;
+    let mut compare = true;
+
+    // the current filtering mode, drawn over the scene
+    let mut overlay = Overlay::new(&logger);
+    let mut show_overlay = true;
+
+    unsafe {
+        gl::ClearColor(0.6, 0.7, 0.8, 1.0);
+        // Cull face.
+        gl::Enable(gl::CULL_FACE);
+        // Cull back face.
+        gl::CullFace(gl::BACK);
+        // GL_CW for clockwise.    
+        gl::FrontFace(gl::CCW);
+    }
+
+    while !context.window.should_close() {
+        let current_seconds = context.glfw.get_time();
+        let elapsed_seconds = current_seconds - context.elapsed_time_seconds;
+        context.elapsed_time_seconds = current_seconds;
+
+        update_fps_counter(&mut context);
+        unsafe {
+            // Wipe the drawing surface clear.
+            gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
+            gl::Viewport(0, 0, context.width as i32, context.height as i32);
+
+            gl::UseProgram(shader_programme);
+            gl::BindVertexArray(vao);
+        }
+        if compare {
+            // Both halves share the one view; the scissor keeps each draw to its half.
+            let half_width = context.width as i32 / 2;
+            let halves = [(0, half_width), (half_width, context.width as i32 - half_width)];
+            unsafe {
+                gl::Enable(gl::SCISSOR_TEST);
+            }
+            for (i, &(x, width)) in halves.iter().enumerate() {
+                let texture = ground_textures.iter().find(|texture| texture.colour_space() == gamma[i].texture);
+                texture.unwrap().bind(0);
+                samplers[i].bind(0);
+                gamma[i].apply();
+                unsafe {
+                    gl::Scissor(x, 0, width, context.height as i32);
+                    gl::DrawArrays(gl::TRIANGLES, 0, 6);
+                }
+            }
+            unsafe {
+                gl::Disable(gl::SCISSOR_TEST);
+            }
+        } else {
+            let texture = ground_textures.iter().find(|texture| texture.colour_space() == gamma[selected].texture);
+            texture.unwrap().bind(0);
+            samplers[selected].bind(0);
+            gamma[selected].apply();
+            unsafe {
+                gl::DrawArrays(gl::TRIANGLES, 0, 6);
+            }
+        }
+        // The overlay samples its own texture with its own settings, and its
+        // colours are already what the display should show.
+        Sampler::unbind(0);
+        GammaSettings::new().apply();
+
+        if show_overlay {
+            let lines = describe_filtering(&samplers, &gamma, selected, compare);
+            let scale = 2.0;
+            let longest = lines.iter().map(|line| line.len()).max().unwrap_or(0);
+            let width = (longest as f32 * overlay::GLYPH_ADVANCE + 4.0) * scale;
+            let height = (lines.len() as f32 * overlay::LINE_HEIGHT + 4.0) * scale;
+            overlay.rect(10.0, 10.0, width, height, OVERLAY_BACKGROUND_COLOUR);
+            for (i, line) in lines.iter().enumerate() {
+                let y = 10.0 + (2.0 + i as f32 * overlay::LINE_HEIGHT) * scale;
+                overlay.text(10.0 + 2.0 * scale, y, scale, OVERLAY_TEXT_COLOUR, line);
+            }
+            overlay.draw(context.width, context.height);
+        }
+
+        context.glfw.poll_events();
+
+        let mut filtering_changed = false;
+        for (_, event) in glfw::flush_messages(&context.events) {
+            match event {
+                glfw::WindowEvent::Key(Key::N, _, Action::Press, _) => {
+                    let filter = samplers[selected].settings().min_filter.next();
+                    samplers[selected].set_min_filter(filter);
+                    filtering_changed = true;
+                }
+                glfw::WindowEvent::Key(Key::M, _, Action::Press, _) => {
+                    let filter = samplers[selected].settings().mag_filter.next();
+                    samplers[selected].set_mag_filter(filter);
+                    filtering_changed = true;
+                }
+                glfw::WindowEvent::Key(Key::F, _, Action::Press, _) => {
+                    anisotropy_indices[selected] = (anisotropy_indices[selected] + 1) % anisotropy_levels.len();
+                    samplers[selected].set_anisotropy(anisotropy_levels[anisotropy_indices[selected]]);
+                    filtering_changed = true;
+                }
+                glfw::WindowEvent::Key(Key::G, _, Action::Press, _) => {
+                    gamma[selected].texture = gamma[selected].texture.toggle();
+                    filtering_changed = true;
+                }
+                glfw::WindowEvent::Key(Key::B, _, Action::Press, _) => {
+                    gamma[selected].srgb_framebuffer = !gamma[selected].srgb_framebuffer;
+                    filtering_changed = true;
+                }
+                glfw::WindowEvent::Key(Key::Tab, _, Action::Press, _) => {
+                    selected = 1 - selected;
+                    filtering_changed = true;
+                }
+                glfw::WindowEvent::Key(Key::C, _, Action::Press, _) => {
+                    compare = !compare;
+                    filtering_changed = true;
+                }
+                glfw::WindowEvent::Key(Key::O, _, Action::Press, _) => {
+                    show_overlay = !show_overlay;
+                }
+                _ => {}
+            }
+        }
+        if filtering_changed {
+            for line in describe_filtering(&samplers, &gamma, selected, compare) {
+                logger.log(&line);
+            }
+        }
+
+        // control keys
+        let mut cam_moved = false;
+        match context.window.get_key(Key::A) {
+            Action::Press | Action::Repeat => {
+                cam_pos[0] -= cam_speed * (elapsed_seconds as GLfloat);
+                cam_moved = true;
+            }
+            _ => {}
+        }
+        match context.window.get_key(Key::D) {
+            Action::Press | Action::Repeat => {
+                cam_pos[0] += cam_speed * (elapsed_seconds as GLfloat);
+                cam_moved = true;
+            }
+            _ => {}
+        }
+        match context.window.get_key(Key::Up) {
+            Action::Press | Action::Repeat => {
+                cam_pos[1] += cam_speed * (elapsed_seconds as GLfloat);
+                cam_moved = true;
+            }
+            _ => {}
+        }
+        match context.window.get_key(Key::Down) {
+            Action::Press | Action::Repeat => {
+                // stay above the ground
+                cam_pos[1] = f32::max(cam_pos[1] - cam_speed * (elapsed_seconds as GLfloat), 0.1);
+                cam_moved = true;
+            }
+            _ => {}
+        }
+        match context.window.get_key(Key::W) {
+            Action::Press | Action::Repeat => {
+                cam_pos[2] -= cam_speed * (elapsed_seconds as GLfloat);
+                cam_moved = true;
+            }
+            _ => {}
+        }
+        match context.window.get_key(Key::S) {
+            Action::Press | Action::Repeat => {
+                cam_pos[2] += cam_speed * (elapsed_seconds as GLfloat);
+                cam_moved = true;
+            }
+            _ => {}
+        }
+        match context.window.get_key(Key::Left) {
+            Action::Press | Action::Repeat => {
+                cam_yaw += cam_yaw_speed * (elapsed_seconds as GLfloat);
+                cam_moved = true;
+            }
+            _ => {}
+        }
+        match context.window.get_key(Key::Right) {
+            Action::Press | Action::Repeat => {
+                cam_yaw -= cam_yaw_speed * (elapsed_seconds as GLfloat);
+                cam_moved = true;
+            }
+            _ => {}
+        }
+        // update view matrix
+        if cam_moved {
+            mat_trans = Mat4::from_translation(&math::vec3((-cam_pos[0], -cam_pos[1], -cam_pos[2]))); // cam translation
+            mat_rot = Mat4::from_rotation_y_deg(-cam_yaw);
+            view_mat = mat_rot * mat_trans;
+            unsafe {
+                gl::UseProgram(shader_programme);
+                gl::UniformMatrix4fv(view_mat_location, 1, gl::FALSE, view_mat.as_ptr());
+            }
+        }
+
+        match context.window.get_key(Key::Escape) {
+            Action::Press | Action::Repeat => {
+                context.window.set_should_close(true);
+            }
+            _ => {}
+        }
+        // Put the stuff we've been drawing onto the display.
+        context.window.swap_buffers();
+    }
+}
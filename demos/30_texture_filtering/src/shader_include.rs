@@ -0,0 +1,358 @@
+use std::fs::File;
+use std::io::Read;
+
+
+///
+/// The text of a shader with its `#include` directives expanded, and the files it
+/// was put together from. The `#line` directives in the text number each file by
+/// its place in `files`, so an error the driver reports at `1(12)` is on line 12
+/// of `files[1]`. The shader file itself is always `files[0]`.
+///
+#[derive(Clone, Debug, PartialEq)]
+pub struct ShaderSource {
+    pub text: String,
+    pub files: Vec<String>,
+}
+
+impl ShaderSource {
+    ///
+    /// List the files with the numbers the driver uses for them in error messages.
+    ///
+    pub fn file_table(&self) -> String {
+        let mut table = String::new();
+        for (i, file_name) in self.files.iter().enumerate() {
+            table.push_str(&format!("  {}: {}\n", i, file_name));
+        }
+
+        table
+    }
+}
+
+///
+/// Tidy a path joined from an including file's directory and an include name, so
+/// that a file reached by two routes has one name: `.` components are dropped and
+/// `..` removes the component before it where there is one.
+///
+fn normalize_path(file_name: &str) -> String {
+    let absolute = file_name.starts_with('/');
+    let mut components: Vec<&str> = vec![];
+    for component in file_name.split('/') {
+        match component {
+            "" | "." => {}
+            ".." if components.last().map_or(false, |&last| last != "..") => {
+                components.pop();
+            }
+            ".." if absolute => {}
+            _ => components.push(component),
+        }
+    }
+
+    let path = components.join("/");
+    if absolute { format!("/{}", path) } else { path }
+}
+
+///
+/// Work out the file an `#include` refers to. The name is taken relative to the
+/// directory of the file that includes it, the same way a C compiler does for a
+/// quoted include.
+///
+pub fn include_path(including_file: &str, include_name: &str) -> String {
+    let include_name = include_name.replace('\\', "/");
+    if include_name.starts_with('/') {
+        return normalize_path(&include_name);
+    }
+
+    let including_file = including_file.replace('\\', "/");
+    match including_file.rfind('/') {
+        Some(slash) => normalize_path(&format!("{}/{}", &including_file[..slash], include_name)),
+        None => normalize_path(&include_name),
+    }
+}
+
+///
+/// Get the file name out of an `#include "file"` line, or `None` if the line is
+/// not an include. An include with a badly written file name is an error.
+///
+fn parse_include(line: &str) -> Option<Result<&str, String>> {
+    let line = line.trim();
+    if !line.starts_with('#') {
+        return None;
+    }
+
+    let directive = line[1..].trim();
+    if !directive.starts_with("include") {
+        return None;
+    }
+
+    let rest = directive["include".len()..].trim();
+    if rest.len() >= 2 && rest.starts_with('"') && rest.ends_with('"') {
+        Some(Ok(&rest[1..rest.len() - 1]))
+    } else {
+        Some(Err(format!("expected #include \"file\" but found {}", line)))
+    }
+}
+
+fn expand<F>(
+    file_name: &str, source: &str, read_file: &F,
+    stack: &mut Vec<String>, files: &mut Vec<String>, text: &mut String) -> Result<(), String>
+    where F: Fn(&str) -> Result<String, String>
+{
+    let file_number = files.iter().position(|f| f == file_name).unwrap();
+    for (i, line) in source.lines().enumerate() {
+        let include_name = match parse_include(line) {
+            Some(Ok(val)) => val,
+            Some(Err(e)) => return Err(format!("ERROR: {}:{}: {}", file_name, i + 1, e)),
+            None => {
+                text.push_str(line);
+                text.push('\n');
+                continue;
+            }
+        };
+
+        let included_file = include_path(file_name, include_name);
+        if stack.contains(&included_file) {
+            let mut cycle = stack.clone();
+            cycle.push(included_file);
+            return Err(format!(
+                "ERROR: {}:{}: #include cycle {}", file_name, i + 1, cycle.join(" -> ")
+            ));
+        }
+
+        let included_source = match read_file(&included_file) {
+            Ok(val) => val,
+            Err(e) => return Err(format!("ERROR: {}:{}: {}", file_name, i + 1, e)),
+        };
+        if !files.contains(&included_file) {
+            files.push(included_file.clone());
+        }
+        let included_number = files.iter().position(|f| *f == included_file).unwrap();
+
+        text.push_str(&format!("#line 1 {}\n", included_number));
+        stack.push(included_file.clone());
+        match expand(&included_file, &included_source, read_file, stack, files, text) {
+            Ok(_) => {}
+            Err(e) => return Err(e),
+        }
+        stack.pop();
+        // From GLSL 3.30 on, `#line` gives the number of the line after it.
+        text.push_str(&format!("#line {} {}\n", i + 2, file_number));
+    }
+
+    Ok(())
+}
+
+///
+/// Expand the `#include "file"` directives in a shader, reading each included file
+/// with `read_file`. Included files may include others; a file that ends up
+/// including itself is an error rather than a hang. `#line` directives around each
+/// included file keep the line numbers in compiler errors pointing at the right
+/// line of the right file.
+///
+pub fn expand_includes<F>(file_name: &str, source: &str, read_file: &F) -> Result<ShaderSource, String>
+    where F: Fn(&str) -> Result<String, String>
+{
+    let file_name = normalize_path(&file_name.replace('\\', "/"));
+    let mut stack = vec![file_name.clone()];
+    let mut files = vec![file_name.clone()];
+    let mut text = String::new();
+    match expand(&file_name, source, read_file, &mut stack, &mut files, &mut text) {
+        Ok(_) => {}
+        Err(e) => return Err(e),
+    }
+
+    Ok(ShaderSource { text: text, files: files })
+}
+
+fn read_file(file_name: &str) -> Result<String, String> {
+    let mut file = match File::open(file_name) {
+        Ok(val) => val,
+        Err(e) => return Err(format!("ERROR: opening file for reading: {}: {}", file_name, e)),
+    };
+    let mut source = String::new();
+    match file.read_to_string(&mut source) {
+        Ok(_) => Ok(source),
+        Err(e) => Err(format!("ERROR: reading shader file {}: {}", file_name, e)),
+    }
+}
+
+///
+/// Read a shader file and expand the files it includes.
+///
+pub fn load_shader_source(file_name: &str) -> Result<ShaderSource, String> {
+    let source = match read_file(file_name) {
+        Ok(val) => val,
+        Err(e) => return Err(e),
+    };
+
+    expand_includes(file_name, &source, &read_file)
+}
+
+///
+/// Put a `#define` line for each name and value into a shader, straight after its
+/// `#version` line, which has to come before anything else. A `#line` directive
+/// after the defines keeps the line numbers of the rest of the shader as they were.
+///
+pub fn insert_defines(source: &str, defines: &[(&str, &str)]) -> String {
+    if defines.is_empty() {
+        return String::from(source);
+    }
+
+    let mut define_lines = String::new();
+    for &(name, value) in defines.iter() {
+        define_lines.push_str(&format!("#define {} {}\n", name, value));
+    }
+
+    // Only blank lines and comments may come before the version.
+    let mut version_line = None;
+    for (i, line) in source.lines().enumerate() {
+        let line = line.trim();
+        if line.starts_with("#version") {
+            version_line = Some(i);
+            break;
+        } else if !line.is_empty() && !line.starts_with("//") {
+            break;
+        }
+    }
+
+    let mut text = String::new();
+    match version_line {
+        Some(version_line) => {
+            for (i, line) in source.lines().enumerate() {
+                text.push_str(line);
+                text.push('\n');
+                if i == version_line {
+                    text.push_str(&define_lines);
+                    text.push_str(&format!("#line {} 0\n", version_line + 2));
+                }
+            }
+        }
+        None => {
+            text.push_str(&define_lines);
+            text.push_str("#line 1 0\n");
+            text.push_str(source);
+        }
+    }
+
+    text
+}
+
+mod shader_include_tests {
+    use super::{expand_includes, include_path, insert_defines};
+
+    use std::cell::Cell;
+    use std::collections::HashMap;
+
+    fn files(entries: &[(&str, &str)]) -> HashMap<String, String> {
+        entries.iter().map(|&(name, text)| (String::from(name), String::from(text))).collect()
+    }
+
+    fn read_from(files: &HashMap<String, String>, file_name: &str) -> Result<String, String> {
+        match files.get(file_name) {
+            Some(text) => Ok(text.clone()),
+            None => Err(format!("no such file {}", file_name)),
+        }
+    }
+
+    #[test]
+    fn test_include_paths_are_relative_to_the_including_file() {
+        assert_eq!(include_path("src/test.frag.glsl", "fog.glsl"), "src/fog.glsl");
+        assert_eq!(include_path("src/test.frag.glsl", "../common/fog.glsl"), "common/fog.glsl");
+        assert_eq!(include_path("src/lib/fog.glsl", "./light.glsl"), "src/lib/light.glsl");
+        assert_eq!(include_path("test.frag.glsl", "fog.glsl"), "fog.glsl");
+        assert_eq!(include_path("src\\test.frag.glsl", "lib\\fog.glsl"), "src/lib/fog.glsl");
+    }
+
+    #[test]
+    fn test_a_shader_without_includes_is_unchanged() {
+        let source = "#version 410\nout vec4 frag_colour;\nvoid main() {}\n";
+        let expanded = expand_includes("src/test.frag.glsl", source, &|_: &str| -> Result<String, String> {
+            panic!("nothing should be read")
+        }).unwrap();
+
+        assert_eq!(expanded.text, source);
+        assert_eq!(expanded.files, vec!["src/test.frag.glsl"]);
+    }
+
+    #[test]
+    fn test_includes_are_expanded_with_line_directives() {
+        let files = files(&[
+            ("src/fog.glsl", "#include \"lib/light.glsl\"\nfloat fog_factor (float dist);"),
+            ("src/lib/light.glsl", "vec3 light ();"),
+        ]);
+        let source = "#version 410\n  #  include \"fog.glsl\"\nvoid main() {}";
+
+        let expanded = expand_includes("src/test.frag.glsl", source, &|f: &str| read_from(&files, f)).unwrap();
+
+        let expected = [
+            "#version 410",
+            "#line 1 1",
+            "#line 1 2",
+            "vec3 light ();",
+            "#line 2 1",
+            "float fog_factor (float dist);",
+            "#line 3 0",
+            "void main() {}",
+            "",
+        ].join("\n");
+        assert_eq!(expanded.text, expected);
+        assert_eq!(expanded.files, vec!["src/test.frag.glsl", "src/fog.glsl", "src/lib/light.glsl"]);
+        assert_eq!(expanded.file_table(), "  0: src/test.frag.glsl\n  1: src/fog.glsl\n  2: src/lib/light.glsl\n");
+    }
+
+    #[test]
+    fn test_a_file_included_twice_keeps_its_number() {
+        let files = files(&[("src/fog.glsl", "// fog")]);
+        let source = "#include \"fog.glsl\"\n#include \"./fog.glsl\"";
+
+        let expanded = expand_includes("src/test.frag.glsl", source, &|f: &str| read_from(&files, f)).unwrap();
+
+        assert_eq!(expanded.text, "#line 1 1\n// fog\n#line 2 0\n#line 1 1\n// fog\n#line 3 0\n");
+        assert_eq!(expanded.files.len(), 2);
+    }
+
+    #[test]
+    fn test_include_cycles_are_reported() {
+        let files = files(&[
+            ("src/a.glsl", "#include \"b.glsl\""),
+            ("src/b.glsl", "\n#include \"a.glsl\""),
+        ]);
+        let reads = Cell::new(0);
+        let read_file = |file_name: &str| {
+            reads.set(reads.get() + 1);
+            read_from(&files, file_name)
+        };
+
+        let error = expand_includes("src/a.glsl", "#include \"b.glsl\"", &read_file).unwrap_err();
+
+        assert_eq!(error, "ERROR: src/b.glsl:2: #include cycle src/a.glsl -> src/b.glsl -> src/a.glsl");
+        assert_eq!(reads.get(), 1);
+    }
+
+    #[test]
+    fn test_missing_and_malformed_includes_name_the_line() {
+        let files = files(&[]);
+
+        let missing = expand_includes("src/test.frag.glsl", "\n#include \"fog.glsl\"", &|f: &str| read_from(&files, f));
+        assert_eq!(missing.unwrap_err(), "ERROR: src/test.frag.glsl:2: no such file src/fog.glsl");
+
+        let malformed = expand_includes("src/test.frag.glsl", "#include <fog.glsl>", &|f: &str| read_from(&files, f));
+        assert!(malformed.unwrap_err().starts_with("ERROR: src/test.frag.glsl:1: expected #include"));
+    }
+
+    #[test]
+    fn test_defines_go_after_the_version_and_keep_the_line_numbers() {
+        let source = "// fog\n#version 410\nout vec4 frag_colour;";
+        let defines = [("USE_FOG", "1"), ("FOG_MODE", "2")];
+
+        let expected = "// fog\n#version 410\n#define USE_FOG 1\n#define FOG_MODE 2\n#line 3 0\nout vec4 frag_colour;\n";
+        assert_eq!(insert_defines(source, &defines), expected);
+    }
+
+    #[test]
+    fn test_defines_go_first_in_a_shader_without_a_version() {
+        let source = "out vec4 frag_colour;\n#version 410\n";
+
+        assert_eq!(insert_defines(source, &[("USE_FOG", "1")]), format!("#define USE_FOG 1\n#line 1 0\n{}", source));
+        assert_eq!(insert_defines(source, &[]), source);
+    }
+}
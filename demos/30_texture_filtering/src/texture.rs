@@ -0,0 +1,522 @@
+use gl;
+use gl::types::{GLenum, GLint, GLuint, GLvoid};
+use stb_image::image::LoadResult;
+
+use assets;
+
+
+pub const GL_TEXTURE_MAX_ANISOTROPY_EXT: GLenum = 0x84FE;
+pub const GL_MAX_TEXTURE_MAX_ANISOTROPY_EXT: GLenum = 0x84FF;
+
+
+///
+/// The ways a texture can be sampled when a texel covers less than a pixel on
+/// screen. The mipmap filters pick one or two mipmap levels first, then filter
+/// within them.
+///
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum MinFilter {
+    Nearest,
+    Linear,
+    NearestMipmapNearest,
+    LinearMipmapNearest,
+    NearestMipmapLinear,
+    LinearMipmapLinear,
+}
+
+impl MinFilter {
+    pub fn to_gl(&self) -> GLenum {
+        match *self {
+            MinFilter::Nearest => gl::NEAREST,
+            MinFilter::Linear => gl::LINEAR,
+            MinFilter::NearestMipmapNearest => gl::NEAREST_MIPMAP_NEAREST,
+            MinFilter::LinearMipmapNearest => gl::LINEAR_MIPMAP_NEAREST,
+            MinFilter::NearestMipmapLinear => gl::NEAREST_MIPMAP_LINEAR,
+            MinFilter::LinearMipmapLinear => gl::LINEAR_MIPMAP_LINEAR,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            MinFilter::Nearest => "GL_NEAREST",
+            MinFilter::Linear => "GL_LINEAR",
+            MinFilter::NearestMipmapNearest => "GL_NEAREST_MIPMAP_NEAREST",
+            MinFilter::LinearMipmapNearest => "GL_LINEAR_MIPMAP_NEAREST",
+            MinFilter::NearestMipmapLinear => "GL_NEAREST_MIPMAP_LINEAR",
+            MinFilter::LinearMipmapLinear => "GL_LINEAR_MIPMAP_LINEAR",
+        }
+    }
+
+    ///
+    /// The next filter, in order from the cheapest to the smoothest.
+    ///
+    pub fn next(&self) -> MinFilter {
+        match *self {
+            MinFilter::Nearest => MinFilter::Linear,
+            MinFilter::Linear => MinFilter::NearestMipmapNearest,
+            MinFilter::NearestMipmapNearest => MinFilter::LinearMipmapNearest,
+            MinFilter::LinearMipmapNearest => MinFilter::NearestMipmapLinear,
+            MinFilter::NearestMipmapLinear => MinFilter::LinearMipmapLinear,
+            MinFilter::LinearMipmapLinear => MinFilter::Nearest,
+        }
+    }
+}
+
+///
+/// The ways a texture can be sampled when a texel covers more than a pixel on
+/// screen. Mipmaps play no part in magnification.
+///
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum MagFilter {
+    Nearest,
+    Linear,
+}
+
+impl MagFilter {
+    pub fn to_gl(&self) -> GLenum {
+        match *self {
+            MagFilter::Nearest => gl::NEAREST,
+            MagFilter::Linear => gl::LINEAR,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            MagFilter::Nearest => "GL_NEAREST",
+            MagFilter::Linear => "GL_LINEAR",
+        }
+    }
+
+    pub fn next(&self) -> MagFilter {
+        match *self {
+            MagFilter::Nearest => MagFilter::Linear,
+            MagFilter::Linear => MagFilter::Nearest,
+        }
+    }
+}
+
+///
+/// The largest anisotropy level the driver supports. A driver without anisotropic
+/// filtering reports 1.0, which is the same as turning it off.
+///
+pub fn max_anisotropy() -> f32 {
+    let mut max_aniso = 0.0;
+    unsafe {
+        gl::GetFloatv(GL_MAX_TEXTURE_MAX_ANISOTROPY_EXT, &mut max_aniso);
+    }
+
+    f32::max(max_aniso, 1.0)
+}
+
+///
+/// The anisotropy levels worth comparing: 1x, which turns anisotropic filtering
+/// off, then each power of two up to the largest level supported.
+///
+pub fn anisotropy_levels(max_aniso: f32) -> Vec<f32> {
+    let mut levels = vec![1.0];
+    let mut level = 2.0;
+    while level <= max_aniso {
+        levels.push(level);
+        level *= 2.0;
+    }
+
+    levels
+}
+
+///
+/// How the bytes of an image are read back when the texture is sampled. Images
+/// painted or photographed for the screen are stored with the display's gamma,
+/// so the same byte means less light at the low end than its value says.
+///
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ColourSpace {
+    ///
+    /// Each byte is read back as it is, over 255.
+    ///
+    Linear,
+    ///
+    /// Each byte is decoded from sRGB to linear light as it is read, before
+    /// filtering, and the mipmaps are averaged in linear light too.
+    ///
+    Srgb,
+}
+
+impl ColourSpace {
+    ///
+    /// The internal format to store an 8 bit RGBA image in.
+    ///
+    pub fn internal_format(&self) -> GLenum {
+        match *self {
+            ColourSpace::Linear => gl::RGBA8,
+            ColourSpace::Srgb => gl::SRGB8_ALPHA8,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            ColourSpace::Linear => "linear",
+            ColourSpace::Srgb => "sRGB",
+        }
+    }
+
+    pub fn toggle(&self) -> ColourSpace {
+        match *self {
+            ColourSpace::Linear => ColourSpace::Srgb,
+            ColourSpace::Srgb => ColourSpace::Linear,
+        }
+    }
+}
+
+///
+/// A 2D texture loaded from an image file, with mipmaps. The sampling state is
+/// changed through the setters, which remember the current settings so they can
+/// be shown to the user. A `Sampler` bound to the same texture unit overrides it.
+///
+pub struct Texture {
+    tex: GLuint,
+    colour_space: ColourSpace,
+    min_filter: MinFilter,
+    mag_filter: MagFilter,
+    anisotropy: f32,
+}
+
+impl Texture {
+    ///
+    /// Load an image file into a texture with a full set of mipmaps. The texture
+    /// repeats, and starts with trilinear filtering and anisotropic filtering off.
+    ///
+    pub fn load(file_name: &str) -> Result<Texture, String> {
+        Texture::load_with_colour_space(file_name, ColourSpace::Linear)
+    }
+
+    ///
+    /// Load an image file into a texture that reads its bytes back in
+    /// `colour_space`.
+    ///
+    pub fn load_with_colour_space(file_name: &str, colour_space: ColourSpace) -> Result<Texture, String> {
+        let force_channels = 4;
+        let mut image_data = match assets::load_image_with_depth(file_name, force_channels) {
+            LoadResult::ImageU8(image_data) => image_data,
+            LoadResult::Error(e) => {
+                return Err(format!("ERROR: could not load {}: {}", file_name, e));
+            }
+            LoadResult::ImageF32(_) => {
+                return Err(format!("ERROR: Tried to load an image as byte vectors, got f32: {}", file_name));
+            }
+        };
+
+        let width = image_data.width;
+        let height = image_data.height;
+
+        // Check that the image size is a power of two.
+        if (width & (width - 1)) != 0 || (height & (height - 1)) != 0 {
+            eprintln!("WARNING: texture {} is not power-of-2 dimensions", file_name);
+        }
+
+        // Flip the image upside down, since GL expects the bottom row first.
+        let width_in_bytes = 4 * width;
+        let half_height = height / 2;
+        for row in 0..half_height {
+            for col in 0..width_in_bytes {
+                let temp = image_data.data[row * width_in_bytes + col];
+                image_data.data[row * width_in_bytes + col] = image_data.data[((height - row - 1) * width_in_bytes) + col];
+                image_data.data[((height - row - 1) * width_in_bytes) + col] = temp;
+            }
+        }
+
+        let mut tex = 0;
+        unsafe {
+            gl::GenTextures(1, &mut tex);
+            gl::ActiveTexture(gl::TEXTURE0);
+            gl::BindTexture(gl::TEXTURE_2D, tex);
+            gl::TexImage2D(
+                gl::TEXTURE_2D, 0, colour_space.internal_format() as i32, width as i32, height as i32, 0,
+                gl::RGBA, gl::UNSIGNED_BYTE,
+                image_data.data.as_ptr() as *const GLvoid
+            );
+            gl::GenerateMipmap(gl::TEXTURE_2D);
+        }
+
+        let mut texture = Texture {
+            tex: tex,
+            colour_space: colour_space,
+            min_filter: MinFilter::LinearMipmapLinear,
+            mag_filter: MagFilter::Linear,
+            anisotropy: 1.0,
+        };
+        texture.set_wrap(gl::REPEAT);
+        texture.set_min_filter(MinFilter::LinearMipmapLinear);
+        texture.set_mag_filter(MagFilter::Linear);
+        texture.set_anisotropy(1.0);
+
+        Ok(texture)
+    }
+
+    pub fn handle(&self) -> GLuint {
+        self.tex
+    }
+
+    pub fn colour_space(&self) -> ColourSpace {
+        self.colour_space
+    }
+
+    pub fn min_filter(&self) -> MinFilter {
+        self.min_filter
+    }
+
+    pub fn mag_filter(&self) -> MagFilter {
+        self.mag_filter
+    }
+
+    pub fn anisotropy(&self) -> f32 {
+        self.anisotropy
+    }
+
+    ///
+    /// Bind the texture to a texture unit.
+    ///
+    pub fn bind(&self, unit: u32) {
+        unsafe {
+            gl::ActiveTexture(gl::TEXTURE0 + unit);
+            gl::BindTexture(gl::TEXTURE_2D, self.tex);
+        }
+    }
+
+    ///
+    /// Set how the texture wraps in both directions, e.g. `gl::REPEAT` or
+    /// `gl::CLAMP_TO_EDGE`. This leaves the texture bound to the active unit.
+    ///
+    pub fn set_wrap(&mut self, wrap: GLenum) {
+        unsafe {
+            gl::BindTexture(gl::TEXTURE_2D, self.tex);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, wrap as GLint);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, wrap as GLint);
+        }
+    }
+
+    ///
+    /// Set the minification filter. This leaves the texture bound to the active unit.
+    ///
+    pub fn set_min_filter(&mut self, filter: MinFilter) {
+        unsafe {
+            gl::BindTexture(gl::TEXTURE_2D, self.tex);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, filter.to_gl() as GLint);
+        }
+        self.min_filter = filter;
+    }
+
+    ///
+    /// Set the magnification filter. This leaves the texture bound to the active unit.
+    ///
+    pub fn set_mag_filter(&mut self, filter: MagFilter) {
+        unsafe {
+            gl::BindTexture(gl::TEXTURE_2D, self.tex);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, filter.to_gl() as GLint);
+        }
+        self.mag_filter = filter;
+    }
+
+    ///
+    /// Set the anisotropy level, clamped to what the driver supports, and return
+    /// the level actually used. A level of 1.0 turns anisotropic filtering off.
+    /// This leaves the texture bound to the active unit.
+    ///
+    pub fn set_anisotropy(&mut self, level: f32) -> f32 {
+        let level = f32::min(f32::max(level, 1.0), max_anisotropy());
+        unsafe {
+            gl::BindTexture(gl::TEXTURE_2D, self.tex);
+            gl::TexParameterf(gl::TEXTURE_2D, GL_TEXTURE_MAX_ANISOTROPY_EXT, level);
+        }
+        self.anisotropy = level;
+
+        level
+    }
+}
+
+///
+/// How a sampler reads a texture: its filters, its anisotropy level, and how it
+/// wraps texture coordinates outside 0 to 1.
+///
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct SamplerSettings {
+    pub min_filter: MinFilter,
+    pub mag_filter: MagFilter,
+    pub anisotropy: f32,
+    pub wrap: GLenum,
+}
+
+impl SamplerSettings {
+    ///
+    /// Trilinear filtering with anisotropic filtering off, repeating the texture;
+    /// the same settings `Texture::load` gives a texture.
+    ///
+    pub fn new() -> SamplerSettings {
+        SamplerSettings {
+            min_filter: MinFilter::LinearMipmapLinear,
+            mag_filter: MagFilter::Linear,
+            anisotropy: 1.0,
+            wrap: gl::REPEAT,
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        format!(
+            "min {}  mag {}  anisotropy {}x", self.min_filter.name(), self.mag_filter.name(), self.anisotropy
+        )
+    }
+}
+
+///
+/// A GL sampler object. A sampler bound to a texture unit overrides the sampling
+/// state of whichever texture is bound to the same unit, so one texture can be
+/// read with different filtering in different draws, or by different passes,
+/// without changing the texture itself.
+///
+pub struct Sampler {
+    id: GLuint,
+    settings: SamplerSettings,
+}
+
+impl Sampler {
+    pub fn new(settings: SamplerSettings) -> Sampler {
+        let mut id = 0;
+        unsafe {
+            gl::GenSamplers(1, &mut id);
+        }
+        let mut sampler = Sampler { id: id, settings: settings };
+        sampler.set(settings);
+
+        sampler
+    }
+
+    pub fn id(&self) -> GLuint {
+        self.id
+    }
+
+    pub fn settings(&self) -> SamplerSettings {
+        self.settings
+    }
+
+    ///
+    /// Change all of the sampling state at once. The anisotropy level is clamped
+    /// to what the driver supports. Samplers are changed directly, without being
+    /// bound.
+    ///
+    pub fn set(&mut self, settings: SamplerSettings) {
+        let anisotropy = f32::min(f32::max(settings.anisotropy, 1.0), max_anisotropy());
+        unsafe {
+            gl::SamplerParameteri(self.id, gl::TEXTURE_WRAP_S, settings.wrap as GLint);
+            gl::SamplerParameteri(self.id, gl::TEXTURE_WRAP_T, settings.wrap as GLint);
+            gl::SamplerParameteri(self.id, gl::TEXTURE_MIN_FILTER, settings.min_filter.to_gl() as GLint);
+            gl::SamplerParameteri(self.id, gl::TEXTURE_MAG_FILTER, settings.mag_filter.to_gl() as GLint);
+            gl::SamplerParameterf(self.id, GL_TEXTURE_MAX_ANISOTROPY_EXT, anisotropy);
+        }
+        self.settings = SamplerSettings { anisotropy: anisotropy, ..settings };
+    }
+
+    pub fn set_min_filter(&mut self, filter: MinFilter) {
+        let settings = SamplerSettings { min_filter: filter, ..self.settings };
+        self.set(settings);
+    }
+
+    pub fn set_mag_filter(&mut self, filter: MagFilter) {
+        let settings = SamplerSettings { mag_filter: filter, ..self.settings };
+        self.set(settings);
+    }
+
+    ///
+    /// Set the anisotropy level, and return the level actually used.
+    ///
+    pub fn set_anisotropy(&mut self, level: f32) -> f32 {
+        let settings = SamplerSettings { anisotropy: level, ..self.settings };
+        self.set(settings);
+
+        self.settings.anisotropy
+    }
+
+    ///
+    /// Bind the sampler to a texture unit, where it stays until another sampler is
+    /// bound there or `unbind` is called.
+    ///
+    pub fn bind(&self, unit: u32) {
+        unsafe {
+            gl::BindSampler(unit, self.id);
+        }
+    }
+
+    ///
+    /// Take any sampler off a texture unit, so its texture's own sampling state is
+    /// used again.
+    ///
+    pub fn unbind(unit: u32) {
+        unsafe {
+            gl::BindSampler(unit, 0);
+        }
+    }
+}
+
+impl Drop for Sampler {
+    fn drop(&mut self) {
+        // The functions are not loaded when there is no GL context, as in the tests.
+        if self.id != 0 && gl::DeleteSamplers::is_loaded() {
+            unsafe {
+                gl::DeleteSamplers(1, &self.id);
+            }
+        }
+    }
+}
+
+mod texture_tests {
+    use super::{anisotropy_levels, ColourSpace, MagFilter, MinFilter, SamplerSettings};
+
+    #[test]
+    fn test_min_filters_cycle_through_all_six_filters() {
+        let mut filter = MinFilter::Nearest;
+        let mut names = vec![];
+        for _ in 0..6 {
+            names.push(filter.name());
+            filter = filter.next();
+        }
+        names.sort();
+        names.dedup();
+
+        assert_eq!(filter, MinFilter::Nearest);
+        assert_eq!(names.len(), 6);
+    }
+
+    #[test]
+    fn test_mag_filters_alternate() {
+        assert_eq!(MagFilter::Nearest.next(), MagFilter::Linear);
+        assert_eq!(MagFilter::Linear.next(), MagFilter::Nearest);
+    }
+
+    #[test]
+    fn test_anisotropy_levels_are_powers_of_two_up_to_the_maximum() {
+        assert_eq!(anisotropy_levels(16.0), vec![1.0, 2.0, 4.0, 8.0, 16.0]);
+        assert_eq!(anisotropy_levels(12.0), vec![1.0, 2.0, 4.0, 8.0]);
+    }
+
+    #[test]
+    fn test_anisotropy_levels_without_support_are_just_off() {
+        assert_eq!(anisotropy_levels(1.0), vec![1.0]);
+        assert_eq!(anisotropy_levels(0.0), vec![1.0]);
+    }
+
+    #[test]
+    fn test_default_sampler_settings_are_trilinear_and_repeat() {
+        let settings = SamplerSettings::new();
+
+        assert_eq!(settings.min_filter, MinFilter::LinearMipmapLinear);
+        assert_eq!(settings.mag_filter, MagFilter::Linear);
+        assert_eq!(settings.wrap, gl::REPEAT);
+        assert_eq!(settings.describe(), "min GL_LINEAR_MIPMAP_LINEAR  mag GL_LINEAR  anisotropy 1x");
+    }
+
+    #[test]
+    fn test_only_srgb_textures_are_decoded() {
+        assert_eq!(ColourSpace::Linear.internal_format(), gl::RGBA8);
+        assert_eq!(ColourSpace::Srgb.internal_format(), gl::SRGB8_ALPHA8);
+        assert_eq!(ColourSpace::Linear.toggle(), ColourSpace::Srgb);
+        assert_eq!(ColourSpace::Srgb.toggle().name(), "linear");
+    }
+}
@@ -0,0 +1,385 @@
+extern crate gl;
+extern crate glfw;
+extern crate chrono;
+extern crate stb_image;
+#[cfg(feature = "glam")]
+extern crate glam;
+#[cfg(feature = "cgmath")]
+extern crate cgmath;
+
+#[macro_use]
+mod gl_utils;
+mod graphics_math;
+mod obj_parser;
+mod colour;
+mod assets;
+mod shader_include;
+mod spirv;
+mod program_cache;
+mod logger;
+mod overlay;
+mod streaming_buffer;
+mod texture_binder;
+mod vertex_layout;
+
+
+use glfw::{Action, Context, Key};
+use gl::types::{GLfloat, GLvoid, GLuint};
+use stb_image::image::LoadResult;
+
+use std::process;
+
+use gl_utils::*;
+
+use graphics_math as math;
+use math::Mat4;
+
+use overlay::Overlay;
+use texture_binder::TextureBinder;
+use vertex_layout::VertexLayout;
+
+
+const GL_LOG_FILE: &str = "gl.log";
+const VERTEX_SHADER_FILE: &str = "src/test.vert.glsl";
+const FRAGMENT_SHADER_FILE: &str = "src/test.frag.glsl";
+const MESH_FILE: &str = "src/suzanne.obj";
+
+// The sampler each map is read through, the image it is loaded from, and the
+// name shown in the overlay. The order matches the components of `map_weights`.
+const MAPS: [(&str, &str, &str); 4] = [
+    ("diffuse_map", "src/boulder_diff.png", "diffuse"),
+    ("specular_map", "src/boulder_spec.png", "specular"),
+    ("ambient_map", "src/ao.png", "ambient occlusion"),
+    ("emission_map", "src/tileable9b_emiss.png", "emission"),
+];
+// How quickly a map fades in or out when it is toggled, in weight per second.
+const MAP_FADE_SPEED: f32 = 2.0;
+
+const OVERLAY_TEXT_COLOUR: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+const OVERLAY_OFF_COLOUR: [f32; 4] = [0.5, 0.5, 0.5, 1.0];
+const OVERLAY_BACKGROUND_COLOUR: [f32; 4] = [0.0, 0.0, 0.0, 0.6];
+
+const GL_TEXTURE_MAX_ANISOTROPY_EXT: u32 = 0x84FE;
+const GL_MAX_TEXTURE_MAX_ANISOTROPY_EXT: u32 = 0x84FF;
+
+
+fn load_texture(file_name: &str, tex: &mut GLuint) -> bool {
+    let force_channels = 4;
+    let mut image_data = match assets::load_image_with_depth(file_name, force_channels) {
+        LoadResult::ImageU8(image_data) => image_data,
+        LoadResult::Error(_) => {
+            eprintln!("ERROR: could not load {}", file_name);
+            return false;
+        }
+        LoadResult::ImageF32(_) => {
+            eprintln!("ERROR: Tried to load an image as byte vectors, got f32: {}", file_name);
+            return false;
+        }
+    };
+
+    let width = image_data.width;
+    let height = image_data.height;
+
+    // Check that the image size is a power of two.
+    if (width & (width - 1)) != 0 || (height & (height - 1)) != 0 {
+        eprintln!("WARNING: texture {} is not power-of-2 dimensions", file_name);
+    }
+
+    let width_in_bytes = width * 4;
+    let half_height = height / 2;
+    for row in 0..half_height {
+        for col in 0..width_in_bytes {
+            let temp = image_data.data[row * width_in_bytes + col];
+            image_data.data[row * width_in_bytes + col] = image_data.data[((height - row - 1) * width_in_bytes) + col];
+            image_data.data[((height - row - 1) * width_in_bytes) + col] = temp;
+        }
+    }
+
+    unsafe {
+        gl::GenTextures(1, tex);
+        gl::ActiveTexture(gl::TEXTURE0);
+        gl::BindTexture(gl::TEXTURE_2D, *tex);
+        gl::TexImage2D(
+            gl::TEXTURE_2D, 0, gl::RGBA as i32, width as i32, height as i32, 0,
+            gl::RGBA, gl::UNSIGNED_BYTE,
+            image_data.data.as_ptr() as *const GLvoid
+        );
+        gl::GenerateMipmap(gl::TEXTURE_2D);
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as i32);
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as i32);
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::LINEAR as i32);
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::LINEAR_MIPMAP_LINEAR as i32);
+    }
+
+    let mut max_aniso = 0.0;
+    unsafe {
+        gl::GetFloatv(GL_MAX_TEXTURE_MAX_ANISOTROPY_EXT, &mut max_aniso);
+        // Set the maximum!
+        gl::TexParameterf(gl::TEXTURE_2D, GL_TEXTURE_MAX_ANISOTROPY_EXT, max_aniso);
+    }
+
+    return true;
+}
+
+///
+/// Move a map's weight towards fully on or fully off, so toggled maps fade in and
+/// out instead of popping.
+///
+fn fade_weight(weight: f32, enabled: bool, elapsed_seconds: f32) -> f32 {
+    let step = MAP_FADE_SPEED * elapsed_seconds;
+    if enabled {
+        f32::min(weight + step, 1.0)
+    } else {
+        f32::max(weight - step, 0.0)
+    }
+}
+
+fn main() {
+    let logger = restart_gl_log(GL_LOG_FILE);
+    // Start a GL context and O/S window using the GLFW helper library.
+    let mut context = match start_gl(&logger) {
+        Ok(val) => val,
+        Err(e) => {
+            eprintln!("Failed to Initialize OpenGL context. Got error:");
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+    };
+
+    unsafe {
+        // Enable depth testing.
+        gl::Enable(gl::DEPTH_TEST);
+        // Depth testing interprets a smaller value as closer to the eye.
+        gl::DepthFunc(gl::LESS);
+        // Cull face.
+        gl::Enable(gl::CULL_FACE);
+        // Cull back face.
+        gl::CullFace(gl::BACK);
+        // GL_CW for clockwise.
+        gl::FrontFace(gl::CCW);
+        // grey background to help spot mistakes
+        gl::ClearColor(0.2, 0.2, 0.2, 1.0);
+        gl::Viewport(0, 0, context.width as i32, context.height as i32);
+    }
+
+    /*------------------------------CREATE GEOMETRY------------------------------*/
+    let mesh = match obj_parser::load_obj_file(MESH_FILE) {
+        Ok(val) => val,
+        Err(e) => {
+            logger.log_err(&format!("ERROR: loading mesh file. Loader returned error\n{}", e));
+            logger.log_err("WARNING: using the embedded fallback mesh instead.");
+            assets::fallback_mesh()
+        }
+    };
+
+    let vp = mesh.points;
+    let vn = mesh.normals;
+    let vt = mesh.tex_coords;
+    let g_point_count = mesh.point_count;
+
+    let mut vao = 0;
+    unsafe {
+        gl::GenVertexArrays(1, &mut vao);
+    }
+    assert!(vao > 0);
+
+    VertexLayout::new().floats(0, 3).upload(vao, &vp, g_point_count);
+    VertexLayout::new().floats(1, 3).upload(vao, &vn, g_point_count);
+    if vt.len() == 2 * g_point_count {
+        VertexLayout::new().floats(2, 2).upload(vao, &vt, g_point_count);
+    }
+
+    /*-------------------------------CREATE SHADERS------------------------------*/
+    let shader_programme = create_programme_from_files(&logger, VERTEX_SHADER_FILE, FRAGMENT_SHADER_FILE);
+    let view_mat_location = uniform_location(shader_programme, "view");
+    assert!(view_mat_location > -1);
+    let proj_mat_location = uniform_location(shader_programme, "proj");
+    assert!(proj_mat_location > -1);
+    let map_weights_location = uniform_location(shader_programme, "map_weights");
+    assert!(map_weights_location > -1);
+
+    // load each map and let the texture binder pick its unit.
+    let mut texture_binder = TextureBinder::new(shader_programme, 0);
+    for &(sampler, file_name, _) in MAPS.iter() {
+        let mut tex = 0;
+        load_texture(file_name, &mut tex);
+        match texture_binder.add_sampler(sampler, tex) {
+            Ok(unit) => {
+                logger.log(&format!("{} is on texture unit {}", sampler, unit));
+            }
+            Err(e) => {
+                logger.log_err(&e);
+            }
+        }
+    }
+
+    let mut maps_enabled = [true; 4];
+    let mut map_weights: [GLfloat; 4] = [1.0; 4];
+    logger.log("keys: 1 to 4 toggle the diffuse, specular, ambient occlusion, and emission maps,");
+    logger.log("      O toggles the overlay");
+
+    // which maps are blended in, drawn over the scene
+    let mut overlay = Overlay::new(&logger);
+    let mut show_overlay = true;
+
+    // input variables
+    let near = 0.1;                                  // clipping plane
+    let far = 100.0;                                 // clipping plane
+    let fov = 67.0;                                  // convert 67 degrees to radians
+    let aspect = context.width as f32 / context.height as f32; // aspect ratio
+    let proj_mat = Mat4::perspective(fov, aspect, near, far);
+
+    // matrix components
+    let cam_speed: GLfloat = 1.0;             // 1 unit per second
+    let cam_yaw_speed: GLfloat = 10.0;        // 10 degrees per second
+    let mut cam_pos: [GLfloat; 3] = [0.0, 0.0, 2.0]; // don't start at zero, or we will be too close
+    let mut cam_yaw: GLfloat = 0.0;               // y-rotation in degrees
+    let mut mat_trans = Mat4::from_translation(&math::vec3((-cam_pos[0], -cam_pos[1], -cam_pos[2])));
+    let mut mat_rot = Mat4::from_rotation_y_deg(-cam_yaw);
+    let mut view_mat = mat_rot * mat_trans;
+
+    unsafe {
+        gl::UseProgram(shader_programme);
+        gl::UniformMatrix4fv(view_mat_location, 1, gl::FALSE, view_mat.as_ptr());
+        gl::UniformMatrix4fv(proj_mat_location, 1, gl::FALSE, proj_mat.as_ptr());
+    }
+
+    while !context.window.should_close() {
+        let current_seconds = context.glfw.get_time();
+        let elapsed_seconds = current_seconds - context.elapsed_time_seconds;
+        context.elapsed_time_seconds = current_seconds;
+
+        update_fps_counter(&mut context);
+        for i in 0..map_weights.len() {
+            map_weights[i] = fade_weight(map_weights[i], maps_enabled[i], elapsed_seconds as f32);
+        }
+        unsafe {
+            // wipe the drawing surface clear
+            gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
+            gl::Viewport(0, 0, context.width as i32, context.height as i32);
+
+            texture_binder.bind();
+            gl::UseProgram(shader_programme);
+            gl::Uniform4fv(map_weights_location, 1, map_weights.as_ptr());
+            gl::BindVertexArray(vao);
+            gl::DrawArrays(gl::TRIANGLES, 0, g_point_count as i32);
+        }
+
+        if show_overlay {
+            let scale = 2.0;
+            let line = overlay::LINE_HEIGHT * scale;
+            let longest_line = MAPS.iter().map(|map| map.2.len()).max().unwrap_or(0) + 7;
+            let width = (longest_line as f32 * overlay::GLYPH_ADVANCE + 4.0) * scale;
+            let height = MAPS.len() as f32 * line + 4.0 * scale;
+            overlay.rect(10.0, 10.0, width, height, OVERLAY_BACKGROUND_COLOUR);
+            for (i, &(_, _, label)) in MAPS.iter().enumerate() {
+                let (state, colour) = if maps_enabled[i] {
+                    ("on ", OVERLAY_TEXT_COLOUR)
+                } else {
+                    ("off", OVERLAY_OFF_COLOUR)
+                };
+                let text = format!("{} {} {}", i + 1, state, label);
+                overlay.text(10.0 + 2.0 * scale, 10.0 + 2.0 * scale + i as f32 * line, scale, colour, &text);
+            }
+            overlay.draw(context.width, context.height);
+        }
+
+        context.glfw.poll_events();
+
+        for (_, event) in glfw::flush_messages(&context.events) {
+            let toggled = match event {
+                glfw::WindowEvent::Key(Key::Num1, _, Action::Press, _) => Some(0),
+                glfw::WindowEvent::Key(Key::Num2, _, Action::Press, _) => Some(1),
+                glfw::WindowEvent::Key(Key::Num3, _, Action::Press, _) => Some(2),
+                glfw::WindowEvent::Key(Key::Num4, _, Action::Press, _) => Some(3),
+                glfw::WindowEvent::Key(Key::O, _, Action::Press, _) => {
+                    show_overlay = !show_overlay;
+                    None
+                }
+                _ => None,
+            };
+            if let Some(i) = toggled {
+                maps_enabled[i] = !maps_enabled[i];
+                logger.log(&format!("{} map: {}", MAPS[i].2, if maps_enabled[i] { "on" } else { "off" }));
+            }
+        }
+
+        // control keys
+        let mut cam_moved = false;
+        match context.window.get_key(Key::A) {
+            Action::Press | Action::Repeat => {
+                cam_pos[0] -= cam_speed * (elapsed_seconds as GLfloat);
+                cam_moved = true;
+            }
+            _ => {}
+        }
+        match context.window.get_key(Key::D) {
+            Action::Press | Action::Repeat => {
+                cam_pos[0] += cam_speed * (elapsed_seconds as GLfloat);
+                cam_moved = true;
+            }
+            _ => {}
+        }
+        match context.window.get_key(Key::Up) {
+            Action::Press | Action::Repeat => {
+                cam_pos[1] += cam_speed * (elapsed_seconds as GLfloat);
+                cam_moved = true;
+            }
+            _ => {}
+        }
+        match context.window.get_key(Key::Down) {
+            Action::Press | Action::Repeat => {
+                cam_pos[1] -= cam_speed * (elapsed_seconds as GLfloat);
+                cam_moved = true;
+            }
+            _ => {}
+        }
+        match context.window.get_key(Key::W) {
+            Action::Press | Action::Repeat => {
+                cam_pos[2] -= cam_speed * (elapsed_seconds as GLfloat);
+                cam_moved = true;
+            }
+            _ => {}
+        }
+        match context.window.get_key(Key::S) {
+            Action::Press | Action::Repeat => {
+                cam_pos[2] += cam_speed * (elapsed_seconds as GLfloat);
+                cam_moved = true;
+            }
+            _ => {}
+        }
+        match context.window.get_key(Key::Left) {
+            Action::Press | Action::Repeat => {
+                cam_yaw += cam_yaw_speed * (elapsed_seconds as GLfloat);
+                cam_moved = true;
+            }
+            _ => {}
+        }
+        match context.window.get_key(Key::Right) {
+            Action::Press | Action::Repeat => {
+                cam_yaw -= cam_yaw_speed * (elapsed_seconds as GLfloat);
+                cam_moved = true;
+            }
+            _ => {}
+        }
+        // update view matrix
+        if cam_moved {
+            mat_trans = Mat4::from_translation(&math::vec3((-cam_pos[0], -cam_pos[1], -cam_pos[2]))); // cam translation
+            mat_rot = Mat4::from_rotation_y_deg(-cam_yaw);
+            view_mat = mat_rot * mat_trans;
+            unsafe {
+                gl::UseProgram(shader_programme);
+                gl::UniformMatrix4fv(view_mat_location, 1, gl::FALSE, view_mat.as_ptr());
+            }
+        }
+
+        match context.window.get_key(Key::Escape) {
+            Action::Press | Action::Repeat => {
+                context.window.set_should_close(true);
+            }
+            _ => {}
+        }
+        // Put the stuff we've been drawing onto the display.
+        context.window.swap_buffers();
+    }
+}
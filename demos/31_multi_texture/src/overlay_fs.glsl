@@ -0,0 +1,14 @@
+#version 410
+
+in vec2 st;
+in vec4 colour;
+
+// single channel font atlas. the red channel is used as coverage.
+uniform sampler2D font_atlas;
+
+out vec4 frag_colour;
+
+void main() {
+	float coverage = texture (font_atlas, st).r;
+	frag_colour = vec4 (colour.rgb, colour.a * coverage);
+}
@@ -0,0 +1,53 @@
+#version 410
+
+in vec3 pos_eye;
+in vec3 norm_eye;
+in vec2 st;
+
+// each sampler's texture unit is assigned by the texture binder in Rust
+uniform sampler2D diffuse_map;
+uniform sampler2D specular_map;
+uniform sampler2D ambient_map;
+uniform sampler2D emission_map;
+/* how much of each map to blend in: x is the diffuse map, y the specular map,
+	 z the ambient occlusion map, and w the emission map. a map that is blended
+	 out leaves the plain lighting in its place */
+uniform vec4 map_weights;
+
+uniform mat4 view;
+
+out vec4 frag_colour;
+
+vec3 light_position_world = vec3 (1.0, 1.0, 10.0);
+vec3 Ls = vec3 (1.0, 1.0, 1.0); // white specular colour
+vec3 Ld = vec3 (0.7, 0.7, 0.7); // dull white diffuse light colour
+vec3 La = vec3 (0.2, 0.2, 0.2); // grey ambient colour
+float specular_exponent = 100.0; // specular 'power'
+
+void main() {
+	vec3 light_pos_eye = (view * vec4 (light_position_world, 1.0)).xyz;
+	vec3 white = vec3 (1.0, 1.0, 1.0);
+
+	// ambient light, darkened in creases by the ambient occlusion map
+	vec3 texel_a = mix (white, texture (ambient_map, st).rgb, map_weights.z);
+	vec3 Ia = La * texel_a;
+
+	// diffuse light, coloured by the diffuse map
+	vec3 texel_d = mix (white, texture (diffuse_map, st).rgb, map_weights.x);
+	vec3 surface_to_light_eye = normalize (light_pos_eye - pos_eye);
+	float dp = max (0.0, dot (normalize (norm_eye), surface_to_light_eye));
+	vec3 Id = texel_d * Ld * dp;
+
+	// specular light, masked by the specular map
+	vec3 texel_s = mix (white, texture (specular_map, st).rgb, map_weights.y);
+	vec3 surface_to_viewer_eye = normalize (-pos_eye);
+	vec3 half_way_eye = normalize (surface_to_viewer_eye + surface_to_light_eye);
+	float dot_prod_specular = max (dot (half_way_eye, normalize (norm_eye)), 0.0);
+	float specular_factor = pow (dot_prod_specular, specular_exponent);
+	vec3 Is = Ls * texel_s * specular_factor;
+
+	// light given off by the surface itself, which is added on top
+	vec3 Ie = texture (emission_map, st).rgb * map_weights.w;
+
+	frag_colour = vec4 (Id + Is + Ia + Ie, 1.0);
+}
@@ -0,0 +1,347 @@
+use gl;
+use gl::types::{GLenum, GLint, GLsizei, GLuint};
+
+use graphics_math::Mat4;
+
+
+///
+/// A uniform value to set on a draw command's programme just before it draws.
+/// Each variant carries the uniform location first.
+///
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Uniform {
+    Float(GLint, f32),
+    Vec2(GLint, [f32; 2]),
+    Vec3(GLint, [f32; 3]),
+    Mat4(GLint, Mat4),
+}
+
+impl Uniform {
+    unsafe fn set(&self) {
+        match *self {
+            Uniform::Float(location, x) => gl_check!(gl::Uniform1f(location, x)),
+            Uniform::Vec2(location, v) => gl_check!(gl::Uniform2f(location, v[0], v[1])),
+            Uniform::Vec3(location, v) => gl_check!(gl::Uniform3f(location, v[0], v[1], v[2])),
+            Uniform::Mat4(location, ref m) => gl_check!(gl::UniformMatrix4fv(location, 1, gl::FALSE, m.as_ptr())),
+        }
+    }
+}
+
+///
+/// One call to `glDrawArrays`, along with the state it needs bound. A texture of
+/// zero means the draw does not sample a texture, so whatever is bound is left
+/// alone. The texture always goes on texture unit 0.
+///
+/// Commands in a lower layer always draw before commands in a higher one, so
+/// things like GUI panels drawn over the scene stay on top after sorting.
+///
+#[derive(Clone, Debug, PartialEq)]
+pub struct DrawCommand {
+    pub layer: u32,
+    pub depth_test: bool,
+    pub program: GLuint,
+    pub texture: GLuint,
+    pub vao: GLuint,
+    pub mode: GLenum,
+    pub first: GLint,
+    pub count: GLsizei,
+    pub uniforms: Vec<Uniform>,
+}
+
+impl DrawCommand {
+    ///
+    /// Draw `count` vertices from `vao` as triangles with `program`, in layer 0
+    /// with depth testing on.
+    ///
+    pub fn new(program: GLuint, vao: GLuint, count: usize) -> DrawCommand {
+        DrawCommand {
+            layer: 0,
+            depth_test: true,
+            program: program,
+            texture: 0,
+            vao: vao,
+            mode: gl::TRIANGLES,
+            first: 0,
+            count: count as GLsizei,
+            uniforms: Vec::new(),
+        }
+    }
+
+    pub fn with_layer(mut self, layer: u32) -> DrawCommand {
+        self.layer = layer;
+        self
+    }
+
+    pub fn with_depth_test(mut self, depth_test: bool) -> DrawCommand {
+        self.depth_test = depth_test;
+        self
+    }
+
+    pub fn with_texture(mut self, texture: GLuint) -> DrawCommand {
+        self.texture = texture;
+        self
+    }
+
+    pub fn with_uniform(mut self, uniform: Uniform) -> DrawCommand {
+        self.uniforms.push(uniform);
+        self
+    }
+
+    ///
+    /// The order draw commands sort in. Changing programme is the most expensive
+    /// state change, then the texture, then the vertex array.
+    ///
+    fn sort_key(&self) -> (u32, bool, GLuint, GLuint, GLuint) {
+        (self.layer, !self.depth_test, self.program, self.texture, self.vao)
+    }
+}
+
+///
+/// How many draws a draw list made, and how many times it had to change each
+/// piece of state to make them.
+///
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct DrawStats {
+    pub draws: usize,
+    pub program_changes: usize,
+    pub texture_changes: usize,
+    pub vao_changes: usize,
+    pub depth_test_changes: usize,
+}
+
+impl DrawStats {
+    ///
+    /// The total number of state changes across all kinds of state.
+    ///
+    pub fn state_changes(&self) -> usize {
+        self.program_changes + self.texture_changes + self.vao_changes + self.depth_test_changes
+    }
+}
+
+///
+/// The state most recently bound by a draw list. Nothing is known to be bound when
+/// a draw list starts executing, because other code such as the overlay binds its
+/// own state in between.
+///
+#[derive(Copy, Clone, Debug, Default)]
+struct BoundState {
+    program: Option<GLuint>,
+    texture: Option<GLuint>,
+    vao: Option<GLuint>,
+    depth_test: Option<bool>,
+}
+
+fn change<T: Copy + PartialEq>(bound: &mut Option<T>, wanted: T) -> bool {
+    if *bound == Some(wanted) {
+        return false;
+    }
+    *bound = Some(wanted);
+
+    true
+}
+
+///
+/// Collects the draw calls for a frame so they can be sorted by the state they
+/// need and then made with as few state changes as possible.
+///
+pub struct DrawList {
+    commands: Vec<DrawCommand>,
+}
+
+impl DrawList {
+    pub fn new() -> DrawList {
+        DrawList {
+            commands: Vec::new(),
+        }
+    }
+
+    pub fn push(&mut self, command: DrawCommand) {
+        self.commands.push(command);
+    }
+
+    pub fn len(&self) -> usize {
+        self.commands.len()
+    }
+
+    pub fn commands(&self) -> &[DrawCommand] {
+        &self.commands
+    }
+
+    ///
+    /// Remove all the commands, ready for the next frame.
+    ///
+    pub fn clear(&mut self) {
+        self.commands.clear();
+    }
+
+    ///
+    /// Sort the commands by layer and then by the state they need. The sort is
+    /// stable, so commands needing the same state keep the order they were pushed in.
+    ///
+    pub fn sort(&mut self) {
+        self.commands.sort_by_key(|command| command.sort_key());
+    }
+
+    ///
+    /// Count the state changes drawing the commands in their current order would
+    /// take, without drawing anything.
+    ///
+    pub fn state_changes(&self) -> DrawStats {
+        let mut bound = BoundState::default();
+        let mut stats = DrawStats::default();
+        for command in self.commands.iter() {
+            Self::track(&mut bound, &mut stats, command);
+        }
+
+        stats
+    }
+
+    ///
+    /// Sort the commands and draw them, binding only the state that differs from
+    /// the previous draw. Texture unit 0 is left active afterwards.
+    ///
+    pub fn execute(&mut self) -> DrawStats {
+        self.sort();
+
+        let mut bound = BoundState::default();
+        let mut stats = DrawStats::default();
+        unsafe {
+            gl::ActiveTexture(gl::TEXTURE0);
+        }
+        for command in self.commands.iter() {
+            let (program, texture, vao, depth_test) = Self::track(&mut bound, &mut stats, command);
+            unsafe {
+                if depth_test {
+                    if command.depth_test {
+                        gl::Enable(gl::DEPTH_TEST);
+                    } else {
+                        gl::Disable(gl::DEPTH_TEST);
+                    }
+                }
+                if program {
+                    gl::UseProgram(command.program);
+                }
+                if texture {
+                    gl::BindTexture(gl::TEXTURE_2D, command.texture);
+                }
+                if vao {
+                    gl::BindVertexArray(command.vao);
+                }
+                for uniform in command.uniforms.iter() {
+                    uniform.set();
+                }
+                gl_check!(gl::DrawArrays(command.mode, command.first, command.count));
+            }
+        }
+
+        stats
+    }
+
+    ///
+    /// Work out which state `command` needs changed, and count the changes and the
+    /// draw. Returns whether the programme, texture, vertex array, and depth test
+    /// need changing, in that order.
+    ///
+    fn track(bound: &mut BoundState, stats: &mut DrawStats, command: &DrawCommand) -> (bool, bool, bool, bool) {
+        let program = change(&mut bound.program, command.program);
+        let texture = command.texture != 0 && change(&mut bound.texture, command.texture);
+        let vao = change(&mut bound.vao, command.vao);
+        let depth_test = change(&mut bound.depth_test, command.depth_test);
+
+        stats.draws += 1;
+        if program {
+            stats.program_changes += 1;
+        }
+        if texture {
+            stats.texture_changes += 1;
+        }
+        if vao {
+            stats.vao_changes += 1;
+        }
+        if depth_test {
+            stats.depth_test_changes += 1;
+        }
+
+        (program, texture, vao, depth_test)
+    }
+}
+
+mod draw_list_tests {
+    use super::{DrawCommand, DrawList, DrawStats, Uniform};
+
+    fn interleaved() -> DrawList {
+        // Two programmes and two vertex arrays, alternating every draw.
+        let mut list = DrawList::new();
+        for i in 0..4 {
+            let program = 1 + (i % 2);
+            let vao = 10 + (i % 2);
+            list.push(DrawCommand::new(program, vao, 36).with_uniform(Uniform::Float(0, i as f32)));
+        }
+
+        list
+    }
+
+    #[test]
+    fn test_state_changes_in_submission_order() {
+        let list = interleaved();
+        let stats = list.state_changes();
+
+        assert_eq!(stats.draws, 4);
+        assert_eq!(stats.program_changes, 4);
+        assert_eq!(stats.vao_changes, 4);
+        assert_eq!(stats.texture_changes, 0);
+        assert_eq!(stats.depth_test_changes, 1);
+    }
+
+    #[test]
+    fn test_sorting_groups_draws_by_state() {
+        let mut list = interleaved();
+        list.sort();
+        let stats = list.state_changes();
+
+        assert_eq!(stats, DrawStats {
+            draws: 4,
+            program_changes: 2,
+            texture_changes: 0,
+            vao_changes: 2,
+            depth_test_changes: 1,
+        });
+        assert_eq!(stats.state_changes(), 5);
+    }
+
+    #[test]
+    fn test_sorting_keeps_submission_order_within_the_same_state() {
+        let mut list = interleaved();
+        list.sort();
+        let order: Vec<Uniform> = list.commands().iter().map(|command| command.uniforms[0]).collect();
+
+        assert_eq!(order, vec![
+            Uniform::Float(0, 0.0), Uniform::Float(0, 2.0), Uniform::Float(0, 1.0), Uniform::Float(0, 3.0)
+        ]);
+    }
+
+    #[test]
+    fn test_higher_layers_draw_last() {
+        let mut list = DrawList::new();
+        list.push(DrawCommand::new(1, 10, 6).with_layer(1).with_depth_test(false).with_texture(21));
+        list.push(DrawCommand::new(2, 10, 6).with_texture(20));
+        list.sort();
+
+        assert_eq!(list.commands()[0].program, 2);
+        assert_eq!(list.commands()[1].program, 1);
+        assert_eq!(list.state_changes().texture_changes, 2);
+        assert_eq!(list.state_changes().depth_test_changes, 2);
+    }
+
+    #[test]
+    fn test_untextured_draws_leave_the_texture_alone() {
+        let mut list = DrawList::new();
+        list.push(DrawCommand::new(1, 10, 6).with_texture(20));
+        list.push(DrawCommand::new(1, 10, 6));
+        list.push(DrawCommand::new(1, 10, 6).with_texture(20));
+
+        assert_eq!(list.state_changes().texture_changes, 1);
+        list.clear();
+        assert_eq!(list.len(), 0);
+    }
+}
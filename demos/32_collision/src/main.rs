@@ -0,0 +1,478 @@
+extern crate gl;
+extern crate glfw;
+extern crate chrono;
+extern crate stb_image;
+#[cfg(feature = "glam")]
+extern crate glam;
+#[cfg(feature = "cgmath")]
+extern crate cgmath;
+
+#[macro_use]
+mod gl_utils;
+mod graphics_math;
+mod obj_parser;
+mod colour;
+mod assets;
+mod shader_include;
+mod spirv;
+mod program_cache;
+mod logger;
+mod collision;
+mod draw_list;
+mod vertex_layout;
+
+
+use glfw::{Action, Context, Key};
+use gl::types::{GLfloat, GLint, GLuint};
+
+use std::process;
+
+use gl_utils::*;
+
+use graphics_math as math;
+use math::{Mat4, Vec3};
+
+use collision::{Aabb, Sphere};
+use draw_list::{DrawCommand, DrawList, DrawStats, Uniform};
+use vertex_layout::VertexLayout;
+
+
+const GL_LOG_FILE: &str = "gl.log";
+const VERTEX_SHADER_FILE: &str = "src/scene_vs.glsl";
+const FRAGMENT_SHADER_FILE: &str = "src/scene_fs.glsl";
+
+// The arena is a square of ground with a low wall around it.
+const ARENA_HALF_SIZE: f32 = 10.0;
+const WALL_THICKNESS: f32 = 0.5;
+const WALL_HEIGHT: f32 = 0.75;
+
+// The camera is carried by an invisible sphere that slides along the ground.
+// Collisions are all worked out in the ground plane, at a height of zero, and
+// everything is only lifted to its proper height when it is drawn.
+const PUSHER_RADIUS: f32 = 0.5;
+const EYE_HEIGHT: f32 = 1.5;
+// How many times each frame overlapping shapes are pushed apart. A push can make
+// a new overlap further along a row of balls, so one pass is not always enough.
+const SOLVER_ITERATIONS: usize = 4;
+
+const GROUND_COLOUR: [f32; 3] = [0.35, 0.45, 0.3];
+const WALL_COLOUR: [f32; 3] = [0.5, 0.45, 0.4];
+const CRATE_COLOUR: [f32; 3] = [0.6, 0.4, 0.2];
+
+// x, z, radius and colour of each ball.
+const BALLS: [(f32, f32, f32, [f32; 3]); 8] = [
+    (0.0, -3.0, 0.5, [0.9, 0.2, 0.2]),
+    (1.5, -4.0, 0.4, [0.9, 0.6, 0.1]),
+    (-1.5, -4.0, 0.4, [0.9, 0.9, 0.2]),
+    (0.0, -5.5, 0.7, [0.2, 0.8, 0.3]),
+    (3.5, -1.0, 0.3, [0.2, 0.7, 0.9]),
+    (-3.5, -1.0, 0.6, [0.3, 0.3, 0.9]),
+    (5.0, -6.0, 0.5, [0.7, 0.3, 0.9]),
+    (-5.0, -6.0, 0.5, [0.9, 0.4, 0.7]),
+];
+
+// x, z, half width, half depth and height of each crate.
+const CRATES: [(f32, f32, f32, f32, f32); 3] = [
+    (-6.0, 2.0, 1.0, 1.0, 1.5),
+    (6.0, 3.0, 0.75, 1.5, 1.0),
+    (0.0, -8.0, 2.0, 0.5, 1.0),
+];
+
+
+///
+/// The ground plane: two triangles facing up, with each position followed by
+/// its normal.
+///
+fn ground_vertices() -> Vec<GLfloat> {
+    let a = ARENA_HALF_SIZE;
+    let corners = [(-a, a), (a, a), (a, -a), (a, -a), (-a, -a), (-a, a)];
+    let mut vertices = Vec::with_capacity(6 * corners.len());
+    for &(x, z) in corners.iter() {
+        vertices.extend_from_slice(&[x, 0.0, z, 0.0, 1.0, 0.0]);
+    }
+
+    vertices
+}
+
+///
+/// A unit cube centred on the origin, with each position followed by its normal.
+///
+fn cube_vertices() -> Vec<GLfloat> {
+    // The normal of each face, and two axes across it whose cross product is the
+    // normal, so the triangles wind counter-clockwise seen from outside.
+    let faces = [
+        ([1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]),
+        ([-1.0, 0.0, 0.0], [0.0, 0.0, 1.0], [0.0, 1.0, 0.0]),
+        ([0.0, 1.0, 0.0], [0.0, 0.0, 1.0], [1.0, 0.0, 0.0]),
+        ([0.0, -1.0, 0.0], [1.0, 0.0, 0.0], [0.0, 0.0, 1.0]),
+        ([0.0, 0.0, 1.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]),
+        ([0.0, 0.0, -1.0], [0.0, 1.0, 0.0], [1.0, 0.0, 0.0]),
+    ];
+    let corners = [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (1.0, 1.0), (-1.0, 1.0), (-1.0, -1.0)];
+
+    let mut vertices = Vec::with_capacity(6 * 6 * faces.len());
+    for &(n, u, v) in faces.iter() {
+        for &(s, t) in corners.iter() {
+            for i in 0..3 {
+                vertices.push(0.5 * (n[i] + s * u[i] + t * v[i]));
+            }
+            vertices.extend_from_slice(&n);
+        }
+    }
+
+    vertices
+}
+
+///
+/// Interleave separate position and normal arrays, as loaded from a mesh.
+///
+fn interleave(points: &[GLfloat], normals: &[GLfloat]) -> Vec<GLfloat> {
+    let mut vertices = Vec::with_capacity(points.len() + normals.len());
+    for i in 0..(points.len() / 3) {
+        vertices.extend_from_slice(&points[3 * i..3 * i + 3]);
+        vertices.extend_from_slice(&normals[3 * i..3 * i + 3]);
+    }
+
+    vertices
+}
+
+///
+/// Copy interleaved positions and normals into a vertex buffer, and make a vertex
+/// array reading positions from location 0 and normals from location 1.
+///
+fn make_vao(vertices: &[GLfloat]) -> GLuint {
+    let mut vao = 0;
+    unsafe {
+        gl::GenVertexArrays(1, &mut vao);
+    }
+    assert!(vao > 0);
+    VertexLayout::new().floats(0, 3).floats(1, 3).upload(vao, vertices, vertices.len() / 6);
+
+    vao
+}
+
+///
+/// The walls around the arena and the crates inside it. Each box reaches below
+/// the ground so that it contains the ground plane the collisions happen in.
+///
+fn make_obstacles() -> Vec<Aabb> {
+    let a = ARENA_HALF_SIZE + 0.5 * WALL_THICKNESS;
+    let long = ARENA_HALF_SIZE + WALL_THICKNESS;
+    let half_thickness = 0.5 * WALL_THICKNESS;
+    let walls = [
+        (0.0, -a, long, half_thickness, WALL_HEIGHT),
+        (0.0, a, long, half_thickness, WALL_HEIGHT),
+        (-a, 0.0, half_thickness, long, WALL_HEIGHT),
+        (a, 0.0, half_thickness, long, WALL_HEIGHT),
+    ];
+
+    walls.iter().chain(CRATES.iter()).map(|&(x, z, half_x, half_z, height)| {
+        Aabb::new(math::vec3((x - half_x, -1.0, z - half_z)), math::vec3((x + half_x, height, z + half_z)))
+    }).collect()
+}
+
+///
+/// The model matrix that stretches the unit cube over the part of an obstacle
+/// above the ground.
+///
+fn obstacle_model_matrix(obstacle: &Aabb) -> Mat4 {
+    let min_y = f32::max(obstacle.min.v[1], 0.0);
+    let half_extents = obstacle.half_extents();
+    let size = math::vec3((2.0 * half_extents.v[0], obstacle.max.v[1] - min_y, 2.0 * half_extents.v[2]));
+    let centre = obstacle.centre();
+    let position = math::vec3((centre.v[0], 0.5 * (min_y + obstacle.max.v[1]), centre.v[2]));
+
+    Mat4::from_scale(&size).then_translate(&position)
+}
+
+///
+/// The model matrix that places the unit sphere on the ground where a ball is.
+///
+fn ball_model_matrix(ball: &Sphere) -> Mat4 {
+    let r = ball.radius;
+    let position = math::vec3((ball.centre.v[0], r, ball.centre.v[2]));
+
+    Mat4::from_scale(&math::vec3((r, r, r))).then_translate(&position)
+}
+
+///
+/// Keep a vector in the ground plane, so nothing is ever pushed off the ground.
+///
+fn flatten(v: Vec3) -> Vec3 {
+    math::vec3((v.v[0], 0.0, v.v[2]))
+}
+
+///
+/// Shove the balls in the way of the pusher as it moves by `motion`. Sweeping the
+/// pusher along its path finds where it first touches each ball, so a ball is hit
+/// even if the pusher moves far enough in one frame to pass right through it. The
+/// ball is pushed along the line between the centres at the moment they touch, by
+/// however much of the rest of the motion points that way, so a glancing blow
+/// only nudges it aside.
+///
+fn push_balls(pusher: &Sphere, motion: &Vec3, balls: &mut [Sphere]) {
+    let end = Sphere::new(pusher.centre + *motion, pusher.radius);
+    let swept_bounds = pusher.bounds().union(&end.bounds());
+    for ball in balls.iter_mut() {
+        // Most balls are nowhere near, and the boxes are cheaper to compare than
+        // sweeping the pusher.
+        if collision::aabb_aabb(&swept_bounds, &ball.bounds()).is_none() {
+            continue;
+        }
+
+        if let Some(t) = collision::sweep_sphere_sphere(pusher, motion, ball) {
+            let touch = pusher.centre + *motion * t;
+            let normal = flatten(ball.centre - touch).normalize();
+            let push = (*motion * (1.0 - t)).dot(&normal);
+            if push > 0.0 {
+                ball.centre += normal * push;
+            }
+        }
+    }
+}
+
+///
+/// Push overlapping balls apart, and out of the pusher and the obstacles. A ball
+/// that is pinned against an obstacle cannot give way, so after the balls have
+/// moved, the pusher is pushed back out of anything it still overlaps instead.
+///
+fn separate(pusher: &mut Sphere, balls: &mut [Sphere], obstacles: &[Aabb]) {
+    for _ in 0..SOLVER_ITERATIONS {
+        for i in 0..balls.len() {
+            if let Some(contact) = collision::sphere_sphere(&balls[i], pusher) {
+                balls[i].centre += flatten(contact.normal) * contact.depth;
+            }
+            for j in (i + 1)..balls.len() {
+                if let Some(contact) = collision::sphere_sphere(&balls[i], &balls[j]) {
+                    let half = flatten(contact.normal) * (0.5 * contact.depth);
+                    balls[i].centre += half;
+                    balls[j].centre -= half;
+                }
+            }
+            for obstacle in obstacles.iter() {
+                if let Some(contact) = collision::sphere_aabb(&balls[i], obstacle) {
+                    balls[i].centre += flatten(contact.normal) * contact.depth;
+                }
+            }
+        }
+    }
+
+    for ball in balls.iter() {
+        if let Some(contact) = collision::sphere_sphere(pusher, ball) {
+            pusher.centre += flatten(contact.normal) * contact.depth;
+        }
+    }
+    for obstacle in obstacles.iter() {
+        if let Some(contact) = collision::sphere_aabb(pusher, obstacle) {
+            pusher.centre += flatten(contact.normal) * contact.depth;
+        }
+    }
+}
+
+///
+/// The shader everything in the scene is drawn with: a flat colour lit by one
+/// light from above.
+///
+struct SceneShader {
+    sp: GLuint,
+    model_location: GLint,
+    colour_location: GLint,
+}
+
+impl SceneShader {
+    fn command(&self, vao: GLuint, point_count: usize, model: &Mat4, colour: &[f32; 3]) -> DrawCommand {
+        DrawCommand::new(self.sp, vao, point_count)
+            .with_uniform(Uniform::Mat4(self.model_location, *model))
+            .with_uniform(Uniform::Vec3(self.colour_location, *colour))
+    }
+}
+
+fn main() {
+    let logger = restart_gl_log(GL_LOG_FILE);
+    // Start a GL context and O/S window using the GLFW helper library.
+    let mut context = match start_gl(&logger) {
+        Ok(val) => val,
+        Err(e) => {
+            eprintln!("Failed to Initialize OpenGL context. Got error:");
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+    };
+
+    unsafe {
+        // Enable depth testing.
+        gl::Enable(gl::DEPTH_TEST);
+        // Depth testing interprets a smaller value as closer to the eye.
+        gl::DepthFunc(gl::LESS);
+        // Cull face.
+        gl::Enable(gl::CULL_FACE);
+        // Cull back face.
+        gl::CullFace(gl::BACK);
+        // GL_CW for clockwise.
+        gl::FrontFace(gl::CCW);
+        gl::ClearColor(0.6, 0.7, 0.8, 1.0);
+    }
+
+    /*------------------------------CREATE GEOMETRY------------------------------*/
+    let ground = ground_vertices();
+    let ground_vao = make_vao(&ground);
+    let ground_point_count = ground.len() / 6;
+
+    let cube = cube_vertices();
+    let cube_vao = make_vao(&cube);
+    let cube_point_count = cube.len() / 6;
+
+    // the balls use the embedded unit sphere
+    let sphere_mesh = assets::fallback_mesh();
+    let sphere_vao = make_vao(&interleave(&sphere_mesh.points, &sphere_mesh.normals));
+    let sphere_point_count = sphere_mesh.point_count;
+
+    /*-------------------------------CREATE SHADERS------------------------------*/
+    let shader_programme = create_programme_from_files(&logger, VERTEX_SHADER_FILE, FRAGMENT_SHADER_FILE);
+    let model_mat_location = uniform_location(shader_programme, "M");
+    assert!(model_mat_location > -1);
+    let view_mat_location = uniform_location(shader_programme, "V");
+    assert!(view_mat_location > -1);
+    let proj_mat_location = uniform_location(shader_programme, "P");
+    assert!(proj_mat_location > -1);
+    let colour_location = uniform_location(shader_programme, "colour");
+    assert!(colour_location > -1);
+    let scene_shader = SceneShader {
+        sp: shader_programme,
+        model_location: model_mat_location,
+        colour_location: colour_location,
+    };
+
+    /*-------------------------------CREATE SCENE--------------------------------*/
+    let obstacles = make_obstacles();
+    let mut balls: Vec<Sphere> = BALLS.iter().map(|&(x, z, radius, _)| {
+        Sphere::new(math::vec3((x, 0.0, z)), radius)
+    }).collect();
+    let mut pusher = Sphere::new(math::vec3((0.0, 0.0, 5.0)), PUSHER_RADIUS);
+    logger.log("keys: W and S walk, A and D step sideways, and the left and right arrows turn.");
+    logger.log("      walk into the balls to push them around.");
+
+    // input variables
+    let near = 0.1;                                  // clipping plane
+    let far = 100.0;                                 // clipping plane
+    let fov = 67.0;                                  // convert 67 degrees to radians
+    let aspect = context.width as f32 / context.height as f32; // aspect ratio
+    let proj_mat = Mat4::perspective(fov, aspect, near, far);
+
+    // matrix components
+    let cam_speed: GLfloat = 4.0;             // 4 units per second
+    let cam_yaw_speed: GLfloat = 90.0;        // 90 degrees per second
+    let mut cam_yaw: GLfloat = 0.0;               // y-rotation in degrees
+
+    // the draws for each frame, sorted by the state they need. the state change
+    // counts go in the log whenever they change.
+    let mut draw_list = DrawList::new();
+    let mut last_draw_stats = DrawStats::default();
+
+    unsafe {
+        gl::UseProgram(shader_programme);
+        gl::UniformMatrix4fv(proj_mat_location, 1, gl::FALSE, proj_mat.as_ptr());
+    }
+
+    while !context.window.should_close() {
+        let current_seconds = context.glfw.get_time();
+        let elapsed_seconds = current_seconds - context.elapsed_time_seconds;
+        context.elapsed_time_seconds = current_seconds;
+
+        update_fps_counter(&mut context);
+
+        // the camera sits above the pusher, looking along its heading
+        let eye = pusher.centre + math::vec3((0.0, EYE_HEIGHT, 0.0));
+        let mat_trans = Mat4::from_translation(&-eye);
+        let mat_rot = Mat4::from_rotation_y_deg(-cam_yaw);
+        let view_mat = mat_rot * mat_trans;
+
+        unsafe {
+            // Wipe the drawing surface clear.
+            gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
+            gl::Viewport(0, 0, context.width as i32, context.height as i32);
+
+            gl::UseProgram(shader_programme);
+            gl::UniformMatrix4fv(view_mat_location, 1, gl::FALSE, view_mat.as_ptr());
+        }
+
+        draw_list.clear();
+        draw_list.push(scene_shader.command(ground_vao, ground_point_count, &Mat4::identity(), &GROUND_COLOUR));
+        for (i, obstacle) in obstacles.iter().enumerate() {
+            let colour = if i < 4 { &WALL_COLOUR } else { &CRATE_COLOUR };
+            draw_list.push(scene_shader.command(cube_vao, cube_point_count, &obstacle_model_matrix(obstacle), colour));
+        }
+        for (ball, &(_, _, _, ref colour)) in balls.iter().zip(BALLS.iter()) {
+            draw_list.push(scene_shader.command(sphere_vao, sphere_point_count, &ball_model_matrix(ball), colour));
+        }
+        let unsorted_stats = draw_list.state_changes();
+        let draw_stats = draw_list.execute();
+        if draw_stats != last_draw_stats {
+            logger.log(&format!(
+                "{} draws: {} state changes sorted, {} in the order they were pushed",
+                draw_stats.draws, draw_stats.state_changes(), unsorted_stats.state_changes()
+            ));
+            last_draw_stats = draw_stats;
+        }
+
+        context.glfw.poll_events();
+
+        // control keys
+        let yaw_rad = cam_yaw.to_radians();
+        let forward = math::vec3((-f32::sin(yaw_rad), 0.0, -f32::cos(yaw_rad)));
+        let right = math::vec3((f32::cos(yaw_rad), 0.0, -f32::sin(yaw_rad)));
+        let step = cam_speed * (elapsed_seconds as GLfloat);
+        let mut motion = Vec3::zero();
+        match context.window.get_key(Key::W) {
+            Action::Press | Action::Repeat => {
+                motion += forward * step;
+            }
+            _ => {}
+        }
+        match context.window.get_key(Key::S) {
+            Action::Press | Action::Repeat => {
+                motion -= forward * step;
+            }
+            _ => {}
+        }
+        match context.window.get_key(Key::A) {
+            Action::Press | Action::Repeat => {
+                motion -= right * step;
+            }
+            _ => {}
+        }
+        match context.window.get_key(Key::D) {
+            Action::Press | Action::Repeat => {
+                motion += right * step;
+            }
+            _ => {}
+        }
+        match context.window.get_key(Key::Left) {
+            Action::Press | Action::Repeat => {
+                cam_yaw += cam_yaw_speed * (elapsed_seconds as GLfloat);
+            }
+            _ => {}
+        }
+        match context.window.get_key(Key::Right) {
+            Action::Press | Action::Repeat => {
+                cam_yaw -= cam_yaw_speed * (elapsed_seconds as GLfloat);
+            }
+            _ => {}
+        }
+
+        if motion.norm2() > 0.0 {
+            push_balls(&pusher, &motion, &mut balls);
+            pusher.centre += motion;
+            separate(&mut pusher, &mut balls, &obstacles);
+        }
+
+        match context.window.get_key(Key::Escape) {
+            Action::Press | Action::Repeat => {
+                context.window.set_should_close(true);
+            }
+            _ => {}
+        }
+        // Put the stuff we've been drawing onto the display.
+        context.window.swap_buffers();
+    }
+}
@@ -0,0 +1,235 @@
+use gl;
+use gl::types::{GLchar, GLenum, GLint, GLsizei, GLuint};
+
+use gl_utils;
+use logger::Logger;
+
+use std::ffi::{c_void, CStr};
+use std::fs::File;
+use std::io::Read;
+use std::mem;
+use std::path::Path;
+use std::ptr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+
+// From ARB_gl_spirv. The gl crate's bindings stop at GL 4.5, which has neither
+// the format nor glSpecializeShader.
+pub const SHADER_BINARY_FORMAT_SPIR_V: GLenum = 0x9551;
+const SPIRV_MAGIC_NUMBER: u32 = 0x0723_0203;
+
+type SpecializeShaderFn = extern "system" fn(
+    shader: GLuint, entry_point: *const GLchar, num_specialization_constants: GLuint,
+    constant_index: *const GLuint, constant_value: *const GLuint
+);
+
+// The address of glSpecializeShader, or zero when the driver cannot take SPIR-V.
+static SPECIALIZE_SHADER: AtomicUsize = AtomicUsize::new(0);
+
+
+///
+/// The file a pre-compiled SPIR-V shader is looked for in: the GLSL file's name
+/// with `.spv` on the end, as `glslangValidator -G -o src/test.vert.glsl.spv
+/// src/test.vert.glsl` writes it.
+///
+pub fn spirv_file_name(file_name: &str) -> String {
+    format!("{}.spv", file_name)
+}
+
+///
+/// Pick the name of the function that specialises SPIR-V shaders, if the driver
+/// has one. GL 4.6 has it in the core, and older versions can have it through
+/// the ARB_gl_spirv extension.
+///
+fn specialize_shader_name(major_version: GLint, minor_version: GLint, extensions: &[String]) -> Option<&'static str> {
+    if major_version > 4 || (major_version == 4 && minor_version >= 6) {
+        Some("glSpecializeShader")
+    } else if extensions.iter().any(|extension| extension == "GL_ARB_gl_spirv") {
+        Some("glSpecializeShaderARB")
+    } else {
+        None
+    }
+}
+
+///
+/// Look up glSpecializeShader once the GL functions are loaded, so SPIR-V
+/// shaders can be used when the driver supports them.
+///
+pub fn load_with<F>(mut load: F) where F: FnMut(&str) -> *const c_void {
+    let mut major_version = 0;
+    let mut minor_version = 0;
+    let mut extension_count = 0;
+    unsafe {
+        gl::GetIntegerv(gl::MAJOR_VERSION, &mut major_version);
+        gl::GetIntegerv(gl::MINOR_VERSION, &mut minor_version);
+        gl::GetIntegerv(gl::NUM_EXTENSIONS, &mut extension_count);
+    }
+    let mut extensions = vec![];
+    for i in 0..extension_count {
+        let extension = unsafe { gl::GetStringi(gl::EXTENSIONS, i as GLuint) };
+        if !extension.is_null() {
+            let extension = unsafe { CStr::from_ptr(extension as *const GLchar) };
+            extensions.push(extension.to_string_lossy().into_owned());
+        }
+    }
+
+    let address = match specialize_shader_name(major_version, minor_version, &extensions) {
+        Some(name) => load(name) as usize,
+        None => 0,
+    };
+    SPECIALIZE_SHADER.store(address, Ordering::SeqCst);
+}
+
+pub fn is_supported() -> bool {
+    SPECIALIZE_SHADER.load(Ordering::SeqCst) != 0
+}
+
+///
+/// Whether a GLSL shader file will be made from pre-compiled SPIR-V instead.
+///
+pub fn has_spirv(file_name: &str) -> bool {
+    is_supported() && Path::new(&spirv_file_name(file_name)).exists()
+}
+
+///
+/// Check a SPIR-V module and turn it into words. A module written on a machine
+/// of the other endianness has its magic number backwards, and its words are
+/// swapped round.
+///
+pub fn parse_spirv(bytes: &[u8]) -> Result<Vec<u32>, String> {
+    if bytes.len() < 20 || bytes.len() % 4 != 0 {
+        return Err(format!("ERROR: a SPIR-V module is a whole number of words, at least 5 long, not {} bytes", bytes.len()));
+    }
+
+    let mut words: Vec<u32> = bytes.chunks(4)
+        .map(|word| u32::from(word[0]) | u32::from(word[1]) << 8 | u32::from(word[2]) << 16 | u32::from(word[3]) << 24)
+        .collect();
+    if words[0] == SPIRV_MAGIC_NUMBER.swap_bytes() {
+        for word in words.iter_mut() {
+            *word = word.swap_bytes();
+        }
+    }
+    if words[0] != SPIRV_MAGIC_NUMBER {
+        return Err(format!("ERROR: not a SPIR-V module; the magic number is {:#010x}", words[0]));
+    }
+
+    Ok(words)
+}
+
+fn read_spirv_file(file_name: &str) -> Result<Vec<u32>, String> {
+    let mut file = match File::open(file_name) {
+        Ok(val) => val,
+        Err(e) => return Err(format!("ERROR: opening file for reading: {}: {}", file_name, e)),
+    };
+    let mut bytes = vec![];
+    match file.read_to_end(&mut bytes) {
+        Ok(_) => {}
+        Err(e) => return Err(format!("ERROR: reading SPIR-V file {}: {}", file_name, e)),
+    }
+
+    parse_spirv(&bytes)
+}
+
+///
+/// Make a shader from the pre-compiled SPIR-V next to a GLSL shader file, if
+/// there is one and the driver can take it. Returns `None` when the GLSL source
+/// should be compiled instead, which is also what happens when the SPIR-V fails
+/// to load. Uniforms in a SPIR-V shader need explicit locations, since the
+/// driver does not have to keep their names.
+///
+pub fn create_shader_from_spirv(logger: &Logger, file_name: &str, gl_type: GLenum) -> Option<GLuint> {
+    if !has_spirv(file_name) {
+        return None;
+    }
+
+    let spirv_file = spirv_file_name(file_name);
+
+    let words = match read_spirv_file(&spirv_file) {
+        Ok(val) => val,
+        Err(e) => {
+            logger.log_err(&format!("{}\nWARNING: compiling {} from source instead.\n", e, file_name));
+            return None;
+        }
+    };
+
+    logger.log(&format!("Creating shader from SPIR-V in {}...\n", spirv_file));
+    let specialize_shader: SpecializeShaderFn = unsafe { mem::transmute(SPECIALIZE_SHADER.load(Ordering::SeqCst)) };
+    let shader = unsafe { gl::CreateShader(gl_type) };
+    let mut params = -1;
+    unsafe {
+        gl::ShaderBinary(
+            1, &shader, SHADER_BINARY_FORMAT_SPIR_V,
+            words.as_ptr() as *const c_void, (4 * words.len()) as GLsizei
+        );
+        specialize_shader(shader, "main\0".as_ptr() as *const GLchar, 0, ptr::null(), ptr::null());
+        gl::GetShaderiv(shader, gl::COMPILE_STATUS, &mut params);
+    }
+
+    if params != gl::TRUE as i32 {
+        logger.log_err(&format!(
+            "ERROR: SPIR-V shader {} did not specialise\nWARNING: compiling {} from source instead.\n",
+            spirv_file, file_name
+        ));
+        gl_utils::print_shader_info_log(shader);
+        unsafe {
+            gl::DeleteShader(shader);
+        }
+
+        return None;
+    }
+    logger.log(&format!("Shader specialised with index {}\n", shader));
+
+    Some(shader)
+}
+
+mod spirv_tests {
+    use super::{parse_spirv, specialize_shader_name, spirv_file_name};
+
+    fn module_bytes(magic: [u8; 4]) -> Vec<u8> {
+        let mut bytes = magic.to_vec();
+        // version 1.0, generator, bound, and schema.
+        bytes.extend_from_slice(&[0x00, 0x00, 0x01, 0x00, 0x08, 0x00, 0x08, 0x00, 0x10, 0x00, 0x00, 0x00, 0, 0, 0, 0]);
+
+        bytes
+    }
+
+    #[test]
+    fn test_spirv_files_sit_next_to_their_glsl() {
+        assert_eq!(spirv_file_name("src/test.vert.glsl"), "src/test.vert.glsl.spv");
+    }
+
+    #[test]
+    fn test_spirv_needs_gl_4_6_or_the_extension() {
+        let no_extensions: Vec<String> = vec![];
+        let extensions = vec![String::from("GL_ARB_debug_output"), String::from("GL_ARB_gl_spirv")];
+
+        assert_eq!(specialize_shader_name(4, 6, &no_extensions), Some("glSpecializeShader"));
+        assert_eq!(specialize_shader_name(4, 1, &extensions), Some("glSpecializeShaderARB"));
+        assert_eq!(specialize_shader_name(4, 5, &no_extensions), None);
+    }
+
+    #[test]
+    fn test_parse_spirv_reads_little_endian_words() {
+        let words = parse_spirv(&module_bytes([0x03, 0x02, 0x23, 0x07])).unwrap();
+
+        assert_eq!(words, vec![0x0723_0203, 0x0001_0000, 0x0008_0008, 0x10, 0]);
+    }
+
+    #[test]
+    fn test_parse_spirv_swaps_big_endian_words() {
+        let mut bytes = module_bytes([0x07, 0x23, 0x02, 0x03]);
+        bytes[4..8].copy_from_slice(&[0x00, 0x01, 0x00, 0x00]);
+
+        let words = parse_spirv(&bytes).unwrap();
+
+        assert_eq!(words[0], 0x0723_0203);
+        assert_eq!(words[1], 0x0001_0000);
+    }
+
+    #[test]
+    fn test_parse_spirv_rejects_other_files() {
+        assert!(parse_spirv(b"#version 410\nvoid main() {}\n").is_err());
+        assert!(parse_spirv(&[0; 20]).is_err());
+        assert!(parse_spirv(&[0x03, 0x02, 0x23]).is_err());
+    }
+}
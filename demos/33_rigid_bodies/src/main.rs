@@ -0,0 +1,487 @@
+extern crate gl;
+extern crate glfw;
+extern crate chrono;
+extern crate stb_image;
+#[cfg(feature = "glam")]
+extern crate glam;
+#[cfg(feature = "cgmath")]
+extern crate cgmath;
+
+#[macro_use]
+mod gl_utils;
+mod graphics_math;
+mod obj_parser;
+mod colour;
+mod assets;
+mod shader_include;
+mod spirv;
+mod program_cache;
+mod logger;
+mod overlay;
+mod streaming_buffer;
+mod perf_stats;
+mod gpu_timer;
+mod collision;
+mod physics;
+mod draw_list;
+mod vertex_layout;
+// The time controls are shared with the other demos that have them.
+#[path = "../../shared/frame_clock.rs"]
+mod frame_clock;
+
+
+use glfw::{Action, Context, Key};
+use gl::types::{GLfloat, GLint, GLuint};
+
+use std::process;
+use std::time::Instant;
+
+use gl_utils::*;
+
+use graphics_math as math;
+use math::{Mat4, Vec3};
+
+use overlay::Overlay;
+use perf_stats::{PerfStats, PERF_STATS_HISTORY};
+use gpu_timer::GpuTimer;
+use physics::{Body, Shape, World};
+use frame_clock::FrameClock;
+use draw_list::{DrawCommand, DrawList, Uniform};
+use vertex_layout::VertexLayout;
+
+
+const GL_LOG_FILE: &str = "gl.log";
+const VERTEX_SHADER_FILE: &str = "src/scene_vs.glsl";
+const FRAGMENT_SHADER_FILE: &str = "src/scene_fs.glsl";
+
+// The physics is stepped this many times per simulated second whatever the frame
+// rate, so the stacks behave the same on every machine.
+const PHYSICS_STEPS_PER_SECOND: f64 = 120.0;
+const MAX_PHYSICS_STEPS_PER_FRAME: u32 = 10;
+
+const GRAVITY: f32 = -9.81;
+const DEFAULT_RESTITUTION: f32 = 0.3;
+const RESTITUTION_STEP: f32 = 0.1;
+const THROW_SPEED: f32 = 12.0;
+const THROWN_BALL_RADIUS: f32 = 0.3;
+
+const GROUND_COLOUR: [f32; 3] = [0.35, 0.45, 0.3];
+const BODY_COLOURS: [[f32; 3]; 6] = [
+    [0.9, 0.2, 0.2],
+    [0.9, 0.6, 0.1],
+    [0.9, 0.9, 0.2],
+    [0.2, 0.8, 0.3],
+    [0.2, 0.6, 0.9],
+    [0.6, 0.3, 0.9],
+];
+
+
+///
+/// A unit cube centred on the origin, with each position followed by its normal.
+///
+fn cube_vertices() -> Vec<GLfloat> {
+    // The normal of each face, and two axes across it whose cross product is the
+    // normal, so the triangles wind counter-clockwise seen from outside.
+    let faces = [
+        ([1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]),
+        ([-1.0, 0.0, 0.0], [0.0, 0.0, 1.0], [0.0, 1.0, 0.0]),
+        ([0.0, 1.0, 0.0], [0.0, 0.0, 1.0], [1.0, 0.0, 0.0]),
+        ([0.0, -1.0, 0.0], [1.0, 0.0, 0.0], [0.0, 0.0, 1.0]),
+        ([0.0, 0.0, 1.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]),
+        ([0.0, 0.0, -1.0], [0.0, 1.0, 0.0], [1.0, 0.0, 0.0]),
+    ];
+    let corners = [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (1.0, 1.0), (-1.0, 1.0), (-1.0, -1.0)];
+
+    let mut vertices = Vec::with_capacity(6 * 6 * faces.len());
+    for &(n, u, v) in faces.iter() {
+        for &(s, t) in corners.iter() {
+            for i in 0..3 {
+                vertices.push(0.5 * (n[i] + s * u[i] + t * v[i]));
+            }
+            vertices.extend_from_slice(&n);
+        }
+    }
+
+    vertices
+}
+
+///
+/// Interleave separate position and normal arrays, as loaded from a mesh.
+///
+fn interleave(points: &[GLfloat], normals: &[GLfloat]) -> Vec<GLfloat> {
+    let mut vertices = Vec::with_capacity(points.len() + normals.len());
+    for i in 0..(points.len() / 3) {
+        vertices.extend_from_slice(&points[3 * i..3 * i + 3]);
+        vertices.extend_from_slice(&normals[3 * i..3 * i + 3]);
+    }
+
+    vertices
+}
+
+///
+/// Copy interleaved positions and normals into a vertex buffer, and make a vertex
+/// array reading positions from location 0 and normals from location 1.
+///
+fn make_vao(vertices: &[GLfloat]) -> GLuint {
+    let mut vao = 0;
+    unsafe {
+        gl::GenVertexArrays(1, &mut vao);
+    }
+    assert!(vao > 0);
+    VertexLayout::new().floats(0, 3).floats(1, 3).upload(vao, vertices, vertices.len() / 6);
+
+    vao
+}
+
+///
+/// Build the scene: the ground, a tower of boxes, a pyramid of boxes with a ball
+/// on top, and a pile of balls in a box pen.
+///
+fn make_world(restitution: f32) -> World {
+    let mut world = World::new(math::vec3((0.0, GRAVITY, 0.0)));
+    world.add(Body::fixed(Shape::Box(math::vec3((20.0, 1.0, 20.0))), math::vec3((0.0, -1.0, 0.0))));
+
+    let half = math::vec3((0.5, 0.5, 0.5));
+    for i in 0..6 {
+        world.add(Body::cuboid(math::vec3((-3.0, 0.5 + i as f32, 0.0)), half, 1.0, restitution));
+    }
+
+    for row in 0..3 {
+        for i in 0..(3 - row) {
+            let x = 1.0 + i as f32 * 1.05 + row as f32 * 0.525;
+            world.add(Body::cuboid(math::vec3((x, 0.5 + row as f32, 0.0)), half, 1.0, restitution));
+        }
+    }
+    world.add(Body::sphere(math::vec3((2.05, 3.5, 0.0)), 0.5, 0.5, restitution));
+
+    let pen_half = math::vec3((0.25, 0.5, 1.5));
+    world.add(Body::cuboid(math::vec3((-1.75, 0.5, -4.0)), pen_half, 4.0, restitution));
+    world.add(Body::cuboid(math::vec3((1.75, 0.5, -4.0)), pen_half, 4.0, restitution));
+    for i in 0..9 {
+        let x = -1.0 + (i % 3) as f32;
+        let z = -5.0 + (i / 3) as f32;
+        let y = 0.4 + (i % 2) as f32 * 0.9;
+        world.add(Body::sphere(math::vec3((x, y, z)), 0.4, 0.3, restitution));
+    }
+
+    world
+}
+
+fn set_restitution(world: &mut World, restitution: f32) {
+    for body in world.bodies.iter_mut().filter(|body| !body.is_fixed()) {
+        body.restitution = restitution;
+    }
+}
+
+///
+/// The model matrix that stretches the unit cube or unit sphere over a body at
+/// `position`.
+///
+fn body_model_matrix(body: &Body, position: &Vec3) -> Mat4 {
+    let size = match body.shape {
+        Shape::Sphere(radius) => math::vec3((radius, radius, radius)),
+        Shape::Box(half_extents) => half_extents * 2.0,
+    };
+
+    Mat4::from_scale(&size).then_translate(position)
+}
+
+///
+/// Where to draw a body between physics steps: `alpha` of the way from where it
+/// was before the last step to where it is now. A body added since the last
+/// step has no earlier position, so it is drawn where it is.
+///
+fn interpolated_position(previous_positions: &[Vec3], i: usize, body: &Body, alpha: f32) -> Vec3 {
+    match previous_positions.get(i) {
+        Some(previous) => *previous * (1.0 - alpha) + body.position * alpha,
+        None => body.position,
+    }
+}
+
+///
+/// The shader everything in the scene is drawn with: a flat colour lit by one
+/// light from above.
+///
+struct SceneShader {
+    sp: GLuint,
+    model_location: GLint,
+    colour_location: GLint,
+}
+
+impl SceneShader {
+    fn command(&self, vao: GLuint, point_count: usize, model: &Mat4, colour: &[f32; 3]) -> DrawCommand {
+        DrawCommand::new(self.sp, vao, point_count)
+            .with_uniform(Uniform::Mat4(self.model_location, *model))
+            .with_uniform(Uniform::Vec3(self.colour_location, *colour))
+    }
+}
+
+fn main() {
+    let logger = restart_gl_log(GL_LOG_FILE);
+    // Start a GL context and O/S window using the GLFW helper library.
+    let mut context = match start_gl(&logger) {
+        Ok(val) => val,
+        Err(e) => {
+            eprintln!("Failed to Initialize OpenGL context. Got error:");
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+    };
+
+    unsafe {
+        // Enable depth testing.
+        gl::Enable(gl::DEPTH_TEST);
+        // Depth testing interprets a smaller value as closer to the eye.
+        gl::DepthFunc(gl::LESS);
+        // Cull face.
+        gl::Enable(gl::CULL_FACE);
+        // Cull back face.
+        gl::CullFace(gl::BACK);
+        // GL_CW for clockwise.
+        gl::FrontFace(gl::CCW);
+        gl::ClearColor(0.6, 0.7, 0.8, 1.0);
+    }
+
+    /*------------------------------CREATE GEOMETRY------------------------------*/
+    let cube = cube_vertices();
+    let cube_vao = make_vao(&cube);
+    let cube_point_count = cube.len() / 6;
+
+    // the balls use the embedded unit sphere
+    let sphere_mesh = assets::fallback_mesh();
+    let sphere_vao = make_vao(&interleave(&sphere_mesh.points, &sphere_mesh.normals));
+    let sphere_point_count = sphere_mesh.point_count;
+
+    /*-------------------------------CREATE SHADERS------------------------------*/
+    let shader_programme = create_programme_from_files(&logger, VERTEX_SHADER_FILE, FRAGMENT_SHADER_FILE);
+    let model_mat_location = uniform_location(shader_programme, "M");
+    assert!(model_mat_location > -1);
+    let view_mat_location = uniform_location(shader_programme, "V");
+    assert!(view_mat_location > -1);
+    let proj_mat_location = uniform_location(shader_programme, "P");
+    assert!(proj_mat_location > -1);
+    let colour_location = uniform_location(shader_programme, "colour");
+    assert!(colour_location > -1);
+    let scene_shader = SceneShader {
+        sp: shader_programme,
+        model_location: model_mat_location,
+        colour_location: colour_location,
+    };
+
+    /*-------------------------------CREATE SCENE--------------------------------*/
+    let mut restitution = DEFAULT_RESTITUTION;
+    let mut world = make_world(restitution);
+    let mut fixed_step = FixedStep::new(PHYSICS_STEPS_PER_SECOND, MAX_PHYSICS_STEPS_PER_FRAME);
+    let mut previous_positions: Vec<Vec3> = world.bodies.iter().map(|body| body.position).collect();
+    // the physics follows simulation time, which the time controls pause, slow
+    // down, speed up, and step; the camera follows real time
+    let mut clock = FrameClock::new();
+    logger.log("keys: space throws a ball, R rebuilds the scene,");
+    logger.log("      [ and ] change how bouncy the bodies are, F3 toggles the HUD");
+    logger.log("      F6 pauses time, F7 and F8 slow it down and speed it up, F10 steps a frame while paused");
+
+    // frame times, and how many physics steps each frame took
+    let mut overlay = Overlay::new(&logger);
+    let mut perf_stats = PerfStats::new(PERF_STATS_HISTORY);
+    let mut show_perf_stats = true;
+    let mut scene_timer = GpuTimer::new();
+    // the draws for each frame, sorted by the state they need
+    let mut draw_list = DrawList::new();
+
+    // input variables
+    let near = 0.1;                                  // clipping plane
+    let far = 100.0;                                 // clipping plane
+    let fov = 67.0;                                  // convert 67 degrees to radians
+    let aspect = context.width as f32 / context.height as f32; // aspect ratio
+    let proj_mat = Mat4::perspective(fov, aspect, near, far);
+
+    // matrix components
+    let cam_speed: GLfloat = 4.0;             // 4 units per second
+    let cam_yaw_speed: GLfloat = 60.0;        // 60 degrees per second
+    let mut cam_pos: Vec3 = math::vec3((0.0, 2.5, 9.0));
+    let mut cam_yaw: GLfloat = 0.0;               // y-rotation in degrees
+
+    unsafe {
+        gl::UseProgram(shader_programme);
+        gl::UniformMatrix4fv(proj_mat_location, 1, gl::FALSE, proj_mat.as_ptr());
+    }
+
+    while !context.window.should_close() {
+        let frame_start = Instant::now();
+        let current_seconds = context.glfw.get_time();
+        let elapsed_seconds = current_seconds - context.elapsed_time_seconds;
+        context.elapsed_time_seconds = current_seconds;
+
+        update_fps_counter(&mut context);
+
+        // step the physics as many times as the time since the last frame calls for
+        clock.tick(elapsed_seconds);
+        let physics_steps_this_frame = fixed_step.advance(clock.dt());
+        for _ in 0..physics_steps_this_frame {
+            previous_positions.clear();
+            previous_positions.extend(world.bodies.iter().map(|body| body.position));
+            world.step(fixed_step.step_seconds() as f32);
+        }
+        let alpha = fixed_step.alpha() as f32;
+
+        let mat_trans = Mat4::from_translation(&-cam_pos);
+        let mat_rot = Mat4::from_rotation_y_deg(-cam_yaw);
+        let view_mat = mat_rot * mat_trans;
+
+        unsafe {
+            // Wipe the drawing surface clear.
+            gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
+            gl::Viewport(0, 0, context.width as i32, context.height as i32);
+
+            gl::UseProgram(shader_programme);
+            gl::UniformMatrix4fv(view_mat_location, 1, gl::FALSE, view_mat.as_ptr());
+        }
+
+        draw_list.clear();
+        let mut colour_index = 0;
+        for (i, body) in world.bodies.iter().enumerate() {
+            let position = interpolated_position(&previous_positions, i, body, alpha);
+            let model_mat = body_model_matrix(body, &position);
+            let colour = if body.is_fixed() {
+                &GROUND_COLOUR
+            } else {
+                colour_index += 1;
+                &BODY_COLOURS[colour_index % BODY_COLOURS.len()]
+            };
+            match body.shape {
+                Shape::Sphere(_) => {
+                    draw_list.push(scene_shader.command(sphere_vao, sphere_point_count, &model_mat, colour));
+                    perf_stats.count_draw(sphere_point_count / 3);
+                }
+                Shape::Box(_) => {
+                    draw_list.push(scene_shader.command(cube_vao, cube_point_count, &model_mat, colour));
+                    perf_stats.count_draw(cube_point_count / 3);
+                }
+            }
+        }
+        let unsorted_stats = draw_list.state_changes();
+        scene_timer.begin();
+        let draw_stats = draw_list.execute();
+        scene_timer.end();
+
+        perf_stats.set_gpu_pass_ms("scene", scene_timer.elapsed_ms());
+        if show_perf_stats {
+            let hud_bottom = perf_stats.draw_hud(&mut overlay, 10.0, 10.0);
+            let text = format!(
+                "physics steps {} this frame at {} Hz  bodies {}  restitution {:.1}  {}",
+                physics_steps_this_frame, PHYSICS_STEPS_PER_SECOND, world.bodies.len(), restitution,
+                clock.describe()
+            );
+            overlay.text(16.0, hud_bottom + 6.0, 2.0, [1.0, 1.0, 1.0, 1.0], &text);
+            let state_text = format!(
+                "state changes {} sorted, {} unsorted",
+                draw_stats.state_changes(), unsorted_stats.state_changes()
+            );
+            overlay.text(16.0, hud_bottom + 26.0, 2.0, [1.0, 1.0, 1.0, 1.0], &state_text);
+            overlay.draw(context.width, context.height);
+        }
+
+        context.glfw.poll_events();
+
+        let yaw_rad = cam_yaw.to_radians();
+        let forward = math::vec3((-f32::sin(yaw_rad), 0.0, -f32::cos(yaw_rad)));
+        let right = math::vec3((f32::cos(yaw_rad), 0.0, -f32::sin(yaw_rad)));
+
+        let mut restitution_changed = false;
+        for (_, event) in glfw::flush_messages(&context.events) {
+            match event {
+                glfw::WindowEvent::Key(Key::Space, _, Action::Press, _) => {
+                    let direction = (forward + math::vec3((0.0, 0.15, 0.0))).normalize();
+                    let mut ball = Body::sphere(cam_pos + forward, THROWN_BALL_RADIUS, 2.0, restitution);
+                    ball.velocity = direction * THROW_SPEED;
+                    world.add(ball);
+                }
+                glfw::WindowEvent::Key(Key::R, _, Action::Press, _) => {
+                    world = make_world(restitution);
+                    previous_positions = world.bodies.iter().map(|body| body.position).collect();
+                    fixed_step.reset();
+                }
+                glfw::WindowEvent::Key(Key::LeftBracket, _, Action::Press, _) => {
+                    restitution = f32::max(restitution - RESTITUTION_STEP, 0.0);
+                    restitution_changed = true;
+                }
+                glfw::WindowEvent::Key(Key::RightBracket, _, Action::Press, _) => {
+                    restitution = f32::min(restitution + RESTITUTION_STEP, 1.0);
+                    restitution_changed = true;
+                }
+                glfw::WindowEvent::Key(Key::F3, _, Action::Press, _) => {
+                    show_perf_stats = !show_perf_stats;
+                }
+                glfw::WindowEvent::Key(key, _, Action::Press, _) => {
+                    clock.handle_key(key);
+                }
+                _ => {}
+            }
+        }
+        if restitution_changed {
+            set_restitution(&mut world, restitution);
+            logger.log(&format!("restitution: {:.1}", restitution));
+        }
+
+        // control keys
+        let step = cam_speed * (elapsed_seconds as GLfloat);
+        match context.window.get_key(Key::W) {
+            Action::Press | Action::Repeat => {
+                cam_pos += forward * step;
+            }
+            _ => {}
+        }
+        match context.window.get_key(Key::S) {
+            Action::Press | Action::Repeat => {
+                cam_pos -= forward * step;
+            }
+            _ => {}
+        }
+        match context.window.get_key(Key::A) {
+            Action::Press | Action::Repeat => {
+                cam_pos -= right * step;
+            }
+            _ => {}
+        }
+        match context.window.get_key(Key::D) {
+            Action::Press | Action::Repeat => {
+                cam_pos += right * step;
+            }
+            _ => {}
+        }
+        match context.window.get_key(Key::Up) {
+            Action::Press | Action::Repeat => {
+                cam_pos.v[1] += step;
+            }
+            _ => {}
+        }
+        match context.window.get_key(Key::Down) {
+            Action::Press | Action::Repeat => {
+                cam_pos.v[1] -= step;
+            }
+            _ => {}
+        }
+        match context.window.get_key(Key::Left) {
+            Action::Press | Action::Repeat => {
+                cam_yaw += cam_yaw_speed * (elapsed_seconds as GLfloat);
+            }
+            _ => {}
+        }
+        match context.window.get_key(Key::Right) {
+            Action::Press | Action::Repeat => {
+                cam_yaw -= cam_yaw_speed * (elapsed_seconds as GLfloat);
+            }
+            _ => {}
+        }
+
+        match context.window.get_key(Key::Escape) {
+            Action::Press | Action::Repeat => {
+                context.window.set_should_close(true);
+            }
+            _ => {}
+        }
+        perf_stats.end_frame(perf_stats::duration_to_ms(frame_start.elapsed()));
+        // Put the stuff we've been drawing onto the display.
+        context.window.swap_buffers();
+    }
+}
@@ -0,0 +1,385 @@
+use graphics_math::Vec3;
+
+use collision;
+use collision::{Aabb, Contact, Sphere};
+
+
+///
+/// Bodies closing slower than this, in units per second, are treated as resting
+/// on each other and do not bounce. Without it a body lying on the ground would
+/// bounce a tiny amount every step from the speed gravity gave it.
+///
+pub const RESTING_SPEED: f32 = 0.5;
+///
+/// How much sliding bodies rub against each other. The friction impulse at a
+/// contact can be at most this times the impulse pushing the bodies apart.
+///
+pub const FRICTION: f32 = 0.5;
+///
+/// Impulses only stop bodies moving further into each other, so the overlap a
+/// step leaves behind is removed by moving them apart directly. Only this
+/// fraction of it is removed each step, and overlaps shallower than the slop are
+/// left alone, so bodies resting on each other do not jitter.
+///
+const PENETRATION_CORRECTION: f32 = 0.4;
+const PENETRATION_SLOP: f32 = 0.005;
+///
+/// How many times each step the contacts are resolved. A contact in the middle of
+/// a stack changes the velocities at the contacts above and below it, so it
+/// takes several passes for a stack to settle.
+///
+const SOLVER_ITERATIONS: usize = 8;
+
+
+///
+/// The shape of a body, centred on its position.
+///
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Shape {
+    Sphere(f32),
+    /// A box, given by its half extents.
+    Box(Vec3),
+}
+
+///
+/// A rigid body. Bodies move but never rotate, so boxes stay lined up with the
+/// axes and the collision module's box tests are enough for them. A body with an
+/// inverse mass of zero has infinite mass and never moves.
+///
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Body {
+    pub shape: Shape,
+    pub position: Vec3,
+    pub velocity: Vec3,
+    pub inverse_mass: f32,
+    /// How much of its speed the body keeps when it bounces, from 0 for no bounce
+    /// at all to 1 for a perfectly elastic bounce.
+    pub restitution: f32,
+}
+
+impl Body {
+    pub fn sphere(position: Vec3, radius: f32, mass: f32, restitution: f32) -> Body {
+        Body {
+            shape: Shape::Sphere(radius),
+            position: position,
+            velocity: Vec3::zero(),
+            inverse_mass: 1.0 / mass,
+            restitution: restitution,
+        }
+    }
+
+    pub fn cuboid(position: Vec3, half_extents: Vec3, mass: f32, restitution: f32) -> Body {
+        Body {
+            shape: Shape::Box(half_extents),
+            position: position,
+            velocity: Vec3::zero(),
+            inverse_mass: 1.0 / mass,
+            restitution: restitution,
+        }
+    }
+
+    ///
+    /// A body that never moves, such as the ground.
+    ///
+    pub fn fixed(shape: Shape, position: Vec3) -> Body {
+        Body {
+            shape: shape,
+            position: position,
+            velocity: Vec3::zero(),
+            inverse_mass: 0.0,
+            restitution: 1.0,
+        }
+    }
+
+    pub fn is_fixed(&self) -> bool {
+        self.inverse_mass == 0.0
+    }
+}
+
+///
+/// The contact between two bodies, or `None` if they do not overlap. The normal
+/// points the way `a` has to move to separate from `b`.
+///
+pub fn body_contact(a: &Body, b: &Body) -> Option<Contact> {
+    match (a.shape, b.shape) {
+        (Shape::Sphere(radius_a), Shape::Sphere(radius_b)) => {
+            collision::sphere_sphere(&Sphere::new(a.position, radius_a), &Sphere::new(b.position, radius_b))
+        }
+        (Shape::Sphere(radius), Shape::Box(half_extents)) => {
+            collision::sphere_aabb(&Sphere::new(a.position, radius), &Aabb::from_centre(b.position, half_extents))
+        }
+        (Shape::Box(half_extents), Shape::Sphere(radius)) => {
+            let sphere = Sphere::new(b.position, radius);
+            collision::sphere_aabb(&sphere, &Aabb::from_centre(a.position, half_extents)).map(|contact| {
+                Contact { normal: contact.normal * -1.0, depth: contact.depth }
+            })
+        }
+        (Shape::Box(half_extents_a), Shape::Box(half_extents_b)) => {
+            collision::aabb_aabb(
+                &Aabb::from_centre(a.position, half_extents_a), &Aabb::from_centre(b.position, half_extents_b)
+            )
+        }
+    }
+}
+
+///
+/// A contact found at the start of a step, and the impulses the solver has
+/// applied at it so far.
+///
+struct BodyContact {
+    a: usize,
+    b: usize,
+    contact: Contact,
+    /// The speed the bodies should be moving apart at once the contact is resolved.
+    target_speed: f32,
+    normal_impulse: f32,
+    friction_impulse: Vec3,
+}
+
+///
+/// A set of bodies, moved by gravity and kept apart by their contacts.
+///
+pub struct World {
+    pub bodies: Vec<Body>,
+    pub gravity: Vec3,
+}
+
+impl World {
+    pub fn new(gravity: Vec3) -> World {
+        World {
+            bodies: vec![],
+            gravity: gravity,
+        }
+    }
+
+    ///
+    /// Add a body, and return its index in `bodies`.
+    ///
+    pub fn add(&mut self, body: Body) -> usize {
+        self.bodies.push(body);
+        self.bodies.len() - 1
+    }
+
+    fn find_contacts(&self) -> Vec<BodyContact> {
+        let mut contacts = vec![];
+        for i in 0..self.bodies.len() {
+            for j in (i + 1)..self.bodies.len() {
+                let a = &self.bodies[i];
+                let b = &self.bodies[j];
+                if a.is_fixed() && b.is_fixed() {
+                    continue;
+                }
+
+                if let Some(contact) = body_contact(a, b) {
+                    let closing_speed = -(a.velocity - b.velocity).dot(&contact.normal);
+                    let restitution = f32::min(a.restitution, b.restitution);
+                    let target_speed = if closing_speed > RESTING_SPEED {
+                        restitution * closing_speed
+                    } else {
+                        0.0
+                    };
+                    contacts.push(BodyContact {
+                        a: i,
+                        b: j,
+                        contact: contact,
+                        target_speed: target_speed,
+                        normal_impulse: 0.0,
+                        friction_impulse: Vec3::zero(),
+                    });
+                }
+            }
+        }
+
+        contacts
+    }
+
+    ///
+    /// Apply an impulse at a contact, pushing `a` along it and `b` the other way.
+    ///
+    fn apply_impulse(&mut self, a: usize, b: usize, impulse: Vec3) {
+        let inverse_mass_a = self.bodies[a].inverse_mass;
+        let inverse_mass_b = self.bodies[b].inverse_mass;
+        self.bodies[a].velocity += impulse * inverse_mass_a;
+        self.bodies[b].velocity -= impulse * inverse_mass_b;
+    }
+
+    ///
+    /// Move the bodies at a contact towards the velocities the contact calls for.
+    /// The total impulse at a contact can only ever push the bodies apart, and the
+    /// friction is limited by how hard they are pushed together, so each pass
+    /// works out the new totals and applies the difference from the old ones.
+    ///
+    fn resolve_contact(&mut self, contact: &mut BodyContact) {
+        let inverse_mass_sum = self.bodies[contact.a].inverse_mass + self.bodies[contact.b].inverse_mass;
+        let normal = contact.contact.normal;
+
+        let relative_velocity = self.bodies[contact.a].velocity - self.bodies[contact.b].velocity;
+        let separating_speed = relative_velocity.dot(&normal);
+        let normal_impulse = f32::max(
+            contact.normal_impulse + (contact.target_speed - separating_speed) / inverse_mass_sum, 0.0
+        );
+        let delta = normal_impulse - contact.normal_impulse;
+        contact.normal_impulse = normal_impulse;
+        self.apply_impulse(contact.a, contact.b, normal * delta);
+
+        let relative_velocity = self.bodies[contact.a].velocity - self.bodies[contact.b].velocity;
+        let sliding_velocity = relative_velocity - normal * relative_velocity.dot(&normal);
+        let mut friction_impulse = contact.friction_impulse - sliding_velocity / inverse_mass_sum;
+        let max_friction = FRICTION * contact.normal_impulse;
+        let friction = friction_impulse.norm();
+        if friction > max_friction {
+            friction_impulse = friction_impulse * (max_friction / friction);
+        }
+        let delta = friction_impulse - contact.friction_impulse;
+        contact.friction_impulse = friction_impulse;
+        self.apply_impulse(contact.a, contact.b, delta);
+    }
+
+    ///
+    /// Move the bodies at a contact part of the way out of each other.
+    ///
+    fn correct_penetration(&mut self, contact: &BodyContact) {
+        let inverse_mass_a = self.bodies[contact.a].inverse_mass;
+        let inverse_mass_b = self.bodies[contact.b].inverse_mass;
+        let depth = f32::max(contact.contact.depth - PENETRATION_SLOP, 0.0);
+        let correction = contact.contact.normal * (PENETRATION_CORRECTION * depth / (inverse_mass_a + inverse_mass_b));
+        self.bodies[contact.a].position += correction * inverse_mass_a;
+        self.bodies[contact.b].position -= correction * inverse_mass_b;
+    }
+
+    ///
+    /// Advance the world by `dt` seconds. Gravity is applied first, then impulses
+    /// at the contacts stop the bodies moving into each other, and only then are
+    /// the bodies moved, so a body resting on another never falls into it. The
+    /// step should be short and always the same length: a long step lets fast
+    /// bodies pass through each other, and the stacks settle differently if it
+    /// changes from step to step.
+    ///
+    pub fn step(&mut self, dt: f32) {
+        let gravity = self.gravity;
+        for body in self.bodies.iter_mut().filter(|body| !body.is_fixed()) {
+            body.velocity += gravity * dt;
+        }
+
+        let mut contacts = self.find_contacts();
+        for _ in 0..SOLVER_ITERATIONS {
+            for contact in contacts.iter_mut() {
+                self.resolve_contact(contact);
+            }
+        }
+
+        for body in self.bodies.iter_mut().filter(|body| !body.is_fixed()) {
+            body.position += body.velocity * dt;
+        }
+
+        for contact in contacts.iter() {
+            self.correct_penetration(contact);
+        }
+    }
+}
+
+mod physics_tests {
+    use super::{Body, Shape, World};
+    use graphics_math as math;
+
+    const DT: f32 = 1.0 / 120.0;
+
+    fn world_with_ground() -> World {
+        let mut world = World::new(math::vec3((0.0, -9.81, 0.0)));
+        world.add(Body::fixed(Shape::Box(math::vec3((10.0, 1.0, 10.0))), math::vec3((0.0, -1.0, 0.0))));
+
+        world
+    }
+
+    fn run(world: &mut World, seconds: f32) {
+        let steps = (seconds / DT) as usize;
+        for _ in 0..steps {
+            world.step(DT);
+        }
+    }
+
+    #[test]
+    fn test_a_body_in_free_fall_gains_speed_from_gravity() {
+        let mut world = World::new(math::vec3((0.0, -10.0, 0.0)));
+        let ball = world.add(Body::sphere(math::vec3((0.0, 100.0, 0.0)), 0.5, 1.0, 0.5));
+        for _ in 0..120 {
+            world.step(DT);
+        }
+
+        assert!((world.bodies[ball].velocity.v[1] + 10.0).abs() < 1e-3);
+        assert!(world.bodies[ball].position.v[1] < 95.0);
+    }
+
+    #[test]
+    fn test_fixed_bodies_never_move() {
+        let mut world = world_with_ground();
+        world.add(Body::cuboid(math::vec3((0.0, 0.5, 0.0)), math::vec3((0.5, 0.5, 0.5)), 1000.0, 0.0));
+        run(&mut world, 1.0);
+
+        assert_eq!(world.bodies[0].position, math::vec3((0.0, -1.0, 0.0)));
+        assert_eq!(world.bodies[0].velocity, math::vec3((0.0, 0.0, 0.0)));
+    }
+
+    #[test]
+    fn test_an_elastic_head_on_collision_swaps_the_velocities_of_equal_bodies() {
+        let mut world = World::new(math::vec3((0.0, 0.0, 0.0)));
+        let a = world.add(Body::sphere(math::vec3((-0.49, 0.0, 0.0)), 0.5, 1.0, 1.0));
+        let b = world.add(Body::sphere(math::vec3((0.49, 0.0, 0.0)), 0.5, 1.0, 1.0));
+        world.bodies[a].velocity = math::vec3((2.0, 0.0, 0.0));
+        world.step(DT);
+
+        assert!((world.bodies[a].velocity.v[0] - 0.0).abs() < 1e-4);
+        assert!((world.bodies[b].velocity.v[0] - 2.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_a_dead_ball_comes_to_rest_on_the_ground() {
+        let mut world = world_with_ground();
+        let ball = world.add(Body::sphere(math::vec3((0.0, 2.0, 0.0)), 0.5, 1.0, 0.0));
+        run(&mut world, 2.0);
+
+        let body = &world.bodies[ball];
+        assert!(body.velocity.norm() < 0.1);
+        assert!((body.position.v[1] - 0.5).abs() < 0.02);
+    }
+
+    #[test]
+    fn test_a_bouncy_ball_bounces_back_up() {
+        let mut world = world_with_ground();
+        let ball = world.add(Body::sphere(math::vec3((0.0, 2.5, 0.0)), 0.5, 1.0, 0.8));
+        let mut bounced = false;
+        let mut peak_after_bounce: f32 = 0.0;
+        for _ in 0..240 {
+            world.step(DT);
+            let body = &world.bodies[ball];
+            if body.velocity.v[1] > 0.0 {
+                bounced = true;
+            }
+            if bounced {
+                peak_after_bounce = f32::max(peak_after_bounce, body.position.v[1]);
+            }
+        }
+
+        // Dropped from 2 units above the ground, it should come back to about
+        // 0.8 * 0.8 * 2 = 1.28 units above it.
+        assert!(bounced);
+        assert!(peak_after_bounce > 0.5 + 1.1);
+        assert!(peak_after_bounce < 0.5 + 1.5);
+    }
+
+    #[test]
+    fn test_a_stack_of_boxes_stays_put() {
+        let mut world = world_with_ground();
+        let half = math::vec3((0.5, 0.5, 0.5));
+        let boxes: Vec<usize> = (0..4).map(|i| {
+            world.add(Body::cuboid(math::vec3((0.0, 0.5 + i as f32, 0.0)), half, 1.0, 0.2))
+        }).collect();
+        run(&mut world, 3.0);
+
+        for (i, &b) in boxes.iter().enumerate() {
+            let position = world.bodies[b].position;
+            assert!(position.v[0].abs() < 1e-4);
+            assert!((position.v[1] - (0.5 + i as f32)).abs() < 0.05, "box {} is at {}", i, position.v[1]);
+        }
+    }
+}
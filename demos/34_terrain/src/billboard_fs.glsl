@@ -0,0 +1,21 @@
+#version 410
+
+in vec3 pos_eye;
+in vec2 st;
+out vec4 frag_colour;
+
+uniform sampler2D sprite_map;
+
+#include "fog.glsl"
+
+void main() {
+	vec4 texel = texture (sprite_map, st);
+	// cut the sprite out of its background, so billboards need no sorting
+	if (texel.a < 0.5) {
+		discard;
+	}
+	frag_colour = vec4 (texel.rgb * 0.9, 1.0);
+
+	// blend the fog colour with the lighting colour, based on distance from the camera
+	frag_colour.rgb = apply_fog (frag_colour.rgb, pos_eye);
+}
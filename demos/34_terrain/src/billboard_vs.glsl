@@ -0,0 +1,16 @@
+#version 410
+
+// corners of a quad standing on its bottom edge, from -0.5 to 0.5 across and
+// from 0 to 1 up
+layout(location = 0) in vec2 vertex_position;
+
+uniform mat4 model, view, proj;
+
+out vec3 pos_eye;
+out vec2 st;
+
+void main() {
+	pos_eye = (view * model * vec4 (vertex_position, 0.0, 1.0)).xyz;
+	st = vec2 (vertex_position.x + 0.5, vertex_position.y);
+	gl_Position = proj * vec4 (pos_eye, 1.0);
+}
@@ -0,0 +1,104 @@
+use camera::Camera;
+use graphics_math::Mat4;
+use render_state::DepthState;
+
+
+///
+/// The ways the terrain demo maps distance to depth. A standard projection stops
+/// at the far clipping plane and packs most of its depth values close to the
+/// camera, so distant surfaces fight over the few left. Reversed-Z spreads them
+/// out again, given a floating point depth buffer to draw into.
+///
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum DepthMode {
+    Standard,
+    Infinite,
+    ReversedZ,
+}
+
+impl DepthMode {
+    ///
+    /// The mode after this one, leaving out reversed-Z when the context cannot do it.
+    ///
+    pub fn next(&self, reversed_z_supported: bool) -> DepthMode {
+        match *self {
+            DepthMode::Standard => DepthMode::Infinite,
+            DepthMode::Infinite if reversed_z_supported => DepthMode::ReversedZ,
+            DepthMode::Infinite => DepthMode::Standard,
+            DepthMode::ReversedZ => DepthMode::Standard,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            DepthMode::Standard => "standard",
+            DepthMode::Infinite => "infinite far plane",
+            DepthMode::ReversedZ => "reversed-Z",
+        }
+    }
+
+    ///
+    /// The projection matrix for the camera in this mode. Only the standard
+    /// projection has a far clipping plane.
+    ///
+    pub fn proj_mat(&self, camera: &Camera, aspect: f32, near: f32, far: f32) -> Mat4 {
+        match *self {
+            DepthMode::Standard => camera.proj_mat(aspect, near, far),
+            DepthMode::Infinite => Mat4::perspective_infinite(camera.fovy, aspect, near),
+            DepthMode::ReversedZ => Mat4::perspective_reversed_z(camera.fovy, aspect, near),
+        }
+    }
+
+    ///
+    /// The depth test state this mode needs. Reversed-Z takes depth in the range
+    /// 0 to 1 instead of -1 to 1, clears the depth buffer to 0, and keeps the
+    /// fragment with the greater depth.
+    ///
+    pub fn depth_state(&self) -> DepthState {
+        match *self {
+            DepthMode::Standard | DepthMode::Infinite => DepthState::standard(),
+            DepthMode::ReversedZ => DepthState::reversed_z(),
+        }
+    }
+
+    ///
+    /// Whether this mode draws into a floating point depth buffer instead of the
+    /// window's fixed point one. Reversed-Z only pays off with floating point
+    /// depth, whose precision is finest near 0, where it puts distant surfaces.
+    ///
+    pub fn needs_float_depth(&self) -> bool {
+        *self == DepthMode::ReversedZ
+    }
+}
+
+mod depth_mode_tests {
+    use super::DepthMode;
+    use render_state::DepthState;
+
+    #[test]
+    fn test_the_modes_cycle_through_reversed_z_when_it_is_supported() {
+        let mut mode = DepthMode::Standard;
+        let mut names = vec![];
+        for _ in 0..3 {
+            names.push(mode.name());
+            mode = mode.next(true);
+        }
+
+        assert_eq!(names, vec!["standard", "infinite far plane", "reversed-Z"]);
+        assert_eq!(mode, DepthMode::Standard);
+    }
+
+    #[test]
+    fn test_reversed_z_is_skipped_when_it_is_not_supported() {
+        assert_eq!(DepthMode::Infinite.next(false), DepthMode::Standard);
+    }
+
+    #[test]
+    fn test_only_reversed_z_changes_the_depth_state() {
+        assert_eq!(DepthMode::Standard.depth_state(), DepthState::standard());
+        assert_eq!(DepthMode::Infinite.depth_state(), DepthState::standard());
+        assert_eq!(DepthMode::ReversedZ.depth_state(), DepthState::reversed_z());
+        assert!(DepthMode::ReversedZ.needs_float_depth());
+        assert!(!DepthMode::Infinite.needs_float_depth());
+    }
+}
@@ -0,0 +1,130 @@
+use gl;
+use gl::types::{GLint, GLsizei, GLuint};
+
+
+///
+/// An off-screen framebuffer with a 32 bit floating point depth buffer, for the
+/// projections that need more depth precision than the window's fixed point one
+/// can give. The scene is drawn into it, then copied to the window. It has as
+/// many samples as the window, since a multisampled framebuffer can only be
+/// copied into another with the same number.
+///
+pub struct DepthTarget {
+    fbo: GLuint,
+    colour_rbo: GLuint,
+    depth_rbo: GLuint,
+    samples: GLsizei,
+    width: u32,
+    height: u32,
+}
+
+impl DepthTarget {
+    ///
+    /// Make a depth target `width` by `height` pixels in size. Returns an error if
+    /// the driver cannot render to a floating point depth buffer.
+    ///
+    pub fn new(width: u32, height: u32) -> Result<DepthTarget, String> {
+        let mut fbo = 0;
+        let mut rbos = [0; 2];
+        let mut samples = 0;
+        unsafe {
+            // ask while the window's framebuffer is still bound
+            gl::GetIntegerv(gl::SAMPLES, &mut samples);
+            gl::GenFramebuffers(1, &mut fbo);
+            gl::GenRenderbuffers(2, rbos.as_mut_ptr());
+        }
+
+        let mut target = DepthTarget {
+            fbo: fbo,
+            colour_rbo: rbos[0],
+            depth_rbo: rbos[1],
+            samples: samples,
+            width: 0,
+            height: 0,
+        };
+        match target.resize(width, height) {
+            Ok(_) => Ok(target),
+            Err(e) => Err(e),
+        }
+    }
+
+    ///
+    /// Make the buffers a new size, after the window changes size. What was drawn
+    /// into them is lost.
+    ///
+    pub fn resize(&mut self, width: u32, height: u32) -> Result<(), String> {
+        // GL cannot make a renderbuffer with no pixels, as a minimised window has.
+        let width = u32::max(width, 1);
+        let height = u32::max(height, 1);
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, self.fbo);
+            gl::BindRenderbuffer(gl::RENDERBUFFER, self.colour_rbo);
+            gl_check!(gl::RenderbufferStorageMultisample(
+                gl::RENDERBUFFER, self.samples, gl::RGBA8, width as GLsizei, height as GLsizei
+            ));
+            gl::FramebufferRenderbuffer(gl::FRAMEBUFFER, gl::COLOR_ATTACHMENT0, gl::RENDERBUFFER, self.colour_rbo);
+            gl::BindRenderbuffer(gl::RENDERBUFFER, self.depth_rbo);
+            gl_check!(gl::RenderbufferStorageMultisample(
+                gl::RENDERBUFFER, self.samples, gl::DEPTH_COMPONENT32F, width as GLsizei, height as GLsizei
+            ));
+            gl::FramebufferRenderbuffer(gl::FRAMEBUFFER, gl::DEPTH_ATTACHMENT, gl::RENDERBUFFER, self.depth_rbo);
+            gl::BindRenderbuffer(gl::RENDERBUFFER, 0);
+        }
+        let status = unsafe { gl::CheckFramebufferStatus(gl::FRAMEBUFFER) };
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+        }
+        self.width = width;
+        self.height = height;
+
+        if status != gl::FRAMEBUFFER_COMPLETE {
+            return Err(format!(
+                "ERROR: the 32 bit float depth framebuffer is incomplete, status 0x{:x}", status
+            ));
+        }
+
+        Ok(())
+    }
+
+    ///
+    /// Draw into this target from now on, after making it `width` by `height`
+    /// pixels if the window has changed size since the last frame.
+    ///
+    pub fn bind(&mut self, width: u32, height: u32) -> Result<(), String> {
+        if u32::max(width, 1) != self.width || u32::max(height, 1) != self.height {
+            if let Err(e) = self.resize(width, height) {
+                return Err(e);
+            }
+        }
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, self.fbo);
+        }
+
+        Ok(())
+    }
+
+    ///
+    /// Copy the colours drawn into this target onto the window, and draw into the
+    /// window again from then on.
+    ///
+    pub fn blit_to_window(&self) {
+        let (width, height) = (self.width as GLint, self.height as GLint);
+        unsafe {
+            gl::BindFramebuffer(gl::READ_FRAMEBUFFER, self.fbo);
+            gl::BindFramebuffer(gl::DRAW_FRAMEBUFFER, 0);
+            gl_check!(gl::BlitFramebuffer(
+                0, 0, width, height, 0, 0, width, height, gl::COLOR_BUFFER_BIT, gl::NEAREST
+            ));
+            gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+        }
+    }
+}
+
+impl Drop for DepthTarget {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteRenderbuffers(2, [self.colour_rbo, self.depth_rbo].as_ptr());
+            gl::DeleteFramebuffers(1, &self.fbo);
+        }
+    }
+}
@@ -0,0 +1,679 @@
+extern crate gl;
+extern crate glfw;
+extern crate chrono;
+extern crate stb_image;
+#[cfg(feature = "glam")]
+extern crate glam;
+#[cfg(feature = "cgmath")]
+extern crate cgmath;
+
+#[macro_use]
+mod gl_utils;
+mod graphics_math;
+mod obj_parser;
+mod colour;
+mod assets;
+mod shader_include;
+mod spirv;
+mod program_cache;
+mod logger;
+mod overlay;
+mod streaming_buffer;
+mod perf_stats;
+mod gpu_timer;
+mod geometry;
+mod fog;
+mod camera;
+mod texture_binder;
+mod noise;
+mod terrain;
+mod vertex_layout;
+mod depth_mode;
+mod render_state;
+mod depth_target;
+
+
+use glfw::{Action, Context, Key};
+use gl::types::{GLenum, GLfloat, GLsizeiptr, GLvoid, GLuint};
+
+use stb_image::image::LoadResult;
+
+use std::mem;
+use std::ptr;
+use std::process;
+use std::time::Instant;
+
+use gl_utils::*;
+
+use graphics_math as math;
+use math::{Mat4, Vec3, Versor};
+
+use camera::Camera;
+use fog::{FogSettings, FogUniforms};
+use overlay::Overlay;
+use perf_stats::{PerfStats, PERF_STATS_HISTORY};
+use gpu_timer::GpuTimer;
+use texture_binder::TextureBinder;
+use noise::Fbm;
+use terrain::Heightmap;
+use vertex_layout::VertexLayout;
+use depth_mode::DepthMode;
+use render_state::clip_control_supported;
+use depth_target::DepthTarget;
+
+
+const GL_LOG_FILE: &str = "gl.log";
+const VERTEX_SHADER_FILE: &str = "src/terrain_vs.glsl";
+const FRAGMENT_SHADER_FILE: &str = "src/terrain_fs.glsl";
+const BILLBOARD_VS_FILE: &str = "src/billboard_vs.glsl";
+const BILLBOARD_FS_FILE: &str = "src/billboard_fs.glsl";
+const TREE_FILE: &str = "src/tree.png";
+
+// The ground textures the fragment shader splats across the terrain, by sampler.
+const GROUND_MAPS: [(&str, &str); 3] = [
+    ("grass_map", "src/grass.png"),
+    ("rock_map", "src/rock.png"),
+    ("snow_map", "src/snow.png"),
+];
+
+const GL_TEXTURE_MAX_ANISOTROPY_EXT: u32 = 0x84FE;
+const GL_MAX_TEXTURE_MAX_ANISOTROPY_EXT: u32 = 0x84FF;
+
+// The number of heightmap samples along each side, the distance between
+// neighbouring samples, and the height the noise peaks at, in world units.
+const TERRAIN_SAMPLES: usize = 129;
+const TERRAIN_SPACING: f32 = 0.5;
+const TERRAIN_MAX_HEIGHT: f32 = 14.0;
+// The fractal noise the terrain is made from: hills about 25 units across,
+// roughened by five more octaves of finer detail.
+const TERRAIN_OCTAVES: u32 = 6;
+const TERRAIN_FREQUENCY: f32 = 0.04;
+const TREE_COUNT: usize = 150;
+// Trees only grow below this fraction of the maximum height, on ground whose
+// normal points at least this far up.
+const TREE_LINE: f32 = 0.45;
+const MIN_TREE_NORMAL_Y: f32 = 0.85;
+// The camera never gets closer to the ground than this.
+const CAMERA_CLEARANCE: f32 = 1.5;
+const MAX_CAMERA_PITCH: f32 = 89.0;
+
+// How far each key press changes the fog density, and the linear fog distances.
+const FOG_DENSITY_STEP: f32 = 0.005;
+const FOG_DISTANCE_STEP: f32 = 2.0;
+// The near clipping planes the N key switches between. Pulling the near plane in
+// close leaves a standard projection little depth precision for the far side of
+// the terrain, where the trees start to flicker through the ground.
+const NEAR_PLANE: f32 = 0.1;
+const CLOSE_NEAR_PLANE: f32 = 0.001;
+
+
+///
+/// Load a texture. The ground textures use `gl::REPEAT` to tile across the
+/// terrain, and sprites use `gl::CLAMP_TO_EDGE`.
+///
+fn load_texture(file_name: &str, wrap: GLenum, tex: &mut GLuint) -> bool {
+    let force_channels = 4;
+    let mut image_data = match assets::load_image_with_depth(file_name, force_channels) {
+        LoadResult::ImageU8(image_data) => image_data,
+        LoadResult::Error(_) => {
+            eprintln!("ERROR: could not load {}", file_name);
+            return false;
+        }
+        LoadResult::ImageF32(_) => {
+            eprintln!("ERROR: Tried to load an image as byte vectors, got f32: {}", file_name);
+            return false;
+        }
+    };
+
+    let width = image_data.width;
+    let height = image_data.height;
+
+    // Check that the image size is a power of two.
+    if (width & (width - 1)) != 0 || (height & (height - 1)) != 0 {
+        eprintln!("WARNING: texture {} is not power-of-2 dimensions", file_name);
+    }
+
+    let width_in_bytes = width * 4;
+    let half_height = height / 2;
+    for row in 0..half_height {
+        for col in 0..width_in_bytes {
+            let temp = image_data.data[row * width_in_bytes + col];
+            image_data.data[row * width_in_bytes + col] = image_data.data[((height - row - 1) * width_in_bytes) + col];
+            image_data.data[((height - row - 1) * width_in_bytes) + col] = temp;
+        }
+    }
+
+    unsafe {
+        gl::GenTextures(1, tex);
+        gl::ActiveTexture(gl::TEXTURE0);
+        gl::BindTexture(gl::TEXTURE_2D, *tex);
+        gl::TexImage2D(
+            gl::TEXTURE_2D, 0, gl::RGBA as i32, width as i32, height as i32, 0,
+            gl::RGBA, gl::UNSIGNED_BYTE,
+            image_data.data.as_ptr() as *const GLvoid
+        );
+        gl::GenerateMipmap(gl::TEXTURE_2D);
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, wrap as i32);
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, wrap as i32);
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::LINEAR as i32);
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::LINEAR_MIPMAP_LINEAR as i32);
+    }
+
+    let mut max_aniso = 0.0;
+    unsafe {
+        gl::GetFloatv(GL_MAX_TEXTURE_MAX_ANISOTROPY_EXT, &mut max_aniso);
+        // Set the maximum!
+        gl::TexParameterf(gl::TEXTURE_2D, GL_TEXTURE_MAX_ANISOTROPY_EXT, max_aniso);
+    }
+
+    return true;
+}
+
+///
+/// Upload the fog settings to every shader that draws fogged geometry, and
+/// clear the background to the fog colour so the fog fades into it.
+///
+fn set_fog(programmes: &[(GLuint, &FogUniforms)], fog: &FogSettings) {
+    for &(sp, uniforms) in programmes.iter() {
+        uniforms.upload(sp, fog);
+    }
+    let colour = fog.colour();
+    unsafe {
+        gl::ClearColor(colour[0], colour[1], colour[2], 1.0);
+    }
+}
+
+///
+/// How the tree billboards turn to face the camera. B switches between them.
+///
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum BillboardStyle {
+    Cylindrical,
+    Spherical,
+}
+
+impl BillboardStyle {
+    fn next(&self) -> BillboardStyle {
+        match *self {
+            BillboardStyle::Cylindrical => BillboardStyle::Spherical,
+            BillboardStyle::Spherical => BillboardStyle::Cylindrical,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match *self {
+            BillboardStyle::Cylindrical => "cylindrical",
+            BillboardStyle::Spherical => "spherical",
+        }
+    }
+
+    fn model_matrix(&self, view: &Mat4, position: &Vec3, size: &Vec3) -> Mat4 {
+        match *self {
+            BillboardStyle::Cylindrical => Mat4::billboard_cylindrical(view, position, size),
+            BillboardStyle::Spherical => Mat4::billboard_spherical(view, position, size),
+        }
+    }
+}
+
+///
+/// A tree drawn as a billboard standing on the terrain at `position`.
+///
+struct Tree {
+    position: Vec3,
+    size: Vec3,
+}
+
+///
+/// A repeatable pseudo-random number from 0 to 1 for the `i`th try, so the trees
+/// grow in the same places every run.
+///
+fn hash01(i: u32, salt: u32) -> f32 {
+    let mut h = i.wrapping_mul(374761393) ^ salt.wrapping_mul(668265263);
+    h = (h ^ (h >> 13)).wrapping_mul(1274126177);
+    h = h ^ (h >> 16);
+
+    (h & 0xffff) as f32 / 65535.0
+}
+
+///
+/// Scatter up to `count` trees over the low, gentle parts of the terrain. The
+/// steep slopes and the mountain tops stay bare.
+///
+fn plant_trees(heightmap: &Heightmap, count: usize) -> Vec<Tree> {
+    let mut trees = Vec::with_capacity(count);
+    let max_tries = 20 * count as u32;
+    for i in 0..max_tries {
+        if trees.len() >= count {
+            break;
+        }
+        let x = (hash01(i, 1) - 0.5) * heightmap.width();
+        let z = (hash01(i, 2) - 0.5) * heightmap.depth();
+        let y = heightmap.height_at(x, z);
+        if y > TREE_LINE * TERRAIN_MAX_HEIGHT || heightmap.normal_at(x, z).v[1] < MIN_TREE_NORMAL_Y {
+            continue;
+        }
+
+        let width = 1.5 + hash01(i, 3);
+        // sink the trunk a little, so trees on a slope do not float
+        let position = math::vec3((x, y - 0.2, z));
+        trees.push(Tree { position: position, size: math::vec3((width, 2.0 * width, 1.0)) });
+    }
+
+    trees
+}
+
+///
+/// The camera orientation for a heading and a look up or down, in degrees.
+///
+fn look_orientation(yaw: f32, pitch: f32) -> Versor {
+    let q_yaw = Versor::from_axis_deg(yaw, 0.0, 1.0, 0.0);
+    let q_pitch = Versor::from_axis_deg(pitch, 1.0, 0.0, 0.0);
+
+    q_yaw * q_pitch
+}
+
+fn main() {
+    let logger = restart_gl_log(GL_LOG_FILE);
+    // Start a GL context and O/S window using the GLFW helper library.
+    let mut context = match start_gl(&logger) {
+        Ok(val) => val,
+        Err(e) => {
+            eprintln!("Failed to Initialize OpenGL context. Got error:");
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+    };
+
+    unsafe {
+        // Depth testing is left to the depth mode, further down.
+        // Cull face.
+        gl::Enable(gl::CULL_FACE);
+        // Cull back face.
+        gl::CullFace(gl::BACK);
+        // GL_CW for clockwise.
+        gl::FrontFace(gl::CCW);
+    }
+
+    /*------------------------------CREATE GEOMETRY------------------------------*/
+    let fbm = Fbm::new(TERRAIN_OCTAVES, TERRAIN_FREQUENCY);
+    let heightmap = Heightmap::from_noise(
+        &fbm, TERRAIN_SAMPLES, TERRAIN_SAMPLES, TERRAIN_SPACING, TERRAIN_MAX_HEIGHT
+    ).unwrap();
+    logger.log(&format!(
+        "terrain: {}x{} samples, {} by {} units", heightmap.columns, heightmap.rows, heightmap.width(), heightmap.depth()
+    ));
+
+    let mesh = heightmap.mesh();
+    let index_count = mesh.indices.len();
+
+    let vertex_count = mesh.positions.len() / 3;
+    let mut vao = 0;
+    unsafe {
+        gl::GenVertexArrays(1, &mut vao);
+    }
+    assert!(vao > 0);
+    VertexLayout::new().floats(0, 3).upload(vao, &mesh.positions, vertex_count);
+    VertexLayout::new().floats(1, 3).upload(vao, &mesh.normals, vertex_count);
+    VertexLayout::new().floats(2, 2).upload(vao, &mesh.uvs, vertex_count);
+
+    // the index buffer is part of the vertex array state, so it stays bound with it
+    let mut ibo = 0;
+    unsafe {
+        gl::BindVertexArray(vao);
+        gl::GenBuffers(1, &mut ibo);
+        gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, ibo);
+        gl::BufferData(
+            gl::ELEMENT_ARRAY_BUFFER, (index_count * mem::size_of::<u32>()) as GLsizeiptr,
+            mesh.indices.as_ptr() as *const GLvoid, gl::STATIC_DRAW
+        );
+    }
+    assert!(ibo > 0);
+
+    /*-------------------------------CREATE SHADERS------------------------------*/
+    let shader_programme = create_programme_from_files(&logger, VERTEX_SHADER_FILE, FRAGMENT_SHADER_FILE);
+    let view_mat_location = uniform_location(shader_programme, "view");
+    assert!(view_mat_location > -1);
+    let proj_mat_location = uniform_location(shader_programme, "proj");
+    assert!(proj_mat_location > -1);
+    let max_height_location = uniform_location(shader_programme, "max_height");
+    assert!(max_height_location > -1);
+    unsafe {
+        gl::UseProgram(shader_programme);
+        gl::Uniform1f(max_height_location, TERRAIN_MAX_HEIGHT);
+    }
+
+    // load each ground texture and let the texture binder pick its unit.
+    let mut texture_binder = TextureBinder::new(shader_programme, 0);
+    for &(sampler, file_name) in GROUND_MAPS.iter() {
+        let mut tex = 0;
+        load_texture(file_name, gl::REPEAT, &mut tex);
+        if let Err(e) = texture_binder.add_sampler(sampler, tex) {
+            logger.log_err(&e);
+        }
+    }
+
+    /*-----------------------------CREATE BILLBOARDS-----------------------------*/
+    let trees = plant_trees(&heightmap, TREE_COUNT);
+    logger.log(&format!("planted {} trees", trees.len()));
+
+    // one quad standing on its bottom edge, shared by every billboard
+    let quad_points: [GLfloat; 12] = [
+        -0.5, 0.0, 0.5, 0.0, 0.5, 1.0, 0.5, 1.0, -0.5, 1.0, -0.5, 0.0
+    ];
+    let mut quad_vao = 0;
+    unsafe {
+        gl::GenVertexArrays(1, &mut quad_vao);
+    }
+    assert!(quad_vao > 0);
+    VertexLayout::new().floats(0, 2).upload(quad_vao, &quad_points, 6);
+
+    let billboard_programme = create_programme_from_files(&logger, BILLBOARD_VS_FILE, BILLBOARD_FS_FILE);
+    let billboard_model_location = uniform_location(billboard_programme, "model");
+    assert!(billboard_model_location > -1);
+    let billboard_view_location = uniform_location(billboard_programme, "view");
+    assert!(billboard_view_location > -1);
+    let billboard_proj_location = uniform_location(billboard_programme, "proj");
+    assert!(billboard_proj_location > -1);
+
+    let mut tree_tex = 0;
+    load_texture(TREE_FILE, gl::CLAMP_TO_EDGE, &mut tree_tex);
+    let mut billboard_binder = TextureBinder::new(billboard_programme, 0);
+    if let Err(e) = billboard_binder.add_sampler("sprite_map", tree_tex) {
+        logger.log_err(&e);
+    }
+    let mut billboard_style = BillboardStyle::Cylindrical;
+
+    // the fog hides the far edge of the terrain.
+    let fog_uniforms = FogUniforms::new(shader_programme);
+    let billboard_fog_uniforms = FogUniforms::new(billboard_programme);
+    let fogged_programmes = [(shader_programme, &fog_uniforms), (billboard_programme, &billboard_fog_uniforms)];
+    let mut fog = FogSettings::new();
+    fog.colour_index = 1;
+    fog.density = 0.03;
+    fog.start = 15.0;
+    fog.end = 55.0;
+    set_fog(&fogged_programmes, &fog);
+    logger.log("keys: W, S, A, and D move, Q and E rise and sink, the arrow keys look around,");
+    logger.log("      F cycles the fog equation, C cycles the fog colour, [ and ] change the exponential fog density,");
+    logger.log("      - and = move the start of linear fog, 9 and 0 move its end, B switches the billboard style,");
+    logger.log("      F3 toggles the performance HUD, P cycles the depth mode, N moves the near plane in and out;");
+    logger.log("      F5 saves the camera to camera.txt, and F9 restores it");
+
+    // frame time statistics, drawn over the scene along with the fog settings.
+    // F3 toggles them on and off.
+    let mut overlay = Overlay::new(&logger);
+    let mut perf_stats = PerfStats::new(PERF_STATS_HISTORY);
+    let mut show_perf_stats = true;
+    let mut terrain_timer = GpuTimer::new();
+    let mut billboard_timer = GpuTimer::new();
+
+    // the depth mode P cycles through, and the near plane N moves
+    let mut depth_mode = DepthMode::Standard;
+    depth_mode.depth_state().apply();
+    // reversed-Z draws into a floating point depth buffer of its own, since the
+    // window's is fixed point.
+    let mut depth_target = if !clip_control_supported() {
+        logger.log("glClipControl is not available, so reversed-Z is left out of the depth modes.");
+        None
+    } else {
+        match DepthTarget::new(context.width, context.height) {
+            Ok(target) => Some(target),
+            Err(e) => {
+                logger.log_err(&e);
+                logger.log("reversed-Z is left out of the depth modes.");
+                None
+            }
+        }
+    };
+    let reversed_z_supported = depth_target.is_some();
+
+    // input variables
+    let mut near = NEAR_PLANE;                       // clipping plane
+    let far = 200.0;                                 // clipping plane, unless the depth mode has none
+
+    // the camera starts over the near edge of the terrain, looking across it
+    let cam_speed: GLfloat = 8.0;                    // 8 units per second
+    let cam_heading_speed: GLfloat = 60.0;           // 60 degrees per second
+    let mut cam_yaw: GLfloat = 0.0;                  // y-rotation in degrees
+    let mut cam_pitch: GLfloat = -10.0;              // x-rotation in degrees
+    let start_z = 0.4 * heightmap.depth();
+    let start_pos = math::vec3((0.0, heightmap.height_at(0.0, start_z) + 4.0, start_z));
+    let mut camera = Camera::new(math::dvec3(start_pos), look_orientation(cam_yaw, cam_pitch), 67.0);
+
+    while !context.window.should_close() {
+        let frame_start = Instant::now();
+        let current_seconds = context.glfw.get_time();
+        let elapsed_seconds = current_seconds - context.elapsed_time_seconds;
+        context.elapsed_time_seconds = current_seconds;
+
+        update_fps_counter(&mut context);
+
+        let aspect = context.width as f32 / context.height as f32;
+        let view_mat = camera.view_mat();
+        let proj_mat = depth_mode.proj_mat(&camera, aspect, near, far);
+
+        let float_depth_target = match depth_target {
+            Some(ref mut target) if depth_mode.needs_float_depth() => {
+                match target.bind(context.width, context.height) {
+                    Ok(_) => Some(target),
+                    Err(e) => {
+                        logger.log_err(&e);
+                        None
+                    }
+                }
+            }
+            _ => None,
+        };
+
+        unsafe {
+            // Wipe the drawing surface clear.
+            gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
+            gl::Viewport(0, 0, context.width as i32, context.height as i32);
+
+            terrain_timer.begin();
+            texture_binder.bind();
+            gl::UseProgram(shader_programme);
+            gl::UniformMatrix4fv(view_mat_location, 1, gl::FALSE, view_mat.as_ptr());
+            gl::UniformMatrix4fv(proj_mat_location, 1, gl::FALSE, proj_mat.as_ptr());
+            gl::BindVertexArray(vao);
+            gl::DrawElements(gl::TRIANGLES, index_count as i32, gl::UNSIGNED_INT, ptr::null());
+            perf_stats.count_draw(index_count / 3);
+            terrain_timer.end();
+
+            billboard_timer.begin();
+            billboard_binder.bind();
+            gl::UseProgram(billboard_programme);
+            gl::UniformMatrix4fv(billboard_view_location, 1, gl::FALSE, view_mat.as_ptr());
+            gl::UniformMatrix4fv(billboard_proj_location, 1, gl::FALSE, proj_mat.as_ptr());
+            gl::BindVertexArray(quad_vao);
+            for tree in trees.iter() {
+                let model_mat = billboard_style.model_matrix(&view_mat, &tree.position, &tree.size);
+                gl::UniformMatrix4fv(billboard_model_location, 1, gl::FALSE, model_mat.as_ptr());
+                gl::DrawArrays(gl::TRIANGLES, 0, 6);
+                perf_stats.count_draw(2);
+            }
+            billboard_timer.end();
+        }
+
+        if let Some(target) = float_depth_target {
+            target.blit_to_window();
+        }
+
+        perf_stats.set_gpu_pass_ms("terrain", terrain_timer.elapsed_ms());
+        perf_stats.set_gpu_pass_ms("billboards", billboard_timer.elapsed_ms());
+        if show_perf_stats {
+            let hud_bottom = perf_stats.draw_hud(&mut overlay, 10.0, 10.0);
+            overlay.text(16.0, hud_bottom + 6.0, 2.0, [1.0, 1.0, 1.0, 1.0], &fog.describe());
+            let billboard_text = format!("{} trees  {} billboards", trees.len(), billboard_style.name());
+            overlay.text(16.0, hud_bottom + 26.0, 2.0, [1.0, 1.0, 1.0, 1.0], &billboard_text);
+            let depth_text = format!("depth: {}  near plane {}", depth_mode.name(), near);
+            overlay.text(16.0, hud_bottom + 46.0, 2.0, [1.0, 1.0, 1.0, 1.0], &depth_text);
+            overlay.draw(context.width, context.height);
+        }
+
+        context.glfw.poll_events();
+
+        let mut fog_changed = false;
+        for (_, event) in glfw::flush_messages(&context.events) {
+            match event {
+                glfw::WindowEvent::Key(Key::F, _, Action::Press, _) => {
+                    fog.mode = fog.mode.next();
+                    fog_changed = true;
+                }
+                glfw::WindowEvent::Key(Key::C, _, Action::Press, _) => {
+                    fog.next_colour();
+                    fog_changed = true;
+                }
+                glfw::WindowEvent::Key(Key::LeftBracket, _, Action::Press, _) => {
+                    fog.change_density(-FOG_DENSITY_STEP);
+                    fog_changed = true;
+                }
+                glfw::WindowEvent::Key(Key::RightBracket, _, Action::Press, _) => {
+                    fog.change_density(FOG_DENSITY_STEP);
+                    fog_changed = true;
+                }
+                glfw::WindowEvent::Key(Key::Minus, _, Action::Press, _) => {
+                    fog.change_start(-FOG_DISTANCE_STEP);
+                    fog_changed = true;
+                }
+                glfw::WindowEvent::Key(Key::Equal, _, Action::Press, _) => {
+                    fog.change_start(FOG_DISTANCE_STEP);
+                    fog_changed = true;
+                }
+                glfw::WindowEvent::Key(Key::Num9, _, Action::Press, _) => {
+                    fog.change_end(-FOG_DISTANCE_STEP);
+                    fog_changed = true;
+                }
+                glfw::WindowEvent::Key(Key::Num0, _, Action::Press, _) => {
+                    fog.change_end(FOG_DISTANCE_STEP);
+                    fog_changed = true;
+                }
+                glfw::WindowEvent::Key(Key::B, _, Action::Press, _) => {
+                    billboard_style = billboard_style.next();
+                    logger.log(&format!("billboards: {}", billboard_style.name()));
+                }
+                glfw::WindowEvent::Key(Key::F3, _, Action::Press, _) => {
+                    show_perf_stats = !show_perf_stats;
+                }
+                glfw::WindowEvent::Key(Key::P, _, Action::Press, _) => {
+                    depth_mode = depth_mode.next(reversed_z_supported);
+                    depth_mode.depth_state().apply();
+                    logger.log(&format!("depth mode: {}", depth_mode.name()));
+                }
+                glfw::WindowEvent::Key(Key::N, _, Action::Press, _) => {
+                    near = if near == NEAR_PLANE { CLOSE_NEAR_PLANE } else { NEAR_PLANE };
+                    logger.log(&format!("near plane: {}", near));
+                }
+                glfw::WindowEvent::Key(Key::F5, _, Action::Press, _) => {
+                    camera.save_to_camera_file();
+                }
+                glfw::WindowEvent::Key(Key::F9, _, Action::Press, _) => {
+                    if let Some(restored) = Camera::restore_from_camera_file() {
+                        // the heading and look are worked back out, so the keys carry on from
+                        // where the restored camera faces.
+                        cam_yaw = restored.yaw_deg();
+                        cam_pitch = restored.pitch_deg().clamp(-MAX_CAMERA_PITCH, MAX_CAMERA_PITCH);
+                        camera = restored;
+                    }
+                }
+                _ => {}
+            }
+        }
+        if fog_changed {
+            set_fog(&fogged_programmes, &fog);
+            logger.log(&fog.describe());
+        }
+
+        // control keys
+        let turn = cam_heading_speed * (elapsed_seconds as GLfloat);
+        match context.window.get_key(Key::Left) {
+            Action::Press | Action::Repeat => {
+                cam_yaw += turn;
+            }
+            _ => {}
+        }
+        match context.window.get_key(Key::Right) {
+            Action::Press | Action::Repeat => {
+                cam_yaw -= turn;
+            }
+            _ => {}
+        }
+        match context.window.get_key(Key::Up) {
+            Action::Press | Action::Repeat => {
+                cam_pitch = f32::min(cam_pitch + turn, MAX_CAMERA_PITCH);
+            }
+            _ => {}
+        }
+        match context.window.get_key(Key::Down) {
+            Action::Press | Action::Repeat => {
+                cam_pitch = f32::max(cam_pitch - turn, -MAX_CAMERA_PITCH);
+            }
+            _ => {}
+        }
+        camera.orientation = look_orientation(cam_yaw, cam_pitch);
+
+        // move along the direction the camera faces, including up and down
+        let yaw_rad = cam_yaw.to_radians();
+        let pitch_rad = cam_pitch.to_radians();
+        let forward = math::vec3((
+            -f32::sin(yaw_rad) * f32::cos(pitch_rad), f32::sin(pitch_rad), -f32::cos(yaw_rad) * f32::cos(pitch_rad)
+        ));
+        let right = math::vec3((f32::cos(yaw_rad), 0.0, -f32::sin(yaw_rad)));
+        // the camera position adds up in double precision, so small steps are not
+        // lost to rounding however far the camera flies
+        let forward = math::dvec3(forward);
+        let right = math::dvec3(right);
+        let step = cam_speed as f64 * elapsed_seconds;
+        match context.window.get_key(Key::W) {
+            Action::Press | Action::Repeat => {
+                camera.position += forward * step;
+            }
+            _ => {}
+        }
+        match context.window.get_key(Key::S) {
+            Action::Press | Action::Repeat => {
+                camera.position -= forward * step;
+            }
+            _ => {}
+        }
+        match context.window.get_key(Key::A) {
+            Action::Press | Action::Repeat => {
+                camera.position -= right * step;
+            }
+            _ => {}
+        }
+        match context.window.get_key(Key::D) {
+            Action::Press | Action::Repeat => {
+                camera.position += right * step;
+            }
+            _ => {}
+        }
+        match context.window.get_key(Key::Q) {
+            Action::Press | Action::Repeat => {
+                camera.position.v[1] += step;
+            }
+            _ => {}
+        }
+        match context.window.get_key(Key::E) {
+            Action::Press | Action::Repeat => {
+                camera.position.v[1] -= step;
+            }
+            _ => {}
+        }
+
+        // keep the camera from sinking into the hills
+        let position = math::vec3(camera.position);
+        let ground = heightmap.height_at(position.v[0], position.v[2]);
+        camera.position.v[1] = f64::max(camera.position.v[1], (ground + CAMERA_CLEARANCE) as f64);
+
+        match context.window.get_key(Key::Escape) {
+            Action::Press | Action::Repeat => {
+                context.window.set_should_close(true);
+            }
+            _ => {}
+        }
+        perf_stats.end_frame(perf_stats::duration_to_ms(frame_start.elapsed()));
+        // Put the stuff we've been drawing onto the display.
+        context.window.swap_buffers();
+    }
+}
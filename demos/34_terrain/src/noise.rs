@@ -0,0 +1,359 @@
+// The skew that takes the square grid to the grid of equilateral triangles 2D
+// simplex noise is built on, and the unskew that takes it back.
+const SKEW_2D: f32 = 0.36602540378;    // (sqrt(3) - 1) / 2
+const UNSKEW_2D: f32 = 0.21132486540;  // (3 - sqrt(3)) / 6
+const SKEW_3D: f32 = 1.0 / 3.0;
+const UNSKEW_3D: f32 = 1.0 / 6.0;
+
+// Scale the sums of the simplex corners so the noise comes out roughly -1 to 1.
+const SIMPLEX_2D_SCALE: f32 = 70.0;
+const SIMPLEX_3D_SCALE: f32 = 32.0;
+
+// Each octave of fractal noise is shifted by this much, so that the octaves do
+// not all pass through zero together at the origin.
+const OCTAVE_OFFSET: f32 = 19.19;
+
+// The gradients of 2D noise: the four axes and the four diagonals.
+const GRADIENTS_2D: [[f32; 2]; 8] = [
+    [1.0, 0.0], [-1.0, 0.0], [0.0, 1.0], [0.0, -1.0],
+    [1.0, 1.0], [-1.0, 1.0], [1.0, -1.0], [-1.0, -1.0],
+];
+
+// The gradients of 3D noise: the midpoints of the edges of a cube, as in Ken
+// Perlin's improved noise.
+const GRADIENTS_3D: [[f32; 3]; 12] = [
+    [1.0, 1.0, 0.0], [-1.0, 1.0, 0.0], [1.0, -1.0, 0.0], [-1.0, -1.0, 0.0],
+    [1.0, 0.0, 1.0], [-1.0, 0.0, 1.0], [1.0, 0.0, -1.0], [-1.0, 0.0, -1.0],
+    [0.0, 1.0, 1.0], [0.0, -1.0, 1.0], [0.0, 1.0, -1.0], [0.0, -1.0, -1.0],
+];
+
+
+///
+/// Scramble the coordinates of a lattice point into a number with no obvious
+/// relation to its neighbours'. This stands in for the permutation table of the
+/// classic noise functions, without the table's repeat every 256 units.
+///
+fn hash(x: i32, y: i32, z: i32) -> u32 {
+    let mut h = (x as u32).wrapping_mul(0x8da6_b343)
+        ^ (y as u32).wrapping_mul(0xd816_3841)
+        ^ (z as u32).wrapping_mul(0xcb1a_b31f);
+    h ^= h >> 15;
+    h = h.wrapping_mul(0x2c1b_3c6d);
+    h ^= h >> 12;
+    h = h.wrapping_mul(0x297a_2d39);
+    h ^= h >> 15;
+
+    h
+}
+
+fn gradient_2d(i: i32, j: i32, x: f32, y: f32) -> f32 {
+    let g = GRADIENTS_2D[(hash(i, j, 0) % 8) as usize];
+
+    g[0] * x + g[1] * y
+}
+
+fn gradient_3d(i: i32, j: i32, k: i32, x: f32, y: f32, z: f32) -> f32 {
+    let g = GRADIENTS_3D[(hash(i, j, k) % 12) as usize];
+
+    g[0] * x + g[1] * y + g[2] * z
+}
+
+///
+/// Perlin's quintic ease curve, which has no slope and no curvature at 0 and 1,
+/// so the noise stays smooth across the edges of the lattice cells.
+///
+fn fade(t: f32) -> f32 {
+    t * t * t * (t * (t * 6.0 - 15.0) + 10.0)
+}
+
+fn lerp(a: f32, b: f32, t: f32) -> f32 {
+    a + (b - a) * t
+}
+
+///
+/// 2D Perlin noise: a smooth random surface, roughly -1 to 1, with features
+/// about one unit across. It is zero at every whole-numbered point.
+///
+pub fn perlin2(x: f32, y: f32) -> f32 {
+    let (i, j) = (x.floor(), y.floor());
+    let (fx, fy) = (x - i, y - j);
+    let (i, j) = (i as i32, j as i32);
+    let (u, v) = (fade(fx), fade(fy));
+
+    let back = lerp(gradient_2d(i, j, fx, fy), gradient_2d(i + 1, j, fx - 1.0, fy), u);
+    let front = lerp(gradient_2d(i, j + 1, fx, fy - 1.0), gradient_2d(i + 1, j + 1, fx - 1.0, fy - 1.0), u);
+
+    lerp(back, front, v)
+}
+
+///
+/// 3D Perlin noise. Using time for one of the coordinates makes 2D noise that
+/// changes smoothly from one frame to the next.
+///
+pub fn perlin3(x: f32, y: f32, z: f32) -> f32 {
+    let (i, j, k) = (x.floor(), y.floor(), z.floor());
+    let (fx, fy, fz) = (x - i, y - j, z - k);
+    let (i, j, k) = (i as i32, j as i32, k as i32);
+    let (u, v, w) = (fade(fx), fade(fy), fade(fz));
+
+    let corner = |di: i32, dj: i32, dk: i32| {
+        gradient_3d(i + di, j + dj, k + dk, fx - di as f32, fy - dj as f32, fz - dk as f32)
+    };
+    let near = lerp(
+        lerp(corner(0, 0, 0), corner(1, 0, 0), u),
+        lerp(corner(0, 1, 0), corner(1, 1, 0), u),
+        v
+    );
+    let far = lerp(
+        lerp(corner(0, 0, 1), corner(1, 0, 1), u),
+        lerp(corner(0, 1, 1), corner(1, 1, 1), u),
+        v
+    );
+
+    lerp(near, far, w)
+}
+
+///
+/// 2D simplex noise, roughly -1 to 1. Like Perlin noise, but blended from the
+/// three corners of a triangle instead of the four of a square, which is cheaper
+/// and leaves no grid-aligned streaks.
+///
+pub fn simplex2(x: f32, y: f32) -> f32 {
+    // Find the triangle the point is in, and where it is from the first corner.
+    let s = (x + y) * SKEW_2D;
+    let (i, j) = ((x + s).floor(), (y + s).floor());
+    let t = (i + j) * UNSKEW_2D;
+    let (x0, y0) = (x - (i - t), y - (j - t));
+    let (i, j) = (i as i32, j as i32);
+
+    // The middle corner is one step along whichever axis the point is further along.
+    let (di, dj) = if x0 > y0 { (1, 0) } else { (0, 1) };
+    let corners = [
+        (0, 0, x0, y0),
+        (di, dj, x0 - di as f32 + UNSKEW_2D, y0 - dj as f32 + UNSKEW_2D),
+        (1, 1, x0 - 1.0 + 2.0 * UNSKEW_2D, y0 - 1.0 + 2.0 * UNSKEW_2D),
+    ];
+
+    let mut sum = 0.0;
+    for &(ci, cj, cx, cy) in corners.iter() {
+        let falloff = 0.5 - cx * cx - cy * cy;
+        if falloff > 0.0 {
+            let falloff = falloff * falloff;
+            sum += falloff * falloff * gradient_2d(i + ci, j + cj, cx, cy);
+        }
+    }
+
+    SIMPLEX_2D_SCALE * sum
+}
+
+///
+/// 3D simplex noise, roughly -1 to 1, blended from the four corners of a
+/// tetrahedron.
+///
+pub fn simplex3(x: f32, y: f32, z: f32) -> f32 {
+    let s = (x + y + z) * SKEW_3D;
+    let (i, j, k) = ((x + s).floor(), (y + s).floor(), (z + s).floor());
+    let t = (i + j + k) * UNSKEW_3D;
+    let (x0, y0, z0) = (x - (i - t), y - (j - t), z - (k - t));
+    let (i, j, k) = (i as i32, j as i32, k as i32);
+
+    // The tetrahedron runs from the cell's first corner to its last, stepping
+    // along the axes in order of how far along each the point is.
+    let (first, second) = if x0 >= y0 {
+        if y0 >= z0 {
+            ((1, 0, 0), (1, 1, 0))
+        } else if x0 >= z0 {
+            ((1, 0, 0), (1, 0, 1))
+        } else {
+            ((0, 0, 1), (1, 0, 1))
+        }
+    } else if y0 < z0 {
+        ((0, 0, 1), (0, 1, 1))
+    } else if x0 < z0 {
+        ((0, 1, 0), (0, 1, 1))
+    } else {
+        ((0, 1, 0), (1, 1, 0))
+    };
+
+    let mut sum = 0.0;
+    for (n, &(ci, cj, ck)) in [(0, 0, 0), first, second, (1, 1, 1)].iter().enumerate() {
+        let unskew = n as f32 * UNSKEW_3D;
+        let cx = x0 - ci as f32 + unskew;
+        let cy = y0 - cj as f32 + unskew;
+        let cz = z0 - ck as f32 + unskew;
+        let falloff = 0.6 - cx * cx - cy * cy - cz * cz;
+        if falloff > 0.0 {
+            let falloff = falloff * falloff;
+            sum += falloff * falloff * gradient_3d(i + ci, j + cj, k + ck, cx, cy, cz);
+        }
+    }
+
+    SIMPLEX_3D_SCALE * sum
+}
+
+///
+/// Fractal Brownian motion: several octaves of a noise function added together,
+/// each `lacunarity` times the frequency of the one before and `gain` times its
+/// amplitude. The first octave gives the broad shape, and the later ones roughen
+/// it with finer and finer detail. The sum is divided by the total amplitude, so
+/// it stays in the same range as the noise.
+///
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Fbm {
+    pub octaves: u32,
+    pub frequency: f32,
+    pub lacunarity: f32,
+    pub gain: f32,
+}
+
+impl Fbm {
+    ///
+    /// `octaves` octaves, the first with features about `1 / frequency` across, each
+    /// twice the frequency and half the amplitude of the one before.
+    ///
+    pub fn new(octaves: u32, frequency: f32) -> Fbm {
+        Fbm {
+            octaves: octaves,
+            frequency: frequency,
+            lacunarity: 2.0,
+            gain: 0.5,
+        }
+    }
+
+    ///
+    /// Add up the octaves of `noise` at the point `(x, y)`.
+    ///
+    pub fn sample2<F: Fn(f32, f32) -> f32>(&self, noise: F, x: f32, y: f32) -> f32 {
+        self.sum(|octave, frequency| {
+            let offset = octave as f32 * OCTAVE_OFFSET;
+            noise(x * frequency + offset, y * frequency + offset)
+        })
+    }
+
+    ///
+    /// Add up the octaves of `noise` at the point `(x, y, z)`.
+    ///
+    pub fn sample3<F: Fn(f32, f32, f32) -> f32>(&self, noise: F, x: f32, y: f32, z: f32) -> f32 {
+        self.sum(|octave, frequency| {
+            let offset = octave as f32 * OCTAVE_OFFSET;
+            noise(x * frequency + offset, y * frequency + offset, z * frequency + offset)
+        })
+    }
+
+    fn sum<F: Fn(u32, f32) -> f32>(&self, octave_value: F) -> f32 {
+        let mut sum = 0.0;
+        let mut total_amplitude = 0.0;
+        let mut frequency = self.frequency;
+        let mut amplitude = 1.0;
+        for octave in 0..self.octaves {
+            sum += amplitude * octave_value(octave, frequency);
+            total_amplitude += amplitude;
+            frequency *= self.lacunarity;
+            amplitude *= self.gain;
+        }
+
+        if total_amplitude > 0.0 { sum / total_amplitude } else { 0.0 }
+    }
+}
+
+mod noise_tests {
+    use super::{perlin2, perlin3, simplex2, simplex3, Fbm};
+
+    // Sample a noise function across a few cells, at points that do not line up
+    // with the lattice.
+    fn samples_2d<F: Fn(f32, f32) -> f32>(noise: F) -> Vec<f32> {
+        let mut samples = vec![];
+        for j in 0..60 {
+            for i in 0..60 {
+                samples.push(noise(i as f32 * 0.137 - 3.0, j as f32 * 0.173 - 4.0));
+            }
+        }
+
+        samples
+    }
+
+    fn samples_3d<F: Fn(f32, f32, f32) -> f32>(noise: F) -> Vec<f32> {
+        let mut samples = vec![];
+        for k in 0..16 {
+            for j in 0..16 {
+                for i in 0..16 {
+                    samples.push(noise(i as f32 * 0.311 - 2.0, j as f32 * 0.293 + 5.0, k as f32 * 0.357 - 7.0));
+                }
+            }
+        }
+
+        samples
+    }
+
+    fn assert_in_range_and_varied(samples: &[f32]) {
+        let min = samples.iter().cloned().fold(1.0, f32::min);
+        let max = samples.iter().cloned().fold(-1.0, f32::max);
+        assert!(min >= -1.1 && max <= 1.1, "noise ran from {} to {}", min, max);
+        // the noise should swing well to either side of zero, not sit on it
+        assert!(min < -0.3 && max > 0.3, "noise only ran from {} to {}", min, max);
+    }
+
+    #[test]
+    fn test_every_noise_function_stays_roughly_between_minus_one_and_one() {
+        assert_in_range_and_varied(&samples_2d(perlin2));
+        assert_in_range_and_varied(&samples_2d(simplex2));
+        assert_in_range_and_varied(&samples_3d(perlin3));
+        assert_in_range_and_varied(&samples_3d(simplex3));
+    }
+
+    #[test]
+    fn test_perlin_noise_is_zero_on_the_lattice() {
+        for &(x, y) in [(0.0, 0.0), (3.0, -2.0), (-17.0, 40.0)].iter() {
+            assert_eq!(perlin2(x, y), 0.0);
+            assert_eq!(perlin3(x, y, 5.0), 0.0);
+        }
+    }
+
+    #[test]
+    fn test_the_noise_changes_smoothly() {
+        let step = 1e-3;
+        for &(x, y, z) in [(0.3, 0.7, 0.1), (2.99, -1.01, 4.5), (-5.5, 8.25, -0.75)].iter() {
+            assert!(f32::abs(perlin2(x + step, y) - perlin2(x, y)) < 0.01);
+            assert!(f32::abs(simplex2(x, y + step) - simplex2(x, y)) < 0.01);
+            assert!(f32::abs(perlin3(x, y, z + step) - perlin3(x, y, z)) < 0.01);
+            assert!(f32::abs(simplex3(x + step, y, z) - simplex3(x, y, z)) < 0.01);
+        }
+    }
+
+    #[test]
+    fn test_the_same_point_always_gives_the_same_noise() {
+        assert_eq!(simplex2(1.5, -2.25), simplex2(1.5, -2.25));
+        assert_eq!(perlin3(0.5, 0.5, 0.5), perlin3(0.5, 0.5, 0.5));
+        assert!(perlin2(0.5, 0.5) != perlin2(1.5, 0.5));
+    }
+
+    #[test]
+    fn test_one_octave_of_fbm_is_the_noise_at_its_frequency() {
+        let fbm = Fbm::new(1, 0.25);
+
+        assert_eq!(fbm.sample2(simplex2, 2.0, 6.0), simplex2(0.5, 1.5));
+        assert_eq!(fbm.sample3(perlin3, 2.0, 6.0, 1.0), perlin3(0.5, 1.5, 0.25));
+    }
+
+    #[test]
+    fn test_fbm_stays_in_the_range_of_its_noise() {
+        let fbm = Fbm::new(6, 0.5);
+
+        assert_in_range_and_varied(&samples_2d(|x, y| fbm.sample2(perlin2, x, y)));
+        assert_in_range_and_varied(&samples_3d(|x, y, z| fbm.sample3(simplex3, x, y, z)));
+        assert_eq!(Fbm::new(0, 1.0).sample2(perlin2, 0.3, 0.4), 0.0);
+    }
+
+    #[test]
+    fn test_more_octaves_add_finer_detail() {
+        // Finer detail shows up as bigger differences between close neighbours.
+        let roughness = |fbm: Fbm| {
+            (0..200).map(|i| {
+                let x = i as f32 * 0.05;
+                f32::abs(fbm.sample2(simplex2, x + 0.05, 0.5) - fbm.sample2(simplex2, x, 0.5))
+            }).sum::<f32>()
+        };
+
+        assert!(roughness(Fbm::new(5, 0.5)) > roughness(Fbm::new(1, 0.5)));
+    }
+}
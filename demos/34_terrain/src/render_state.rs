@@ -0,0 +1,101 @@
+use gl;
+use gl::types::{GLclampd, GLenum};
+
+
+///
+/// The range of clip space depth that maps onto the depth buffer. OpenGL's own
+/// is -1 to 1, which throws away half the precision of a floating point depth
+/// buffer around 0. `glClipControl` can change it to 0 to 1, as Direct3D and
+/// Vulkan have it.
+///
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ClipDepthRange {
+    NegativeOneToOne,
+    ZeroToOne,
+}
+
+impl ClipDepthRange {
+    fn gl_enum(&self) -> GLenum {
+        match *self {
+            ClipDepthRange::NegativeOneToOne => gl::NEGATIVE_ONE_TO_ONE,
+            ClipDepthRange::ZeroToOne => gl::ZERO_TO_ONE,
+        }
+    }
+}
+
+///
+/// Everything about depth testing that changes from one projection to another:
+/// the clip space depth range, the comparison that decides which fragment is in
+/// front, and the value the depth buffer is cleared to.
+///
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct DepthState {
+    pub range: ClipDepthRange,
+    pub func: GLenum,
+    pub clear_depth: GLclampd,
+}
+
+impl DepthState {
+    ///
+    /// OpenGL's default: depth from -1 to 1, cleared to the far plane at 1, and
+    /// smaller values closer to the eye.
+    ///
+    pub fn standard() -> DepthState {
+        DepthState {
+            range: ClipDepthRange::NegativeOneToOne,
+            func: gl::LESS,
+            clear_depth: 1.0,
+        }
+    }
+
+    ///
+    /// Reversed-Z: depth from 0 to 1 with the near plane at 1, so the depth buffer
+    /// is cleared to 0 and the fragment with the greater depth wins.
+    ///
+    pub fn reversed_z() -> DepthState {
+        DepthState {
+            range: ClipDepthRange::ZeroToOne,
+            func: gl::GREATER,
+            clear_depth: 0.0,
+        }
+    }
+
+    ///
+    /// Turn on depth testing and set the clip control, depth function, and depth
+    /// clear value to these. The clip control is only touched when the context
+    /// has it; without it the range stays at -1 to 1.
+    ///
+    pub fn apply(&self) {
+        unsafe {
+            gl::Enable(gl::DEPTH_TEST);
+            if clip_control_supported() {
+                gl_check!(gl::ClipControl(gl::LOWER_LEFT, self.range.gl_enum()));
+            }
+            gl::DepthFunc(self.func);
+            gl::ClearDepth(self.clear_depth);
+        }
+    }
+}
+
+///
+/// Whether the context can change the clip space depth range. `glClipControl` is
+/// core in OpenGL 4.5, and also comes with `GL_ARB_clip_control`.
+///
+pub fn clip_control_supported() -> bool {
+    gl::ClipControl::is_loaded()
+}
+
+mod render_state_tests {
+    use super::{ClipDepthRange, DepthState};
+    use gl;
+
+    #[test]
+    fn test_reversed_z_clears_to_zero_and_keeps_the_greater_depth() {
+        let state = DepthState::reversed_z();
+
+        assert_eq!(state.range, ClipDepthRange::ZeroToOne);
+        assert_eq!(state.func, gl::GREATER);
+        assert_eq!(state.clear_depth, 0.0);
+        assert_eq!(DepthState::standard().range, ClipDepthRange::NegativeOneToOne);
+    }
+}
@@ -0,0 +1,262 @@
+use stb_image::image::LoadResult;
+
+use graphics_math as math;
+use math::Vec3;
+
+use assets;
+use geometry;
+use geometry::GridMesh;
+use noise;
+use noise::Fbm;
+
+
+///
+/// A grid of terrain heights in world units, stored row by row with the rows
+/// running along +z and the columns along +x, `spacing` apart. The terrain is
+/// centred on the origin in x and z, the same way `geometry::heightfield_grid`
+/// lays out its mesh.
+///
+#[derive(Clone, Debug, PartialEq)]
+pub struct Heightmap {
+    pub columns: usize,
+    pub rows: usize,
+    pub spacing: f32,
+    pub heights: Vec<f32>,
+}
+
+impl Heightmap {
+    ///
+    /// Make a heightmap from one byte per sample, where 0 is at height zero and
+    /// 255 is at `max_height`.
+    ///
+    pub fn from_pixels(
+        pixels: &[u8], columns: usize, rows: usize,
+        spacing: f32, max_height: f32) -> Result<Heightmap, String> {
+
+        if columns < 2 || rows < 2 {
+            return Err(format!("ERROR: a heightmap needs at least 2x2 samples, got {}x{}", columns, rows));
+        }
+        if pixels.len() != columns * rows {
+            return Err(format!(
+                "ERROR: a {}x{} heightmap needs {} samples, got {}", columns, rows, columns * rows, pixels.len()
+            ));
+        }
+
+        let heights = pixels.iter().map(|&p| p as f32 / 255.0 * max_height).collect();
+
+        Ok(Heightmap {
+            columns: columns,
+            rows: rows,
+            spacing: spacing,
+            heights: heights,
+        })
+    }
+
+    ///
+    /// Make a heightmap from fractal simplex noise, so the terrain needs no image.
+    /// The noise is sampled in world units, `fbm.frequency` setting the size of the
+    /// hills, and mapped from -1 to 1 onto heights from zero to `max_height`. The
+    /// same settings always give the same terrain.
+    ///
+    pub fn from_noise(
+        fbm: &Fbm, columns: usize, rows: usize,
+        spacing: f32, max_height: f32) -> Result<Heightmap, String> {
+
+        if columns < 2 || rows < 2 {
+            return Err(format!("ERROR: a heightmap needs at least 2x2 samples, got {}x{}", columns, rows));
+        }
+
+        let x_offset = 0.5 * (columns - 1) as f32 * spacing;
+        let z_offset = 0.5 * (rows - 1) as f32 * spacing;
+        let mut heights = Vec::with_capacity(columns * rows);
+        for j in 0..rows {
+            for i in 0..columns {
+                let x = i as f32 * spacing - x_offset;
+                let z = j as f32 * spacing - z_offset;
+                let value = fbm.sample2(noise::simplex2, x, z);
+                heights.push(f32::min(f32::max(0.5 * value + 0.5, 0.0), 1.0) * max_height);
+            }
+        }
+
+        Ok(Heightmap {
+            columns: columns,
+            rows: rows,
+            spacing: spacing,
+            heights: heights,
+        })
+    }
+
+    ///
+    /// Load a grayscale image as a heightmap. The top row of the image is the far
+    /// edge of the terrain, at -z. A colour image is reduced to its brightness.
+    ///
+    pub fn load(file_name: &str, spacing: f32, max_height: f32) -> Result<Heightmap, String> {
+        let force_channels = 1;
+        let image_data = match assets::load_image_with_depth(file_name, force_channels) {
+            LoadResult::ImageU8(image_data) => image_data,
+            LoadResult::Error(e) => {
+                return Err(format!("ERROR: could not load heightmap {}: {}", file_name, e));
+            }
+            LoadResult::ImageF32(_) => {
+                return Err(format!("ERROR: Tried to load an image as byte vectors, got f32: {}", file_name));
+            }
+        };
+
+        Heightmap::from_pixels(&image_data.data, image_data.width, image_data.height, spacing, max_height)
+    }
+
+    ///
+    /// The size of the terrain along x.
+    ///
+    pub fn width(&self) -> f32 {
+        (self.columns - 1) as f32 * self.spacing
+    }
+
+    ///
+    /// The size of the terrain along z.
+    ///
+    pub fn depth(&self) -> f32 {
+        (self.rows - 1) as f32 * self.spacing
+    }
+
+    fn sample(&self, i: usize, j: usize) -> f32 {
+        self.heights[j * self.columns + i]
+    }
+
+    ///
+    /// The height of the terrain surface at `x` and `z` in world space, blending
+    /// between the four nearest samples. Points off the terrain get the height at
+    /// the nearest point on its edge.
+    ///
+    pub fn height_at(&self, x: f32, z: f32) -> f32 {
+        let max_u = (self.columns - 1) as f32;
+        let max_v = (self.rows - 1) as f32;
+        let u = f32::min(f32::max((x + 0.5 * self.width()) / self.spacing, 0.0), max_u);
+        let v = f32::min(f32::max((z + 0.5 * self.depth()) / self.spacing, 0.0), max_v);
+
+        let i = usize::min(u as usize, self.columns - 2);
+        let j = usize::min(v as usize, self.rows - 2);
+        let s = u - i as f32;
+        let t = v - j as f32;
+        let back = self.sample(i, j) * (1.0 - s) + self.sample(i + 1, j) * s;
+        let front = self.sample(i, j + 1) * (1.0 - s) + self.sample(i + 1, j + 1) * s;
+
+        back * (1.0 - t) + front * t
+    }
+
+    ///
+    /// The direction the terrain surface faces at `x` and `z` in world space,
+    /// worked out from the slope of the heights one sample spacing either side.
+    ///
+    pub fn normal_at(&self, x: f32, z: f32) -> Vec3 {
+        let d = self.spacing;
+        let dh_dx = (self.height_at(x + d, z) - self.height_at(x - d, z)) / (2.0 * d);
+        let dh_dz = (self.height_at(x, z + d) - self.height_at(x, z - d)) / (2.0 * d);
+
+        math::vec3((-dh_dx, 1.0, -dh_dz)).normalize()
+    }
+
+    ///
+    /// Build the triangle mesh for the terrain.
+    ///
+    pub fn mesh(&self) -> GridMesh {
+        geometry::heightfield_grid(&self.heights, self.columns, self.rows, self.spacing)
+    }
+}
+
+mod terrain_tests {
+    use super::Heightmap;
+    use graphics_math as math;
+    use noise::Fbm;
+
+    const EPSILON: f32 = 1e-5;
+
+    fn ramp() -> Heightmap {
+        // Three columns and two rows, rising along x and z.
+        Heightmap::from_pixels(&[0, 51, 102, 51, 102, 153], 3, 2, 2.0, 10.0).unwrap()
+    }
+
+    #[test]
+    fn test_pixels_scale_to_the_maximum_height() {
+        let heightmap = Heightmap::from_pixels(&[0, 255, 51, 102], 2, 2, 1.0, 5.0).unwrap();
+
+        assert_eq!(heightmap.heights, vec![0.0, 5.0, 1.0, 2.0]);
+    }
+
+    #[test]
+    fn test_pixel_count_must_match_the_size() {
+        assert!(Heightmap::from_pixels(&[0, 1, 2], 2, 2, 1.0, 1.0).is_err());
+        assert!(Heightmap::from_pixels(&[0, 1], 2, 1, 1.0, 1.0).is_err());
+    }
+
+    #[test]
+    fn test_noise_heights_stay_between_zero_and_the_maximum_height() {
+        let heightmap = Heightmap::from_noise(&Fbm::new(5, 0.05), 33, 17, 0.5, 14.0).unwrap();
+
+        assert_eq!(heightmap.heights.len(), 33 * 17);
+        assert!(heightmap.heights.iter().all(|&h| h >= 0.0 && h <= 14.0));
+        // the terrain should be hilly, not flat
+        let lowest = heightmap.heights.iter().cloned().fold(14.0, f32::min);
+        let highest = heightmap.heights.iter().cloned().fold(0.0, f32::max);
+        assert!(highest - lowest > 1.0, "heights only ran from {} to {}", lowest, highest);
+    }
+
+    #[test]
+    fn test_the_same_noise_gives_the_same_terrain() {
+        let fbm = Fbm::new(4, 0.1);
+
+        assert_eq!(Heightmap::from_noise(&fbm, 9, 9, 1.0, 5.0), Heightmap::from_noise(&fbm, 9, 9, 1.0, 5.0));
+        assert!(Heightmap::from_noise(&fbm, 1, 9, 1.0, 5.0).is_err());
+    }
+
+    #[test]
+    fn test_height_at_matches_the_samples() {
+        let heightmap = ramp();
+
+        assert_eq!(heightmap.width(), 4.0);
+        assert_eq!(heightmap.depth(), 2.0);
+        assert!((heightmap.height_at(-2.0, -1.0) - 0.0).abs() < EPSILON);
+        assert!((heightmap.height_at(2.0, -1.0) - 4.0).abs() < EPSILON);
+        assert!((heightmap.height_at(2.0, 1.0) - 6.0).abs() < EPSILON);
+    }
+
+    #[test]
+    fn test_height_at_blends_between_samples() {
+        let heightmap = ramp();
+
+        assert!((heightmap.height_at(-1.0, 0.0) - 2.0).abs() < EPSILON);
+        assert!((heightmap.height_at(1.0, -1.0) - 3.0).abs() < EPSILON);
+    }
+
+    #[test]
+    fn test_height_at_clamps_to_the_edge() {
+        let heightmap = ramp();
+
+        assert!((heightmap.height_at(-50.0, -50.0) - 0.0).abs() < EPSILON);
+        assert!((heightmap.height_at(50.0, 50.0) - 6.0).abs() < EPSILON);
+    }
+
+    #[test]
+    fn test_normal_at_leans_away_from_the_slope() {
+        let flat = Heightmap::from_pixels(&[10; 9], 3, 3, 1.0, 5.0).unwrap();
+        assert_eq!(flat.normal_at(0.0, 0.0), math::vec3((0.0, 1.0, 0.0)));
+
+        // Rising one unit per unit along x, and not at all along z.
+        let slope = Heightmap::from_pixels(&[0, 51, 102, 0, 51, 102], 3, 2, 1.0, 5.0).unwrap();
+        let normal = slope.normal_at(0.0, 0.0);
+        let expected = f32::sqrt(0.5);
+        assert!((normal.v[0] + expected).abs() < EPSILON);
+        assert!((normal.v[1] - expected).abs() < EPSILON);
+        assert!(normal.v[2].abs() < EPSILON);
+    }
+
+    #[test]
+    fn test_mesh_matches_the_heightmap() {
+        let heightmap = ramp();
+        let mesh = heightmap.mesh();
+
+        assert_eq!(mesh.positions.len(), 3 * 6);
+        assert_eq!(&mesh.positions[0..3], &[-2.0, 0.0, -1.0]);
+        assert!((mesh.positions[16] - 6.0).abs() < EPSILON);
+    }
+}
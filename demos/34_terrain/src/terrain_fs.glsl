@@ -0,0 +1,42 @@
+#version 410
+
+in vec3 pos_eye;
+in vec3 normal_wor;
+in float height;
+in vec2 st;
+out vec4 frag_colour;
+
+// the three ground textures blended together across the terrain
+uniform sampler2D grass_map;
+uniform sampler2D rock_map;
+uniform sampler2D snow_map;
+// height of the highest possible point, so the snow line scales with the terrain
+uniform float max_height;
+
+#include "fog.glsl"
+
+// how many times the ground textures repeat across the terrain
+const float tiling = 24.0;
+// the direction the sunlight comes from, in world space
+const vec3 sun_dir = vec3 (0.48, 0.8, 0.36);
+
+void main() {
+	vec3 n = normalize (normal_wor);
+	vec2 tiled_st = st * tiling;
+	vec3 grass = texture (grass_map, tiled_st).rgb;
+	vec3 rock = texture (rock_map, tiled_st).rgb;
+	vec3 snow = texture (snow_map, tiled_st).rgb;
+
+	// snow settles above the snow line, but slides off steep slopes, which show
+	// bare rock. everything else is grass.
+	float slope = 1.0 - n.y;
+	float rock_weight = smoothstep (0.25, 0.45, slope);
+	float snow_weight = smoothstep (0.6, 0.7, height / max_height) * (1.0 - smoothstep (0.35, 0.55, slope));
+	vec3 albedo = mix (mix (grass, rock, rock_weight), snow, snow_weight);
+
+	float diffuse = max (dot (n, normalize (sun_dir)), 0.0);
+	frag_colour = vec4 (albedo * (0.3 + 0.7 * diffuse), 1.0);
+
+	// blend the fog colour with the lighting colour, based on distance from the camera
+	frag_colour.rgb = apply_fog (frag_colour.rgb, pos_eye);
+}
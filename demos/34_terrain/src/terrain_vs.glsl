@@ -0,0 +1,20 @@
+#version 410
+
+layout(location = 0) in vec3 vertex_position;
+layout(location = 1) in vec3 vertex_normal;
+layout(location = 2) in vec2 texture_coord;
+
+uniform mat4 view, proj;
+
+out vec3 pos_eye;
+out vec3 normal_wor;
+out float height;
+out vec2 st;
+
+void main() {
+	pos_eye = (view * vec4 (vertex_position, 1.0)).xyz;
+	normal_wor = vertex_normal;
+	height = vertex_position.y;
+	st = texture_coord;
+	gl_Position = proj * vec4 (pos_eye, 1.0);
+}
@@ -0,0 +1,17 @@
+#version 410
+
+in vec2 st;
+
+// each eye's view of the scene, and the colours that eye's filter lets through.
+uniform sampler2D left_eye;
+uniform sampler2D right_eye;
+uniform vec3 left_mask;
+uniform vec3 right_mask;
+
+out vec4 frag_colour;
+
+void main() {
+	vec3 left = texture (left_eye, st).rgb;
+	vec3 right = texture (right_eye, st).rgb;
+	frag_colour = vec4 (left * left_mask + right * right_mask, 1.0);
+}
@@ -0,0 +1,608 @@
+extern crate gl;
+extern crate glfw;
+extern crate chrono;
+extern crate stb_image;
+#[cfg(feature = "glam")]
+extern crate glam;
+#[cfg(feature = "cgmath")]
+extern crate cgmath;
+
+#[macro_use]
+mod gl_utils;
+mod graphics_math;
+mod obj_parser;
+mod colour;
+mod assets;
+mod shader_include;
+mod spirv;
+mod program_cache;
+mod logger;
+mod overlay;
+mod streaming_buffer;
+mod camera;
+mod camera_controller;
+mod split_view;
+mod rear_view;
+mod stereo;
+mod vertex_layout;
+mod fullscreen_pass;
+
+
+use glfw::{Action, Context, Key};
+use gl::types::{GLfloat, GLint, GLuint};
+
+use std::process;
+
+use gl_utils::*;
+
+use graphics_math as math;
+use math::Mat4;
+
+use camera::Camera;
+use camera_controller::{CameraController, ControlKeys};
+use overlay::Overlay;
+use split_view::{SplitLayout, Viewport};
+use stereo::{Eye, EyeFramebuffer, StereoSettings};
+use vertex_layout::VertexLayout;
+use fullscreen_pass::{FullscreenPass, FULLSCREEN_VS_FILE};
+
+
+const GL_LOG_FILE: &str = "gl.log";
+const VERTEX_SHADER_FILE: &str = "src/scene_vs.glsl";
+const FRAGMENT_SHADER_FILE: &str = "src/scene_fs.glsl";
+const ANAGLYPH_FS_FILE: &str = "src/anaglyph_fs.glsl";
+
+const GROUND_HALF_SIZE: f32 = 20.0;
+const EYE_HEIGHT: f32 = 1.5;
+const PILLAR_COUNT: usize = 12;
+const PILLAR_RING_RADIUS: f32 = 8.0;
+
+const GROUND_COLOUR: [f32; 3] = [0.35, 0.45, 0.3];
+const PILLAR_COLOUR: [f32; 3] = [0.6, 0.55, 0.5];
+// Each player's camera shows up in the other player's view in its own colour.
+const PLAYER_COLOURS: [[f32; 3]; 2] = [[0.9, 0.3, 0.2], [0.2, 0.5, 0.9]];
+const PLAYER_NAMES: [&str; 2] = ["player 1", "player 2"];
+
+// How much the eye separation and convergence distance change with each key press.
+const EYE_SEPARATION_STEP: f32 = 0.01;
+const CONVERGENCE_STEP: f32 = 0.5;
+const MIN_CONVERGENCE: f32 = 0.5;
+
+const REAR_VIEW_BORDER_COLOUR: [f32; 3] = [0.1, 0.1, 0.1];
+
+const OVERLAY_TEXT_COLOUR: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+const OVERLAY_BACKGROUND_COLOUR: [f32; 4] = [0.0, 0.0, 0.0, 0.6];
+
+
+///
+/// The ground plane: two triangles facing up, with each position followed by
+/// its normal.
+///
+fn ground_vertices() -> Vec<GLfloat> {
+    let a = GROUND_HALF_SIZE;
+    let corners = [(-a, a), (a, a), (a, -a), (a, -a), (-a, -a), (-a, a)];
+    let mut vertices = Vec::with_capacity(6 * corners.len());
+    for &(x, z) in corners.iter() {
+        vertices.extend_from_slice(&[x, 0.0, z, 0.0, 1.0, 0.0]);
+    }
+
+    vertices
+}
+
+///
+/// A unit cube centred on the origin, with each position followed by its normal.
+///
+fn cube_vertices() -> Vec<GLfloat> {
+    // The normal of each face, and two axes across it whose cross product is the
+    // normal, so the triangles wind counter-clockwise seen from outside.
+    let faces = [
+        ([1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]),
+        ([-1.0, 0.0, 0.0], [0.0, 0.0, 1.0], [0.0, 1.0, 0.0]),
+        ([0.0, 1.0, 0.0], [0.0, 0.0, 1.0], [1.0, 0.0, 0.0]),
+        ([0.0, -1.0, 0.0], [1.0, 0.0, 0.0], [0.0, 0.0, 1.0]),
+        ([0.0, 0.0, 1.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]),
+        ([0.0, 0.0, -1.0], [0.0, 1.0, 0.0], [1.0, 0.0, 0.0]),
+    ];
+    let corners = [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (1.0, 1.0), (-1.0, 1.0), (-1.0, -1.0)];
+
+    let mut vertices = Vec::with_capacity(6 * 6 * faces.len());
+    for &(n, u, v) in faces.iter() {
+        for &(s, t) in corners.iter() {
+            for i in 0..3 {
+                vertices.push(0.5 * (n[i] + s * u[i] + t * v[i]));
+            }
+            vertices.extend_from_slice(&n);
+        }
+    }
+
+    vertices
+}
+
+///
+/// Interleave separate position and normal arrays, as loaded from a mesh.
+///
+fn interleave(points: &[GLfloat], normals: &[GLfloat]) -> Vec<GLfloat> {
+    let mut vertices = Vec::with_capacity(points.len() + normals.len());
+    for i in 0..(points.len() / 3) {
+        vertices.extend_from_slice(&points[3 * i..3 * i + 3]);
+        vertices.extend_from_slice(&normals[3 * i..3 * i + 3]);
+    }
+
+    vertices
+}
+
+///
+/// Copy interleaved positions and normals into a vertex buffer, and make a vertex
+/// array reading positions from location 0 and normals from location 1.
+///
+fn make_vao(vertices: &[GLfloat]) -> GLuint {
+    let mut vao = 0;
+    unsafe {
+        gl::GenVertexArrays(1, &mut vao);
+    }
+    assert!(vao > 0);
+    VertexLayout::new().floats(0, 3).floats(1, 3).upload(vao, vertices, vertices.len() / 6);
+
+    vao
+}
+
+///
+/// The model matrices of a ring of pillars of different heights around the
+/// middle of the ground, so each player has something to walk around.
+///
+fn pillar_model_matrices() -> Vec<Mat4> {
+    (0..PILLAR_COUNT).map(|i| {
+        let angle = (i as f32 / PILLAR_COUNT as f32) * 2.0 * std::f32::consts::PI;
+        let height = 1.0 + (i % 3) as f32;
+        let position = math::vec3((
+            PILLAR_RING_RADIUS * f32::cos(angle), 0.5 * height, PILLAR_RING_RADIUS * f32::sin(angle)
+        ));
+        Mat4::from_scale(&math::vec3((0.8, height, 0.8))).then_translate(&position)
+    }).collect()
+}
+
+///
+/// The model matrix of the sphere standing in for a player's camera, so the
+/// other player can see where it is.
+///
+fn player_model_matrix(controller: &CameraController) -> Mat4 {
+    let position = math::vec3(controller.position());
+
+    Mat4::from_scale(&math::vec3((0.4, 0.4, 0.4))).then_translate(&position)
+}
+
+///
+/// The ground, the pillars, and the sphere each player is drawn as.
+///
+struct Scene {
+    ground_vao: GLuint,
+    ground_point_count: usize,
+    cube_vao: GLuint,
+    cube_point_count: usize,
+    sphere_vao: GLuint,
+    sphere_point_count: usize,
+    pillars: Vec<Mat4>,
+}
+
+impl Scene {
+    ///
+    /// Draw the scene as the player `viewer` sees it. Each player sees the other
+    /// players, but not their own camera.
+    ///
+    fn draw(&self, shader: &SceneShader, view_mat: &Mat4, proj_mat: &Mat4, players: &[CameraController], viewer: usize) {
+        shader.set_camera(view_mat, proj_mat);
+        shader.draw(self.ground_vao, self.ground_point_count, &Mat4::identity(), &GROUND_COLOUR);
+        for pillar in self.pillars.iter() {
+            shader.draw(self.cube_vao, self.cube_point_count, pillar, &PILLAR_COLOUR);
+        }
+        for (i, other) in players.iter().enumerate() {
+            if i != viewer {
+                shader.draw(self.sphere_vao, self.sphere_point_count, &player_model_matrix(other), &PLAYER_COLOURS[i]);
+            }
+        }
+    }
+}
+
+///
+/// The shader everything in the scene is drawn with: a flat colour lit by one
+/// light from above.
+///
+struct SceneShader {
+    sp: GLuint,
+    model_location: GLint,
+    view_location: GLint,
+    proj_location: GLint,
+    colour_location: GLint,
+}
+
+impl SceneShader {
+    fn new(sp: GLuint) -> SceneShader {
+        let location = |name: &str| {
+            let location = uniform_location(sp, name);
+            assert!(location > -1);
+            location
+        };
+
+        SceneShader {
+            sp: sp,
+            model_location: location("M"),
+            view_location: location("V"),
+            proj_location: location("P"),
+            colour_location: location("colour"),
+        }
+    }
+
+    fn set_camera(&self, view_mat: &Mat4, proj_mat: &Mat4) {
+        unsafe {
+            gl::UseProgram(self.sp);
+            gl::UniformMatrix4fv(self.view_location, 1, gl::FALSE, view_mat.as_ptr());
+            gl::UniformMatrix4fv(self.proj_location, 1, gl::FALSE, proj_mat.as_ptr());
+        }
+    }
+
+    fn draw(&self, vao: GLuint, point_count: usize, model_mat: &Mat4, colour: &[f32; 3]) {
+        unsafe {
+            gl::UniformMatrix4fv(self.model_location, 1, gl::FALSE, model_mat.as_ptr());
+            gl::Uniform3f(self.colour_location, colour[0], colour[1], colour[2]);
+            gl::BindVertexArray(vao);
+            gl::DrawArrays(gl::TRIANGLES, 0, point_count as i32);
+        }
+    }
+}
+
+///
+/// Combines the two eyes' views into one red-cyan anaglyph, drawn over the whole
+/// viewport. Each eye's view is filtered down to the colours its side of the
+/// glasses lets through.
+///
+struct AnaglyphShader {
+    sp: GLuint,
+    pass: FullscreenPass,
+}
+
+impl AnaglyphShader {
+    fn new(sp: GLuint) -> AnaglyphShader {
+        let location = |name: &str| {
+            let location = uniform_location(sp, name);
+            assert!(location > -1);
+            location
+        };
+        unsafe {
+            gl::UseProgram(sp);
+            gl::Uniform1i(location("left_eye"), 0);
+            gl::Uniform1i(location("right_eye"), 1);
+            let mask = stereo::LEFT_EYE_MASK;
+            gl::Uniform3f(location("left_mask"), mask[0], mask[1], mask[2]);
+            let mask = stereo::RIGHT_EYE_MASK;
+            gl::Uniform3f(location("right_mask"), mask[0], mask[1], mask[2]);
+        }
+
+        AnaglyphShader { sp: sp, pass: FullscreenPass::new() }
+    }
+
+    fn draw(&self, left_eye: GLuint, right_eye: GLuint) {
+        self.pass.draw(self.sp, &[left_eye, right_eye]);
+    }
+}
+
+///
+/// Draw the scene a second time, as the player `viewer` sees it looking back, into
+/// a framed panel in the corner of their `view`. The frame is cleared into the
+/// panel grown by the border, then the panel is cleared inside it, with the
+/// scissor keeping both clears to their rectangles. This leaves `view` applied
+/// and the scissor test on.
+///
+fn draw_rear_view(
+    scene: &Scene, shader: &SceneShader, view: &Viewport, players: &[CameraController], viewer: usize, near: f32, far: f32
+) {
+    let panel = rear_view::rear_view_panel(view);
+    if panel.is_empty() {
+        return;
+    }
+
+    unsafe {
+        gl::Enable(gl::SCISSOR_TEST);
+    }
+    panel.outset(rear_view::REAR_VIEW_BORDER).apply();
+    unsafe {
+        let colour = REAR_VIEW_BORDER_COLOUR;
+        gl::ClearColor(colour[0], colour[1], colour[2], 1.0);
+        gl::Clear(gl::COLOR_BUFFER_BIT);
+    }
+    panel.apply();
+    unsafe {
+        gl::ClearColor(0.6, 0.7, 0.8, 1.0);
+        gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
+    }
+
+    let camera = rear_view::rear_camera(&players[viewer].camera);
+    scene.draw(shader, &camera.view_mat(), &camera.proj_mat(panel.aspect(), near, far), players, viewer);
+    view.apply();
+}
+
+///
+/// Queue up a label in the top-left corner of the viewport.
+///
+fn label(overlay: &mut Overlay, text: &str) {
+    let scale = 2.0;
+    let longest = text.lines().map(|line| line.len()).max().unwrap_or(0);
+    let width = (longest as f32 * overlay::GLYPH_ADVANCE + 4.0) * scale;
+    let height = (text.lines().count() as f32 * overlay::LINE_HEIGHT + 4.0) * scale;
+    overlay.rect(10.0, 10.0, width, height, OVERLAY_BACKGROUND_COLOUR);
+    overlay.text(10.0 + 2.0 * scale, 10.0 + 2.0 * scale, scale, OVERLAY_TEXT_COLOUR, text);
+}
+
+///
+/// Keep the context's idea of the framebuffer size up to date. Nothing else needs
+/// to change, since the views are split up and each camera's projection matrix is
+/// worked out again from the size every frame, and the eye framebuffers follow the
+/// size the next time they are drawn into.
+///
+fn glfw_framebuffer_size_callback(context: &mut GLContext, width: u32, height: u32) {
+    context.width = width;
+    context.height = height;
+}
+
+fn main() {
+    let logger = restart_gl_log(GL_LOG_FILE);
+    // Start a GL context and O/S window using the GLFW helper library.
+    let mut context = match start_gl(&logger) {
+        Ok(val) => val,
+        Err(e) => {
+            eprintln!("Failed to Initialize OpenGL context. Got error:");
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+    };
+    // The framebuffer can be bigger than the window on high DPI screens.
+    context.window.set_framebuffer_size_polling(true);
+    let (width, height) = context.window.get_framebuffer_size();
+    glfw_framebuffer_size_callback(&mut context, width as u32, height as u32);
+
+    unsafe {
+        // Enable depth testing.
+        gl::Enable(gl::DEPTH_TEST);
+        // Depth testing interprets a smaller value as closer to the eye.
+        gl::DepthFunc(gl::LESS);
+        // Cull face.
+        gl::Enable(gl::CULL_FACE);
+        // Cull back face.
+        gl::CullFace(gl::BACK);
+        // GL_CW for clockwise.
+        gl::FrontFace(gl::CCW);
+    }
+
+    /*------------------------------CREATE GEOMETRY------------------------------*/
+    let ground = ground_vertices();
+    let cube = cube_vertices();
+    // the players use the embedded unit sphere
+    let sphere_mesh = assets::fallback_mesh();
+    let scene = Scene {
+        ground_vao: make_vao(&ground),
+        ground_point_count: ground.len() / 6,
+        cube_vao: make_vao(&cube),
+        cube_point_count: cube.len() / 6,
+        sphere_vao: make_vao(&interleave(&sphere_mesh.points, &sphere_mesh.normals)),
+        sphere_point_count: sphere_mesh.point_count,
+        pillars: pillar_model_matrices(),
+    };
+
+    /*-------------------------------CREATE SHADERS------------------------------*/
+    let shader_programme = create_programme_from_files(&logger, VERTEX_SHADER_FILE, FRAGMENT_SHADER_FILE);
+    let scene_shader = SceneShader::new(shader_programme);
+    let anaglyph_programme = create_programme_from_files(&logger, FULLSCREEN_VS_FILE, ANAGLYPH_FS_FILE);
+    let anaglyph_shader = AnaglyphShader::new(anaglyph_programme);
+
+    /*-----------------------------STEREO FRAMEBUFFERS---------------------------*/
+    // in stereo, player 1's view fills the window as an anaglyph. each eye is
+    // rendered into its own texture, and the two are combined at the end.
+    let mut eye_framebuffers = match (
+        EyeFramebuffer::new(context.width, context.height), EyeFramebuffer::new(context.width, context.height)
+    ) {
+        (Ok(left), Ok(right)) => Some([left, right]),
+        (Err(e), _) | (_, Err(e)) => {
+            logger.log_err(&e);
+            logger.log_err("WARNING: stereo is not available.");
+            None
+        }
+    };
+    let mut stereo = StereoSettings::new();
+    let mut show_stereo = false;
+
+    /*-------------------------------CREATE CAMERAS------------------------------*/
+    // the same controller twice, each driven by its own keys. the players start
+    // on opposite sides of the ring of pillars, facing each other.
+    let mut players = [
+        CameraController::new(math::vec3((0.0, EYE_HEIGHT, 14.0)), 0.0, 0.0, 67.0, ControlKeys {
+            forward: Key::W, back: Key::S, step_left: Key::Q, step_right: Key::E,
+            turn_left: Key::A, turn_right: Key::D, look_up: Key::R, look_down: Key::F,
+        }),
+        CameraController::new(math::vec3((0.0, EYE_HEIGHT, -14.0)), 180.0, 0.0, 67.0, ControlKeys {
+            forward: Key::I, back: Key::K, step_left: Key::U, step_right: Key::O,
+            turn_left: Key::J, turn_right: Key::L, look_up: Key::Y, look_down: Key::H,
+        }),
+    ];
+    let mut layout = SplitLayout::SideBySide;
+    let mut show_rear_view = false;
+    logger.log("keys: player 1 walks with W and S, steps with Q and E, turns with A and D, and looks with R and F;");
+    logger.log("      player 2 walks with I and K, steps with U and O, turns with J and L, and looks with Y and H;");
+    logger.log("      V switches between side by side and stacked views, and Alt+Enter toggles fullscreen;");
+    logger.log("      3 switches player 1 to red-cyan stereo, where [ and ] change the eye separation");
+    logger.log("      and - and = move the convergence distance; B shows each player's rear view in the corner;");
+    logger.log("      F5 saves player 1's camera to camera.txt, and F9 restores it");
+
+    // input variables
+    let near = 0.1;                                  // clipping plane
+    let far = 100.0;                                 // clipping plane
+
+    // the name of each view, and the stereo settings, drawn in its corner
+    let mut overlay = Overlay::new(&logger);
+
+    while !context.window.should_close() {
+        let current_seconds = context.glfw.get_time();
+        let elapsed_seconds = current_seconds - context.elapsed_time_seconds;
+        context.elapsed_time_seconds = current_seconds;
+
+        update_fps_counter(&mut context);
+
+        match eye_framebuffers {
+            Some(ref mut eye_framebuffers) if show_stereo => {
+                let size = (u32::max(context.width, 1), u32::max(context.height, 1));
+                let aspect = size.0 as f32 / size.1 as f32;
+                let camera = &players[0].camera;
+                for (framebuffer, &eye) in eye_framebuffers.iter_mut().zip([Eye::Left, Eye::Right].iter()) {
+                    if framebuffer.size() != size {
+                        if let Err(e) = framebuffer.resize(size.0, size.1) {
+                            logger.log_err(&e);
+                        }
+                    }
+                    framebuffer.bind();
+                    unsafe {
+                        gl::ClearColor(0.6, 0.7, 0.8, 1.0);
+                        gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
+                    }
+                    let view_mat = stereo.eye_view_mat(camera, eye);
+                    let proj_mat = stereo.eye_proj_mat(camera, eye, aspect, near, far);
+                    scene.draw(&scene_shader, &view_mat, &proj_mat, &players, 0);
+                    framebuffer.unbind();
+                }
+
+                unsafe {
+                    gl::Viewport(0, 0, context.width as i32, context.height as i32);
+                }
+                anaglyph_shader.draw(eye_framebuffers[0].texture(), eye_framebuffers[1].texture());
+                if show_rear_view {
+                    // the rear view is small enough that it is drawn in mono
+                    let window = Viewport::new(0, 0, context.width, context.height);
+                    draw_rear_view(&scene, &scene_shader, &window, &players, 0, near, far);
+                    unsafe {
+                        gl::Disable(gl::SCISSOR_TEST);
+                    }
+                }
+                label(&mut overlay, &format!("{} in stereo\n{}", PLAYER_NAMES[0], stereo.describe()));
+                overlay.draw(context.width, context.height);
+            }
+            _ => {
+                unsafe {
+                    // The divider shows through wherever neither view draws.
+                    gl::Viewport(0, 0, context.width as i32, context.height as i32);
+                    gl::ClearColor(0.1, 0.1, 0.1, 1.0);
+                    gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
+                    gl::Enable(gl::SCISSOR_TEST);
+                    gl::ClearColor(0.6, 0.7, 0.8, 1.0);
+                }
+
+                let views = split_view::split(context.width, context.height, layout);
+                for (i, view) in views.iter().enumerate() {
+                    // a minimised window has nothing to draw into
+                    if view.is_empty() {
+                        continue;
+                    }
+                    view.apply();
+                    unsafe {
+                        // With the scissor set, this only clears this view.
+                        gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
+                    }
+
+                    let camera = &players[i].camera;
+                    scene.draw(&scene_shader, &camera.view_mat(), &camera.proj_mat(view.aspect(), near, far), &players, i);
+                    if show_rear_view {
+                        draw_rear_view(&scene, &scene_shader, view, &players, i, near, far);
+                    }
+
+                    // the overlay measures from the top-left of the viewport, so each
+                    // label lands in the corner of its own view
+                    label(&mut overlay, PLAYER_NAMES[i]);
+                    overlay.draw(view.width, view.height);
+                }
+                unsafe {
+                    gl::Disable(gl::SCISSOR_TEST);
+                }
+            }
+        }
+
+        context.glfw.poll_events();
+
+        let mut toggle_fullscreen = false;
+        let mut resized_to = None;
+        for (_, event) in glfw::flush_messages(&context.events) {
+            match event {
+                glfw::WindowEvent::FramebufferSize(width, height) => {
+                    resized_to = Some((width, height));
+                }
+                glfw::WindowEvent::Key(Key::V, _, Action::Press, _) => {
+                    layout = layout.next();
+                    logger.log(&format!("views: {}", layout.name()));
+                }
+                glfw::WindowEvent::Key(Key::B, _, Action::Press, _) => {
+                    show_rear_view = !show_rear_view;
+                    logger.log(&format!("rear view: {}", show_rear_view));
+                }
+                glfw::WindowEvent::Key(Key::Num3, _, Action::Press, _) => {
+                    if eye_framebuffers.is_some() {
+                        show_stereo = !show_stereo;
+                        logger.log(&format!("stereo: {}", show_stereo));
+                    }
+                }
+                glfw::WindowEvent::Key(Key::LeftBracket, _, Action::Press, _) => {
+                    let eye_separation = stereo.eye_separation - EYE_SEPARATION_STEP;
+                    stereo.set_eye_separation(eye_separation);
+                    logger.log(&stereo.describe());
+                }
+                glfw::WindowEvent::Key(Key::RightBracket, _, Action::Press, _) => {
+                    let eye_separation = stereo.eye_separation + EYE_SEPARATION_STEP;
+                    stereo.set_eye_separation(eye_separation);
+                    logger.log(&stereo.describe());
+                }
+                glfw::WindowEvent::Key(Key::Minus, _, Action::Press, _) => {
+                    stereo.convergence = f32::max(stereo.convergence - CONVERGENCE_STEP, MIN_CONVERGENCE);
+                    logger.log(&stereo.describe());
+                }
+                glfw::WindowEvent::Key(Key::Equal, _, Action::Press, _) => {
+                    stereo.convergence += CONVERGENCE_STEP;
+                    logger.log(&stereo.describe());
+                }
+                glfw::WindowEvent::Key(Key::Enter, _, Action::Press, mods) => {
+                    if mods.contains(glfw::Modifiers::Alt) {
+                        toggle_fullscreen = true;
+                    }
+                }
+                glfw::WindowEvent::Key(Key::F5, _, Action::Press, _) => {
+                    players[0].camera.save_to_camera_file();
+                }
+                glfw::WindowEvent::Key(Key::F9, _, Action::Press, _) => {
+                    if let Some(camera) = Camera::restore_from_camera_file() {
+                        players[0].set_camera(&camera);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        if let Some((width, height)) = resized_to {
+            glfw_framebuffer_size_callback(&mut context, width as u32, height as u32);
+            logger.log(&format!("framebuffer resized to {}x{}", width, height));
+        }
+
+        if toggle_fullscreen {
+            let fullscreen = context.toggle_fullscreen();
+            logger.log(&format!("fullscreen: {}", fullscreen));
+            let (width, height) = context.window.get_framebuffer_size();
+            glfw_framebuffer_size_callback(&mut context, width as u32, height as u32);
+        }
+
+        // control keys
+        for player in players.iter_mut() {
+            player.update(&context.window, elapsed_seconds);
+        }
+
+        match context.window.get_key(Key::Escape) {
+            Action::Press | Action::Repeat => {
+                context.window.set_should_close(true);
+            }
+            _ => {}
+        }
+        // Put the stuff we've been drawing onto the display.
+        context.window.swap_buffers();
+    }
+}
@@ -0,0 +1,97 @@
+use camera::Camera;
+use graphics_math::Versor;
+use split_view::Viewport;
+
+
+///
+/// The size of the rear view panel, as a fraction of the width and height of the
+/// view it sits in. Taking the same fraction of both keeps the panel the same
+/// shape as the view, so the view's projection matrix would suit it too.
+///
+pub const REAR_VIEW_FRACTION: f32 = 0.3;
+///
+/// The gap between the rear view panel and the edges of its view, in pixels.
+///
+pub const REAR_VIEW_MARGIN: u32 = 10;
+///
+/// The width of the frame around the rear view panel, in pixels.
+///
+pub const REAR_VIEW_BORDER: u32 = 2;
+
+
+///
+/// The camera looking back the way `camera` came: in the same place, turned half
+/// way round about the vertical. Turning about the world's up, rather than the
+/// camera's, keeps the tilt the same, so a camera looking down at the ground in
+/// front of it sees the ground behind it too.
+///
+pub fn rear_camera(camera: &Camera) -> Camera {
+    let mut rear = *camera;
+    rear.orientation = Versor::from_axis_deg(180.0, 0.0, 1.0, 0.0) * camera.orientation;
+
+    rear
+}
+
+///
+/// The picture in picture panel the rear view is drawn into, in the top-right
+/// corner of `view`, like a rear view mirror. A view too small to hold the
+/// panel, its frame, and the margins gives an empty viewport.
+///
+pub fn rear_view_panel(view: &Viewport) -> Viewport {
+    let width = (view.width as f32 * REAR_VIEW_FRACTION) as u32;
+    let height = (view.height as f32 * REAR_VIEW_FRACTION) as u32;
+    let edge = REAR_VIEW_MARGIN + REAR_VIEW_BORDER;
+    if width == 0 || height == 0 || view.width < width + 2 * edge || view.height < height + 2 * edge {
+        return Viewport::new(0, 0, 0, 0);
+    }
+
+    // GL counts rows up from the bottom, so the top of the view is at y + height.
+    Viewport::new(
+        view.x + (view.width - width - edge) as i32,
+        view.y + (view.height - height - edge) as i32,
+        width,
+        height,
+    )
+}
+
+mod rear_view_tests {
+    use super::{rear_camera, rear_view_panel, REAR_VIEW_BORDER, REAR_VIEW_MARGIN};
+    use camera::Camera;
+    use camera_controller::look_orientation;
+    use graphics_math as math;
+    use split_view::Viewport;
+
+    const EPSILON: f32 = 1e-5;
+
+    #[test]
+    fn test_the_rear_camera_looks_the_other_way_at_the_same_tilt() {
+        let camera = Camera::new(math::dvec3((1.0, 1.5, 2.0)), look_orientation(30.0, -20.0), 67.0);
+        let rear = rear_camera(&camera);
+        let ahead = camera.orientation.rotate_vec3(&math::vec3((0.0, 0.0, -1.0)));
+        let behind = rear.orientation.rotate_vec3(&math::vec3((0.0, 0.0, -1.0)));
+
+        assert!(f32::abs(behind.v[0] + ahead.v[0]) < EPSILON);
+        assert!(f32::abs(behind.v[1] - ahead.v[1]) < EPSILON);
+        assert!(f32::abs(behind.v[2] + ahead.v[2]) < EPSILON);
+        assert_eq!(rear.position, camera.position);
+        assert_eq!(rear.fovy, camera.fovy);
+    }
+
+    #[test]
+    fn test_the_panel_sits_in_the_top_right_corner_of_its_view() {
+        let view = Viewport::new(644, 0, 640, 480);
+        let panel = rear_view_panel(&view);
+        let edge = (REAR_VIEW_MARGIN + REAR_VIEW_BORDER) as i32;
+
+        assert_eq!((panel.width, panel.height), (192, 144));
+        assert_eq!(panel.x + panel.width as i32 + edge, view.x + view.width as i32);
+        assert_eq!(panel.y + panel.height as i32 + edge, view.y + view.height as i32);
+        assert!(f32::abs(panel.aspect() - view.aspect()) < 0.01);
+    }
+
+    #[test]
+    fn test_a_view_too_small_for_the_panel_has_none() {
+        assert!(rear_view_panel(&Viewport::new(0, 0, 0, 0)).is_empty());
+        assert!(rear_view_panel(&Viewport::new(0, 0, 20, 480)).is_empty());
+    }
+}
@@ -0,0 +1,174 @@
+use gl;
+
+
+///
+/// The gap left between the views, in pixels, so it is clear where one ends and
+/// the next begins.
+///
+pub const DIVIDER_WIDTH: u32 = 4;
+
+
+///
+/// A rectangle of the framebuffer to draw one view into, in pixels from the
+/// bottom-left corner as GL measures them.
+///
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Viewport {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Viewport {
+    pub fn new(x: i32, y: i32, width: u32, height: u32) -> Viewport {
+        Viewport { x: x, y: y, width: width, height: height }
+    }
+
+    ///
+    /// The aspect ratio for the projection matrix of a camera drawing into this
+    /// viewport. Each view needs its own, since the views are narrower or shorter
+    /// than the window.
+    ///
+    pub fn aspect(&self) -> f32 {
+        if self.height == 0 {
+            return 1.0;
+        }
+
+        self.width as f32 / self.height as f32
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.width == 0 || self.height == 0
+    }
+
+    ///
+    /// This viewport grown by `pixels` on every side, as for a border around it.
+    ///
+    pub fn outset(&self, pixels: u32) -> Viewport {
+        Viewport::new(self.x - pixels as i32, self.y - pixels as i32, self.width + 2 * pixels, self.height + 2 * pixels)
+    }
+
+    ///
+    /// Make this viewport the part of the framebuffer GL draws into. The viewport
+    /// only maps the scene into the rectangle; it is the scissor that stops clears
+    /// and anything drawn over the edge from landing in the other views.
+    ///
+    pub fn apply(&self) {
+        unsafe {
+            gl::Viewport(self.x, self.y, self.width as i32, self.height as i32);
+            gl::Scissor(self.x, self.y, self.width as i32, self.height as i32);
+        }
+    }
+}
+
+///
+/// How the window is split between the two views.
+///
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SplitLayout {
+    /// The first view on the left and the second on the right.
+    SideBySide,
+    /// The first view on the top and the second underneath.
+    Stacked,
+}
+
+impl SplitLayout {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            SplitLayout::SideBySide => "side by side",
+            SplitLayout::Stacked => "stacked",
+        }
+    }
+
+    pub fn next(&self) -> SplitLayout {
+        match *self {
+            SplitLayout::SideBySide => SplitLayout::Stacked,
+            SplitLayout::Stacked => SplitLayout::SideBySide,
+        }
+    }
+}
+
+///
+/// Split a framebuffer between two views, leaving `DIVIDER_WIDTH` pixels between
+/// them. When the size does not divide evenly, the second view gets the spare
+/// pixel. A framebuffer too small to hold both views and the divider, like that
+/// of a minimised window, gives two empty viewports.
+///
+pub fn split(width: u32, height: u32, layout: SplitLayout) -> [Viewport; 2] {
+    match layout {
+        SplitLayout::SideBySide => {
+            if width <= DIVIDER_WIDTH || height == 0 {
+                return [Viewport::new(0, 0, 0, 0), Viewport::new(0, 0, 0, 0)];
+            }
+            let first = (width - DIVIDER_WIDTH) / 2;
+            let second = width - DIVIDER_WIDTH - first;
+            [
+                Viewport::new(0, 0, first, height),
+                Viewport::new((first + DIVIDER_WIDTH) as i32, 0, second, height),
+            ]
+        }
+        SplitLayout::Stacked => {
+            if height <= DIVIDER_WIDTH || width == 0 {
+                return [Viewport::new(0, 0, 0, 0), Viewport::new(0, 0, 0, 0)];
+            }
+            // GL counts rows up from the bottom, so the top view starts higher up.
+            let first = (height - DIVIDER_WIDTH) / 2;
+            let second = height - DIVIDER_WIDTH - first;
+            [
+                Viewport::new(0, (second + DIVIDER_WIDTH) as i32, width, first),
+                Viewport::new(0, 0, width, second),
+            ]
+        }
+    }
+}
+
+mod split_view_tests {
+    use super::{split, SplitLayout, Viewport, DIVIDER_WIDTH};
+
+    #[test]
+    fn test_side_by_side_views_fill_the_width_around_the_divider() {
+        let views = split(641, 480, SplitLayout::SideBySide);
+
+        assert_eq!(views[0], Viewport::new(0, 0, 318, 480));
+        assert_eq!(views[1], Viewport::new(318 + DIVIDER_WIDTH as i32, 0, 319, 480));
+        assert_eq!(views[1].x as u32 + views[1].width, 641);
+    }
+
+    #[test]
+    fn test_stacked_views_put_the_first_view_on_top() {
+        let views = split(640, 480, SplitLayout::Stacked);
+
+        assert_eq!(views[1], Viewport::new(0, 0, 640, 238));
+        assert_eq!(views[0], Viewport::new(0, 238 + DIVIDER_WIDTH as i32, 640, 238));
+        assert_eq!(views[0].y as u32 + views[0].height, 480);
+    }
+
+    #[test]
+    fn test_each_view_has_its_own_aspect_ratio() {
+        let side_by_side = split(1284, 480, SplitLayout::SideBySide);
+        let stacked = split(640, 964, SplitLayout::Stacked);
+
+        assert_eq!(side_by_side[0].aspect(), 640.0 / 480.0);
+        assert_eq!(stacked[0].aspect(), 640.0 / 480.0);
+    }
+
+    #[test]
+    fn test_an_outset_viewport_surrounds_the_original() {
+        let view = Viewport::new(100, 50, 200, 150);
+
+        assert_eq!(view.outset(2), Viewport::new(98, 48, 204, 154));
+        assert_eq!(view.outset(0), view);
+    }
+
+    #[test]
+    fn test_a_minimised_window_has_empty_views() {
+        for &layout in [SplitLayout::SideBySide, SplitLayout::Stacked].iter() {
+            let views = split(0, 0, layout);
+
+            assert!(views[0].is_empty() && views[1].is_empty());
+            assert_eq!(views[0].aspect(), 1.0);
+        }
+        assert!(split(DIVIDER_WIDTH, 480, SplitLayout::SideBySide)[0].is_empty());
+    }
+}
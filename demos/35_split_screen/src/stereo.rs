@@ -0,0 +1,294 @@
+use gl;
+use gl::types::{GLint, GLsizei, GLuint};
+
+use graphics_math as math;
+use math::Mat4;
+
+use camera::Camera;
+
+use std::ptr;
+
+
+///
+/// The colours each eye sees through red-cyan glasses. The left eye looks through
+/// the red filter, and the right eye through the cyan one.
+///
+pub const LEFT_EYE_MASK: [f32; 3] = [1.0, 0.0, 0.0];
+pub const RIGHT_EYE_MASK: [f32; 3] = [0.0, 1.0, 1.0];
+
+///
+/// The closest and furthest apart the eyes can be set, in world units.
+///
+pub const MIN_EYE_SEPARATION: f32 = 0.0;
+pub const MAX_EYE_SEPARATION: f32 = 1.0;
+
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Eye {
+    Left,
+    Right,
+}
+
+impl Eye {
+    ///
+    /// Which way along the camera's right vector the eye sits.
+    ///
+    fn side(&self) -> f32 {
+        match *self {
+            Eye::Left => -1.0,
+            Eye::Right => 1.0,
+        }
+    }
+}
+
+///
+/// How far apart the eyes are, and how far away the plane is where the two eyes'
+/// images line up. Anything on that plane looks like it is at the depth of the
+/// screen; anything nearer seems to stick out of it.
+///
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct StereoSettings {
+    pub eye_separation: f32,
+    pub convergence: f32,
+}
+
+impl StereoSettings {
+    pub fn new() -> StereoSettings {
+        StereoSettings {
+            eye_separation: 0.065,
+            convergence: 5.0,
+        }
+    }
+
+    pub fn set_eye_separation(&mut self, eye_separation: f32) {
+        self.eye_separation = f32::min(f32::max(eye_separation, MIN_EYE_SEPARATION), MAX_EYE_SEPARATION);
+    }
+
+    pub fn describe(&self) -> String {
+        format!("eye separation {:.3}  convergence {:.1}", self.eye_separation, self.convergence)
+    }
+
+    ///
+    /// The view matrix of one eye: the camera's own view, moved half the eye
+    /// separation to the side. The eyes look straight ahead, in parallel.
+    ///
+    pub fn eye_view_mat(&self, camera: &Camera, eye: Eye) -> Mat4 {
+        let offset = eye.side() * 0.5 * self.eye_separation;
+
+        Mat4::from_translation(&math::vec3((-offset, 0.0, 0.0))) * camera.view_mat()
+    }
+
+    ///
+    /// The projection matrix of one eye. Its frustum is skewed back towards the
+    /// middle, so both eyes see the same rectangle on the convergence plane. Turning
+    /// the eyes in towards each other instead would also make them converge, but
+    /// tilts their images against each other and strains the eyes.
+    ///
+    pub fn eye_proj_mat(&self, camera: &Camera, eye: Eye, aspect: f32, near: f32, far: f32) -> Mat4 {
+        let mut proj_mat = camera.proj_mat(aspect, near, far);
+        let half_width_at_convergence = self.convergence * f32::tan(0.5 * camera.fovy.to_radians()) * aspect;
+        // The third column scales with the depth, which is what slides the frustum
+        // sideways; a point at the convergence distance moves back by exactly the
+        // eye's offset.
+        proj_mat.m[8] = -eye.side() * 0.5 * self.eye_separation / half_width_at_convergence;
+
+        proj_mat
+    }
+}
+
+///
+/// A framebuffer that one eye's view is rendered into: a colour texture the size
+/// of the window, with a depth buffer.
+///
+pub struct EyeFramebuffer {
+    fbo: GLuint,
+    tex: GLuint,
+    depth_rb: GLuint,
+    width: u32,
+    height: u32,
+}
+
+impl EyeFramebuffer {
+    ///
+    /// Make a framebuffer to render into a texture `width` by `height` texels in
+    /// size. Returns an error if the driver cannot render to it.
+    ///
+    pub fn new(width: u32, height: u32) -> Result<EyeFramebuffer, String> {
+        let mut tex = 0;
+        let mut depth_rb = 0;
+        let mut fbo = 0;
+        unsafe {
+            gl::GenTextures(1, &mut tex);
+            gl::BindTexture(gl::TEXTURE_2D, tex);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::NEAREST as GLint);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::NEAREST as GLint);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as GLint);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as GLint);
+            gl::GenRenderbuffers(1, &mut depth_rb);
+            gl::GenFramebuffers(1, &mut fbo);
+        }
+
+        let mut framebuffer = EyeFramebuffer {
+            fbo: fbo,
+            tex: tex,
+            depth_rb: depth_rb,
+            width: 0,
+            height: 0,
+        };
+        match framebuffer.resize(width, height) {
+            Ok(_) => Ok(framebuffer),
+            Err(e) => Err(e),
+        }
+    }
+
+    ///
+    /// Make the texture and depth buffer a new size, after the window changes size.
+    /// What was drawn into them is lost.
+    ///
+    pub fn resize(&mut self, width: u32, height: u32) -> Result<(), String> {
+        // GL cannot make a texture with no texels, as a minimised window has.
+        let width = u32::max(width, 1);
+        let height = u32::max(height, 1);
+        unsafe {
+            gl::BindTexture(gl::TEXTURE_2D, self.tex);
+            gl::TexImage2D(
+                gl::TEXTURE_2D, 0, gl::RGBA as GLint, width as GLsizei, height as GLsizei, 0,
+                gl::RGBA, gl::UNSIGNED_BYTE, ptr::null()
+            );
+            gl::BindRenderbuffer(gl::RENDERBUFFER, self.depth_rb);
+            gl::RenderbufferStorage(gl::RENDERBUFFER, gl::DEPTH_COMPONENT24, width as GLsizei, height as GLsizei);
+
+            gl::BindFramebuffer(gl::FRAMEBUFFER, self.fbo);
+            gl::FramebufferTexture2D(gl::FRAMEBUFFER, gl::COLOR_ATTACHMENT0, gl::TEXTURE_2D, self.tex, 0);
+            gl::FramebufferRenderbuffer(gl::FRAMEBUFFER, gl::DEPTH_ATTACHMENT, gl::RENDERBUFFER, self.depth_rb);
+        }
+        let status = unsafe { gl::CheckFramebufferStatus(gl::FRAMEBUFFER) };
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+        }
+        self.width = width;
+        self.height = height;
+
+        if status != gl::FRAMEBUFFER_COMPLETE {
+            return Err(format!("ERROR: eye framebuffer is incomplete. Status: 0x{:x}", status));
+        }
+
+        Ok(())
+    }
+
+    ///
+    /// The texture the eye's view is rendered into.
+    ///
+    pub fn texture(&self) -> GLuint {
+        self.tex
+    }
+
+    pub fn size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    ///
+    /// Bind the framebuffer and set the viewport to cover it. Draw calls after this
+    /// render into the eye's texture.
+    ///
+    pub fn bind(&self) {
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, self.fbo);
+            gl::Viewport(0, 0, self.width as GLsizei, self.height as GLsizei);
+        }
+    }
+
+    ///
+    /// Go back to drawing to the window. The caller has to restore the viewport.
+    ///
+    pub fn unbind(&self) {
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+        }
+    }
+}
+
+impl Drop for EyeFramebuffer {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteFramebuffers(1, &self.fbo);
+            gl::DeleteRenderbuffers(1, &self.depth_rb);
+            gl::DeleteTextures(1, &self.tex);
+        }
+    }
+}
+
+mod stereo_tests {
+    use super::{Eye, StereoSettings, MAX_EYE_SEPARATION};
+    use camera::Camera;
+    use graphics_math as math;
+    use math::Versor;
+
+    const EPSILON: f32 = 1e-5;
+
+    fn camera() -> Camera {
+        Camera::new(math::dvec3((1.0, 1.5, 4.0)), Versor::from_axis_deg(30.0, 0.0, 1.0, 0.0), 67.0)
+    }
+
+    ///
+    /// Where a point lands across the screen, from -1 at the left edge to 1 at the right.
+    ///
+    fn screen_x(settings: &StereoSettings, eye: Eye, point: math::Vec4) -> f32 {
+        let camera = camera();
+        let clip = settings.eye_proj_mat(&camera, eye, 1.5, 0.1, 100.0) * (settings.eye_view_mat(&camera, eye) * point);
+
+        clip.v[0] / clip.v[3]
+    }
+
+    ///
+    /// A point straight ahead of the camera, `distance` away.
+    ///
+    fn ahead(distance: f32) -> math::Vec4 {
+        let camera = camera();
+        let inverse_view = camera.view_mat().inverse();
+
+        inverse_view * math::vec4((0.0, 0.0, -distance, 1.0))
+    }
+
+    #[test]
+    fn test_the_eyes_agree_at_the_convergence_distance() {
+        let settings = StereoSettings::new();
+        let point = ahead(settings.convergence);
+
+        let left = screen_x(&settings, Eye::Left, point);
+        let right = screen_x(&settings, Eye::Right, point);
+
+        assert!(f32::abs(left) < EPSILON, "left eye: {}", left);
+        assert!(f32::abs(right) < EPSILON, "right eye: {}", right);
+    }
+
+    #[test]
+    fn test_nearer_points_cross_over_between_the_eyes() {
+        let settings = StereoSettings::new();
+        let near_point = ahead(0.5 * settings.convergence);
+        let far_point = ahead(4.0 * settings.convergence);
+
+        // in front of the screen, the left eye sees the point to the right
+        assert!(screen_x(&settings, Eye::Left, near_point) > screen_x(&settings, Eye::Right, near_point));
+        assert!(screen_x(&settings, Eye::Left, far_point) < screen_x(&settings, Eye::Right, far_point));
+    }
+
+    #[test]
+    fn test_no_separation_gives_both_eyes_the_camera_view() {
+        let mut settings = StereoSettings::new();
+        settings.set_eye_separation(-1.0);
+        let camera = camera();
+
+        assert_eq!(settings.eye_separation, 0.0);
+        assert_eq!(settings.eye_view_mat(&camera, Eye::Left), camera.view_mat());
+        assert_eq!(settings.eye_proj_mat(&camera, Eye::Right, 1.5, 0.1, 100.0), camera.proj_mat(1.5, 0.1, 100.0));
+    }
+
+    #[test]
+    fn test_eye_separation_is_clamped() {
+        let mut settings = StereoSettings::new();
+        settings.set_eye_separation(10.0);
+
+        assert_eq!(settings.eye_separation, MAX_EYE_SEPARATION);
+    }
+}
@@ -0,0 +1,298 @@
+use gl;
+use gl::types::{GLenum, GLsizeiptr, GLsync, GLuint, GLvoid};
+
+use std::mem;
+use std::ptr;
+
+
+///
+/// The number of regions in a persistently mapped buffer's ring. The CPU writes one
+/// region while the GPU may still be reading the other two from earlier frames.
+///
+pub const STREAMING_REGION_COUNT: usize = 3;
+
+///
+/// How long to wait on a fence at a time, in nanoseconds, before checking again.
+///
+const FENCE_TIMEOUT_NS: u64 = 1_000_000;
+
+
+///
+/// How a `StreamingBuffer` gets new data to the GPU.
+///
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum StreamingMode {
+    /// Respecify the buffer with `glBufferData` and a null pointer before each
+    /// upload. The driver hands back fresh storage rather than waiting for draws
+    /// still reading the old contents.
+    Orphaning,
+    /// Map the buffer once with `GL_MAP_PERSISTENT_BIT` and `GL_MAP_COHERENT_BIT`,
+    /// and write each upload straight into the next region of a ring, waiting on a
+    /// fence only if the GPU has not finished with that region yet.
+    PersistentMapped,
+}
+
+impl StreamingMode {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            StreamingMode::Orphaning => "orphaning",
+            StreamingMode::PersistentMapped => "persistent mapping",
+        }
+    }
+}
+
+///
+/// Whether the context has the functions persistent mapping needs. These are core
+/// in OpenGL 4.4, and also come with `GL_ARB_buffer_storage`.
+///
+pub fn persistent_mapping_supported() -> bool {
+    gl::BufferStorage::is_loaded() && gl::MapBufferRange::is_loaded() &&
+    gl::FenceSync::is_loaded() && gl::ClientWaitSync::is_loaded() && gl::DeleteSync::is_loaded()
+}
+
+///
+/// The capacity in bytes a region needs to hold `size` bytes. Regions never shrink,
+/// and grow to the next power of two so a slowly growing upload does not reallocate
+/// every frame.
+///
+fn grown_capacity(capacity: usize, size: usize) -> usize {
+    if size <= capacity {
+        return capacity;
+    }
+
+    size.next_power_of_two()
+}
+
+///
+/// A buffer object for data that changes every frame, such as batched sprites or
+/// text. Each call to `upload` replaces the previous contents, and returns the byte
+/// offset of the new data within the buffer, which the caller passes on to its
+/// vertex attribute pointers. The buffer object itself changes when the buffer has
+/// to grow, so fetch it with `buffer` after each upload.
+///
+pub struct StreamingBuffer {
+    mode: StreamingMode,
+    target: GLenum,
+    buffer: GLuint,
+    capacity: usize,
+    region: usize,
+    uploaded: bool,
+    mapped: *mut u8,
+    fences: [GLsync; STREAMING_REGION_COUNT],
+}
+
+impl StreamingBuffer {
+    ///
+    /// Make a streaming buffer for `target` holding up to `capacity` bytes before it
+    /// has to grow. This uses persistent mapping where the context supports it, and
+    /// orphaning otherwise.
+    ///
+    pub fn new(target: GLenum, capacity: usize) -> StreamingBuffer {
+        let mode = if persistent_mapping_supported() {
+            StreamingMode::PersistentMapped
+        } else {
+            StreamingMode::Orphaning
+        };
+
+        StreamingBuffer::with_mode(target, capacity, mode)
+    }
+
+    ///
+    /// Make a streaming buffer with a particular mode. Asking for persistent mapping
+    /// on a context without it falls back to orphaning.
+    ///
+    pub fn with_mode(target: GLenum, capacity: usize, mode: StreamingMode) -> StreamingBuffer {
+        let mode = if mode == StreamingMode::PersistentMapped && !persistent_mapping_supported() {
+            StreamingMode::Orphaning
+        } else {
+            mode
+        };
+        let mut streaming_buffer = StreamingBuffer {
+            mode: mode,
+            target: target,
+            buffer: 0,
+            capacity: usize::max(capacity, 1),
+            region: 0,
+            uploaded: false,
+            mapped: ptr::null_mut(),
+            fences: [ptr::null(); STREAMING_REGION_COUNT],
+        };
+        streaming_buffer.allocate();
+
+        streaming_buffer
+    }
+
+    pub fn mode(&self) -> StreamingMode {
+        self.mode
+    }
+
+    ///
+    /// The buffer object holding the most recent upload.
+    ///
+    pub fn buffer(&self) -> GLuint {
+        self.buffer
+    }
+
+    ///
+    /// The number of bytes one upload can hold before the buffer has to grow.
+    ///
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    fn allocate(&mut self) {
+        unsafe {
+            gl::GenBuffers(1, &mut self.buffer);
+            gl::BindBuffer(self.target, self.buffer);
+        }
+        assert!(self.buffer > 0);
+
+        if self.mode == StreamingMode::PersistentMapped {
+            let size = (self.capacity * STREAMING_REGION_COUNT) as GLsizeiptr;
+            let flags = gl::MAP_WRITE_BIT | gl::MAP_PERSISTENT_BIT | gl::MAP_COHERENT_BIT;
+            unsafe {
+                gl::BufferStorage(self.target, size, ptr::null(), flags);
+                self.mapped = gl::MapBufferRange(self.target, 0, size, flags) as *mut u8;
+            }
+            if !self.mapped.is_null() {
+                return;
+            }
+
+            // The driver would not map the buffer after all. Start again without
+            // persistent mapping.
+            unsafe {
+                gl::DeleteBuffers(1, &self.buffer);
+            }
+            self.mode = StreamingMode::Orphaning;
+            self.allocate();
+            return;
+        }
+
+        unsafe {
+            gl_check!(gl::BufferData(self.target, self.capacity as GLsizeiptr, ptr::null(), gl::STREAM_DRAW));
+        }
+    }
+
+    fn release(&mut self) {
+        unsafe {
+            for fence in self.fences.iter_mut() {
+                if !fence.is_null() {
+                    gl::DeleteSync(*fence);
+                    *fence = ptr::null();
+                }
+            }
+            if !self.mapped.is_null() {
+                gl::BindBuffer(self.target, self.buffer);
+                gl::UnmapBuffer(self.target);
+                self.mapped = ptr::null_mut();
+            }
+            gl::DeleteBuffers(1, &self.buffer);
+        }
+        self.buffer = 0;
+    }
+
+    ///
+    /// Block until the GPU has finished with the region about to be written.
+    ///
+    fn wait_for_region(&mut self) {
+        let fence = self.fences[self.region];
+        if fence.is_null() {
+            return;
+        }
+
+        unsafe {
+            loop {
+                let status = gl::ClientWaitSync(fence, gl::SYNC_FLUSH_COMMANDS_BIT, FENCE_TIMEOUT_NS);
+                if status != gl::TIMEOUT_EXPIRED {
+                    break;
+                }
+            }
+            gl::DeleteSync(fence);
+        }
+        self.fences[self.region] = ptr::null();
+    }
+
+    ///
+    /// Copy `data` into the buffer, replacing the last upload, and return the byte
+    /// offset it starts at. The buffer grows if `data` does not fit.
+    ///
+    /// With persistent mapping, this also puts a fence after everything issued since
+    /// the last upload, so the draws reading that region are covered without the
+    /// caller having to say when they are done.
+    ///
+    pub fn upload<T: Copy>(&mut self, data: &[T]) -> usize {
+        let size = data.len() * mem::size_of::<T>();
+        match self.mode {
+            StreamingMode::Orphaning => {
+                self.capacity = grown_capacity(self.capacity, size);
+                unsafe {
+                    gl::BindBuffer(self.target, self.buffer);
+                    gl_check!(gl::BufferData(self.target, self.capacity as GLsizeiptr, ptr::null(), gl::STREAM_DRAW));
+                    gl_check!(gl::BufferSubData(self.target, 0, size as GLsizeiptr, data.as_ptr() as *const GLvoid));
+                }
+
+                0
+            }
+            StreamingMode::PersistentMapped => {
+                if self.uploaded {
+                    unsafe {
+                        self.fences[self.region] = gl::FenceSync(gl::SYNC_GPU_COMMANDS_COMPLETE, 0);
+                    }
+                    self.region = (self.region + 1) % STREAMING_REGION_COUNT;
+                }
+                if size > self.capacity {
+                    // Draws still reading the old buffer keep it alive until they finish.
+                    self.release();
+                    self.capacity = grown_capacity(self.capacity, size);
+                    self.region = 0;
+                    self.allocate();
+                    if self.mode == StreamingMode::Orphaning {
+                        return self.upload(data);
+                    }
+                } else {
+                    self.wait_for_region();
+                }
+
+                let offset = self.region * self.capacity;
+                unsafe {
+                    ptr::copy_nonoverlapping(
+                        data.as_ptr() as *const u8, self.mapped.offset(offset as isize), size
+                    );
+                }
+                self.uploaded = true;
+
+                offset
+            }
+        }
+    }
+}
+
+impl Drop for StreamingBuffer {
+    fn drop(&mut self) {
+        self.release();
+    }
+}
+
+mod streaming_buffer_tests {
+    use super::{grown_capacity, StreamingMode};
+
+    #[test]
+    fn test_capacity_only_grows_when_the_data_does_not_fit() {
+        assert_eq!(grown_capacity(1024, 0), 1024);
+        assert_eq!(grown_capacity(1024, 1024), 1024);
+        assert_eq!(grown_capacity(1024, 100), 1024);
+    }
+
+    #[test]
+    fn test_capacity_grows_to_a_power_of_two() {
+        assert_eq!(grown_capacity(1024, 1025), 2048);
+        assert_eq!(grown_capacity(1000, 3000), 4096);
+        assert_eq!(grown_capacity(1, 4096), 4096);
+    }
+
+    #[test]
+    fn test_mode_names() {
+        assert_eq!(StreamingMode::Orphaning.name(), "orphaning");
+        assert_eq!(StreamingMode::PersistentMapped.name(), "persistent mapping");
+    }
+}
@@ -0,0 +1,339 @@
+use graphics_math as math;
+use math::Vec3;
+
+use picking::{self, Plane, Ray};
+use scene::Transform;
+
+
+///
+/// How far apart the grid lines are that snapped positions land on, in world units.
+///
+pub const GRID_STEP: f32 = 0.5;
+///
+/// The angle snapped rotations are a multiple of, in degrees.
+///
+pub const ROTATION_STEP: f32 = 15.0;
+///
+/// The step snapped scales are a multiple of.
+///
+pub const SCALE_STEP: f32 = 0.25;
+///
+/// The smallest an object can be scaled down to, so it never turns inside out or
+/// vanishes where it cannot be clicked again.
+///
+pub const MIN_SCALE: f32 = 0.05;
+
+// How far the cursor moves across the screen, in pixels, to turn an object by one
+// degree, and to double its size.
+const DEGREES_PER_PIXEL: f32 = 0.5;
+const PIXELS_PER_DOUBLING: f32 = 200.0;
+
+
+///
+/// Round a value to the nearest multiple of `step`.
+///
+pub fn snap(value: f32, step: f32) -> f32 {
+    f32::round(value / step) * step
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum GizmoMode {
+    Move,
+    Rotate,
+    Scale,
+}
+
+impl GizmoMode {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            GizmoMode::Move => "move",
+            GizmoMode::Rotate => "rotate",
+            GizmoMode::Scale => "scale",
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Axis {
+    X,
+    Y,
+    Z,
+}
+
+impl Axis {
+    pub fn index(&self) -> usize {
+        match *self {
+            Axis::X => 0,
+            Axis::Y => 1,
+            Axis::Z => 2,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Axis::X => "x",
+            Axis::Y => "y",
+            Axis::Z => "z",
+        }
+    }
+
+    pub fn vector(&self) -> Vec3 {
+        match *self {
+            Axis::X => math::vec3((1.0, 0.0, 0.0)),
+            Axis::Y => math::vec3((0.0, 1.0, 0.0)),
+            Axis::Z => math::vec3((0.0, 0.0, 1.0)),
+        }
+    }
+}
+
+///
+/// A move, rotate, or scale of one object in progress, driven by the cursor. The
+/// gizmo remembers the object's transform and where the cursor was when it began,
+/// and works out the new transform from how far the cursor has gone since. That
+/// way cancelling is just putting the starting transform back.
+///
+/// Moving drags the object across the ground, or along one axis when the gizmo is
+/// constrained to it. Rotating and scaling follow the cursor sideways.
+///
+#[derive(Copy, Clone, Debug)]
+pub struct Gizmo {
+    pub mode: GizmoMode,
+    pub axis: Option<Axis>,
+    start: Transform,
+    start_cursor: (f32, f32),
+    start_ray: Ray,
+}
+
+impl Gizmo {
+    ///
+    /// Start changing an object with the transform `start`, from the cursor at
+    /// `cursor`, in pixels, casting the ray `ray` into the scene.
+    ///
+    pub fn begin(mode: GizmoMode, start: Transform, cursor: (f32, f32), ray: Ray) -> Gizmo {
+        Gizmo {
+            mode: mode,
+            axis: None,
+            start: start,
+            start_cursor: cursor,
+            start_ray: ray,
+        }
+    }
+
+    ///
+    /// The transform the object had when the gizmo began.
+    ///
+    pub fn start(&self) -> Transform {
+        self.start
+    }
+
+    ///
+    /// Constrain the change to one axis. Picking the same axis again lifts the
+    /// constraint.
+    ///
+    pub fn constrain(&mut self, axis: Axis) {
+        if self.axis == Some(axis) {
+            self.axis = None;
+        } else {
+            self.axis = Some(axis);
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        match (self.mode, self.axis) {
+            (GizmoMode::Move, None) => String::from("move across the ground"),
+            (GizmoMode::Move, Some(axis)) => format!("move along {}", axis.name()),
+            (GizmoMode::Rotate, None) => String::from("rotate about y"),
+            (GizmoMode::Rotate, Some(axis)) => format!("rotate about {}", axis.name()),
+            (GizmoMode::Scale, None) => String::from("scale evenly"),
+            (GizmoMode::Scale, Some(axis)) => format!("scale along {}", axis.name()),
+        }
+    }
+
+    ///
+    /// The plane the object is dragged across while moving. Moving up and down
+    /// uses an upright plane turned to face the camera, so the cursor can reach it.
+    ///
+    fn drag_plane(&self) -> Plane {
+        let origin = self.start.position();
+        let up = math::vec3((0.0, 1.0, 0.0));
+        if self.axis != Some(Axis::Y) {
+            return Plane::from_point_normal(&origin, &up);
+        }
+
+        let d = self.start_ray.direction;
+        let facing = math::vec3((-d.v[0], 0.0, -d.v[2]));
+        if facing.norm() < 1e-3 {
+            // Looking straight down, any upright plane is seen edge on.
+            return Plane::from_point_normal(&origin, &math::vec3((0.0, 0.0, 1.0)));
+        }
+
+        Plane::from_point_normal(&origin, &facing)
+    }
+
+    ///
+    /// The object's transform with the cursor at `cursor`, casting the ray `ray`.
+    /// With `snapping` on, positions land on the grid, and angles and scales on
+    /// whole steps. Returns `None` while moving if the cursor is off the drag plane,
+    /// such as above the horizon.
+    ///
+    pub fn drag(&self, ray: &Ray, cursor: (f32, f32), snapping: bool) -> Option<Transform> {
+        let mut transform = self.start;
+        let dx = cursor.0 - self.start_cursor.0;
+        match self.mode {
+            GizmoMode::Move => {
+                let plane = self.drag_plane();
+                let (from, to) = match (picking::ray_plane(&self.start_ray, &plane), picking::ray_plane(ray, &plane)) {
+                    (Some(from), Some(to)) => (from, to),
+                    _ => return None,
+                };
+                let mut offset = to - from;
+                if let Some(axis) = self.axis {
+                    offset = axis.vector() * offset.dot(&axis.vector());
+                }
+                for i in 0..3 {
+                    transform.position[i] += offset.v[i];
+                    // A free move slides across the ground, keeping the height.
+                    let moved = match self.axis {
+                        Some(axis) => axis.index() == i,
+                        None => i != 1,
+                    };
+                    if snapping && moved {
+                        transform.position[i] = snap(transform.position[i], GRID_STEP);
+                    }
+                }
+            }
+            GizmoMode::Rotate => {
+                let i = self.axis.unwrap_or(Axis::Y).index();
+                transform.rotation[i] += dx * DEGREES_PER_PIXEL;
+                if snapping {
+                    transform.rotation[i] = snap(transform.rotation[i], ROTATION_STEP);
+                }
+            }
+            GizmoMode::Scale => {
+                let factor = f32::powf(2.0, dx / PIXELS_PER_DOUBLING);
+                for i in 0..3 {
+                    if self.axis.map_or(true, |axis| axis.index() == i) {
+                        let mut scale = transform.scale[i] * factor;
+                        if snapping {
+                            scale = snap(scale, SCALE_STEP);
+                        }
+                        transform.scale[i] = f32::max(scale, MIN_SCALE);
+                    }
+                }
+            }
+        }
+
+        Some(transform)
+    }
+}
+
+mod gizmo_tests {
+    use super::{snap, Axis, Gizmo, GizmoMode, GRID_STEP, MIN_SCALE};
+    use graphics_math as math;
+    use math::Vec3;
+    use picking::Ray;
+    use scene::Transform;
+
+    const EPSILON: f32 = 1e-4;
+
+    fn assert_near(a: f32, b: f32) {
+        assert!(f32::abs(a - b) < EPSILON, "{} is not {}", a, b);
+    }
+
+    ///
+    /// A ray from a camera up and behind the origin, looking at `target`.
+    ///
+    fn ray_to(target: Vec3) -> Ray {
+        let eye = math::vec3((0.0, 5.0, 10.0));
+
+        Ray::new(eye, target - eye)
+    }
+
+    fn start() -> Transform {
+        Transform::new(math::vec3((1.0, 0.5, 0.0)))
+    }
+
+    #[test]
+    fn test_moving_slides_the_object_across_the_ground() {
+        let gizmo = Gizmo::begin(GizmoMode::Move, start(), (100.0, 100.0), ray_to(math::vec3((1.0, 0.5, 0.0))));
+
+        let moved = gizmo.drag(&ray_to(math::vec3((3.2, 0.5, -1.3))), (150.0, 80.0), false).unwrap();
+
+        assert_near(moved.position[0], 3.2);
+        assert_near(moved.position[1], 0.5);
+        assert_near(moved.position[2], -1.3);
+        // snapped to the grid, except the height, which a free move keeps
+        let snapped = gizmo.drag(&ray_to(math::vec3((3.2, 0.5, -1.3))), (150.0, 80.0), true).unwrap();
+        assert_near(snapped.position[0], 3.0);
+        assert_near(snapped.position[1], 0.5);
+        assert_near(snapped.position[2], -1.5);
+    }
+
+    #[test]
+    fn test_moving_along_an_axis_ignores_the_others() {
+        let mut gizmo = Gizmo::begin(GizmoMode::Move, start(), (100.0, 100.0), ray_to(math::vec3((1.0, 0.5, 0.0))));
+        gizmo.constrain(Axis::X);
+
+        let moved = gizmo.drag(&ray_to(math::vec3((3.0, 0.5, -2.0))), (150.0, 80.0), false).unwrap();
+
+        assert_near(moved.position[0], 3.0);
+        assert_near(moved.position[2], 0.0);
+    }
+
+    #[test]
+    fn test_moving_up_uses_an_upright_plane() {
+        let mut gizmo = Gizmo::begin(GizmoMode::Move, start(), (100.0, 100.0), ray_to(math::vec3((1.0, 0.5, 0.0))));
+        gizmo.constrain(Axis::Y);
+
+        let moved = gizmo.drag(&ray_to(math::vec3((2.0, 2.0, 0.1))), (110.0, 40.0), false).unwrap();
+
+        assert_near(moved.position[0], 1.0);
+        assert_near(moved.position[1], 2.0);
+        assert_near(moved.position[2], 0.0);
+    }
+
+    #[test]
+    fn test_the_cursor_above_the_horizon_does_not_move_the_object() {
+        let gizmo = Gizmo::begin(GizmoMode::Move, start(), (100.0, 100.0), ray_to(math::vec3((1.0, 0.5, 0.0))));
+        let skyward = Ray::new(math::vec3((0.0, 5.0, 10.0)), math::vec3((0.0, 1.0, -1.0)));
+
+        assert_eq!(gizmo.drag(&skyward, (100.0, 0.0), false), None);
+    }
+
+    #[test]
+    fn test_rotating_follows_the_cursor_sideways() {
+        let ray = ray_to(math::vec3((1.0, 0.5, 0.0)));
+        let mut gizmo = Gizmo::begin(GizmoMode::Rotate, start(), (100.0, 100.0), ray);
+
+        assert_near(gizmo.drag(&ray, (190.0, 100.0), false).unwrap().rotation[1], 45.0);
+        assert_near(gizmo.drag(&ray, (118.0, 100.0), true).unwrap().rotation[1], 15.0);
+        gizmo.constrain(Axis::X);
+        let rotated = gizmo.drag(&ray, (190.0, 100.0), false).unwrap();
+        assert_near(rotated.rotation[0], 45.0);
+        assert_near(rotated.rotation[1], 0.0);
+    }
+
+    #[test]
+    fn test_scaling_doubles_and_never_collapses() {
+        let ray = ray_to(math::vec3((1.0, 0.5, 0.0)));
+        let mut gizmo = Gizmo::begin(GizmoMode::Scale, start(), (300.0, 100.0), ray);
+
+        assert_near(gizmo.drag(&ray, (500.0, 100.0), false).unwrap().scale[2], 2.0);
+        assert_near(gizmo.drag(&ray, (0.0, 100.0), true).unwrap().scale[0], 0.25);
+        gizmo.constrain(Axis::Z);
+        let squashed = gizmo.drag(&ray, (-5000.0, 100.0), false).unwrap();
+        assert_near(squashed.scale[0], 1.0);
+        assert_near(squashed.scale[2], MIN_SCALE);
+    }
+
+    #[test]
+    fn test_constraining_to_the_same_axis_again_lifts_it() {
+        let mut gizmo = Gizmo::begin(GizmoMode::Move, start(), (0.0, 0.0), ray_to(math::vec3((0.0, 0.0, 0.0))));
+
+        gizmo.constrain(Axis::Z);
+        gizmo.constrain(Axis::Z);
+
+        assert_eq!(gizmo.axis, None);
+        assert_near(snap(1.3, GRID_STEP), 1.5);
+    }
+}
@@ -0,0 +1,612 @@
+extern crate gl;
+extern crate glfw;
+extern crate chrono;
+extern crate stb_image;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+extern crate serde_json;
+#[cfg(feature = "glam")]
+extern crate glam;
+#[cfg(feature = "cgmath")]
+extern crate cgmath;
+
+#[macro_use]
+mod gl_utils;
+mod graphics_math;
+mod obj_parser;
+mod colour;
+mod assets;
+mod shader_include;
+mod spirv;
+mod program_cache;
+mod logger;
+mod overlay;
+mod streaming_buffer;
+mod camera;
+mod camera_controller;
+mod picking;
+mod gizmo;
+mod scene;
+mod vertex_layout;
+
+
+use glfw::{Action, Context, Key, MouseButton};
+use gl::types::{GLfloat, GLint, GLuint};
+
+use std::process;
+
+use gl_utils::*;
+
+use graphics_math as math;
+use math::Mat4;
+
+use camera::Camera;
+use camera_controller::{CameraController, ControlKeys};
+use gizmo::{Axis, Gizmo, GizmoMode};
+use overlay::Overlay;
+use picking::{Plane, Ray};
+use scene::{Primitive, Scene, SceneObject};
+use vertex_layout::VertexLayout;
+
+
+const GL_LOG_FILE: &str = "gl.log";
+const VERTEX_SHADER_FILE: &str = "src/scene_vs.glsl";
+const FRAGMENT_SHADER_FILE: &str = "src/scene_fs.glsl";
+// The layout is loaded from here at startup, and saved back with Ctrl+S.
+const SCENE_FILE: &str = "src/scene.json";
+
+const GROUND_HALF_SIZE: f32 = 20.0;
+const EYE_HEIGHT: f32 = 3.0;
+
+const GROUND_COLOUR: [f32; 3] = [0.35, 0.45, 0.3];
+// Each object placed gets the next of these colours.
+const OBJECT_COLOURS: [[f32; 3]; 5] = [
+    [0.8, 0.3, 0.2], [0.2, 0.5, 0.8], [0.9, 0.7, 0.2], [0.5, 0.3, 0.7], [0.3, 0.7, 0.6],
+];
+// The gizmo handles, in axis order, and the one the gizmo is constrained to.
+const HANDLE_COLOURS: [[f32; 3]; 3] = [[0.9, 0.2, 0.2], [0.2, 0.9, 0.2], [0.2, 0.3, 0.9]];
+const ACTIVE_HANDLE_COLOUR: [f32; 3] = [1.0, 1.0, 0.4];
+const HANDLE_THICKNESS: f32 = 0.05;
+
+const OVERLAY_TEXT_COLOUR: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+const OVERLAY_BACKGROUND_COLOUR: [f32; 4] = [0.0, 0.0, 0.0, 0.6];
+
+
+///
+/// The ground plane: two triangles facing up, with each position followed by
+/// its normal.
+///
+fn ground_vertices() -> Vec<GLfloat> {
+    let a = GROUND_HALF_SIZE;
+    let corners = [(-a, a), (a, a), (a, -a), (a, -a), (-a, -a), (-a, a)];
+    let mut vertices = Vec::with_capacity(6 * corners.len());
+    for &(x, z) in corners.iter() {
+        vertices.extend_from_slice(&[x, 0.0, z, 0.0, 1.0, 0.0]);
+    }
+
+    vertices
+}
+
+///
+/// A unit cube centred on the origin, with each position followed by its normal.
+///
+fn cube_vertices() -> Vec<GLfloat> {
+    // The normal of each face, and two axes across it whose cross product is the
+    // normal, so the triangles wind counter-clockwise seen from outside.
+    let faces = [
+        ([1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]),
+        ([-1.0, 0.0, 0.0], [0.0, 0.0, 1.0], [0.0, 1.0, 0.0]),
+        ([0.0, 1.0, 0.0], [0.0, 0.0, 1.0], [1.0, 0.0, 0.0]),
+        ([0.0, -1.0, 0.0], [1.0, 0.0, 0.0], [0.0, 0.0, 1.0]),
+        ([0.0, 0.0, 1.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]),
+        ([0.0, 0.0, -1.0], [0.0, 1.0, 0.0], [1.0, 0.0, 0.0]),
+    ];
+    let corners = [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (1.0, 1.0), (-1.0, 1.0), (-1.0, -1.0)];
+
+    let mut vertices = Vec::with_capacity(6 * 6 * faces.len());
+    for &(n, u, v) in faces.iter() {
+        for &(s, t) in corners.iter() {
+            for i in 0..3 {
+                vertices.push(0.5 * (n[i] + s * u[i] + t * v[i]));
+            }
+            vertices.extend_from_slice(&n);
+        }
+    }
+
+    vertices
+}
+
+///
+/// Interleave separate position and normal arrays, as loaded from a mesh.
+///
+fn interleave(points: &[GLfloat], normals: &[GLfloat]) -> Vec<GLfloat> {
+    let mut vertices = Vec::with_capacity(points.len() + normals.len());
+    for i in 0..(points.len() / 3) {
+        vertices.extend_from_slice(&points[3 * i..3 * i + 3]);
+        vertices.extend_from_slice(&normals[3 * i..3 * i + 3]);
+    }
+
+    vertices
+}
+
+///
+/// Copy interleaved positions and normals into a vertex buffer, and make a vertex
+/// array reading positions from location 0 and normals from location 1.
+///
+fn make_vao(vertices: &[GLfloat]) -> GLuint {
+    let mut vao = 0;
+    unsafe {
+        gl::GenVertexArrays(1, &mut vao);
+    }
+    assert!(vao > 0);
+    VertexLayout::new().floats(0, 3).floats(1, 3).upload(vao, vertices, vertices.len() / 6);
+
+    vao
+}
+
+///
+/// The shader everything in the scene is drawn with: a flat colour lit by one
+/// light from above.
+///
+struct SceneShader {
+    sp: GLuint,
+    model_location: GLint,
+    view_location: GLint,
+    proj_location: GLint,
+    colour_location: GLint,
+}
+
+impl SceneShader {
+    fn new(sp: GLuint) -> SceneShader {
+        let location = |name: &str| {
+            let location = uniform_location(sp, name);
+            assert!(location > -1);
+            location
+        };
+
+        SceneShader {
+            sp: sp,
+            model_location: location("M"),
+            view_location: location("V"),
+            proj_location: location("P"),
+            colour_location: location("colour"),
+        }
+    }
+
+    fn set_camera(&self, view_mat: &Mat4, proj_mat: &Mat4) {
+        unsafe {
+            gl::UseProgram(self.sp);
+            gl::UniformMatrix4fv(self.view_location, 1, gl::FALSE, view_mat.as_ptr());
+            gl::UniformMatrix4fv(self.proj_location, 1, gl::FALSE, proj_mat.as_ptr());
+        }
+    }
+
+    fn draw(&self, vao: GLuint, point_count: usize, model_mat: &Mat4, colour: &[f32; 3]) {
+        unsafe {
+            gl::UniformMatrix4fv(self.model_location, 1, gl::FALSE, model_mat.as_ptr());
+            gl::Uniform3f(self.colour_location, colour[0], colour[1], colour[2]);
+            gl::BindVertexArray(vao);
+            gl::DrawArrays(gl::TRIANGLES, 0, point_count as i32);
+        }
+    }
+}
+
+///
+/// The vertex arrays of the ground and of each primitive.
+///
+struct Meshes {
+    ground_vao: GLuint,
+    ground_point_count: usize,
+    cube_vao: GLuint,
+    cube_point_count: usize,
+    sphere_vao: GLuint,
+    sphere_point_count: usize,
+}
+
+impl Meshes {
+    ///
+    /// Draw one object. The embedded sphere has a radius of one, so it is halved
+    /// to fit the unit cube every primitive is sized to.
+    ///
+    fn draw_object(&self, shader: &SceneShader, object: &SceneObject, colour: &[f32; 3]) {
+        let model_mat = object.transform.model_mat();
+        match object.primitive {
+            Primitive::Cube => {
+                shader.draw(self.cube_vao, self.cube_point_count, &model_mat, colour);
+            }
+            Primitive::Sphere => {
+                let model_mat = model_mat * Mat4::from_scale(&math::vec3((0.5, 0.5, 0.5)));
+                shader.draw(self.sphere_vao, self.sphere_point_count, &model_mat, colour);
+            }
+        }
+    }
+
+    ///
+    /// Draw the gizmo's three handles sticking out of the selected object along
+    /// the world axes, over the top of everything so they are never hidden.
+    ///
+    fn draw_handles(&self, shader: &SceneShader, object: &SceneObject, active_axis: Option<Axis>) {
+        let length = object.bounding_radius() + 0.5;
+        unsafe {
+            gl::Disable(gl::DEPTH_TEST);
+        }
+        for &axis in [Axis::X, Axis::Y, Axis::Z].iter() {
+            let mut size = [HANDLE_THICKNESS; 3];
+            size[axis.index()] = length;
+            let centre = object.transform.position() + axis.vector() * (0.5 * length);
+            let model_mat = Mat4::from_scale(&math::vec3((size[0], size[1], size[2]))).then_translate(&centre);
+            let colour = if active_axis == Some(axis) {
+                ACTIVE_HANDLE_COLOUR
+            } else {
+                HANDLE_COLOURS[axis.index()]
+            };
+            shader.draw(self.cube_vao, self.cube_point_count, &model_mat, &colour);
+        }
+        unsafe {
+            gl::Enable(gl::DEPTH_TEST);
+        }
+    }
+}
+
+///
+/// Lighten a colour, to pick out the selected object.
+///
+fn highlight(colour: &[f32; 3]) -> [f32; 3] {
+    [0.5 + 0.5 * colour[0], 0.5 + 0.5 * colour[1], 0.5 + 0.5 * colour[2]]
+}
+
+///
+/// Where the cursor is in the window, in pixels, and the ray from the camera into
+/// the scene through it. The cursor is measured in screen coordinates, which only
+/// match the framebuffer's pixels on screens without high DPI scaling, so the ray
+/// is worked out against the window size.
+///
+fn cursor_ray(context: &GLContext, controller: &CameraController, proj_mat: &Mat4) -> ((f32, f32), Ray) {
+    let (x_pos, y_pos) = context.window.get_cursor_pos();
+    let (width, height) = context.window.get_size();
+    let cursor = (x_pos as f32, y_pos as f32);
+    let ray = picking::ray_from_mouse(
+        u32::max(width as u32, 1), u32::max(height as u32, 1), proj_mat, &controller.camera.view_mat(),
+        math::vec3(controller.position()), cursor.0, cursor.1
+    );
+
+    (cursor, ray)
+}
+
+///
+/// Describe what the editor is doing, for the overlay.
+///
+fn describe_state(
+    scene: &Scene, selected: Option<usize>, gizmo: &Option<Gizmo>, primitive: Primitive, snapping: bool) -> String {
+    let mut lines = vec![
+        format!("placing: {}   snapping: {}   objects: {}", primitive.name(), if snapping { "on" } else { "off" }, scene.objects.len()),
+    ];
+    match selected {
+        Some(i) => {
+            let transform = scene.objects[i].transform;
+            lines.push(format!(
+                "selected: {} {}  at ({:.2}, {:.2}, {:.2})",
+                scene.objects[i].primitive.name(), i,
+                transform.position[0], transform.position[1], transform.position[2]
+            ));
+        }
+        None => {
+            lines.push(String::from("selected: nothing"));
+        }
+    }
+    if let Some(ref gizmo) = *gizmo {
+        lines.push(format!("{}: click or Enter to keep, Esc or right click to cancel", gizmo.describe()));
+    }
+
+    lines.join("\n")
+}
+
+///
+/// Queue up a label in the top-left corner of the viewport.
+///
+fn label(overlay: &mut Overlay, text: &str) {
+    let scale = 2.0;
+    let longest = text.lines().map(|line| line.len()).max().unwrap_or(0);
+    let width = (longest as f32 * overlay::GLYPH_ADVANCE + 4.0) * scale;
+    let height = (text.lines().count() as f32 * overlay::LINE_HEIGHT + 4.0) * scale;
+    overlay.rect(10.0, 10.0, width, height, OVERLAY_BACKGROUND_COLOUR);
+    overlay.text(10.0 + 2.0 * scale, 10.0 + 2.0 * scale, scale, OVERLAY_TEXT_COLOUR, text);
+}
+
+///
+/// Keep the context's idea of the framebuffer size up to date. The projection
+/// matrix is worked out again from the size every frame.
+///
+fn glfw_framebuffer_size_callback(context: &mut GLContext, width: u32, height: u32) {
+    context.width = width;
+    context.height = height;
+}
+
+fn main() {
+    let logger = restart_gl_log(GL_LOG_FILE);
+    // Start a GL context and O/S window using the GLFW helper library.
+    let mut context = match start_gl(&logger) {
+        Ok(val) => val,
+        Err(e) => {
+            eprintln!("Failed to Initialize OpenGL context. Got error:");
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+    };
+    // The framebuffer can be bigger than the window on high DPI screens.
+    context.window.set_framebuffer_size_polling(true);
+    context.window.set_mouse_button_polling(true);
+    let (width, height) = context.window.get_framebuffer_size();
+    glfw_framebuffer_size_callback(&mut context, width as u32, height as u32);
+
+    unsafe {
+        // Enable depth testing.
+        gl::Enable(gl::DEPTH_TEST);
+        // Depth testing interprets a smaller value as closer to the eye.
+        gl::DepthFunc(gl::LESS);
+        // Cull face.
+        gl::Enable(gl::CULL_FACE);
+        // Cull back face.
+        gl::CullFace(gl::BACK);
+        // GL_CW for clockwise.
+        gl::FrontFace(gl::CCW);
+        gl::ClearColor(0.6, 0.7, 0.8, 1.0);
+    }
+
+    /*------------------------------CREATE GEOMETRY------------------------------*/
+    let ground = ground_vertices();
+    let cube = cube_vertices();
+    let sphere_mesh = assets::fallback_mesh();
+    let meshes = Meshes {
+        ground_vao: make_vao(&ground),
+        ground_point_count: ground.len() / 6,
+        cube_vao: make_vao(&cube),
+        cube_point_count: cube.len() / 6,
+        sphere_vao: make_vao(&interleave(&sphere_mesh.points, &sphere_mesh.normals)),
+        sphere_point_count: sphere_mesh.point_count,
+    };
+    // clicks that miss every object land on the ground
+    let ground_plane = Plane::from_point_normal(&math::vec3((0.0, 0.0, 0.0)), &math::vec3((0.0, 1.0, 0.0)));
+
+    /*-------------------------------CREATE SHADERS------------------------------*/
+    let shader_programme = create_programme_from_files(&logger, VERTEX_SHADER_FILE, FRAGMENT_SHADER_FILE);
+    let scene_shader = SceneShader::new(shader_programme);
+
+    /*--------------------------------LOAD SCENE---------------------------------*/
+    let mut scene = match Scene::load(SCENE_FILE) {
+        Ok(scene) => {
+            logger.log(&format!("loaded {} objects from {}", scene.objects.len(), SCENE_FILE));
+            scene
+        }
+        Err(e) => {
+            logger.log_err(&e);
+            logger.log("starting with an empty scene");
+            Scene::new()
+        }
+    };
+    let mut selected: Option<usize> = None;
+    let mut gizmo: Option<Gizmo> = None;
+    let mut primitive = Primitive::Cube;
+    let mut snapping = true;
+
+    /*-------------------------------CREATE CAMERA-------------------------------*/
+    // the letter keys edit the scene, so the camera walks with the arrow keys
+    let mut controller = CameraController::new(math::vec3((0.0, EYE_HEIGHT, 10.0)), 0.0, -15.0, 67.0, ControlKeys {
+        forward: Key::Up, back: Key::Down, step_left: Key::Comma, step_right: Key::Period,
+        turn_left: Key::Left, turn_right: Key::Right, look_up: Key::PageUp, look_down: Key::PageDown,
+    });
+    logger.log("keys: click the ground to place an object, and click an object to select it;");
+    logger.log("      Tab changes the shape placed, and N turns snapping to the grid on and off;");
+    logger.log("      G, R, and S move, rotate, and scale the selected object, and X, Y, and Z constrain it to an axis;");
+    logger.log("      Delete removes the selected object; Ctrl+S saves the scene, and Ctrl+O loads it again;");
+    logger.log("      the arrow keys walk and turn, comma and period step sideways, and Page Up and Page Down look;");
+    logger.log("      F5 saves the camera to camera.txt, and F9 restores it");
+
+    // input variables
+    let near = 0.1;                                  // clipping plane
+    let far = 100.0;                                 // clipping plane
+
+    // what the editor is doing, drawn in the corner
+    let mut overlay = Overlay::new(&logger);
+
+    while !context.window.should_close() {
+        let current_seconds = context.glfw.get_time();
+        let elapsed_seconds = current_seconds - context.elapsed_time_seconds;
+        context.elapsed_time_seconds = current_seconds;
+
+        update_fps_counter(&mut context);
+
+        let aspect = context.width as f32 / u32::max(context.height, 1) as f32;
+        let proj_mat = controller.camera.proj_mat(aspect, near, far);
+        let view_mat = controller.camera.view_mat();
+        let (cursor, ray) = cursor_ray(&context, &controller, &proj_mat);
+
+        // the object being changed follows the cursor
+        if let (Some(ref gizmo), Some(i)) = (gizmo, selected) {
+            if let Some(transform) = gizmo.drag(&ray, cursor, snapping) {
+                scene.objects[i].transform = transform;
+            }
+        }
+
+        unsafe {
+            gl::Viewport(0, 0, context.width as i32, context.height as i32);
+            gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
+        }
+        scene_shader.set_camera(&view_mat, &proj_mat);
+        scene_shader.draw(meshes.ground_vao, meshes.ground_point_count, &Mat4::identity(), &GROUND_COLOUR);
+        for (i, object) in scene.objects.iter().enumerate() {
+            if selected == Some(i) {
+                meshes.draw_object(&scene_shader, object, &highlight(&object.colour));
+            } else {
+                meshes.draw_object(&scene_shader, object, &object.colour);
+            }
+        }
+        if let Some(i) = selected {
+            let active_axis = gizmo.and_then(|gizmo| gizmo.axis);
+            meshes.draw_handles(&scene_shader, &scene.objects[i], active_axis);
+        }
+
+        label(&mut overlay, &describe_state(&scene, selected, &gizmo, primitive, snapping));
+        overlay.draw(context.width, context.height);
+
+        context.glfw.poll_events();
+
+        let mut clicked = false;
+        let mut cancel = false;
+        let mut toggle_fullscreen = false;
+        let mut resized_to = None;
+        for (_, event) in glfw::flush_messages(&context.events) {
+            match event {
+                glfw::WindowEvent::FramebufferSize(width, height) => {
+                    resized_to = Some((width, height));
+                }
+                glfw::WindowEvent::MouseButton(MouseButton::Button1, Action::Press, _) => {
+                    clicked = true;
+                }
+                glfw::WindowEvent::MouseButton(MouseButton::Button2, Action::Press, _) => {
+                    cancel = true;
+                }
+                glfw::WindowEvent::Key(Key::S, _, Action::Press, mods) if mods.contains(glfw::Modifiers::Control) => {
+                    match scene.save(SCENE_FILE) {
+                        Ok(_) => {
+                            logger.log(&format!("saved {} objects to {}", scene.objects.len(), SCENE_FILE));
+                        }
+                        Err(e) => {
+                            logger.log_err(&e);
+                        }
+                    }
+                }
+                glfw::WindowEvent::Key(Key::O, _, Action::Press, mods) if mods.contains(glfw::Modifiers::Control) => {
+                    match Scene::load(SCENE_FILE) {
+                        Ok(loaded) => {
+                            logger.log(&format!("loaded {} objects from {}", loaded.objects.len(), SCENE_FILE));
+                            scene = loaded;
+                            selected = None;
+                            gizmo = None;
+                        }
+                        Err(e) => {
+                            logger.log_err(&e);
+                        }
+                    }
+                }
+                glfw::WindowEvent::Key(key @ Key::G, _, Action::Press, _) |
+                glfw::WindowEvent::Key(key @ Key::R, _, Action::Press, _) |
+                glfw::WindowEvent::Key(key @ Key::S, _, Action::Press, _) => {
+                    if let Some(i) = selected {
+                        // switching modes starts over from where the object was
+                        if let Some(previous) = gizmo {
+                            scene.objects[i].transform = previous.start();
+                        }
+                        let mode = match key {
+                            Key::G => GizmoMode::Move,
+                            Key::R => GizmoMode::Rotate,
+                            _ => GizmoMode::Scale,
+                        };
+                        gizmo = Some(Gizmo::begin(mode, scene.objects[i].transform, cursor, ray));
+                        logger.log(&format!("{} object {}", mode.name(), i));
+                    }
+                }
+                glfw::WindowEvent::Key(key @ Key::X, _, Action::Press, _) |
+                glfw::WindowEvent::Key(key @ Key::Y, _, Action::Press, _) |
+                glfw::WindowEvent::Key(key @ Key::Z, _, Action::Press, _) => {
+                    if let Some(ref mut gizmo) = gizmo {
+                        let axis = match key {
+                            Key::X => Axis::X,
+                            Key::Y => Axis::Y,
+                            _ => Axis::Z,
+                        };
+                        gizmo.constrain(axis);
+                    }
+                }
+                glfw::WindowEvent::Key(Key::Enter, _, Action::Press, mods) => {
+                    if mods.contains(glfw::Modifiers::Alt) {
+                        toggle_fullscreen = true;
+                    } else if gizmo.is_some() {
+                        clicked = true;
+                    }
+                }
+                glfw::WindowEvent::Key(Key::Escape, _, Action::Press, _) => {
+                    if gizmo.is_some() {
+                        cancel = true;
+                    } else {
+                        context.window.set_should_close(true);
+                    }
+                }
+                glfw::WindowEvent::Key(Key::Delete, _, Action::Press, _) |
+                glfw::WindowEvent::Key(Key::Backspace, _, Action::Press, _) => {
+                    if let (Some(i), None) = (selected, gizmo) {
+                        scene.remove(i);
+                        selected = None;
+                        logger.log(&format!("removed object {}", i));
+                    }
+                }
+                glfw::WindowEvent::Key(Key::Tab, _, Action::Press, _) => {
+                    primitive = primitive.next();
+                    logger.log(&format!("placing: {}", primitive.name()));
+                }
+                glfw::WindowEvent::Key(Key::N, _, Action::Press, _) => {
+                    snapping = !snapping;
+                    logger.log(&format!("snapping: {}", snapping));
+                }
+                glfw::WindowEvent::Key(Key::F5, _, Action::Press, _) => {
+                    controller.camera.save_to_camera_file();
+                }
+                glfw::WindowEvent::Key(Key::F9, _, Action::Press, _) => {
+                    if let Some(camera) = Camera::restore_from_camera_file() {
+                        controller.set_camera(&camera);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        if cancel {
+            if let (Some(previous), Some(i)) = (gizmo, selected) {
+                scene.objects[i].transform = previous.start();
+                logger.log("cancelled");
+            }
+            gizmo = None;
+        } else if clicked {
+            if gizmo.is_some() {
+                // the object keeps the transform it was dragged to
+                gizmo = None;
+            } else if let Some(i) = scene.pick(&ray) {
+                selected = Some(i);
+            } else if let Some(point) = picking::ray_plane(&ray, &ground_plane) {
+                // stand the new object on the ground where the click landed
+                let mut position = point + math::vec3((0.0, 0.5, 0.0));
+                if snapping {
+                    position.v[0] = gizmo::snap(position.v[0], gizmo::GRID_STEP);
+                    position.v[2] = gizmo::snap(position.v[2], gizmo::GRID_STEP);
+                }
+                let colour = OBJECT_COLOURS[scene.objects.len() % OBJECT_COLOURS.len()];
+                let i = scene.add(SceneObject::new(primitive, position, colour));
+                selected = Some(i);
+                logger.log(&format!(
+                    "placed {} {} at ({:.2}, {:.2}, {:.2})", primitive.name(), i, position.v[0], position.v[1], position.v[2]
+                ));
+            } else {
+                selected = None;
+            }
+        }
+
+        if let Some((width, height)) = resized_to {
+            glfw_framebuffer_size_callback(&mut context, width as u32, height as u32);
+            logger.log(&format!("framebuffer resized to {}x{}", width, height));
+        }
+
+        if toggle_fullscreen {
+            let fullscreen = context.toggle_fullscreen();
+            logger.log(&format!("fullscreen: {}", fullscreen));
+            let (width, height) = context.window.get_framebuffer_size();
+            glfw_framebuffer_size_callback(&mut context, width as u32, height as u32);
+        }
+
+        // control keys
+        controller.update(&context.window, elapsed_seconds);
+
+        // Put the stuff we've been drawing onto the display.
+        context.window.swap_buffers();
+    }
+}
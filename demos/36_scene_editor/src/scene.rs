@@ -0,0 +1,296 @@
+use serde_json;
+
+use graphics_math as math;
+use math::{Mat4, Vec3};
+
+use picking::{self, Ray};
+
+use std::fs::File;
+use std::io::{Read, Write};
+
+
+///
+/// The shapes that can be placed in the scene. Each one fits inside a unit cube
+/// centred on its origin.
+///
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Primitive {
+    Cube,
+    Sphere,
+}
+
+impl Primitive {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Primitive::Cube => "cube",
+            Primitive::Sphere => "sphere",
+        }
+    }
+
+    pub fn next(&self) -> Primitive {
+        match *self {
+            Primitive::Cube => Primitive::Sphere,
+            Primitive::Sphere => Primitive::Cube,
+        }
+    }
+
+    ///
+    /// The radius of the smallest sphere around the unscaled shape.
+    ///
+    fn bounding_radius(&self) -> f32 {
+        match *self {
+            Primitive::Cube => 0.5 * f32::sqrt(3.0),
+            Primitive::Sphere => 0.5,
+        }
+    }
+}
+
+///
+/// Where an object is, how it is turned, and how big it is. The rotation is in
+/// degrees about the x, y, and z axes, applied in that order, after scaling and
+/// before moving the object into place.
+///
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Transform {
+    pub position: [f32; 3],
+    pub rotation: [f32; 3],
+    pub scale: [f32; 3],
+}
+
+impl Transform {
+    pub fn new(position: Vec3) -> Transform {
+        Transform {
+            position: [position.v[0], position.v[1], position.v[2]],
+            rotation: [0.0, 0.0, 0.0],
+            scale: [1.0, 1.0, 1.0],
+        }
+    }
+
+    pub fn position(&self) -> Vec3 {
+        math::vec3((self.position[0], self.position[1], self.position[2]))
+    }
+
+    pub fn model_mat(&self) -> Mat4 {
+        Mat4::from_scale(&math::vec3((self.scale[0], self.scale[1], self.scale[2])))
+            .rotate_x_deg(self.rotation[0])
+            .rotate_y_deg(self.rotation[1])
+            .rotate_z_deg(self.rotation[2])
+            .then_translate(&self.position())
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SceneObject {
+    pub primitive: Primitive,
+    pub transform: Transform,
+    pub colour: [f32; 3],
+}
+
+impl SceneObject {
+    pub fn new(primitive: Primitive, position: Vec3, colour: [f32; 3]) -> SceneObject {
+        SceneObject {
+            primitive: primitive,
+            transform: Transform::new(position),
+            colour: colour,
+        }
+    }
+
+    ///
+    /// The radius of a sphere around the object's position that the whole object
+    /// fits inside, however it is turned. Clicks are tested against this sphere.
+    ///
+    pub fn bounding_radius(&self) -> f32 {
+        let scale = self.transform.scale;
+        let largest_scale = f32::max(f32::max(scale[0].abs(), scale[1].abs()), scale[2].abs());
+
+        self.primitive.bounding_radius() * largest_scale
+    }
+}
+
+///
+/// The objects placed in the editor, in the order they were placed. The scene is
+/// saved as JSON, so a layout can be written or tweaked by hand too.
+///
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Scene {
+    pub objects: Vec<SceneObject>,
+}
+
+impl Scene {
+    pub fn new() -> Scene {
+        Scene { objects: vec![] }
+    }
+
+    ///
+    /// Add an object to the scene, and return its index.
+    ///
+    pub fn add(&mut self, object: SceneObject) -> usize {
+        self.objects.push(object);
+        self.objects.len() - 1
+    }
+
+    ///
+    /// Take an object out of the scene. The objects after it move down one place.
+    ///
+    pub fn remove(&mut self, index: usize) -> Option<SceneObject> {
+        if index >= self.objects.len() {
+            return None;
+        }
+
+        Some(self.objects.remove(index))
+    }
+
+    ///
+    /// The nearest object the ray passes through, if any, going by each object's
+    /// bounding sphere.
+    ///
+    pub fn pick(&self, ray: &Ray) -> Option<usize> {
+        let mut closest = None;
+        let mut closest_distance = 0.0;
+        for (i, object) in self.objects.iter().enumerate() {
+            if let Some(distance) = picking::ray_sphere(ray, object.transform.position(), object.bounding_radius()) {
+                if closest.is_none() || distance < closest_distance {
+                    closest = Some(i);
+                    closest_distance = distance;
+                }
+            }
+        }
+
+        closest
+    }
+
+    pub fn to_json(&self) -> Result<String, String> {
+        match serde_json::to_string_pretty(self) {
+            Ok(text) => Ok(text),
+            Err(e) => Err(format!("ERROR: could not write scene as JSON: {}", e)),
+        }
+    }
+
+    ///
+    /// Read a scene back from JSON written by `to_json`.
+    ///
+    pub fn from_json(text: &str) -> Result<Scene, String> {
+        match serde_json::from_str(text) {
+            Ok(scene) => Ok(scene),
+            Err(e) => Err(format!("ERROR: invalid scene JSON: {}", e)),
+        }
+    }
+
+    ///
+    /// Save the scene to a file.
+    ///
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        let text = match self.to_json() {
+            Ok(val) => val,
+            Err(e) => return Err(e),
+        };
+        let mut file = match File::create(path) {
+            Ok(handle) => handle,
+            Err(e) => return Err(format!("ERROR: could not create scene file {}: {}", path, e)),
+        };
+
+        match file.write_all(text.as_bytes()) {
+            Ok(_) => Ok(()),
+            Err(e) => Err(format!("ERROR: could not write scene file {}: {}", path, e)),
+        }
+    }
+
+    ///
+    /// Load a scene previously written by `save`.
+    ///
+    pub fn load(path: &str) -> Result<Scene, String> {
+        let mut file = match File::open(path) {
+            Ok(handle) => handle,
+            Err(_) => return Err(format!("ERROR: file not found: {}", path)),
+        };
+
+        let mut text = String::new();
+        if let Err(e) = file.read_to_string(&mut text) {
+            return Err(format!("ERROR: could not read scene file {}: {}", path, e));
+        }
+
+        Scene::from_json(&text)
+    }
+}
+
+mod scene_tests {
+    use super::{Primitive, Scene, SceneObject};
+    use graphics_math as math;
+    use picking::Ray;
+
+    fn scene() -> Scene {
+        let mut scene = Scene::new();
+        scene.add(SceneObject::new(Primitive::Cube, math::vec3((1.0, 0.5, -2.0)), [0.8, 0.2, 0.2]));
+        let mut sphere = SceneObject::new(Primitive::Sphere, math::vec3((-3.0, 1.0, 0.0)), [0.2, 0.2, 0.8]);
+        sphere.transform.rotation = [0.0, 45.0, 0.0];
+        sphere.transform.scale = [2.0, 2.0, 2.0];
+        scene.add(sphere);
+
+        scene
+    }
+
+    #[test]
+    fn test_scene_json_round_trip() {
+        let scene = scene();
+
+        let result = Scene::from_json(&scene.to_json().unwrap()).unwrap();
+
+        assert_eq!(result, scene);
+    }
+
+    #[test]
+    fn test_the_sample_scene_loads() {
+        let scene = Scene::load("src/scene.json").unwrap();
+
+        assert_eq!(scene.objects.len(), 3);
+        assert_eq!(scene.objects[1].primitive, Primitive::Sphere);
+    }
+
+    #[test]
+    fn test_invalid_json_is_an_error() {
+        assert!(Scene::from_json("{ \"objects\": [ { \"primitive\": \"Teapot\" } ] }").is_err());
+        assert!(Scene::from_json("not a scene").is_err());
+    }
+
+    #[test]
+    fn test_removing_an_object_shifts_the_rest_down() {
+        let mut scene = scene();
+
+        assert_eq!(scene.remove(0).map(|object| object.primitive), Some(Primitive::Cube));
+        assert_eq!(scene.objects[0].primitive, Primitive::Sphere);
+        assert_eq!(scene.remove(1), None);
+    }
+
+    #[test]
+    fn test_picking_finds_the_nearest_object_along_the_ray() {
+        let mut scene = scene();
+        scene.add(SceneObject::new(Primitive::Sphere, math::vec3((1.0, 0.5, 2.0)), [0.2, 0.8, 0.2]));
+        let ray = Ray::new(math::vec3((1.0, 0.5, 10.0)), math::vec3((0.0, 0.0, -1.0)));
+
+        assert_eq!(scene.pick(&ray), Some(2));
+        let miss = Ray::new(math::vec3((10.0, 0.5, 10.0)), math::vec3((0.0, 0.0, -1.0)));
+        assert_eq!(scene.pick(&miss), None);
+    }
+
+    #[test]
+    fn test_the_bounding_radius_grows_with_the_largest_scale() {
+        let mut cube = SceneObject::new(Primitive::Cube, math::vec3((0.0, 0.0, 0.0)), [1.0, 1.0, 1.0]);
+        cube.transform.scale = [1.0, 4.0, 2.0];
+
+        assert!(f32::abs(cube.bounding_radius() - 2.0 * f32::sqrt(3.0)) < 1e-5);
+    }
+
+    #[test]
+    fn test_the_model_matrix_scales_then_turns_then_moves() {
+        let mut object = SceneObject::new(Primitive::Cube, math::vec3((1.0, 2.0, 3.0)), [1.0, 1.0, 1.0]);
+        object.transform.rotation = [0.0, 90.0, 0.0];
+        object.transform.scale = [2.0, 1.0, 1.0];
+
+        // the corner at +x is stretched to 2, turned round to -z, then moved
+        let corner = object.transform.model_mat() * math::vec4((1.0, 0.0, 0.0, 1.0));
+
+        assert!(f32::abs(corner.v[0] - 1.0) < 1e-5, "{:?}", corner);
+        assert!(f32::abs(corner.v[1] - 2.0) < 1e-5, "{:?}", corner);
+        assert!(f32::abs(corner.v[2] - 1.0) < 1e-5, "{:?}", corner);
+    }
+}
@@ -0,0 +1,12 @@
+#version 410
+
+in vec3 n_world;
+uniform vec3 colour;
+out vec4 frag_colour;
+
+const vec3 light_dir_world = normalize (vec3 (0.4, 1.0, 0.3));
+
+void main () {
+	float diffuse = max (dot (normalize (n_world), light_dir_world), 0.0);
+	frag_colour = vec4 (colour * (0.3 + 0.7 * diffuse), 1.0);
+}
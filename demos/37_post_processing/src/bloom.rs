@@ -0,0 +1,303 @@
+use gl;
+use gl::types::{GLint, GLuint};
+
+use fullscreen_pass::FullscreenPass;
+use gpu_timer::GpuTimer;
+use render_target::{RenderTarget, TargetFormat};
+use gl_utils::uniform_location;
+
+
+///
+/// How bright a pixel has to be, by luminance, before it starts to glow.
+///
+pub const DEFAULT_BLOOM_THRESHOLD: f32 = 0.7;
+///
+/// How strongly the blurred glow is added back over the frame.
+///
+pub const DEFAULT_BLOOM_INTENSITY: f32 = 1.0;
+pub const MAX_BLOOM_INTENSITY: f32 = 4.0;
+///
+/// The number of texels the blur reaches out to either side of a pixel. The
+/// shader's weights array has one more entry than this, for the pixel itself.
+///
+pub const BLUR_RADIUS: usize = 6;
+///
+/// The standard deviation of the blur, in texels of the half size bloom targets.
+///
+pub const BLUR_SIGMA: f32 = 3.0;
+
+
+///
+/// The weights of a one dimensional Gaussian blur, from the centre texel out to
+/// `radius` texels away. Each weight but the centre one is used on both sides, and
+/// the weights are scaled so that all of them together add up to one, so blurring
+/// neither brightens nor darkens the image.
+///
+pub fn gaussian_weights(radius: usize, sigma: f32) -> Vec<f32> {
+    let mut weights: Vec<f32> = (0..(radius + 1))
+        .map(|i| f32::exp(-((i * i) as f32) / (2.0 * sigma * sigma)))
+        .collect();
+    let total = weights[0] + 2.0 * weights[1..].iter().sum::<f32>();
+    for weight in weights.iter_mut() {
+        *weight /= total;
+    }
+
+    weights
+}
+
+///
+/// The luminance of a linear colour, weighted for how bright each channel looks.
+///
+pub fn luminance(colour: [f32; 3]) -> f32 {
+    0.2126 * colour[0] + 0.7152 * colour[1] + 0.0722 * colour[2]
+}
+
+///
+/// How much of a colour with the given luminance the bright pass keeps: nothing
+/// at or below the threshold, and only the part above it beyond, so the glow
+/// fades in rather than switching on. The same sum as `bloom_bright_fs.glsl`.
+///
+pub fn bright_pass_scale(luminance: f32, threshold: f32) -> f32 {
+    if luminance <= 0.0 {
+        return 0.0;
+    }
+
+    f32::max(luminance - threshold, 0.0) / luminance
+}
+
+///
+/// The settings of the bloom that can be changed while the demo runs.
+///
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct BloomSettings {
+    pub enabled: bool,
+    pub threshold: f32,
+    pub intensity: f32,
+}
+
+impl BloomSettings {
+    pub fn new() -> BloomSettings {
+        BloomSettings {
+            enabled: true,
+            threshold: DEFAULT_BLOOM_THRESHOLD,
+            intensity: DEFAULT_BLOOM_INTENSITY,
+        }
+    }
+
+    ///
+    /// Raise or lower the threshold, between 0 where everything glows and 1
+    /// where nothing the scene draws does.
+    ///
+    pub fn change_threshold(&mut self, step: f32) {
+        self.threshold = f32::min(f32::max(self.threshold + step, 0.0), 1.0);
+    }
+
+    pub fn change_intensity(&mut self, step: f32) {
+        self.intensity = f32::min(f32::max(self.intensity + step, 0.0), MAX_BLOOM_INTENSITY);
+    }
+
+    ///
+    /// The intensity to composite the glow with, which is none while the bloom
+    /// is off.
+    ///
+    pub fn composite_intensity(&self) -> f32 {
+        if self.enabled { self.intensity } else { 0.0 }
+    }
+
+    pub fn describe(&self) -> String {
+        if !self.enabled {
+            return String::from("bloom: off");
+        }
+
+        format!("bloom: threshold {:.2}, intensity {:.2}", self.threshold, self.intensity)
+    }
+}
+
+///
+/// The size of the bloom targets for a frame `width` by `height` pixels. The glow
+/// is blurry anyway, so half the size in each direction loses nothing that shows
+/// and makes the blur reach twice as far for the same number of taps.
+///
+pub fn bloom_size(width: u32, height: u32) -> (u32, u32) {
+    (u32::max(width / 2, 1), u32::max(height / 2, 1))
+}
+
+///
+/// Makes a frame glow around its brightest parts. A bright pass keeps what is
+/// over the threshold at half size, then a separable Gaussian blur spreads it out,
+/// first across and then down, ping-ponging between two render targets. The
+/// post-processing pass adds the result back over the frame. Each pass is timed on
+/// the GPU.
+///
+pub struct Bloom {
+    bright_sp: GLuint,
+    blur_sp: GLuint,
+    pass: FullscreenPass,
+    threshold_location: GLint,
+    direction_location: GLint,
+    targets: [RenderTarget; 2],
+    bright_timer: GpuTimer,
+    blur_across_timer: GpuTimer,
+    blur_down_timer: GpuTimer,
+    pub settings: BloomSettings,
+}
+
+impl Bloom {
+    ///
+    /// Set up the bloom for a frame `width` by `height` pixels, with the bright
+    /// pass and blur shader programmes.
+    ///
+    pub fn new(bright_sp: GLuint, blur_sp: GLuint, width: u32, height: u32) -> Result<Bloom, String> {
+        let location = |sp: GLuint, name: &str| {
+            let location = uniform_location(sp, name);
+            assert!(location > -1);
+            location
+        };
+        let weights = gaussian_weights(BLUR_RADIUS, BLUR_SIGMA);
+        unsafe {
+            gl::UseProgram(bright_sp);
+            gl::Uniform1i(location(bright_sp, "colour_tex"), 0);
+            gl::UseProgram(blur_sp);
+            gl::Uniform1i(location(blur_sp, "image"), 0);
+            gl::Uniform1fv(location(blur_sp, "weights"), weights.len() as i32, weights.as_ptr());
+        }
+
+        let (bloom_width, bloom_height) = bloom_size(width, height);
+        let first = match RenderTarget::with_formats(bloom_width, bloom_height, &[TargetFormat::Rgba16F]) {
+            Ok(val) => val,
+            Err(e) => return Err(e),
+        };
+        let second = match RenderTarget::with_formats(bloom_width, bloom_height, &[TargetFormat::Rgba16F]) {
+            Ok(val) => val,
+            Err(e) => return Err(e),
+        };
+
+        Ok(Bloom {
+            bright_sp: bright_sp,
+            blur_sp: blur_sp,
+            pass: FullscreenPass::new(),
+            threshold_location: location(bright_sp, "threshold"),
+            direction_location: location(blur_sp, "direction"),
+            targets: [first, second],
+            bright_timer: GpuTimer::new(),
+            blur_across_timer: GpuTimer::new(),
+            blur_down_timer: GpuTimer::new(),
+            settings: BloomSettings::new(),
+        })
+    }
+
+    ///
+    /// Follow the frame to a new size.
+    ///
+    pub fn resize(&mut self, width: u32, height: u32) -> Result<(), String> {
+        let (bloom_width, bloom_height) = bloom_size(width, height);
+        for target in self.targets.iter_mut() {
+            if let Err(e) = target.resize(bloom_width, bloom_height) {
+                return Err(e);
+            }
+        }
+
+        Ok(())
+    }
+
+    ///
+    /// The blurred glow, as left by the last `apply`.
+    ///
+    pub fn texture(&self) -> GLuint {
+        self.targets[0].colour_texture()
+    }
+
+    ///
+    /// Run the bright pass and the blur over the frame in `colour_tex`. Does
+    /// nothing while the bloom is off. The caller has to restore the framebuffer
+    /// and viewport afterwards.
+    ///
+    pub fn apply(&mut self, colour_tex: GLuint) {
+        if !self.settings.enabled {
+            return;
+        }
+
+        self.bright_timer.begin();
+        self.targets[0].bind();
+        unsafe {
+            gl::UseProgram(self.bright_sp);
+            gl::Uniform1f(self.threshold_location, self.settings.threshold);
+        }
+        self.pass.draw(self.bright_sp, &[colour_tex]);
+        self.bright_timer.end();
+
+        let (width, height) = self.targets[0].size();
+        let steps = [
+            (1.0 / width as f32, 0.0),
+            (0.0, 1.0 / height as f32),
+        ];
+        for (i, &(step_x, step_y)) in steps.iter().enumerate() {
+            let (from, to) = if i == 0 { (0, 1) } else { (1, 0) };
+            let timer = if i == 0 { &mut self.blur_across_timer } else { &mut self.blur_down_timer };
+            timer.begin();
+            self.targets[to].bind();
+            unsafe {
+                gl::UseProgram(self.blur_sp);
+                gl::Uniform2f(self.direction_location, step_x, step_y);
+            }
+            self.pass.draw(self.blur_sp, &[self.targets[from].colour_texture()]);
+            timer.end();
+        }
+        self.targets[0].unbind();
+    }
+
+    ///
+    /// The GPU time of each pass in milliseconds, a few frames behind.
+    ///
+    pub fn pass_times_ms(&mut self) -> [(&'static str, f64); 3] {
+        [
+            ("bright", self.bright_timer.elapsed_ms()),
+            ("blur x", self.blur_across_timer.elapsed_ms()),
+            ("blur y", self.blur_down_timer.elapsed_ms()),
+        ]
+    }
+}
+
+mod bloom_tests {
+    use super::{bloom_size, bright_pass_scale, gaussian_weights, luminance, BloomSettings, MAX_BLOOM_INTENSITY};
+
+    #[test]
+    fn test_the_blur_weights_add_up_to_one_and_fall_away_from_the_centre() {
+        let weights = gaussian_weights(6, 3.0);
+
+        assert_eq!(weights.len(), 7);
+        let total = weights[0] + 2.0 * weights[1..].iter().sum::<f32>();
+        assert!(f32::abs(total - 1.0) < 1e-5);
+        for pair in weights.windows(2) {
+            assert!(pair[0] > pair[1]);
+        }
+    }
+
+    #[test]
+    fn test_the_bright_pass_keeps_only_what_is_over_the_threshold() {
+        assert_eq!(bright_pass_scale(0.5, 0.7), 0.0);
+        assert_eq!(bright_pass_scale(0.0, 0.0), 0.0);
+        assert!(f32::abs(bright_pass_scale(1.0, 0.7) - 0.3) < 1e-6);
+        // white has a luminance of one
+        assert!(f32::abs(luminance([1.0, 1.0, 1.0]) - 1.0) < 1e-6);
+    }
+
+    #[test]
+    fn test_the_settings_stay_in_range_and_turn_the_glow_off() {
+        let mut settings = BloomSettings::new();
+        settings.change_threshold(5.0);
+        settings.change_intensity(100.0);
+
+        assert_eq!(settings.threshold, 1.0);
+        assert_eq!(settings.intensity, MAX_BLOOM_INTENSITY);
+        settings.enabled = false;
+        assert_eq!(settings.composite_intensity(), 0.0);
+        assert_eq!(settings.describe(), "bloom: off");
+    }
+
+    #[test]
+    fn test_the_bloom_targets_are_half_size_but_never_empty() {
+        assert_eq!(bloom_size(1280, 720), (640, 360));
+        assert_eq!(bloom_size(1, 0), (1, 1));
+    }
+}
@@ -0,0 +1,24 @@
+#version 410
+
+// keep in step with BLUR_RADIUS in bloom.rs
+#define BLUR_RADIUS 6
+
+in vec2 st;
+
+uniform sampler2D image;
+// the gaussian weights from the centre texel outwards, adding up to one over
+// both sides.
+uniform float weights[BLUR_RADIUS + 1];
+// one texel across or one texel down: the blur runs in one direction per pass.
+uniform vec2 direction;
+
+out vec4 frag_colour;
+
+void main() {
+	vec3 sum = texture (image, st).rgb * weights[0];
+	for (int i = 1; i <= BLUR_RADIUS; i++) {
+		sum += texture (image, st + float (i) * direction).rgb * weights[i];
+		sum += texture (image, st - float (i) * direction).rgb * weights[i];
+	}
+	frag_colour = vec4 (sum, 1.0);
+}
@@ -0,0 +1,32 @@
+#version 410
+
+in vec2 st;
+
+// the frame the scene was drawn into, at twice the size of the target.
+uniform sampler2D colour_tex;
+// the luminance a pixel has to be over before it starts to glow.
+uniform float threshold;
+
+out vec4 frag_colour;
+
+float luminance (vec3 colour) {
+	return dot (colour, vec3 (0.2126, 0.7152, 0.0722));
+}
+
+void main() {
+	// each pixel here covers two by two pixels of the frame, so average them all
+	// rather than keep one and drop the other three.
+	vec2 texel = 1.0 / vec2 (textureSize (colour_tex, 0));
+	vec3 colour = 0.25 * (
+		texture (colour_tex, st + vec2 (-0.5, -0.5) * texel).rgb +
+		texture (colour_tex, st + vec2 (0.5, -0.5) * texel).rgb +
+		texture (colour_tex, st + vec2 (-0.5, 0.5) * texel).rgb +
+		texture (colour_tex, st + vec2 (0.5, 0.5) * texel).rgb
+	);
+
+	// keep only the part over the threshold, so the glow fades in. the same sum
+	// as bright_pass_scale in bloom.rs.
+	float l = luminance (colour);
+	float scale = l > 0.0 ? max (l - threshold, 0.0) / l : 0.0;
+	frag_colour = vec4 (colour * scale, 1.0);
+}
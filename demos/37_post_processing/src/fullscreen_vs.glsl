@@ -0,0 +1,14 @@
+#version 410
+
+// one triangle big enough to cover the whole viewport, with its corners at
+// (-1, -1), (3, -1), and (-1, 3) in clip space. the corners are made from the
+// vertex number, so no vertex buffer is needed. the same sum as corner in
+// fullscreen_pass.rs.
+out vec2 st;
+
+void main() {
+	vec2 corner = vec2 (float ((gl_VertexID & 1) << 2) - 1.0, float ((gl_VertexID & 2) << 1) - 1.0);
+	// 0 at the bottom-left of the viewport and 1 at the top-right.
+	st = 0.5 * corner + 0.5;
+	gl_Position = vec4 (corner, 0.0, 1.0);
+}
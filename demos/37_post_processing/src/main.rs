@@ -0,0 +1,590 @@
+extern crate gl;
+extern crate glfw;
+extern crate chrono;
+extern crate stb_image;
+#[cfg(feature = "glam")]
+extern crate glam;
+#[cfg(feature = "cgmath")]
+extern crate cgmath;
+
+#[macro_use]
+mod gl_utils;
+mod graphics_math;
+mod obj_parser;
+mod colour;
+mod assets;
+mod shader_include;
+mod spirv;
+mod program_cache;
+mod logger;
+mod overlay;
+mod streaming_buffer;
+mod camera;
+mod camera_controller;
+mod render_target;
+mod post_process;
+mod vertex_layout;
+mod fullscreen_pass;
+mod gpu_timer;
+mod perf_stats;
+mod bloom;
+mod ssao;
+
+
+use glfw::{Action, Context, Key};
+use gl::types::{GLfloat, GLint, GLuint};
+
+use std::process;
+use std::time::Instant;
+
+use gl_utils::*;
+
+use graphics_math as math;
+use math::Mat4;
+
+use bloom::Bloom;
+use camera::Camera;
+use camera_controller::{CameraController, ControlKeys};
+use gpu_timer::GpuTimer;
+use overlay::Overlay;
+use perf_stats::{PerfStats, PERF_STATS_HISTORY};
+use post_process::{PostProcess, GBUFFER_FORMATS, NORMAL_ATTACHMENT};
+use render_target::{RenderTarget, TargetFormat};
+use ssao::Ssao;
+use vertex_layout::VertexLayout;
+use fullscreen_pass::FULLSCREEN_VS_FILE;
+
+
+const GL_LOG_FILE: &str = "gl.log";
+const VERTEX_SHADER_FILE: &str = "src/scene_vs.glsl";
+const FRAGMENT_SHADER_FILE: &str = "src/scene_fs.glsl";
+const POST_FS_FILE: &str = "src/post_fs.glsl";
+const OVERDRAW_FS_FILE: &str = "src/overdraw_fs.glsl";
+const BLOOM_BRIGHT_FS_FILE: &str = "src/bloom_bright_fs.glsl";
+const BLOOM_BLUR_FS_FILE: &str = "src/bloom_blur_fs.glsl";
+const SSAO_FS_FILE: &str = "src/ssao_fs.glsl";
+const SSAO_BLUR_FS_FILE: &str = "src/ssao_blur_fs.glsl";
+
+const GROUND_HALF_SIZE: f32 = 40.0;
+const EYE_HEIGHT: f32 = 2.0;
+// The scene is rows of shapes marching off into the distance, so the depth view
+// has something to show at every distance.
+const ROW_COUNT: usize = 12;
+const ROW_SPACING: f32 = 3.0;
+const COLUMN_COUNT: usize = 5;
+const COLUMN_SPACING: f32 = 2.5;
+// How far each key press scales the depth view out or in.
+const DEPTH_RANGE_STEP: f32 = 5.0;
+// How far each key press moves the bloom's threshold and intensity.
+const BLOOM_THRESHOLD_STEP: f32 = 0.05;
+const BLOOM_INTENSITY_STEP: f32 = 0.25;
+// How far each key press moves the reach of the ambient occlusion.
+const SSAO_RADIUS_STEP: f32 = 0.1;
+
+const SKY_COLOUR: [f32; 4] = [0.6, 0.7, 0.8, 1.0];
+const GROUND_COLOUR: [f32; 3] = [0.35, 0.45, 0.3];
+const OBJECT_COLOURS: [[f32; 3]; 5] = [
+    [0.8, 0.3, 0.2], [0.2, 0.5, 0.8], [0.9, 0.7, 0.2], [0.5, 0.3, 0.7], [0.3, 0.7, 0.6],
+];
+
+const OVERLAY_TEXT_COLOUR: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+const OVERLAY_BACKGROUND_COLOUR: [f32; 4] = [0.0, 0.0, 0.0, 0.6];
+
+
+///
+/// The ground plane: two triangles facing up, with each position followed by
+/// its normal.
+///
+fn ground_vertices() -> Vec<GLfloat> {
+    let a = GROUND_HALF_SIZE;
+    let corners = [(-a, a), (a, a), (a, -a), (a, -a), (-a, -a), (-a, a)];
+    let mut vertices = Vec::with_capacity(6 * corners.len());
+    for &(x, z) in corners.iter() {
+        vertices.extend_from_slice(&[x, 0.0, z, 0.0, 1.0, 0.0]);
+    }
+
+    vertices
+}
+
+///
+/// A unit cube centred on the origin, with each position followed by its normal.
+///
+fn cube_vertices() -> Vec<GLfloat> {
+    // The normal of each face, and two axes across it whose cross product is the
+    // normal, so the triangles wind counter-clockwise seen from outside.
+    let faces = [
+        ([1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]),
+        ([-1.0, 0.0, 0.0], [0.0, 0.0, 1.0], [0.0, 1.0, 0.0]),
+        ([0.0, 1.0, 0.0], [0.0, 0.0, 1.0], [1.0, 0.0, 0.0]),
+        ([0.0, -1.0, 0.0], [1.0, 0.0, 0.0], [0.0, 0.0, 1.0]),
+        ([0.0, 0.0, 1.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]),
+        ([0.0, 0.0, -1.0], [0.0, 1.0, 0.0], [1.0, 0.0, 0.0]),
+    ];
+    let corners = [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (1.0, 1.0), (-1.0, 1.0), (-1.0, -1.0)];
+
+    let mut vertices = Vec::with_capacity(6 * 6 * faces.len());
+    for &(n, u, v) in faces.iter() {
+        for &(s, t) in corners.iter() {
+            for i in 0..3 {
+                vertices.push(0.5 * (n[i] + s * u[i] + t * v[i]));
+            }
+            vertices.extend_from_slice(&n);
+        }
+    }
+
+    vertices
+}
+
+///
+/// Interleave separate position and normal arrays, as loaded from a mesh.
+///
+fn interleave(points: &[GLfloat], normals: &[GLfloat]) -> Vec<GLfloat> {
+    let mut vertices = Vec::with_capacity(points.len() + normals.len());
+    for i in 0..(points.len() / 3) {
+        vertices.extend_from_slice(&points[3 * i..3 * i + 3]);
+        vertices.extend_from_slice(&normals[3 * i..3 * i + 3]);
+    }
+
+    vertices
+}
+
+///
+/// Copy interleaved positions and normals into a vertex buffer, and make a vertex
+/// array reading positions from location 0 and normals from location 1.
+///
+fn make_vao(vertices: &[GLfloat]) -> GLuint {
+    let mut vao = 0;
+    unsafe {
+        gl::GenVertexArrays(1, &mut vao);
+    }
+    assert!(vao > 0);
+    VertexLayout::new().floats(0, 3).floats(1, 3).upload(vao, vertices, vertices.len() / 6);
+
+    vao
+}
+
+///
+/// The shader everything in the scene is drawn with: a flat colour lit by one
+/// light from above. The overdraw count is drawn with the same vertex shader and
+/// a fragment shader that has no colour.
+///
+struct SceneShader {
+    sp: GLuint,
+    model_location: GLint,
+    view_location: GLint,
+    proj_location: GLint,
+    colour_location: GLint,
+}
+
+impl SceneShader {
+    fn new(sp: GLuint) -> SceneShader {
+        let location = |name: &str| {
+            let location = uniform_location(sp, name);
+            assert!(location > -1);
+            location
+        };
+
+        SceneShader {
+            sp: sp,
+            model_location: location("M"),
+            view_location: location("V"),
+            proj_location: location("P"),
+            colour_location: uniform_location(sp, "colour"),
+        }
+    }
+
+    fn set_camera(&self, view_mat: &Mat4, proj_mat: &Mat4) {
+        unsafe {
+            gl::UseProgram(self.sp);
+            gl::UniformMatrix4fv(self.view_location, 1, gl::FALSE, view_mat.as_ptr());
+            gl::UniformMatrix4fv(self.proj_location, 1, gl::FALSE, proj_mat.as_ptr());
+        }
+    }
+
+    fn draw(&self, vao: GLuint, point_count: usize, model_mat: &Mat4, colour: &[f32; 3], perf_stats: &mut PerfStats) {
+        unsafe {
+            gl::UniformMatrix4fv(self.model_location, 1, gl::FALSE, model_mat.as_ptr());
+            gl::Uniform3f(self.colour_location, colour[0], colour[1], colour[2]);
+            gl::BindVertexArray(vao);
+            gl::DrawArrays(gl::TRIANGLES, 0, point_count as i32);
+        }
+        perf_stats.count_draw(point_count / 3);
+    }
+}
+
+///
+/// The vertex arrays of the ground and of the shapes standing on it.
+///
+struct Meshes {
+    ground_vao: GLuint,
+    ground_point_count: usize,
+    cube_vao: GLuint,
+    cube_point_count: usize,
+    sphere_vao: GLuint,
+    sphere_point_count: usize,
+}
+
+impl Meshes {
+    ///
+    /// Draw the ground, and the rows of shapes on it. Cubes and spheres take turns
+    /// down each row, and each one is turned a little more than the last.
+    ///
+    fn draw_scene(&self, shader: &SceneShader, perf_stats: &mut PerfStats) {
+        shader.draw(self.ground_vao, self.ground_point_count, &Mat4::identity(), &GROUND_COLOUR, perf_stats);
+        for row in 0..ROW_COUNT {
+            for column in 0..COLUMN_COUNT {
+                let i = row * COLUMN_COUNT + column;
+                let x = (column as f32 - 0.5 * (COLUMN_COUNT - 1) as f32) * COLUMN_SPACING;
+                let z = -(row as f32) * ROW_SPACING;
+                let colour = &OBJECT_COLOURS[i % OBJECT_COLOURS.len()];
+                if i % 2 == 0 {
+                    let model_mat = Mat4::from_rotation_y_deg(15.0 * i as f32)
+                        .then_translate(&math::vec3((x, 0.5, z)));
+                    shader.draw(self.cube_vao, self.cube_point_count, &model_mat, colour, perf_stats);
+                } else {
+                    // The embedded sphere has a radius of one, so halve it to match the cubes.
+                    let model_mat = Mat4::from_scale(&math::vec3((0.5, 0.5, 0.5)))
+                        .then_translate(&math::vec3((x, 0.5, z)));
+                    shader.draw(self.sphere_vao, self.sphere_point_count, &model_mat, colour, perf_stats);
+                }
+            }
+        }
+    }
+}
+
+///
+/// Draw the scene into `overdraw` with additive blending and no depth test, so
+/// each pixel counts every surface drawn at it, hidden or not.
+///
+fn draw_overdraw(
+    overdraw: &RenderTarget, shader: &SceneShader, meshes: &Meshes,
+    view_mat: &Mat4, proj_mat: &Mat4, perf_stats: &mut PerfStats) {
+
+    overdraw.bind();
+    unsafe {
+        gl::ClearColor(0.0, 0.0, 0.0, 0.0);
+        gl::Clear(gl::COLOR_BUFFER_BIT);
+        gl::Disable(gl::DEPTH_TEST);
+        gl::Enable(gl::BLEND);
+        gl::BlendFunc(gl::ONE, gl::ONE);
+    }
+    shader.set_camera(view_mat, proj_mat);
+    meshes.draw_scene(shader, perf_stats);
+    unsafe {
+        gl::Disable(gl::BLEND);
+        gl::Enable(gl::DEPTH_TEST);
+        gl::ClearColor(SKY_COLOUR[0], SKY_COLOUR[1], SKY_COLOUR[2], SKY_COLOUR[3]);
+    }
+    overdraw.unbind();
+}
+
+///
+/// Queue up a label in the top-left corner of the viewport. Returns the bottom
+/// edge of the label.
+///
+fn label(overlay: &mut Overlay, text: &str) -> f32 {
+    let scale = 2.0;
+    let longest = text.lines().map(|line| line.len()).max().unwrap_or(0);
+    let width = (longest as f32 * overlay::GLYPH_ADVANCE + 4.0) * scale;
+    let height = (text.lines().count() as f32 * overlay::LINE_HEIGHT + 4.0) * scale;
+    overlay.rect(10.0, 10.0, width, height, OVERLAY_BACKGROUND_COLOUR);
+    overlay.text(10.0 + 2.0 * scale, 10.0 + 2.0 * scale, scale, OVERLAY_TEXT_COLOUR, text);
+
+    10.0 + height
+}
+
+///
+/// Keep the context's idea of the framebuffer size up to date, and the render
+/// targets the same size as the window.
+///
+fn glfw_framebuffer_size_callback(
+    context: &mut GLContext, target: &mut RenderTarget, overdraw: &mut RenderTarget,
+    bloom: &mut Bloom, ssao: &mut Ssao, width: u32, height: u32) -> Result<(), String> {
+
+    context.width = width;
+    context.height = height;
+
+    if let Err(e) = target.resize(width, height) {
+        return Err(e);
+    }
+    if let Err(e) = overdraw.resize(width, height) {
+        return Err(e);
+    }
+    if let Err(e) = ssao.resize(width, height) {
+        return Err(e);
+    }
+
+    bloom.resize(width, height)
+}
+
+fn main() {
+    let logger = restart_gl_log(GL_LOG_FILE);
+    // Start a GL context and O/S window using the GLFW helper library.
+    let mut context = match start_gl(&logger) {
+        Ok(val) => val,
+        Err(e) => {
+            eprintln!("Failed to Initialize OpenGL context. Got error:");
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+    };
+    // The framebuffer can be bigger than the window on high DPI screens.
+    context.window.set_framebuffer_size_polling(true);
+    let (width, height) = context.window.get_framebuffer_size();
+    context.width = width as u32;
+    context.height = height as u32;
+
+    // the scene is drawn into this, and the post-processing pass draws it onto the window.
+    let mut target = match RenderTarget::with_formats(context.width, context.height, &GBUFFER_FORMATS) {
+        Ok(val) => val,
+        Err(e) => {
+            logger.log_err(&e);
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+    };
+    // the number of surfaces drawn at each pixel, for the overdraw view.
+    let mut overdraw_target = match RenderTarget::with_formats(context.width, context.height, &[TargetFormat::R16F]) {
+        Ok(val) => val,
+        Err(e) => {
+            logger.log_err(&e);
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+    };
+
+    unsafe {
+        // Enable depth testing.
+        gl::Enable(gl::DEPTH_TEST);
+        // Depth testing interprets a smaller value as closer to the eye.
+        gl::DepthFunc(gl::LESS);
+        // Cull face.
+        gl::Enable(gl::CULL_FACE);
+        // Cull back face.
+        gl::CullFace(gl::BACK);
+        // GL_CW for clockwise.
+        gl::FrontFace(gl::CCW);
+        gl::ClearColor(SKY_COLOUR[0], SKY_COLOUR[1], SKY_COLOUR[2], SKY_COLOUR[3]);
+    }
+
+    /*------------------------------CREATE GEOMETRY------------------------------*/
+    let ground = ground_vertices();
+    let cube = cube_vertices();
+    let sphere_mesh = assets::fallback_mesh();
+    let meshes = Meshes {
+        ground_vao: make_vao(&ground),
+        ground_point_count: ground.len() / 6,
+        cube_vao: make_vao(&cube),
+        cube_point_count: cube.len() / 6,
+        sphere_vao: make_vao(&interleave(&sphere_mesh.points, &sphere_mesh.normals)),
+        sphere_point_count: sphere_mesh.point_count,
+    };
+
+    /*-------------------------------CREATE SHADERS------------------------------*/
+    let shader_programme = create_programme_from_files(&logger, VERTEX_SHADER_FILE, FRAGMENT_SHADER_FILE);
+    let scene_shader = SceneShader::new(shader_programme);
+    let overdraw_programme = create_programme_from_files(&logger, VERTEX_SHADER_FILE, OVERDRAW_FS_FILE);
+    let overdraw_shader = SceneShader::new(overdraw_programme);
+    let post_programme = create_programme_from_files(&logger, FULLSCREEN_VS_FILE, POST_FS_FILE);
+    let mut post_process = PostProcess::new(post_programme);
+    let bloom_bright_programme = create_programme_from_files(&logger, FULLSCREEN_VS_FILE, BLOOM_BRIGHT_FS_FILE);
+    let bloom_blur_programme = create_programme_from_files(&logger, FULLSCREEN_VS_FILE, BLOOM_BLUR_FS_FILE);
+    let mut bloom = match Bloom::new(bloom_bright_programme, bloom_blur_programme, context.width, context.height) {
+        Ok(val) => val,
+        Err(e) => {
+            logger.log_err(&e);
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+    };
+
+    let ssao_programme = create_programme_from_files(&logger, FULLSCREEN_VS_FILE, SSAO_FS_FILE);
+    let ssao_blur_programme = create_programme_from_files(&logger, FULLSCREEN_VS_FILE, SSAO_BLUR_FS_FILE);
+    let mut ssao = match Ssao::new(ssao_programme, ssao_blur_programme, context.width, context.height) {
+        Ok(val) => val,
+        Err(e) => {
+            logger.log_err(&e);
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+    };
+
+    /*-------------------------------CREATE CAMERA-------------------------------*/
+    let mut controller = CameraController::new(math::vec3((0.0, EYE_HEIGHT, 6.0)), 0.0, -10.0, 67.0, ControlKeys {
+        forward: Key::W, back: Key::S, step_left: Key::A, step_right: Key::D,
+        turn_left: Key::Left, turn_right: Key::Right, look_up: Key::Up, look_down: Key::Down,
+    });
+    logger.log("keys: W, A, S, and D walk, and the arrow keys turn and look up and down;");
+    logger.log("      V cycles through the finished frame, the normals, albedo, depth, ambient occlusion, and overdraw,");
+    logger.log("      [ and ] scale the depth view, B toggles the bloom, - and = lower and raise its threshold,");
+    logger.log("      , and . lower and raise its intensity, O toggles the ambient occlusion, 9 and 0 shrink and");
+    logger.log("      grow its radius, and F3 toggles the performance HUD;");
+    logger.log("      F5 saves the camera to camera.txt, and F9 restores it");
+
+    // input variables
+    let near = 0.1;                                  // clipping plane
+    let far = 100.0;                                 // clipping plane
+
+    // which view is showing, drawn in the corner, with the frame times and the
+    // GPU time of each pass underneath. F3 toggles the times on and off.
+    let mut overlay = Overlay::new(&logger);
+    let mut perf_stats = PerfStats::new(PERF_STATS_HISTORY);
+    let mut show_perf_stats = true;
+    let mut scene_timer = GpuTimer::new();
+    let mut post_timer = GpuTimer::new();
+
+    while !context.window.should_close() {
+        let frame_start = Instant::now();
+        let current_seconds = context.glfw.get_time();
+        let elapsed_seconds = current_seconds - context.elapsed_time_seconds;
+        context.elapsed_time_seconds = current_seconds;
+
+        update_fps_counter(&mut context);
+
+        let aspect = context.width as f32 / u32::max(context.height, 1) as f32;
+        let proj_mat = controller.camera.proj_mat(aspect, near, far);
+        let view_mat = controller.camera.view_mat();
+
+        // Draw the scene into the render target.
+        scene_timer.begin();
+        target.bind();
+        unsafe {
+            gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
+        }
+        // The background has no normal.
+        target.clear_attachment(NORMAL_ATTACHMENT, [0.0, 0.0, 0.0, 0.0]);
+        scene_shader.set_camera(&view_mat, &proj_mat);
+        meshes.draw_scene(&scene_shader, &mut perf_stats);
+        target.unbind();
+        scene_timer.end();
+        if post_process.needs_overdraw() {
+            draw_overdraw(&overdraw_target, &overdraw_shader, &meshes, &view_mat, &proj_mat, &mut perf_stats);
+        }
+        ssao.apply(&target, NORMAL_ATTACHMENT, &view_mat, &proj_mat);
+        bloom.apply(target.colour_texture());
+
+        // Then draw it onto the window through the post-processing pass.
+        post_timer.begin();
+        unsafe {
+            gl::Viewport(0, 0, context.width as i32, context.height as i32);
+            gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
+        }
+        post_process.draw(&target, &overdraw_target, &bloom, &ssao, near, far);
+        post_timer.end();
+
+        perf_stats.set_gpu_pass_ms("scene", scene_timer.elapsed_ms());
+        for &(pass, gpu_time_ms) in ssao.pass_times_ms().iter() {
+            perf_stats.set_gpu_pass_ms(pass, gpu_time_ms);
+        }
+        for &(pass, gpu_time_ms) in bloom.pass_times_ms().iter() {
+            perf_stats.set_gpu_pass_ms(pass, gpu_time_ms);
+        }
+        perf_stats.set_gpu_pass_ms("post", post_timer.elapsed_ms());
+        let label_text = format!(
+            "{}\n{}\n{}", post_process.describe(), ssao.settings.describe(), bloom.settings.describe()
+        );
+        let label_bottom = label(&mut overlay, &label_text);
+        if show_perf_stats {
+            perf_stats.draw_hud(&mut overlay, 10.0, label_bottom + 6.0);
+        }
+        overlay.draw(context.width, context.height);
+
+        context.glfw.poll_events();
+
+        let mut toggle_fullscreen = false;
+        let mut resized_to = None;
+        for (_, event) in glfw::flush_messages(&context.events) {
+            match event {
+                glfw::WindowEvent::FramebufferSize(width, height) => {
+                    resized_to = Some((width, height));
+                }
+                glfw::WindowEvent::Key(Key::V, _, Action::Press, _) => {
+                    post_process.view_mode = post_process.view_mode.next();
+                    logger.log(&post_process.describe());
+                }
+                glfw::WindowEvent::Key(Key::LeftBracket, _, Action::Press, _) => {
+                    post_process.change_depth_range(-DEPTH_RANGE_STEP, far);
+                    logger.log(&post_process.describe());
+                }
+                glfw::WindowEvent::Key(Key::RightBracket, _, Action::Press, _) => {
+                    post_process.change_depth_range(DEPTH_RANGE_STEP, far);
+                    logger.log(&post_process.describe());
+                }
+                glfw::WindowEvent::Key(Key::O, _, Action::Press, _) => {
+                    ssao.settings.enabled = !ssao.settings.enabled;
+                    logger.log(&ssao.settings.describe());
+                }
+                glfw::WindowEvent::Key(Key::Num9, _, Action::Press, _) => {
+                    ssao.settings.change_radius(-SSAO_RADIUS_STEP);
+                    logger.log(&ssao.settings.describe());
+                }
+                glfw::WindowEvent::Key(Key::Num0, _, Action::Press, _) => {
+                    ssao.settings.change_radius(SSAO_RADIUS_STEP);
+                    logger.log(&ssao.settings.describe());
+                }
+                glfw::WindowEvent::Key(Key::B, _, Action::Press, _) => {
+                    bloom.settings.enabled = !bloom.settings.enabled;
+                    logger.log(&bloom.settings.describe());
+                }
+                glfw::WindowEvent::Key(Key::Minus, _, Action::Press, _) => {
+                    bloom.settings.change_threshold(-BLOOM_THRESHOLD_STEP);
+                    logger.log(&bloom.settings.describe());
+                }
+                glfw::WindowEvent::Key(Key::Equal, _, Action::Press, _) => {
+                    bloom.settings.change_threshold(BLOOM_THRESHOLD_STEP);
+                    logger.log(&bloom.settings.describe());
+                }
+                glfw::WindowEvent::Key(Key::Comma, _, Action::Press, _) => {
+                    bloom.settings.change_intensity(-BLOOM_INTENSITY_STEP);
+                    logger.log(&bloom.settings.describe());
+                }
+                glfw::WindowEvent::Key(Key::Period, _, Action::Press, _) => {
+                    bloom.settings.change_intensity(BLOOM_INTENSITY_STEP);
+                    logger.log(&bloom.settings.describe());
+                }
+                glfw::WindowEvent::Key(Key::F3, _, Action::Press, _) => {
+                    show_perf_stats = !show_perf_stats;
+                }
+                glfw::WindowEvent::Key(Key::Enter, _, Action::Press, mods) => {
+                    if mods.contains(glfw::Modifiers::Alt) {
+                        toggle_fullscreen = true;
+                    }
+                }
+                glfw::WindowEvent::Key(Key::Escape, _, Action::Press, _) => {
+                    context.window.set_should_close(true);
+                }
+                glfw::WindowEvent::Key(Key::F5, _, Action::Press, _) => {
+                    controller.camera.save_to_camera_file();
+                }
+                glfw::WindowEvent::Key(Key::F9, _, Action::Press, _) => {
+                    if let Some(camera) = Camera::restore_from_camera_file() {
+                        controller.set_camera(&camera);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        if toggle_fullscreen {
+            let fullscreen = context.toggle_fullscreen();
+            logger.log(&format!("fullscreen: {}", fullscreen));
+            let (width, height) = context.window.get_framebuffer_size();
+            resized_to = Some((width, height));
+        }
+
+        if let Some((width, height)) = resized_to {
+            if let Err(e) = glfw_framebuffer_size_callback(
+                &mut context, &mut target, &mut overdraw_target, &mut bloom, &mut ssao, width as u32, height as u32) {
+                logger.log_err(&e);
+            }
+            logger.log(&format!("framebuffer resized to {}x{}", width, height));
+        }
+
+        // control keys
+        controller.update(&context.window, elapsed_seconds);
+
+        // Put the stuff we've been drawing onto the display.
+        context.window.swap_buffers();
+        perf_stats.end_frame(perf_stats::duration_to_ms(frame_start.elapsed()));
+    }
+}
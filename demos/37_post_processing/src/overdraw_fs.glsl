@@ -0,0 +1,9 @@
+#version 410
+
+// drawn with additive blending and no depth test, so each pixel ends up holding
+// the number of surfaces that covered it.
+out vec4 frag_colour;
+
+void main () {
+	frag_colour = vec4 (1.0, 0.0, 0.0, 0.0);
+}
@@ -0,0 +1,95 @@
+#version 410
+
+// keep in step with ViewMode in post_process.rs
+#define VIEW_FINAL 0
+#define VIEW_NORMALS 1
+#define VIEW_ALBEDO 2
+#define VIEW_DEPTH 3
+#define VIEW_OCCLUSION 4
+#define VIEW_OVERDRAW 5
+
+in vec2 st;
+
+// what the scene rendered into its render target.
+uniform sampler2D colour_tex;
+uniform sampler2D depth_tex;
+uniform sampler2D albedo_tex;
+uniform sampler2D normal_tex;
+// the number of surfaces drawn at each pixel, counted in a separate pass.
+uniform sampler2D overdraw_tex;
+// the blurred glow around the brightest parts of the frame, at half size.
+uniform sampler2D bloom_tex;
+// how open each pixel is to the ambient light, from 0 to 1.
+uniform sampler2D ssao_tex;
+uniform int view_mode;
+// the camera's clipping planes, and the distance drawn as white in the depth view.
+uniform float near;
+uniform float far;
+uniform float depth_range;
+// the number of surfaces drawn as white in the overdraw view.
+uniform float max_overdraw;
+// how strongly the glow is added over the finished frame. zero turns it off.
+uniform float bloom_intensity;
+// take the ambient occlusion off the ambient part of the scene's lighting.
+uniform bool ssao_enabled;
+uniform float ambient;
+
+out vec4 frag_colour;
+
+// undo the perspective divide to get the distance from the camera. the same
+// sum as linearize_depth in post_process.rs.
+float linearize_depth (float depth) {
+	float z_ndc = 2.0 * depth - 1.0;
+	return (2.0 * near * far) / (far + near - z_ndc * (far - near));
+}
+
+// blue for one surface, then red, yellow, and white as more pile up.
+vec3 heat (float t) {
+	vec3 blue = vec3 (0.0, 0.0, 1.0);
+	vec3 red = vec3 (1.0, 0.0, 0.0);
+	vec3 yellow = vec3 (1.0, 1.0, 0.0);
+	vec3 white = vec3 (1.0, 1.0, 1.0);
+	if (t < 0.33) {
+		return mix (blue, red, t / 0.33);
+	}
+	if (t < 0.66) {
+		return mix (red, yellow, (t - 0.33) / 0.33);
+	}
+	return mix (yellow, white, (t - 0.66) / 0.34);
+}
+
+void main() {
+	if (view_mode == VIEW_NORMALS) {
+		// the background has no normal, and is left black.
+		vec3 n = texture (normal_tex, st).xyz;
+		frag_colour = vec4 (length (n) > 0.0 ? 0.5 * n + 0.5 : vec3 (0.0), 1.0);
+	} else if (view_mode == VIEW_ALBEDO) {
+		frag_colour = vec4 (texture (albedo_tex, st).rgb, 1.0);
+	} else if (view_mode == VIEW_DEPTH) {
+		float grey = clamp (linearize_depth (texture (depth_tex, st).r) / depth_range, 0.0, 1.0);
+		frag_colour = vec4 (grey, grey, grey, 1.0);
+	} else if (view_mode == VIEW_OCCLUSION) {
+		float open = ssao_enabled ? texture (ssao_tex, st).r : 1.0;
+		frag_colour = vec4 (open, open, open, 1.0);
+	} else if (view_mode == VIEW_OVERDRAW) {
+		// the same sum as overdraw_level in post_process.rs.
+		float count = texture (overdraw_tex, st).r;
+		if (count < 0.5) {
+			frag_colour = vec4 (0.0, 0.0, 0.0, 1.0);
+		} else {
+			frag_colour = vec4 (heat (clamp ((count - 1.0) / (max_overdraw - 1.0), 0.0, 1.0)), 1.0);
+		}
+	} else {
+		vec3 colour = texture (colour_tex, st).rgb;
+		if (ssao_enabled) {
+			// the scene lit the surface with ambient * albedo, all of it. take off
+			// the part that cannot reach it.
+			vec3 albedo = texture (albedo_tex, st).rgb;
+			colour -= ambient * albedo * (1.0 - texture (ssao_tex, st).r);
+		}
+		if (bloom_intensity > 0.0) {
+			colour += bloom_intensity * texture (bloom_tex, st).rgb;
+		}
+		frag_colour = vec4 (colour, 1.0);
+	}
+}
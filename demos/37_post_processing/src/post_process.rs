@@ -0,0 +1,284 @@
+use gl;
+use gl::types::{GLint, GLuint};
+
+use bloom::Bloom;
+use fullscreen_pass::FullscreenPass;
+use render_target::{RenderTarget, TargetFormat};
+use ssao::{Ssao, SCENE_AMBIENT};
+use gl_utils::uniform_location;
+
+
+///
+/// The distance drawn as white in the depth view to begin with. The far clipping
+/// plane is usually so far off that a view scaled to it is black all over.
+///
+pub const DEFAULT_DEPTH_RANGE: f32 = 30.0;
+///
+/// The shortest distance the depth view can be scaled to.
+///
+pub const MIN_DEPTH_RANGE: f32 = 1.0;
+///
+/// The number of surfaces drawn over one another that the overdraw view shows as
+/// white. Fewer fade through yellow and red down to blue for a single surface.
+///
+pub const MAX_OVERDRAW: f32 = 8.0;
+
+///
+/// The colour attachments the scene is rendered into: the lit colour, then the
+/// surface's own colour, then its world space normal. The normals can be negative,
+/// so they are kept as floats.
+///
+pub const GBUFFER_FORMATS: [TargetFormat; 3] = [TargetFormat::Rgba8, TargetFormat::Rgba8, TargetFormat::Rgba16F];
+pub const ALBEDO_ATTACHMENT: usize = 1;
+pub const NORMAL_ATTACHMENT: usize = 2;
+
+
+///
+/// What the post-processing pass shows: the finished frame, or one of the
+/// buffers the frame was drawn with.
+///
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ViewMode {
+    Final,
+    Normals,
+    Albedo,
+    Depth,
+    Occlusion,
+    Overdraw,
+}
+
+impl ViewMode {
+    pub fn next(&self) -> ViewMode {
+        match *self {
+            ViewMode::Final => ViewMode::Normals,
+            ViewMode::Normals => ViewMode::Albedo,
+            ViewMode::Albedo => ViewMode::Depth,
+            ViewMode::Depth => ViewMode::Occlusion,
+            ViewMode::Occlusion => ViewMode::Overdraw,
+            ViewMode::Overdraw => ViewMode::Final,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            ViewMode::Final => "final",
+            ViewMode::Normals => "world normals",
+            ViewMode::Albedo => "albedo",
+            ViewMode::Depth => "depth",
+            ViewMode::Occlusion => "ambient occlusion",
+            ViewMode::Overdraw => "overdraw",
+        }
+    }
+
+    ///
+    /// The number the fragment shader knows the view by.
+    ///
+    fn index(&self) -> GLint {
+        match *self {
+            ViewMode::Final => 0,
+            ViewMode::Normals => 1,
+            ViewMode::Albedo => 2,
+            ViewMode::Depth => 3,
+            ViewMode::Occlusion => 4,
+            ViewMode::Overdraw => 5,
+        }
+    }
+}
+
+///
+/// Turn a depth buffer value back into the distance from the camera along its
+/// view direction, undoing the perspective divide for a projection with clipping
+/// planes at `near` and `far`. Depth buffer values bunch up towards 1 a short way
+/// from the camera, which is why the raw depth buffer looks all white.
+///
+pub fn linearize_depth(depth: f32, near: f32, far: f32) -> f32 {
+    let z_ndc = 2.0 * depth - 1.0;
+
+    (2.0 * near * far) / (far + near - z_ndc * (far - near))
+}
+
+///
+/// The shade of grey the depth view draws a depth buffer value as: black at the
+/// camera, fading to white at `range` units away and beyond.
+///
+pub fn depth_grey(depth: f32, near: f32, far: f32, range: f32) -> f32 {
+    f32::min(f32::max(linearize_depth(depth, near, far) / range, 0.0), 1.0)
+}
+
+///
+/// How far along the overdraw view's colour ramp a pixel `count` surfaces deep
+/// is, from 0 for a single surface to 1 for `MAX_OVERDRAW` or more. Returns `None`
+/// for a pixel nothing was drawn at, which the view leaves black.
+///
+pub fn overdraw_level(count: f32) -> Option<f32> {
+    if count < 0.5 {
+        return None;
+    }
+
+    Some(f32::min(f32::max((count - 1.0) / (MAX_OVERDRAW - 1.0), 0.0), 1.0))
+}
+
+///
+/// Draws what the scene rendered into a render target onto the window, as the
+/// finished frame or as a view of one of its buffers.
+///
+pub struct PostProcess {
+    sp: GLuint,
+    pass: FullscreenPass,
+    view_mode_location: GLint,
+    near_location: GLint,
+    far_location: GLint,
+    depth_range_location: GLint,
+    max_overdraw_location: GLint,
+    bloom_intensity_location: GLint,
+    ssao_enabled_location: GLint,
+    pub view_mode: ViewMode,
+    pub depth_range: f32,
+}
+
+impl PostProcess {
+    pub fn new(sp: GLuint) -> PostProcess {
+        let location = |name: &str| {
+            let location = uniform_location(sp, name);
+            assert!(location > -1);
+            location
+        };
+        unsafe {
+            gl::UseProgram(sp);
+            gl::Uniform1i(location("colour_tex"), 0);
+            gl::Uniform1i(location("depth_tex"), 1);
+            gl::Uniform1i(location("albedo_tex"), 2);
+            gl::Uniform1i(location("normal_tex"), 3);
+            gl::Uniform1i(location("overdraw_tex"), 4);
+            gl::Uniform1i(location("bloom_tex"), 5);
+            gl::Uniform1i(location("ssao_tex"), 6);
+            gl::Uniform1f(location("ambient"), SCENE_AMBIENT);
+        }
+
+        PostProcess {
+            sp: sp,
+            pass: FullscreenPass::new(),
+            view_mode_location: location("view_mode"),
+            near_location: location("near"),
+            far_location: location("far"),
+            depth_range_location: location("depth_range"),
+            max_overdraw_location: location("max_overdraw"),
+            bloom_intensity_location: location("bloom_intensity"),
+            ssao_enabled_location: location("ssao_enabled"),
+            view_mode: ViewMode::Final,
+            depth_range: DEFAULT_DEPTH_RANGE,
+        }
+    }
+
+    ///
+    /// Scale the depth view out or in, never past the far clipping plane.
+    ///
+    pub fn change_depth_range(&mut self, step: f32, far: f32) {
+        self.depth_range = f32::min(f32::max(self.depth_range + step, MIN_DEPTH_RANGE), far);
+    }
+
+    pub fn describe(&self) -> String {
+        match self.view_mode {
+            ViewMode::Depth => format!("view: depth, white at {:.0} units", self.depth_range),
+            ViewMode::Overdraw => format!("view: overdraw, white at {:.0} surfaces", MAX_OVERDRAW),
+            _ => format!("view: {}", self.view_mode.name()),
+        }
+    }
+
+    ///
+    /// Whether the scene has to be drawn into an overdraw count this frame.
+    ///
+    pub fn needs_overdraw(&self) -> bool {
+        self.view_mode == ViewMode::Overdraw
+    }
+
+    ///
+    /// Draw the render target over the whole viewport, for a camera with clipping
+    /// planes at `near` and `far`. The target has the attachments in
+    /// `GBUFFER_FORMATS`, and `overdraw` holds the count of surfaces drawn at each
+    /// pixel, if the view shows it. The finished frame has its ambient light
+    /// darkened by the ambient occlusion, and the bloom's glow added over it.
+    ///
+    pub fn draw(
+        &self, target: &RenderTarget, overdraw: &RenderTarget, bloom: &Bloom, ssao: &Ssao,
+        near: f32, far: f32) {
+
+        unsafe {
+            gl::UseProgram(self.sp);
+            gl::Uniform1i(self.view_mode_location, self.view_mode.index());
+            gl::Uniform1f(self.near_location, near);
+            gl::Uniform1f(self.far_location, far);
+            gl::Uniform1f(self.depth_range_location, self.depth_range);
+            gl::Uniform1f(self.max_overdraw_location, MAX_OVERDRAW);
+            gl::Uniform1f(self.bloom_intensity_location, bloom.settings.composite_intensity());
+            gl::Uniform1i(self.ssao_enabled_location, ssao.settings.enabled as GLint);
+        }
+        // In the order of the samplers set up in new.
+        let inputs = [
+            target.colour_texture(),
+            target.depth_texture(),
+            target.attachment(ALBEDO_ATTACHMENT),
+            target.attachment(NORMAL_ATTACHMENT),
+            overdraw.colour_texture(),
+            bloom.texture(),
+            ssao.texture(),
+        ];
+        self.pass.draw(self.sp, &inputs);
+    }
+}
+
+mod post_process_tests {
+    use super::{depth_grey, linearize_depth, overdraw_level, ViewMode, MAX_OVERDRAW};
+
+    const NEAR: f32 = 0.1;
+    const FAR: f32 = 100.0;
+
+    ///
+    /// The depth buffer value of a point `distance` units in front of the camera.
+    ///
+    fn depth_of(distance: f32) -> f32 {
+        let z_eye = -distance;
+        let z_clip = -(FAR + NEAR) / (FAR - NEAR) * z_eye - 2.0 * FAR * NEAR / (FAR - NEAR);
+
+        0.5 * (z_clip / distance) + 0.5
+    }
+
+    #[test]
+    fn test_linearize_depth_undoes_the_projection() {
+        for &distance in [NEAR, 1.0, 5.0, 42.0, FAR].iter() {
+            let result = linearize_depth(depth_of(distance), NEAR, FAR);
+
+            assert!(f32::abs(result - distance) < 1e-3 * distance, "{} is not {}", result, distance);
+        }
+    }
+
+    #[test]
+    fn test_depth_grey_fades_from_black_to_white_over_the_range() {
+        assert!(depth_grey(depth_of(NEAR), NEAR, FAR, 30.0) < 0.01);
+        assert!(f32::abs(depth_grey(depth_of(15.0), NEAR, FAR, 30.0) - 0.5) < 1e-2);
+        assert_eq!(depth_grey(depth_of(60.0), NEAR, FAR, 30.0), 1.0);
+        // the cleared depth buffer is as far as can be
+        assert_eq!(depth_grey(1.0, NEAR, FAR, 30.0), 1.0);
+    }
+
+    #[test]
+    fn test_view_modes_cycle_back_to_the_final_frame() {
+        let mut mode = ViewMode::Final;
+        for _ in 0..6 {
+            mode = mode.next();
+            assert!(mode == ViewMode::Final || mode.index() > 0);
+        }
+
+        assert_eq!(mode, ViewMode::Final);
+        assert_eq!(ViewMode::Final.next(), ViewMode::Normals);
+    }
+
+    #[test]
+    fn test_overdraw_ramps_from_one_surface_to_the_maximum() {
+        assert_eq!(overdraw_level(0.0), None);
+        assert_eq!(overdraw_level(1.0), Some(0.0));
+        assert_eq!(overdraw_level(MAX_OVERDRAW), Some(1.0));
+        assert_eq!(overdraw_level(3.0 * MAX_OVERDRAW), Some(1.0));
+        assert!(overdraw_level(2.0).unwrap() < overdraw_level(3.0).unwrap());
+    }
+}
@@ -0,0 +1,22 @@
+#version 410
+
+in vec3 n_world;
+uniform vec3 colour;
+// the lit colour, then the surface's own colour and its normal for the debug
+// views. keep in step with GBUFFER_FORMATS in post_process.rs.
+layout (location = 0) out vec4 frag_colour;
+layout (location = 1) out vec4 albedo;
+layout (location = 2) out vec4 normal_world;
+
+const vec3 light_dir_world = normalize (vec3 (0.4, 1.0, 0.3));
+// keep in step with SCENE_AMBIENT in ssao.rs, which takes ambient occlusion off
+// this part of the light.
+const float ambient = 0.3;
+
+void main () {
+	vec3 n = normalize (n_world);
+	float diffuse = max (dot (n, light_dir_world), 0.0);
+	frag_colour = vec4 (colour * (ambient + (1.0 - ambient) * diffuse), 1.0);
+	albedo = vec4 (colour, 1.0);
+	normal_world = vec4 (n, 0.0);
+}
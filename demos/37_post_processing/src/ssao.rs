@@ -0,0 +1,396 @@
+use gl;
+use gl::types::{GLint, GLsizei, GLsizeiptr, GLuint, GLvoid};
+
+use fullscreen_pass::FullscreenPass;
+use gpu_timer::GpuTimer;
+use graphics_math::Mat4;
+use render_target::{RenderTarget, TargetFormat};
+use gl_utils::uniform_location;
+
+use std::mem;
+
+
+///
+/// The number of samples in the kernel. Keep in step with `ssao_fs.glsl`.
+///
+pub const SSAO_KERNEL_SIZE: usize = 32;
+///
+/// The noise texture is this many texels on a side, tiled over the frame. Each
+/// texel turns the kernel a different way, and the blur averages a tile's worth of
+/// pixels to hide the pattern.
+///
+pub const SSAO_NOISE_SIZE: usize = 4;
+///
+/// The uniform buffer binding point the kernel is bound to.
+///
+pub const SSAO_KERNEL_BINDING: GLuint = 0;
+///
+/// How much of the scene's lighting is ambient, which is the part occlusion
+/// darkens. Keep in step with `scene_fs.glsl`.
+///
+pub const SCENE_AMBIENT: f32 = 0.3;
+pub const DEFAULT_SSAO_RADIUS: f32 = 0.5;
+pub const MIN_SSAO_RADIUS: f32 = 0.1;
+pub const MAX_SSAO_RADIUS: f32 = 2.0;
+///
+/// How far in front of a surface a sample has to be hidden before it counts, so a
+/// flat surface does not shadow itself through depth buffer rounding.
+///
+pub const SSAO_BIAS: f32 = 0.025;
+///
+/// The seed the kernel and noise are made from, so every run looks the same.
+///
+const SSAO_SEED: u32 = 0x5eed_a0a0;
+
+
+///
+/// A small xorshift random number generator. The kernel and noise only need to
+/// be spread out, not unpredictable.
+///
+pub struct XorShift32 {
+    state: u32,
+}
+
+impl XorShift32 {
+    pub fn new(seed: u32) -> XorShift32 {
+        // A state of zero would stay zero forever.
+        XorShift32 { state: if seed == 0 { 1 } else { seed } }
+    }
+
+    pub fn next_u32(&mut self) -> u32 {
+        let mut x = self.state;
+        x ^= x << 13;
+        x ^= x >> 17;
+        x ^= x << 5;
+        self.state = x;
+
+        x
+    }
+
+    ///
+    /// A number from 0 up to but not including 1.
+    ///
+    pub fn next_f32(&mut self) -> f32 {
+        (self.next_u32() >> 8) as f32 / (1 << 24) as f32
+    }
+}
+
+///
+/// The sample points the occlusion is measured at, in a hemisphere of radius one
+/// around +z, which the shader turns to face along each pixel's normal. Most of
+/// the points are bunched up close to the centre, where what is nearby matters
+/// most.
+///
+pub fn ssao_kernel(size: usize, rng: &mut XorShift32) -> Vec<[f32; 4]> {
+    let mut kernel = Vec::with_capacity(size);
+    while kernel.len() < size {
+        let x = 2.0 * rng.next_f32() - 1.0;
+        let y = 2.0 * rng.next_f32() - 1.0;
+        let z = rng.next_f32();
+        let length = f32::sqrt(x * x + y * y + z * z);
+        // Keep only points inside the hemisphere, so they are spread evenly through it.
+        if length > 1.0 || length < 1e-3 {
+            continue;
+        }
+
+        let t = kernel.len() as f32 / size as f32;
+        let scale = 0.1 + 0.9 * t * t;
+        kernel.push([x * scale, y * scale, z * scale, 0.0]);
+    }
+
+    kernel
+}
+
+///
+/// The vectors in the noise texture, which turn the kernel about the normal by a
+/// different angle at each pixel of a tile. They lie flat in the xy plane.
+///
+pub fn ssao_noise(count: usize, rng: &mut XorShift32) -> Vec<[f32; 3]> {
+    (0..count).map(|_| [2.0 * rng.next_f32() - 1.0, 2.0 * rng.next_f32() - 1.0, 0.0]).collect()
+}
+
+///
+/// The settings of the ambient occlusion that can be changed while the demo runs.
+///
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct SsaoSettings {
+    pub enabled: bool,
+    pub radius: f32,
+}
+
+impl SsaoSettings {
+    pub fn new() -> SsaoSettings {
+        SsaoSettings {
+            enabled: true,
+            radius: DEFAULT_SSAO_RADIUS,
+        }
+    }
+
+    pub fn change_radius(&mut self, step: f32) {
+        self.radius = f32::min(f32::max(self.radius + step, MIN_SSAO_RADIUS), MAX_SSAO_RADIUS);
+    }
+
+    pub fn describe(&self) -> String {
+        if !self.enabled {
+            return String::from("ssao: off");
+        }
+
+        format!("ssao: radius {:.2}", self.radius)
+    }
+}
+
+///
+/// Make the noise texture, tiled over the frame, so it has to repeat.
+///
+fn create_noise_texture(noise: &[[f32; 3]]) -> GLuint {
+    assert_eq!(noise.len(), SSAO_NOISE_SIZE * SSAO_NOISE_SIZE);
+    let mut tex = 0;
+    unsafe {
+        gl::GenTextures(1, &mut tex);
+        gl::BindTexture(gl::TEXTURE_2D, tex);
+        gl::TexImage2D(
+            gl::TEXTURE_2D, 0, gl::RGB16F as GLint, SSAO_NOISE_SIZE as GLsizei, SSAO_NOISE_SIZE as GLsizei, 0,
+            gl::RGB, gl::FLOAT, noise.as_ptr() as *const GLvoid
+        );
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::NEAREST as GLint);
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::NEAREST as GLint);
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::REPEAT as GLint);
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::REPEAT as GLint);
+    }
+    assert!(tex > 0);
+
+    tex
+}
+
+///
+/// Screen space ambient occlusion. Each pixel of the frame is rebuilt in view
+/// space from the depth buffer and the G-buffer's normals, and a kernel of points
+/// in the hemisphere over it is checked against the depth buffer. The more of
+/// them are hidden behind something, the more the surface is tucked away out of
+/// the ambient light. The kernel is in a uniform buffer, and is turned by a tiled
+/// noise texture so that few samples do the work of many, which leaves a pattern
+/// that a blur over one tile then smooths away.
+///
+pub struct Ssao {
+    ssao_sp: GLuint,
+    blur_sp: GLuint,
+    pass: FullscreenPass,
+    kernel_ubo: GLuint,
+    noise_tex: GLuint,
+    proj_location: GLint,
+    inv_proj_location: GLint,
+    view_location: GLint,
+    radius_location: GLint,
+    noise_scale_location: GLint,
+    targets: [RenderTarget; 2],
+    ssao_timer: GpuTimer,
+    blur_timer: GpuTimer,
+    pub settings: SsaoSettings,
+}
+
+impl Ssao {
+    ///
+    /// Set up the ambient occlusion for a frame `width` by `height` pixels, with
+    /// the occlusion and blur shader programmes.
+    ///
+    pub fn new(ssao_sp: GLuint, blur_sp: GLuint, width: u32, height: u32) -> Result<Ssao, String> {
+        let location = |sp: GLuint, name: &str| {
+            let location = uniform_location(sp, name);
+            assert!(location > -1);
+            location
+        };
+
+        let mut rng = XorShift32::new(SSAO_SEED);
+        let kernel = ssao_kernel(SSAO_KERNEL_SIZE, &mut rng);
+        let noise = ssao_noise(SSAO_NOISE_SIZE * SSAO_NOISE_SIZE, &mut rng);
+
+        // Each sample is a vec4, so the std140 layout has no padding to add.
+        let mut kernel_ubo = 0;
+        unsafe {
+            gl::GenBuffers(1, &mut kernel_ubo);
+            gl::BindBuffer(gl::UNIFORM_BUFFER, kernel_ubo);
+            gl::BufferData(
+                gl::UNIFORM_BUFFER, (kernel.len() * mem::size_of::<[f32; 4]>()) as GLsizeiptr,
+                kernel.as_ptr() as *const GLvoid, gl::STATIC_DRAW
+            );
+            gl::BindBuffer(gl::UNIFORM_BUFFER, 0);
+
+            let block_index = gl::GetUniformBlockIndex(ssao_sp, "SsaoKernel\0".as_ptr() as *const i8);
+            if block_index == gl::INVALID_INDEX {
+                return Err(String::from("ERROR: the SSAO shader has no SsaoKernel uniform block"));
+            }
+            gl::UniformBlockBinding(ssao_sp, block_index, SSAO_KERNEL_BINDING);
+
+            gl::UseProgram(ssao_sp);
+            gl::Uniform1i(location(ssao_sp, "depth_tex"), 0);
+            gl::Uniform1i(location(ssao_sp, "normal_tex"), 1);
+            gl::Uniform1i(location(ssao_sp, "noise_tex"), 2);
+            gl::Uniform1f(location(ssao_sp, "bias"), SSAO_BIAS);
+            gl::UseProgram(blur_sp);
+            gl::Uniform1i(location(blur_sp, "ssao_tex"), 0);
+        }
+        assert!(kernel_ubo > 0);
+
+        let first = match RenderTarget::with_formats(width, height, &[TargetFormat::R16F]) {
+            Ok(val) => val,
+            Err(e) => return Err(e),
+        };
+        let second = match RenderTarget::with_formats(width, height, &[TargetFormat::R16F]) {
+            Ok(val) => val,
+            Err(e) => return Err(e),
+        };
+
+        Ok(Ssao {
+            ssao_sp: ssao_sp,
+            blur_sp: blur_sp,
+            pass: FullscreenPass::new(),
+            kernel_ubo: kernel_ubo,
+            noise_tex: create_noise_texture(&noise),
+            proj_location: location(ssao_sp, "P"),
+            inv_proj_location: location(ssao_sp, "inv_P"),
+            view_location: location(ssao_sp, "V"),
+            radius_location: location(ssao_sp, "radius"),
+            noise_scale_location: location(ssao_sp, "noise_scale"),
+            targets: [first, second],
+            ssao_timer: GpuTimer::new(),
+            blur_timer: GpuTimer::new(),
+            settings: SsaoSettings::new(),
+        })
+    }
+
+    ///
+    /// Follow the frame to a new size.
+    ///
+    pub fn resize(&mut self, width: u32, height: u32) -> Result<(), String> {
+        for target in self.targets.iter_mut() {
+            if let Err(e) = target.resize(width, height) {
+                return Err(e);
+            }
+        }
+
+        Ok(())
+    }
+
+    ///
+    /// The blurred ambient occlusion as left by the last `apply`, from 0 where a
+    /// surface is shut away from the ambient light to 1 where it is out in the open.
+    ///
+    pub fn texture(&self) -> GLuint {
+        self.targets[1].colour_texture()
+    }
+
+    ///
+    /// Work out the occlusion of the frame in `gbuffer`, drawn with `view_mat` and
+    /// `proj_mat`, and blur it. Does nothing while the occlusion is off. The caller
+    /// has to restore the framebuffer and viewport afterwards.
+    ///
+    pub fn apply(&mut self, gbuffer: &RenderTarget, normal_attachment: usize, view_mat: &Mat4, proj_mat: &Mat4) {
+        if !self.settings.enabled {
+            return;
+        }
+
+        let (width, height) = self.targets[0].size();
+        self.ssao_timer.begin();
+        self.targets[0].bind();
+        unsafe {
+            gl::BindBufferBase(gl::UNIFORM_BUFFER, SSAO_KERNEL_BINDING, self.kernel_ubo);
+            gl::UseProgram(self.ssao_sp);
+            gl::UniformMatrix4fv(self.proj_location, 1, gl::FALSE, proj_mat.as_ptr());
+            gl::UniformMatrix4fv(self.inv_proj_location, 1, gl::FALSE, proj_mat.inverse().as_ptr());
+            gl::UniformMatrix4fv(self.view_location, 1, gl::FALSE, view_mat.as_ptr());
+            gl::Uniform1f(self.radius_location, self.settings.radius);
+            gl::Uniform2f(
+                self.noise_scale_location,
+                width as f32 / SSAO_NOISE_SIZE as f32, height as f32 / SSAO_NOISE_SIZE as f32
+            );
+        }
+        let inputs = [gbuffer.depth_texture(), gbuffer.attachment(normal_attachment), self.noise_tex];
+        self.pass.draw(self.ssao_sp, &inputs);
+        self.ssao_timer.end();
+
+        self.blur_timer.begin();
+        self.targets[1].bind();
+        self.pass.draw(self.blur_sp, &[self.targets[0].colour_texture()]);
+        self.blur_timer.end();
+        self.targets[1].unbind();
+    }
+
+    ///
+    /// The GPU time of each pass in milliseconds, a few frames behind.
+    ///
+    pub fn pass_times_ms(&mut self) -> [(&'static str, f64); 2] {
+        [
+            ("ssao", self.ssao_timer.elapsed_ms()),
+            ("ssao blur", self.blur_timer.elapsed_ms()),
+        ]
+    }
+}
+
+impl Drop for Ssao {
+    fn drop(&mut self) {
+        if gl::DeleteBuffers::is_loaded() {
+            unsafe {
+                gl::DeleteBuffers(1, &self.kernel_ubo);
+                gl::DeleteTextures(1, &self.noise_tex);
+            }
+        }
+    }
+}
+
+mod ssao_tests {
+    use super::{ssao_kernel, ssao_noise, SsaoSettings, XorShift32, MAX_SSAO_RADIUS, MIN_SSAO_RADIUS};
+
+    fn length(v: &[f32]) -> f32 {
+        f32::sqrt(v.iter().map(|x| x * x).sum())
+    }
+
+    #[test]
+    fn test_the_kernel_lies_in_the_hemisphere_over_the_surface() {
+        let kernel = ssao_kernel(32, &mut XorShift32::new(7));
+
+        assert_eq!(kernel.len(), 32);
+        for sample in kernel.iter() {
+            assert!(sample[2] >= 0.0);
+            assert!(length(&sample[..3]) <= 1.0);
+            assert_eq!(sample[3], 0.0);
+        }
+    }
+
+    #[test]
+    fn test_the_kernel_is_bunched_up_towards_the_centre() {
+        let kernel = ssao_kernel(64, &mut XorShift32::new(7));
+        let average_length = |samples: &[[f32; 4]]| {
+            samples.iter().map(|s| length(&s[..3])).sum::<f32>() / samples.len() as f32
+        };
+
+        assert!(average_length(&kernel[..32]) < average_length(&kernel[32..]));
+    }
+
+    #[test]
+    fn test_the_same_seed_makes_the_same_kernel() {
+        let first = ssao_kernel(16, &mut XorShift32::new(42));
+        let second = ssao_kernel(16, &mut XorShift32::new(42));
+
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn test_the_noise_lies_flat_and_is_in_range() {
+        let noise = ssao_noise(16, &mut XorShift32::new(1));
+
+        for v in noise.iter() {
+            assert_eq!(v[2], 0.0);
+            assert!(v[0] >= -1.0 && v[0] < 1.0);
+            assert!(v[1] >= -1.0 && v[1] < 1.0);
+        }
+    }
+
+    #[test]
+    fn test_the_radius_stays_in_range() {
+        let mut settings = SsaoSettings::new();
+        settings.change_radius(-100.0);
+        assert_eq!(settings.radius, MIN_SSAO_RADIUS);
+        settings.change_radius(100.0);
+        assert_eq!(settings.radius, MAX_SSAO_RADIUS);
+    }
+}
@@ -0,0 +1,23 @@
+#version 410
+
+// keep in step with SSAO_NOISE_SIZE in ssao.rs
+#define NOISE_SIZE 4
+
+in vec2 st;
+
+uniform sampler2D ssao_tex;
+
+out vec4 frag_colour;
+
+// average a square as big as the noise tile, so every way the kernel was turned
+// counts once and the tile's pattern evens out.
+void main() {
+	vec2 texel = 1.0 / vec2 (textureSize (ssao_tex, 0));
+	float sum = 0.0;
+	for (int y = -NOISE_SIZE / 2; y < NOISE_SIZE / 2; y++) {
+		for (int x = -NOISE_SIZE / 2; x < NOISE_SIZE / 2; x++) {
+			sum += texture (ssao_tex, st + vec2 (float (x), float (y)) * texel).r;
+		}
+	}
+	frag_colour = vec4 (sum / float (NOISE_SIZE * NOISE_SIZE), 0.0, 0.0, 1.0);
+}
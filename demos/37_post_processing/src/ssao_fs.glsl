@@ -0,0 +1,66 @@
+#version 410
+
+// keep in step with SSAO_KERNEL_SIZE in ssao.rs
+#define KERNEL_SIZE 32
+
+in vec2 st;
+
+uniform sampler2D depth_tex;
+// world space normals from the G-buffer. the background has none.
+uniform sampler2D normal_tex;
+// a tile of vectors that turn the kernel a different way at each pixel.
+uniform sampler2D noise_tex;
+// points in the hemisphere around +z, bunched up towards the centre.
+layout (std140) uniform SsaoKernel {
+	vec4 samples[KERNEL_SIZE];
+};
+uniform mat4 P, inv_P, V;
+// how far out from a surface the kernel reaches, in world units.
+uniform float radius;
+uniform float bias;
+// how many times the noise tile repeats across and down the frame.
+uniform vec2 noise_scale;
+
+out vec4 frag_colour;
+
+// rebuild the view space position of whatever was drawn at a point of the frame
+// from the depth buffer, by undoing the projection.
+vec3 view_position (vec2 uv) {
+	float depth = texture (depth_tex, uv).r;
+	vec4 ndc = vec4 (2.0 * uv - 1.0, 2.0 * depth - 1.0, 1.0);
+	vec4 view = inv_P * ndc;
+	return view.xyz / view.w;
+}
+
+void main() {
+	vec3 normal_world = texture (normal_tex, st).xyz;
+	// nothing was drawn here, so nothing can be in its way.
+	if (dot (normal_world, normal_world) == 0.0) {
+		frag_colour = vec4 (1.0, 0.0, 0.0, 1.0);
+		return;
+	}
+
+	vec3 p = view_position (st);
+	vec3 n = normalize (mat3 (V) * normal_world);
+	// turn the kernel about the normal by the noise, with gram-schmidt to make the
+	// noise vector at right angles to the normal.
+	vec3 random_vec = texture (noise_tex, st * noise_scale).xyz;
+	vec3 t = normalize (random_vec - n * dot (random_vec, n));
+	vec3 b = cross (n, t);
+	mat3 tbn = mat3 (t, b, n);
+
+	float occlusion = 0.0;
+	for (int i = 0; i < KERNEL_SIZE; i++) {
+		vec3 sample_pos = p + tbn * samples[i].xyz * radius;
+		// where the sample lands on the frame.
+		vec4 offset = P * vec4 (sample_pos, 1.0);
+		vec2 sample_st = 0.5 * (offset.xy / offset.w) + 0.5;
+		float scene_z = view_position (sample_st).z;
+		// something much closer to the camera than the surface is not in its way,
+		// so fade out occluders further than the radius away.
+		float in_range = smoothstep (0.0, 1.0, radius / abs (p.z - scene_z));
+		occlusion += (scene_z >= sample_pos.z + bias ? 1.0 : 0.0) * in_range;
+	}
+
+	frag_colour = vec4 (1.0 - occlusion / float (KERNEL_SIZE), 0.0, 0.0, 1.0);
+}
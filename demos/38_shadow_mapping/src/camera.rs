@@ -0,0 +1,257 @@
+use graphics_math as math;
+use math::{DMat4, DVec3, Mat4, Versor};
+
+use std::fs::File;
+use std::io::{Read, Write};
+
+
+///
+/// The file F5 saves the camera to and F9 restores it from, in the demo's
+/// working directory.
+///
+pub const CAMERA_FILE: &str = "camera.txt";
+
+///
+/// The parts of a virtual camera that determine what ends up on screen: where
+/// it sits, which way it faces, and how wide its vertical field of view is. The
+/// position is kept in double precision, so a camera far from the origin still
+/// moves smoothly when it moves a little at a time.
+///
+#[derive(Copy, Clone, Debug)]
+pub struct Camera {
+    pub position: DVec3,
+    pub orientation: Versor,
+    pub fovy: f32,
+}
+
+impl Camera {
+    pub fn new(position: DVec3, orientation: Versor, fovy: f32) -> Camera {
+        Camera {
+            position: position,
+            orientation: orientation,
+            fovy: fovy,
+        }
+    }
+
+    ///
+    /// Compute the view matrix from the camera position and orientation. The
+    /// matrix is worked out in double precision and only rounded to single
+    /// precision at the end, ready to upload.
+    ///
+    pub fn view_mat(&self) -> Mat4 {
+        let mat_rot = DMat4::from(self.orientation.to_mat4());
+        let mat_trans = DMat4::from_translation(&self.position);
+
+        Mat4::from(mat_rot.inverse() * mat_trans.inverse())
+    }
+
+    ///
+    /// The camera's heading in degrees, turning left from looking down -z about
+    /// the vertical axis, for the demos whose cameras only yaw. Any pitch or roll
+    /// is left out.
+    ///
+    pub fn yaw_deg(&self) -> f32 {
+        let fwd = self.orientation.rotate_vec3(&math::vec3((0.0, 0.0, -1.0)));
+
+        f32::atan2(-fwd.v[0], -fwd.v[2]) * math::ONE_RAD_IN_DEG
+    }
+
+    ///
+    /// How far the camera looks up from the horizon in degrees, or down when it
+    /// is negative. Together with `yaw_deg` this gives back the heading and look
+    /// of a camera that does not roll.
+    ///
+    pub fn pitch_deg(&self) -> f32 {
+        let fwd = self.orientation.rotate_vec3(&math::vec3((0.0, 0.0, -1.0)));
+
+        f32::asin(fwd.v[1].clamp(-1.0, 1.0)) * math::ONE_RAD_IN_DEG
+    }
+
+    ///
+    /// Compute a perspective projection matrix using the camera field of view.
+    ///
+    pub fn proj_mat(&self, aspect: f32, near: f32, far: f32) -> Mat4 {
+        Mat4::perspective(self.fovy, aspect, near, far)
+    }
+
+    ///
+    /// Write the camera state out as plain text. Each line is a label followed by
+    /// whitespace-separated numbers, so the file is easy to edit by hand.
+    ///
+    pub fn to_text(&self) -> String {
+        let q = self.orientation.as_ref();
+        format!(
+            "position {} {} {}\norientation {} {} {} {}\nfovy {}\n",
+            self.position.v[0], self.position.v[1], self.position.v[2],
+            q[0], q[1], q[2], q[3],
+            self.fovy
+        )
+    }
+
+    ///
+    /// Read the camera state back from text written by `to_text`.
+    ///
+    pub fn from_text(text: &str) -> Result<Camera, String> {
+        let mut position = None;
+        let mut orientation = None;
+        let mut fovy = None;
+        for line in text.lines() {
+            let mut words = line.split_whitespace();
+            let label = match words.next() {
+                Some(label) => label,
+                None => continue,
+            };
+            let mut values = vec![];
+            for word in words {
+                match word.parse::<f64>() {
+                    Ok(value) => values.push(value),
+                    Err(_) => return Err(format!("ERROR: invalid number {} in camera line: {}", word, line)),
+                }
+            }
+
+            match (label, values.len()) {
+                ("position", 3) => {
+                    position = Some(math::dvec3((values[0], values[1], values[2])));
+                }
+                ("orientation", 4) => {
+                    let q: Vec<f32> = values.iter().map(|&value| value as f32).collect();
+                    orientation = Some(Versor::new(q[0], q[1], q[2], q[3]).normalize());
+                }
+                ("fovy", 1) => {
+                    fovy = Some(values[0] as f32);
+                }
+                _ => {
+                    return Err(format!("ERROR: unrecognised camera line: {}", line));
+                }
+            }
+        }
+
+        match (position, orientation, fovy) {
+            (Some(position), Some(orientation), Some(fovy)) => {
+                Ok(Camera::new(position, orientation, fovy))
+            }
+            _ => Err(format!("ERROR: camera state is missing a position, orientation, or fovy.")),
+        }
+    }
+
+    ///
+    /// Save the camera state to a file.
+    ///
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        let mut file = match File::create(path) {
+            Ok(handle) => handle,
+            Err(e) => return Err(format!("ERROR: could not create camera file {}: {}", path, e)),
+        };
+
+        match file.write_all(self.to_text().as_bytes()) {
+            Ok(_) => Ok(()),
+            Err(e) => Err(format!("ERROR: could not write camera file {}: {}", path, e)),
+        }
+    }
+
+    ///
+    /// Load a camera state previously written by `save`.
+    ///
+    pub fn load(path: &str) -> Result<Camera, String> {
+        let mut file = match File::open(path) {
+            Ok(handle) => handle,
+            Err(e) => return Err(format!("ERROR: could not open camera file {}: {}", path, e)),
+        };
+
+        let mut text = String::new();
+        if let Err(e) = file.read_to_string(&mut text) {
+            return Err(format!("ERROR: could not read camera file {}: {}", path, e));
+        }
+
+        Camera::from_text(&text)
+    }
+
+    ///
+    /// Save the camera to `CAMERA_FILE` for F5, reporting how it went on the
+    /// console.
+    ///
+    pub fn save_to_camera_file(&self) {
+        match self.save(CAMERA_FILE) {
+            Ok(_) => println!("Saved camera to {}", CAMERA_FILE),
+            Err(e) => eprintln!("{}", e),
+        }
+    }
+
+    ///
+    /// Restore the camera saved by F5 for F9, reporting how it went on the
+    /// console. Returns `None` if there is no camera to restore.
+    ///
+    pub fn restore_from_camera_file() -> Option<Camera> {
+        match Camera::load(CAMERA_FILE) {
+            Ok(camera) => {
+                println!("Restored camera from {}", CAMERA_FILE);
+                Some(camera)
+            }
+            Err(e) => {
+                eprintln!("{}", e);
+                None
+            }
+        }
+    }
+}
+
+mod camera_tests {
+    use super::Camera;
+    use graphics_math as math;
+    use math::Versor;
+
+    #[test]
+    fn test_camera_text_round_trip() {
+        let camera = Camera::new(
+            math::dvec3((1.5, -2.25, 10.0)), Versor::from_axis_deg(30.0, 0.0, 1.0, 0.0), 55.0
+        );
+        let result = Camera::from_text(&camera.to_text()).unwrap();
+
+        assert_eq!(result.position, camera.position);
+        assert_eq!(result.fovy, camera.fovy);
+        for i in 0..4 {
+            assert!(f32::abs(result.orientation.as_ref()[i] - camera.orientation.as_ref()[i]) < math::EPSILON);
+        }
+    }
+
+    #[test]
+    fn test_camera_keeps_small_steps_far_from_the_origin() {
+        let mut camera = Camera::new(math::dvec3((500_000.0, 0.0, 0.0)), Versor::new(1.0, 0.0, 0.0, 0.0), 67.0);
+        for _ in 0..1000 {
+            camera.position += math::dvec3((0.001, 0.0, 0.0));
+        }
+
+        assert_eq!(camera.view_mat().m[12], -500_001.0);
+        let result = Camera::from_text(&camera.to_text()).unwrap();
+        assert_eq!(result.position, camera.position);
+    }
+
+    #[test]
+    fn test_camera_yaw_is_the_heading_it_was_turned_to() {
+        for &yaw in [0.0, 30.0, -120.0].iter() {
+            let camera = Camera::new(math::dvec3((0.0, 0.0, 5.0)), Versor::from_axis_deg(yaw, 0.0, 1.0, 0.0), 67.0);
+            assert!(f32::abs(camera.yaw_deg() - yaw) < 1e-3);
+        }
+    }
+
+    #[test]
+    fn test_camera_pitch_is_the_look_it_was_tilted_to() {
+        for &(yaw, pitch) in [(0.0, 0.0), (45.0, -30.0), (-120.0, 60.0)].iter() {
+            let q_yaw = Versor::from_axis_deg(yaw, 0.0, 1.0, 0.0);
+            let q_pitch = Versor::from_axis_deg(pitch, 1.0, 0.0, 0.0);
+            let camera = Camera::new(math::dvec3((0.0, 0.0, 5.0)), q_yaw * q_pitch, 67.0);
+            assert!(f32::abs(camera.yaw_deg() - yaw) < 1e-3);
+            assert!(f32::abs(camera.pitch_deg() - pitch) < 1e-3);
+        }
+    }
+
+    #[test]
+    fn test_camera_text_rejects_missing_fields() {
+        assert!(Camera::from_text("position 0 1 5\nfovy 67\n").is_err());
+    }
+
+    #[test]
+    fn test_camera_text_rejects_garbage() {
+        assert!(Camera::from_text("position 0 one 5\norientation 1 0 0 0\nfovy 67\n").is_err());
+    }
+}
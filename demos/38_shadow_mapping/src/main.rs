@@ -0,0 +1,735 @@
+extern crate gl;
+extern crate glfw;
+extern crate chrono;
+extern crate stb_image;
+#[cfg(feature = "glam")]
+extern crate glam;
+#[cfg(feature = "cgmath")]
+extern crate cgmath;
+
+#[macro_use]
+mod gl_utils;
+mod graphics_math;
+mod obj_parser;
+mod colour;
+mod assets;
+mod shader_include;
+mod spirv;
+mod program_cache;
+mod logger;
+mod overlay;
+mod streaming_buffer;
+mod camera;
+mod camera_controller;
+mod vertex_layout;
+mod gpu_timer;
+mod perf_stats;
+mod shadow_cascades;
+mod point_shadows;
+
+
+use glfw::{Action, Context, Key};
+use gl::types::{GLfloat, GLint, GLuint};
+
+use std::process;
+use std::time::Instant;
+
+use gl_utils::*;
+
+use graphics_math as math;
+use math::{Mat4, Vec3};
+
+use camera::Camera;
+use camera_controller::{CameraController, ControlKeys};
+use gpu_timer::GpuTimer;
+use overlay::Overlay;
+use perf_stats::{PerfStats, PERF_STATS_HISTORY};
+use point_shadows::{PointLightSettings, PointShadow, POINT_SHADOW_SIZE};
+use shadow_cascades::{ShadowCascades, CASCADE_COUNT, SHADOW_MAP_SIZE};
+use vertex_layout::VertexLayout;
+
+
+const GL_LOG_FILE: &str = "gl.log";
+const VERTEX_SHADER_FILE: &str = "src/scene_vs.glsl";
+const FRAGMENT_SHADER_FILE: &str = "src/scene_fs.glsl";
+const SHADOW_FS_FILE: &str = "src/shadow_fs.glsl";
+const POINT_SHADOW_FS_FILE: &str = "src/point_shadow_fs.glsl";
+
+const GROUND_HALF_SIZE: f32 = 100.0;
+const EYE_HEIGHT: f32 = 2.0;
+// The scene is rows of shapes marching off into the distance, with a pillar at
+// the end of every few rows, so there are shadows in every cascade.
+const ROW_COUNT: usize = 30;
+const ROW_SPACING: f32 = 4.0;
+const COLUMN_COUNT: usize = 5;
+const COLUMN_SPACING: f32 = 2.5;
+const PILLAR_EVERY: usize = 3;
+const PILLAR_HEIGHT: f32 = 6.0;
+// How far each key press leans the cascade splits.
+const SPLIT_LAMBDA_STEP: f32 = 0.05;
+// How far each key press turns or raises the sun, in degrees.
+const SUN_STEP_DEG: f32 = 5.0;
+const MIN_SUN_ELEVATION_DEG: f32 = 10.0;
+const MAX_SUN_ELEVATION_DEG: f32 = 90.0;
+// The point light circles over the first few rows, in radians a second.
+const POINT_LIGHT_ORBIT_CENTRE: [f32; 3] = [0.0, 1.8, -4.0];
+const POINT_LIGHT_ORBIT_RADIUS: f32 = 3.5;
+const POINT_LIGHT_ORBIT_SPEED: f64 = 0.5;
+const POINT_LIGHT_MARKER_SIZE: f32 = 0.15;
+// How far each key press changes the point light's radius and shadow bias.
+const LIGHT_RADIUS_STEP: f32 = 1.0;
+const POINT_SHADOW_BIAS_STEP: f32 = 0.01;
+
+const SKY_COLOUR: [f32; 4] = [0.6, 0.7, 0.8, 1.0];
+const GROUND_COLOUR: [f32; 3] = [0.35, 0.45, 0.3];
+const PILLAR_COLOUR: [f32; 3] = [0.75, 0.75, 0.7];
+const POINT_LIGHT_MARKER_COLOUR: [f32; 3] = [1.0, 0.9, 0.6];
+const OBJECT_COLOURS: [[f32; 3]; 5] = [
+    [0.8, 0.3, 0.2], [0.2, 0.5, 0.8], [0.9, 0.7, 0.2], [0.5, 0.3, 0.7], [0.3, 0.7, 0.6],
+];
+
+const OVERLAY_TEXT_COLOUR: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+const OVERLAY_BACKGROUND_COLOUR: [f32; 4] = [0.0, 0.0, 0.0, 0.6];
+
+
+///
+/// The ground plane: two triangles facing up, with each position followed by
+/// its normal.
+///
+fn ground_vertices() -> Vec<GLfloat> {
+    let a = GROUND_HALF_SIZE;
+    let corners = [(-a, a), (a, a), (a, -a), (a, -a), (-a, -a), (-a, a)];
+    let mut vertices = Vec::with_capacity(6 * corners.len());
+    for &(x, z) in corners.iter() {
+        vertices.extend_from_slice(&[x, 0.0, z, 0.0, 1.0, 0.0]);
+    }
+
+    vertices
+}
+
+///
+/// A unit cube centred on the origin, with each position followed by its normal.
+///
+fn cube_vertices() -> Vec<GLfloat> {
+    // The normal of each face, and two axes across it whose cross product is the
+    // normal, so the triangles wind counter-clockwise seen from outside.
+    let faces = [
+        ([1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]),
+        ([-1.0, 0.0, 0.0], [0.0, 0.0, 1.0], [0.0, 1.0, 0.0]),
+        ([0.0, 1.0, 0.0], [0.0, 0.0, 1.0], [1.0, 0.0, 0.0]),
+        ([0.0, -1.0, 0.0], [1.0, 0.0, 0.0], [0.0, 0.0, 1.0]),
+        ([0.0, 0.0, 1.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]),
+        ([0.0, 0.0, -1.0], [0.0, 1.0, 0.0], [1.0, 0.0, 0.0]),
+    ];
+    let corners = [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (1.0, 1.0), (-1.0, 1.0), (-1.0, -1.0)];
+
+    let mut vertices = Vec::with_capacity(6 * 6 * faces.len());
+    for &(n, u, v) in faces.iter() {
+        for &(s, t) in corners.iter() {
+            for i in 0..3 {
+                vertices.push(0.5 * (n[i] + s * u[i] + t * v[i]));
+            }
+            vertices.extend_from_slice(&n);
+        }
+    }
+
+    vertices
+}
+
+///
+/// Interleave separate position and normal arrays, as loaded from a mesh.
+///
+fn interleave(points: &[GLfloat], normals: &[GLfloat]) -> Vec<GLfloat> {
+    let mut vertices = Vec::with_capacity(points.len() + normals.len());
+    for i in 0..(points.len() / 3) {
+        vertices.extend_from_slice(&points[3 * i..3 * i + 3]);
+        vertices.extend_from_slice(&normals[3 * i..3 * i + 3]);
+    }
+
+    vertices
+}
+
+///
+/// Copy interleaved positions and normals into a vertex buffer, and make a vertex
+/// array reading positions from location 0 and normals from location 1.
+///
+fn make_vao(vertices: &[GLfloat]) -> GLuint {
+    let mut vao = 0;
+    unsafe {
+        gl::GenVertexArrays(1, &mut vao);
+    }
+    assert!(vao > 0);
+    VertexLayout::new().floats(0, 3).floats(1, 3).upload(vao, vertices, vertices.len() / 6);
+
+    vao
+}
+
+///
+/// The direction towards the sun, from its heading around the sky and its height
+/// above the horizon, in degrees.
+///
+fn sun_direction(azimuth_deg: f32, elevation_deg: f32) -> Vec3 {
+    let azimuth = azimuth_deg.to_radians();
+    let elevation = elevation_deg.to_radians();
+
+    math::vec3((
+        f32::cos(elevation) * f32::sin(azimuth), f32::sin(elevation), f32::cos(elevation) * f32::cos(azimuth)
+    ))
+}
+
+///
+/// The shader everything in the scene is drawn with: a flat colour lit by the
+/// sun and a point light. The sun's shadow maps are drawn with the same vertex shader and a fragment
+/// shader that has no colour.
+///
+struct SceneShader {
+    sp: GLuint,
+    model_location: GLint,
+    view_location: GLint,
+    proj_location: GLint,
+    colour_location: GLint,
+}
+
+impl SceneShader {
+    fn new(sp: GLuint) -> SceneShader {
+        let location = |name: &str| {
+            let location = uniform_location(sp, name);
+            assert!(location > -1);
+            location
+        };
+
+        SceneShader {
+            sp: sp,
+            model_location: location("M"),
+            view_location: location("V"),
+            proj_location: location("P"),
+            colour_location: uniform_location(sp, "colour"),
+        }
+    }
+
+    fn set_camera(&self, view_mat: &Mat4, proj_mat: &Mat4) {
+        unsafe {
+            gl::UseProgram(self.sp);
+            gl::UniformMatrix4fv(self.view_location, 1, gl::FALSE, view_mat.as_ptr());
+            gl::UniformMatrix4fv(self.proj_location, 1, gl::FALSE, proj_mat.as_ptr());
+        }
+    }
+
+    fn draw(&self, vao: GLuint, point_count: usize, model_mat: &Mat4, colour: &[f32; 3], perf_stats: &mut PerfStats) {
+        unsafe {
+            gl::UniformMatrix4fv(self.model_location, 1, gl::FALSE, model_mat.as_ptr());
+            gl::Uniform3f(self.colour_location, colour[0], colour[1], colour[2]);
+            gl::BindVertexArray(vao);
+            gl::DrawArrays(gl::TRIANGLES, 0, point_count as i32);
+        }
+        perf_stats.count_draw(point_count / 3);
+    }
+}
+
+///
+/// The scene shader's lighting and shadow uniforms, for the sun and the point
+/// light.
+///
+struct Lighting {
+    sp: GLuint,
+    light_dir_location: GLint,
+    light_view_proj_location: GLint,
+    cascade_far_location: GLint,
+    shadows_enabled_location: GLint,
+    show_cascades_location: GLint,
+    point_light_enabled_location: GLint,
+    point_light_pos_location: GLint,
+    point_light_radius_location: GLint,
+    point_shadow_bias_location: GLint,
+    eye_location: GLint,
+    unlit_location: GLint,
+}
+
+impl Lighting {
+    fn new(sp: GLuint) -> Lighting {
+        let location = |name: &str| {
+            let location = uniform_location(sp, name);
+            assert!(location > -1);
+            location
+        };
+        unsafe {
+            gl::UseProgram(sp);
+            gl::Uniform1i(location("shadow_map"), 0);
+            gl::Uniform1i(location("point_shadow_map"), 1);
+        }
+
+        Lighting {
+            sp: sp,
+            light_dir_location: location("light_dir_world"),
+            light_view_proj_location: location("light_view_proj"),
+            cascade_far_location: location("cascade_far"),
+            shadows_enabled_location: location("shadows_enabled"),
+            show_cascades_location: location("show_cascades"),
+            point_light_enabled_location: location("point_light_enabled"),
+            point_light_pos_location: location("point_light_pos_world"),
+            point_light_radius_location: location("point_light_radius"),
+            point_shadow_bias_location: location("point_shadow_bias"),
+            eye_location: location("eye_world"),
+            unlit_location: location("unlit"),
+        }
+    }
+
+    ///
+    /// Light the scene from `sun`, the direction towards the sun, with the shadow
+    /// cascades bound to texture unit 0.
+    ///
+    fn set_sun(&self, sun: &Vec3, cascades: &ShadowCascades, shadows_enabled: bool, show_cascades: bool) {
+        let mut view_projs = Vec::with_capacity(16 * CASCADE_COUNT);
+        for view_proj in cascades.view_projs().iter() {
+            view_projs.extend_from_slice(&view_proj.m);
+        }
+        let far_distances = cascades.far_distances();
+        unsafe {
+            gl::UseProgram(self.sp);
+            gl::Uniform3f(self.light_dir_location, sun.v[0], sun.v[1], sun.v[2]);
+            gl::UniformMatrix4fv(
+                self.light_view_proj_location, CASCADE_COUNT as i32, gl::FALSE, view_projs.as_ptr()
+            );
+            gl::Uniform1fv(self.cascade_far_location, CASCADE_COUNT as i32, far_distances.as_ptr());
+            gl::Uniform1i(self.shadows_enabled_location, shadows_enabled as GLint);
+            gl::Uniform1i(self.show_cascades_location, show_cascades as GLint);
+            gl::ActiveTexture(gl::TEXTURE0);
+            gl::BindTexture(gl::TEXTURE_2D_ARRAY, cascades.texture());
+        }
+    }
+
+    ///
+    /// Light the scene from a point light at `position` as well, with its shadow
+    /// cube map bound to texture unit 1.
+    ///
+    fn set_point_light(&self, position: &Vec3, settings: &PointLightSettings, shadow: &PointShadow) {
+        unsafe {
+            gl::UseProgram(self.sp);
+            gl::Uniform1i(self.point_light_enabled_location, settings.enabled as GLint);
+            gl::Uniform3f(self.point_light_pos_location, position.v[0], position.v[1], position.v[2]);
+            gl::Uniform1f(self.point_light_radius_location, settings.radius);
+            gl::Uniform1f(self.point_shadow_bias_location, settings.bias);
+            gl::ActiveTexture(gl::TEXTURE1);
+            gl::BindTexture(gl::TEXTURE_CUBE_MAP, shadow.texture());
+            gl::ActiveTexture(gl::TEXTURE0);
+        }
+    }
+
+    ///
+    /// Where the camera is, for the specular highlights.
+    ///
+    fn set_eye(&self, eye: &Vec3) {
+        unsafe {
+            gl::UseProgram(self.sp);
+            gl::Uniform3f(self.eye_location, eye.v[0], eye.v[1], eye.v[2]);
+        }
+    }
+
+    ///
+    /// Draw in flat colour, unlit and unshadowed, or go back to lighting.
+    ///
+    fn set_unlit(&self, unlit: bool) {
+        unsafe {
+            gl::UseProgram(self.sp);
+            gl::Uniform1i(self.unlit_location, unlit as GLint);
+        }
+    }
+}
+
+///
+/// The shader the point light's shadow cube map is drawn with: the scene's vertex
+/// shader, and a fragment shader that writes the distance to the light as depth.
+///
+struct PointShadowShader {
+    shader: SceneShader,
+    light_pos_location: GLint,
+    light_radius_location: GLint,
+}
+
+impl PointShadowShader {
+    fn new(sp: GLuint) -> PointShadowShader {
+        let location = |name: &str| {
+            let location = uniform_location(sp, name);
+            assert!(location > -1);
+            location
+        };
+
+        PointShadowShader {
+            shader: SceneShader::new(sp),
+            light_pos_location: location("light_pos_world"),
+            light_radius_location: location("light_radius"),
+        }
+    }
+
+    fn set_light(&self, position: &Vec3, radius: f32) {
+        unsafe {
+            gl::UseProgram(self.shader.sp);
+            gl::Uniform3f(self.light_pos_location, position.v[0], position.v[1], position.v[2]);
+            gl::Uniform1f(self.light_radius_location, radius);
+        }
+    }
+}
+
+///
+/// Where the point light is after `seconds`, circling low over the first few rows.
+///
+fn point_light_position(seconds: f64) -> Vec3 {
+    let angle = (seconds * POINT_LIGHT_ORBIT_SPEED) as f32;
+
+    math::vec3((
+        POINT_LIGHT_ORBIT_CENTRE[0] + POINT_LIGHT_ORBIT_RADIUS * f32::cos(angle),
+        POINT_LIGHT_ORBIT_CENTRE[1],
+        POINT_LIGHT_ORBIT_CENTRE[2] + POINT_LIGHT_ORBIT_RADIUS * f32::sin(angle)
+    ))
+}
+
+///
+/// The vertex arrays of the ground and of the shapes standing on it.
+///
+struct Meshes {
+    ground_vao: GLuint,
+    ground_point_count: usize,
+    cube_vao: GLuint,
+    cube_point_count: usize,
+    sphere_vao: GLuint,
+    sphere_point_count: usize,
+}
+
+impl Meshes {
+    ///
+    /// Draw the ground, and the rows of shapes on it. Cubes and spheres take turns
+    /// down each row, and each one is turned a little more than the last. Every
+    /// few rows end in a tall pillar either side.
+    ///
+    fn draw_scene(&self, shader: &SceneShader, perf_stats: &mut PerfStats) {
+        shader.draw(self.ground_vao, self.ground_point_count, &Mat4::identity(), &GROUND_COLOUR, perf_stats);
+        for row in 0..ROW_COUNT {
+            let z = -(row as f32) * ROW_SPACING;
+            for column in 0..COLUMN_COUNT {
+                let i = row * COLUMN_COUNT + column;
+                let x = (column as f32 - 0.5 * (COLUMN_COUNT - 1) as f32) * COLUMN_SPACING;
+                let colour = &OBJECT_COLOURS[i % OBJECT_COLOURS.len()];
+                if i % 2 == 0 {
+                    let model_mat = Mat4::from_rotation_y_deg(15.0 * i as f32)
+                        .then_translate(&math::vec3((x, 0.5, z)));
+                    shader.draw(self.cube_vao, self.cube_point_count, &model_mat, colour, perf_stats);
+                } else {
+                    // The embedded sphere has a radius of one, so halve it to match the cubes.
+                    let model_mat = Mat4::from_scale(&math::vec3((0.5, 0.5, 0.5)))
+                        .then_translate(&math::vec3((x, 0.5, z)));
+                    shader.draw(self.sphere_vao, self.sphere_point_count, &model_mat, colour, perf_stats);
+                }
+            }
+            if row % PILLAR_EVERY == 0 {
+                let x = 0.5 * (COLUMN_COUNT + 1) as f32 * COLUMN_SPACING;
+                for &side in [-1.0, 1.0].iter() {
+                    let model_mat = Mat4::from_scale(&math::vec3((0.6, PILLAR_HEIGHT, 0.6)))
+                        .then_translate(&math::vec3((side * x, 0.5 * PILLAR_HEIGHT, z)));
+                    shader.draw(self.cube_vao, self.cube_point_count, &model_mat, &PILLAR_COLOUR, perf_stats);
+                }
+            }
+        }
+    }
+}
+
+///
+/// Queue up a label in the top-left corner of the viewport. Returns the bottom
+/// edge of the label.
+///
+fn label(overlay: &mut Overlay, text: &str) -> f32 {
+    let scale = 2.0;
+    let longest = text.lines().map(|line| line.len()).max().unwrap_or(0);
+    let width = (longest as f32 * overlay::GLYPH_ADVANCE + 4.0) * scale;
+    let height = (text.lines().count() as f32 * overlay::LINE_HEIGHT + 4.0) * scale;
+    overlay.rect(10.0, 10.0, width, height, OVERLAY_BACKGROUND_COLOUR);
+    overlay.text(10.0 + 2.0 * scale, 10.0 + 2.0 * scale, scale, OVERLAY_TEXT_COLOUR, text);
+
+    10.0 + height
+}
+
+///
+/// Keep the context's idea of the framebuffer size up to date.
+///
+fn glfw_framebuffer_size_callback(context: &mut GLContext, width: u32, height: u32) {
+    context.width = width;
+    context.height = height;
+}
+
+fn main() {
+    let logger = restart_gl_log(GL_LOG_FILE);
+    // Start a GL context and O/S window using the GLFW helper library.
+    let mut context = match start_gl(&logger) {
+        Ok(val) => val,
+        Err(e) => {
+            eprintln!("Failed to Initialize OpenGL context. Got error:");
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+    };
+    // The framebuffer can be bigger than the window on high DPI screens.
+    context.window.set_framebuffer_size_polling(true);
+    let (width, height) = context.window.get_framebuffer_size();
+    context.width = width as u32;
+    context.height = height as u32;
+
+    // the depth of the scene seen from the sun, one layer per cascade.
+    let mut cascades = match ShadowCascades::new(SHADOW_MAP_SIZE) {
+        Ok(val) => val,
+        Err(e) => {
+            logger.log_err(&e);
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+    };
+    // the distance to the nearest surface in every direction from the point light.
+    let point_shadow = match PointShadow::new(POINT_SHADOW_SIZE) {
+        Ok(val) => val,
+        Err(e) => {
+            logger.log_err(&e);
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+    };
+
+    unsafe {
+        // Enable depth testing.
+        gl::Enable(gl::DEPTH_TEST);
+        // Depth testing interprets a smaller value as closer to the eye.
+        gl::DepthFunc(gl::LESS);
+        // Cull face.
+        gl::Enable(gl::CULL_FACE);
+        // Cull back face.
+        gl::CullFace(gl::BACK);
+        // GL_CW for clockwise.
+        gl::FrontFace(gl::CCW);
+        gl::ClearColor(SKY_COLOUR[0], SKY_COLOUR[1], SKY_COLOUR[2], SKY_COLOUR[3]);
+    }
+
+    /*------------------------------CREATE GEOMETRY------------------------------*/
+    let ground = ground_vertices();
+    let cube = cube_vertices();
+    let sphere_mesh = assets::fallback_mesh();
+    let meshes = Meshes {
+        ground_vao: make_vao(&ground),
+        ground_point_count: ground.len() / 6,
+        cube_vao: make_vao(&cube),
+        cube_point_count: cube.len() / 6,
+        sphere_vao: make_vao(&interleave(&sphere_mesh.points, &sphere_mesh.normals)),
+        sphere_point_count: sphere_mesh.point_count,
+    };
+
+    /*-------------------------------CREATE SHADERS------------------------------*/
+    let shader_programme = create_programme_from_files(&logger, VERTEX_SHADER_FILE, FRAGMENT_SHADER_FILE);
+    let scene_shader = SceneShader::new(shader_programme);
+    let lighting = Lighting::new(shader_programme);
+    let shadow_programme = create_programme_from_files(&logger, VERTEX_SHADER_FILE, SHADOW_FS_FILE);
+    let shadow_shader = SceneShader::new(shadow_programme);
+    let point_shadow_programme = create_programme_from_files(&logger, VERTEX_SHADER_FILE, POINT_SHADOW_FS_FILE);
+    let point_shadow_shader = PointShadowShader::new(point_shadow_programme);
+
+    /*-------------------------------CREATE CAMERA-------------------------------*/
+    let mut controller = CameraController::new(math::vec3((0.0, EYE_HEIGHT, 6.0)), 0.0, -10.0, 67.0, ControlKeys {
+        forward: Key::W, back: Key::S, step_left: Key::A, step_right: Key::D,
+        turn_left: Key::Left, turn_right: Key::Right, look_up: Key::Up, look_down: Key::Down,
+    });
+    logger.log("keys: W, A, S, and D walk, and the arrow keys turn and look up and down;");
+    logger.log("      C tints each shadow cascade, H toggles the shadows, [ and ] lean the cascade splits");
+    logger.log("      towards even or logarithmic, , and . turn the sun, - and = lower and raise it,");
+    logger.log("      L toggles the point light, 9 and 0 shrink and grow its radius, 7 and 8 lower and");
+    logger.log("      raise its shadow bias, and F3 toggles the performance HUD;");
+    logger.log("      F5 saves the camera to camera.txt, and F9 restores it");
+
+    // input variables
+    let near = 0.1;                                  // clipping plane
+    let far = 150.0;                                 // clipping plane
+
+    // where the sun is in the sky, in degrees.
+    let mut sun_azimuth = 35.0;
+    let mut sun_elevation = 45.0;
+    let mut shadows_enabled = true;
+    let mut show_cascades = false;
+    let mut point_light = PointLightSettings::new();
+
+    // the cascade splits, drawn in the corner, with the frame times and the GPU
+    // time of each pass underneath. F3 toggles the times on and off.
+    let mut overlay = Overlay::new(&logger);
+    let mut perf_stats = PerfStats::new(PERF_STATS_HISTORY);
+    let mut show_perf_stats = true;
+    let mut shadow_timer = GpuTimer::new();
+    let mut point_shadow_timer = GpuTimer::new();
+    let mut scene_timer = GpuTimer::new();
+
+    while !context.window.should_close() {
+        let frame_start = Instant::now();
+        let current_seconds = context.glfw.get_time();
+        let elapsed_seconds = current_seconds - context.elapsed_time_seconds;
+        context.elapsed_time_seconds = current_seconds;
+
+        update_fps_counter(&mut context);
+
+        let aspect = context.width as f32 / u32::max(context.height, 1) as f32;
+        let proj_mat = controller.camera.proj_mat(aspect, near, far);
+        let view_mat = controller.camera.view_mat();
+        let sun = sun_direction(sun_azimuth, sun_elevation);
+
+        // Draw the scene's depth from the sun into each cascade.
+        cascades.update(&view_mat, controller.camera.fovy, aspect, near, far, &-sun);
+        shadow_timer.begin();
+        if shadows_enabled {
+            cascades.render(|light_view_proj| {
+                shadow_shader.set_camera(&Mat4::identity(), light_view_proj);
+                meshes.draw_scene(&shadow_shader, &mut perf_stats);
+            });
+        }
+        shadow_timer.end();
+
+        // And the distance to the scene in every direction from the point light.
+        let light_position = point_light_position(current_seconds);
+        point_shadow_timer.begin();
+        if point_light.enabled {
+            point_shadow_shader.set_light(&light_position, point_light.radius);
+            point_shadow.render(&light_position, point_light.radius, |face_view, face_proj| {
+                point_shadow_shader.shader.set_camera(face_view, face_proj);
+                meshes.draw_scene(&point_shadow_shader.shader, &mut perf_stats);
+            });
+        }
+        point_shadow_timer.end();
+
+        // Then draw the scene lit by the sun, looking each fragment up in its cascade.
+        scene_timer.begin();
+        unsafe {
+            gl::Viewport(0, 0, context.width as i32, context.height as i32);
+            gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
+        }
+        lighting.set_sun(&sun, &cascades, shadows_enabled, show_cascades);
+        lighting.set_point_light(&light_position, &point_light, &point_shadow);
+        lighting.set_eye(&Vec3::from(controller.camera.position));
+        scene_shader.set_camera(&view_mat, &proj_mat);
+        meshes.draw_scene(&scene_shader, &mut perf_stats);
+        if point_light.enabled {
+            // A small glowing ball where the light is. It is left out of the shadow
+            // maps, or the light would be shut inside it.
+            let marker_mat = Mat4::from_scale(&math::vec3((POINT_LIGHT_MARKER_SIZE, POINT_LIGHT_MARKER_SIZE, POINT_LIGHT_MARKER_SIZE)))
+                .then_translate(&light_position);
+            lighting.set_unlit(true);
+            scene_shader.draw(
+                meshes.sphere_vao, meshes.sphere_point_count, &marker_mat, &POINT_LIGHT_MARKER_COLOUR, &mut perf_stats
+            );
+            lighting.set_unlit(false);
+        }
+        scene_timer.end();
+
+        perf_stats.set_gpu_pass_ms("shadows", shadow_timer.elapsed_ms());
+        perf_stats.set_gpu_pass_ms("point shadow", point_shadow_timer.elapsed_ms());
+        perf_stats.set_gpu_pass_ms("scene", scene_timer.elapsed_ms());
+        let label_text = format!(
+            "shadows: {}, cascade tint: {}\n{}\nsun: heading {:.0}, height {:.0}\n{}",
+            if shadows_enabled { "on" } else { "off" }, if show_cascades { "on" } else { "off" },
+            cascades.describe(), sun_azimuth, sun_elevation, point_light.describe()
+        );
+        let label_bottom = label(&mut overlay, &label_text);
+        if show_perf_stats {
+            perf_stats.draw_hud(&mut overlay, 10.0, label_bottom + 6.0);
+        }
+        overlay.draw(context.width, context.height);
+
+        context.glfw.poll_events();
+
+        let mut toggle_fullscreen = false;
+        let mut resized_to = None;
+        for (_, event) in glfw::flush_messages(&context.events) {
+            match event {
+                glfw::WindowEvent::FramebufferSize(width, height) => {
+                    resized_to = Some((width, height));
+                }
+                glfw::WindowEvent::Key(Key::C, _, Action::Press, _) => {
+                    show_cascades = !show_cascades;
+                    logger.log(&format!("cascade tint: {}", show_cascades));
+                }
+                glfw::WindowEvent::Key(Key::H, _, Action::Press, _) => {
+                    shadows_enabled = !shadows_enabled;
+                    logger.log(&format!("shadows: {}", shadows_enabled));
+                }
+                glfw::WindowEvent::Key(Key::LeftBracket, _, Action::Press, _) => {
+                    cascades.change_lambda(-SPLIT_LAMBDA_STEP);
+                    logger.log(&cascades.describe());
+                }
+                glfw::WindowEvent::Key(Key::RightBracket, _, Action::Press, _) => {
+                    cascades.change_lambda(SPLIT_LAMBDA_STEP);
+                    logger.log(&cascades.describe());
+                }
+                glfw::WindowEvent::Key(Key::Comma, _, Action::Press, _) => {
+                    sun_azimuth = (sun_azimuth - SUN_STEP_DEG) % 360.0;
+                }
+                glfw::WindowEvent::Key(Key::Period, _, Action::Press, _) => {
+                    sun_azimuth = (sun_azimuth + SUN_STEP_DEG) % 360.0;
+                }
+                glfw::WindowEvent::Key(Key::Minus, _, Action::Press, _) => {
+                    sun_elevation = f32::max(sun_elevation - SUN_STEP_DEG, MIN_SUN_ELEVATION_DEG);
+                }
+                glfw::WindowEvent::Key(Key::Equal, _, Action::Press, _) => {
+                    sun_elevation = f32::min(sun_elevation + SUN_STEP_DEG, MAX_SUN_ELEVATION_DEG);
+                }
+                glfw::WindowEvent::Key(Key::L, _, Action::Press, _) => {
+                    point_light.enabled = !point_light.enabled;
+                    logger.log(&point_light.describe());
+                }
+                glfw::WindowEvent::Key(Key::Num9, _, Action::Press, _) => {
+                    point_light.change_radius(-LIGHT_RADIUS_STEP);
+                    logger.log(&point_light.describe());
+                }
+                glfw::WindowEvent::Key(Key::Num0, _, Action::Press, _) => {
+                    point_light.change_radius(LIGHT_RADIUS_STEP);
+                    logger.log(&point_light.describe());
+                }
+                glfw::WindowEvent::Key(Key::Num7, _, Action::Press, _) => {
+                    point_light.change_bias(-POINT_SHADOW_BIAS_STEP);
+                    logger.log(&point_light.describe());
+                }
+                glfw::WindowEvent::Key(Key::Num8, _, Action::Press, _) => {
+                    point_light.change_bias(POINT_SHADOW_BIAS_STEP);
+                    logger.log(&point_light.describe());
+                }
+                glfw::WindowEvent::Key(Key::F3, _, Action::Press, _) => {
+                    show_perf_stats = !show_perf_stats;
+                }
+                glfw::WindowEvent::Key(Key::Enter, _, Action::Press, mods) => {
+                    if mods.contains(glfw::Modifiers::Alt) {
+                        toggle_fullscreen = true;
+                    }
+                }
+                glfw::WindowEvent::Key(Key::Escape, _, Action::Press, _) => {
+                    context.window.set_should_close(true);
+                }
+                glfw::WindowEvent::Key(Key::F5, _, Action::Press, _) => {
+                    controller.camera.save_to_camera_file();
+                }
+                glfw::WindowEvent::Key(Key::F9, _, Action::Press, _) => {
+                    if let Some(camera) = Camera::restore_from_camera_file() {
+                        controller.set_camera(&camera);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        if toggle_fullscreen {
+            let fullscreen = context.toggle_fullscreen();
+            logger.log(&format!("fullscreen: {}", fullscreen));
+            let (width, height) = context.window.get_framebuffer_size();
+            resized_to = Some((width, height));
+        }
+
+        if let Some((width, height)) = resized_to {
+            glfw_framebuffer_size_callback(&mut context, width as u32, height as u32);
+            logger.log(&format!("framebuffer resized to {}x{}", width, height));
+        }
+
+        // control keys
+        controller.update(&context.window, elapsed_seconds);
+
+        // Put the stuff we've been drawing onto the display.
+        context.window.swap_buffers();
+        perf_stats.end_frame(perf_stats::duration_to_ms(frame_start.elapsed()));
+    }
+}
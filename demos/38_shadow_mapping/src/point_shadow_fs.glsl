@@ -0,0 +1,11 @@
+#version 410
+
+in vec3 p_world;
+uniform vec3 light_pos_world;
+uniform float light_radius;
+
+// store the straight line distance to the light rather than the projection's
+// depth, so the scene shader can compare against it from any direction.
+void main () {
+	gl_FragDepth = length (p_world - light_pos_world) / light_radius;
+}
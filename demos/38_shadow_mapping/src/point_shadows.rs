@@ -0,0 +1,279 @@
+use gl;
+use gl::types::{GLenum, GLint, GLsizei, GLuint};
+
+use graphics_math as math;
+use math::{Mat4, Vec3};
+
+use std::ptr;
+
+
+///
+/// The width and height of each face of the point light's shadow cube map, in
+/// texels.
+///
+pub const POINT_SHADOW_SIZE: u32 = 1024;
+///
+/// The near plane of each face's projection. Nothing closer to the light than
+/// this casts a shadow.
+///
+pub const POINT_SHADOW_NEAR: f32 = 0.05;
+///
+/// How far the light reaches, and how far it can be shrunk or grown.
+///
+pub const DEFAULT_LIGHT_RADIUS: f32 = 15.0;
+pub const MIN_LIGHT_RADIUS: f32 = 2.0;
+pub const MAX_LIGHT_RADIUS: f32 = 40.0;
+///
+/// How much further from the light than the stored depth a surface has to be
+/// before it counts as in shadow, in world units.
+///
+pub const DEFAULT_POINT_SHADOW_BIAS: f32 = 0.05;
+pub const MAX_POINT_SHADOW_BIAS: f32 = 0.5;
+
+
+///
+/// The direction each face of a cube map looks in from its centre, and which way
+/// is up on it, in the order of the `TEXTURE_CUBE_MAP_POSITIVE_X` face targets.
+/// Cube map faces are laid out upside down from what a camera sees, hence the
+/// upside down ups.
+///
+pub const CUBE_FACES: [([f32; 3], [f32; 3]); 6] = [
+    ([1.0, 0.0, 0.0], [0.0, -1.0, 0.0]),
+    ([-1.0, 0.0, 0.0], [0.0, -1.0, 0.0]),
+    ([0.0, 1.0, 0.0], [0.0, 0.0, 1.0]),
+    ([0.0, -1.0, 0.0], [0.0, 0.0, -1.0]),
+    ([0.0, 0.0, 1.0], [0.0, -1.0, 0.0]),
+    ([0.0, 0.0, -1.0], [0.0, -1.0, 0.0]),
+];
+
+
+///
+/// A view matrix for a viewer at `eye` looking along `forward`, with `up`
+/// pointing up the screen.
+///
+pub fn look_along(eye: &Vec3, forward: &Vec3, up: &Vec3) -> Mat4 {
+    let f = forward.normalize();
+    let r = f.cross(up).normalize();
+    let u = r.cross(&f);
+
+    Mat4::new(
+        r.v[0], u.v[0], -f.v[0], 0.0,
+        r.v[1], u.v[1], -f.v[1], 0.0,
+        r.v[2], u.v[2], -f.v[2], 0.0,
+        -r.dot(eye), -u.dot(eye), f.dot(eye), 1.0
+    )
+}
+
+///
+/// The view matrix of cube map face `face` for a light at `position`.
+///
+pub fn cube_face_view(position: &Vec3, face: usize) -> Mat4 {
+    let (forward, up) = CUBE_FACES[face];
+
+    look_along(position, &math::vec3((forward[0], forward[1], forward[2])), &math::vec3((up[0], up[1], up[2])))
+}
+
+///
+/// The projection every face of the cube map shares: a quarter turn across, so
+/// the six faces meet edge to edge, out to the light's radius.
+///
+pub fn cube_face_proj(radius: f32) -> Mat4 {
+    Mat4::perspective(90.0, 1.0, POINT_SHADOW_NEAR, radius)
+}
+
+///
+/// The settings of the point light that can be changed while the demo runs.
+///
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct PointLightSettings {
+    pub enabled: bool,
+    pub radius: f32,
+    pub bias: f32,
+}
+
+impl PointLightSettings {
+    pub fn new() -> PointLightSettings {
+        PointLightSettings {
+            enabled: true,
+            radius: DEFAULT_LIGHT_RADIUS,
+            bias: DEFAULT_POINT_SHADOW_BIAS,
+        }
+    }
+
+    pub fn change_radius(&mut self, step: f32) {
+        self.radius = f32::min(f32::max(self.radius + step, MIN_LIGHT_RADIUS), MAX_LIGHT_RADIUS);
+    }
+
+    ///
+    /// Raise or lower the bias. Too little and lit surfaces speckle with their own
+    /// shadow; too much and shadows come loose from the feet of what casts them.
+    ///
+    pub fn change_bias(&mut self, step: f32) {
+        self.bias = f32::min(f32::max(self.bias + step, 0.0), MAX_POINT_SHADOW_BIAS);
+    }
+
+    pub fn describe(&self) -> String {
+        if !self.enabled {
+            return String::from("point light: off");
+        }
+
+        format!("point light: radius {:.1}, shadow bias {:.3}", self.radius, self.bias)
+    }
+}
+
+///
+/// Shadows cast in every direction by a point light. The distance from the light
+/// to the nearest surface is drawn into each face of a depth cube map in turn,
+/// scaled so the light's radius is a depth of one. Unlike the depth a projection
+/// leaves, that is the same straight line distance the scene shader works out for
+/// each fragment, so the two can be compared directly along any direction.
+///
+pub struct PointShadow {
+    fbo: GLuint,
+    depth_tex: GLuint,
+    size: u32,
+}
+
+impl PointShadow {
+    ///
+    /// Make the depth cube map, `size` texels on a side. Returns an error if the
+    /// driver cannot render to it.
+    ///
+    pub fn new(size: u32) -> Result<PointShadow, String> {
+        let mut depth_tex = 0;
+        let mut fbo = 0;
+        unsafe {
+            gl::GenTextures(1, &mut depth_tex);
+            gl::BindTexture(gl::TEXTURE_CUBE_MAP, depth_tex);
+            for face in 0..6 {
+                gl::TexImage2D(
+                    gl::TEXTURE_CUBE_MAP_POSITIVE_X + face as GLenum, 0, gl::DEPTH_COMPONENT24 as GLint,
+                    size as GLsizei, size as GLsizei, 0, gl::DEPTH_COMPONENT, gl::FLOAT, ptr::null()
+                );
+            }
+            gl::TexParameteri(gl::TEXTURE_CUBE_MAP, gl::TEXTURE_MAG_FILTER, gl::NEAREST as GLint);
+            gl::TexParameteri(gl::TEXTURE_CUBE_MAP, gl::TEXTURE_MIN_FILTER, gl::NEAREST as GLint);
+            gl::TexParameteri(gl::TEXTURE_CUBE_MAP, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as GLint);
+            gl::TexParameteri(gl::TEXTURE_CUBE_MAP, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as GLint);
+            gl::TexParameteri(gl::TEXTURE_CUBE_MAP, gl::TEXTURE_WRAP_R, gl::CLAMP_TO_EDGE as GLint);
+
+            gl::GenFramebuffers(1, &mut fbo);
+            gl::BindFramebuffer(gl::FRAMEBUFFER, fbo);
+            gl::FramebufferTexture2D(
+                gl::FRAMEBUFFER, gl::DEPTH_ATTACHMENT, gl::TEXTURE_CUBE_MAP_POSITIVE_X, depth_tex, 0
+            );
+            // Only depth is drawn.
+            gl::DrawBuffer(gl::NONE);
+            gl::ReadBuffer(gl::NONE);
+        }
+        let status = unsafe { gl::CheckFramebufferStatus(gl::FRAMEBUFFER) };
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+        }
+        assert!(depth_tex > 0);
+
+        let shadow = PointShadow {
+            fbo: fbo,
+            depth_tex: depth_tex,
+            size: size,
+        };
+        if status != gl::FRAMEBUFFER_COMPLETE {
+            return Err(format!("ERROR: point shadow framebuffer is incomplete. Status: 0x{:x}", status));
+        }
+
+        Ok(shadow)
+    }
+
+    ///
+    /// Draw the shadow casters around a light at `position` into each face of the
+    /// cube map. `draw` is called once a face with the face's view and projection
+    /// to draw with. The shader it draws with has to write the distance to the
+    /// light over `radius` as the fragment's depth.
+    ///
+    pub fn render<F>(&self, position: &Vec3, radius: f32, mut draw: F) where F: FnMut(&Mat4, &Mat4) {
+        let proj_mat = cube_face_proj(radius);
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, self.fbo);
+            gl::Viewport(0, 0, self.size as GLsizei, self.size as GLsizei);
+        }
+        for face in 0..6 {
+            unsafe {
+                gl::FramebufferTexture2D(
+                    gl::FRAMEBUFFER, gl::DEPTH_ATTACHMENT, gl::TEXTURE_CUBE_MAP_POSITIVE_X + face as GLenum,
+                    self.depth_tex, 0
+                );
+                gl::Clear(gl::DEPTH_BUFFER_BIT);
+            }
+            draw(&cube_face_view(position, face), &proj_mat);
+        }
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+        }
+    }
+
+    ///
+    /// The depth cube map, with distances to the light over its radius.
+    ///
+    pub fn texture(&self) -> GLuint {
+        self.depth_tex
+    }
+}
+
+impl Drop for PointShadow {
+    fn drop(&mut self) {
+        if gl::DeleteFramebuffers::is_loaded() {
+            unsafe {
+                gl::DeleteFramebuffers(1, &self.fbo);
+                gl::DeleteTextures(1, &self.depth_tex);
+            }
+        }
+    }
+}
+
+mod point_shadows_tests {
+    use super::{cube_face_proj, cube_face_view, PointLightSettings, CUBE_FACES, MAX_LIGHT_RADIUS};
+    use graphics_math as math;
+
+    #[test]
+    fn test_each_face_looks_out_from_the_light_along_its_axis() {
+        let position = math::vec3((1.0, 2.0, 3.0));
+        for face in 0..6 {
+            let view = cube_face_view(&position, face);
+            let (forward, _) = CUBE_FACES[face];
+            let ahead = math::vec3((1.0 + forward[0], 2.0 + forward[1], 3.0 + forward[2]));
+
+            let eye = view * math::vec4((position, 1.0));
+            let looking = view * math::vec4((ahead, 1.0));
+            assert!(eye.v[0].abs() < 1e-5 && eye.v[1].abs() < 1e-5 && eye.v[2].abs() < 1e-5);
+            assert!(looking.v[0].abs() < 1e-5 && looking.v[1].abs() < 1e-5);
+            assert!(f32::abs(looking.v[2] + 1.0) < 1e-5);
+        }
+    }
+
+    #[test]
+    fn test_every_direction_lands_on_one_of_the_faces() {
+        let position = math::vec3((0.0, 0.0, 0.0));
+        let proj = cube_face_proj(10.0);
+        for &direction in [(0.3, -0.8, 0.1), (-1.0, 0.9, 0.8), (0.0, 0.0, -2.0), (0.5, 0.4, 0.45)].iter() {
+            let point = math::vec4((math::vec3(direction), 1.0));
+            let inside = (0..6).any(|face| {
+                let clip = proj * (cube_face_view(&position, face) * point);
+                clip.v[3] > 0.0 && (0..3).all(|i| f32::abs(clip.v[i] / clip.v[3]) <= 1.0)
+            });
+
+            assert!(inside, "{:?} is not on any face", direction);
+        }
+    }
+
+    #[test]
+    fn test_the_settings_stay_in_range() {
+        let mut settings = PointLightSettings::new();
+        settings.change_radius(1000.0);
+        settings.change_bias(-1.0);
+
+        assert_eq!(settings.radius, MAX_LIGHT_RADIUS);
+        assert_eq!(settings.bias, 0.0);
+        settings.enabled = false;
+        assert_eq!(settings.describe(), "point light: off");
+    }
+}
@@ -0,0 +1,127 @@
+#version 410
+
+// keep in step with CASCADE_COUNT in shadow_cascades.rs.
+#define CASCADE_COUNT 4
+
+in vec3 p_world;
+in vec3 n_world;
+in float view_depth;
+uniform vec3 colour;
+// the direction towards the sun.
+uniform vec3 light_dir_world;
+uniform sampler2DArrayShadow shadow_map;
+// the light's view and projection, and where each cascade ends.
+uniform mat4 light_view_proj[CASCADE_COUNT];
+uniform float cascade_far[CASCADE_COUNT];
+uniform bool shadows_enabled;
+uniform bool show_cascades;
+// the point light, with the distance to the nearest surface in every direction
+// from it over its radius.
+uniform bool point_light_enabled;
+uniform vec3 point_light_pos_world;
+uniform float point_light_radius;
+uniform float point_shadow_bias;
+uniform samplerCube point_shadow_map;
+uniform vec3 eye_world;
+// drawn in a flat colour, for the point light's marker.
+uniform bool unlit;
+out vec4 frag_colour;
+
+const float ambient = 0.3;
+const vec3 point_light_colour = vec3 (1.0, 0.8, 0.5);
+const float specular_strength = 0.4;
+const float specular_exponent = 32.0;
+// directions to nudge the point shadow lookup in, for softer edges.
+const vec3 point_offsets[8] = vec3[] (
+	vec3 (1.0, 1.0, 1.0), vec3 (1.0, -1.0, 1.0), vec3 (-1.0, -1.0, 1.0), vec3 (-1.0, 1.0, 1.0),
+	vec3 (1.0, 1.0, -1.0), vec3 (1.0, -1.0, -1.0), vec3 (-1.0, -1.0, -1.0), vec3 (-1.0, 1.0, -1.0)
+);
+const vec3 cascade_tints[CASCADE_COUNT] = vec3[] (
+	vec3 (1.0, 0.3, 0.3), vec3 (0.3, 1.0, 0.3), vec3 (0.3, 0.3, 1.0), vec3 (1.0, 1.0, 0.3)
+);
+
+// the first cascade that reaches past the fragment.
+int pick_cascade () {
+	for (int i = 0; i < CASCADE_COUNT - 1; i++) {
+		if (view_depth < cascade_far[i]) {
+			return i;
+		}
+	}
+	return CASCADE_COUNT - 1;
+}
+
+// how much of the sun reaches the fragment, from 0 in full shadow to 1 in full
+// light, averaged over 3x3 texels for softer edges.
+float sunlight (int cascade, vec3 n, float n_dot_l) {
+	// slide the point out along its normal a little, further the more the
+	// surface turns away from the light, so it does not shadow itself.
+	float offset = 0.02 * (1.0 + float (cascade)) * (1.0 - n_dot_l);
+	vec4 p_light = light_view_proj[cascade] * vec4 (p_world + n * offset, 1.0);
+	vec3 p_map = p_light.xyz / p_light.w * 0.5 + 0.5;
+	if (p_map.z > 1.0) {
+		return 1.0;
+	}
+	vec2 texel = 1.0 / vec2 (textureSize (shadow_map, 0).xy);
+	float lit = 0.0;
+	for (int y = -1; y <= 1; y++) {
+		for (int x = -1; x <= 1; x++) {
+			vec2 st = p_map.xy + vec2 (x, y) * texel;
+			lit += texture (shadow_map, vec4 (st, float (cascade), p_map.z));
+		}
+	}
+	return lit / 9.0;
+}
+
+// how much of the point light reaches the fragment, from 0 in full shadow to 1
+// in full light.
+float point_light (vec3 to_fragment) {
+	float distance = length (to_fragment);
+	// spread the samples wider the further the fragment is from the light.
+	float spread = 0.005 * distance;
+	float lit = 0.0;
+	for (int i = 0; i < 8; i++) {
+		float nearest = texture (point_shadow_map, to_fragment + point_offsets[i] * spread).r;
+		lit += distance - point_shadow_bias > nearest * point_light_radius ? 0.0 : 1.0;
+	}
+	return lit / 8.0;
+}
+
+// phong specular highlight for light arriving from direction l.
+float specular (vec3 n, vec3 l) {
+	vec3 to_eye = normalize (eye_world - p_world);
+	return specular_strength * pow (max (dot (reflect (-l, n), to_eye), 0.0), specular_exponent);
+}
+
+void main () {
+	if (unlit) {
+		frag_colour = vec4 (colour, 1.0);
+		return;
+	}
+	vec3 n = normalize (n_world);
+	vec3 l = normalize (light_dir_world);
+	float n_dot_l = max (dot (n, l), 0.0);
+	int cascade = pick_cascade ();
+	float lit = 1.0;
+	if (shadows_enabled && n_dot_l > 0.0) {
+		lit = sunlight (cascade, n, n_dot_l);
+	}
+	vec3 lit_colour = colour * (ambient + (1.0 - ambient) * n_dot_l * lit);
+	if (n_dot_l > 0.0) {
+		lit_colour += vec3 (specular (n, l) * lit);
+	}
+
+	if (point_light_enabled) {
+		vec3 to_fragment = p_world - point_light_pos_world;
+		vec3 l_point = normalize (-to_fragment);
+		float n_dot_l_point = max (dot (n, l_point), 0.0);
+		// fall away to nothing at the light's radius.
+		float falloff = clamp (1.0 - length (to_fragment) / point_light_radius, 0.0, 1.0);
+		float point_lit = n_dot_l_point > 0.0 && falloff > 0.0 ? point_light (to_fragment) : 0.0;
+		float diffuse_point = n_dot_l_point + specular (n, l_point);
+		lit_colour += point_light_colour * colour * diffuse_point * falloff * falloff * point_lit;
+	}
+	if (show_cascades) {
+		lit_colour *= cascade_tints[cascade];
+	}
+	frag_colour = vec4 (lit_colour, 1.0);
+}
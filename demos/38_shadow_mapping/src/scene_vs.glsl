@@ -0,0 +1,17 @@
+#version 410
+
+layout(location = 0) in vec3 vp; // positions
+layout(location = 1) in vec3 vn; // normals
+uniform mat4 P, V, M; // proj, view, model matrices
+out vec3 p_world;
+out vec3 n_world;
+// how far in front of the camera the vertex is, to pick a shadow cascade with.
+out float view_depth;
+
+void main () {
+	p_world = vec3 (M * vec4 (vp, 1.0));
+	n_world = vec3 (M * vec4 (vn, 0.0));
+	vec4 p_eye = V * vec4 (p_world, 1.0);
+	view_depth = -p_eye.z;
+	gl_Position = P * p_eye;
+}
@@ -0,0 +1,346 @@
+use gl;
+use gl::types::{GLfloat, GLint, GLsizei, GLuint};
+
+use graphics_math as math;
+use math::{Mat4, Vec3};
+
+use std::ptr;
+
+
+///
+/// The number of cascades the view frustum is split into. Keep in step with
+/// `scene_fs.glsl`.
+///
+pub const CASCADE_COUNT: usize = 4;
+///
+/// The width and height of each cascade's shadow map, in texels.
+///
+pub const SHADOW_MAP_SIZE: u32 = 2048;
+///
+/// How far the splits lean towards a logarithmic spread, from 0 for even slices
+/// to 1 for slices that grow in proportion to their distance.
+///
+pub const DEFAULT_SPLIT_LAMBDA: f32 = 0.75;
+///
+/// How far past each slice, towards the light, shadow casters are still drawn
+/// into its map. Anything further up that could throw a shadow into the slice
+/// would otherwise be clipped away.
+///
+pub const CASTER_MARGIN: f32 = 50.0;
+
+
+///
+/// The distances from the camera where the view frustum is split into `count`
+/// cascades, starting with `near` and ending with `far`. A pure logarithmic split
+/// gives each cascade the same texels per unit of screen, but squeezes the first
+/// cascade into a sliver, so it is blended with an even split by `lambda`.
+///
+pub fn cascade_splits(near: f32, far: f32, count: usize, lambda: f32) -> Vec<f32> {
+    (0..(count + 1))
+        .map(|i| {
+            let t = i as f32 / count as f32;
+            let logarithmic = near * f32::powf(far / near, t);
+            let even = near + (far - near) * t;
+
+            lambda * logarithmic + (1.0 - lambda) * even
+        })
+        .collect()
+}
+
+///
+/// The eight corners in world space of the part of the view frustum between the
+/// distances `near` and `far` from the camera.
+///
+pub fn slice_corners(view_mat: &Mat4, fovy: f32, aspect: f32, near: f32, far: f32) -> [Vec3; 8] {
+    math::frustum_corners_world(view_mat, &Mat4::perspective(fovy, aspect, near, far))
+}
+
+///
+/// The centre of `points` and the distance to the one furthest from it.
+///
+pub fn bounding_sphere(points: &[Vec3]) -> (Vec3, f32) {
+    let mut centre = Vec3::zero();
+    for point in points.iter() {
+        centre += point;
+    }
+    centre = centre / points.len() as f32;
+    let radius = points.iter().map(|point| (point - centre).norm()).fold(0.0, f32::max);
+
+    (centre, radius)
+}
+
+///
+/// A view matrix that looks along `direction` from the origin. Sunlight has no
+/// position, only a direction, so where it looks from is left to the projection.
+///
+pub fn light_view_mat(direction: &Vec3) -> Mat4 {
+    let forward = direction.normalize();
+    // Any up will do as long as it is not parallel to the light.
+    let up = if f32::abs(forward.v[1]) > 0.99 { Vec3::unit_z() } else { Vec3::unit_y() };
+    let right = forward.cross(&up).normalize();
+    let up = right.cross(&forward);
+
+    Mat4::new(
+        right.v[0], up.v[0], -forward.v[0], 0.0,
+        right.v[1], up.v[1], -forward.v[1], 0.0,
+        right.v[2], up.v[2], -forward.v[2], 0.0,
+        0.0, 0.0, 0.0, 1.0
+    )
+}
+
+///
+/// An orthographic projection of the box from `left` to `right`, `bottom` to
+/// `top`, and `near` to `far` in front of the viewer, as sunlight's parallel rays
+/// need.
+///
+pub fn ortho_mat(left: f32, right: f32, bottom: f32, top: f32, near: f32, far: f32) -> Mat4 {
+    Mat4::new(
+        2.0 / (right - left), 0.0, 0.0, 0.0,
+        0.0, 2.0 / (top - bottom), 0.0, 0.0,
+        0.0, 0.0, -2.0 / (far - near), 0.0,
+        -(right + left) / (right - left), -(top + bottom) / (top - bottom), -(far + near) / (far - near), 1.0
+    )
+}
+
+///
+/// The light's view and projection for the slice of the view frustum with the
+/// given corners, lit along `light_direction`, in a shadow map `map_size` texels
+/// across. The box is fitted around the slice's bounding sphere rather than the
+/// slice itself, so its size stays the same as the camera turns, and it only
+/// moves a whole texel at a time. Otherwise the edges of shadows crawl as the
+/// texels they fall into shift under them.
+///
+pub fn cascade_view_proj(light_direction: &Vec3, corners: &[Vec3], map_size: u32) -> Mat4 {
+    let (centre, radius) = bounding_sphere(corners);
+    let light_view = light_view_mat(light_direction);
+    let centre_light = light_view * math::vec4((centre, 1.0));
+
+    let texel = 2.0 * radius / map_size as f32;
+    let x = f32::floor(centre_light.v[0] / texel) * texel;
+    let y = f32::floor(centre_light.v[1] / texel) * texel;
+    // The light looks down -z, so the slice is in front of it at -z.
+    let distance = -centre_light.v[2];
+    let proj = ortho_mat(
+        x - radius, x + radius, y - radius, y + radius, distance - radius - CASTER_MARGIN, distance + radius
+    );
+
+    proj * light_view
+}
+
+///
+/// Cascaded shadow maps for one directional light. The view frustum is cut into
+/// slices along its length, and each slice gets a shadow map of its own covering
+/// just that slice, so shadows near the camera get as many texels as those far off
+/// without one huge map. The maps are the layers of a single depth texture array,
+/// and each fragment picks the layer for the slice it is in.
+///
+pub struct ShadowCascades {
+    fbo: GLuint,
+    depth_tex: GLuint,
+    size: u32,
+    splits: Vec<f32>,
+    view_projs: [Mat4; CASCADE_COUNT],
+    pub lambda: f32,
+}
+
+impl ShadowCascades {
+    ///
+    /// Make the depth texture array, `size` texels on a side. Returns an error if
+    /// the driver cannot render to it.
+    ///
+    pub fn new(size: u32) -> Result<ShadowCascades, String> {
+        let mut depth_tex = 0;
+        let mut fbo = 0;
+        let border: [GLfloat; 4] = [1.0, 1.0, 1.0, 1.0];
+        unsafe {
+            gl::GenTextures(1, &mut depth_tex);
+            gl::BindTexture(gl::TEXTURE_2D_ARRAY, depth_tex);
+            gl::TexImage3D(
+                gl::TEXTURE_2D_ARRAY, 0, gl::DEPTH_COMPONENT24 as GLint, size as GLsizei, size as GLsizei,
+                CASCADE_COUNT as GLsizei, 0, gl::DEPTH_COMPONENT, gl::FLOAT, ptr::null()
+            );
+            // Compare against the stored depth in the sampler, which filters the
+            // results of four comparisons together for softer edges.
+            gl::TexParameteri(gl::TEXTURE_2D_ARRAY, gl::TEXTURE_MAG_FILTER, gl::LINEAR as GLint);
+            gl::TexParameteri(gl::TEXTURE_2D_ARRAY, gl::TEXTURE_MIN_FILTER, gl::LINEAR as GLint);
+            gl::TexParameteri(gl::TEXTURE_2D_ARRAY, gl::TEXTURE_COMPARE_MODE, gl::COMPARE_REF_TO_TEXTURE as GLint);
+            gl::TexParameteri(gl::TEXTURE_2D_ARRAY, gl::TEXTURE_COMPARE_FUNC, gl::LEQUAL as GLint);
+            // Nothing outside a map is in shadow.
+            gl::TexParameteri(gl::TEXTURE_2D_ARRAY, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_BORDER as GLint);
+            gl::TexParameteri(gl::TEXTURE_2D_ARRAY, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_BORDER as GLint);
+            gl::TexParameterfv(gl::TEXTURE_2D_ARRAY, gl::TEXTURE_BORDER_COLOR, border.as_ptr());
+
+            gl::GenFramebuffers(1, &mut fbo);
+            gl::BindFramebuffer(gl::FRAMEBUFFER, fbo);
+            gl::FramebufferTextureLayer(gl::FRAMEBUFFER, gl::DEPTH_ATTACHMENT, depth_tex, 0, 0);
+            // Only depth is drawn.
+            gl::DrawBuffer(gl::NONE);
+            gl::ReadBuffer(gl::NONE);
+        }
+        let status = unsafe { gl::CheckFramebufferStatus(gl::FRAMEBUFFER) };
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+        }
+        assert!(depth_tex > 0);
+
+        let cascades = ShadowCascades {
+            fbo: fbo,
+            depth_tex: depth_tex,
+            size: size,
+            splits: vec![],
+            view_projs: [Mat4::identity(); CASCADE_COUNT],
+            lambda: DEFAULT_SPLIT_LAMBDA,
+        };
+        if status != gl::FRAMEBUFFER_COMPLETE {
+            return Err(format!("ERROR: shadow map framebuffer is incomplete. Status: 0x{:x}", status));
+        }
+
+        Ok(cascades)
+    }
+
+    ///
+    /// Split the camera's view frustum, and fit each cascade's light projection
+    /// around its slice. Call once a frame, before drawing the shadow maps.
+    ///
+    pub fn update(
+        &mut self, view_mat: &Mat4, fovy: f32, aspect: f32, near: f32, far: f32, light_direction: &Vec3) {
+
+        self.splits = cascade_splits(near, far, CASCADE_COUNT, self.lambda);
+        for i in 0..CASCADE_COUNT {
+            let corners = slice_corners(view_mat, fovy, aspect, self.splits[i], self.splits[i + 1]);
+            self.view_projs[i] = cascade_view_proj(light_direction, &corners, self.size);
+        }
+    }
+
+    ///
+    /// Draw the shadow casters into every cascade's map. `draw` is called once a
+    /// cascade with the light's view and projection to draw with.
+    ///
+    pub fn render<F>(&self, mut draw: F) where F: FnMut(&Mat4) {
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, self.fbo);
+            gl::Viewport(0, 0, self.size as GLsizei, self.size as GLsizei);
+            // Push the depths back a little, so lit surfaces do not shadow themselves.
+            gl::Enable(gl::POLYGON_OFFSET_FILL);
+            gl::PolygonOffset(2.0, 4.0);
+        }
+        for (i, view_proj) in self.view_projs.iter().enumerate() {
+            unsafe {
+                gl::FramebufferTextureLayer(gl::FRAMEBUFFER, gl::DEPTH_ATTACHMENT, self.depth_tex, 0, i as GLint);
+                gl::Clear(gl::DEPTH_BUFFER_BIT);
+            }
+            draw(view_proj);
+        }
+        unsafe {
+            gl::Disable(gl::POLYGON_OFFSET_FILL);
+            gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+        }
+    }
+
+    ///
+    /// The depth texture array, with one layer for each cascade.
+    ///
+    pub fn texture(&self) -> GLuint {
+        self.depth_tex
+    }
+
+    ///
+    /// The light's view and projection for each cascade.
+    ///
+    pub fn view_projs(&self) -> &[Mat4; CASCADE_COUNT] {
+        &self.view_projs
+    }
+
+    ///
+    /// The distance from the camera where each cascade ends.
+    ///
+    pub fn far_distances(&self) -> [f32; CASCADE_COUNT] {
+        let mut distances = [0.0; CASCADE_COUNT];
+        distances.copy_from_slice(&self.splits[1..]);
+
+        distances
+    }
+
+    ///
+    /// Lean the splits towards an even or a logarithmic spread.
+    ///
+    pub fn change_lambda(&mut self, step: f32) {
+        self.lambda = f32::min(f32::max(self.lambda + step, 0.0), 1.0);
+    }
+
+    pub fn describe(&self) -> String {
+        let splits: Vec<String> = self.splits.iter().map(|split| format!("{:.1}", split)).collect();
+
+        format!("split lambda {:.2}: {}", self.lambda, splits.join(" / "))
+    }
+}
+
+impl Drop for ShadowCascades {
+    fn drop(&mut self) {
+        if gl::DeleteFramebuffers::is_loaded() {
+            unsafe {
+                gl::DeleteFramebuffers(1, &self.fbo);
+                gl::DeleteTextures(1, &self.depth_tex);
+            }
+        }
+    }
+}
+
+mod shadow_cascades_tests {
+    use super::{cascade_splits, cascade_view_proj, light_view_mat, slice_corners};
+    use graphics_math as math;
+    use math::Mat4;
+
+    #[test]
+    fn test_the_splits_run_from_near_to_far() {
+        for &lambda in [0.0, 0.5, 1.0].iter() {
+            let splits = cascade_splits(0.1, 100.0, 4, lambda);
+
+            assert_eq!(splits.len(), 5);
+            assert!(f32::abs(splits[0] - 0.1) < 1e-5);
+            assert!(f32::abs(splits[4] - 100.0) < 1e-3);
+            for pair in splits.windows(2) {
+                assert!(pair[0] < pair[1]);
+            }
+        }
+        // an even split cuts the range into equal lengths
+        let splits = cascade_splits(1.0, 101.0, 4, 0.0);
+        assert!(f32::abs(splits[1] - 26.0) < 1e-4);
+    }
+
+    #[test]
+    fn test_the_slice_corners_lie_on_its_near_and_far_planes() {
+        let corners = slice_corners(&Mat4::identity(), 67.0, 1.5, 2.0, 10.0);
+
+        for i in 0..4 {
+            assert!(f32::abs(corners[i].v[2] + 2.0) < 1e-3);
+            assert!(f32::abs(corners[i + 4].v[2] + 10.0) < 1e-2);
+        }
+    }
+
+    #[test]
+    fn test_the_light_looks_along_its_direction() {
+        let direction = math::vec3((0.3, -1.0, 0.2)).normalize();
+        let view = light_view_mat(&direction);
+
+        let looking = view * math::vec4((direction, 0.0));
+
+        assert!(f32::abs(looking.v[0]) < 1e-5);
+        assert!(f32::abs(looking.v[1]) < 1e-5);
+        assert!(f32::abs(looking.v[2] + 1.0) < 1e-5);
+    }
+
+    #[test]
+    fn test_a_cascade_contains_its_whole_slice() {
+        let view_mat = Mat4::from_translation(&math::vec3((-3.0, -2.0, -5.0)));
+        let corners = slice_corners(&view_mat, 67.0, 1.5, 5.0, 20.0);
+        let view_proj = cascade_view_proj(&math::vec3((0.3, -1.0, 0.2)), &corners, 2048);
+
+        for corner in corners.iter() {
+            let clip = view_proj * math::vec4((*corner, 1.0));
+            for i in 0..3 {
+                assert!(f32::abs(clip.v[i] / clip.v[3]) <= 1.0, "{} is outside the cascade", corner);
+            }
+        }
+    }
+}
@@ -0,0 +1,5 @@
+#version 410
+
+// the shadow maps only need depth, so there is nothing to write.
+void main () {
+}
@@ -0,0 +1,11 @@
+#version 410
+
+in vec3 p_world;
+in vec3 n_world;
+// the light's radiance, so the marker is as bright as what it lights.
+uniform vec3 colour;
+out vec4 frag_colour;
+
+void main () {
+	frag_colour = vec4 (colour, 1.0);
+}
@@ -0,0 +1,148 @@
+use gl;
+use gl::types::{GLsizeiptr, GLuint, GLvoid};
+
+use std::mem;
+use std::ptr;
+
+
+///
+/// The most point lights the `Lights` uniform block has room for. Keep in step
+/// with `MAX_LIGHTS` in `pbr_fs.glsl`.
+///
+pub const MAX_LIGHTS: usize = 4;
+///
+/// The uniform buffer binding point the `Lights` block is read from.
+///
+pub const LIGHTS_BINDING: GLuint = 0;
+///
+/// The number of floats in the `Lights` block: a position and a colour for each
+/// light, each taking up a whole vec4 under the std140 layout.
+///
+pub const LIGHTS_BLOCK_FLOATS: usize = 2 * 4 * MAX_LIGHTS;
+
+
+///
+/// A light shining equally in every direction from a point, falling off with the
+/// square of the distance.
+///
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct PointLight {
+    pub position: [f32; 3],
+    ///
+    /// The light's colour times its brightness, so it can be far brighter than
+    /// one. A surface one unit away is lit by all of it.
+    ///
+    pub radiance: [f32; 3],
+}
+
+///
+/// Lay `lights` out as the std140 `Lights` block in `pbr_fs.glsl` has them: the
+/// positions, then the radiances, each in the first three floats of a vec4. The
+/// fourth float of a position is one for a light that is on, and zero for the
+/// unused slots after the last light, which the shader skips.
+///
+pub fn pack_lights(lights: &[PointLight]) -> [f32; LIGHTS_BLOCK_FLOATS] {
+    assert!(lights.len() <= MAX_LIGHTS);
+    let mut block = [0.0; LIGHTS_BLOCK_FLOATS];
+    let radiance_offset = 4 * MAX_LIGHTS;
+    for (i, light) in lights.iter().enumerate() {
+        block[4 * i..4 * i + 3].copy_from_slice(&light.position);
+        block[4 * i + 3] = 1.0;
+        block[radiance_offset + 4 * i..radiance_offset + 4 * i + 3].copy_from_slice(&light.radiance);
+    }
+
+    block
+}
+
+///
+/// The uniform buffer behind the `Lights` block. Every programme that has the
+/// block reads the same buffer, so the lights are set once a frame however many
+/// shaders draw with them.
+///
+pub struct LightsBuffer {
+    ubo: GLuint,
+}
+
+impl LightsBuffer {
+    ///
+    /// Make the buffer and point the `Lights` block of each programme in `sps` at
+    /// it. Returns an error if one of them has no such block.
+    ///
+    pub fn new(sps: &[GLuint]) -> Result<LightsBuffer, String> {
+        let mut ubo = 0;
+        unsafe {
+            gl::GenBuffers(1, &mut ubo);
+            gl::BindBuffer(gl::UNIFORM_BUFFER, ubo);
+            gl::BufferData(
+                gl::UNIFORM_BUFFER, (LIGHTS_BLOCK_FLOATS * mem::size_of::<f32>()) as GLsizeiptr,
+                ptr::null(), gl::DYNAMIC_DRAW
+            );
+            gl::BindBuffer(gl::UNIFORM_BUFFER, 0);
+            gl::BindBufferBase(gl::UNIFORM_BUFFER, LIGHTS_BINDING, ubo);
+        }
+        assert!(ubo > 0);
+
+        let buffer = LightsBuffer { ubo: ubo };
+        for &sp in sps.iter() {
+            let block_index = unsafe { gl::GetUniformBlockIndex(sp, "Lights\0".as_ptr() as *const i8) };
+            if block_index == gl::INVALID_INDEX {
+                return Err(format!("ERROR: shader programme {} has no Lights uniform block", sp));
+            }
+            unsafe {
+                gl::UniformBlockBinding(sp, block_index, LIGHTS_BINDING);
+            }
+        }
+
+        Ok(buffer)
+    }
+
+    ///
+    /// Copy `lights` into the buffer, turning off any slots they leave over.
+    ///
+    pub fn update(&self, lights: &[PointLight]) {
+        let block = pack_lights(lights);
+        unsafe {
+            gl::BindBuffer(gl::UNIFORM_BUFFER, self.ubo);
+            gl::BufferSubData(
+                gl::UNIFORM_BUFFER, 0, (block.len() * mem::size_of::<f32>()) as GLsizeiptr,
+                block.as_ptr() as *const GLvoid
+            );
+            gl::BindBuffer(gl::UNIFORM_BUFFER, 0);
+        }
+    }
+}
+
+impl Drop for LightsBuffer {
+    fn drop(&mut self) {
+        if gl::DeleteBuffers::is_loaded() {
+            unsafe {
+                gl::DeleteBuffers(1, &self.ubo);
+            }
+        }
+    }
+}
+
+mod lights_tests {
+    use super::{pack_lights, PointLight, MAX_LIGHTS};
+
+    #[test]
+    fn test_the_positions_come_before_the_radiances_a_vec4_each() {
+        let lights = [
+            PointLight { position: [1.0, 2.0, 3.0], radiance: [10.0, 20.0, 30.0] },
+            PointLight { position: [4.0, 5.0, 6.0], radiance: [40.0, 50.0, 60.0] },
+        ];
+        let block = pack_lights(&lights);
+
+        assert_eq!(&block[0..8], &[1.0, 2.0, 3.0, 1.0, 4.0, 5.0, 6.0, 1.0]);
+        assert_eq!(&block[4 * MAX_LIGHTS..4 * MAX_LIGHTS + 7], &[10.0, 20.0, 30.0, 0.0, 40.0, 50.0, 60.0]);
+    }
+
+    #[test]
+    fn test_the_slots_after_the_last_light_are_off() {
+        let block = pack_lights(&[PointLight { position: [1.0, 1.0, 1.0], radiance: [1.0, 1.0, 1.0] }]);
+        for i in 1..MAX_LIGHTS {
+            assert_eq!(block[4 * i + 3], 0.0);
+            assert_eq!(&block[4 * (MAX_LIGHTS + i)..4 * (MAX_LIGHTS + i) + 3], &[0.0, 0.0, 0.0]);
+        }
+    }
+}
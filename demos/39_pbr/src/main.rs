@@ -0,0 +1,719 @@
+extern crate gl;
+extern crate glfw;
+extern crate chrono;
+extern crate stb_image;
+#[cfg(feature = "glam")]
+extern crate glam;
+#[cfg(feature = "cgmath")]
+extern crate cgmath;
+
+#[macro_use]
+mod gl_utils;
+mod graphics_math;
+mod obj_parser;
+mod colour;
+mod assets;
+mod shader_include;
+mod spirv;
+mod program_cache;
+mod logger;
+mod overlay;
+mod streaming_buffer;
+mod camera;
+mod camera_controller;
+mod render_target;
+mod vertex_layout;
+mod fullscreen_pass;
+mod gpu_timer;
+mod perf_stats;
+mod framebuffer;
+mod ibl;
+mod pbr_material;
+mod lights;
+mod sphere_grid;
+mod tone_map;
+
+
+use glfw::{Action, Context, Key};
+use gl::types::{GLenum, GLfloat, GLint, GLuint, GLvoid};
+
+use std::process;
+use std::time::Instant;
+
+use stb_image::image::LoadResult;
+
+use gl_utils::*;
+
+use graphics_math as math;
+use math::{Mat4, Vec3};
+
+use camera::Camera;
+use camera_controller::{CameraController, ControlKeys};
+use gpu_timer::GpuTimer;
+use ibl::SpecularIbl;
+use lights::{LightsBuffer, PointLight};
+use overlay::Overlay;
+use pbr_material::PbrMaterial;
+use perf_stats::{PerfStats, PERF_STATS_HISTORY};
+use render_target::{RenderTarget, TargetFormat};
+use sphere_grid::{GRID_COLUMNS, GRID_ROWS, GRID_SPHERE_RADIUS};
+use tone_map::ToneMap;
+use vertex_layout::VertexLayout;
+use fullscreen_pass::FULLSCREEN_VS_FILE;
+
+
+const GL_LOG_FILE: &str = "gl.log";
+const PBR_VS_FILE: &str = "src/pbr_vs.glsl";
+const PBR_FS_FILE: &str = "src/pbr_fs.glsl";
+const LIGHT_FS_FILE: &str = "src/light_fs.glsl";
+const CUBE_VS_FILE: &str = "src/cube_vs.glsl";
+const CUBE_FS_FILE: &str = "src/cube_fs.glsl";
+const PREFILTER_FS_FILE: &str = "src/prefilter_fs.glsl";
+const TONE_MAP_FS_FILE: &str = "src/tone_map_fs.glsl";
+const FRONT: &str = "src/negz.jpg";
+const BACK: &str = "src/posz.jpg";
+const TOP: &str = "src/posy.jpg";
+const BOTTOM: &str = "src/negy.jpg";
+const LEFT: &str = "src/negx.jpg";
+const RIGHT: &str = "src/posx.jpg";
+
+// The sphere whose material the keys change stands to the right of the grid.
+const HERO_POSITION: [f32; 3] = [6.5, 0.0, 0.0];
+const HERO_RADIUS: f32 = 1.5;
+// The colours the hero sphere cycles through, all in linear light. The metals'
+// colours are what they reflect head on.
+const ALBEDO_PRESETS: [(&str, [f32; 3]); 5] = [
+    ("gold", [1.0, 0.78, 0.34]),
+    ("copper", [0.95, 0.64, 0.54]),
+    ("iron", [0.56, 0.57, 0.58]),
+    ("red plastic", [0.6, 0.05, 0.05]),
+    ("white clay", [0.8, 0.8, 0.8]),
+];
+const GRID_ALBEDO: [f32; 3] = [0.5, 0.0, 0.0];
+// How far each key press moves the hero's material sliders, and the exposure.
+const MATERIAL_STEP: f32 = 0.05;
+const EXPOSURE_STEP_STOPS: f32 = 0.5;
+
+// Four white lights in front of the grid, one off each corner.
+const LIGHTS: [PointLight; 4] = [
+    PointLight { position: [-6.0, 5.0, 6.0], radiance: [60.0, 60.0, 60.0] },
+    PointLight { position: [6.0, 5.0, 6.0], radiance: [60.0, 60.0, 60.0] },
+    PointLight { position: [-6.0, -5.0, 6.0], radiance: [60.0, 60.0, 60.0] },
+    PointLight { position: [6.0, -5.0, 6.0], radiance: [60.0, 60.0, 60.0] },
+];
+const LIGHT_MARKER_RADIUS: f32 = 0.15;
+
+const OVERLAY_TEXT_COLOUR: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+const OVERLAY_BACKGROUND_COLOUR: [f32; 4] = [0.0, 0.0, 0.0, 0.6];
+
+
+///
+/// The sky box's shader and the big cube it is drawn on, which is also what the
+/// environment is prefiltered over.
+///
+struct Skybox {
+    sp: GLuint,
+    view_location: GLint,
+    proj_location: GLint,
+    vao: GLuint,
+}
+
+impl Skybox {
+    ///
+    /// Draw the sky behind everything. The view matrix should have no camera
+    /// translation, so the sky stays infinitely far away.
+    ///
+    fn draw(&self, texture: GLuint, view_rotation: &Mat4, proj: &Mat4) {
+        unsafe {
+            gl::DepthMask(gl::FALSE);
+            gl::UseProgram(self.sp);
+            gl::UniformMatrix4fv(self.view_location, 1, gl::FALSE, view_rotation.as_ptr());
+            gl::UniformMatrix4fv(self.proj_location, 1, gl::FALSE, proj.as_ptr());
+            gl::ActiveTexture(gl::TEXTURE0);
+            gl::BindTexture(gl::TEXTURE_CUBE_MAP, texture);
+            gl::BindVertexArray(self.vao);
+            gl::DrawArrays(gl::TRIANGLES, 0, 36);
+            gl::DepthMask(gl::TRUE);
+        }
+    }
+}
+
+///
+/// The PBR shader, with the uniforms each sphere's material sets.
+///
+struct PbrShader {
+    sp: GLuint,
+    model_location: GLint,
+    view_location: GLint,
+    proj_location: GLint,
+    eye_location: GLint,
+    albedo_location: GLint,
+    metallic_location: GLint,
+    roughness_location: GLint,
+    max_lod_location: GLint,
+    direct_enabled_location: GLint,
+    ibl_enabled_location: GLint,
+}
+
+impl PbrShader {
+    fn new(sp: GLuint) -> PbrShader {
+        let location = |name: &str| {
+            let location = uniform_location(sp, name);
+            assert!(location > -1);
+            location
+        };
+        unsafe {
+            gl::UseProgram(sp);
+            gl::Uniform1i(location("prefiltered_map"), 0);
+            gl::Uniform1i(location("brdf_lut"), 1);
+        }
+
+        PbrShader {
+            sp: sp,
+            model_location: location("M"),
+            view_location: location("V"),
+            proj_location: location("P"),
+            eye_location: location("eye_world"),
+            albedo_location: location("albedo"),
+            metallic_location: location("metallic"),
+            roughness_location: location("roughness"),
+            max_lod_location: location("max_lod"),
+            direct_enabled_location: location("direct_enabled"),
+            ibl_enabled_location: location("ibl_enabled"),
+        }
+    }
+
+    ///
+    /// Set what stays the same for every sphere in a frame: the camera, which
+    /// halves of the lighting are on, and the environment's textures.
+    ///
+    fn begin(&self, view: &Mat4, proj: &Mat4, eye: &Vec3, ibl: &SpecularIbl, switches: &LightingSwitches) {
+        unsafe {
+            gl::UseProgram(self.sp);
+            gl::UniformMatrix4fv(self.view_location, 1, gl::FALSE, view.as_ptr());
+            gl::UniformMatrix4fv(self.proj_location, 1, gl::FALSE, proj.as_ptr());
+            gl::Uniform3f(self.eye_location, eye.v[0], eye.v[1], eye.v[2]);
+            gl::Uniform1f(self.max_lod_location, ibl.max_lod());
+            gl::Uniform1i(self.direct_enabled_location, switches.direct as GLint);
+            gl::Uniform1i(self.ibl_enabled_location, switches.ibl as GLint);
+            gl::ActiveTexture(gl::TEXTURE0);
+            gl::BindTexture(gl::TEXTURE_CUBE_MAP, ibl.prefiltered_texture());
+            gl::ActiveTexture(gl::TEXTURE1);
+            gl::BindTexture(gl::TEXTURE_2D, ibl.brdf_lut_texture());
+            gl::ActiveTexture(gl::TEXTURE0);
+        }
+    }
+
+    fn draw(&self, sphere: &SphereMesh, model: &Mat4, material: &PbrMaterial, perf_stats: &mut PerfStats) {
+        unsafe {
+            gl::UniformMatrix4fv(self.model_location, 1, gl::FALSE, model.as_ptr());
+            gl::Uniform3f(self.albedo_location, material.albedo[0], material.albedo[1], material.albedo[2]);
+            gl::Uniform1f(self.metallic_location, material.metallic);
+            gl::Uniform1f(self.roughness_location, material.roughness);
+        }
+        sphere.draw(perf_stats);
+    }
+}
+
+///
+/// The shader the lights' markers are drawn with, a flat colour as bright as
+/// the light.
+///
+struct LightShader {
+    sp: GLuint,
+    model_location: GLint,
+    view_location: GLint,
+    proj_location: GLint,
+    colour_location: GLint,
+}
+
+impl LightShader {
+    fn new(sp: GLuint) -> LightShader {
+        let location = |name: &str| {
+            let location = uniform_location(sp, name);
+            assert!(location > -1);
+            location
+        };
+
+        LightShader {
+            sp: sp,
+            model_location: location("M"),
+            view_location: location("V"),
+            proj_location: location("P"),
+            colour_location: location("colour"),
+        }
+    }
+
+    fn draw(&self, sphere: &SphereMesh, lights: &[PointLight], view: &Mat4, proj: &Mat4, perf_stats: &mut PerfStats) {
+        unsafe {
+            gl::UseProgram(self.sp);
+            gl::UniformMatrix4fv(self.view_location, 1, gl::FALSE, view.as_ptr());
+            gl::UniformMatrix4fv(self.proj_location, 1, gl::FALSE, proj.as_ptr());
+        }
+        for light in lights.iter() {
+            let model = sphere_model_mat(&light.position, LIGHT_MARKER_RADIUS);
+            unsafe {
+                gl::UniformMatrix4fv(self.model_location, 1, gl::FALSE, model.as_ptr());
+                gl::Uniform3f(self.colour_location, light.radiance[0], light.radiance[1], light.radiance[2]);
+            }
+            sphere.draw(perf_stats);
+        }
+    }
+}
+
+///
+/// Which halves of the lighting are switched on.
+///
+struct LightingSwitches {
+    direct: bool,
+    ibl: bool,
+}
+
+impl LightingSwitches {
+    fn describe(&self) -> String {
+        let on_off = |on: bool| if on { "on" } else { "off" };
+        format!("direct light: {}, image based light: {}", on_off(self.direct), on_off(self.ibl))
+    }
+}
+
+///
+/// The embedded unit sphere every sphere in the scene is drawn with.
+///
+struct SphereMesh {
+    vao: GLuint,
+    point_count: usize,
+}
+
+impl SphereMesh {
+    fn new() -> SphereMesh {
+        let mesh = assets::fallback_mesh();
+        let mut vao = 0;
+        unsafe {
+            gl::GenVertexArrays(1, &mut vao);
+        }
+        assert!(vao > 0);
+        VertexLayout::new().floats(0, 3).upload(vao, &mesh.points, mesh.point_count);
+        VertexLayout::new().floats(1, 3).upload(vao, &mesh.normals, mesh.point_count);
+
+        SphereMesh {
+            vao: vao,
+            point_count: mesh.point_count,
+        }
+    }
+
+    fn draw(&self, perf_stats: &mut PerfStats) {
+        unsafe {
+            gl::BindVertexArray(self.vao);
+            gl::DrawArrays(gl::TRIANGLES, 0, self.point_count as i32);
+        }
+        perf_stats.count_draw(self.point_count / 3);
+    }
+}
+
+///
+/// Place the unit sphere at `centre`, scaled to `radius`.
+///
+fn sphere_model_mat(centre: &[f32; 3], radius: f32) -> Mat4 {
+    Mat4::from_scale(&math::vec3((radius, radius, radius)))
+        .then_translate(&math::vec3((centre[0], centre[1], centre[2])))
+}
+
+///
+/// Draw the grid of spheres, and the hero sphere beside it.
+///
+fn draw_spheres(shader: &PbrShader, sphere: &SphereMesh, hero: &PbrMaterial, perf_stats: &mut PerfStats) {
+    for row in 0..GRID_ROWS {
+        for column in 0..GRID_COLUMNS {
+            let centre = sphere_grid::grid_position(row, column);
+            let model = sphere_model_mat(&[centre.v[0], centre.v[1], centre.v[2]], GRID_SPHERE_RADIUS);
+            let material = sphere_grid::grid_material(row, column, GRID_ALBEDO);
+            shader.draw(sphere, &model, &material, perf_stats);
+        }
+    }
+    shader.draw(sphere, &sphere_model_mat(&HERO_POSITION, HERO_RADIUS), hero, perf_stats);
+}
+
+///
+/// Big cube. Returns its vertex array object.
+///
+fn make_big_cube() -> GLuint {
+    let points: [GLfloat; 108] = [
+        -10.0,  10.0, -10.0, -10.0, -10.0, -10.0,  10.0, -10.0, -10.0,
+         10.0, -10.0, -10.0,  10.0,  10.0, -10.0, -10.0,  10.0, -10.0,
+
+        -10.0, -10.0,  10.0, -10.0, -10.0, -10.0, -10.0,  10.0, -10.0,
+        -10.0,  10.0, -10.0, -10.0,  10.0,  10.0, -10.0, -10.0,  10.0,
+
+         10.0, -10.0, -10.0,  10.0, -10.0,  10.0,  10.0,  10.0,  10.0,
+         10.0,  10.0,  10.0,  10.0,  10.0, -10.0,  10.0, -10.0, -10.0,
+
+        -10.0, -10.0,  10.0, -10.0,  10.0,  10.0,  10.0,  10.0,  10.0,
+         10.0,  10.0,  10.0,  10.0, -10.0,  10.0, -10.0, -10.0,  10.0,
+
+        -10.0,  10.0, -10.0,  10.0,  10.0, -10.0,  10.0,  10.0,  10.0,
+         10.0,  10.0,  10.0, -10.0,  10.0,  10.0, -10.0,  10.0, -10.0,
+
+        -10.0, -10.0, -10.0, -10.0, -10.0,  10.0,  10.0, -10.0, -10.0,
+         10.0, -10.0, -10.0, -10.0, -10.0,  10.0,  10.0, -10.0,  10.0
+    ];
+
+    let mut vao = 0;
+    unsafe {
+        gl::GenVertexArrays(1, &mut vao);
+    }
+    assert!(vao > 0);
+    VertexLayout::new().floats(0, 3).upload(vao, &points, 36);
+
+    vao
+}
+
+///
+/// Load an image file into one side of a cube map texture. The images are stored
+/// with the display's gamma, so they are uploaded as sRGB and read back in linear
+/// light, ready to light with.
+///
+fn load_cube_map_side(texture: GLuint, side_target: GLenum, file_name: &str) -> Result<(), String> {
+    let force_channels = 4;
+    let image_data = match assets::load_image_with_depth(file_name, force_channels) {
+        LoadResult::ImageU8(image_data) => image_data,
+        LoadResult::Error(_) => {
+            return Err(format!("ERROR: could not load {}", file_name));
+        }
+        LoadResult::ImageF32(_) => {
+            return Err(format!("ERROR: Tried to load an image as byte vectors, got f32: {}", file_name));
+        }
+    };
+
+    unsafe {
+        gl::BindTexture(gl::TEXTURE_CUBE_MAP, texture);
+        gl::TexImage2D(
+            side_target, 0, gl::SRGB8_ALPHA8 as GLint, image_data.width as GLint, image_data.height as GLint, 0,
+            gl::RGBA, gl::UNSIGNED_BYTE, image_data.data.as_ptr() as *const GLvoid
+        );
+    }
+
+    Ok(())
+}
+
+///
+/// Load all six sides of the sky's cube map.
+///
+fn create_cube_map() -> Result<GLuint, String> {
+    let mut texture = 0;
+    unsafe {
+        gl::ActiveTexture(gl::TEXTURE0);
+        gl::GenTextures(1, &mut texture);
+    }
+    assert!(texture > 0);
+
+    let sides = [
+        (gl::TEXTURE_CUBE_MAP_NEGATIVE_Z, FRONT), (gl::TEXTURE_CUBE_MAP_POSITIVE_Z, BACK),
+        (gl::TEXTURE_CUBE_MAP_POSITIVE_Y, TOP), (gl::TEXTURE_CUBE_MAP_NEGATIVE_Y, BOTTOM),
+        (gl::TEXTURE_CUBE_MAP_NEGATIVE_X, LEFT), (gl::TEXTURE_CUBE_MAP_POSITIVE_X, RIGHT),
+    ];
+    for &(target, file_name) in sides.iter() {
+        if let Err(e) = load_cube_map_side(texture, target, file_name) {
+            return Err(e);
+        }
+    }
+
+    unsafe {
+        gl::TexParameteri(gl::TEXTURE_CUBE_MAP, gl::TEXTURE_MAG_FILTER, gl::LINEAR as GLint);
+        gl::TexParameteri(gl::TEXTURE_CUBE_MAP, gl::TEXTURE_MIN_FILTER, gl::LINEAR as GLint);
+        gl::TexParameteri(gl::TEXTURE_CUBE_MAP, gl::TEXTURE_WRAP_R, gl::CLAMP_TO_EDGE as GLint);
+        gl::TexParameteri(gl::TEXTURE_CUBE_MAP, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as GLint);
+        gl::TexParameteri(gl::TEXTURE_CUBE_MAP, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as GLint);
+    }
+
+    Ok(texture)
+}
+
+///
+/// Queue up a label in the top-left corner of the viewport. Returns the bottom
+/// edge of the label.
+///
+fn label(overlay: &mut Overlay, text: &str) -> f32 {
+    let scale = 2.0;
+    let longest = text.lines().map(|line| line.len()).max().unwrap_or(0);
+    let width = (longest as f32 * overlay::GLYPH_ADVANCE + 4.0) * scale;
+    let height = (text.lines().count() as f32 * overlay::LINE_HEIGHT + 4.0) * scale;
+    overlay.rect(10.0, 10.0, width, height, OVERLAY_BACKGROUND_COLOUR);
+    overlay.text(10.0 + 2.0 * scale, 10.0 + 2.0 * scale, scale, OVERLAY_TEXT_COLOUR, text);
+
+    10.0 + height
+}
+
+///
+/// Keep the context's idea of the framebuffer size up to date, and the render
+/// target the same size as the window.
+///
+fn glfw_framebuffer_size_callback(
+    context: &mut GLContext, target: &mut RenderTarget, width: u32, height: u32) -> Result<(), String> {
+
+    context.width = width;
+    context.height = height;
+
+    target.resize(width, height)
+}
+
+fn main() {
+    let logger = restart_gl_log(GL_LOG_FILE);
+    // Start a GL context and O/S window using the GLFW helper library.
+    let mut context = match start_gl(&logger) {
+        Ok(val) => val,
+        Err(e) => {
+            eprintln!("Failed to Initialize OpenGL context. Got error:");
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+    };
+    // The framebuffer can be bigger than the window on high DPI screens.
+    context.window.set_framebuffer_size_polling(true);
+    let (width, height) = context.window.get_framebuffer_size();
+    context.width = width as u32;
+    context.height = height as u32;
+
+    // the scene is lit in linear light into this, and tone mapped onto the window.
+    let mut hdr_target = match RenderTarget::with_formats(context.width, context.height, &[TargetFormat::Rgba16F]) {
+        Ok(val) => val,
+        Err(e) => {
+            logger.log_err(&e);
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+    };
+
+    unsafe {
+        // Enable depth testing.
+        gl::Enable(gl::DEPTH_TEST);
+        // Depth testing interprets a smaller value as closer to the eye.
+        gl::DepthFunc(gl::LESS);
+        // Cull face.
+        gl::Enable(gl::CULL_FACE);
+        // Cull back face.
+        gl::CullFace(gl::BACK);
+        // GL_CW for clockwise.
+        gl::FrontFace(gl::CCW);
+        gl::ClearColor(0.0, 0.0, 0.0, 1.0);
+    }
+
+    /*---------------------------------CUBE MAP-----------------------------------*/
+    let cube_vao = make_big_cube();
+    let sky_texture = match create_cube_map() {
+        Ok(val) => val,
+        Err(e) => {
+            logger.log_err(&e);
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+    };
+
+    /*------------------------------CREATE GEOMETRY------------------------------*/
+    let sphere = SphereMesh::new();
+
+    /*-------------------------------CREATE SHADERS------------------------------*/
+    let cube_sp = create_programme_from_files(&logger, CUBE_VS_FILE, CUBE_FS_FILE);
+    let skybox = Skybox {
+        sp: cube_sp,
+        view_location: uniform_location(cube_sp, "V"),
+        proj_location: uniform_location(cube_sp, "P"),
+        vao: cube_vao,
+    };
+    let pbr_sp = create_programme_from_files(&logger, PBR_VS_FILE, PBR_FS_FILE);
+    let pbr_shader = PbrShader::new(pbr_sp);
+    let light_sp = create_programme_from_files(&logger, PBR_VS_FILE, LIGHT_FS_FILE);
+    let light_shader = LightShader::new(light_sp);
+    let tone_map_sp = create_programme_from_files(&logger, FULLSCREEN_VS_FILE, TONE_MAP_FS_FILE);
+    let mut tone_map = ToneMap::new(tone_map_sp);
+
+    // The lights never move, so the buffer is filled once.
+    let lights_buffer = match LightsBuffer::new(&[pbr_sp]) {
+        Ok(val) => val,
+        Err(e) => {
+            logger.log_err(&e);
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+    };
+    lights_buffer.update(&LIGHTS);
+
+    // The sky never changes either, so it is prefiltered once too.
+    let prefilter_sp = create_programme_from_files(&logger, CUBE_VS_FILE, PREFILTER_FS_FILE);
+    let ibl = match SpecularIbl::new(prefilter_sp, cube_vao) {
+        Ok(val) => val,
+        Err(e) => {
+            logger.log_err(&e);
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+    };
+    ibl.prefilter(sky_texture);
+
+    /*-------------------------------CREATE CAMERA-------------------------------*/
+    let mut controller = CameraController::new(math::vec3((1.5, 0.0, 13.0)), 0.0, 0.0, 67.0, ControlKeys {
+        forward: Key::W, back: Key::S, step_left: Key::A, step_right: Key::D,
+        turn_left: Key::Left, turn_right: Key::Right, look_up: Key::Up, look_down: Key::Down,
+    });
+    logger.log("keys: W, A, S, and D walk, and the arrow keys turn and look up and down;");
+    logger.log("      metallic goes up the grid's rows and roughness across its columns. , and . lower and");
+    logger.log("      raise the big sphere's metallic, - and = its roughness, and C cycles its colour;");
+    logger.log("      1 toggles the direct light, 2 the image based light, [ and ] close and open the");
+    logger.log("      exposure, and F3 toggles the performance HUD;");
+    logger.log("      F5 saves the camera to camera.txt, and F9 restores it");
+
+    let mut preset = 0;
+    let mut hero = PbrMaterial::new(ALBEDO_PRESETS[preset].1, 1.0, 0.3);
+    let mut switches = LightingSwitches { direct: true, ibl: true };
+
+    // input variables
+    let near = 0.1;                                  // clipping plane
+    let far = 100.0;                                 // clipping plane
+
+    // the material and lighting settings, drawn in the corner, with the frame
+    // times and the GPU time of each pass underneath. F3 toggles the times.
+    let mut overlay = Overlay::new(&logger);
+    let mut perf_stats = PerfStats::new(PERF_STATS_HISTORY);
+    let mut show_perf_stats = true;
+    let mut scene_timer = GpuTimer::new();
+    let mut tone_map_timer = GpuTimer::new();
+
+    while !context.window.should_close() {
+        let frame_start = Instant::now();
+        let current_seconds = context.glfw.get_time();
+        let elapsed_seconds = current_seconds - context.elapsed_time_seconds;
+        context.elapsed_time_seconds = current_seconds;
+
+        update_fps_counter(&mut context);
+
+        let aspect = context.width as f32 / u32::max(context.height, 1) as f32;
+        let proj_mat = controller.camera.proj_mat(aspect, near, far);
+        let view_mat = controller.camera.view_mat();
+        let eye = Vec3::from(controller.camera.position);
+
+        // Light the scene into the floating point render target.
+        scene_timer.begin();
+        hdr_target.bind();
+        unsafe {
+            gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
+        }
+        skybox.draw(sky_texture, &controller.camera.orientation.to_mat4().inverse(), &proj_mat);
+        pbr_shader.begin(&view_mat, &proj_mat, &eye, &ibl, &switches);
+        draw_spheres(&pbr_shader, &sphere, &hero, &mut perf_stats);
+        if switches.direct {
+            light_shader.draw(&sphere, &LIGHTS, &view_mat, &proj_mat, &mut perf_stats);
+        }
+        hdr_target.unbind();
+        scene_timer.end();
+
+        // Then tone map it onto the window.
+        tone_map_timer.begin();
+        unsafe {
+            gl::Viewport(0, 0, context.width as i32, context.height as i32);
+            gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
+        }
+        tone_map.draw(hdr_target.colour_texture());
+        tone_map_timer.end();
+
+        perf_stats.set_gpu_pass_ms("scene", scene_timer.elapsed_ms());
+        perf_stats.set_gpu_pass_ms("tone map", tone_map_timer.elapsed_ms());
+        let label_text = format!(
+            "{}: {}\n{}\n{}",
+            ALBEDO_PRESETS[preset].0, hero.describe(), switches.describe(), tone_map.settings.describe()
+        );
+        let label_bottom = label(&mut overlay, &label_text);
+        if show_perf_stats {
+            perf_stats.draw_hud(&mut overlay, 10.0, label_bottom + 6.0);
+        }
+        overlay.draw(context.width, context.height);
+
+        context.glfw.poll_events();
+
+        let mut toggle_fullscreen = false;
+        let mut resized_to = None;
+        for (_, event) in glfw::flush_messages(&context.events) {
+            match event {
+                glfw::WindowEvent::FramebufferSize(width, height) => {
+                    resized_to = Some((width, height));
+                }
+                glfw::WindowEvent::Key(Key::Comma, _, Action::Press, _) => {
+                    hero.change_metallic(-MATERIAL_STEP);
+                    logger.log(&hero.describe());
+                }
+                glfw::WindowEvent::Key(Key::Period, _, Action::Press, _) => {
+                    hero.change_metallic(MATERIAL_STEP);
+                    logger.log(&hero.describe());
+                }
+                glfw::WindowEvent::Key(Key::Minus, _, Action::Press, _) => {
+                    hero.change_roughness(-MATERIAL_STEP);
+                    logger.log(&hero.describe());
+                }
+                glfw::WindowEvent::Key(Key::Equal, _, Action::Press, _) => {
+                    hero.change_roughness(MATERIAL_STEP);
+                    logger.log(&hero.describe());
+                }
+                glfw::WindowEvent::Key(Key::C, _, Action::Press, _) => {
+                    preset = (preset + 1) % ALBEDO_PRESETS.len();
+                    hero.albedo = ALBEDO_PRESETS[preset].1;
+                    logger.log(&format!("albedo: {}", ALBEDO_PRESETS[preset].0));
+                }
+                glfw::WindowEvent::Key(Key::Num1, _, Action::Press, _) => {
+                    switches.direct = !switches.direct;
+                    logger.log(&switches.describe());
+                }
+                glfw::WindowEvent::Key(Key::Num2, _, Action::Press, _) => {
+                    switches.ibl = !switches.ibl;
+                    logger.log(&switches.describe());
+                }
+                glfw::WindowEvent::Key(Key::LeftBracket, _, Action::Press, _) => {
+                    tone_map.settings.change_exposure(-EXPOSURE_STEP_STOPS);
+                    logger.log(&tone_map.settings.describe());
+                }
+                glfw::WindowEvent::Key(Key::RightBracket, _, Action::Press, _) => {
+                    tone_map.settings.change_exposure(EXPOSURE_STEP_STOPS);
+                    logger.log(&tone_map.settings.describe());
+                }
+                glfw::WindowEvent::Key(Key::F3, _, Action::Press, _) => {
+                    show_perf_stats = !show_perf_stats;
+                }
+                glfw::WindowEvent::Key(Key::Enter, _, Action::Press, mods) => {
+                    if mods.contains(glfw::Modifiers::Alt) {
+                        toggle_fullscreen = true;
+                    }
+                }
+                glfw::WindowEvent::Key(Key::Escape, _, Action::Press, _) => {
+                    context.window.set_should_close(true);
+                }
+                glfw::WindowEvent::Key(Key::F5, _, Action::Press, _) => {
+                    controller.camera.save_to_camera_file();
+                }
+                glfw::WindowEvent::Key(Key::F9, _, Action::Press, _) => {
+                    if let Some(camera) = Camera::restore_from_camera_file() {
+                        controller.set_camera(&camera);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        if toggle_fullscreen {
+            let fullscreen = context.toggle_fullscreen();
+            logger.log(&format!("fullscreen: {}", fullscreen));
+            let (width, height) = context.window.get_framebuffer_size();
+            resized_to = Some((width, height));
+        }
+
+        if let Some((width, height)) = resized_to {
+            if let Err(e) = glfw_framebuffer_size_callback(&mut context, &mut hdr_target, width as u32, height as u32) {
+                logger.log_err(&e);
+            }
+            logger.log(&format!("framebuffer resized to {}x{}", width, height));
+        }
+
+        // control keys
+        controller.update(&context.window, elapsed_seconds);
+
+        // Put the stuff we've been drawing onto the display.
+        context.window.swap_buffers();
+        perf_stats.end_frame(perf_stats::duration_to_ms(frame_start.elapsed()));
+    }
+}
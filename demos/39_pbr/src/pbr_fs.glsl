@@ -0,0 +1,118 @@
+#version 410
+
+// keep in step with MAX_LIGHTS in lights.rs
+#define MAX_LIGHTS 4
+
+in vec3 p_world;
+in vec3 n_world;
+
+// the point lights, shared by every programme that lights with them. the w of a
+// position is 1 for a light that is on and 0 for an unused slot. keep in step
+// with pack_lights in lights.rs.
+layout(std140) uniform Lights {
+	vec4 light_positions[MAX_LIGHTS];
+	vec4 light_radiances[MAX_LIGHTS];
+};
+// the environment blurred for rougher surfaces at each mip level down, and the
+// scale and bias to the fresnel term for each n.v and roughness.
+uniform samplerCube prefiltered_map;
+uniform sampler2D brdf_lut;
+uniform float max_lod;
+uniform vec3 albedo;
+uniform float metallic;
+uniform float roughness;
+uniform vec3 eye_world;
+// either half of the lighting can be switched off to see what the other adds.
+uniform bool direct_enabled;
+uniform bool ibl_enabled;
+// linear light, brighter than one where it needs to be. the tone mapping pass
+// brings it down to the window's range.
+out vec4 frag_colour;
+
+const float PI = 3.14159265359;
+
+// the ggx/trowbridge-reitz distribution: how many microfacets face along h.
+float distribution_ggx (float n_dot_h) {
+	float a = roughness * roughness;
+	float a2 = a * a;
+	float d = n_dot_h * n_dot_h * (a2 - 1.0) + 1.0;
+	return a2 / (PI * d * d);
+}
+
+// how much of the surface one direction can see past the microfacets' shadows.
+// direct light uses a different k from the image based lighting in ibl.rs.
+float geometry_schlick_ggx (float n_dot_x) {
+	float r = roughness + 1.0;
+	float k = r * r / 8.0;
+	return n_dot_x / (n_dot_x * (1.0 - k) + k);
+}
+
+vec3 fresnel_schlick (float cos_theta, vec3 f0) {
+	return f0 + (1.0 - f0) * pow (1.0 - cos_theta, 5.0);
+}
+
+// schlick's fresnel, held back on rough surfaces, whose microfacets mostly face
+// away from a grazing viewer.
+vec3 fresnel_schlick_roughness (float cos_theta, vec3 f0) {
+	return f0 + (max (vec3 (1.0 - roughness), f0) - f0) * pow (1.0 - cos_theta, 5.0);
+}
+
+// the cook-torrance brdf, times the light arriving from each point light.
+vec3 direct_light (vec3 n, vec3 v, vec3 f0) {
+	float n_dot_v = max (dot (n, v), 0.0);
+	vec3 total = vec3 (0.0);
+	for (int i = 0; i < MAX_LIGHTS; i++) {
+		if (light_positions[i].w == 0.0) {
+			continue;
+		}
+		vec3 to_light = light_positions[i].xyz - p_world;
+		float dist = length (to_light);
+		vec3 l = to_light / dist;
+		vec3 h = normalize (v + l);
+		float n_dot_l = max (dot (n, l), 0.0);
+		vec3 radiance = light_radiances[i].rgb / (dist * dist);
+
+		float d = distribution_ggx (max (dot (n, h), 0.0));
+		float g = geometry_schlick_ggx (n_dot_v) * geometry_schlick_ggx (n_dot_l);
+		vec3 f = fresnel_schlick (max (dot (h, v), 0.0), f0);
+		vec3 specular = d * g * f / max (4.0 * n_dot_v * n_dot_l, 0.001);
+
+		// what is not reflected is refracted, and metals absorb all of that.
+		vec3 k_d = (1.0 - f) * (1.0 - metallic);
+		total += (k_d * albedo / PI + specular) * radiance * n_dot_l;
+	}
+	return total;
+}
+
+// the split sum approximation of the environment's light, as in the cube
+// mapping demo.
+vec3 image_based_light (vec3 n, vec3 v, vec3 f0) {
+	float n_dot_v = max (dot (n, v), 0.0);
+	vec3 f = fresnel_schlick_roughness (n_dot_v, f0);
+
+	vec3 prefiltered = textureLod (prefiltered_map, reflect (-v, n), roughness * max_lod).rgb;
+	vec2 brdf = texture (brdf_lut, vec2 (n_dot_v, roughness)).rg;
+	vec3 specular = prefiltered * (f * brdf.x + brdf.y);
+
+	// the roughest level is blurred enough to stand in for the diffuse light
+	// arriving from around the normal.
+	vec3 irradiance = textureLod (prefiltered_map, n, max_lod).rgb;
+	vec3 k_d = (1.0 - f) * (1.0 - metallic);
+	return k_d * albedo * irradiance + specular;
+}
+
+void main () {
+	vec3 n = normalize (n_world);
+	vec3 v = normalize (eye_world - p_world);
+	// dielectrics all reflect about 4% head on, and metals tint it with their colour.
+	vec3 f0 = mix (vec3 (0.04), albedo, metallic);
+
+	vec3 colour = vec3 (0.0);
+	if (direct_enabled) {
+		colour += direct_light (n, v, f0);
+	}
+	if (ibl_enabled) {
+		colour += image_based_light (n, v, f0);
+	}
+	frag_colour = vec4 (colour, 1.0);
+}
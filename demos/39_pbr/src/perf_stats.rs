@@ -0,0 +1,279 @@
+use overlay::{Overlay, GLYPH_ADVANCE, LINE_HEIGHT};
+
+use std::collections::VecDeque;
+use std::time::Duration;
+
+
+///
+/// The number of frames the rolling statistics are computed over.
+///
+pub const PERF_STATS_HISTORY: usize = 120;
+
+// Frame time budgets in milliseconds for 60 Hz and 30 Hz.
+const BUDGET_60HZ_MS: f64 = 1000.0 / 60.0;
+const BUDGET_30HZ_MS: f64 = 1000.0 / 30.0;
+
+const HUD_TEXT_COLOUR: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+const HUD_BACKGROUND_COLOUR: [f32; 4] = [0.0, 0.0, 0.0, 0.6];
+const HUD_GOOD_COLOUR: [f32; 4] = [0.2, 0.9, 0.2, 1.0];
+const HUD_OK_COLOUR: [f32; 4] = [0.9, 0.8, 0.2, 1.0];
+const HUD_BAD_COLOUR: [f32; 4] = [0.9, 0.2, 0.2, 1.0];
+const HUD_BUDGET_LINE_COLOUR: [f32; 4] = [1.0, 1.0, 1.0, 0.5];
+
+
+///
+/// Convert a `Duration` into milliseconds.
+///
+pub fn duration_to_ms(duration: Duration) -> f64 {
+    duration.as_secs() as f64 * 1000.0 + duration.subsec_nanos() as f64 / 1_000_000.0
+}
+
+///
+/// Collects per-frame CPU times along with draw call and triangle counters, and
+/// computes rolling percentiles over the most recent frames. Demos call `count_draw`
+/// whenever they issue a draw call, and `end_frame` once per frame.
+///
+pub struct PerfStats {
+    frame_times_ms: VecDeque<f64>,
+    capacity: usize,
+    draw_calls: u32,
+    triangles: u32,
+    last_draw_calls: u32,
+    last_triangles: u32,
+    gpu_pass_times_ms: Vec<(&'static str, f64)>,
+}
+
+impl PerfStats {
+    pub fn new(capacity: usize) -> PerfStats {
+        assert!(capacity > 0);
+        PerfStats {
+            frame_times_ms: VecDeque::with_capacity(capacity),
+            capacity: capacity,
+            draw_calls: 0,
+            triangles: 0,
+            last_draw_calls: 0,
+            last_triangles: 0,
+            gpu_pass_times_ms: vec![],
+        }
+    }
+
+    ///
+    /// Record one draw call producing `triangles` triangles in the current frame.
+    ///
+    pub fn count_draw(&mut self, triangles: usize) {
+        self.draw_calls += 1;
+        self.triangles += triangles as u32;
+    }
+
+    ///
+    /// Finish the current frame, recording how long it took on the CPU, and reset
+    /// the draw counters for the next frame.
+    ///
+    pub fn end_frame(&mut self, frame_time_ms: f64) {
+        if self.frame_times_ms.len() == self.capacity {
+            self.frame_times_ms.pop_front();
+        }
+        self.frame_times_ms.push_back(frame_time_ms);
+
+        self.last_draw_calls = self.draw_calls;
+        self.last_triangles = self.triangles;
+        self.draw_calls = 0;
+        self.triangles = 0;
+    }
+
+    ///
+    /// The frame times currently in the rolling window, oldest first.
+    ///
+    pub fn frame_times_ms(&self) -> &VecDeque<f64> {
+        &self.frame_times_ms
+    }
+
+    ///
+    /// The number of draw calls issued in the last completed frame.
+    ///
+    pub fn draw_calls(&self) -> u32 {
+        self.last_draw_calls
+    }
+
+    ///
+    /// The number of triangles drawn in the last completed frame.
+    ///
+    pub fn triangles(&self) -> u32 {
+        self.last_triangles
+    }
+
+    ///
+    /// Record the GPU time for a named render pass, as measured by a `GpuTimer`.
+    /// The passes are shown on the HUD in the order they were first recorded.
+    ///
+    pub fn set_gpu_pass_ms(&mut self, pass: &'static str, gpu_time_ms: f64) {
+        for entry in self.gpu_pass_times_ms.iter_mut() {
+            if entry.0 == pass {
+                entry.1 = gpu_time_ms;
+                return;
+            }
+        }
+        self.gpu_pass_times_ms.push((pass, gpu_time_ms));
+    }
+
+    ///
+    /// The GPU time of each named render pass.
+    ///
+    pub fn gpu_pass_times_ms(&self) -> &[(&'static str, f64)] {
+        &self.gpu_pass_times_ms
+    }
+
+    ///
+    /// Compute the `p`th percentile frame time over the rolling window using the
+    /// nearest-rank method. Returns zero if no frames have been recorded yet.
+    ///
+    pub fn percentile(&self, p: f64) -> f64 {
+        if self.frame_times_ms.is_empty() {
+            return 0.0;
+        }
+
+        let mut sorted: Vec<f64> = self.frame_times_ms.iter().cloned().collect();
+        sorted.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+        let p = f64::min(f64::max(p, 0.0), 100.0);
+        let rank = f64::ceil(p / 100.0 * sorted.len() as f64) as usize;
+        let index = if rank == 0 { 0 } else { rank - 1 };
+
+        sorted[index]
+    }
+
+    pub fn p50(&self) -> f64 {
+        self.percentile(50.0)
+    }
+
+    pub fn p95(&self) -> f64 {
+        self.percentile(95.0)
+    }
+
+    pub fn p99(&self) -> f64 {
+        self.percentile(99.0)
+    }
+
+    ///
+    /// Queue up the statistics and a bar graph of the frame times on an overlay.
+    /// The graph is scaled so the 30 Hz budget is always in view, and the 60 Hz
+    /// budget is marked with a line. Returns the y coordinate just below the HUD so
+    /// callers can stack more text underneath it.
+    ///
+    pub fn draw_hud(&self, overlay: &mut Overlay, x: f32, y: f32) -> f32 {
+        let scale = 2.0;
+        let line = LINE_HEIGHT * scale;
+        let graph_width = 2.0 * self.capacity as f32;
+        let graph_height = 60.0;
+        let padding = 6.0;
+        let mut lines = vec![
+            format!("cpu ms  p50 {:5.2}  p95 {:5.2}  p99 {:5.2}", self.p50(), self.p95(), self.p99()),
+            format!("draws {}  tris {}", self.last_draw_calls, self.last_triangles),
+        ];
+        if !self.gpu_pass_times_ms.is_empty() {
+            let mut gpu_line = String::from("gpu ms");
+            for &(pass, gpu_time_ms) in self.gpu_pass_times_ms.iter() {
+                gpu_line.push_str(&format!("  {} {:5.2}", pass, gpu_time_ms));
+            }
+            lines.push(gpu_line);
+        }
+
+        let longest_line = lines.iter().map(|line| line.len()).max().unwrap_or(0);
+        let width = f32::max(graph_width, longest_line as f32 * GLYPH_ADVANCE * scale) + 2.0 * padding;
+        let height = lines.len() as f32 * line + graph_height + 3.0 * padding;
+        overlay.rect(x, y, width, height, HUD_BACKGROUND_COLOUR);
+
+        let mut text_y = y + padding;
+        for text in lines.iter() {
+            overlay.text(x + padding, text_y, scale, HUD_TEXT_COLOUR, text);
+            text_y += line;
+        }
+
+        let graph_x = x + padding;
+        let graph_bottom = text_y + padding + graph_height;
+        let max_ms = f64::max(self.p99(), BUDGET_30HZ_MS);
+        let bar_width = graph_width / self.capacity as f32;
+        for (i, &frame_time_ms) in self.frame_times_ms.iter().enumerate() {
+            let bar_height = (frame_time_ms / max_ms) as f32 * graph_height;
+            let bar_height = f32::min(bar_height, graph_height);
+            let colour = if frame_time_ms <= BUDGET_60HZ_MS {
+                HUD_GOOD_COLOUR
+            } else if frame_time_ms <= BUDGET_30HZ_MS {
+                HUD_OK_COLOUR
+            } else {
+                HUD_BAD_COLOUR
+            };
+            overlay.rect(
+                graph_x + i as f32 * bar_width, graph_bottom - bar_height, bar_width, bar_height, colour
+            );
+        }
+
+        let budget_y = graph_bottom - (BUDGET_60HZ_MS / max_ms) as f32 * graph_height;
+        overlay.rect(graph_x, budget_y, graph_width, 1.0, HUD_BUDGET_LINE_COLOUR);
+
+        y + height
+    }
+}
+
+mod perf_stats_tests {
+    use super::PerfStats;
+
+    #[test]
+    fn test_percentiles_of_empty_window_are_zero() {
+        let stats = PerfStats::new(10);
+
+        assert_eq!(stats.p50(), 0.0);
+        assert_eq!(stats.p99(), 0.0);
+    }
+
+    #[test]
+    fn test_percentiles_use_nearest_rank() {
+        let mut stats = PerfStats::new(100);
+        for i in 1..101 {
+            stats.end_frame(i as f64);
+        }
+
+        assert_eq!(stats.p50(), 50.0);
+        assert_eq!(stats.p95(), 95.0);
+        assert_eq!(stats.p99(), 99.0);
+        assert_eq!(stats.percentile(100.0), 100.0);
+        assert_eq!(stats.percentile(0.0), 1.0);
+    }
+
+    #[test]
+    fn test_window_only_keeps_most_recent_frames() {
+        let mut stats = PerfStats::new(4);
+        for &frame_time_ms in [100.0, 100.0, 1.0, 2.0, 3.0, 4.0].iter() {
+            stats.end_frame(frame_time_ms);
+        }
+
+        assert_eq!(stats.frame_times_ms().len(), 4);
+        assert_eq!(stats.percentile(100.0), 4.0);
+    }
+
+    #[test]
+    fn test_draw_counters_reset_each_frame() {
+        let mut stats = PerfStats::new(4);
+        stats.count_draw(2);
+        stats.count_draw(10);
+        stats.end_frame(1.0);
+
+        assert_eq!(stats.draw_calls(), 2);
+        assert_eq!(stats.triangles(), 12);
+
+        stats.end_frame(1.0);
+
+        assert_eq!(stats.draw_calls(), 0);
+        assert_eq!(stats.triangles(), 0);
+    }
+
+    #[test]
+    fn test_gpu_pass_times_update_in_place() {
+        let mut stats = PerfStats::new(4);
+        stats.set_gpu_pass_ms("ground", 1.0);
+        stats.set_gpu_pass_ms("gui", 2.0);
+        stats.set_gpu_pass_ms("ground", 3.0);
+
+        assert_eq!(stats.gpu_pass_times_ms(), &[("ground", 3.0), ("gui", 2.0)]);
+    }
+}
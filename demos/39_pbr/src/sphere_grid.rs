@@ -0,0 +1,83 @@
+use graphics_math as math;
+use math::Vec3;
+
+use pbr_material::PbrMaterial;
+
+
+///
+/// The number of rows and columns of spheres. Metallic goes up the rows, from a
+/// dielectric along the bottom to a bare metal along the top, and roughness goes
+/// across the columns, from a mirror finish on the left to a matte one on the right.
+///
+pub const GRID_ROWS: usize = 7;
+pub const GRID_COLUMNS: usize = 7;
+///
+/// The distance between the centres of neighbouring spheres, and the radius of each.
+///
+pub const GRID_SPACING: f32 = 1.2;
+pub const GRID_SPHERE_RADIUS: f32 = 0.5;
+///
+/// The smoothest roughness in the grid. A roughness of exactly zero shrinks the
+/// highlight of a point light to nothing, so the left column would look unlit.
+///
+pub const MIN_GRID_ROUGHNESS: f32 = 0.05;
+
+
+///
+/// How far along a row or column of `count` spheres sphere `i` is, from 0 at the
+/// first to 1 at the last.
+///
+fn fraction(i: usize, count: usize) -> f32 {
+    if count < 2 {
+        return 0.0;
+    }
+
+    i as f32 / (count - 1) as f32
+}
+
+///
+/// The material of the sphere at `row` and `column`, counting rows from the bottom
+/// and columns from the left.
+///
+pub fn grid_material(row: usize, column: usize, albedo: [f32; 3]) -> PbrMaterial {
+    let metallic = fraction(row, GRID_ROWS);
+    let roughness = MIN_GRID_ROUGHNESS + (1.0 - MIN_GRID_ROUGHNESS) * fraction(column, GRID_COLUMNS);
+
+    PbrMaterial::new(albedo, metallic, roughness)
+}
+
+///
+/// The centre of the sphere at `row` and `column`. The grid stands upright in the
+/// plane z = 0, centred on the origin.
+///
+pub fn grid_position(row: usize, column: usize) -> Vec3 {
+    let x = (column as f32 - 0.5 * (GRID_COLUMNS - 1) as f32) * GRID_SPACING;
+    let y = (row as f32 - 0.5 * (GRID_ROWS - 1) as f32) * GRID_SPACING;
+
+    math::vec3((x, y, 0.0))
+}
+
+mod sphere_grid_tests {
+    use super::{grid_material, grid_position, GRID_COLUMNS, GRID_ROWS, MIN_GRID_ROUGHNESS};
+
+    #[test]
+    fn test_the_corners_of_the_grid_span_every_material() {
+        let albedo = [0.5, 0.0, 0.0];
+        let bottom_left = grid_material(0, 0, albedo);
+        let top_right = grid_material(GRID_ROWS - 1, GRID_COLUMNS - 1, albedo);
+
+        assert_eq!((bottom_left.metallic, bottom_left.roughness), (0.0, MIN_GRID_ROUGHNESS));
+        assert_eq!((top_right.metallic, top_right.roughness), (1.0, 1.0));
+        assert_eq!(top_right.albedo, albedo);
+    }
+
+    #[test]
+    fn test_the_grid_is_centred_on_the_origin() {
+        let bottom_left = grid_position(0, 0);
+        let top_right = grid_position(GRID_ROWS - 1, GRID_COLUMNS - 1);
+        for i in 0..3 {
+            assert!(f32::abs(bottom_left.v[i] + top_right.v[i]) < 1e-5);
+        }
+        assert!(bottom_left.v[0] < 0.0 && bottom_left.v[1] < 0.0);
+    }
+}
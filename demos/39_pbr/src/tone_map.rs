@@ -0,0 +1,119 @@
+use gl;
+use gl::types::{GLint, GLuint};
+
+use fullscreen_pass::FullscreenPass;
+use gl_utils::uniform_location;
+
+
+///
+/// The exposure the demo starts at, and how far it can be turned down or up, in
+/// stops either side of one.
+///
+pub const DEFAULT_EXPOSURE: f32 = 1.0;
+pub const MAX_EXPOSURE_STOPS: f32 = 6.0;
+
+
+///
+/// The exposure and tone mapping curve, as `tone_map_fs.glsl` applies them to one
+/// linear colour channel, before the gamma correction.
+///
+pub fn tone_map(value: f32, exposure: f32) -> f32 {
+    1.0 - f32::exp(-value * exposure)
+}
+
+///
+/// The settings of the tone mapping that can be changed while the demo runs.
+///
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ToneMapSettings {
+    pub exposure: f32,
+}
+
+impl ToneMapSettings {
+    pub fn new() -> ToneMapSettings {
+        ToneMapSettings {
+            exposure: DEFAULT_EXPOSURE,
+        }
+    }
+
+    ///
+    /// Open or close the exposure by `stops`, each one doubling or halving it.
+    ///
+    pub fn change_exposure(&mut self, stops: f32) {
+        let current = f32::log2(self.exposure);
+        let stops = f32::min(f32::max(current + stops, -MAX_EXPOSURE_STOPS), MAX_EXPOSURE_STOPS);
+        self.exposure = f32::powf(2.0, stops);
+    }
+
+    pub fn describe(&self) -> String {
+        format!("exposure {:.3} ({:+.1} stops)", self.exposure, f32::log2(self.exposure))
+    }
+}
+
+///
+/// Brings the high dynamic range frame the scene is lit into down to the window's
+/// range. The exposure scales the light, an exponential curve rolls off the
+/// highlights instead of clipping them, and the result is gamma corrected, since
+/// every sum before it is done in linear light.
+///
+pub struct ToneMap {
+    sp: GLuint,
+    pass: FullscreenPass,
+    exposure_location: GLint,
+    pub settings: ToneMapSettings,
+}
+
+impl ToneMap {
+    pub fn new(sp: GLuint) -> ToneMap {
+        let location = |name: &str| {
+            let location = uniform_location(sp, name);
+            assert!(location > -1);
+            location
+        };
+        unsafe {
+            gl::UseProgram(sp);
+            gl::Uniform1i(location("hdr_tex"), 0);
+        }
+
+        ToneMap {
+            sp: sp,
+            pass: FullscreenPass::new(),
+            exposure_location: location("exposure"),
+            settings: ToneMapSettings::new(),
+        }
+    }
+
+    ///
+    /// Draw `hdr_texture` over the viewport, tone mapped.
+    ///
+    pub fn draw(&self, hdr_texture: GLuint) {
+        unsafe {
+            gl::UseProgram(self.sp);
+            gl::Uniform1f(self.exposure_location, self.settings.exposure);
+        }
+        self.pass.draw(self.sp, &[hdr_texture]);
+    }
+}
+
+mod tone_map_tests {
+    use super::{tone_map, ToneMapSettings, MAX_EXPOSURE_STOPS};
+
+    #[test]
+    fn test_the_curve_rolls_off_towards_white_without_reaching_it() {
+        assert_eq!(tone_map(0.0, 1.0), 0.0);
+        assert!(tone_map(1.0, 1.0) < tone_map(2.0, 1.0));
+        assert!(tone_map(10.0, 1.0) < 1.0);
+        assert_eq!(tone_map(1.0, 2.0), tone_map(2.0, 1.0));
+    }
+
+    #[test]
+    fn test_each_stop_doubles_the_exposure_up_to_the_limit() {
+        let mut settings = ToneMapSettings::new();
+        settings.change_exposure(1.0);
+        assert!(f32::abs(settings.exposure - 2.0) < 1e-5);
+
+        settings.change_exposure(-100.0);
+        assert!(f32::abs(settings.exposure - f32::powf(2.0, -MAX_EXPOSURE_STOPS)) < 1e-7);
+        assert_eq!(settings.describe(), "exposure 0.016 (-6.0 stops)");
+    }
+}
@@ -0,0 +1,15 @@
+#version 410
+
+in vec2 st;
+// the lit scene, in linear light.
+uniform sampler2D hdr_tex;
+uniform float exposure;
+out vec4 frag_colour;
+
+void main () {
+	vec3 hdr = texture (hdr_tex, st).rgb;
+	// roll the highlights off towards white. the same sum as tone_map in tone_map.rs.
+	vec3 mapped = vec3 (1.0) - exp (-hdr * exposure);
+	// the window expects colours with its gamma, not linear light.
+	frag_colour = vec4 (pow (mapped, vec3 (1.0 / 2.2)), 1.0);
+}
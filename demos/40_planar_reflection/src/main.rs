@@ -0,0 +1,627 @@
+extern crate gl;
+extern crate glfw;
+extern crate chrono;
+extern crate stb_image;
+#[cfg(feature = "glam")]
+extern crate glam;
+#[cfg(feature = "cgmath")]
+extern crate cgmath;
+
+#[macro_use]
+mod gl_utils;
+mod graphics_math;
+mod obj_parser;
+mod colour;
+mod assets;
+mod shader_include;
+mod spirv;
+mod program_cache;
+mod logger;
+mod overlay;
+mod streaming_buffer;
+mod camera;
+mod camera_controller;
+mod render_target;
+mod vertex_layout;
+mod gpu_timer;
+mod perf_stats;
+mod material;
+mod planar_mirror;
+
+
+use glfw::{Action, Context, Key};
+use gl::types::{GLfloat, GLint, GLuint};
+
+use std::process;
+use std::time::Instant;
+
+use gl_utils::*;
+
+use graphics_math as math;
+use math::Mat4;
+
+use camera::Camera;
+use camera_controller::{CameraController, ControlKeys};
+use colour::Colour;
+use gpu_timer::GpuTimer;
+use material::{MaterialBinder, MaterialTextures};
+use obj_parser::Material;
+use overlay::Overlay;
+use perf_stats::{PerfStats, PERF_STATS_HISTORY};
+use planar_mirror::PlanarMirror;
+use vertex_layout::VertexLayout;
+
+
+const GL_LOG_FILE: &str = "gl.log";
+const SCENE_VS_FILE: &str = "src/scene_vs.glsl";
+const SCENE_FS_FILE: &str = "src/scene_fs.glsl";
+const MIRROR_VS_FILE: &str = "src/mirror_vs.glsl";
+const MIRROR_FS_FILE: &str = "src/mirror_fs.glsl";
+
+const GROUND_HALF_SIZE: f32 = 20.0;
+const EYE_HEIGHT: f32 = 1.7;
+// The mirror stands upright on the ground at the origin, facing the camera.
+const MIRROR_CENTRE: [f32; 3] = [0.0, 1.5, 0.0];
+const MIRROR_WIDTH: f32 = 4.0;
+const MIRROR_HEIGHT: f32 = 3.0;
+// The mirror sits in a dark frame, a little thicker than the glass is wide.
+const FRAME_BORDER: f32 = 0.1;
+const FRAME_DEPTH: f32 = 0.1;
+const MIRROR_TINT: [f32; 3] = [0.9, 0.95, 1.0];
+// How far each key press turns the mirror about its upright axis.
+const MIRROR_TURN_STEP_DEG: f32 = 5.0;
+
+const SKY_COLOUR: Colour = Colour { r: 0.6, g: 0.7, b: 0.8, a: 1.0 };
+const GROUND_COLOUR: Colour = Colour { r: 0.35, g: 0.45, b: 0.3, a: 1.0 };
+const FRAME_COLOUR: Colour = Colour { r: 0.15, g: 0.1, b: 0.08, a: 1.0 };
+
+const OVERLAY_TEXT_COLOUR: Colour = Colour::WHITE;
+const OVERLAY_BACKGROUND_COLOUR: Colour = Colour { r: 0.0, g: 0.0, b: 0.0, a: 0.6 };
+
+
+///
+/// What each shape in the scene is, and where. The shapes in front of the mirror
+/// turn so the reflection has something moving in it. The pillars behind the
+/// mirror only show in its reflection while the clip plane is off.
+///
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Shape {
+    Cube,
+    Sphere,
+}
+
+struct Prop {
+    shape: Shape,
+    position: [f32; 3],
+    scale: [f32; 3],
+    degrees_per_second: f32,
+    colour: [f32; 3],
+}
+
+const PROPS: [Prop; 5] = [
+    Prop {
+        shape: Shape::Cube, position: [-1.5, 0.5, 2.5], scale: [1.0, 1.0, 1.0],
+        degrees_per_second: 30.0, colour: [0.8, 0.3, 0.2],
+    },
+    Prop {
+        shape: Shape::Sphere, position: [1.5, 0.7, 2.0], scale: [0.7, 0.7, 0.7],
+        degrees_per_second: 0.0, colour: [0.2, 0.5, 0.8],
+    },
+    Prop {
+        shape: Shape::Cube, position: [0.3, 0.4, 4.0], scale: [0.8, 0.8, 0.8],
+        degrees_per_second: -45.0, colour: [0.9, 0.7, 0.2],
+    },
+    Prop {
+        shape: Shape::Cube, position: [-1.2, 1.5, -1.5], scale: [0.6, 3.0, 0.6],
+        degrees_per_second: 0.0, colour: [0.5, 0.3, 0.7],
+    },
+    Prop {
+        shape: Shape::Cube, position: [1.0, 1.5, -1.2], scale: [0.6, 3.0, 0.6],
+        degrees_per_second: 0.0, colour: [0.3, 0.7, 0.6],
+    },
+];
+
+impl Prop {
+    fn model_mat(&self, seconds: f64) -> Mat4 {
+        Mat4::from_scale(&math::vec3((self.scale[0], self.scale[1], self.scale[2])))
+            .rotate_y_deg(self.degrees_per_second * seconds as f32)
+            .then_translate(&math::vec3((self.position[0], self.position[1], self.position[2])))
+    }
+}
+
+///
+/// A plain coloured material with a little shine.
+///
+fn coloured_material(name: &str, colour: Colour) -> Material {
+    let mut material = Material::new(name);
+    material.ka = colour;
+    material.kd = colour;
+    material.ks = Colour::new(0.4, 0.4, 0.4);
+    material.shininess = 40.0;
+
+    material
+}
+
+///
+/// The ground plane: two triangles facing up, with each position followed by
+/// its normal.
+///
+fn ground_vertices() -> Vec<GLfloat> {
+    let a = GROUND_HALF_SIZE;
+    let corners = [(-a, a), (a, a), (a, -a), (a, -a), (-a, -a), (-a, a)];
+    let mut vertices = Vec::with_capacity(6 * corners.len());
+    for &(x, z) in corners.iter() {
+        vertices.extend_from_slice(&[x, 0.0, z, 0.0, 1.0, 0.0]);
+    }
+
+    vertices
+}
+
+///
+/// A unit cube centred on the origin, with each position followed by its normal.
+///
+fn cube_vertices() -> Vec<GLfloat> {
+    // The normal of each face, and two axes across it whose cross product is the
+    // normal, so the triangles wind counter-clockwise seen from outside.
+    let faces = [
+        ([1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]),
+        ([-1.0, 0.0, 0.0], [0.0, 0.0, 1.0], [0.0, 1.0, 0.0]),
+        ([0.0, 1.0, 0.0], [0.0, 0.0, 1.0], [1.0, 0.0, 0.0]),
+        ([0.0, -1.0, 0.0], [1.0, 0.0, 0.0], [0.0, 0.0, 1.0]),
+        ([0.0, 0.0, 1.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]),
+        ([0.0, 0.0, -1.0], [0.0, 1.0, 0.0], [1.0, 0.0, 0.0]),
+    ];
+    let corners = [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (1.0, 1.0), (-1.0, 1.0), (-1.0, -1.0)];
+
+    let mut vertices = Vec::with_capacity(6 * 6 * faces.len());
+    for &(n, u, v) in faces.iter() {
+        for &(s, t) in corners.iter() {
+            for i in 0..3 {
+                vertices.push(0.5 * (n[i] + s * u[i] + t * v[i]));
+            }
+            vertices.extend_from_slice(&n);
+        }
+    }
+
+    vertices
+}
+
+///
+/// A unit square in the xy plane, facing along +z. The mirror's glass.
+///
+fn quad_vertices() -> Vec<GLfloat> {
+    vec![
+        -0.5, -0.5, 0.0, 0.5, -0.5, 0.0, 0.5, 0.5, 0.0,
+        0.5, 0.5, 0.0, -0.5, 0.5, 0.0, -0.5, -0.5, 0.0,
+    ]
+}
+
+///
+/// Interleave separate position and normal arrays, as loaded from a mesh.
+///
+fn interleave(points: &[GLfloat], normals: &[GLfloat]) -> Vec<GLfloat> {
+    let mut vertices = Vec::with_capacity(points.len() + normals.len());
+    for i in 0..(points.len() / 3) {
+        vertices.extend_from_slice(&points[3 * i..3 * i + 3]);
+        vertices.extend_from_slice(&normals[3 * i..3 * i + 3]);
+    }
+
+    vertices
+}
+
+///
+/// Copy interleaved positions and normals into a vertex buffer, and make a vertex
+/// array reading positions from location 0 and normals from location 1.
+///
+fn make_vao(vertices: &[GLfloat]) -> GLuint {
+    let mut vao = 0;
+    unsafe {
+        gl::GenVertexArrays(1, &mut vao);
+    }
+    assert!(vao > 0);
+    VertexLayout::new().floats(0, 3).floats(1, 3).upload(vao, vertices, vertices.len() / 6);
+
+    vao
+}
+
+///
+/// The shader the scene is drawn with, lit by one point light, with its material
+/// and clip plane set through a `MaterialBinder`.
+///
+struct SceneShader {
+    sp: GLuint,
+    binder: MaterialBinder,
+    model_location: GLint,
+    view_location: GLint,
+    proj_location: GLint,
+}
+
+impl SceneShader {
+    fn new(sp: GLuint) -> SceneShader {
+        let location = |name: &str| {
+            let location = uniform_location(sp, name);
+            assert!(location > -1);
+            location
+        };
+
+        SceneShader {
+            sp: sp,
+            binder: MaterialBinder::new(sp),
+            model_location: location("M"),
+            view_location: location("V"),
+            proj_location: location("P"),
+        }
+    }
+
+    ///
+    /// Set the camera, and the plane to clip away what is behind, if any.
+    ///
+    fn begin(&self, view_mat: &Mat4, proj_mat: &Mat4, clip_plane: Option<[f32; 4]>) {
+        self.binder.set_clip_plane(clip_plane);
+        unsafe {
+            gl::UseProgram(self.sp);
+            gl::UniformMatrix4fv(self.view_location, 1, gl::FALSE, view_mat.as_ptr());
+            gl::UniformMatrix4fv(self.proj_location, 1, gl::FALSE, proj_mat.as_ptr());
+        }
+    }
+
+    fn draw(&self, vao: GLuint, point_count: usize, model_mat: &Mat4, material: &Material, perf_stats: &mut PerfStats) {
+        self.binder.bind(material, &MaterialTextures::default());
+        unsafe {
+            gl::UniformMatrix4fv(self.model_location, 1, gl::FALSE, model_mat.as_ptr());
+            gl::BindVertexArray(vao);
+            gl::DrawArrays(gl::TRIANGLES, 0, point_count as i32);
+        }
+        perf_stats.count_draw(point_count / 3);
+    }
+}
+
+///
+/// The vertex arrays of everything in the scene, and the materials of the props.
+///
+struct Meshes {
+    ground_vao: GLuint,
+    ground_point_count: usize,
+    cube_vao: GLuint,
+    cube_point_count: usize,
+    sphere_vao: GLuint,
+    sphere_point_count: usize,
+    ground_material: Material,
+    frame_material: Material,
+    prop_materials: Vec<Material>,
+}
+
+impl Meshes {
+    ///
+    /// Draw the ground, the props, and the mirror's frame. The glass is drawn
+    /// separately, since the mirror cannot see itself.
+    ///
+    fn draw_scene(&self, shader: &SceneShader, seconds: f64, mirror_mat: &Mat4, perf_stats: &mut PerfStats) {
+        shader.draw(self.ground_vao, self.ground_point_count, &Mat4::identity(), &self.ground_material, perf_stats);
+        for (prop, material) in PROPS.iter().zip(self.prop_materials.iter()) {
+            let (vao, point_count) = match prop.shape {
+                Shape::Cube => (self.cube_vao, self.cube_point_count),
+                Shape::Sphere => (self.sphere_vao, self.sphere_point_count),
+            };
+            shader.draw(vao, point_count, &prop.model_mat(seconds), material, perf_stats);
+        }
+
+        // The frame's front face sits just behind the glass.
+        let frame_mat = *mirror_mat * Mat4::from_scale(&math::vec3((MIRROR_WIDTH + 2.0 * FRAME_BORDER, MIRROR_HEIGHT + 2.0 * FRAME_BORDER, FRAME_DEPTH)))
+            .then_translate(&math::vec3((0.0, 0.0, -0.5 * FRAME_DEPTH - 0.02)));
+        shader.draw(self.cube_vao, self.cube_point_count, &frame_mat, &self.frame_material, perf_stats);
+    }
+}
+
+///
+/// The shader the mirror's glass is drawn with, looking up the reflection behind
+/// each of its pixels.
+///
+struct MirrorShader {
+    sp: GLuint,
+    model_location: GLint,
+    view_location: GLint,
+    proj_location: GLint,
+    viewport_size_location: GLint,
+    reflection_enabled_location: GLint,
+    vao: GLuint,
+}
+
+impl MirrorShader {
+    fn new(sp: GLuint) -> MirrorShader {
+        let location = |name: &str| {
+            let location = uniform_location(sp, name);
+            assert!(location > -1);
+            location
+        };
+        unsafe {
+            gl::UseProgram(sp);
+            gl::Uniform1i(location("reflection_tex"), 0);
+            gl::Uniform3f(location("tint"), MIRROR_TINT[0], MIRROR_TINT[1], MIRROR_TINT[2]);
+        }
+
+        let quad = quad_vertices();
+        let mut vao = 0;
+        unsafe {
+            gl::GenVertexArrays(1, &mut vao);
+        }
+        assert!(vao > 0);
+        VertexLayout::new().floats(0, 3).upload(vao, &quad, quad.len() / 3);
+
+        MirrorShader {
+            sp: sp,
+            model_location: location("M"),
+            view_location: location("V"),
+            proj_location: location("P"),
+            viewport_size_location: location("viewport_size"),
+            reflection_enabled_location: location("reflection_enabled"),
+            vao: vao,
+        }
+    }
+
+    fn draw(
+        &self, mirror: &PlanarMirror, mirror_mat: &Mat4, view_mat: &Mat4, proj_mat: &Mat4,
+        width: u32, height: u32, perf_stats: &mut PerfStats) {
+
+        let model_mat = *mirror_mat * Mat4::from_scale(&math::vec3((MIRROR_WIDTH, MIRROR_HEIGHT, 1.0)));
+        unsafe {
+            gl::UseProgram(self.sp);
+            gl::UniformMatrix4fv(self.model_location, 1, gl::FALSE, model_mat.as_ptr());
+            gl::UniformMatrix4fv(self.view_location, 1, gl::FALSE, view_mat.as_ptr());
+            gl::UniformMatrix4fv(self.proj_location, 1, gl::FALSE, proj_mat.as_ptr());
+            gl::Uniform2f(self.viewport_size_location, width as f32, height as f32);
+            gl::Uniform1i(self.reflection_enabled_location, mirror.settings.enabled as GLint);
+            gl::ActiveTexture(gl::TEXTURE0);
+            gl::BindTexture(gl::TEXTURE_2D, mirror.texture());
+            gl::BindVertexArray(self.vao);
+            gl::DrawArrays(gl::TRIANGLES, 0, 6);
+        }
+        perf_stats.count_draw(2);
+    }
+}
+
+///
+/// Where the mirror is: turned `yaw_deg` about its upright axis, and standing at
+/// its centre. The glass faces along +z before it is turned.
+///
+fn mirror_model_mat(yaw_deg: f32) -> Mat4 {
+    Mat4::from_rotation_y_deg(yaw_deg)
+        .then_translate(&math::vec3((MIRROR_CENTRE[0], MIRROR_CENTRE[1], MIRROR_CENTRE[2])))
+}
+
+///
+/// The plane of the mirror's glass, facing the way the glass does.
+///
+fn mirror_plane(yaw_deg: f32) -> [f32; 4] {
+    let normal = Mat4::from_rotation_y_deg(yaw_deg) * math::vec4((0.0, 0.0, 1.0, 0.0));
+
+    material::clip_plane(&MIRROR_CENTRE, &[normal.v[0], normal.v[1], normal.v[2]])
+}
+
+///
+/// Queue up a label in the top-left corner of the viewport. Returns the bottom
+/// edge of the label.
+///
+fn label(overlay: &mut Overlay, text: &str) -> f32 {
+    let scale = 2.0;
+    let longest = text.lines().map(|line| line.len()).max().unwrap_or(0);
+    let width = (longest as f32 * overlay::GLYPH_ADVANCE + 4.0) * scale;
+    let height = (text.lines().count() as f32 * overlay::LINE_HEIGHT + 4.0) * scale;
+    overlay.rect(10.0, 10.0, width, height, OVERLAY_BACKGROUND_COLOUR);
+    overlay.text(10.0 + 2.0 * scale, 10.0 + 2.0 * scale, scale, OVERLAY_TEXT_COLOUR, text);
+
+    10.0 + height
+}
+
+///
+/// Keep the context's idea of the framebuffer size up to date, and the mirror's
+/// render target the same size as the window.
+///
+fn glfw_framebuffer_size_callback(
+    context: &mut GLContext, mirror: &mut PlanarMirror, width: u32, height: u32) -> Result<(), String> {
+
+    context.width = width;
+    context.height = height;
+
+    mirror.resize(width, height)
+}
+
+fn main() {
+    let logger = restart_gl_log(GL_LOG_FILE);
+    // Start a GL context and O/S window using the GLFW helper library.
+    let mut context = match start_gl(&logger) {
+        Ok(val) => val,
+        Err(e) => {
+            eprintln!("Failed to Initialize OpenGL context. Got error:");
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+    };
+    // The framebuffer can be bigger than the window on high DPI screens.
+    context.window.set_framebuffer_size_polling(true);
+    let (width, height) = context.window.get_framebuffer_size();
+    context.width = width as u32;
+    context.height = height as u32;
+
+    // the scene as the mirror sees it.
+    let mut mirror = match PlanarMirror::new(context.width, context.height) {
+        Ok(val) => val,
+        Err(e) => {
+            logger.log_err(&e);
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+    };
+
+    unsafe {
+        // Enable depth testing.
+        gl::Enable(gl::DEPTH_TEST);
+        // Depth testing interprets a smaller value as closer to the eye.
+        gl::DepthFunc(gl::LESS);
+        // Cull face.
+        gl::Enable(gl::CULL_FACE);
+        // Cull back face.
+        gl::CullFace(gl::BACK);
+        // GL_CW for clockwise.
+        gl::FrontFace(gl::CCW);
+        gl::ClearColor(SKY_COLOUR.r, SKY_COLOUR.g, SKY_COLOUR.b, SKY_COLOUR.a);
+    }
+
+    /*------------------------------CREATE GEOMETRY------------------------------*/
+    let ground = ground_vertices();
+    let cube = cube_vertices();
+    let sphere_mesh = assets::fallback_mesh();
+    let meshes = Meshes {
+        ground_vao: make_vao(&ground),
+        ground_point_count: ground.len() / 6,
+        cube_vao: make_vao(&cube),
+        cube_point_count: cube.len() / 6,
+        sphere_vao: make_vao(&interleave(&sphere_mesh.points, &sphere_mesh.normals)),
+        sphere_point_count: sphere_mesh.point_count,
+        ground_material: coloured_material("ground", GROUND_COLOUR),
+        frame_material: coloured_material("frame", FRAME_COLOUR),
+        prop_materials: PROPS.iter().map(|prop| coloured_material("prop", Colour::from(prop.colour))).collect(),
+    };
+
+    /*-------------------------------CREATE SHADERS------------------------------*/
+    let scene_programme = create_programme_from_files(&logger, SCENE_VS_FILE, SCENE_FS_FILE);
+    let scene_shader = SceneShader::new(scene_programme);
+    let mirror_programme = create_programme_from_files(&logger, MIRROR_VS_FILE, MIRROR_FS_FILE);
+    let mirror_shader = MirrorShader::new(mirror_programme);
+
+    /*-------------------------------CREATE CAMERA-------------------------------*/
+    let mut controller = CameraController::new(math::vec3((0.0, EYE_HEIGHT, 9.0)), 0.0, -5.0, 67.0, ControlKeys {
+        forward: Key::W, back: Key::S, step_left: Key::A, step_right: Key::D,
+        turn_left: Key::Left, turn_right: Key::Right, look_up: Key::Up, look_down: Key::Down,
+    });
+    logger.log("keys: W, A, S, and D walk, and the arrow keys turn and look up and down;");
+    logger.log("      R toggles the reflection, K toggles the clip plane that keeps what is behind the");
+    logger.log("      mirror out of it, , and . turn the mirror, and F3 toggles the performance HUD;");
+    logger.log("      F5 saves the camera to camera.txt, and F9 restores it");
+
+    // input variables
+    let near = 0.1;                                  // clipping plane
+    let far = 100.0;                                 // clipping plane
+    let mut mirror_yaw_deg = 0.0;
+
+    // the mirror's settings, drawn in the corner, with the frame times and the
+    // GPU time of each pass underneath. F3 toggles the times on and off.
+    let mut overlay = Overlay::new(&logger);
+    let mut perf_stats = PerfStats::new(PERF_STATS_HISTORY);
+    let mut show_perf_stats = true;
+    let mut reflection_timer = GpuTimer::new();
+    let mut scene_timer = GpuTimer::new();
+
+    while !context.window.should_close() {
+        let frame_start = Instant::now();
+        let current_seconds = context.glfw.get_time();
+        let elapsed_seconds = current_seconds - context.elapsed_time_seconds;
+        context.elapsed_time_seconds = current_seconds;
+
+        update_fps_counter(&mut context);
+
+        let aspect = context.width as f32 / u32::max(context.height, 1) as f32;
+        let proj_mat = controller.camera.proj_mat(aspect, near, far);
+        let view_mat = controller.camera.view_mat();
+        let mirror_mat = mirror_model_mat(mirror_yaw_deg);
+        let plane = mirror_plane(mirror_yaw_deg);
+
+        // Draw the scene mirrored about the glass first, clipped to what is in
+        // front of it.
+        reflection_timer.begin();
+        mirror.render(&view_mat, &plane, |mirrored_view_mat, clip_plane| {
+            scene_shader.begin(mirrored_view_mat, &proj_mat, clip_plane);
+            meshes.draw_scene(&scene_shader, current_seconds, &mirror_mat, &mut perf_stats);
+        });
+        reflection_timer.end();
+
+        // Then the scene itself, with the reflection on the glass.
+        scene_timer.begin();
+        unsafe {
+            gl::Viewport(0, 0, context.width as i32, context.height as i32);
+            gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
+        }
+        scene_shader.begin(&view_mat, &proj_mat, None);
+        meshes.draw_scene(&scene_shader, current_seconds, &mirror_mat, &mut perf_stats);
+        mirror_shader.draw(
+            &mirror, &mirror_mat, &view_mat, &proj_mat, context.width, context.height, &mut perf_stats
+        );
+        scene_timer.end();
+
+        perf_stats.set_gpu_pass_ms("reflection", reflection_timer.elapsed_ms());
+        perf_stats.set_gpu_pass_ms("scene", scene_timer.elapsed_ms());
+        let label_text = format!("{}\nmirror turned {:.0} degrees", mirror.settings.describe(), mirror_yaw_deg);
+        let label_bottom = label(&mut overlay, &label_text);
+        if show_perf_stats {
+            perf_stats.draw_hud(&mut overlay, 10.0, label_bottom + 6.0);
+        }
+        overlay.draw(context.width, context.height);
+
+        context.glfw.poll_events();
+
+        let mut toggle_fullscreen = false;
+        let mut resized_to = None;
+        for (_, event) in glfw::flush_messages(&context.events) {
+            match event {
+                glfw::WindowEvent::FramebufferSize(width, height) => {
+                    resized_to = Some((width, height));
+                }
+                glfw::WindowEvent::Key(Key::R, _, Action::Press, _) => {
+                    mirror.settings.enabled = !mirror.settings.enabled;
+                    logger.log(&mirror.settings.describe());
+                }
+                glfw::WindowEvent::Key(Key::K, _, Action::Press, _) => {
+                    mirror.settings.clip = !mirror.settings.clip;
+                    logger.log(&mirror.settings.describe());
+                }
+                glfw::WindowEvent::Key(Key::Comma, _, Action::Press, _) => {
+                    mirror_yaw_deg -= MIRROR_TURN_STEP_DEG;
+                }
+                glfw::WindowEvent::Key(Key::Period, _, Action::Press, _) => {
+                    mirror_yaw_deg += MIRROR_TURN_STEP_DEG;
+                }
+                glfw::WindowEvent::Key(Key::F3, _, Action::Press, _) => {
+                    show_perf_stats = !show_perf_stats;
+                }
+                glfw::WindowEvent::Key(Key::Enter, _, Action::Press, mods) => {
+                    if mods.contains(glfw::Modifiers::Alt) {
+                        toggle_fullscreen = true;
+                    }
+                }
+                glfw::WindowEvent::Key(Key::Escape, _, Action::Press, _) => {
+                    context.window.set_should_close(true);
+                }
+                glfw::WindowEvent::Key(Key::F5, _, Action::Press, _) => {
+                    controller.camera.save_to_camera_file();
+                }
+                glfw::WindowEvent::Key(Key::F9, _, Action::Press, _) => {
+                    if let Some(camera) = Camera::restore_from_camera_file() {
+                        controller.set_camera(&camera);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        if toggle_fullscreen {
+            let fullscreen = context.toggle_fullscreen();
+            logger.log(&format!("fullscreen: {}", fullscreen));
+            let (width, height) = context.window.get_framebuffer_size();
+            resized_to = Some((width, height));
+        }
+
+        if let Some((width, height)) = resized_to {
+            if let Err(e) = glfw_framebuffer_size_callback(&mut context, &mut mirror, width as u32, height as u32) {
+                logger.log_err(&e);
+            }
+            logger.log(&format!("framebuffer resized to {}x{}", width, height));
+        }
+
+        // control keys
+        controller.update(&context.window, elapsed_seconds);
+
+        // Put the stuff we've been drawing onto the display.
+        context.window.swap_buffers();
+        perf_stats.end_frame(perf_stats::duration_to_ms(frame_start.elapsed()));
+    }
+}
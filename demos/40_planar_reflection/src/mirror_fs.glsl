@@ -0,0 +1,17 @@
+#version 410
+
+// the scene drawn mirrored about the mirror's plane, the same size as the window.
+uniform sampler2D reflection_tex;
+uniform vec2 viewport_size;
+uniform bool reflection_enabled;
+// the glass takes a little of the light, and is all there is without a reflection.
+uniform vec3 tint;
+out vec4 frag_colour;
+
+void main () {
+	// the reflection was drawn from the mirrored camera with the same projection,
+	// so the texel behind each pixel of the mirror is the one to show there.
+	vec2 st = gl_FragCoord.xy / viewport_size;
+	vec3 reflection = reflection_enabled ? texture (reflection_tex, st).rgb : vec3 (0.2);
+	frag_colour = vec4 (reflection * tint, 1.0);
+}
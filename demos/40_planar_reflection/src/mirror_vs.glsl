@@ -0,0 +1,8 @@
+#version 410
+
+layout(location = 0) in vec3 vp; // positions
+uniform mat4 P, V, M; // proj, view, model matrices
+
+void main () {
+	gl_Position = P * V * M * vec4 (vp, 1.0);
+}
@@ -0,0 +1,158 @@
+use gl;
+use gl::types::GLuint;
+
+use graphics_math::Mat4;
+
+use render_target::RenderTarget;
+
+
+///
+/// The matrix that mirrors points about `plane`, given as a unit normal and an
+/// offset as `material::clip_plane` makes it. Each point moves twice its
+/// distance from the plane back along the normal.
+///
+pub fn reflection_mat(plane: &[f32; 4]) -> Mat4 {
+    let (a, b, c, d) = (plane[0], plane[1], plane[2], plane[3]);
+
+    Mat4::new(
+        1.0 - 2.0 * a * a, -2.0 * a * b, -2.0 * a * c, 0.0,
+        -2.0 * a * b, 1.0 - 2.0 * b * b, -2.0 * b * c, 0.0,
+        -2.0 * a * c, -2.0 * b * c, 1.0 - 2.0 * c * c, 0.0,
+        -2.0 * a * d, -2.0 * b * d, -2.0 * c * d, 1.0
+    )
+}
+
+///
+/// The settings of the mirror that can be changed while the demo runs.
+///
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct MirrorSettings {
+    ///
+    /// Whether the reflection is drawn at all. The mirror is a flat tint without it.
+    ///
+    pub enabled: bool,
+    ///
+    /// Whether what is behind the mirror is clipped out of the reflection. Turning
+    /// it off shows why it is needed: anything behind the mirror comes out in front
+    /// of it once mirrored, and covers up what the mirror should show.
+    ///
+    pub clip: bool,
+}
+
+impl MirrorSettings {
+    pub fn new() -> MirrorSettings {
+        MirrorSettings {
+            enabled: true,
+            clip: true,
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        if !self.enabled {
+            return String::from("reflection: off");
+        }
+
+        format!("reflection: on, clip plane {}", if self.clip { "on" } else { "off" })
+    }
+}
+
+///
+/// A flat mirror. The scene is drawn a second time from the camera's mirror image
+/// behind the plane, into a render target the size of the window, so the mirror's
+/// surface can look its reflection up by its own position on the screen.
+///
+pub struct PlanarMirror {
+    target: RenderTarget,
+    pub settings: MirrorSettings,
+}
+
+impl PlanarMirror {
+    ///
+    /// Make the mirror's render target, the same size as the window. Returns an
+    /// error if the driver cannot render to it.
+    ///
+    pub fn new(width: u32, height: u32) -> Result<PlanarMirror, String> {
+        let target = match RenderTarget::new(width, height) {
+            Ok(val) => val,
+            Err(e) => return Err(e),
+        };
+
+        Ok(PlanarMirror {
+            target: target,
+            settings: MirrorSettings::new(),
+        })
+    }
+
+    pub fn resize(&mut self, width: u32, height: u32) -> Result<(), String> {
+        self.target.resize(width, height)
+    }
+
+    ///
+    /// Draw the reflection in `plane` of what the camera sees through `view_mat`.
+    /// `draw` is called once with the mirrored view matrix and the clip plane to
+    /// draw the scene with, which is `None` while clipping is off. Mirroring turns
+    /// the triangles inside out, so front faces wind clockwise while it draws. The
+    /// caller has to restore the viewport afterwards.
+    ///
+    pub fn render<F>(&self, view_mat: &Mat4, plane: &[f32; 4], mut draw: F)
+        where F: FnMut(&Mat4, Option<[f32; 4]>)
+    {
+        if !self.settings.enabled {
+            return;
+        }
+
+        let mirrored_view_mat = *view_mat * reflection_mat(plane);
+        self.target.bind();
+        unsafe {
+            gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
+            gl::FrontFace(gl::CW);
+        }
+        draw(&mirrored_view_mat, if self.settings.clip { Some(*plane) } else { None });
+        unsafe {
+            gl::FrontFace(gl::CCW);
+        }
+        self.target.unbind();
+    }
+
+    ///
+    /// The reflection, as the window would see it through the mirror.
+    ///
+    pub fn texture(&self) -> GLuint {
+        self.target.colour_texture()
+    }
+}
+
+mod planar_mirror_tests {
+    use super::{reflection_mat, MirrorSettings};
+    use graphics_math as math;
+
+    #[test]
+    fn test_points_move_to_the_other_side_of_the_plane() {
+        // The plane z = 2, facing along +z.
+        let reflection = reflection_mat(&[0.0, 0.0, 1.0, -2.0]);
+        let mirrored = reflection * math::vec4((1.0, 3.0, 5.0, 1.0));
+
+        assert!(f32::abs(mirrored.v[0] - 1.0) < 1e-5);
+        assert!(f32::abs(mirrored.v[1] - 3.0) < 1e-5);
+        assert!(f32::abs(mirrored.v[2] + 1.0) < 1e-5);
+    }
+
+    #[test]
+    fn test_mirroring_twice_puts_everything_back() {
+        let s = f32::sqrt(0.5);
+        let reflection = reflection_mat(&[s, 0.0, s, 1.5]);
+        let point = math::vec4((0.3, -2.0, 4.0, 1.0));
+        let twice = reflection * (reflection * point);
+        for i in 0..4 {
+            assert!(f32::abs(twice.v[i] - point.v[i]) < 1e-5);
+        }
+    }
+
+    #[test]
+    fn test_the_settings_describe_the_clip_plane() {
+        let mut settings = MirrorSettings::new();
+        assert_eq!(settings.describe(), "reflection: on, clip plane on");
+        settings.enabled = false;
+        assert_eq!(settings.describe(), "reflection: off");
+    }
+}
@@ -0,0 +1,30 @@
+#version 410
+
+in vec3 pos_eye;
+in vec3 n_eye;
+// the material, as MaterialBinder sets it.
+uniform vec3 Ka, Kd, Ks;
+uniform float specular_exponent;
+uniform mat4 V;
+out vec4 frag_colour;
+
+vec3 light_position_world = vec3 (3.0, 6.0, 6.0);
+vec3 Ls = vec3 (1.0, 1.0, 1.0); // white specular colour
+vec3 Ld = vec3 (0.8, 0.8, 0.8); // dull white diffuse light colour
+vec3 La = vec3 (0.25, 0.25, 0.25); // grey ambient colour
+
+void main () {
+	// the light is moved into eye space with the view matrix, so in the mirror it
+	// is mirrored along with everything else.
+	vec3 light_position_eye = vec3 (V * vec4 (light_position_world, 1.0));
+	vec3 n = normalize (n_eye);
+	vec3 to_light = normalize (light_position_eye - pos_eye);
+	vec3 to_viewer = normalize (-pos_eye);
+
+	vec3 Ia = La * Ka;
+	vec3 Id = Ld * Kd * max (dot (n, to_light), 0.0);
+	vec3 half_way = normalize (to_viewer + to_light);
+	vec3 Is = Ls * Ks * pow (max (dot (half_way, n), 0.0), specular_exponent);
+
+	frag_colour = vec4 (Ia + Id + Is, 1.0);
+}
@@ -0,0 +1,19 @@
+#version 410
+
+layout(location = 0) in vec3 vp; // positions
+layout(location = 1) in vec3 vn; // normals
+uniform mat4 P, V, M; // proj, view, model matrices
+// the plane to clip away what is behind, as a normal and an offset. only
+// applies while GL_CLIP_DISTANCE0 is enabled.
+uniform vec4 clip_plane;
+out vec3 pos_eye;
+out vec3 n_eye;
+
+void main () {
+	vec4 p_world = M * vec4 (vp, 1.0);
+	// the signed distance from the plane. negative is clipped.
+	gl_ClipDistance[0] = dot (p_world, clip_plane);
+	pos_eye = vec3 (V * p_world);
+	n_eye = vec3 (V * M * vec4 (vn, 0.0));
+	gl_Position = P * vec4 (pos_eye, 1.0);
+}
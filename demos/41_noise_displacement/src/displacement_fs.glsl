@@ -0,0 +1,32 @@
+#version 410
+
+in vec3 pos_eye;
+in vec3 n_eye;
+in float height;
+// how far the surface is pushed up or down at most
+uniform float amplitude;
+uniform mat4 view;
+out vec4 frag_colour;
+
+vec3 light_direction_world = normalize (vec3 (-0.4, 1.0, 0.3)); // towards the light
+vec3 low_colour = vec3 (0.05, 0.2, 0.45);  // deep blue in the troughs
+vec3 high_colour = vec3 (0.9, 0.95, 1.0);  // near white on the crests
+vec3 La = vec3 (0.25, 0.25, 0.25);
+
+void main () {
+	// colour the surface by how high it has been pushed, so the noise shows even
+	// where the lighting is flat.
+	float t = clamp (0.5 * height / amplitude + 0.5, 0.0, 1.0);
+	vec3 Kd = mix (low_colour, high_colour, t);
+
+	vec3 n = normalize (n_eye);
+	vec3 to_light = normalize (vec3 (view * vec4 (light_direction_world, 0.0)));
+	vec3 to_viewer = normalize (-pos_eye);
+	vec3 half_way = normalize (to_viewer + to_light);
+
+	vec3 Ia = La * Kd;
+	vec3 Id = Kd * max (dot (n, to_light), 0.0);
+	vec3 Is = vec3 (0.3) * pow (max (dot (half_way, n), 0.0), 40.0);
+
+	frag_colour = vec4 (Ia + Id + Is, 1.0);
+}
@@ -0,0 +1,15 @@
+#version 410
+
+layout(location = 0) in vec3 vp; // positions, already displaced on the CPU
+layout(location = 1) in vec3 vn; // normals
+uniform mat4 view, proj;
+out vec3 pos_eye;
+out vec3 n_eye;
+out float height;
+
+void main () {
+	height = vp.y;
+	pos_eye = vec3 (view * vec4 (vp, 1.0));
+	n_eye = vec3 (view * vec4 (vn, 0.0));
+	gl_Position = proj * vec4 (pos_eye, 1.0);
+}
@@ -0,0 +1,368 @@
+extern crate gl;
+extern crate glfw;
+extern crate chrono;
+extern crate stb_image;
+#[cfg(feature = "glam")]
+extern crate glam;
+#[cfg(feature = "cgmath")]
+extern crate cgmath;
+
+#[macro_use]
+mod gl_utils;
+mod graphics_math;
+mod obj_parser;
+mod colour;
+mod assets;
+mod shader_include;
+mod spirv;
+mod program_cache;
+mod logger;
+mod overlay;
+mod streaming_buffer;
+mod camera;
+mod camera_controller;
+mod vertex_layout;
+mod gpu_timer;
+mod perf_stats;
+mod geometry;
+mod noise;
+
+
+use glfw::{Action, Context, Key};
+use gl::types::{GLfloat, GLsizeiptr, GLvoid};
+
+use std::mem;
+use std::process;
+use std::ptr;
+use std::time::Instant;
+
+use gl_utils::*;
+
+use graphics_math as math;
+
+use camera::Camera;
+use camera_controller::{CameraController, ControlKeys};
+use colour::Colour;
+use geometry::GridMesh;
+use gpu_timer::GpuTimer;
+use noise::Fbm;
+use overlay::Overlay;
+use perf_stats::{PerfStats, PERF_STATS_HISTORY};
+use streaming_buffer::StreamingBuffer;
+use vertex_layout::VertexLayout;
+
+
+const GL_LOG_FILE: &str = "gl.log";
+const VERTEX_SHADER_FILE: &str = "src/displacement_vs.glsl";
+const FRAGMENT_SHADER_FILE: &str = "src/displacement_fs.glsl";
+
+// The number of vertices along each side of the grid, and the distance between
+// neighbouring vertices, in world units.
+const GRID_SAMPLES: usize = 97;
+const GRID_SPACING: f32 = 0.25;
+// How far the noise pushes the grid up or down at most.
+const AMPLITUDE: f32 = 2.0;
+// The noise has features about 1 / NOISE_FREQUENCY units across, and drifts
+// through its third dimension NOISE_SPEED units each second.
+const NOISE_FREQUENCY: f32 = 0.15;
+const NOISE_SPEED: f32 = 0.25;
+const DEFAULT_OCTAVES: u32 = 4;
+const MAX_OCTAVES: u32 = 8;
+
+const BACKGROUND_COLOUR: Colour = Colour { r: 0.55, g: 0.65, b: 0.75, a: 1.0 };
+const OVERLAY_TEXT_COLOUR: Colour = Colour::WHITE;
+const OVERLAY_BACKGROUND_COLOUR: Colour = Colour { r: 0.0, g: 0.0, b: 0.0, a: 0.5 };
+
+///
+/// The noise function the grid is displaced by. N cycles between them.
+///
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum NoiseKind {
+    Perlin,
+    Simplex,
+}
+
+impl NoiseKind {
+    fn next(&self) -> NoiseKind {
+        match *self {
+            NoiseKind::Perlin => NoiseKind::Simplex,
+            NoiseKind::Simplex => NoiseKind::Perlin,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match *self {
+            NoiseKind::Perlin => "Perlin",
+            NoiseKind::Simplex => "simplex",
+        }
+    }
+
+    fn sample(&self, fbm: &Fbm, x: f32, y: f32, z: f32) -> f32 {
+        match *self {
+            NoiseKind::Perlin => fbm.sample3(noise::perlin3, x, y, z),
+            NoiseKind::Simplex => fbm.sample3(noise::simplex3, x, y, z),
+        }
+    }
+}
+
+///
+/// The height of each vertex of the grid at `time`, row by row as
+/// `geometry::heightfield_grid` takes them. Time is the noise's third dimension,
+/// so each frame is a slice a little further through it than the last.
+///
+fn displaced_heights(kind: NoiseKind, fbm: &Fbm, time: f32) -> Vec<f32> {
+    let offset = 0.5 * (GRID_SAMPLES - 1) as f32 * GRID_SPACING;
+    let mut heights = Vec::with_capacity(GRID_SAMPLES * GRID_SAMPLES);
+    for j in 0..GRID_SAMPLES {
+        for i in 0..GRID_SAMPLES {
+            let x = i as f32 * GRID_SPACING - offset;
+            let z = j as f32 * GRID_SPACING - offset;
+            heights.push(AMPLITUDE * kind.sample(fbm, x, z, time));
+        }
+    }
+
+    heights
+}
+
+///
+/// Interleave the positions and normals of the grid, as the vertex stream takes them.
+///
+fn interleave(mesh: &GridMesh) -> Vec<GLfloat> {
+    let mut vertices = Vec::with_capacity(mesh.positions.len() + mesh.normals.len());
+    for i in 0..(mesh.positions.len() / 3) {
+        vertices.extend_from_slice(&mesh.positions[3 * i..3 * i + 3]);
+        vertices.extend_from_slice(&mesh.normals[3 * i..3 * i + 3]);
+    }
+
+    vertices
+}
+
+///
+/// Queue up a label in the top-left corner of the viewport. Returns the bottom
+/// edge of the label.
+///
+fn label(overlay: &mut Overlay, text: &str) -> f32 {
+    let scale = 2.0;
+    let longest = text.lines().map(|line| line.len()).max().unwrap_or(0);
+    let width = (longest as f32 * overlay::GLYPH_ADVANCE + 4.0) * scale;
+    let height = (text.lines().count() as f32 * overlay::LINE_HEIGHT + 4.0) * scale;
+    overlay.rect(10.0, 10.0, width, height, OVERLAY_BACKGROUND_COLOUR);
+    overlay.text(10.0 + 2.0 * scale, 10.0 + 2.0 * scale, scale, OVERLAY_TEXT_COLOUR, text);
+
+    10.0 + height
+}
+
+fn main() {
+    let logger = restart_gl_log(GL_LOG_FILE);
+    // Start a GL context and O/S window using the GLFW helper library.
+    let mut context = match start_gl(&logger) {
+        Ok(val) => val,
+        Err(e) => {
+            eprintln!("Failed to Initialize OpenGL context. Got error:");
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+    };
+    // The framebuffer can be bigger than the window on high DPI screens.
+    context.window.set_framebuffer_size_polling(true);
+    let (width, height) = context.window.get_framebuffer_size();
+    context.width = width as u32;
+    context.height = height as u32;
+
+    unsafe {
+        // Enable depth testing.
+        gl::Enable(gl::DEPTH_TEST);
+        // Depth testing interprets a smaller value as closer to the eye.
+        gl::DepthFunc(gl::LESS);
+        // Cull face.
+        gl::Enable(gl::CULL_FACE);
+        // Cull back face.
+        gl::CullFace(gl::BACK);
+        // GL_CW for clockwise.
+        gl::FrontFace(gl::CCW);
+        gl::ClearColor(BACKGROUND_COLOUR.r, BACKGROUND_COLOUR.g, BACKGROUND_COLOUR.b, BACKGROUND_COLOUR.a);
+    }
+
+    /*------------------------------CREATE GEOMETRY------------------------------*/
+    // The triangles of the grid stay the same from frame to frame, so only the
+    // vertices are streamed; the indices go into a buffer once.
+    let mut noise_kind = NoiseKind::Simplex;
+    let mut fbm = Fbm::new(DEFAULT_OCTAVES, NOISE_FREQUENCY);
+    let heights = displaced_heights(noise_kind, &fbm, 0.0);
+    let mesh = geometry::heightfield_grid(&heights, GRID_SAMPLES, GRID_SAMPLES, GRID_SPACING);
+    let index_count = mesh.indices.len();
+
+    let mut vao = 0;
+    unsafe {
+        gl::GenVertexArrays(1, &mut vao);
+    }
+    assert!(vao > 0);
+    let vertex_layout = VertexLayout::new().floats(0, 3).floats(1, 3);
+    let mut vertex_stream = StreamingBuffer::new(
+        gl::ARRAY_BUFFER, vertex_layout.size_of_vertices(mesh.positions.len() / 3)
+    );
+    logger.log(&format!("streaming the grid's vertices with {}", vertex_stream.mode().name()));
+
+    // the index buffer is part of the vertex array state, so it stays bound with it
+    let mut ibo = 0;
+    unsafe {
+        gl::BindVertexArray(vao);
+        gl::GenBuffers(1, &mut ibo);
+        gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, ibo);
+        gl::BufferData(
+            gl::ELEMENT_ARRAY_BUFFER, (index_count * mem::size_of::<u32>()) as GLsizeiptr,
+            mesh.indices.as_ptr() as *const GLvoid, gl::STATIC_DRAW
+        );
+    }
+    assert!(ibo > 0);
+
+    /*-------------------------------CREATE SHADERS------------------------------*/
+    let shader_programme = create_programme_from_files(&logger, VERTEX_SHADER_FILE, FRAGMENT_SHADER_FILE);
+    let view_mat_location = uniform_location(shader_programme, "view");
+    assert!(view_mat_location > -1);
+    let proj_mat_location = uniform_location(shader_programme, "proj");
+    assert!(proj_mat_location > -1);
+    let amplitude_location = uniform_location(shader_programme, "amplitude");
+    assert!(amplitude_location > -1);
+    unsafe {
+        gl::UseProgram(shader_programme);
+        gl::Uniform1f(amplitude_location, AMPLITUDE);
+    }
+
+    /*-------------------------------CREATE CAMERA-------------------------------*/
+    let mut controller = CameraController::new(math::vec3((0.0, 8.0, 16.0)), 0.0, -25.0, 67.0, ControlKeys {
+        forward: Key::W, back: Key::S, step_left: Key::A, step_right: Key::D,
+        turn_left: Key::Left, turn_right: Key::Right, look_up: Key::Up, look_down: Key::Down,
+    });
+    logger.log("keys: W, A, S, and D move, and the arrow keys turn and look up and down;");
+    logger.log("      N switches between Perlin and simplex noise, [ and ] take away and add octaves,");
+    logger.log("      Space pauses the animation, and F3 toggles the performance HUD;");
+    logger.log("      F5 saves the camera to camera.txt, and F9 restores it");
+
+    // input variables
+    let near = 0.1;                                  // clipping plane
+    let far = 100.0;                                 // clipping plane
+    let mut noise_time = 0.0;
+    let mut paused = false;
+
+    // the noise settings and how long the CPU took to displace the grid, drawn in
+    // the corner, with the frame times underneath. F3 toggles the times on and off.
+    let mut overlay = Overlay::new(&logger);
+    let mut perf_stats = PerfStats::new(PERF_STATS_HISTORY);
+    let mut show_perf_stats = true;
+    let mut grid_timer = GpuTimer::new();
+
+    while !context.window.should_close() {
+        let frame_start = Instant::now();
+        let current_seconds = context.glfw.get_time();
+        let elapsed_seconds = current_seconds - context.elapsed_time_seconds;
+        context.elapsed_time_seconds = current_seconds;
+
+        update_fps_counter(&mut context);
+
+        if !paused {
+            noise_time += NOISE_SPEED * elapsed_seconds as f32;
+        }
+
+        // Displace the grid on the CPU, and stream the new vertices to the GPU.
+        let displace_start = Instant::now();
+        let heights = displaced_heights(noise_kind, &fbm, noise_time);
+        let mesh = geometry::heightfield_grid(&heights, GRID_SAMPLES, GRID_SAMPLES, GRID_SPACING);
+        let vertices = interleave(&mesh);
+        let displace_ms = perf_stats::duration_to_ms(displace_start.elapsed());
+
+        // The vertices land somewhere different in the stream each frame, so point the
+        // attributes at wherever they went this time.
+        let offset = vertex_stream.upload(&vertices);
+        vertex_layout.apply_at(vao, vertex_stream.buffer(), offset);
+
+        let aspect = context.width as f32 / u32::max(context.height, 1) as f32;
+        let proj_mat = controller.camera.proj_mat(aspect, near, far);
+        let view_mat = controller.camera.view_mat();
+
+        grid_timer.begin();
+        unsafe {
+            gl::Viewport(0, 0, context.width as i32, context.height as i32);
+            gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
+            gl::UseProgram(shader_programme);
+            gl::UniformMatrix4fv(view_mat_location, 1, gl::FALSE, view_mat.as_ptr());
+            gl::UniformMatrix4fv(proj_mat_location, 1, gl::FALSE, proj_mat.as_ptr());
+            gl::BindVertexArray(vao);
+            gl::DrawElements(gl::TRIANGLES, index_count as i32, gl::UNSIGNED_INT, ptr::null());
+        }
+        perf_stats.count_draw(index_count / 3);
+        grid_timer.end();
+
+        perf_stats.set_gpu_pass_ms("grid", grid_timer.elapsed_ms());
+        let label_text = format!(
+            "{} noise, {} octave{}{}\nCPU displacement {:.2} ms",
+            noise_kind.name(), fbm.octaves, if fbm.octaves == 1 { "" } else { "s" },
+            if paused { ", paused" } else { "" }, displace_ms
+        );
+        let label_bottom = label(&mut overlay, &label_text);
+        if show_perf_stats {
+            perf_stats.draw_hud(&mut overlay, 10.0, label_bottom + 6.0);
+        }
+        overlay.draw(context.width, context.height);
+
+        context.glfw.poll_events();
+
+        let mut toggle_fullscreen = false;
+        for (_, event) in glfw::flush_messages(&context.events) {
+            match event {
+                glfw::WindowEvent::FramebufferSize(width, height) => {
+                    context.width = width as u32;
+                    context.height = height as u32;
+                }
+                glfw::WindowEvent::Key(Key::N, _, Action::Press, _) => {
+                    noise_kind = noise_kind.next();
+                    logger.log(&format!("noise: {}", noise_kind.name()));
+                }
+                glfw::WindowEvent::Key(Key::LeftBracket, _, Action::Press, _) => {
+                    fbm.octaves = u32::max(fbm.octaves - 1, 1);
+                }
+                glfw::WindowEvent::Key(Key::RightBracket, _, Action::Press, _) => {
+                    fbm.octaves = u32::min(fbm.octaves + 1, MAX_OCTAVES);
+                }
+                glfw::WindowEvent::Key(Key::Space, _, Action::Press, _) => {
+                    paused = !paused;
+                }
+                glfw::WindowEvent::Key(Key::F3, _, Action::Press, _) => {
+                    show_perf_stats = !show_perf_stats;
+                }
+                glfw::WindowEvent::Key(Key::Enter, _, Action::Press, mods) => {
+                    if mods.contains(glfw::Modifiers::Alt) {
+                        toggle_fullscreen = true;
+                    }
+                }
+                glfw::WindowEvent::Key(Key::Escape, _, Action::Press, _) => {
+                    context.window.set_should_close(true);
+                }
+                glfw::WindowEvent::Key(Key::F5, _, Action::Press, _) => {
+                    controller.camera.save_to_camera_file();
+                }
+                glfw::WindowEvent::Key(Key::F9, _, Action::Press, _) => {
+                    if let Some(camera) = Camera::restore_from_camera_file() {
+                        controller.set_camera(&camera);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        if toggle_fullscreen {
+            let fullscreen = context.toggle_fullscreen();
+            logger.log(&format!("fullscreen: {}", fullscreen));
+            let (width, height) = context.window.get_framebuffer_size();
+            context.width = width as u32;
+            context.height = height as u32;
+        }
+
+        // control keys
+        controller.update(&context.window, elapsed_seconds);
+
+        // Put the stuff we've been drawing onto the display.
+        context.window.swap_buffers();
+        perf_stats.end_frame(perf_stats::duration_to_ms(frame_start.elapsed()));
+    }
+}
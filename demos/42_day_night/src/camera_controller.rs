@@ -0,0 +1,245 @@
+use glfw;
+use glfw::{Action, Key};
+
+use graphics_math as math;
+use math::{DVec3, Vec3, Versor};
+
+use camera::Camera;
+
+
+///
+/// The furthest the camera can look up or down, in degrees. Looking straight up
+/// or down would leave no heading to turn around.
+///
+pub const MAX_PITCH: f32 = 89.0;
+
+
+///
+/// The camera orientation for a heading and a look up or down, in degrees.
+///
+pub fn look_orientation(yaw: f32, pitch: f32) -> Versor {
+    let q_yaw = Versor::from_axis_deg(yaw, 0.0, 1.0, 0.0);
+    let q_pitch = Versor::from_axis_deg(pitch, 1.0, 0.0, 0.0);
+
+    q_yaw * q_pitch
+}
+
+///
+/// The keys that drive one camera controller, so that two controllers can share
+/// a keyboard.
+///
+#[derive(Copy, Clone, Debug)]
+pub struct ControlKeys {
+    pub forward: Key,
+    pub back: Key,
+    pub step_left: Key,
+    pub step_right: Key,
+    pub turn_left: Key,
+    pub turn_right: Key,
+    pub look_up: Key,
+    pub look_down: Key,
+}
+
+///
+/// Which way the camera is being pushed this frame. Each value is -1, 0, or 1,
+/// or anything in between for an analogue stick.
+///
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct ControlInput {
+    pub forward: f32,
+    pub right: f32,
+    pub turn_left: f32,
+    pub look_up: f32,
+}
+
+fn is_down(window: &glfw::Window, key: Key) -> bool {
+    match window.get_key(key) {
+        Action::Press | Action::Repeat => true,
+        Action::Release => false,
+    }
+}
+
+///
+/// Positive, negative, or no push, from a pair of keys working against each other.
+///
+fn axis(window: &glfw::Window, positive: Key, negative: Key) -> f32 {
+    let mut value = 0.0;
+    if is_down(window, positive) {
+        value += 1.0;
+    }
+    if is_down(window, negative) {
+        value -= 1.0;
+    }
+
+    value
+}
+
+impl ControlInput {
+    pub fn from_keys(window: &glfw::Window, keys: &ControlKeys) -> ControlInput {
+        ControlInput {
+            forward: axis(window, keys.forward, keys.back),
+            right: axis(window, keys.step_right, keys.step_left),
+            turn_left: axis(window, keys.turn_left, keys.turn_right),
+            look_up: axis(window, keys.look_up, keys.look_down),
+        }
+    }
+}
+
+///
+/// Walks a camera across the ground from its own set of keys: forwards, backwards,
+/// and sideways, turning, and looking up and down. The camera stays at the height
+/// it starts at, and moves along its heading whichever way it looks.
+///
+pub struct CameraController {
+    pub camera: Camera,
+    pub yaw: f32,
+    pub pitch: f32,
+    // in units per second
+    pub move_speed: f32,
+    // in degrees per second
+    pub turn_speed: f32,
+    keys: ControlKeys,
+}
+
+impl CameraController {
+    pub fn new(position: Vec3, yaw: f32, pitch: f32, fovy: f32, keys: ControlKeys) -> CameraController {
+        CameraController {
+            camera: Camera::new(math::dvec3(position), look_orientation(yaw, pitch), fovy),
+            yaw: yaw,
+            pitch: pitch,
+            move_speed: 4.0,
+            turn_speed: 90.0,
+            keys: keys,
+        }
+    }
+
+    ///
+    /// The direction the camera is heading across the ground.
+    ///
+    pub fn forward(&self) -> Vec3 {
+        let yaw_rad = self.yaw.to_radians();
+
+        math::vec3((-f32::sin(yaw_rad), 0.0, -f32::cos(yaw_rad)))
+    }
+
+    pub fn right(&self) -> Vec3 {
+        let yaw_rad = self.yaw.to_radians();
+
+        math::vec3((f32::cos(yaw_rad), 0.0, -f32::sin(yaw_rad)))
+    }
+
+    ///
+    /// Move and turn the camera by however much the input pushes it over the
+    /// elapsed time.
+    ///
+    pub fn apply(&mut self, input: &ControlInput, elapsed_seconds: f64) {
+        let turn = self.turn_speed * elapsed_seconds as f32;
+        self.yaw += input.turn_left * turn;
+        self.pitch = f32::min(f32::max(self.pitch + input.look_up * turn, -MAX_PITCH), MAX_PITCH);
+        self.camera.orientation = look_orientation(self.yaw, self.pitch);
+
+        let step = self.move_speed as f64 * elapsed_seconds;
+        let motion = math::dvec3(self.forward() * input.forward + self.right() * input.right);
+        self.camera.position += motion * step;
+    }
+
+    ///
+    /// Read this controller's keys and move the camera for one frame.
+    ///
+    pub fn update(&mut self, window: &glfw::Window, elapsed_seconds: f64) {
+        let input = ControlInput::from_keys(window, &self.keys);
+        self.apply(&input, elapsed_seconds);
+    }
+
+    pub fn position(&self) -> DVec3 {
+        self.camera.position
+    }
+
+    ///
+    /// Move the controller to a camera restored from a file. The heading and look
+    /// are worked back out from its orientation, so the keys carry on from where
+    /// it faces; any roll is dropped.
+    ///
+    pub fn set_camera(&mut self, camera: &Camera) {
+        self.yaw = camera.yaw_deg();
+        self.pitch = camera.pitch_deg().clamp(-MAX_PITCH, MAX_PITCH);
+        self.camera = Camera::new(camera.position, look_orientation(self.yaw, self.pitch), camera.fovy);
+    }
+}
+
+mod camera_controller_tests {
+    use super::{CameraController, ControlInput, ControlKeys, MAX_PITCH};
+    use glfw::Key;
+    use graphics_math as math;
+
+    fn keys() -> ControlKeys {
+        ControlKeys {
+            forward: Key::W, back: Key::S, step_left: Key::Q, step_right: Key::E,
+            turn_left: Key::A, turn_right: Key::D, look_up: Key::R, look_down: Key::F,
+        }
+    }
+
+    fn assert_near(a: f64, b: f64) {
+        assert!(f64::abs(a - b) < 1e-5, "{} is not {}", a, b);
+    }
+
+    #[test]
+    fn test_moving_forward_follows_the_heading() {
+        let mut controller = CameraController::new(math::vec3((0.0, 1.5, 0.0)), 0.0, 0.0, 67.0, keys());
+        let input = ControlInput { forward: 1.0, ..ControlInput::default() };
+
+        controller.apply(&input, 0.5);
+        assert_near(controller.position().v[2], -2.0);
+
+        controller.yaw = 90.0;
+        controller.apply(&input, 0.5);
+        assert_near(controller.position().v[0], -2.0);
+        assert_near(controller.position().v[2], -2.0);
+        assert_near(controller.position().v[1], 1.5);
+    }
+
+    #[test]
+    fn test_looking_down_does_not_sink_the_camera() {
+        let mut controller = CameraController::new(math::vec3((0.0, 1.5, 0.0)), 0.0, -45.0, 67.0, keys());
+        let input = ControlInput { forward: 1.0, right: 1.0, ..ControlInput::default() };
+
+        controller.apply(&input, 1.0);
+
+        assert_near(controller.position().v[0], 4.0);
+        assert_near(controller.position().v[1], 1.5);
+        assert_near(controller.position().v[2], -4.0);
+    }
+
+    #[test]
+    fn test_the_pitch_stops_short_of_straight_up_and_down() {
+        let mut controller = CameraController::new(math::vec3((0.0, 1.5, 0.0)), 0.0, 0.0, 67.0, keys());
+
+        controller.apply(&ControlInput { look_up: 1.0, ..ControlInput::default() }, 10.0);
+        assert_eq!(controller.pitch, MAX_PITCH);
+
+        controller.apply(&ControlInput { look_up: -1.0, ..ControlInput::default() }, 10.0);
+        assert_eq!(controller.pitch, -MAX_PITCH);
+    }
+
+    #[test]
+    fn test_turning_changes_the_heading() {
+        let mut controller = CameraController::new(math::vec3((0.0, 1.5, 0.0)), 0.0, 0.0, 67.0, keys());
+
+        controller.apply(&ControlInput { turn_left: 1.0, ..ControlInput::default() }, 0.5);
+
+        assert_eq!(controller.yaw, 45.0);
+    }
+
+    #[test]
+    fn test_a_restored_camera_keeps_its_heading_and_look() {
+        let saved = CameraController::new(math::vec3((3.0, 2.0, -1.0)), 30.0, -20.0, 55.0, keys());
+        let mut controller = CameraController::new(math::vec3((0.0, 1.5, 0.0)), 0.0, 0.0, 67.0, keys());
+
+        controller.set_camera(&saved.camera);
+
+        assert!(f32::abs(controller.yaw - 30.0) < 1e-3);
+        assert!(f32::abs(controller.pitch + 20.0) < 1e-3);
+        assert_eq!(controller.position(), saved.position());
+        assert_eq!(controller.camera.fovy, 55.0);
+    }
+}
@@ -0,0 +1,53 @@
+use gl;
+use gl::types::{GLint, GLuint};
+
+use colour::Colour;
+use gl_utils::uniform_location;
+use graphics_math::Vec3;
+
+
+///
+/// Light from so far away, like the sun's, that it falls on everything from the
+/// same direction. `direction` points towards the light, in world space. The
+/// ambient colour stands in for the light bounced around by the sky and the
+/// ground, which reaches surfaces facing away from the light too.
+///
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct DirectionalLight {
+    pub direction: Vec3,
+    pub colour: Colour,
+    pub ambient: Colour,
+}
+
+///
+/// The locations of the directional light's uniforms in a shader programme:
+/// `light_direction`, `light_colour`, and `ambient_colour`.
+///
+pub struct DirectionalLightUniforms {
+    direction_loc: GLint,
+    colour_loc: GLint,
+    ambient_loc: GLint,
+}
+
+impl DirectionalLightUniforms {
+    pub fn new(sp: GLuint) -> DirectionalLightUniforms {
+        DirectionalLightUniforms {
+            direction_loc: uniform_location(sp, "light_direction"),
+            colour_loc: uniform_location(sp, "light_colour"),
+            ambient_loc: uniform_location(sp, "ambient_colour"),
+        }
+    }
+
+    ///
+    /// Upload the light to the shader programme, leaving it in use.
+    ///
+    pub fn upload(&self, sp: GLuint, light: &DirectionalLight) {
+        let direction = light.direction.normalize();
+        unsafe {
+            gl::UseProgram(sp);
+            gl::Uniform3fv(self.direction_loc, 1, direction.v.as_ptr());
+            gl::Uniform3fv(self.colour_loc, 1, light.colour.rgb().as_ptr());
+            gl::Uniform3fv(self.ambient_loc, 1, light.ambient.rgb().as_ptr());
+        }
+    }
+}
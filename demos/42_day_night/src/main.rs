@@ -0,0 +1,337 @@
+extern crate gl;
+extern crate glfw;
+extern crate chrono;
+extern crate stb_image;
+#[cfg(feature = "glam")]
+extern crate glam;
+#[cfg(feature = "cgmath")]
+extern crate cgmath;
+
+#[macro_use]
+mod gl_utils;
+mod graphics_math;
+mod obj_parser;
+mod colour;
+mod assets;
+mod shader_include;
+mod spirv;
+mod program_cache;
+mod logger;
+mod overlay;
+mod streaming_buffer;
+mod camera;
+mod camera_controller;
+mod vertex_layout;
+mod gpu_timer;
+mod perf_stats;
+mod geometry;
+mod noise;
+mod terrain;
+mod fog;
+mod skybox;
+mod directional_light;
+mod time_of_day;
+
+
+use glfw::{Action, Context, Key};
+use gl::types::{GLsizeiptr, GLvoid};
+
+use std::mem;
+use std::process;
+use std::ptr;
+use std::time::Instant;
+
+use gl_utils::*;
+
+use graphics_math as math;
+
+use camera::Camera;
+use camera_controller::{CameraController, ControlKeys};
+use colour::Colour;
+use directional_light::DirectionalLightUniforms;
+use fog::{FogMode, FogSettings, FogUniforms};
+use gpu_timer::GpuTimer;
+use noise::Fbm;
+use overlay::Overlay;
+use perf_stats::{PerfStats, PERF_STATS_HISTORY};
+use skybox::{ProceduralSky, Skybox};
+use terrain::Heightmap;
+use time_of_day::TimeOfDay;
+use vertex_layout::VertexLayout;
+
+
+const GL_LOG_FILE: &str = "gl.log";
+const VERTEX_SHADER_FILE: &str = "src/terrain_vs.glsl";
+const FRAGMENT_SHADER_FILE: &str = "src/terrain_fs.glsl";
+
+// The terrain, made from fractal noise as in the terrain demo.
+const TERRAIN_SAMPLES: usize = 129;
+const TERRAIN_SPACING: f32 = 0.5;
+const TERRAIN_MAX_HEIGHT: f32 = 14.0;
+const TERRAIN_OCTAVES: u32 = 6;
+const TERRAIN_FREQUENCY: f32 = 0.04;
+// The camera never gets closer to the ground than this.
+const CAMERA_CLEARANCE: f32 = 1.5;
+
+// The day starts just before sunrise. Left to itself, a whole day goes by in
+// two minutes; holding , or . scrubs through it ten times as fast.
+const START_HOURS: f32 = 5.0;
+const HOURS_PER_SECOND: f32 = 24.0 / 120.0;
+const SCRUB_HOURS_PER_SECOND: f32 = 10.0 * HOURS_PER_SECOND;
+const FOG_DENSITY: f32 = 0.025;
+
+const OVERLAY_TEXT_COLOUR: Colour = Colour::WHITE;
+const OVERLAY_BACKGROUND_COLOUR: Colour = Colour { r: 0.0, g: 0.0, b: 0.0, a: 0.5 };
+
+///
+/// Queue up a label in the top-left corner of the viewport. Returns the bottom
+/// edge of the label.
+///
+fn label(overlay: &mut Overlay, text: &str) -> f32 {
+    let scale = 2.0;
+    let longest = text.lines().map(|line| line.len()).max().unwrap_or(0);
+    let width = (longest as f32 * overlay::GLYPH_ADVANCE + 4.0) * scale;
+    let height = (text.lines().count() as f32 * overlay::LINE_HEIGHT + 4.0) * scale;
+    overlay.rect(10.0, 10.0, width, height, OVERLAY_BACKGROUND_COLOUR);
+    overlay.text(10.0 + 2.0 * scale, 10.0 + 2.0 * scale, scale, OVERLAY_TEXT_COLOUR, text);
+
+    10.0 + height
+}
+
+fn main() {
+    let logger = restart_gl_log(GL_LOG_FILE);
+    // Start a GL context and O/S window using the GLFW helper library.
+    let mut context = match start_gl(&logger) {
+        Ok(val) => val,
+        Err(e) => {
+            eprintln!("Failed to Initialize OpenGL context. Got error:");
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+    };
+    // The framebuffer can be bigger than the window on high DPI screens.
+    context.window.set_framebuffer_size_polling(true);
+    let (width, height) = context.window.get_framebuffer_size();
+    context.width = width as u32;
+    context.height = height as u32;
+
+    unsafe {
+        // Enable depth testing.
+        gl::Enable(gl::DEPTH_TEST);
+        // Depth testing interprets a smaller value as closer to the eye.
+        gl::DepthFunc(gl::LESS);
+        // Cull face.
+        gl::Enable(gl::CULL_FACE);
+        // Cull back face.
+        gl::CullFace(gl::BACK);
+        // GL_CW for clockwise.
+        gl::FrontFace(gl::CCW);
+    }
+
+    /*------------------------------CREATE GEOMETRY------------------------------*/
+    let fbm = Fbm::new(TERRAIN_OCTAVES, TERRAIN_FREQUENCY);
+    let heightmap = Heightmap::from_noise(
+        &fbm, TERRAIN_SAMPLES, TERRAIN_SAMPLES, TERRAIN_SPACING, TERRAIN_MAX_HEIGHT
+    ).unwrap();
+    let mesh = heightmap.mesh();
+    let index_count = mesh.indices.len();
+
+    let vertex_count = mesh.positions.len() / 3;
+    let mut vao = 0;
+    unsafe {
+        gl::GenVertexArrays(1, &mut vao);
+    }
+    assert!(vao > 0);
+    VertexLayout::new().floats(0, 3).upload(vao, &mesh.positions, vertex_count);
+    VertexLayout::new().floats(1, 3).upload(vao, &mesh.normals, vertex_count);
+    VertexLayout::new().floats(2, 2).upload(vao, &mesh.uvs, vertex_count);
+
+    // the index buffer is part of the vertex array state, so it stays bound with it
+    let mut ibo = 0;
+    unsafe {
+        gl::BindVertexArray(vao);
+        gl::GenBuffers(1, &mut ibo);
+        gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, ibo);
+        gl::BufferData(
+            gl::ELEMENT_ARRAY_BUFFER, (index_count * mem::size_of::<u32>()) as GLsizeiptr,
+            mesh.indices.as_ptr() as *const GLvoid, gl::STATIC_DRAW
+        );
+    }
+    assert!(ibo > 0);
+
+    /*-------------------------------CREATE SHADERS------------------------------*/
+    let shader_programme = create_programme_from_files(&logger, VERTEX_SHADER_FILE, FRAGMENT_SHADER_FILE);
+    let view_mat_location = uniform_location(shader_programme, "view");
+    assert!(view_mat_location > -1);
+    let proj_mat_location = uniform_location(shader_programme, "proj");
+    assert!(proj_mat_location > -1);
+    let max_height_location = uniform_location(shader_programme, "max_height");
+    assert!(max_height_location > -1);
+    unsafe {
+        gl::UseProgram(shader_programme);
+        gl::Uniform1f(max_height_location, TERRAIN_MAX_HEIGHT);
+    }
+    let light_uniforms = DirectionalLightUniforms::new(shader_programme);
+
+    // the fog thickens with distance the same way all day, but takes its colour
+    // from the sky along the horizon.
+    let fog_uniforms = FogUniforms::new(shader_programme);
+    let mut fog = FogSettings::new();
+    fog.mode = FogMode::ExponentialSquared;
+    fog.density = FOG_DENSITY;
+    fog_uniforms.upload(shader_programme, &fog);
+
+    /*--------------------------------CREATE SKY---------------------------------*/
+    let mut time_of_day = TimeOfDay::new(START_HOURS);
+    let base_sky = ProceduralSky::new(time_of_day.sun_direction());
+    let mut skybox = Skybox::procedural(&logger, base_sky);
+
+    /*-------------------------------CREATE CAMERA-------------------------------*/
+    let start_z = 0.4 * heightmap.depth();
+    let start_pos = math::vec3((0.0, heightmap.height_at(0.0, start_z) + 4.0, start_z));
+    let mut controller = CameraController::new(start_pos, 0.0, -5.0, 67.0, ControlKeys {
+        forward: Key::W, back: Key::S, step_left: Key::A, step_right: Key::D,
+        turn_left: Key::Left, turn_right: Key::Right, look_up: Key::Up, look_down: Key::Down,
+    });
+    logger.log("keys: W, A, S, and D move, and the arrow keys turn and look up and down;");
+    logger.log("      hold , and . to wind the time of day back and on, Space stops and starts the clock,");
+    logger.log("      and F3 toggles the performance HUD;");
+    logger.log("      F5 saves the camera to camera.txt, and F9 restores it");
+
+    // input variables
+    let near = 0.1;                                  // clipping plane
+    let far = 200.0;                                 // clipping plane
+    let mut paused = false;
+
+    // the time of day, drawn in the corner, with the frame times underneath. F3
+    // toggles the times on and off.
+    let mut overlay = Overlay::new(&logger);
+    let mut perf_stats = PerfStats::new(PERF_STATS_HISTORY);
+    let mut show_perf_stats = true;
+    let mut sky_timer = GpuTimer::new();
+    let mut terrain_timer = GpuTimer::new();
+
+    while !context.window.should_close() {
+        let frame_start = Instant::now();
+        let current_seconds = context.glfw.get_time();
+        let elapsed_seconds = current_seconds - context.elapsed_time_seconds;
+        context.elapsed_time_seconds = current_seconds;
+
+        update_fps_counter(&mut context);
+
+        // Move the clock on, and light the scene for the new time of day.
+        let mut hours_per_second = if paused { 0.0 } else { HOURS_PER_SECOND };
+        if context.window.get_key(Key::Comma) != Action::Release {
+            hours_per_second -= SCRUB_HOURS_PER_SECOND;
+        }
+        if context.window.get_key(Key::Period) != Action::Release {
+            hours_per_second += SCRUB_HOURS_PER_SECOND;
+        }
+        time_of_day.advance(hours_per_second * elapsed_seconds as f32);
+
+        if let Some(sky) = skybox.procedural_sky_mut() {
+            *sky = time_of_day.sky(&base_sky);
+        }
+        let fog_colour = time_of_day.fog_colour(&base_sky);
+        light_uniforms.upload(shader_programme, &time_of_day.light());
+        fog_uniforms.upload_colour(shader_programme, fog_colour.rgb());
+
+        let aspect = context.width as f32 / u32::max(context.height, 1) as f32;
+        let proj_mat = controller.camera.proj_mat(aspect, near, far);
+        let view_mat = controller.camera.view_mat();
+
+        unsafe {
+            gl::Viewport(0, 0, context.width as i32, context.height as i32);
+            gl::ClearColor(fog_colour.r, fog_colour.g, fog_colour.b, 1.0);
+            gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
+        }
+
+        // The sky goes first, behind everything. It is drawn from inside its cube.
+        sky_timer.begin();
+        unsafe {
+            gl::Disable(gl::CULL_FACE);
+        }
+        skybox.draw(&view_mat, &proj_mat);
+        unsafe {
+            gl::Enable(gl::CULL_FACE);
+        }
+        perf_stats.count_draw(12);
+        sky_timer.end();
+
+        terrain_timer.begin();
+        unsafe {
+            gl::UseProgram(shader_programme);
+            gl::UniformMatrix4fv(view_mat_location, 1, gl::FALSE, view_mat.as_ptr());
+            gl::UniformMatrix4fv(proj_mat_location, 1, gl::FALSE, proj_mat.as_ptr());
+            gl::BindVertexArray(vao);
+            gl::DrawElements(gl::TRIANGLES, index_count as i32, gl::UNSIGNED_INT, ptr::null());
+        }
+        perf_stats.count_draw(index_count / 3);
+        terrain_timer.end();
+
+        perf_stats.set_gpu_pass_ms("sky", sky_timer.elapsed_ms());
+        perf_stats.set_gpu_pass_ms("terrain", terrain_timer.elapsed_ms());
+        let label_text = format!("time {}{}", time_of_day.clock(), if paused { ", clock stopped" } else { "" });
+        let label_bottom = label(&mut overlay, &label_text);
+        if show_perf_stats {
+            perf_stats.draw_hud(&mut overlay, 10.0, label_bottom + 6.0);
+        }
+        overlay.draw(context.width, context.height);
+
+        context.glfw.poll_events();
+
+        let mut toggle_fullscreen = false;
+        for (_, event) in glfw::flush_messages(&context.events) {
+            match event {
+                glfw::WindowEvent::FramebufferSize(width, height) => {
+                    context.width = width as u32;
+                    context.height = height as u32;
+                }
+                glfw::WindowEvent::Key(Key::Space, _, Action::Press, _) => {
+                    paused = !paused;
+                    logger.log(&format!("time {}, clock {}", time_of_day.clock(), if paused { "stopped" } else { "running" }));
+                }
+                glfw::WindowEvent::Key(Key::F3, _, Action::Press, _) => {
+                    show_perf_stats = !show_perf_stats;
+                }
+                glfw::WindowEvent::Key(Key::Enter, _, Action::Press, mods) => {
+                    if mods.contains(glfw::Modifiers::Alt) {
+                        toggle_fullscreen = true;
+                    }
+                }
+                glfw::WindowEvent::Key(Key::Escape, _, Action::Press, _) => {
+                    context.window.set_should_close(true);
+                }
+                glfw::WindowEvent::Key(Key::F5, _, Action::Press, _) => {
+                    controller.camera.save_to_camera_file();
+                }
+                glfw::WindowEvent::Key(Key::F9, _, Action::Press, _) => {
+                    if let Some(camera) = Camera::restore_from_camera_file() {
+                        controller.set_camera(&camera);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        if toggle_fullscreen {
+            let fullscreen = context.toggle_fullscreen();
+            logger.log(&format!("fullscreen: {}", fullscreen));
+            let (width, height) = context.window.get_framebuffer_size();
+            context.width = width as u32;
+            context.height = height as u32;
+        }
+
+        // control keys
+        controller.update(&context.window, elapsed_seconds);
+
+        // keep the camera from sinking into the hills
+        let position = math::vec3(controller.camera.position);
+        let ground = heightmap.height_at(position.v[0], position.v[2]);
+        controller.camera.position.v[1] = f64::max(controller.camera.position.v[1], (ground + CAMERA_CLEARANCE) as f64);
+
+        // Put the stuff we've been drawing onto the display.
+        context.window.swap_buffers();
+        perf_stats.end_frame(perf_stats::duration_to_ms(frame_start.elapsed()));
+    }
+}
@@ -0,0 +1,1118 @@
+use colour::Colour;
+
+use std::fs;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+
+///
+/// A `MaterialGroup` is a run of consecutive vertices in a mesh that all use
+/// the same material, so the whole run can be drawn with a single draw call.
+/// An empty material name means the faces came before any `usemtl` statement.
+///
+#[derive(Clone, Debug, PartialEq)]
+pub struct MaterialGroup {
+    pub material: String,
+    pub first: usize,
+    pub count: usize,
+}
+
+///
+/// An `ObjMesh` is a model space representation of a 3D geometric figure.
+/// You typically generate one from parsing a Wavefront *.obj file into
+/// an `ObjMesh`.
+///
+#[derive(Clone, Debug, PartialEq)]
+pub struct ObjMesh {
+    pub point_count: usize,
+    pub points: Vec<f32>,
+    pub tex_coords: Vec<f32>,
+    pub normals: Vec<f32>,
+    pub material_libs: Vec<String>,
+    pub groups: Vec<MaterialGroup>,
+}
+
+impl ObjMesh {
+    ///
+    /// Generate a new mesh object.
+    ///
+    fn new(
+        points: Vec<f32>, tex_coords: Vec<f32>, normals: Vec<f32>,
+        material_libs: Vec<String>, groups: Vec<MaterialGroup>) -> ObjMesh {
+
+        ObjMesh {
+            point_count: points.len() / 3,
+            points: points,
+            tex_coords: tex_coords,
+            normals: normals,
+            material_libs: material_libs,
+            groups: groups,
+        }
+    }
+
+    ///
+    /// Present the points map as an array slice. This function can be used
+    /// to present the internal array buffer to OpenGL or another Graphics
+    /// system for rendering.
+    ///
+    #[inline]
+    fn points(&self) -> &[f32] {
+        &self.points
+    }
+
+    ///
+    /// Present the texture map as an array slice. This function can be used
+    /// to present the internal array buffer to OpenGL or another Graphics
+    /// system for rendering.
+    ///
+    #[inline]
+    fn tex_coords(&self) -> &[f32] {
+        &self.tex_coords
+    }
+
+    ///
+    /// Present the normal vector map as an array slice. This function can be used
+    /// to present the internal array buffer to OpenGL or another Graphics
+    /// system for rendering.
+    ///
+    #[inline]
+    fn normals(&self) -> &[f32] {
+        &self.normals
+    }
+}
+
+struct UnsortedVertexData {
+    vp: Vec<f32>,
+    vt: Vec<f32>,
+    vn: Vec<f32>,
+}
+
+struct SortedVertexData {
+    points: Vec<f32>,
+    tex_coords: Vec<f32>,
+    normals: Vec<f32>,
+}
+
+impl SortedVertexData {
+    fn reserve(&mut self, triangles: usize, has_tex_coords: bool) {
+        self.points.reserve(9 * triangles);
+        if has_tex_coords {
+            self.tex_coords.reserve(6 * triangles);
+        }
+        self.normals.reserve(9 * triangles);
+    }
+}
+
+fn is_separator(byte: u8) -> bool {
+    match byte {
+        b' ' | b'\t' | b'\r' | b'\n' | b'\\' => true,
+        _ => false,
+    }
+}
+
+///
+/// Reads the lines of a run of whole lines of an *.obj file where they lie in
+/// memory, a word at a time. Words are separated by spaces, tabs, line endings,
+/// or backslashes, and are slices of the run, so reading them allocates nothing.
+///
+struct LineScanner<'a> {
+    bytes: &'a [u8],
+    position: usize,
+    line_start: usize,
+}
+
+impl<'a> LineScanner<'a> {
+    fn new(bytes: &'a [u8]) -> LineScanner<'a> {
+        LineScanner { bytes: bytes, position: 0, line_start: 0 }
+    }
+
+    ///
+    /// Skip the spaces between words, stopping at the end of the line.
+    ///
+    fn skip_spaces(&mut self) {
+        let rest = &self.bytes[self.position..];
+        self.position += rest.iter().position(|&byte| byte == b'\n' || !is_separator(byte)).unwrap_or(rest.len());
+    }
+
+    fn at_line_end(&self) -> bool {
+        self.position == self.bytes.len() || self.bytes[self.position] == b'\n'
+    }
+
+    ///
+    /// The next word on the line, or an empty slice at the end of the line.
+    ///
+    fn word(&mut self) -> &'a [u8] {
+        self.skip_spaces();
+        let rest = &self.bytes[self.position..];
+        let length = rest.iter().position(|&byte| is_separator(byte)).unwrap_or(rest.len());
+        self.position += length;
+
+        &rest[..length]
+    }
+
+    ///
+    /// The next word on the line read as a number, or the word itself if it is not
+    /// one. Most numbers are worked out as their digits are read, and only the rest
+    /// are split off as a word first.
+    ///
+    fn number(&mut self) -> Result<f32, &'a [u8]> {
+        self.skip_spaces();
+        let rest = &self.bytes[self.position..];
+        if let Some((value, length)) = parse_f32_prefix(rest) {
+            if rest.get(length).map_or(true, |&byte| is_separator(byte)) {
+                self.position += length;
+                return Ok(value);
+            }
+        }
+        let word = self.word();
+
+        match parse_f32(word) {
+            Some(value) => Ok(value),
+            None => Err(word),
+        }
+    }
+
+    ///
+    /// Move on to the start of the next line, skipping whatever is left of this
+    /// one. Returns `false` when there are no more lines.
+    ///
+    fn next_line(&mut self) -> bool {
+        while self.position < self.bytes.len() && self.bytes[self.position] != b'\n' {
+            self.position += 1;
+        }
+        if self.position < self.bytes.len() {
+            self.position += 1;
+        }
+        self.line_start = self.position;
+
+        self.position < self.bytes.len()
+    }
+
+    ///
+    /// The line being read, for error messages.
+    ///
+    fn line(&self) -> String {
+        let rest = &self.bytes[self.line_start..];
+        let end = rest.iter().position(|&byte| byte == b'\n').unwrap_or(rest.len());
+
+        String::from(String::from_utf8_lossy(&rest[..end]).trim_end())
+    }
+
+    ///
+    /// Read one corner of a face, written either `vp/vt/vn` or `vp//vn`, into its
+    /// position, texture coordinate, and normal indices. Indices in the file count
+    /// from one, and the results count from zero. The digits are worked out as
+    /// they are read, rather than the word being split up first. Indices too large
+    /// to hold saturate, and fail the range check later on.
+    ///
+    fn face_corner(&mut self) -> Option<(usize, Option<usize>, usize)> {
+        self.skip_spaces();
+        let vp = self.index();
+        if !self.skip_slash() {
+            return None;
+        }
+        let vt = self.index();
+        if !self.skip_slash() {
+            return None;
+        }
+        let vn = self.index();
+        if !self.at_word_end() {
+            return None;
+        }
+
+        match (vp, vt, vn) {
+            (Some(vp), Some(vt), Some(vn)) if vp > 0 && vt > 0 && vn > 0 => Some((vp - 1, Some(vt - 1), vn - 1)),
+            (Some(vp), None, Some(vn)) if vp > 0 && vn > 0 => Some((vp - 1, None, vn - 1)),
+            _ => None,
+        }
+    }
+
+    ///
+    /// Read the digits of an index, or `None` if there are none. Indices too large
+    /// to hold saturate.
+    ///
+    fn index(&mut self) -> Option<usize> {
+        let start = self.position;
+        let mut index: usize = 0;
+        while self.position < self.bytes.len() {
+            let digit = self.bytes[self.position].wrapping_sub(b'0');
+            if digit >= 10 {
+                break;
+            }
+            index = index.saturating_mul(10).saturating_add(digit as usize);
+            self.position += 1;
+        }
+
+        if self.position == start { None } else { Some(index) }
+    }
+
+    fn skip_slash(&mut self) -> bool {
+        if self.position < self.bytes.len() && self.bytes[self.position] == b'/' {
+            self.position += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn at_word_end(&self) -> bool {
+        self.position == self.bytes.len() || is_separator(self.bytes[self.position])
+    }
+}
+
+///
+/// The powers of ten an `f64` holds exactly.
+///
+const POWERS_OF_TEN: [f64; 23] = [
+    1e0, 1e1, 1e2, 1e3, 1e4, 1e5, 1e6, 1e7, 1e8, 1e9, 1e10, 1e11,
+    1e12, 1e13, 1e14, 1e15, 1e16, 1e17, 1e18, 1e19, 1e20, 1e21, 1e22,
+];
+
+///
+/// Read the number at the start of `bytes`, such as `-1.25`, `.5`, or `3e-2`, to
+/// the same `f32` as `str::parse` gives, along with how many bytes it took up.
+/// The digits are gathered into an integer and scaled by a power of ten in one
+/// exactly rounded `f64` operation. Returns `None` for numbers with too many
+/// digits, too large an exponent, or an `f64` result too close to halfway between
+/// two `f32`s to round again safely, which `str::parse` has to read instead.
+///
+fn parse_f32_prefix(bytes: &[u8]) -> Option<(f32, usize)> {
+    let mut i = 0;
+    let negative = match bytes.first() {
+        Some(&b'-') => { i += 1; true }
+        Some(&b'+') => { i += 1; false }
+        _ => false,
+    };
+    let mut mantissa: u64 = 0;
+    let mut exponent: i32 = 0;
+    let digits_start = i;
+    while i < bytes.len() && bytes[i].is_ascii_digit() {
+        mantissa = mantissa.wrapping_mul(10).wrapping_add((bytes[i] - b'0') as u64);
+        i += 1;
+    }
+    let mut digits = i - digits_start;
+    if i < bytes.len() && bytes[i] == b'.' {
+        i += 1;
+        let fraction_start = i;
+        while i < bytes.len() && bytes[i].is_ascii_digit() {
+            mantissa = mantissa.wrapping_mul(10).wrapping_add((bytes[i] - b'0') as u64);
+            i += 1;
+        }
+        exponent = -((i - fraction_start) as i32);
+        digits += i - fraction_start;
+    }
+    // Nineteen digits always fit in a u64, so only longer numbers can have wrapped.
+    if digits == 0 || digits > 19 {
+        return None;
+    }
+    if i < bytes.len() && (bytes[i] == b'e' || bytes[i] == b'E') {
+        i += 1;
+        let exponent_negative = match bytes.get(i) {
+            Some(&b'-') => { i += 1; true }
+            Some(&b'+') => { i += 1; false }
+            _ => false,
+        };
+        let mut written_exponent: i32 = 0;
+        let exponent_start = i;
+        while i < bytes.len() && bytes[i].is_ascii_digit() {
+            written_exponent = written_exponent.saturating_mul(10).saturating_add((bytes[i] - b'0') as i32);
+            i += 1;
+        }
+        if i == exponent_start {
+            return None;
+        }
+        exponent = exponent.saturating_add(if exponent_negative { -written_exponent } else { written_exponent });
+    }
+
+    if mantissa >= 1 << 53 || exponent.abs() > 22 {
+        return None;
+    }
+    let value = if exponent >= 0 {
+        mantissa as f64 * POWERS_OF_TEN[exponent as usize]
+    } else {
+        mantissa as f64 / POWERS_OF_TEN[-exponent as usize]
+    };
+    // An f32 keeps the top 23 of the f64's 52 fraction bits. If the 29 it drops are
+    // within one of halfway, the f64 may already have been rounded onto or off the
+    // halfway point, and rounding again could land on the wrong f32.
+    let dropped_bits = value.to_bits() & ((1 << 29) - 1);
+    let near_halfway = (dropped_bits as i64 - (1 << 28)).abs() <= 1;
+    if near_halfway || (value != 0.0 && value < ::std::f32::MIN_POSITIVE as f64) || value > ::std::f32::MAX as f64 {
+        return None;
+    }
+
+    Some((if negative { -(value as f32) } else { value as f32 }, i))
+}
+
+///
+/// Parse a whole word as a number, to the same `f32` as `str::parse` gives.
+///
+fn parse_f32(word: &[u8]) -> Option<f32> {
+    match parse_f32_prefix(word) {
+        Some((value, length)) if length == word.len() => Some(value),
+        _ => ::std::str::from_utf8(word).ok().and_then(|word| word.parse::<f32>().ok()),
+    }
+}
+
+///
+/// Parse the numbers after the keyword of a vertex line into `values`. Any numbers
+/// beyond those, such as an optional w coordinate, are ignored.
+///
+fn parse_floats(line: &mut LineScanner, values: &mut [f32]) -> Result<(), String> {
+    for i in 0..values.len() {
+        values[i] = match line.number() {
+            Ok(value) => value,
+            Err(word) if word.is_empty() => return Err(format!(
+                "ERROR: expected {} numbers in mesh line: {}", values.len(), line.line()
+            )),
+            Err(word) => return Err(format!(
+                "ERROR: invalid number {} in mesh line: {}", String::from_utf8_lossy(word), line.line()
+            )),
+        };
+    }
+
+    Ok(())
+}
+
+fn parse_face(
+    line: &mut LineScanner, unsorted_vtn: &UnsortedVertexData, sorted_vtn: &mut SortedVertexData) -> Result<(), String> {
+
+    let mut corners = [(0, None, 0); 3];
+    for j in 0..4 {
+        line.skip_spaces();
+        if line.at_line_end() != (j == 3) {
+            return Err(format!(
+                "ERROR: file contains quads or does not match v vp/vt/vn layout - 
+                 make sure exported mesh is triangulated and contains vertex points, 
+                 texture coordinates, and normals"
+            ));
+        }
+        if j == 3 {
+            break;
+        }
+        corners[j] = match line.face_corner() {
+            Some(corner) => corner,
+            None => return Err(format!(
+                "ERROR: This file contains a face element that is neither
+                 a vp/vt/vn index or a vp//vn index. Got line \"{}\"",
+                 line.line()
+            )),
+        };
+    }
+    let has_tex_coords = corners[0].1.is_some();
+    if corners.iter().any(|corner| corner.1.is_some() != has_tex_coords) {
+        return Err(format!("ERROR: face mixes vp/vt/vn and vp//vn indices: \"{}\"", line.line()));
+    }
+
+    for &(vp, vt, vn) in corners.iter() {
+        if 3 * vp >= unsorted_vtn.vp.len() {
+            return Err(format!("ERROR: invalid vertex position index in face"));
+        }
+        if let Some(vt) = vt {
+            if 2 * vt >= unsorted_vtn.vt.len() {
+                return Err(format!("ERROR: invalid texture coord index {} in face.", vt + 1));
+            }
+        }
+        if 3 * vn >= unsorted_vtn.vn.len() {
+            return Err(format!("ERROR: invalid vertex normal index in face"));
+        }
+    }
+
+    // Gather the face's three corners first, so each array only grows once a face.
+    let mut points = [0.0; 9];
+    let mut tex_coords = [0.0; 6];
+    let mut normals = [0.0; 9];
+    for (j, &(vp, vt, vn)) in corners.iter().enumerate() {
+        points[3 * j..3 * j + 3].copy_from_slice(&unsorted_vtn.vp[3 * vp..3 * vp + 3]);
+        if let Some(vt) = vt {
+            tex_coords[2 * j..2 * j + 2].copy_from_slice(&unsorted_vtn.vt[2 * vt..2 * vt + 2]);
+        }
+        normals[3 * j..3 * j + 3].copy_from_slice(&unsorted_vtn.vn[3 * vn..3 * vn + 3]);
+    }
+    sorted_vtn.points.extend_from_slice(&points);
+    if has_tex_coords {
+        sorted_vtn.tex_coords.extend_from_slice(&tex_coords);
+    }
+    sorted_vtn.normals.extend_from_slice(&normals);
+
+    Ok(())
+}
+
+///
+/// What has been read of a mesh so far, a line at a time.
+///
+struct ObjParser {
+    unsorted_vtn: UnsortedVertexData,
+    sorted_vtn: SortedVertexData,
+    material_libs: Vec<String>,
+    groups: Vec<MaterialGroup>,
+    current_material: String,
+    // whether the next face may start a new group, after a `usemtl` or at the start
+    material_changed: bool,
+}
+
+impl ObjParser {
+    fn new() -> ObjParser {
+        ObjParser {
+            unsorted_vtn: UnsortedVertexData { vp: vec![], vt: vec![], vn: vec![] },
+            sorted_vtn: SortedVertexData { points: vec![], tex_coords: vec![], normals: vec![] },
+            material_libs: vec![],
+            groups: vec![],
+            current_material: String::new(),
+            material_changed: true,
+        }
+    }
+
+    fn parse_line(&mut self, line: &mut LineScanner) -> Result<(), String> {
+        match line.word() {
+            b"v" => {
+                // Vertex point.
+                let mut point = [0.0; 3];
+                if let Err(e) = parse_floats(line, &mut point) {
+                    return Err(e);
+                }
+                self.unsorted_vtn.vp.extend_from_slice(&point);
+            }
+            b"vt" => {
+                // Vertex texture coordinate.
+                let mut tex_coord = [0.0; 2];
+                if let Err(e) = parse_floats(line, &mut tex_coord) {
+                    return Err(e);
+                }
+                self.unsorted_vtn.vt.extend_from_slice(&tex_coord);
+            }
+            b"vn" => {
+                // Vertex normal coordinate.
+                let mut normal = [0.0; 3];
+                if let Err(e) = parse_floats(line, &mut normal) {
+                    return Err(e);
+                }
+                self.unsorted_vtn.vn.extend_from_slice(&normal);
+            }
+            b"f" => {
+                // Face line. Closed meshes have about twice as many triangles as
+                // vertices, so that many are made room for once the first face shows
+                // up, rather than the arrays doubling their way up to it.
+                if self.sorted_vtn.points.is_empty() {
+                    self.sorted_vtn.reserve(2 * self.unsorted_vtn.vp.len() / 3, !self.unsorted_vtn.vt.is_empty());
+                }
+                if let Err(e) = parse_face(line, &self.unsorted_vtn, &mut self.sorted_vtn) {
+                    return Err(e);
+                }
+
+                // Faces with the same material as the one before extend its group. The
+                // names are only compared when a `usemtl` might have changed it.
+                if self.material_changed {
+                    let same_material = match self.groups.last() {
+                        Some(group) => group.material == self.current_material,
+                        None => false,
+                    };
+                    if !same_material {
+                        let first = self.sorted_vtn.points.len() / 3 - 3;
+                        self.groups.push(MaterialGroup { material: self.current_material.clone(), first: first, count: 0 });
+                    }
+                    self.material_changed = false;
+                }
+                if let Some(group) = self.groups.last_mut() {
+                    group.count += 3;
+                }
+            }
+            b"mtllib" => {
+                // Material library line.
+                loop {
+                    let file_name = line.word();
+                    if file_name.is_empty() {
+                        break;
+                    }
+                    self.material_libs.push(String::from_utf8_lossy(file_name).into_owned());
+                }
+            }
+            b"usemtl" => {
+                // Material selection line. It applies to every face up to the next one.
+                let name = line.word();
+                if name.is_empty() {
+                    return Err(format!("ERROR: usemtl without a material name: {}", line.line()));
+                }
+                self.current_material = String::from_utf8_lossy(name).into_owned();
+                self.material_changed = true;
+            }
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    ///
+    /// Parse a run of whole lines.
+    ///
+    fn parse_lines(&mut self, bytes: &[u8]) -> Result<(), String> {
+        let mut line = LineScanner::new(bytes);
+        loop {
+            if let Err(e) = self.parse_line(&mut line) {
+                return Err(e);
+            }
+            if !line.next_line() {
+                return Ok(());
+            }
+        }
+    }
+
+    fn into_mesh(self) -> ObjMesh {
+        let sorted_vtn = self.sorted_vtn;
+
+        ObjMesh::new(sorted_vtn.points, sorted_vtn.tex_coords, sorted_vtn.normals, self.material_libs, self.groups)
+    }
+}
+
+///
+/// Parse a Wavefront *.obj file into an unindexed triangle mesh in one pass. The
+/// whole lines in the reader's buffer are read where they lie, and the numbers
+/// worked out as their digits go by. Only a line that runs over the end of the
+/// buffer is copied out, so the only allocations are for the mesh itself. There
+/// is no counting pass ahead of this one: the output arrays are sized from the
+/// number of vertices read by the first face, and grow from there if need be.
+///
+pub fn load_obj_mesh<T: BufRead>(reader: &mut T) -> Result<ObjMesh, String> {
+    let mut parser = ObjParser::new();
+    let mut partial_line = vec![];
+    loop {
+        let consumed = {
+            let buffer = match reader.fill_buf() {
+                Ok(buffer) => buffer,
+                Err(e) => return Err(format!("ERROR: could not read mesh file: {}", e)),
+            };
+            if buffer.is_empty() {
+                break;
+            }
+
+            // Finish the line the last buffer ended in the middle of.
+            let mut start = 0;
+            if !partial_line.is_empty() {
+                start = match buffer.iter().position(|&byte| byte == b'\n') {
+                    Some(end) => end + 1,
+                    None => buffer.len(),
+                };
+                partial_line.extend_from_slice(&buffer[..start]);
+                if partial_line.last() == Some(&b'\n') {
+                    if let Err(e) = parser.parse_lines(&partial_line) {
+                        return Err(e);
+                    }
+                    partial_line.clear();
+                }
+            }
+            let rest = &buffer[start..];
+            let whole_lines = match rest.iter().rposition(|&byte| byte == b'\n') {
+                Some(last) => last + 1,
+                None => 0,
+            };
+            if whole_lines > 0 {
+                if let Err(e) = parser.parse_lines(&rest[..whole_lines]) {
+                    return Err(e);
+                }
+            }
+            partial_line.extend_from_slice(&rest[whole_lines..]);
+
+            buffer.len()
+        };
+        reader.consume(consumed);
+    }
+    // The last line need not end with a newline.
+    if let Err(e) = parser.parse_lines(&partial_line) {
+        return Err(e);
+    }
+
+    Ok(parser.into_mesh())
+}
+
+pub fn load_obj_file(file_name: &str) -> Result<ObjMesh, String> {
+    let file = match File::open(file_name) {
+        Ok(handle) => handle,
+        Err(_) => {
+            return Err(format!("ERROR: file not found: {}", file_name));
+        }
+    };
+
+    let mut reader = BufReader::new(file);
+    load_obj_mesh(&mut reader)
+}
+
+///
+/// A `Material` describes how the surface of a mesh reflects light, as read
+/// from a Wavefront *.mtl file. The texture maps are file names; it is up to
+/// the renderer to load them.
+///
+#[derive(Clone, Debug, PartialEq)]
+pub struct Material {
+    pub name: String,
+    pub ka: Colour,
+    pub kd: Colour,
+    pub ks: Colour,
+    pub shininess: f32,
+    pub diffuse_map: Option<String>,
+    pub specular_map: Option<String>,
+    pub normal_map: Option<String>,
+    pub height_map: Option<String>,
+}
+
+impl Material {
+    ///
+    /// Generate a new material with plain white surface properties.
+    ///
+    pub fn new(name: &str) -> Material {
+        Material {
+            name: String::from(name),
+            ka: Colour::WHITE,
+            kd: Colour::new(0.8, 0.8, 0.8),
+            ks: Colour::WHITE,
+            shininess: 100.0,
+            diffuse_map: None,
+            specular_map: None,
+            normal_map: None,
+            height_map: None,
+        }
+    }
+}
+
+impl Default for Material {
+    fn default() -> Material {
+        Material::new("default")
+    }
+}
+
+fn parse_mtl_colour(words: &[&str], line: &str) -> Result<Colour, String> {
+    if words.len() != 4 {
+        return Err(format!("ERROR: expected three colour components in material line: {}", line));
+    }
+
+    let mut colour = [0.0; 3];
+    for i in 0..3 {
+        colour[i] = match words[i + 1].parse::<f32>() {
+            Ok(value) => value,
+            Err(_) => return Err(format!("ERROR: invalid number {} in material line: {}", words[i + 1], line)),
+        };
+    }
+
+    Ok(Colour::from(colour))
+}
+
+fn parse_mtl_map(words: &[&str], line: &str) -> Result<String, String> {
+    // Texture map statements can carry options such as `-bm 1.0` before the
+    // file name, so the file name is always the last word.
+    if words.len() < 2 {
+        return Err(format!("ERROR: missing file name in material line: {}", line));
+    }
+
+    Ok(String::from(words[words.len() - 1]))
+}
+
+///
+/// Parse the materials in a Wavefront *.mtl file. Statements the renderer has
+/// no use for, such as transparency and illumination models, are skipped.
+///
+pub fn load_mtl<T: BufRead>(reader: &mut T) -> Result<Vec<Material>, String> {
+    let mut materials: Vec<Material> = vec![];
+    for line in reader.lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(e) => return Err(format!("ERROR: could not read material file: {}", e)),
+        };
+        let words: Vec<&str> = line.split_whitespace().collect();
+        if words.is_empty() || words[0].starts_with('#') {
+            continue;
+        }
+
+        if words[0] == "newmtl" {
+            if words.len() != 2 {
+                return Err(format!("ERROR: invalid material name declaration: {}", line));
+            }
+            materials.push(Material::new(words[1]));
+            continue;
+        }
+
+        let material = match materials.last_mut() {
+            Some(material) => material,
+            None => return Err(format!("ERROR: material statement before any newmtl: {}", line)),
+        };
+        let result = match words[0] {
+            "Ka" => parse_mtl_colour(&words, &line).map(|colour| material.ka = colour),
+            "Kd" => parse_mtl_colour(&words, &line).map(|colour| material.kd = colour),
+            "Ks" => parse_mtl_colour(&words, &line).map(|colour| material.ks = colour),
+            "Ns" => match words.get(1).map(|word| word.parse::<f32>()) {
+                Some(Ok(value)) => Ok(material.shininess = value),
+                _ => Err(format!("ERROR: invalid shininess in material line: {}", line)),
+            },
+            "map_Kd" => parse_mtl_map(&words, &line).map(|map| material.diffuse_map = Some(map)),
+            "map_Ks" => parse_mtl_map(&words, &line).map(|map| material.specular_map = Some(map)),
+            "map_Bump" | "map_bump" | "bump" | "norm" => {
+                parse_mtl_map(&words, &line).map(|map| material.normal_map = Some(map))
+            }
+            "disp" | "map_disp" => parse_mtl_map(&words, &line).map(|map| material.height_map = Some(map)),
+            _ => Ok(()),
+        };
+        if let Err(e) = result {
+            return Err(e);
+        }
+    }
+
+    Ok(materials)
+}
+
+///
+/// Load the materials in a Wavefront *.mtl file. Texture map file names are
+/// relative to the material file, so they are returned joined onto its directory.
+///
+pub fn load_mtl_file(file_name: &str) -> Result<Vec<Material>, String> {
+    let file = match File::open(file_name) {
+        Ok(handle) => handle,
+        Err(_) => {
+            return Err(format!("ERROR: file not found: {}", file_name));
+        }
+    };
+
+    let mut reader = BufReader::new(file);
+    let mut materials = match load_mtl(&mut reader) {
+        Ok(materials) => materials,
+        Err(e) => return Err(format!("ERROR: parsing material file {}\n{}", file_name, e)),
+    };
+    let base_dir = Path::new(file_name).parent().unwrap_or(Path::new(""));
+    let resolve = |map: &mut Option<String>| {
+        if let Some(ref mut map) = *map {
+            *map = String::from(base_dir.join(&map).to_string_lossy());
+        }
+    };
+    for material in materials.iter_mut() {
+        resolve(&mut material.diffuse_map);
+        resolve(&mut material.specular_map);
+        resolve(&mut material.normal_map);
+        resolve(&mut material.height_map);
+    }
+
+    Ok(materials)
+}
+
+///
+/// The file name of a material library named by a `mtllib` line, which is
+/// relative to the *.obj file that names it.
+///
+fn material_lib_file_name(obj_file_name: &str, material_lib: &str) -> String {
+    let base_dir = Path::new(obj_file_name).parent().unwrap_or(Path::new(""));
+
+    String::from(base_dir.join(material_lib).to_string_lossy())
+}
+
+///
+/// Load every material library a mesh refers to with `mtllib`. The library
+/// file names are relative to the *.obj file the mesh was loaded from.
+///
+pub fn load_obj_materials(mesh: &ObjMesh, obj_file_name: &str) -> Result<Vec<Material>, String> {
+    let mut materials = vec![];
+    for material_lib in mesh.material_libs.iter() {
+        match load_mtl_file(&material_lib_file_name(obj_file_name, material_lib)) {
+            Ok(mut lib_materials) => materials.append(&mut lib_materials),
+            Err(e) => return Err(e),
+        }
+    }
+
+    Ok(materials)
+}
+
+///
+/// The material library files an *.obj file refers to with `mtllib`, which are
+/// the files `load_obj_materials` reads. Only the first word of each line is
+/// looked at, so this is much quicker than loading the mesh.
+///
+pub fn obj_material_lib_files(obj_file_name: &str) -> Result<Vec<String>, String> {
+    let bytes = match fs::read(obj_file_name) {
+        Ok(bytes) => bytes,
+        Err(_) => return Err(format!("ERROR: file not found: {}", obj_file_name)),
+    };
+    let mut files = vec![];
+    let mut line = LineScanner::new(&bytes);
+    loop {
+        if line.word() == b"mtllib" {
+            loop {
+                let material_lib = line.word();
+                if material_lib.is_empty() {
+                    break;
+                }
+                files.push(material_lib_file_name(obj_file_name, &String::from_utf8_lossy(material_lib)));
+            }
+        }
+        if !line.next_line() {
+            return Ok(files);
+        }
+    }
+}
+
+mod parser_tests {
+    use super::{MaterialGroup, ObjMesh};
+    use std::io::{BufReader, Cursor};
+    use std::fmt::Write;
+
+    struct Test {
+        obj_file: String,
+        obj_mesh: ObjMesh,
+    }
+
+    fn test() -> Test {
+        let obj_file = String::from(r"        \
+            o object1                         \
+            g cube                            \
+            v  0.0  0.0  0.0                  \
+            v  0.0  0.0  1.0                  \
+            v  0.0  1.0  0.0                  \
+            v  0.0  1.0  1.0                  \
+            v  1.0  0.0  0.0                  \
+            v  1.0  0.0  1.0                  \
+            v  1.0  1.0  0.0                  \
+            v  1.0  1.0  1.0                  \
+                                              \
+            vn  0.0  0.0  1.0                 \
+            vn  0.0  0.0 -1.0                 \
+            vn  0.0  1.0  0.0                 \
+            vn  0.0 -1.0  0.0                 \
+            vn  1.0  0.0  0.0                 \
+            vn -1.0  0.0  0.0                 \
+                                              \
+            f  1//2  7//2  5//2               \
+            f  1//2  3//2  7//2               \
+            f  1//6  4//6  3//6               \
+            f  1//6  2//6  4//6               \
+            f  3//3  8//3  7//3               \
+            f  3//3  4//3  8//3               \
+            f  5//5  7//5  8//5               \
+            f  5//5  8//5  6//5               \
+            f  1//4  5//4  6//4               \
+            f  1//4  6//4  2//4               \
+            f  2//1  6//1  8//1               \
+            f  2//1  8//1  4//1               \
+        ");
+        let point_count = 36;
+        let points = vec![
+            0.0, 0.0, 0.0, 1.0, 1.0, 0.0, 1.0, 0.0, 0.0,
+            0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 1.0, 1.0, 0.0,
+            0.0, 0.0, 0.0, 0.0, 1.0, 1.0, 0.0, 1.0, 0.0,
+            0.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 1.0, 1.0,
+            0.0, 1.0, 0.0, 1.0, 1.0, 1.0, 1.0, 1.0, 0.0,
+            0.0, 1.0, 0.0, 0.0, 1.0, 1.0, 1.0, 1.0, 1.0,
+            1.0, 0.0, 0.0, 1.0, 1.0, 0.0, 1.0, 1.0, 1.0,
+            1.0, 0.0, 0.0, 1.0, 1.0, 1.0, 1.0, 0.0, 1.0,
+            0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 1.0, 0.0, 1.0,
+            0.0, 0.0, 0.0, 1.0, 0.0, 1.0, 0.0, 0.0, 1.0,
+            0.0, 0.0, 1.0, 1.0, 0.0, 1.0, 1.0, 1.0, 1.0,
+            0.0, 0.0, 1.0, 1.0, 1.0, 1.0, 0.0, 1.0, 1.0,
+        ];
+        let tex_coords = vec![];
+        let normals = vec![
+             0.0,  0.0, -1.0,  0.0,  0.0, -1.0,  0.0,  0.0, -1.0,
+             0.0,  0.0, -1.0,  0.0,  0.0, -1.0,  0.0,  0.0, -1.0,
+            -1.0,  0.0,  0.0, -1.0,  0.0,  0.0, -1.0,  0.0,  0.0,
+            -1.0,  0.0,  0.0, -1.0,  0.0,  0.0, -1.0,  0.0,  0.0,
+             0.0,  1.0,  0.0,  0.0,  1.0,  0.0,  0.0,  1.0,  0.0,
+             0.0,  1.0,  0.0,  0.0,  1.0,  0.0,  0.0,  1.0,  0.0,
+             1.0,  0.0,  0.0,  1.0,  0.0,  0.0,  1.0,  0.0,  0.0,
+             1.0,  0.0,  0.0,  1.0,  0.0,  0.0,  1.0,  0.0,  0.0,
+             0.0, -1.0,  0.0,  0.0, -1.0,  0.0,  0.0, -1.0,  0.0,
+             0.0, -1.0,  0.0,  0.0, -1.0,  0.0,  0.0, -1.0,  0.0,
+             0.0,  0.0,  1.0,  0.0,  0.0,  1.0,  0.0,  0.0,  1.0,
+             0.0,  0.0,  1.0,  0.0,  0.0,  1.0,  0.0,  0.0,  1.0,
+        ];
+
+        let obj_mesh = ObjMesh {
+            point_count: point_count,
+            points: points,
+            tex_coords: tex_coords,
+            normals: normals,
+            material_libs: vec![],
+            groups: vec![MaterialGroup { material: String::new(), first: 0, count: 36 }],
+        };
+
+        Test {
+            obj_file: obj_file,
+            obj_mesh: obj_mesh,
+        }
+    }
+
+    #[test]
+    fn test_parse_f32_matches_str_parse() {
+        let mut words = vec![
+            "0", "-0.0", "+1", "1.", ".5", "-.25", "3e-2", "1E+3", "0.000001234", "123456789012345678",
+            "1.00000005960464477539062500001", "16777217", "33554431", "3.4028236e38", "1e39", "1e-40",
+            "1e-50", "0.1e-44", "12345678901234567890123", "inf", "-infinity", "NaN",
+        ];
+        let mut generated = String::new();
+        for i in 0..10000 {
+            let value = (i as f32 * 0.7313).sin() * 10f32.powi(i % 13 - 6);
+            write!(generated, "{} {:e} {:.9} ", value, value, value as f64).unwrap();
+        }
+        words.extend(generated.split_whitespace());
+        for word in words.iter() {
+            let expected: f32 = word.parse().unwrap();
+            let result = super::parse_f32(word.as_bytes()).unwrap();
+
+            assert!(result.to_bits() == expected.to_bits() || (result.is_nan() && expected.is_nan()), "{}", word);
+        }
+    }
+
+    #[test]
+    fn test_parse_f32_rejects_non_numbers() {
+        for word in ["", "-", ".", "e5", "1e", "1.0.0", "1,0", "0x10", "1f"].iter() {
+            assert_eq!(super::parse_f32(word.as_bytes()), None, "{}", word);
+        }
+    }
+
+    #[test]
+    fn test_parse_obj_mesh_lines_split_across_buffers() {
+        let test = test();
+        for capacity in 1..16 {
+            let mut reader = BufReader::with_capacity(capacity, Cursor::new(test.obj_file.as_bytes()));
+            let result = super::load_obj_mesh(&mut reader).unwrap();
+
+            assert_eq!(result, test.obj_mesh, "capacity {}", capacity);
+        }
+    }
+
+    #[test]
+    fn test_parse_obj_mesh_elementwise() {
+        let test = test();
+        let mut reader = BufReader::new(Cursor::new(test.obj_file.as_bytes()));
+        let result = super::load_obj_mesh(&mut reader).unwrap();
+        let expected = test.obj_mesh;
+
+        assert_eq!(result.point_count, expected.point_count);
+        assert_eq!(result.points, expected.points);
+        assert_eq!(result.tex_coords, expected.tex_coords);
+        assert_eq!(result.normals, expected.normals);
+    }
+
+    #[test]
+    fn test_parse_obj_mesh() {
+        let test = test();
+        let mut reader = BufReader::new(Cursor::new(test.obj_file.as_bytes()));
+        let result = super::load_obj_mesh(&mut reader).unwrap();
+        let expected = test.obj_mesh;
+
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn test_parse_obj_mesh_material_groups() {
+        let obj_file = "\
+            mtllib scene.mtl\n\
+            \n\
+            v 0.0 0.0 0.0\n\
+            v 1.0 0.0 0.0\n\
+            v 0.0 1.0 0.0\n\
+            vn 0.0 0.0 1.0\n\
+            f 1//1 2//1 3//1\n\
+            usemtl red\n\
+            f 1//1 2//1 3//1\n\
+            f 1//1 2//1 3//1\n\
+            usemtl blue\n\
+            usemtl green\n\
+            f 1//1 2//1 3//1\n\
+        ";
+        let mut reader = BufReader::new(Cursor::new(obj_file.as_bytes()));
+        let result = super::load_obj_mesh(&mut reader).unwrap();
+
+        assert_eq!(result.material_libs, vec![String::from("scene.mtl")]);
+        assert_eq!(result.groups, vec![
+            MaterialGroup { material: String::new(), first: 0, count: 3 },
+            MaterialGroup { material: String::from("red"), first: 3, count: 6 },
+            MaterialGroup { material: String::from("green"), first: 9, count: 3 },
+        ]);
+        assert_eq!(result.point_count, 12);
+    }
+
+    #[test]
+    fn test_parse_obj_mesh_with_tex_coords() {
+        let obj_file = "\
+            # a single triangle\r\n\
+            v 0.0 0.0 0.0 1.0\r\n\
+            v\t1.0 0.0 0.0\r\n\
+            v 0.0 2.5 -1.0\r\n\
+            vt 0.0 0.0\r\n\
+            vt 1.0 0.5 0.0\r\n\
+            vn 0.0 0.0 1.0\r\n\
+            f 1/1/1 2/2/1 3/1/1\r\n\
+        ";
+        let mut reader = BufReader::new(Cursor::new(obj_file.as_bytes()));
+        let result = super::load_obj_mesh(&mut reader).unwrap();
+
+        assert_eq!(result.points, vec![0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 2.5, -1.0]);
+        assert_eq!(result.tex_coords, vec![0.0, 0.0, 1.0, 0.5, 0.0, 0.0]);
+        assert_eq!(result.normals, vec![0.0, 0.0, 1.0, 0.0, 0.0, 1.0, 0.0, 0.0, 1.0]);
+    }
+
+    #[test]
+    fn test_parse_obj_mesh_rejects_bad_faces() {
+        let vertices = "v 0.0 0.0 0.0\nv 1.0 0.0 0.0\nv 0.0 1.0 0.0\nv 1.0 1.0 0.0\nvt 0.0 0.0\nvn 0.0 0.0 1.0\n";
+        let faces = [
+            "f 1//1 2//1 4//1 3//1\n",
+            "f 1 2 3\n",
+            "f 1//1 2//1 5//1\n",
+            "f 0//1 2//1 3//1\n",
+            "f 1/2/1 2/1/1 3/1/1\n",
+            "f 1/1/1 2//1 3/1/1\n",
+            "v 1.0 zero 0.0\n",
+        ];
+        for face in faces.iter() {
+            let obj_file = format!("{}{}", vertices, face);
+            let mut reader = BufReader::new(Cursor::new(obj_file.as_bytes()));
+
+            assert!(super::load_obj_mesh(&mut reader).is_err(), "{}", face);
+        }
+    }
+}
+
+mod mtl_parser_tests {
+    use super::Material;
+    use colour::Colour;
+    use std::io::{BufReader, Cursor};
+
+    #[test]
+    fn test_parse_mtl() {
+        let mtl_file = "\
+            # two materials\n\
+            newmtl orange\n\
+            Ka 1.0 1.0 1.0\n\
+            Kd 1.0 0.5 0.0\n\
+            Ks 0.5 0.5 0.5\n\
+            Ns 50.0\n\
+            illum 2\n\
+            map_Kd orange.png\n\
+            map_Bump -bm 1.0 bricks.png\n\
+            disp bricks_height.png\n\
+            \n\
+            newmtl plain\n\
+        ";
+        let mut reader = BufReader::new(Cursor::new(mtl_file.as_bytes()));
+        let result = super::load_mtl(&mut reader).unwrap();
+
+        let mut orange = Material::new("orange");
+        orange.kd = Colour::new(1.0, 0.5, 0.0);
+        orange.ks = Colour::new(0.5, 0.5, 0.5);
+        orange.shininess = 50.0;
+        orange.diffuse_map = Some(String::from("orange.png"));
+        orange.normal_map = Some(String::from("bricks.png"));
+        orange.height_map = Some(String::from("bricks_height.png"));
+
+        assert_eq!(result, vec![orange, Material::new("plain")]);
+    }
+
+    #[test]
+    fn test_parse_mtl_rejects_statements_before_newmtl() {
+        let mut reader = BufReader::new(Cursor::new("Kd 1.0 0.5 0.0\n".as_bytes()));
+
+        assert!(super::load_mtl(&mut reader).is_err());
+    }
+
+    #[test]
+    fn test_parse_mtl_rejects_bad_colours() {
+        let mut reader = BufReader::new(Cursor::new("newmtl bad\nKd 1.0 half 0.0\n".as_bytes()));
+
+        assert!(super::load_mtl(&mut reader).is_err());
+    }
+}
+
@@ -0,0 +1,37 @@
+#version 410
+
+in vec3 pos_eye;
+in vec3 normal_wor;
+in float height;
+out vec4 frag_colour;
+
+// height of the highest possible point, so the snow line scales with the terrain
+uniform float max_height;
+// the sunlight, as the time of day sets it. the direction is towards the sun,
+// in world space.
+uniform vec3 light_direction;
+uniform vec3 light_colour;
+uniform vec3 ambient_colour;
+
+#include "fog.glsl"
+
+vec3 grass = vec3 (0.25, 0.45, 0.15);
+vec3 rock = vec3 (0.4, 0.37, 0.33);
+vec3 snow = vec3 (0.95, 0.95, 1.0);
+
+void main() {
+	vec3 n = normalize (normal_wor);
+
+	// snow settles above the snow line, but slides off steep slopes, which show
+	// bare rock. everything else is grass.
+	float slope = 1.0 - n.y;
+	float rock_weight = smoothstep (0.25, 0.45, slope);
+	float snow_weight = smoothstep (0.6, 0.7, height / max_height) * (1.0 - smoothstep (0.35, 0.55, slope));
+	vec3 albedo = mix (mix (grass, rock, rock_weight), snow, snow_weight);
+
+	float diffuse = max (dot (n, normalize (light_direction)), 0.0);
+	frag_colour = vec4 (albedo * (ambient_colour + light_colour * diffuse), 1.0);
+
+	// blend the fog colour with the lighting colour, based on distance from the camera
+	frag_colour.rgb = apply_fog (frag_colour.rgb, pos_eye);
+}
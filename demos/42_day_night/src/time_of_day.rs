@@ -0,0 +1,217 @@
+use colour::Colour;
+use directional_light::DirectionalLight;
+use graphics_math as math;
+use math::Vec3;
+use skybox::ProceduralSky;
+
+
+const HOURS_PER_DAY: f32 = 24.0;
+// How far the sun's path leans from passing straight overhead, towards +z.
+const SUN_PATH_TILT_DEG: f32 = 30.0;
+
+// These match the colours the skydome shader tints the sky with, so the fog
+// fades into the sky behind it.
+const SUNSET_COLOUR: Colour = Colour { r: 1.0, g: 0.45, b: 0.15, a: 1.0 };
+const NIGHT_COLOUR: Colour = Colour { r: 0.01, g: 0.015, b: 0.04, a: 1.0 };
+
+const NOON_LIGHT: Colour = Colour { r: 0.75, g: 0.72, b: 0.65, a: 1.0 };
+const SUNRISE_LIGHT: Colour = Colour { r: 0.75, g: 0.4, b: 0.2, a: 1.0 };
+const DAY_AMBIENT: Colour = Colour { r: 0.3, g: 0.32, b: 0.35, a: 1.0 };
+const NIGHT_AMBIENT: Colour = Colour { r: 0.03, g: 0.04, b: 0.08, a: 1.0 };
+
+
+///
+/// The same curve as GLSL's `smoothstep`: 0 below `edge0`, 1 above `edge1`, and an
+/// S-shaped blend in between.
+///
+fn smoothstep(edge0: f32, edge1: f32, x: f32) -> f32 {
+    let t = f32::min(f32::max((x - edge0) / (edge1 - edge0), 0.0), 1.0);
+
+    t * t * (3.0 - 2.0 * t)
+}
+
+fn mix(a: Colour, b: Colour, t: f32) -> Colour {
+    Colour::new(a.r + (b.r - a.r) * t, a.g + (b.g - a.g) * t, a.b + (b.b - a.b) * t)
+}
+
+fn scale(colour: Colour, factor: f32) -> Colour {
+    Colour::new(colour.r * factor, colour.g * factor, colour.b * factor)
+}
+
+///
+/// The time of day, in hours from midnight, and everything that follows from it:
+/// where the sun is, the light it casts, and the colours of the sky and the fog.
+/// The sun rises in the east, along +x, at six, is highest at noon, and sets in
+/// the west at six in the evening.
+///
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct TimeOfDay {
+    hours: f32,
+}
+
+impl TimeOfDay {
+    pub fn new(hours: f32) -> TimeOfDay {
+        let mut time = TimeOfDay { hours: 0.0 };
+        time.advance(hours);
+
+        time
+    }
+
+    pub fn hours(&self) -> f32 {
+        self.hours
+    }
+
+    ///
+    /// Move the time on by `hours`, or back for a negative number, wrapping round
+    /// at midnight.
+    ///
+    pub fn advance(&mut self, hours: f32) {
+        self.hours = ((self.hours + hours) % HOURS_PER_DAY + HOURS_PER_DAY) % HOURS_PER_DAY;
+    }
+
+    ///
+    /// The time on a 24 hour clock, as `hh:mm`.
+    ///
+    pub fn clock(&self) -> String {
+        let minutes = (self.hours * 60.0) as u32;
+
+        format!("{:02}:{:02}", minutes / 60, minutes % 60)
+    }
+
+    ///
+    /// The direction towards the sun, in world space. The sun goes round once a day
+    /// on a circle leaning `SUN_PATH_TILT_DEG` from overhead, so it is below the
+    /// horizon half the time.
+    ///
+    pub fn sun_direction(&self) -> Vec3 {
+        let angle = (self.hours - 6.0) / HOURS_PER_DAY * 360.0 * math::ONE_DEG_IN_RAD;
+        let tilt = SUN_PATH_TILT_DEG * math::ONE_DEG_IN_RAD;
+
+        math::vec3((f32::cos(angle), f32::sin(angle) * f32::cos(tilt), f32::sin(angle) * f32::sin(tilt)))
+    }
+
+    ///
+    /// How much daylight there is, from 0 at night to 1 once the sun is up. It
+    /// changes over the same twilight as the sky darkens over in the skydome shader.
+    ///
+    pub fn daylight(&self) -> f32 {
+        smoothstep(-0.15, 0.1, self.sun_direction().v[1])
+    }
+
+    ///
+    /// How close the sun is to the horizon, from 0 when it is high to 1 when it is
+    /// on or below the horizon, which turns its light orange.
+    ///
+    fn low_sun(&self) -> f32 {
+        1.0 - smoothstep(0.0, 0.35, self.sun_direction().v[1])
+    }
+
+    ///
+    /// The sunlight: white and strong at noon, orange and weak when the sun is low,
+    /// and gone once it has set, leaving only a dim blue ambient light.
+    ///
+    pub fn light(&self) -> DirectionalLight {
+        let sun_up = smoothstep(-0.05, 0.15, self.sun_direction().v[1]);
+
+        DirectionalLight {
+            direction: self.sun_direction(),
+            colour: scale(mix(NOON_LIGHT, SUNRISE_LIGHT, self.low_sun()), sun_up),
+            ambient: mix(NIGHT_AMBIENT, DAY_AMBIENT, self.daylight()),
+        }
+    }
+
+    ///
+    /// `sky` with the sun moved to where it is at this time of day, and turned
+    /// orange when low. The skydome shader darkens and tints the rest of the sky
+    /// from the sun's direction.
+    ///
+    pub fn sky(&self, sky: &ProceduralSky) -> ProceduralSky {
+        let mut sky = *sky;
+        sky.sun_direction = self.sun_direction();
+        sky.sun = mix(sky.sun, SUNSET_COLOUR, self.low_sun());
+
+        sky
+    }
+
+    ///
+    /// Roughly the colour the skydome shader draws along the horizon of `sky`, for
+    /// the fog to fade distant ground into it.
+    ///
+    pub fn fog_colour(&self, sky: &ProceduralSky) -> Colour {
+        // the sky at the horizon, a quarter turn from the sun, where the scattering
+        // brightens it by 0.6 + 0.4 * 0.75, and half way round to the sun's side.
+        let sunset = 0.5 * self.low_sun() * smoothstep(-0.2, 0.0, self.sun_direction().v[1]);
+        let horizon = mix(scale(sky.horizon, 0.9), SUNSET_COLOUR, sunset);
+
+        mix(NIGHT_COLOUR, horizon, self.daylight())
+    }
+}
+
+mod time_of_day_tests {
+    use super::TimeOfDay;
+    use graphics_math as math;
+    use skybox::ProceduralSky;
+
+    const EPSILON: f32 = 1e-5;
+
+    fn brightness(colour: [f32; 3]) -> f32 {
+        colour[0] + colour[1] + colour[2]
+    }
+
+    #[test]
+    fn test_the_sun_rises_in_the_east_and_sets_in_the_west() {
+        let sunrise = TimeOfDay::new(6.0).sun_direction();
+        let noon = TimeOfDay::new(12.0).sun_direction();
+        let sunset = TimeOfDay::new(18.0).sun_direction();
+        let midnight = TimeOfDay::new(0.0).sun_direction();
+
+        assert!(f32::abs(sunrise.v[0] - 1.0) < EPSILON && f32::abs(sunrise.v[1]) < EPSILON);
+        assert!(f32::abs(sunset.v[0] + 1.0) < EPSILON && f32::abs(sunset.v[1]) < EPSILON);
+        assert!(noon.v[1] > 0.8);
+        assert!(midnight.v[1] < -0.8);
+        assert!(f32::abs(noon.norm() - 1.0) < EPSILON);
+    }
+
+    #[test]
+    fn test_the_time_wraps_round_at_midnight() {
+        let mut time = TimeOfDay::new(23.0);
+        time.advance(2.0);
+        assert!(f32::abs(time.hours() - 1.0) < EPSILON);
+
+        time.advance(-3.0);
+        assert!(f32::abs(time.hours() - 22.0) < EPSILON);
+        assert_eq!(TimeOfDay::new(-0.5).clock(), "23:30");
+        assert_eq!(TimeOfDay::new(6.25).clock(), "06:15");
+    }
+
+    #[test]
+    fn test_there_is_daylight_only_while_the_sun_is_up() {
+        assert_eq!(TimeOfDay::new(12.0).daylight(), 1.0);
+        assert_eq!(TimeOfDay::new(0.0).daylight(), 0.0);
+
+        let noon = TimeOfDay::new(12.0).light();
+        let midnight = TimeOfDay::new(0.0).light();
+        assert_eq!(midnight.colour.rgb(), [0.0, 0.0, 0.0]);
+        assert!(brightness(midnight.ambient.rgb()) < brightness(noon.ambient.rgb()));
+    }
+
+    #[test]
+    fn test_the_low_sun_is_redder_than_the_high_sun() {
+        let morning = TimeOfDay::new(6.75).light().colour;
+        let noon = TimeOfDay::new(12.0).light().colour;
+
+        assert!(morning.r / morning.b > noon.r / noon.b);
+        assert!(brightness(morning.rgb()) < brightness(noon.rgb()));
+    }
+
+    #[test]
+    fn test_the_fog_darkens_with_the_sky_at_night() {
+        let sky = ProceduralSky::new(math::vec3((0.0, 1.0, 0.0)));
+        let noon = TimeOfDay::new(12.0);
+        let midnight = TimeOfDay::new(0.0);
+
+        assert!(brightness(midnight.fog_colour(&sky).rgb()) < 0.1);
+        assert!(brightness(noon.fog_colour(&sky).rgb()) > 1.5);
+        assert_eq!(noon.sky(&sky).sun_direction, noon.sun_direction());
+    }
+}
@@ -0,0 +1,571 @@
+use gl;
+use glfw;
+use glfw::{Action, Context, Key, Modifiers, WindowEvent};
+
+use camera::Camera;
+use frame_clock;
+use frame_clock::FrameClock;
+use gl_utils::{update_fps_counter, GLContext};
+use input_replay::{replay_mode_from_args, InputRecording, ReplayMode, REPLAY_STEP_SECONDS};
+use tweak::{Tweaks, TWEAKS_FILE};
+
+use std::collections::HashSet;
+use std::env;
+
+
+///
+/// The keyboard as a demo sees it on one frame: the keys held down, and the keys
+/// pressed since the last frame together with the modifiers held at the time.
+/// It is built up from the window's events, so a demo can read it without
+/// borrowing the window.
+///
+#[derive(Clone, Debug, Default)]
+pub struct Input {
+    held: HashSet<Key>,
+    pressed: Vec<(Key, Modifiers)>,
+    events: Vec<WindowEvent>,
+}
+
+impl Input {
+    pub fn new() -> Input {
+        Input::default()
+    }
+
+    ///
+    /// Forget the last frame's presses and events. Held keys stay held until
+    /// they are released.
+    ///
+    pub fn begin_frame(&mut self) {
+        self.pressed.clear();
+        self.events.clear();
+    }
+
+    pub fn handle_event(&mut self, event: WindowEvent) {
+        if let WindowEvent::Key(key, _, action, mods) = event {
+            match action {
+                Action::Press => {
+                    self.held.insert(key);
+                    self.pressed.push((key, mods));
+                }
+                Action::Repeat => {
+                    self.held.insert(key);
+                }
+                Action::Release => {
+                    self.held.remove(&key);
+                }
+            }
+        }
+        self.events.push(event);
+    }
+
+    pub fn is_held(&self, key: Key) -> bool {
+        self.held.contains(&key)
+    }
+
+    ///
+    /// Whether `key` went down this frame. Keys repeating while held down do not
+    /// count.
+    ///
+    pub fn was_pressed(&self, key: Key) -> bool {
+        self.pressed.iter().any(|&(pressed, _)| pressed == key)
+    }
+
+    ///
+    /// Whether `key` went down this frame while all of `mods` were held.
+    ///
+    pub fn was_pressed_with(&self, key: Key, mods: Modifiers) -> bool {
+        self.pressed.iter().any(|&(pressed, pressed_mods)| pressed == key && pressed_mods.contains(mods))
+    }
+
+    ///
+    /// Take `key` out of this frame's input, as though it were not down, once
+    /// something else has answered it.
+    ///
+    pub fn consume(&mut self, key: Key) {
+        self.held.remove(&key);
+        self.pressed.retain(|&(pressed, _)| pressed != key);
+    }
+
+    ///
+    /// Every event the window sent this frame, for the ones `Input` does not
+    /// keep track of itself.
+    ///
+    pub fn events(&self) -> &[WindowEvent] {
+        &self.events
+    }
+}
+
+///
+/// The name of `key` as the help shows it, such as `[` or `Space`.
+///
+pub fn key_name(key: Key) -> String {
+    let name = match key {
+        Key::Num0 => "0", Key::Num1 => "1", Key::Num2 => "2", Key::Num3 => "3", Key::Num4 => "4",
+        Key::Num5 => "5", Key::Num6 => "6", Key::Num7 => "7", Key::Num8 => "8", Key::Num9 => "9",
+        Key::LeftBracket => "[", Key::RightBracket => "]", Key::Minus => "-", Key::Equal => "=",
+        Key::Comma => ",", Key::Period => ".", Key::Slash => "/", Key::Backslash => "\\",
+        Key::Semicolon => ";", Key::Apostrophe => "'", Key::GraveAccent => "`",
+        _ => return format!("{:?}", key),
+    };
+
+    String::from(name)
+}
+
+///
+/// A key, or a few keys that work together, and what they do.
+///
+#[derive(Clone, Debug, PartialEq)]
+pub struct KeyBinding {
+    pub keys: String,
+    pub action: String,
+}
+
+///
+/// The keys a demo answers to, registered in one place so the help can list
+/// them.
+///
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct KeyBindings {
+    bindings: Vec<KeyBinding>,
+}
+
+impl KeyBindings {
+    pub fn new() -> KeyBindings {
+        KeyBindings::default()
+    }
+
+    pub fn bind(&mut self, key: Key, action: &str) {
+        self.bind_keys(&[key], action);
+    }
+
+    ///
+    /// Register `key` pressed while all of `mods` are held, such as Alt+Enter.
+    ///
+    pub fn bind_with(&mut self, key: Key, mods: Modifiers, action: &str) {
+        let mut keys = String::new();
+        let names = [
+            (Modifiers::Control, "Control"), (Modifiers::Alt, "Alt"),
+            (Modifiers::Shift, "Shift"), (Modifiers::Super, "Super"),
+        ];
+        for &(modifier, name) in names.iter() {
+            if mods.contains(modifier) {
+                keys.push_str(name);
+                keys.push('+');
+            }
+        }
+        keys.push_str(&key_name(key));
+        self.bindings.push(KeyBinding { keys: keys, action: String::from(action) });
+    }
+
+    ///
+    /// Register a few keys that share a line in the help, such as the keys that
+    /// move the camera.
+    ///
+    pub fn bind_keys(&mut self, keys: &[Key], action: &str) {
+        let names: Vec<String> = keys.iter().map(|&key| key_name(key)).collect();
+        self.bindings.push(KeyBinding { keys: names.join(" "), action: String::from(action) });
+    }
+
+    ///
+    /// The help as lines of text: every key and what it does, then the current
+    /// value of each of `values`, given as name and value.
+    ///
+    pub fn help_lines(&self, values: &[(String, String)]) -> Vec<String> {
+        let keys_width = self.bindings.iter().map(|binding| binding.keys.len()).max().unwrap_or(0);
+        let mut lines = vec![String::from("keys")];
+        for binding in self.bindings.iter() {
+            lines.push(format!("  {:width$}  {}", binding.keys, binding.action, width = keys_width));
+        }
+        if !values.is_empty() {
+            let names_width = values.iter().map(|&(ref name, _)| name.len()).max().unwrap_or(0);
+            lines.push(String::new());
+            lines.push(String::from("settings"));
+            for &(ref name, ref value) in values.iter() {
+                lines.push(format!("  {:width$}  {}", name, value, width = names_width));
+            }
+        }
+
+        lines
+    }
+}
+
+///
+/// A demo driven by `run_demo`, which owns the window and the main loop. The
+/// demo only sets itself up, moves on each frame, and draws. Each method gets
+/// the GL context as well, for the demos that change the window, such as
+/// switching vsync or going fullscreen.
+///
+pub trait DemoApp: Sized {
+    ///
+    /// Make the demo's shaders, buffers, and textures once the window is open.
+    ///
+    fn init(context: &mut GLContext) -> Result<Self, String>;
+
+    ///
+    /// Move the demo on by a frame of `clock`, reading the keys from `input`.
+    ///
+    fn update(&mut self, context: &mut GLContext, clock: &FrameClock, input: &Input);
+
+    ///
+    /// Draw a frame. The driver shows it once `update` has run.
+    ///
+    fn render(&mut self, context: &mut GLContext);
+
+    ///
+    /// The framebuffer has changed size. The driver has already set the viewport
+    /// and the size in the context, so only the demo's own projections are left.
+    ///
+    fn resize(&mut self, _context: &mut GLContext, _width: u32, _height: u32) {}
+
+    ///
+    /// The window is closing, and the GL context is still current.
+    ///
+    fn shutdown(&mut self, _context: &mut GLContext) {}
+
+    ///
+    /// Whether the driver should handle the time control keys, F6, F7, F8, and
+    /// F10. Demos that use those keys for something else turn them off.
+    ///
+    fn time_controls(&self) -> bool {
+        true
+    }
+
+    ///
+    /// Register the demo's keys, for the help F1 shows. The driver registers its
+    /// own keys first.
+    ///
+    fn bindings(&self, _bindings: &mut KeyBindings) {}
+
+    ///
+    /// The current values of the settings the demo's keys change, as name and
+    /// value, for the help.
+    ///
+    fn help_values(&self) -> Vec<(String, String)> {
+        vec![]
+    }
+
+    ///
+    /// Draw the help over the frame, one entry of `lines` to a line. The driver
+    /// prints the help to the terminal as well, so demos without any text on
+    /// screen can leave this alone.
+    ///
+    fn draw_help(&mut self, _context: &mut GLContext, _lines: &[String]) {}
+
+    ///
+    /// The demo's tweakable parameters, if it has any. The driver loads them when
+    /// the demo starts and saves them when it closes, and F2 shows them in a panel
+    /// the arrow keys adjust.
+    ///
+    fn tweaks(&mut self) -> Option<&mut Tweaks> {
+        None
+    }
+
+    ///
+    /// Draw the tweak panel over the frame, one entry of `lines` to a line.
+    ///
+    fn draw_tweaks(&mut self, _context: &mut GLContext, _lines: &[String]) {}
+
+    ///
+    /// The demo's camera as it is now, if it has one. The driver saves it to
+    /// `camera::CAMERA_FILE` on F5, so the same viewpoint can be restored in a
+    /// later run.
+    ///
+    fn camera(&self) -> Option<Camera> {
+        None
+    }
+
+    ///
+    /// Move the demo's camera to one the driver restored from
+    /// `camera::CAMERA_FILE` on F9. Only called for demos that have a camera.
+    ///
+    fn set_camera(&mut self, _context: &mut GLContext, _camera: &Camera) {}
+}
+
+///
+/// Save the demo's camera on F5, and restore the saved one on F9, so an exact
+/// viewpoint can be returned to across runs, such as to compare shader changes.
+/// Demos without a camera ignore both keys.
+///
+fn handle_camera_keys<T: DemoApp>(app: &mut T, context: &mut GLContext, input: &Input) {
+    if input.was_pressed(Key::F5) {
+        if let Some(camera) = app.camera() {
+            camera.save_to_camera_file();
+        }
+    }
+    if input.was_pressed(Key::F9) && app.camera().is_some() {
+        if let Some(camera) = Camera::restore_from_camera_file() {
+            app.set_camera(context, &camera);
+        }
+    }
+}
+
+///
+/// The demo's settings and then its tweaks, as name and value, for the help.
+///
+fn help_values<T: DemoApp>(app: &mut T) -> Vec<(String, String)> {
+    let mut values = app.help_values();
+    if let Some(tweaks) = app.tweaks() {
+        values.extend(tweaks.values());
+    }
+
+    values
+}
+
+///
+/// Put the framebuffer's new size in the context and the viewport, then tell the
+/// demo. A minimised window has a zero size framebuffer, which is left alone.
+///
+fn resize_framebuffer<T: DemoApp>(app: &mut T, context: &mut GLContext, width: i32, height: i32) {
+    if width <= 0 || height <= 0 {
+        return;
+    }
+    context.width = width as u32;
+    context.height = height as u32;
+    unsafe {
+        gl::Viewport(0, 0, width, height);
+    }
+    app.resize(context, width as u32, height as u32);
+}
+
+///
+/// Run a demo in the window of `context` until the window closes or Escape is
+/// pressed. Each frame is drawn, then the window's events are read and the demo
+/// is updated, then the frame is shown, as the demos have always done it. The
+/// time control keys pause, slow down, speed up, and step simulation time.
+///
+/// Given `--record <file>` on the command line, the keyboard and mouse input is
+/// saved to the file when the window closes. Given `--replay <file>`, the saved
+/// input is played back in place of the keyboard and mouse, and the window closes
+/// when it runs out. Both run with a fixed time step, so a replay sees exactly
+/// the frames the recording did. F1 shows the help, listing the keys the driver
+/// and the demo have registered, and F2 shows the demo's tweaks. F5 and F9 save
+/// and restore the demo's camera. Returns an error if the demo could not set
+/// itself up, or the recording could not be read or saved.
+///
+pub fn run_demo<T: DemoApp>(mut context: GLContext) -> Result<(), String> {
+    let replay_mode = match replay_mode_from_args(env::args()) {
+        Ok(val) => val,
+        Err(e) => return Err(e),
+    };
+    let mut recording = match replay_mode {
+        ReplayMode::Replay(ref file_name) => match InputRecording::load(file_name) {
+            Ok(val) => val,
+            Err(e) => return Err(e),
+        },
+        _ => InputRecording::new(),
+    };
+
+    let mut app: T = match T::init(&mut context) {
+        Ok(val) => val,
+        Err(e) => return Err(e),
+    };
+    if let Some(tweaks) = app.tweaks() {
+        if let Err(e) = tweaks.load(TWEAKS_FILE) {
+            eprintln!("{}", e);
+        }
+    }
+    let mut input = Input::new();
+    let mut clock = FrameClock::new();
+    let mut frame = 0;
+
+    let mut bindings = KeyBindings::new();
+    bindings.bind(Key::Escape, "quit");
+    bindings.bind(Key::F1, "show or hide this help");
+    if app.time_controls() {
+        bindings.bind(frame_clock::PAUSE_KEY, "pause or resume time");
+        bindings.bind_keys(&[frame_clock::SLOWER_KEY, frame_clock::FASTER_KEY], "slow down or speed up time");
+        bindings.bind(frame_clock::STEP_KEY, "step one frame while time is paused");
+    }
+    if app.tweaks().is_some() {
+        bindings.bind(Key::F2, "show or hide the tweaks");
+        bindings.bind_keys(&[Key::Up, Key::Down], "pick a tweak while they are shown");
+        bindings.bind_keys(&[Key::Left, Key::Right], "adjust the picked tweak");
+    }
+    if app.camera().is_some() {
+        bindings.bind(Key::F5, "save the camera");
+        bindings.bind(Key::F9, "restore the saved camera");
+    }
+    app.bindings(&mut bindings);
+    let mut show_help = false;
+    // the framebuffer can start out bigger than the window asked for, such as on a
+    // high DPI screen, and no event says so
+    let (width, height) = context.window.get_framebuffer_size();
+    resize_framebuffer(&mut app, &mut context, width, height);
+
+    while !context.window.should_close() {
+        let current_seconds = context.glfw.get_time();
+        let elapsed_seconds = match replay_mode {
+            ReplayMode::Live => current_seconds - context.elapsed_time_seconds,
+            _ => REPLAY_STEP_SECONDS,
+        };
+        context.elapsed_time_seconds = current_seconds;
+        update_fps_counter(&mut context);
+
+        app.render(&mut context);
+        let tweak_lines = match app.tweaks() {
+            Some(ref tweaks) if tweaks.visible => Some(tweaks.lines()),
+            _ => None,
+        };
+        if let Some(lines) = tweak_lines {
+            app.draw_tweaks(&mut context, &lines);
+        }
+        if show_help {
+            let lines = bindings.help_lines(&help_values(&mut app));
+            app.draw_help(&mut context, &lines);
+        }
+
+        context.glfw.poll_events();
+        input.begin_frame();
+        let mut framebuffer_size = None;
+        for (_, event) in glfw::flush_messages(&context.events) {
+            // the window's size is the window's own, even when replaying
+            if let WindowEvent::FramebufferSize(width, height) = event {
+                framebuffer_size = Some((width, height));
+                continue;
+            }
+            match replay_mode {
+                // the recording stands in for the keyboard and mouse
+                ReplayMode::Replay(_) => {}
+                ReplayMode::Record(_) => {
+                    recording.record(frame, &event);
+                    input.handle_event(event);
+                }
+                ReplayMode::Live => input.handle_event(event),
+            }
+        }
+        if let ReplayMode::Replay(_) = replay_mode {
+            for event in recording.events_on_frame(frame) {
+                input.handle_event(event);
+            }
+            if frame >= recording.frame_count() {
+                context.window.set_should_close(true);
+            }
+        }
+
+        if let Some((width, height)) = framebuffer_size {
+            resize_framebuffer(&mut app, &mut context, width, height);
+        }
+
+        if input.was_pressed(Key::F1) {
+            show_help = !show_help;
+            if show_help {
+                for line in bindings.help_lines(&help_values(&mut app)).iter() {
+                    println!("{}", line);
+                }
+            }
+        }
+        if let Some(tweaks) = app.tweaks() {
+            if input.was_pressed(Key::F2) {
+                tweaks.visible = !tweaks.visible;
+            }
+            tweaks.handle_input(&mut input);
+        }
+        handle_camera_keys(&mut app, &mut context, &input);
+        if app.time_controls() {
+            for &key in frame_clock::TIME_CONTROL_KEYS.iter() {
+                if input.was_pressed(key) {
+                    clock.handle_key(key);
+                }
+            }
+        }
+        clock.tick(elapsed_seconds);
+        app.update(&mut context, &clock, &input);
+        if input.is_held(Key::Escape) {
+            context.window.set_should_close(true);
+        }
+
+        // Put the stuff we've been drawing onto the display.
+        context.window.swap_buffers();
+        frame += 1;
+    }
+
+    app.shutdown(&mut context);
+    if let Some(tweaks) = app.tweaks() {
+        if let Err(e) = tweaks.save(TWEAKS_FILE) {
+            return Err(e);
+        }
+    }
+
+    match replay_mode {
+        ReplayMode::Record(ref file_name) => recording.save(file_name),
+        _ => Ok(()),
+    }
+}
+
+mod demo_app_tests {
+    use super::{key_name, Input, KeyBindings};
+    use glfw::{Action, Key, Modifiers, WindowEvent};
+
+    fn key_event(key: Key, action: Action, mods: Modifiers) -> WindowEvent {
+        WindowEvent::Key(key, 0, action, mods)
+    }
+
+    #[test]
+    fn test_keys_stay_held_until_released() {
+        let mut input = Input::new();
+        input.handle_event(key_event(Key::W, Action::Press, Modifiers::empty()));
+        assert!(input.is_held(Key::W));
+
+        input.begin_frame();
+        input.handle_event(key_event(Key::W, Action::Repeat, Modifiers::empty()));
+        assert!(input.is_held(Key::W));
+
+        input.begin_frame();
+        assert!(input.is_held(Key::W));
+        input.handle_event(key_event(Key::W, Action::Release, Modifiers::empty()));
+        assert!(!input.is_held(Key::W));
+    }
+
+    #[test]
+    fn test_a_press_only_lasts_one_frame() {
+        let mut input = Input::new();
+        input.handle_event(key_event(Key::F3, Action::Press, Modifiers::empty()));
+        assert!(input.was_pressed(Key::F3));
+        assert!(!input.was_pressed(Key::F5));
+        assert_eq!(input.events().len(), 1);
+
+        input.begin_frame();
+        input.handle_event(key_event(Key::F3, Action::Repeat, Modifiers::empty()));
+        assert!(!input.was_pressed(Key::F3));
+    }
+
+    #[test]
+    fn test_presses_remember_their_modifiers() {
+        let mut input = Input::new();
+        input.handle_event(key_event(Key::Enter, Action::Press, Modifiers::Alt | Modifiers::Shift));
+
+        assert!(input.was_pressed_with(Key::Enter, Modifiers::Alt));
+        assert!(!input.was_pressed_with(Key::Enter, Modifiers::Control));
+    }
+
+    #[test]
+    fn test_keys_are_named_the_way_they_are_labelled() {
+        assert_eq!(key_name(Key::LeftBracket), "[");
+        assert_eq!(key_name(Key::Num9), "9");
+        assert_eq!(key_name(Key::F1), "F1");
+        assert_eq!(key_name(Key::Space), "Space");
+    }
+
+    #[test]
+    fn test_the_help_lines_up_keys_and_settings() {
+        let mut bindings = KeyBindings::new();
+        bindings.bind(Key::F3, "toggle the HUD");
+        bindings.bind_with(Key::Enter, Modifiers::Alt, "toggle fullscreen");
+        bindings.bind_keys(&[Key::W, Key::S], "move");
+        let values = vec![(String::from("vsync"), String::from("on"))];
+
+        assert_eq!(
+            bindings.help_lines(&values),
+            vec![
+                String::from("keys"),
+                String::from("  F3         toggle the HUD"),
+                String::from("  Alt+Enter  toggle fullscreen"),
+                String::from("  W S        move"),
+                String::new(),
+                String::from("settings"),
+                String::from("  vsync  on"),
+            ]
+        );
+        assert_eq!(bindings.help_lines(&[]).len(), 4);
+    }
+}
@@ -0,0 +1,223 @@
+use glfw::Key;
+
+
+///
+/// The slowest and fastest the time controls run simulation time, as a fraction
+/// of real time. Each press of F7 or F8 halves or doubles it.
+///
+pub const MIN_TIME_SCALE: f64 = 1.0 / 16.0;
+pub const MAX_TIME_SCALE: f64 = 4.0;
+///
+/// How far F10 moves simulation time on while it is paused: one frame at 60
+/// frames per second, however long the frame really took.
+///
+pub const SINGLE_STEP_SECONDS: f64 = 1.0 / 60.0;
+
+///
+/// The time control keys. They are function keys no demo uses for anything
+/// else, so every demo can have them.
+///
+pub const PAUSE_KEY: Key = Key::F6;
+pub const SLOWER_KEY: Key = Key::F7;
+pub const FASTER_KEY: Key = Key::F8;
+pub const STEP_KEY: Key = Key::F10;
+pub const TIME_CONTROL_KEYS: [Key; 4] = [PAUSE_KEY, SLOWER_KEY, FASTER_KEY, STEP_KEY];
+
+
+///
+/// Simulation time, which can be paused, slowed down, sped up, and stepped a
+/// frame at a time while real time carries on. Animation, physics, and anything
+/// else that moves on its own follows `dt`; the camera follows `real_dt`, so it
+/// can still fly around a paused scene.
+///
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct FrameClock {
+    real_dt: f64,
+    dt: f64,
+    seconds: f64,
+    time_scale: f64,
+    paused: bool,
+    step_pending: bool,
+}
+
+impl FrameClock {
+    pub fn new() -> FrameClock {
+        FrameClock {
+            real_dt: 0.0,
+            dt: 0.0,
+            seconds: 0.0,
+            time_scale: 1.0,
+            paused: false,
+            step_pending: false,
+        }
+    }
+
+    ///
+    /// The real time the last frame took, in seconds.
+    ///
+    pub fn real_dt(&self) -> f64 {
+        self.real_dt
+    }
+
+    ///
+    /// How far simulation time moved on over the last frame, in seconds. Zero
+    /// while paused, apart from a single step.
+    ///
+    pub fn dt(&self) -> f64 {
+        self.dt
+    }
+
+    ///
+    /// The simulation time since the demo started, for time uniforms and the like.
+    ///
+    pub fn seconds(&self) -> f64 {
+        self.seconds
+    }
+
+    pub fn time_scale(&self) -> f64 {
+        self.time_scale
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    pub fn toggle_pause(&mut self) {
+        self.paused = !self.paused;
+        self.step_pending = false;
+    }
+
+    pub fn slower(&mut self) {
+        self.time_scale = f64::max(self.time_scale / 2.0, MIN_TIME_SCALE);
+    }
+
+    pub fn faster(&mut self) {
+        self.time_scale = f64::min(self.time_scale * 2.0, MAX_TIME_SCALE);
+    }
+
+    ///
+    /// Move simulation time on by a single frame on the next tick. Does nothing
+    /// unless the clock is paused.
+    ///
+    pub fn step(&mut self) {
+        if self.paused {
+            self.step_pending = true;
+        }
+    }
+
+    ///
+    /// Apply a time control key that was pressed: F6 pauses, F7 and F8 halve and
+    /// double the time scale, and F10 steps a single frame while paused. Returns
+    /// whether `key` was one of them.
+    ///
+    pub fn handle_key(&mut self, key: Key) -> bool {
+        match key {
+            PAUSE_KEY => self.toggle_pause(),
+            SLOWER_KEY => self.slower(),
+            FASTER_KEY => self.faster(),
+            STEP_KEY => self.step(),
+            _ => return false,
+        }
+
+        true
+    }
+
+    ///
+    /// Start a new frame that took `real_dt` seconds of real time.
+    ///
+    pub fn tick(&mut self, real_dt: f64) {
+        self.real_dt = real_dt;
+        self.dt = if !self.paused {
+            real_dt * self.time_scale
+        } else if self.step_pending {
+            SINGLE_STEP_SECONDS
+        } else {
+            0.0
+        };
+        self.step_pending = false;
+        self.seconds += self.dt;
+    }
+
+    ///
+    /// The state of the clock for a HUD, such as `time x0.25` or `time paused`.
+    ///
+    pub fn describe(&self) -> String {
+        if self.paused {
+            String::from("time paused")
+        } else {
+            format!("time x{}", self.time_scale)
+        }
+    }
+}
+
+impl Default for FrameClock {
+    fn default() -> FrameClock {
+        FrameClock::new()
+    }
+}
+
+mod frame_clock_tests {
+    use super::{FrameClock, MAX_TIME_SCALE, MIN_TIME_SCALE, SINGLE_STEP_SECONDS};
+    use super::{FASTER_KEY, PAUSE_KEY, SLOWER_KEY, STEP_KEY, TIME_CONTROL_KEYS};
+    use glfw::Key;
+
+    #[test]
+    fn test_pausing_stops_simulation_time_but_not_real_time() {
+        let mut clock = FrameClock::new();
+        clock.tick(0.5);
+        assert_eq!(clock.dt(), 0.5);
+
+        assert!(clock.handle_key(PAUSE_KEY));
+        clock.tick(0.5);
+        assert!(clock.is_paused());
+        assert_eq!(clock.real_dt(), 0.5);
+        assert_eq!(clock.dt(), 0.0);
+        assert_eq!(clock.seconds(), 0.5);
+        assert_eq!(clock.describe(), "time paused");
+    }
+
+    #[test]
+    fn test_the_time_scale_halves_and_doubles_within_its_limits() {
+        let mut clock = FrameClock::new();
+        clock.handle_key(SLOWER_KEY);
+        clock.tick(0.5);
+        assert_eq!(clock.dt(), 0.25);
+        assert_eq!(clock.describe(), "time x0.5");
+
+        for _ in 0..10 {
+            clock.slower();
+        }
+        assert_eq!(clock.time_scale(), MIN_TIME_SCALE);
+        for _ in 0..10 {
+            clock.handle_key(FASTER_KEY);
+        }
+        assert_eq!(clock.time_scale(), MAX_TIME_SCALE);
+    }
+
+    #[test]
+    fn test_a_single_step_only_moves_a_paused_clock_one_frame() {
+        let mut clock = FrameClock::new();
+        // stepping a running clock does nothing extra
+        clock.step();
+        clock.tick(0.1);
+        assert_eq!(clock.dt(), 0.1);
+
+        clock.toggle_pause();
+        clock.handle_key(STEP_KEY);
+        clock.tick(0.1);
+        assert_eq!(clock.dt(), SINGLE_STEP_SECONDS);
+        clock.tick(0.1);
+        assert_eq!(clock.dt(), 0.0);
+        assert_eq!(clock.seconds(), 0.1 + SINGLE_STEP_SECONDS);
+    }
+
+    #[test]
+    fn test_other_keys_leave_the_clock_alone() {
+        let mut clock = FrameClock::new();
+        for &key in [Key::P, Key::N, Key::LeftBracket, Key::RightBracket, Key::F5, Key::F9].iter() {
+            assert!(!clock.handle_key(key));
+            assert!(!TIME_CONTROL_KEYS.contains(&key));
+        }
+        assert_eq!(clock, FrameClock::new());
+    }
+}